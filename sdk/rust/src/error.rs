@@ -2,6 +2,8 @@
 
 use thiserror::Error;
 
+use crate::types::Revocation;
+
 /// Error codes for Paycheck errors
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PaycheckErrorCode {
@@ -25,6 +27,8 @@ pub enum PaycheckErrorCode {
     NetworkError,
     /// Invalid request parameters
     ValidationError,
+    /// Buyer already has an active license for this product
+    AlreadyLicensed,
 }
 
 impl std::fmt::Display for PaycheckErrorCode {
@@ -40,6 +44,7 @@ impl std::fmt::Display for PaycheckErrorCode {
             Self::InvalidCode => write!(f, "INVALID_CODE"),
             Self::NetworkError => write!(f, "NETWORK_ERROR"),
             Self::ValidationError => write!(f, "VALIDATION_ERROR"),
+            Self::AlreadyLicensed => write!(f, "ALREADY_LICENSED"),
         }
     }
 }
@@ -54,6 +59,8 @@ pub struct PaycheckError {
     pub message: String,
     /// HTTP status code (for API errors)
     pub status_code: Option<u16>,
+    /// Why the license was revoked (set with `LicenseRevoked` when the server says)
+    pub revocation: Option<Revocation>,
 }
 
 impl PaycheckError {
@@ -63,6 +70,7 @@ impl PaycheckError {
             code,
             message: message.into(),
             status_code: None,
+            revocation: None,
         }
     }
 
@@ -76,6 +84,7 @@ impl PaycheckError {
             code,
             message: message.into(),
             status_code: Some(status_code),
+            revocation: None,
         }
     }
 
@@ -1,7 +1,7 @@
 //! JWT decoding and verification utilities
 
 use crate::error::{PaycheckError, PaycheckErrorCode, Result};
-use crate::types::LicenseClaims;
+use crate::types::{EntitlementValue, LicenseClaims};
 use base64::{engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD}, Engine};
 use ed25519_dalek::{Signature, Verifier, VerifyingKey};
 use std::time::{SystemTime, UNIX_EPOCH};
@@ -134,6 +134,11 @@ pub fn has_feature(claims: &LicenseClaims, feature: &str) -> bool {
     claims.features.iter().any(|f| f == feature)
 }
 
+/// Get a structured entitlement value, or None if the license doesn't have it.
+pub fn get_entitlement<'a>(claims: &'a LicenseClaims, name: &str) -> Option<&'a EntitlementValue> {
+    claims.entitlements.get(name)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -150,5 +155,37 @@ mod tests {
         assert_eq!(claims.tier, "pro");
         assert!(has_feature(&claims, "export"));
         assert!(!has_feature(&claims, "nonexistent"));
+        // Tokens from older servers have no entitlements
+        assert!(claims.entitlements.is_empty());
+    }
+
+    #[test]
+    fn test_decode_entitlements() {
+        let payload = serde_json::json!({
+            "iss": "paycheck", "sub": "license-123", "aud": "test.com", "jti": "jti-123",
+            "iat": 1704067200, "exp": 1704070800, "license_exp": null, "updates_exp": null,
+            "tier": "pro", "features": ["export"],
+            "entitlements": { "seats": 5, "sso": true, "support": "priority" },
+            "device_id": "device-123", "device_type": "uuid", "product_id": "product-123"
+        });
+        let token = format!(
+            "eyJhbGciOiJFZERTQSIsInR5cCI6IkpXVCJ9.{}.signature",
+            URL_SAFE_NO_PAD.encode(payload.to_string())
+        );
+
+        let claims = decode_token(&token).unwrap();
+        assert_eq!(
+            get_entitlement(&claims, "seats").and_then(EntitlementValue::as_i64),
+            Some(5)
+        );
+        assert_eq!(
+            get_entitlement(&claims, "sso").and_then(EntitlementValue::as_bool),
+            Some(true)
+        );
+        assert_eq!(
+            get_entitlement(&claims, "support").and_then(EntitlementValue::as_str),
+            Some("priority")
+        );
+        assert_eq!(get_entitlement(&claims, "nonexistent"), None);
     }
 }
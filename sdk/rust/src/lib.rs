@@ -47,9 +47,12 @@
 //! The SDK is designed for offline-first operation:
 //!
 //! - `is_licensed()`, `has_feature()`, `get_tier()` work without network
+//! - `ensure_valid()` checks with the server on a schedule and keeps working
+//!   offline for a grace period (see `ValidationPolicy`)
 //! - Ed25519 signature verification ensures JWT authenticity offline
 //! - License validity is checked via `license_exp` claim, not JWT `exp`
 //! - Tokens auto-refresh when network is available
+//! - Validation and refresh retry transient failures (see `RetryPolicy`)
 //! - JWTs can be refreshed up to 10 years after issuance
 //!
 //! ## Understanding Expiration Times
@@ -77,8 +80,8 @@ pub mod types;
 
 // Main client
 pub use paycheck::{
-    CheckoutOptions, ImportResult, OfflineValidateResult, Paycheck, PaycheckOptions, SyncResult,
-    DEFAULT_BASE_URL,
+    CheckoutOptions, EnsureValidResult, ImportResult, OfflineValidateResult, Operation, Paycheck,
+    PaycheckOptions, RetryPolicy, SyncResult, ValidationPolicy, DEFAULT_BASE_URL, DEFAULT_TIMEOUT,
 };
 
 // Error types
@@ -89,9 +92,10 @@ pub use storage::{MemoryStorage, StorageAdapter};
 
 // Types
 pub use types::{
-    ActivationResult, CallbackResult, CallbackStatus, CheckoutParams, CheckoutResult,
-    DeactivateResult, DeviceInfo, DeviceType, LicenseClaims, LicenseDeviceInfo, LicenseInfo,
-    LicenseStatus, RequestCodeResult, ValidateResult,
+    ActivationResult, Attestation, CallbackResult, CallbackStatus, CheckoutParams, CheckoutResult,
+    ClientHints, DeactivateResult, DeviceInfo, DeviceType, EntitlementValue, LicenseClaims,
+    LicenseDeviceInfo, LicenseInfo, LicenseStatus, RequestCodeResult, Revocation, RevocationReason,
+    RevokedToken, ValidateResult,
 };
 
 // Re-export storage implementations
@@ -103,6 +107,6 @@ pub use device::{generate_uuid, get_machine_id};
 
 // Re-export JWT utilities
 pub use jwt::{
-    covers_version, decode_token, has_feature, is_jwt_expired, is_license_expired, verify_token,
+    covers_version, decode_token, get_entitlement, has_feature, is_jwt_expired, is_license_expired, verify_token,
     verify_and_decode_token,
 };
@@ -1,18 +1,35 @@
 //! New Paycheck client with public key-based initialization
 
 use crate::device::{generate_uuid, get_machine_id};
-use crate::error::{map_status_to_error_code, PaycheckError, Result};
+use crate::error::{map_status_to_error_code, PaycheckError, PaycheckErrorCode, Result};
 use crate::jwt::{decode_token, is_jwt_expired, is_license_expired, verify_token};
 use crate::storage::{keys, MemoryStorage, StorageAdapter};
 use crate::types::*;
 use reqwest::Client as HttpClient;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use url::Url;
 
 /// Default Paycheck API URL
 pub const DEFAULT_BASE_URL: &str = "https://api.paycheck.dev";
 
+/// Header identifying the project by its public key on every request
+pub const PROJECT_KEY_HEADER: &str = "X-Paycheck-Project";
+
+/// Header reporting the app's version, for the server's `update_required` hint
+pub const CLIENT_VERSION_HEADER: &str = "X-Paycheck-Client-Version";
+
+/// Header carrying the key shared by every attempt of one activation
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// How long one attempt at a request may take, unless overridden per call
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long connecting to the server may take
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
 /// Configuration options for the Paycheck client
 #[derive(Clone, Default)]
 pub struct PaycheckOptions {
@@ -26,6 +43,17 @@ pub struct PaycheckOptions {
     pub device_id: Option<String>,
     /// Auto-refresh expired tokens (default: true)
     pub auto_refresh: Option<bool>,
+    /// Your app's version, e.g. "2.1.0", so the server can say when it's
+    /// older than the project's minimum (default: not sent)
+    pub client_version: Option<String>,
+    /// When `ensure_valid()` contacts the server (default: `ValidationPolicy::default()`)
+    pub validation_policy: Option<ValidationPolicy>,
+    /// How failed requests are retried (default: `RetryPolicy::default()`)
+    pub retry_policy: Option<RetryPolicy>,
+    /// How long one attempt at a request may take (default: 30 seconds)
+    pub timeout: Option<Duration>,
+    /// Timeouts for particular calls, overriding `timeout`
+    pub call_timeouts: HashMap<Operation, Duration>,
 }
 
 impl std::fmt::Debug for PaycheckOptions {
@@ -36,6 +64,11 @@ impl std::fmt::Debug for PaycheckOptions {
             .field("device_type", &self.device_type)
             .field("device_id", &self.device_id)
             .field("auto_refresh", &self.auto_refresh)
+            .field("client_version", &self.client_version)
+            .field("validation_policy", &self.validation_policy)
+            .field("retry_policy", &self.retry_policy)
+            .field("timeout", &self.timeout)
+            .field("call_timeouts", &self.call_timeouts)
             .finish()
     }
 }
@@ -66,6 +99,159 @@ pub struct SyncResult {
     pub reason: Option<String>,
 }
 
+/// When [`Paycheck::ensure_valid`] asks the server about the license.
+///
+/// The defaults suit desktop apps: one server check an hour, and a week of
+/// working offline since the last successful one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValidationPolicy {
+    /// How long a successful server check is trusted before the next call
+    /// checks again (default: 1 hour)
+    pub revalidate_interval: Duration,
+    /// How long the license keeps working while the server can't be
+    /// reached, counted from the last successful check (default: 7 days)
+    pub offline_grace: Duration,
+    /// Refresh the token when its `exp` is this close (default: 5 minutes)
+    pub refresh_before_exp: Duration,
+}
+
+impl Default for ValidationPolicy {
+    fn default() -> Self {
+        Self {
+            revalidate_interval: Duration::from_secs(60 * 60),
+            offline_grace: Duration::from_secs(7 * 24 * 60 * 60),
+            refresh_before_exp: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+/// How the client retries a request that failed for a reason that may pass:
+/// a failed connection, a 5xx, or a 429 with `Retry-After`.
+///
+/// Only calls that are safe to repeat are retried: validation, refresh,
+/// license info, attestations and update checks. Checkout, activation codes
+/// and deactivation never are. Activation sends an `Idempotency-Key` that
+/// stays the same across its attempts, but is only retried with
+/// `retry_activation`, since a server that doesn't deduplicate by the key
+/// would see a second redemption of the code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts per call, including the first; 1 turns retries off
+    /// (default: 3)
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for each retry after it, with
+    /// jitter (default: 500 milliseconds)
+    pub initial_backoff: Duration,
+    /// Longest delay between attempts. A 429 asking to wait longer than this
+    /// isn't retried (default: 5 seconds)
+    pub max_backoff: Duration,
+    /// Retry `activate_with_code()` too. Only turn this on if your server
+    /// deduplicates `/redeem` by `Idempotency-Key` (default: false)
+    pub retry_activation: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(5),
+            retry_activation: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Never retry.
+    pub fn disabled() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// The delay before retry number `retry` (1 for the first), between half
+    /// and all of the exponential backoff so clients that failed together
+    /// don't retry together.
+    fn backoff(&self, retry: u32) -> Duration {
+        let exponential = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+            .min(self.max_backoff);
+        exponential.mul_f64(0.5 + 0.5 * random_fraction())
+    }
+}
+
+/// A random number in `[0, 1)`, from the standard library's per-process
+/// hash keys.
+fn random_fraction() -> f64 {
+    use std::hash::{BuildHasher, RandomState};
+    let bits = RandomState::new().hash_one(crate::jwt::now());
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// The calls the client makes, for per-call timeouts in
+/// [`PaycheckOptions::call_timeouts`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    /// `checkout()`
+    Checkout,
+    /// `/validate`, from `validate_online()`, `sync()` and `ensure_valid()`
+    Validate,
+    /// `/refresh`
+    Refresh,
+    /// `activate_with_code()`
+    Activate,
+    /// `request_activation_code()`
+    RequestCode,
+    /// `deactivate()`
+    Deactivate,
+    /// `get_license_info()`
+    LicenseInfo,
+    /// `get_attestation()`
+    Attestation,
+    /// `can_update()`
+    UpdateCheck,
+}
+
+impl Operation {
+    /// Whether repeating the call can't do anything the first attempt
+    /// didn't.
+    fn is_idempotent(self) -> bool {
+        matches!(
+            self,
+            Self::Validate
+                | Self::Refresh
+                | Self::LicenseInfo
+                | Self::Attestation
+                | Self::UpdateCheck
+        )
+    }
+}
+
+/// Result from [`Paycheck::ensure_valid`]
+#[derive(Debug, Clone)]
+pub struct EnsureValidResult {
+    /// Claims of the stored token (refreshed if `refreshed`)
+    pub claims: LicenseClaims,
+    /// When the server last confirmed the license (Unix timestamp). Falls
+    /// back to the token's `iat` if it never has.
+    pub validated_at: i64,
+    /// Whether the server couldn't be reached and the offline grace
+    /// period was used
+    pub offline: bool,
+    /// Whether the token was refreshed during this call
+    pub refreshed: bool,
+}
+
+/// The last successful server check, kept in storage under
+/// `keys::LAST_VALIDATION`
+#[derive(Serialize, Deserialize)]
+struct LastValidation {
+    jti: String,
+    validated_at: i64,
+}
+
 /// Result from importing a token
 #[derive(Debug, Clone)]
 pub struct ImportResult {
@@ -101,6 +287,13 @@ pub struct Paycheck {
     auto_refresh: bool,
     device_id: String,
     device_type: DeviceType,
+    client_version: Option<String>,
+    validation_policy: ValidationPolicy,
+    retry_policy: RetryPolicy,
+    timeout: Duration,
+    call_timeouts: HashMap<Operation, Duration>,
+    /// Hints from the last `/validate` or `/refresh` response
+    hints: Mutex<ClientHints>,
     http: HttpClient,
 }
 
@@ -143,6 +336,7 @@ impl Paycheck {
 
         let http = HttpClient::builder()
             .user_agent("paycheck-sdk-rust/0.2.0")
+            .connect_timeout(CONNECT_TIMEOUT)
             .build()
             .map_err(|e| PaycheckError::network(e.to_string()))?;
 
@@ -153,6 +347,12 @@ impl Paycheck {
             auto_refresh,
             device_id,
             device_type,
+            client_version: options.client_version,
+            validation_policy: options.validation_policy.unwrap_or_default(),
+            retry_policy: options.retry_policy.unwrap_or_default(),
+            timeout: options.timeout.unwrap_or(DEFAULT_TIMEOUT),
+            call_timeouts: options.call_timeouts,
+            hints: Mutex::new(ClientHints::default()),
             http,
         })
     }
@@ -176,6 +376,8 @@ impl Paycheck {
             provider: Option<String>,
             #[serde(skip_serializing_if = "Option::is_none")]
             customer_id: Option<String>,
+            #[serde(skip_serializing_if = "std::ops::Not::not")]
+            force_new_purchase: bool,
         }
 
         let opts = options.unwrap_or_default();
@@ -184,9 +386,10 @@ impl Paycheck {
             product_id: product_id.to_string(),
             provider: opts.provider,
             customer_id: opts.customer_id,
+            force_new_purchase: opts.force_new_purchase,
         };
 
-        self.post("/buy", &body).await
+        self.post(Operation::Checkout, "/buy", &body).await
     }
 
     /// Validate the stored license.
@@ -256,23 +459,26 @@ impl Paycheck {
 
     /// Online validation check (also checks revocation).
     pub async fn validate_online(&self) -> Result<ValidateResult> {
+        self.validate_online_at("/validate").await
+    }
+
+    /// Online validation that also lists the tokens revoked on the license
+    /// (`revoked_jtis`), such as those of devices deactivated remotely. Apps
+    /// that keep several tokens, or validate offline most of the time, can
+    /// drop any cached token listed there.
+    pub async fn validate_online_with_revocations(&self) -> Result<ValidateResult> {
+        self.validate_online_at("/validate?include_revocations=true")
+            .await
+    }
+
+    async fn validate_online_at(&self, path: &str) -> Result<ValidateResult> {
         let Some(token) = self.get_token() else {
-            return Ok(ValidateResult {
-                valid: false,
-                license_exp: None,
-                updates_exp: None,
-            });
+            return Ok(ValidateResult::default());
         };
 
         let claims = match decode_token(&token) {
             Ok(c) => c,
-            Err(_) => {
-                return Ok(ValidateResult {
-                    valid: false,
-                    license_exp: None,
-                    updates_exp: None,
-                });
-            }
+            Err(_) => return Ok(ValidateResult::default()),
         };
 
         #[derive(Serialize)]
@@ -286,13 +492,15 @@ impl Paycheck {
             jti: claims.jti,
         };
 
-        match self.post::<ValidateResponse, _>("/validate", &body).await {
-            Ok(r) => Ok(r.into()),
-            Err(_) => Ok(ValidateResult {
-                valid: false,
-                license_exp: None,
-                updates_exp: None,
-            }),
+        match self
+            .post::<ValidateResponse, _>(Operation::Validate, path, &body)
+            .await
+        {
+            Ok(r) => {
+                self.remember_hints(&r.hints);
+                Ok(r.into())
+            }
+            Err(_) => Ok(ValidateResult::default()),
         }
     }
 
@@ -382,15 +590,25 @@ impl Paycheck {
             jti: claims.jti.clone(),
         };
 
-        match self.post::<ValidateResponse, _>("/validate", &body).await {
+        match self
+            .post::<ValidateResponse, _>(Operation::Validate, "/validate", &body)
+            .await
+        {
             Ok(response) => {
+                self.remember_hints(&response.hints);
                 if !response.valid {
+                    let reason = match response.revocation {
+                        Some(revocation) => revocation
+                            .message
+                            .unwrap_or_else(|| "License revoked".to_string()),
+                        None => "Revoked or invalid".to_string(),
+                    };
                     return SyncResult {
                         valid: false,
                         claims: Some(claims),
                         synced: true,
                         offline: false,
-                        reason: Some("Revoked or invalid".to_string()),
+                        reason: Some(reason),
                     };
                 }
 
@@ -446,6 +664,96 @@ impl Paycheck {
         }
     }
 
+    /// Make sure the stored license is good to use, contacting the server
+    /// only as often as the [`ValidationPolicy`] says.
+    ///
+    /// This is the one call most apps need on startup and before gated
+    /// actions. It:
+    /// 1. Verifies the token locally (signature, device, `license_exp`)
+    /// 2. Refreshes it with `/refresh` when its `exp` is within
+    ///    `refresh_before_exp` or the license has expired locally
+    /// 3. Otherwise calls `/validate` at most once per `revalidate_interval`,
+    ///    refreshing if the server has newer expiration dates
+    /// 4. Keeps working offline for `offline_grace` after the last
+    ///    successful server check when the server can't be reached
+    ///
+    /// On revocation the stored token is cleared and the error is
+    /// `LicenseRevoked`, with the server's `revocation` when it gave one.
+    /// An expired license is `LicenseExpired` and keeps its token, so a
+    /// renewal can still be picked up. Running out of offline grace is
+    /// `NetworkError`.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// match paycheck.ensure_valid().await {
+    ///     Ok(status) => load_app(&status.claims.tier),
+    ///     Err(e) if e.code == PaycheckErrorCode::LicenseRevoked => show_revoked(e.revocation),
+    ///     Err(e) if e.code == PaycheckErrorCode::NetworkError => ask_to_connect(),
+    ///     Err(_) => show_activation_prompt(),
+    /// }
+    /// ```
+    pub async fn ensure_valid(&self) -> Result<EnsureValidResult> {
+        let token = self.get_token().ok_or_else(PaycheckError::no_token)?;
+        if !verify_token(&token, &self.public_key) {
+            return Err(PaycheckError::validation("Invalid signature"));
+        }
+        let mut claims = decode_token(&token)?;
+        if claims.device_id != self.device_id {
+            return Err(PaycheckError::validation("Device mismatch"));
+        }
+
+        let policy = self.validation_policy;
+        let now = crate::jwt::now();
+        let mut validated_at = self.last_validation(&claims.jti);
+
+        let refresh_due = is_license_expired(&claims)
+            || claims.exp - now <= policy.refresh_before_exp.as_secs() as i64;
+        let validate_due =
+            validated_at.is_none_or(|at| now - at >= policy.revalidate_interval.as_secs() as i64);
+
+        let mut refreshed = false;
+        let mut offline = false;
+        if refresh_due || validate_due {
+            let outcome = if refresh_due {
+                self.refresh_or_explain(&claims).await
+            } else {
+                self.revalidate(&claims).await
+            };
+            match outcome {
+                Ok(new_claims) => {
+                    if let Some(new_claims) = new_claims {
+                        claims = new_claims;
+                        refreshed = true;
+                    }
+                    validated_at = Some(self.remember_validation(&claims.jti, now));
+                }
+                Err(e) if e.code == PaycheckErrorCode::NetworkError => offline = true,
+                Err(e) => return Err(e),
+            }
+        }
+
+        if is_license_expired(&claims) {
+            return Err(PaycheckError::new(
+                PaycheckErrorCode::LicenseExpired,
+                "License expired",
+            ));
+        }
+
+        let validated_at = validated_at.unwrap_or(claims.iat);
+        if offline && now - validated_at > policy.offline_grace.as_secs() as i64 {
+            return Err(PaycheckError::network(
+                "Server unreachable and the offline grace period has run out",
+            ));
+        }
+
+        Ok(EnsureValidResult {
+            claims,
+            validated_at,
+            offline,
+            refreshed,
+        })
+    }
+
     /// Import a JWT token directly (offline activation).
     ///
     /// Use this when you have a JWT from another source (clipboard, QR code,
@@ -512,6 +820,9 @@ impl Paycheck {
     }
 
     /// Activate with a short-lived activation code.
+    ///
+    /// The request carries an `Idempotency-Key` that every retry of this
+    /// call reuses; see [`RetryPolicy::retry_activation`].
     pub async fn activate_with_code(
         &self,
         code: &str,
@@ -535,7 +846,15 @@ impl Paycheck {
             device_name: options.and_then(|d| d.device_name),
         };
 
-        let response: RedeemResponse = self.post("/redeem", &body).await?;
+        let url = format!("{}/redeem", self.base_url);
+        let request = self
+            .http
+            .post(&url)
+            .header(PROJECT_KEY_HEADER, &self.public_key)
+            .header(IDEMPOTENCY_KEY_HEADER, generate_uuid())
+            .headers(self.client_version_header())
+            .json(&body);
+        let response: RedeemResponse = self.send(Operation::Activate, request).await?;
 
         self.storage.set(keys::TOKEN, &response.token);
 
@@ -567,7 +886,9 @@ impl Paycheck {
             public_key: self.public_key.clone(),
         };
 
-        let response: RequestCodeResponse = self.post("/activation/request-code", &body).await?;
+        let response: RequestCodeResponse = self
+            .post(Operation::RequestCode, "/activation/request-code", &body)
+            .await?;
 
         Ok(response.into())
     }
@@ -593,6 +914,11 @@ impl Paycheck {
             .unwrap_or(false)
     }
 
+    /// Get a structured entitlement value (e.g. a numeric limit like `seats`).
+    pub fn get_entitlement(&self, name: &str) -> Option<EntitlementValue> {
+        self.get_license()?.entitlements.remove(name)
+    }
+
     /// Get the product tier.
     pub fn get_tier(&self) -> Option<String> {
         self.get_license().map(|c| c.tier)
@@ -622,6 +948,7 @@ impl Paycheck {
     /// Clear stored token.
     pub fn clear_token(&self) {
         self.storage.remove(keys::TOKEN);
+        self.storage.remove(keys::LAST_VALIDATION);
     }
 
     /// Refresh the JWT token.
@@ -631,14 +958,47 @@ impl Paycheck {
         #[derive(Deserialize)]
         struct RefreshResponse {
             token: String,
+            #[serde(flatten)]
+            hints: ClientHints,
         }
 
-        let response: RefreshResponse = self.post_with_auth("/refresh", &(), &token).await?;
+        let response: RefreshResponse = self
+            .post_with_auth(Operation::Refresh, "/refresh", &(), &token)
+            .await?;
 
+        self.remember_hints(&response.hints);
         self.storage.set(keys::TOKEN, &response.token);
         Ok(response.token)
     }
 
+    // ==================== Client Flags ====================
+
+    /// The project's client flags from the last `/validate` or `/refresh`
+    /// (empty until one succeeds). Flags aren't in the token, so they change
+    /// as soon as the publisher changes them.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// paycheck.sync().await;
+    /// if paycheck.client_flags().get("sync_enabled") == Some(&false.into()) {
+    ///     disable_sync();
+    /// }
+    /// ```
+    pub fn client_flags(&self) -> HashMap<String, serde_json::Value> {
+        self.hints.lock().unwrap().client_flags.clone()
+    }
+
+    /// Whether the last `/validate` or `/refresh` said this app is older than
+    /// the project's minimum supported version. Needs
+    /// [`PaycheckOptions::client_version`].
+    pub fn update_required(&self) -> bool {
+        self.hints.lock().unwrap().update_required
+    }
+
+    fn remember_hints(&self, hints: &ClientHints) {
+        *self.hints.lock().unwrap() = hints.clone();
+    }
+
     // ==================== Device Management ====================
 
     /// Deactivate this device.
@@ -646,7 +1006,7 @@ impl Paycheck {
         let token = self.ensure_fresh_token().await?;
 
         let response: DeactivateResponse = self
-            .post_with_auth("/devices/deactivate", &(), &token)
+            .post_with_auth(Operation::Deactivate, "/devices/deactivate", &(), &token)
             .await?;
 
         self.clear_token();
@@ -665,10 +1025,56 @@ impl Paycheck {
             urlencoding::encode(&self.public_key)
         );
 
-        let response: LicenseInfoResponse = self.get_with_auth(&url, &token).await?;
+        let response: LicenseInfoResponse = self
+            .get_with_auth(Operation::LicenseInfo, &url, &token)
+            .await?;
         Ok(response.into())
     }
 
+    /// Get a short-lived attestation of the license for a third party.
+    ///
+    /// The server validates the stored token first; `audience` must be in the
+    /// project's `attestation_audiences`. Hand the attestation to the third
+    /// party instead of the license token itself.
+    pub async fn get_attestation(&self, audience: &str) -> Result<Attestation> {
+        let token = self.ensure_fresh_token().await?;
+
+        let url = format!(
+            "{}/validate/attest?audience={}",
+            self.base_url,
+            urlencoding::encode(audience)
+        );
+
+        self.get_with_auth(Operation::Attestation, &url, &token)
+            .await
+    }
+
+    /// Check with the server whether the license's updates cover a build
+    /// released at `released_at` (Unix seconds: the build timestamp or the
+    /// version's release date).
+    ///
+    /// Unlike [`covers_version`](Self::covers_version), which reads the
+    /// token's `updates_exp` claim, this answers from the license as it is
+    /// now, so a renewal counts before the token is refreshed.
+    pub async fn can_update(&self, released_at: i64) -> Result<bool> {
+        let token = self.ensure_fresh_token().await?;
+
+        #[derive(Deserialize)]
+        struct UpdatesCheckResponse {
+            covered: bool,
+        }
+
+        let url = format!(
+            "{}/updates/check?released_at={}",
+            self.base_url, released_at
+        );
+
+        let response: UpdatesCheckResponse = self
+            .get_with_auth(Operation::UpdateCheck, &url, &token)
+            .await?;
+        Ok(response.covered)
+    }
+
     // ==================== Callback Handling ====================
 
     /// Handle the callback URL after payment redirect.
@@ -723,58 +1129,256 @@ impl Paycheck {
         Ok(token)
     }
 
+    /// `/refresh` for `ensure_valid()`, returning the new claims. The server
+    /// refuses a refresh with a bare 401, so `/validate` is asked why before
+    /// the token is given up on.
+    async fn refresh_or_explain(&self, claims: &LicenseClaims) -> Result<Option<LicenseClaims>> {
+        let error = match self.refresh_token().await {
+            Ok(token) => return decode_token(&token).map(Some),
+            Err(e) => e,
+        };
+        if !matches!(error.status_code, Some(401 | 403)) {
+            return Err(error);
+        }
+
+        match self.post_validate(&claims.jti).await {
+            Ok(response) if !response.valid => Err(self.rejected(claims, response)),
+            _ => Err(error),
+        }
+    }
+
+    /// `/validate` for `ensure_valid()`, refreshing when the server has
+    /// newer expiration dates than the token. Returns the new claims if it
+    /// refreshed.
+    async fn revalidate(&self, claims: &LicenseClaims) -> Result<Option<LicenseClaims>> {
+        let response = self.post_validate(&claims.jti).await?;
+        if !response.valid {
+            return Err(self.rejected(claims, response));
+        }
+
+        if response.license_exp != claims.license_exp {
+            // A failed refresh keeps the current token; the license is valid
+            if let Ok(token) = self.refresh_token().await {
+                return decode_token(&token).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    async fn post_validate(&self, jti: &str) -> Result<ValidateResponse> {
+        #[derive(Serialize)]
+        struct ValidateRequest<'a> {
+            public_key: &'a str,
+            jti: &'a str,
+        }
+
+        let body = ValidateRequest {
+            public_key: &self.public_key,
+            jti,
+        };
+        let response: ValidateResponse = self.post(Operation::Validate, "/validate", &body).await?;
+        self.remember_hints(&response.hints);
+        Ok(response)
+    }
+
+    /// The error for a license the server says is no longer valid. Revoked
+    /// licenses and deactivated devices lose their stored token; expired and
+    /// suspended licenses keep it, since either can come back.
+    fn rejected(&self, claims: &LicenseClaims, response: ValidateResponse) -> PaycheckError {
+        if let Some(revocation) = response.revocation {
+            self.clear_token();
+            let message = revocation
+                .message
+                .clone()
+                .unwrap_or_else(|| "License revoked".to_string());
+            let mut error = PaycheckError::new(PaycheckErrorCode::LicenseRevoked, message);
+            error.revocation = Some(revocation);
+            return error;
+        }
+
+        // Suspensions end when the dispute does, so the token is kept
+        if response.reason.as_deref() == Some("suspended_for_dispute") {
+            return PaycheckError::new(
+                PaycheckErrorCode::LicenseRevoked,
+                "License suspended during a payment dispute",
+            );
+        }
+
+        let expired_on_server = response
+            .license_exp
+            .is_some_and(|exp| exp < crate::jwt::now());
+        if expired_on_server || is_license_expired(claims) {
+            return PaycheckError::new(PaycheckErrorCode::LicenseExpired, "License expired");
+        }
+
+        self.clear_token();
+        PaycheckError::new(
+            PaycheckErrorCode::LicenseRevoked,
+            "License is no longer valid on this device",
+        )
+    }
+
+    /// When the server last confirmed the activation `jti`, if it has.
+    fn last_validation(&self, jti: &str) -> Option<i64> {
+        let stored = self.storage.get(keys::LAST_VALIDATION)?;
+        let last: LastValidation = serde_json::from_str(&stored).ok()?;
+        (last.jti == jti).then_some(last.validated_at)
+    }
+
+    fn remember_validation(&self, jti: &str, validated_at: i64) -> i64 {
+        let last = LastValidation {
+            jti: jti.to_string(),
+            validated_at,
+        };
+        if let Ok(stored) = serde_json::to_string(&last) {
+            self.storage.set(keys::LAST_VALIDATION, &stored);
+        }
+        validated_at
+    }
+
     async fn get_with_auth<T: for<'de> Deserialize<'de>>(
         &self,
+        operation: Operation,
         url: &str,
         token: &str,
     ) -> Result<T> {
-        let response = self
+        let request = self
             .http
             .get(url)
+            .header(PROJECT_KEY_HEADER, &self.public_key)
             .header("Authorization", format!("Bearer {}", token))
-            .send()
-            .await
-            .map_err(|e| PaycheckError::network(e.to_string()))?;
+            .headers(self.client_version_header());
 
-        self.handle_response(response).await
+        self.send(operation, request).await
     }
 
     async fn post<T: for<'de> Deserialize<'de>, B: Serialize>(
         &self,
+        operation: Operation,
         path: &str,
         body: &B,
     ) -> Result<T> {
         let url = format!("{}{}", self.base_url, path);
 
-        let response = self
+        let request = self
             .http
             .post(&url)
-            .json(body)
-            .send()
-            .await
-            .map_err(|e| PaycheckError::network(e.to_string()))?;
+            .header(PROJECT_KEY_HEADER, &self.public_key)
+            .headers(self.client_version_header())
+            .json(body);
 
-        self.handle_response(response).await
+        self.send(operation, request).await
     }
 
     async fn post_with_auth<T: for<'de> Deserialize<'de>, B: Serialize>(
         &self,
+        operation: Operation,
         path: &str,
         body: &B,
         token: &str,
     ) -> Result<T> {
         let url = format!("{}{}", self.base_url, path);
 
-        let response = self
+        let request = self
             .http
             .post(&url)
+            .header(PROJECT_KEY_HEADER, &self.public_key)
             .header("Authorization", format!("Bearer {}", token))
-            .json(body)
-            .send()
-            .await
-            .map_err(|e| PaycheckError::network(e.to_string()))?;
+            .headers(self.client_version_header())
+            .json(body);
+
+        self.send(operation, request).await
+    }
+
+    /// Send `request` with the call's timeout, retrying as the
+    /// [`RetryPolicy`] allows.
+    async fn send<T: for<'de> Deserialize<'de>>(
+        &self,
+        operation: Operation,
+        request: reqwest::RequestBuilder,
+    ) -> Result<T> {
+        let timeout = self
+            .call_timeouts
+            .get(&operation)
+            .copied()
+            .unwrap_or(self.timeout);
+        let retries = operation.is_idempotent()
+            || (operation == Operation::Activate && self.retry_policy.retry_activation);
+        let attempts = if retries {
+            self.retry_policy.max_attempts.max(1)
+        } else {
+            1
+        };
+
+        let mut request = request.timeout(timeout);
+        let mut attempt = 1;
+        loop {
+            // JSON bodies can always be cloned, so every retry sends the same request
+            let next = (attempt < attempts).then(|| request.try_clone()).flatten();
+            let outcome = request.send().await;
+
+            let delay = next
+                .as_ref()
+                .and_then(|_| self.retry_delay(&outcome, attempt));
+            match (next, delay) {
+                (Some(next), Some(delay)) => {
+                    tokio::time::sleep(delay).await;
+                    request = next;
+                    attempt += 1;
+                }
+                _ => {
+                    let response = outcome.map_err(|e| PaycheckError::network(e.to_string()))?;
+                    return self.handle_response(response).await;
+                }
+            }
+        }
+    }
+
+    /// How long to wait before retrying after attempt number `attempt`, or
+    /// `None` if the failure isn't one that's worth retrying.
+    fn retry_delay(
+        &self,
+        outcome: &std::result::Result<reqwest::Response, reqwest::Error>,
+        attempt: u32,
+    ) -> Option<Duration> {
+        let response = match outcome {
+            Ok(response) => response,
+            Err(e) if e.is_connect() => return Some(self.retry_policy.backoff(attempt)),
+            Err(_) => return None,
+        };
 
-        self.handle_response(response).await
+        let status = response.status();
+        if status.is_server_error() {
+            return Some(self.retry_policy.backoff(attempt));
+        }
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            // Only when the server says when; without it, waiting is a guess
+            let retry_after = response
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)?
+                .to_str()
+                .ok()?
+                .trim()
+                .parse::<u64>()
+                .ok()
+                .map(Duration::from_secs)?;
+            return (retry_after <= self.retry_policy.max_backoff).then_some(retry_after);
+        }
+        None
+    }
+
+    /// The `X-Paycheck-Client-Version` header, if a version was configured
+    /// and is a valid header value.
+    fn client_version_header(&self) -> reqwest::header::HeaderMap {
+        let mut headers = reqwest::header::HeaderMap::new();
+        if let Some(value) = self
+            .client_version
+            .as_deref()
+            .and_then(|v| reqwest::header::HeaderValue::from_str(v).ok())
+        {
+            headers.insert(CLIENT_VERSION_HEADER, value);
+        }
+        headers
     }
 
     async fn handle_response<T: for<'de> Deserialize<'de>>(
@@ -788,11 +1392,15 @@ impl Paycheck {
             struct ErrorResponse {
                 error: Option<String>,
                 details: Option<String>,
+                code: Option<String>,
+                revocation: Option<Revocation>,
             }
 
             let error_body: ErrorResponse = response.json().await.unwrap_or(ErrorResponse {
                 error: Some("Unknown error".to_string()),
                 details: None,
+                code: None,
+                revocation: None,
             });
 
             let message = match (&error_body.error, &error_body.details) {
@@ -801,9 +1409,15 @@ impl Paycheck {
                 (None, Some(details)) => details.clone(),
                 (None, None) => format!("Request failed: {}", status),
             };
-            let code = map_status_to_error_code(status, &message);
+            let code = match error_body.code.as_deref() {
+                Some("license_revoked") => PaycheckErrorCode::LicenseRevoked,
+                Some("already_licensed") => PaycheckErrorCode::AlreadyLicensed,
+                _ => map_status_to_error_code(status, &message),
+            };
 
-            return Err(PaycheckError::with_status(code, message, status));
+            let mut error = PaycheckError::with_status(code, message, status);
+            error.revocation = error_body.revocation;
+            return Err(error);
         }
 
         response
@@ -820,6 +1434,11 @@ impl std::fmt::Debug for Paycheck {
             .field("device_id", &self.device_id)
             .field("device_type", &self.device_type)
             .field("auto_refresh", &self.auto_refresh)
+            .field("client_version", &self.client_version)
+            .field("validation_policy", &self.validation_policy)
+            .field("retry_policy", &self.retry_policy)
+            .field("timeout", &self.timeout)
+            .field("call_timeouts", &self.call_timeouts)
             .finish()
     }
 }
@@ -834,4 +1453,7 @@ pub struct CheckoutOptions {
     pub provider: Option<String>,
     /// Your customer identifier (flows through to license)
     pub customer_id: Option<String>,
+    /// Buy even if this customer already has an active license for the
+    /// product (otherwise the server answers `AlreadyLicensed`)
+    pub force_new_purchase: bool,
 }
@@ -7,6 +7,7 @@ use std::sync::RwLock;
 pub mod keys {
     pub const TOKEN: &str = concat!("paycheck:", "token");
     pub const DEVICE_ID: &str = concat!("paycheck:", "device_id");
+    pub const LAST_VALIDATION: &str = concat!("paycheck:", "last_validation");
 }
 
 /// Storage adapter trait for custom storage implementations
@@ -1,5 +1,7 @@
 //! Type definitions for the Paycheck SDK
 
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
 /// Device type for license activation
@@ -88,6 +90,8 @@ pub struct ActivationResult {
     pub tier: String,
     /// Enabled features
     pub features: Vec<String>,
+    /// Structured entitlements (limits, flags, quotas)
+    pub entitlements: HashMap<String, EntitlementValue>,
     /// Short-lived code for future activations (PREFIX-XXXX-XXXX format)
     pub activation_code: String,
     /// When activation code expires (30 minutes from creation)
@@ -102,6 +106,8 @@ pub(crate) struct RedeemResponse {
     pub updates_exp: Option<i64>,
     pub tier: String,
     pub features: Vec<String>,
+    #[serde(default)]
+    pub entitlements: HashMap<String, EntitlementValue>,
     pub activation_code: String,
     pub activation_code_expires_at: i64,
 }
@@ -114,12 +120,51 @@ impl From<RedeemResponse> for ActivationResult {
             updates_exp: r.updates_exp,
             tier: r.tier,
             features: r.features,
+            entitlements: r.entitlements,
             activation_code: r.activation_code,
             activation_code_expires_at: r.activation_code_expires_at,
         }
     }
 }
 
+/// A structured entitlement value: a numeric limit, a flag, or a text value
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum EntitlementValue {
+    /// On/off flag (e.g. `"sso": true`)
+    Bool(bool),
+    /// Numeric limit or quota (e.g. `"seats": 5`)
+    Number(i64),
+    /// Free-form value (e.g. `"support": "priority"`)
+    Text(String),
+}
+
+impl EntitlementValue {
+    /// The numeric value, if this is a number.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Self::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// The flag value, if this is a bool.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// The text value, if this is a string.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::Text(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
 /// Decoded JWT claims.
 ///
 /// # Important: Three Expiration-Related Claims
@@ -140,7 +185,7 @@ pub struct LicenseClaims {
     // Standard JWT claims
     /// Issuer ("paycheck")
     pub iss: String,
-    /// Subject (license_id)
+    /// Subject (license ref: the license's public handle, not its ID)
     pub sub: String,
     /// Audience (project name, for debugging - not verified)
     pub aud: String,
@@ -170,16 +215,53 @@ pub struct LicenseClaims {
     pub tier: String,
     /// Enabled feature flags for `has_feature()` checks
     pub features: Vec<String>,
+    /// Structured entitlements for `get_entitlement()` checks (empty when the
+    /// product has none)
+    #[serde(default)]
+    pub entitlements: HashMap<String, EntitlementValue>,
     /// Device identifier (verified against current device to prevent token theft)
     pub device_id: String,
     /// Device type
     pub device_type: DeviceType,
+    /// License ref, same as `sub` (None on tokens from older servers)
+    #[serde(default)]
+    pub license_ref: Option<String>,
     /// Product UUID
     pub product_id: String,
 }
 
+/// Why a license was revoked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RevocationReason {
+    /// The purchase was refunded
+    Refund,
+    /// The payment was disputed with the bank
+    Chargeback,
+    /// A delayed payment (e.g. bank debit) failed after checkout
+    PaymentFailed,
+    /// Revoked for abuse (e.g. key sharing)
+    Abuse,
+    /// Replaced by an upgrade purchase
+    Superseded,
+    /// Revoked by the publisher for another reason
+    AdminOther,
+    /// A reason this SDK version doesn't know
+    #[serde(other)]
+    Unknown,
+}
+
+/// Why and when a license was revoked, safe to show to the user
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Revocation {
+    pub reason: RevocationReason,
+    /// Message from the publisher, if they left one
+    pub message: Option<String>,
+    pub revoked_at: Option<i64>,
+}
+
 /// Result from online validation
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct ValidateResult {
     /// Whether the license is valid
     pub valid: bool,
@@ -187,14 +269,68 @@ pub struct ValidateResult {
     pub license_exp: Option<i64>,
     /// When version access expires (if valid)
     pub updates_exp: Option<i64>,
+    /// Whether the license's update window is still open
+    pub updates_valid: bool,
+    /// When update access ends, as of now on the server (`None` = perpetual).
+    /// Unlike `updates_exp`, this reflects renewals made since the token was issued.
+    pub updates_expires_at: Option<i64>,
+    /// Set when the license has been revoked
+    pub revocation: Option<Revocation>,
+    /// The product's structured entitlements as of now (empty if invalid)
+    pub entitlements: HashMap<String, EntitlementValue>,
+    /// Tokens revoked on the license, such as those of remotely deactivated
+    /// devices. Only filled in by `validate_online_with_revocations()`.
+    pub revoked_jtis: Vec<RevokedToken>,
+    /// The project's client flags and update hint
+    pub hints: ClientHints,
+}
+
+/// A token revoked on the license. A cached token whose `jti` is listed
+/// should no longer be trusted, even offline.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RevokedToken {
+    pub jti: String,
+    pub revoked_at: i64,
+}
+
+/// What the publisher wants deployed apps to know, returned by `/validate`
+/// and `/refresh`. Read live from the project, not from the token.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct ClientHints {
+    /// The project's client flags (announcements, feature killswitches), as
+    /// the publisher set them
+    #[serde(default)]
+    pub client_flags: HashMap<String, serde_json::Value>,
+    /// This app is older than the project's minimum supported version. Only
+    /// set when the client was created with a `client_version`.
+    #[serde(default)]
+    pub update_required: bool,
+    /// The minimum supported version, if the project has one
+    #[serde(default)]
+    pub min_client_version: Option<String>,
 }
 
 /// API response for validate endpoint
 #[derive(Debug, Deserialize)]
 pub(crate) struct ValidateResponse {
     pub valid: bool,
+    /// "revoked" or "suspended_for_dispute" when the server says why
+    #[serde(default)]
+    pub reason: Option<String>,
     pub license_exp: Option<i64>,
     pub updates_exp: Option<i64>,
+    #[serde(default)]
+    pub updates_valid: bool,
+    #[serde(default)]
+    pub updates_expires_at: Option<i64>,
+    #[serde(default)]
+    pub revocation: Option<Revocation>,
+    #[serde(default)]
+    pub entitlements: HashMap<String, EntitlementValue>,
+    #[serde(default)]
+    pub revoked_jtis: Vec<RevokedToken>,
+    #[serde(flatten)]
+    pub hints: ClientHints,
 }
 
 impl From<ValidateResponse> for ValidateResult {
@@ -203,10 +339,29 @@ impl From<ValidateResponse> for ValidateResult {
             valid: r.valid,
             license_exp: r.license_exp,
             updates_exp: r.updates_exp,
+            updates_valid: r.updates_valid,
+            updates_expires_at: r.updates_expires_at,
+            revocation: r.revocation,
+            entitlements: r.entitlements,
+            revoked_jtis: r.revoked_jtis,
+            hints: r.hints,
         }
     }
 }
 
+/// Short-lived proof of a valid license for a third party.
+///
+/// The attestation is a JWT signed with the project key (verify it against
+/// the project's JWKS) carrying only the tier, product, license expiration,
+/// and a per-audience license hash.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Attestation {
+    /// The signed attestation JWT
+    pub attestation: String,
+    /// When the attestation expires (Unix seconds)
+    pub expires_at: i64,
+}
+
 /// Device info from license info endpoint
 #[derive(Debug, Clone)]
 pub struct LicenseDeviceInfo {
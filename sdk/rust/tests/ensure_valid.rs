@@ -0,0 +1,492 @@
+//! `ensure_valid()` walked through its states against a scripted server:
+//! fresh, near expiry, revoked, and server down. Each test asserts the exact
+//! requests the SDK made.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use ed25519_dalek::{Signer, SigningKey};
+use paycheck_sdk::jwt::now;
+use paycheck_sdk::storage::keys;
+use paycheck_sdk::{
+    DeviceType, MemoryStorage, Paycheck, PaycheckErrorCode, PaycheckOptions, RetryPolicy,
+    RevocationReason, StorageAdapter, ValidationPolicy,
+};
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+const DEVICE_ID: &str = "device-1";
+const JTI: &str = "jti-1";
+const ONE_HOUR: i64 = 60 * 60;
+const ONE_DAY: i64 = 24 * ONE_HOUR;
+
+/// A server that answers each request with the next scripted response and
+/// records "METHOD /path" for every request it gets.
+struct MockServer {
+    base_url: String,
+    calls: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockServer {
+    async fn start(script: Vec<(u16, Value)>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let script = Arc::new(Mutex::new(VecDeque::from(script)));
+
+        let recorded = calls.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let Some(call) = read_request(&mut stream).await else {
+                    continue;
+                };
+                recorded.lock().unwrap().push(call);
+                let (status, body) = script
+                    .lock()
+                    .unwrap()
+                    .pop_front()
+                    .unwrap_or((418, json!({ "error": "Unscripted request" })));
+                let body = body.to_string();
+                let response = format!(
+                    "HTTP/1.1 {} Scripted\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+
+        Self { base_url, calls }
+    }
+
+    /// An address nothing listens on, so every request fails to connect.
+    async fn down() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        Self {
+            base_url,
+            calls: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+/// Read one request and return its method and path.
+async fn read_request(stream: &mut TcpStream) -> Option<String> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    loop {
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") else {
+            continue;
+        };
+
+        let head = String::from_utf8_lossy(&buf[..end]).to_string();
+        let length = head
+            .lines()
+            .find_map(|line| {
+                line.to_ascii_lowercase()
+                    .strip_prefix("content-length:")
+                    .map(|v| v.trim().parse::<usize>().unwrap_or(0))
+            })
+            .unwrap_or(0);
+        while buf.len() < end + 4 + length {
+            let n = stream.read(&mut chunk).await.ok()?;
+            if n == 0 {
+                break;
+            }
+            buf.extend_from_slice(&chunk[..n]);
+        }
+
+        let mut request_line = head.lines().next()?.split_whitespace();
+        return Some(format!("{} {}", request_line.next()?, request_line.next()?));
+    }
+}
+
+fn signing_key() -> SigningKey {
+    SigningKey::from_bytes(&[7u8; 32])
+}
+
+fn public_key() -> String {
+    STANDARD.encode(signing_key().verifying_key().to_bytes())
+}
+
+/// A signed license token whose `exp` is `exp_in` seconds away.
+fn token(exp_in: i64, license_exp: Option<i64>) -> String {
+    let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"EdDSA","typ":"JWT"}"#);
+    let claims = json!({
+        "iss": "paycheck",
+        "sub": "license-1",
+        "aud": "Test Project",
+        "jti": JTI,
+        "iat": now() - 60,
+        "exp": now() + exp_in,
+        "license_exp": license_exp,
+        "updates_exp": null,
+        "tier": "pro",
+        "features": ["export"],
+        "device_id": DEVICE_ID,
+        "device_type": "uuid",
+        "product_id": "product-1",
+    });
+    let payload = URL_SAFE_NO_PAD.encode(claims.to_string());
+    let message = format!("{}.{}", header, payload);
+    let signature = URL_SAFE_NO_PAD.encode(signing_key().sign(message.as_bytes()).to_bytes());
+    format!("{}.{}", message, signature)
+}
+
+fn valid_response(license_exp: Option<i64>) -> (u16, Value) {
+    (
+        200,
+        json!({ "valid": true, "license_exp": license_exp, "updates_valid": true }),
+    )
+}
+
+fn revoked_response() -> (u16, Value) {
+    (
+        200,
+        json!({
+            "valid": false,
+            "reason": "revoked",
+            "revocation": { "reason": "refund", "message": "Refunded on request", "revoked_at": now() },
+        }),
+    )
+}
+
+fn refresh_response(token: &str) -> (u16, Value) {
+    (200, json!({ "token": token }))
+}
+
+fn unauthorized() -> (u16, Value) {
+    (401, json!({ "error": "Unauthorized" }))
+}
+
+struct Scenario {
+    server: MockServer,
+    storage: Arc<MemoryStorage>,
+    paycheck: Paycheck,
+}
+
+impl Scenario {
+    async fn new(server: MockServer, token: &str) -> Self {
+        let storage = Arc::new(MemoryStorage::new());
+        storage.set(keys::TOKEN, token);
+        let paycheck = Paycheck::new(
+            &public_key(),
+            PaycheckOptions {
+                base_url: Some(server.base_url.clone()),
+                storage: Some(storage.clone() as Arc<dyn StorageAdapter>),
+                device_type: Some(DeviceType::Uuid),
+                device_id: Some(DEVICE_ID.into()),
+                validation_policy: Some(ValidationPolicy::default()),
+                // Retries have their own tests; each call here is one request
+                retry_policy: Some(RetryPolicy::disabled()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        Self {
+            server,
+            storage,
+            paycheck,
+        }
+    }
+
+    /// Pretend the server last confirmed the license at `validated_at`.
+    fn validated_at(self, validated_at: i64) -> Self {
+        let last = json!({ "jti": JTI, "validated_at": validated_at });
+        self.storage.set(keys::LAST_VALIDATION, &last.to_string());
+        self
+    }
+
+    fn calls(&self) -> Vec<String> {
+        self.server.calls()
+    }
+}
+
+// ==================== Fresh ====================
+
+#[tokio::test]
+async fn test_fresh_and_recently_validated_makes_no_calls() {
+    let s = Scenario::new(MockServer::start(vec![]).await, &token(ONE_HOUR, None))
+        .await
+        .validated_at(now() - 60);
+
+    let status = s.paycheck.ensure_valid().await.unwrap();
+    assert_eq!(status.claims.tier, "pro");
+    assert!(!status.offline);
+    assert!(!status.refreshed);
+    assert!(s.calls().is_empty());
+}
+
+#[tokio::test]
+async fn test_fresh_validates_at_most_once_per_interval() {
+    let s = Scenario::new(
+        MockServer::start(vec![valid_response(None)]).await,
+        &token(ONE_HOUR, None),
+    )
+    .await;
+
+    let first = s.paycheck.ensure_valid().await.unwrap();
+    assert!(first.validated_at >= now() - 5);
+    let second = s.paycheck.ensure_valid().await.unwrap();
+    assert_eq!(second.validated_at, first.validated_at);
+    assert_eq!(s.calls(), ["POST /validate"]);
+}
+
+#[tokio::test]
+async fn test_interval_elapsed_revalidates() {
+    let s = Scenario::new(
+        MockServer::start(vec![valid_response(None)]).await,
+        &token(ONE_HOUR, None),
+    )
+    .await
+    .validated_at(now() - 2 * ONE_HOUR);
+
+    let status = s.paycheck.ensure_valid().await.unwrap();
+    assert!(status.validated_at >= now() - 5);
+    assert_eq!(s.calls(), ["POST /validate"]);
+}
+
+#[tokio::test]
+async fn test_renewal_on_server_refreshes_token() {
+    let renewed_until = now() + 365 * ONE_DAY;
+    let renewed = token(ONE_HOUR, Some(renewed_until));
+    let s = Scenario::new(
+        MockServer::start(vec![
+            valid_response(Some(renewed_until)),
+            refresh_response(&renewed),
+        ])
+        .await,
+        &token(ONE_HOUR, Some(now() + ONE_DAY)),
+    )
+    .await;
+
+    let status = s.paycheck.ensure_valid().await.unwrap();
+    assert!(status.refreshed);
+    assert_eq!(status.claims.license_exp, Some(renewed_until));
+    assert_eq!(s.paycheck.get_token(), Some(renewed));
+    assert_eq!(s.calls(), ["POST /validate", "POST /refresh"]);
+}
+
+// ==================== Near expiry ====================
+
+#[tokio::test]
+async fn test_near_expiry_refreshes_instead_of_validating() {
+    let fresh = token(ONE_HOUR, None);
+    let s = Scenario::new(
+        MockServer::start(vec![refresh_response(&fresh)]).await,
+        &token(60, None),
+    )
+    .await;
+
+    let status = s.paycheck.ensure_valid().await.unwrap();
+    assert!(status.refreshed);
+    assert!(!status.offline);
+    assert_eq!(s.paycheck.get_token(), Some(fresh));
+
+    // The refresh counts as the server check
+    s.paycheck.ensure_valid().await.unwrap();
+    assert_eq!(s.calls(), ["POST /refresh"]);
+}
+
+#[tokio::test]
+async fn test_expired_jwt_is_refreshed() {
+    let fresh = token(ONE_HOUR, None);
+    let s = Scenario::new(
+        MockServer::start(vec![refresh_response(&fresh)]).await,
+        &token(-ONE_HOUR, None),
+    )
+    .await
+    .validated_at(now() - 60);
+
+    assert!(s.paycheck.ensure_valid().await.unwrap().refreshed);
+    assert_eq!(s.calls(), ["POST /refresh"]);
+}
+
+// ==================== Revoked ====================
+
+#[tokio::test]
+async fn test_revoked_on_validate_clears_token() {
+    let s = Scenario::new(
+        MockServer::start(vec![revoked_response()]).await,
+        &token(ONE_HOUR, None),
+    )
+    .await;
+
+    let error = s.paycheck.ensure_valid().await.unwrap_err();
+    assert_eq!(error.code, PaycheckErrorCode::LicenseRevoked);
+    assert_eq!(error.message, "Refunded on request");
+    assert_eq!(error.revocation.unwrap().reason, RevocationReason::Refund);
+    assert_eq!(s.paycheck.get_token(), None);
+    assert_eq!(s.storage.get(keys::LAST_VALIDATION), None);
+
+    // Nothing left to check
+    let error = s.paycheck.ensure_valid().await.unwrap_err();
+    assert_eq!(error.code, PaycheckErrorCode::NoToken);
+    assert_eq!(s.calls(), ["POST /validate"]);
+}
+
+#[tokio::test]
+async fn test_refused_refresh_asks_validate_why() {
+    let s = Scenario::new(
+        MockServer::start(vec![unauthorized(), revoked_response()]).await,
+        &token(60, None),
+    )
+    .await
+    .validated_at(now() - 60);
+
+    let error = s.paycheck.ensure_valid().await.unwrap_err();
+    assert_eq!(error.code, PaycheckErrorCode::LicenseRevoked);
+    assert!(error.revocation.is_some());
+    assert_eq!(s.paycheck.get_token(), None);
+    assert_eq!(s.calls(), ["POST /refresh", "POST /validate"]);
+}
+
+#[tokio::test]
+async fn test_deactivated_device_clears_token() {
+    let s = Scenario::new(
+        MockServer::start(vec![(200, json!({ "valid": false }))]).await,
+        &token(ONE_HOUR, None),
+    )
+    .await;
+
+    let error = s.paycheck.ensure_valid().await.unwrap_err();
+    assert_eq!(error.code, PaycheckErrorCode::LicenseRevoked);
+    assert!(error.revocation.is_none());
+    assert_eq!(s.paycheck.get_token(), None);
+    assert_eq!(s.calls(), ["POST /validate"]);
+}
+
+#[tokio::test]
+async fn test_suspended_license_keeps_token() {
+    let s = Scenario::new(
+        MockServer::start(vec![(
+            200,
+            json!({ "valid": false, "reason": "suspended_for_dispute" }),
+        )])
+        .await,
+        &token(ONE_HOUR, None),
+    )
+    .await;
+
+    let error = s.paycheck.ensure_valid().await.unwrap_err();
+    assert_eq!(error.code, PaycheckErrorCode::LicenseRevoked);
+    assert!(s.paycheck.get_token().is_some());
+}
+
+#[tokio::test]
+async fn test_expired_license_keeps_token_for_renewal() {
+    let expired = token(ONE_HOUR, Some(now() - 10));
+    let s = Scenario::new(
+        MockServer::start(vec![unauthorized(), (200, json!({ "valid": false }))]).await,
+        &expired,
+    )
+    .await
+    .validated_at(now() - 60);
+
+    let error = s.paycheck.ensure_valid().await.unwrap_err();
+    assert_eq!(error.code, PaycheckErrorCode::LicenseExpired);
+    assert_eq!(s.paycheck.get_token(), Some(expired));
+    assert_eq!(s.calls(), ["POST /refresh", "POST /validate"]);
+}
+
+// ==================== Server down ====================
+
+#[tokio::test]
+async fn test_server_down_within_grace_works_offline() {
+    let last_check = now() - 2 * ONE_HOUR;
+    let s = Scenario::new(MockServer::down().await, &token(ONE_HOUR, None))
+        .await
+        .validated_at(last_check);
+
+    let status = s.paycheck.ensure_valid().await.unwrap();
+    assert!(status.offline);
+    assert_eq!(status.validated_at, last_check);
+    assert!(s.paycheck.get_token().is_some());
+}
+
+#[tokio::test]
+async fn test_server_errors_count_as_down() {
+    let s = Scenario::new(
+        MockServer::start(vec![(503, json!({ "error": "Service unavailable" }))]).await,
+        &token(ONE_HOUR, None),
+    )
+    .await
+    .validated_at(now() - 2 * ONE_HOUR);
+
+    assert!(s.paycheck.ensure_valid().await.unwrap().offline);
+    assert_eq!(s.calls(), ["POST /validate"]);
+}
+
+#[tokio::test]
+async fn test_server_down_near_expiry_works_offline() {
+    let s = Scenario::new(MockServer::down().await, &token(-ONE_DAY, None))
+        .await
+        .validated_at(now() - ONE_DAY);
+
+    let status = s.paycheck.ensure_valid().await.unwrap();
+    assert!(status.offline);
+    assert!(!status.refreshed);
+}
+
+#[tokio::test]
+async fn test_server_down_past_grace_fails() {
+    let s = Scenario::new(MockServer::down().await, &token(ONE_HOUR, None))
+        .await
+        .validated_at(now() - 8 * ONE_DAY);
+
+    let error = s.paycheck.ensure_valid().await.unwrap_err();
+    assert_eq!(error.code, PaycheckErrorCode::NetworkError);
+    // Reconnecting brings the license back
+    assert!(s.paycheck.get_token().is_some());
+}
+
+#[tokio::test]
+async fn test_server_down_with_expired_license_fails() {
+    let s = Scenario::new(MockServer::down().await, &token(ONE_HOUR, Some(now() - 10)))
+        .await
+        .validated_at(now() - 60);
+
+    let error = s.paycheck.ensure_valid().await.unwrap_err();
+    assert_eq!(error.code, PaycheckErrorCode::LicenseExpired);
+}
+
+#[tokio::test]
+async fn test_policy_knobs_are_honored() {
+    let server = MockServer::start(vec![valid_response(None), valid_response(None)]).await;
+    let storage = Arc::new(MemoryStorage::new());
+    storage.set(keys::TOKEN, &token(ONE_HOUR, None));
+    let paycheck = Paycheck::new(
+        &public_key(),
+        PaycheckOptions {
+            base_url: Some(server.base_url.clone()),
+            storage: Some(storage as Arc<dyn StorageAdapter>),
+            device_type: Some(DeviceType::Uuid),
+            device_id: Some(DEVICE_ID.into()),
+            validation_policy: Some(ValidationPolicy {
+                revalidate_interval: Duration::ZERO,
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    paycheck.ensure_valid().await.unwrap();
+    paycheck.ensure_valid().await.unwrap();
+    assert_eq!(server.calls(), ["POST /validate", "POST /validate"]);
+}
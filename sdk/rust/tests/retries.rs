@@ -0,0 +1,497 @@
+//! Retries and timeouts against a scripted server that fails before it
+//! succeeds: which calls are retried and on which failures, how often, and
+//! that one activation keeps its `Idempotency-Key` across attempts.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use ed25519_dalek::{Signer, SigningKey};
+use paycheck_sdk::jwt::now;
+use paycheck_sdk::storage::keys;
+use paycheck_sdk::{
+    DeviceType, MemoryStorage, Operation, Paycheck, PaycheckErrorCode, PaycheckOptions,
+    RetryPolicy, StorageAdapter,
+};
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+const DEVICE_ID: &str = "device-1";
+const ONE_HOUR: i64 = 60 * 60;
+
+/// One scripted answer.
+struct Reply {
+    status: u16,
+    body: Value,
+    retry_after: Option<&'static str>,
+    delay: Duration,
+}
+
+fn reply(status: u16, body: Value) -> Reply {
+    Reply {
+        status,
+        body,
+        retry_after: None,
+        delay: Duration::ZERO,
+    }
+}
+
+fn unavailable() -> Reply {
+    reply(503, json!({ "error": "Service unavailable" }))
+}
+
+fn rate_limited(retry_after: Option<&'static str>) -> Reply {
+    Reply {
+        retry_after,
+        ..reply(429, json!({ "error": "Too many requests" }))
+    }
+}
+
+/// A request the server got: "METHOD /path" and its `Idempotency-Key`.
+#[derive(Debug, Clone, PartialEq)]
+struct Call {
+    line: String,
+    idempotency_key: Option<String>,
+}
+
+/// A server that answers each request with the next scripted reply and
+/// records every request it gets.
+struct MockServer {
+    base_url: String,
+    calls: Arc<Mutex<Vec<Call>>>,
+}
+
+impl MockServer {
+    async fn start(script: Vec<Reply>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        serve(listener, script, calls.clone());
+        Self { base_url, calls }
+    }
+
+    /// A server that refuses connections until `after` has passed.
+    async fn start_after(after: Duration, script: Vec<Reply>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        let calls = Arc::new(Mutex::new(Vec::new()));
+
+        let recorded = calls.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(after).await;
+            serve(TcpListener::bind(addr).await.unwrap(), script, recorded);
+        });
+
+        Self {
+            base_url: format!("http://{}", addr),
+            calls,
+        }
+    }
+
+    fn lines(&self) -> Vec<String> {
+        self.calls().into_iter().map(|call| call.line).collect()
+    }
+
+    fn calls(&self) -> Vec<Call> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+fn serve(listener: TcpListener, script: Vec<Reply>, calls: Arc<Mutex<Vec<Call>>>) {
+    let script = Arc::new(Mutex::new(VecDeque::from(script)));
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let Some(call) = read_request(&mut stream).await else {
+                continue;
+            };
+            calls.lock().unwrap().push(call);
+            let reply = script
+                .lock()
+                .unwrap()
+                .pop_front()
+                .unwrap_or_else(|| reply(418, json!({ "error": "Unscripted request" })));
+
+            // Answer on its own task so a slow reply doesn't hold up the next request
+            tokio::spawn(async move {
+                tokio::time::sleep(reply.delay).await;
+                let body = reply.body.to_string();
+                let retry_after = reply
+                    .retry_after
+                    .map(|secs| format!("Retry-After: {}\r\n", secs))
+                    .unwrap_or_default();
+                let response = format!(
+                    "HTTP/1.1 {} Scripted\r\nContent-Type: application/json\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    reply.status,
+                    retry_after,
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            });
+        }
+    });
+}
+
+/// Read one request and return its method, path and `Idempotency-Key`.
+async fn read_request(stream: &mut TcpStream) -> Option<Call> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    loop {
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") else {
+            continue;
+        };
+
+        let head = String::from_utf8_lossy(&buf[..end]).to_string();
+        let header = |name: &str| {
+            head.lines().find_map(|line| {
+                let (key, value) = line.split_once(':')?;
+                key.eq_ignore_ascii_case(name)
+                    .then(|| value.trim().to_string())
+            })
+        };
+        let length = header("content-length")
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(0);
+        while buf.len() < end + 4 + length {
+            let n = stream.read(&mut chunk).await.ok()?;
+            if n == 0 {
+                break;
+            }
+            buf.extend_from_slice(&chunk[..n]);
+        }
+
+        let mut request_line = head.lines().next()?.split_whitespace();
+        return Some(Call {
+            line: format!("{} {}", request_line.next()?, request_line.next()?),
+            idempotency_key: header("idempotency-key"),
+        });
+    }
+}
+
+fn signing_key() -> SigningKey {
+    SigningKey::from_bytes(&[7u8; 32])
+}
+
+fn public_key() -> String {
+    STANDARD.encode(signing_key().verifying_key().to_bytes())
+}
+
+/// A signed license token that's good for another hour.
+fn token() -> String {
+    let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"EdDSA","typ":"JWT"}"#);
+    let claims = json!({
+        "iss": "paycheck",
+        "sub": "license-1",
+        "aud": "Test Project",
+        "jti": "jti-1",
+        "iat": now() - 60,
+        "exp": now() + ONE_HOUR,
+        "license_exp": null,
+        "updates_exp": null,
+        "tier": "pro",
+        "features": ["export"],
+        "device_id": DEVICE_ID,
+        "device_type": "uuid",
+        "product_id": "product-1",
+    });
+    let payload = URL_SAFE_NO_PAD.encode(claims.to_string());
+    let message = format!("{}.{}", header, payload);
+    let signature = URL_SAFE_NO_PAD.encode(signing_key().sign(message.as_bytes()).to_bytes());
+    format!("{}.{}", message, signature)
+}
+
+fn valid_response() -> Reply {
+    reply(
+        200,
+        json!({ "valid": true, "license_exp": null, "updates_valid": true }),
+    )
+}
+
+fn redeem_response() -> Reply {
+    reply(
+        200,
+        json!({
+            "token": token(),
+            "license_exp": null,
+            "updates_exp": null,
+            "tier": "pro",
+            "features": ["export"],
+            "activation_code": "MYAPP-AB3D-EF5G",
+            "activation_code_expires_at": now() + 1800,
+        }),
+    )
+}
+
+/// Short backoffs so the tests don't wait on them.
+fn fast_retries() -> RetryPolicy {
+    RetryPolicy {
+        initial_backoff: Duration::from_millis(10),
+        max_backoff: Duration::from_secs(2),
+        ..Default::default()
+    }
+}
+
+fn client(server: &MockServer, options: PaycheckOptions) -> Paycheck {
+    let storage = Arc::new(MemoryStorage::new());
+    storage.set(keys::TOKEN, &token());
+    Paycheck::new(
+        &public_key(),
+        PaycheckOptions {
+            base_url: Some(server.base_url.clone()),
+            storage: Some(storage as Arc<dyn StorageAdapter>),
+            device_type: Some(DeviceType::Uuid),
+            device_id: Some(DEVICE_ID.into()),
+            retry_policy: options.retry_policy.or(Some(fast_retries())),
+            ..options
+        },
+    )
+    .unwrap()
+}
+
+// ==================== Idempotent calls ====================
+
+#[tokio::test]
+async fn test_validate_retried_after_server_error() {
+    let server = MockServer::start(vec![unavailable(), valid_response()]).await;
+    let paycheck = client(&server, Default::default());
+
+    assert!(paycheck.validate_online().await.unwrap().valid);
+    assert_eq!(server.lines(), ["POST /validate", "POST /validate"]);
+}
+
+#[tokio::test]
+async fn test_refresh_and_license_info_retried() {
+    let server = MockServer::start(vec![
+        reply(502, json!({ "error": "Bad gateway" })),
+        reply(200, json!({ "token": token() })),
+        unavailable(),
+        reply(
+            200,
+            json!({
+                "status": "active",
+                "created_at": now(),
+                "expires_at": null,
+                "updates_expires_at": null,
+                "activation_count": 1,
+                "activation_limit": null,
+                "device_count": 1,
+                "device_limit": null,
+                "devices": [],
+            }),
+        ),
+    ])
+    .await;
+    let paycheck = client(&server, Default::default());
+
+    paycheck.refresh_token().await.unwrap();
+    let info = paycheck.get_license_info().await.unwrap();
+    assert_eq!(info.activation_count, 1);
+    let license_info = format!(
+        "GET /license?public_key={}",
+        urlencoding::encode(&public_key())
+    );
+    assert_eq!(
+        server.lines(),
+        [
+            "POST /refresh".to_string(),
+            "POST /refresh".to_string(),
+            license_info.clone(),
+            license_info,
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_connection_failures_retried() {
+    let server = MockServer::start_after(Duration::from_millis(20), vec![valid_response()]).await;
+    let paycheck = client(
+        &server,
+        PaycheckOptions {
+            retry_policy: Some(RetryPolicy {
+                initial_backoff: Duration::from_millis(200),
+                ..fast_retries()
+            }),
+            ..Default::default()
+        },
+    );
+
+    assert!(paycheck.validate_online().await.unwrap().valid);
+    assert_eq!(server.lines(), ["POST /validate"]);
+}
+
+#[tokio::test]
+async fn test_gives_up_after_max_attempts() {
+    let server = MockServer::start(vec![
+        unavailable(),
+        unavailable(),
+        unavailable(),
+        valid_response(),
+    ])
+    .await;
+    let paycheck = client(&server, Default::default());
+
+    let error = paycheck.refresh_token().await.unwrap_err();
+    assert_eq!(error.code, PaycheckErrorCode::NetworkError);
+    assert_eq!(error.status_code, Some(503));
+    assert_eq!(server.lines().len(), 3);
+}
+
+#[tokio::test]
+async fn test_client_errors_not_retried() {
+    let server = MockServer::start(vec![reply(401, json!({ "error": "Unauthorized" }))]).await;
+    let paycheck = client(&server, Default::default());
+
+    let error = paycheck.refresh_token().await.unwrap_err();
+    assert_eq!(error.status_code, Some(401));
+    assert_eq!(server.lines(), ["POST /refresh"]);
+}
+
+#[tokio::test]
+async fn test_rate_limit_retried_only_with_retry_after() {
+    // Retry-After within max_backoff: retried
+    let server = MockServer::start(vec![rate_limited(Some("0")), valid_response()]).await;
+    let paycheck = client(&server, Default::default());
+    assert!(paycheck.validate_online().await.unwrap().valid);
+    assert_eq!(server.lines().len(), 2);
+
+    // No Retry-After, or longer than max_backoff: surfaced as is
+    for retry_after in [None, Some("120")] {
+        let server = MockServer::start(vec![rate_limited(retry_after), valid_response()]).await;
+        let paycheck = client(&server, Default::default());
+        assert!(!paycheck.validate_online().await.unwrap().valid);
+        assert_eq!(server.lines().len(), 1, "{:?}", retry_after);
+    }
+}
+
+#[tokio::test]
+async fn test_disabled_policy_makes_one_attempt() {
+    let server = MockServer::start(vec![unavailable(), valid_response()]).await;
+    let paycheck = client(
+        &server,
+        PaycheckOptions {
+            retry_policy: Some(RetryPolicy::disabled()),
+            ..Default::default()
+        },
+    );
+
+    assert!(!paycheck.validate_online().await.unwrap().valid);
+    assert_eq!(server.lines(), ["POST /validate"]);
+}
+
+// ==================== Activation ====================
+
+#[tokio::test]
+async fn test_activation_not_retried_by_default() {
+    let server = MockServer::start(vec![unavailable(), redeem_response()]).await;
+    let paycheck = client(&server, Default::default());
+
+    let error = paycheck
+        .activate_with_code("MYAPP-AB3D-EF5G", None)
+        .await
+        .unwrap_err();
+    assert_eq!(error.status_code, Some(503));
+    let calls = server.calls();
+    assert_eq!(calls.len(), 1);
+    assert!(calls[0].idempotency_key.is_some());
+}
+
+#[tokio::test]
+async fn test_activation_retries_reuse_idempotency_key() {
+    let server = MockServer::start(vec![
+        unavailable(),
+        unavailable(),
+        redeem_response(),
+        redeem_response(),
+    ])
+    .await;
+    let paycheck = client(
+        &server,
+        PaycheckOptions {
+            retry_policy: Some(RetryPolicy {
+                retry_activation: true,
+                ..fast_retries()
+            }),
+            ..Default::default()
+        },
+    );
+
+    let result = paycheck
+        .activate_with_code("MYAPP-AB3D-EF5G", None)
+        .await
+        .unwrap();
+    assert_eq!(result.tier, "pro");
+    paycheck
+        .activate_with_code("MYAPP-AB3D-EF5G", None)
+        .await
+        .unwrap();
+
+    let calls = server.calls();
+    assert_eq!(calls.len(), 4);
+    assert!(calls.iter().all(|call| call.line == "POST /redeem"));
+    let first = calls[0].idempotency_key.clone().unwrap();
+    assert_eq!(calls[1].idempotency_key.as_ref(), Some(&first));
+    assert_eq!(calls[2].idempotency_key.as_ref(), Some(&first));
+    // A new activation is a new key
+    assert_ne!(calls[3].idempotency_key.as_ref(), Some(&first));
+}
+
+#[tokio::test]
+async fn test_other_writes_not_retried() {
+    let server = MockServer::start(vec![unavailable(), unavailable(), unavailable()]).await;
+    let paycheck = client(&server, Default::default());
+
+    assert!(paycheck.checkout("product-1", None).await.is_err());
+    assert!(paycheck
+        .request_activation_code("a@example.com")
+        .await
+        .is_err());
+    assert!(paycheck.deactivate().await.is_err());
+    assert_eq!(
+        server.lines(),
+        [
+            "POST /buy",
+            "POST /activation/request-code",
+            "POST /devices/deactivate"
+        ]
+    );
+}
+
+// ==================== Timeouts ====================
+
+#[tokio::test]
+async fn test_call_timeout_overrides_default() {
+    let slow = |reply: Reply| Reply {
+        delay: Duration::from_millis(500),
+        ..reply
+    };
+    let server = MockServer::start(vec![
+        slow(valid_response()),
+        slow(reply(200, json!({ "token": token() }))),
+    ])
+    .await;
+    let paycheck = client(
+        &server,
+        PaycheckOptions {
+            timeout: Some(Duration::from_secs(5)),
+            call_timeouts: HashMap::from([(Operation::Validate, Duration::from_millis(100))]),
+            ..Default::default()
+        },
+    );
+
+    // Timing out isn't a connection failure, so it isn't retried
+    assert!(!paycheck.validate_online().await.unwrap().valid);
+    assert_eq!(server.lines(), ["POST /validate"]);
+
+    // Other calls keep the default
+    paycheck.refresh_token().await.unwrap();
+    assert_eq!(server.lines(), ["POST /validate", "POST /refresh"]);
+}
@@ -18,9 +18,16 @@ export type {
   DeviceInfo,
   ActivationResult,
   LicenseClaims,
+  EntitlementValue,
+  Entitlements,
   ValidateResult,
+  ClientHints,
+  Revocation,
+  RevocationReason,
+  RevokedToken,
   LicenseInfo,
   LicenseDeviceInfo,
+  Attestation,
   DeactivateResult,
   RequestCodeResult,
   PaycheckErrorCode,
@@ -43,4 +50,5 @@ export {
   isLicenseExpired,
   coversVersion,
   hasFeature,
+  getEntitlement,
 } from './jwt';
@@ -1,5 +1,5 @@
 import * as ed25519 from '@noble/ed25519';
-import type { LicenseClaims } from './types';
+import type { EntitlementValue, LicenseClaims } from './types';
 import { PaycheckError } from './types';
 
 /**
@@ -128,6 +128,16 @@ export function hasFeature(claims: LicenseClaims, feature: string): boolean {
   return claims.features.includes(feature);
 }
 
+/**
+ * Gets a structured entitlement value, or undefined if the license doesn't have it.
+ */
+export function getEntitlement(
+  claims: LicenseClaims,
+  name: string
+): EntitlementValue | undefined {
+  return claims.entitlements?.[name];
+}
+
 /**
  * Verifies a JWT signature using Ed25519.
  * Returns true if the signature is valid, false otherwise.
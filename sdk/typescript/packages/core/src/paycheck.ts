@@ -7,9 +7,15 @@ import type {
   DeviceInfo,
   ActivationResult,
   LicenseClaims,
+  EntitlementValue,
+  Entitlements,
   LicenseInfo,
+  Attestation,
   DeactivateResult,
   RequestCodeResult,
+  Revocation,
+  RevokedToken,
+  ClientHints,
 } from './types';
 import { PaycheckError } from './types';
 import {
@@ -24,6 +30,7 @@ import {
   isLicenseExpired,
   coversVersion as checkCoversVersion,
   hasFeature as checkHasFeature,
+  getEntitlement as checkGetEntitlement,
 } from './jwt';
 
 /**
@@ -40,6 +47,11 @@ export interface PaycheckOptions {
   deviceId?: string;
   /** Auto-refresh expired tokens (default: true) */
   autoRefresh?: boolean;
+  /**
+   * Your app's version, e.g. "2.1.0", so the server can say when it's older
+   * than the project's minimum (default: not sent)
+   */
+  clientVersion?: string;
 }
 
 /**
@@ -52,6 +64,10 @@ export interface OfflineValidateResult {
   claims?: LicenseClaims;
   /** Reason for invalidity */
   reason?: string;
+  /** Why the license was revoked (online checks only) */
+  revocation?: Revocation;
+  /** Tokens revoked on the license (online checks with `includeRevocations` only) */
+  revokedJtis?: RevokedToken[];
 }
 
 /**
@@ -68,6 +84,8 @@ export interface SyncResult {
   offline: boolean;
   /** Reason for invalidity */
   reason?: string;
+  /** Why the license was revoked, when the server reported it */
+  revocation?: Revocation;
 }
 
 /**
@@ -187,6 +205,9 @@ export class Paycheck {
   private deviceId: string;
   private deviceType: DeviceType;
   private autoRefresh: boolean;
+  private clientVersion?: string;
+  /** Hints from the last `/validate` or `/refresh` response */
+  private clientHints: ClientHints = { clientFlags: {}, updateRequired: false };
 
   /**
    * Creates a new Paycheck client.
@@ -205,6 +226,7 @@ export class Paycheck {
     this.deviceType = options.deviceType ?? 'uuid';
     this.deviceId = options.deviceId ?? getOrCreateDeviceId(this.storage);
     this.autoRefresh = options.autoRefresh ?? true;
+    this.clientVersion = options.clientVersion;
   }
 
   // ==================== Private Helpers ====================
@@ -247,6 +269,10 @@ export class Paycheck {
 
     const headers: Record<string, string> = {
       'Content-Type': 'application/json',
+      'X-Paycheck-Project': this.publicKey,
+      ...(this.clientVersion
+        ? { 'X-Paycheck-Client-Version': this.clientVersion }
+        : {}),
       ...options.headers,
     };
 
@@ -266,14 +292,28 @@ export class Paycheck {
 
     if (!response.ok) {
       const errorData = await response.json().catch(() => ({}));
-      const errorObj = errorData as { error?: string; details?: string };
+      const errorObj = errorData as {
+        error?: string;
+        details?: string;
+        code?: string;
+        revocation?: unknown;
+      };
       const message = errorObj.details
         ? `${errorObj.error}: ${errorObj.details}`
         : errorObj.error || `Request failed: ${response.status}`;
+      const code =
+        errorObj.code === 'license_revoked'
+          ? 'LICENSE_REVOKED'
+          : errorObj.code === 'already_licensed'
+            ? 'ALREADY_LICENSED'
+            : mapStatusToErrorCode(response.status, message);
       throw new PaycheckError(
-        mapStatusToErrorCode(response.status, message),
+        code,
         message,
-        response.status
+        response.status,
+        errorObj.revocation
+          ? keysToCamelCase<Revocation>(errorObj.revocation)
+          : undefined
       );
     }
 
@@ -321,6 +361,7 @@ export class Paycheck {
       product_id: productId,
       provider: options.provider,
       customer_id: options.customerId,
+      force_new_purchase: options.forceNewPurchase,
     };
 
     return this.apiRequest<CheckoutResult>('POST', '/buy', { body });
@@ -331,13 +372,16 @@ export class Paycheck {
    *
    * By default, performs offline validation by verifying the Ed25519 signature
    * and checking expiration. Use `{ online: true }` to also check revocation
-   * with the server.
+   * with the server, and add `includeRevocations: true` to get back the
+   * tokens revoked on the license (e.g. devices deactivated remotely), so
+   * cached tokens listed there can be dropped.
    *
    * @param options - Validation options
    * @returns Validation result with claims if valid
    */
   async validate(options?: {
     online?: boolean;
+    includeRevocations?: boolean;
     token?: string;
   }): Promise<OfflineValidateResult> {
     const token = options?.token || this.getStoredToken();
@@ -376,6 +420,13 @@ export class Paycheck {
           valid: boolean;
           license_exp?: number | null;
           updates_exp?: number | null;
+          updates_valid?: boolean;
+          updates_expires_at?: number | null;
+          revocation?: Revocation | null;
+          revoked_jtis?: { jti: string; revoked_at: number }[];
+          client_flags?: Record<string, unknown>;
+          update_required?: boolean;
+          min_client_version?: string;
         }
 
         const response = await this.apiRequest<ValidateResponse>(
@@ -386,11 +437,33 @@ export class Paycheck {
               public_key: this.publicKey,
               jti: claims.jti,
             },
+            query: options.includeRevocations
+              ? { include_revocations: 'true' }
+              : undefined,
           }
         );
+        this.rememberHints(response);
 
         if (!response.valid) {
-          return { valid: false, reason: 'Revoked or invalid', claims };
+          return {
+            valid: false,
+            reason: response.revocation
+              ? 'License revoked'
+              : 'Revoked or invalid',
+            revocation: response.revocation ?? undefined,
+            claims,
+          };
+        }
+
+        if (response.revoked_jtis) {
+          return {
+            valid: true,
+            claims,
+            revokedJtis: response.revoked_jtis.map((r) => ({
+              jti: r.jti,
+              revokedAt: r.revoked_at,
+            })),
+          };
         }
       } catch {
         return { valid: false, reason: 'Online validation failed', claims };
@@ -479,6 +552,10 @@ export class Paycheck {
         valid: boolean;
         license_exp?: number | null;
         updates_exp?: number | null;
+        revocation?: Revocation | null;
+        client_flags?: Record<string, unknown>;
+        update_required?: boolean;
+        min_client_version?: string;
       }
 
       const response = await this.apiRequest<ValidateResponse>(
@@ -491,13 +568,17 @@ export class Paycheck {
           },
         }
       );
+      this.rememberHints(response);
 
       if (!response.valid) {
         return {
           valid: false,
           synced: true,
           offline: false,
-          reason: 'Revoked or invalid',
+          reason: response.revocation
+            ? 'License revoked'
+            : 'Revoked or invalid',
+          revocation: response.revocation ?? undefined,
           claims,
         };
       }
@@ -580,6 +661,7 @@ export class Paycheck {
       updates_exp: number | null;
       tier: string;
       features: string[];
+      entitlements?: Entitlements;
       activation_code: string;
       activation_code_expires_at: number;
     }
@@ -602,6 +684,7 @@ export class Paycheck {
       updatesExp: response.updates_exp,
       tier: response.tier,
       features: response.features,
+      entitlements: response.entitlements ?? {},
       activationCode: response.activation_code,
       activationCodeExpiresAt: response.activation_code_expires_at,
     };
@@ -727,6 +810,15 @@ export class Paycheck {
     return checkHasFeature(claims, feature);
   }
 
+  /**
+   * Get a structured entitlement value (e.g. a numeric limit like `seats`).
+   */
+  getEntitlement(name: string): EntitlementValue | undefined {
+    const claims = this.getLicense();
+    if (!claims) return undefined;
+    return checkGetEntitlement(claims, name);
+  }
+
   /**
    * Get the product tier.
    */
@@ -753,6 +845,75 @@ export class Paycheck {
     return checkCoversVersion(claims, timestamp);
   }
 
+  /**
+   * Check with the server whether the license's updates cover a build
+   * released at `releasedAt` (Unix seconds: the build timestamp or the
+   * version's release date).
+   *
+   * Unlike `coversVersion()`, which reads the token's `updates_exp` claim,
+   * this answers from the license as it is now, so a renewal counts before
+   * the token is refreshed.
+   */
+  async canUpdate(releasedAt: number): Promise<boolean> {
+    const token = await this.ensureFreshToken();
+
+    interface UpdatesCheckResponse {
+      covered: boolean;
+      released_at: number;
+      updates_expires_at: number | null;
+    }
+
+    const response = await this.apiRequest<UpdatesCheckResponse>(
+      'GET',
+      '/updates/check',
+      {
+        query: {
+          released_at: String(releasedAt),
+        },
+        headers: {
+          Authorization: `Bearer ${token}`,
+        },
+      }
+    );
+
+    return response.covered;
+  }
+
+  /**
+   * The project's client flags from the last `/validate` or `/refresh`
+   * (empty until one succeeds). Flags aren't in the token, so they change as
+   * soon as the publisher changes them.
+   *
+   * @example
+   * ```typescript
+   * await paycheck.sync();
+   * if (paycheck.getClientFlags().sync_enabled === false) disableSync();
+   * ```
+   */
+  getClientFlags(): Record<string, unknown> {
+    return this.clientHints.clientFlags;
+  }
+
+  /**
+   * Whether the last `/validate` or `/refresh` said this app is older than
+   * the project's minimum supported version. Needs the `clientVersion` option.
+   */
+  isUpdateRequired(): boolean {
+    return this.clientHints.updateRequired;
+  }
+
+  private rememberHints(response: {
+    client_flags?: Record<string, unknown>;
+    update_required?: boolean;
+    min_client_version?: string;
+  }): void {
+    this.clientHints = {
+      clientFlags: response.client_flags ?? {},
+      updateRequired: response.update_required ?? false,
+      minClientVersion: response.min_client_version,
+    };
+  }
+
   // ==================== Token Management ====================
 
   /**
@@ -784,6 +945,9 @@ export class Paycheck {
 
     interface RefreshResponse {
       token: string;
+      client_flags?: Record<string, unknown>;
+      update_required?: boolean;
+      min_client_version?: string;
     }
 
     const response = await this.apiRequest<RefreshResponse>('POST', '/refresh', {
@@ -792,6 +956,7 @@ export class Paycheck {
       },
     });
 
+    this.rememberHints(response);
     await this.storeToken(response.token);
     return response.token;
   }
@@ -880,6 +1045,35 @@ export class Paycheck {
     };
   }
 
+  /**
+   * Get a short-lived attestation of the license for a third party.
+   * The server validates the stored token first; `audience` must be in the
+   * project's `attestation_audiences`. Hand the attestation to the third
+   * party instead of the license token itself.
+   */
+  async getAttestation(audience: string): Promise<Attestation> {
+    const token = await this.ensureFreshToken();
+
+    interface AttestResponse {
+      attestation: string;
+      expires_at: number;
+    }
+
+    const response = await this.apiRequest<AttestResponse>('GET', '/validate/attest', {
+      query: {
+        audience,
+      },
+      headers: {
+        Authorization: `Bearer ${token}`,
+      },
+    });
+
+    return {
+      attestation: response.attestation,
+      expiresAt: response.expires_at,
+    };
+  }
+
   // ==================== Callback Handling ====================
 
   /**
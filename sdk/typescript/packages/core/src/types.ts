@@ -44,6 +44,11 @@ export interface CheckoutParams {
   provider?: 'stripe' | 'lemonsqueezy';
   /** Your customer identifier (flows through to license) */
   customerId?: string;
+  /**
+   * Buy even if this customer already has an active license for the product
+   * (otherwise the server answers with `ALREADY_LICENSED`)
+   */
+  forceNewPurchase?: boolean;
 }
 
 /**
@@ -77,6 +82,16 @@ export interface DeviceInfo {
   deviceName?: string;
 }
 
+/**
+ * A structured entitlement value: a numeric limit, a flag, or a text value
+ */
+export type EntitlementValue = number | boolean | string;
+
+/**
+ * Structured entitlements by name (e.g. `{ seats: 5, sso: true }`)
+ */
+export type Entitlements = Record<string, EntitlementValue>;
+
 /**
  * Result from license activation
  */
@@ -91,6 +106,8 @@ export interface ActivationResult {
   tier: string;
   /** Enabled features */
   features: string[];
+  /** Structured entitlements (limits, flags, quotas) */
+  entitlements: Entitlements;
   /** Short-lived activation code for future activations (PREFIX-XXXX-XXXX format) */
   activationCode: string;
   /** When activation code expires (30 minutes from creation) */
@@ -118,7 +135,7 @@ export interface LicenseClaims {
   // Standard JWT claims
   /** Issuer ("paycheck") */
   iss: string;
-  /** Subject (license_id) */
+  /** Subject (license ref: the license's public handle, not its ID) */
   sub: string;
   /** Audience (project name, for debugging - not verified) */
   aud: string;
@@ -154,14 +171,48 @@ export interface LicenseClaims {
   tier: string;
   /** Enabled feature flags for hasFeature() checks */
   features: string[];
+  /** Structured entitlements for getEntitlement() (absent when the product has none) */
+  entitlements?: Entitlements;
   /** Device identifier (verified against current device to prevent token theft) */
   device_id: string;
   /** Device type */
   device_type: DeviceType;
+  /** License ref, same as `sub` (absent on tokens from older servers) */
+  license_ref?: string;
   /** Product UUID */
   product_id: string;
 }
 
+/**
+ * Why a license was revoked
+ */
+export type RevocationReason =
+  | 'refund'
+  | 'chargeback'
+  | 'payment_failed'
+  | 'abuse'
+  | 'superseded'
+  | 'admin_other';
+
+/**
+ * Why and when a license was revoked, safe to show to the user
+ */
+export interface Revocation {
+  reason: RevocationReason;
+  /** Message from the publisher, if they left one */
+  message: string | null;
+  revokedAt: number | null;
+}
+
+/**
+ * A token revoked on the license, such as one from a device deactivated
+ * remotely. A cached token whose `jti` is listed should no longer be trusted.
+ */
+export interface RevokedToken {
+  jti: string;
+  revokedAt: number;
+}
+
 /**
  * Result from online validation
  */
@@ -172,6 +223,33 @@ export interface ValidateResult {
   licenseExp?: number | null;
   /** When version access expires (if valid) */
   updatesExp?: number | null;
+  /** Whether the license's update window is still open */
+  updatesValid?: boolean;
+  /**
+   * When update access ends, as of now on the server (`null` = perpetual).
+   * Unlike `updatesExp`, this reflects renewals made since the token was issued.
+   */
+  updatesExpiresAt?: number | null;
+  /** Set when the license has been revoked */
+  revocation?: Revocation;
+  /** Tokens revoked on the license (only when asked for with `includeRevocations`) */
+  revokedJtis?: RevokedToken[];
+}
+
+/**
+ * What the publisher wants deployed apps to know, returned by `/validate`
+ * and `/refresh`. Read live from the project, not from the token.
+ */
+export interface ClientHints {
+  /** The project's client flags (announcements, feature killswitches) */
+  clientFlags: Record<string, unknown>;
+  /**
+   * This app is older than the project's minimum supported version.
+   * Only set when the client was created with `clientVersion`.
+   */
+  updateRequired: boolean;
+  /** The minimum supported version, if the project has one */
+  minClientVersion?: string;
 }
 
 /**
@@ -209,6 +287,19 @@ export interface LicenseInfo {
   devices: LicenseDeviceInfo[];
 }
 
+/**
+ * Short-lived proof of a valid license for a third party. The attestation
+ * is a JWT signed with the project key (verify it against the project's
+ * JWKS) carrying only the tier, product, license expiration, and a
+ * per-audience license hash.
+ */
+export interface Attestation {
+  /** The signed attestation JWT */
+  attestation: string;
+  /** When the attestation expires (Unix seconds) */
+  expiresAt: number;
+}
+
 /**
  * Result from device deactivation
  */
@@ -241,7 +332,8 @@ export type PaycheckErrorCode =
   | 'INVALID_CODE'
   | 'NETWORK_ERROR'
   | 'VALIDATION_ERROR'
-  | 'DUPLICATE_REQUEST';
+  | 'DUPLICATE_REQUEST'
+  | 'ALREADY_LICENSED';
 
 /**
  * Paycheck SDK error
@@ -250,7 +342,9 @@ export class PaycheckError extends Error {
   constructor(
     public code: PaycheckErrorCode,
     message: string,
-    public statusCode?: number
+    public statusCode?: number,
+    /** Why the license was revoked (with `LICENSE_REVOKED`) */
+    public revocation?: Revocation
   ) {
     super(message);
     this.name = 'PaycheckError';
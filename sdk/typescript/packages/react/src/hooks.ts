@@ -3,6 +3,7 @@
 import React, { useState, useEffect, useCallback, useRef } from 'react';
 import type {
   LicenseClaims,
+  Entitlements,
   ActivationResult,
   DeactivateResult,
   DeviceInfo,
@@ -66,6 +67,8 @@ export interface UseLicenseResult {
   tier: string | null;
   /** Enabled features */
   features: string[];
+  /** Structured entitlements (empty if no license) */
+  entitlements: Entitlements;
   /** Whether the license has expired (checks license_exp, not JWT exp) */
   isExpired: boolean;
   /** Error message if validation failed */
@@ -238,6 +241,7 @@ export function useLicense(options: UseLicenseOptions = {}): UseLicenseResult {
   // Derived state
   const tier = license?.tier ?? null;
   const features = license?.features ?? [];
+  const entitlements = license?.entitlements ?? {};
   const isExpired = paycheck.isExpired();
 
   // Actions
@@ -325,6 +329,7 @@ export function useLicense(options: UseLicenseOptions = {}): UseLicenseResult {
     isLicensed,
     tier,
     features,
+    entitlements,
     isExpired,
     error,
     synced,
@@ -0,0 +1,115 @@
+//! Signed download URLs for release artifacts.
+//!
+//! `/download/{artifact_id}` never serves an artifact itself: once the
+//! license checks out, it redirects to the artifact's URL with two query
+//! parameters appended, for the vendor's CDN or edge function to check:
+//!
+//! - `paycheck_expires`: Unix seconds after which the URL is refused
+//! - `paycheck_signature`: hex HMAC-SHA256 of `"{path}\n{expires}"` under the
+//!   project's download signing key, where `path` is the URL path exactly as
+//!   it appears in the URL (percent-encoded, no query string)
+//!
+//! The key comes from `GET .../artifacts/signing-key` and is derived from the
+//! master key, so it changes when the master key is rotated. Other query
+//! parameters on the artifact URL aren't covered by the signature.
+//! [`verify_download_url`] is the reference check.
+
+use hmac::{Hmac, Mac};
+use reqwest::Url;
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+
+use crate::error::{AppError, Result};
+
+/// How long a signed download URL works for.
+pub const DOWNLOAD_URL_LIFETIME_SECS: i64 = 300;
+
+const EXPIRES_PARAM: &str = "paycheck_expires";
+const SIGNATURE_PARAM: &str = "paycheck_signature";
+
+/// `url` with the expiry and signature appended.
+pub fn signed_download_url(url: &str, key: &[u8; 32], expires_at: i64) -> Result<String> {
+    let mut url =
+        Url::parse(url).map_err(|e| AppError::Internal(format!("Invalid artifact URL: {}", e)))?;
+    let signature = hex::encode(signature(key, url.path(), expires_at));
+    url.query_pairs_mut()
+        .append_pair(EXPIRES_PARAM, &expires_at.to_string())
+        .append_pair(SIGNATURE_PARAM, &signature);
+    Ok(url.into())
+}
+
+/// Whether `url` carries a signature made with `key` that hasn't expired at `now`.
+pub fn verify_download_url(url: &str, key: &[u8; 32], now: i64) -> bool {
+    let Ok(url) = Url::parse(url) else {
+        return false;
+    };
+    let mut expires_at = None;
+    let mut provided = None;
+    for (name, value) in url.query_pairs() {
+        match name.as_ref() {
+            EXPIRES_PARAM => expires_at = value.parse::<i64>().ok(),
+            SIGNATURE_PARAM => provided = hex::decode(value.as_ref()).ok(),
+            _ => {}
+        }
+    }
+    let (Some(expires_at), Some(provided)) = (expires_at, provided) else {
+        return false;
+    };
+    now < expires_at
+        && signature(key, url.path(), expires_at)
+            .ct_eq(&provided)
+            .into()
+}
+
+fn signature(key: &[u8; 32], path: &str, expires_at: i64) -> Vec<u8> {
+    let mut mac: Hmac<Sha256> = Mac::new_from_slice(key).expect("HMAC can take key of any size");
+    mac.update(format!("{}\n{}", path, expires_at).as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; 32] = [7; 32];
+
+    #[test]
+    fn test_signed_url_verifies_until_it_expires() {
+        let url =
+            signed_download_url("https://cdn.example.com/app/MyApp 2.dmg", &KEY, 1_300).unwrap();
+        assert!(url.starts_with(
+            "https://cdn.example.com/app/MyApp%202.dmg?paycheck_expires=1300&paycheck_signature="
+        ));
+        assert!(verify_download_url(&url, &KEY, 1_000));
+        assert!(verify_download_url(&url, &KEY, 1_299));
+        assert!(!verify_download_url(&url, &KEY, 1_300));
+    }
+
+    #[test]
+    fn test_existing_query_is_kept() {
+        let url = signed_download_url("https://cdn.example.com/app.zip?v=2", &KEY, 1_300).unwrap();
+        assert!(url.starts_with("https://cdn.example.com/app.zip?v=2&paycheck_expires=1300&"));
+        assert!(verify_download_url(&url, &KEY, 1_000));
+    }
+
+    #[test]
+    fn test_tampered_url_is_rejected() {
+        let url = signed_download_url("https://cdn.example.com/app.zip", &KEY, 1_300).unwrap();
+        assert!(!verify_download_url(&url, &[8; 32], 1_000));
+        assert!(!verify_download_url(
+            &url.replace("app.zip", "other.zip"),
+            &KEY,
+            1_000
+        ));
+        assert!(!verify_download_url(
+            &url.replace("expires=1300", "expires=9300"),
+            &KEY,
+            1_000
+        ));
+        assert!(!verify_download_url(
+            "https://cdn.example.com/app.zip",
+            &KEY,
+            1_000
+        ));
+    }
+}
@@ -0,0 +1,209 @@
+//! Signed audit log archives.
+//!
+//! An archive is gzip'd NDJSON: one audit log entry per line, oldest first,
+//! followed by a single `{"manifest": {...}}` line. The manifest records the
+//! entry count and a SHA-256 digest over the entry lines exactly as written
+//! (trailing newlines included), and is signed with a key derived from the
+//! master key. Changing, adding, or dropping an entry breaks the digest or the
+//! count; changing the manifest breaks the signature.
+
+use std::io::{BufRead, BufReader, Read, Write};
+
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::crypto::MasterKey;
+use crate::db::queries;
+use crate::error::{AppError, Result};
+
+/// Archive format version, part of the signed manifest.
+pub const ARCHIVE_VERSION: u32 = 1;
+
+/// Entries read from the audit database per query while writing an archive.
+const PAGE_SIZE: i64 = 500;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub version: u32,
+    pub org_id: String,
+    /// Inclusive timestamp range the archive covers
+    pub from: i64,
+    pub to: i64,
+    pub row_count: u64,
+    /// Hex SHA-256 of all entry lines
+    pub sha256: String,
+    pub generated_at: i64,
+    /// Hex HMAC-SHA256 over the other fields (see [`MasterKey::sign_audit_archive`])
+    pub signature: String,
+}
+
+impl ArchiveManifest {
+    fn signed_message(&self) -> String {
+        format!(
+            "paycheck-audit-archive-v{}\n{}\n{}\n{}\n{}\n{}\n{}",
+            self.version,
+            self.org_id,
+            self.from,
+            self.to,
+            self.row_count,
+            self.sha256,
+            self.generated_at
+        )
+    }
+}
+
+#[derive(Deserialize)]
+struct ManifestLine {
+    manifest: ArchiveManifest,
+}
+
+/// Result of checking an uploaded archive.
+#[derive(Debug, Serialize)]
+pub struct ArchiveVerification {
+    pub valid: bool,
+    /// Why verification failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// The archive's manifest, when one could be read
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<ArchiveManifest>,
+}
+
+impl ArchiveVerification {
+    fn invalid(reason: impl Into<String>, manifest: Option<ArchiveManifest>) -> Self {
+        Self {
+            valid: false,
+            reason: Some(reason.into()),
+            manifest,
+        }
+    }
+}
+
+/// Write the archive of `org_id`'s audit logs in `[from, to]` to `out`,
+/// paging through the audit database so the whole range is never in memory.
+pub fn write_archive<W: Write>(
+    conn: &Connection,
+    master_key: &MasterKey,
+    org_id: &str,
+    from: i64,
+    to: i64,
+    generated_at: i64,
+    out: W,
+) -> Result<ArchiveManifest> {
+    let mut gz = GzEncoder::new(out, Compression::default());
+    let mut hasher = Sha256::new();
+    let mut row_count = 0u64;
+    let mut after: Option<(i64, String)> = None;
+
+    loop {
+        let page = queries::list_org_audit_logs_page(
+            conn,
+            org_id,
+            from,
+            to,
+            after.as_ref().map(|(ts, id)| (*ts, id.as_str())),
+            PAGE_SIZE,
+        )?;
+        for log in &page {
+            let mut line = serde_json::to_vec(log)?;
+            line.push(b'\n');
+            hasher.update(&line);
+            gz.write_all(&line).map_err(write_error)?;
+        }
+        row_count += page.len() as u64;
+        match page.last() {
+            Some(last) if page.len() as i64 == PAGE_SIZE => {
+                after = Some((last.timestamp, last.id.clone()));
+            }
+            _ => break,
+        }
+    }
+
+    let mut manifest = ArchiveManifest {
+        version: ARCHIVE_VERSION,
+        org_id: org_id.to_string(),
+        from,
+        to,
+        row_count,
+        sha256: hex::encode(hasher.finalize()),
+        generated_at,
+        signature: String::new(),
+    };
+    manifest.signature = master_key.sign_audit_archive(manifest.signed_message().as_bytes());
+
+    let mut line = serde_json::to_vec(&serde_json::json!({ "manifest": &manifest }))?;
+    line.push(b'\n');
+    gz.write_all(&line).map_err(write_error)?;
+    gz.finish()
+        .and_then(|mut out| out.flush())
+        .map_err(write_error)?;
+
+    Ok(manifest)
+}
+
+fn write_error(e: std::io::Error) -> AppError {
+    AppError::Internal(format!("Failed to write audit archive: {}", e))
+}
+
+/// Check an archive produced by [`write_archive`]. Covers the decompressed
+/// content; gzip header fields such as the modification time are not signed.
+pub fn verify_archive<R: Read>(input: R, master_key: &MasterKey) -> ArchiveVerification {
+    let mut reader = BufReader::new(GzDecoder::new(input));
+    let mut hasher = Sha256::new();
+    let mut row_count = 0u64;
+    // The last line read; it's an entry unless nothing follows it
+    let mut pending: Option<Vec<u8>> = None;
+
+    loop {
+        let mut line = Vec::new();
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e) => {
+                return ArchiveVerification::invalid(format!("Archive is corrupted: {}", e), None);
+            }
+        }
+        if let Some(entry) = pending.replace(line) {
+            hasher.update(&entry);
+            row_count += 1;
+        }
+    }
+
+    let Some(last) = pending else {
+        return ArchiveVerification::invalid("Archive is empty", None);
+    };
+    let Ok(ManifestLine { manifest }) = serde_json::from_slice(&last) else {
+        return ArchiveVerification::invalid("Archive has no manifest", None);
+    };
+
+    if manifest.version != ARCHIVE_VERSION {
+        let reason = format!("Unsupported archive version {}", manifest.version);
+        return ArchiveVerification::invalid(reason, Some(manifest));
+    }
+    if !master_key.verify_audit_archive(manifest.signed_message().as_bytes(), &manifest.signature) {
+        return ArchiveVerification::invalid("Manifest signature is invalid", Some(manifest));
+    }
+    if manifest.row_count != row_count {
+        let reason = format!(
+            "Archive has {} entries but the manifest records {}",
+            row_count, manifest.row_count
+        );
+        return ArchiveVerification::invalid(reason, Some(manifest));
+    }
+    if hex::encode(hasher.finalize()) != manifest.sha256 {
+        return ArchiveVerification::invalid(
+            "Entries do not match the manifest digest",
+            Some(manifest),
+        );
+    }
+
+    ArchiveVerification {
+        valid: true,
+        reason: None,
+        manifest: Some(manifest),
+    }
+}
@@ -0,0 +1,195 @@
+//! CSV exports of audit logs.
+//!
+//! An export has one row per entry matching an [`AuditLogQuery`], newest
+//! first like the JSON endpoints, with `details` as a JSON string. Entries
+//! are read from the audit database a page at a time, so an export of any
+//! size is never held in memory.
+
+use std::io::Write;
+
+use chrono::DateTime;
+use rusqlite::Connection;
+
+use crate::db::queries;
+use crate::error::{AppError, Result};
+use crate::models::{AuditLog, AuditLogQuery, Timestamp};
+
+/// Entries read from the audit database per query while writing an export.
+const PAGE_SIZE: i64 = 1000;
+
+const HEADER: &[&str] = &[
+    "id",
+    "timestamp",
+    "time",
+    "actor_type",
+    "user_id",
+    "user_name",
+    "user_email",
+    "action",
+    "resource_type",
+    "resource_id",
+    "resource_name",
+    "resource_email",
+    "org_id",
+    "org_name",
+    "project_id",
+    "project_name",
+    "ip_address",
+    "user_agent",
+    "auth_type",
+    "auth_credential",
+    "request_id",
+    "details",
+];
+
+/// Write the entries matching `query` (its limit and offset are ignored) to
+/// `out` as CSV. `resolve` is called on each page before it's written, to
+/// fill in anything the stored entries leave out. Returns the row count.
+pub fn write_csv<W: Write>(
+    conn: &Connection,
+    query: &AuditLogQuery,
+    mut resolve: impl FnMut(&mut [AuditLog]) -> Result<()>,
+    mut out: W,
+) -> Result<u64> {
+    write_row(&mut out, HEADER)?;
+
+    let mut row_count = 0u64;
+    let mut after: Option<(i64, String)> = None;
+    loop {
+        let mut page = queries::query_audit_logs_page(
+            conn,
+            query,
+            after.as_ref().map(|(ts, id)| (*ts, id.as_str())),
+            PAGE_SIZE,
+        )?;
+        resolve(&mut page)?;
+        for log in &page {
+            write_log(&mut out, log)?;
+        }
+        row_count += page.len() as u64;
+        match page.last() {
+            Some(last) if page.len() as i64 == PAGE_SIZE => {
+                after = Some((last.timestamp, last.id.clone()));
+            }
+            _ => break,
+        }
+    }
+
+    out.flush().map_err(write_error)?;
+    Ok(row_count)
+}
+
+/// Download filename for an export of `scope` (an org ID, or "all") made at `now`.
+pub fn filename(scope: &str, now: i64) -> String {
+    let date = DateTime::from_timestamp(now, 0)
+        .map(|dt| dt.format("%Y-%m-%d").to_string())
+        .unwrap_or_else(|| now.to_string());
+    format!("audit-logs-{}-{}.csv", scope, date)
+}
+
+fn write_log<W: Write>(out: &mut W, log: &AuditLog) -> Result<()> {
+    let timestamp = log.timestamp.to_string();
+    let time = Timestamp(log.timestamp).to_rfc3339().unwrap_or_default();
+    let details = match &log.details {
+        Some(details) => serde_json::to_string(details)?,
+        None => String::new(),
+    };
+    let opt = |value: &Option<String>| value.as_deref().unwrap_or("");
+
+    write_row(
+        out,
+        &[
+            log.id.as_str(),
+            &timestamp,
+            &time,
+            log.actor_type.as_ref(),
+            opt(&log.user_id),
+            opt(&log.user_name),
+            opt(&log.user_email),
+            &log.action,
+            &log.resource_type,
+            &log.resource_id,
+            opt(&log.resource_name),
+            opt(&log.resource_email),
+            opt(&log.org_id),
+            opt(&log.org_name),
+            opt(&log.project_id),
+            opt(&log.project_name),
+            opt(&log.ip_address),
+            opt(&log.user_agent),
+            opt(&log.auth_type),
+            opt(&log.auth_credential),
+            opt(&log.request_id),
+            &details,
+        ],
+    )
+}
+
+fn write_row<W: Write>(out: &mut W, fields: &[&str]) -> Result<()> {
+    let mut line = String::new();
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            line.push(',');
+        }
+        push_field(&mut line, field);
+    }
+    line.push_str("\r\n");
+    out.write_all(line.as_bytes()).map_err(write_error)
+}
+
+/// Append `field` to a CSV line (RFC 4180). Fields with a comma, quote, or
+/// line break are quoted, with quotes doubled. Fields a spreadsheet would
+/// read as a formula (user agents and names are caller-controlled) get a
+/// leading `'`.
+fn push_field(line: &mut String, field: &str) {
+    let formula = field.starts_with(['=', '+', '-', '@', '\t', '\r']);
+    if !formula && !field.contains([',', '"', '\n', '\r']) {
+        line.push_str(field);
+        return;
+    }
+    line.push('"');
+    if formula {
+        line.push('\'');
+    }
+    line.push_str(&field.replace('"', "\"\""));
+    line.push('"');
+}
+
+fn write_error(e: std::io::Error) -> AppError {
+    AppError::Internal(format!("Failed to write audit log export: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(value: &str) -> String {
+        let mut line = String::new();
+        push_field(&mut line, value);
+        line
+    }
+
+    #[test]
+    fn test_plain_fields_are_not_quoted() {
+        assert_eq!(field("update_product"), "update_product");
+        assert_eq!(field(""), "");
+    }
+
+    #[test]
+    fn test_special_characters_are_quoted() {
+        assert_eq!(field("a,b"), "\"a,b\"");
+        assert_eq!(field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(field("line\nbreak"), "\"line\nbreak\"");
+        assert_eq!(
+            field(r#"{"name":"Pro, yearly"}"#),
+            r#""{""name"":""Pro, yearly""}""#
+        );
+    }
+
+    #[test]
+    fn test_formulas_are_neutralized() {
+        assert_eq!(field("=HYPERLINK(\"x\")"), "\"'=HYPERLINK(\"\"x\"\")\"");
+        assert_eq!(field("@SUM(A1)"), "\"'@SUM(A1)\"");
+        assert_eq!(field("-1"), "\"'-1\"");
+    }
+}
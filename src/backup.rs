@@ -0,0 +1,392 @@
+//! Encrypted backups of the main and audit databases.
+//!
+//! Each database is copied with SQLite's online backup API in a single step,
+//! so the copy is a consistent snapshot even while requests keep writing. The
+//! two databases are copied one after the other, not at the same instant.
+//! Dedicated org databases (data residency mode) are not included.
+//!
+//! A backup file is a bundle of both copies, gzip'd and then encrypted with
+//! the backup key (see [`MasterKey::encrypt_backup`]). The bundle is
+//! `BUNDLE_MAGIC` followed by one section per database: name length (1 byte),
+//! name, data length (8 bytes, little-endian), and the SQLite file itself.
+
+use std::fs;
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use rusqlite::{Connection, OpenFlags};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::config::Config;
+use crate::crypto::MasterKey;
+use crate::db::queries;
+use crate::error::{AppError, Result};
+use crate::models::Backup;
+
+/// Name of the main database's section in a backup
+pub const MAIN_DATABASE: &str = "main";
+
+/// Name of the audit database's section in a backup
+pub const AUDIT_DATABASE: &str = "audit";
+
+/// Start of every decrypted, decompressed bundle
+const BUNDLE_MAGIC: &[u8] = b"PCBK1";
+
+/// Where backup files are kept. Files are written once and read back whole,
+/// so an object store only needs these three operations.
+pub trait BackupStorage: Send + Sync {
+    fn put(&self, name: &str, data: &[u8]) -> Result<()>;
+    fn get(&self, name: &str) -> Result<Vec<u8>>;
+    /// Deleting a file that doesn't exist is not an error.
+    fn delete(&self, name: &str) -> Result<()>;
+}
+
+/// Backup files in a local directory (`PAYCHECK_BACKUP_DIR`).
+pub struct LocalBackupStorage {
+    dir: PathBuf,
+}
+
+impl LocalBackupStorage {
+    /// Store backups in `dir`, creating it if needed.
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).map_err(|e| io_error("create the backup directory", e))?;
+        Ok(Self { dir })
+    }
+}
+
+impl BackupStorage for LocalBackupStorage {
+    fn put(&self, name: &str, data: &[u8]) -> Result<()> {
+        // Written under another name first so a crash never leaves half a backup
+        let partial = self.dir.join(format!(".{}.partial", name));
+        fs::write(&partial, data)
+            .and_then(|_| fs::rename(&partial, self.dir.join(name)))
+            .map_err(|e| io_error("write the backup file", e))
+    }
+
+    fn get(&self, name: &str) -> Result<Vec<u8>> {
+        fs::read(self.dir.join(name)).map_err(|e| io_error("read the backup file", e))
+    }
+
+    fn delete(&self, name: &str) -> Result<()> {
+        match fs::remove_file(self.dir.join(name)) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(io_error("delete the backup file", e)),
+        }
+    }
+}
+
+/// Result of checking a stored backup.
+#[derive(Debug, Serialize)]
+pub struct BackupVerification {
+    pub valid: bool,
+    /// Why verification failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// The databases found in the backup, once it could be decrypted
+    pub databases: Vec<DatabaseCheck>,
+}
+
+impl BackupVerification {
+    fn invalid(reason: impl Into<String>, databases: Vec<DatabaseCheck>) -> Self {
+        Self {
+            valid: false,
+            reason: Some(reason.into()),
+            databases,
+        }
+    }
+}
+
+/// One database in a backup, opened read-only and checked.
+#[derive(Debug, Serialize)]
+pub struct DatabaseCheck {
+    pub name: String,
+    pub size_bytes: i64,
+    /// `PRAGMA integrity_check` output: "ok", or SQLite's list of problems
+    pub integrity: String,
+}
+
+/// The configured backup storage, encryption key, and retention.
+pub struct Backups {
+    storage: Box<dyn BackupStorage>,
+    key: MasterKey,
+    /// Whether `key` is a dedicated backup key rather than the master key
+    dedicated_key: bool,
+    /// Newest backups kept by [`Backups::prune`] (0 = keep all)
+    retention_count: i64,
+}
+
+impl Backups {
+    pub fn new(
+        storage: Box<dyn BackupStorage>,
+        key: MasterKey,
+        dedicated_key: bool,
+        retention_count: i64,
+    ) -> Self {
+        Self {
+            storage,
+            key,
+            dedicated_key,
+            retention_count,
+        }
+    }
+
+    /// Backups in `PAYCHECK_BACKUP_DIR`, or None when it isn't set.
+    pub fn from_config(config: &Config) -> Result<Option<Self>> {
+        let Some(dir) = &config.backup_dir else {
+            return Ok(None);
+        };
+        let storage = LocalBackupStorage::new(dir)?;
+        let (key, dedicated_key) = match &config.backup_key {
+            Some(key) => (key.clone(), true),
+            None => (config.master_key.clone(), false),
+        };
+        Ok(Some(Self::new(
+            Box::new(storage),
+            key,
+            dedicated_key,
+            config.backup_retention_count,
+        )))
+    }
+
+    /// Name of a backup's file in the storage.
+    pub fn file_name(backup_id: &str) -> String {
+        format!("paycheck-backup-{}.bin", backup_id)
+    }
+
+    /// Snapshot both databases and store them, encrypted, as `backup_id`.
+    /// Returns the record to save; the caller inserts it.
+    pub fn create(
+        &self,
+        main: &Connection,
+        audit: &Connection,
+        backup_id: &str,
+        created_by: Option<&str>,
+        created_at: i64,
+    ) -> Result<Backup> {
+        let started = Instant::now();
+
+        let mut bundle = BUNDLE_MAGIC.to_vec();
+        for (name, conn) in [(MAIN_DATABASE, main), (AUDIT_DATABASE, audit)] {
+            let file = TempFile::new(backup_id, name);
+            let data = snapshot(conn, &file.0)?;
+            bundle.push(name.len() as u8);
+            bundle.extend_from_slice(name.as_bytes());
+            bundle.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            bundle.extend_from_slice(&data);
+        }
+
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(&bundle)
+            .map_err(|e| io_error("compress the backup", e))?;
+        let compressed = gz
+            .finish()
+            .map_err(|e| io_error("compress the backup", e))?;
+        let encrypted = self.key.encrypt_backup(backup_id, &compressed)?;
+
+        let file_name = Self::file_name(backup_id);
+        self.storage.put(&file_name, &encrypted)?;
+
+        Ok(Backup {
+            id: backup_id.to_string(),
+            file_name,
+            size_bytes: encrypted.len() as i64,
+            duration_ms: started.elapsed().as_millis() as i64,
+            sha256: hex::encode(Sha256::digest(&encrypted)),
+            encrypted_with: if self.dedicated_key {
+                "backup_key"
+            } else {
+                "master_key"
+            }
+            .to_string(),
+            created_by: created_by.map(String::from),
+            created_at,
+            verified_at: None,
+            verified_ok: None,
+        })
+    }
+
+    /// Check a stored backup: its file matches the recorded digest, decrypts
+    /// with the configured key, and holds both databases, each of which
+    /// opens read-only and passes `PRAGMA integrity_check`. Problems are
+    /// reported as `valid: false` with a reason, not as errors.
+    pub fn verify(&self, backup: &Backup) -> BackupVerification {
+        let encrypted = match self.storage.get(&backup.file_name) {
+            Ok(encrypted) => encrypted,
+            Err(e) => return BackupVerification::invalid(e.to_string(), vec![]),
+        };
+        if hex::encode(Sha256::digest(&encrypted)) != backup.sha256 {
+            return BackupVerification::invalid(
+                "Backup file does not match its recorded digest",
+                vec![],
+            );
+        }
+        let databases = match self.unseal(&backup.id, &encrypted) {
+            Ok(databases) => databases,
+            Err(reason) => return BackupVerification::invalid(reason, vec![]),
+        };
+
+        let mut checks = Vec::with_capacity(databases.len());
+        for (name, data) in &databases {
+            let integrity = match integrity_check(&backup.id, name, data) {
+                Ok(integrity) => integrity,
+                Err(e) => format!("Can't be opened: {}", e),
+            };
+            checks.push(DatabaseCheck {
+                name: name.clone(),
+                size_bytes: data.len() as i64,
+                integrity,
+            });
+        }
+
+        for expected in [MAIN_DATABASE, AUDIT_DATABASE] {
+            if !checks.iter().any(|check| check.name == expected) {
+                let reason = format!("Backup has no {} database", expected);
+                return BackupVerification::invalid(reason, checks);
+            }
+        }
+        if let Some(failed) = checks.iter().find(|check| check.integrity != "ok") {
+            let reason = format!("The {} database failed the integrity check", failed.name);
+            return BackupVerification::invalid(reason, checks);
+        }
+
+        BackupVerification {
+            valid: true,
+            reason: None,
+            databases: checks,
+        }
+    }
+
+    /// Write a backup's databases to `main_path` and `audit_path`. Works from
+    /// the file alone, since the `backups` table is in the database being
+    /// restored. Refuses to overwrite existing files.
+    pub fn restore(&self, backup_id: &str, main_path: &Path, audit_path: &Path) -> Result<()> {
+        for path in [main_path, audit_path] {
+            if path.exists() {
+                return Err(AppError::Conflict(format!(
+                    "{} already exists; move it aside before restoring",
+                    path.display()
+                )));
+            }
+        }
+
+        let encrypted = self.storage.get(&Self::file_name(backup_id))?;
+        let databases = self
+            .unseal(backup_id, &encrypted)
+            .map_err(AppError::Internal)?;
+        for (name, path) in [(MAIN_DATABASE, main_path), (AUDIT_DATABASE, audit_path)] {
+            let (_, data) = databases
+                .iter()
+                .find(|(section, _)| section == name)
+                .ok_or_else(|| AppError::Internal(format!("Backup has no {} database", name)))?;
+            fs::write(path, data).map_err(|e| io_error("write the restored database", e))?;
+        }
+        Ok(())
+    }
+
+    /// Delete backups beyond the newest `retention_count`, file first, then
+    /// record. Returns how many were deleted.
+    pub fn prune(&self, conn: &Connection) -> Result<usize> {
+        if self.retention_count <= 0 {
+            return Ok(0);
+        }
+        let expired = queries::list_backups_beyond_retention(conn, self.retention_count)?;
+        for backup in &expired {
+            self.storage.delete(&backup.file_name)?;
+            queries::delete_backup(conn, &backup.id)?;
+        }
+        Ok(expired.len())
+    }
+
+    /// Decrypt, decompress, and split a backup file into its databases.
+    fn unseal(
+        &self,
+        backup_id: &str,
+        encrypted: &[u8],
+    ) -> std::result::Result<Vec<(String, Vec<u8>)>, String> {
+        let compressed = self
+            .key
+            .decrypt_backup(backup_id, encrypted)
+            .map_err(|_| "Backup can't be decrypted with the configured backup key".to_string())?;
+        let mut bundle = Vec::new();
+        GzDecoder::new(compressed.as_slice())
+            .read_to_end(&mut bundle)
+            .map_err(|e| format!("Backup is corrupted: {}", e))?;
+        split_bundle(&bundle)
+    }
+}
+
+/// Copy `conn`'s database to a new file at `path` and read it back.
+fn snapshot(conn: &Connection, path: &Path) -> Result<Vec<u8>> {
+    let mut copy = Connection::open(path)?;
+    // -1 copies every page in one step, holding a single read transaction
+    rusqlite::backup::Backup::new(conn, &mut copy)?.run_to_completion(-1, Duration::ZERO, None)?;
+    drop(copy);
+    fs::read(path).map_err(|e| io_error("read the database snapshot", e))
+}
+
+/// Open a database from a backup read-only and run `PRAGMA integrity_check`.
+fn integrity_check(backup_id: &str, name: &str, data: &[u8]) -> Result<String> {
+    let file = TempFile::new(backup_id, name);
+    fs::write(&file.0, data).map_err(|e| io_error("write the database for checking", e))?;
+    let conn = Connection::open_with_flags(
+        &file.0,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+    let lines = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(lines.join("\n"))
+}
+
+fn split_bundle(bundle: &[u8]) -> std::result::Result<Vec<(String, Vec<u8>)>, String> {
+    let truncated = || "Backup is truncated".to_string();
+    let mut rest = bundle
+        .strip_prefix(BUNDLE_MAGIC)
+        .ok_or_else(|| "Not a Paycheck backup".to_string())?;
+
+    let mut databases = Vec::new();
+    while let Some((&name_len, tail)) = rest.split_first() {
+        let name_len = name_len as usize;
+        let name = tail.get(..name_len).ok_or_else(truncated)?;
+        let name = String::from_utf8(name.to_vec()).map_err(|_| truncated())?;
+        let tail = &tail[name_len..];
+        let len = tail.get(..8).ok_or_else(truncated)?;
+        let len = u64::from_le_bytes(len.try_into().expect("8 bytes")) as usize;
+        let data = tail.get(8..8 + len).ok_or_else(truncated)?;
+        databases.push((name, data.to_vec()));
+        rest = &tail[8 + len..];
+    }
+    Ok(databases)
+}
+
+/// A scratch database file, removed when dropped.
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(backup_id: &str, name: &str) -> Self {
+        Self(std::env::temp_dir().join(format!(
+            "paycheck-backup-{}-{}-{}.db",
+            backup_id,
+            name,
+            uuid::Uuid::new_v4()
+        )))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+fn io_error(action: &str, e: std::io::Error) -> AppError {
+    AppError::Internal(format!("Failed to {}: {}", action, e))
+}
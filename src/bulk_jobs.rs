@@ -0,0 +1,224 @@
+//! Bulk license jobs: creating more licenses than one request should hold.
+//!
+//! `POST .../licenses` with a `count` above [`SYNC_MAX_COUNT`] records a job
+//! and answers 202 right away. The licenses are then created here, in the
+//! background, [`CHUNK_SIZE`] at a time. Each chunk is its own transaction
+//! and the job pauses between chunks, so the database's write lock keeps
+//! changing hands and the API stays responsive while a job runs. A chunk that
+//! fails rolls back on its own: the error is recorded against it and the job
+//! moves on to the next one.
+//!
+//! Cancelling a running job rolls back the chunk in flight and keeps the
+//! chunks already created.
+
+use std::time::Duration;
+
+use axum::http::HeaderMap;
+use rusqlite::Connection;
+
+use crate::db::{AppState, outbox, queries};
+use crate::error::{AppError, Result, msg};
+use crate::middleware::AuthMethod;
+use crate::models::{ActorType, AuditAction, AuditLogNames, BulkJob, BulkJobStatus, CreateLicense};
+use crate::util::AuditLogBuilder;
+
+/// Largest count still created within the request
+pub const SYNC_MAX_COUNT: i32 = 100;
+
+/// Largest count one request can ask for
+pub const MAX_COUNT: i32 = 10_000;
+
+/// Licenses created per transaction
+pub const CHUNK_SIZE: i64 = 100;
+
+/// Pause between chunks, giving other writers a turn at the database
+pub const CHUNK_PACE: Duration = Duration::from_millis(50);
+
+/// Who started a job. Its licenses are audited as created by them, as they
+/// would be by a synchronous request.
+#[derive(Debug, Clone)]
+pub struct JobActor {
+    pub user_id: String,
+    pub auth_method: AuthMethod,
+    pub names: AuditLogNames,
+    /// The starting request's headers, for the IP and user agent
+    pub headers: HeaderMap,
+    pub impersonator: Option<serde_json::Value>,
+}
+
+/// Number of chunks a job of `requested` licenses is split into.
+pub fn chunk_count(requested: i64) -> i64 {
+    (requested + CHUNK_SIZE - 1) / CHUNK_SIZE
+}
+
+/// Run a job in the background. A job that can't go on (say, its database
+/// is unreachable) is marked failed rather than left running.
+pub fn spawn(state: AppState, org_id: String, job: BulkJob, actor: JobActor) {
+    tokio::spawn(async move {
+        if let Err(e) = run(&state, &org_id, &job, &actor, CHUNK_PACE).await {
+            tracing::error!(job_id = %job.id, "Bulk license job stopped: {}", e);
+            let failed = state
+                .org_db(&org_id)
+                .get()
+                .map_err(AppError::from)
+                .and_then(|conn| {
+                    queries::fail_bulk_jobs(&conn, Some(&job.id), msg::BULK_CHUNK_FAILED)
+                });
+            if let Err(e) = failed {
+                tracing::error!(job_id = %job.id, "Failed to mark bulk license job failed: {}", e);
+            }
+        }
+    });
+}
+
+/// Create a job's remaining chunks, `pace` apart, and finish it. Picks up
+/// after the chunks already attempted. Returns the job's final status, which
+/// is `Cancelled` if it was cancelled along the way.
+pub async fn run(
+    state: &AppState,
+    org_id: &str,
+    job: &BulkJob,
+    actor: &JobActor,
+    pace: Duration,
+) -> Result<BulkJobStatus> {
+    let start = queries::list_bulk_job_chunks(&*state.org_db(org_id).get()?, &job.id)?.len() as i64;
+
+    for chunk in start..chunk_count(job.requested) {
+        if chunk > start {
+            tokio::time::sleep(pace).await;
+        }
+        if !run_chunk(state, org_id, job, actor, chunk)? {
+            break;
+        }
+    }
+
+    let conn = state.org_db(org_id).get()?;
+    queries::finish_bulk_job(&conn, &job.id)?;
+    let job = queries::get_bulk_job(&conn, &job.project_id, &job.id)?
+        .ok_or_else(|| AppError::NotFound(msg::BULK_JOB_NOT_FOUND.into()))?;
+
+    tracing::info!(
+        "Bulk license job {} {}: {} of {} license(s) created (project: {})",
+        job.id,
+        job.status.as_ref(),
+        job.created_count,
+        job.requested,
+        job.project_id
+    );
+    Ok(job.status)
+}
+
+/// Create one chunk's licenses in a transaction of their own. Returns false,
+/// with the chunk rolled back, once the job is no longer running.
+pub fn run_chunk(
+    state: &AppState,
+    org_id: &str,
+    job: &BulkJob,
+    actor: &JobActor,
+    chunk: i64,
+) -> Result<bool> {
+    let mut conn = state.org_db(org_id).get()?;
+    let audit_conn = state.audit.get()?;
+
+    let size = CHUNK_SIZE.min(job.requested - chunk * CHUNK_SIZE);
+    let params = &job.params;
+    let details = serde_json::json!({
+        "product_id": job.product_id,
+        "expires_at": params.expires_at,
+        "has_email": params.email_hash.is_some(),
+        "seats": params.seats,
+        "is_trial": params.is_trial,
+        "bulk_job_id": job.id,
+        "impersonator": actor.impersonator
+    });
+
+    // A chunk's licenses, its progress, and their audit entries commit together
+    let created = outbox::with_audited_tx(
+        &mut conn,
+        |tx| {
+            let mut license_ids = Vec::with_capacity(size as usize);
+            for _ in 0..size {
+                let license = queries::create_license(
+                    tx,
+                    &job.project_id,
+                    &job.product_id,
+                    &CreateLicense {
+                        email_hash: params.email_hash.clone(),
+                        customer_id: params.customer_id.clone(),
+                        expires_at: params.expires_at,
+                        updates_expires_at: params.updates_expires_at,
+                        payment_provider: None,
+                        payment_provider_customer_id: None,
+                        payment_provider_subscription_id: None,
+                        payment_provider_order_id: None,
+                        seats: params.seats,
+                    },
+                )?;
+                if params.is_trial {
+                    queries::mark_license_trial(tx, &license.id)?;
+                }
+                license_ids.push(license.id);
+            }
+            if !queries::record_bulk_job_chunk(tx, &job.id, chunk, &license_ids)? {
+                return Err(AppError::Conflict(msg::BULK_JOB_NOT_RUNNING.into()));
+            }
+            Ok(license_ids)
+        },
+        |license_ids| {
+            license_ids
+                .iter()
+                .filter_map(|license_id| {
+                    AuditLogBuilder::for_state(&audit_conn, state, &actor.headers)
+                        .actor(ActorType::User, Some(&actor.user_id))
+                        .action(AuditAction::CreateLicense)
+                        .resource("license", license_id)
+                        .details(&details)
+                        .org(org_id)
+                        .project(&job.project_id)
+                        .names(&actor.names)
+                        .auth_method(&actor.auth_method)
+                        .entry()
+                })
+                .collect::<Vec<_>>()
+        },
+    );
+
+    match created {
+        Ok(_) => {
+            outbox::relay(&conn, &audit_conn);
+            Ok(true)
+        }
+        Err(AppError::Conflict(ref m)) if m == msg::BULK_JOB_NOT_RUNNING => Ok(false),
+        Err(e) => {
+            tracing::warn!(job_id = %job.id, chunk, "Bulk license job chunk failed: {}", e);
+            queries::record_bulk_job_chunk_error(&conn, &job.id, chunk, msg::BULK_CHUNK_FAILED)?;
+            Ok(true)
+        }
+    }
+}
+
+/// Issue a fresh activation code for each license, as CSV
+/// (`license_id,activation_code`). Run inside a transaction so a failure
+/// issues none.
+pub fn activation_codes_csv(
+    conn: &Connection,
+    prefix: &str,
+    license_ids: &[String],
+) -> Result<String> {
+    let mut csv = String::from("license_id,activation_code\n");
+    for license_id in license_ids {
+        let code = queries::create_activation_code(conn, license_id, prefix)?;
+        csv.push_str(&format!("{},{}\n", license_id, code.code));
+    }
+    Ok(csv)
+}
+
+/// Mark jobs a restart cut off as failed, in every database. Their chunks
+/// already created are kept.
+pub fn fail_interrupted(state: &AppState) -> Result<usize> {
+    let mut failed = 0;
+    for pool in state.tenant_pools() {
+        failed += queries::fail_bulk_jobs(&*pool.get()?, None, msg::BULK_JOB_INTERRUPTED)?;
+    }
+    Ok(failed)
+}
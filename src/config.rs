@@ -1,12 +1,71 @@
+use std::collections::HashMap;
 use std::env;
+use std::fmt::Display;
 use std::fs;
+use std::net::IpAddr;
 use std::path::Path;
+use std::str::FromStr;
+
+use reqwest::Url;
+use serde::{Deserialize, Serialize, Serializer};
 
 use crate::crypto::MasterKey;
+use crate::custom_domains::DEFAULT_DNS_RESOLVER_URL;
+use crate::models::AuditFailureMode;
+
+/// Names an optional TOML file with the same settings as the environment.
+/// Its keys are the variable names in lower case, without any `PAYCHECK_`
+/// prefix (`base_url`, `resend_api_key`); environment variables win.
+pub const CONFIG_FILE_VAR: &str = "PAYCHECK_CONFIG_FILE";
+
+/// Every setting, by environment variable. Unknown `PAYCHECK_*` variables
+/// are logged as likely typos.
+const SETTINGS: &[&str] = &[
+    "PAYCHECK_ENV",
+    "HOST",
+    "PORT",
+    "BASE_URL",
+    "DATABASE_PATH",
+    "AUDIT_DATABASE_PATH",
+    "BOOTSTRAP_OPERATOR_EMAIL",
+    "AUDIT_LOG_ENABLED",
+    "AUDIT_FAILURE_MODE",
+    "PUBLIC_AUDIT_LOG_RETENTION_DAYS",
+    "SOFT_DELETE_RETENTION_DAYS",
+    "WEBHOOK_EVENT_RETENTION_DAYS",
+    "PAYMENT_SESSION_RETENTION_DAYS",
+    "RECONCILE_INTERVAL_HOURS",
+    "PAYCHECK_MASTER_KEY_FILE",
+    "PAYCHECK_SUCCESS_PAGE_URL",
+    "RATE_LIMIT_STRICT_RPM",
+    "RATE_LIMIT_STANDARD_RPM",
+    "RATE_LIMIT_RELAXED_RPM",
+    "RATE_LIMIT_ORG_OPS_RPM",
+    "BODY_LIMIT_BYTES",
+    "PUBLIC_BODY_LIMIT_BYTES",
+    "PROVIDER_CALLS_PER_ORG",
+    "PROVIDER_CALLS_GLOBAL",
+    "PROVIDER_CALL_WAIT_MS",
+    "PAYCHECK_CONSOLE_ORIGINS",
+    "PAYCHECK_RESEND_API_KEY",
+    "PAYCHECK_DEFAULT_FROM_EMAIL",
+    "PAYCHECK_TRUSTED_ISSUERS",
+    "MIGRATION_BACKUP_COUNT",
+    "PAYCHECK_ORG_DATA_DIR",
+    "PAYCHECK_BACKUP_DIR",
+    "PAYCHECK_BACKUP_KEY_FILE",
+    "BACKUP_RETENTION_COUNT",
+    "PII_MINIMIZATION",
+    "PAYCHECK_TRUST_REQUEST_ID",
+    "PAYCHECK_STATUS_PAGE",
+    "PAYCHECK_ALLOW_LOCALHOST_URLS",
+    "PAYCHECK_DNS_RESOLVER_URL",
+    "PAYCHECK_GEOIP_DATABASE",
+];
 
 /// Configuration for a trusted JWT issuer (e.g., Console, mobile app).
 /// JWTs from these issuers can authenticate to the API alongside API keys.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TrustedIssuer {
     /// Issuer URL (must match `iss` claim in JWT)
     pub issuer: String,
@@ -17,7 +76,7 @@ pub struct TrustedIssuer {
 }
 
 /// Rate limiting configuration for API endpoints
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize)]
 pub struct RateLimitConfig {
     /// Strict tier: requests per minute (for endpoints with external API calls like /buy)
     pub strict_rpm: u32,
@@ -53,7 +112,46 @@ impl RateLimitConfig {
     }
 }
 
-#[derive(Clone)]
+/// Request body size limits, in bytes
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct BodyLimitConfig {
+    /// Operator, org, and webhook endpoints
+    pub default_bytes: usize,
+    /// Public endpoints, whose bodies are small JSON documents
+    pub public_bytes: usize,
+}
+
+impl Default for BodyLimitConfig {
+    fn default() -> Self {
+        Self {
+            default_bytes: 1024 * 1024,
+            public_bytes: 64 * 1024,
+        }
+    }
+}
+
+/// Concurrency limits for outbound payment provider API calls
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct ProviderCallLimits {
+    /// Calls one org may have in flight at once
+    pub per_org: usize,
+    /// Calls in flight across all orgs
+    pub global: usize,
+    /// How long a call waits for a slot before the request gets a 503
+    pub wait_timeout_ms: u64,
+}
+
+impl Default for ProviderCallLimits {
+    fn default() -> Self {
+        Self {
+            per_org: 5,
+            global: 50,
+            wait_timeout_ms: 2000,
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
 pub struct Config {
     pub host: String,
     pub port: u16,
@@ -64,6 +162,10 @@ pub struct Config {
     pub dev_mode: bool,
     /// Enable/disable audit logging entirely
     pub audit_log_enabled: bool,
+    /// What a request does when its audit entry can't be written.
+    /// Set via AUDIT_FAILURE_MODE: `log_and_continue` (default) or
+    /// `fail_request`, which rolls back sensitive changes with a 500.
+    pub audit_failure_mode: AuditFailureMode,
     /// Days to retain public (end-user) audit logs before purging.
     /// Internal actions (operator, org_member, system) are kept forever.
     /// 0 = never purge (default).
@@ -79,20 +181,32 @@ pub struct Config {
     /// Abandoned carts have no value after checkout expiry (~24h).
     /// Default: 7 days. 0 = never purge.
     pub payment_session_retention_days: i64,
+    /// Hours between scheduled report-only subscription reconciliation runs
+    /// for every org and configured payment provider.
+    /// Set via RECONCILE_INTERVAL_HOURS. Default: 0 = never (operators run it).
+    pub reconcile_interval_hours: u64,
     /// Master key for envelope encryption of project private keys.
     /// Required in production; auto-generated in dev mode if not set.
+    #[serde(serialize_with = "redact")]
     pub master_key: MasterKey,
     /// URL for the success page after payment (when no project redirect is configured).
     /// If not set, defaults to {base_url}/success
     pub success_page_url: String,
     /// Rate limiting configuration for public endpoints
     pub rate_limit: RateLimitConfig,
+    /// Request body size limits.
+    /// Set via BODY_LIMIT_BYTES and PUBLIC_BODY_LIMIT_BYTES.
+    pub body_limits: BodyLimitConfig,
+    /// Outbound provider call limits.
+    /// Set via PROVIDER_CALLS_PER_ORG, PROVIDER_CALLS_GLOBAL, PROVIDER_CALL_WAIT_MS.
+    pub provider_calls: ProviderCallLimits,
     /// Allowed origins for admin console CORS (operator/org APIs)
     /// Set via PAYCHECK_CONSOLE_ORIGINS (comma-separated)
     pub console_origins: Vec<String>,
     /// System-level Resend API key for email delivery.
     /// Set via PAYCHECK_RESEND_API_KEY.
     /// Organizations can override with their own key; this is the fallback.
+    #[serde(serialize_with = "redact_option")]
     pub resend_api_key: Option<String>,
     /// Default "from" email address for activation emails.
     /// Set via PAYCHECK_DEFAULT_FROM_EMAIL.
@@ -104,6 +218,251 @@ pub struct Config {
     /// Number of database migration backups to keep.
     /// Set via MIGRATION_BACKUP_COUNT. Default: 3. -1 = keep all. 0 = no backups.
     pub migration_backup_count: i32,
+    /// Directory for dedicated per-org database files (data residency mode).
+    /// Set via PAYCHECK_ORG_DATA_DIR. When set, each new organization gets its own
+    /// SQLite file; existing orgs stay in the shared database until moved with
+    /// `--isolate-org`. Unset (default) = all orgs share the main database.
+    pub org_data_dir: Option<String>,
+    /// Directory for encrypted database backups (`POST /operators/backups`).
+    /// Set via PAYCHECK_BACKUP_DIR. Unset (default) = backups disabled.
+    pub backup_dir: Option<String>,
+    /// Key backups are encrypted with, so they survive a master key rotation.
+    /// Set via PAYCHECK_BACKUP_KEY_FILE (same format and 0400 permissions as
+    /// the master key file). Default: the master key.
+    #[serde(serialize_with = "redact_option")]
+    pub backup_key: Option<MasterKey>,
+    /// Newest backups to keep; older ones are deleted by the hourly maintenance task.
+    /// Set via BACKUP_RETENTION_COUNT. Default: 7. 0 = keep all.
+    pub backup_retention_count: i64,
+    /// Store user emails encrypted under the master key and look them up by hash.
+    /// Set via PII_MINIMIZATION. Audit logs record user IDs instead of names/emails.
+    /// Existing plaintext rows are converted with `--encrypt-user-emails`.
+    pub pii_minimization: bool,
+    /// Keep the `X-Request-Id` header sent by a reverse proxy instead of generating one.
+    /// Set via PAYCHECK_TRUST_REQUEST_ID. Only enable when the proxy always sets
+    /// or strips the header, otherwise clients choose their own request IDs.
+    pub trust_request_id: bool,
+    /// Serve the HTML status page at /operators/status-page, with its API key
+    /// login form. Set via PAYCHECK_STATUS_PAGE. Off by default.
+    pub status_page: bool,
+    /// Accept `localhost` and loopback addresses, over http too, in
+    /// org-provided URLs (`email_webhook_url`, `redirect_url`).
+    /// Set via PAYCHECK_ALLOW_LOCALHOST_URLS. For local development only.
+    pub allow_localhost_urls: bool,
+    /// DNS-over-HTTPS JSON endpoint for custom domain TXT lookups.
+    /// Set via PAYCHECK_DNS_RESOLVER_URL. Default: Cloudflare's resolver.
+    pub dns_resolver_url: String,
+    /// MaxMind-format database for the country of validating clients, in
+    /// projects that opt into usage geography (see `geoip`).
+    /// Set via PAYCHECK_GEOIP_DATABASE. Needs a build with the `geoip` feature.
+    pub geoip_database: Option<String>,
+}
+
+fn redact<T, S: Serializer>(_: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str("[redacted]")
+}
+
+fn redact_option<T, S: Serializer>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error> {
+    match value {
+        Some(_) => serializer.serialize_str("[redacted]"),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Raw settings keyed by environment variable: the config file, if any,
+/// overlaid by the environment.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigSource {
+    values: HashMap<String, String>,
+    unknown_vars: Vec<String>,
+}
+
+impl ConfigSource {
+    /// Read `.env`, the file named by `PAYCHECK_CONFIG_FILE`, and the environment.
+    pub fn load() -> Result<Self, Vec<String>> {
+        dotenvy::dotenv().ok();
+        let file = match env::var(CONFIG_FILE_VAR) {
+            Ok(path) => Some(fs::read_to_string(&path).map_err(|e| {
+                vec![format!(
+                    "{}: failed to read {}: {}",
+                    CONFIG_FILE_VAR, path, e
+                )]
+            })?),
+            Err(_) => None,
+        };
+        Self::from_parts(file.as_deref(), env::vars())
+    }
+
+    /// Settings from a TOML document, with `vars` taking precedence.
+    pub fn from_parts<K, V>(
+        file: Option<&str>,
+        vars: impl IntoIterator<Item = (K, V)>,
+    ) -> Result<Self, Vec<String>>
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let mut source = Self::default();
+        if let Some(file) = file {
+            source.values = parse_file(file)?;
+        }
+        for (name, value) in vars {
+            let name = name.into();
+            if SETTINGS.contains(&name.as_str()) {
+                source.values.insert(name, value.into());
+            } else if name.starts_with("PAYCHECK_") && name != CONFIG_FILE_VAR {
+                source.unknown_vars.push(name);
+            }
+        }
+        source.unknown_vars.sort();
+        Ok(source)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+
+    /// `PAYCHECK_*` environment variables that aren't settings
+    pub fn unknown_vars(&self) -> &[String] {
+        &self.unknown_vars
+    }
+
+    /// Main database path. CLI commands that don't need the master key read
+    /// it from here instead of loading the whole config.
+    pub fn database_path(&self) -> String {
+        self.get("DATABASE_PATH")
+            .unwrap_or("paycheck.db")
+            .to_string()
+    }
+
+    /// Directory for dedicated org databases, if data residency is on
+    pub fn org_data_dir(&self) -> Option<String> {
+        self.get("PAYCHECK_ORG_DATA_DIR")
+            .filter(|v| !v.trim().is_empty())
+            .map(String::from)
+    }
+}
+
+/// Flatten a TOML config file into settings keyed by variable name
+fn parse_file(contents: &str) -> Result<HashMap<String, String>, Vec<String>> {
+    let table: toml::Table = contents
+        .parse()
+        .map_err(|e| vec![format!("{}: {}", CONFIG_FILE_VAR, e)])?;
+    let mut values = HashMap::new();
+    let mut errors = Vec::new();
+    for (key, value) in table {
+        match setting_for_key(&key) {
+            Some(name) => {
+                values.insert(name.to_string(), file_value(&value));
+            }
+            None => errors.push(format!("{}: unknown setting `{}`", CONFIG_FILE_VAR, key)),
+        }
+    }
+    if errors.is_empty() {
+        Ok(values)
+    } else {
+        Err(errors)
+    }
+}
+
+/// The variable a file key sets: `base_url` is `BASE_URL`, and
+/// `resend_api_key` is `PAYCHECK_RESEND_API_KEY`
+fn setting_for_key(key: &str) -> Option<&'static str> {
+    let key = key.to_ascii_uppercase();
+    SETTINGS
+        .iter()
+        .copied()
+        .find(|name| *name == key || name.strip_prefix("PAYCHECK_") == Some(key.as_str()))
+}
+
+/// A file value as its variable would hold it: lists of strings are
+/// comma-separated, and tables (`trusted_issuers`) become JSON.
+fn file_value(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(s) => s.clone(),
+        toml::Value::Array(items) if items.iter().all(toml::Value::is_str) => items
+            .iter()
+            .filter_map(toml::Value::as_str)
+            .collect::<Vec<_>>()
+            .join(","),
+        toml::Value::Array(_) | toml::Value::Table(_) => {
+            serde_json::to_string(value).unwrap_or_default()
+        }
+        other => other.to_string(),
+    }
+}
+
+/// Typed reads from a [`ConfigSource`] that collect every problem instead of
+/// stopping at the first. A bad value falls back to its default so checks
+/// can carry on.
+struct Reader<'a> {
+    source: &'a ConfigSource,
+    errors: Vec<String>,
+}
+
+impl Reader<'_> {
+    fn string(&self, name: &str) -> Option<String> {
+        self.source.get(name).map(String::from)
+    }
+
+    fn parse<T>(&mut self, name: &str, default: T) -> T
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        let Some(raw) = self.source.get(name) else {
+            return default;
+        };
+        raw.trim().parse().unwrap_or_else(|e| {
+            self.errors
+                .push(format!("{}: invalid value {:?}: {}", name, raw, e));
+            default
+        })
+    }
+
+    fn flag(&mut self, name: &str, default: bool) -> bool {
+        let Some(raw) = self.source.get(name) else {
+            return default;
+        };
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "true" | "1" => true,
+            "false" | "0" => false,
+            _ => {
+                self.errors
+                    .push(format!("{}: expected true or false, got {:?}", name, raw));
+                default
+            }
+        }
+    }
+
+    fn key_file(&mut self, name: &str) -> Option<MasterKey> {
+        let path = self.string(name)?;
+        load_master_key_from_file(&path)
+            .map_err(|e| self.errors.push(format!("{}: {}", name, e)))
+            .ok()
+    }
+}
+
+/// Hosts `BASE_URL` may reach over plain http
+fn is_local_host(host: &str) -> bool {
+    host == "localhost"
+        || host.ends_with(".localhost")
+        || host
+            .trim_matches(['[', ']'])
+            .parse::<IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback() || ip.is_unspecified())
+}
+
+fn check_http_url(url: &str) -> Result<(), String> {
+    match Url::parse(url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(()),
+        Ok(_) => Err("must be an http or https URL".to_string()),
+        Err(e) => Err(format!("invalid URL: {}", e)),
+    }
+}
+
+fn invalid_config(errors: &[String]) -> ! {
+    panic!("Invalid configuration:\n  {}", errors.join("\n  "));
 }
 
 /// Check that a file has secure permissions (owner read-only, no write, no group/other access).
@@ -164,183 +523,297 @@ pub fn load_master_key_from_file(path: &str) -> Result<MasterKey, String> {
 }
 
 impl Config {
+    /// Load the configuration: the TOML file named by `PAYCHECK_CONFIG_FILE`
+    /// (if any), overlaid by `.env` and the environment. Panics listing every
+    /// invalid setting, and logs `PAYCHECK_*` variables it doesn't know.
     pub fn from_env() -> Self {
-        dotenvy::dotenv().ok();
-
-        let dev_mode = env::var("PAYCHECK_ENV")
-            .map(|v| v == "dev" || v == "development")
-            .unwrap_or(false);
-
-        let host = env::var("HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
-        let port: u16 = env::var("PORT")
-            .ok()
-            .and_then(|p| p.parse().ok())
-            .unwrap_or(4242);
-
-        let base_url = env::var("BASE_URL").unwrap_or_else(|_| format!("http://{}:{}", host, port));
+        let source = ConfigSource::load().unwrap_or_else(|errors| invalid_config(&errors));
+        for name in source.unknown_vars() {
+            tracing::warn!("Ignoring unknown setting {} (misspelled?)", name);
+        }
+        Self::from_source(&source).unwrap_or_else(|errors| invalid_config(&errors))
+    }
 
-        let audit_log_enabled = env::var("AUDIT_LOG_ENABLED")
-            .map(|v| v != "false" && v != "0")
-            .unwrap_or(true);
+    /// Parse and validate settings, reporting every problem at once.
+    pub fn from_source(source: &ConfigSource) -> Result<Self, Vec<String>> {
+        let mut r = Reader {
+            source,
+            errors: Vec::new(),
+        };
 
-        let public_audit_log_retention_days: i64 = env::var("PUBLIC_AUDIT_LOG_RETENTION_DAYS")
-            .ok()
-            .and_then(|v| v.parse().ok())
-            .unwrap_or(0);
+        let dev_mode = source
+            .get("PAYCHECK_ENV")
+            .is_some_and(|v| v == "dev" || v == "development");
 
-        let soft_delete_retention_days: i64 = env::var("SOFT_DELETE_RETENTION_DAYS")
-            .ok()
-            .and_then(|v| v.parse().ok())
-            .unwrap_or(0);
+        let host = r.string("HOST").unwrap_or_else(|| "127.0.0.1".to_string());
+        let port: u16 = r.parse("PORT", 4242);
 
-        let webhook_event_retention_days: i64 = env::var("WEBHOOK_EVENT_RETENTION_DAYS")
-            .ok()
-            .and_then(|v| v.parse().ok())
-            .unwrap_or(30); // Default 30 days - webhook events only needed for replay protection
+        let base_url = r
+            .string("BASE_URL")
+            .unwrap_or_else(|| format!("http://{}:{}", host, port));
 
-        let payment_session_retention_days: i64 = env::var("PAYMENT_SESSION_RETENTION_DAYS")
-            .ok()
-            .and_then(|v| v.parse().ok())
-            .unwrap_or(7); // Default 7 days - checkout sessions expire in ~24h
+        let audit_log_enabled = r.flag("AUDIT_LOG_ENABLED", true);
+        let audit_failure_mode = r.parse("AUDIT_FAILURE_MODE", AuditFailureMode::default());
+        let public_audit_log_retention_days = r.parse("PUBLIC_AUDIT_LOG_RETENTION_DAYS", 0);
+        let soft_delete_retention_days = r.parse("SOFT_DELETE_RETENTION_DAYS", 0);
+        // Webhook events are only needed for replay protection
+        let webhook_event_retention_days = r.parse("WEBHOOK_EVENT_RETENTION_DAYS", 30);
+        // Checkout sessions expire in ~24h
+        let payment_session_retention_days = r.parse("PAYMENT_SESSION_RETENTION_DAYS", 7);
+        let reconcile_interval_hours = r.parse("RECONCILE_INTERVAL_HOURS", 0);
 
         // Master key for envelope encryption - loaded from file with permission checks
-        let master_key = match env::var("PAYCHECK_MASTER_KEY_FILE") {
-            Ok(path) => load_master_key_from_file(&path).unwrap_or_else(|e| {
-                panic!("Failed to load master key:\n{}", e);
-            }),
-            Err(_) if dev_mode => {
-                // In dev mode, generate an ephemeral key and warn
-                let generated = MasterKey::generate();
-                eprintln!("============================================");
-                eprintln!("WARNING: No PAYCHECK_MASTER_KEY_FILE set.");
-                eprintln!("Using ephemeral key for dev mode.");
-                eprintln!("Private keys will NOT be recoverable after restart!");
-                eprintln!();
-                eprintln!("For persistent dev usage, create a key file:");
-                eprintln!("  openssl rand -base64 32 > /path/to/master.key");
-                eprintln!("  chmod 400 /path/to/master.key");
-                eprintln!("  export PAYCHECK_MASTER_KEY_FILE=/path/to/master.key");
-                eprintln!("============================================");
-                MasterKey::from_base64(&generated).unwrap()
-            }
-            Err(_) => {
-                panic!(
-                    "PAYCHECK_MASTER_KEY_FILE environment variable is required.\n\n\
-                     Create a master key file:\n  \
-                       openssl rand -base64 32 > /etc/paycheck/master.key\n  \
-                       chmod 400 /etc/paycheck/master.key\n\n\
-                     Then set the environment variable:\n  \
-                       export PAYCHECK_MASTER_KEY_FILE=/etc/paycheck/master.key"
-                );
-            }
+        let master_key = if source.get("PAYCHECK_MASTER_KEY_FILE").is_some() {
+            r.key_file("PAYCHECK_MASTER_KEY_FILE")
+        } else if dev_mode {
+            // In dev mode, generate an ephemeral key and warn
+            eprintln!("============================================");
+            eprintln!("WARNING: No PAYCHECK_MASTER_KEY_FILE set.");
+            eprintln!("Using ephemeral key for dev mode.");
+            eprintln!("Private keys will NOT be recoverable after restart!");
+            eprintln!();
+            eprintln!("For persistent dev usage, create a key file:");
+            eprintln!("  openssl rand -base64 32 > /path/to/master.key");
+            eprintln!("  chmod 400 /path/to/master.key");
+            eprintln!("  export PAYCHECK_MASTER_KEY_FILE=/path/to/master.key");
+            eprintln!("============================================");
+            Some(MasterKey::random())
+        } else {
+            r.errors.push(
+                "PAYCHECK_MASTER_KEY_FILE is required.\n\n\
+                 Create a master key file:\n  \
+                   openssl rand -base64 32 > /etc/paycheck/master.key\n  \
+                   chmod 400 /etc/paycheck/master.key\n\n\
+                 Then set the environment variable:\n  \
+                   export PAYCHECK_MASTER_KEY_FILE=/etc/paycheck/master.key"
+                    .to_string(),
+            );
+            None
         };
 
-        let success_page_url = env::var("PAYCHECK_SUCCESS_PAGE_URL")
-            .unwrap_or_else(|_| format!("{}/success", base_url));
+        let success_page_url = r
+            .string("PAYCHECK_SUCCESS_PAGE_URL")
+            .unwrap_or_else(|| format!("{}/success", base_url));
 
-        // Rate limiting configuration
         let rate_limit_defaults = RateLimitConfig::default();
         let rate_limit = RateLimitConfig {
-            strict_rpm: env::var("RATE_LIMIT_STRICT_RPM")
-                .ok()
-                .and_then(|v| v.parse().ok())
-                .unwrap_or(rate_limit_defaults.strict_rpm),
-            standard_rpm: env::var("RATE_LIMIT_STANDARD_RPM")
-                .ok()
-                .and_then(|v| v.parse().ok())
-                .unwrap_or(rate_limit_defaults.standard_rpm),
-            relaxed_rpm: env::var("RATE_LIMIT_RELAXED_RPM")
-                .ok()
-                .and_then(|v| v.parse().ok())
-                .unwrap_or(rate_limit_defaults.relaxed_rpm),
-            org_ops_rpm: env::var("RATE_LIMIT_ORG_OPS_RPM")
-                .ok()
-                .and_then(|v| v.parse().ok())
-                .unwrap_or(rate_limit_defaults.org_ops_rpm),
+            strict_rpm: r.parse("RATE_LIMIT_STRICT_RPM", rate_limit_defaults.strict_rpm),
+            standard_rpm: r.parse("RATE_LIMIT_STANDARD_RPM", rate_limit_defaults.standard_rpm),
+            relaxed_rpm: r.parse("RATE_LIMIT_RELAXED_RPM", rate_limit_defaults.relaxed_rpm),
+            org_ops_rpm: r.parse("RATE_LIMIT_ORG_OPS_RPM", rate_limit_defaults.org_ops_rpm),
         };
 
-        // Console origins for admin API CORS
-        // In dev mode, defaults to localhost:3001 if not set
-        let console_origins: Vec<String> = env::var("PAYCHECK_CONSOLE_ORIGINS")
-            .map(|s| s.split(',').map(|s| s.trim().to_string()).collect())
-            .unwrap_or_else(|_| {
-                if dev_mode {
-                    vec![
-                        "http://localhost:3001".to_string(),
-                        "http://127.0.0.1:3001".to_string(),
-                    ]
-                } else {
-                    vec![]
-                }
-            });
+        let body_limit_defaults = BodyLimitConfig::default();
+        let body_limits = BodyLimitConfig {
+            default_bytes: r.parse("BODY_LIMIT_BYTES", body_limit_defaults.default_bytes),
+            public_bytes: r.parse("PUBLIC_BODY_LIMIT_BYTES", body_limit_defaults.public_bytes),
+        };
 
-        // Resend API key for email delivery (optional - orgs can set their own)
-        let resend_api_key = env::var("PAYCHECK_RESEND_API_KEY").ok();
+        let provider_call_defaults = ProviderCallLimits::default();
+        let provider_calls = ProviderCallLimits {
+            per_org: r.parse("PROVIDER_CALLS_PER_ORG", provider_call_defaults.per_org),
+            global: r.parse("PROVIDER_CALLS_GLOBAL", provider_call_defaults.global),
+            wait_timeout_ms: r.parse(
+                "PROVIDER_CALL_WAIT_MS",
+                provider_call_defaults.wait_timeout_ms,
+            ),
+        };
 
-        // Default "from" email address for activation emails
-        let default_from_email = env::var("PAYCHECK_DEFAULT_FROM_EMAIL")
-            .unwrap_or_else(|_| "noreply@paycheck.dev".to_string());
+        // Console origins for admin API CORS
+        // In dev mode, defaults to localhost:3001 if not set
+        let console_origins: Vec<String> = match r.string("PAYCHECK_CONSOLE_ORIGINS") {
+            Some(origins) => origins
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            None if dev_mode => vec![
+                "http://localhost:3001".to_string(),
+                "http://127.0.0.1:3001".to_string(),
+            ],
+            None => vec![],
+        };
 
         // Trusted JWT issuers for first-party app authentication
         // Format: JSON array of {issuer, jwks_url, audience} objects
-        let trusted_issuers: Vec<TrustedIssuer> = env::var("PAYCHECK_TRUSTED_ISSUERS")
-            .ok()
-            .and_then(|json| {
-                #[derive(serde::Deserialize)]
-                struct IssuerConfig {
-                    issuer: String,
-                    jwks_url: String,
-                    audience: String,
-                }
-                serde_json::from_str::<Vec<IssuerConfig>>(&json)
-                    .map_err(|e| {
-                        eprintln!("WARNING: Failed to parse PAYCHECK_TRUSTED_ISSUERS: {}", e);
-                        e
-                    })
-                    .ok()
-            })
-            .map(|configs| {
-                configs
-                    .into_iter()
-                    .map(|c| TrustedIssuer {
-                        issuer: c.issuer,
-                        jwks_url: c.jwks_url,
-                        audience: c.audience,
-                    })
-                    .collect()
-            })
-            .unwrap_or_default();
-
-        // Migration backup count (how many backups to keep)
-        // -1 = keep all backups, 0 = no backups, n = keep n backups
-        let migration_backup_count: i32 = env::var("MIGRATION_BACKUP_COUNT")
-            .ok()
-            .and_then(|v| v.parse().ok())
-            .unwrap_or(3);
+        let trusted_issuers: Vec<TrustedIssuer> = match r.string("PAYCHECK_TRUSTED_ISSUERS") {
+            Some(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                r.errors
+                    .push(format!("PAYCHECK_TRUSTED_ISSUERS: invalid JSON: {}", e));
+                Vec::new()
+            }),
+            None => Vec::new(),
+        };
 
-        Self {
+        let backup_key = r.key_file("PAYCHECK_BACKUP_KEY_FILE");
+
+        let config = Self {
             host,
             port,
-            database_path: env::var("DATABASE_PATH").unwrap_or_else(|_| "paycheck.db".to_string()),
-            audit_database_path: env::var("AUDIT_DATABASE_PATH")
-                .unwrap_or_else(|_| "paycheck_audit.db".to_string()),
+            database_path: source.database_path(),
+            audit_database_path: r
+                .string("AUDIT_DATABASE_PATH")
+                .unwrap_or_else(|| "paycheck_audit.db".to_string()),
             base_url,
-            bootstrap_operator_email: env::var("BOOTSTRAP_OPERATOR_EMAIL").ok(),
+            bootstrap_operator_email: r.string("BOOTSTRAP_OPERATOR_EMAIL"),
             dev_mode,
             audit_log_enabled,
+            audit_failure_mode,
             public_audit_log_retention_days,
             soft_delete_retention_days,
             webhook_event_retention_days,
             payment_session_retention_days,
-            master_key,
+            reconcile_interval_hours,
+            // A missing key is already an error; the stand-in is never used
+            master_key: master_key.unwrap_or_else(MasterKey::random),
             success_page_url,
             rate_limit,
+            body_limits,
+            provider_calls,
             console_origins,
-            resend_api_key,
-            default_from_email,
+            // Optional - orgs can set their own
+            resend_api_key: r.string("PAYCHECK_RESEND_API_KEY"),
+            default_from_email: r
+                .string("PAYCHECK_DEFAULT_FROM_EMAIL")
+                .unwrap_or_else(|| "noreply@paycheck.dev".to_string()),
             trusted_issuers,
-            migration_backup_count,
+            // -1 = keep all backups, 0 = no backups, n = keep n backups
+            migration_backup_count: r.parse("MIGRATION_BACKUP_COUNT", 3),
+            org_data_dir: source.org_data_dir(),
+            backup_dir: r
+                .string("PAYCHECK_BACKUP_DIR")
+                .filter(|v| !v.trim().is_empty()),
+            backup_key,
+            backup_retention_count: r.parse("BACKUP_RETENTION_COUNT", 7),
+            pii_minimization: r.flag("PII_MINIMIZATION", false),
+            trust_request_id: r.flag("PAYCHECK_TRUST_REQUEST_ID", false),
+            status_page: r.flag("PAYCHECK_STATUS_PAGE", false),
+            allow_localhost_urls: r.flag("PAYCHECK_ALLOW_LOCALHOST_URLS", false),
+            dns_resolver_url: r
+                .string("PAYCHECK_DNS_RESOLVER_URL")
+                .unwrap_or_else(|| DEFAULT_DNS_RESOLVER_URL.to_string()),
+            geoip_database: r
+                .string("PAYCHECK_GEOIP_DATABASE")
+                .filter(|v| !v.trim().is_empty()),
+        };
+
+        let mut errors = r.errors;
+        errors.extend(config.validate());
+        if errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Range and cross-field checks on parsed settings
+    fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        match Url::parse(&self.base_url) {
+            Ok(url) if url.scheme() == "https" => {}
+            Ok(url) if url.scheme() == "http" => {
+                if !self.dev_mode && !url.host_str().is_some_and(is_local_host) {
+                    errors.push(
+                        "BASE_URL must use https unless it is localhost (or PAYCHECK_ENV=dev)"
+                            .to_string(),
+                    );
+                }
+            }
+            Ok(_) => errors.push("BASE_URL must be an http or https URL".to_string()),
+            Err(e) => errors.push(format!("BASE_URL: invalid URL: {}", e)),
+        }
+        if let Err(e) = check_http_url(&self.success_page_url) {
+            errors.push(format!("PAYCHECK_SUCCESS_PAGE_URL: {}", e));
+        }
+        if let Err(e) = check_http_url(&self.dns_resolver_url) {
+            errors.push(format!("PAYCHECK_DNS_RESOLVER_URL: {}", e));
+        }
+        for origin in &self.console_origins {
+            if let Err(e) = check_http_url(origin) {
+                errors.push(format!("PAYCHECK_CONSOLE_ORIGINS: {}: {}", origin, e));
+            }
+        }
+        for issuer in &self.trusted_issuers {
+            if let Err(e) = check_http_url(&issuer.jwks_url) {
+                errors.push(format!(
+                    "PAYCHECK_TRUSTED_ISSUERS: jwks_url for {}: {}",
+                    issuer.issuer, e
+                ));
+            }
+        }
+
+        for (name, value) in [
+            (
+                "PUBLIC_AUDIT_LOG_RETENTION_DAYS",
+                self.public_audit_log_retention_days,
+            ),
+            (
+                "SOFT_DELETE_RETENTION_DAYS",
+                self.soft_delete_retention_days,
+            ),
+            (
+                "WEBHOOK_EVENT_RETENTION_DAYS",
+                self.webhook_event_retention_days,
+            ),
+            (
+                "PAYMENT_SESSION_RETENTION_DAYS",
+                self.payment_session_retention_days,
+            ),
+            ("BACKUP_RETENTION_COUNT", self.backup_retention_count),
+        ] {
+            if value < 0 {
+                errors.push(format!("{} must be 0 or more (0 = keep forever)", name));
+            }
         }
+        if self.migration_backup_count < -1 {
+            errors.push("MIGRATION_BACKUP_COUNT must be -1 (keep all) or more".to_string());
+        }
+        for (name, value) in [
+            ("BODY_LIMIT_BYTES", self.body_limits.default_bytes),
+            ("PUBLIC_BODY_LIMIT_BYTES", self.body_limits.public_bytes),
+            ("PROVIDER_CALLS_PER_ORG", self.provider_calls.per_org),
+            ("PROVIDER_CALLS_GLOBAL", self.provider_calls.global),
+        ] {
+            if value == 0 {
+                errors.push(format!("{} must be more than 0", name));
+            }
+        }
+        if self.provider_calls.per_org > self.provider_calls.global {
+            errors.push(
+                "PROVIDER_CALLS_PER_ORG can't be more than PROVIDER_CALLS_GLOBAL".to_string(),
+            );
+        }
+
+        if self.default_from_email.trim().is_empty() {
+            errors.push("PAYCHECK_DEFAULT_FROM_EMAIL can't be empty".to_string());
+        }
+        if self.audit_failure_mode == AuditFailureMode::FailRequest && !self.audit_log_enabled {
+            errors.push(
+                "AUDIT_FAILURE_MODE=fail_request has no effect with AUDIT_LOG_ENABLED=false"
+                    .to_string(),
+            );
+        }
+        if self.geoip_database.is_some() && !cfg!(feature = "geoip") {
+            errors.push(
+                "PAYCHECK_GEOIP_DATABASE is set but this build doesn't have the geoip feature"
+                    .to_string(),
+            );
+        }
+        if self.backup_key.is_some() && self.backup_dir.is_none() {
+            errors.push(
+                "PAYCHECK_BACKUP_KEY_FILE is set but PAYCHECK_BACKUP_DIR isn't, so backups are off"
+                    .to_string(),
+            );
+        }
+
+        errors
+    }
+
+    /// The configuration as JSON, with keys and API keys shown as
+    /// "[redacted]" (for `GET /operators/config`).
+    pub fn redacted(&self) -> serde_json::Value {
+        serde_json::to_value(self).expect("config serializes to JSON")
     }
 
     pub fn addr(&self) -> String {
@@ -372,6 +845,210 @@ impl Config {
                 HeaderName::from_static("authorization"),
                 HeaderName::from_static("content-type"),
             ])
+            .expose_headers([HeaderName::from_static(
+                crate::middleware::REQUEST_ID_HEADER,
+            )])
             .allow_credentials(true)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(file: Option<&str>, vars: &[(&str, &str)]) -> Result<Config, Vec<String>> {
+        let source = ConfigSource::from_parts(file, vars.iter().copied())?;
+        Config::from_source(&source)
+    }
+
+    #[test]
+    fn test_environment_overrides_config_file() {
+        let file = r#"
+            env = "dev"
+            base_url = "https://file.example.com"
+            port = 5000
+            console_origins = ["https://console.example.com", "https://admin.example.com"]
+            pii_minimization = true
+
+            [[trusted_issuers]]
+            issuer = "https://console.example.com"
+            jwks_url = "https://console.example.com/.well-known/jwks.json"
+            audience = "paycheck"
+        "#;
+        let config = load(
+            Some(file),
+            &[
+                ("BASE_URL", "https://env.example.com"),
+                ("PII_MINIMIZATION", "false"),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(config.base_url, "https://env.example.com");
+        assert!(!config.pii_minimization);
+        assert_eq!(config.port, 5000);
+        assert_eq!(
+            config.console_origins,
+            ["https://console.example.com", "https://admin.example.com"]
+        );
+        assert_eq!(config.trusted_issuers.len(), 1);
+        assert_eq!(config.trusted_issuers[0].audience, "paycheck");
+        assert_eq!(config.success_page_url, "https://env.example.com/success");
+    }
+
+    #[test]
+    fn test_unknown_settings() {
+        let errors = load(Some("env = \"dev\"\nbase_ur = \"https://x.example\""), &[])
+            .err()
+            .unwrap();
+        assert_eq!(errors, ["PAYCHECK_CONFIG_FILE: unknown setting `base_ur`"]);
+
+        let source = ConfigSource::from_parts(
+            None,
+            [
+                ("PAYCHECK_ENV", "dev"),
+                ("PAYCHECK_RESEND_KEY", "re_123"),
+                ("PAYCHECK_CONFIG_FILE", "paycheck.toml"),
+                ("HOME", "/root"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(source.unknown_vars(), ["PAYCHECK_RESEND_KEY"]);
+    }
+
+    #[test]
+    fn test_every_invalid_setting_is_reported() {
+        let errors = load(
+            None,
+            &[
+                ("BASE_URL", "http://paycheck.example.com"),
+                ("PORT", "http"),
+                ("SOFT_DELETE_RETENTION_DAYS", "-1"),
+                ("PROVIDER_CALLS_PER_ORG", "10"),
+                ("PROVIDER_CALLS_GLOBAL", "5"),
+                ("PII_MINIMIZATION", "yes please"),
+            ],
+        )
+        .err()
+        .unwrap();
+
+        let expected = [
+            "PORT",
+            "PAYCHECK_MASTER_KEY_FILE is required",
+            "PII_MINIMIZATION",
+            "BASE_URL must use https",
+            "SOFT_DELETE_RETENTION_DAYS",
+            "PROVIDER_CALLS_PER_ORG",
+        ];
+        assert_eq!(errors.len(), expected.len(), "{:?}", errors);
+        for (error, start) in errors.iter().zip(expected) {
+            assert!(
+                error.starts_with(start),
+                "{:?} should start with {:?}",
+                error,
+                start
+            );
+        }
+    }
+
+    #[test]
+    fn test_audit_failure_mode() {
+        let config = load(None, &[("PAYCHECK_ENV", "dev")]).unwrap();
+        assert_eq!(config.audit_failure_mode, AuditFailureMode::LogAndContinue);
+
+        let config = load(
+            Some("env = \"dev\"\naudit_failure_mode = \"fail_request\""),
+            &[],
+        )
+        .unwrap();
+        assert_eq!(config.audit_failure_mode, AuditFailureMode::FailRequest);
+
+        let errors = load(
+            None,
+            &[("PAYCHECK_ENV", "dev"), ("AUDIT_FAILURE_MODE", "fail")],
+        )
+        .err()
+        .unwrap();
+        assert!(errors[0].starts_with("AUDIT_FAILURE_MODE: invalid value"));
+
+        let errors = load(
+            None,
+            &[
+                ("PAYCHECK_ENV", "dev"),
+                ("AUDIT_FAILURE_MODE", "fail_request"),
+                ("AUDIT_LOG_ENABLED", "false"),
+            ],
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            errors,
+            ["AUDIT_FAILURE_MODE=fail_request has no effect with AUDIT_LOG_ENABLED=false"]
+        );
+    }
+
+    #[test]
+    fn test_geoip_database_needs_the_feature() {
+        let result = load(
+            None,
+            &[
+                ("PAYCHECK_ENV", "dev"),
+                ("PAYCHECK_GEOIP_DATABASE", "/var/lib/GeoLite2-Country.mmdb"),
+            ],
+        );
+        if cfg!(feature = "geoip") {
+            assert_eq!(
+                result.unwrap().geoip_database.as_deref(),
+                Some("/var/lib/GeoLite2-Country.mmdb")
+            );
+        } else {
+            assert_eq!(
+                result.err().unwrap(),
+                ["PAYCHECK_GEOIP_DATABASE is set but this build doesn't have the geoip feature"]
+            );
+        }
+    }
+
+    #[test]
+    fn test_plain_http_base_url_only_for_localhost_or_dev() {
+        for host in [
+            "localhost",
+            "127.0.0.1",
+            "[::1]",
+            "0.0.0.0",
+            "app.localhost",
+        ] {
+            assert!(is_local_host(host), "{}", host);
+        }
+        assert!(!is_local_host("paycheck.example.com"));
+
+        let config = load(
+            None,
+            &[
+                ("PAYCHECK_ENV", "dev"),
+                ("BASE_URL", "http://192.168.1.20:4242"),
+            ],
+        );
+        assert!(config.is_ok(), "dev mode allows http anywhere");
+    }
+
+    #[test]
+    fn test_redacted_hides_secrets() {
+        let config = load(
+            None,
+            &[
+                ("PAYCHECK_ENV", "dev"),
+                ("PAYCHECK_RESEND_API_KEY", "re_secret"),
+            ],
+        )
+        .unwrap();
+        let redacted = config.redacted();
+
+        assert_eq!(redacted["master_key"], "[redacted]");
+        assert_eq!(redacted["resend_api_key"], "[redacted]");
+        assert_eq!(redacted["backup_key"], serde_json::Value::Null);
+        assert_eq!(redacted["port"], 4242);
+        assert_eq!(redacted["rate_limit"]["strict_rpm"], 10);
+        assert!(!redacted.to_string().contains("re_secret"));
+    }
+}
@@ -9,6 +9,13 @@
 //! - Project Ed25519 private keys (DEK derived from project_id)
 //! - Organization payment configs (DEK derived from org_id)
 //! - License keys (DEK derived from project_id)
+//! - Database backups (DEK derived from the backup id, under the backup key)
+//!
+//! Org-owned secrets (org configs, project private keys) are encrypted under
+//! the org's own data key rather than the master key directly. The org data
+//! key is random and stored wrapped by the master key (see
+//! [`MasterKey::wrap_key`]), so a leaked org key exposes only that org and a
+//! master key rotation only has to re-wrap org keys.
 
 use aes_gcm::{
     Aes256Gcm, Nonce,
@@ -20,6 +27,7 @@ use hkdf::Hkdf;
 use sha2::{Digest, Sha256};
 
 use crate::error::{AppError, Result};
+use crate::models::EmailAddress;
 
 /// Nonce size for AES-GCM (96 bits)
 const NONCE_SIZE: usize = 12;
@@ -74,6 +82,54 @@ impl MasterKey {
         Self { key }
     }
 
+    /// Generate a new random key, used as an org's data key.
+    pub fn random() -> Self {
+        use rand::RngCore;
+        use rand::rngs::OsRng;
+        let mut key = [0u8; MASTER_KEY_SIZE];
+        OsRng.fill_bytes(&mut key);
+        Self { key }
+    }
+
+    /// Encrypt another key (an org's data key) for storage on the org row.
+    /// Same format as [`MasterKey::encrypt_private_key`], bound to the org id.
+    pub fn wrap_key(&self, org_id: &str, key: &MasterKey) -> Result<Vec<u8>> {
+        self.encrypt_private_key(&wrap_context(org_id), &key.key)
+    }
+
+    /// Encrypt a database backup. Same format as
+    /// [`MasterKey::encrypt_private_key`], bound to the backup id so a file
+    /// can't be passed off as a different backup.
+    pub fn encrypt_backup(&self, backup_id: &str, data: &[u8]) -> Result<Vec<u8>> {
+        self.encrypt_private_key(&backup_context(backup_id), data)
+    }
+
+    /// Decrypt a backup produced by [`MasterKey::encrypt_backup`].
+    pub fn decrypt_backup(&self, backup_id: &str, encrypted: &[u8]) -> Result<Vec<u8>> {
+        self.decrypt_private_key(&backup_context(backup_id), encrypted)
+    }
+
+    /// Encrypt a project's email webhook signing secret. Same format as
+    /// [`MasterKey::encrypt_private_key`], bound to the project id apart from
+    /// its signing key.
+    pub fn encrypt_webhook_secret(&self, project_id: &str, secret: &[u8]) -> Result<Vec<u8>> {
+        self.encrypt_private_key(&webhook_secret_context(project_id), secret)
+    }
+
+    /// Decrypt a secret produced by [`MasterKey::encrypt_webhook_secret`].
+    pub fn decrypt_webhook_secret(&self, project_id: &str, encrypted: &[u8]) -> Result<Vec<u8>> {
+        self.decrypt_private_key(&webhook_secret_context(project_id), encrypted)
+    }
+
+    /// Decrypt a key produced by [`MasterKey::wrap_key`] for the same org.
+    pub fn unwrap_key(&self, org_id: &str, wrapped: &[u8]) -> Result<MasterKey> {
+        let bytes = self.decrypt_private_key(&wrap_context(org_id), wrapped)?;
+        let key: [u8; MASTER_KEY_SIZE] = bytes
+            .try_into()
+            .map_err(|_| AppError::Internal("Wrapped key has wrong length".into()))?;
+        Ok(Self { key })
+    }
+
     /// Derive a per-project data encryption key using HKDF.
     fn derive_dek(&self, project_id: &str) -> [u8; 32] {
         let hk = Hkdf::<Sha256>::new(Some(b"paycheck-v1"), &self.key);
@@ -143,6 +199,92 @@ impl MasterKey {
 
         Ok(plaintext)
     }
+
+    /// Sign an audit archive manifest. Returns hex HMAC-SHA256 under a key
+    /// derived from the master key for archive signing only.
+    ///
+    /// Archives signed before a master key rotation no longer verify.
+    pub fn sign_audit_archive(&self, message: &[u8]) -> String {
+        hex::encode(self.audit_archive_mac(message))
+    }
+
+    /// Check a signature produced by [`MasterKey::sign_audit_archive`].
+    pub fn verify_audit_archive(&self, message: &[u8], signature: &str) -> bool {
+        use subtle::ConstantTimeEq;
+
+        let Ok(provided) = hex::decode(signature) else {
+            return false;
+        };
+        self.audit_archive_mac(message).ct_eq(&provided).into()
+    }
+
+    fn audit_archive_mac(&self, message: &[u8]) -> Vec<u8> {
+        self.mac(b"audit-archive-signing", message)
+    }
+
+    /// Sign a status page session cookie. Returns hex HMAC-SHA256 under a
+    /// key derived for sessions only, so a master key rotation logs everyone out.
+    pub fn sign_status_session(&self, message: &[u8]) -> String {
+        hex::encode(self.mac(b"status-page-session", message))
+    }
+
+    /// Check a signature produced by [`MasterKey::sign_status_session`].
+    pub fn verify_status_session(&self, message: &[u8], signature: &str) -> bool {
+        use subtle::ConstantTimeEq;
+
+        let Ok(provided) = hex::decode(signature) else {
+            return false;
+        };
+        self.mac(b"status-page-session", message)
+            .ct_eq(&provided)
+            .into()
+    }
+
+    /// Key a project's download URLs are signed with (see [`crate::artifacts`]).
+    /// The vendor's CDN holds a copy to check them, so it must be fetched
+    /// again after a master key rotation.
+    pub fn download_signing_key(&self, project_id: &str) -> [u8; 32] {
+        self.signing_key(download_signing_context(project_id).as_bytes())
+    }
+
+    /// HMAC-SHA256 of `message` under a key derived for `purpose`.
+    fn mac(&self, purpose: &[u8], message: &[u8]) -> Vec<u8> {
+        use hmac::{Hmac, Mac};
+
+        let mut mac: Hmac<Sha256> =
+            Mac::new_from_slice(&self.signing_key(purpose)).expect("HMAC can take key of any size");
+        mac.update(message);
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    /// Derive a signing key for `purpose`.
+    fn signing_key(&self, purpose: &[u8]) -> [u8; 32] {
+        let hk = Hkdf::<Sha256>::new(Some(b"paycheck-v1"), &self.key);
+        let mut signing_key = [0u8; 32];
+        hk.expand(purpose, &mut signing_key)
+            .expect("HKDF expand should not fail with valid length");
+        signing_key
+    }
+}
+
+/// DEK context for wrapped org data keys, kept apart from entity ids.
+fn wrap_context(org_id: &str) -> String {
+    format!("org-data-key:{}", org_id)
+}
+
+/// DEK context for database backups.
+fn backup_context(backup_id: &str) -> String {
+    format!("backup:{}", backup_id)
+}
+
+/// DEK context for a project's email webhook signing secret.
+fn webhook_secret_context(project_id: &str) -> String {
+    format!("webhook-secret:{}", project_id)
+}
+
+/// Signing key context for a project's download URLs.
+fn download_signing_context(project_id: &str) -> String {
+    format!("artifact-download:{}", project_id)
 }
 
 /// Email hasher with a stable HMAC key.
@@ -181,18 +323,50 @@ impl EmailHasher {
     /// database access cannot precompute hashes without the master key to
     /// decrypt the HMAC key.
     ///
-    /// The email is normalized (NFC Unicode, lowercase, trimmed) before hashing
-    /// to ensure consistent lookups regardless of input encoding.
-    pub fn hash(&self, email: &str) -> String {
-        use hmac::{Hmac, Mac};
-        use unicode_normalization::UnicodeNormalization;
+    /// Hashes the normalized form, so every spelling of an address that
+    /// [`EmailAddress::parse`] accepts hashes the same.
+    pub fn hash(&self, email: &EmailAddress) -> String {
+        self.hmac(email.as_str())
+    }
 
-        // Normalize email: NFC Unicode form, lowercase, trimmed
-        let normalized: String = email.nfc().collect();
-        let normalized = normalized.to_lowercase();
-        let normalized = normalized.trim();
+    /// Hash of the address as it was normalized before [`EmailAddress`]
+    /// existed, if that differs from [`EmailHasher::hash`]. Rows written from
+    /// a display-name or Unicode-domain input still carry this hash.
+    pub fn legacy_hash(&self, email: &EmailAddress) -> Option<String> {
+        email.legacy_form().map(|legacy| self.hmac(legacy))
+    }
+
+    /// Look something up by email hash, falling back to the legacy hash when
+    /// the normalized one finds nothing (`found` decides what counts as a
+    /// hit). A legacy match is logged so stale rows can be spotted.
+    pub fn find_by_hash<T, E>(
+        &self,
+        email: &EmailAddress,
+        context: &str,
+        mut lookup: impl FnMut(&str) -> std::result::Result<T, E>,
+        found: impl Fn(&T) -> bool,
+    ) -> std::result::Result<T, E> {
+        let result = lookup(&self.hash(email))?;
+        if found(&result) {
+            return Ok(result);
+        }
+        let Some(legacy) = self.legacy_hash(email) else {
+            return Ok(result);
+        };
+        let fallback = lookup(&legacy)?;
+        if found(&fallback) {
+            tracing::warn!(
+                context,
+                "Email matched only by its legacy (pre-normalization) hash"
+            );
+            return Ok(fallback);
+        }
+        Ok(result)
+    }
+
+    fn hmac(&self, normalized: &str) -> String {
+        use hmac::{Hmac, Mac};
 
-        // Compute HMAC-SHA256
         let mut mac: Hmac<Sha256> =
             Mac::new_from_slice(&self.hmac_key).expect("HMAC can take key of any size");
         mac.update(normalized.as_bytes());
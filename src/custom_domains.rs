@@ -0,0 +1,218 @@
+//! Custom domains: a vendor's own hostname (e.g. `licenses.example.com`)
+//! serving one project's public endpoints.
+//!
+//! An operator adds the hostname to a project and gets a verification
+//! token. The vendor publishes it as a TXT record at
+//! `_paycheck-challenge.<hostname>`, and the operator asks Paycheck to check.
+//! Once verified, [`crate::middleware::custom_domain_host`] resolves the
+//! project from the `Host` header of public requests, as if the client had
+//! sent the project's `X-Paycheck-Project` header.
+//!
+//! TLS for the hostname is up to the proxy in front of Paycheck, which must
+//! pass the original `Host` header through.
+//!
+//! TXT records are looked up over DNS-over-HTTPS (the JSON API Cloudflare and
+//! Google serve), at `PAYCHECK_DNS_RESOLVER_URL`.
+
+use std::time::Duration;
+
+use axum::http::{HeaderMap, Uri, header};
+use reqwest::Client;
+use serde::Deserialize;
+
+/// Label the verification TXT record goes under.
+pub const CHALLENGE_LABEL: &str = "_paycheck-challenge";
+
+/// DNS-over-HTTPS resolver used unless `PAYCHECK_DNS_RESOLVER_URL` is set.
+pub const DEFAULT_DNS_RESOLVER_URL: &str = "https://cloudflare-dns.com/dns-query";
+
+/// TXT lookups give up after this long.
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// DNS record type number for TXT.
+const TXT_TYPE: u16 = 16;
+
+/// DNS response code for a name that doesn't exist.
+const NXDOMAIN: u32 = 3;
+
+/// Where the vendor publishes the verification token for `hostname`.
+pub fn challenge_name(hostname: &str) -> String {
+    format!("{}.{}", CHALLENGE_LABEL, hostname)
+}
+
+/// Generate a verification token: `pcv_` followed by 128 random bits in hex.
+/// It ends up in public DNS, so it's no secret; it only proves control.
+pub fn generate_verification_token() -> String {
+    use rand::RngCore;
+    use rand::rngs::OsRng;
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    format!("pcv_{}", hex::encode(bytes))
+}
+
+/// A hostname in the form custom domains are stored: lowercase, without a
+/// trailing dot. None unless it's a domain name with at least two labels of
+/// letters, digits, and hyphens (IP addresses and `localhost` don't qualify).
+pub fn normalize_hostname(hostname: &str) -> Option<String> {
+    let hostname = hostname.trim().trim_end_matches('.').to_ascii_lowercase();
+    let labels: Vec<&str> = hostname.split('.').collect();
+    let valid = hostname.len() <= 253
+        && labels.len() >= 2
+        && labels.iter().all(|label| {
+            (1..=63).contains(&label.len())
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+        // The last label of a domain name is never all digits
+        && !labels[labels.len() - 1].chars().all(|c| c.is_ascii_digit());
+    valid.then_some(hostname)
+}
+
+/// The hostname a request was sent to, from the `Host` header (or the URI
+/// authority, for HTTP/2), without the port.
+pub fn request_hostname(headers: &HeaderMap, uri: &Uri) -> Option<String> {
+    let host = match headers.get(header::HOST) {
+        Some(value) => value.to_str().ok()?,
+        None => uri.authority()?.as_str(),
+    };
+    // Bracketed IPv6 literals never name a custom domain
+    let host = host.rsplit_once(':').map_or(host, |(name, _port)| name);
+    normalize_hostname(host)
+}
+
+/// Looks up TXT records over DNS-over-HTTPS.
+#[derive(Clone)]
+pub struct DnsResolver {
+    client: Client,
+    endpoint: String,
+}
+
+#[derive(Deserialize)]
+struct DohResponse {
+    #[serde(rename = "Status")]
+    status: u32,
+    #[serde(rename = "Answer", default)]
+    answer: Vec<DohAnswer>,
+}
+
+#[derive(Deserialize)]
+struct DohAnswer {
+    #[serde(rename = "type")]
+    record_type: u16,
+    data: String,
+}
+
+impl DnsResolver {
+    /// Resolver using the DNS-over-HTTPS JSON API at `endpoint`.
+    pub fn new(endpoint: &str) -> Self {
+        Self {
+            client: Client::builder()
+                .timeout(LOOKUP_TIMEOUT)
+                .build()
+                .expect("Failed to create HTTP client"),
+            endpoint: endpoint.to_string(),
+        }
+    }
+
+    /// The TXT records at `name`, each as one string. A name that doesn't
+    /// exist has none; a failed lookup is an error describing why.
+    pub async fn txt_records(&self, name: &str) -> Result<Vec<String>, String> {
+        let response = self
+            .client
+            .get(&self.endpoint)
+            .query(&[("name", name), ("type", "TXT")])
+            .header(header::ACCEPT, "application/dns-json")
+            .send()
+            .await
+            .map_err(|e| format!("resolver unreachable: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!("resolver returned {}", response.status()));
+        }
+        let body: DohResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("unreadable resolver response: {}", e))?;
+        match body.status {
+            0 => Ok(body
+                .answer
+                .into_iter()
+                .filter(|answer| answer.record_type == TXT_TYPE)
+                .map(|answer| txt_value(&answer.data))
+                .collect()),
+            NXDOMAIN => Ok(Vec::new()),
+            code => Err(format!("DNS lookup failed with response code {}", code)),
+        }
+    }
+}
+
+/// A TXT record's value from its presentation form: the quoted character
+/// strings (long records are split into several) joined together.
+fn txt_value(data: &str) -> String {
+    let data = data.trim();
+    if !data.starts_with('"') {
+        return data.to_string();
+    }
+    let mut value = String::new();
+    let mut in_quotes = false;
+    let mut chars = data.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '\\' if in_quotes => value.extend(chars.next()),
+            c if in_quotes => value.push(c),
+            _ => {}
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalizes_hostnames() {
+        assert_eq!(
+            normalize_hostname(" Licenses.Example.COM. ").as_deref(),
+            Some("licenses.example.com")
+        );
+        for invalid in [
+            "localhost",
+            "127.0.0.1",
+            "example..com",
+            "-bad.example.com",
+            "under_score.example.com",
+            "https://example.com",
+            "",
+        ] {
+            assert_eq!(normalize_hostname(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_request_hostname_drops_the_port() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, "Licenses.Example.com:8443".parse().unwrap());
+        assert_eq!(
+            request_hostname(&headers, &Uri::from_static("/validate")).as_deref(),
+            Some("licenses.example.com")
+        );
+
+        let uri: Uri = "https://licenses.example.com/validate".parse().unwrap();
+        assert_eq!(
+            request_hostname(&HeaderMap::new(), &uri).as_deref(),
+            Some("licenses.example.com")
+        );
+    }
+
+    #[test]
+    fn test_txt_value_joins_quoted_strings() {
+        assert_eq!(txt_value("\"pcv_abc\""), "pcv_abc");
+        assert_eq!(txt_value("\"pcv_\" \"abc\""), "pcv_abc");
+        assert_eq!(txt_value("\"say \\\"hi\\\"\""), "say \"hi\"");
+        assert_eq!(txt_value("pcv_abc"), "pcv_abc");
+    }
+}
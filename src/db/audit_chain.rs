@@ -0,0 +1,455 @@
+//! Hash chaining for audit log entries.
+//!
+//! Every entry stores `prev_hash` and `row_hash`, where
+//! `row_hash = SHA-256(prev_hash || canonical row)` and the canonical row is a
+//! JSON array of the hashed columns as stored. Editing an entry breaks its own
+//! hash; deleting one breaks the link from the entry after it.
+//!
+//! There are two chains, numbered by `chain_seq` in insertion order:
+//! - `public`: end-user actions, purged after the instance's or org's retention
+//! - `internal`: everything else, kept forever unless an org sets its own
+//!   retention
+//!
+//! A purge stores the hash of the last entry it removed from the front of a
+//! chain in `audit_chain_anchors`, and the next remaining entry links to that
+//! anchor. Orgs keep their entries for different lengths of time, so purges
+//! also remove entries from the middle of a chain; each leaves a tombstone in
+//! `audit_chain_tombstones` with its `row_hash` for the entry after it to link
+//! to. Tombstones at the front of a chain fold into its anchor.
+//!
+//! Appends read the chain head and insert under one write lock, so chains
+//! never fork; a unique index on `(chain, chain_seq)` rejects any that would.
+//!
+//! Chaining can't reveal removal of the newest entries on its own. Record the
+//! reported heads somewhere the database admin can't edit to catch that.
+
+use chrono::Utc;
+use rusqlite::types::Value;
+use rusqlite::{Connection, OptionalExtension, params, params_from_iter};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::error::Result;
+use crate::models::{ActorType, AuditLog};
+
+pub const PUBLIC_CHAIN: &str = "public";
+pub const INTERNAL_CHAIN: &str = "internal";
+pub const CHAINS: [&str; 2] = [INTERNAL_CHAIN, PUBLIC_CHAIN];
+
+/// `prev_hash` of the first entry in a chain.
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Columns covered by `row_hash`, in canonical order.
+const HASHED_COLS: &str = "chain, chain_seq, id, timestamp, actor_type, user_id, user_email, user_name, action, resource_type, resource_id, resource_name, resource_email, details, org_id, org_name, project_id, project_name, ip_address, user_agent, auth_type, auth_credential, request_id";
+const HASHED_COL_COUNT: usize = 23;
+
+/// Which chain an entry belongs to.
+pub fn chain_for(actor_type: ActorType) -> &'static str {
+    match actor_type {
+        ActorType::Public => PUBLIC_CHAIN,
+        ActorType::User | ActorType::System => INTERNAL_CHAIN,
+    }
+}
+
+/// Hex `row_hash` for an entry with the given hashed column values.
+pub fn row_hash(prev_hash: &str, values: &[Value]) -> String {
+    let canonical: Vec<serde_json::Value> = values
+        .iter()
+        .map(|v| match v {
+            Value::Null => serde_json::Value::Null,
+            Value::Integer(i) => (*i).into(),
+            Value::Real(f) => (*f).into(),
+            Value::Text(s) => s.clone().into(),
+            Value::Blob(b) => hex::encode(b).into(),
+        })
+        .collect();
+
+    let mut hasher = Sha256::new();
+    hasher.update(prev_hash.as_bytes());
+    hasher.update(serde_json::Value::Array(canonical).to_string().as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+/// Last `(chain_seq, row_hash)` of `chain`: the newest entry or tombstone,
+/// else the purge anchor, else the genesis hash at 0.
+pub fn head(conn: &Connection, chain: &str) -> Result<(i64, String)> {
+    let newest = conn
+        .query_row(
+            "SELECT chain_seq, row_hash FROM audit_logs WHERE chain = ?1
+             UNION ALL
+             SELECT chain_seq, row_hash FROM audit_chain_tombstones WHERE chain = ?1
+             ORDER BY chain_seq DESC LIMIT 1",
+            [chain],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    if let Some(head) = newest {
+        return Ok(head);
+    }
+    Ok(anchor(conn, chain)?.unwrap_or((0, GENESIS_HASH.to_string())))
+}
+
+fn anchor(conn: &Connection, chain: &str) -> Result<Option<(i64, String)>> {
+    Ok(conn
+        .query_row(
+            "SELECT chain_seq, row_hash FROM audit_chain_anchors WHERE chain = ?1",
+            [chain],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?)
+}
+
+/// Insert `log` at the head of its chain. The caller must hold the audit
+/// database's write lock (see `queries::insert_audit_log`).
+pub fn append(conn: &Connection, log: &AuditLog) -> Result<()> {
+    let chain = chain_for(log.actor_type);
+    let (prev_seq, prev_hash) = head(conn, chain)?;
+
+    let opt = |v: &Option<String>| v.clone().map_or(Value::Null, Value::Text);
+    let mut values = vec![
+        Value::Text(chain.to_string()),
+        Value::Integer(prev_seq + 1),
+        Value::Text(log.id.clone()),
+        Value::Integer(log.timestamp),
+        Value::Text(log.actor_type.as_ref().to_string()),
+        opt(&log.user_id),
+        opt(&log.user_email),
+        opt(&log.user_name),
+        Value::Text(log.action.clone()),
+        Value::Text(log.resource_type.clone()),
+        Value::Text(log.resource_id.clone()),
+        opt(&log.resource_name),
+        opt(&log.resource_email),
+        opt(&log.details.as_ref().map(|d| d.to_string())),
+        opt(&log.org_id),
+        opt(&log.org_name),
+        opt(&log.project_id),
+        opt(&log.project_name),
+        opt(&log.ip_address),
+        opt(&log.user_agent),
+        opt(&log.auth_type),
+        opt(&log.auth_credential),
+        opt(&log.request_id),
+    ];
+    debug_assert_eq!(values.len(), HASHED_COL_COUNT);
+
+    let hash = row_hash(&prev_hash, &values);
+    values.push(Value::Text(prev_hash));
+    values.push(Value::Text(hash));
+
+    let placeholders: Vec<String> = (1..=values.len()).map(|i| format!("?{}", i)).collect();
+    conn.execute(
+        &format!(
+            "INSERT INTO audit_logs ({}, prev_hash, row_hash) VALUES ({})",
+            HASHED_COLS,
+            placeholders.join(", ")
+        ),
+        params_from_iter(values),
+    )?;
+    Ok(())
+}
+
+/// Delete the entries of `chain` matching `condition`, an SQL expression over
+/// `audit_logs` with `?` placeholders for `params`. Each deleted entry leaves
+/// a tombstone, and tombstones at the front of the chain then fold into its
+/// anchor. The caller must hold the write lock. Returns the number deleted.
+pub fn remove_where(
+    conn: &Connection,
+    chain: &str,
+    condition: &str,
+    params: &[Value],
+) -> Result<usize> {
+    let mut bound = vec![Value::Text(chain.to_string())];
+    bound.extend_from_slice(params);
+
+    conn.execute(
+        &format!(
+            "INSERT OR REPLACE INTO audit_chain_tombstones (chain, chain_seq, row_hash)
+             SELECT chain, chain_seq, row_hash FROM audit_logs WHERE chain = ? AND ({})",
+            condition
+        ),
+        params_from_iter(&bound),
+    )?;
+    let deleted = conn.execute(
+        &format!("DELETE FROM audit_logs WHERE chain = ? AND ({})", condition),
+        params_from_iter(&bound),
+    )?;
+    if deleted > 0 {
+        fold_tombstones(conn, chain)?;
+    }
+    Ok(deleted)
+}
+
+/// Move the anchor of `chain` past the tombstones in front of its oldest
+/// remaining entry, and drop those tombstones. Leaves the anchor alone if one
+/// of them is missing, so the verifier still reports the gap.
+fn fold_tombstones(conn: &Connection, chain: &str) -> Result<()> {
+    let from = anchor(conn, chain)?.map_or(0, |(seq, _)| seq) + 1;
+    let oldest: Option<i64> = conn.query_row(
+        "SELECT MIN(chain_seq) FROM audit_logs WHERE chain = ?1",
+        [chain],
+        |row| row.get(0),
+    )?;
+    let through: Option<i64> = match oldest {
+        Some(seq) => Some(seq - 1),
+        None => conn.query_row(
+            "SELECT MAX(chain_seq) FROM audit_chain_tombstones WHERE chain = ?1",
+            [chain],
+            |row| row.get(0),
+        )?,
+    };
+    let Some(through) = through.filter(|through| *through >= from) else {
+        return Ok(());
+    };
+    let Some(anchor_hash) = tombstoned_through(conn, chain, from, through)? else {
+        return Ok(());
+    };
+
+    conn.execute(
+        "INSERT INTO audit_chain_anchors (chain, chain_seq, row_hash, anchored_at)
+         VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(chain) DO UPDATE SET
+             chain_seq = excluded.chain_seq,
+             row_hash = excluded.row_hash,
+             anchored_at = excluded.anchored_at",
+        params![chain, through, anchor_hash, Utc::now().timestamp()],
+    )?;
+    conn.execute(
+        "DELETE FROM audit_chain_tombstones WHERE chain = ?1 AND chain_seq <= ?2",
+        params![chain, through],
+    )?;
+    Ok(())
+}
+
+/// `row_hash` of the tombstone at `to`, if every entry of `chain` from `from`
+/// to `to` has one.
+fn tombstoned_through(
+    conn: &Connection,
+    chain: &str,
+    from: i64,
+    to: i64,
+) -> Result<Option<String>> {
+    let (count, hash): (i64, Option<String>) = conn.query_row(
+        "SELECT COUNT(*), MAX(CASE WHEN chain_seq = ?3 THEN row_hash END)
+         FROM audit_chain_tombstones WHERE chain = ?1 AND chain_seq BETWEEN ?2 AND ?3",
+        params![chain, from, to],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    Ok(hash.filter(|_| count == to - from + 1))
+}
+
+/// Set `chain`, `chain_seq`, `prev_hash`, and `row_hash` on entries written
+/// before chaining, oldest first. Used by the audit database migration.
+pub fn backfill(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE audit_logs SET chain = CASE actor_type WHEN 'public' THEN ?1 ELSE ?2 END
+         WHERE chain IS NULL",
+        params![PUBLIC_CHAIN, INTERNAL_CHAIN],
+    )?;
+
+    for chain in CHAINS {
+        let ids: Vec<String> = conn
+            .prepare(
+                "SELECT id FROM audit_logs WHERE chain = ?1 AND row_hash IS NULL
+                 ORDER BY timestamp, rowid",
+            )?
+            .query_map([chain], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let (mut seq, mut prev_hash) = match head_hashed(conn, chain)? {
+            Some(head) => head,
+            None => (0, GENESIS_HASH.to_string()),
+        };
+        for id in ids {
+            seq += 1;
+            conn.execute(
+                "UPDATE audit_logs SET chain_seq = ?1 WHERE id = ?2",
+                params![seq, &id],
+            )?;
+            let values = hashed_values(conn, &id)?;
+            let hash = row_hash(&prev_hash, &values);
+            conn.execute(
+                "UPDATE audit_logs SET prev_hash = ?1, row_hash = ?2 WHERE id = ?3",
+                params![&prev_hash, &hash, &id],
+            )?;
+            prev_hash = hash;
+        }
+    }
+    Ok(())
+}
+
+fn head_hashed(conn: &Connection, chain: &str) -> rusqlite::Result<Option<(i64, String)>> {
+    conn.query_row(
+        "SELECT chain_seq, row_hash FROM audit_logs
+         WHERE chain = ?1 AND row_hash IS NOT NULL ORDER BY chain_seq DESC LIMIT 1",
+        [chain],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()
+}
+
+fn hashed_values(conn: &Connection, id: &str) -> rusqlite::Result<Vec<Value>> {
+    conn.query_row(
+        &format!("SELECT {} FROM audit_logs WHERE id = ?1", HASHED_COLS),
+        [id],
+        |row| (0..HASHED_COL_COUNT).map(|i| row.get(i)).collect(),
+    )
+}
+
+/// Result of re-walking the chains.
+#[derive(Debug, Serialize)]
+pub struct ChainVerification {
+    pub valid: bool,
+    pub chains: Vec<ChainReport>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChainReport {
+    pub chain: &'static str,
+    pub valid: bool,
+    /// Entries whose hashes were checked
+    pub checked: u64,
+    /// The chain's current head, for comparing against an earlier record
+    pub head_seq: i64,
+    pub head_hash: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_break: Option<ChainBreak>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChainBreak {
+    pub chain_seq: i64,
+    /// The entry at `chain_seq`, unless it's missing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entry_id: Option<String>,
+    pub reason: String,
+}
+
+/// Re-walk both chains over the entries with timestamps in `[from, to]`
+/// (unbounded when omitted), stopping each at its first break. Every entry
+/// between the first and last in range is checked, whatever its timestamp.
+pub fn verify(conn: &Connection, from: Option<i64>, to: Option<i64>) -> Result<ChainVerification> {
+    let chains = CHAINS
+        .into_iter()
+        .map(|chain| verify_chain(conn, chain, from, to))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(ChainVerification {
+        valid: chains.iter().all(|c| c.valid),
+        chains,
+    })
+}
+
+fn verify_chain(
+    conn: &Connection,
+    chain: &'static str,
+    from: Option<i64>,
+    to: Option<i64>,
+) -> Result<ChainReport> {
+    let (head_seq, head_hash) = head(conn, chain)?;
+    let mut report = ChainReport {
+        chain,
+        valid: true,
+        checked: 0,
+        head_seq,
+        head_hash,
+        first_break: None,
+    };
+
+    let (start, end): (Option<i64>, Option<i64>) = conn.query_row(
+        "SELECT
+             (SELECT MIN(chain_seq) FROM audit_logs WHERE chain = ?1 AND timestamp >= ?2),
+             (SELECT MAX(chain_seq) FROM audit_logs WHERE chain = ?1 AND timestamp <= ?3)",
+        params![chain, from.unwrap_or(i64::MIN), to.unwrap_or(i64::MAX)],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    let (Some(start), Some(end)) = (start, end) else {
+        return Ok(report);
+    };
+    if start > end {
+        return Ok(report);
+    }
+
+    // What the first entry in range must link to
+    let predecessor = start - 1;
+    let linked: Option<String> = if predecessor == 0 {
+        Some(GENESIS_HASH.to_string())
+    } else {
+        let stored = conn
+            .query_row(
+                "SELECT row_hash FROM audit_logs WHERE chain = ?1 AND chain_seq = ?2",
+                params![chain, predecessor],
+                |row| row.get(0),
+            )
+            .optional()?;
+        match stored {
+            Some(hash) => Some(hash),
+            None => match tombstoned_through(conn, chain, predecessor, predecessor)? {
+                Some(hash) => Some(hash),
+                None => anchor(conn, chain)?
+                    .filter(|(seq, _)| *seq == predecessor)
+                    .map(|(_, hash)| hash),
+            },
+        }
+    };
+    let Some(mut expected_prev) = linked else {
+        report.valid = false;
+        report.first_break = Some(ChainBreak {
+            chain_seq: predecessor,
+            entry_id: None,
+            reason: "Entry is missing and the chain has no anchor for it".into(),
+        });
+        return Ok(report);
+    };
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}, prev_hash, row_hash FROM audit_logs
+         WHERE chain = ?1 AND chain_seq >= ?2 AND chain_seq <= ?3
+         ORDER BY chain_seq",
+        HASHED_COLS
+    ))?;
+    let mut rows = stmt.query(params![chain, start, end])?;
+    let mut expected_seq = start;
+
+    while let Some(row) = rows.next()? {
+        let values: Vec<Value> = (0..HASHED_COL_COUNT)
+            .map(|i| row.get(i))
+            .collect::<rusqlite::Result<_>>()?;
+        let seq: i64 = row.get(1)?;
+        let id: String = row.get(2)?;
+        let prev_hash: Option<String> = row.get(HASHED_COL_COUNT)?;
+        let stored_hash: Option<String> = row.get(HASHED_COL_COUNT + 1)?;
+
+        // Purged entries in between leave tombstones to link to instead
+        if seq > expected_seq
+            && let Some(hash) = tombstoned_through(conn, chain, expected_seq, seq - 1)?
+        {
+            expected_prev = hash;
+            expected_seq = seq;
+        }
+
+        let broken = if seq != expected_seq {
+            Some((expected_seq, None, "Entry is missing"))
+        } else if prev_hash.as_deref() != Some(expected_prev.as_str()) {
+            Some((seq, Some(id), "prev_hash does not match the previous entry"))
+        } else if stored_hash.as_deref() != Some(row_hash(&expected_prev, &values).as_str()) {
+            Some((seq, Some(id), "Entry does not match its row_hash"))
+        } else {
+            None
+        };
+        if let Some((chain_seq, entry_id, reason)) = broken {
+            report.valid = false;
+            report.first_break = Some(ChainBreak {
+                chain_seq,
+                entry_id,
+                reason: reason.into(),
+            });
+            return Ok(report);
+        }
+
+        report.checked += 1;
+        expected_prev = stored_hash.unwrap_or_default();
+        expected_seq += 1;
+    }
+
+    Ok(report)
+}
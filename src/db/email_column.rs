@@ -0,0 +1,166 @@
+//! Storage format for user email addresses.
+//!
+//! With `PII_MINIMIZATION=true`, the `users.email` column holds the address
+//! encrypted under the master key (`enc1:` + base64 envelope), and lookups go
+//! through the deterministic `users.email_hash` column instead. Without it,
+//! emails are stored in plaintext as before. The hash column is written in
+//! both modes so switching modes (or running `--encrypt-user-emails`) never
+//! needs a rehash.
+//!
+//! Queries that read or write user emails take an [`EmailColumn`] so callers
+//! always get plaintext back regardless of the storage format.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+
+use crate::crypto::{EmailHasher, MasterKey};
+use crate::error::{AppError, Result};
+use crate::models::{
+    EmailAddress, OrgMemberWithUser, ProjectMemberWithDetails, User, UserWithRoles,
+};
+
+use super::queries::OrgModifier;
+
+/// Prefix marking an encrypted email value.
+const SEALED_PREFIX: &str = "enc1:";
+
+/// HKDF context for email encryption (shared by every user row).
+const EMAIL_KEY_CONTEXT: &str = "user-email";
+
+/// Encrypts, decrypts, and hashes values of the `users.email` column.
+#[derive(Clone)]
+pub struct EmailColumn {
+    master_key: MasterKey,
+    hasher: EmailHasher,
+    minimized: bool,
+}
+
+impl EmailColumn {
+    pub fn new(master_key: MasterKey, hasher: EmailHasher, minimized: bool) -> Self {
+        Self {
+            master_key,
+            hasher,
+            minimized,
+        }
+    }
+
+    /// Whether new emails are stored encrypted.
+    pub fn is_minimized(&self) -> bool {
+        self.minimized
+    }
+
+    /// Deterministic lookup hash for the `email_hash` column.
+    pub fn hash(&self, email: &EmailAddress) -> String {
+        self.hasher.hash(email)
+    }
+
+    pub fn hasher(&self) -> &EmailHasher {
+        &self.hasher
+    }
+
+    /// Whether a stored value is encrypted.
+    pub fn is_sealed(stored: &str) -> bool {
+        stored.starts_with(SEALED_PREFIX)
+    }
+
+    /// Convert a (normalized) email to its stored form.
+    pub fn seal(&self, email: &str) -> Result<String> {
+        if !self.minimized {
+            return Ok(email.to_string());
+        }
+        self.encrypt(email)
+    }
+
+    /// Encrypt an email regardless of mode (for the migration command).
+    pub fn encrypt(&self, email: &str) -> Result<String> {
+        encrypt_email(&self.master_key, email)
+    }
+
+    /// Convert a stored value back to plaintext. Plaintext values pass through,
+    /// so rows written before minimization was enabled still read correctly.
+    pub fn reveal(&self, stored: &str) -> Result<String> {
+        if !Self::is_sealed(stored) {
+            return Ok(stored.to_string());
+        }
+        decrypt_email(&self.master_key, stored)
+    }
+
+    /// Re-encrypt a sealed value under a new master key (key rotation).
+    pub fn rotate(stored: &str, old_key: &MasterKey, new_key: &MasterKey) -> Result<String> {
+        encrypt_email(new_key, &decrypt_email(old_key, stored)?)
+    }
+
+    /// Decrypt the email of a loaded row in place.
+    pub fn reveal_in<T: HasEmail>(&self, item: &mut T) -> Result<()> {
+        let email = item.email_mut();
+        if Self::is_sealed(email) {
+            *email = self.reveal(email)?;
+        }
+        Ok(())
+    }
+
+    pub fn reveal_opt<T: HasEmail>(&self, item: Option<T>) -> Result<Option<T>> {
+        item.map(|mut item| {
+            self.reveal_in(&mut item)?;
+            Ok(item)
+        })
+        .transpose()
+    }
+
+    pub fn reveal_all<T: HasEmail>(&self, mut items: Vec<T>) -> Result<Vec<T>> {
+        for item in &mut items {
+            self.reveal_in(item)?;
+        }
+        Ok(items)
+    }
+}
+
+fn encrypt_email(key: &MasterKey, email: &str) -> Result<String> {
+    let encrypted = key.encrypt_private_key(EMAIL_KEY_CONTEXT, email.as_bytes())?;
+    Ok(format!("{}{}", SEALED_PREFIX, BASE64.encode(encrypted)))
+}
+
+fn decrypt_email(key: &MasterKey, stored: &str) -> Result<String> {
+    let encoded = stored.strip_prefix(SEALED_PREFIX).unwrap_or(stored);
+    let encrypted = BASE64
+        .decode(encoded)
+        .map_err(|e| AppError::Internal(format!("Invalid encrypted email: {}", e)))?;
+    let plaintext = key.decrypt_private_key(EMAIL_KEY_CONTEXT, &encrypted)?;
+    String::from_utf8(plaintext)
+        .map_err(|e| AppError::Internal(format!("Invalid encrypted email: {}", e)))
+}
+
+/// Rows carrying a user email read from the `users.email` column.
+pub trait HasEmail {
+    fn email_mut(&mut self) -> &mut String;
+}
+
+impl HasEmail for User {
+    fn email_mut(&mut self) -> &mut String {
+        &mut self.email
+    }
+}
+
+impl HasEmail for UserWithRoles {
+    fn email_mut(&mut self) -> &mut String {
+        &mut self.email
+    }
+}
+
+impl HasEmail for OrgMemberWithUser {
+    fn email_mut(&mut self) -> &mut String {
+        &mut self.email
+    }
+}
+
+impl HasEmail for ProjectMemberWithDetails {
+    fn email_mut(&mut self) -> &mut String {
+        &mut self.email
+    }
+}
+
+impl HasEmail for OrgModifier {
+    fn email_mut(&mut self) -> &mut String {
+        &mut self.email
+    }
+}
@@ -18,6 +18,35 @@ fn parse_enum<T: std::str::FromStr>(row: &Row, col: usize, col_name: &str) -> ru
     })
 }
 
+/// Like [`parse_enum`], for a nullable column.
+fn parse_optional_enum<T: std::str::FromStr>(
+    row: &Row,
+    col: usize,
+    col_name: &str,
+) -> rusqlite::Result<Option<T>> {
+    row.get::<_, Option<String>>(col)?
+        .map(|s| s.parse::<T>())
+        .transpose()
+        .map_err(|_| {
+            rusqlite::Error::InvalidColumnType(
+                col,
+                col_name.to_string(),
+                rusqlite::types::Type::Text,
+            )
+        })
+}
+
+/// Read a nullable JSON object of checkout field values (NULL = none submitted).
+fn checkout_values(
+    row: &Row,
+    col: usize,
+) -> rusqlite::Result<std::collections::BTreeMap<String, String>> {
+    Ok(row
+        .get::<_, Option<String>>(col)?
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default())
+}
+
 /// Trait for constructing a type from a database row.
 ///
 /// Implementing this trait allows using the `query_one` and `query_all`
@@ -53,42 +82,114 @@ pub fn query_all<T: FromRow>(
 
 // ============ SQL SELECT Constants ============
 
-pub const USER_COLS: &str =
-    "id, email, name, operator_role, created_at, updated_at, deleted_at, deleted_cascade_depth";
+pub const USER_COLS: &str = "id, email, name, operator_role, created_at, updated_at, deleted_at, deleted_cascade_depth, merged_into";
+
+pub const ORGANIZATION_COLS: &str = "id, name, payment_provider, created_at, updated_at, deleted_at, deleted_cascade_depth, email_from, public_audit_retention_days, admin_audit_retention_days, version";
 
-pub const ORGANIZATION_COLS: &str =
-    "id, name, payment_provider, created_at, updated_at, deleted_at, deleted_cascade_depth";
+/// `ORGANIZATION_COLS` plus `OrgStats` from the org's tenant tables, for
+/// queries over `organizations`. Audit activity lives in another database and
+/// is added by the caller.
+pub const ORGANIZATION_WITH_STATS_COLS: &str = "id, name, payment_provider, created_at, updated_at, deleted_at, deleted_cascade_depth, email_from, public_audit_retention_days, admin_audit_retention_days, version,
+    (SELECT COUNT(*) FROM projects p WHERE p.org_id = organizations.id AND p.deleted_at IS NULL),
+    (SELECT COUNT(*) FROM licenses l JOIN projects p ON l.project_id = p.id
+     WHERE p.org_id = organizations.id AND p.deleted_at IS NULL AND l.deleted_at IS NULL),
+    (SELECT COUNT(*) FROM org_members m WHERE m.org_id = organizations.id AND m.deleted_at IS NULL),
+    (SELECT MAX(l.created_at) FROM licenses l JOIN projects p ON l.project_id = p.id
+     WHERE p.org_id = organizations.id),
+    (SELECT COUNT(*) FROM licenses l JOIN projects p ON l.project_id = p.id
+     WHERE p.org_id = organizations.id AND p.deleted_at IS NULL AND l.deleted_at IS NULL
+       AND l.dormant_since IS NOT NULL)";
 
 pub const ORG_SERVICE_CONFIG_COLS: &str =
     "id, org_id, category, provider, config_encrypted, created_at, updated_at";
 
-pub const ORG_MEMBER_COLS: &str =
-    "id, user_id, org_id, role, created_at, updated_at, deleted_at, deleted_cascade_depth";
+pub const ORG_LIMIT_COLS: &str =
+    "org_id, limit_name, soft_value, hard_value, created_at, updated_at";
+
+pub const ORG_MEMBER_COLS: &str = "id, user_id, org_id, role, created_at, updated_at, deleted_at, deleted_cascade_depth, removal_scheduled_at";
 
-pub const ORG_MEMBER_WITH_USER_COLS: &str = "m.id, m.user_id, u.email, u.name, m.org_id, m.role, m.created_at, m.updated_at, m.deleted_at, m.deleted_cascade_depth";
+pub const ORG_MEMBER_WITH_USER_COLS: &str = "m.id, m.user_id, u.email, u.name, m.org_id, m.role, m.created_at, m.updated_at, m.deleted_at, m.deleted_cascade_depth, m.removal_scheduled_at";
 
 pub const API_KEY_COLS: &str = "id, user_id, name, key_prefix, key_hash, user_manageable, created_at, last_used_at, expires_at, revoked_at";
 
 pub const API_KEY_SCOPE_COLS: &str = "api_key_id, org_id, project_id, access";
 
-pub const PROJECT_COLS: &str = "id, org_id, name, license_key_prefix, private_key, public_key, redirect_url, email_from, email_enabled, email_webhook_url, created_at, updated_at, deleted_at, deleted_cascade_depth";
+pub const OPERATOR_ORG_SCOPE_COLS: &str = "operator_id, org_id, created_at";
+
+pub const PROJECT_COLS: &str = "id, org_id, name, license_key_prefix, private_key, public_key, redirect_url, email_from, email_enabled, email_webhook_url, created_at, updated_at, deleted_at, deleted_cascade_depth, jwt_issuer, jwt_audience, jwt_previous_issuer, jwt_previous_audience, jwt_previous_until, upgrade_auto_discount, upgrade_old_license, allow_project_id_auth, allow_link_checkout, max_validations_per_hour_per_license, statement_descriptor_suffix, receipt_email_enabled, checkout_message, attestation_audiences, duplicate_purchase_check, converted_trial_action, webhook_mirror_url, webhook_mirror_expires_at, require_terms_acceptance, client_flags, min_client_version, checkout_metadata_keys, usage_geo_enabled, dormant_after_days, dormant_nudge_email, version, webhook_schema_version, email_webhook_secret";
 
 pub const PROJECT_MEMBER_COLS: &str = "id, org_member_id, project_id, role, created_at, updated_at, deleted_at, deleted_cascade_depth";
 
-pub const PRODUCT_COLS: &str = "id, project_id, name, tier, license_exp_days, updates_exp_days, activation_limit, device_limit, device_inactive_days, features, price_cents, currency, created_at, deleted_at, deleted_cascade_depth";
+/// Joined with org_members (aliased `om`) for the grantee's user_id
+pub const TEMPORARY_ROLE_GRANT_COLS: &str = "g.id, g.org_member_id, om.user_id, g.project_id, g.role, g.reason, g.granted_by, g.created_at, g.expires_at, g.revoked_at, g.revoked_by";
+
+pub const PRODUCT_COLS: &str = "id, project_id, name, tier, license_exp_days, updates_exp_days, activation_limit, device_limit, device_inactive_days, features, price_cents, currency, created_at, deleted_at, deleted_cascade_depth, seat_count, available_from, available_until, checkout_fields, extends_updates_for_product_id, renewal_fallback, upgrade_to_product_id, upsell_highlight_features, upsell_blurb, entitlements, version, revoke_on_partial_refund";
+
+pub const PRODUCT_TEMPLATE_COLS: &str = "id, org_id, name, tier, license_exp_days, updates_exp_days, activation_limit, device_limit, device_inactive_days, features, price_cents, currency, seat_count, checkout_fields, created_at, updated_at";
 
 pub const PROVIDER_LINK_COLS: &str = "id, product_id, provider, linked_id, created_at, updated_at";
 
 /// Columns for licenses table (no encryption - email_hash instead of key)
-pub const LICENSE_COLS: &str = "id, email_hash, project_id, product_id, customer_id, activation_count, revoked, created_at, expires_at, updates_expires_at, payment_provider, payment_provider_customer_id, payment_provider_subscription_id, payment_provider_order_id, deleted_at, deleted_cascade_depth";
+pub const LICENSE_COLS: &str = "id, email_hash, project_id, product_id, customer_id, activation_count, revoked, created_at, expires_at, updates_expires_at, payment_provider, payment_provider_customer_id, payment_provider_subscription_id, payment_provider_order_id, deleted_at, deleted_cascade_depth, paused_at, paused_seconds, seats, abuse_flags, abuse_flagged_at, abuse_distinct_ips, revoked_reason, revoked_message, revoked_at, revoked_by, checkout_fields, suspended_for_dispute, is_trial, converted_from_license_id, accepted_terms_version, license_ref, metadata, dormant_since, reference_number, first_seen_version, last_seen_version, last_seen_version_at";
+
+/// Number of columns in [`LICENSE_COLS`], the index of the first column a
+/// query selects after them
+pub const LICENSE_COL_COUNT: usize = 38;
 
 pub const DEVICE_COLS: &str =
-    "id, license_id, device_id, device_type, name, jti, activated_at, last_seen_at";
+    "id, license_id, device_id, device_type, name, jti, activated_at, last_seen_at, seat_id, signed_with_kid";
 
 pub const PAYMENT_SESSION_COLS: &str =
-    "id, product_id, customer_id, created_at, completed, license_id";
+    "id, product_id, customer_id, created_at, completed, license_id, upgrade_from_license_id, upgrade_days_remaining, upgrade_credit_cents, checkout_fields, checkout_url, needs_review, claimed_at, accepted_terms_version, metadata";
+
+pub const ACTIVATION_CODE_COLS: &str =
+    "code_hash, license_id, expires_at, used, created_at, seat_id";
+
+pub const LICENSE_SEAT_COLS: &str = "id, license_id, seat_email_hash, assigned_at, removed_at";
+
+pub const SHARE_LINK_COLS: &str =
+    "id, license_id, token_hash, masked_email, created_by, expires_at, revoked, created_at";
+
+/// Includes the number of codes redeemed so far
+pub const PREPAID_CODE_BATCH_COLS: &str = "id, project_id, product_id, label, code_count,
+    (SELECT COUNT(*) FROM prepaid_codes c WHERE c.batch_id = prepaid_code_batches.id AND c.redeemed_at IS NOT NULL),
+    created_by, expires_at, downloaded_at, revoked_at, created_at";
+
+pub const BULK_JOB_COLS: &str = "id, project_id, product_id, status, requested, created_count, activation_codes, params, created_by, error, created_at, finished_at";
+
+pub const BULK_JOB_CHUNK_COLS: &str = "chunk, license_ids, error, created_at";
+
+pub const TERMS_COLS: &str = "id, project_id, version, url, effective_at, created_by, created_at";
+
+/// Includes the number of downloads across all licenses
+pub const ARTIFACT_COLS: &str = "id, project_id, name, released_at, url,
+    (SELECT COALESCE(SUM(d.count), 0) FROM artifact_downloads d WHERE d.artifact_id = artifacts.id),
+    created_by, created_at";
+
+pub const ARTIFACT_DOWNLOAD_COLS: &str =
+    "artifact_id, license_id, count, first_downloaded_at, last_downloaded_at";
+
+pub const LICENSE_UPGRADE_COLS: &str = "id, from_license_id, to_license_id, payment_session_id, days_remaining, credit_cents, old_license_action, created_at";
+
+pub const LICENSE_UPDATE_RENEWAL_COLS: &str = "id, license_id, product_id, payment_session_id, previous_updates_expires_at, updates_expires_at, created_at";
+
+pub const RECONCILIATION_RUN_COLS: &str = "id, org_id, provider, applied, started_by, started_at, finished_at, checked, report";
+
+pub const BACKUP_COLS: &str = "id, file_name, size_bytes, duration_ms, sha256, encrypted_with, created_by, created_at, verified_at, verified_ok";
+
+pub const STORAGE_HEARTBEAT_COLS: &str = "db, path, beat, started_at, beat_at";
+
+pub const CUSTOM_DOMAIN_COLS: &str =
+    "hostname, org_id, project_id, verification_token, verified_at, created_by, created_at";
+
+pub const DISPUTE_COLS: &str = "id, license_id, project_id, provider, provider_dispute_id, payment_id, amount_cents, currency, reason, status, created_at, closed_at";
 
-pub const ACTIVATION_CODE_COLS: &str = "code_hash, license_id, expires_at, used, created_at";
+pub const EMAIL_LOG_COLS: &str = "id, license_id, project_id, to_email_hash, email_trigger, result, error_status, provider_message_id, created_at";
+
+pub const AUDIT_LOG_COLS: &str = "id, timestamp, actor_type, user_id, user_email, user_name, action, resource_type, resource_id, resource_name, resource_email, details, org_id, org_name, project_id, project_name, ip_address, user_agent, auth_type, auth_credential, request_id";
+
+/// The audit log columns a project's activity feed shows
+pub const PROJECT_ACTIVITY_COLS: &str = "id, timestamp, actor_type, user_id, user_name, user_email, action, resource_type, resource_id, resource_name";
 
 // ============ FromRow Implementations ============
 
@@ -107,6 +208,7 @@ impl FromRow for User {
             updated_at: row.get(5)?,
             deleted_at: row.get(6)?,
             deleted_cascade_depth: row.get(7)?,
+            merged_into: row.get(8)?,
         })
     }
 }
@@ -121,10 +223,29 @@ impl FromRow for Organization {
             updated_at: row.get(4)?,
             deleted_at: row.get(5)?,
             deleted_cascade_depth: row.get(6)?,
+            email_from: row.get(7)?,
+            public_audit_retention_days: row.get(8)?,
+            admin_audit_retention_days: row.get(9)?,
+            version: row.get(10)?,
         })
     }
 }
 
+impl FromRow for (Organization, OrgStats) {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok((
+            Organization::from_row(row)?,
+            OrgStats {
+                project_count: row.get(11)?,
+                license_count: row.get(12)?,
+                member_count: row.get(13)?,
+                last_activity_at: row.get(14)?,
+                dormant_license_count: row.get(15)?,
+            },
+        ))
+    }
+}
+
 impl FromRow for OrgServiceConfig {
     fn from_row(row: &Row) -> rusqlite::Result<Self> {
         Ok(OrgServiceConfig {
@@ -139,6 +260,79 @@ impl FromRow for OrgServiceConfig {
     }
 }
 
+impl FromRow for OrgLimit {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(OrgLimit {
+            org_id: row.get(0)?,
+            limit_name: parse_enum(row, 1, "limit_name")?,
+            soft_value: row.get(2)?,
+            hard_value: row.get(3)?,
+            created_at: row.get(4)?,
+            updated_at: row.get(5)?,
+        })
+    }
+}
+
+impl FromRow for ReconciliationRun {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let report: String = row.get(8)?;
+        Ok(ReconciliationRun {
+            id: row.get(0)?,
+            org_id: row.get(1)?,
+            provider: row.get(2)?,
+            applied: row.get::<_, i32>(3)? != 0,
+            started_by: row.get(4)?,
+            started_at: row.get(5)?,
+            finished_at: row.get(6)?,
+            checked: row.get(7)?,
+            discrepancies: serde_json::from_str(&report).unwrap_or_default(),
+        })
+    }
+}
+
+impl FromRow for Backup {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Backup {
+            id: row.get(0)?,
+            file_name: row.get(1)?,
+            size_bytes: row.get(2)?,
+            duration_ms: row.get(3)?,
+            sha256: row.get(4)?,
+            encrypted_with: row.get(5)?,
+            created_by: row.get(6)?,
+            created_at: row.get(7)?,
+            verified_at: row.get(8)?,
+            verified_ok: row.get::<_, Option<i32>>(9)?.map(|ok| ok != 0),
+        })
+    }
+}
+
+impl FromRow for StorageHeartbeat {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(StorageHeartbeat {
+            db: row.get(0)?,
+            path: row.get(1)?,
+            beat: row.get(2)?,
+            started_at: row.get(3)?,
+            beat_at: row.get(4)?,
+        })
+    }
+}
+
+impl FromRow for CustomDomain {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(CustomDomain {
+            hostname: row.get(0)?,
+            org_id: row.get(1)?,
+            project_id: row.get(2)?,
+            verification_token: row.get(3)?,
+            verified_at: row.get(4)?,
+            created_by: row.get(5)?,
+            created_at: row.get(6)?,
+        })
+    }
+}
+
 impl FromRow for OrgMember {
     fn from_row(row: &Row) -> rusqlite::Result<Self> {
         Ok(OrgMember {
@@ -150,6 +344,7 @@ impl FromRow for OrgMember {
             updated_at: row.get(5)?,
             deleted_at: row.get(6)?,
             deleted_cascade_depth: row.get(7)?,
+            removal_scheduled_at: row.get(8)?,
         })
     }
 }
@@ -167,6 +362,7 @@ impl FromRow for OrgMemberWithUser {
             updated_at: row.get(7)?,
             deleted_at: row.get(8)?,
             deleted_cascade_depth: row.get(9)?,
+            removal_scheduled_at: row.get(10)?,
         })
     }
 }
@@ -199,8 +395,21 @@ impl FromRow for ApiKeyScope {
     }
 }
 
+impl FromRow for OperatorOrgScope {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(OperatorOrgScope {
+            operator_id: row.get(0)?,
+            org_id: row.get(1)?,
+            created_at: row.get(2)?,
+        })
+    }
+}
+
 impl FromRow for Project {
     fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let attestation_audiences_str: String = row.get(27)?;
+        let client_flags_str: String = row.get(33)?;
+        let checkout_metadata_keys_str: String = row.get(35)?;
         Ok(Project {
             id: row.get(0)?,
             org_id: row.get(1)?,
@@ -216,6 +425,36 @@ impl FromRow for Project {
             updated_at: row.get(11)?,
             deleted_at: row.get(12)?,
             deleted_cascade_depth: row.get(13)?,
+            jwt_issuer: row.get(14)?,
+            jwt_audience: row.get(15)?,
+            jwt_previous_issuer: row.get(16)?,
+            jwt_previous_audience: row.get(17)?,
+            jwt_previous_until: row.get(18)?,
+            upgrade_auto_discount: row.get::<_, i32>(19)? != 0,
+            upgrade_old_license: parse_enum(row, 20, "upgrade_old_license")?,
+            allow_project_id_auth: row.get::<_, i32>(21)? != 0,
+            allow_link_checkout: row.get::<_, i32>(22)? != 0,
+            max_validations_per_hour_per_license: row.get(23)?,
+            statement_descriptor_suffix: row.get(24)?,
+            receipt_email_enabled: row.get(25)?,
+            checkout_message: row.get(26)?,
+            attestation_audiences: serde_json::from_str(&attestation_audiences_str)
+                .unwrap_or_default(),
+            duplicate_purchase_check: row.get(28)?,
+            converted_trial_action: parse_enum(row, 29, "converted_trial_action")?,
+            webhook_mirror_url: row.get(30)?,
+            webhook_mirror_expires_at: row.get(31)?,
+            require_terms_acceptance: row.get::<_, i32>(32)? != 0,
+            client_flags: serde_json::from_str(&client_flags_str).unwrap_or_default(),
+            min_client_version: row.get(34)?,
+            checkout_metadata_keys: serde_json::from_str(&checkout_metadata_keys_str)
+                .unwrap_or_default(),
+            usage_geo_enabled: row.get::<_, i32>(36)? != 0,
+            dormant_after_days: row.get(37)?,
+            dormant_nudge_email: row.get::<_, i32>(38)? != 0,
+            version: row.get(39)?,
+            webhook_schema_version: parse_optional_enum(row, 40, "webhook_schema_version")?,
+            email_webhook_secret: row.get(41)?,
         })
     }
 }
@@ -235,6 +474,24 @@ impl FromRow for ProjectMember {
     }
 }
 
+impl FromRow for TemporaryRoleGrant {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(TemporaryRoleGrant {
+            id: row.get(0)?,
+            org_member_id: row.get(1)?,
+            user_id: row.get(2)?,
+            project_id: row.get(3)?,
+            role: parse_enum(row, 4, "role")?,
+            reason: row.get(5)?,
+            granted_by: row.get(6)?,
+            created_at: row.get(7)?,
+            expires_at: row.get(8)?,
+            revoked_at: row.get(9)?,
+            revoked_by: row.get(10)?,
+        })
+    }
+}
+
 impl FromRow for ProjectMemberWithDetails {
     fn from_row(row: &Row) -> rusqlite::Result<Self> {
         Ok(ProjectMemberWithDetails {
@@ -256,6 +513,18 @@ impl FromRow for ProjectMemberWithDetails {
 impl FromRow for Product {
     fn from_row(row: &Row) -> rusqlite::Result<Self> {
         let features_str: String = row.get(9)?;
+        let checkout_fields_str: String = row.get(18)?;
+        let upgrade_to_product_id: Option<String> = row.get(21)?;
+        let highlights_str: String = row.get(22)?;
+        let entitlements_str: String = row.get(24)?;
+        let upsell = match upgrade_to_product_id {
+            Some(upgrade_to_product_id) => Some(ProductUpsell {
+                upgrade_to_product_id,
+                highlight_features: serde_json::from_str(&highlights_str).unwrap_or_default(),
+                marketing_blurb: row.get(23)?,
+            }),
+            None => None,
+        };
         Ok(Product {
             id: row.get(0)?,
             project_id: row.get(1)?,
@@ -267,11 +536,21 @@ impl FromRow for Product {
             device_limit: row.get(7)?,
             device_inactive_days: row.get(8)?,
             features: serde_json::from_str(&features_str).unwrap_or_default(),
+            entitlements: serde_json::from_str(&entitlements_str).unwrap_or_default(),
             price_cents: row.get(10)?,
             currency: row.get(11)?,
             created_at: row.get(12)?,
             deleted_at: row.get(13)?,
             deleted_cascade_depth: row.get(14)?,
+            seat_count: row.get(15)?,
+            available_from: row.get(16)?,
+            available_until: row.get(17)?,
+            checkout_fields: serde_json::from_str(&checkout_fields_str).unwrap_or_default(),
+            extends_updates_for_product_id: row.get(19)?,
+            renewal_fallback: parse_enum(row, 20, "renewal_fallback")?,
+            upsell,
+            version: row.get(25)?,
+            revoke_on_partial_refund: row.get(26)?,
         })
     }
 }
@@ -308,6 +587,28 @@ impl FromRow for License {
             payment_provider_order_id: row.get(13)?,
             deleted_at: row.get(14)?,
             deleted_cascade_depth: row.get(15)?,
+            paused_at: row.get(16)?,
+            paused_seconds: row.get(17)?,
+            seats: row.get(18)?,
+            abuse_flags: row.get(19)?,
+            abuse_flagged_at: row.get(20)?,
+            abuse_distinct_ips: row.get(21)?,
+            revoked_reason: parse_optional_enum(row, 22, "revoked_reason")?,
+            revoked_message: row.get(23)?,
+            revoked_at: row.get(24)?,
+            revoked_by: row.get(25)?,
+            checkout_fields: checkout_values(row, 26)?,
+            suspended_for_dispute: row.get::<_, i32>(27)? != 0,
+            is_trial: row.get::<_, i32>(28)? != 0,
+            converted_from_license_id: row.get(29)?,
+            accepted_terms_version: row.get(30)?,
+            license_ref: row.get(31)?,
+            metadata: checkout_values(row, 32)?,
+            dormant_since: row.get(33)?,
+            reference_number: row.get(34)?,
+            first_seen_version: row.get(35)?,
+            last_seen_version: row.get(36)?,
+            last_seen_version_at: row.get(37)?,
         })
     }
 }
@@ -323,6 +624,8 @@ impl FromRow for Device {
             jti: row.get(5)?,
             activated_at: row.get(6)?,
             last_seen_at: row.get(7)?,
+            seat_id: row.get(8)?,
+            signed_with_kid: row.get(9)?,
         })
     }
 }
@@ -336,6 +639,15 @@ impl FromRow for PaymentSession {
             created_at: row.get(3)?,
             completed: row.get::<_, i32>(4)? != 0,
             license_id: row.get(5)?,
+            upgrade_from_license_id: row.get(6)?,
+            upgrade_days_remaining: row.get(7)?,
+            upgrade_credit_cents: row.get(8)?,
+            checkout_fields: checkout_values(row, 9)?,
+            checkout_url: row.get(10)?,
+            needs_review: row.get::<_, i32>(11)? != 0,
+            claimed_at: row.get(12)?,
+            accepted_terms_version: row.get(13)?,
+            metadata: checkout_values(row, 14)?,
         })
     }
 }
@@ -348,6 +660,261 @@ impl FromRow for ActivationCode {
             expires_at: row.get(2)?,
             used: row.get::<_, i32>(3)? != 0,
             created_at: row.get(4)?,
+            seat_id: row.get(5)?,
+        })
+    }
+}
+
+impl FromRow for LicenseSeat {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(LicenseSeat {
+            id: row.get(0)?,
+            license_id: row.get(1)?,
+            seat_email_hash: row.get(2)?,
+            assigned_at: row.get(3)?,
+            removed_at: row.get(4)?,
+        })
+    }
+}
+
+impl FromRow for ShareLink {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(ShareLink {
+            id: row.get(0)?,
+            license_id: row.get(1)?,
+            token_hash: row.get(2)?,
+            masked_email: row.get(3)?,
+            created_by: row.get(4)?,
+            expires_at: row.get(5)?,
+            revoked: row.get::<_, i32>(6)? != 0,
+            created_at: row.get(7)?,
+        })
+    }
+}
+
+impl FromRow for PrepaidCodeBatch {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(PrepaidCodeBatch {
+            id: row.get(0)?,
+            project_id: row.get(1)?,
+            product_id: row.get(2)?,
+            label: row.get(3)?,
+            code_count: row.get(4)?,
+            redeemed_count: row.get(5)?,
+            created_by: row.get(6)?,
+            expires_at: row.get(7)?,
+            downloaded_at: row.get(8)?,
+            revoked_at: row.get(9)?,
+            created_at: row.get(10)?,
+        })
+    }
+}
+
+impl FromRow for BulkJob {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(BulkJob {
+            id: row.get(0)?,
+            project_id: row.get(1)?,
+            product_id: row.get(2)?,
+            status: parse_enum(row, 3, "status")?,
+            requested: row.get(4)?,
+            created_count: row.get(5)?,
+            activation_codes: row.get::<_, i32>(6)? != 0,
+            params: serde_json::from_str(&row.get::<_, String>(7)?).unwrap_or_default(),
+            created_by: row.get(8)?,
+            error: row.get(9)?,
+            created_at: row.get(10)?,
+            finished_at: row.get(11)?,
+        })
+    }
+}
+
+impl FromRow for BulkJobChunk {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(BulkJobChunk {
+            chunk: row.get(0)?,
+            license_ids: serde_json::from_str(&row.get::<_, String>(1)?).unwrap_or_default(),
+            error: row.get(2)?,
+            created_at: row.get(3)?,
+        })
+    }
+}
+
+impl FromRow for ProductTemplate {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let features_str: String = row.get(9)?;
+        let checkout_fields_str: String = row.get(13)?;
+        Ok(ProductTemplate {
+            id: row.get(0)?,
+            org_id: row.get(1)?,
+            name: row.get(2)?,
+            tier: row.get(3)?,
+            license_exp_days: row.get(4)?,
+            updates_exp_days: row.get(5)?,
+            activation_limit: row.get(6)?,
+            device_limit: row.get(7)?,
+            device_inactive_days: row.get(8)?,
+            features: serde_json::from_str(&features_str).unwrap_or_default(),
+            price_cents: row.get(10)?,
+            currency: row.get(11)?,
+            seat_count: row.get(12)?,
+            checkout_fields: serde_json::from_str(&checkout_fields_str).unwrap_or_default(),
+            created_at: row.get(14)?,
+            updated_at: row.get(15)?,
+        })
+    }
+}
+
+impl FromRow for Terms {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Terms {
+            id: row.get(0)?,
+            project_id: row.get(1)?,
+            version: row.get(2)?,
+            url: row.get(3)?,
+            effective_at: row.get(4)?,
+            created_by: row.get(5)?,
+            created_at: row.get(6)?,
+        })
+    }
+}
+
+impl FromRow for Artifact {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Artifact {
+            id: row.get(0)?,
+            project_id: row.get(1)?,
+            name: row.get(2)?,
+            released_at: row.get(3)?,
+            url: row.get(4)?,
+            download_count: row.get(5)?,
+            created_by: row.get(6)?,
+            created_at: row.get(7)?,
+        })
+    }
+}
+
+impl FromRow for ArtifactDownload {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(ArtifactDownload {
+            artifact_id: row.get(0)?,
+            license_id: row.get(1)?,
+            count: row.get(2)?,
+            first_downloaded_at: row.get(3)?,
+            last_downloaded_at: row.get(4)?,
+        })
+    }
+}
+
+impl FromRow for Dispute {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Dispute {
+            id: row.get(0)?,
+            license_id: row.get(1)?,
+            project_id: row.get(2)?,
+            provider: row.get(3)?,
+            provider_dispute_id: row.get(4)?,
+            payment_id: row.get(5)?,
+            amount_cents: row.get(6)?,
+            currency: row.get(7)?,
+            reason: row.get(8)?,
+            status: parse_enum(row, 9, "status")?,
+            created_at: row.get(10)?,
+            closed_at: row.get(11)?,
+        })
+    }
+}
+
+impl FromRow for EmailLogEntry {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(EmailLogEntry {
+            id: row.get(0)?,
+            license_id: row.get(1)?,
+            project_id: row.get(2)?,
+            to_email_hash: row.get(3)?,
+            trigger: parse_enum(row, 4, "email_trigger")?,
+            result: parse_enum(row, 5, "result")?,
+            error_status: row.get(6)?,
+            provider_message_id: row.get(7)?,
+            created_at: row.get(8)?,
+        })
+    }
+}
+
+impl FromRow for LicenseUpgrade {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(LicenseUpgrade {
+            id: row.get(0)?,
+            from_license_id: row.get(1)?,
+            to_license_id: row.get(2)?,
+            payment_session_id: row.get(3)?,
+            days_remaining: row.get(4)?,
+            credit_cents: row.get(5)?,
+            old_license_action: parse_enum(row, 6, "old_license_action")?,
+            created_at: row.get(7)?,
+        })
+    }
+}
+
+impl FromRow for LicenseUpdateRenewal {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(LicenseUpdateRenewal {
+            id: row.get(0)?,
+            license_id: row.get(1)?,
+            product_id: row.get(2)?,
+            payment_session_id: row.get(3)?,
+            previous_updates_expires_at: row.get(4)?,
+            updates_expires_at: row.get(5)?,
+            created_at: row.get(6)?,
+        })
+    }
+}
+
+impl FromRow for ProjectActivity {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let mut activity = ProjectActivity {
+            id: row.get(0)?,
+            timestamp: row.get(1)?,
+            actor_type: parse_enum(row, 2, "actor_type")?,
+            user_id: row.get(3)?,
+            user_name: row.get(4)?,
+            user_email: row.get(5)?,
+            action: row.get(6)?,
+            resource_type: row.get(7)?,
+            resource_id: row.get(8)?,
+            resource_name: row.get(9)?,
+            summary: String::new(),
+        };
+        activity.summary = activity.summarize();
+        Ok(activity)
+    }
+}
+
+impl FromRow for AuditLog {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let details_str: Option<String> = row.get(11)?;
+        Ok(AuditLog {
+            id: row.get(0)?,
+            timestamp: row.get(1)?,
+            actor_type: parse_enum(row, 2, "actor_type")?,
+            user_id: row.get(3)?,
+            user_email: row.get(4)?,
+            user_name: row.get(5)?,
+            action: row.get(6)?,
+            resource_type: row.get(7)?,
+            resource_id: row.get(8)?,
+            resource_name: row.get(9)?,
+            resource_email: row.get(10)?,
+            details: details_str.and_then(|s| serde_json::from_str(&s).ok()),
+            org_id: row.get(12)?,
+            org_name: row.get(13)?,
+            project_id: row.get(14)?,
+            project_name: row.get(15)?,
+            ip_address: row.get(16)?,
+            user_agent: row.get(17)?,
+            auth_type: row.get(18)?,
+            auth_credential: row.get(19)?,
+            request_id: row.get(20)?,
         })
     }
 }
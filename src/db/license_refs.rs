@@ -0,0 +1,106 @@
+//! Public license handles.
+//!
+//! License IDs are never shown to end users. Tokens carry a `license_ref`
+//! instead: 12 characters of Crockford base32 taken from
+//! `HMAC-SHA256(project.license_ref_key, license_id)`. The key is random per
+//! project, so refs can't be guessed from IDs or matched up across projects.
+//!
+//! Refs are unique per project (`idx_licenses_project_ref`). On the rare
+//! collision the ref is recomputed with an attempt counter mixed in, until a
+//! free one turns up.
+//!
+//! Admin endpoints take either form; [`is_license_ref`] tells them apart
+//! (IDs are 36-character UUIDs).
+
+use hmac::{Hmac, Mac};
+use rusqlite::{Connection, OptionalExtension, params};
+use sha2::Sha256;
+
+/// Length of a license ref
+pub const LICENSE_REF_LEN: usize = 12;
+
+/// Crockford base32, lowercase: no i, l, o or u
+const ALPHABET: &[u8; 32] = b"0123456789abcdefghjkmnpqrstvwxyz";
+
+/// Give up looking for a free ref after this many collisions. With 60 bits
+/// per ref, even one collision needs about a billion licenses in a project.
+const MAX_ATTEMPTS: u32 = 16;
+
+/// The ref for `license_id` under `key`. Attempt 0 is the normal ref; later
+/// attempts only come up after a collision.
+pub fn compute(key: &str, license_id: &str, attempt: u32) -> String {
+    let mut mac: Hmac<Sha256> =
+        Mac::new_from_slice(key.as_bytes()).expect("HMAC can take key of any size");
+    mac.update(license_id.as_bytes());
+    if attempt > 0 {
+        mac.update(format!(":{}", attempt).as_bytes());
+    }
+    let digest = mac.finalize().into_bytes();
+
+    let bits = u64::from_be_bytes(digest[..8].try_into().expect("digest is 32 bytes"));
+    (0..LICENSE_REF_LEN)
+        .map(|i| ALPHABET[((bits >> (59 - 5 * i)) & 0x1f) as usize] as char)
+        .collect()
+}
+
+/// Whether `value` has the shape of a license ref rather than a license ID.
+pub fn is_license_ref(value: &str) -> bool {
+    value.len() == LICENSE_REF_LEN && value.bytes().all(|b| ALPHABET.contains(&b))
+}
+
+/// The project's ref key, generated the first time one is needed.
+pub fn project_key(conn: &Connection, project_id: &str) -> rusqlite::Result<String> {
+    conn.execute(
+        "UPDATE projects SET license_ref_key = lower(hex(randomblob(32)))
+         WHERE id = ?1 AND license_ref_key IS NULL",
+        [project_id],
+    )?;
+    let key: Option<Option<String>> = conn
+        .query_row(
+            "SELECT license_ref_key FROM projects WHERE id = ?1",
+            [project_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    // A license whose project row is gone still gets a ref, just not a stable one
+    Ok(key
+        .flatten()
+        .unwrap_or_else(|| hex::encode(rand::random::<[u8; 32]>())))
+}
+
+/// A ref for `license_id` not yet taken by another license in the project.
+pub fn assign(conn: &Connection, project_id: &str, license_id: &str) -> rusqlite::Result<String> {
+    let key = project_key(conn, project_id)?;
+    for attempt in 0..MAX_ATTEMPTS {
+        let candidate = compute(&key, license_id, attempt);
+        let taken: bool = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM licenses
+                           WHERE project_id = ?1 AND license_ref = ?2 AND id != ?3)",
+            params![project_id, &candidate, license_id],
+            |row| row.get(0),
+        )?;
+        if !taken {
+            return Ok(candidate);
+        }
+    }
+    Err(rusqlite::Error::ToSqlConversionFailure(
+        format!("no free license ref for {}", license_id).into(),
+    ))
+}
+
+/// Give every license without a ref one.
+pub fn backfill(conn: &Connection) -> rusqlite::Result<usize> {
+    let licenses: Vec<(String, String)> = conn
+        .prepare("SELECT id, project_id FROM licenses WHERE license_ref IS NULL ORDER BY rowid")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    for (id, project_id) in &licenses {
+        let license_ref = assign(conn, project_id, id)?;
+        conn.execute(
+            "UPDATE licenses SET license_ref = ?1 WHERE id = ?2",
+            params![license_ref, id],
+        )?;
+    }
+    Ok(licenses.len())
+}
@@ -6,7 +6,7 @@
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use rusqlite::Connection;
+use rusqlite::{Connection, OptionalExtension};
 use thiserror::Error;
 
 /// Target database for a migration.
@@ -49,6 +49,221 @@ pub const MIGRATIONS: &[Migration] = &[Migration {
     description: "v0.3.0 baseline",
     target: MigrationTarget::Audit,
     up: migration_001_baseline_audit,
+}, Migration {
+    version: 2,
+    description: "v0.5.0 license pause tracking",
+    target: MigrationTarget::Main,
+    up: migration_002_license_pause,
+}, Migration {
+    version: 3,
+    description: "v0.5.0 user email hash",
+    target: MigrationTarget::Main,
+    up: migration_003_user_email_hash,
+}, Migration {
+    version: 4,
+    description: "v0.5.0 project JWT issuer/audience overrides",
+    target: MigrationTarget::Main,
+    up: migration_004_project_jwt_claims,
+}, Migration {
+    version: 5,
+    description: "v0.5.0 license seats for team licenses",
+    target: MigrationTarget::Main,
+    up: migration_005_license_seats,
+}, Migration {
+    version: 6,
+    description: "v0.5.0 scheduled org member removal",
+    target: MigrationTarget::Main,
+    up: migration_006_member_removal_grace,
+}, Migration {
+    version: 7,
+    description: "v0.5.0 upgrade purchases with proration",
+    target: MigrationTarget::Main,
+    up: migration_007_upgrade_purchases,
+}, Migration {
+    version: 8,
+    description: "v0.5.0 publishable key header",
+    target: MigrationTarget::Main,
+    up: migration_008_allow_project_id_auth,
+}, Migration {
+    version: 9,
+    description: "v0.5.0 token key ids",
+    target: MigrationTarget::Main,
+    up: migration_009_device_signed_with_kid,
+}, Migration {
+    version: 10,
+    description: "v0.5.0 product sale windows",
+    target: MigrationTarget::Main,
+    up: migration_010_product_sale_window,
+}, Migration {
+    version: 11,
+    description: "v0.5.0 partner operators",
+    target: MigrationTarget::Main,
+    up: migration_011_partner_operators,
+}, Migration {
+    version: 12,
+    description: "v0.5.0 link checkout",
+    target: MigrationTarget::Main,
+    up: migration_012_allow_link_checkout,
+}, Migration {
+    version: 2,
+    description: "v0.5.0 audit log request ids",
+    target: MigrationTarget::Audit,
+    up: migration_002_audit_request_id,
+}, Migration {
+    version: 13,
+    description: "v0.5.0 license validation throttling",
+    target: MigrationTarget::Main,
+    up: migration_013_validation_throttling,
+}, Migration {
+    version: 14,
+    description: "v0.5.0 license revocation reasons",
+    target: MigrationTarget::Main,
+    up: migration_014_revocation_reasons,
+}, Migration {
+    version: 15,
+    description: "v0.5.0 checkout fields",
+    target: MigrationTarget::Main,
+    up: migration_015_checkout_fields,
+}, Migration {
+    version: 16,
+    description: "v0.5.0 checkout statement descriptor and receipts",
+    target: MigrationTarget::Main,
+    up: migration_016_checkout_presentation,
+}, Migration {
+    version: 17,
+    description: "v0.5.0 license attestation audiences",
+    target: MigrationTarget::Main,
+    up: migration_017_attestation_audiences,
+}, Migration {
+    version: 18,
+    description: "v0.5.0 org default email sender",
+    target: MigrationTarget::Main,
+    up: migration_018_org_email_from,
+}, Migration {
+    version: 19,
+    description: "v0.5.0 duplicate purchase detection",
+    target: MigrationTarget::Main,
+    up: migration_019_duplicate_purchase_check,
+}, Migration {
+    version: 20,
+    description: "v0.5.0 imported provider license keys",
+    target: MigrationTarget::Main,
+    up: migration_020_license_external_keys,
+}, Migration {
+    version: 21,
+    description: "v0.5.0 dispute suspension",
+    target: MigrationTarget::Main,
+    up: migration_021_dispute_suspension,
+}, Migration {
+    version: 22,
+    description: "v0.5.0 updates-only renewal products",
+    target: MigrationTarget::Main,
+    up: migration_022_updates_renewals,
+}, Migration {
+    version: 23,
+    description: "v0.5.0 per-org data keys",
+    target: MigrationTarget::Main,
+    up: migration_023_org_data_keys,
+}, Migration {
+    version: 24,
+    description: "v0.5.0 trial conversions",
+    target: MigrationTarget::Main,
+    up: migration_024_trial_conversions,
+}, Migration {
+    version: 25,
+    description: "v0.5.0 merged users",
+    target: MigrationTarget::Main,
+    up: migration_025_merged_users,
+}, Migration {
+    version: 26,
+    description: "v0.5.0 webhook mirrors",
+    target: MigrationTarget::Main,
+    up: migration_026_webhook_mirrors,
+}, Migration {
+    version: 27,
+    description: "v0.5.0 product upsells",
+    target: MigrationTarget::Main,
+    up: migration_027_product_upsells,
+}, Migration {
+    version: 28,
+    description: "v0.5.0 payment session claim codes",
+    target: MigrationTarget::Main,
+    up: migration_028_payment_session_claims,
+}, Migration {
+    version: 29,
+    description: "v0.5.0 terms acceptance",
+    target: MigrationTarget::Main,
+    up: migration_029_terms_acceptance,
+}, Migration {
+    version: 30,
+    description: "v0.5.0 client flags",
+    target: MigrationTarget::Main,
+    up: migration_030_client_flags,
+}, Migration {
+    version: 31,
+    description: "v0.5.0 license refs",
+    target: MigrationTarget::Main,
+    up: migration_031_license_refs,
+}, Migration {
+    version: 32,
+    description: "v0.5.0 checkout metadata",
+    target: MigrationTarget::Main,
+    up: migration_032_checkout_metadata,
+}, Migration {
+    version: 33,
+    description: "v0.5.0 usage geography",
+    target: MigrationTarget::Main,
+    up: migration_033_usage_geo,
+}, Migration {
+    version: 34,
+    description: "v0.5.0 dormant licenses",
+    target: MigrationTarget::Main,
+    up: migration_034_dormant_licenses,
+}, Migration {
+    version: 35,
+    description: "v0.5.0 per-org audit retention",
+    target: MigrationTarget::Main,
+    up: migration_035_org_audit_retention,
+}, Migration {
+    version: 36,
+    description: "v0.5.0 structured product entitlements",
+    target: MigrationTarget::Main,
+    up: migration_036_product_entitlements,
+}, Migration {
+    version: 37,
+    description: "v0.5.0 row versions for optimistic locking",
+    target: MigrationTarget::Main,
+    up: migration_037_row_versions,
+}, Migration {
+    version: 38,
+    description: "v0.5.0 license reference numbers",
+    target: MigrationTarget::Main,
+    up: migration_038_license_reference_numbers,
+}, Migration {
+    version: 39,
+    description: "v0.5.0 webhook payload schema versions",
+    target: MigrationTarget::Main,
+    up: migration_039_webhook_schema_version,
+}, Migration {
+    version: 40,
+    description: "v0.5.0 partial refund revocation",
+    target: MigrationTarget::Main,
+    up: migration_040_revoke_on_partial_refund,
+}, Migration {
+    version: 41,
+    description: "v0.5.0 email webhook signing secrets",
+    target: MigrationTarget::Main,
+    up: migration_041_email_webhook_secret,
+}, Migration {
+    version: 42,
+    description: "v0.5.0 app versions seen per license",
+    target: MigrationTarget::Main,
+    up: migration_042_license_seen_versions,
+}, Migration {
+    version: 3,
+    description: "v0.5.0 audit log hash chains",
+    target: MigrationTarget::Audit,
+    up: migration_003_audit_hash_chain,
 }];
 
 /// Migration errors.
@@ -79,6 +294,16 @@ pub fn get_version(conn: &Connection) -> rusqlite::Result<i32> {
     conn.pragma_query_value(None, "user_version", |row| row.get(0))
 }
 
+/// Latest migration version for a target (the version a fully migrated database reports).
+pub fn latest_version(target: MigrationTarget) -> i32 {
+    MIGRATIONS
+        .iter()
+        .filter(|m| m.target.applies_to(target))
+        .map(|m| m.version)
+        .max()
+        .unwrap_or(0)
+}
+
 /// Set the schema version in the database.
 fn set_version(conn: &Connection, version: i32) -> rusqlite::Result<()> {
     conn.pragma_update(None, "user_version", version)
@@ -285,6 +510,606 @@ fn migration_001_baseline_audit(conn: &Connection) -> rusqlite::Result<()> {
     Ok(())
 }
 
+/// Migration 2: v0.5.0 subscription pause tracking on licenses.
+///
+/// Fresh databases get these columns from `init_db`; only existing licenses
+/// tables need them added.
+fn migration_002_license_pause(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "licenses", "paused_at", "INTEGER")?;
+    add_column_if_missing(
+        conn,
+        "licenses",
+        "paused_seconds",
+        "INTEGER NOT NULL DEFAULT 0",
+    )
+}
+
+/// Migration 3: v0.5.0 deterministic email hash on users (PII minimization).
+///
+/// Existing rows are hashed at startup, since the HMAC key isn't available here.
+/// The unique index is created by `init_db`.
+fn migration_003_user_email_hash(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "users", "email_hash", "TEXT")
+}
+
+/// Migration 4: v0.5.0 per-project `iss`/`aud` overrides, plus the previous
+/// values accepted during the grace window after a change.
+fn migration_004_project_jwt_claims(conn: &Connection) -> rusqlite::Result<()> {
+    for column in [
+        "jwt_issuer",
+        "jwt_audience",
+        "jwt_previous_issuer",
+        "jwt_previous_audience",
+    ] {
+        add_column_if_missing(conn, "projects", column, "TEXT")?;
+    }
+    add_column_if_missing(conn, "projects", "jwt_previous_until", "INTEGER")
+}
+
+/// Migration 5: v0.5.0 seat-based team licenses. The `license_seats` table
+/// itself is created by `init_db`.
+fn migration_005_license_seats(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "products", "seat_count", "INTEGER")?;
+    add_column_if_missing(conn, "licenses", "seats", "INTEGER")?;
+    add_column_if_missing(
+        conn,
+        "activation_codes",
+        "seat_id",
+        "TEXT REFERENCES license_seats(id) ON DELETE CASCADE",
+    )?;
+    add_column_if_missing(
+        conn,
+        "devices",
+        "seat_id",
+        "TEXT REFERENCES license_seats(id) ON DELETE CASCADE",
+    )
+}
+
+/// Migration 6: v0.5.0 grace period before a removed org member loses access.
+fn migration_006_member_removal_grace(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "org_members", "removal_scheduled_at", "INTEGER")
+}
+
+/// Migration 7: v0.5.0 upgrade purchases. The `license_upgrades` table itself
+/// is created by `init_db`.
+fn migration_007_upgrade_purchases(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(
+        conn,
+        "projects",
+        "upgrade_auto_discount",
+        "INTEGER NOT NULL DEFAULT 0",
+    )?;
+    add_column_if_missing(
+        conn,
+        "projects",
+        "upgrade_old_license",
+        "TEXT NOT NULL DEFAULT 'revoke'",
+    )?;
+    add_column_if_missing(
+        conn,
+        "payment_sessions",
+        "upgrade_from_license_id",
+        "TEXT REFERENCES licenses(id) ON DELETE SET NULL",
+    )?;
+    add_column_if_missing(conn, "payment_sessions", "upgrade_days_remaining", "INTEGER")?;
+    add_column_if_missing(conn, "payment_sessions", "upgrade_credit_cents", "INTEGER")
+}
+
+/// Migration 8: v0.5.0 per-project opt-out of product_id-only project lookup.
+/// Existing projects keep accepting it until they switch it off.
+fn migration_008_allow_project_id_auth(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(
+        conn,
+        "projects",
+        "allow_project_id_auth",
+        "INTEGER NOT NULL DEFAULT 1",
+    )
+}
+
+fn migration_009_device_signed_with_kid(conn: &Connection) -> rusqlite::Result<()> {
+    // Devices activated before this have no record of the signing key
+    add_column_if_missing(conn, "devices", "signed_with_kid", "TEXT")
+}
+
+/// Migration 10: v0.5.0 sale windows. Existing products stay on sale
+/// indefinitely (both bounds NULL).
+fn migration_010_product_sale_window(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "products", "available_from", "INTEGER")?;
+    add_column_if_missing(conn, "products", "available_until", "INTEGER")
+}
+
+/// Migration 11: v0.5.0 partner operators. Widens the `operator_role` CHECK
+/// on existing users tables to accept 'partner'; the `operator_org_scopes`
+/// table itself is created by `init_db`.
+///
+/// Rebuilding `users` would cascade-delete every row that references it, so
+/// the stored definition is edited in place instead. SQLite supports this for
+/// constraint changes that existing rows already satisfy.
+fn migration_011_partner_operators(conn: &Connection) -> rusqlite::Result<()> {
+    const OLD_ROLES: &str = "IN ('owner', 'admin', 'view')";
+    const NEW_ROLES: &str = "IN ('owner', 'admin', 'view', 'partner')";
+
+    let sql: Option<String> = conn
+        .query_row(
+            "SELECT sql FROM sqlite_master WHERE type='table' AND name='users'",
+            [],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let Some(sql) = sql.filter(|sql| sql.contains(OLD_ROLES)) else {
+        // Fresh database (init_db creates the new CHECK) or already widened
+        return Ok(());
+    };
+
+    let schema_version: i64 = conn.pragma_query_value(None, "schema_version", |row| row.get(0))?;
+    conn.pragma_update(None, "writable_schema", true)?;
+    conn.execute(
+        "UPDATE sqlite_master SET sql = ?1 WHERE type='table' AND name='users'",
+        [sql.replace(OLD_ROLES, NEW_ROLES)],
+    )?;
+    // Bumping the schema version makes SQLite re-parse the edited definition
+    conn.pragma_update(None, "schema_version", schema_version + 1)?;
+    conn.pragma_update(None, "writable_schema", false)
+}
+
+/// Migration 12: v0.5.0 per-project opt-in to `GET /buy` purchase links.
+fn migration_012_allow_link_checkout(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(
+        conn,
+        "projects",
+        "allow_link_checkout",
+        "INTEGER NOT NULL DEFAULT 0",
+    )
+}
+
+/// Migration 13: v0.5.0 per-license validation limit on projects (NULL =
+/// unlimited, so existing projects are unaffected) and the throttling history
+/// it records on licenses.
+fn migration_013_validation_throttling(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(
+        conn,
+        "projects",
+        "max_validations_per_hour_per_license",
+        "INTEGER",
+    )?;
+    add_column_if_missing(
+        conn,
+        "licenses",
+        "abuse_flags",
+        "INTEGER NOT NULL DEFAULT 0",
+    )?;
+    add_column_if_missing(conn, "licenses", "abuse_flagged_at", "INTEGER")?;
+    add_column_if_missing(conn, "licenses", "abuse_distinct_ips", "INTEGER")
+}
+
+/// Migration 14: v0.5.0 revocation reason, customer message, time, and actor
+/// on licenses, plus the provider payment ID refunds are matched by. Licenses
+/// revoked before this get `admin_other`, the reason a revoke without one gets.
+fn migration_014_revocation_reasons(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "licenses", "revoked_reason", "TEXT")?;
+    add_column_if_missing(conn, "licenses", "revoked_message", "TEXT")?;
+    add_column_if_missing(conn, "licenses", "revoked_at", "INTEGER")?;
+    add_column_if_missing(conn, "licenses", "revoked_by", "TEXT")?;
+    add_column_if_missing(conn, "payment_sessions", "provider_payment_id", "TEXT")?;
+
+    let has_licenses: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type='table' AND name='licenses'",
+        [],
+        |row| row.get(0),
+    )?;
+    if has_licenses {
+        conn.execute(
+            "UPDATE licenses SET revoked_reason = 'admin_other' WHERE revoked = 1 AND revoked_reason IS NULL",
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+/// Migration 15: v0.5.0 custom checkout fields. Existing products collect
+/// none; sessions and licenses from before this have no values.
+fn migration_015_checkout_fields(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(
+        conn,
+        "products",
+        "checkout_fields",
+        "TEXT NOT NULL DEFAULT '[]'",
+    )?;
+    add_column_if_missing(conn, "payment_sessions", "checkout_fields", "TEXT")?;
+    add_column_if_missing(conn, "licenses", "checkout_fields", "TEXT")
+}
+
+/// Migration 16: v0.5.0 statement descriptor suffix, receipt emails, and
+/// checkout page text on projects. Existing projects keep the provider's
+/// defaults.
+fn migration_016_checkout_presentation(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "projects", "statement_descriptor_suffix", "TEXT")?;
+    add_column_if_missing(
+        conn,
+        "projects",
+        "receipt_email_enabled",
+        "INTEGER NOT NULL DEFAULT 0",
+    )?;
+    add_column_if_missing(conn, "projects", "checkout_message", "TEXT")
+}
+
+/// Migration 17: v0.5.0 audiences `/validate/attest` signs attestations
+/// for. Existing projects allow none, so attestations start off.
+fn migration_017_attestation_audiences(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(
+        conn,
+        "projects",
+        "attestation_audiences",
+        "TEXT NOT NULL DEFAULT '[]'",
+    )
+}
+
+/// Migration 18: v0.5.0 default "from" address for an org's Resend key.
+/// Existing orgs have none and keep using the server default.
+fn migration_018_org_email_from(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "organizations", "email_from", "TEXT")
+}
+
+/// Migration 19: v0.5.0 duplicate purchase detection in `/buy`. Existing
+/// projects get it turned on; sessions from before this have no checkout URL
+/// and are never reused.
+fn migration_019_duplicate_purchase_check(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(
+        conn,
+        "projects",
+        "duplicate_purchase_check",
+        "INTEGER NOT NULL DEFAULT 1",
+    )?;
+    add_column_if_missing(conn, "payment_sessions", "checkout_url", "TEXT")?;
+    add_column_if_missing(conn, "payment_sessions", "buyer_email_hash", "TEXT")
+}
+
+/// Migration 20: v0.5.0 license keys imported from a payment provider
+/// (LemonSqueezy), redeemable through `/redeem/lemonsqueezy`. The unique
+/// index is created by `init_db`.
+fn migration_020_license_external_keys(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "licenses", "external_key_hash", "TEXT")?;
+    add_column_if_missing(conn, "licenses", "external_key_provider", "TEXT")
+}
+
+/// Migration 21: v0.5.0 licenses suspended while a chargeback is disputed.
+/// The `disputes` table is created by `init_db`.
+fn migration_021_dispute_suspension(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(
+        conn,
+        "licenses",
+        "suspended_for_dispute",
+        "INTEGER NOT NULL DEFAULT 0",
+    )
+}
+
+/// Migration 22: v0.5.0 updates-only renewal products, and payment sessions
+/// flagged for review when one can't be applied. The `license_update_renewals`
+/// table is created by `init_db`.
+fn migration_022_updates_renewals(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "products", "extends_updates_for_product_id", "TEXT")?;
+    add_column_if_missing(
+        conn,
+        "products",
+        "renewal_fallback",
+        "TEXT NOT NULL DEFAULT 'create_license'",
+    )?;
+    add_column_if_missing(
+        conn,
+        "payment_sessions",
+        "needs_review",
+        "INTEGER NOT NULL DEFAULT 0",
+    )
+}
+
+/// Migration 23: v0.5.0 per-org data keys. Existing orgs get none and keep
+/// their secrets under the master key until `--migrate-org-keys` is run.
+fn migration_023_org_data_keys(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "organizations", "org_data_key", "BLOB")
+}
+
+/// Migration 24: v0.5.0 trial licenses and the paid licenses they became.
+/// Existing licenses aren't trials, and existing projects keep converted
+/// trials as they are.
+fn migration_024_trial_conversions(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "licenses", "is_trial", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(conn, "licenses", "converted_from_license_id", "TEXT")?;
+    add_column_if_missing(
+        conn,
+        "projects",
+        "converted_trial_action",
+        "TEXT NOT NULL DEFAULT 'keep'",
+    )
+}
+
+/// Migration 25: v0.5.0 user merges. No existing user was merged.
+fn migration_025_merged_users(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "users", "merged_into", "TEXT")
+}
+
+/// Migration 26: v0.5.0 webhook mirrors. No project has one.
+fn migration_026_webhook_mirrors(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "projects", "webhook_mirror_url", "TEXT")?;
+    add_column_if_missing(conn, "projects", "webhook_mirror_expires_at", "INTEGER")
+}
+
+/// Migration 27: v0.5.0 upsell metadata on products. No product has any.
+fn migration_027_product_upsells(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "products", "upgrade_to_product_id", "TEXT")?;
+    add_column_if_missing(
+        conn,
+        "products",
+        "upsell_highlight_features",
+        "TEXT NOT NULL DEFAULT '[]'",
+    )?;
+    add_column_if_missing(conn, "products", "upsell_blurb", "TEXT")
+}
+
+/// Migration 28: v0.5.0 claim codes on payment sessions. No session has one,
+/// and none counts as claimed.
+fn migration_028_payment_session_claims(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "payment_sessions", "claim_code_hash", "TEXT")?;
+    add_column_if_missing(conn, "payment_sessions", "claim_expires_at", "INTEGER")?;
+    add_column_if_missing(conn, "payment_sessions", "claimed_at", "INTEGER")
+}
+
+/// Migration 29: v0.5.0 terms acceptance. No project requires it, and no
+/// purchase recorded an accepted version. The `terms` table is created by
+/// `init_db`.
+fn migration_029_terms_acceptance(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(
+        conn,
+        "projects",
+        "require_terms_acceptance",
+        "INTEGER NOT NULL DEFAULT 0",
+    )?;
+    add_column_if_missing(conn, "payment_sessions", "accepted_terms_version", "TEXT")?;
+    add_column_if_missing(conn, "licenses", "accepted_terms_version", "TEXT")
+}
+
+/// Migration 30: v0.5.0 client flags. Projects start with no flags and no
+/// minimum app version.
+fn migration_030_client_flags(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(
+        conn,
+        "projects",
+        "client_flags",
+        "TEXT NOT NULL DEFAULT '{}'",
+    )?;
+    add_column_if_missing(conn, "projects", "min_client_version", "TEXT")
+}
+
+/// Migration 31: v0.5.0 license refs. Every existing license gets one, and
+/// each project its ref key along with its first license.
+fn migration_031_license_refs(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "projects", "license_ref_key", "TEXT")?;
+    add_column_if_missing(conn, "licenses", "license_ref", "TEXT")?;
+
+    let table_exists: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type='table' AND name='licenses'",
+        [],
+        |row| row.get(0),
+    )?;
+    if table_exists {
+        super::license_refs::backfill(conn)?;
+    }
+    Ok(())
+}
+
+/// Migration 32: v0.5.0 checkout metadata. Projects start accepting no
+/// metadata keys, and nothing has any metadata yet.
+fn migration_032_checkout_metadata(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(
+        conn,
+        "projects",
+        "checkout_metadata_keys",
+        "TEXT NOT NULL DEFAULT '[]'",
+    )?;
+    add_column_if_missing(conn, "payment_sessions", "metadata", "TEXT")?;
+    add_column_if_missing(conn, "licenses", "metadata", "TEXT")
+}
+
+/// Migration 33: v0.5.0 usage geography. No project counts usage by country
+/// until it opts in.
+fn migration_033_usage_geo(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(
+        conn,
+        "projects",
+        "usage_geo_enabled",
+        "INTEGER NOT NULL DEFAULT 0",
+    )
+}
+
+/// Migration 34: v0.5.0 dormant licenses. No project marks licenses dormant
+/// until it sets `dormant_after_days`. The index is created by `init_db`.
+fn migration_034_dormant_licenses(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "projects", "dormant_after_days", "INTEGER")?;
+    add_column_if_missing(
+        conn,
+        "projects",
+        "dormant_nudge_email",
+        "INTEGER NOT NULL DEFAULT 0",
+    )?;
+    add_column_if_missing(conn, "licenses", "dormant_since", "INTEGER")
+}
+
+/// Migration 35: v0.5.0 per-org audit retention. Existing orgs follow the
+/// instance default until they set their own.
+fn migration_035_org_audit_retention(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(
+        conn,
+        "organizations",
+        "public_audit_retention_days",
+        "INTEGER",
+    )?;
+    add_column_if_missing(
+        conn,
+        "organizations",
+        "admin_audit_retention_days",
+        "INTEGER",
+    )
+}
+
+/// Migration 36: v0.5.0 structured product entitlements. Existing products
+/// keep their feature strings and start with none.
+fn migration_036_product_entitlements(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(
+        conn,
+        "products",
+        "entitlements",
+        "TEXT NOT NULL DEFAULT '{}'",
+    )
+}
+
+/// Migration 37: v0.5.0 row versions on organizations, projects, and
+/// products, checked by updates that send `expected_version`. Existing rows
+/// start at 1.
+fn migration_037_row_versions(conn: &Connection) -> rusqlite::Result<()> {
+    for table in ["organizations", "projects", "products"] {
+        add_column_if_missing(conn, table, "version", "INTEGER NOT NULL DEFAULT 1")?;
+    }
+    Ok(())
+}
+
+/// Migration 38: v0.5.0 license reference numbers. Existing licenses are
+/// numbered in the order they were created, and each project's counter
+/// carries on from its last one.
+fn migration_038_license_reference_numbers(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "licenses", "reference_number", "TEXT")?;
+
+    let table_exists: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type='table' AND name='licenses'",
+        [],
+        |row| row.get(0),
+    )?;
+    if table_exists {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS project_counters (
+                project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+                name TEXT NOT NULL,
+                value INTEGER NOT NULL,
+                PRIMARY KEY (project_id, name)
+            );",
+        )?;
+        super::reference_numbers::backfill(conn)?;
+    }
+    Ok(())
+}
+
+/// Migration 39: v0.5.0 webhook payload schema versions. Projects that
+/// already have an email webhook are pinned to version 1, the payloads
+/// their receivers were written for; the rest follow the latest.
+fn migration_039_webhook_schema_version(conn: &Connection) -> rusqlite::Result<()> {
+    let column_exists: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info('projects') WHERE name = 'webhook_schema_version'",
+        [],
+        |row| row.get(0),
+    )?;
+    if column_exists {
+        return Ok(());
+    }
+
+    add_column_if_missing(conn, "projects", "webhook_schema_version", "TEXT")?;
+    let table_exists: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type='table' AND name='projects'",
+        [],
+        |row| row.get(0),
+    )?;
+    if table_exists {
+        conn.execute(
+            "UPDATE projects SET webhook_schema_version = '1' WHERE email_webhook_url IS NOT NULL",
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+/// Migration 40: v0.5.0 per-product revocation on partial refunds. Off for
+/// existing products, which only revoked on full refunds.
+fn migration_040_revoke_on_partial_refund(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(
+        conn,
+        "products",
+        "revoke_on_partial_refund",
+        "INTEGER NOT NULL DEFAULT 0",
+    )
+}
+
+/// Migration 41: v0.5.0 optional secret the email webhook's payloads are
+/// signed with, encrypted with the org's data key. Existing webhooks stay
+/// unsigned until one is set.
+fn migration_041_email_webhook_secret(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "projects", "email_webhook_secret", "BLOB")
+}
+
+/// Migration 42: v0.5.0 first and last app version each license was
+/// validated or refreshed with. Existing licenses start with none.
+fn migration_042_license_seen_versions(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "licenses", "first_seen_version", "TEXT")?;
+    add_column_if_missing(conn, "licenses", "last_seen_version", "TEXT")?;
+    add_column_if_missing(conn, "licenses", "last_seen_version_at", "INTEGER")
+}
+
+/// Migration 2 (audit database): v0.5.0 request ID on audit log entries.
+/// Entries written before this have none.
+fn migration_002_audit_request_id(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "audit_logs", "request_id", "TEXT")
+}
+
+/// Migration 3 (audit database): v0.5.0 hash chains on audit log entries.
+/// Existing entries are chained in timestamp order.
+fn migration_003_audit_hash_chain(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "audit_logs", "chain", "TEXT")?;
+    add_column_if_missing(conn, "audit_logs", "chain_seq", "INTEGER")?;
+    add_column_if_missing(conn, "audit_logs", "prev_hash", "TEXT")?;
+    add_column_if_missing(conn, "audit_logs", "row_hash", "TEXT")?;
+
+    let table_exists: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type='table' AND name='audit_logs'",
+        [],
+        |row| row.get(0),
+    )?;
+    if table_exists {
+        super::audit_chain::backfill(conn)?;
+    }
+    Ok(())
+}
+
+/// Add a column to an existing table. No-op if the table doesn't exist yet
+/// (fresh database, `init_db` creates it) or the column is already there.
+fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> rusqlite::Result<()> {
+    let table_exists: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type='table' AND name=?1",
+        [table],
+        |row| row.get(0),
+    )?;
+    if !table_exists {
+        return Ok(());
+    }
+
+    let column_exists: bool = conn.query_row(
+        &format!(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('{}') WHERE name = ?1",
+            table
+        ),
+        [column],
+        |row| row.get(0),
+    )?;
+    if column_exists {
+        return Ok(());
+    }
+
+    conn.execute_batch(&format!(
+        "ALTER TABLE {} ADD COLUMN {} {}",
+        table, column, definition
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -320,6 +1145,832 @@ mod tests {
         // Should complete without error (existing DB detected)
     }
 
+    #[test]
+    fn test_migration_002_adds_pause_columns() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE licenses (id TEXT PRIMARY KEY)", [])
+            .unwrap();
+
+        migration_002_license_pause(&conn).unwrap();
+        // Re-running is harmless (columns already present)
+        migration_002_license_pause(&conn).unwrap();
+
+        conn.execute("INSERT INTO licenses (id) VALUES ('lic')", [])
+            .unwrap();
+        let (paused_at, paused_seconds): (Option<i64>, i64) = conn
+            .query_row(
+                "SELECT paused_at, paused_seconds FROM licenses",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(paused_at, None);
+        assert_eq!(paused_seconds, 0);
+    }
+
+    #[test]
+    fn test_migration_002_fresh_database() {
+        let conn = Connection::open_in_memory().unwrap();
+        // No licenses table yet - init_db creates it with the columns
+        migration_002_license_pause(&conn).unwrap();
+    }
+
+    #[test]
+    fn test_migration_003_adds_email_hash() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute(
+            "CREATE TABLE users (id TEXT PRIMARY KEY, email TEXT NOT NULL)",
+            [],
+        )
+        .unwrap();
+        conn.execute("INSERT INTO users (id, email) VALUES ('u', 'a@b.c')", [])
+            .unwrap();
+
+        migration_003_user_email_hash(&conn).unwrap();
+        migration_003_user_email_hash(&conn).unwrap();
+
+        let hash: Option<String> = conn
+            .query_row("SELECT email_hash FROM users", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(hash, None);
+    }
+
+    #[test]
+    fn test_migration_004_adds_project_jwt_columns() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE projects (id TEXT PRIMARY KEY)", [])
+            .unwrap();
+
+        migration_004_project_jwt_claims(&conn).unwrap();
+        migration_004_project_jwt_claims(&conn).unwrap();
+
+        conn.execute("INSERT INTO projects (id) VALUES ('p')", [])
+            .unwrap();
+        let (issuer, until): (Option<String>, Option<i64>) = conn
+            .query_row(
+                "SELECT jwt_issuer, jwt_previous_until FROM projects",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(issuer, None);
+        assert_eq!(until, None);
+    }
+
+    #[test]
+    fn test_migration_005_adds_seat_columns() {
+        let conn = Connection::open_in_memory().unwrap();
+        for table in ["products", "licenses", "activation_codes", "devices"] {
+            conn.execute(&format!("CREATE TABLE {} (id TEXT PRIMARY KEY)", table), [])
+                .unwrap();
+        }
+
+        migration_005_license_seats(&conn).unwrap();
+        migration_005_license_seats(&conn).unwrap();
+
+        for (table, column) in [
+            ("products", "seat_count"),
+            ("licenses", "seats"),
+            ("activation_codes", "seat_id"),
+            ("devices", "seat_id"),
+        ] {
+            let exists: bool = conn
+                .query_row(
+                    &format!(
+                        "SELECT COUNT(*) > 0 FROM pragma_table_info('{}') WHERE name = ?1",
+                        table
+                    ),
+                    [column],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert!(exists, "{}.{} should exist", table, column);
+        }
+    }
+
+    #[test]
+    fn test_migration_006_adds_removal_scheduled_at() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE org_members (id TEXT PRIMARY KEY)", [])
+            .unwrap();
+
+        migration_006_member_removal_grace(&conn).unwrap();
+        migration_006_member_removal_grace(&conn).unwrap();
+
+        let exists: bool = conn
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM pragma_table_info('org_members') WHERE name = 'removal_scheduled_at'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(exists);
+    }
+
+    #[test]
+    fn test_migration_007_adds_upgrade_columns() {
+        let conn = Connection::open_in_memory().unwrap();
+        for table in ["projects", "payment_sessions"] {
+            conn.execute(&format!("CREATE TABLE {} (id TEXT PRIMARY KEY)", table), [])
+                .unwrap();
+        }
+        conn.execute("INSERT INTO projects (id) VALUES ('p1')", [])
+            .unwrap();
+
+        migration_007_upgrade_purchases(&conn).unwrap();
+        migration_007_upgrade_purchases(&conn).unwrap();
+
+        for (table, column) in [
+            ("projects", "upgrade_auto_discount"),
+            ("projects", "upgrade_old_license"),
+            ("payment_sessions", "upgrade_from_license_id"),
+            ("payment_sessions", "upgrade_days_remaining"),
+            ("payment_sessions", "upgrade_credit_cents"),
+        ] {
+            let exists: bool = conn
+                .query_row(
+                    &format!(
+                        "SELECT COUNT(*) > 0 FROM pragma_table_info('{}') WHERE name = ?1",
+                        table
+                    ),
+                    [column],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert!(exists, "{}.{} should exist", table, column);
+        }
+
+        // Existing projects get the defaults
+        let (discount, old_license): (i32, String) = conn
+            .query_row(
+                "SELECT upgrade_auto_discount, upgrade_old_license FROM projects WHERE id = 'p1'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((discount, old_license.as_str()), (0, "revoke"));
+    }
+
+    #[test]
+    fn test_migration_008_keeps_project_id_auth_for_existing_projects() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE projects (id TEXT PRIMARY KEY)", [])
+            .unwrap();
+        conn.execute("INSERT INTO projects (id) VALUES ('p1')", [])
+            .unwrap();
+
+        migration_008_allow_project_id_auth(&conn).unwrap();
+        migration_008_allow_project_id_auth(&conn).unwrap();
+
+        let allowed: i32 = conn
+            .query_row(
+                "SELECT allow_project_id_auth FROM projects WHERE id = 'p1'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(allowed, 1);
+    }
+
+    #[test]
+    fn test_migration_009_adds_signed_with_kid() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE devices (id TEXT PRIMARY KEY)", [])
+            .unwrap();
+        conn.execute("INSERT INTO devices (id) VALUES ('d1')", [])
+            .unwrap();
+
+        migration_009_device_signed_with_kid(&conn).unwrap();
+        migration_009_device_signed_with_kid(&conn).unwrap();
+
+        let kid: Option<String> = conn
+            .query_row(
+                "SELECT signed_with_kid FROM devices WHERE id = 'd1'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(kid, None);
+    }
+
+    #[test]
+    fn test_migration_010_adds_sale_window() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE products (id TEXT PRIMARY KEY)", [])
+            .unwrap();
+        conn.execute("INSERT INTO products (id) VALUES ('p1')", [])
+            .unwrap();
+
+        migration_010_product_sale_window(&conn).unwrap();
+        migration_010_product_sale_window(&conn).unwrap();
+
+        let (from, until): (Option<i64>, Option<i64>) = conn
+            .query_row(
+                "SELECT available_from, available_until FROM products WHERE id = 'p1'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(from, None);
+        assert_eq!(until, None);
+    }
+
+    #[test]
+    fn test_migration_011_allows_partner_role() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute(
+            "CREATE TABLE users (id TEXT PRIMARY KEY, operator_role TEXT CHECK (operator_role IS NULL OR operator_role IN ('owner', 'admin', 'view')))",
+            [],
+        )
+        .unwrap();
+        conn.execute("INSERT INTO users (id, operator_role) VALUES ('u1', 'owner')", [])
+            .unwrap();
+        assert!(
+            conn.execute(
+                "INSERT INTO users (id, operator_role) VALUES ('u2', 'partner')",
+                []
+            )
+            .is_err()
+        );
+
+        migration_011_partner_operators(&conn).unwrap();
+        migration_011_partner_operators(&conn).unwrap();
+
+        conn.execute(
+            "INSERT INTO users (id, operator_role) VALUES ('u2', 'partner')",
+            [],
+        )
+        .unwrap();
+        assert!(
+            conn.execute(
+                "INSERT INTO users (id, operator_role) VALUES ('u3', 'root')",
+                []
+            )
+            .is_err(),
+            "other roles are still rejected"
+        );
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM users", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 2, "existing rows are kept");
+    }
+
+    #[test]
+    fn test_migration_012_link_checkout_off_for_existing_projects() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE projects (id TEXT PRIMARY KEY)", [])
+            .unwrap();
+        conn.execute("INSERT INTO projects (id) VALUES ('p1')", [])
+            .unwrap();
+
+        migration_012_allow_link_checkout(&conn).unwrap();
+        migration_012_allow_link_checkout(&conn).unwrap();
+
+        let allowed: i32 = conn
+            .query_row(
+                "SELECT allow_link_checkout FROM projects WHERE id = 'p1'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(allowed, 0);
+    }
+
+    #[test]
+    fn test_migration_002_audit_adds_request_id() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE audit_logs (id TEXT PRIMARY KEY)", [])
+            .unwrap();
+        conn.execute("INSERT INTO audit_logs (id) VALUES ('a1')", [])
+            .unwrap();
+
+        migration_002_audit_request_id(&conn).unwrap();
+        migration_002_audit_request_id(&conn).unwrap();
+
+        let request_id: Option<String> = conn
+            .query_row(
+                "SELECT request_id FROM audit_logs WHERE id = 'a1'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(request_id, None);
+    }
+
+    #[test]
+    fn test_migration_003_audit_chains_existing_entries() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE audit_logs (
+                id TEXT PRIMARY KEY, timestamp INTEGER NOT NULL, actor_type TEXT NOT NULL,
+                user_id TEXT, user_email TEXT, user_name TEXT, action TEXT NOT NULL,
+                resource_type TEXT NOT NULL, resource_id TEXT NOT NULL, resource_name TEXT,
+                resource_email TEXT, details TEXT, org_id TEXT, org_name TEXT, project_id TEXT,
+                project_name TEXT, ip_address TEXT, user_agent TEXT, auth_type TEXT,
+                auth_credential TEXT, request_id TEXT
+            );
+            INSERT INTO audit_logs (id, timestamp, actor_type, action, resource_type, resource_id)
+            VALUES ('a2', 200, 'user', 'update_org', 'org', 'o1'),
+                   ('a1', 100, 'system', 'create_org', 'org', 'o1'),
+                   ('p1', 150, 'public', 'activate_device', 'device', 'd1');",
+        )
+        .unwrap();
+
+        migration_003_audit_hash_chain(&conn).unwrap();
+        migration_003_audit_hash_chain(&conn).unwrap();
+
+        let chained: Vec<(String, String, i64)> = conn
+            .prepare("SELECT id, chain, chain_seq FROM audit_logs ORDER BY chain, chain_seq")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            chained,
+            [
+                ("a1".to_string(), "internal".to_string(), 1),
+                ("a2".to_string(), "internal".to_string(), 2),
+                ("p1".to_string(), "public".to_string(), 1),
+            ]
+        );
+
+        let verification = crate::db::audit_chain::verify(&conn, None, None).unwrap();
+        assert!(verification.valid);
+        assert_eq!(verification.chains[0].checked, 2);
+        assert_eq!(verification.chains[1].checked, 1);
+    }
+
+    #[test]
+    fn test_migration_013_existing_projects_unthrottled() {
+        let conn = Connection::open_in_memory().unwrap();
+        for table in ["projects", "licenses"] {
+            conn.execute(&format!("CREATE TABLE {} (id TEXT PRIMARY KEY)", table), [])
+                .unwrap();
+            conn.execute(&format!("INSERT INTO {} (id) VALUES ('x1')", table), [])
+                .unwrap();
+        }
+
+        migration_013_validation_throttling(&conn).unwrap();
+        migration_013_validation_throttling(&conn).unwrap();
+
+        let limit: Option<i64> = conn
+            .query_row(
+                "SELECT max_validations_per_hour_per_license FROM projects WHERE id = 'x1'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(limit, None);
+
+        let (flags, flagged_at, distinct_ips): (i64, Option<i64>, Option<i64>) = conn
+            .query_row(
+                "SELECT abuse_flags, abuse_flagged_at, abuse_distinct_ips FROM licenses WHERE id = 'x1'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!((flags, flagged_at, distinct_ips), (0, None, None));
+    }
+
+    #[test]
+    fn test_migration_014_existing_revocations_get_admin_other() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute(
+            "CREATE TABLE licenses (id TEXT PRIMARY KEY, revoked INTEGER NOT NULL DEFAULT 0)",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO licenses (id, revoked) VALUES ('active', 0), ('revoked', 1)",
+            [],
+        )
+        .unwrap();
+        conn.execute("CREATE TABLE payment_sessions (id TEXT PRIMARY KEY)", [])
+            .unwrap();
+
+        migration_014_revocation_reasons(&conn).unwrap();
+        migration_014_revocation_reasons(&conn).unwrap();
+
+        let reason = |id: &str| -> Option<String> {
+            conn.query_row(
+                "SELECT revoked_reason FROM licenses WHERE id = ?1",
+                [id],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+        assert_eq!(reason("active"), None);
+        assert_eq!(reason("revoked").as_deref(), Some("admin_other"));
+
+        let has_payment_id: bool = conn
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM pragma_table_info('payment_sessions') WHERE name = 'provider_payment_id'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(has_payment_id);
+    }
+
+    #[test]
+    fn test_migration_015_existing_products_collect_no_fields() {
+        let conn = Connection::open_in_memory().unwrap();
+        for table in ["products", "payment_sessions", "licenses"] {
+            conn.execute(&format!("CREATE TABLE {} (id TEXT PRIMARY KEY)", table), [])
+                .unwrap();
+            conn.execute(&format!("INSERT INTO {} (id) VALUES ('x1')", table), [])
+                .unwrap();
+        }
+
+        migration_015_checkout_fields(&conn).unwrap();
+        migration_015_checkout_fields(&conn).unwrap();
+
+        let product_fields: String = conn
+            .query_row("SELECT checkout_fields FROM products", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(product_fields, "[]");
+        for table in ["payment_sessions", "licenses"] {
+            let values: Option<String> = conn
+                .query_row(
+                    &format!("SELECT checkout_fields FROM {}", table),
+                    [],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert_eq!(values, None, "{} should have no values", table);
+        }
+    }
+
+    #[test]
+    fn test_migration_016_existing_projects_keep_provider_defaults() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE projects (id TEXT PRIMARY KEY)", [])
+            .unwrap();
+        conn.execute("INSERT INTO projects (id) VALUES ('x1')", [])
+            .unwrap();
+
+        migration_016_checkout_presentation(&conn).unwrap();
+        migration_016_checkout_presentation(&conn).unwrap();
+
+        let (suffix, receipts, message): (Option<String>, bool, Option<String>) = conn
+            .query_row(
+                "SELECT statement_descriptor_suffix, receipt_email_enabled, checkout_message
+                 FROM projects WHERE id = 'x1'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(suffix, None);
+        assert!(!receipts);
+        assert_eq!(message, None);
+    }
+
+    #[test]
+    fn test_migration_017_existing_projects_allow_no_audiences() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE projects (id TEXT PRIMARY KEY)", [])
+            .unwrap();
+        conn.execute("INSERT INTO projects (id) VALUES ('x1')", [])
+            .unwrap();
+
+        migration_017_attestation_audiences(&conn).unwrap();
+        migration_017_attestation_audiences(&conn).unwrap();
+
+        let audiences: String = conn
+            .query_row(
+                "SELECT attestation_audiences FROM projects WHERE id = 'x1'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(audiences, "[]");
+    }
+
+    #[test]
+    fn test_migration_018_existing_orgs_have_no_email_from() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE organizations (id TEXT PRIMARY KEY)", [])
+            .unwrap();
+        conn.execute("INSERT INTO organizations (id) VALUES ('o1')", [])
+            .unwrap();
+
+        migration_018_org_email_from(&conn).unwrap();
+        migration_018_org_email_from(&conn).unwrap();
+
+        let email_from: Option<String> = conn
+            .query_row(
+                "SELECT email_from FROM organizations WHERE id = 'o1'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(email_from, None);
+    }
+
+    #[test]
+    fn test_migration_019_turns_duplicate_check_on_for_existing_projects() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE projects (id TEXT PRIMARY KEY);
+             CREATE TABLE payment_sessions (id TEXT PRIMARY KEY);
+             INSERT INTO projects (id) VALUES ('p1');
+             INSERT INTO payment_sessions (id) VALUES ('s1');",
+        )
+        .unwrap();
+
+        migration_019_duplicate_purchase_check(&conn).unwrap();
+        migration_019_duplicate_purchase_check(&conn).unwrap();
+
+        let enabled: bool = conn
+            .query_row(
+                "SELECT duplicate_purchase_check FROM projects WHERE id = 'p1'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(enabled);
+        let (checkout_url, email_hash): (Option<String>, Option<String>) = conn
+            .query_row(
+                "SELECT checkout_url, buyer_email_hash FROM payment_sessions WHERE id = 's1'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(checkout_url, None);
+        assert_eq!(email_hash, None);
+    }
+
+    #[test]
+    fn test_migration_020_existing_licenses_have_no_external_key() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE licenses (id TEXT PRIMARY KEY);
+             INSERT INTO licenses (id) VALUES ('l1');",
+        )
+        .unwrap();
+
+        migration_020_license_external_keys(&conn).unwrap();
+        migration_020_license_external_keys(&conn).unwrap();
+
+        let (key_hash, provider): (Option<String>, Option<String>) = conn
+            .query_row(
+                "SELECT external_key_hash, external_key_provider FROM licenses WHERE id = 'l1'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(key_hash, None);
+        assert_eq!(provider, None);
+    }
+
+    #[test]
+    fn test_migration_021_existing_licenses_are_not_suspended() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE licenses (id TEXT PRIMARY KEY);
+             INSERT INTO licenses (id) VALUES ('l1');",
+        )
+        .unwrap();
+
+        migration_021_dispute_suspension(&conn).unwrap();
+        migration_021_dispute_suspension(&conn).unwrap();
+
+        let suspended: i32 = conn
+            .query_row(
+                "SELECT suspended_for_dispute FROM licenses WHERE id = 'l1'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(suspended, 0);
+    }
+
+    #[test]
+    fn test_migration_023_existing_orgs_have_no_data_key() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE organizations (id TEXT PRIMARY KEY);
+             INSERT INTO organizations (id) VALUES ('o1');",
+        )
+        .unwrap();
+
+        migration_023_org_data_keys(&conn).unwrap();
+        migration_023_org_data_keys(&conn).unwrap();
+
+        let key: Option<Vec<u8>> = conn
+            .query_row(
+                "SELECT org_data_key FROM organizations WHERE id = 'o1'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(key, None);
+    }
+
+    #[test]
+    fn test_migration_024_existing_licenses_are_not_trials() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE licenses (id TEXT PRIMARY KEY);
+             CREATE TABLE projects (id TEXT PRIMARY KEY);
+             INSERT INTO licenses (id) VALUES ('l1');
+             INSERT INTO projects (id) VALUES ('p1');",
+        )
+        .unwrap();
+
+        migration_024_trial_conversions(&conn).unwrap();
+        migration_024_trial_conversions(&conn).unwrap();
+
+        let (is_trial, converted_from): (i32, Option<String>) = conn
+            .query_row(
+                "SELECT is_trial, converted_from_license_id FROM licenses WHERE id = 'l1'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(is_trial, 0);
+        assert_eq!(converted_from, None);
+        let action: String = conn
+            .query_row(
+                "SELECT converted_trial_action FROM projects WHERE id = 'p1'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(action, "keep");
+    }
+
+    #[test]
+    fn test_migration_025_existing_users_not_merged() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE users (id TEXT PRIMARY KEY);
+             INSERT INTO users (id) VALUES ('u1');",
+        )
+        .unwrap();
+
+        migration_025_merged_users(&conn).unwrap();
+        migration_025_merged_users(&conn).unwrap();
+
+        let merged_into: Option<String> = conn
+            .query_row("SELECT merged_into FROM users WHERE id = 'u1'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(merged_into, None);
+    }
+
+    #[test]
+    fn test_migration_026_existing_projects_have_no_mirror() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE projects (id TEXT PRIMARY KEY);
+             INSERT INTO projects (id) VALUES ('p1');",
+        )
+        .unwrap();
+
+        migration_026_webhook_mirrors(&conn).unwrap();
+        migration_026_webhook_mirrors(&conn).unwrap();
+
+        let (url, expires_at): (Option<String>, Option<i64>) = conn
+            .query_row(
+                "SELECT webhook_mirror_url, webhook_mirror_expires_at FROM projects WHERE id = 'p1'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((url, expires_at), (None, None));
+    }
+
+    #[test]
+    fn test_migration_027_existing_products_have_no_upsell() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE products (id TEXT PRIMARY KEY);
+             INSERT INTO products (id) VALUES ('p1');",
+        )
+        .unwrap();
+
+        migration_027_product_upsells(&conn).unwrap();
+        migration_027_product_upsells(&conn).unwrap();
+
+        let (target, highlights, blurb): (Option<String>, String, Option<String>) = conn
+            .query_row(
+                "SELECT upgrade_to_product_id, upsell_highlight_features, upsell_blurb FROM products WHERE id = 'p1'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!((target, highlights.as_str(), blurb), (None, "[]", None));
+    }
+
+    #[test]
+    fn test_migration_031_backfills_license_refs() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE projects (id TEXT PRIMARY KEY);
+             CREATE TABLE licenses (id TEXT PRIMARY KEY, project_id TEXT NOT NULL);
+             INSERT INTO projects (id) VALUES ('p1'), ('p2');
+             INSERT INTO licenses (id, project_id) VALUES ('l1', 'p1'), ('l2', 'p1'), ('l3', 'p2');",
+        )
+        .unwrap();
+
+        migration_031_license_refs(&conn).unwrap();
+        let license_ref = |id: &str| -> String {
+            conn.query_row(
+                "SELECT license_ref FROM licenses WHERE id = ?1",
+                [id],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+        let first = license_ref("l1");
+        assert!(crate::db::license_refs::is_license_ref(&first));
+        assert_ne!(first, license_ref("l2"));
+
+        let key: String = conn
+            .query_row(
+                "SELECT license_ref_key FROM projects WHERE id = 'p1'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(first, crate::db::license_refs::compute(&key, "l1", 0));
+
+        // Re-running keeps the refs already handed out
+        migration_031_license_refs(&conn).unwrap();
+        assert_eq!(license_ref("l1"), first);
+    }
+
+    #[test]
+    fn test_migration_038_numbers_licenses_in_creation_order() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE projects (id TEXT PRIMARY KEY, license_key_prefix TEXT NOT NULL);
+             CREATE TABLE licenses (id TEXT PRIMARY KEY, project_id TEXT NOT NULL, created_at INTEGER NOT NULL);
+             INSERT INTO projects (id, license_key_prefix) VALUES ('p1', 'pro'), ('p2', 'PC');
+             INSERT INTO licenses (id, project_id, created_at)
+             VALUES ('newer', 'p1', 200), ('older', 'p1', 100), ('other', 'p2', 300);",
+        )
+        .unwrap();
+
+        migration_038_license_reference_numbers(&conn).unwrap();
+        let reference_number = |id: &str| -> String {
+            conn.query_row(
+                "SELECT reference_number FROM licenses WHERE id = ?1",
+                [id],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+        assert_eq!(reference_number("older"), "PRO-000001");
+        assert_eq!(reference_number("newer"), "PRO-000002");
+        assert_eq!(reference_number("other"), "PC-000001");
+
+        // New licenses carry on from the backfilled ones
+        assert_eq!(
+            crate::db::reference_numbers::next(&conn, "p1").unwrap(),
+            "PRO-000003"
+        );
+
+        // Re-running keeps the numbers already handed out
+        migration_038_license_reference_numbers(&conn).unwrap();
+        assert_eq!(reference_number("older"), "PRO-000001");
+    }
+
+    #[test]
+    fn test_migration_039_pins_existing_webhooks_to_v1() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE projects (id TEXT PRIMARY KEY, email_webhook_url TEXT);
+             INSERT INTO projects (id, email_webhook_url)
+             VALUES ('hooked', 'https://example.com/hook'), ('plain', NULL);",
+        )
+        .unwrap();
+
+        migration_039_webhook_schema_version(&conn).unwrap();
+        let version = |id: &str| -> Option<String> {
+            conn.query_row(
+                "SELECT webhook_schema_version FROM projects WHERE id = ?1",
+                [id],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+        assert_eq!(version("hooked").as_deref(), Some("1"));
+        assert_eq!(version("plain"), None);
+
+        // Re-running leaves later choices alone
+        conn.execute(
+            "UPDATE projects SET webhook_schema_version = NULL WHERE id = 'hooked'",
+            [],
+        )
+        .unwrap();
+        migration_039_webhook_schema_version(&conn).unwrap();
+        assert_eq!(version("hooked"), None);
+    }
+
     #[test]
     fn test_run_migrations_fresh_db() {
         let dir = tempdir().unwrap();
@@ -330,8 +1981,11 @@ mod tests {
 
         run_migrations(&mut conn, db_path_str, MigrationTarget::Main, 1).unwrap();
 
-        // Should be at version 1
-        assert_eq!(get_version(&conn).unwrap(), 1);
+        // Should be at the latest version
+        assert_eq!(
+            get_version(&conn).unwrap(),
+            latest_version(MigrationTarget::Main)
+        );
 
         // Backup should exist
         let backups: Vec<_> = fs::read_dir(dir.path())
@@ -356,7 +2010,7 @@ mod tests {
         let mut conn = Connection::open(&db_path).unwrap();
 
         // Set version to current
-        set_version(&conn, 1).unwrap();
+        set_version(&conn, latest_version(MigrationTarget::Main)).unwrap();
 
         run_migrations(&mut conn, db_path_str, MigrationTarget::Main, 1).unwrap();
 
@@ -386,7 +2040,10 @@ mod tests {
         run_migrations(&mut conn, db_path_str, MigrationTarget::Main, 0).unwrap();
 
         // Should still migrate
-        assert_eq!(get_version(&conn).unwrap(), 1);
+        assert_eq!(
+            get_version(&conn).unwrap(),
+            latest_version(MigrationTarget::Main)
+        );
 
         // No backup should be created
         let backups: Vec<_> = fs::read_dir(dir.path())
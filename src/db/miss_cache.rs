@@ -0,0 +1,123 @@
+//! Negative cache for project lookups on public endpoints.
+//!
+//! Public endpoints identify the project by a caller-supplied public key, so a
+//! burst of garbage keys would otherwise cost a database roundtrip each. Keys
+//! that recently matched no project are remembered for a short TTL, bounded by
+//! capacity (oldest miss evicted first), and skipped without touching the DB.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Default number of missed keys remembered at once.
+pub const DEFAULT_MISS_CAPACITY: usize = 1024;
+
+/// Default time a missed key stays cached.
+pub const DEFAULT_MISS_TTL: Duration = Duration::from_secs(30);
+
+#[derive(Default)]
+struct Entries {
+    /// Key -> time the miss was recorded
+    recorded: HashMap<String, Instant>,
+    /// Keys in the order they were recorded (may hold stale duplicates)
+    order: VecDeque<(String, Instant)>,
+}
+
+impl Entries {
+    /// Drop queue entries that no longer match a cached miss.
+    fn compact(&mut self) {
+        let recorded = &self.recorded;
+        self.order.retain(|(key, at)| recorded.get(key) == Some(at));
+    }
+}
+
+/// Bounded, TTL-based cache of project identifiers that matched no project.
+pub struct ProjectMissCache {
+    entries: Mutex<Entries>,
+    capacity: usize,
+    ttl: Duration,
+}
+
+impl ProjectMissCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            entries: Mutex::new(Entries::default()),
+            capacity,
+            ttl,
+        }
+    }
+
+    /// Whether `key` missed recently enough that the DB lookup can be skipped.
+    pub fn is_known_missing(&self, key: &str) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.recorded.get(key) {
+            Some(at) if at.elapsed() < self.ttl => true,
+            Some(_) => {
+                entries.recorded.remove(key);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Remember that `key` matched no project.
+    pub fn record_miss(&self, key: &str) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        let now = Instant::now();
+        entries.recorded.insert(key.to_string(), now);
+        entries.order.push_back((key.to_string(), now));
+
+        while entries.recorded.len() > self.capacity {
+            let Some((oldest, at)) = entries.order.pop_front() else {
+                break;
+            };
+            // Skip queue entries superseded by a later miss for the same key
+            if entries.recorded.get(&oldest) == Some(&at) {
+                entries.recorded.remove(&oldest);
+            }
+        }
+        if entries.order.len() > self.capacity * 2 {
+            entries.compact();
+        }
+    }
+
+    /// Forget a cached miss (the key now belongs to a project).
+    pub fn invalidate(&self, key: &str) {
+        self.entries.lock().unwrap().recorded.remove(key);
+    }
+
+    /// Forget every cached miss.
+    pub fn clear(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.recorded.clear();
+        entries.order.clear();
+    }
+
+    /// Number of keys currently cached as missing (including expired ones
+    /// not yet cleaned up).
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().recorded.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop expired entries to prevent memory growth.
+    /// Call periodically (e.g., every few minutes).
+    pub fn cleanup(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        let ttl = self.ttl;
+        entries.recorded.retain(|_, at| at.elapsed() < ttl);
+        entries.compact();
+    }
+}
+
+impl Default for ProjectMissCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_MISS_CAPACITY, DEFAULT_MISS_TTL)
+    }
+}
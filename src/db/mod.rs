@@ -1,22 +1,39 @@
+pub mod audit_chain;
+mod email_column;
 mod from_row;
+pub mod license_refs;
+mod miss_cache;
 pub mod migrations;
+pub mod outbox;
 pub mod queries;
+pub mod reference_numbers;
+pub mod residency;
 mod schema;
 pub mod soft_delete;
 
+pub use email_column::{EmailColumn, HasEmail};
 pub use migrations::{run_migrations, MigrationError, MigrationTarget};
-pub use schema::{init_audit_db, init_db};
+pub use miss_cache::ProjectMissCache;
+pub use residency::OrgDbRegistry;
+pub use schema::{init_audit_db, init_db, init_org_db};
 
 use std::sync::Arc;
 
-use r2d2::Pool;
+use r2d2::{Pool, PooledConnection};
 use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::Connection;
 
+use crate::backup::Backups;
 use crate::config::TrustedIssuer;
 use crate::crypto::{EmailHasher, MasterKey};
 use crate::email::EmailService;
+use crate::error;
 use crate::jwt::JwksCache;
-use crate::rate_limit::ActivationRateLimiter;
+use crate::middleware::MaintenanceMode;
+use crate::models::{AuditFailureMode, Project};
+use crate::payments::ProviderCallGovernor;
+use crate::rate_limit::{ActivationRateLimiter, ValidationRateLimiter};
+use crate::util::Clock;
 
 pub type DbPool = Pool<SqliteConnectionManager>;
 
@@ -31,10 +48,17 @@ pub struct AppState {
     pub base_url: String,
     /// Whether audit logging is enabled
     pub audit_log_enabled: bool,
+    /// What a request does when its audit entry can't be written (AUDIT_FAILURE_MODE)
+    pub audit_failure_mode: AuditFailureMode,
+    /// Days public audit entries are kept for orgs without their own policy
+    /// (PUBLIC_AUDIT_LOG_RETENTION_DAYS, 0 = forever)
+    pub public_audit_retention_days: i64,
     /// Master key for envelope encryption of project private keys
     pub master_key: MasterKey,
     /// Email hasher with stable HMAC key (survives master key rotation)
     pub email_hasher: EmailHasher,
+    /// Store user emails encrypted, looked up by hash (PII_MINIMIZATION)
+    pub pii_minimization: bool,
     /// URL for the success page after payment (when no project redirect is configured)
     pub success_page_url: String,
     /// Rate limiter for activation code requests (per email)
@@ -45,6 +69,116 @@ pub struct AppState {
     pub jwks_cache: Arc<JwksCache>,
     /// Trusted JWT issuers for first-party app authentication
     pub trusted_issuers: Vec<TrustedIssuer>,
+    /// Dedicated per-org database pools (data residency mode)
+    pub org_dbs: Arc<OrgDbRegistry>,
+    /// Public keys that recently matched no project (skips the DB on repeat misses)
+    pub project_misses: Arc<ProjectMissCache>,
+    /// Current time for webhook processing (fixed in tests)
+    pub clock: Clock,
+    /// Per-org and global limits on outbound payment provider calls
+    pub provider_calls: Arc<ProviderCallGovernor>,
+    /// Per-license `/validate` counters (project `max_validations_per_hour_per_license`)
+    pub validation_limiter: Arc<ValidationRateLimiter>,
+    /// Free license claims through /buy (per client IP and per email)
+    pub free_license_limiter: Arc<ActivationRateLimiter>,
+    /// Long polls on /buy/wait (per payment session and per client IP)
+    pub session_wait_limiter: Arc<ActivationRateLimiter>,
+    /// Accept localhost URLs for project webhooks and redirects (PAYCHECK_ALLOW_LOCALHOST_URLS)
+    pub allow_localhost_urls: bool,
+    /// Maintenance mode switch, checked by the `maintenance_gate` middleware
+    pub maintenance: Arc<MaintenanceMode>,
+    /// Encrypted database backups (None unless PAYCHECK_BACKUP_DIR is set)
+    pub backups: Option<Arc<Backups>>,
+}
+
+impl AppState {
+    /// Codec for the `users.email` column in the configured storage mode.
+    pub fn emails(&self) -> EmailColumn {
+        EmailColumn::new(
+            self.master_key.clone(),
+            self.email_hasher.clone(),
+            self.pii_minimization,
+        )
+    }
+
+    /// Pool holding an org's tenant data: its dedicated file, or the shared database.
+    pub fn org_db(&self, org_id: &str) -> DbPool {
+        self.org_dbs.get(org_id).unwrap_or_else(|| self.db.clone())
+    }
+
+    /// Pool holding a project's data, resolved via the shared project routes.
+    pub fn project_db(&self, project_id: &str) -> error::Result<DbPool> {
+        if !self.org_dbs.is_enabled() {
+            return Ok(self.db.clone());
+        }
+        let conn = self.db.get()?;
+        Ok(match queries::get_project_route_org_id(&conn, project_id)? {
+            Some(org_id) => self.org_db(&org_id),
+            None => self.db.clone(),
+        })
+    }
+
+    /// Pool holding the project with the given public key.
+    pub fn public_key_db(&self, public_key: &str) -> error::Result<DbPool> {
+        if !self.org_dbs.is_enabled() {
+            return Ok(self.db.clone());
+        }
+        let conn = self.db.get()?;
+        Ok(match queries::get_public_key_route_org_id(&conn, public_key)? {
+            Some(org_id) => self.org_db(&org_id),
+            None => self.db.clone(),
+        })
+    }
+
+    /// Look up a project by public key for a public endpoint, along with a
+    /// connection to the database holding it. Keys that recently matched no
+    /// project are answered from `project_misses` without querying.
+    pub fn project_by_public_key(
+        &self,
+        public_key: &str,
+    ) -> error::Result<Option<(PooledConnection<SqliteConnectionManager>, Project)>> {
+        if self.project_misses.is_known_missing(public_key) {
+            return Ok(None);
+        }
+        let conn = self.public_key_db(public_key)?.get()?;
+        match queries::get_project_by_public_key(&conn, public_key)? {
+            Some(project) => Ok(Some((conn, project))),
+            None => {
+                self.project_misses.record_miss(public_key);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Pool holding the given product (public endpoints that only know a product ID).
+    pub fn product_db(&self, product_id: &str) -> error::Result<DbPool> {
+        self.find_db(|conn| Ok(queries::get_product_by_id(conn, product_id)?.is_some()))
+    }
+
+    /// Shared pool followed by every dedicated org pool (for maintenance jobs).
+    pub fn tenant_pools(&self) -> Vec<DbPool> {
+        std::iter::once(self.db.clone())
+            .chain(self.org_dbs.dedicated_pools().into_iter().map(|(_, pool)| pool))
+            .collect()
+    }
+
+    /// Find the pool where `probe` matches, checking the shared database first.
+    /// For lookups that only know a child ID (product, payment session, subscription).
+    /// Falls back to the shared pool when nothing matches.
+    pub fn find_db(
+        &self,
+        probe: impl Fn(&Connection) -> error::Result<bool>,
+    ) -> error::Result<DbPool> {
+        if probe(&*self.db.get()?)? {
+            return Ok(self.db.clone());
+        }
+        for (_, pool) in self.org_dbs.dedicated_pools() {
+            if probe(&*pool.get()?)? {
+                return Ok(pool);
+            }
+        }
+        Ok(self.db.clone())
+    }
 }
 
 pub fn create_pool(database_path: &str) -> Result<DbPool, r2d2::Error> {
@@ -0,0 +1,160 @@
+//! Transactional outbox for audit log entries.
+//!
+//! Audit logs live in their own database, so a handler that commits a change
+//! and then saves its audit entry loses the entry if it dies in between.
+//! [`with_audited_tx`] writes the entries to `audit_outbox` in the same
+//! transaction as the change instead, and [`relay`] copies them to the audit
+//! database afterwards:
+//!
+//! - Handlers relay right after committing, so entries normally show up at once
+//! - The maintenance task and server startup relay whatever is left
+//!
+//! Entries keep the ID and timestamp they were built with, and the audit
+//! insert ignores IDs it already has, so relaying the same row twice (two
+//! relays racing, or a crash before the row was marked) stores it once.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rusqlite::{Connection, Transaction, params};
+
+use super::{AppState, queries};
+use crate::error::Result;
+use crate::models::AuditLog;
+
+/// Rows copied per relay query.
+const RELAY_BATCH_SIZE: i64 = 500;
+
+/// How long relayed rows are kept before [`relay_all`] purges them.
+const RELAYED_RETENTION_SECS: i64 = 86400;
+
+/// Get current Unix timestamp in seconds.
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+/// Run `f` in a transaction and queue the audit entries built from its result
+/// in the same transaction. Nothing is queued if `f` fails.
+///
+/// Build entries with [`crate::util::AuditLogBuilder::entry`], which returns
+/// None when audit logging is disabled. Call [`relay`] after this returns to
+/// copy them to the audit database.
+///
+/// ```ignore
+/// let license = outbox::with_audited_tx(
+///     &mut conn,
+///     |tx| queries::create_license(tx, &project.id, &product.id, &input),
+///     |license| {
+///         AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+///             .action(AuditAction::CreateLicense)
+///             .resource("license", &license.id)
+///             .entry()
+///     },
+/// )?;
+/// outbox::relay(&conn, &audit_conn);
+/// ```
+pub fn with_audited_tx<T, E>(
+    conn: &mut Connection,
+    f: impl FnOnce(&Transaction) -> Result<T>,
+    audit: impl FnOnce(&T) -> E,
+) -> Result<T>
+where
+    E: IntoIterator<Item = AuditLog>,
+{
+    try_with_audited_tx(conn, f, |value| Ok(audit(value)))
+}
+
+/// [`with_audited_tx`] for sensitive actions, whose entries are built with
+/// [`crate::util::AuditLogBuilder::checked_entry`]. If building them fails
+/// (the audit write failed under `AUDIT_FAILURE_MODE=fail_request`), the
+/// transaction rolls back and the error is returned.
+pub fn try_with_audited_tx<T, E>(
+    conn: &mut Connection,
+    f: impl FnOnce(&Transaction) -> Result<T>,
+    audit: impl FnOnce(&T) -> Result<E>,
+) -> Result<T>
+where
+    E: IntoIterator<Item = AuditLog>,
+{
+    let tx = conn.transaction()?;
+    let value = f(&tx)?;
+    for entry in audit(&value)? {
+        enqueue(&tx, &entry)?;
+    }
+    tx.commit()?;
+    Ok(value)
+}
+
+/// Queue an audit entry in the outbox.
+pub fn enqueue(conn: &Connection, entry: &AuditLog) -> Result<()> {
+    conn.execute(
+        "INSERT INTO audit_outbox (id, entry, created_at) VALUES (?1, ?2, ?3)",
+        params![&entry.id, serde_json::to_string(entry)?, now()],
+    )?;
+    Ok(())
+}
+
+/// Number of queued entries not yet relayed.
+pub fn pending_count(conn: &Connection) -> Result<i64> {
+    Ok(conn.query_row(
+        "SELECT COUNT(*) FROM audit_outbox WHERE relayed_at IS NULL",
+        [],
+        |row| row.get(0),
+    )?)
+}
+
+/// Copy unrelayed entries from `conn`'s outbox to the audit database, oldest
+/// first, marking each relayed after it's stored. Returns how many were relayed.
+pub fn try_relay(conn: &Connection, audit_conn: &Connection) -> Result<usize> {
+    let mut relayed = 0;
+    loop {
+        let batch: Vec<(String, String)> = {
+            let mut stmt = conn.prepare(
+                "SELECT id, entry FROM audit_outbox WHERE relayed_at IS NULL
+                 ORDER BY created_at, rowid LIMIT ?1",
+            )?;
+            stmt.query_map([RELAY_BATCH_SIZE], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<rusqlite::Result<_>>()?
+        };
+
+        for (id, entry) in &batch {
+            let entry: AuditLog = serde_json::from_str(entry)?;
+            queries::insert_audit_log(audit_conn, &entry)?;
+            conn.execute(
+                "UPDATE audit_outbox SET relayed_at = ?1 WHERE id = ?2",
+                params![now(), id],
+            )?;
+        }
+
+        relayed += batch.len();
+        if (batch.len() as i64) < RELAY_BATCH_SIZE {
+            return Ok(relayed);
+        }
+    }
+}
+
+/// Relay after a handler's commit. The change is already saved, so a failure
+/// is logged rather than returned; the entries stay queued for [`relay_all`].
+pub fn relay(conn: &Connection, audit_conn: &Connection) {
+    if let Err(e) = try_relay(conn, audit_conn) {
+        tracing::warn!("Failed to relay audit outbox, will retry: {}", e);
+    }
+}
+
+/// Relay every tenant database's outbox and purge rows relayed more than a
+/// day ago. Run at startup and by the maintenance task.
+pub fn relay_all(state: &AppState) -> Result<usize> {
+    let audit_conn = state.audit.get()?;
+    let mut relayed = 0;
+    for pool in state.tenant_pools() {
+        let conn = pool.get()?;
+        relayed += try_relay(&conn, &audit_conn)?;
+        conn.execute(
+            "DELETE FROM audit_outbox WHERE relayed_at < ?1",
+            [now() - RELAYED_RETENTION_SECS],
+        )?;
+    }
+    Ok(relayed)
+}
@@ -0,0 +1,379 @@
+//! Audit log queries: writing and listing entries, and retention purges.
+
+use rusqlite::types::Value;
+use rusqlite::{Connection, params};
+
+use crate::db::audit_chain;
+use crate::db::from_row::{AUDIT_LOG_COLS, PROJECT_ACTIVITY_COLS, query_all};
+use crate::error::Result;
+use crate::models::*;
+
+use super::util::{gen_id, now};
+
+// ============ Audit Logs ============
+
+#[allow(clippy::too_many_arguments)]
+pub fn create_audit_log(
+    conn: &Connection,
+    enabled: bool,
+    actor_type: ActorType,
+    user_id: Option<&str>,
+    action: &str,
+    resource_type: &str,
+    resource_id: &str,
+    details: Option<&serde_json::Value>,
+    org_id: Option<&str>,
+    project_id: Option<&str>,
+    ip_address: Option<&str>,
+    user_agent: Option<&str>,
+    names: &AuditLogNames,
+    auth_type: Option<&str>,
+    auth_credential: Option<&str>,
+    request_id: Option<&str>,
+) -> Result<AuditLog> {
+    let log = new_audit_log(
+        actor_type,
+        user_id,
+        action,
+        resource_type,
+        resource_id,
+        details,
+        org_id,
+        project_id,
+        ip_address,
+        user_agent,
+        names,
+        auth_type,
+        auth_credential,
+        request_id,
+    );
+
+    // Skip database insert if audit logging is disabled
+    if enabled {
+        insert_audit_log(conn, &log)?;
+    }
+
+    Ok(log)
+}
+
+/// Build an audit log entry with a fresh ID and timestamp, without saving it.
+#[allow(clippy::too_many_arguments)]
+pub fn new_audit_log(
+    actor_type: ActorType,
+    user_id: Option<&str>,
+    action: &str,
+    resource_type: &str,
+    resource_id: &str,
+    details: Option<&serde_json::Value>,
+    org_id: Option<&str>,
+    project_id: Option<&str>,
+    ip_address: Option<&str>,
+    user_agent: Option<&str>,
+    names: &AuditLogNames,
+    auth_type: Option<&str>,
+    auth_credential: Option<&str>,
+    request_id: Option<&str>,
+) -> AuditLog {
+    AuditLog {
+        id: gen_id(),
+        timestamp: now(),
+        actor_type,
+        user_id: user_id.map(String::from),
+        user_email: names.user_email.clone(),
+        user_name: names.user_name.clone(),
+        action: action.to_string(),
+        resource_type: resource_type.to_string(),
+        resource_id: resource_id.to_string(),
+        resource_name: names.resource_name.clone(),
+        resource_email: names.resource_email.clone(),
+        details: details.cloned(),
+        org_id: org_id.map(String::from),
+        org_name: names.org_name.clone(),
+        project_id: project_id.map(String::from),
+        project_name: names.project_name.clone(),
+        ip_address: ip_address.map(String::from),
+        user_agent: user_agent.map(String::from),
+        auth_type: auth_type.map(String::from),
+        auth_credential: auth_credential.map(String::from),
+        request_id: request_id.map(String::from),
+    }
+}
+
+/// Insert a prepared audit log entry, keeping its ID and timestamp, at the
+/// head of its hash chain (see `audit_chain`).
+/// Returns false if an entry with that ID is already stored.
+///
+/// Takes the write lock for the read-then-append, unless the caller already
+/// has a transaction open on `conn`.
+pub fn insert_audit_log(conn: &Connection, log: &AuditLog) -> Result<bool> {
+    let tx = if conn.is_autocommit() {
+        Some(rusqlite::Transaction::new_unchecked(
+            conn,
+            rusqlite::TransactionBehavior::Immediate,
+        )?)
+    } else {
+        None
+    };
+
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM audit_logs WHERE id = ?1)",
+        [&log.id],
+        |row| row.get(0),
+    )?;
+    if exists {
+        return Ok(false);
+    }
+    audit_chain::append(conn, log)?;
+
+    if let Some(tx) = tx {
+        tx.commit()?;
+    }
+    Ok(true)
+}
+
+pub fn query_audit_logs(conn: &Connection, query: &AuditLogQuery) -> Result<(Vec<AuditLog>, i64)> {
+    let (where_clause, filter_params) = audit_log_filter(query);
+    let filter_refs: Vec<&dyn rusqlite::ToSql> = filter_params.iter().map(|b| b.as_ref()).collect();
+
+    // Get total count
+    let count_sql = format!("SELECT COUNT(*) FROM audit_logs {}", where_clause);
+    let total: i64 = conn.query_row(&count_sql, filter_refs.as_slice(), |row| row.get(0))?;
+
+    // Build SELECT query with pagination
+    let limit = query.limit();
+    let offset = query.offset();
+    let select_sql = format!(
+        "SELECT {} FROM audit_logs {} ORDER BY timestamp DESC, id DESC LIMIT ? OFFSET ?",
+        AUDIT_LOG_COLS, where_clause
+    );
+
+    // Reuse filter params and add pagination
+    let mut select_refs = filter_refs;
+    select_refs.push(&limit);
+    select_refs.push(&offset);
+    let logs = query_all(conn, &select_sql, &select_refs)?;
+
+    Ok((logs, total))
+}
+
+/// One page of the audit logs matching `query` (its limit and offset are
+/// ignored), newest first, starting after the `(timestamp, id)` of the last
+/// row of the previous page. Used by exports, which read every match.
+pub fn query_audit_logs_page(
+    conn: &Connection,
+    query: &AuditLogQuery,
+    after: Option<(i64, &str)>,
+    limit: i64,
+) -> Result<Vec<AuditLog>> {
+    let (mut where_clause, filter_params) = audit_log_filter(query);
+    let mut refs: Vec<&dyn rusqlite::ToSql> = filter_params.iter().map(|b| b.as_ref()).collect();
+    if let Some((ts, id)) = &after {
+        where_clause.push_str(" AND (timestamp < ? OR (timestamp = ? AND id < ?))");
+        refs.push(ts);
+        refs.push(ts);
+        refs.push(id);
+    }
+    refs.push(&limit);
+
+    let sql = format!(
+        "SELECT {} FROM audit_logs {} ORDER BY timestamp DESC, id DESC LIMIT ?",
+        AUDIT_LOG_COLS, where_clause
+    );
+    query_all(conn, &sql, &refs)
+}
+
+/// WHERE clause and its parameters for the filters set on `query`.
+fn audit_log_filter(query: &AuditLogQuery) -> (String, Vec<Box<dyn rusqlite::ToSql>>) {
+    let mut where_clause = String::from("WHERE 1=1");
+    let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+    let mut filter = |column: &str, value: Box<dyn rusqlite::ToSql>| {
+        where_clause.push_str(&format!(" AND {}", column));
+        params.push(value);
+    };
+
+    if let Some(ref v) = query.actor_type {
+        filter("actor_type = ?", Box::new(v.as_ref().to_string()));
+    }
+    if let Some(ref v) = query.user_id {
+        filter("user_id = ?", Box::new(v.clone()));
+    }
+    if let Some(ref v) = query.action {
+        filter("action = ?", Box::new(v.clone()));
+    }
+    if let Some(ref v) = query.resource_type {
+        filter("resource_type = ?", Box::new(v.clone()));
+    }
+    if let Some(ref v) = query.resource_id {
+        filter("resource_id = ?", Box::new(v.clone()));
+    }
+    if let Some(ref v) = query.org_id {
+        filter("org_id = ?", Box::new(v.clone()));
+    }
+    if let Some(ref v) = query.project_id {
+        filter("project_id = ?", Box::new(v.clone()));
+    }
+    if let Some(v) = query.from_timestamp {
+        filter("timestamp >= ?", Box::new(v));
+    }
+    if let Some(v) = query.to_timestamp {
+        filter("timestamp <= ?", Box::new(v));
+    }
+    if let Some(ref v) = query.auth_type {
+        filter("auth_type = ?", Box::new(v.clone()));
+    }
+    if let Some(ref v) = query.auth_credential {
+        filter("auth_credential = ?", Box::new(v.clone()));
+    }
+    if let Some(ref v) = query.request_id {
+        filter("request_id = ?", Box::new(v.clone()));
+    }
+
+    match query.project_ids.as_deref() {
+        Some([]) => where_clause.push_str(" AND 0"),
+        Some(ids) => {
+            where_clause.push_str(&format!(
+                " AND project_id IN ({})",
+                vec!["?"; ids.len()].join(", ")
+            ));
+            for id in ids {
+                params.push(Box::new(id.clone()));
+            }
+        }
+        None => {}
+    }
+
+    (where_clause, params)
+}
+
+/// One page of an org's audit logs in `[from, to]`, oldest first, starting
+/// after the `(timestamp, id)` of the last row of the previous page.
+/// Keyset pagination keeps archive pages stable while new entries are written.
+pub fn list_org_audit_logs_page(
+    conn: &Connection,
+    org_id: &str,
+    from: i64,
+    to: i64,
+    after: Option<(i64, &str)>,
+    limit: i64,
+) -> Result<Vec<AuditLog>> {
+    let (after_ts, after_id) = after.unwrap_or((i64::MIN, ""));
+    query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM audit_logs
+             WHERE org_id = ?1 AND timestamp >= ?2 AND timestamp <= ?3
+               AND (timestamp > ?4 OR (timestamp = ?4 AND id > ?5))
+             ORDER BY timestamp ASC, id ASC LIMIT ?6",
+            AUDIT_LOG_COLS
+        ),
+        params![org_id, from, to, after_ts, after_id, limit],
+    )
+}
+
+/// A project's latest changes by org members, newest first. Public and
+/// system entries (webhooks, activations, maintenance) are left out.
+/// Served by `idx_audit_logs_project_activity`.
+pub fn list_recent_project_activity(
+    conn: &Connection,
+    project_id: &str,
+    limit: i64,
+) -> Result<Vec<ProjectActivity>> {
+    query_all(
+        conn,
+        &recent_project_activity_sql(),
+        params![project_id, limit],
+    )
+}
+
+fn recent_project_activity_sql() -> String {
+    format!(
+        "SELECT {} FROM audit_logs
+         WHERE project_id = ?1 AND actor_type = 'user'
+         ORDER BY timestamp DESC, id DESC LIMIT ?2",
+        PROJECT_ACTIVITY_COLS
+    )
+}
+
+/// Latest audit log timestamp for each of `org_ids` that has any entries.
+pub fn get_orgs_last_audit_at(
+    conn: &Connection,
+    org_ids: &[&str],
+) -> Result<std::collections::HashMap<String, i64>> {
+    if org_ids.is_empty() {
+        return Ok(Default::default());
+    }
+
+    let placeholders: Vec<String> = (1..=org_ids.len()).map(|i| format!("?{}", i)).collect();
+    let sql = format!(
+        "SELECT org_id, MAX(timestamp) FROM audit_logs WHERE org_id IN ({}) GROUP BY org_id",
+        placeholders.join(", ")
+    );
+
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt
+        .query_map(rusqlite::params_from_iter(org_ids), |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })?
+        .collect::<std::result::Result<_, _>>()?;
+    Ok(rows)
+}
+
+// ============ Audit Log Maintenance ============
+
+/// Purge audit logs past their org's retention. Public (end-user) entries
+/// follow the org's `public_audit_retention_days`, or `default_public_days`
+/// (PUBLIC_AUDIT_LOG_RETENTION_DAYS, 0 = forever) for orgs without one and
+/// entries without an org. Internal entries (operator, org_member, system)
+/// are only purged for orgs that set `admin_audit_retention_days`.
+/// Purged entries leave the hash chains verifiable (see `audit_chain::remove_where`).
+/// Returns the number of deleted records.
+/// Called on startup with the orgs from `list_org_audit_retention`.
+pub fn purge_old_audit_logs(
+    conn: &Connection,
+    default_public_days: i64,
+    orgs: &[OrgAuditRetention],
+) -> Result<usize> {
+    let now = now();
+    let cutoff = |days: i64| Value::Integer(now - days * 86400);
+    let tx = rusqlite::Transaction::new_unchecked(conn, rusqlite::TransactionBehavior::Immediate)?;
+
+    let mut deleted = 0;
+    for org in orgs {
+        let org_id = Value::Text(org.org_id.clone());
+        for (chain, days) in [
+            (audit_chain::PUBLIC_CHAIN, org.public_days),
+            (audit_chain::INTERNAL_CHAIN, org.admin_days),
+        ] {
+            if let Some(days) = days {
+                deleted += audit_chain::remove_where(
+                    &tx,
+                    chain,
+                    "org_id = ? AND timestamp < ?",
+                    &[org_id.clone(), cutoff(days)],
+                )?;
+            }
+        }
+    }
+
+    if default_public_days > 0 {
+        let mut params = vec![cutoff(default_public_days)];
+        params.extend(
+            orgs.iter()
+                .filter(|org| org.public_days.is_some())
+                .map(|org| Value::Text(org.org_id.clone())),
+        );
+        let own_policy = vec!["?"; params.len() - 1].join(", ");
+        deleted += audit_chain::remove_where(
+            &tx,
+            audit_chain::PUBLIC_CHAIN,
+            &format!(
+                "timestamp < ? AND (org_id IS NULL OR org_id NOT IN ({}))",
+                own_policy
+            ),
+            &params,
+        )?;
+    }
+
+    tx.commit()?;
+    Ok(deleted)
+}
@@ -0,0 +1,382 @@
+//! Device queries, including device limit enforcement on activation.
+
+use rusqlite::{Connection, ToSql, params, types::Value};
+
+use crate::db::from_row::{DEVICE_COLS, query_all, query_one};
+use crate::error::{AppError, Result};
+use crate::models::*;
+
+use super::util::{gen_id, now};
+
+// ============ Devices ============
+
+/// Result of attempting to acquire a device for a license
+pub enum DeviceAcquisitionResult {
+    /// Returned an existing device (already activated on this device_id)
+    Existing(Device),
+    /// Created a new device successfully
+    Created(Device),
+}
+
+/// Atomically acquire a device for a license, enforcing device and activation limits.
+///
+/// For team licenses, `seat_id` scopes the device limit to the seat: only devices
+/// activated under the same seat count. Devices activated without a seat share
+/// one pool, which is the whole license for single-user licenses.
+///
+/// This function uses a transaction with IMMEDIATE mode (SQLite) to prevent race conditions
+/// where multiple concurrent requests could bypass the device limit.
+///
+/// # PostgreSQL Migration Note
+/// When migrating to PostgreSQL, add `FOR UPDATE` to the license SELECT query to achieve
+/// the same row-level locking behavior. SQLite's IMMEDIATE transaction provides this
+/// implicitly by serializing all writes.
+#[allow(clippy::too_many_arguments)]
+pub fn acquire_device_atomic(
+    conn: &mut Connection,
+    license_id: &str,
+    device_id: &str,
+    device_type: DeviceType,
+    jti: &str,
+    name: Option<&str>,
+    seat_id: Option<&str>,
+    signed_with_kid: Option<&str>,
+    device_limit: Option<i32>,
+    activation_limit: Option<i32>,
+    device_inactive_days: Option<i32>,
+) -> Result<DeviceAcquisitionResult> {
+    // Use IMMEDIATE to acquire write lock at transaction start, preventing TOCTOU races
+    let tx = conn.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
+
+    // Check if device already exists for this license
+    let existing_device: Option<Device> = query_one(
+        &tx,
+        &format!(
+            "SELECT {} FROM devices WHERE license_id = ?1 AND device_id = ?2",
+            DEVICE_COLS
+        ),
+        &[&license_id, &device_id],
+    )?;
+
+    if let Some(device) = existing_device {
+        // Re-activated by another seat holder: the device moves to their seat,
+        // so it has to fit under that seat's device limit
+        if device.seat_id.as_deref() != seat_id {
+            check_device_limit(&tx, license_id, seat_id, device_limit, device_inactive_days)?;
+        }
+
+        // Device exists - update JTI (and seat) and return
+        let now = now();
+        tx.execute(
+            "UPDATE devices SET jti = ?1, last_seen_at = ?2, seat_id = ?3, signed_with_kid = ?4 WHERE id = ?5",
+            params![jti, now, seat_id, signed_with_kid, device.id],
+        )?;
+        tx.commit()?;
+        return Ok(DeviceAcquisitionResult::Existing(Device {
+            jti: jti.to_string(),
+            last_seen_at: now,
+            seat_id: seat_id.map(String::from),
+            signed_with_kid: signed_with_kid.map(String::from),
+            ..device
+        }));
+    }
+
+    // New device - check device limit
+    check_device_limit(&tx, license_id, seat_id, device_limit, device_inactive_days)?;
+
+    // Check activation limit if set (None = unlimited)
+    if let Some(limit) = activation_limit {
+        let current_activation_count: i32 = tx.query_row(
+            "SELECT activation_count FROM licenses WHERE id = ?1",
+            params![license_id],
+            |row| row.get(0),
+        )?;
+
+        if current_activation_count >= limit {
+            return Err(AppError::Forbidden(format!(
+                "Activation limit reached ({}/{})",
+                current_activation_count, limit
+            )));
+        }
+    }
+
+    // All checks passed - create device and increment activation count
+    let id = gen_id();
+    let now = now();
+
+    tx.execute(
+        "INSERT INTO devices (id, license_id, device_id, device_type, name, jti, activated_at, last_seen_at, seat_id, signed_with_kid)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![&id, license_id, device_id, device_type.as_ref(), name, jti, now, now, seat_id, signed_with_kid],
+    )?;
+
+    tx.execute(
+        "UPDATE licenses SET activation_count = activation_count + 1, dormant_since = NULL WHERE id = ?1",
+        params![license_id],
+    )?;
+
+    tx.commit()?;
+
+    Ok(DeviceAcquisitionResult::Created(Device {
+        id,
+        license_id: license_id.to_string(),
+        device_id: device_id.to_string(),
+        device_type,
+        name: name.map(String::from),
+        jti: jti.to_string(),
+        activated_at: now,
+        last_seen_at: now,
+        seat_id: seat_id.map(String::from),
+        signed_with_kid: signed_with_kid.map(String::from),
+    }))
+}
+
+/// Fail if the devices under `seat_id` (the seatless pool when None) already
+/// fill `device_limit` (None = unlimited).
+fn check_device_limit(
+    conn: &Connection,
+    license_id: &str,
+    seat_id: Option<&str>,
+    device_limit: Option<i32>,
+    device_inactive_days: Option<i32>,
+) -> Result<()> {
+    let Some(limit) = device_limit else {
+        return Ok(());
+    };
+
+    // If device_inactive_days is set, only count devices seen within that threshold
+    let current_device_count: i32 = if let Some(inactive_days) = device_inactive_days {
+        let cutoff = now() - (inactive_days as i64 * 86400);
+        conn.query_row(
+            "SELECT COUNT(*) FROM devices WHERE license_id = ?1 AND seat_id IS ?2 AND last_seen_at >= ?3",
+            params![license_id, seat_id, cutoff],
+            |row| row.get(0),
+        )?
+    } else {
+        conn.query_row(
+            "SELECT COUNT(*) FROM devices WHERE license_id = ?1 AND seat_id IS ?2",
+            params![license_id, seat_id],
+            |row| row.get(0),
+        )?
+    };
+
+    if current_device_count >= limit {
+        return Err(AppError::Forbidden(format!(
+            "Device limit reached ({}/{}). Deactivate a device first.",
+            current_device_count, limit
+        )));
+    }
+    Ok(())
+}
+
+/// Insert a device without checking any limits. Activation paths must use
+/// [`acquire_device_atomic`] instead; a separate count-then-insert lets
+/// concurrent activations exceed `device_limit`.
+pub fn create_device(
+    conn: &Connection,
+    license_id: &str,
+    device_id: &str,
+    device_type: DeviceType,
+    jti: &str,
+    name: Option<&str>,
+) -> Result<Device> {
+    let id = gen_id();
+    let now = now();
+
+    conn.execute(
+        "INSERT INTO devices (id, license_id, device_id, device_type, name, jti, activated_at, last_seen_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![&id, license_id, device_id, device_type.as_ref(), name, jti, now, now],
+    )?;
+
+    Ok(Device {
+        id,
+        license_id: license_id.to_string(),
+        device_id: device_id.to_string(),
+        device_type,
+        name: name.map(String::from),
+        jti: jti.to_string(),
+        activated_at: now,
+        last_seen_at: now,
+        seat_id: None,
+        signed_with_kid: None,
+    })
+}
+
+pub fn get_device_by_jti(conn: &Connection, jti: &str) -> Result<Option<Device>> {
+    query_one(
+        conn,
+        &format!("SELECT {} FROM devices WHERE jti = ?1", DEVICE_COLS),
+        &[&jti],
+    )
+}
+
+pub fn get_device_for_license(
+    conn: &Connection,
+    license_id: &str,
+    device_id: &str,
+) -> Result<Option<Device>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM devices WHERE license_id = ?1 AND device_id = ?2",
+            DEVICE_COLS
+        ),
+        &[&license_id, &device_id],
+    )
+}
+
+/// Devices in a project matching `filter`, newest first. `license_id`
+/// narrows them to one license.
+pub fn list_devices_by_filter(
+    conn: &Connection,
+    project_id: &str,
+    license_id: Option<&str>,
+    filter: &DeviceFilter,
+) -> Result<Vec<Device>> {
+    let mut conditions = vec!["l.project_id = ?".to_string()];
+    let mut values: Vec<Value> = vec![project_id.to_string().into()];
+    if let Some(license_id) = license_id {
+        conditions.push("d.license_id = ?".into());
+        values.push(license_id.to_string().into());
+    }
+    if let Some(after) = filter.activated_after {
+        conditions.push("d.activated_at >= ?".into());
+        values.push(after.into());
+    }
+    if let Some(before) = filter.activated_before {
+        conditions.push("d.activated_at < ?".into());
+        values.push(before.into());
+    }
+    if let Some(device_type) = filter.device_type {
+        conditions.push("d.device_type = ?".into());
+        values.push(device_type.as_ref().to_string().into());
+    }
+    if !filter.except_device_ids.is_empty() {
+        let placeholders = vec!["?"; filter.except_device_ids.len()].join(", ");
+        conditions.push(format!("d.device_id NOT IN ({})", placeholders));
+        values.extend(filter.except_device_ids.iter().cloned().map(Value::from));
+    }
+
+    let params: Vec<&dyn ToSql> = values.iter().map(|v| v as &dyn ToSql).collect();
+    query_all(
+        conn,
+        &format!(
+            "SELECT d.{} FROM devices d JOIN licenses l ON l.id = d.license_id
+             WHERE {} ORDER BY d.activated_at DESC",
+            DEVICE_COLS.replace(", ", ", d."),
+            conditions.join(" AND ")
+        ),
+        &params,
+    )
+}
+
+pub fn list_devices_for_license(conn: &Connection, license_id: &str) -> Result<Vec<Device>> {
+    query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM devices WHERE license_id = ?1 ORDER BY activated_at DESC",
+            DEVICE_COLS
+        ),
+        &[&license_id],
+    )
+}
+
+pub fn count_devices_for_license(conn: &Connection, license_id: &str) -> Result<i32> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM devices WHERE license_id = ?1",
+        params![license_id],
+        |row| row.get(0),
+    )
+    .map_err(Into::into)
+}
+
+/// Count devices that have been seen within the inactive_days threshold.
+/// If inactive_days is None, returns the total device count.
+pub fn count_active_devices_for_license(
+    conn: &Connection,
+    license_id: &str,
+    inactive_days: Option<i32>,
+) -> Result<i32> {
+    if let Some(days) = inactive_days {
+        let cutoff = now() - (days as i64 * 86400);
+        conn.query_row(
+            "SELECT COUNT(*) FROM devices WHERE license_id = ?1 AND last_seen_at >= ?2",
+            params![license_id, cutoff],
+            |row| row.get(0),
+        )
+        .map_err(Into::into)
+    } else {
+        count_devices_for_license(conn, license_id)
+    }
+}
+
+pub fn update_device_last_seen(conn: &Connection, id: &str) -> Result<()> {
+    let now = now();
+    conn.execute(
+        "UPDATE devices SET last_seen_at = ?1 WHERE id = ?2",
+        params![now, id],
+    )?;
+    Ok(())
+}
+
+pub fn update_device_jti(conn: &Connection, id: &str, jti: &str) -> Result<()> {
+    let now = now();
+    conn.execute(
+        "UPDATE devices SET jti = ?1, last_seen_at = ?2 WHERE id = ?3",
+        params![jti, now, id],
+    )?;
+    Ok(())
+}
+
+pub fn delete_device(conn: &Connection, id: &str) -> Result<bool> {
+    let deleted = conn.execute("DELETE FROM devices WHERE id = ?1", params![id])?;
+    Ok(deleted > 0)
+}
+
+// ============ Usage Geography ============
+
+/// Count one valid check for a product from `country` on `date` (YYYY-MM-DD).
+pub fn record_usage_geo(
+    conn: &Connection,
+    date: &str,
+    project_id: &str,
+    product_id: &str,
+    country: &str,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO usage_geo_daily (date, project_id, product_id, country, count)
+         VALUES (?1, ?2, ?3, ?4, 1)
+         ON CONFLICT (project_id, date, product_id, country) DO UPDATE SET count = count + 1",
+        params![date, project_id, product_id, country],
+    )?;
+    Ok(())
+}
+
+/// A project's daily usage by country from `from` to `to` (YYYY-MM-DD,
+/// inclusive), optionally for one product. Oldest first.
+pub fn get_usage_geo_days(
+    conn: &Connection,
+    project_id: &str,
+    from: &str,
+    to: &str,
+    product_id: Option<&str>,
+) -> Result<Vec<UsageGeoDay>> {
+    let mut stmt = conn.prepare(
+        "SELECT date, product_id, country, count FROM usage_geo_daily
+         WHERE project_id = ?1 AND date >= ?2 AND date <= ?3
+           AND (?4 IS NULL OR product_id = ?4)
+         ORDER BY date, product_id, country",
+    )?;
+    let days = stmt
+        .query_map(params![project_id, from, to, product_id], |row| {
+            Ok(UsageGeoDay {
+                date: row.get(0)?,
+                product_id: row.get(1)?,
+                country: row.get(2)?,
+                count: row.get(3)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(days)
+}
@@ -0,0 +1,2150 @@
+//! License queries: licenses and everything hanging off them (activation
+//! codes, seats, share links, prepaid codes, bulk jobs, upgrades, update
+//! renewals, tags, entitlements, and activation email logs).
+
+use std::collections::BTreeMap;
+
+use rusqlite::{Connection, OptionalExtension, params, types::Value};
+
+use crate::crypto::{MasterKey, hash_secret};
+use crate::db::from_row::{
+    ACTIVATION_CODE_COLS, BULK_JOB_CHUNK_COLS, BULK_JOB_COLS, EMAIL_LOG_COLS, FromRow,
+    LICENSE_COL_COUNT, LICENSE_COLS, LICENSE_SEAT_COLS, LICENSE_UPDATE_RENEWAL_COLS,
+    LICENSE_UPGRADE_COLS, PREPAID_CODE_BATCH_COLS, SHARE_LINK_COLS, query_all, query_one,
+};
+use crate::db::{license_refs, reference_numbers};
+use crate::error::{AppError, Result, msg};
+use crate::models::*;
+
+use super::orgs::org_data_key;
+use super::util::{gen_id, now};
+
+// ============ Licenses ============
+
+/// Generate a short-lived activation code: PREFIX-XXXX-XXXX (40 bits entropy)
+///
+/// With 30-min TTL and rate limiting, 40 bits provides adequate security
+/// (~4 billion codes, making brute force economically unviable).
+pub fn generate_activation_code(prefix: &str) -> String {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    let chars: Vec<char> = "ABCDEFGHJKLMNPQRSTUVWXYZ23456789".chars().collect();
+
+    let mut part = || -> String {
+        (0..4)
+            .map(|_| chars[rng.gen_range(0..chars.len())])
+            .collect()
+    };
+
+    format!("{}-{}-{}", prefix, part(), part())
+}
+
+/// Create a new license (no user-facing key - email hash is the identity)
+pub fn create_license(
+    conn: &Connection,
+    project_id: &str,
+    product_id: &str,
+    input: &CreateLicense,
+) -> Result<License> {
+    // Validate that at least one identifier is present for license recovery
+    let has_identifier = input.email_hash.is_some()
+        || input.customer_id.is_some()
+        || input.payment_provider_order_id.is_some();
+
+    if !has_identifier {
+        return Err(AppError::BadRequest(
+            "License must have at least one identifier: email, customer_id, or payment_provider_order_id".into(),
+        ));
+    }
+
+    let id = gen_id();
+    let now = now();
+
+    // The counter bump commits with the insert, so concurrent creations get
+    // consecutive numbers and a failed insert hands its number back
+    conn.execute_batch("SAVEPOINT create_license")?;
+    let inserted = (|| -> Result<(String, String)> {
+        let license_ref = license_refs::assign(conn, project_id, &id)?;
+        let reference_number = reference_numbers::next(conn, project_id)?;
+        conn.execute(
+            "INSERT INTO licenses (id, email_hash, project_id, product_id, customer_id, activation_count, revoked, created_at, expires_at, updates_expires_at, payment_provider, payment_provider_customer_id, payment_provider_subscription_id, payment_provider_order_id, seats, license_ref, reference_number)
+             VALUES (?1, ?2, ?3, ?4, ?5, 0, 0, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+            params![&id, &input.email_hash, project_id, product_id, &input.customer_id, now, input.expires_at, input.updates_expires_at, &input.payment_provider, &input.payment_provider_customer_id, &input.payment_provider_subscription_id, &input.payment_provider_order_id, input.seats, &license_ref, &reference_number],
+        )?;
+        Ok((license_ref, reference_number))
+    })();
+    conn.execute_batch(if inserted.is_ok() {
+        "RELEASE create_license"
+    } else {
+        "ROLLBACK TO create_license; RELEASE create_license"
+    })?;
+    let (license_ref, reference_number) = inserted?;
+
+    Ok(License {
+        id,
+        email_hash: input.email_hash.clone(),
+        project_id: project_id.to_string(),
+        product_id: product_id.to_string(),
+        customer_id: input.customer_id.clone(),
+        activation_count: 0,
+        revoked: false,
+        created_at: now,
+        expires_at: input.expires_at,
+        updates_expires_at: input.updates_expires_at,
+        payment_provider: input.payment_provider.clone(),
+        payment_provider_customer_id: input.payment_provider_customer_id.clone(),
+        payment_provider_subscription_id: input.payment_provider_subscription_id.clone(),
+        payment_provider_order_id: input.payment_provider_order_id.clone(),
+        deleted_at: None,
+        deleted_cascade_depth: None,
+        paused_at: None,
+        paused_seconds: 0,
+        seats: input.seats,
+        abuse_flags: 0,
+        abuse_flagged_at: None,
+        abuse_distinct_ips: None,
+        revoked_reason: None,
+        revoked_message: None,
+        revoked_at: None,
+        revoked_by: None,
+        checkout_fields: BTreeMap::new(),
+        suspended_for_dispute: false,
+        is_trial: false,
+        converted_from_license_id: None,
+        accepted_terms_version: None,
+        license_ref: Some(license_ref),
+        metadata: BTreeMap::new(),
+        dormant_since: None,
+        reference_number: Some(reference_number),
+        first_seen_version: None,
+        last_seen_version: None,
+        last_seen_version_at: None,
+    })
+}
+
+/// The license ID behind `id_or_ref`, which may be either a license ID or a
+/// license ref in the project. Unknown refs come back unchanged, so callers'
+/// own lookups report them as not found.
+pub fn resolve_license_id(conn: &Connection, project_id: &str, id_or_ref: &str) -> Result<String> {
+    if !license_refs::is_license_ref(id_or_ref) {
+        return Ok(id_or_ref.to_string());
+    }
+    let id: Option<String> = conn
+        .query_row(
+            "SELECT id FROM licenses WHERE project_id = ?1 AND license_ref = ?2",
+            params![project_id, id_or_ref],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(id.unwrap_or_else(|| id_or_ref.to_string()))
+}
+
+/// The license ID behind `value` on an admin endpoint, which may be a
+/// license ID, license ref, or reference number in the project. Reference
+/// numbers are guessable, so public endpoints use [`resolve_license_id`].
+pub fn resolve_admin_license_id(
+    conn: &Connection,
+    project_id: &str,
+    value: &str,
+) -> Result<String> {
+    if reference_numbers::is_reference_number(value) {
+        let id: Option<String> = conn
+            .query_row(
+                "SELECT id FROM licenses WHERE project_id = ?1 AND reference_number = ?2",
+                params![project_id, reference_numbers::normalize(value)],
+                |row| row.get(0),
+            )
+            .optional()?;
+        // A UUID can end in twelve digits, so no match falls through to the ID
+        if let Some(id) = id {
+            return Ok(id);
+        }
+    }
+    resolve_license_id(conn, project_id, value)
+}
+
+pub fn get_license_by_id(conn: &Connection, id: &str) -> Result<Option<License>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM licenses WHERE id = ?1 AND deleted_at IS NULL",
+            LICENSE_COLS
+        ),
+        &[&id],
+    )
+}
+
+/// Look up an active (non-revoked, non-expired) license by email hash and project.
+pub fn get_license_by_email_hash(
+    conn: &Connection,
+    project_id: &str,
+    email_hash: &str,
+) -> Result<Option<License>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM licenses WHERE project_id = ?1 AND email_hash = ?2 AND revoked = 0 AND deleted_at IS NULL AND (expires_at IS NULL OR expires_at > unixepoch())",
+            LICENSE_COLS
+        ),
+        &[&project_id, &email_hash],
+    )
+}
+
+/// Look up all active (non-revoked, non-expired) licenses by email hash and project.
+/// Used when a user may have multiple licenses (e.g., bought multiple products).
+pub fn get_licenses_by_email_hash(
+    conn: &Connection,
+    project_id: &str,
+    email_hash: &str,
+) -> Result<Vec<License>> {
+    query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM licenses WHERE project_id = ?1 AND email_hash = ?2 AND revoked = 0 AND deleted_at IS NULL AND (expires_at IS NULL OR expires_at > unixepoch()) ORDER BY created_at DESC",
+            LICENSE_COLS
+        ),
+        &[&project_id, &email_hash],
+    )
+}
+
+/// The newest active license for `product_id` held by the buyer, matched by
+/// customer ID or email hash (for duplicate purchase detection in /buy).
+pub fn find_active_license_for_buyer(
+    conn: &Connection,
+    product_id: &str,
+    customer_id: Option<&str>,
+    email_hash: Option<&str>,
+    now: i64,
+) -> Result<Option<License>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM licenses WHERE product_id = ?1 AND (customer_id = ?2 OR email_hash = ?3) AND revoked = 0 AND deleted_at IS NULL AND (expires_at IS NULL OR expires_at > ?4) ORDER BY created_at DESC LIMIT 1",
+            LICENSE_COLS
+        ),
+        &[&product_id, &customer_id, &email_hash, &now],
+    )
+}
+
+/// Whether `email_hash` already claimed a free license for this product.
+/// Revoked and deleted claims count, so a free license can't be claimed again.
+pub fn free_license_claimed(conn: &Connection, product_id: &str, email_hash: &str) -> Result<bool> {
+    Ok(conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM licenses WHERE product_id = ?1 AND email_hash = ?2 AND payment_provider = 'free')",
+        params![product_id, email_hash],
+        |row| row.get(0),
+    )?)
+}
+
+/// Look up ALL licenses by email hash and project (for admin support) with pagination.
+/// Includes expired and revoked licenses so support can see full history.
+/// Note: Excludes soft-deleted licenses.
+pub fn get_all_licenses_by_email_hash_for_admin_paginated(
+    conn: &Connection,
+    project_id: &str,
+    email_hash: &str,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<LicenseWithProduct>, i64)> {
+    // Get total count
+    let total: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM licenses WHERE project_id = ?1 AND email_hash = ?2 AND deleted_at IS NULL",
+        params![project_id, email_hash],
+        |row| row.get(0),
+    )?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT l.{}, p.name
+         FROM licenses l
+         JOIN products p ON l.product_id = p.id
+         WHERE l.project_id = ?1 AND l.email_hash = ?2 AND l.deleted_at IS NULL
+         ORDER BY l.created_at DESC, l.id DESC
+         LIMIT ?3 OFFSET ?4",
+        LICENSE_COLS.replace(", ", ", l.")
+    ))?;
+
+    let rows = stmt
+        .query_map(params![project_id, email_hash, limit, offset], |row| {
+            Ok(LicenseWithProduct {
+                license: License::from_row(row)?,
+                product_name: row.get(LICENSE_COL_COUNT)?,
+                tags: Vec::new(),
+            })
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok((rows, total))
+}
+
+pub fn list_licenses_for_project_paginated(
+    conn: &Connection,
+    project_id: &str,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<LicenseWithProduct>, i64)> {
+    // Get total count
+    let total: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM licenses WHERE project_id = ?1 AND deleted_at IS NULL",
+        params![project_id],
+        |row| row.get(0),
+    )?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT l.{}, p.name
+         FROM licenses l
+         JOIN products p ON l.product_id = p.id
+         WHERE l.project_id = ?1 AND l.deleted_at IS NULL
+         ORDER BY l.created_at DESC, l.id DESC
+         LIMIT ?2 OFFSET ?3",
+        LICENSE_COLS.replace(", ", ", l.")
+    ))?;
+
+    let rows = stmt
+        .query_map(params![project_id, limit, offset], |row| {
+            Ok(LicenseWithProduct {
+                license: License::from_row(row)?,
+                product_name: row.get(LICENSE_COL_COUNT)?,
+                tags: Vec::new(),
+            })
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok((rows, total))
+}
+
+pub fn list_licenses_for_project(
+    conn: &Connection,
+    project_id: &str,
+) -> Result<Vec<LicenseWithProduct>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT l.{}, p.name
+         FROM licenses l
+         JOIN products p ON l.product_id = p.id
+         WHERE l.project_id = ?1 AND l.deleted_at IS NULL
+         ORDER BY l.created_at DESC",
+        LICENSE_COLS.replace(", ", ", l.")
+    ))?;
+
+    let rows = stmt
+        .query_map(params![project_id], |row| {
+            Ok(LicenseWithProduct {
+                license: License::from_row(row)?,
+                product_name: row.get(LICENSE_COL_COUNT)?,
+                tags: Vec::new(),
+            })
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok(rows)
+}
+
+pub fn increment_activation_count(conn: &Connection, id: &str) -> Result<()> {
+    conn.execute(
+        "UPDATE licenses SET activation_count = activation_count + 1, dormant_since = NULL WHERE id = ?1",
+        params![id],
+    )?;
+    Ok(())
+}
+
+/// Revoke a license, recording why and by whom. `revoked_by` is the acting
+/// user ID, or the payment provider name for webhook revokes.
+pub fn revoke_license(
+    conn: &Connection,
+    id: &str,
+    revoke: &RevokeLicense,
+    revoked_by: Option<&str>,
+) -> Result<bool> {
+    let affected = conn.execute(
+        "UPDATE licenses SET revoked = 1, revoked_reason = ?2, revoked_message = ?3, revoked_at = ?4, revoked_by = ?5 WHERE id = ?1",
+        params![id, revoke.reason.as_ref(), revoke.message(), now(), revoked_by],
+    )?;
+    Ok(affected > 0)
+}
+
+/// Soft delete a license. No cascade needed (devices/codes use FK CASCADE for hard delete).
+pub fn soft_delete_license(conn: &Connection, id: &str) -> Result<bool> {
+    use crate::db::soft_delete::soft_delete_entity;
+    Ok(soft_delete_entity(conn, "licenses", id)?.deleted)
+}
+
+/// Get a soft-deleted license by ID (for restore operations).
+pub fn get_deleted_license_by_id(conn: &Connection, id: &str) -> Result<Option<License>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM licenses WHERE id = ?1 AND deleted_at IS NOT NULL",
+            LICENSE_COLS
+        ),
+        &[&id],
+    )
+}
+
+/// Restore a soft-deleted license.
+/// Returns Err if depth > 0 and force=false (was cascaded from product/project/org delete).
+pub fn restore_license(conn: &Connection, id: &str, force: bool) -> Result<bool> {
+    use crate::db::soft_delete::{check_restore_allowed, restore_entity};
+
+    let Some(license) = get_deleted_license_by_id(conn, id)? else {
+        return Ok(false);
+    };
+
+    check_restore_allowed(license.deleted_cascade_depth, force, "License")?;
+    restore_entity(conn, "licenses", id)?;
+
+    Ok(true)
+}
+
+pub fn add_revoked_jti(
+    conn: &Connection,
+    license_id: &str,
+    jti: &str,
+    details: Option<&str>,
+) -> Result<()> {
+    let now = now();
+    conn.execute(
+        "INSERT OR IGNORE INTO revoked_jtis (jti, license_id, revoked_at, details) VALUES (?1, ?2, ?3, ?4)",
+        params![jti, license_id, now, details],
+    )?;
+    Ok(())
+}
+
+/// Revoke the tokens of all of a license's devices. Returns how many were
+/// newly revoked.
+pub fn revoke_device_jtis(conn: &Connection, license_id: &str, details: &str) -> Result<usize> {
+    let now = now();
+    Ok(conn.execute(
+        "INSERT OR IGNORE INTO revoked_jtis (jti, license_id, revoked_at, details)
+         SELECT jti, license_id, ?2, ?3 FROM devices WHERE license_id = ?1",
+        params![license_id, now, details],
+    )?)
+}
+
+/// Check if a JTI has been revoked.
+/// JTIs are globally unique UUIDs, so no need to scope by license_id.
+pub fn is_jti_revoked(conn: &Connection, jti: &str) -> Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM revoked_jtis WHERE jti = ?1",
+        params![jti],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// The license's revoked JTIs, oldest first.
+pub fn list_revoked_jtis(conn: &Connection, license_id: &str) -> Result<Vec<RevokedJti>> {
+    let mut stmt = conn.prepare(
+        "SELECT jti, license_id, revoked_at, details FROM revoked_jtis
+         WHERE license_id = ?1 ORDER BY revoked_at, jti",
+    )?;
+    let rows = stmt
+        .query_map(params![license_id], |row| {
+            Ok(RevokedJti {
+                jti: row.get(0)?,
+                license_id: row.get(1)?,
+                revoked_at: row.get(2)?,
+                details: row.get(3)?,
+            })
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// Look up licenses by payment provider order ID (for admin support via receipt).
+/// Includes expired and revoked licenses so support can see full history.
+/// Note: Excludes soft-deleted licenses.
+pub fn get_licenses_by_payment_order_id_paginated(
+    conn: &Connection,
+    project_id: &str,
+    payment_provider_order_id: &str,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<LicenseWithProduct>, i64)> {
+    // Get total count
+    let total: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM licenses WHERE project_id = ?1 AND payment_provider_order_id = ?2 AND deleted_at IS NULL",
+        params![project_id, payment_provider_order_id],
+        |row| row.get(0),
+    )?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT l.{}, p.name
+         FROM licenses l
+         JOIN products p ON l.product_id = p.id
+         WHERE l.project_id = ?1 AND l.payment_provider_order_id = ?2 AND l.deleted_at IS NULL
+         ORDER BY l.created_at DESC, l.id DESC
+         LIMIT ?3 OFFSET ?4",
+        LICENSE_COLS.replace(", ", ", l.")
+    ))?;
+
+    let rows = stmt
+        .query_map(
+            params![project_id, payment_provider_order_id, limit, offset],
+            |row| {
+                Ok(LicenseWithProduct {
+                    license: License::from_row(row)?,
+                    product_name: row.get(LICENSE_COL_COUNT)?,
+                    tags: Vec::new(),
+                })
+            },
+        )?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok((rows, total))
+}
+
+/// Get the license with a reference number (e.g. read out by a customer on
+/// the phone) in a project, as a page like the other support lookups.
+pub fn get_licenses_by_reference_number_paginated(
+    conn: &Connection,
+    project_id: &str,
+    reference_number: &str,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<LicenseWithProduct>, i64)> {
+    let reference_number = reference_numbers::normalize(reference_number);
+    let total: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM licenses WHERE project_id = ?1 AND reference_number = ?2 AND deleted_at IS NULL",
+        params![project_id, &reference_number],
+        |row| row.get(0),
+    )?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT l.{}, p.name
+         FROM licenses l
+         JOIN products p ON l.product_id = p.id
+         WHERE l.project_id = ?1 AND l.reference_number = ?2 AND l.deleted_at IS NULL
+         LIMIT ?3 OFFSET ?4",
+        LICENSE_COLS.replace(", ", ", l.")
+    ))?;
+
+    let rows = stmt
+        .query_map(
+            params![project_id, &reference_number, limit, offset],
+            |row| {
+                Ok(LicenseWithProduct {
+                    license: License::from_row(row)?,
+                    product_name: row.get(LICENSE_COL_COUNT)?,
+                    tags: Vec::new(),
+                })
+            },
+        )?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok((rows, total))
+}
+
+/// Get licenses by developer-managed customer ID for a project (paginated).
+/// Use this to find all licenses linked to a customer in your own system.
+pub fn get_licenses_by_customer_id_paginated(
+    conn: &Connection,
+    project_id: &str,
+    customer_id: &str,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<LicenseWithProduct>, i64)> {
+    // Get total count
+    let total: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM licenses WHERE project_id = ?1 AND customer_id = ?2 AND deleted_at IS NULL",
+        params![project_id, customer_id],
+        |row| row.get(0),
+    )?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT l.{}, p.name
+         FROM licenses l
+         JOIN products p ON l.product_id = p.id
+         WHERE l.project_id = ?1 AND l.customer_id = ?2 AND l.deleted_at IS NULL
+         ORDER BY l.created_at DESC, l.id DESC
+         LIMIT ?3 OFFSET ?4",
+        LICENSE_COLS.replace(", ", ", l.")
+    ))?;
+
+    let rows = stmt
+        .query_map(params![project_id, customer_id, limit, offset], |row| {
+            Ok(LicenseWithProduct {
+                license: License::from_row(row)?,
+                product_name: row.get(LICENSE_COL_COUNT)?,
+                tags: Vec::new(),
+            })
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok((rows, total))
+}
+
+/// Licenses whose validation has been throttled at least once, most recently
+/// flagged first (paginated, includes expired/revoked).
+pub fn list_flagged_licenses_paginated(
+    conn: &Connection,
+    project_id: &str,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<LicenseWithProduct>, i64)> {
+    let total: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM licenses WHERE project_id = ?1 AND abuse_flags > 0 AND deleted_at IS NULL",
+        params![project_id],
+        |row| row.get(0),
+    )?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT l.{}, p.name
+         FROM licenses l
+         JOIN products p ON l.product_id = p.id
+         WHERE l.project_id = ?1 AND l.abuse_flags > 0 AND l.deleted_at IS NULL
+         ORDER BY l.abuse_flagged_at DESC, l.id DESC
+         LIMIT ?2 OFFSET ?3",
+        LICENSE_COLS.replace(", ", ", l.")
+    ))?;
+
+    let rows = stmt
+        .query_map(params![project_id, limit, offset], |row| {
+            Ok(LicenseWithProduct {
+                license: License::from_row(row)?,
+                product_name: row.get(LICENSE_COL_COUNT)?,
+                tags: Vec::new(),
+            })
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok((rows, total))
+}
+
+/// List a project's dormant licenses (never activated within the project's
+/// `dormant_after_days`), most recently marked first.
+pub fn list_dormant_licenses_paginated(
+    conn: &Connection,
+    project_id: &str,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<LicenseWithProduct>, i64)> {
+    let total: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM licenses WHERE project_id = ?1 AND dormant_since IS NOT NULL AND deleted_at IS NULL",
+        params![project_id],
+        |row| row.get(0),
+    )?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT l.{}, p.name
+         FROM licenses l
+         JOIN products p ON l.product_id = p.id
+         WHERE l.project_id = ?1 AND l.dormant_since IS NOT NULL AND l.deleted_at IS NULL
+         ORDER BY l.dormant_since DESC, l.id DESC
+         LIMIT ?2 OFFSET ?3",
+        LICENSE_COLS.replace(", ", ", l.")
+    ))?;
+
+    let rows = stmt
+        .query_map(params![project_id, limit, offset], |row| {
+            Ok(LicenseWithProduct {
+                license: License::from_row(row)?,
+                product_name: row.get(LICENSE_COL_COUNT)?,
+                tags: Vec::new(),
+            })
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok((rows, total))
+}
+
+/// Mark licenses dormant that are at least their project's
+/// `dormant_after_days` old and were never activated: no activations and no
+/// devices. Revoked, expired, trial and deleted licenses are left alone, as
+/// are projects without the setting. Returns the licenses marked.
+pub fn mark_dormant_licenses(conn: &Connection, now: i64) -> Result<Vec<License>> {
+    query_all(
+        conn,
+        &format!(
+            "UPDATE licenses SET dormant_since = ?1
+             WHERE dormant_since IS NULL AND activation_count = 0 AND revoked = 0
+               AND is_trial = 0 AND deleted_at IS NULL
+               AND (expires_at IS NULL OR expires_at > ?1)
+               AND NOT EXISTS (SELECT 1 FROM devices d WHERE d.license_id = licenses.id)
+               AND created_at <= ?1 - 86400 * (
+                   SELECT p.dormant_after_days FROM projects p
+                   WHERE p.id = licenses.project_id AND p.deleted_at IS NULL
+               )
+             RETURNING {}",
+            LICENSE_COLS
+        ),
+        params![now],
+    )
+}
+
+/// Record that a license went over its project's hourly validation limit.
+/// Called once per throttled window, with the approximate number of distinct
+/// IPs seen validating it in that window.
+pub fn flag_license_abuse(conn: &Connection, id: &str, distinct_ips: i64, now: i64) -> Result<()> {
+    conn.execute(
+        "UPDATE licenses SET abuse_flags = abuse_flags + 1, abuse_flagged_at = ?2, abuse_distinct_ips = ?3 WHERE id = ?1",
+        params![id, now, distinct_ips],
+    )?;
+    Ok(())
+}
+
+/// Record the app version a license was just validated or refreshed with.
+/// The first version sticks in `first_seen_version`; the last one is
+/// written at most once per [`SEEN_VERSION_INTERVAL_SECS`], so concurrent
+/// calls within the window don't write again. Returns whether it was written.
+pub fn record_license_seen_version(
+    conn: &Connection,
+    id: &str,
+    version: &str,
+    now: i64,
+) -> Result<bool> {
+    let updated = conn.execute(
+        "UPDATE licenses
+         SET first_seen_version = COALESCE(first_seen_version, ?2),
+             last_seen_version = ?2, last_seen_version_at = ?3
+         WHERE id = ?1 AND (last_seen_version_at IS NULL OR last_seen_version_at <= ?3 - ?4)",
+        params![id, version, now, SEEN_VERSION_INTERVAL_SECS],
+    )?;
+    Ok(updated > 0)
+}
+
+/// Find a license by payment provider and subscription ID (for subscription renewals)
+pub fn get_license_by_subscription(
+    conn: &Connection,
+    provider: &str,
+    subscription_id: &str,
+) -> Result<Option<License>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM licenses WHERE payment_provider = ?1 AND payment_provider_subscription_id = ?2 AND deleted_at IS NULL",
+            LICENSE_COLS
+        ),
+        &[&provider, &subscription_id],
+    )
+}
+
+/// Update a license's email hash (for fixing typo'd purchase emails).
+/// This enables self-service recovery with the corrected email address.
+pub fn update_license_email_hash(
+    conn: &Connection,
+    license_id: &str,
+    email_hash: &str,
+) -> Result<bool> {
+    let affected = conn.execute(
+        "UPDATE licenses SET email_hash = ?1 WHERE id = ?2",
+        params![email_hash, license_id],
+    )?;
+    Ok(affected > 0)
+}
+
+/// Extend license expiration dates (for subscription renewals)
+pub fn extend_license_expiration(
+    conn: &Connection,
+    license_id: &str,
+    new_expires_at: Option<i64>,
+    new_updates_expires_at: Option<i64>,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE licenses SET expires_at = ?1, updates_expires_at = ?2 WHERE id = ?3",
+        params![new_expires_at, new_updates_expires_at, license_id],
+    )?;
+    Ok(())
+}
+
+/// Mark a license's subscription as paused. No-op if it's already paused
+/// (providers may redeliver the event).
+pub fn pause_license(conn: &Connection, license_id: &str, paused_at: i64) -> Result<bool> {
+    let affected = conn.execute(
+        "UPDATE licenses SET paused_at = ?1 WHERE id = ?2 AND paused_at IS NULL",
+        params![paused_at, license_id],
+    )?;
+    Ok(affected > 0)
+}
+
+/// Resume a paused license, pushing `expires_at` out by the time spent paused
+/// so the customer doesn't lose paid time. No-op if the license isn't paused.
+pub fn resume_license(conn: &Connection, license_id: &str, resumed_at: i64) -> Result<bool> {
+    let affected = conn.execute(
+        "UPDATE licenses SET
+            expires_at = CASE WHEN expires_at IS NULL THEN NULL
+                              ELSE expires_at + MAX(?1 - paused_at, 0) END,
+            paused_seconds = paused_seconds + MAX(?1 - paused_at, 0),
+            paused_at = NULL
+         WHERE id = ?2 AND paused_at IS NOT NULL",
+        params![resumed_at, license_id],
+    )?;
+    Ok(affected > 0)
+}
+
+// ============ Activation Codes ============
+
+const ACTIVATION_CODE_TTL_SECONDS: i64 = 30 * 60; // 30 minutes
+
+/// Create an activation code in PREFIX-XXXX-XXXX format (40 bits entropy)
+pub fn create_activation_code(
+    conn: &Connection,
+    license_id: &str,
+    prefix: &str,
+) -> Result<ActivationCode> {
+    create_activation_code_for_seat(conn, license_id, None, prefix)
+}
+
+/// Create an activation code, optionally scoped to a seat on a team license.
+/// Devices activated with a seat code count against that seat's device limit.
+pub fn create_activation_code_for_seat(
+    conn: &Connection,
+    license_id: &str,
+    seat_id: Option<&str>,
+    prefix: &str,
+) -> Result<ActivationCode> {
+    let code = generate_activation_code(prefix);
+    let code_hash = hash_secret(&code);
+    let now = now();
+    let expires_at = now + ACTIVATION_CODE_TTL_SECONDS;
+
+    conn.execute(
+        "INSERT INTO activation_codes (code_hash, license_id, expires_at, used, created_at, seat_id)
+         VALUES (?1, ?2, ?3, 0, ?4, ?5)",
+        params![&code_hash, license_id, expires_at, now, seat_id],
+    )?;
+
+    Ok(ActivationCode {
+        code,
+        license_id: license_id.to_string(),
+        expires_at,
+        used: false,
+        created_at: now,
+        seat_id: seat_id.map(String::from),
+    })
+}
+
+pub fn get_activation_code_by_code(
+    conn: &Connection,
+    code: &str,
+) -> Result<Option<ActivationCode>> {
+    let code_hash = hash_secret(code);
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM activation_codes WHERE code_hash = ?1",
+            ACTIVATION_CODE_COLS
+        ),
+        &[&code_hash],
+    )
+}
+
+/// Atomically claim an activation code for redemption.
+///
+/// This prevents race conditions where multiple concurrent requests could use
+/// the same activation code. The UPDATE only succeeds if:
+/// - The code exists
+/// - The code is not already used
+/// - The code has not expired
+///
+/// Returns Ok(Some(ActivationCode)) if successfully claimed.
+/// Returns Ok(None) if the code doesn't exist, is already used, or is expired.
+pub fn try_claim_activation_code(conn: &Connection, code: &str) -> Result<Option<ActivationCode>> {
+    let code_hash = hash_secret(code);
+    let now = now();
+
+    // Atomically mark as used only if not already used and not expired
+    let affected = conn.execute(
+        "UPDATE activation_codes SET used = 1 WHERE code_hash = ?1 AND used = 0 AND expires_at > ?2",
+        params![&code_hash, now],
+    )?;
+
+    if affected == 0 {
+        // Code doesn't exist, already used, or expired
+        return Ok(None);
+    }
+
+    // Successfully claimed - now fetch the full record
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM activation_codes WHERE code_hash = ?1",
+            ACTIVATION_CODE_COLS
+        ),
+        &[&code_hash],
+    )
+}
+
+pub fn mark_activation_code_used(conn: &Connection, code: &str) -> Result<()> {
+    let code_hash = hash_secret(code);
+    conn.execute(
+        "UPDATE activation_codes SET used = 1 WHERE code_hash = ?1",
+        params![code_hash],
+    )?;
+    Ok(())
+}
+
+pub fn cleanup_expired_activation_codes(conn: &Connection) -> Result<usize> {
+    let now = now();
+    let deleted = conn.execute(
+        "DELETE FROM activation_codes WHERE expires_at < ?1 OR used = 1",
+        params![now],
+    )?;
+    Ok(deleted)
+}
+
+// ============ License Seats ============
+
+/// Assign a seat on a team license to an email hash.
+///
+/// The capacity check and insert are a single statement, so concurrent
+/// assignments can't exceed `license.seats`.
+pub fn assign_license_seat(
+    conn: &Connection,
+    license: &License,
+    seat_email_hash: &str,
+) -> Result<LicenseSeat> {
+    let seats = license
+        .seats
+        .ok_or_else(|| AppError::BadRequest(msg::LICENSE_NOT_SEAT_BASED.into()))?;
+
+    let already_assigned: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM license_seats WHERE license_id = ?1 AND seat_email_hash = ?2 AND removed_at IS NULL)",
+        params![&license.id, seat_email_hash],
+        |row| row.get(0),
+    )?;
+    if already_assigned {
+        return Err(AppError::Conflict(msg::SEAT_ALREADY_ASSIGNED.into()));
+    }
+
+    let id = gen_id();
+    let now = now();
+    let inserted = conn.execute(
+        "INSERT INTO license_seats (id, license_id, seat_email_hash, assigned_at)
+         SELECT ?1, ?2, ?3, ?4
+         WHERE (SELECT COUNT(*) FROM license_seats WHERE license_id = ?2 AND removed_at IS NULL) < ?5",
+        params![&id, &license.id, seat_email_hash, now, seats],
+    )?;
+    if inserted == 0 {
+        return Err(AppError::BadRequest(format!(
+            "All {} seats are assigned. Remove a seat first.",
+            seats
+        )));
+    }
+
+    Ok(LicenseSeat {
+        id,
+        license_id: license.id.clone(),
+        seat_email_hash: seat_email_hash.to_string(),
+        assigned_at: now,
+        removed_at: None,
+    })
+}
+
+/// List the active seats on a license, oldest first.
+pub fn list_license_seats(conn: &Connection, license_id: &str) -> Result<Vec<LicenseSeat>> {
+    query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM license_seats WHERE license_id = ?1 AND removed_at IS NULL ORDER BY assigned_at, id",
+            LICENSE_SEAT_COLS
+        ),
+        &[&license_id],
+    )
+}
+
+pub fn get_active_license_seat(
+    conn: &Connection,
+    license_id: &str,
+    seat_id: &str,
+) -> Result<Option<LicenseSeat>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM license_seats WHERE id = ?1 AND license_id = ?2 AND removed_at IS NULL",
+            LICENSE_SEAT_COLS
+        ),
+        &[&seat_id, &license_id],
+    )
+}
+
+/// Active seats held by an email hash on active (non-revoked, non-expired)
+/// licenses in a project.
+pub fn get_active_seats_by_email_hash(
+    conn: &Connection,
+    project_id: &str,
+    seat_email_hash: &str,
+) -> Result<Vec<LicenseSeat>> {
+    query_all(
+        conn,
+        &format!(
+            "SELECT s.{} FROM license_seats s
+             JOIN licenses l ON s.license_id = l.id
+             WHERE l.project_id = ?1 AND s.seat_email_hash = ?2 AND s.removed_at IS NULL
+               AND l.revoked = 0 AND l.deleted_at IS NULL
+               AND (l.expires_at IS NULL OR l.expires_at > unixepoch())
+             ORDER BY s.assigned_at DESC, s.id DESC",
+            LICENSE_SEAT_COLS.replace(", ", ", s.")
+        ),
+        &[&project_id, &seat_email_hash],
+    )
+}
+
+pub fn count_devices_for_seat(conn: &Connection, seat_id: &str) -> Result<i32> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM devices WHERE seat_id = ?1",
+        params![seat_id],
+        |row| row.get(0),
+    )
+    .map_err(Into::into)
+}
+
+/// Unassign a seat: revoke and delete the devices activated under it and drop
+/// its pending activation codes. Returns the number of devices deactivated.
+pub fn remove_license_seat(
+    conn: &mut Connection,
+    seat: &LicenseSeat,
+    details: Option<&str>,
+) -> Result<usize> {
+    let tx = conn.transaction()?;
+    let now = now();
+
+    tx.execute(
+        "UPDATE license_seats SET removed_at = ?1 WHERE id = ?2 AND removed_at IS NULL",
+        params![now, &seat.id],
+    )?;
+    tx.execute(
+        "INSERT OR IGNORE INTO revoked_jtis (jti, license_id, revoked_at, details)
+         SELECT jti, license_id, ?1, ?2 FROM devices WHERE seat_id = ?3",
+        params![now, details, &seat.id],
+    )?;
+    let removed = tx.execute("DELETE FROM devices WHERE seat_id = ?1", params![&seat.id])?;
+    tx.execute(
+        "DELETE FROM activation_codes WHERE seat_id = ?1 AND used = 0",
+        params![&seat.id],
+    )?;
+
+    tx.commit()?;
+    Ok(removed)
+}
+
+// ============ Share Links ============
+
+/// Generate a share link token: `psl_` followed by 256 random bits in hex.
+pub fn generate_share_token() -> String {
+    use rand::RngCore;
+    use rand::rngs::OsRng;
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    format!("psl_{}", hex::encode(bytes))
+}
+
+/// Create a share link for a license. Returns the link and its token; only
+/// the token's hash is stored, so the token can't be recovered later.
+pub fn create_share_link(
+    conn: &Connection,
+    license_id: &str,
+    masked_email: Option<&str>,
+    created_by: Option<&str>,
+    expires_at: i64,
+) -> Result<(ShareLink, String)> {
+    let id = gen_id();
+    let token = generate_share_token();
+    let token_hash = hash_secret(&token);
+    let now = now();
+
+    conn.execute(
+        "INSERT INTO share_links (id, license_id, token_hash, masked_email, created_by, expires_at, revoked, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, 0, ?7)",
+        params![&id, license_id, &token_hash, masked_email, created_by, expires_at, now],
+    )?;
+
+    Ok((
+        ShareLink {
+            id,
+            license_id: license_id.to_string(),
+            token_hash,
+            masked_email: masked_email.map(String::from),
+            created_by: created_by.map(String::from),
+            expires_at,
+            revoked: false,
+            created_at: now,
+        },
+        token,
+    ))
+}
+
+/// Look up a share link by token hash, including revoked and expired links.
+pub fn get_share_link_by_token_hash(
+    conn: &Connection,
+    token_hash: &str,
+) -> Result<Option<ShareLink>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM share_links WHERE token_hash = ?1",
+            SHARE_LINK_COLS
+        ),
+        &[&token_hash],
+    )
+}
+
+pub fn get_share_link(
+    conn: &Connection,
+    license_id: &str,
+    share_link_id: &str,
+) -> Result<Option<ShareLink>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM share_links WHERE id = ?1 AND license_id = ?2",
+            SHARE_LINK_COLS
+        ),
+        &[&share_link_id, &license_id],
+    )
+}
+
+/// Revoke a share link. Returns false if it was already revoked.
+pub fn revoke_share_link(conn: &Connection, share_link_id: &str) -> Result<bool> {
+    let updated = conn.execute(
+        "UPDATE share_links SET revoked = 1 WHERE id = ?1 AND revoked = 0",
+        params![share_link_id],
+    )?;
+    Ok(updated > 0)
+}
+
+// ============ Prepaid Codes ============
+
+/// Rows per INSERT when storing a batch's code hashes
+const PREPAID_CODE_INSERT_CHUNK: usize = 500;
+
+/// Generate a prepaid code: PREFIX-XXXX-XXXX-XXXX-XXXX (80 bits entropy)
+///
+/// Unlike activation codes these can live for months with no TTL, so they
+/// carry twice the random groups.
+pub fn generate_prepaid_code(prefix: &str) -> String {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    let chars: Vec<char> = "ABCDEFGHJKLMNPQRSTUVWXYZ23456789".chars().collect();
+
+    let mut part = || -> String {
+        (0..4)
+            .map(|_| chars[rng.gen_range(0..chars.len())])
+            .collect()
+    };
+
+    format!("{}-{}-{}-{}-{}", prefix, part(), part(), part(), part())
+}
+
+/// Create a batch of prepaid codes for a product. Only the code hashes are
+/// stored as rows; the codes themselves are kept as CSV, encrypted under the
+/// org's data key, until [`take_prepaid_codes`] hands them out once.
+///
+/// Run inside a transaction so a batch is never left half inserted.
+pub fn create_prepaid_code_batch(
+    conn: &Connection,
+    master_key: &MasterKey,
+    project: &Project,
+    product_id: &str,
+    input: &CreatePrepaidCodes,
+    created_by: Option<&str>,
+) -> Result<PrepaidCodeBatch> {
+    let id = gen_id();
+    let now = now();
+    let expires_at = input.expires_in_days.map(|days| now + days * 86400);
+
+    let codes: Vec<String> = (0..input.count)
+        .map(|_| generate_prepaid_code(&project.license_key_prefix))
+        .collect();
+    let mut csv = String::from("serial,code\n");
+    for (i, code) in codes.iter().enumerate() {
+        csv.push_str(&format!("{},{}\n", i + 1, code));
+    }
+    let pending_codes = org_data_key(conn, &project.org_id, master_key)?
+        .encrypt_private_key(&id, csv.as_bytes())?;
+
+    conn.execute(
+        "INSERT INTO prepaid_code_batches (id, project_id, product_id, label, code_count, created_by, expires_at, pending_codes, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![&id, &project.id, product_id, &input.label, input.count, created_by, expires_at, &pending_codes, now],
+    )?;
+
+    for chunk in codes.chunks(PREPAID_CODE_INSERT_CHUNK) {
+        let placeholders = vec!["(?, ?)"; chunk.len()].join(", ");
+        let hashes: Vec<String> = chunk.iter().map(|code| hash_secret(code)).collect();
+        let mut values: Vec<&dyn rusqlite::ToSql> = Vec::with_capacity(chunk.len() * 2);
+        for hash in &hashes {
+            values.push(hash);
+            values.push(&id);
+        }
+        conn.execute(
+            &format!(
+                "INSERT INTO prepaid_codes (code_hash, batch_id) VALUES {}",
+                placeholders
+            ),
+            values.as_slice(),
+        )?;
+    }
+
+    Ok(PrepaidCodeBatch {
+        id,
+        project_id: project.id.clone(),
+        product_id: product_id.to_string(),
+        label: input.label.clone(),
+        code_count: input.count,
+        redeemed_count: 0,
+        created_by: created_by.map(String::from),
+        expires_at,
+        downloaded_at: None,
+        revoked_at: None,
+        created_at: now,
+    })
+}
+
+pub fn get_prepaid_code_batch(
+    conn: &Connection,
+    product_id: &str,
+    batch_id: &str,
+) -> Result<Option<PrepaidCodeBatch>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM prepaid_code_batches WHERE id = ?1 AND product_id = ?2",
+            PREPAID_CODE_BATCH_COLS
+        ),
+        &[&batch_id, &product_id],
+    )
+}
+
+/// A product's prepaid code batches, newest first.
+pub fn list_prepaid_code_batches(
+    conn: &Connection,
+    product_id: &str,
+) -> Result<Vec<PrepaidCodeBatch>> {
+    query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM prepaid_code_batches WHERE product_id = ?1 ORDER BY created_at DESC, id",
+            PREPAID_CODE_BATCH_COLS
+        ),
+        &[&product_id],
+    )
+}
+
+/// Hand out a batch's codes for its one download: returns the decrypted CSV
+/// and clears the stored copy in the same statement, so concurrent downloads
+/// can't both get it. Returns None once downloaded, or if the batch was
+/// revoked first.
+///
+/// Run inside a transaction so a failed decrypt leaves the codes downloadable.
+pub fn take_prepaid_codes(
+    conn: &Connection,
+    master_key: &MasterKey,
+    org_id: &str,
+    batch_id: &str,
+) -> Result<Option<Vec<u8>>> {
+    let encrypted: Option<Vec<u8>> = conn
+        .query_row(
+            "UPDATE prepaid_code_batches SET pending_codes = NULL, downloaded_at = ?2
+             WHERE id = ?1 AND pending_codes IS NOT NULL
+             RETURNING pending_codes",
+            params![batch_id, now()],
+            |row| row.get(0),
+        )
+        .optional()?;
+    encrypted
+        .map(|encrypted| {
+            org_data_key(conn, org_id, master_key)?.decrypt_private_key(batch_id, &encrypted)
+        })
+        .transpose()
+}
+
+/// Revoke a batch: its unredeemed codes stop working, and codes not yet
+/// downloaded are dropped. Licenses already redeemed are left alone.
+/// Returns false if the batch was already revoked.
+pub fn revoke_prepaid_code_batch(conn: &Connection, batch_id: &str) -> Result<bool> {
+    let updated = conn.execute(
+        "UPDATE prepaid_code_batches SET revoked_at = ?2, pending_codes = NULL
+         WHERE id = ?1 AND revoked_at IS NULL",
+        params![batch_id, now()],
+    )?;
+    Ok(updated > 0)
+}
+
+/// Atomically claim a prepaid code for redemption.
+///
+/// The UPDATE only succeeds if the code belongs to a batch in this project
+/// that isn't revoked or expired and hasn't been redeemed yet, so concurrent
+/// requests can't redeem the same code twice.
+///
+/// Returns the code's batch, or None if the code can't be redeemed.
+pub fn try_claim_prepaid_code(
+    conn: &Connection,
+    project_id: &str,
+    code: &str,
+    now: i64,
+) -> Result<Option<PrepaidCodeBatch>> {
+    let code_hash = hash_secret(code);
+
+    let affected = conn.execute(
+        "UPDATE prepaid_codes SET redeemed_at = ?3
+         WHERE code_hash = ?1 AND redeemed_at IS NULL
+           AND batch_id IN (
+               SELECT id FROM prepaid_code_batches
+               WHERE project_id = ?2 AND revoked_at IS NULL
+                 AND (expires_at IS NULL OR expires_at > ?3)
+           )",
+        params![&code_hash, project_id, now],
+    )?;
+
+    if affected == 0 {
+        return Ok(None);
+    }
+
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM prepaid_code_batches
+             WHERE id = (SELECT batch_id FROM prepaid_codes WHERE code_hash = ?1)",
+            PREPAID_CODE_BATCH_COLS
+        ),
+        &[&code_hash],
+    )
+}
+
+/// Record the license a redeemed prepaid code created.
+pub fn set_prepaid_code_license(conn: &Connection, code: &str, license_id: &str) -> Result<()> {
+    conn.execute(
+        "UPDATE prepaid_codes SET license_id = ?2 WHERE code_hash = ?1",
+        params![hash_secret(code), license_id],
+    )?;
+    Ok(())
+}
+
+/// A project's batches whose codes haven't been downloaded yet, with their
+/// encrypted CSV (for moving them to a new key).
+pub fn list_pending_prepaid_codes(
+    conn: &Connection,
+    project_id: &str,
+) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, pending_codes FROM prepaid_code_batches
+         WHERE project_id = ?1 AND pending_codes IS NOT NULL",
+    )?;
+    let rows = stmt
+        .query_map(params![project_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(rows)
+}
+
+pub fn update_pending_prepaid_codes(
+    conn: &Connection,
+    batch_id: &str,
+    pending_codes: &[u8],
+) -> Result<()> {
+    conn.execute(
+        "UPDATE prepaid_code_batches SET pending_codes = ?2 WHERE id = ?1",
+        params![batch_id, pending_codes],
+    )?;
+    Ok(())
+}
+
+// ============ Bulk License Jobs ============
+
+/// Record a bulk license job, running from the start.
+pub fn create_bulk_job(
+    conn: &Connection,
+    project_id: &str,
+    product_id: &str,
+    requested: i64,
+    activation_codes: bool,
+    params: &BulkLicenseParams,
+    created_by: Option<&str>,
+) -> Result<BulkJob> {
+    let id = gen_id();
+    let now = now();
+    conn.execute(
+        "INSERT INTO bulk_jobs (id, project_id, product_id, requested, activation_codes, params, created_by, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![&id, project_id, product_id, requested, activation_codes as i32, serde_json::to_string(params)?, created_by, now],
+    )?;
+
+    Ok(BulkJob {
+        id,
+        project_id: project_id.to_string(),
+        product_id: product_id.to_string(),
+        status: BulkJobStatus::Running,
+        requested,
+        created_count: 0,
+        activation_codes,
+        created_by: created_by.map(String::from),
+        error: None,
+        params: params.clone(),
+        created_at: now,
+        finished_at: None,
+    })
+}
+
+pub fn get_bulk_job(conn: &Connection, project_id: &str, job_id: &str) -> Result<Option<BulkJob>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM bulk_jobs WHERE id = ?1 AND project_id = ?2",
+            BULK_JOB_COLS
+        ),
+        &[&job_id, &project_id],
+    )
+}
+
+/// The chunks a job has attempted so far, in order.
+pub fn list_bulk_job_chunks(conn: &Connection, job_id: &str) -> Result<Vec<BulkJobChunk>> {
+    query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM bulk_job_chunks WHERE job_id = ?1 ORDER BY chunk",
+            BULK_JOB_CHUNK_COLS
+        ),
+        &[&job_id],
+    )
+}
+
+/// Record a chunk's licenses and add them to the job's count. Run in the
+/// chunk's transaction: returns false, recording nothing, if the job stopped
+/// running meanwhile, so the caller can roll the chunk back.
+pub fn record_bulk_job_chunk(
+    conn: &Connection,
+    job_id: &str,
+    chunk: i64,
+    license_ids: &[String],
+) -> Result<bool> {
+    let updated = conn.execute(
+        "UPDATE bulk_jobs SET created_count = created_count + ?2 WHERE id = ?1 AND status = 'running'",
+        params![job_id, license_ids.len() as i64],
+    )?;
+    if updated == 0 {
+        return Ok(false);
+    }
+    conn.execute(
+        "INSERT INTO bulk_job_chunks (job_id, chunk, license_ids, created_at) VALUES (?1, ?2, ?3, ?4)",
+        params![job_id, chunk, serde_json::to_string(license_ids)?, now()],
+    )?;
+    Ok(true)
+}
+
+/// Record a chunk that failed and was rolled back.
+pub fn record_bulk_job_chunk_error(
+    conn: &Connection,
+    job_id: &str,
+    chunk: i64,
+    error: &str,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO bulk_job_chunks (job_id, chunk, error, created_at) VALUES (?1, ?2, ?3, ?4)",
+        params![job_id, chunk, error, now()],
+    )?;
+    Ok(())
+}
+
+/// Stop a running job. Returns false if it had already finished.
+pub fn cancel_bulk_job(conn: &Connection, job_id: &str) -> Result<bool> {
+    let updated = conn.execute(
+        "UPDATE bulk_jobs SET status = 'cancelled', finished_at = ?2 WHERE id = ?1 AND status = 'running'",
+        params![job_id, now()],
+    )?;
+    Ok(updated > 0)
+}
+
+/// Mark a running job finished after its last chunk: failed if any chunk
+/// failed, completed otherwise. Returns false if it was cancelled first.
+pub fn finish_bulk_job(conn: &Connection, job_id: &str) -> Result<bool> {
+    let updated = conn.execute(
+        "UPDATE bulk_jobs SET finished_at = ?2,
+             status = CASE WHEN EXISTS (
+                 SELECT 1 FROM bulk_job_chunks WHERE job_id = ?1 AND error IS NOT NULL
+             ) THEN 'failed' ELSE 'completed' END
+         WHERE id = ?1 AND status = 'running'",
+        params![job_id, now()],
+    )?;
+    Ok(updated > 0)
+}
+
+/// Mark running jobs failed with `error`: one job, or every job in the
+/// database when `job_id` is None (at startup, for jobs a restart cut off).
+pub fn fail_bulk_jobs(conn: &Connection, job_id: Option<&str>, error: &str) -> Result<usize> {
+    let updated = conn.execute(
+        "UPDATE bulk_jobs SET status = 'failed', error = ?2, finished_at = ?3
+         WHERE status = 'running' AND (?1 IS NULL OR id = ?1)",
+        params![job_id, error, now()],
+    )?;
+    Ok(updated)
+}
+
+// ============ Imported Provider Keys ============
+
+/// Attach the hash of a provider-issued license key to a license, so the old
+/// key can be redeemed for it.
+pub fn set_license_external_key(
+    conn: &Connection,
+    license_id: &str,
+    provider: &str,
+    key_hash: &str,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE licenses SET external_key_provider = ?2, external_key_hash = ?3 WHERE id = ?1",
+        params![license_id, provider, key_hash],
+    )?;
+    Ok(())
+}
+
+/// The live license imported for a provider-issued key.
+pub fn get_license_by_external_key(
+    conn: &Connection,
+    project_id: &str,
+    provider: &str,
+    key_hash: &str,
+) -> Result<Option<License>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM licenses WHERE project_id = ?1 AND external_key_provider = ?2 AND external_key_hash = ?3 AND deleted_at IS NULL",
+            LICENSE_COLS
+        ),
+        &[&project_id, &provider, &key_hash],
+    )
+}
+
+/// Whether a provider-issued key was already imported into the project,
+/// counting licenses deleted since.
+pub fn external_key_imported(
+    conn: &Connection,
+    project_id: &str,
+    provider: &str,
+    key_hash: &str,
+) -> Result<bool> {
+    Ok(conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM licenses WHERE project_id = ?1 AND external_key_provider = ?2 AND external_key_hash = ?3)",
+        params![project_id, provider, key_hash],
+        |row| row.get(0),
+    )?)
+}
+
+// ============ License Upgrades ============
+
+/// Record that `to_license_id` was bought to upgrade `from_license_id`.
+pub fn create_license_upgrade(
+    conn: &Connection,
+    from_license_id: &str,
+    to_license_id: &str,
+    payment_session: &PaymentSession,
+    old_license_action: UpgradeOldLicense,
+) -> Result<LicenseUpgrade> {
+    let id = gen_id();
+    let now = now();
+
+    conn.execute(
+        "INSERT INTO license_upgrades (id, from_license_id, to_license_id, payment_session_id, days_remaining, credit_cents, old_license_action, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            &id,
+            from_license_id,
+            to_license_id,
+            &payment_session.id,
+            payment_session.upgrade_days_remaining,
+            payment_session.upgrade_credit_cents,
+            old_license_action.as_ref(),
+            now
+        ],
+    )?;
+
+    Ok(LicenseUpgrade {
+        id,
+        from_license_id: from_license_id.to_string(),
+        to_license_id: to_license_id.to_string(),
+        payment_session_id: Some(payment_session.id.clone()),
+        days_remaining: payment_session.upgrade_days_remaining,
+        credit_cents: payment_session.upgrade_credit_cents,
+        old_license_action,
+        created_at: now,
+    })
+}
+
+/// The upgrade that replaced this license, if any.
+pub fn get_upgrade_replacing_license(
+    conn: &Connection,
+    license_id: &str,
+) -> Result<Option<LicenseUpgrade>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM license_upgrades WHERE from_license_id = ?1 ORDER BY created_at DESC, id DESC LIMIT 1",
+            LICENSE_UPGRADE_COLS
+        ),
+        &[&license_id],
+    )
+}
+
+/// The upgrade this license was bought through, if any.
+pub fn get_upgrade_creating_license(
+    conn: &Connection,
+    license_id: &str,
+) -> Result<Option<LicenseUpgrade>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM license_upgrades WHERE to_license_id = ?1",
+            LICENSE_UPGRADE_COLS
+        ),
+        &[&license_id],
+    )
+}
+
+/// End a license's update window now, leaving it valid for the versions it covers.
+/// Used when an upgrade replaces the license with `UpgradeOldLicense::UpdatesOnly`.
+pub fn end_license_updates(conn: &Connection, license_id: &str, now: i64) -> Result<()> {
+    conn.execute(
+        "UPDATE licenses SET updates_expires_at = ?1
+         WHERE id = ?2 AND (updates_expires_at IS NULL OR updates_expires_at > ?1)",
+        params![now, license_id],
+    )?;
+    Ok(())
+}
+
+// ============ License Update Renewals ============
+
+/// Apply an updates-only renewal: move the license's update window end to
+/// `updates_expires_at` and record the purchase, leaving everything else on
+/// the license as it was.
+pub fn apply_license_update_renewal(
+    conn: &Connection,
+    license: &License,
+    product_id: &str,
+    payment_session_id: &str,
+    updates_expires_at: Option<i64>,
+    now: i64,
+) -> Result<LicenseUpdateRenewal> {
+    conn.execute(
+        "UPDATE licenses SET updates_expires_at = ?1 WHERE id = ?2",
+        params![updates_expires_at, &license.id],
+    )?;
+
+    let id = gen_id();
+    conn.execute(
+        "INSERT INTO license_update_renewals (id, license_id, product_id, payment_session_id, previous_updates_expires_at, updates_expires_at, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            &id,
+            &license.id,
+            product_id,
+            payment_session_id,
+            license.updates_expires_at,
+            updates_expires_at,
+            now
+        ],
+    )?;
+
+    Ok(LicenseUpdateRenewal {
+        id,
+        license_id: license.id.clone(),
+        product_id: product_id.to_string(),
+        payment_session_id: Some(payment_session_id.to_string()),
+        previous_updates_expires_at: license.updates_expires_at,
+        updates_expires_at,
+        created_at: now,
+    })
+}
+
+/// Updates-only renewals applied to a license, oldest first.
+pub fn list_license_update_renewals(
+    conn: &Connection,
+    license_id: &str,
+) -> Result<Vec<LicenseUpdateRenewal>> {
+    query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM license_update_renewals WHERE license_id = ?1 ORDER BY created_at, id",
+            LICENSE_UPDATE_RENEWAL_COLS
+        ),
+        &[&license_id],
+    )
+}
+
+// ============ Trial Conversions ============
+
+/// Mark a license as a trial, so a later purchase by the same buyer converts it.
+pub fn mark_license_trial(conn: &Connection, license_id: &str) -> Result<()> {
+    conn.execute(
+        "UPDATE licenses SET is_trial = 1 WHERE id = ?1",
+        params![license_id],
+    )?;
+    Ok(())
+}
+
+/// The buyer's newest trial license in the project that no purchase has
+/// converted yet, matched by customer ID or email hash.
+pub fn find_unconverted_trial_for_buyer(
+    conn: &Connection,
+    project_id: &str,
+    customer_id: Option<&str>,
+    email_hash: Option<&str>,
+) -> Result<Option<License>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT t.{} FROM licenses t
+             WHERE t.project_id = ?1 AND t.is_trial = 1 AND t.deleted_at IS NULL
+               AND (t.customer_id = ?2 OR t.email_hash = ?3)
+               AND NOT EXISTS (SELECT 1 FROM licenses p WHERE p.converted_from_license_id = t.id)
+             ORDER BY t.created_at DESC, t.id DESC LIMIT 1",
+            LICENSE_COLS.replace(", ", ", t.")
+        ),
+        &[&project_id, &customer_id, &email_hash],
+    )
+}
+
+/// Link a paid license to the trial it converted, and apply the project's
+/// `converted_trial_action` to the trial.
+pub fn convert_trial_license(
+    conn: &Connection,
+    trial_id: &str,
+    paid_license_id: &str,
+    action: ConvertedTrialAction,
+    revoked_by: Option<&str>,
+    now: i64,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE licenses SET converted_from_license_id = ?1 WHERE id = ?2",
+        params![trial_id, paid_license_id],
+    )?;
+    match action {
+        ConvertedTrialAction::Keep => {}
+        ConvertedTrialAction::Expire => {
+            conn.execute(
+                "UPDATE licenses SET expires_at = ?1
+                 WHERE id = ?2 AND (expires_at IS NULL OR expires_at > ?1)",
+                params![now, trial_id],
+            )?;
+        }
+        ConvertedTrialAction::Revoke => {
+            revoke_license(
+                conn,
+                trial_id,
+                &RevokeLicense::new(RevocationReason::Superseded),
+                revoked_by,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Trials started in `[from, to)` and how many of them converted, counted in
+/// SQL: the median is the middle one or two conversion times.
+pub fn get_trial_conversion_stats(
+    conn: &Connection,
+    project_id: &str,
+    from: Option<i64>,
+    to: Option<i64>,
+) -> Result<TrialConversionStats> {
+    let (trials_started, trials_converted, median_days_to_convert) = conn.query_row(
+        "WITH trials AS (
+             SELECT t.created_at,
+                    (SELECT MIN(p.created_at) FROM licenses p
+                     WHERE p.converted_from_license_id = t.id AND p.deleted_at IS NULL) AS converted_at
+             FROM licenses t
+             WHERE t.project_id = ?1 AND t.is_trial = 1 AND t.deleted_at IS NULL
+               AND t.created_at >= ?2 AND t.created_at < ?3
+         ),
+         days AS (
+             SELECT (converted_at - created_at) / 86400.0 AS d FROM trials
+             WHERE converted_at IS NOT NULL
+         )
+         SELECT (SELECT COUNT(*) FROM trials),
+                (SELECT COUNT(*) FROM days),
+                (SELECT AVG(d) FROM (
+                     SELECT d FROM days ORDER BY d
+                     LIMIT 2 - (SELECT COUNT(*) FROM days) % 2
+                     OFFSET (SELECT (COUNT(*) - 1) / 2 FROM days)
+                 ))",
+        params![project_id, from.unwrap_or(i64::MIN), to.unwrap_or(i64::MAX)],
+        |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?, row.get(2)?)),
+    )?;
+
+    Ok(TrialConversionStats {
+        from,
+        to,
+        trials_started,
+        trials_converted,
+        conversion_rate: (trials_started > 0)
+            .then(|| trials_converted as f64 / trials_started as f64),
+        median_days_to_convert,
+    })
+}
+
+// ============ License Tags ============
+
+/// Add tags to a license (tags it already has are skipped). Returns how many were added.
+pub fn add_license_tags(conn: &Connection, license_id: &str, tags: &[String]) -> Result<usize> {
+    let now = now();
+    let mut added = 0;
+    for tag in tags {
+        added += conn.execute(
+            "INSERT OR IGNORE INTO license_tags (license_id, tag, created_at) VALUES (?1, ?2, ?3)",
+            params![license_id, tag, now],
+        )?;
+    }
+    Ok(added)
+}
+
+/// Remove tags from a license. Returns how many were removed.
+pub fn remove_license_tags(conn: &Connection, license_id: &str, tags: &[String]) -> Result<usize> {
+    let mut removed = 0;
+    for tag in tags {
+        removed += conn.execute(
+            "DELETE FROM license_tags WHERE license_id = ?1 AND tag = ?2",
+            params![license_id, tag],
+        )?;
+    }
+    Ok(removed)
+}
+
+/// A license's tags, sorted by name.
+pub fn list_license_tags(conn: &Connection, license_id: &str) -> Result<Vec<String>> {
+    let mut stmt =
+        conn.prepare("SELECT tag FROM license_tags WHERE license_id = ?1 ORDER BY tag")?;
+    let tags = stmt
+        .query_map(params![license_id], |row| row.get(0))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(tags)
+}
+
+/// Fill in `tags` for a page of licenses with one query.
+pub fn attach_license_tags(conn: &Connection, licenses: &mut [LicenseWithProduct]) -> Result<()> {
+    if licenses.is_empty() {
+        return Ok(());
+    }
+    let placeholders: Vec<String> = (1..=licenses.len()).map(|i| format!("?{}", i)).collect();
+    let sql = format!(
+        "SELECT license_id, tag FROM license_tags WHERE license_id IN ({}) ORDER BY tag",
+        placeholders.join(", ")
+    );
+    let ids: Vec<&str> = licenses.iter().map(|l| l.license.id.as_str()).collect();
+
+    let mut by_license: std::collections::HashMap<String, Vec<String>> =
+        std::collections::HashMap::new();
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(rusqlite::params_from_iter(&ids), |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    })?;
+    for row in rows {
+        let (license_id, tag) = row?;
+        by_license.entry(license_id).or_default().push(tag);
+    }
+
+    for license in licenses {
+        license.tags = by_license.remove(&license.license.id).unwrap_or_default();
+    }
+    Ok(())
+}
+
+/// Tags used by a project's licenses with how many licenses carry each,
+/// most used first.
+pub fn list_project_license_tags(
+    conn: &Connection,
+    project_id: &str,
+) -> Result<Vec<LicenseTagUsage>> {
+    let mut stmt = conn.prepare(
+        "SELECT t.tag, COUNT(*) AS license_count
+         FROM license_tags t
+         JOIN licenses l ON t.license_id = l.id
+         WHERE l.project_id = ?1 AND l.deleted_at IS NULL
+         GROUP BY t.tag
+         ORDER BY license_count DESC, t.tag",
+    )?;
+    let rows = stmt
+        .query_map(params![project_id], |row| {
+            Ok(LicenseTagUsage {
+                tag: row.get(0)?,
+                license_count: row.get(1)?,
+            })
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// A project's licenses (not deleted) grouped by their last reported app
+/// version, most licenses first. Licenses that never reported one are a
+/// group with no version.
+pub fn list_license_version_counts(
+    conn: &Connection,
+    project_id: &str,
+) -> Result<Vec<LicenseVersionCount>> {
+    let mut stmt = conn.prepare(
+        "SELECT last_seen_version, COUNT(*) AS license_count
+         FROM licenses
+         WHERE project_id = ?1 AND deleted_at IS NULL
+         GROUP BY last_seen_version
+         ORDER BY license_count DESC, last_seen_version",
+    )?;
+    let rows = stmt
+        .query_map(params![project_id], |row| {
+            Ok(LicenseVersionCount {
+                version: row.get(0)?,
+                license_count: row.get(1)?,
+            })
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// Licenses in a project carrying `tag` (paginated, includes expired/revoked).
+pub fn list_licenses_by_tag_paginated(
+    conn: &Connection,
+    project_id: &str,
+    tag: &str,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<LicenseWithProduct>, i64)> {
+    let total: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM licenses l
+         JOIN license_tags t ON t.license_id = l.id AND t.tag = ?2
+         WHERE l.project_id = ?1 AND l.deleted_at IS NULL",
+        params![project_id, tag],
+        |row| row.get(0),
+    )?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT l.{}, p.name
+         FROM licenses l
+         JOIN products p ON l.product_id = p.id
+         JOIN license_tags t ON t.license_id = l.id AND t.tag = ?2
+         WHERE l.project_id = ?1 AND l.deleted_at IS NULL
+         ORDER BY l.created_at DESC, l.id DESC
+         LIMIT ?3 OFFSET ?4",
+        LICENSE_COLS.replace(", ", ", l.")
+    ))?;
+
+    let rows = stmt
+        .query_map(params![project_id, tag, limit, offset], |row| {
+            Ok(LicenseWithProduct {
+                license: License::from_row(row)?,
+                product_name: row.get(LICENSE_COL_COUNT)?,
+                tags: Vec::new(),
+            })
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok((rows, total))
+}
+
+/// Add `tags` to every license in a project matching `filter`. Licenses
+/// already at `MAX_TAGS_PER_LICENSE` are skipped. Returns (licenses matched,
+/// tags added).
+pub fn bulk_add_license_tags(
+    conn: &mut Connection,
+    project_id: &str,
+    filter: &LicenseFilter,
+    tags: &[String],
+) -> Result<(usize, usize)> {
+    let mut conditions = vec!["l.project_id = ?", "l.deleted_at IS NULL"];
+    let mut values: Vec<Value> = vec![project_id.to_string().into()];
+    let mut push = |condition: &'static str, value: Value| {
+        conditions.push(condition);
+        values.push(value);
+    };
+    if let Some(ref product_id) = filter.product_id {
+        push("l.product_id = ?", product_id.clone().into());
+    }
+    if let Some(ref customer_id) = filter.customer_id {
+        push("l.customer_id = ?", customer_id.clone().into());
+    }
+    if let Some(ref email_hash) = filter.email_hash {
+        push("l.email_hash = ?", email_hash.clone().into());
+    }
+    if let Some(ref tag) = filter.tag {
+        push(
+            "EXISTS (SELECT 1 FROM license_tags f WHERE f.license_id = l.id AND f.tag = ?)",
+            tag.clone().into(),
+        );
+    }
+    if let Some(revoked) = filter.revoked {
+        push("l.revoked = ?", i64::from(revoked).into());
+    }
+    if let Some(after) = filter.created_after {
+        push("l.created_at >= ?", after.into());
+    }
+    if let Some(before) = filter.created_before {
+        push("l.created_at < ?", before.into());
+    }
+    let where_clause = conditions.join(" AND ");
+
+    let tx = conn.transaction()?;
+    let matched: i64 = tx.query_row(
+        &format!("SELECT COUNT(*) FROM licenses l WHERE {}", where_clause),
+        rusqlite::params_from_iter(&values),
+        |row| row.get(0),
+    )?;
+
+    let insert = format!(
+        "INSERT OR IGNORE INTO license_tags (license_id, tag, created_at)
+         SELECT l.id, ?, ? FROM licenses l
+         WHERE {} AND (SELECT COUNT(*) FROM license_tags c WHERE c.license_id = l.id) < {}",
+        where_clause, MAX_TAGS_PER_LICENSE
+    );
+    let now = now();
+    let mut added = 0;
+    for tag in tags {
+        let mut params: Vec<Value> = vec![tag.clone().into(), now.into()];
+        params.extend(values.iter().cloned());
+        added += tx.execute(&insert, rusqlite::params_from_iter(params))?;
+    }
+    tx.commit()?;
+
+    Ok((matched as usize, added))
+}
+
+// ============ Entitlements ============
+
+/// Active licenses joined with their product's tier, features and
+/// entitlements. A paused license stays active past `expires_at`, as in /validate.
+const LICENSE_GRANT_QUERY: &str =
+    "SELECT l.id, l.customer_id, l.product_id, p.tier, p.features, l.expires_at, p.entitlements
+     FROM licenses l
+     JOIN products p ON l.product_id = p.id
+     WHERE l.project_id = ?1 AND l.deleted_at IS NULL AND p.deleted_at IS NULL
+       AND l.revoked = 0 AND l.suspended_for_dispute = 0
+       AND (l.expires_at IS NULL OR l.expires_at >= ?2 OR l.paused_at IS NOT NULL)";
+
+fn license_grant_from_row(row: &rusqlite::Row) -> rusqlite::Result<LicenseGrant> {
+    let features: String = row.get(4)?;
+    let entitlements: String = row.get(6)?;
+    Ok(LicenseGrant {
+        license_id: row.get(0)?,
+        customer_id: row.get(1)?,
+        product_id: row.get(2)?,
+        tier: row.get(3)?,
+        features: serde_json::from_str(&features).unwrap_or_default(),
+        entitlements: serde_json::from_str(&entitlements).unwrap_or_default(),
+        expires_at: row.get(5)?,
+    })
+}
+
+/// Active licenses held by any of `customer_ids`, in one query.
+pub fn get_license_grants_by_customer_ids(
+    conn: &Connection,
+    project_id: &str,
+    customer_ids: &[String],
+    now: i64,
+) -> Result<Vec<LicenseGrant>> {
+    if customer_ids.is_empty() {
+        return Ok(vec![]);
+    }
+    let placeholders: Vec<String> = (3..customer_ids.len() + 3)
+        .map(|i| format!("?{}", i))
+        .collect();
+    let sql = format!(
+        "{} AND l.customer_id IN ({}) ORDER BY l.created_at, l.id",
+        LICENSE_GRANT_QUERY,
+        placeholders.join(", ")
+    );
+
+    let mut params: Vec<&dyn rusqlite::ToSql> = vec![&project_id, &now];
+    params.extend(customer_ids.iter().map(|id| id as &dyn rusqlite::ToSql));
+
+    let mut stmt = conn.prepare(&sql)?;
+    let grants = stmt
+        .query_map(rusqlite::params_from_iter(&params), license_grant_from_row)?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(grants)
+}
+
+/// Active licenses bought with the email that hashes to `email_hash`.
+pub fn get_license_grants_by_email_hash(
+    conn: &Connection,
+    project_id: &str,
+    email_hash: &str,
+    now: i64,
+) -> Result<Vec<LicenseGrant>> {
+    let mut stmt = conn.prepare(&format!(
+        "{} AND l.email_hash = ?3 ORDER BY l.created_at, l.id",
+        LICENSE_GRANT_QUERY
+    ))?;
+    let grants = stmt
+        .query_map(params![project_id, now, email_hash], license_grant_from_row)?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(grants)
+}
+
+// ============ Email Log ============
+
+/// Record an activation code email attempt, one row per license it covered.
+pub fn record_email_attempt(
+    conn: &Connection,
+    project_id: &str,
+    license_ids: &[String],
+    to_email_hash: &str,
+    trigger: crate::email::EmailTrigger,
+    result: &crate::email::EmailSendResult,
+) -> Result<()> {
+    use crate::email::EmailSendResult;
+
+    let (error_status, message_id) = match result {
+        EmailSendResult::Sent { message_id } => (None, message_id.as_deref()),
+        EmailSendResult::Failed { status } => (*status, None),
+        _ => (None, None),
+    };
+    let now = now();
+
+    let mut stmt = conn.prepare(
+        "INSERT INTO email_log (id, license_id, project_id, to_email_hash, email_trigger, result, error_status, provider_message_id, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+    )?;
+    for license_id in license_ids {
+        stmt.execute(params![
+            gen_id(),
+            license_id,
+            project_id,
+            to_email_hash,
+            trigger.as_ref(),
+            EmailLogResult::from(result).as_ref(),
+            error_status,
+            message_id,
+            now
+        ])?;
+    }
+    Ok(())
+}
+
+/// Most recent email attempts for a license, newest first.
+pub fn list_recent_emails_for_license(
+    conn: &Connection,
+    license_id: &str,
+    limit: i64,
+) -> Result<Vec<EmailLogEntry>> {
+    query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM email_log WHERE license_id = ?1
+             ORDER BY created_at DESC, id DESC LIMIT ?2",
+            EMAIL_LOG_COLS
+        ),
+        &[&license_id, &limit],
+    )
+}
+
+/// Email attempts across a project, newest first, optionally only one result.
+pub fn list_project_email_log_paginated(
+    conn: &Connection,
+    project_id: &str,
+    result: Option<EmailLogResult>,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<EmailLogEntry>, i64)> {
+    let result = result.map(|r| r.as_ref().to_string());
+
+    let total: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM email_log
+         WHERE project_id = ?1 AND (?2 IS NULL OR result = ?2)",
+        params![project_id, result],
+        |row| row.get(0),
+    )?;
+
+    let entries = query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM email_log
+             WHERE project_id = ?1 AND (?2 IS NULL OR result = ?2)
+             ORDER BY created_at DESC, id DESC LIMIT ?3 OFFSET ?4",
+            EMAIL_LOG_COLS
+        ),
+        &[&project_id, &result, &limit, &offset],
+    )?;
+
+    Ok((entries, total))
+}
@@ -0,0 +1,27 @@
+//! Database queries, one module per domain.
+//!
+//! Every query is re-exported from here, so callers keep using
+//! `queries::get_license_by_id` without knowing which module holds it.
+
+mod audit;
+mod devices;
+mod licenses;
+mod operators;
+mod orgs;
+mod payments;
+mod products;
+mod projects;
+mod system;
+mod users;
+mod util;
+
+pub use audit::*;
+pub use devices::*;
+pub use licenses::*;
+pub use operators::*;
+pub use orgs::*;
+pub use payments::*;
+pub use products::*;
+pub use projects::*;
+pub use system::*;
+pub use users::*;
@@ -0,0 +1,168 @@
+//! Operator queries: the operator role and partner operators' org scopes.
+
+use rusqlite::{Connection, params};
+
+use crate::db::EmailColumn;
+use crate::db::from_row::{OPERATOR_ORG_SCOPE_COLS, USER_COLS, query_all, query_one};
+use crate::error::{AppError, Result};
+use crate::models::*;
+
+use super::users::get_user_by_id;
+use super::util::now;
+
+// ============ Operators ============
+
+/// Grant operator role to a user. Returns the updated user.
+pub fn grant_operator_role(
+    conn: &Connection,
+    user_id: &str,
+    role: OperatorRole,
+    emails: &EmailColumn,
+) -> Result<User> {
+    let affected = conn.execute(
+        "UPDATE users SET operator_role = ?1, updated_at = ?2 WHERE id = ?3 AND deleted_at IS NULL",
+        params![role.as_ref(), now(), user_id],
+    )?;
+
+    if affected == 0 {
+        return Err(AppError::NotFound("User not found".into()));
+    }
+
+    get_user_by_id(conn, user_id, emails)?
+        .ok_or_else(|| AppError::NotFound("User not found".into()))
+}
+
+/// Revoke operator role from a user. Returns true if the user was found.
+/// Org scopes go with the role, so a later re-grant starts unscoped.
+pub fn revoke_operator_role(conn: &Connection, user_id: &str) -> Result<bool> {
+    let affected = conn.execute(
+        "UPDATE users SET operator_role = NULL, updated_at = ?1 WHERE id = ?2 AND deleted_at IS NULL",
+        params![now(), user_id],
+    )?;
+    conn.execute(
+        "DELETE FROM operator_org_scopes WHERE operator_id = ?1",
+        params![user_id],
+    )?;
+    Ok(affected > 0)
+}
+
+/// Update a user's operator role. Returns the updated user, or None if not found/not an operator.
+pub fn update_operator_role(
+    conn: &Connection,
+    user_id: &str,
+    role: OperatorRole,
+    emails: &EmailColumn,
+) -> Result<Option<User>> {
+    emails.reveal_opt(query_one(
+        conn,
+        &format!(
+            "UPDATE users SET operator_role = ?1, updated_at = ?2
+             WHERE id = ?3 AND operator_role IS NOT NULL AND deleted_at IS NULL
+             RETURNING {}",
+            USER_COLS
+        ),
+        params![role.as_ref(), now(), user_id],
+    )?)
+}
+
+/// List all operators (users with operator_role set).
+pub fn list_operators(conn: &Connection, emails: &EmailColumn) -> Result<Vec<User>> {
+    emails.reveal_all(query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM users WHERE operator_role IN ('owner', 'admin', 'view', 'partner') AND deleted_at IS NULL ORDER BY created_at DESC",
+            USER_COLS
+        ),
+        &[],
+    )?)
+}
+
+/// List operators with pagination.
+pub fn list_operators_paginated(
+    conn: &Connection,
+    limit: i64,
+    offset: i64,
+    emails: &EmailColumn,
+) -> Result<(Vec<User>, i64)> {
+    let total: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM users WHERE operator_role IN ('owner', 'admin', 'view', 'partner') AND deleted_at IS NULL",
+        [],
+        |row| row.get(0),
+    )?;
+    let items = query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM users WHERE operator_role IN ('owner', 'admin', 'view', 'partner') AND deleted_at IS NULL ORDER BY created_at DESC, id DESC LIMIT ?1 OFFSET ?2",
+            USER_COLS
+        ),
+        params![limit, offset],
+    )?;
+    Ok((emails.reveal_all(items)?, total))
+}
+
+/// Count operators.
+pub fn count_operators(conn: &Connection) -> Result<i64> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM users WHERE operator_role IN ('owner', 'admin', 'view', 'partner') AND deleted_at IS NULL",
+        [],
+        |row| row.get(0),
+    )
+    .map_err(Into::into)
+}
+
+// ============ Operator Org Scopes ============
+
+/// Give an operator access to an org. Returns None if it already has access.
+pub fn add_operator_org_scope(
+    conn: &Connection,
+    operator_id: &str,
+    org_id: &str,
+) -> Result<Option<OperatorOrgScope>> {
+    query_one(
+        conn,
+        &format!(
+            "INSERT INTO operator_org_scopes (operator_id, org_id, created_at)
+             VALUES (?1, ?2, ?3)
+             ON CONFLICT (operator_id, org_id) DO NOTHING
+             RETURNING {}",
+            OPERATOR_ORG_SCOPE_COLS
+        ),
+        params![operator_id, org_id, now()],
+    )
+}
+
+/// Remove an operator's access to an org. Returns true if it had access.
+pub fn remove_operator_org_scope(
+    conn: &Connection,
+    operator_id: &str,
+    org_id: &str,
+) -> Result<bool> {
+    let affected = conn.execute(
+        "DELETE FROM operator_org_scopes WHERE operator_id = ?1 AND org_id = ?2",
+        params![operator_id, org_id],
+    )?;
+    Ok(affected > 0)
+}
+
+pub fn list_operator_org_scopes(
+    conn: &Connection,
+    operator_id: &str,
+) -> Result<Vec<OperatorOrgScope>> {
+    query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM operator_org_scopes WHERE operator_id = ?1 ORDER BY created_at, org_id",
+            OPERATOR_ORG_SCOPE_COLS
+        ),
+        &[&operator_id],
+    )
+}
+
+pub fn operator_has_org_scope(conn: &Connection, operator_id: &str, org_id: &str) -> Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM operator_org_scopes WHERE operator_id = ?1 AND org_id = ?2)",
+        params![operator_id, org_id],
+        |row| row.get(0),
+    )
+    .map_err(Into::into)
+}
@@ -0,0 +1,1266 @@
+//! Organization queries: orgs, their data keys and service configs, limits,
+//! members, access checks, and dedicated org database routing.
+
+use rusqlite::{Connection, OptionalExtension, params};
+
+use crate::crypto::MasterKey;
+use crate::db::EmailColumn;
+use crate::db::from_row::{
+    ORG_LIMIT_COLS, ORG_MEMBER_COLS, ORG_MEMBER_WITH_USER_COLS, ORG_SERVICE_CONFIG_COLS,
+    ORGANIZATION_COLS, ORGANIZATION_WITH_STATS_COLS, query_all, query_one,
+};
+use crate::error::{AppError, Result};
+use crate::models::*;
+
+use super::licenses::{list_pending_prepaid_codes, update_pending_prepaid_codes};
+use super::projects::{update_project_private_key, update_project_webhook_secret};
+use super::util::{UpdateBuilder, gen_id, now};
+
+// ============ Organizations ============
+
+/// Create an org with a fresh data key, stored wrapped by the master key.
+pub fn create_organization(
+    conn: &Connection,
+    input: &CreateOrganization,
+    master_key: &MasterKey,
+) -> Result<Organization> {
+    let id = gen_id();
+    let now = now();
+    let org_data_key = master_key.wrap_key(&id, &MasterKey::random())?;
+
+    conn.execute(
+        "INSERT INTO organizations (id, name, payment_provider, created_at, updated_at, org_data_key)
+         VALUES (?1, ?2, NULL, ?3, ?4, ?5)",
+        params![&id, &input.name, now, now, &org_data_key],
+    )?;
+
+    Ok(Organization {
+        id,
+        name: input.name.clone(),
+        payment_provider: None,
+        created_at: now,
+        updated_at: now,
+        deleted_at: None,
+        deleted_cascade_depth: None,
+        email_from: None,
+        public_audit_retention_days: None,
+        admin_audit_retention_days: None,
+        version: 1,
+    })
+}
+
+pub fn get_organization_by_id(conn: &Connection, id: &str) -> Result<Option<Organization>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM organizations WHERE id = ?1 AND deleted_at IS NULL",
+            ORGANIZATION_COLS
+        ),
+        &[&id],
+    )
+}
+
+pub fn list_organizations(conn: &Connection) -> Result<Vec<Organization>> {
+    query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM organizations WHERE deleted_at IS NULL ORDER BY created_at DESC",
+            ORGANIZATION_COLS
+        ),
+        &[],
+    )
+}
+
+/// Count organizations, soft-deleted ones included.
+pub fn count_organizations(conn: &Connection) -> Result<i64> {
+    conn.query_row("SELECT COUNT(*) FROM organizations", [], |row| row.get(0))
+        .map_err(Into::into)
+}
+
+/// List organizations with pagination
+pub fn list_organizations_paginated(
+    conn: &Connection,
+    limit: i64,
+    offset: i64,
+    include_deleted: bool,
+) -> Result<(Vec<Organization>, i64)> {
+    let deleted_filter = if include_deleted {
+        ""
+    } else {
+        "WHERE deleted_at IS NULL"
+    };
+    let total: i64 = conn.query_row(
+        &format!("SELECT COUNT(*) FROM organizations {}", deleted_filter),
+        [],
+        |row| row.get(0),
+    )?;
+
+    let items = query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM organizations {} ORDER BY created_at DESC, id DESC LIMIT ?1 OFFSET ?2",
+            ORGANIZATION_COLS, deleted_filter
+        ),
+        params![limit, offset],
+    )?;
+
+    Ok((items, total))
+}
+
+/// List organizations with their `OrgStats` in one query, newest first.
+/// `scoped_operator_id` limits the list to a partner operator's orgs and
+/// `user_id` to orgs the user is a member of.
+pub fn list_organizations_with_stats_paginated(
+    conn: &Connection,
+    scoped_operator_id: Option<&str>,
+    user_id: Option<&str>,
+    include_deleted: bool,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<(Organization, OrgStats)>, i64)> {
+    let mut filter = String::from(
+        "WHERE (?1 IS NULL OR id IN (SELECT org_id FROM operator_org_scopes WHERE operator_id = ?1))
+         AND (?2 IS NULL OR id IN (SELECT org_id FROM org_members WHERE user_id = ?2 AND deleted_at IS NULL))",
+    );
+    if !include_deleted {
+        filter.push_str(" AND deleted_at IS NULL");
+    }
+
+    let total: i64 = conn.query_row(
+        &format!("SELECT COUNT(*) FROM organizations {}", filter),
+        params![scoped_operator_id, user_id],
+        |row| row.get(0),
+    )?;
+
+    let items = query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM organizations {} ORDER BY created_at DESC, id DESC LIMIT ?3 OFFSET ?4",
+            ORGANIZATION_WITH_STATS_COLS, filter
+        ),
+        params![scoped_operator_id, user_id, limit, offset],
+    )?;
+
+    Ok((items, total))
+}
+
+pub fn get_organization_with_stats(
+    conn: &Connection,
+    id: &str,
+) -> Result<Option<(Organization, OrgStats)>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM organizations WHERE id = ?1 AND deleted_at IS NULL",
+            ORGANIZATION_WITH_STATS_COLS
+        ),
+        &[&id],
+    )
+}
+
+/// Project and license counts for an org whose tenant data lives in a
+/// dedicated database (member_count is always 0; members stay shared).
+pub fn get_org_tenant_stats(conn: &Connection, org_id: &str) -> Result<OrgStats> {
+    conn.query_row(
+        "SELECT
+            (SELECT COUNT(*) FROM projects WHERE org_id = ?1 AND deleted_at IS NULL),
+            (SELECT COUNT(*) FROM licenses l JOIN projects p ON l.project_id = p.id
+             WHERE p.org_id = ?1 AND p.deleted_at IS NULL AND l.deleted_at IS NULL),
+            (SELECT MAX(l.created_at) FROM licenses l JOIN projects p ON l.project_id = p.id
+             WHERE p.org_id = ?1),
+            (SELECT COUNT(*) FROM licenses l JOIN projects p ON l.project_id = p.id
+             WHERE p.org_id = ?1 AND p.deleted_at IS NULL AND l.deleted_at IS NULL
+               AND l.dormant_since IS NOT NULL)",
+        [org_id],
+        |row| {
+            Ok(OrgStats {
+                project_count: row.get(0)?,
+                license_count: row.get(1)?,
+                member_count: 0,
+                last_activity_at: row.get(2)?,
+                dormant_license_count: row.get(3)?,
+            })
+        },
+    )
+    .map_err(Into::into)
+}
+
+/// Update organization's basic fields (name, payment_provider, audit retention).
+/// Service configs (stripe, lemonsqueezy, resend) are managed via upsert_org_service_config.
+pub fn update_organization(
+    conn: &Connection,
+    id: &str,
+    input: &UpdateOrganization,
+) -> Result<bool> {
+    let now = now();
+    let mut updated = false;
+
+    if let Some(ref name) = input.name {
+        conn.execute(
+            "UPDATE organizations SET name = ?1, updated_at = ?2 WHERE id = ?3",
+            params![name, now, id],
+        )?;
+        updated = true;
+    }
+    if let Some(ref payment_provider) = input.payment_provider {
+        // Some(None) clears the value, Some(Some(value)) sets it
+        conn.execute(
+            "UPDATE organizations SET payment_provider = ?1, updated_at = ?2 WHERE id = ?3",
+            params![payment_provider, now, id],
+        )?;
+        updated = true;
+    }
+    if update_org_audit_retention(
+        conn,
+        id,
+        input.public_audit_retention_days,
+        input.admin_audit_retention_days,
+    )? {
+        updated = true;
+    }
+    Ok(updated)
+}
+
+/// Bump the org's version ahead of an update. With `expected`, fails (returns
+/// false) unless the org is still at that version. Payment configs live in
+/// their own table, so an org update claims the version before changing them.
+pub fn claim_organization_version(
+    conn: &Connection,
+    id: &str,
+    expected: Option<i64>,
+) -> Result<bool> {
+    UpdateBuilder::new("organizations", id)
+        .versioned(expected)
+        .set("updated_at", now())
+        .execute(conn)
+}
+
+/// Set or clear the org's audit retention. For each setting, None leaves it
+/// unchanged and Some(None) clears it. Returns whether anything was set.
+pub fn update_org_audit_retention(
+    conn: &Connection,
+    id: &str,
+    public_days: Option<Option<i64>>,
+    admin_days: Option<Option<i64>>,
+) -> Result<bool> {
+    let mut updated = false;
+    for (column, days) in [
+        ("public_audit_retention_days", public_days),
+        ("admin_audit_retention_days", admin_days),
+    ] {
+        if let Some(days) = days {
+            conn.execute(
+                &format!(
+                    "UPDATE organizations SET {} = ?1, updated_at = ?2 WHERE id = ?3",
+                    column
+                ),
+                params![days, now(), id],
+            )?;
+            updated = true;
+        }
+    }
+    Ok(updated)
+}
+
+/// Orgs with their own audit retention, for the purge. Soft-deleted orgs are
+/// included: their entries stay until purged like any other.
+pub fn list_org_audit_retention(conn: &Connection) -> Result<Vec<OrgAuditRetention>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, public_audit_retention_days, admin_audit_retention_days FROM organizations
+         WHERE public_audit_retention_days IS NOT NULL OR admin_audit_retention_days IS NOT NULL",
+    )?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(OrgAuditRetention {
+                org_id: row.get(0)?,
+                public_days: row.get(1)?,
+                admin_days: row.get(2)?,
+            })
+        })?
+        .collect::<std::result::Result<_, _>>()?;
+    Ok(rows)
+}
+
+/// Set or clear the "from" address used with the org's Resend API key
+pub fn set_org_email_from(conn: &Connection, id: &str, email_from: Option<&str>) -> Result<()> {
+    conn.execute(
+        "UPDATE organizations SET email_from = ?1, updated_at = ?2 WHERE id = ?3",
+        params![email_from, now(), id],
+    )?;
+    Ok(())
+}
+
+/// Clear the organization's payment_provider field
+pub fn clear_org_payment_provider(conn: &Connection, id: &str) -> Result<()> {
+    conn.execute(
+        "UPDATE organizations SET payment_provider = NULL, updated_at = ?1 WHERE id = ?2",
+        params![now(), id],
+    )?;
+    Ok(())
+}
+
+// ============ Org Data Keys ============
+
+/// The key an org's secrets (service configs, project private keys) are
+/// encrypted under: the org's own data key, unwrapped with the master key.
+/// Orgs created before per-org keys have none until `--migrate-org-keys`
+/// runs, and their secrets are still under the master key itself.
+pub fn org_data_key(conn: &Connection, org_id: &str, master_key: &MasterKey) -> Result<MasterKey> {
+    let wrapped: Option<Option<Vec<u8>>> = conn
+        .query_row(
+            "SELECT org_data_key FROM organizations WHERE id = ?1",
+            params![org_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    match wrapped.flatten() {
+        Some(wrapped) => master_key.unwrap_key(org_id, &wrapped),
+        None => Ok(master_key.clone()),
+    }
+}
+
+/// Store an org's data key, already wrapped by the master key.
+pub fn set_org_data_key(conn: &Connection, org_id: &str, wrapped: &[u8]) -> Result<()> {
+    conn.execute(
+        "UPDATE organizations SET org_data_key = ?1 WHERE id = ?2",
+        params![wrapped, org_id],
+    )?;
+    Ok(())
+}
+
+/// List (org_id, wrapped data key) for every org that has one, soft-deleted
+/// orgs included (for key rotation).
+pub fn list_org_data_keys(conn: &Connection) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, org_data_key FROM organizations WHERE org_data_key IS NOT NULL ORDER BY created_at",
+    )?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// List orgs still on the master key, soft-deleted ones included.
+pub fn list_orgs_without_data_key(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn
+        .prepare("SELECT id FROM organizations WHERE org_data_key IS NULL ORDER BY created_at")?;
+    let rows = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// Re-wrap every org data key from `old_key` to `new_key` (for key rotation).
+/// The secrets under the org keys are untouched. Returns the re-wrapped org IDs.
+pub fn rewrap_org_data_keys(
+    conn: &Connection,
+    old_key: &MasterKey,
+    new_key: &MasterKey,
+) -> Result<Vec<String>> {
+    let mut rewrapped = Vec::new();
+    for (org_id, wrapped) in list_org_data_keys(conn)? {
+        let org_key = old_key.unwrap_key(&org_id, &wrapped)?;
+        set_org_data_key(conn, &org_id, &new_key.wrap_key(&org_id, &org_key)?)?;
+        rewrapped.push(org_id);
+    }
+    Ok(rewrapped)
+}
+
+/// Give an org created before per-org keys its own data key: re-encrypt its
+/// service configs and its projects' private keys, webhook secrets, and
+/// undownloaded prepaid codes from the master key to a new key, then store
+/// that key wrapped. `tenant_conn` holds the org's
+/// projects (the same connection as `conn` unless the org has a dedicated
+/// database). Returns false, changing nothing, if the org already has a key.
+///
+/// Run inside transactions: a partial run leaves secrets the org can't read.
+pub fn migrate_org_data_key(
+    conn: &Connection,
+    tenant_conn: &Connection,
+    org_id: &str,
+    master_key: &MasterKey,
+) -> Result<bool> {
+    let has_key: bool = conn.query_row(
+        "SELECT org_data_key IS NOT NULL FROM organizations WHERE id = ?1",
+        params![org_id],
+        |row| row.get(0),
+    )?;
+    if has_key {
+        return Ok(false);
+    }
+    let org_key = MasterKey::random();
+
+    for config in get_org_service_configs(conn, org_id)? {
+        let plaintext = master_key.decrypt_private_key(org_id, &config.config_encrypted)?;
+        let encrypted = org_key.encrypt_private_key(org_id, &plaintext)?;
+        update_org_service_config_encrypted(conn, &config.id, &encrypted)?;
+    }
+
+    let projects: Vec<(String, Vec<u8>, Option<Vec<u8>>)> = {
+        let mut stmt = tenant_conn.prepare(
+            "SELECT id, private_key, email_webhook_secret FROM projects WHERE org_id = ?1",
+        )?;
+        stmt.query_map(params![org_id], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })?
+        .collect::<std::result::Result<_, _>>()?
+    };
+    for (project_id, private_key, webhook_secret) in projects {
+        let plaintext = master_key.decrypt_private_key(&project_id, &private_key)?;
+        let encrypted = org_key.encrypt_private_key(&project_id, &plaintext)?;
+        update_project_private_key(tenant_conn, &project_id, &encrypted)?;
+        if let Some(webhook_secret) = webhook_secret {
+            let plaintext = master_key.decrypt_webhook_secret(&project_id, &webhook_secret)?;
+            let encrypted = org_key.encrypt_webhook_secret(&project_id, &plaintext)?;
+            update_project_webhook_secret(tenant_conn, &project_id, &encrypted)?;
+        }
+        for (batch_id, pending_codes) in list_pending_prepaid_codes(tenant_conn, &project_id)? {
+            let plaintext = master_key.decrypt_private_key(&batch_id, &pending_codes)?;
+            let encrypted = org_key.encrypt_private_key(&batch_id, &plaintext)?;
+            update_pending_prepaid_codes(tenant_conn, &batch_id, &encrypted)?;
+        }
+    }
+
+    set_org_data_key(conn, org_id, &master_key.wrap_key(org_id, &org_key)?)?;
+    Ok(true)
+}
+
+// ============ Organization Service Configs ============
+
+/// Get a service config for an org by provider
+pub fn get_org_service_config(
+    conn: &Connection,
+    org_id: &str,
+    provider: ServiceProvider,
+) -> Result<Option<OrgServiceConfig>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM org_service_configs WHERE org_id = ?1 AND provider = ?2",
+            ORG_SERVICE_CONFIG_COLS
+        ),
+        params![org_id, provider.as_str()],
+    )
+}
+
+/// Get all service configs for an org
+pub fn get_org_service_configs(conn: &Connection, org_id: &str) -> Result<Vec<OrgServiceConfig>> {
+    query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM org_service_configs WHERE org_id = ?1 ORDER BY provider",
+            ORG_SERVICE_CONFIG_COLS
+        ),
+        &[&org_id],
+    )
+}
+
+/// Get service configs for an org by category
+pub fn get_org_service_configs_by_category(
+    conn: &Connection,
+    org_id: &str,
+    category: ServiceCategory,
+) -> Result<Vec<OrgServiceConfig>> {
+    query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM org_service_configs WHERE org_id = ?1 AND category = ?2 ORDER BY provider",
+            ORG_SERVICE_CONFIG_COLS
+        ),
+        params![org_id, category.as_str()],
+    )
+}
+
+/// Check if an org has a specific service config
+pub fn org_has_service_config(
+    conn: &Connection,
+    org_id: &str,
+    provider: ServiceProvider,
+) -> Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM org_service_configs WHERE org_id = ?1 AND provider = ?2",
+        params![org_id, provider.as_str()],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// Upsert a service config (insert or update if exists)
+pub fn upsert_org_service_config(
+    conn: &Connection,
+    org_id: &str,
+    provider: ServiceProvider,
+    encrypted_config: &[u8],
+) -> Result<OrgServiceConfig> {
+    let now = now();
+    let id = gen_id();
+    let category = provider.category();
+
+    conn.execute(
+        "INSERT INTO org_service_configs (id, org_id, category, provider, config_encrypted, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)
+         ON CONFLICT(org_id, provider) DO UPDATE SET
+             config_encrypted = excluded.config_encrypted,
+             updated_at = excluded.updated_at",
+        params![id, org_id, category.as_str(), provider.as_str(), encrypted_config, now],
+    )?;
+
+    // Return the config (either newly inserted or updated)
+    get_org_service_config(conn, org_id, provider)?
+        .ok_or_else(|| AppError::Internal("Config not found after upsert".into()))
+}
+
+/// Delete a service config
+pub fn delete_org_service_config(
+    conn: &Connection,
+    org_id: &str,
+    provider: ServiceProvider,
+) -> Result<bool> {
+    let deleted = conn.execute(
+        "DELETE FROM org_service_configs WHERE org_id = ?1 AND provider = ?2",
+        params![org_id, provider.as_str()],
+    )?;
+    Ok(deleted > 0)
+}
+
+/// List all service configs (for key rotation of orgs without a data key)
+pub fn list_all_org_service_configs(conn: &Connection) -> Result<Vec<OrgServiceConfig>> {
+    query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM org_service_configs ORDER BY org_id, provider",
+            ORG_SERVICE_CONFIG_COLS
+        ),
+        &[],
+    )
+}
+
+/// Update encrypted config for a specific config row (for key rotation)
+pub fn update_org_service_config_encrypted(
+    conn: &Connection,
+    config_id: &str,
+    encrypted_config: &[u8],
+) -> Result<()> {
+    conn.execute(
+        "UPDATE org_service_configs SET config_encrypted = ?1, updated_at = ?2 WHERE id = ?3",
+        params![encrypted_config, now(), config_id],
+    )?;
+    Ok(())
+}
+
+// ============ Service Config Helper Functions ============
+
+/// Get decrypted Stripe config for an org
+pub fn get_org_stripe_config(
+    conn: &Connection,
+    org_id: &str,
+    master_key: &MasterKey,
+) -> Result<Option<StripeConfig>> {
+    let Some(config) = get_org_service_config(conn, org_id, ServiceProvider::Stripe)? else {
+        return Ok(None);
+    };
+    config
+        .decrypt_stripe_config(&org_data_key(conn, org_id, master_key)?)
+        .map(Some)
+}
+
+/// Get decrypted LemonSqueezy config for an org
+pub fn get_org_ls_config(
+    conn: &Connection,
+    org_id: &str,
+    master_key: &MasterKey,
+) -> Result<Option<LemonSqueezyConfig>> {
+    let Some(config) = get_org_service_config(conn, org_id, ServiceProvider::LemonSqueezy)? else {
+        return Ok(None);
+    };
+    config
+        .decrypt_ls_config(&org_data_key(conn, org_id, master_key)?)
+        .map(Some)
+}
+
+/// Get decrypted Resend API key for an org
+pub fn get_org_resend_api_key(
+    conn: &Connection,
+    org_id: &str,
+    master_key: &MasterKey,
+) -> Result<Option<String>> {
+    let Some(config) = get_org_service_config(conn, org_id, ServiceProvider::Resend)? else {
+        return Ok(None);
+    };
+    config
+        .decrypt_resend_api_key(&org_data_key(conn, org_id, master_key)?)
+        .map(Some)
+}
+
+pub fn delete_organization(conn: &Connection, id: &str) -> Result<bool> {
+    let deleted = conn.execute("DELETE FROM organizations WHERE id = ?1", params![id])?;
+    Ok(deleted > 0)
+}
+
+/// Rows soft-deleted at each level by [`soft_delete_organization`].
+#[derive(Debug, Default)]
+pub struct OrgDeleteCounts {
+    /// Timestamp shared by the org and every cascaded row (restore matches on it)
+    pub deleted_at: i64,
+    pub org_members: usize,
+    pub projects: usize,
+    pub products: usize,
+    pub licenses: usize,
+}
+
+/// Soft delete an organization and cascade to all children in one transaction.
+/// Cascade: org_members (depth 1), projects (depth 1), products (depth 2), licenses (depth 3)
+/// Returns None if the org doesn't exist or is already deleted.
+pub fn soft_delete_organization(
+    conn: &mut Connection,
+    id: &str,
+) -> Result<Option<OrgDeleteCounts>> {
+    use crate::db::soft_delete::{
+        PROJECTS_IN_ORG_DELETE_SUBQUERY, cascade_delete_direct, cascade_delete_via_subquery,
+        soft_delete_entity,
+    };
+
+    let tx = conn.transaction()?;
+
+    let result = soft_delete_entity(&tx, "organizations", id)?;
+    if !result.deleted {
+        return Ok(None);
+    }
+
+    let counts = OrgDeleteCounts {
+        deleted_at: result.deleted_at,
+        // Direct children (depth 1)
+        org_members: cascade_delete_direct(&tx, "org_members", "org_id", id, result.deleted_at, 1)?,
+        projects: cascade_delete_direct(&tx, "projects", "org_id", id, result.deleted_at, 1)?,
+        // Transitive children via projects (depth 2, 3)
+        products: cascade_delete_via_subquery(
+            &tx,
+            "products",
+            "project_id",
+            PROJECTS_IN_ORG_DELETE_SUBQUERY,
+            id,
+            result.deleted_at,
+            2,
+        )?,
+        licenses: cascade_delete_via_subquery(
+            &tx,
+            "licenses",
+            "project_id",
+            PROJECTS_IN_ORG_DELETE_SUBQUERY,
+            id,
+            result.deleted_at,
+            3,
+        )?,
+    };
+
+    tx.commit()?;
+    Ok(Some(counts))
+}
+
+/// Get a soft-deleted organization by ID (for restore operations).
+pub fn get_deleted_organization_by_id(conn: &Connection, id: &str) -> Result<Option<Organization>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM organizations WHERE id = ?1 AND deleted_at IS NOT NULL",
+            ORGANIZATION_COLS
+        ),
+        &[&id],
+    )
+}
+
+/// Restore a soft-deleted organization and all cascaded children.
+/// Organizations are always directly deleted (depth=0), so no cascade check needed.
+pub fn restore_organization(conn: &Connection, id: &str) -> Result<bool> {
+    use crate::db::soft_delete::{
+        PROJECTS_IN_ORG_RESTORE_SUBQUERY, restore_cascaded_direct, restore_cascaded_via_subquery,
+        restore_entity,
+    };
+
+    let Some(org) = get_deleted_organization_by_id(conn, id)? else {
+        return Ok(false);
+    };
+
+    let deleted_at = org.deleted_at.unwrap();
+
+    // Restore in reverse order: deepest children first
+    restore_cascaded_via_subquery(
+        conn,
+        "licenses",
+        "project_id",
+        PROJECTS_IN_ORG_RESTORE_SUBQUERY,
+        id,
+        deleted_at,
+    )?;
+    restore_cascaded_via_subquery(
+        conn,
+        "products",
+        "project_id",
+        PROJECTS_IN_ORG_RESTORE_SUBQUERY,
+        id,
+        deleted_at,
+    )?;
+    restore_cascaded_direct(conn, "projects", "org_id", id, deleted_at)?;
+    restore_cascaded_direct(conn, "org_members", "org_id", id, deleted_at)?;
+
+    // Restore the organization itself
+    restore_entity(conn, "organizations", id)?;
+
+    Ok(true)
+}
+
+// ============ Org Limits ============
+
+pub fn get_org_limits(conn: &Connection, org_id: &str) -> Result<Vec<OrgLimit>> {
+    query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM org_limits WHERE org_id = ?1 ORDER BY limit_name",
+            ORG_LIMIT_COLS
+        ),
+        &[&org_id],
+    )
+}
+
+pub fn get_org_limit(
+    conn: &Connection,
+    org_id: &str,
+    limit_name: OrgLimitName,
+) -> Result<Option<OrgLimit>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM org_limits WHERE org_id = ?1 AND limit_name = ?2",
+            ORG_LIMIT_COLS
+        ),
+        &[&org_id, &limit_name.as_ref()],
+    )
+}
+
+/// Set an org limit, or remove it when both values are None (unlimited).
+pub fn set_org_limit(
+    conn: &Connection,
+    org_id: &str,
+    limit_name: OrgLimitName,
+    input: &SetOrgLimit,
+) -> Result<Option<OrgLimit>> {
+    if input.is_unlimited() {
+        conn.execute(
+            "DELETE FROM org_limits WHERE org_id = ?1 AND limit_name = ?2",
+            params![org_id, limit_name.as_ref()],
+        )?;
+        return Ok(None);
+    }
+
+    let now = now();
+    conn.execute(
+        "INSERT INTO org_limits (org_id, limit_name, soft_value, hard_value, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?5)
+         ON CONFLICT(org_id, limit_name) DO UPDATE SET
+            soft_value = excluded.soft_value,
+            hard_value = excluded.hard_value,
+            updated_at = excluded.updated_at",
+        params![
+            org_id,
+            limit_name.as_ref(),
+            input.soft_value,
+            input.hard_value,
+            now
+        ],
+    )?;
+    get_org_limit(conn, org_id, limit_name)
+}
+
+/// Products in the org's busiest project, with that project's ID (0 and
+/// None when the org has no products).
+pub fn get_max_products_per_project(
+    conn: &Connection,
+    org_id: &str,
+) -> Result<(i64, Option<String>)> {
+    conn.query_row(
+        "SELECT pr.project_id, COUNT(*) AS n FROM products pr
+         JOIN projects p ON pr.project_id = p.id
+         WHERE p.org_id = ?1 AND p.deleted_at IS NULL AND pr.deleted_at IS NULL
+         GROUP BY pr.project_id ORDER BY n DESC, pr.project_id LIMIT 1",
+        [org_id],
+        |row| Ok((row.get(1)?, Some(row.get(0)?))),
+    )
+    .optional()
+    .map(|usage| usage.unwrap_or((0, None)))
+    .map_err(Into::into)
+}
+
+pub fn count_products_for_project(conn: &Connection, project_id: &str) -> Result<i64> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM products WHERE project_id = ?1 AND deleted_at IS NULL",
+        [project_id],
+        |row| row.get(0),
+    )
+    .map_err(Into::into)
+}
+
+/// Licenses created in the org's projects since `since`, deleted ones included.
+pub fn count_org_licenses_created_since(
+    conn: &Connection,
+    org_id: &str,
+    since: i64,
+) -> Result<i64> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM licenses l JOIN projects p ON l.project_id = p.id
+         WHERE p.org_id = ?1 AND l.created_at >= ?2",
+        params![org_id, since],
+        |row| row.get(0),
+    )
+    .map_err(Into::into)
+}
+
+// ============ Org Members ============
+
+/// Create an org member (links a user to an org with a role).
+pub fn create_org_member(
+    conn: &Connection,
+    org_id: &str,
+    input: &CreateOrgMember,
+) -> Result<OrgMember> {
+    let id = gen_id();
+    let now = now();
+
+    conn.execute(
+        "INSERT INTO org_members (id, user_id, org_id, role, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![&id, &input.user_id, org_id, input.role.as_ref(), now, now],
+    )?;
+
+    Ok(OrgMember {
+        id,
+        user_id: input.user_id.clone(),
+        org_id: org_id.to_string(),
+        role: input.role,
+        created_at: now,
+        updated_at: now,
+        deleted_at: None,
+        deleted_cascade_depth: None,
+        removal_scheduled_at: None,
+    })
+}
+
+pub fn get_org_member_by_id(conn: &Connection, id: &str) -> Result<Option<OrgMember>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM org_members WHERE id = ?1 AND deleted_at IS NULL",
+            ORG_MEMBER_COLS
+        ),
+        &[&id],
+    )
+}
+
+/// Get org member with user details joined.
+pub fn get_org_member_with_user_by_id(
+    conn: &Connection,
+    id: &str,
+    emails: &EmailColumn,
+) -> Result<Option<OrgMemberWithUser>> {
+    emails.reveal_opt(query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM org_members m JOIN users u ON m.user_id = u.id WHERE m.id = ?1 AND m.deleted_at IS NULL AND u.deleted_at IS NULL",
+            ORG_MEMBER_WITH_USER_COLS
+        ),
+        &[&id],
+    )?)
+}
+
+/// Get org member by user_id and org_id.
+pub fn get_org_member_by_user_and_org(
+    conn: &Connection,
+    user_id: &str,
+    org_id: &str,
+) -> Result<Option<OrgMember>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM org_members WHERE user_id = ?1 AND org_id = ?2 AND deleted_at IS NULL",
+            ORG_MEMBER_COLS
+        ),
+        params![user_id, org_id],
+    )
+}
+
+/// Get org member with user details by user_id and org_id.
+pub fn get_org_member_with_user_by_user_and_org(
+    conn: &Connection,
+    user_id: &str,
+    org_id: &str,
+    emails: &EmailColumn,
+) -> Result<Option<OrgMemberWithUser>> {
+    emails.reveal_opt(query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM org_members m JOIN users u ON m.user_id = u.id WHERE m.user_id = ?1 AND m.org_id = ?2 AND m.deleted_at IS NULL AND u.deleted_at IS NULL",
+            ORG_MEMBER_WITH_USER_COLS
+        ),
+        params![user_id, org_id],
+    )?)
+}
+
+/// List all orgs where a user is a member.
+pub fn list_orgs_by_user_id(conn: &Connection, user_id: &str) -> Result<Vec<Organization>> {
+    query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM organizations WHERE deleted_at IS NULL AND id IN (SELECT org_id FROM org_members WHERE user_id = ?1 AND deleted_at IS NULL) ORDER BY created_at DESC",
+            ORGANIZATION_COLS
+        ),
+        &[&user_id],
+    )
+}
+
+pub fn list_org_members(conn: &Connection, org_id: &str) -> Result<Vec<OrgMember>> {
+    query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM org_members WHERE org_id = ?1 AND deleted_at IS NULL ORDER BY created_at DESC",
+            ORG_MEMBER_COLS
+        ),
+        &[&org_id],
+    )
+}
+
+/// List org members with user details joined.
+pub fn list_org_members_with_user(
+    conn: &Connection,
+    org_id: &str,
+    emails: &EmailColumn,
+) -> Result<Vec<OrgMemberWithUser>> {
+    emails.reveal_all(query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM org_members m JOIN users u ON m.user_id = u.id WHERE m.org_id = ?1 AND m.deleted_at IS NULL AND u.deleted_at IS NULL ORDER BY m.created_at DESC",
+            ORG_MEMBER_WITH_USER_COLS
+        ),
+        &[&org_id],
+    )?)
+}
+
+/// List org members with pagination
+pub fn list_org_members_paginated(
+    conn: &Connection,
+    org_id: &str,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<OrgMember>, i64)> {
+    let total: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM org_members WHERE org_id = ?1 AND deleted_at IS NULL",
+        params![org_id],
+        |row| row.get(0),
+    )?;
+
+    let items = query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM org_members WHERE org_id = ?1 AND deleted_at IS NULL ORDER BY created_at DESC, id DESC LIMIT ?2 OFFSET ?3",
+            ORG_MEMBER_COLS
+        ),
+        params![org_id, limit, offset],
+    )?;
+
+    Ok((items, total))
+}
+
+/// List org members with user details and pagination.
+pub fn list_org_members_with_user_paginated(
+    conn: &Connection,
+    org_id: &str,
+    limit: i64,
+    offset: i64,
+    emails: &EmailColumn,
+) -> Result<(Vec<OrgMemberWithUser>, i64)> {
+    let total: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM org_members WHERE org_id = ?1 AND deleted_at IS NULL",
+        params![org_id],
+        |row| row.get(0),
+    )?;
+
+    let items = query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM org_members m JOIN users u ON m.user_id = u.id WHERE m.org_id = ?1 AND m.deleted_at IS NULL AND u.deleted_at IS NULL ORDER BY m.created_at DESC, m.id DESC LIMIT ?2 OFFSET ?3",
+            ORG_MEMBER_WITH_USER_COLS
+        ),
+        params![org_id, limit, offset],
+    )?;
+
+    Ok((emails.reveal_all(items)?, total))
+}
+
+/// Update an org member. Returns the updated member, or None if not found.
+pub fn update_org_member(
+    conn: &Connection,
+    id: &str,
+    input: &UpdateOrgMember,
+) -> Result<Option<OrgMember>> {
+    UpdateBuilder::new("org_members", id)
+        .with_updated_at()
+        .set_opt("role", input.role.map(|r| r.as_ref().to_string()))
+        .execute_returning(conn, ORG_MEMBER_COLS)
+}
+
+pub fn delete_org_member(conn: &Connection, id: &str) -> Result<bool> {
+    let deleted = conn.execute("DELETE FROM org_members WHERE id = ?1", params![id])?;
+    Ok(deleted > 0)
+}
+
+/// Soft delete an org member and cascade to project_members.
+pub fn soft_delete_org_member(conn: &Connection, id: &str) -> Result<bool> {
+    use crate::db::soft_delete::{cascade_delete_direct, soft_delete_entity};
+
+    let result = soft_delete_entity(conn, "org_members", id)?;
+    if !result.deleted {
+        return Ok(false);
+    }
+
+    // A restored member starts with no pending removal
+    conn.execute(
+        "UPDATE org_members SET removal_scheduled_at = NULL WHERE id = ?1",
+        params![id],
+    )?;
+
+    // Cascade to project_members (depth 1)
+    cascade_delete_direct(
+        conn,
+        "project_members",
+        "org_member_id",
+        id,
+        result.deleted_at,
+        1,
+    )?;
+
+    Ok(true)
+}
+
+/// Schedule an org member's removal. Access continues until `removal_at`,
+/// when the maintenance task soft deletes the member. Rescheduling replaces
+/// any earlier time.
+pub fn schedule_org_member_removal(conn: &Connection, id: &str, removal_at: i64) -> Result<bool> {
+    let updated = conn.execute(
+        "UPDATE org_members SET removal_scheduled_at = ?1, updated_at = ?2 WHERE id = ?3 AND deleted_at IS NULL",
+        params![removal_at, now(), id],
+    )?;
+    Ok(updated > 0)
+}
+
+/// Cancel a scheduled removal. Returns false if none was scheduled.
+pub fn cancel_org_member_removal(conn: &Connection, id: &str) -> Result<bool> {
+    let updated = conn.execute(
+        "UPDATE org_members SET removal_scheduled_at = NULL, updated_at = ?1
+         WHERE id = ?2 AND deleted_at IS NULL AND removal_scheduled_at IS NOT NULL",
+        params![now(), id],
+    )?;
+    Ok(updated > 0)
+}
+
+/// Active org members whose scheduled removal time has passed.
+pub fn list_due_org_member_removals(conn: &Connection, now: i64) -> Result<Vec<OrgMember>> {
+    query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM org_members WHERE removal_scheduled_at <= ?1 AND deleted_at IS NULL ORDER BY removal_scheduled_at, id",
+            ORG_MEMBER_COLS
+        ),
+        &[&now],
+    )
+}
+
+/// Get a soft-deleted org member by ID (for restore operations).
+pub fn get_deleted_org_member_by_id(conn: &Connection, id: &str) -> Result<Option<OrgMember>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM org_members WHERE id = ?1 AND deleted_at IS NOT NULL",
+            ORG_MEMBER_COLS
+        ),
+        &[&id],
+    )
+}
+
+/// Get a soft-deleted org member by user_id and org_id (for restore operations).
+pub fn get_deleted_org_member_by_user_and_org(
+    conn: &Connection,
+    user_id: &str,
+    org_id: &str,
+) -> Result<Option<OrgMember>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM org_members WHERE user_id = ?1 AND org_id = ?2 AND deleted_at IS NOT NULL",
+            ORG_MEMBER_COLS
+        ),
+        params![user_id, org_id],
+    )
+}
+
+/// Restore a soft-deleted org member.
+/// Returns Err if depth > 0 and force=false (was cascaded from org/user delete).
+pub fn restore_org_member(conn: &Connection, id: &str, force: bool) -> Result<bool> {
+    use crate::db::soft_delete::{check_restore_allowed, restore_entity};
+
+    let Some(member) = get_deleted_org_member_by_id(conn, id)? else {
+        return Ok(false);
+    };
+
+    check_restore_allowed(member.deleted_cascade_depth, force, "Org member")?;
+    restore_entity(conn, "org_members", id)?;
+
+    Ok(true)
+}
+
+// ============ Org Access Helpers ============
+
+/// Result type for users who can modify an organization.
+#[derive(Debug, Clone)]
+pub struct OrgModifier {
+    pub user_id: String,
+    pub email: String,
+    pub name: String,
+    /// "operator" if admin+ operator, or the org member role ("owner"/"admin")
+    pub access_type: String,
+}
+
+/// Get all users who can modify an organization.
+///
+/// Returns a combined list of:
+/// - Org members with Owner or Admin role
+/// - Operators with Admin or Owner role (they get synthetic owner access)
+///
+/// This is useful for admin dashboards and notifications.
+pub fn get_org_modifiers(
+    conn: &Connection,
+    org_id: &str,
+    emails: &EmailColumn,
+) -> Result<Vec<OrgModifier>> {
+    let mut stmt = conn.prepare(
+        "SELECT u.id, u.email, u.name, m.role as access_type
+         FROM org_members m
+         JOIN users u ON m.user_id = u.id
+         WHERE m.org_id = ?1 AND m.role IN ('owner', 'admin')
+         AND m.deleted_at IS NULL AND u.deleted_at IS NULL
+         UNION
+         SELECT u.id, u.email, u.name, 'operator' as access_type
+         FROM users u
+         WHERE u.operator_role IN ('owner', 'admin')
+         AND u.deleted_at IS NULL",
+    )?;
+
+    let rows = stmt.query_map(params![org_id], |row| {
+        Ok(OrgModifier {
+            user_id: row.get(0)?,
+            email: row.get(1)?,
+            name: row.get(2)?,
+            access_type: row.get(3)?,
+        })
+    })?;
+
+    // Sorted after decryption: stored emails may be ciphertext
+    let mut modifiers = emails.reveal_all(rows.collect::<std::result::Result<Vec<_>, _>>()?)?;
+    modifiers.sort_by(|a, b| (&a.access_type, &a.email).cmp(&(&b.access_type, &b.email)));
+    Ok(modifiers)
+}
+
+/// Check if a user can modify an organization.
+///
+/// Returns true if the user is:
+/// - An org member with Owner or Admin role, OR
+/// - An operator with Admin or Owner role
+pub fn can_user_modify_org(conn: &Connection, user_id: &str, org_id: &str) -> Result<bool> {
+    // Check org membership first (most common case)
+    let member_count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM org_members
+         WHERE user_id = ?1 AND org_id = ?2 AND role IN ('owner', 'admin') AND deleted_at IS NULL",
+        params![user_id, org_id],
+        |row| row.get(0),
+    )?;
+
+    if member_count > 0 {
+        return Ok(true);
+    }
+
+    // Check if user is an admin+ operator
+    let operator_count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM users
+         WHERE id = ?1 AND operator_role IN ('owner', 'admin') AND deleted_at IS NULL",
+        params![user_id],
+        |row| row.get(0),
+    )?;
+
+    Ok(operator_count > 0)
+}
+
+// ============================================================================
+// Org Databases (data residency)
+// ============================================================================
+
+/// Register a dedicated database file for an org.
+pub fn create_org_database(conn: &Connection, org_id: &str, db_path: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO org_databases (org_id, db_path, created_at) VALUES (?1, ?2, ?3)",
+        params![org_id, db_path, now()],
+    )?;
+    Ok(())
+}
+
+/// List all registered org databases as (org_id, db_path) pairs.
+pub fn list_org_databases(conn: &Connection) -> Result<Vec<(String, String)>> {
+    let mut stmt = conn.prepare("SELECT org_id, db_path FROM org_databases ORDER BY created_at")?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// Remove an org's database registration (and its project routes via CASCADE).
+pub fn delete_org_database(conn: &Connection, org_id: &str) -> Result<bool> {
+    let deleted = conn.execute(
+        "DELETE FROM org_databases WHERE org_id = ?1",
+        params![org_id],
+    )?;
+    Ok(deleted > 0)
+}
+
+/// Record which org database holds a project, so public endpoints can route by
+/// project ID or public key without scanning every org file.
+pub fn create_project_route(
+    conn: &Connection,
+    project_id: &str,
+    org_id: &str,
+    public_key: &str,
+) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO org_project_routes (project_id, org_id, public_key, created_at)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![project_id, org_id, public_key, now()],
+    )?;
+    Ok(())
+}
+
+/// Look up the org owning a routed project. None = project lives in the shared database.
+pub fn get_project_route_org_id(conn: &Connection, project_id: &str) -> Result<Option<String>> {
+    conn.query_row(
+        "SELECT org_id FROM org_project_routes WHERE project_id = ?1",
+        params![project_id],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(Into::into)
+}
+
+/// Look up the org owning a routed project by public key.
+pub fn get_public_key_route_org_id(conn: &Connection, public_key: &str) -> Result<Option<String>> {
+    conn.query_row(
+        "SELECT org_id FROM org_project_routes WHERE public_key = ?1",
+        params![public_key],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(Into::into)
+}
@@ -0,0 +1,545 @@
+//! Payment queries: checkout sessions, webhook deduplication, the licenses
+//! subscription reconciliation walks, and payment disputes.
+
+use std::collections::BTreeMap;
+
+use rusqlite::{Connection, params};
+
+use crate::crypto::hash_secret;
+use crate::db::from_row::{
+    DISPUTE_COLS, LICENSE_COLS, PAYMENT_SESSION_COLS, RECONCILIATION_RUN_COLS, query_all, query_one,
+};
+use crate::error::Result;
+use crate::models::*;
+
+use super::util::{checkout_values_json, gen_id, now};
+
+// ============ Payment Sessions ============
+
+pub fn create_payment_session(
+    conn: &Connection,
+    input: &CreatePaymentSession,
+) -> Result<PaymentSession> {
+    let id = gen_id();
+    let now = now();
+
+    conn.execute(
+        "INSERT INTO payment_sessions (id, product_id, customer_id, created_at, completed, upgrade_from_license_id, upgrade_days_remaining, upgrade_credit_cents, checkout_fields, accepted_terms_version, metadata)
+         VALUES (?1, ?2, ?3, ?4, 0, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            &id,
+            &input.product_id,
+            &input.customer_id,
+            now,
+            &input.upgrade_from_license_id,
+            input.upgrade_days_remaining,
+            input.upgrade_credit_cents,
+            checkout_values_json(&input.checkout_fields)?,
+            &input.accepted_terms_version,
+            checkout_values_json(&input.metadata)?
+        ],
+    )?;
+
+    Ok(PaymentSession {
+        id,
+        product_id: input.product_id.clone(),
+        customer_id: input.customer_id.clone(),
+        created_at: now,
+        completed: false,
+        license_id: None,
+        upgrade_from_license_id: input.upgrade_from_license_id.clone(),
+        upgrade_days_remaining: input.upgrade_days_remaining,
+        upgrade_credit_cents: input.upgrade_credit_cents,
+        checkout_fields: input.checkout_fields.clone(),
+        checkout_url: None,
+        needs_review: false,
+        claimed_at: None,
+        accepted_terms_version: input.accepted_terms_version.clone(),
+        metadata: input.metadata.clone(),
+    })
+}
+
+pub fn get_payment_session(conn: &Connection, id: &str) -> Result<Option<PaymentSession>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM payment_sessions WHERE id = ?1",
+            PAYMENT_SESSION_COLS
+        ),
+        &[&id],
+    )
+}
+
+/// Record the provider checkout a session opened, and the buyer's email hash
+/// if they gave one, so a repeat /buy can hand the same checkout back.
+pub fn set_payment_session_checkout(
+    conn: &Connection,
+    session_id: &str,
+    checkout_url: &str,
+    buyer_email_hash: Option<&str>,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE payment_sessions SET checkout_url = ?1, buyer_email_hash = ?2 WHERE id = ?3",
+        params![checkout_url, buyer_email_hash, session_id],
+    )?;
+    Ok(())
+}
+
+/// The buyer's newest uncompleted checkout for `product_id` opened since
+/// `since`, matched by customer ID or email hash. Upgrade checkouts are
+/// left out: they carry a price for one particular old license.
+pub fn find_pending_payment_session(
+    conn: &Connection,
+    product_id: &str,
+    customer_id: Option<&str>,
+    buyer_email_hash: Option<&str>,
+    since: i64,
+) -> Result<Option<PaymentSession>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM payment_sessions
+             WHERE product_id = ?1 AND (customer_id = ?2 OR buyer_email_hash = ?3)
+               AND completed = 0 AND checkout_url IS NOT NULL AND upgrade_from_license_id IS NULL
+               AND created_at >= ?4
+             ORDER BY created_at DESC LIMIT 1",
+            PAYMENT_SESSION_COLS
+        ),
+        &[&product_id, &customer_id, &buyer_email_hash, &since],
+    )
+}
+
+/// Atomically mark a payment session as completed, returning whether the claim was successful.
+///
+/// Uses compare-and-swap to prevent race conditions where multiple concurrent webhook
+/// requests could create multiple licenses from a single payment.
+///
+/// Returns:
+/// - `Ok(true)` if this call successfully claimed the session (was not already completed)
+/// - `Ok(false)` if the session was already completed by another request
+/// - `Err(_)` if the session doesn't exist or a database error occurred
+pub fn try_claim_payment_session(conn: &Connection, id: &str) -> Result<bool> {
+    let affected = conn.execute(
+        "UPDATE payment_sessions SET completed = 1 WHERE id = ?1 AND completed = 0",
+        params![id],
+    )?;
+    Ok(affected > 0)
+}
+
+/// Set the license_id on a payment session after license creation.
+/// Called after try_claim_payment_session succeeds and license is created.
+/// Link a completed checkout to its license and the provider payment it
+/// settled with (used to match later refunds and chargebacks).
+pub fn set_payment_session_license(
+    conn: &Connection,
+    session_id: &str,
+    license_id: &str,
+    provider_payment_id: Option<&str>,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE payment_sessions SET license_id = ?1, provider_payment_id = ?3 WHERE id = ?2",
+        params![license_id, session_id, provider_payment_id],
+    )?;
+    Ok(())
+}
+
+/// Flag a claimed checkout for manual review (an updates-only renewal bought
+/// by someone with no license to renew).
+pub fn mark_payment_session_for_review(conn: &Connection, session_id: &str) -> Result<()> {
+    conn.execute(
+        "UPDATE payment_sessions SET needs_review = 1 WHERE id = ?1",
+        params![session_id],
+    )?;
+    Ok(())
+}
+
+// ============ Payment Session Claims ============
+
+const CLAIM_CODE_TTL_SECONDS: i64 = 5 * 60; // 5 minutes
+
+/// Generate a payment session claim code: `pcl_` followed by 192 random bits in hex.
+pub fn generate_claim_code() -> String {
+    use rand::RngCore;
+    use rand::rngs::OsRng;
+    let mut bytes = [0u8; 24];
+    OsRng.fill_bytes(&mut bytes);
+    format!("pcl_{}", hex::encode(bytes))
+}
+
+/// Issue a fresh claim code for a completed checkout whose license hasn't been
+/// claimed yet, replacing any earlier code. Only the code's hash is stored.
+///
+/// Returns the code and its expiry, or `None` if the session has nothing to
+/// claim (not completed, held for review, or already claimed).
+pub fn issue_payment_session_claim(
+    conn: &Connection,
+    session_id: &str,
+) -> Result<Option<(String, i64)>> {
+    let code = generate_claim_code();
+    let expires_at = now() + CLAIM_CODE_TTL_SECONDS;
+
+    let affected = conn.execute(
+        "UPDATE payment_sessions SET claim_code_hash = ?1, claim_expires_at = ?2
+         WHERE id = ?3 AND completed = 1 AND license_id IS NOT NULL AND needs_review = 0
+           AND claimed_at IS NULL",
+        params![hash_secret(&code), expires_at, session_id],
+    )?;
+    Ok((affected > 0).then_some((code, expires_at)))
+}
+
+/// Atomically exchange a session's claim code, returning the session.
+///
+/// Like `try_claim_activation_code`, the UPDATE only succeeds once: for a
+/// code that matches this session, hasn't expired, and hasn't been used.
+/// Returns `Ok(None)` otherwise.
+pub fn try_claim_payment_session_code(
+    conn: &Connection,
+    session_id: &str,
+    code: &str,
+) -> Result<Option<PaymentSession>> {
+    let now = now();
+    let affected = conn.execute(
+        "UPDATE payment_sessions SET claimed_at = ?1
+         WHERE id = ?2 AND claim_code_hash = ?3 AND claimed_at IS NULL AND claim_expires_at > ?1",
+        params![now, session_id, hash_secret(code)],
+    )?;
+    if affected == 0 {
+        return Ok(None);
+    }
+    get_payment_session(conn, session_id)
+}
+
+/// Undo a claim whose redemption failed (say, the license's device limit was
+/// reached), so the same code can be retried until it expires.
+pub fn release_payment_session_claim(conn: &Connection, session_id: &str) -> Result<()> {
+    conn.execute(
+        "UPDATE payment_sessions SET claimed_at = NULL WHERE id = ?1",
+        params![session_id],
+    )?;
+    Ok(())
+}
+
+/// Store the checkout field values a license was bought with.
+pub fn set_license_checkout_fields(
+    conn: &Connection,
+    license_id: &str,
+    values: &BTreeMap<String, String>,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE licenses SET checkout_fields = ?1 WHERE id = ?2",
+        params![checkout_values_json(values)?, license_id],
+    )?;
+    Ok(())
+}
+
+/// Store the `/buy` metadata a license was bought with.
+pub fn set_license_metadata(
+    conn: &Connection,
+    license_id: &str,
+    metadata: &BTreeMap<String, String>,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE licenses SET metadata = ?1 WHERE id = ?2",
+        params![checkout_values_json(metadata)?, license_id],
+    )?;
+    Ok(())
+}
+
+/// Record the terms version a license was bought under.
+pub fn set_license_accepted_terms(
+    conn: &Connection,
+    license_id: &str,
+    version: &str,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE licenses SET accepted_terms_version = ?1 WHERE id = ?2",
+        params![version, license_id],
+    )?;
+    Ok(())
+}
+
+/// Find the license bought with a provider payment (for refund and chargeback webhooks)
+pub fn get_license_by_provider_payment(
+    conn: &Connection,
+    provider: &str,
+    provider_payment_id: &str,
+) -> Result<Option<License>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT l.{} FROM licenses l
+             JOIN payment_sessions s ON s.license_id = l.id
+             WHERE l.payment_provider = ?1 AND s.provider_payment_id = ?2 AND l.deleted_at IS NULL",
+            LICENSE_COLS.replace(", ", ", l.")
+        ),
+        &[&provider, &provider_payment_id],
+    )
+}
+
+/// Find the license bought in a provider checkout order (Stripe: checkout
+/// session, LemonSqueezy: order), for refunds that don't name the payment
+pub fn get_license_by_provider_order(
+    conn: &Connection,
+    provider: &str,
+    provider_order_id: &str,
+) -> Result<Option<License>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM licenses WHERE payment_provider = ?1 AND payment_provider_order_id = ?2 AND deleted_at IS NULL",
+            LICENSE_COLS
+        ),
+        &[&provider, &provider_order_id],
+    )
+}
+
+/// Purge old incomplete payment sessions beyond the retention period.
+/// Only deletes sessions where completed = 0 (abandoned carts).
+/// Completed sessions are kept as they link to licenses.
+/// Returns the number of deleted records.
+pub fn purge_old_payment_sessions(conn: &Connection, retention_days: i64) -> Result<usize> {
+    let cutoff = now() - (retention_days * 86400);
+    let deleted = conn.execute(
+        "DELETE FROM payment_sessions WHERE completed = 0 AND created_at < ?1",
+        params![cutoff],
+    )?;
+    Ok(deleted)
+}
+
+// ============ Webhook Event Deduplication ============
+
+/// Atomically record a webhook event, returning true if this is a new event.
+/// Returns false if the event was already processed (replay attack prevention).
+///
+/// Uses INSERT OR IGNORE for atomicity - if the (provider, event_id) pair
+/// already exists, the insert is silently ignored and we return false.
+pub fn try_record_webhook_event(conn: &Connection, provider: &str, event_id: &str) -> Result<bool> {
+    let affected = conn.execute(
+        "INSERT OR IGNORE INTO webhook_events (provider, event_id, created_at) VALUES (?1, ?2, ?3)",
+        params![provider, event_id, now()],
+    )?;
+    Ok(affected > 0)
+}
+
+/// Purge old webhook events beyond the retention period.
+/// These are only used for replay attack prevention (Stripe/LemonSqueezy retry for ~3 days max).
+/// Returns the number of deleted records.
+pub fn purge_old_webhook_events(conn: &Connection, retention_days: i64) -> Result<usize> {
+    let cutoff = now() - (retention_days * 86400);
+    let deleted = conn.execute(
+        "DELETE FROM webhook_events WHERE created_at < ?1",
+        params![cutoff],
+    )?;
+    Ok(deleted)
+}
+
+/// Webhook events received at or after `since`.
+pub fn count_webhook_events_since(conn: &Connection, since: i64) -> Result<i64> {
+    Ok(conn.query_row(
+        "SELECT COUNT(*) FROM webhook_events WHERE created_at >= ?1",
+        params![since],
+        |row| row.get(0),
+    )?)
+}
+
+// ============ Subscription Reconciliation ============
+
+/// An org's live licenses linked to a `provider` subscription, oldest first.
+/// Revoked and deleted licenses are left out.
+pub fn list_subscription_licenses(
+    conn: &Connection,
+    org_id: &str,
+    provider: &str,
+) -> Result<Vec<License>> {
+    query_all(
+        conn,
+        &format!(
+            "SELECT l.{} FROM licenses l
+             JOIN projects p ON l.project_id = p.id
+             WHERE p.org_id = ?1 AND l.payment_provider = ?2
+               AND l.payment_provider_subscription_id IS NOT NULL
+               AND l.revoked = 0 AND l.deleted_at IS NULL AND p.deleted_at IS NULL
+             ORDER BY l.created_at, l.id",
+            LICENSE_COLS.replace(", ", ", l.")
+        ),
+        &[&org_id, &provider],
+    )
+}
+
+pub fn create_reconciliation_run(conn: &Connection, run: &ReconciliationRun) -> Result<()> {
+    conn.execute(
+        "INSERT INTO reconciliation_runs (id, org_id, provider, applied, started_by, started_at, finished_at, checked, report)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            &run.id,
+            &run.org_id,
+            &run.provider,
+            run.applied,
+            &run.started_by,
+            run.started_at,
+            run.finished_at,
+            run.checked,
+            serde_json::to_string(&run.discrepancies)?
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn get_reconciliation_run(conn: &Connection, id: &str) -> Result<Option<ReconciliationRun>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM reconciliation_runs WHERE id = ?1",
+            RECONCILIATION_RUN_COLS
+        ),
+        &[&id],
+    )
+}
+
+/// The most recent reconciliation runs across all orgs, newest first.
+pub fn list_recent_reconciliation_runs(
+    conn: &Connection,
+    limit: i64,
+) -> Result<Vec<ReconciliationRun>> {
+    query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM reconciliation_runs ORDER BY started_at DESC, id DESC LIMIT ?1",
+            RECONCILIATION_RUN_COLS
+        ),
+        &[&limit],
+    )
+}
+
+// ============ Disputes ============
+
+/// Record a dispute opened against a license's payment. Returns None if the
+/// provider's dispute was already recorded (a redelivered webhook).
+pub fn create_dispute(
+    conn: &Connection,
+    license: &License,
+    provider: &str,
+    input: &CreateDispute,
+    now: i64,
+) -> Result<Option<Dispute>> {
+    let id = gen_id();
+    let affected = conn.execute(
+        "INSERT OR IGNORE INTO disputes (id, license_id, project_id, provider, provider_dispute_id, payment_id, amount_cents, currency, reason, status, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, 'open', ?10)",
+        params![
+            &id,
+            &license.id,
+            &license.project_id,
+            provider,
+            &input.provider_dispute_id,
+            &input.payment_id,
+            input.amount_cents,
+            &input.currency,
+            &input.reason,
+            now
+        ],
+    )?;
+    if affected == 0 {
+        return Ok(None);
+    }
+    Ok(Some(Dispute {
+        id,
+        license_id: license.id.clone(),
+        project_id: license.project_id.clone(),
+        provider: provider.to_string(),
+        provider_dispute_id: input.provider_dispute_id.clone(),
+        payment_id: input.payment_id.clone(),
+        amount_cents: input.amount_cents,
+        currency: input.currency.clone(),
+        reason: input.reason.clone(),
+        status: DisputeStatus::Open,
+        created_at: now,
+        closed_at: None,
+    }))
+}
+
+pub fn get_dispute_by_provider_id(
+    conn: &Connection,
+    provider: &str,
+    provider_dispute_id: &str,
+) -> Result<Option<Dispute>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM disputes WHERE provider = ?1 AND provider_dispute_id = ?2",
+            DISPUTE_COLS
+        ),
+        &[&provider, &provider_dispute_id],
+    )
+}
+
+/// Decide an open dispute. Returns false if it was already closed.
+pub fn close_dispute(conn: &Connection, id: &str, status: DisputeStatus, now: i64) -> Result<bool> {
+    let affected = conn.execute(
+        "UPDATE disputes SET status = ?2, closed_at = ?3 WHERE id = ?1 AND status = 'open'",
+        params![id, status.as_ref(), now],
+    )?;
+    Ok(affected > 0)
+}
+
+pub fn count_open_disputes_for_license(conn: &Connection, license_id: &str) -> Result<i64> {
+    Ok(conn.query_row(
+        "SELECT COUNT(*) FROM disputes WHERE license_id = ?1 AND status = 'open'",
+        params![license_id],
+        |row| row.get(0),
+    )?)
+}
+
+pub fn set_license_suspended_for_dispute(
+    conn: &Connection,
+    license_id: &str,
+    suspended: bool,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE licenses SET suspended_for_dispute = ?2 WHERE id = ?1",
+        params![license_id, suspended as i32],
+    )?;
+    Ok(())
+}
+
+/// A license's disputes, newest first.
+pub fn list_disputes_for_license(conn: &Connection, license_id: &str) -> Result<Vec<Dispute>> {
+    query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM disputes WHERE license_id = ?1 ORDER BY created_at DESC, id DESC",
+            DISPUTE_COLS
+        ),
+        &[&license_id],
+    )
+}
+
+/// Disputes across a project, newest first, optionally only one status.
+pub fn list_project_disputes_paginated(
+    conn: &Connection,
+    project_id: &str,
+    status: Option<DisputeStatus>,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<Dispute>, i64)> {
+    let status = status.map(|s| s.as_ref().to_string());
+
+    let total: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM disputes WHERE project_id = ?1 AND (?2 IS NULL OR status = ?2)",
+        params![project_id, status],
+        |row| row.get(0),
+    )?;
+
+    let disputes = query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM disputes
+             WHERE project_id = ?1 AND (?2 IS NULL OR status = ?2)
+             ORDER BY created_at DESC, id DESC LIMIT ?3 OFFSET ?4",
+            DISPUTE_COLS
+        ),
+        &[&project_id, &status, &limit, &offset],
+    )?;
+
+    Ok((disputes, total))
+}
@@ -0,0 +1,674 @@
+//! Product queries, including payment provider links.
+
+use rusqlite::{Connection, params};
+
+use crate::db::from_row::{
+    PRODUCT_COLS, PRODUCT_TEMPLATE_COLS, PROVIDER_LINK_COLS, query_all, query_one,
+};
+use crate::error::Result;
+use crate::models::*;
+
+use super::util::{UpdateBuilder, gen_id, now};
+
+// ============ Products ============
+
+pub fn create_product(
+    conn: &Connection,
+    project_id: &str,
+    input: &CreateProduct,
+) -> Result<Product> {
+    let id = gen_id();
+    let now = now();
+    let features_json = serde_json::to_string(&input.features)?;
+    let checkout_fields_json = serde_json::to_string(&input.checkout_fields)?;
+    let entitlements_json = serde_json::to_string(&input.entitlements)?;
+
+    conn.execute(
+        "INSERT INTO products (id, project_id, name, tier, license_exp_days, updates_exp_days, activation_limit, device_limit, device_inactive_days, features, price_cents, currency, created_at, seat_count, available_from, available_until, checkout_fields, extends_updates_for_product_id, renewal_fallback, entitlements, revoke_on_partial_refund)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21)",
+        params![
+            &id,
+            project_id,
+            &input.name,
+            &input.tier,
+            input.license_exp_days,
+            input.updates_exp_days,
+            input.activation_limit,
+            input.device_limit,
+            input.device_inactive_days,
+            &features_json,
+            input.price_cents,
+            &input.currency,
+            now,
+            input.seat_count,
+            input.available_from,
+            input.available_until,
+            &checkout_fields_json,
+            &input.extends_updates_for_product_id,
+            input.renewal_fallback.as_ref(),
+            &entitlements_json,
+            input.revoke_on_partial_refund
+        ],
+    )?;
+
+    Ok(Product {
+        id,
+        project_id: project_id.to_string(),
+        name: input.name.clone(),
+        tier: input.tier.clone(),
+        license_exp_days: input.license_exp_days,
+        updates_exp_days: input.updates_exp_days,
+        activation_limit: input.activation_limit,
+        device_limit: input.device_limit,
+        device_inactive_days: input.device_inactive_days,
+        features: input.features.clone(),
+        entitlements: input.entitlements.clone(),
+        price_cents: input.price_cents,
+        currency: input.currency.clone(),
+        created_at: now,
+        deleted_at: None,
+        deleted_cascade_depth: None,
+        seat_count: input.seat_count,
+        available_from: input.available_from,
+        available_until: input.available_until,
+        checkout_fields: input.checkout_fields.clone(),
+        extends_updates_for_product_id: input.extends_updates_for_product_id.clone(),
+        renewal_fallback: input.renewal_fallback,
+        upsell: None,
+        version: 1,
+        revoke_on_partial_refund: input.revoke_on_partial_refund,
+    })
+}
+
+pub fn get_product_by_id(conn: &Connection, id: &str) -> Result<Option<Product>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM products WHERE id = ?1 AND deleted_at IS NULL",
+            PRODUCT_COLS
+        ),
+        &[&id],
+    )
+}
+
+/// Batch fetch products by IDs. Returns all found products (missing IDs are silently skipped).
+pub fn get_products_by_ids(conn: &Connection, ids: &[&str]) -> Result<Vec<Product>> {
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let placeholders: Vec<String> = (1..=ids.len()).map(|i| format!("?{}", i)).collect();
+    let sql = format!(
+        "SELECT {} FROM products WHERE id IN ({}) AND deleted_at IS NULL",
+        PRODUCT_COLS,
+        placeholders.join(", ")
+    );
+    let params: Vec<&dyn rusqlite::ToSql> =
+        ids.iter().map(|id| id as &dyn rusqlite::ToSql).collect();
+    query_all(conn, &sql, &params)
+}
+
+pub fn list_products_for_project(conn: &Connection, project_id: &str) -> Result<Vec<Product>> {
+    query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM products WHERE project_id = ?1 AND deleted_at IS NULL ORDER BY created_at DESC",
+            PRODUCT_COLS
+        ),
+        &[&project_id],
+    )
+}
+
+pub fn list_products_for_project_paginated(
+    conn: &Connection,
+    project_id: &str,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<Product>, i64)> {
+    let total: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM products WHERE project_id = ?1 AND deleted_at IS NULL",
+        params![project_id],
+        |row| row.get(0),
+    )?;
+
+    let products = query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM products WHERE project_id = ?1 AND deleted_at IS NULL ORDER BY created_at DESC, id DESC LIMIT ?2 OFFSET ?3",
+            PRODUCT_COLS
+        ),
+        params![project_id, limit, offset],
+    )?;
+
+    Ok((products, total))
+}
+
+/// Update a product. Returns the updated product, or None if not found (or
+/// no longer at `input.expected_version`).
+pub fn update_product(
+    conn: &Connection,
+    id: &str,
+    input: &UpdateProduct,
+) -> Result<Option<Product>> {
+    let features_json = input
+        .features
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()?;
+    let checkout_fields_json = input
+        .checkout_fields
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()?;
+    let entitlements_json = input
+        .entitlements
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()?;
+    // Each upsell column is replaced (or cleared) together
+    let upsell = input.upsell.as_ref().map(Option::as_ref);
+    let upsell_highlights_json = upsell
+        .map(|upsell| serde_json::to_string(upsell.map_or(&[][..], |u| &u.highlight_features)))
+        .transpose()?;
+
+    UpdateBuilder::new("products", id)
+        .versioned(input.expected_version)
+        .set_opt("name", input.name.clone())
+        .set_opt("tier", input.tier.clone())
+        .set_opt("license_exp_days", input.license_exp_days)
+        .set_opt("updates_exp_days", input.updates_exp_days)
+        .set_opt("activation_limit", input.activation_limit)
+        .set_opt("device_limit", input.device_limit)
+        .set_opt("device_inactive_days", input.device_inactive_days)
+        .set_opt("features", features_json)
+        .set_opt("entitlements", entitlements_json)
+        .set_opt("price_cents", input.price_cents)
+        .set_opt("currency", input.currency.clone())
+        .set_opt("seat_count", input.seat_count)
+        .set_opt("available_from", input.available_from)
+        .set_opt("available_until", input.available_until)
+        .set_opt("checkout_fields", checkout_fields_json)
+        .set_opt(
+            "extends_updates_for_product_id",
+            input.extends_updates_for_product_id.clone(),
+        )
+        .set_opt(
+            "renewal_fallback",
+            input.renewal_fallback.map(|f| f.as_ref().to_string()),
+        )
+        .set_opt(
+            "upgrade_to_product_id",
+            upsell.map(|u| u.map(|u| u.upgrade_to_product_id.clone())),
+        )
+        .set_opt("upsell_highlight_features", upsell_highlights_json)
+        .set_opt("revoke_on_partial_refund", input.revoke_on_partial_refund)
+        .set_opt(
+            "upsell_blurb",
+            upsell.map(|u| u.and_then(|u| u.marketing_blurb.clone())),
+        )
+        .execute_returning(conn, PRODUCT_COLS)
+}
+
+/// Live products whose upsell offers an upgrade to `product_id`.
+pub fn list_upsells_to_product(conn: &Connection, product_id: &str) -> Result<Vec<Product>> {
+    query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM products WHERE upgrade_to_product_id = ?1 AND deleted_at IS NULL",
+            PRODUCT_COLS
+        ),
+        &[&product_id],
+    )
+}
+
+/// Whether any live product is an updates-only renewal of `product_id`.
+pub fn product_has_updates_renewals(conn: &Connection, product_id: &str) -> Result<bool> {
+    Ok(conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM products WHERE extends_updates_for_product_id = ?1 AND deleted_at IS NULL)",
+        params![product_id],
+        |row| row.get(0),
+    )?)
+}
+
+/// Live updates-only renewals of `product_id` that are on sale at `now`.
+pub fn list_updates_renewals_for_product(
+    conn: &Connection,
+    product_id: &str,
+    now: i64,
+) -> Result<Vec<Product>> {
+    query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM products
+             WHERE extends_updates_for_product_id = ?1 AND deleted_at IS NULL
+               AND (available_from IS NULL OR available_from <= ?2)
+               AND (available_until IS NULL OR available_until > ?2)
+             ORDER BY created_at",
+            PRODUCT_COLS
+        ),
+        params![product_id, now],
+    )
+}
+
+pub fn delete_product(conn: &Connection, id: &str) -> Result<bool> {
+    let deleted = conn.execute("DELETE FROM products WHERE id = ?1", params![id])?;
+    Ok(deleted > 0)
+}
+
+/// Soft delete a product and cascade to licenses.
+pub fn soft_delete_product(conn: &Connection, id: &str) -> Result<bool> {
+    use crate::db::soft_delete::{cascade_delete_direct, soft_delete_entity};
+
+    let result = soft_delete_entity(conn, "products", id)?;
+    if !result.deleted {
+        return Ok(false);
+    }
+
+    // Cascade to licenses (depth 1)
+    cascade_delete_direct(conn, "licenses", "product_id", id, result.deleted_at, 1)?;
+
+    Ok(true)
+}
+
+/// Get a soft-deleted product by ID (for restore operations).
+pub fn get_deleted_product_by_id(conn: &Connection, id: &str) -> Result<Option<Product>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM products WHERE id = ?1 AND deleted_at IS NOT NULL",
+            PRODUCT_COLS
+        ),
+        &[&id],
+    )
+}
+
+/// Get a soft-deleted product by name. Deleted products keep their name, so a
+/// new product can't reuse it.
+pub fn get_deleted_product_by_name(
+    conn: &Connection,
+    project_id: &str,
+    name: &str,
+) -> Result<Option<Product>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM products WHERE project_id = ?1 AND name = ?2 AND deleted_at IS NOT NULL",
+            PRODUCT_COLS
+        ),
+        &[&project_id, &name],
+    )
+}
+
+/// Restore a soft-deleted product and all cascaded licenses.
+/// Returns Err if depth > 0 and force=false (was cascaded from project/org delete).
+pub fn restore_product(conn: &Connection, id: &str, force: bool) -> Result<bool> {
+    use crate::db::soft_delete::{check_restore_allowed, restore_cascaded_direct, restore_entity};
+
+    let Some(product) = get_deleted_product_by_id(conn, id)? else {
+        return Ok(false);
+    };
+
+    check_restore_allowed(product.deleted_cascade_depth, force, "Product")?;
+
+    let deleted_at = product.deleted_at.unwrap();
+
+    // Restore licenses that were cascaded
+    restore_cascaded_direct(conn, "licenses", "product_id", id, deleted_at)?;
+
+    // Restore the product itself
+    restore_entity(conn, "products", id)?;
+
+    Ok(true)
+}
+
+// ============ Product Templates ============
+
+pub fn create_product_template(
+    conn: &Connection,
+    org_id: &str,
+    input: &CreateProductTemplate,
+) -> Result<ProductTemplate> {
+    let id = gen_id();
+    let now = now();
+    let features_json = serde_json::to_string(&input.features)?;
+    let checkout_fields_json = serde_json::to_string(&input.checkout_fields)?;
+
+    conn.execute(
+        "INSERT INTO product_templates (id, org_id, name, tier, license_exp_days, updates_exp_days, activation_limit, device_limit, device_inactive_days, features, price_cents, currency, seat_count, checkout_fields, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?15)",
+        params![
+            &id,
+            org_id,
+            &input.name,
+            &input.tier,
+            input.license_exp_days,
+            input.updates_exp_days,
+            input.activation_limit,
+            input.device_limit,
+            input.device_inactive_days,
+            &features_json,
+            input.price_cents,
+            &input.currency,
+            input.seat_count,
+            &checkout_fields_json,
+            now
+        ],
+    )?;
+
+    Ok(ProductTemplate {
+        id,
+        org_id: org_id.to_string(),
+        name: input.name.clone(),
+        tier: input.tier.clone(),
+        license_exp_days: input.license_exp_days,
+        updates_exp_days: input.updates_exp_days,
+        activation_limit: input.activation_limit,
+        device_limit: input.device_limit,
+        device_inactive_days: input.device_inactive_days,
+        features: input.features.clone(),
+        price_cents: input.price_cents,
+        currency: input.currency.clone(),
+        seat_count: input.seat_count,
+        checkout_fields: input.checkout_fields.clone(),
+        created_at: now,
+        updated_at: now,
+    })
+}
+
+pub fn get_product_template(
+    conn: &Connection,
+    org_id: &str,
+    id: &str,
+) -> Result<Option<ProductTemplate>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM product_templates WHERE id = ?1 AND org_id = ?2",
+            PRODUCT_TEMPLATE_COLS
+        ),
+        &[&id, &org_id],
+    )
+}
+
+/// An org's product templates, oldest first.
+pub fn list_product_templates(conn: &Connection, org_id: &str) -> Result<Vec<ProductTemplate>> {
+    query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM product_templates WHERE org_id = ?1 ORDER BY created_at, id",
+            PRODUCT_TEMPLATE_COLS
+        ),
+        &[&org_id],
+    )
+}
+
+pub fn update_product_template(
+    conn: &Connection,
+    id: &str,
+    input: &UpdateProductTemplate,
+) -> Result<bool> {
+    let features_json = input
+        .features
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()?;
+    let checkout_fields_json = input
+        .checkout_fields
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()?;
+
+    UpdateBuilder::new("product_templates", id)
+        .set_opt("name", input.name.clone())
+        .set_opt("tier", input.tier.clone())
+        .set_opt("license_exp_days", input.license_exp_days)
+        .set_opt("updates_exp_days", input.updates_exp_days)
+        .set_opt("activation_limit", input.activation_limit)
+        .set_opt("device_limit", input.device_limit)
+        .set_opt("device_inactive_days", input.device_inactive_days)
+        .set_opt("features", features_json)
+        .set_opt("price_cents", input.price_cents)
+        .set_opt("currency", input.currency.clone())
+        .set_opt("seat_count", input.seat_count)
+        .set_opt("checkout_fields", checkout_fields_json)
+        .with_updated_at()
+        .execute(conn)
+}
+
+/// Delete a template. Products made from it are separate rows and stay.
+pub fn delete_product_template(conn: &Connection, org_id: &str, id: &str) -> Result<bool> {
+    let deleted = conn.execute(
+        "DELETE FROM product_templates WHERE id = ?1 AND org_id = ?2",
+        params![id, org_id],
+    )?;
+    Ok(deleted > 0)
+}
+
+/// Create one product per template in `project_id`, in order. Run it in a
+/// transaction so a failure leaves none of them behind.
+pub fn create_products_from_templates(
+    conn: &Connection,
+    project_id: &str,
+    templates: &[ProductTemplate],
+) -> Result<Vec<Product>> {
+    templates
+        .iter()
+        .map(|template| create_product(conn, project_id, &template.product()))
+        .collect()
+}
+
+// ============ Product Provider Links ============
+
+pub fn create_provider_link(
+    conn: &Connection,
+    product_id: &str,
+    input: &CreateProviderLink,
+) -> Result<ProductProviderLink> {
+    let id = gen_id();
+    let now = now();
+
+    conn.execute(
+        "INSERT INTO product_provider_links (id, product_id, provider, linked_id, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![&id, product_id, &input.provider, &input.linked_id, now, now],
+    )?;
+
+    Ok(ProductProviderLink {
+        id,
+        product_id: product_id.to_string(),
+        provider: input.provider.clone(),
+        linked_id: input.linked_id.clone(),
+        created_at: now,
+        updated_at: now,
+    })
+}
+
+pub fn get_provider_link(
+    conn: &Connection,
+    product_id: &str,
+    provider: &str,
+) -> Result<Option<ProductProviderLink>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM product_provider_links WHERE product_id = ?1 AND provider = ?2",
+            PROVIDER_LINK_COLS
+        ),
+        &[&product_id, &provider],
+    )
+}
+
+pub fn get_provider_link_by_id(conn: &Connection, id: &str) -> Result<Option<ProductProviderLink>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM product_provider_links WHERE id = ?1",
+            PROVIDER_LINK_COLS
+        ),
+        &[&id],
+    )
+}
+
+pub fn get_provider_links_for_product(
+    conn: &Connection,
+    product_id: &str,
+) -> Result<Vec<ProductProviderLink>> {
+    query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM product_provider_links WHERE product_id = ?1 ORDER BY created_at",
+            PROVIDER_LINK_COLS
+        ),
+        &[&product_id],
+    )
+}
+
+pub fn update_provider_link(
+    conn: &Connection,
+    id: &str,
+    input: &UpdateProviderLink,
+) -> Result<bool> {
+    UpdateBuilder::new("product_provider_links", id)
+        .with_updated_at()
+        .set_opt("linked_id", input.linked_id.clone())
+        .execute(conn)
+}
+
+pub fn delete_provider_link(conn: &Connection, id: &str) -> Result<bool> {
+    let deleted = conn.execute(
+        "DELETE FROM product_provider_links WHERE id = ?1",
+        params![id],
+    )?;
+    Ok(deleted > 0)
+}
+
+/// Product with its provider links included inline.
+/// Used for API responses to avoid N+1 queries.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ProductWithProviderLinks {
+    #[serde(flatten)]
+    pub product: Product,
+    pub provider_links: Vec<ProductProviderLink>,
+}
+
+pub fn get_product_with_links(
+    conn: &Connection,
+    id: &str,
+) -> Result<Option<ProductWithProviderLinks>> {
+    let product = get_product_by_id(conn, id)?;
+    match product {
+        Some(product) => {
+            let provider_links = get_provider_links_for_product(conn, &product.id)?;
+            Ok(Some(ProductWithProviderLinks {
+                product,
+                provider_links,
+            }))
+        }
+        None => Ok(None),
+    }
+}
+
+pub fn list_products_with_links(
+    conn: &Connection,
+    project_id: &str,
+) -> Result<Vec<ProductWithProviderLinks>> {
+    // Get all products for the project
+    let products = list_products_for_project(conn, project_id)?;
+
+    if products.is_empty() {
+        return Ok(vec![]);
+    }
+
+    // Get all provider links for these products in one query
+    let product_ids: Vec<&str> = products.iter().map(|p| p.id.as_str()).collect();
+    let placeholders: Vec<String> = (1..=product_ids.len()).map(|i| format!("?{}", i)).collect();
+    let sql = format!(
+        "SELECT {} FROM product_provider_links WHERE product_id IN ({}) ORDER BY product_id, created_at",
+        PROVIDER_LINK_COLS,
+        placeholders.join(", ")
+    );
+
+    let params: Vec<&dyn rusqlite::ToSql> = product_ids
+        .iter()
+        .map(|id| id as &dyn rusqlite::ToSql)
+        .collect();
+
+    let links: Vec<ProductProviderLink> = query_all(conn, &sql, &params)?;
+
+    // Group links by product_id
+    let mut link_map: std::collections::HashMap<String, Vec<ProductProviderLink>> =
+        std::collections::HashMap::new();
+    for link in links {
+        link_map
+            .entry(link.product_id.clone())
+            .or_default()
+            .push(link);
+    }
+
+    // Build result
+    let result = products
+        .into_iter()
+        .map(|product| {
+            let provider_links = link_map.remove(&product.id).unwrap_or_default();
+            ProductWithProviderLinks {
+                product,
+                provider_links,
+            }
+        })
+        .collect();
+
+    Ok(result)
+}
+
+pub fn list_products_with_links_paginated(
+    conn: &Connection,
+    project_id: &str,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<ProductWithProviderLinks>, i64)> {
+    // Get paginated products for the project
+    let (products, total) = list_products_for_project_paginated(conn, project_id, limit, offset)?;
+
+    if products.is_empty() {
+        return Ok((vec![], total));
+    }
+
+    // Get all provider links for these products in one query
+    let product_ids: Vec<&str> = products.iter().map(|p| p.id.as_str()).collect();
+    let placeholders: Vec<String> = (1..=product_ids.len()).map(|i| format!("?{}", i)).collect();
+    let sql = format!(
+        "SELECT {} FROM product_provider_links WHERE product_id IN ({}) ORDER BY product_id, created_at",
+        PROVIDER_LINK_COLS,
+        placeholders.join(", ")
+    );
+
+    let params: Vec<&dyn rusqlite::ToSql> = product_ids
+        .iter()
+        .map(|id| id as &dyn rusqlite::ToSql)
+        .collect();
+
+    let links: Vec<ProductProviderLink> = query_all(conn, &sql, &params)?;
+
+    // Group links by product_id
+    let mut link_map: std::collections::HashMap<String, Vec<ProductProviderLink>> =
+        std::collections::HashMap::new();
+    for link in links {
+        link_map
+            .entry(link.product_id.clone())
+            .or_default()
+            .push(link);
+    }
+
+    // Build result
+    let result = products
+        .into_iter()
+        .map(|product| {
+            let provider_links = link_map.remove(&product.id).unwrap_or_default();
+            ProductWithProviderLinks {
+                product,
+                provider_links,
+            }
+        })
+        .collect();
+
+    Ok((result, total))
+}
@@ -0,0 +1,1095 @@
+//! Project queries: projects, project members, temporary role grants,
+//! terms of sale, release artifacts, and custom domains.
+
+use rusqlite::{Connection, OptionalExtension, params};
+
+use crate::crypto::MasterKey;
+use crate::db::EmailColumn;
+use crate::db::from_row::{
+    ARTIFACT_COLS, ARTIFACT_DOWNLOAD_COLS, CUSTOM_DOMAIN_COLS, PROJECT_COLS, PROJECT_MEMBER_COLS,
+    TEMPORARY_ROLE_GRANT_COLS, TERMS_COLS, query_all, query_one,
+};
+use crate::error::{AppError, Result};
+use crate::models::*;
+
+use super::orgs::org_data_key;
+use super::util::{UpdateBuilder, gen_id, now};
+
+// ============ Projects ============
+
+/// Create a project, encrypting the private key with its org's data key.
+/// The project ID is generated internally and used as the encryption context.
+pub fn create_project(
+    conn: &Connection,
+    org_id: &str,
+    input: &CreateProject,
+    private_key: &[u8],
+    public_key: &str,
+    master_key: &MasterKey,
+) -> Result<Project> {
+    let id = gen_id();
+    let now = now();
+    let org_key = org_data_key(conn, org_id, master_key)?;
+    let encrypted_private_key = org_key.encrypt_private_key(&id, private_key)?;
+    let email_webhook_secret = input
+        .email_webhook_secret
+        .as_ref()
+        .map(|secret| org_key.encrypt_webhook_secret(&id, secret.as_bytes()))
+        .transpose()?;
+
+    conn.execute(
+        "INSERT INTO projects (id, org_id, name, license_key_prefix, private_key, public_key, redirect_url, email_from, email_enabled, email_webhook_url, created_at, updated_at, jwt_issuer, jwt_audience, upgrade_auto_discount, upgrade_old_license, allow_project_id_auth, allow_link_checkout, max_validations_per_hour_per_license, statement_descriptor_suffix, receipt_email_enabled, checkout_message, attestation_audiences, duplicate_purchase_check, converted_trial_action, require_terms_acceptance, checkout_metadata_keys, email_webhook_secret)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28)",
+        params![&id, org_id, &input.name, &input.license_key_prefix, &encrypted_private_key, public_key, &input.redirect_url, &input.email_from, input.email_enabled, &input.email_webhook_url, now, now, &input.jwt_issuer, &input.jwt_audience, input.upgrade_auto_discount, input.upgrade_old_license.as_ref(), input.allow_project_id_auth, input.allow_link_checkout, input.max_validations_per_hour_per_license, &input.statement_descriptor_suffix, input.receipt_email_enabled, &input.checkout_message, serde_json::to_string(&input.attestation_audiences)?, input.duplicate_purchase_check, input.converted_trial_action.as_ref(), input.require_terms_acceptance, serde_json::to_string(&input.checkout_metadata_keys)?, &email_webhook_secret],
+    )?;
+
+    Ok(Project {
+        id,
+        org_id: org_id.to_string(),
+        name: input.name.clone(),
+        license_key_prefix: input.license_key_prefix.clone(),
+        private_key: encrypted_private_key,
+        public_key: public_key.to_string(),
+        redirect_url: input.redirect_url.clone(),
+        email_from: input.email_from.clone(),
+        email_enabled: input.email_enabled,
+        email_webhook_url: input.email_webhook_url.clone(),
+        created_at: now,
+        updated_at: now,
+        deleted_at: None,
+        deleted_cascade_depth: None,
+        jwt_issuer: input.jwt_issuer.clone(),
+        jwt_audience: input.jwt_audience.clone(),
+        jwt_previous_issuer: None,
+        jwt_previous_audience: None,
+        jwt_previous_until: None,
+        upgrade_auto_discount: input.upgrade_auto_discount,
+        upgrade_old_license: input.upgrade_old_license,
+        allow_project_id_auth: input.allow_project_id_auth,
+        allow_link_checkout: input.allow_link_checkout,
+        max_validations_per_hour_per_license: input.max_validations_per_hour_per_license,
+        statement_descriptor_suffix: input.statement_descriptor_suffix.clone(),
+        receipt_email_enabled: input.receipt_email_enabled,
+        checkout_message: input.checkout_message.clone(),
+        attestation_audiences: input.attestation_audiences.clone(),
+        duplicate_purchase_check: input.duplicate_purchase_check,
+        converted_trial_action: input.converted_trial_action,
+        webhook_mirror_url: None,
+        webhook_mirror_expires_at: None,
+        require_terms_acceptance: input.require_terms_acceptance,
+        client_flags: Default::default(),
+        min_client_version: None,
+        checkout_metadata_keys: input.checkout_metadata_keys.clone(),
+        usage_geo_enabled: false,
+        dormant_after_days: None,
+        dormant_nudge_email: false,
+        webhook_schema_version: None,
+        email_webhook_secret,
+        version: 1,
+    })
+}
+
+pub fn get_project_by_id(conn: &Connection, id: &str) -> Result<Option<Project>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM projects WHERE id = ?1 AND deleted_at IS NULL",
+            PROJECT_COLS
+        ),
+        &[&id],
+    )
+}
+
+pub fn list_projects_for_org(conn: &Connection, org_id: &str) -> Result<Vec<Project>> {
+    query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM projects WHERE org_id = ?1 AND deleted_at IS NULL ORDER BY created_at DESC",
+            PROJECT_COLS
+        ),
+        &[&org_id],
+    )
+}
+
+/// List projects for an org with pagination
+pub fn list_projects_for_org_paginated(
+    conn: &Connection,
+    org_id: &str,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<Project>, i64)> {
+    let total: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM projects WHERE org_id = ?1 AND deleted_at IS NULL",
+        params![org_id],
+        |row| row.get(0),
+    )?;
+
+    let items = query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM projects WHERE org_id = ?1 AND deleted_at IS NULL ORDER BY created_at DESC, id DESC LIMIT ?2 OFFSET ?3",
+            PROJECT_COLS
+        ),
+        params![org_id, limit, offset],
+    )?;
+
+    Ok((items, total))
+}
+
+/// List projects accessible by a specific org member with pagination
+/// For "member" role users who only see projects they're explicitly added to
+pub fn list_accessible_projects_for_member_paginated(
+    conn: &Connection,
+    org_id: &str,
+    org_member_id: &str,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<Project>, i64)> {
+    let total: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM projects
+         WHERE org_id = ?1 AND deleted_at IS NULL
+         AND id IN (SELECT project_id FROM project_members
+                    WHERE org_member_id = ?2 AND deleted_at IS NULL)",
+        params![org_id, org_member_id],
+        |row| row.get(0),
+    )?;
+
+    let items = query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM projects
+             WHERE org_id = ?1 AND deleted_at IS NULL
+             AND id IN (SELECT project_id FROM project_members WHERE org_member_id = ?2)
+             ORDER BY created_at DESC, id DESC LIMIT ?3 OFFSET ?4",
+            PROJECT_COLS
+        ),
+        params![org_id, org_member_id, limit, offset],
+    )?;
+
+    Ok((items, total))
+}
+
+/// IDs of the org's projects a `member`-role user was added to, or holds a
+/// temporary role on that is in effect at `now`
+pub fn list_accessible_project_ids_for_member(
+    conn: &Connection,
+    org_id: &str,
+    org_member_id: &str,
+    now: i64,
+) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT id FROM projects
+         WHERE org_id = ?1 AND deleted_at IS NULL
+         AND (id IN (SELECT project_id FROM project_members
+                     WHERE org_member_id = ?2 AND deleted_at IS NULL)
+              OR id IN (SELECT project_id FROM temporary_role_grants
+                        WHERE org_member_id = ?2 AND revoked_at IS NULL AND expires_at > ?3))",
+    )?;
+    let ids = stmt
+        .query_map(params![org_id, org_member_id, now], |row| row.get(0))?
+        .collect::<std::result::Result<Vec<String>, _>>()?;
+    Ok(ids)
+}
+
+/// List all projects (for migration purposes - includes soft-deleted)
+pub fn list_all_projects(conn: &Connection) -> Result<Vec<Project>> {
+    query_all(
+        conn,
+        &format!("SELECT {} FROM projects ORDER BY created_at", PROJECT_COLS),
+        &[],
+    )
+}
+
+/// Decrypt a project's signing key with its org's data key.
+pub fn decrypt_project_private_key(
+    conn: &Connection,
+    project: &Project,
+    master_key: &MasterKey,
+) -> Result<Vec<u8>> {
+    org_data_key(conn, &project.org_id, master_key)?
+        .decrypt_private_key(&project.id, &project.private_key)
+}
+
+/// Decrypt a project's email webhook signing secret with its org's data key.
+/// None if the project's webhooks are unsigned.
+pub fn decrypt_project_webhook_secret(
+    conn: &Connection,
+    project: &Project,
+    master_key: &MasterKey,
+) -> Result<Option<String>> {
+    let Some(ref encrypted) = project.email_webhook_secret else {
+        return Ok(None);
+    };
+    let decrypted = org_data_key(conn, &project.org_id, master_key)?
+        .decrypt_webhook_secret(&project.id, encrypted)?;
+    String::from_utf8(decrypted)
+        .map(Some)
+        .map_err(|_| AppError::Internal("Invalid UTF-8 in email webhook secret".into()))
+}
+
+/// Update a project's private key (for key rotation)
+pub fn update_project_private_key(conn: &Connection, id: &str, private_key: &[u8]) -> Result<()> {
+    conn.execute(
+        "UPDATE projects SET private_key = ?1, updated_at = ?2 WHERE id = ?3",
+        params![private_key, now(), id],
+    )?;
+    Ok(())
+}
+
+/// Update a project's encrypted email webhook secret (for key migration)
+pub fn update_project_webhook_secret(conn: &Connection, id: &str, secret: &[u8]) -> Result<()> {
+    conn.execute(
+        "UPDATE projects SET email_webhook_secret = ?1, updated_at = ?2 WHERE id = ?3",
+        params![secret, now(), id],
+    )?;
+    Ok(())
+}
+
+/// Set a project's webhook mirror until `expires_at`, or turn it off (None).
+pub fn set_project_webhook_mirror(
+    conn: &Connection,
+    id: &str,
+    mirror: Option<(&str, i64)>,
+) -> Result<bool> {
+    let (url, expires_at) = mirror.unzip();
+    let updated = conn.execute(
+        "UPDATE projects SET webhook_mirror_url = ?1, webhook_mirror_expires_at = ?2, updated_at = ?3
+         WHERE id = ?4 AND deleted_at IS NULL",
+        params![url, expires_at, now(), id],
+    )?;
+    Ok(updated > 0)
+}
+
+/// Update a project, encrypting a new `email_webhook_secret` with its org's
+/// data key. Returns the updated project, or None if not found (or no longer
+/// at `input.expected_version`).
+pub fn update_project(
+    conn: &Connection,
+    id: &str,
+    input: &UpdateProject,
+    master_key: &MasterKey,
+) -> Result<Option<Project>> {
+    // All nullable fields use Option<Option<T>> pattern:
+    // None = leave unchanged, Some(None) = clear, Some(Some(v)) = set
+    let mut builder = UpdateBuilder::new("projects", id)
+        .with_updated_at()
+        .versioned(input.expected_version)
+        .set_opt("name", input.name.clone())
+        .set_opt("license_key_prefix", input.license_key_prefix.clone());
+
+    // Handle redirect_url: Option<Option<String>>
+    if let Some(ref redirect_url) = input.redirect_url {
+        builder = builder.set_nullable("redirect_url", redirect_url.clone());
+    }
+
+    // Handle email_from: Option<Option<String>>
+    if let Some(ref email_from) = input.email_from {
+        builder = builder.set_nullable("email_from", email_from.clone());
+    }
+
+    // Handle email_enabled: Option<bool>
+    if let Some(email_enabled) = input.email_enabled {
+        builder = builder.set("email_enabled", email_enabled as i32);
+    }
+
+    // Handle email_webhook_url: Option<Option<String>>
+    if let Some(ref email_webhook_url) = input.email_webhook_url {
+        builder = builder.set_nullable("email_webhook_url", email_webhook_url.clone());
+    }
+
+    // Handle upgrade settings: Option<T>
+    if let Some(upgrade_auto_discount) = input.upgrade_auto_discount {
+        builder = builder.set("upgrade_auto_discount", upgrade_auto_discount as i32);
+    }
+    if let Some(upgrade_old_license) = input.upgrade_old_license {
+        builder = builder.set(
+            "upgrade_old_license",
+            upgrade_old_license.as_ref().to_string(),
+        );
+    }
+
+    // Handle allow_project_id_auth: Option<bool>
+    if let Some(allow_project_id_auth) = input.allow_project_id_auth {
+        builder = builder.set("allow_project_id_auth", allow_project_id_auth as i32);
+    }
+    if let Some(allow_link_checkout) = input.allow_link_checkout {
+        builder = builder.set("allow_link_checkout", allow_link_checkout as i32);
+    }
+    if let Some(limit) = input.max_validations_per_hour_per_license {
+        builder = builder.set_nullable("max_validations_per_hour_per_license", limit);
+    }
+
+    // Handle checkout presentation settings
+    if let Some(ref suffix) = input.statement_descriptor_suffix {
+        builder = builder.set_nullable("statement_descriptor_suffix", suffix.clone());
+    }
+    if let Some(receipt_email_enabled) = input.receipt_email_enabled {
+        builder = builder.set("receipt_email_enabled", receipt_email_enabled as i32);
+    }
+    if let Some(ref message) = input.checkout_message {
+        builder = builder.set_nullable("checkout_message", message.clone());
+    }
+    if let Some(ref audiences) = input.attestation_audiences {
+        builder = builder.set("attestation_audiences", serde_json::to_string(audiences)?);
+    }
+    if let Some(duplicate_purchase_check) = input.duplicate_purchase_check {
+        builder = builder.set("duplicate_purchase_check", duplicate_purchase_check as i32);
+    }
+    if let Some(action) = input.converted_trial_action {
+        builder = builder.set("converted_trial_action", action.as_ref().to_string());
+    }
+    if let Some(require_terms_acceptance) = input.require_terms_acceptance {
+        builder = builder.set("require_terms_acceptance", require_terms_acceptance as i32);
+    }
+    if let Some(ref flags) = input.client_flags {
+        builder = builder.set("client_flags", serde_json::to_string(flags)?);
+    }
+    if let Some(ref version) = input.min_client_version {
+        builder = builder.set_nullable("min_client_version", version.clone());
+    }
+    if let Some(ref keys) = input.checkout_metadata_keys {
+        builder = builder.set("checkout_metadata_keys", serde_json::to_string(keys)?);
+    }
+    if let Some(usage_geo_enabled) = input.usage_geo_enabled {
+        builder = builder.set("usage_geo_enabled", usage_geo_enabled as i32);
+    }
+    if let Some(days) = input.dormant_after_days {
+        builder = builder.set_nullable("dormant_after_days", days);
+    }
+    if let Some(dormant_nudge_email) = input.dormant_nudge_email {
+        builder = builder.set("dormant_nudge_email", dormant_nudge_email as i32);
+    }
+    if let Some(version) = input.webhook_schema_version {
+        builder = builder.set_nullable(
+            "webhook_schema_version",
+            version.map(|v| v.as_ref().to_string()),
+        );
+    }
+
+    if let Some(ref secret) = input.email_webhook_secret {
+        let encrypted = match secret {
+            Some(secret) => {
+                let Some(existing) = get_project_by_id(conn, id)? else {
+                    return Ok(None);
+                };
+                Some(
+                    org_data_key(conn, &existing.org_id, master_key)?
+                        .encrypt_webhook_secret(id, secret.as_bytes())?,
+                )
+            }
+            None => None,
+        };
+        builder = builder.set_nullable("email_webhook_secret", encrypted);
+    }
+
+    // Handle jwt_issuer / jwt_audience: Option<Option<String>>
+    if input.jwt_issuer.is_some() || input.jwt_audience.is_some() {
+        let Some(existing) = get_project_by_id(conn, id)? else {
+            return Ok(None);
+        };
+        let issuer = input
+            .jwt_issuer
+            .clone()
+            .unwrap_or(existing.jwt_issuer.clone());
+        let audience = input
+            .jwt_audience
+            .clone()
+            .unwrap_or(existing.jwt_audience.clone());
+        let new_issuer = issuer.as_deref().unwrap_or(crate::jwt::DEFAULT_ISSUER);
+        let new_audience = audience.as_deref().unwrap_or(&existing.name);
+
+        // Record the values being replaced so tokens already issued with them
+        // keep verifying for the grace window (replaces any earlier window)
+        if new_issuer != existing.token_issuer() || new_audience != existing.token_audience() {
+            let grace_days = input.jwt_grace_days.unwrap_or(DEFAULT_JWT_GRACE_DAYS);
+            builder = if grace_days > 0 {
+                builder
+                    .set("jwt_previous_issuer", existing.token_issuer().to_string())
+                    .set(
+                        "jwt_previous_audience",
+                        existing.token_audience().to_string(),
+                    )
+                    .set("jwt_previous_until", now() + grace_days * 86400)
+            } else {
+                builder
+                    .set_nullable("jwt_previous_issuer", None::<String>)
+                    .set_nullable("jwt_previous_audience", None::<String>)
+                    .set_nullable("jwt_previous_until", None::<i64>)
+            };
+        }
+
+        builder = builder
+            .set_nullable("jwt_issuer", issuer)
+            .set_nullable("jwt_audience", audience);
+    }
+
+    builder.execute_returning(conn, PROJECT_COLS)
+}
+
+/// Replace the project's client flags and minimum app version.
+pub fn set_project_client_flags(
+    conn: &Connection,
+    id: &str,
+    flags: &ClientFlags,
+) -> Result<Option<Project>> {
+    UpdateBuilder::new("projects", id)
+        .with_updated_at()
+        .versioned(None)
+        .set("client_flags", serde_json::to_string(&flags.client_flags)?)
+        .set_nullable("min_client_version", flags.min_client_version.clone())
+        .execute_returning(conn, PROJECT_COLS)
+}
+
+pub fn delete_project(conn: &Connection, id: &str) -> Result<bool> {
+    let deleted = conn.execute("DELETE FROM projects WHERE id = ?1", params![id])?;
+    Ok(deleted > 0)
+}
+
+/// Soft delete a project and cascade to products and licenses.
+pub fn soft_delete_project(conn: &Connection, id: &str) -> Result<bool> {
+    use crate::db::soft_delete::{cascade_delete_direct, soft_delete_entity};
+
+    let result = soft_delete_entity(conn, "projects", id)?;
+    if !result.deleted {
+        return Ok(false);
+    }
+
+    // Cascade to products (depth 1) and licenses (depth 2)
+    cascade_delete_direct(conn, "products", "project_id", id, result.deleted_at, 1)?;
+    cascade_delete_direct(conn, "licenses", "project_id", id, result.deleted_at, 2)?;
+
+    Ok(true)
+}
+
+/// Get a soft-deleted project by ID (for restore operations).
+pub fn get_deleted_project_by_id(conn: &Connection, id: &str) -> Result<Option<Project>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM projects WHERE id = ?1 AND deleted_at IS NOT NULL",
+            PROJECT_COLS
+        ),
+        &[&id],
+    )
+}
+
+/// Restore a soft-deleted project and all cascaded children.
+/// Returns Err if depth > 0 and force=false (was cascaded from org delete).
+pub fn restore_project(conn: &Connection, id: &str, force: bool) -> Result<bool> {
+    use crate::db::soft_delete::{check_restore_allowed, restore_cascaded_direct, restore_entity};
+
+    let Some(project) = get_deleted_project_by_id(conn, id)? else {
+        return Ok(false);
+    };
+
+    check_restore_allowed(project.deleted_cascade_depth, force, "Project")?;
+
+    let deleted_at = project.deleted_at.unwrap();
+
+    // Restore in reverse order: deepest children first
+    restore_cascaded_direct(conn, "licenses", "project_id", id, deleted_at)?;
+    restore_cascaded_direct(conn, "products", "project_id", id, deleted_at)?;
+
+    // Restore the project itself
+    restore_entity(conn, "projects", id)?;
+
+    Ok(true)
+}
+
+/// Look up a project by its public key.
+/// Used by public endpoints to identify the project without requiring a project_id.
+pub fn get_project_by_public_key(conn: &Connection, public_key: &str) -> Result<Option<Project>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM projects WHERE public_key = ?1 AND deleted_at IS NULL",
+            PROJECT_COLS
+        ),
+        &[&public_key],
+    )
+}
+
+// ============ Project Members ============
+
+pub fn create_project_member(
+    conn: &Connection,
+    org_member_id: &str,
+    project_id: &str,
+    role: ProjectMemberRole,
+) -> Result<ProjectMember> {
+    let id = gen_id();
+    let now = now();
+
+    conn.execute(
+        "INSERT INTO project_members (id, org_member_id, project_id, role, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![&id, org_member_id, project_id, role.as_ref(), now, now],
+    )?;
+
+    Ok(ProjectMember {
+        id,
+        org_member_id: org_member_id.to_string(),
+        project_id: project_id.to_string(),
+        role,
+        created_at: now,
+        updated_at: now,
+        deleted_at: None,
+        deleted_cascade_depth: None,
+    })
+}
+
+pub fn get_project_member(
+    conn: &Connection,
+    org_member_id: &str,
+    project_id: &str,
+) -> Result<Option<ProjectMember>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM project_members WHERE org_member_id = ?1 AND project_id = ?2 AND deleted_at IS NULL",
+            PROJECT_MEMBER_COLS
+        ),
+        &[&org_member_id, &project_id],
+    )
+}
+
+/// User ID behind a project membership, including soft-deleted rows (audit log display).
+pub fn get_project_member_user_id(conn: &Connection, id: &str) -> Result<Option<String>> {
+    conn.query_row(
+        "SELECT om.user_id FROM project_members pm
+         JOIN org_members om ON pm.org_member_id = om.id
+         WHERE pm.id = ?1",
+        [id],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(Into::into)
+}
+
+/// Get a project member by ID with org member details
+pub fn get_project_member_by_id(
+    conn: &Connection,
+    id: &str,
+    emails: &EmailColumn,
+) -> Result<Option<ProjectMemberWithDetails>> {
+    emails.reveal_opt(query_one(
+        conn,
+        "SELECT pm.id, pm.org_member_id, om.user_id, pm.project_id, pm.role, pm.created_at, pm.updated_at, pm.deleted_at, pm.deleted_cascade_depth, u.email, u.name
+         FROM project_members pm
+         JOIN org_members om ON pm.org_member_id = om.id
+         JOIN users u ON om.user_id = u.id
+         WHERE pm.id = ?1 AND pm.deleted_at IS NULL",
+        &[&id],
+    )?)
+}
+
+/// Get a project member by user_id and project_id
+pub fn get_project_member_by_user_and_project(
+    conn: &Connection,
+    user_id: &str,
+    org_id: &str,
+    project_id: &str,
+    emails: &EmailColumn,
+) -> Result<Option<ProjectMemberWithDetails>> {
+    emails.reveal_opt(query_one(
+        conn,
+        "SELECT pm.id, pm.org_member_id, om.user_id, pm.project_id, pm.role, pm.created_at, pm.updated_at, pm.deleted_at, pm.deleted_cascade_depth, u.email, u.name
+         FROM project_members pm
+         JOIN org_members om ON pm.org_member_id = om.id
+         JOIN users u ON om.user_id = u.id
+         WHERE u.id = ?1 AND om.org_id = ?2 AND pm.project_id = ?3 AND om.deleted_at IS NULL AND pm.deleted_at IS NULL",
+        params![user_id, org_id, project_id],
+    )?)
+}
+
+pub fn list_project_members(
+    conn: &Connection,
+    project_id: &str,
+    emails: &EmailColumn,
+) -> Result<Vec<ProjectMemberWithDetails>> {
+    emails.reveal_all(query_all(
+        conn,
+        "SELECT pm.id, pm.org_member_id, om.user_id, pm.project_id, pm.role, pm.created_at, pm.updated_at, pm.deleted_at, pm.deleted_cascade_depth, u.email, u.name
+         FROM project_members pm
+         JOIN org_members om ON pm.org_member_id = om.id
+         JOIN users u ON om.user_id = u.id
+         WHERE pm.project_id = ?1 AND pm.deleted_at IS NULL
+         ORDER BY pm.created_at DESC",
+        &[&project_id],
+    )?)
+}
+
+/// List project members with pagination
+pub fn list_project_members_paginated(
+    conn: &Connection,
+    project_id: &str,
+    limit: i64,
+    offset: i64,
+    emails: &EmailColumn,
+) -> Result<(Vec<ProjectMemberWithDetails>, i64)> {
+    let total: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM project_members WHERE project_id = ?1 AND deleted_at IS NULL",
+        params![project_id],
+        |row| row.get(0),
+    )?;
+
+    let items = query_all(
+        conn,
+        "SELECT pm.id, pm.org_member_id, om.user_id, pm.project_id, pm.role, pm.created_at, pm.updated_at, pm.deleted_at, pm.deleted_cascade_depth, u.email, u.name
+         FROM project_members pm
+         JOIN org_members om ON pm.org_member_id = om.id
+         JOIN users u ON om.user_id = u.id
+         WHERE pm.project_id = ?1 AND pm.deleted_at IS NULL
+         ORDER BY pm.created_at DESC, pm.id DESC
+         LIMIT ?2 OFFSET ?3",
+        params![project_id, limit, offset],
+    )?;
+
+    Ok((emails.reveal_all(items)?, total))
+}
+
+/// Update a project member. Returns the updated member, or None if not found.
+pub fn update_project_member(
+    conn: &Connection,
+    id: &str,
+    project_id: &str,
+    input: &UpdateProjectMember,
+) -> Result<Option<ProjectMember>> {
+    query_one(
+        conn,
+        &format!(
+            "UPDATE project_members SET role = ?1, updated_at = ?2
+             WHERE id = ?3 AND project_id = ?4 AND deleted_at IS NULL
+             RETURNING {}",
+            PROJECT_MEMBER_COLS
+        ),
+        params![input.role.as_ref(), now(), id, project_id],
+    )
+}
+
+/// Soft delete a project member. Returns true if the member was found and soft deleted.
+pub fn soft_delete_project_member(conn: &Connection, id: &str, project_id: &str) -> Result<bool> {
+    use crate::db::soft_delete::soft_delete_entity;
+    // Verify project_id matches before soft deleting
+    let exists: bool = conn.query_row(
+        "SELECT 1 FROM project_members WHERE id = ?1 AND project_id = ?2 AND deleted_at IS NULL",
+        params![id, project_id],
+        |_| Ok(true),
+    ).unwrap_or(false);
+    if !exists {
+        return Ok(false);
+    }
+    Ok(soft_delete_entity(conn, "project_members", id)?.deleted)
+}
+
+// ============ Temporary Role Grants ============
+
+/// Grant a temporary project role, replacing any grant the member still has
+/// on the project. `now` comes from the caller's clock.
+pub fn create_temporary_role_grant(
+    conn: &mut Connection,
+    org_member_id: &str,
+    project_id: &str,
+    input: &GrantTemporaryRole,
+    granted_by: &str,
+    now: i64,
+) -> Result<TemporaryRoleGrant> {
+    let id = gen_id();
+    let expires_at = now + input.expires_in_minutes * 60;
+
+    let tx = conn.transaction()?;
+    tx.execute(
+        "UPDATE temporary_role_grants SET revoked_at = ?1, revoked_by = ?2
+         WHERE org_member_id = ?3 AND project_id = ?4 AND revoked_at IS NULL AND expires_at > ?1",
+        params![now, granted_by, org_member_id, project_id],
+    )?;
+    tx.execute(
+        "INSERT INTO temporary_role_grants (id, org_member_id, project_id, role, reason, granted_by, created_at, expires_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            &id,
+            org_member_id,
+            project_id,
+            input.role.as_ref(),
+            input.reason,
+            granted_by,
+            now,
+            expires_at
+        ],
+    )?;
+    let grant = get_temporary_role_grant(&tx, &id)?
+        .ok_or_else(|| AppError::Internal("Temporary role grant not found after insert".into()))?;
+    tx.commit()?;
+
+    Ok(grant)
+}
+
+pub fn get_temporary_role_grant(conn: &Connection, id: &str) -> Result<Option<TemporaryRoleGrant>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM temporary_role_grants g
+             JOIN org_members om ON g.org_member_id = om.id
+             WHERE g.id = ?1",
+            TEMPORARY_ROLE_GRANT_COLS
+        ),
+        &[&id],
+    )
+}
+
+/// The member's grant on a project that is unrevoked and unexpired at `now`.
+pub fn get_active_temporary_role_grant(
+    conn: &Connection,
+    org_member_id: &str,
+    project_id: &str,
+    now: i64,
+) -> Result<Option<TemporaryRoleGrant>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM temporary_role_grants g
+             JOIN org_members om ON g.org_member_id = om.id
+             WHERE g.org_member_id = ?1 AND g.project_id = ?2
+               AND g.revoked_at IS NULL AND g.expires_at > ?3
+             ORDER BY g.expires_at DESC LIMIT 1",
+            TEMPORARY_ROLE_GRANT_COLS
+        ),
+        params![org_member_id, project_id, now],
+    )
+}
+
+/// Grants on a project, newest first. With `active_at`, only grants still
+/// in effect at that time.
+pub fn list_temporary_role_grants_paginated(
+    conn: &Connection,
+    project_id: &str,
+    active_at: Option<i64>,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<TemporaryRoleGrant>, i64)> {
+    let total: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM temporary_role_grants
+         WHERE project_id = ?1 AND (?2 IS NULL OR (revoked_at IS NULL AND expires_at > ?2))",
+        params![project_id, active_at],
+        |row| row.get(0),
+    )?;
+
+    let grants = query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM temporary_role_grants g
+             JOIN org_members om ON g.org_member_id = om.id
+             WHERE g.project_id = ?1 AND (?2 IS NULL OR (g.revoked_at IS NULL AND g.expires_at > ?2))
+             ORDER BY g.created_at DESC, g.id DESC LIMIT ?3 OFFSET ?4",
+            TEMPORARY_ROLE_GRANT_COLS
+        ),
+        params![project_id, active_at, limit, offset],
+    )?;
+
+    Ok((grants, total))
+}
+
+/// End a grant early. Returns the revoked grant, or None if it had already
+/// expired or been revoked.
+pub fn revoke_temporary_role_grant(
+    conn: &Connection,
+    id: &str,
+    revoked_by: &str,
+    now: i64,
+) -> Result<Option<TemporaryRoleGrant>> {
+    let updated = conn.execute(
+        "UPDATE temporary_role_grants SET revoked_at = ?1, revoked_by = ?2
+         WHERE id = ?3 AND revoked_at IS NULL AND expires_at > ?1",
+        params![now, revoked_by, id],
+    )?;
+    if updated == 0 {
+        return Ok(None);
+    }
+    get_temporary_role_grant(conn, id)
+}
+
+// ============ Terms ============
+
+/// Publish a terms version. Fails on the unique index if the project already
+/// has this version.
+pub fn create_terms(
+    conn: &Connection,
+    project_id: &str,
+    input: &CreateTerms,
+    effective_at: i64,
+    created_by: Option<&str>,
+) -> Result<Terms> {
+    let id = gen_id();
+    let now = now();
+    let version = input.version.trim();
+    conn.execute(
+        "INSERT INTO terms (id, project_id, version, url, effective_at, created_by, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            &id,
+            project_id,
+            version,
+            &input.url,
+            effective_at,
+            created_by,
+            now
+        ],
+    )?;
+
+    Ok(Terms {
+        id,
+        project_id: project_id.to_string(),
+        version: version.to_string(),
+        url: input.url.clone(),
+        effective_at,
+        created_by: created_by.map(String::from),
+        created_at: now,
+    })
+}
+
+pub fn get_terms(conn: &Connection, project_id: &str, id: &str) -> Result<Option<Terms>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM terms WHERE id = ?1 AND project_id = ?2",
+            TERMS_COLS
+        ),
+        &[&id, &project_id],
+    )
+}
+
+/// A project's terms versions, newest effective first.
+pub fn list_terms(conn: &Connection, project_id: &str) -> Result<Vec<Terms>> {
+    query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM terms WHERE project_id = ?1 ORDER BY effective_at DESC, created_at DESC",
+            TERMS_COLS
+        ),
+        &[&project_id],
+    )
+}
+
+/// The terms in effect at `now`: the latest version whose `effective_at`
+/// has passed. None if the project hasn't published any yet.
+pub fn get_current_terms(conn: &Connection, project_id: &str, now: i64) -> Result<Option<Terms>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM terms WHERE project_id = ?1 AND effective_at <= ?2
+             ORDER BY effective_at DESC, created_at DESC LIMIT 1",
+            TERMS_COLS
+        ),
+        params![project_id, now],
+    )
+}
+
+/// Whether any license, deleted ones included, was bought under this
+/// version of the project's terms.
+pub fn terms_accepted_by_license(
+    conn: &Connection,
+    project_id: &str,
+    version: &str,
+) -> Result<bool> {
+    Ok(conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM licenses WHERE project_id = ?1 AND accepted_terms_version = ?2)",
+        params![project_id, version],
+        |row| row.get(0),
+    )?)
+}
+
+pub fn update_terms(conn: &Connection, id: &str, input: &UpdateTerms) -> Result<bool> {
+    UpdateBuilder::new("terms", id)
+        .set_opt("url", input.url.clone())
+        .set_opt("effective_at", input.effective_at)
+        .execute(conn)
+}
+
+// ============ Artifacts ============
+
+pub fn create_artifact(
+    conn: &Connection,
+    project_id: &str,
+    input: &CreateArtifact,
+    released_at: i64,
+    created_by: Option<&str>,
+) -> Result<Artifact> {
+    let id = gen_id();
+    let now = now();
+    let name = input.name.trim();
+    conn.execute(
+        "INSERT INTO artifacts (id, project_id, name, released_at, url, created_by, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            &id,
+            project_id,
+            name,
+            released_at,
+            &input.url,
+            created_by,
+            now
+        ],
+    )?;
+
+    Ok(Artifact {
+        id,
+        project_id: project_id.to_string(),
+        name: name.to_string(),
+        released_at,
+        url: input.url.clone(),
+        download_count: 0,
+        created_by: created_by.map(String::from),
+        created_at: now,
+    })
+}
+
+pub fn get_artifact(conn: &Connection, project_id: &str, id: &str) -> Result<Option<Artifact>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM artifacts WHERE id = ?1 AND project_id = ?2",
+            ARTIFACT_COLS
+        ),
+        &[&id, &project_id],
+    )
+}
+
+/// A project's artifacts, newest release first.
+pub fn list_artifacts(conn: &Connection, project_id: &str) -> Result<Vec<Artifact>> {
+    query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM artifacts WHERE project_id = ?1 ORDER BY released_at DESC, created_at DESC",
+            ARTIFACT_COLS
+        ),
+        &[&project_id],
+    )
+}
+
+/// Delete an artifact and its download counts.
+pub fn delete_artifact(conn: &Connection, project_id: &str, id: &str) -> Result<bool> {
+    let deleted = conn.execute(
+        "DELETE FROM artifacts WHERE id = ?1 AND project_id = ?2",
+        params![id, project_id],
+    )?;
+    Ok(deleted > 0)
+}
+
+/// Count a download of an artifact by a license.
+pub fn record_artifact_download(
+    conn: &Connection,
+    artifact_id: &str,
+    license_id: &str,
+    at: i64,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO artifact_downloads (artifact_id, license_id, count, first_downloaded_at, last_downloaded_at)
+         VALUES (?1, ?2, 1, ?3, ?3)
+         ON CONFLICT (artifact_id, license_id) DO UPDATE SET count = count + 1, last_downloaded_at = ?3",
+        params![artifact_id, license_id, at],
+    )?;
+    Ok(())
+}
+
+/// Per-license download counts for an artifact, most recently downloaded first.
+pub fn list_artifact_downloads(
+    conn: &Connection,
+    artifact_id: &str,
+) -> Result<Vec<ArtifactDownload>> {
+    query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM artifact_downloads WHERE artifact_id = ?1 ORDER BY last_downloaded_at DESC",
+            ARTIFACT_DOWNLOAD_COLS
+        ),
+        &[&artifact_id],
+    )
+}
+
+// ============ Custom Domains ============
+// Stored in the shared database, since the Host header is all a request to
+// one tells us about its project.
+
+/// Bind `hostname` (already normalized) to a project, unverified. Fails on
+/// the primary key if another project already has it.
+pub fn create_custom_domain(
+    conn: &Connection,
+    org_id: &str,
+    project_id: &str,
+    hostname: &str,
+    verification_token: &str,
+    created_by: Option<&str>,
+) -> Result<CustomDomain> {
+    let now = now();
+    conn.execute(
+        "INSERT INTO custom_domains (hostname, org_id, project_id, verification_token, created_by, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![hostname, org_id, project_id, verification_token, created_by, now],
+    )?;
+
+    Ok(CustomDomain {
+        hostname: hostname.to_string(),
+        org_id: org_id.to_string(),
+        project_id: project_id.to_string(),
+        verification_token: verification_token.to_string(),
+        verified_at: None,
+        created_by: created_by.map(String::from),
+        created_at: now,
+    })
+}
+
+pub fn get_custom_domain(conn: &Connection, hostname: &str) -> Result<Option<CustomDomain>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM custom_domains WHERE hostname = ?1",
+            CUSTOM_DOMAIN_COLS
+        ),
+        &[&hostname],
+    )
+}
+
+/// The project a verified custom domain serves. None for unknown and
+/// unverified hostnames alike.
+pub fn get_verified_custom_domain_project_id(
+    conn: &Connection,
+    hostname: &str,
+) -> Result<Option<String>> {
+    Ok(conn
+        .query_row(
+            "SELECT project_id FROM custom_domains WHERE hostname = ?1 AND verified_at IS NOT NULL",
+            params![hostname],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+pub fn list_custom_domains_for_project(
+    conn: &Connection,
+    project_id: &str,
+) -> Result<Vec<CustomDomain>> {
+    query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM custom_domains WHERE project_id = ?1 ORDER BY hostname",
+            CUSTOM_DOMAIN_COLS
+        ),
+        &[&project_id],
+    )
+}
+
+pub fn mark_custom_domain_verified(conn: &Connection, hostname: &str, at: i64) -> Result<bool> {
+    let updated = conn.execute(
+        "UPDATE custom_domains SET verified_at = ?1 WHERE hostname = ?2",
+        params![at, hostname],
+    )?;
+    Ok(updated > 0)
+}
+
+pub fn delete_custom_domain(conn: &Connection, hostname: &str) -> Result<bool> {
+    let deleted = conn.execute(
+        "DELETE FROM custom_domains WHERE hostname = ?1",
+        params![hostname],
+    )?;
+    Ok(deleted > 0)
+}
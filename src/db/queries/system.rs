@@ -0,0 +1,251 @@
+//! System config, soft-delete retention purges, database backup records,
+//! and status page counts.
+
+use rusqlite::{Connection, params};
+
+use crate::db::from_row::{BACKUP_COLS, FromRow, STORAGE_HEARTBEAT_COLS, query_all, query_one};
+use crate::error::Result;
+use crate::models::{Backup, StorageHeartbeat};
+
+use super::util::now;
+
+// ============ Soft Delete Maintenance ============
+
+/// Result of purging soft-deleted records.
+#[derive(Debug, Default)]
+pub struct PurgeResult {
+    pub users: usize,
+    pub organizations: usize,
+    pub org_members: usize,
+    pub projects: usize,
+    pub products: usize,
+    pub licenses: usize,
+}
+
+impl PurgeResult {
+    pub fn total(&self) -> usize {
+        self.users
+            + self.organizations
+            + self.org_members
+            + self.projects
+            + self.products
+            + self.licenses
+    }
+}
+
+/// Permanently delete soft-deleted records older than retention_days.
+/// Deletes in order to respect FK constraints (children first).
+/// Returns counts of deleted records per table.
+/// Called periodically when SOFT_DELETE_RETENTION_DAYS > 0.
+pub fn purge_soft_deleted_records(conn: &Connection, retention_days: i64) -> Result<PurgeResult> {
+    use crate::db::soft_delete::purge_table;
+
+    let cutoff = now() - (retention_days * 86400);
+
+    // Delete in order: deepest children first to respect FK constraints
+    // Note: devices, activation_codes, revoked_jtis use FK CASCADE so they'll be deleted
+    // automatically when their parent license is deleted.
+    Ok(PurgeResult {
+        licenses: purge_table(conn, "licenses", cutoff)?,
+        products: purge_table(conn, "products", cutoff)?,
+        projects: purge_table(conn, "projects", cutoff)?,
+        org_members: purge_table(conn, "org_members", cutoff)?,
+        organizations: purge_table(conn, "organizations", cutoff)?,
+        users: purge_table(conn, "users", cutoff)?,
+    })
+}
+
+// ============================================================================
+// System Config
+// ============================================================================
+
+/// Get a system config value by key.
+pub fn get_system_config(conn: &Connection, key: &str) -> Result<Option<Vec<u8>>> {
+    let result = conn.query_row(
+        "SELECT value FROM system_config WHERE key = ?",
+        [key],
+        |row| row.get::<_, Vec<u8>>(0),
+    );
+
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Set a system config value (insert or update).
+pub fn set_system_config(conn: &Connection, key: &str, value: &[u8]) -> Result<()> {
+    let now = now();
+    conn.execute(
+        "INSERT INTO system_config (key, value, created_at, updated_at)
+         VALUES (?, ?, ?, ?)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+        rusqlite::params![key, value, now, now],
+    )?;
+    Ok(())
+}
+
+/// Delete a system config value. Returns whether it existed.
+pub fn delete_system_config(conn: &Connection, key: &str) -> Result<bool> {
+    let deleted = conn.execute("DELETE FROM system_config WHERE key = ?", [key])?;
+    Ok(deleted > 0)
+}
+
+// ============ Backups ============
+
+pub fn create_backup(conn: &Connection, backup: &Backup) -> Result<()> {
+    conn.execute(
+        "INSERT INTO backups (id, file_name, size_bytes, duration_ms, sha256, encrypted_with, created_by, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            &backup.id,
+            &backup.file_name,
+            backup.size_bytes,
+            backup.duration_ms,
+            &backup.sha256,
+            &backup.encrypted_with,
+            &backup.created_by,
+            backup.created_at
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn get_backup(conn: &Connection, id: &str) -> Result<Option<Backup>> {
+    query_one(
+        conn,
+        &format!("SELECT {} FROM backups WHERE id = ?1", BACKUP_COLS),
+        &[&id],
+    )
+}
+
+/// All recorded backups, newest first.
+pub fn list_backups(conn: &Connection) -> Result<Vec<Backup>> {
+    query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM backups ORDER BY created_at DESC, id DESC",
+            BACKUP_COLS
+        ),
+        &[],
+    )
+}
+
+/// Backups older than the newest `keep`, oldest first.
+pub fn list_backups_beyond_retention(conn: &Connection, keep: i64) -> Result<Vec<Backup>> {
+    query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM (
+                 SELECT * FROM backups ORDER BY created_at DESC, id DESC LIMIT -1 OFFSET ?1
+             ) ORDER BY created_at, id",
+            BACKUP_COLS
+        ),
+        &[&keep],
+    )
+}
+
+pub fn record_backup_verification(
+    conn: &Connection,
+    id: &str,
+    ok: bool,
+    verified_at: i64,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE backups SET verified_at = ?1, verified_ok = ?2 WHERE id = ?3",
+        params![verified_at, ok, id],
+    )?;
+    Ok(())
+}
+
+/// Delete a backup's record. Returns whether it existed.
+pub fn delete_backup(conn: &Connection, id: &str) -> Result<bool> {
+    Ok(conn.execute("DELETE FROM backups WHERE id = ?1", [id])? > 0)
+}
+
+// ============ Storage Heartbeats ============
+
+pub fn get_storage_heartbeat(conn: &Connection, db: &str) -> Result<Option<StorageHeartbeat>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM storage_heartbeats WHERE db = ?1",
+            STORAGE_HEARTBEAT_COLS
+        ),
+        &[&db],
+    )
+}
+
+/// Record a server start with `db` at `path`. The start counts as a beat;
+/// the count carries on from the previous run.
+pub fn record_storage_start(
+    conn: &Connection,
+    db: &str,
+    path: &str,
+    now: i64,
+) -> Result<StorageHeartbeat> {
+    Ok(conn.query_row(
+        &format!(
+            "INSERT INTO storage_heartbeats (db, path, beat, started_at, beat_at)
+             VALUES (?1, ?2, 1, ?3, ?3)
+             ON CONFLICT(db) DO UPDATE SET path = excluded.path, beat = beat + 1,
+                 started_at = excluded.started_at, beat_at = excluded.beat_at
+             RETURNING {}",
+            STORAGE_HEARTBEAT_COLS
+        ),
+        params![db, path, now],
+        StorageHeartbeat::from_row,
+    )?)
+}
+
+/// Increment `db`'s heartbeat. A database without a row yet gets one,
+/// recorded as started now at `path`.
+pub fn beat_storage_heartbeat(
+    conn: &Connection,
+    db: &str,
+    path: &str,
+    now: i64,
+) -> Result<StorageHeartbeat> {
+    Ok(conn.query_row(
+        &format!(
+            "INSERT INTO storage_heartbeats (db, path, beat, started_at, beat_at)
+             VALUES (?1, ?2, 1, ?3, ?3)
+             ON CONFLICT(db) DO UPDATE SET beat = beat + 1, beat_at = excluded.beat_at
+             RETURNING {}",
+            STORAGE_HEARTBEAT_COLS
+        ),
+        params![db, path, now],
+        StorageHeartbeat::from_row,
+    )?)
+}
+
+/// Store a copy of another database's heartbeat row.
+pub fn save_storage_heartbeat(conn: &Connection, heartbeat: &StorageHeartbeat) -> Result<()> {
+    conn.execute(
+        "INSERT INTO storage_heartbeats (db, path, beat, started_at, beat_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT(db) DO UPDATE SET path = excluded.path, beat = excluded.beat,
+             started_at = excluded.started_at, beat_at = excluded.beat_at",
+        params![
+            &heartbeat.db,
+            &heartbeat.path,
+            heartbeat.beat,
+            heartbeat.started_at,
+            heartbeat.beat_at
+        ],
+    )?;
+    Ok(())
+}
+
+// ============ Status Page ============
+
+/// Rows in `table` that aren't soft-deleted. `table` must have a
+/// `deleted_at` column and is never user input.
+pub fn count_live_rows(conn: &Connection, table: &str) -> Result<i64> {
+    Ok(conn.query_row(
+        &format!("SELECT COUNT(*) FROM {} WHERE deleted_at IS NULL", table),
+        [],
+        |row| row.get(0),
+    )?)
+}
@@ -0,0 +1,1060 @@
+//! User and API key queries.
+
+use rusqlite::{Connection, OptionalExtension, params};
+use uuid::Uuid;
+
+use crate::crypto::{MasterKey, hash_secret};
+use crate::db::EmailColumn;
+use crate::db::from_row::{
+    API_KEY_COLS, API_KEY_SCOPE_COLS, ORG_MEMBER_COLS, PROJECT_MEMBER_COLS, USER_COLS, query_all,
+    query_one,
+};
+use crate::error::{AppError, Result};
+use crate::models::*;
+
+use super::util::{UpdateBuilder, gen_id, now};
+
+// ============ Users ============
+
+/// Create a user.
+pub fn create_user(conn: &Connection, input: &CreateUser, emails: &EmailColumn) -> Result<User> {
+    let id = gen_id();
+    let now = now();
+    let email = EmailAddress::parse(&input.email)?;
+
+    conn.execute(
+        "INSERT INTO users (id, email, email_hash, name, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            &id,
+            &emails.seal(email.as_str())?,
+            &emails.hash(&email),
+            &input.name,
+            now,
+            now
+        ],
+    )?;
+
+    Ok(User {
+        id,
+        email: email.into(),
+        name: input.name.clone(),
+        operator_role: None,
+        created_at: now,
+        updated_at: now,
+        deleted_at: None,
+        deleted_cascade_depth: None,
+        merged_into: None,
+    })
+}
+
+pub fn get_user_by_id(conn: &Connection, id: &str, emails: &EmailColumn) -> Result<Option<User>> {
+    emails.reveal_opt(query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM users WHERE id = ?1 AND deleted_at IS NULL",
+            USER_COLS
+        ),
+        &[&id],
+    )?)
+}
+
+/// Look up a user by email. Matches the hash column, plus the plaintext column
+/// for rows written before their hash was backfilled. Users stored under the
+/// legacy hash are found through [`crate::crypto::EmailHasher::find_by_hash`]; an invalid
+/// address matches nobody.
+pub fn get_user_by_email(
+    conn: &Connection,
+    email: &str,
+    emails: &EmailColumn,
+) -> Result<Option<User>> {
+    let Ok(email) = EmailAddress::parse(email) else {
+        return Ok(None);
+    };
+    let sql = format!(
+        "SELECT {} FROM users WHERE (email_hash = ?1 OR email = ?2) AND deleted_at IS NULL",
+        USER_COLS
+    );
+    let normalized_hash = emails.hash(&email);
+    let user = emails.hasher().find_by_hash(
+        &email,
+        "user",
+        |hash| {
+            // Plaintext emails were stored in the same form they were hashed
+            let plaintext = match email.legacy_form() {
+                Some(legacy) if hash != normalized_hash => legacy,
+                _ => email.as_str(),
+            };
+            query_one::<User>(conn, &sql, &[&hash, &plaintext])
+        },
+        Option::is_some,
+    )?;
+    emails.reveal_opt(user)
+}
+
+pub fn list_users(conn: &Connection, emails: &EmailColumn) -> Result<Vec<User>> {
+    emails.reveal_all(query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM users WHERE deleted_at IS NULL ORDER BY created_at DESC",
+            USER_COLS
+        ),
+        &[],
+    )?)
+}
+
+pub fn list_users_paginated(
+    conn: &Connection,
+    limit: i64,
+    offset: i64,
+    include_deleted: bool,
+    emails: &EmailColumn,
+) -> Result<(Vec<User>, i64)> {
+    let deleted_filter = if include_deleted {
+        ""
+    } else {
+        "WHERE deleted_at IS NULL"
+    };
+    let total: i64 = conn.query_row(
+        &format!("SELECT COUNT(*) FROM users {}", deleted_filter),
+        [],
+        |row| row.get(0),
+    )?;
+    let items = query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM users {} ORDER BY created_at DESC, id DESC LIMIT ?1 OFFSET ?2",
+            USER_COLS, deleted_filter
+        ),
+        params![limit, offset],
+    )?;
+    Ok((emails.reveal_all(items)?, total))
+}
+
+/// Update a user. Returns the updated user, or None if not found.
+pub fn update_user(
+    conn: &Connection,
+    id: &str,
+    input: &UpdateUser,
+    emails: &EmailColumn,
+) -> Result<Option<User>> {
+    let email = input
+        .email
+        .as_deref()
+        .map(EmailAddress::parse)
+        .transpose()?;
+    let (stored, hash) = match &email {
+        Some(email) => (Some(emails.seal(email.as_str())?), Some(emails.hash(email))),
+        None => (None, None),
+    };
+    emails.reveal_opt(
+        UpdateBuilder::new("users", id)
+            .with_updated_at()
+            .set_opt("email", stored)
+            .set_opt("email_hash", hash)
+            .set_opt("name", input.name.clone())
+            .execute_returning(conn, USER_COLS)?,
+    )
+}
+
+pub fn delete_user(conn: &Connection, id: &str) -> Result<bool> {
+    let deleted = conn.execute("DELETE FROM users WHERE id = ?1", params![id])?;
+    Ok(deleted > 0)
+}
+
+/// Soft delete a user and cascade to org_members.
+/// Returns true if the user was found and soft deleted.
+pub fn soft_delete_user(conn: &Connection, id: &str) -> Result<bool> {
+    use crate::db::soft_delete::{cascade_delete_direct, soft_delete_entity};
+
+    let result = soft_delete_entity(conn, "users", id)?;
+    if !result.deleted {
+        return Ok(false);
+    }
+
+    // Cascade to org_members (depth 1)
+    // Note: org_members cascade will further cascade to project_members
+    cascade_delete_direct(conn, "org_members", "user_id", id, result.deleted_at, 1)?;
+    // Cascade to project_members (depth 2 - via org_members)
+    cascade_delete_user_project_members(conn, id, result.deleted_at)?;
+
+    Ok(true)
+}
+
+/// Soft delete a user's project memberships (depth 2, via org_members).
+/// Called by `soft_delete_user`, and separately for each dedicated org database.
+pub fn cascade_delete_user_project_members(
+    conn: &Connection,
+    user_id: &str,
+    deleted_at: i64,
+) -> Result<usize> {
+    Ok(conn.execute(
+        "UPDATE project_members SET deleted_at = ?1, deleted_cascade_depth = 2
+         WHERE org_member_id IN (SELECT id FROM org_members WHERE user_id = ?2) AND deleted_at IS NULL",
+        params![deleted_at, user_id],
+    )?)
+}
+
+/// Get a soft-deleted user by ID (for restore operations).
+pub fn get_deleted_user_by_id(
+    conn: &Connection,
+    id: &str,
+    emails: &EmailColumn,
+) -> Result<Option<User>> {
+    emails.reveal_opt(get_deleted_user_row(conn, id)?)
+}
+
+/// Soft-deleted user row with the email as stored.
+fn get_deleted_user_row(conn: &Connection, id: &str) -> Result<Option<User>> {
+    query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM users WHERE id = ?1 AND deleted_at IS NOT NULL",
+            USER_COLS
+        ),
+        &[&id],
+    )
+}
+
+/// Restore a soft-deleted user and optionally cascade to children.
+/// Returns Err if depth > 0 and force=false (was cascaded from parent).
+/// If force=true or depth=0, restores user and all cascaded children.
+pub fn restore_user(conn: &Connection, id: &str, force: bool) -> Result<bool> {
+    use crate::db::soft_delete::{check_restore_allowed, restore_cascaded_direct, restore_entity};
+
+    let Some(user) = get_deleted_user_row(conn, id)? else {
+        return Ok(false);
+    };
+
+    check_restore_allowed(user.deleted_cascade_depth, force, "User")?;
+
+    let deleted_at = user.deleted_at.unwrap();
+
+    // Restore cascaded children (org_members)
+    restore_cascaded_direct(conn, "org_members", "user_id", id, deleted_at)?;
+
+    // Restore the user
+    restore_entity(conn, "users", id)?;
+
+    Ok(true)
+}
+
+/// All user rows (including deleted) as (id, stored email), filtered by `filter`.
+fn list_user_email_rows(conn: &Connection, filter: &str) -> Result<Vec<(String, String)>> {
+    let mut stmt = conn.prepare(&format!("SELECT id, email FROM users WHERE {}", filter))?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// Fill in `email_hash` for users created before the column existed.
+/// Run at startup; returns the number of rows updated. Rows whose email no
+/// longer parses are skipped and stay reachable through the plaintext match.
+pub fn backfill_user_email_hashes(conn: &Connection, emails: &EmailColumn) -> Result<usize> {
+    let rows = list_user_email_rows(conn, "email_hash IS NULL")?;
+    let mut updated = 0;
+    for (id, stored) in &rows {
+        let Ok(email) = EmailAddress::parse(&emails.reveal(stored)?) else {
+            tracing::warn!(user_id = %id, "Skipping email hash backfill: invalid stored email");
+            continue;
+        };
+        conn.execute(
+            "UPDATE users SET email_hash = ?1 WHERE id = ?2",
+            params![emails.hash(&email), id],
+        )?;
+        updated += 1;
+    }
+    Ok(updated)
+}
+
+/// Encrypt every plaintext user email in place (`--encrypt-user-emails`).
+/// Runs in one transaction; returns the number of rows encrypted.
+pub fn encrypt_user_emails(conn: &mut Connection, emails: &EmailColumn) -> Result<usize> {
+    let tx = conn.transaction()?;
+    let rows = list_user_email_rows(&tx, "email NOT LIKE 'enc1:%'")?;
+    for (id, email) in &rows {
+        // An unparseable legacy email keeps whatever hash it already has
+        let hash = EmailAddress::parse(email).ok().map(|e| emails.hash(&e));
+        tx.execute(
+            "UPDATE users SET email = ?1, email_hash = COALESCE(?2, email_hash) WHERE id = ?3",
+            params![emails.encrypt(email)?, hash, id],
+        )?;
+    }
+    tx.commit()?;
+    Ok(rows.len())
+}
+
+/// Re-encrypt sealed user emails under a new master key (key rotation).
+/// Hashes are unaffected: the HMAC key survives rotation.
+pub fn rotate_user_emails(
+    conn: &Connection,
+    old_key: &MasterKey,
+    new_key: &MasterKey,
+) -> Result<usize> {
+    let rows = list_user_email_rows(conn, "email LIKE 'enc1:%'")?;
+    for (id, stored) in &rows {
+        conn.execute(
+            "UPDATE users SET email = ?1 WHERE id = ?2",
+            params![EmailColumn::rotate(stored, old_key, new_key)?, id],
+        )?;
+    }
+    Ok(rows.len())
+}
+
+/// Get a user with their operator role and org memberships.
+pub fn get_user_with_roles(
+    conn: &Connection,
+    id: &str,
+    emails: &EmailColumn,
+) -> Result<Option<UserWithRoles>> {
+    // Get the base user
+    let user: Option<User> = emails.reveal_opt(query_one(
+        conn,
+        &format!("SELECT {} FROM users WHERE id = ?1", USER_COLS),
+        &[&id],
+    )?)?;
+
+    let Some(user) = user else {
+        return Ok(None);
+    };
+
+    // Get org memberships with org names
+    let memberships: Vec<(String, String, String, OrgMemberRole)> = {
+        let mut stmt = conn.prepare(
+            "SELECT m.id, m.org_id, o.name, m.role
+             FROM org_members m
+             JOIN organizations o ON o.id = m.org_id
+             WHERE m.user_id = ?1
+             ORDER BY o.name",
+        )?;
+        stmt.query_map([&id], |row| {
+            let role: OrgMemberRole = row.get::<_, String>(3)?.parse().map_err(|_| {
+                rusqlite::Error::InvalidColumnType(
+                    3,
+                    "role".to_string(),
+                    rusqlite::types::Type::Text,
+                )
+            })?;
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, role))
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?
+    };
+
+    Ok(Some(UserWithRoles {
+        id: user.id,
+        email: user.email,
+        name: user.name,
+        created_at: user.created_at,
+        updated_at: user.updated_at,
+        operator_role: user.operator_role,
+        memberships: memberships
+            .into_iter()
+            .map(|(id, org_id, org_name, role)| UserOrgMembership {
+                id,
+                org_id,
+                org_name,
+                role,
+            })
+            .collect(),
+    }))
+}
+
+/// List users with their roles, paginated.
+pub fn list_users_with_roles_paginated(
+    conn: &Connection,
+    limit: i64,
+    offset: i64,
+    include_deleted: bool,
+    emails: &EmailColumn,
+) -> Result<(Vec<UserWithRoles>, i64)> {
+    use std::collections::HashMap;
+
+    let deleted_filter = if include_deleted {
+        ""
+    } else {
+        "WHERE deleted_at IS NULL"
+    };
+    let total: i64 = conn.query_row(
+        &format!("SELECT COUNT(*) FROM users {}", deleted_filter),
+        [],
+        |row| row.get(0),
+    )?;
+
+    let users: Vec<User> = emails.reveal_all(query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM users {} ORDER BY created_at DESC, id DESC LIMIT ?1 OFFSET ?2",
+            USER_COLS, deleted_filter
+        ),
+        params![limit, offset],
+    )?)?;
+
+    if users.is_empty() {
+        return Ok((vec![], total));
+    }
+
+    // Batch fetch all org memberships for these users in one query
+    let user_ids: Vec<&str> = users.iter().map(|u| u.id.as_str()).collect();
+    let placeholders: Vec<String> = (1..=user_ids.len()).map(|i| format!("?{}", i)).collect();
+    let sql = format!(
+        "SELECT m.user_id, m.id, m.org_id, o.name, m.role
+         FROM org_members m
+         JOIN organizations o ON o.id = m.org_id
+         WHERE m.user_id IN ({}) AND m.deleted_at IS NULL
+         ORDER BY o.name",
+        placeholders.join(", ")
+    );
+
+    let params: Vec<&dyn rusqlite::ToSql> = user_ids
+        .iter()
+        .map(|id| id as &dyn rusqlite::ToSql)
+        .collect();
+
+    let mut stmt = conn.prepare(&sql)?;
+    let membership_rows = stmt
+        .query_map(rusqlite::params_from_iter(&params), |row| {
+            Ok((
+                row.get::<_, String>(0)?, // user_id
+                row.get::<_, String>(1)?, // membership id
+                row.get::<_, String>(2)?, // org_id
+                row.get::<_, String>(3)?, // org_name
+                row.get::<_, String>(4)?, // role
+            ))
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    // Group memberships by user_id
+    let mut membership_map: HashMap<String, Vec<UserOrgMembership>> = HashMap::new();
+    for (user_id, id, org_id, org_name, role_str) in membership_rows {
+        let role = role_str
+            .parse::<OrgMemberRole>()
+            .map_err(|_| AppError::Internal(format!("Invalid role in database: {}", role_str)))?;
+        membership_map
+            .entry(user_id)
+            .or_default()
+            .push(UserOrgMembership {
+                id,
+                org_id,
+                org_name,
+                role,
+            });
+    }
+
+    // Build results
+    let results = users
+        .into_iter()
+        .map(|user| {
+            let memberships = membership_map.remove(&user.id).unwrap_or_default();
+            UserWithRoles {
+                id: user.id,
+                email: user.email,
+                name: user.name,
+                created_at: user.created_at,
+                updated_at: user.updated_at,
+                operator_role: user.operator_role,
+                memberships,
+            }
+        })
+        .collect();
+
+    Ok((results, total))
+}
+
+// ============ User Merges ============
+
+/// Merge `source` into `target`: move the source's org memberships, API
+/// keys, and operator role to the target, then soft-delete the source with
+/// `merged_into` set. Where both users are in an org, the target keeps its
+/// membership with the higher role and takes the source's project
+/// memberships. Call inside a transaction. Project memberships in dedicated
+/// org databases are left to `merge_project_memberships` on those databases.
+pub fn merge_users(conn: &Connection, source: &User, target: &User) -> Result<UserMerge> {
+    let now = now();
+    let (source_id, target_id) = (source.id.as_str(), target.id.as_str());
+    let source_members: Vec<OrgMember> = query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM org_members WHERE user_id = ?1 AND deleted_at IS NULL",
+            ORG_MEMBER_COLS
+        ),
+        &[&source_id],
+    )?;
+
+    let mut moved_org_ids = Vec::new();
+    let mut conflicts = Vec::new();
+    for theirs in source_members {
+        // A membership the target had deleted gives way to the source's
+        conn.execute(
+            "DELETE FROM org_members WHERE user_id = ?1 AND org_id = ?2 AND deleted_at IS NOT NULL",
+            params![target_id, &theirs.org_id],
+        )?;
+        let ours: Option<OrgMember> = query_one(
+            conn,
+            &format!(
+                "SELECT {} FROM org_members WHERE user_id = ?1 AND org_id = ?2",
+                ORG_MEMBER_COLS
+            ),
+            &[&target_id, &theirs.org_id],
+        )?;
+        let Some(ours) = ours else {
+            conn.execute(
+                "UPDATE org_members SET user_id = ?1, updated_at = ?2 WHERE id = ?3",
+                params![target_id, now, &theirs.id],
+            )?;
+            moved_org_ids.push(theirs.org_id);
+            continue;
+        };
+
+        let kept_role = ours.role.higher(theirs.role);
+        if kept_role != ours.role {
+            conn.execute(
+                "UPDATE org_members SET role = ?1, updated_at = ?2 WHERE id = ?3",
+                params![kept_role.as_ref(), now, &ours.id],
+            )?;
+        }
+        merge_project_memberships(conn, &theirs.id, &ours.id)?;
+        conflicts.push(MembershipConflict {
+            org_id: theirs.org_id,
+            source_role: theirs.role,
+            target_role: ours.role,
+            kept_role,
+            source_member_id: theirs.id,
+            target_member_id: ours.id,
+        });
+    }
+
+    // Keys keep their names unless the target already uses one
+    let key_query = format!("SELECT {} FROM api_keys WHERE user_id = ?1", API_KEY_COLS);
+    let target_names: std::collections::HashSet<String> =
+        query_all::<ApiKey>(conn, &key_query, &[&target_id])?
+            .into_iter()
+            .map(|key| key.name)
+            .collect();
+    let mut moved_api_key_ids = Vec::new();
+    let mut renamed_api_key_ids = Vec::new();
+    for key in query_all::<ApiKey>(conn, &key_query, &[&source_id])? {
+        let name = if target_names.contains(&key.name) {
+            renamed_api_key_ids.push(key.id.clone());
+            format!("{} ({})", key.name, key.prefix)
+        } else {
+            key.name
+        };
+        conn.execute(
+            "UPDATE api_keys SET user_id = ?1, name = ?2 WHERE id = ?3",
+            params![target_id, name, &key.id],
+        )?;
+        moved_api_key_ids.push(key.id);
+    }
+
+    let operator_role = match (source.operator_role, target.operator_role) {
+        (Some(source), Some(target)) => Some(target.higher(source)),
+        (source, target) => target.or(source),
+    };
+    if operator_role != target.operator_role {
+        conn.execute(
+            "UPDATE users SET operator_role = ?1, updated_at = ?2 WHERE id = ?3",
+            params![
+                operator_role.map(|r| r.as_ref().to_string()),
+                now,
+                target_id
+            ],
+        )?;
+    }
+    // A partner's orgs carry over (unused unless the target ends up a partner)
+    conn.execute(
+        "INSERT OR IGNORE INTO operator_org_scopes (operator_id, org_id, created_at)
+         SELECT ?1, org_id, created_at FROM operator_org_scopes WHERE operator_id = ?2",
+        params![target_id, source_id],
+    )?;
+
+    soft_delete_user(conn, source_id)?;
+    conn.execute(
+        "UPDATE users SET merged_into = ?1, operator_role = NULL WHERE id = ?2",
+        params![target_id, source_id],
+    )?;
+
+    Ok(UserMerge {
+        source_user_id: source_id.to_string(),
+        moved_org_ids,
+        conflicts,
+        moved_api_key_ids,
+        renamed_api_key_ids,
+        operator_role,
+    })
+}
+
+/// Move one org member's active project memberships and live temporary
+/// roles to another member of the same org. Where both are on a project,
+/// the target keeps the higher role and the source's membership is deleted.
+pub fn merge_project_memberships(
+    conn: &Connection,
+    source_member_id: &str,
+    target_member_id: &str,
+) -> Result<()> {
+    let now = now();
+    let source_projects: Vec<ProjectMember> = query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM project_members WHERE org_member_id = ?1 AND deleted_at IS NULL",
+            PROJECT_MEMBER_COLS
+        ),
+        &[&source_member_id],
+    )?;
+    for source in source_projects {
+        let target: Option<ProjectMember> = query_one(
+            conn,
+            &format!(
+                "SELECT {} FROM project_members
+                 WHERE org_member_id = ?1 AND project_id = ?2 AND deleted_at IS NULL",
+                PROJECT_MEMBER_COLS
+            ),
+            &[&target_member_id, &source.project_id],
+        )?;
+        match target {
+            None => {
+                conn.execute(
+                    "UPDATE project_members SET org_member_id = ?1, updated_at = ?2 WHERE id = ?3",
+                    params![target_member_id, now, &source.id],
+                )?;
+            }
+            Some(target) => {
+                let role = target.role.higher(source.role);
+                conn.execute(
+                    "UPDATE project_members SET role = ?1, updated_at = ?2 WHERE id = ?3",
+                    params![role.as_ref(), now, &target.id],
+                )?;
+                conn.execute(
+                    "UPDATE project_members SET deleted_at = ?1, deleted_cascade_depth = 0 WHERE id = ?2",
+                    params![now, &source.id],
+                )?;
+            }
+        }
+    }
+    conn.execute(
+        "UPDATE temporary_role_grants SET org_member_id = ?1
+         WHERE org_member_id = ?2 AND revoked_at IS NULL AND expires_at > ?3",
+        params![target_member_id, source_member_id, now],
+    )?;
+    Ok(())
+}
+
+// ============ API Keys (Unified) ============
+
+/// Generate an API key with pc_ prefix
+pub fn generate_api_key() -> String {
+    format!("pc_{}", Uuid::new_v4().to_string().replace("-", ""))
+}
+
+/// Get user by API key. Returns the user and key info if found and valid.
+pub fn get_user_by_api_key(
+    conn: &Connection,
+    api_key: &str,
+    emails: &EmailColumn,
+) -> Result<Option<(User, ApiKey)>> {
+    let hash = hash_secret(api_key);
+
+    let key: Option<ApiKey> = query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM api_keys WHERE key_hash = ?1 AND revoked_at IS NULL AND (expires_at IS NULL OR expires_at > unixepoch())",
+            API_KEY_COLS
+        ),
+        &[&hash],
+    )?;
+
+    if let Some(key) = key {
+        // Update last_used_at (fire and forget)
+        let _ = conn.execute(
+            "UPDATE api_keys SET last_used_at = ?1 WHERE id = ?2",
+            params![now(), &key.id],
+        );
+
+        // Get the user
+        if let Some(user) = get_user_by_id(conn, &key.user_id, emails)? {
+            return Ok(Some((user, key)));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Create an API key for a user.
+///
+/// This function uses a transaction with IMMEDIATE mode to prevent TOCTOU races
+/// where the validated state (org membership, project ownership) could change
+/// between validation and insertion.
+pub fn create_api_key(
+    conn: &mut Connection,
+    user_id: &str,
+    name: &str,
+    expires_in_days: Option<i64>,
+    user_manageable: bool,
+    scopes: Option<&[CreateApiKeyScope]>,
+) -> Result<(ApiKey, String)> {
+    // Use IMMEDIATE to acquire write lock at transaction start, preventing TOCTOU races
+    let tx = conn.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
+    let created = insert_api_key(&tx, user_id, name, expires_in_days, user_manageable, scopes)?;
+
+    // Commit the transaction - all or nothing
+    tx.commit()?;
+    Ok(created)
+}
+
+/// [`create_api_key`] inside a transaction the caller already holds, for
+/// creating a key along with other rows.
+pub fn insert_api_key(
+    conn: &Connection,
+    user_id: &str,
+    name: &str,
+    expires_in_days: Option<i64>,
+    user_manageable: bool,
+    scopes: Option<&[CreateApiKeyScope]>,
+) -> Result<(ApiKey, String)> {
+    // Validate all scopes within the transaction
+    if let Some(scopes) = scopes {
+        for scope in scopes {
+            // Validate that org exists
+            let org_exists: bool = conn
+                .query_row(
+                    "SELECT 1 FROM organizations WHERE id = ?1 AND deleted_at IS NULL",
+                    params![&scope.org_id],
+                    |_| Ok(true),
+                )
+                .optional()?
+                .unwrap_or(false);
+
+            if !org_exists {
+                return Err(AppError::BadRequest(
+                    "Invalid scope: organization not found".into(),
+                ));
+            }
+
+            // Validate that user is a member of the organization OR is an admin+ operator
+            // Operators with admin/owner role have synthetic access to all orgs
+            let is_member: bool = conn
+                .query_row(
+                    "SELECT 1 FROM org_members WHERE user_id = ?1 AND org_id = ?2 AND deleted_at IS NULL",
+                    params![user_id, &scope.org_id],
+                    |_| Ok(true),
+                )
+                .optional()?
+                .unwrap_or(false);
+
+            let is_admin_operator: bool = conn
+                .query_row(
+                    "SELECT 1 FROM users WHERE id = ?1 AND operator_role IN ('admin', 'owner') AND deleted_at IS NULL",
+                    params![user_id],
+                    |_| Ok(true),
+                )
+                .optional()?
+                .unwrap_or(false);
+
+            if !is_member && !is_admin_operator {
+                return Err(AppError::BadRequest(
+                    "Invalid scope: user is not a member of the specified organization".into(),
+                ));
+            }
+
+            // Validate that project belongs to org (if project_id is specified)
+            if let Some(ref project_id) = scope.project_id {
+                let project_org_id: Option<String> = conn
+                    .query_row(
+                        "SELECT org_id FROM projects WHERE id = ?1 AND deleted_at IS NULL",
+                        params![project_id],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+
+                match project_org_id {
+                    None => {
+                        return Err(AppError::BadRequest(
+                            "Invalid scope: project not found".into(),
+                        ));
+                    }
+                    Some(org_id) if org_id != scope.org_id => {
+                        return Err(AppError::BadRequest(
+                            "Invalid scope: project does not belong to the specified organization"
+                                .into(),
+                        ));
+                    }
+                    _ => {} // Valid: project exists and belongs to the org
+                }
+            }
+        }
+    }
+
+    let id = gen_id();
+    let now = now();
+    let key = generate_api_key();
+    let prefix = &key[..12];
+    let key_hash = hash_secret(&key);
+    let expires_at = expires_in_days.map(|days| now + days * 86400);
+
+    conn.execute(
+        "INSERT INTO api_keys (id, user_id, name, key_prefix, key_hash, user_manageable, created_at, last_used_at, expires_at, revoked_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, NULL, ?8, NULL)",
+        params![&id, user_id, name, prefix, &key_hash, user_manageable as i32, now, expires_at],
+    )?;
+
+    // Insert scopes (already validated above)
+    if let Some(scopes) = scopes {
+        for scope in scopes {
+            let scope_id = gen_id();
+            conn.execute(
+                "INSERT INTO api_key_scopes (id, api_key_id, org_id, project_id, access)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    &scope_id,
+                    &id,
+                    &scope.org_id,
+                    &scope.project_id,
+                    scope.access.as_ref()
+                ],
+            )?;
+        }
+    }
+
+    Ok((
+        ApiKey {
+            id,
+            user_id: user_id.to_string(),
+            name: name.to_string(),
+            prefix: prefix.to_string(),
+            key_hash,
+            user_manageable,
+            created_at: now,
+            last_used_at: None,
+            expires_at,
+            revoked_at: None,
+        },
+        key,
+    ))
+}
+
+/// List API keys for a user (active only, excludes revoked)
+/// If user_manageable_only is true, only returns user-manageable keys
+pub fn list_api_keys(
+    conn: &Connection,
+    user_id: &str,
+    user_manageable_only: bool,
+) -> Result<Vec<ApiKey>> {
+    if user_manageable_only {
+        query_all(
+            conn,
+            &format!(
+                "SELECT {} FROM api_keys WHERE user_id = ?1 AND user_manageable = 1 AND revoked_at IS NULL ORDER BY created_at DESC",
+                API_KEY_COLS
+            ),
+            &[&user_id],
+        )
+    } else {
+        query_all(
+            conn,
+            &format!(
+                "SELECT {} FROM api_keys WHERE user_id = ?1 AND revoked_at IS NULL ORDER BY created_at DESC",
+                API_KEY_COLS
+            ),
+            &[&user_id],
+        )
+    }
+}
+
+pub fn list_api_keys_paginated(
+    conn: &Connection,
+    user_id: &str,
+    user_manageable_only: bool,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<ApiKey>, i64)> {
+    let (count_sql, list_sql) = if user_manageable_only {
+        (
+            "SELECT COUNT(*) FROM api_keys WHERE user_id = ?1 AND user_manageable = 1 AND revoked_at IS NULL",
+            format!(
+                "SELECT {} FROM api_keys WHERE user_id = ?1 AND user_manageable = 1 AND revoked_at IS NULL ORDER BY created_at DESC, id DESC LIMIT ?2 OFFSET ?3",
+                API_KEY_COLS
+            ),
+        )
+    } else {
+        (
+            "SELECT COUNT(*) FROM api_keys WHERE user_id = ?1 AND revoked_at IS NULL",
+            format!(
+                "SELECT {} FROM api_keys WHERE user_id = ?1 AND revoked_at IS NULL ORDER BY created_at DESC, id DESC LIMIT ?2 OFFSET ?3",
+                API_KEY_COLS
+            ),
+        )
+    };
+
+    let total: i64 = conn.query_row(count_sql, params![user_id], |row| row.get(0))?;
+    let keys = query_all(conn, &list_sql, params![user_id, limit, offset])?;
+    Ok((keys, total))
+}
+
+/// Get an API key by ID
+pub fn get_api_key_by_id(conn: &Connection, key_id: &str) -> Result<Option<ApiKey>> {
+    query_one(
+        conn,
+        &format!("SELECT {} FROM api_keys WHERE id = ?1", API_KEY_COLS),
+        &[&key_id],
+    )
+}
+
+/// Get scopes for an API key
+pub fn get_api_key_scopes(conn: &Connection, key_id: &str) -> Result<Vec<ApiKeyScope>> {
+    query_all(
+        conn,
+        &format!(
+            "SELECT {} FROM api_key_scopes WHERE api_key_id = ?1",
+            API_KEY_SCOPE_COLS
+        ),
+        &[&key_id],
+    )
+}
+
+/// Get scopes for multiple API keys in a single query (fixes N+1).
+/// Returns a map of key_id -> Vec<ApiKeyScope>.
+pub fn get_api_key_scopes_batch(
+    conn: &Connection,
+    key_ids: &[String],
+) -> Result<std::collections::HashMap<String, Vec<ApiKeyScope>>> {
+    use std::collections::HashMap;
+
+    if key_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    // Build placeholders: ?1, ?2, ?3, ...
+    let placeholders: Vec<String> = (1..=key_ids.len()).map(|i| format!("?{}", i)).collect();
+    let sql = format!(
+        "SELECT {} FROM api_key_scopes WHERE api_key_id IN ({})",
+        API_KEY_SCOPE_COLS,
+        placeholders.join(", ")
+    );
+
+    // Convert to params
+    let params: Vec<&dyn rusqlite::ToSql> =
+        key_ids.iter().map(|s| s as &dyn rusqlite::ToSql).collect();
+    let scopes: Vec<ApiKeyScope> = query_all(conn, &sql, params.as_slice())?;
+
+    // Group by key_id
+    let mut result: HashMap<String, Vec<ApiKeyScope>> = HashMap::new();
+    for scope in scopes {
+        result
+            .entry(scope.api_key_id.clone())
+            .or_default()
+            .push(scope);
+    }
+
+    Ok(result)
+}
+
+/// Revoke an API key (soft delete)
+pub fn revoke_api_key(conn: &Connection, key_id: &str) -> Result<bool> {
+    let now = now();
+    let affected = conn.execute(
+        "UPDATE api_keys SET revoked_at = ?1 WHERE id = ?2 AND revoked_at IS NULL",
+        params![now, key_id],
+    )?;
+    Ok(affected > 0)
+}
+
+/// Check if an API key has any scopes defined
+pub fn api_key_has_scopes(conn: &Connection, key_id: &str) -> Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM api_key_scopes WHERE api_key_id = ?1",
+        params![key_id],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// Get the access level for an API key on a specific org/project.
+/// Returns the access level if allowed, None if no matching scope.
+pub fn get_api_key_access_level(
+    conn: &Connection,
+    key_id: &str,
+    org_id: &str,
+    project_id: Option<&str>,
+) -> Result<Option<AccessLevel>> {
+    // Check for matching scope
+    // First try exact project match, then org-wide match (project_id IS NULL)
+    let scope: Option<ApiKeyScope> = if let Some(proj_id) = project_id {
+        // Try exact project match first
+        let exact: Option<ApiKeyScope> = query_one(
+            conn,
+            &format!(
+                "SELECT {} FROM api_key_scopes WHERE api_key_id = ?1 AND org_id = ?2 AND project_id = ?3",
+                API_KEY_SCOPE_COLS
+            ),
+            params![key_id, org_id, proj_id],
+        )?;
+
+        if exact.is_some() {
+            exact
+        } else {
+            // Fall back to org-wide scope
+            query_one(
+                conn,
+                &format!(
+                    "SELECT {} FROM api_key_scopes WHERE api_key_id = ?1 AND org_id = ?2 AND project_id IS NULL",
+                    API_KEY_SCOPE_COLS
+                ),
+                params![key_id, org_id],
+            )?
+        }
+    } else {
+        // Just check org-level access
+        query_one(
+            conn,
+            &format!(
+                "SELECT {} FROM api_key_scopes WHERE api_key_id = ?1 AND org_id = ?2",
+                API_KEY_SCOPE_COLS
+            ),
+            params![key_id, org_id],
+        )?
+    };
+
+    Ok(scope.map(|s| s.access))
+}
+
+/// Check if an API key has at least the required access level for an org/project.
+/// Returns true if access is granted, false otherwise.
+pub fn check_api_key_scope(
+    conn: &Connection,
+    key_id: &str,
+    org_id: &str,
+    project_id: Option<&str>,
+    required_access: AccessLevel,
+) -> Result<bool> {
+    let access_level = get_api_key_access_level(conn, key_id, org_id, project_id)?;
+
+    match access_level {
+        Some(AccessLevel::Admin) => Ok(true), // Admin has all access
+        Some(AccessLevel::View) => Ok(required_access == AccessLevel::View), // View only has view access
+        None => Ok(false),                                                   // No scope = no access
+    }
+}
+
+/// Check if an API key has org-level access (not just project-level).
+/// Returns the access level if the key has an org-wide scope (project_id IS NULL).
+/// Returns None if the key only has project-specific scopes.
+///
+/// This is used for org-level endpoints (e.g., /orgs/{org_id}/members) where
+/// a project-scoped key should NOT grant access.
+pub fn get_api_key_org_level_access(
+    conn: &Connection,
+    key_id: &str,
+    org_id: &str,
+) -> Result<Option<AccessLevel>> {
+    // Only check for org-level scopes (project_id IS NULL)
+    let scope: Option<ApiKeyScope> = query_one(
+        conn,
+        &format!(
+            "SELECT {} FROM api_key_scopes WHERE api_key_id = ?1 AND org_id = ?2 AND project_id IS NULL",
+            API_KEY_SCOPE_COLS
+        ),
+        params![key_id, org_id],
+    )?;
+
+    Ok(scope.map(|s| s.access))
+}
@@ -0,0 +1,150 @@
+//! Helpers shared by the query modules.
+
+use std::collections::BTreeMap;
+
+use chrono::Utc;
+use rusqlite::{Connection, OptionalExtension, types::Value};
+use uuid::Uuid;
+
+use crate::db::from_row::FromRow;
+use crate::error::Result;
+
+pub(super) fn now() -> i64 {
+    Utc::now().timestamp()
+}
+
+pub(super) fn gen_id() -> String {
+    Uuid::new_v4().to_string()
+}
+
+/// Checkout field values (or `/buy` metadata) as stored: a JSON object, or
+/// NULL when there are none.
+pub(super) fn checkout_values_json(values: &BTreeMap<String, String>) -> Result<Option<String>> {
+    if values.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::to_string(values)?))
+}
+
+/// Builder for dynamic UPDATE statements with optional fields.
+/// Combines multiple field updates into a single query for efficiency.
+pub(crate) struct UpdateBuilder {
+    table: &'static str,
+    id: String,
+    fields: Vec<(&'static str, Value)>,
+    track_updated_at: bool,
+    versioned: bool,
+    expected_version: Option<i64>,
+}
+
+impl UpdateBuilder {
+    pub(crate) fn new(table: &'static str, id: &str) -> Self {
+        Self {
+            table,
+            id: id.to_string(),
+            fields: Vec::new(),
+            track_updated_at: false,
+            versioned: false,
+            expected_version: None,
+        }
+    }
+
+    pub(crate) fn with_updated_at(mut self) -> Self {
+        self.track_updated_at = true;
+        self
+    }
+
+    /// Bump the row's `version` column with the update. With `expected`,
+    /// only a row still at that version is updated (optimistic locking);
+    /// a row another update got to first is left alone, as if not found.
+    pub(crate) fn versioned(mut self, expected: Option<i64>) -> Self {
+        self.versioned = true;
+        self.expected_version = expected;
+        self
+    }
+
+    pub(crate) fn set(mut self, column: &'static str, value: impl Into<Value>) -> Self {
+        self.fields.push((column, value.into()));
+        self
+    }
+
+    pub(crate) fn set_opt<V: Into<Value>>(self, column: &'static str, value: Option<V>) -> Self {
+        match value {
+            Some(v) => self.set(column, v),
+            None => self,
+        }
+    }
+
+    /// Set a column to an explicit value (including NULL).
+    /// Use this for Option<T> where Some(v) = set to v, None = set to NULL.
+    pub(crate) fn set_nullable<V: Into<Value>>(
+        mut self,
+        column: &'static str,
+        value: Option<V>,
+    ) -> Self {
+        match value {
+            Some(v) => self.fields.push((column, v.into())),
+            None => self.fields.push((column, Value::Null)),
+        }
+        self
+    }
+
+    pub(crate) fn execute(self, conn: &Connection) -> Result<bool> {
+        if self.fields.is_empty() {
+            return Ok(false);
+        }
+        let (sql, values) = self.statement("");
+        let affected = conn.execute(&sql, rusqlite::params_from_iter(values))?;
+        Ok(affected > 0)
+    }
+
+    /// Execute the update and return the updated entity using RETURNING clause.
+    /// Returns None if no rows matched (entity not found or no fields to update).
+    pub(crate) fn execute_returning<T: FromRow>(
+        self,
+        conn: &Connection,
+        returning_cols: &str,
+    ) -> Result<Option<T>> {
+        if self.fields.is_empty() {
+            return Ok(None);
+        }
+        let (sql, values) = self.statement(&format!(
+            " AND deleted_at IS NULL RETURNING {}",
+            returning_cols
+        ));
+        conn.query_row(&sql, rusqlite::params_from_iter(values), T::from_row)
+            .optional()
+            .map_err(Into::into)
+    }
+
+    /// The UPDATE statement and its parameters, with `suffix` after the
+    /// WHERE clause's own conditions.
+    fn statement(mut self, suffix: &str) -> (String, Vec<Value>) {
+        if self.track_updated_at {
+            self.fields.push(("updated_at", now().into()));
+        }
+        let mut sets: Vec<String> = self
+            .fields
+            .iter()
+            .map(|(col, _)| format!("{} = ?", col))
+            .collect();
+        if self.versioned {
+            sets.push("version = version + 1".to_string());
+        }
+        let mut values: Vec<Value> = self.fields.into_iter().map(|(_, v)| v).collect();
+        values.push(self.id.into());
+        let mut conditions = "id = ?".to_string();
+        if let Some(expected) = self.expected_version {
+            conditions.push_str(" AND version = ?");
+            values.push(expected.into());
+        }
+        let sql = format!(
+            "UPDATE {} SET {} WHERE {}{}",
+            self.table,
+            sets.join(", "),
+            conditions,
+            suffix
+        );
+        (sql, values)
+    }
+}
@@ -0,0 +1,112 @@
+//! License reference numbers.
+//!
+//! Every license also gets a short number support can read out over the
+//! phone: the project's `license_key_prefix`, a dash, and the next value of
+//! the project's counter in `project_counters`, zero-padded to six digits
+//! (`PRO-000123`). The counter is bumped in the same transaction as the
+//! license insert, so two licenses never share a number; a rolled-back
+//! insert can leave a gap.
+//!
+//! Being sequential, reference numbers are easy to guess. Only admin
+//! endpoints resolve them ([`crate::db::queries::resolve_admin_license_id`]);
+//! public endpoints never accept one in place of a license ID, ref or key.
+
+use rusqlite::{Connection, OptionalExtension, params};
+
+/// Name of the license counter in `project_counters`
+pub const LICENSE_COUNTER: &str = "license";
+
+/// Numbers are zero-padded to at least this many digits
+const MIN_DIGITS: usize = 6;
+
+/// Prefix for licenses whose project row is gone (the column's default)
+const FALLBACK_PREFIX: &str = "PC";
+
+/// The reference number for the `number`th license under `prefix`.
+pub fn format(prefix: &str, number: i64) -> String {
+    format!(
+        "{}-{:0width$}",
+        prefix.to_uppercase(),
+        number,
+        width = MIN_DIGITS
+    )
+}
+
+/// Whether `value` has the shape of a reference number: a prefix, a dash,
+/// and at least six digits. Matching is case-insensitive, so callers look
+/// up the [`normalize`]d form.
+pub fn is_reference_number(value: &str) -> bool {
+    value.rsplit_once('-').is_some_and(|(prefix, digits)| {
+        !prefix.is_empty()
+            && digits.len() >= MIN_DIGITS
+            && digits.bytes().all(|b| b.is_ascii_digit())
+    })
+}
+
+/// The stored form of a reference number someone typed in.
+pub fn normalize(value: &str) -> String {
+    value.trim().to_uppercase()
+}
+
+/// Bump the project's license counter and return the reference number for
+/// its new value. Run it in the transaction that inserts the license.
+pub fn next(conn: &Connection, project_id: &str) -> rusqlite::Result<String> {
+    let number: i64 = conn.query_row(
+        "INSERT INTO project_counters (project_id, name, value) VALUES (?1, ?2, 1)
+         ON CONFLICT (project_id, name) DO UPDATE SET value = value + 1
+         RETURNING value",
+        params![project_id, LICENSE_COUNTER],
+        |row| row.get(0),
+    )?;
+    let prefix: Option<String> = conn
+        .query_row(
+            "SELECT license_key_prefix FROM projects WHERE id = ?1",
+            [project_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(format(prefix.as_deref().unwrap_or(FALLBACK_PREFIX), number))
+}
+
+/// Number every license without a reference number, oldest first within
+/// each project.
+pub fn backfill(conn: &Connection) -> rusqlite::Result<usize> {
+    let licenses: Vec<(String, String)> = conn
+        .prepare(
+            "SELECT id, project_id FROM licenses WHERE reference_number IS NULL
+             ORDER BY created_at, rowid",
+        )?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    for (id, project_id) in &licenses {
+        let reference_number = next(conn, project_id)?;
+        conn.execute(
+            "UPDATE licenses SET reference_number = ?1 WHERE id = ?2",
+            params![reference_number, id],
+        )?;
+    }
+    Ok(licenses.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_pads_and_uppercases() {
+        assert_eq!(format("pro", 123), "PRO-000123");
+        assert_eq!(format("PC", 1234567), "PC-1234567");
+    }
+
+    #[test]
+    fn test_reference_numbers_are_told_apart_from_ids_and_refs() {
+        assert!(is_reference_number("PRO-000123"));
+        assert!(is_reference_number("pro-000123"));
+        assert!(is_reference_number("MY-APP-1234567"));
+        assert!(!is_reference_number("PRO-123"));
+        assert!(!is_reference_number("-000123"));
+        assert!(!is_reference_number("0123456789ab"));
+        assert!(!is_reference_number("5f0c6d1e-8a4b-4c2d-9e3f-1a2b3c4d5e6f"));
+    }
+}
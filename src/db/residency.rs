@@ -0,0 +1,412 @@
+//! Optional per-org data residency.
+//!
+//! When `PAYCHECK_ORG_DATA_DIR` is set, each new organization gets its own SQLite
+//! file holding its tenant data: projects, project members, products, provider
+//! links, licenses, devices, activation codes, prepaid codes, revoked JTIs, and
+//! payment sessions.
+//!
+//! Identity and org-level tables (users, API keys, organizations, org members,
+//! service configs, webhook events, system config) stay in the shared database.
+//! The shared file is attached to every org connection as `shared`, and SQLite
+//! resolves unqualified table names against `main` first, so existing queries run
+//! unchanged against an org pool: tenant tables hit the org file, everything else
+//! falls through to the shared database.
+//!
+//! Orgs without a registered file (legacy orgs, or every org when the mode is
+//! disabled) keep using the shared pool.
+//!
+//! # Limitations
+//!
+//! - Project-scoped API keys can't reference projects in a dedicated file (the
+//!   scope table lives in the shared database and validates against it).
+//! - Lookups that only know a child ID (product, payment session, subscription)
+//!   probe the shared pool and then each dedicated pool in turn.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::Connection;
+
+use super::{DbPool, MigrationTarget, init_org_db, queries, run_migrations};
+use crate::error::{AppError, Result};
+use crate::models::Project;
+
+/// Max connections per dedicated org pool (the shared pool uses 10).
+const ORG_POOL_SIZE: u32 = 4;
+
+/// Tenant tables moved into a dedicated file, parents first.
+/// Each entry is (table, filter selecting the org's rows; ?1 = org_id).
+const TENANT_TABLES: &[(&str, &str)] = &[
+    ("projects", "org_id = ?1"),
+    (
+        "terms",
+        "project_id IN (SELECT id FROM main.projects WHERE org_id = ?1)",
+    ),
+    (
+        "project_members",
+        "project_id IN (SELECT id FROM main.projects WHERE org_id = ?1)",
+    ),
+    (
+        "temporary_role_grants",
+        "project_id IN (SELECT id FROM main.projects WHERE org_id = ?1)",
+    ),
+    (
+        "project_counters",
+        "project_id IN (SELECT id FROM main.projects WHERE org_id = ?1)",
+    ),
+    (
+        "products",
+        "project_id IN (SELECT id FROM main.projects WHERE org_id = ?1)",
+    ),
+    (
+        "bulk_jobs",
+        "project_id IN (SELECT id FROM main.projects WHERE org_id = ?1)",
+    ),
+    (
+        "bulk_job_chunks",
+        "job_id IN (SELECT id FROM main.bulk_jobs WHERE project_id IN (SELECT id FROM main.projects WHERE org_id = ?1))",
+    ),
+    (
+        "product_provider_links",
+        "product_id IN (SELECT id FROM main.products WHERE project_id IN (SELECT id FROM main.projects WHERE org_id = ?1))",
+    ),
+    (
+        "licenses",
+        "project_id IN (SELECT id FROM main.projects WHERE org_id = ?1)",
+    ),
+    (
+        "license_seats",
+        "license_id IN (SELECT id FROM main.licenses WHERE project_id IN (SELECT id FROM main.projects WHERE org_id = ?1))",
+    ),
+    (
+        "share_links",
+        "license_id IN (SELECT id FROM main.licenses WHERE project_id IN (SELECT id FROM main.projects WHERE org_id = ?1))",
+    ),
+    (
+        "license_upgrades",
+        "to_license_id IN (SELECT id FROM main.licenses WHERE project_id IN (SELECT id FROM main.projects WHERE org_id = ?1))",
+    ),
+    (
+        "license_update_renewals",
+        "license_id IN (SELECT id FROM main.licenses WHERE project_id IN (SELECT id FROM main.projects WHERE org_id = ?1))",
+    ),
+    (
+        "license_tags",
+        "license_id IN (SELECT id FROM main.licenses WHERE project_id IN (SELECT id FROM main.projects WHERE org_id = ?1))",
+    ),
+    (
+        "disputes",
+        "project_id IN (SELECT id FROM main.projects WHERE org_id = ?1)",
+    ),
+    (
+        "email_log",
+        "project_id IN (SELECT id FROM main.projects WHERE org_id = ?1)",
+    ),
+    (
+        "usage_geo_daily",
+        "project_id IN (SELECT id FROM main.projects WHERE org_id = ?1)",
+    ),
+    (
+        "artifacts",
+        "project_id IN (SELECT id FROM main.projects WHERE org_id = ?1)",
+    ),
+    (
+        "artifact_downloads",
+        "artifact_id IN (SELECT id FROM main.artifacts WHERE project_id IN (SELECT id FROM main.projects WHERE org_id = ?1))",
+    ),
+    (
+        "activation_codes",
+        "license_id IN (SELECT id FROM main.licenses WHERE project_id IN (SELECT id FROM main.projects WHERE org_id = ?1))",
+    ),
+    (
+        "revoked_jtis",
+        "license_id IN (SELECT id FROM main.licenses WHERE project_id IN (SELECT id FROM main.projects WHERE org_id = ?1))",
+    ),
+    (
+        "devices",
+        "license_id IN (SELECT id FROM main.licenses WHERE project_id IN (SELECT id FROM main.projects WHERE org_id = ?1))",
+    ),
+    (
+        "payment_sessions",
+        "product_id IN (SELECT id FROM main.products WHERE project_id IN (SELECT id FROM main.projects WHERE org_id = ?1))",
+    ),
+    (
+        "prepaid_code_batches",
+        "project_id IN (SELECT id FROM main.projects WHERE org_id = ?1)",
+    ),
+    (
+        "prepaid_codes",
+        "batch_id IN (SELECT id FROM main.prepaid_code_batches WHERE project_id IN (SELECT id FROM main.projects WHERE org_id = ?1))",
+    ),
+];
+
+/// Registry of dedicated org database pools.
+pub struct OrgDbRegistry {
+    /// Directory for org database files (None = residency mode disabled)
+    data_dir: Option<PathBuf>,
+    /// Path to the shared database, attached to each org connection
+    shared_path: String,
+    /// Open pools keyed by org_id
+    pools: RwLock<HashMap<String, DbPool>>,
+}
+
+/// Row counts copied into a dedicated file by [`OrgDbRegistry::isolate_org`].
+#[derive(Debug, Default)]
+pub struct IsolateResult {
+    pub db_path: String,
+    pub tables: Vec<(&'static str, usize)>,
+}
+
+impl OrgDbRegistry {
+    /// Residency mode off: every org resolves to the shared pool.
+    pub fn disabled() -> Self {
+        Self {
+            data_dir: None,
+            shared_path: String::new(),
+            pools: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Residency mode on: new orgs get a file in `data_dir`.
+    pub fn new(data_dir: impl Into<PathBuf>, shared_path: &str) -> Self {
+        Self {
+            data_dir: Some(data_dir.into()),
+            shared_path: shared_path.to_string(),
+            pools: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.data_dir.is_some()
+    }
+
+    /// Get the dedicated pool for an org, if it has one.
+    pub fn get(&self, org_id: &str) -> Option<DbPool> {
+        self.pools.read().unwrap().get(org_id).cloned()
+    }
+
+    /// All dedicated pools as (org_id, pool) pairs (for maintenance jobs).
+    pub fn dedicated_pools(&self) -> Vec<(String, DbPool)> {
+        self.pools
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(id, pool)| (id.clone(), pool.clone()))
+            .collect()
+    }
+
+    fn org_db_path(&self, org_id: &str) -> Option<PathBuf> {
+        self.data_dir
+            .as_ref()
+            .map(|dir| dir.join(format!("org_{}.db", org_id)))
+    }
+
+    fn open_pool(&self, db_path: &str) -> Result<DbPool> {
+        let shared_path = self.shared_path.clone();
+        let manager = SqliteConnectionManager::file(db_path).with_init(move |conn| {
+            conn.execute("ATTACH DATABASE ?1 AS shared", [&shared_path])
+                .map(|_| ())
+        });
+        Ok(Pool::builder().max_size(ORG_POOL_SIZE).build(manager)?)
+    }
+
+    /// Open every registered org database, migrating each to the current schema.
+    /// Returns the number of org databases loaded.
+    pub fn load(&self, shared: &Connection, backup_keep_count: i32) -> Result<usize> {
+        let databases = queries::list_org_databases(shared)?;
+        for (org_id, db_path) in &databases {
+            prepare_org_db(db_path, backup_keep_count)?;
+            let pool = self.open_pool(db_path)?;
+            self.pools.write().unwrap().insert(org_id.clone(), pool);
+        }
+        Ok(databases.len())
+    }
+
+    /// Create and register a dedicated file for a new org.
+    /// Returns None when residency mode is disabled.
+    pub fn provision(&self, shared: &Connection, org_id: &str) -> Result<Option<DbPool>> {
+        let Some(path) = self.org_db_path(org_id) else {
+            return Ok(None);
+        };
+        let db_path = path_str(&path)?;
+        create_parent_dir(&path)?;
+
+        prepare_org_db(&db_path, 0)?;
+        queries::create_org_database(shared, org_id, &db_path)?;
+
+        let pool = self.open_pool(&db_path)?;
+        self.pools
+            .write()
+            .unwrap()
+            .insert(org_id.to_string(), pool.clone());
+        Ok(Some(pool))
+    }
+
+    /// Index a newly created project so public endpoints can route to its org file.
+    /// No-op for orgs on the shared database.
+    pub fn index_project(&self, conn: &Connection, project: &Project) -> Result<()> {
+        if self.get(&project.org_id).is_none() {
+            return Ok(());
+        }
+        queries::create_project_route(conn, &project.id, &project.org_id, &project.public_key)
+    }
+
+    /// Drop an org's pool and delete its file (after the org is hard deleted).
+    /// The `org_databases` row goes away with the org via FK CASCADE.
+    pub fn release(&self, org_id: &str) -> Result<bool> {
+        let Some(_pool) = self.pools.write().unwrap().remove(org_id) else {
+            return Ok(false);
+        };
+        let Some(path) = self.org_db_path(org_id) else {
+            return Ok(false);
+        };
+        let db_path = path_str(&path)?;
+        for file in [
+            db_path.clone(),
+            format!("{}-wal", db_path),
+            format!("{}-shm", db_path),
+        ] {
+            match std::fs::remove_file(&file) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    return Err(AppError::Internal(format!(
+                        "Failed to remove org database {}: {}",
+                        file, e
+                    )));
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Move an existing org's tenant rows from the shared database into a new
+    /// dedicated file. Runs in a single transaction: on failure the shared
+    /// database is untouched (the new file may be left behind and can be deleted).
+    ///
+    /// Intended for the `--isolate-org` CLI command while the server is stopped.
+    /// Project-scoped API keys for the org are removed (see module docs).
+    pub fn isolate_org(&self, shared: &mut Connection, org_id: &str) -> Result<IsolateResult> {
+        let path = self.org_db_path(org_id).ok_or_else(|| {
+            AppError::BadRequest("Data residency is disabled (set PAYCHECK_ORG_DATA_DIR)".into())
+        })?;
+        let db_path = path_str(&path)?;
+
+        if queries::get_organization_by_id(shared, org_id)?.is_none()
+            && queries::get_deleted_organization_by_id(shared, org_id)?.is_none()
+        {
+            return Err(AppError::NotFound(format!(
+                "Organization {} not found",
+                org_id
+            )));
+        }
+        if queries::list_org_databases(shared)?
+            .iter()
+            .any(|(id, _)| id == org_id)
+        {
+            return Err(AppError::Conflict(format!(
+                "Organization {} already has a dedicated database",
+                org_id
+            )));
+        }
+        if path.exists() {
+            return Err(AppError::Conflict(format!(
+                "Database file already exists: {}",
+                db_path
+            )));
+        }
+
+        create_parent_dir(&path)?;
+        prepare_org_db(&db_path, 0)?;
+
+        shared.execute("ATTACH DATABASE ?1 AS org", [&db_path])?;
+        let result = copy_tenant_rows(shared, org_id, &db_path);
+        shared.execute_batch("DETACH DATABASE org")?;
+        let tables = result?;
+
+        Ok(IsolateResult { db_path, tables })
+    }
+}
+
+/// Copy the org's tenant rows into the attached `org` database, register the
+/// file and project routes, then delete the rows from the shared database.
+fn copy_tenant_rows(
+    conn: &mut Connection,
+    org_id: &str,
+    db_path: &str,
+) -> Result<Vec<(&'static str, usize)>> {
+    let tx = conn.transaction()?;
+    let mut tables = Vec::with_capacity(TENANT_TABLES.len());
+
+    for (table, filter) in TENANT_TABLES {
+        // Name columns explicitly: migrated shared tables may order them differently
+        let columns: Vec<String> = {
+            let mut stmt = tx.prepare(&format!("PRAGMA org.table_info({})", table))?;
+            stmt.query_map([], |row| row.get::<_, String>(1))?
+                .collect::<std::result::Result<Vec<_>, _>>()?
+        };
+        let cols = columns.join(", ");
+        let copied = tx.execute(
+            &format!(
+                "INSERT INTO org.{table} ({cols}) SELECT {cols} FROM main.{table} WHERE {filter}"
+            ),
+            [org_id],
+        )?;
+        tables.push((*table, copied));
+    }
+
+    queries::create_org_database(&tx, org_id, db_path)?;
+    tx.execute(
+        "INSERT INTO main.org_project_routes (project_id, org_id, public_key, created_at)
+         SELECT id, org_id, public_key, unixepoch() FROM main.projects WHERE org_id = ?1",
+        [org_id],
+    )?;
+
+    // Delete children first so FK checks pass
+    for (table, filter) in TENANT_TABLES.iter().rev() {
+        tx.execute(
+            &format!("DELETE FROM main.{} WHERE {}", table, filter),
+            [org_id],
+        )?;
+    }
+
+    tx.commit()?;
+    Ok(tables)
+}
+
+/// Bring an org database file to the current schema (migrations, then tables).
+fn prepare_org_db(db_path: &str, backup_keep_count: i32) -> Result<()> {
+    let mut conn = Connection::open(db_path)?;
+    // A fresh file has nothing to back up
+    let keep = if super::migrations::get_version(&conn)? == 0 {
+        0
+    } else {
+        backup_keep_count
+    };
+    run_migrations(&mut conn, db_path, MigrationTarget::Main, keep)
+        .map_err(|e| AppError::Internal(format!("Org database migration failed: {}", e)))?;
+    init_org_db(&conn)?;
+    Ok(())
+}
+
+fn create_parent_dir(path: &Path) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| {
+            AppError::Internal(format!(
+                "Failed to create org data directory {}: {}",
+                dir.display(),
+                e
+            ))
+        })?;
+    }
+    Ok(())
+}
+
+fn path_str(path: &Path) -> Result<String> {
+    path.to_str()
+        .map(String::from)
+        .ok_or_else(|| AppError::Internal(format!("Invalid database path: {}", path.display())))
+}
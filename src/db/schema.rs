@@ -7,18 +7,24 @@ pub fn init_db(conn: &Connection) -> rusqlite::Result<()> {
         -- Users (identity - source of truth for name/email)
         -- Soft delete: deleted_at = timestamp when deleted, NULL = active
         -- deleted_cascade_depth: 0 = directly deleted, >0 = cascaded from parent
-        -- operator_role: NULL = not an operator, otherwise 'owner'/'admin'/'view'
+        -- operator_role: NULL = not an operator, otherwise 'owner'/'admin'/'view'/'partner'
+        -- email: plaintext, or 'enc1:' + master-key envelope with PII_MINIMIZATION
+        -- email_hash: HMAC of the normalized email (lookup key in both modes)
         CREATE TABLE IF NOT EXISTS users (
             id TEXT PRIMARY KEY,
             email TEXT NOT NULL UNIQUE,
+            email_hash TEXT,
             name TEXT NOT NULL,
-            operator_role TEXT CHECK (operator_role IS NULL OR operator_role IN ('owner', 'admin', 'view')),
+            operator_role TEXT CHECK (operator_role IS NULL OR operator_role IN ('owner', 'admin', 'view', 'partner')),
             created_at INTEGER NOT NULL,
             updated_at INTEGER NOT NULL,
             deleted_at INTEGER,
-            deleted_cascade_depth INTEGER
+            deleted_cascade_depth INTEGER,
+            -- Set when the user was merged into another (and soft-deleted)
+            merged_into TEXT REFERENCES users(id) ON DELETE SET NULL
         );
         CREATE INDEX IF NOT EXISTS idx_users_email ON users(email);
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_users_email_hash ON users(email_hash) WHERE email_hash IS NOT NULL;
         CREATE INDEX IF NOT EXISTS idx_users_active ON users(id) WHERE deleted_at IS NULL;
         CREATE INDEX IF NOT EXISTS idx_users_operators ON users(id) WHERE operator_role IS NOT NULL AND deleted_at IS NULL;
 
@@ -62,10 +68,29 @@ pub fn init_db(conn: &Connection) -> rusqlite::Result<()> {
             created_at INTEGER NOT NULL,
             updated_at INTEGER NOT NULL,
             deleted_at INTEGER,
-            deleted_cascade_depth INTEGER
+            deleted_cascade_depth INTEGER,
+            email_from TEXT,
+            -- Random per-org key for org secrets, wrapped by the master key
+            -- (NULL for orgs created before v0.5.0 until --migrate-org-keys)
+            org_data_key BLOB,
+            -- Audit retention in days (NULL = instance default for public
+            -- entries, kept forever for internal ones)
+            public_audit_retention_days INTEGER,
+            admin_audit_retention_days INTEGER,
+            -- Bumped by each update, for optimistic locking
+            version INTEGER NOT NULL DEFAULT 1
         );
         CREATE INDEX IF NOT EXISTS idx_organizations_active ON organizations(id) WHERE deleted_at IS NULL;
 
+        -- Orgs a partner operator may access (other operator roles are global)
+        CREATE TABLE IF NOT EXISTS operator_org_scopes (
+            operator_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            org_id TEXT NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+            created_at INTEGER NOT NULL,
+            PRIMARY KEY (operator_id, org_id)
+        );
+        CREATE INDEX IF NOT EXISTS idx_operator_org_scopes_org ON operator_org_scopes(org_id);
+
         -- Organization service configs (normalized from org columns)
         -- Stores encrypted credentials for external services (payment, email, etc.)
         CREATE TABLE IF NOT EXISTS org_service_configs (
@@ -86,6 +111,88 @@ pub fn init_db(conn: &Connection) -> rusqlite::Result<()> {
         CREATE INDEX IF NOT EXISTS idx_org_service_configs_lookup ON org_service_configs(org_id, provider);
         CREATE INDEX IF NOT EXISTS idx_org_service_configs_category ON org_service_configs(org_id, category);
 
+        -- Operator-set quotas per org (no row = unlimited)
+        CREATE TABLE IF NOT EXISTS org_limits (
+            org_id TEXT NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+            limit_name TEXT NOT NULL CHECK (limit_name IN ('projects', 'products_per_project', 'licenses_per_month')),
+            soft_value INTEGER,
+            hard_value INTEGER,
+            created_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL,
+            PRIMARY KEY (org_id, limit_name)
+        );
+
+        -- Org-level product definitions new projects are seeded from
+        -- features, checkout_fields: JSON arrays, as on products
+        -- Products are copied out, so edits and deletes never reach them
+        CREATE TABLE IF NOT EXISTS product_templates (
+            id TEXT PRIMARY KEY,
+            org_id TEXT NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+            name TEXT NOT NULL,
+            tier TEXT NOT NULL,
+            license_exp_days INTEGER,
+            updates_exp_days INTEGER,
+            activation_limit INTEGER,
+            device_limit INTEGER,
+            device_inactive_days INTEGER,
+            features TEXT NOT NULL DEFAULT '[]',
+            price_cents INTEGER,
+            currency TEXT,
+            seat_count INTEGER,
+            checkout_fields TEXT NOT NULL DEFAULT '[]',
+            created_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_product_templates_org ON product_templates(org_id);
+
+        -- Subscription reconciliation runs (POST /operators/reconcile)
+        -- report: JSON array of discrepancies
+        -- started_by: operator user ID (NULL for scheduled runs)
+        CREATE TABLE IF NOT EXISTS reconciliation_runs (
+            id TEXT PRIMARY KEY,
+            org_id TEXT NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+            provider TEXT NOT NULL CHECK (provider IN ('stripe', 'lemonsqueezy')),
+            applied INTEGER NOT NULL DEFAULT 0,
+            started_by TEXT,
+            started_at INTEGER NOT NULL,
+            finished_at INTEGER NOT NULL,
+            checked INTEGER NOT NULL,
+            report TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_reconciliation_runs_org ON reconciliation_runs(org_id, started_at);
+
+        -- Encrypted snapshots of the main and audit databases (POST /operators/backups)
+        -- sha256: hex digest of the encrypted file
+        -- created_by: operator user ID
+        -- verified_ok: result of the last verification (NULL = never verified)
+        CREATE TABLE IF NOT EXISTS backups (
+            id TEXT PRIMARY KEY,
+            file_name TEXT NOT NULL,
+            size_bytes INTEGER NOT NULL,
+            duration_ms INTEGER NOT NULL,
+            sha256 TEXT NOT NULL,
+            encrypted_with TEXT NOT NULL CHECK (encrypted_with IN ('master_key', 'backup_key')),
+            created_by TEXT,
+            created_at INTEGER NOT NULL,
+            verified_at INTEGER,
+            verified_ok INTEGER
+        );
+        CREATE INDEX IF NOT EXISTS idx_backups_created ON backups(created_at);
+
+        -- Storage heartbeats (see storage_health), one row per database. The
+        -- audit database keeps its own row; this copy of it is how a replaced
+        -- audit file is noticed.
+        -- db: 'main' or 'audit'
+        -- path: database file when the server last started
+        -- beat: incremented every heartbeat, never reset
+        CREATE TABLE IF NOT EXISTS storage_heartbeats (
+            db TEXT PRIMARY KEY CHECK (db IN ('main', 'audit')),
+            path TEXT NOT NULL,
+            beat INTEGER NOT NULL,
+            started_at INTEGER NOT NULL,
+            beat_at INTEGER NOT NULL
+        );
+
         -- Organization members (references users for identity)
         CREATE TABLE IF NOT EXISTS org_members (
             id TEXT PRIMARY KEY,
@@ -96,11 +203,13 @@ pub fn init_db(conn: &Connection) -> rusqlite::Result<()> {
             updated_at INTEGER NOT NULL,
             deleted_at INTEGER,
             deleted_cascade_depth INTEGER,
+            removal_scheduled_at INTEGER,
             UNIQUE(user_id, org_id)
         );
         CREATE INDEX IF NOT EXISTS idx_org_members_org ON org_members(org_id);
         CREATE INDEX IF NOT EXISTS idx_org_members_user ON org_members(user_id);
         CREATE INDEX IF NOT EXISTS idx_org_members_active ON org_members(id) WHERE deleted_at IS NULL;
+        CREATE INDEX IF NOT EXISTS idx_org_members_removal ON org_members(removal_scheduled_at) WHERE removal_scheduled_at IS NOT NULL;
 
         -- Projects (software products being licensed)
         CREATE TABLE IF NOT EXISTS projects (
@@ -117,7 +226,60 @@ pub fn init_db(conn: &Connection) -> rusqlite::Result<()> {
             created_at INTEGER NOT NULL,
             updated_at INTEGER NOT NULL,
             deleted_at INTEGER,
-            deleted_cascade_depth INTEGER
+            deleted_cascade_depth INTEGER,
+            -- JWT iss/aud overrides (NULL = "paycheck" / project name)
+            jwt_issuer TEXT,
+            jwt_audience TEXT,
+            -- Values replaced by the last override change, still accepted until jwt_previous_until
+            jwt_previous_issuer TEXT,
+            jwt_previous_audience TEXT,
+            jwt_previous_until INTEGER,
+            -- Upgrade purchases: auto-apply a Stripe coupon for the old license's remaining value,
+            -- and what happens to the old license ('revoke' or 'updates_only')
+            upgrade_auto_discount INTEGER NOT NULL DEFAULT 0,
+            upgrade_old_license TEXT NOT NULL DEFAULT 'revoke',
+            -- Deprecated: let /buy find the project from product_id alone (no public key)
+            allow_project_id_auth INTEGER NOT NULL DEFAULT 1,
+            allow_link_checkout INTEGER NOT NULL DEFAULT 0,
+            -- Per-license /validate calls allowed per hour (NULL = unlimited)
+            max_validations_per_hour_per_license INTEGER,
+            -- Checkout presentation: card statement suffix (Stripe), receipts to the /buy email,
+            -- and text on the checkout page
+            statement_descriptor_suffix TEXT,
+            receipt_email_enabled INTEGER NOT NULL DEFAULT 0,
+            checkout_message TEXT,
+            -- JSON array of audiences /validate/attest signs attestations for
+            attestation_audiences TEXT NOT NULL DEFAULT '[]',
+            -- /buy refuses a second license for a buyer who already has one, and reuses
+            -- their pending checkout
+            duplicate_purchase_check INTEGER NOT NULL DEFAULT 1,
+            -- What a purchase does to the buyer's trial license: keep, expire, or revoke
+            converted_trial_action TEXT NOT NULL DEFAULT 'keep',
+            -- Operator-set URL that gets a copy of every webhook in and out, until it expires
+            webhook_mirror_url TEXT,
+            webhook_mirror_expires_at INTEGER,
+            -- /buy requires accepted_terms_version to be the current terms version
+            require_terms_acceptance INTEGER NOT NULL DEFAULT 0,
+            -- JSON object returned verbatim by /validate and /refresh
+            client_flags TEXT NOT NULL DEFAULT '{}',
+            -- Apps reporting an older version are told update_required
+            min_client_version TEXT,
+            -- HMAC key for license refs (db::license_refs), set with the first license
+            license_ref_key TEXT,
+            -- JSON array of the keys /buy accepts in metadata
+            checkout_metadata_keys TEXT NOT NULL DEFAULT '[]',
+            -- Count valid /validate calls by client country in usage_geo_daily
+            usage_geo_enabled INTEGER NOT NULL DEFAULT 0,
+            -- Mark licenses never activated this many days after purchase as dormant
+            -- (NULL = never), and send their owners a fresh activation code when they are
+            dormant_after_days INTEGER,
+            dormant_nudge_email INTEGER NOT NULL DEFAULT 0,
+            -- Bumped by each update, for optimistic locking
+            version INTEGER NOT NULL DEFAULT 1,
+            -- Payload schema version of outbound webhooks (NULL = latest)
+            webhook_schema_version TEXT,
+            -- Signs email webhook payloads, encrypted with the org's data key (NULL = unsigned)
+            email_webhook_secret BLOB
         );
         CREATE INDEX IF NOT EXISTS idx_projects_org ON projects(org_id);
         CREATE UNIQUE INDEX IF NOT EXISTS idx_projects_public_key ON projects(public_key);
@@ -139,6 +301,30 @@ pub fn init_db(conn: &Connection) -> rusqlite::Result<()> {
         CREATE INDEX IF NOT EXISTS idx_project_members_active ON project_members(id) WHERE deleted_at IS NULL;
         CREATE UNIQUE INDEX IF NOT EXISTS idx_project_members_unique_active ON project_members(org_member_id, project_id) WHERE deleted_at IS NULL;
 
+        -- Per-project counters, e.g. 'license' for license reference numbers
+        CREATE TABLE IF NOT EXISTS project_counters (
+            project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+            name TEXT NOT NULL,
+            value INTEGER NOT NULL,
+            PRIMARY KEY (project_id, name)
+        );
+
+        -- Temporary project roles (break-glass access, expiry checked at auth time)
+        CREATE TABLE IF NOT EXISTS temporary_role_grants (
+            id TEXT PRIMARY KEY,
+            org_member_id TEXT NOT NULL REFERENCES org_members(id) ON DELETE CASCADE,
+            project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+            role TEXT NOT NULL CHECK (role IN ('admin', 'view')),
+            reason TEXT,
+            granted_by TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            expires_at INTEGER NOT NULL,
+            revoked_at INTEGER,
+            revoked_by TEXT
+        );
+        CREATE INDEX IF NOT EXISTS idx_temporary_role_grants_member ON temporary_role_grants(org_member_id, project_id, expires_at);
+        CREATE INDEX IF NOT EXISTS idx_temporary_role_grants_project ON temporary_role_grants(project_id, created_at);
+
         -- Products (tiers/plans within a project)
         CREATE TABLE IF NOT EXISTS products (
             id TEXT PRIMARY KEY,
@@ -156,6 +342,26 @@ pub fn init_db(conn: &Connection) -> rusqlite::Result<()> {
             created_at INTEGER NOT NULL,
             deleted_at INTEGER,
             deleted_cascade_depth INTEGER,
+            seat_count INTEGER,
+            available_from INTEGER,
+            available_until INTEGER,
+            -- Custom fields collected at checkout: JSON list of {key, label, type, required}
+            checkout_fields TEXT NOT NULL DEFAULT '[]',
+            -- Updates-only renewals: the base product whose licenses a purchase extends,
+            -- and what to do when the buyer has none
+            extends_updates_for_product_id TEXT,
+            renewal_fallback TEXT NOT NULL DEFAULT 'create_license',
+            -- Upsell shown to this product's customers: the product to upgrade to,
+            -- which of its features to highlight (JSON list), and a short pitch
+            upgrade_to_product_id TEXT,
+            upsell_highlight_features TEXT NOT NULL DEFAULT '[]',
+            upsell_blurb TEXT,
+            -- Structured entitlements: JSON object of name -> integer, bool, or string
+            entitlements TEXT NOT NULL DEFAULT '{}',
+            -- Bumped by each update, for optimistic locking
+            version INTEGER NOT NULL DEFAULT 1,
+            -- Revoke licenses on partial refunds too (full refunds always revoke)
+            revoke_on_partial_refund INTEGER NOT NULL DEFAULT 0,
             UNIQUE(project_id, name)
         );
         CREATE INDEX IF NOT EXISTS idx_products_project ON products(project_id);
@@ -176,6 +382,11 @@ pub fn init_db(conn: &Connection) -> rusqlite::Result<()> {
         -- Licenses (no user-facing keys - email hash is the identity)
         -- email_hash: SHA-256 hash of purchase email (no PII stored)
         -- project_id: denormalized for efficient lookups
+        -- paused_at: set while the provider subscription is paused
+        -- paused_seconds: total time spent paused (added back to expires_at on resume)
+        -- seats: number of assignable seats (NULL = not a team license)
+        -- abuse_*: validation throttling history (see max_validations_per_hour_per_license)
+        -- revoked_*: why/when/by whom the license was revoked; revoked_message is shown to the customer
         CREATE TABLE IF NOT EXISTS licenses (
             id TEXT PRIMARY KEY,
             email_hash TEXT,
@@ -192,10 +403,50 @@ pub fn init_db(conn: &Connection) -> rusqlite::Result<()> {
             payment_provider_subscription_id TEXT,
             payment_provider_order_id TEXT,
             deleted_at INTEGER,
-            deleted_cascade_depth INTEGER
+            deleted_cascade_depth INTEGER,
+            paused_at INTEGER,
+            paused_seconds INTEGER NOT NULL DEFAULT 0,
+            seats INTEGER,
+            abuse_flags INTEGER NOT NULL DEFAULT 0,
+            abuse_flagged_at INTEGER,
+            abuse_distinct_ips INTEGER,
+            revoked_reason TEXT,
+            revoked_message TEXT,
+            revoked_at INTEGER,
+            revoked_by TEXT,
+            -- Values of the product's checkout fields, JSON object keyed by field key
+            checkout_fields TEXT,
+            -- Key issued by a payment provider before the license was imported (SHA-256),
+            -- and which provider issued it
+            external_key_hash TEXT,
+            external_key_provider TEXT,
+            -- Set while a dispute (chargeback) over the purchase is open
+            suspended_for_dispute INTEGER NOT NULL DEFAULT 0,
+            -- Trial license (created with is_trial), and for a paid license the trial
+            -- it converted from
+            is_trial INTEGER NOT NULL DEFAULT 0,
+            converted_from_license_id TEXT,
+            -- Terms version the buyer accepted at /buy (terms.version)
+            accepted_terms_version TEXT,
+            -- Public handle shown in tokens instead of the id (db::license_refs)
+            license_ref TEXT,
+            -- Metadata sent to /buy (e.g. utm_* parameters), JSON object
+            metadata TEXT,
+            -- When the license was marked dormant (never activated within the project's
+            -- dormant_after_days); cleared by the first activation
+            dormant_since INTEGER,
+            -- Sequential number for support, e.g. PRO-000123 (db::reference_numbers)
+            reference_number TEXT,
+            -- App versions reported to /validate and /refresh (X-Paycheck-Client-Version);
+            -- last_seen_* is written at most hourly
+            first_seen_version TEXT,
+            last_seen_version TEXT,
+            last_seen_version_at INTEGER
         );
         CREATE INDEX IF NOT EXISTS idx_licenses_product ON licenses(product_id);
         CREATE INDEX IF NOT EXISTS idx_licenses_project ON licenses(project_id);
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_licenses_project_ref ON licenses(project_id, license_ref);
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_licenses_project_reference ON licenses(project_id, reference_number);
         CREATE INDEX IF NOT EXISTS idx_licenses_project_email ON licenses(project_id, email_hash);
         CREATE INDEX IF NOT EXISTS idx_licenses_project_order ON licenses(project_id, payment_provider_order_id);
         CREATE INDEX IF NOT EXISTS idx_licenses_project_customer ON licenses(project_id, customer_id);
@@ -203,6 +454,222 @@ pub fn init_db(conn: &Connection) -> rusqlite::Result<()> {
         CREATE INDEX IF NOT EXISTS idx_licenses_provider_subscription ON licenses(payment_provider, payment_provider_subscription_id);
         CREATE INDEX IF NOT EXISTS idx_licenses_provider_order ON licenses(payment_provider, payment_provider_order_id);
         CREATE INDEX IF NOT EXISTS idx_licenses_active ON licenses(id) WHERE deleted_at IS NULL;
+        CREATE INDEX IF NOT EXISTS idx_licenses_converted_from ON licenses(converted_from_license_id) WHERE converted_from_license_id IS NOT NULL;
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_licenses_free_claim ON licenses(product_id, email_hash) WHERE payment_provider = 'free';
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_licenses_external_key ON licenses(project_id, external_key_provider, external_key_hash) WHERE external_key_hash IS NOT NULL;
+        CREATE INDEX IF NOT EXISTS idx_licenses_dormant ON licenses(project_id, dormant_since) WHERE dormant_since IS NOT NULL;
+
+        -- License seats (team licenses: each seat holder activates with their own email)
+        -- removed_at: set when the seat is unassigned (row kept for history)
+        CREATE TABLE IF NOT EXISTS license_seats (
+            id TEXT PRIMARY KEY,
+            license_id TEXT NOT NULL REFERENCES licenses(id) ON DELETE CASCADE,
+            seat_email_hash TEXT NOT NULL,
+            assigned_at INTEGER NOT NULL,
+            removed_at INTEGER
+        );
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_license_seats_active ON license_seats(license_id, seat_email_hash) WHERE removed_at IS NULL;
+        CREATE INDEX IF NOT EXISTS idx_license_seats_email ON license_seats(seat_email_hash);
+
+        -- Share links (customer-facing license detail pages, addressed by an unguessable token)
+        -- token_hash: hash of the token in the URL (the token itself is never stored)
+        -- masked_email: display-only email (e.g. j***@example.com), NULL if none was given
+        CREATE TABLE IF NOT EXISTS share_links (
+            id TEXT PRIMARY KEY,
+            license_id TEXT NOT NULL REFERENCES licenses(id) ON DELETE CASCADE,
+            token_hash TEXT NOT NULL UNIQUE,
+            masked_email TEXT,
+            created_by TEXT,
+            expires_at INTEGER NOT NULL,
+            revoked INTEGER NOT NULL DEFAULT 0,
+            created_at INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_share_links_license ON share_links(license_id);
+
+        -- Prepaid code batches (e.g. codes printed on retail cards, each redeemable once for a license)
+        -- pending_codes: the batch's codes as CSV, encrypted under the org's data key, until
+        -- the one download (NULL afterwards, or once the batch is revoked)
+        -- revoked_at: when the batch was revoked (unredeemed codes stop working)
+        CREATE TABLE IF NOT EXISTS prepaid_code_batches (
+            id TEXT PRIMARY KEY,
+            project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+            product_id TEXT NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+            label TEXT,
+            code_count INTEGER NOT NULL,
+            created_by TEXT,
+            expires_at INTEGER,
+            pending_codes BLOB,
+            downloaded_at INTEGER,
+            revoked_at INTEGER,
+            created_at INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_prepaid_code_batches_product ON prepaid_code_batches(product_id);
+
+        -- Prepaid codes (only the hash is stored)
+        -- license_id: the license created when the code was redeemed
+        CREATE TABLE IF NOT EXISTS prepaid_codes (
+            code_hash TEXT PRIMARY KEY,
+            batch_id TEXT NOT NULL REFERENCES prepaid_code_batches(id) ON DELETE CASCADE,
+            license_id TEXT REFERENCES licenses(id) ON DELETE SET NULL,
+            redeemed_at INTEGER
+        );
+        CREATE INDEX IF NOT EXISTS idx_prepaid_codes_batch ON prepaid_codes(batch_id);
+
+        -- Bulk license jobs (large license creation requests, run in chunks in the background)
+        -- params: the license settings as JSON (email hash, customer_id, expirations, seats, trial)
+        -- error: why the job stopped early, other than a failed chunk
+        CREATE TABLE IF NOT EXISTS bulk_jobs (
+            id TEXT PRIMARY KEY,
+            project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+            product_id TEXT NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+            status TEXT NOT NULL DEFAULT 'running' CHECK (status IN ('running', 'completed', 'failed', 'cancelled')),
+            requested INTEGER NOT NULL,
+            created_count INTEGER NOT NULL DEFAULT 0,
+            activation_codes INTEGER NOT NULL DEFAULT 0,
+            params TEXT NOT NULL,
+            created_by TEXT,
+            error TEXT,
+            created_at INTEGER NOT NULL,
+            finished_at INTEGER
+        );
+        CREATE INDEX IF NOT EXISTS idx_bulk_jobs_project ON bulk_jobs(project_id);
+        CREATE INDEX IF NOT EXISTS idx_bulk_jobs_running ON bulk_jobs(status) WHERE status = 'running';
+
+        -- Chunks a bulk job has attempted: the license IDs created, or the error that rolled it back
+        CREATE TABLE IF NOT EXISTS bulk_job_chunks (
+            job_id TEXT NOT NULL REFERENCES bulk_jobs(id) ON DELETE CASCADE,
+            chunk INTEGER NOT NULL,
+            license_ids TEXT NOT NULL DEFAULT '[]',
+            error TEXT,
+            created_at INTEGER NOT NULL,
+            PRIMARY KEY (job_id, chunk)
+        );
+
+        -- Versions of a project's terms of sale; the current one is the latest in effect
+        -- Immutable once a license accepted it (licenses.accepted_terms_version)
+        CREATE TABLE IF NOT EXISTS terms (
+            id TEXT PRIMARY KEY,
+            project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+            version TEXT NOT NULL,
+            url TEXT NOT NULL,
+            effective_at INTEGER NOT NULL,
+            created_by TEXT,
+            created_at INTEGER NOT NULL
+        );
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_terms_project_version ON terms(project_id, version);
+        CREATE INDEX IF NOT EXISTS idx_terms_project_effective ON terms(project_id, effective_at);
+
+        -- Release artifacts gated by /download/{artifact_id}: a license may fetch one
+        -- released while its updates window was open
+        -- url: the vendor's external URL (or storage key), signed on each download
+        CREATE TABLE IF NOT EXISTS artifacts (
+            id TEXT PRIMARY KEY,
+            project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+            name TEXT NOT NULL,
+            released_at INTEGER NOT NULL,
+            url TEXT NOT NULL,
+            created_by TEXT,
+            created_at INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_artifacts_project ON artifacts(project_id, released_at);
+
+        -- Downloads per artifact and license
+        CREATE TABLE IF NOT EXISTS artifact_downloads (
+            artifact_id TEXT NOT NULL REFERENCES artifacts(id) ON DELETE CASCADE,
+            license_id TEXT NOT NULL REFERENCES licenses(id) ON DELETE CASCADE,
+            count INTEGER NOT NULL DEFAULT 0,
+            first_downloaded_at INTEGER NOT NULL,
+            last_downloaded_at INTEGER NOT NULL,
+            PRIMARY KEY (artifact_id, license_id)
+        );
+        CREATE INDEX IF NOT EXISTS idx_artifact_downloads_license ON artifact_downloads(license_id);
+
+        -- License upgrades (an old license replaced by one bought through an upgrade checkout)
+        -- old_license_action: what was done to the old license ('revoke' or 'updates_only')
+        CREATE TABLE IF NOT EXISTS license_upgrades (
+            id TEXT PRIMARY KEY,
+            from_license_id TEXT NOT NULL REFERENCES licenses(id) ON DELETE CASCADE,
+            to_license_id TEXT NOT NULL REFERENCES licenses(id) ON DELETE CASCADE,
+            payment_session_id TEXT,
+            days_remaining INTEGER,
+            credit_cents INTEGER,
+            old_license_action TEXT NOT NULL,
+            created_at INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_license_upgrades_from ON license_upgrades(from_license_id);
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_license_upgrades_to ON license_upgrades(to_license_id);
+
+        -- Updates-only renewal purchases applied to a license
+        CREATE TABLE IF NOT EXISTS license_update_renewals (
+            id TEXT PRIMARY KEY,
+            license_id TEXT NOT NULL REFERENCES licenses(id) ON DELETE CASCADE,
+            product_id TEXT NOT NULL,
+            payment_session_id TEXT,
+            previous_updates_expires_at INTEGER,
+            updates_expires_at INTEGER,
+            created_at INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_license_update_renewals_license ON license_update_renewals(license_id);
+
+        -- License tags (free-form labels for grouping licenses, e.g. 'beta-cohort')
+        CREATE TABLE IF NOT EXISTS license_tags (
+            license_id TEXT NOT NULL REFERENCES licenses(id) ON DELETE CASCADE,
+            tag TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            PRIMARY KEY (license_id, tag)
+        );
+        CREATE INDEX IF NOT EXISTS idx_license_tags_tag ON license_tags(tag);
+
+        -- Payment disputes (chargebacks) against the purchase behind a license
+        -- payment_id: matches payment_sessions.provider_payment_id of the checkout
+        -- status: 'open' (license suspended), 'won' (reinstated), or 'lost' (revoked)
+        CREATE TABLE IF NOT EXISTS disputes (
+            id TEXT PRIMARY KEY,
+            license_id TEXT NOT NULL REFERENCES licenses(id) ON DELETE CASCADE,
+            project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+            provider TEXT NOT NULL,
+            provider_dispute_id TEXT NOT NULL,
+            payment_id TEXT NOT NULL,
+            amount_cents INTEGER,
+            currency TEXT,
+            reason TEXT,
+            status TEXT NOT NULL CHECK (status IN ('open', 'won', 'lost')),
+            created_at INTEGER NOT NULL,
+            closed_at INTEGER
+        );
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_disputes_provider ON disputes(provider, provider_dispute_id);
+        CREATE INDEX IF NOT EXISTS idx_disputes_license ON disputes(license_id, created_at);
+        CREATE INDEX IF NOT EXISTS idx_disputes_project ON disputes(project_id, status, created_at);
+
+        -- Activation code email attempts, one row per license in the email
+        -- to_email_hash: recipient hashed like licenses.email_hash (no plaintext address stored)
+        -- result: 'sent', 'webhook_called', 'disabled', 'no_api_key' or 'failed'
+        -- error_status: HTTP status from Resend for failed sends (NULL if no response)
+        CREATE TABLE IF NOT EXISTS email_log (
+            id TEXT PRIMARY KEY,
+            license_id TEXT NOT NULL REFERENCES licenses(id) ON DELETE CASCADE,
+            project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+            to_email_hash TEXT NOT NULL,
+            email_trigger TEXT NOT NULL,
+            result TEXT NOT NULL CHECK (result IN ('sent', 'webhook_called', 'disabled', 'no_api_key', 'failed')),
+            error_status INTEGER,
+            provider_message_id TEXT,
+            created_at INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_email_log_license ON email_log(license_id, created_at);
+        CREATE INDEX IF NOT EXISTS idx_email_log_project ON email_log(project_id, result, created_at);
+
+        -- Valid /validate calls per UTC day, product and client country, for projects
+        -- with usage_geo_enabled. Only the country code is kept, never the address.
+        -- date: YYYY-MM-DD; country: ISO 3166-1 alpha-2 code
+        CREATE TABLE IF NOT EXISTS usage_geo_daily (
+            date TEXT NOT NULL,
+            project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+            product_id TEXT NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+            country TEXT NOT NULL,
+            count INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (project_id, date, product_id, country)
+        );
 
         -- Activation codes (short-lived codes in PREFIX-XXXX-XXXX format, 40 bits entropy)
         CREATE TABLE IF NOT EXISTS activation_codes (
@@ -210,7 +677,8 @@ pub fn init_db(conn: &Connection) -> rusqlite::Result<()> {
             license_id TEXT NOT NULL REFERENCES licenses(id) ON DELETE CASCADE,
             expires_at INTEGER NOT NULL,
             used INTEGER NOT NULL DEFAULT 0,
-            created_at INTEGER NOT NULL
+            created_at INTEGER NOT NULL,
+            seat_id TEXT REFERENCES license_seats(id) ON DELETE CASCADE
         );
         CREATE INDEX IF NOT EXISTS idx_activation_codes_license ON activation_codes(license_id);
         CREATE INDEX IF NOT EXISTS idx_activation_codes_expires ON activation_codes(expires_at);
@@ -235,11 +703,14 @@ pub fn init_db(conn: &Connection) -> rusqlite::Result<()> {
             jti TEXT NOT NULL,
             activated_at INTEGER NOT NULL,
             last_seen_at INTEGER NOT NULL,
+            seat_id TEXT REFERENCES license_seats(id) ON DELETE CASCADE,
+            signed_with_kid TEXT,
             UNIQUE(license_id, device_id)
         );
         -- Note: UNIQUE(license_id, device_id) creates implicit index for device lookups
         CREATE INDEX IF NOT EXISTS idx_devices_license_time ON devices(license_id, activated_at DESC);
         CREATE INDEX IF NOT EXISTS idx_devices_jti ON devices(jti);
+        CREATE INDEX IF NOT EXISTS idx_devices_seat ON devices(seat_id) WHERE seat_id IS NOT NULL;
 
         -- Payment sessions (temporary, for tracking buy flow)
         -- Device info removed: purchase ≠ activation. Device created at /redeem time.
@@ -250,9 +721,33 @@ pub fn init_db(conn: &Connection) -> rusqlite::Result<()> {
             customer_id TEXT,
             created_at INTEGER NOT NULL,
             completed INTEGER NOT NULL DEFAULT 0,
-            license_id TEXT REFERENCES licenses(id) ON DELETE SET NULL
+            license_id TEXT REFERENCES licenses(id) ON DELETE SET NULL,
+            -- Upgrade checkouts: the license being replaced and its remaining value
+            upgrade_from_license_id TEXT REFERENCES licenses(id) ON DELETE SET NULL,
+            upgrade_days_remaining INTEGER,
+            upgrade_credit_cents INTEGER,
+            -- Provider payment the checkout settled with (Stripe PaymentIntent, LemonSqueezy order),
+            -- for matching refunds and chargebacks
+            provider_payment_id TEXT,
+            -- Checkout field values submitted to /buy, copied to the license on completion
+            checkout_fields TEXT,
+            -- Provider checkout page, and the buyer's email hash, for reusing a pending checkout
+            checkout_url TEXT,
+            buyer_email_hash TEXT,
+            -- Set when a completed checkout issued nothing and needs someone to look at it
+            needs_review INTEGER NOT NULL DEFAULT 0,
+            -- One-time claim code the buyer's app exchanges for the license (see /buy/wait),
+            -- stored hashed; claimed_at is set once it's been exchanged
+            claim_code_hash TEXT,
+            claim_expires_at INTEGER,
+            claimed_at INTEGER,
+            -- Terms version the buyer accepted at /buy, copied to the license
+            accepted_terms_version TEXT,
+            -- Metadata sent to /buy, passed to the provider and copied to the license
+            metadata TEXT
         );
         CREATE INDEX IF NOT EXISTS idx_payment_sessions_product ON payment_sessions(product_id);
+        CREATE INDEX IF NOT EXISTS idx_payment_sessions_provider_payment ON payment_sessions(provider_payment_id);
 
         -- Webhook events (for replay attack prevention)
         CREATE TABLE IF NOT EXISTS webhook_events (
@@ -262,6 +757,16 @@ pub fn init_db(conn: &Connection) -> rusqlite::Result<()> {
             PRIMARY KEY (provider, event_id)
         );
 
+        -- Audit entries written in the same transaction as the change they record,
+        -- waiting to be copied to the audit database (see db::outbox)
+        CREATE TABLE IF NOT EXISTS audit_outbox (
+            id TEXT PRIMARY KEY, -- audit log entry ID
+            entry TEXT NOT NULL, -- AuditLog JSON
+            created_at INTEGER NOT NULL,
+            relayed_at INTEGER
+        );
+        CREATE INDEX IF NOT EXISTS idx_audit_outbox_pending ON audit_outbox(created_at) WHERE relayed_at IS NULL;
+
         -- System configuration (stable secrets that survive master key rotation)
         -- Used for email HMAC key which must remain stable so email hashes stay valid
         CREATE TABLE IF NOT EXISTS system_config (
@@ -270,6 +775,595 @@ pub fn init_db(conn: &Connection) -> rusqlite::Result<()> {
             created_at INTEGER NOT NULL,
             updated_at INTEGER NOT NULL
         );
+
+        -- Dedicated org databases (data residency mode)
+        -- Orgs without a row keep their tenant data in this shared database
+        CREATE TABLE IF NOT EXISTS org_databases (
+            org_id TEXT PRIMARY KEY REFERENCES organizations(id) ON DELETE CASCADE,
+            db_path TEXT NOT NULL,
+            created_at INTEGER NOT NULL
+        );
+
+        -- Project index for dedicated orgs (public endpoints only know the project/public key)
+        CREATE TABLE IF NOT EXISTS org_project_routes (
+            project_id TEXT PRIMARY KEY,
+            org_id TEXT NOT NULL REFERENCES org_databases(org_id) ON DELETE CASCADE,
+            public_key TEXT NOT NULL UNIQUE,
+            created_at INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_org_project_routes_org ON org_project_routes(org_id);
+
+        -- Vendor hostnames serving a project's public endpoints (see custom_domains)
+        -- Kept here rather than with the project so the Host header resolves
+        -- before anything else about the request is known.
+        -- verification_token: published in a _paycheck-challenge TXT record
+        -- verified_at: NULL until the TXT record is found
+        CREATE TABLE IF NOT EXISTS custom_domains (
+            hostname TEXT PRIMARY KEY,
+            org_id TEXT NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+            project_id TEXT NOT NULL,
+            verification_token TEXT NOT NULL,
+            verified_at INTEGER,
+            created_by TEXT,
+            created_at INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_custom_domains_project ON custom_domains(project_id);
+        "#,
+    )?;
+    Ok(())
+}
+
+/// Initialize a dedicated org database (data residency mode).
+///
+/// Holds only the org's tenant tables. Identity and org-level tables (users,
+/// org members, organizations, service configs) stay in the shared database,
+/// which is attached to every org connection, so foreign keys into those
+/// tables are omitted here. Keep column definitions in sync with `init_db`.
+pub fn init_org_db(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        r#"
+        -- Projects (software products being licensed)
+        CREATE TABLE IF NOT EXISTS projects (
+            id TEXT PRIMARY KEY,
+            org_id TEXT NOT NULL,
+            name TEXT NOT NULL,
+            license_key_prefix TEXT NOT NULL DEFAULT 'PC',
+            private_key BLOB NOT NULL,
+            public_key TEXT NOT NULL,
+            redirect_url TEXT,
+            email_from TEXT,
+            email_enabled INTEGER NOT NULL DEFAULT 1,
+            email_webhook_url TEXT,
+            created_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL,
+            deleted_at INTEGER,
+            deleted_cascade_depth INTEGER,
+            -- JWT iss/aud overrides (NULL = "paycheck" / project name)
+            jwt_issuer TEXT,
+            jwt_audience TEXT,
+            -- Values replaced by the last override change, still accepted until jwt_previous_until
+            jwt_previous_issuer TEXT,
+            jwt_previous_audience TEXT,
+            jwt_previous_until INTEGER,
+            -- Upgrade purchases: auto-apply a Stripe coupon for the old license's remaining value,
+            -- and what happens to the old license ('revoke' or 'updates_only')
+            upgrade_auto_discount INTEGER NOT NULL DEFAULT 0,
+            upgrade_old_license TEXT NOT NULL DEFAULT 'revoke',
+            -- Deprecated: let /buy find the project from product_id alone (no public key)
+            allow_project_id_auth INTEGER NOT NULL DEFAULT 1,
+            allow_link_checkout INTEGER NOT NULL DEFAULT 0,
+            -- Per-license /validate calls allowed per hour (NULL = unlimited)
+            max_validations_per_hour_per_license INTEGER,
+            -- Checkout presentation: card statement suffix (Stripe), receipts to the /buy email,
+            -- and text on the checkout page
+            statement_descriptor_suffix TEXT,
+            receipt_email_enabled INTEGER NOT NULL DEFAULT 0,
+            checkout_message TEXT,
+            -- JSON array of audiences /validate/attest signs attestations for
+            attestation_audiences TEXT NOT NULL DEFAULT '[]',
+            -- /buy refuses a second license for a buyer who already has one, and reuses
+            -- their pending checkout
+            duplicate_purchase_check INTEGER NOT NULL DEFAULT 1,
+            -- What a purchase does to the buyer's trial license: keep, expire, or revoke
+            converted_trial_action TEXT NOT NULL DEFAULT 'keep',
+            -- Operator-set URL that gets a copy of every webhook in and out, until it expires
+            webhook_mirror_url TEXT,
+            webhook_mirror_expires_at INTEGER,
+            -- /buy requires accepted_terms_version to be the current terms version
+            require_terms_acceptance INTEGER NOT NULL DEFAULT 0,
+            -- JSON object returned verbatim by /validate and /refresh
+            client_flags TEXT NOT NULL DEFAULT '{}',
+            -- Apps reporting an older version are told update_required
+            min_client_version TEXT,
+            -- HMAC key for license refs (db::license_refs), set with the first license
+            license_ref_key TEXT,
+            -- JSON array of the keys /buy accepts in metadata
+            checkout_metadata_keys TEXT NOT NULL DEFAULT '[]',
+            -- Count valid /validate calls by client country in usage_geo_daily
+            usage_geo_enabled INTEGER NOT NULL DEFAULT 0,
+            -- Mark licenses never activated this many days after purchase as dormant
+            -- (NULL = never), and send their owners a fresh activation code when they are
+            dormant_after_days INTEGER,
+            dormant_nudge_email INTEGER NOT NULL DEFAULT 0,
+            -- Bumped by each update, for optimistic locking
+            version INTEGER NOT NULL DEFAULT 1,
+            -- Payload schema version of outbound webhooks (NULL = latest)
+            webhook_schema_version TEXT,
+            -- Signs email webhook payloads, encrypted with the org's data key (NULL = unsigned)
+            email_webhook_secret BLOB
+        );
+        CREATE INDEX IF NOT EXISTS idx_projects_org ON projects(org_id);
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_projects_public_key ON projects(public_key);
+        CREATE INDEX IF NOT EXISTS idx_projects_active ON projects(id) WHERE deleted_at IS NULL;
+
+        -- Project members (for 'member' role org members who need explicit access)
+        CREATE TABLE IF NOT EXISTS project_members (
+            id TEXT PRIMARY KEY,
+            org_member_id TEXT NOT NULL,
+            project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+            role TEXT NOT NULL CHECK (role IN ('admin', 'view')),
+            created_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL,
+            deleted_at INTEGER,
+            deleted_cascade_depth INTEGER
+        );
+        CREATE INDEX IF NOT EXISTS idx_project_members_project ON project_members(project_id);
+        CREATE INDEX IF NOT EXISTS idx_project_members_member ON project_members(org_member_id);
+        CREATE INDEX IF NOT EXISTS idx_project_members_active ON project_members(id) WHERE deleted_at IS NULL;
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_project_members_unique_active ON project_members(org_member_id, project_id) WHERE deleted_at IS NULL;
+
+        -- Per-project counters, e.g. 'license' for license reference numbers
+        CREATE TABLE IF NOT EXISTS project_counters (
+            project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+            name TEXT NOT NULL,
+            value INTEGER NOT NULL,
+            PRIMARY KEY (project_id, name)
+        );
+
+        -- Temporary project roles (break-glass access, expiry checked at auth time)
+        CREATE TABLE IF NOT EXISTS temporary_role_grants (
+            id TEXT PRIMARY KEY,
+            org_member_id TEXT NOT NULL,
+            project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+            role TEXT NOT NULL CHECK (role IN ('admin', 'view')),
+            reason TEXT,
+            granted_by TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            expires_at INTEGER NOT NULL,
+            revoked_at INTEGER,
+            revoked_by TEXT
+        );
+        CREATE INDEX IF NOT EXISTS idx_temporary_role_grants_member ON temporary_role_grants(org_member_id, project_id, expires_at);
+        CREATE INDEX IF NOT EXISTS idx_temporary_role_grants_project ON temporary_role_grants(project_id, created_at);
+
+        -- Products (tiers/plans within a project)
+        CREATE TABLE IF NOT EXISTS products (
+            id TEXT PRIMARY KEY,
+            project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+            name TEXT NOT NULL,
+            tier TEXT NOT NULL,
+            license_exp_days INTEGER,
+            updates_exp_days INTEGER,
+            activation_limit INTEGER,
+            device_limit INTEGER,
+            device_inactive_days INTEGER,
+            features TEXT NOT NULL DEFAULT '[]',
+            price_cents INTEGER,
+            currency TEXT,
+            created_at INTEGER NOT NULL,
+            deleted_at INTEGER,
+            deleted_cascade_depth INTEGER,
+            seat_count INTEGER,
+            available_from INTEGER,
+            available_until INTEGER,
+            -- Custom fields collected at checkout: JSON list of {key, label, type, required}
+            checkout_fields TEXT NOT NULL DEFAULT '[]',
+            -- Updates-only renewals: the base product whose licenses a purchase extends,
+            -- and what to do when the buyer has none
+            extends_updates_for_product_id TEXT,
+            renewal_fallback TEXT NOT NULL DEFAULT 'create_license',
+            -- Upsell shown to this product's customers: the product to upgrade to,
+            -- which of its features to highlight (JSON list), and a short pitch
+            upgrade_to_product_id TEXT,
+            upsell_highlight_features TEXT NOT NULL DEFAULT '[]',
+            upsell_blurb TEXT,
+            -- Structured entitlements: JSON object of name -> integer, bool, or string
+            entitlements TEXT NOT NULL DEFAULT '{}',
+            -- Bumped by each update, for optimistic locking
+            version INTEGER NOT NULL DEFAULT 1,
+            -- Revoke licenses on partial refunds too (full refunds always revoke)
+            revoke_on_partial_refund INTEGER NOT NULL DEFAULT 0,
+            UNIQUE(project_id, name)
+        );
+        CREATE INDEX IF NOT EXISTS idx_products_project ON products(project_id);
+        CREATE INDEX IF NOT EXISTS idx_products_active ON products(id) WHERE deleted_at IS NULL;
+
+        -- Product provider links (maps products to payment provider price/variant IDs)
+        CREATE TABLE IF NOT EXISTS product_provider_links (
+            id TEXT PRIMARY KEY,
+            product_id TEXT NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+            provider TEXT NOT NULL CHECK (provider IN ('stripe', 'lemonsqueezy')),
+            linked_id TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL,
+            UNIQUE(product_id, provider)
+        );
+        CREATE INDEX IF NOT EXISTS idx_provider_links_product ON product_provider_links(product_id);
+
+        -- Licenses (no user-facing keys - email hash is the identity)
+        -- email_hash: SHA-256 hash of purchase email (no PII stored)
+        -- project_id: denormalized for efficient lookups
+        CREATE TABLE IF NOT EXISTS licenses (
+            id TEXT PRIMARY KEY,
+            email_hash TEXT,
+            project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+            product_id TEXT NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+            customer_id TEXT,
+            activation_count INTEGER NOT NULL DEFAULT 0,
+            revoked INTEGER NOT NULL DEFAULT 0,
+            created_at INTEGER NOT NULL,
+            expires_at INTEGER,
+            updates_expires_at INTEGER,
+            payment_provider TEXT,
+            payment_provider_customer_id TEXT,
+            payment_provider_subscription_id TEXT,
+            payment_provider_order_id TEXT,
+            deleted_at INTEGER,
+            deleted_cascade_depth INTEGER,
+            paused_at INTEGER,
+            paused_seconds INTEGER NOT NULL DEFAULT 0,
+            seats INTEGER,
+            abuse_flags INTEGER NOT NULL DEFAULT 0,
+            abuse_flagged_at INTEGER,
+            abuse_distinct_ips INTEGER,
+            revoked_reason TEXT,
+            revoked_message TEXT,
+            revoked_at INTEGER,
+            revoked_by TEXT,
+            -- Values of the product's checkout fields, JSON object keyed by field key
+            checkout_fields TEXT,
+            -- Key issued by a payment provider before the license was imported (SHA-256),
+            -- and which provider issued it
+            external_key_hash TEXT,
+            external_key_provider TEXT,
+            -- Set while a dispute (chargeback) over the purchase is open
+            suspended_for_dispute INTEGER NOT NULL DEFAULT 0,
+            -- Trial license (created with is_trial), and for a paid license the trial
+            -- it converted from
+            is_trial INTEGER NOT NULL DEFAULT 0,
+            converted_from_license_id TEXT,
+            -- Terms version the buyer accepted at /buy (terms.version)
+            accepted_terms_version TEXT,
+            -- Public handle shown in tokens instead of the id (db::license_refs)
+            license_ref TEXT,
+            -- Metadata sent to /buy (e.g. utm_* parameters), JSON object
+            metadata TEXT,
+            -- When the license was marked dormant (never activated within the project's
+            -- dormant_after_days); cleared by the first activation
+            dormant_since INTEGER,
+            -- Sequential number for support, e.g. PRO-000123 (db::reference_numbers)
+            reference_number TEXT,
+            -- App versions reported to /validate and /refresh (X-Paycheck-Client-Version);
+            -- last_seen_* is written at most hourly
+            first_seen_version TEXT,
+            last_seen_version TEXT,
+            last_seen_version_at INTEGER
+        );
+        CREATE INDEX IF NOT EXISTS idx_licenses_product ON licenses(product_id);
+        CREATE INDEX IF NOT EXISTS idx_licenses_project ON licenses(project_id);
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_licenses_project_ref ON licenses(project_id, license_ref);
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_licenses_project_reference ON licenses(project_id, reference_number);
+        CREATE INDEX IF NOT EXISTS idx_licenses_project_email ON licenses(project_id, email_hash);
+        CREATE INDEX IF NOT EXISTS idx_licenses_project_order ON licenses(project_id, payment_provider_order_id);
+        CREATE INDEX IF NOT EXISTS idx_licenses_project_customer ON licenses(project_id, customer_id);
+        CREATE INDEX IF NOT EXISTS idx_licenses_provider_customer ON licenses(payment_provider, payment_provider_customer_id);
+        CREATE INDEX IF NOT EXISTS idx_licenses_provider_subscription ON licenses(payment_provider, payment_provider_subscription_id);
+        CREATE INDEX IF NOT EXISTS idx_licenses_provider_order ON licenses(payment_provider, payment_provider_order_id);
+        CREATE INDEX IF NOT EXISTS idx_licenses_active ON licenses(id) WHERE deleted_at IS NULL;
+        CREATE INDEX IF NOT EXISTS idx_licenses_converted_from ON licenses(converted_from_license_id) WHERE converted_from_license_id IS NOT NULL;
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_licenses_free_claim ON licenses(product_id, email_hash) WHERE payment_provider = 'free';
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_licenses_external_key ON licenses(project_id, external_key_provider, external_key_hash) WHERE external_key_hash IS NOT NULL;
+        CREATE INDEX IF NOT EXISTS idx_licenses_dormant ON licenses(project_id, dormant_since) WHERE dormant_since IS NOT NULL;
+
+        -- License seats (team licenses: each seat holder activates with their own email)
+        -- removed_at: set when the seat is unassigned (row kept for history)
+        CREATE TABLE IF NOT EXISTS license_seats (
+            id TEXT PRIMARY KEY,
+            license_id TEXT NOT NULL REFERENCES licenses(id) ON DELETE CASCADE,
+            seat_email_hash TEXT NOT NULL,
+            assigned_at INTEGER NOT NULL,
+            removed_at INTEGER
+        );
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_license_seats_active ON license_seats(license_id, seat_email_hash) WHERE removed_at IS NULL;
+        CREATE INDEX IF NOT EXISTS idx_license_seats_email ON license_seats(seat_email_hash);
+
+        -- Share links (customer-facing license detail pages, addressed by an unguessable token)
+        -- token_hash: hash of the token in the URL (the token itself is never stored)
+        -- masked_email: display-only email (e.g. j***@example.com), NULL if none was given
+        CREATE TABLE IF NOT EXISTS share_links (
+            id TEXT PRIMARY KEY,
+            license_id TEXT NOT NULL REFERENCES licenses(id) ON DELETE CASCADE,
+            token_hash TEXT NOT NULL UNIQUE,
+            masked_email TEXT,
+            created_by TEXT,
+            expires_at INTEGER NOT NULL,
+            revoked INTEGER NOT NULL DEFAULT 0,
+            created_at INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_share_links_license ON share_links(license_id);
+
+        -- Prepaid code batches (e.g. codes printed on retail cards, each redeemable once for a license)
+        -- pending_codes: the batch's codes as CSV, encrypted under the org's data key, until
+        -- the one download (NULL afterwards, or once the batch is revoked)
+        -- revoked_at: when the batch was revoked (unredeemed codes stop working)
+        CREATE TABLE IF NOT EXISTS prepaid_code_batches (
+            id TEXT PRIMARY KEY,
+            project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+            product_id TEXT NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+            label TEXT,
+            code_count INTEGER NOT NULL,
+            created_by TEXT,
+            expires_at INTEGER,
+            pending_codes BLOB,
+            downloaded_at INTEGER,
+            revoked_at INTEGER,
+            created_at INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_prepaid_code_batches_product ON prepaid_code_batches(product_id);
+
+        -- Prepaid codes (only the hash is stored)
+        -- license_id: the license created when the code was redeemed
+        CREATE TABLE IF NOT EXISTS prepaid_codes (
+            code_hash TEXT PRIMARY KEY,
+            batch_id TEXT NOT NULL REFERENCES prepaid_code_batches(id) ON DELETE CASCADE,
+            license_id TEXT REFERENCES licenses(id) ON DELETE SET NULL,
+            redeemed_at INTEGER
+        );
+        CREATE INDEX IF NOT EXISTS idx_prepaid_codes_batch ON prepaid_codes(batch_id);
+
+        -- Bulk license jobs (large license creation requests, run in chunks in the background)
+        -- params: the license settings as JSON (email hash, customer_id, expirations, seats, trial)
+        -- error: why the job stopped early, other than a failed chunk
+        CREATE TABLE IF NOT EXISTS bulk_jobs (
+            id TEXT PRIMARY KEY,
+            project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+            product_id TEXT NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+            status TEXT NOT NULL DEFAULT 'running' CHECK (status IN ('running', 'completed', 'failed', 'cancelled')),
+            requested INTEGER NOT NULL,
+            created_count INTEGER NOT NULL DEFAULT 0,
+            activation_codes INTEGER NOT NULL DEFAULT 0,
+            params TEXT NOT NULL,
+            created_by TEXT,
+            error TEXT,
+            created_at INTEGER NOT NULL,
+            finished_at INTEGER
+        );
+        CREATE INDEX IF NOT EXISTS idx_bulk_jobs_project ON bulk_jobs(project_id);
+        CREATE INDEX IF NOT EXISTS idx_bulk_jobs_running ON bulk_jobs(status) WHERE status = 'running';
+
+        -- Chunks a bulk job has attempted: the license IDs created, or the error that rolled it back
+        CREATE TABLE IF NOT EXISTS bulk_job_chunks (
+            job_id TEXT NOT NULL REFERENCES bulk_jobs(id) ON DELETE CASCADE,
+            chunk INTEGER NOT NULL,
+            license_ids TEXT NOT NULL DEFAULT '[]',
+            error TEXT,
+            created_at INTEGER NOT NULL,
+            PRIMARY KEY (job_id, chunk)
+        );
+
+        -- Versions of a project's terms of sale; the current one is the latest in effect
+        -- Immutable once a license accepted it (licenses.accepted_terms_version)
+        CREATE TABLE IF NOT EXISTS terms (
+            id TEXT PRIMARY KEY,
+            project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+            version TEXT NOT NULL,
+            url TEXT NOT NULL,
+            effective_at INTEGER NOT NULL,
+            created_by TEXT,
+            created_at INTEGER NOT NULL
+        );
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_terms_project_version ON terms(project_id, version);
+        CREATE INDEX IF NOT EXISTS idx_terms_project_effective ON terms(project_id, effective_at);
+
+        -- Release artifacts gated by /download/{artifact_id}: a license may fetch one
+        -- released while its updates window was open
+        -- url: the vendor's external URL (or storage key), signed on each download
+        CREATE TABLE IF NOT EXISTS artifacts (
+            id TEXT PRIMARY KEY,
+            project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+            name TEXT NOT NULL,
+            released_at INTEGER NOT NULL,
+            url TEXT NOT NULL,
+            created_by TEXT,
+            created_at INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_artifacts_project ON artifacts(project_id, released_at);
+
+        -- Downloads per artifact and license
+        CREATE TABLE IF NOT EXISTS artifact_downloads (
+            artifact_id TEXT NOT NULL REFERENCES artifacts(id) ON DELETE CASCADE,
+            license_id TEXT NOT NULL REFERENCES licenses(id) ON DELETE CASCADE,
+            count INTEGER NOT NULL DEFAULT 0,
+            first_downloaded_at INTEGER NOT NULL,
+            last_downloaded_at INTEGER NOT NULL,
+            PRIMARY KEY (artifact_id, license_id)
+        );
+        CREATE INDEX IF NOT EXISTS idx_artifact_downloads_license ON artifact_downloads(license_id);
+
+        -- License upgrades (an old license replaced by one bought through an upgrade checkout)
+        -- old_license_action: what was done to the old license ('revoke' or 'updates_only')
+        CREATE TABLE IF NOT EXISTS license_upgrades (
+            id TEXT PRIMARY KEY,
+            from_license_id TEXT NOT NULL REFERENCES licenses(id) ON DELETE CASCADE,
+            to_license_id TEXT NOT NULL REFERENCES licenses(id) ON DELETE CASCADE,
+            payment_session_id TEXT,
+            days_remaining INTEGER,
+            credit_cents INTEGER,
+            old_license_action TEXT NOT NULL,
+            created_at INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_license_upgrades_from ON license_upgrades(from_license_id);
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_license_upgrades_to ON license_upgrades(to_license_id);
+
+        -- Updates-only renewal purchases applied to a license
+        CREATE TABLE IF NOT EXISTS license_update_renewals (
+            id TEXT PRIMARY KEY,
+            license_id TEXT NOT NULL REFERENCES licenses(id) ON DELETE CASCADE,
+            product_id TEXT NOT NULL,
+            payment_session_id TEXT,
+            previous_updates_expires_at INTEGER,
+            updates_expires_at INTEGER,
+            created_at INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_license_update_renewals_license ON license_update_renewals(license_id);
+
+        -- License tags (free-form labels for grouping licenses, e.g. 'beta-cohort')
+        CREATE TABLE IF NOT EXISTS license_tags (
+            license_id TEXT NOT NULL REFERENCES licenses(id) ON DELETE CASCADE,
+            tag TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            PRIMARY KEY (license_id, tag)
+        );
+        CREATE INDEX IF NOT EXISTS idx_license_tags_tag ON license_tags(tag);
+
+        -- Payment disputes (chargebacks) against the purchase behind a license
+        -- payment_id: matches payment_sessions.provider_payment_id of the checkout
+        -- status: 'open' (license suspended), 'won' (reinstated), or 'lost' (revoked)
+        CREATE TABLE IF NOT EXISTS disputes (
+            id TEXT PRIMARY KEY,
+            license_id TEXT NOT NULL REFERENCES licenses(id) ON DELETE CASCADE,
+            project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+            provider TEXT NOT NULL,
+            provider_dispute_id TEXT NOT NULL,
+            payment_id TEXT NOT NULL,
+            amount_cents INTEGER,
+            currency TEXT,
+            reason TEXT,
+            status TEXT NOT NULL CHECK (status IN ('open', 'won', 'lost')),
+            created_at INTEGER NOT NULL,
+            closed_at INTEGER
+        );
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_disputes_provider ON disputes(provider, provider_dispute_id);
+        CREATE INDEX IF NOT EXISTS idx_disputes_license ON disputes(license_id, created_at);
+        CREATE INDEX IF NOT EXISTS idx_disputes_project ON disputes(project_id, status, created_at);
+
+        -- Activation code email attempts, one row per license in the email
+        -- to_email_hash: recipient hashed like licenses.email_hash (no plaintext address stored)
+        -- result: 'sent', 'webhook_called', 'disabled', 'no_api_key' or 'failed'
+        -- error_status: HTTP status from Resend for failed sends (NULL if no response)
+        CREATE TABLE IF NOT EXISTS email_log (
+            id TEXT PRIMARY KEY,
+            license_id TEXT NOT NULL REFERENCES licenses(id) ON DELETE CASCADE,
+            project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+            to_email_hash TEXT NOT NULL,
+            email_trigger TEXT NOT NULL,
+            result TEXT NOT NULL CHECK (result IN ('sent', 'webhook_called', 'disabled', 'no_api_key', 'failed')),
+            error_status INTEGER,
+            provider_message_id TEXT,
+            created_at INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_email_log_license ON email_log(license_id, created_at);
+        CREATE INDEX IF NOT EXISTS idx_email_log_project ON email_log(project_id, result, created_at);
+
+        -- Valid /validate calls per UTC day, product and client country, for projects
+        -- with usage_geo_enabled. Only the country code is kept, never the address.
+        -- date: YYYY-MM-DD; country: ISO 3166-1 alpha-2 code
+        CREATE TABLE IF NOT EXISTS usage_geo_daily (
+            date TEXT NOT NULL,
+            project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+            product_id TEXT NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+            country TEXT NOT NULL,
+            count INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (project_id, date, product_id, country)
+        );
+
+        -- Activation codes (short-lived codes in PREFIX-XXXX-XXXX format, 40 bits entropy)
+        CREATE TABLE IF NOT EXISTS activation_codes (
+            code_hash TEXT PRIMARY KEY,
+            license_id TEXT NOT NULL REFERENCES licenses(id) ON DELETE CASCADE,
+            expires_at INTEGER NOT NULL,
+            used INTEGER NOT NULL DEFAULT 0,
+            created_at INTEGER NOT NULL,
+            seat_id TEXT REFERENCES license_seats(id) ON DELETE CASCADE
+        );
+        CREATE INDEX IF NOT EXISTS idx_activation_codes_license ON activation_codes(license_id);
+        CREATE INDEX IF NOT EXISTS idx_activation_codes_expires ON activation_codes(expires_at);
+
+        -- Revoked JTIs (individual token revocations)
+        -- JTI is globally unique (UUID), license_id kept for FK cascade and admin queries
+        CREATE TABLE IF NOT EXISTS revoked_jtis (
+            jti TEXT PRIMARY KEY,
+            license_id TEXT NOT NULL REFERENCES licenses(id) ON DELETE CASCADE,
+            revoked_at INTEGER NOT NULL,
+            details TEXT
+        );
+        CREATE INDEX IF NOT EXISTS idx_revoked_jtis_license ON revoked_jtis(license_id);
+
+        -- Devices (activated devices for a license)
+        CREATE TABLE IF NOT EXISTS devices (
+            id TEXT PRIMARY KEY,
+            license_id TEXT NOT NULL REFERENCES licenses(id) ON DELETE CASCADE,
+            device_id TEXT NOT NULL,
+            device_type TEXT NOT NULL CHECK (device_type IN ('uuid', 'machine')),
+            name TEXT,
+            jti TEXT NOT NULL,
+            activated_at INTEGER NOT NULL,
+            last_seen_at INTEGER NOT NULL,
+            seat_id TEXT REFERENCES license_seats(id) ON DELETE CASCADE,
+            signed_with_kid TEXT,
+            UNIQUE(license_id, device_id)
+        );
+        -- Note: UNIQUE(license_id, device_id) creates implicit index for device lookups
+        CREATE INDEX IF NOT EXISTS idx_devices_license_time ON devices(license_id, activated_at DESC);
+        CREATE INDEX IF NOT EXISTS idx_devices_jti ON devices(jti);
+        CREATE INDEX IF NOT EXISTS idx_devices_seat ON devices(seat_id) WHERE seat_id IS NOT NULL;
+
+        -- Payment sessions (temporary, for tracking buy flow)
+        -- Device info removed: purchase ≠ activation. Device created at /redeem time.
+        -- Redirect URL removed: now configured per-project, not per-session.
+        CREATE TABLE IF NOT EXISTS payment_sessions (
+            id TEXT PRIMARY KEY,
+            product_id TEXT NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+            customer_id TEXT,
+            created_at INTEGER NOT NULL,
+            completed INTEGER NOT NULL DEFAULT 0,
+            license_id TEXT REFERENCES licenses(id) ON DELETE SET NULL,
+            -- Upgrade checkouts: the license being replaced and its remaining value
+            upgrade_from_license_id TEXT REFERENCES licenses(id) ON DELETE SET NULL,
+            upgrade_days_remaining INTEGER,
+            upgrade_credit_cents INTEGER,
+            -- Provider payment the checkout settled with (Stripe PaymentIntent, LemonSqueezy order),
+            -- for matching refunds and chargebacks
+            provider_payment_id TEXT,
+            -- Checkout field values submitted to /buy, copied to the license on completion
+            checkout_fields TEXT,
+            -- Provider checkout page, and the buyer's email hash, for reusing a pending checkout
+            checkout_url TEXT,
+            buyer_email_hash TEXT,
+            -- Set when a completed checkout issued nothing and needs someone to look at it
+            needs_review INTEGER NOT NULL DEFAULT 0,
+            -- One-time claim code the buyer's app exchanges for the license (see /buy/wait),
+            -- stored hashed; claimed_at is set once it's been exchanged
+            claim_code_hash TEXT,
+            claim_expires_at INTEGER,
+            claimed_at INTEGER,
+            -- Terms version the buyer accepted at /buy, copied to the license
+            accepted_terms_version TEXT,
+            -- Metadata sent to /buy, passed to the provider and copied to the license
+            metadata TEXT
+        );
+        CREATE INDEX IF NOT EXISTS idx_payment_sessions_product ON payment_sessions(product_id);
+        CREATE INDEX IF NOT EXISTS idx_payment_sessions_provider_payment ON payment_sessions(provider_payment_id);
+
+        -- Audit entries written in the same transaction as the change they record,
+        -- waiting to be copied to the audit database (see db::outbox)
+        CREATE TABLE IF NOT EXISTS audit_outbox (
+            id TEXT PRIMARY KEY, -- audit log entry ID
+            entry TEXT NOT NULL, -- AuditLog JSON
+            created_at INTEGER NOT NULL,
+            relayed_at INTEGER
+        );
+        CREATE INDEX IF NOT EXISTS idx_audit_outbox_pending ON audit_outbox(created_at) WHERE relayed_at IS NULL;
         "#,
     )?;
     Ok(())
@@ -308,14 +1402,48 @@ pub fn init_audit_db(conn: &Connection) -> rusqlite::Result<()> {
             ip_address TEXT,
             user_agent TEXT,
             auth_type TEXT,                       -- 'api_key' or 'jwt' (for filtering)
-            auth_credential TEXT                  -- key prefix (e.g., 'pc_a1b2...') or issuer URL
+            auth_credential TEXT,                 -- key prefix (e.g., 'pc_a1b2...') or issuer URL
+            request_id TEXT,                      -- X-Request-Id of the HTTP request (null outside requests)
+            chain TEXT,                           -- hash chain: 'public' or 'internal' (see db::audit_chain)
+            chain_seq INTEGER,                    -- position in the chain, from 1
+            prev_hash TEXT,                       -- row_hash of the previous entry (zeros for the first)
+            row_hash TEXT                         -- SHA-256(prev_hash || canonical row)
         );
         CREATE INDEX IF NOT EXISTS idx_audit_logs_timestamp ON audit_logs(timestamp);
         CREATE INDEX IF NOT EXISTS idx_audit_logs_user ON audit_logs(user_id);
         CREATE INDEX IF NOT EXISTS idx_audit_logs_resource ON audit_logs(resource_type, resource_id);
         CREATE INDEX IF NOT EXISTS idx_audit_logs_org_time ON audit_logs(org_id, timestamp DESC);
         CREATE INDEX IF NOT EXISTS idx_audit_logs_project ON audit_logs(project_id);
+        CREATE INDEX IF NOT EXISTS idx_audit_logs_project_activity ON audit_logs(project_id, actor_type, timestamp DESC);
         CREATE INDEX IF NOT EXISTS idx_audit_logs_purge ON audit_logs(actor_type, timestamp);
+        CREATE INDEX IF NOT EXISTS idx_audit_logs_request ON audit_logs(request_id);
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_audit_logs_chain ON audit_logs(chain, chain_seq);
+
+        -- Last entry removed from the front of each chain by retention purges
+        CREATE TABLE IF NOT EXISTS audit_chain_anchors (
+            chain TEXT PRIMARY KEY,
+            chain_seq INTEGER NOT NULL,
+            row_hash TEXT NOT NULL,
+            anchored_at INTEGER NOT NULL
+        );
+
+        -- Entries removed from the middle of a chain by retention purges, so
+        -- the entry after each still links (see db::audit_chain)
+        CREATE TABLE IF NOT EXISTS audit_chain_tombstones (
+            chain TEXT NOT NULL,
+            chain_seq INTEGER NOT NULL,
+            row_hash TEXT NOT NULL,
+            PRIMARY KEY (chain, chain_seq)
+        );
+
+        -- The audit database's storage heartbeat (see storage_health)
+        CREATE TABLE IF NOT EXISTS storage_heartbeats (
+            db TEXT PRIMARY KEY CHECK (db = 'audit'),
+            path TEXT NOT NULL,
+            beat INTEGER NOT NULL,
+            started_at INTEGER NOT NULL,
+            beat_at INTEGER NOT NULL
+        );
         "#,
     )?;
     Ok(())
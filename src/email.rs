@@ -5,14 +5,20 @@
 //! 2. POST to webhook URL (for DIY email delivery)
 //! 3. Disabled (no email sent, log only)
 
+use std::collections::BTreeMap;
 use std::time::Duration;
 
 use chrono::{DateTime, Utc};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use strum::{AsRefStr, EnumString};
 
-use crate::error::{AppError, Result};
+use crate::external_url;
 use crate::models::Project;
+use crate::webhook_mirror::WebhookMirror;
+use crate::webhook_payloads::{
+    ActivationCodeCreated, ActivationCodesCreated, LicenseDormant, WebhookBody, WebhookEvent,
+};
 
 /// Retry delays in seconds (exponential backoff: 1s, 4s, 16s)
 const RETRY_DELAYS: &[u64] = &[1, 4, 16];
@@ -26,6 +32,21 @@ fn format_date(timestamp: i64) -> String {
         .unwrap_or_else(|| "Unknown date".to_string())
 }
 
+/// Render `event` in the project's webhook schema version. None (logged) if
+/// it won't serialize.
+fn render_webhook<E: WebhookEvent>(project: &Project, event: &E) -> Option<WebhookBody> {
+    WebhookBody::render(event, project.webhook_schema())
+        .inspect_err(|e| {
+            tracing::error!(
+                project_id = %project.id,
+                "Failed to serialize {} webhook: {}",
+                E::NAME,
+                e
+            );
+        })
+        .ok()
+}
+
 /// Format an activation code for HTML display.
 ///
 /// Input: `PREFIX-XXXX-XXXX`
@@ -69,20 +90,25 @@ fn format_code_text(code: &str) -> String {
 }
 
 /// Result of attempting to send an activation code email.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum EmailSendResult {
-    /// Email was sent successfully via Resend
-    Sent,
+    /// Email was sent successfully via Resend (with Resend's message id)
+    Sent { message_id: Option<String> },
     /// Data was POSTed to the project's webhook URL
     WebhookCalled,
     /// Email delivery is disabled for this project
     Disabled,
     /// No API key available (system or org level)
     NoApiKey,
+    /// Resend rejected the email or couldn't be reached. `status` is the HTTP
+    /// status of the last attempt (None if there was no response).
+    Failed { status: Option<u16> },
 }
 
 /// Configuration for sending an activation code email (single license).
 pub struct EmailSendConfig<'a> {
+    /// Empty for dormant nudges: no address is stored, so they only go to the
+    /// project's webhook, which finds the customer by `license_id`
     pub to_email: &'a str,
     pub code: &'a str,
     pub expires_in_minutes: i32,
@@ -92,8 +118,15 @@ pub struct EmailSendConfig<'a> {
     pub license_id: &'a str,
     /// When the license was purchased (Unix timestamp)
     pub purchased_at: i64,
+    /// The license's `/buy` metadata. Only sent to the project's webhook,
+    /// never put in the email.
+    pub metadata: &'a BTreeMap<String, String>,
     /// Pre-decrypted org-level Resend API key (if set)
     pub org_resend_key: Option<&'a str>,
+    /// Pre-decrypted secret to sign the project's webhook with (if set)
+    pub webhook_secret: Option<&'a str>,
+    /// Org-level default "from" address (if set)
+    pub org_from_email: Option<&'a str>,
     /// What triggered this email
     pub trigger: EmailTrigger,
 }
@@ -106,6 +139,8 @@ pub struct LicenseCodeInfo {
     pub license_id: String,
     /// When the license was purchased (Unix timestamp)
     pub purchased_at: i64,
+    /// The license's `/buy` metadata (webhook only, never emailed)
+    pub metadata: BTreeMap<String, String>,
 }
 
 /// Configuration for sending activation codes for multiple licenses.
@@ -117,13 +152,18 @@ pub struct MultiLicenseEmailConfig<'a> {
     pub licenses: Vec<LicenseCodeInfo>,
     /// Pre-decrypted org-level Resend API key (if set)
     pub org_resend_key: Option<&'a str>,
+    /// Pre-decrypted secret to sign the project's webhook with (if set)
+    pub webhook_secret: Option<&'a str>,
+    /// Org-level default "from" address (if set)
+    pub org_from_email: Option<&'a str>,
     /// What triggered this email
     pub trigger: EmailTrigger,
 }
 
 /// What triggered the activation code email.
-#[derive(Debug, Clone, Copy, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, AsRefStr, EnumString)]
 #[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
 pub enum EmailTrigger {
     /// Initial purchase (callback/webhook)
     Purchase,
@@ -131,43 +171,9 @@ pub enum EmailTrigger {
     RecoveryRequest,
     /// Admin generated code via /orgs/.../send-code
     AdminGenerated,
-}
-
-/// Webhook payload sent when email_webhook_url is configured (single license).
-#[derive(Debug, Serialize)]
-pub struct WebhookPayload<'a> {
-    pub event: &'static str,
-    pub email: &'a str,
-    pub code: &'a str,
-    pub expires_at: i64,
-    pub expires_in_minutes: i32,
-    pub product_name: &'a str,
-    pub project_id: &'a str,
-    pub project_name: &'a str,
-    pub license_id: &'a str,
-    pub trigger: EmailTrigger,
-}
-
-/// License info in multi-license webhook payload.
-#[derive(Debug, Serialize)]
-pub struct WebhookLicenseInfo {
-    pub product_name: String,
-    pub code: String,
-    pub license_id: String,
-    pub purchased_at: i64,
-}
-
-/// Webhook payload for multiple licenses.
-#[derive(Debug, Serialize)]
-pub struct MultiLicenseWebhookPayload<'a> {
-    pub event: &'static str,
-    pub email: &'a str,
-    pub expires_at: i64,
-    pub expires_in_minutes: i32,
-    pub project_id: &'a str,
-    pub project_name: &'a str,
-    pub licenses: Vec<WebhookLicenseInfo>,
-    pub trigger: EmailTrigger,
+    /// License marked dormant (never activated) in a project with
+    /// `dormant_nudge_email` on
+    DormantNudge,
 }
 
 /// Resend API request body.
@@ -183,10 +189,51 @@ struct ResendEmailRequest<'a> {
 /// Resend API response.
 #[derive(Debug, Deserialize)]
 struct ResendEmailResponse {
-    #[allow(dead_code)]
     id: String,
 }
 
+/// Resend API error body.
+#[derive(Debug, Deserialize)]
+struct ResendErrorResponse {
+    message: String,
+}
+
+/// Why a request to Resend failed.
+#[derive(Debug)]
+struct ResendError {
+    /// HTTP status (None if Resend couldn't be reached)
+    status: Option<u16>,
+    /// Resend's error message, or the network error
+    message: String,
+    /// Worth retrying (network error, 429, or 5xx)
+    transient: bool,
+}
+
+/// Outcome of a test email sent with an org's Resend API key.
+#[derive(Debug, Clone, Serialize)]
+pub struct TestEmailResult {
+    /// Whether Resend accepted the email
+    pub sent: bool,
+    pub to: String,
+    pub from: String,
+    /// Resend's id for the email (if sent)
+    pub message_id: Option<String>,
+    /// HTTP status of a failed request (None if sent, or Resend was unreachable)
+    pub status: Option<u16>,
+    /// Resend's error message (None if sent)
+    pub error: Option<String>,
+}
+
+/// What Resend made of the system API key (see [`EmailService::check_system_key`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResendKeyCheck {
+    Accepted,
+    /// Refused as unauthorized, with this HTTP status
+    Rejected(u16),
+    /// Resend couldn't be reached, or answered with something else
+    Unknown(String),
+}
+
 /// Email service using Resend API.
 #[derive(Clone)]
 pub struct EmailService {
@@ -196,6 +243,14 @@ pub struct EmailService {
     default_from_email: String,
     /// HTTP client for API calls
     http_client: Client,
+    /// HTTP client for project webhook URLs (refuses internal addresses)
+    webhook_client: Client,
+    /// Accept localhost webhook URLs (PAYCHECK_ALLOW_LOCALHOST_URLS)
+    allow_localhost_urls: bool,
+    /// Copies webhooks to projects' mirror URLs
+    mirror: WebhookMirror,
+    /// Resend API endpoint
+    api_url: String,
 }
 
 impl EmailService {
@@ -204,27 +259,68 @@ impl EmailService {
         Self {
             system_api_key,
             default_from_email,
-            http_client: Client::new(),
+            http_client: Client::builder()
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .expect("Failed to create HTTP client"),
+            webhook_client: external_url::guarded_client(false),
+            allow_localhost_urls: false,
+            mirror: WebhookMirror::new(false),
+            api_url: RESEND_API_URL.to_string(),
         }
     }
 
+    /// Let project webhooks call `localhost` (local development).
+    pub fn with_localhost_urls(mut self, allow: bool) -> Self {
+        self.webhook_client = external_url::guarded_client(allow);
+        self.allow_localhost_urls = allow;
+        self.mirror = WebhookMirror::new(allow);
+        self
+    }
+
+    /// Send through a different Resend-compatible endpoint (a relay, or a
+    /// stub server in tests).
+    pub fn with_api_url(mut self, api_url: impl Into<String>) -> Self {
+        self.api_url = api_url.into();
+        self
+    }
+
+    /// Ask Resend whether the system API key is valid, without sending
+    /// anything: an empty send request is refused for its body with a good key
+    /// (400/422) and as unauthorized with a bad one (401/403). None without a
+    /// system key.
+    pub async fn check_system_key(&self) -> Option<ResendKeyCheck> {
+        let api_key = self.system_api_key.as_deref()?;
+        let response = self
+            .http_client
+            .post(&self.api_url)
+            .header("Authorization", format!("Bearer {}", api_key))
+            .json(&serde_json::json!({}))
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await;
+        Some(match response.map(|r| r.status().as_u16()) {
+            Ok(400 | 422) => ResendKeyCheck::Accepted,
+            Ok(status @ (401 | 403)) => ResendKeyCheck::Rejected(status),
+            Ok(status) => ResendKeyCheck::Unknown(format!("HTTP {}", status)),
+            Err(e) => ResendKeyCheck::Unknown(e.to_string()),
+        })
+    }
+
     /// Send an activation code email (or call webhook, or skip if disabled).
     ///
     /// Resolution order:
     /// 1. If email_enabled is false -> return Disabled
     /// 2. If email_webhook_url is set -> POST to webhook
     /// 3. Otherwise send via Resend API (org key -> system key)
-    pub async fn send_activation_code(
-        &self,
-        config: EmailSendConfig<'_>,
-    ) -> Result<EmailSendResult> {
+    pub async fn send_activation_code(&self, config: EmailSendConfig<'_>) -> EmailSendResult {
         // Check if email is disabled for this project
         if !config.project.email_enabled {
             tracing::debug!(
                 project_id = %config.project.id,
                 "Email disabled for project, skipping activation code email"
             );
-            return Ok(EmailSendResult::Disabled);
+            return EmailSendResult::Disabled;
         }
 
         // If webhook URL is configured, POST to it instead of sending email
@@ -240,14 +336,15 @@ impl EmailService {
                 project_id = %config.project.id,
                 "No Resend API key available (system or org level), cannot send email"
             );
-            return Ok(EmailSendResult::NoApiKey);
+            return EmailSendResult::NoApiKey;
         };
 
-        // Determine from address: project-level or system default
+        // Determine from address: project-level, org-level, or system default
         let from_email = config
             .project
             .email_from
             .as_deref()
+            .or(config.org_from_email)
             .unwrap_or(&self.default_from_email);
 
         self.send_via_resend(api_key, from_email, &config).await
@@ -259,7 +356,7 @@ impl EmailService {
         api_key: &str,
         from_email: &str,
         config: &EmailSendConfig<'_>,
-    ) -> Result<EmailSendResult> {
+    ) -> EmailSendResult {
         let subject = format!(
             "Your {} license for {}",
             config.product_name, config.project_name
@@ -329,8 +426,8 @@ impl EmailService {
         request: &ResendEmailRequest<'_>,
         to_email: &str,
         project_id: &str,
-    ) -> Result<EmailSendResult> {
-        let mut last_error: Option<AppError> = None;
+    ) -> EmailSendResult {
+        let mut last_status: Option<u16> = None;
 
         for (attempt, delay_secs) in std::iter::once(&0u64).chain(RETRY_DELAYS).enumerate() {
             // Sleep before retry (skip on first attempt)
@@ -344,7 +441,7 @@ impl EmailService {
             }
 
             match self.send_resend_request(api_key, request).await {
-                Ok(()) => {
+                Ok(message_id) => {
                     if attempt > 0 {
                         tracing::info!(
                             attempt,
@@ -359,16 +456,17 @@ impl EmailService {
                             "Activation code email sent via Resend"
                         );
                     }
-                    return Ok(EmailSendResult::Sent);
+                    return EmailSendResult::Sent { message_id };
                 }
-                Err((error, is_transient)) => {
-                    if is_transient {
-                        last_error = Some(error);
-                        // Continue to next retry
-                    } else {
+                Err(error) => {
+                    if !error.transient {
                         // Non-transient error, fail immediately
-                        return Err(error);
+                        return EmailSendResult::Failed {
+                            status: error.status,
+                        };
                     }
+                    last_status = error.status;
+                    // Continue to next retry
                 }
             }
         }
@@ -380,22 +478,22 @@ impl EmailService {
             attempts = RETRY_DELAYS.len() + 1,
             "Email send failed after all retries"
         );
-        Err(last_error.unwrap_or_else(|| {
-            AppError::Internal("Email service error: all retries exhausted".into())
-        }))
+        EmailSendResult::Failed {
+            status: last_status,
+        }
     }
 
     /// Send a single request to Resend API.
     ///
-    /// Returns Ok(()) on success, or Err((AppError, is_transient)) on failure.
+    /// Returns Ok(message_id) on success.
     async fn send_resend_request(
         &self,
         api_key: &str,
         request: &ResendEmailRequest<'_>,
-    ) -> std::result::Result<(), (AppError, bool)> {
+    ) -> std::result::Result<Option<String>, ResendError> {
         let response = self
             .http_client
-            .post(RESEND_API_URL)
+            .post(&self.api_url)
             .header("Authorization", format!("Bearer {}", api_key))
             .header("Content-Type", "application/json")
             .json(request)
@@ -404,21 +502,24 @@ impl EmailService {
             .map_err(|e| {
                 tracing::error!(error = %e, "Failed to send request to Resend API");
                 // Network errors are transient
-                (
-                    AppError::Internal(format!("Email service error: {}", e)),
-                    true,
-                )
+                ResendError {
+                    status: None,
+                    message: e.to_string(),
+                    transient: true,
+                }
             })?;
 
         let status = response.status();
 
         if status.is_success() {
-            let _result: ResendEmailResponse = response.json().await.map_err(|e| {
-                tracing::error!(error = %e, "Failed to parse Resend API response");
-                // Parse errors after success are weird but not transient
-                (AppError::Internal("Email service response error".into()), false)
-            })?;
-            Ok(())
+            // Resend accepted the email; a malformed body only costs us the id
+            match response.json::<ResendEmailResponse>().await {
+                Ok(result) => Ok(Some(result.id)),
+                Err(e) => {
+                    tracing::warn!(error = %e, "Failed to parse Resend API response");
+                    Ok(None)
+                }
+            }
         } else {
             let body = response.text().await.unwrap_or_default();
 
@@ -440,10 +541,64 @@ impl EmailService {
                 );
             }
 
-            Err((
-                AppError::Internal(format!("Email service error: {} - {}", status, body)),
-                is_transient,
-            ))
+            let message = serde_json::from_str::<ResendErrorResponse>(&body)
+                .map(|e| e.message)
+                .unwrap_or(body);
+            Err(ResendError {
+                status: Some(status.as_u16()),
+                message,
+                transient: is_transient,
+            })
+        }
+    }
+
+    /// Send a test email with an org's Resend API key, so the org can check
+    /// the key before a customer's email depends on it. One attempt, no
+    /// retries; `from_email` falls back to the server default.
+    pub async fn send_test_email(
+        &self,
+        api_key: &str,
+        from_email: Option<&str>,
+        to_email: &str,
+        org_name: &str,
+    ) -> TestEmailResult {
+        let from = from_email.unwrap_or(&self.default_from_email);
+        let subject = format!("Paycheck test email for {}", org_name);
+        let text = format!(
+            "This is a test email from Paycheck for {}.\n\nYour Resend API key works: activation codes for your customers will be sent the same way.",
+            org_name
+        );
+        let html = format!(
+            r#"<!DOCTYPE html>
+<html>
+<head><meta charset="utf-8"></head>
+<body style="font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, sans-serif; max-width: 600px; margin: 0 auto; padding: 20px;">
+<h2 style="color: #333;">Paycheck test email</h2>
+<p>This is a test email from Paycheck for <strong>{}</strong>.</p>
+<p>Your Resend API key works: activation codes for your customers will be sent the same way.</p>
+</body>
+</html>"#,
+            org_name
+        );
+        let request = ResendEmailRequest {
+            from,
+            to: vec![to_email],
+            subject,
+            text,
+            html,
+        };
+
+        let (message_id, status, error) = match self.send_resend_request(api_key, &request).await {
+            Ok(message_id) => (message_id, None, None),
+            Err(e) => (None, e.status, Some(e.message)),
+        };
+        TestEmailResult {
+            sent: error.is_none(),
+            to: to_email.to_string(),
+            from: from.to_string(),
+            message_id,
+            status,
+            error,
         }
     }
 
@@ -452,12 +607,11 @@ impl EmailService {
         &self,
         webhook_url: &str,
         config: &EmailSendConfig<'_>,
-    ) -> Result<EmailSendResult> {
+    ) -> EmailSendResult {
         let now = chrono::Utc::now().timestamp();
         let expires_at = now + (config.expires_in_minutes as i64 * 60);
 
-        let payload = WebhookPayload {
-            event: "activation_code_created",
+        let event = ActivationCodeCreated {
             email: config.to_email,
             code: config.code,
             expires_at,
@@ -467,15 +621,54 @@ impl EmailService {
             project_name: config.project_name,
             license_id: config.license_id,
             trigger: config.trigger,
+            metadata: config.metadata,
         };
+        self.deliver_webhook(config.project, webhook_url, &event, config.webhook_secret)
+            .await
+    }
 
-        self.call_webhook_with_retry(
-            webhook_url,
-            "activation_code_created",
-            &payload,
-            &config.project.id,
-        )
-        .await
+    /// POST a `license_dormant` event to the project's email webhook, signed
+    /// with `webhook_secret` if set. Skipped (Disabled) when the project has
+    /// none: without a stored address there's nobody to email directly.
+    pub async fn notify_license_dormant(
+        &self,
+        project: &Project,
+        event: &LicenseDormant<'_>,
+        webhook_secret: Option<&str>,
+    ) -> EmailSendResult {
+        let Some(body) = render_webhook(project, event) else {
+            return EmailSendResult::Failed { status: None };
+        };
+        self.mirror_webhook(project, &body);
+        let Some(ref webhook_url) = project.email_webhook_url else {
+            return EmailSendResult::Disabled;
+        };
+        self.call_webhook_with_retry(webhook_url, &body, &project.id, webhook_secret)
+            .await
+    }
+
+    /// Render `event` in the project's schema version, mirror it, and POST it
+    /// to `webhook_url`, signed with `webhook_secret` if set.
+    async fn deliver_webhook<E: WebhookEvent>(
+        &self,
+        project: &Project,
+        webhook_url: &str,
+        event: &E,
+        webhook_secret: Option<&str>,
+    ) -> EmailSendResult {
+        let Some(body) = render_webhook(project, event) else {
+            return EmailSendResult::Failed { status: None };
+        };
+        self.mirror_webhook(project, &body);
+        self.call_webhook_with_retry(webhook_url, &body, &project.id, webhook_secret)
+            .await
+    }
+
+    /// Copy a webhook to the project's mirror, if it has one on.
+    fn mirror_webhook(&self, project: &Project, body: &WebhookBody) {
+        if let Some(mirror_url) = project.webhook_mirror(chrono::Utc::now().timestamp()) {
+            self.mirror.send_event(mirror_url, body);
+        }
     }
 
     /// Call a webhook URL with exponential backoff retry.
@@ -483,13 +676,24 @@ impl EmailService {
     /// Retries on transient errors (network issues, 5xx, 429 rate limit).
     /// After all retries exhausted, returns success anyway (webhook errors
     /// shouldn't block the user flow - the activation code is already created).
-    async fn call_webhook_with_retry<T: Serialize>(
+    async fn call_webhook_with_retry(
         &self,
         webhook_url: &str,
-        event_name: &str,
-        payload: &T,
+        body: &WebhookBody,
         project_id: &str,
-    ) -> Result<EmailSendResult> {
+        webhook_secret: Option<&str>,
+    ) -> EmailSendResult {
+        // Saved URLs are checked too, but may predate the rules
+        if let Err(rejection) = external_url::check_url(webhook_url, self.allow_localhost_urls) {
+            tracing::error!(
+                webhook_url = %webhook_url,
+                project_id = %project_id,
+                "Refused to call webhook: URL {}",
+                rejection
+            );
+            return EmailSendResult::Failed { status: None };
+        }
+
         for (attempt, delay_secs) in std::iter::once(&0u64).chain(RETRY_DELAYS).enumerate() {
             // Sleep before retry (skip on first attempt)
             if *delay_secs > 0 {
@@ -503,7 +707,7 @@ impl EmailService {
             }
 
             match self
-                .send_webhook_request(webhook_url, event_name, payload)
+                .send_webhook_request(webhook_url, body, webhook_secret)
                 .await
             {
                 Ok(()) => {
@@ -521,7 +725,7 @@ impl EmailService {
                             "Activation webhook called successfully"
                         );
                     }
-                    return Ok(EmailSendResult::WebhookCalled);
+                    return EmailSendResult::WebhookCalled;
                 }
                 Err(is_transient) => {
                     if !is_transient {
@@ -532,7 +736,7 @@ impl EmailService {
                             project_id = %project_id,
                             "Webhook returned non-transient error, not retrying"
                         );
-                        return Ok(EmailSendResult::WebhookCalled);
+                        return EmailSendResult::WebhookCalled;
                     }
                     // Transient error - continue to next retry
                 }
@@ -546,35 +750,41 @@ impl EmailService {
             attempts = RETRY_DELAYS.len() + 1,
             "Webhook call failed after all retries - activation code created but webhook not delivered"
         );
-        Ok(EmailSendResult::WebhookCalled)
+        EmailSendResult::WebhookCalled
     }
 
-    /// Send a single webhook request.
+    /// Send a single webhook request, with `X-Paycheck-Timestamp` and
+    /// `X-Paycheck-Signature` headers if the project has a signing secret.
     ///
     /// Returns Ok(()) on success, or Err(is_transient) on failure.
-    async fn send_webhook_request<T: Serialize>(
+    async fn send_webhook_request(
         &self,
         webhook_url: &str,
-        event_name: &str,
-        payload: &T,
+        body: &WebhookBody,
+        webhook_secret: Option<&str>,
     ) -> std::result::Result<(), bool> {
-        let response = self
-            .http_client
+        let mut request = self
+            .webhook_client
             .post(webhook_url)
             .header("Content-Type", "application/json")
-            .header("X-Paycheck-Event", event_name)
-            .json(payload)
-            .send()
-            .await
-            .map_err(|e| {
-                tracing::error!(
-                    error = %e,
-                    webhook_url = %webhook_url,
-                    "Failed to send webhook request"
-                );
-                // Network errors are transient
-                true
-            })?;
+            .header("X-Paycheck-Event", &body.event_header);
+        if let Some(secret) = webhook_secret {
+            // Signed per attempt, so a retry isn't turned away as a replay
+            let timestamp = chrono::Utc::now().timestamp();
+            request = request
+                .header("X-Paycheck-Timestamp", timestamp.to_string())
+                .header("X-Paycheck-Signature", body.signature(secret, timestamp));
+        }
+        let response = request.body(body.json.clone()).send().await.map_err(|e| {
+            tracing::error!(
+                error = %e,
+                webhook_url = %webhook_url,
+                "Failed to send webhook request"
+            );
+            // Network errors are transient, but a refused address or
+            // redirect will be refused again
+            !external_url::is_refused(&e)
+        })?;
 
         let status = response.status();
 
@@ -613,14 +823,14 @@ impl EmailService {
     pub async fn send_multi_license_activation_codes(
         &self,
         config: MultiLicenseEmailConfig<'_>,
-    ) -> Result<EmailSendResult> {
+    ) -> EmailSendResult {
         // Check if email is disabled for this project
         if !config.project.email_enabled {
             tracing::debug!(
                 project_id = %config.project.id,
                 "Email disabled for project, skipping activation code email"
             );
-            return Ok(EmailSendResult::Disabled);
+            return EmailSendResult::Disabled;
         }
 
         // If webhook URL is configured, POST to it instead of sending email
@@ -636,14 +846,15 @@ impl EmailService {
                 project_id = %config.project.id,
                 "No Resend API key available (system or org level), cannot send email"
             );
-            return Ok(EmailSendResult::NoApiKey);
+            return EmailSendResult::NoApiKey;
         };
 
-        // Determine from address: project-level or system default
+        // Determine from address: project-level, org-level, or system default
         let from_email = config
             .project
             .email_from
             .as_deref()
+            .or(config.org_from_email)
             .unwrap_or(&self.default_from_email);
 
         self.send_multi_license_via_resend(api_key, from_email, &config)
@@ -656,7 +867,7 @@ impl EmailService {
         api_key: &str,
         from_email: &str,
         config: &MultiLicenseEmailConfig<'_>,
-    ) -> Result<EmailSendResult> {
+    ) -> EmailSendResult {
         let subject = format!("Your licenses for {}", config.project_name);
 
         // Build text version
@@ -731,37 +942,21 @@ impl EmailService {
         &self,
         webhook_url: &str,
         config: &MultiLicenseEmailConfig<'_>,
-    ) -> Result<EmailSendResult> {
+    ) -> EmailSendResult {
         let now = chrono::Utc::now().timestamp();
         let expires_at = now + (config.expires_in_minutes as i64 * 60);
 
-        let payload = MultiLicenseWebhookPayload {
-            event: "activation_codes_created",
+        let event = ActivationCodesCreated {
             email: config.to_email,
             expires_at,
             expires_in_minutes: config.expires_in_minutes,
             project_id: &config.project.id,
             project_name: config.project_name,
-            licenses: config
-                .licenses
-                .iter()
-                .map(|l| WebhookLicenseInfo {
-                    product_name: l.product_name.clone(),
-                    code: l.code.clone(),
-                    license_id: l.license_id.clone(),
-                    purchased_at: l.purchased_at,
-                })
-                .collect(),
+            licenses: &config.licenses,
             trigger: config.trigger,
         };
-
-        self.call_webhook_with_retry(
-            webhook_url,
-            "activation_codes_created",
-            &payload,
-            &config.project.id,
-        )
-        .await
+        self.deliver_webhook(config.project, webhook_url, &event, config.webhook_secret)
+            .await
     }
 }
 
@@ -783,6 +978,22 @@ mod tests {
             serde_json::to_string(&EmailTrigger::AdminGenerated).unwrap(),
             "\"admin_generated\""
         );
+        assert_eq!(
+            serde_json::to_string(&EmailTrigger::DormantNudge).unwrap(),
+            "\"dormant_nudge\""
+        );
+    }
+
+    #[test]
+    fn test_email_trigger_round_trips_through_db_form() {
+        for trigger in [
+            EmailTrigger::Purchase,
+            EmailTrigger::RecoveryRequest,
+            EmailTrigger::AdminGenerated,
+            EmailTrigger::DormantNudge,
+        ] {
+            assert_eq!(trigger.as_ref().parse::<EmailTrigger>().unwrap(), trigger);
+        }
     }
 
     #[test]
@@ -1,13 +1,17 @@
 use axum::{
     Json,
-    extract::rejection::{JsonRejection, PathRejection, QueryRejection},
-    http::StatusCode,
+    extract::rejection::{FormRejection, JsonRejection, PathRejection, QueryRejection},
+    http::{StatusCode, header},
     response::{IntoResponse, Response},
 };
 use axum_extra::typed_header::TypedHeaderRejection;
 use serde::Serialize;
 use thiserror::Error;
 
+use crate::middleware::RequestId;
+use crate::models::{Availability, CurrentTerms, OrgLimitName, Revocation, VersionConflict};
+use crate::payments::PaymentError;
+
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("Not found: {0}")]
@@ -37,9 +41,20 @@ pub enum AppError {
     #[error("JSON body error: {0}")]
     JsonBody(#[from] JsonRejection),
 
+    /// Request body parsed but a field didn't fit the expected shape
+    #[error("Invalid body field {}: {}", .0.path, .0.message)]
+    InvalidBodyField(FieldError),
+
+    /// Query string parsed but a parameter didn't fit the expected shape
+    #[error("Invalid query parameter {}: {}", .0.path, .0.message)]
+    InvalidQueryField(FieldError),
+
     #[error("Query error: {0}")]
     Query(#[from] QueryRejection),
 
+    #[error("Form body error: {0}")]
+    Form(#[from] FormRejection),
+
     #[error("Path error: {0}")]
     Path(#[from] PathRejection),
 
@@ -64,13 +79,159 @@ pub enum AppError {
 
     #[error("User not found")]
     UserNotFound,
+
+    /// Purchase attempted outside the product's sale window
+    #[error("Product is not available for purchase")]
+    ProductUnavailable {
+        availability: Availability,
+        available_from: Option<i64>,
+        available_until: Option<i64>,
+    },
+
+    /// Outbound payment provider calls are saturated; retry after the given seconds
+    #[error("Payment provider busy")]
+    ProviderBusy { retry_after_secs: u64 },
+
+    /// A payment provider call failed (logged where it was classified)
+    #[error(transparent)]
+    Payment(#[from] PaymentError),
+
+    /// License exceeded its project's hourly validation limit; retry after the given seconds
+    #[error("License validation throttled")]
+    ValidationThrottled { retry_after_secs: u64 },
+
+    /// Request body is larger than the route's limit
+    #[error("Payload too large")]
+    PayloadTooLarge,
+
+    /// Too many free license claims from this client or email
+    #[error("Free license claims throttled")]
+    FreeLicenseThrottled,
+
+    /// Too many /buy/wait polls for this payment session or from this client
+    #[error("Payment session waits throttled")]
+    SessionWaitThrottled,
+
+    /// Create refused because it would take the org past an operator-set hard limit
+    #[error("Org limit reached: {limit_name:?}")]
+    QuotaExceeded {
+        limit_name: OrgLimitName,
+        hard_value: i64,
+    },
+
+    /// Customer action on a revoked license; carries the reason they're shown
+    #[error("License is revoked")]
+    LicenseRevoked(Revocation),
+
+    /// Download of a release newer than the license's updates window; carries
+    /// the renewal products that would extend it
+    #[error("Updates expired")]
+    UpdatesExpired {
+        updates_expires_at: i64,
+        renewal_product_ids: Vec<String>,
+    },
+
+    /// Purchase by a buyer who already has an active license for the product
+    #[error("Already licensed")]
+    AlreadyLicensed { license_created_at: i64 },
+
+    /// Purchase that didn't accept the project's current terms; carries them
+    #[error("Terms not accepted")]
+    TermsNotAccepted(CurrentTerms),
+
+    /// Update whose `expected_version` is no longer the row's version;
+    /// carries the current version and what changed
+    #[error("Version conflict")]
+    VersionConflict(VersionConflict),
+
+    /// Request held back by maintenance mode; carries the operator's message
+    #[error("Maintenance mode: {message}")]
+    Maintenance {
+        message: String,
+        retry_after_secs: u64,
+    },
 }
 
-#[derive(Serialize)]
+#[derive(Default, Serialize)]
 struct ErrorResponse {
     error: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     details: Option<String>,
+    /// Machine-readable code, for errors clients are expected to handle
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<&'static str>,
+    /// Sale window, for `product_unavailable`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    window: Option<SaleWindow>,
+    /// Why the license was revoked, for `license_revoked`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    revocation: Option<Revocation>,
+    /// The field at fault, for `invalid_body_field` and `invalid_query_field`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    field: Option<FieldError>,
+    /// The buyer's existing license, for `already_licensed`
+    #[serde(flatten)]
+    already_licensed: Option<AlreadyLicensed>,
+    /// The terms to accept, for `terms_not_accepted`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    current_terms: Option<CurrentTerms>,
+    /// When updates ended and how to renew, for `updates_expired`
+    #[serde(flatten)]
+    updates_expired: Option<UpdatesExpired>,
+    /// The current version and the fields that differ, for `version_conflict`
+    #[serde(flatten)]
+    version_conflict: Option<VersionConflict>,
+    /// Same as the `X-Request-Id` response header, for quoting in support requests
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+}
+
+/// A field of a request body or query string that failed to deserialize.
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    /// Path to the field, like `scopes[1].access`; `.` is the whole body
+    pub path: String,
+    /// serde's message, shown in `details`
+    #[serde(skip)]
+    pub message: String,
+    /// What the field should hold, when serde says
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected: Option<String>,
+    /// The offending value as JSON, truncated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+}
+
+impl FieldError {
+    fn details(&self) -> String {
+        if self.path == "." {
+            self.message.clone()
+        } else {
+            format!("{}: {}", self.path, self.message)
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct AlreadyLicensed {
+    already_licensed: bool,
+    license_created_at: i64,
+    recovery_hint: &'static str,
+}
+
+#[derive(Serialize)]
+struct UpdatesExpired {
+    updates_expires_at: i64,
+    /// Updates-only renewal products on sale for the license's product;
+    /// pass one to /buy to extend the window
+    renewal_product_ids: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct SaleWindow {
+    availability: Availability,
+    available_from: Option<i64>,
+    available_until: Option<i64>,
 }
 
 impl From<StatusCode> for AppError {
@@ -116,11 +277,26 @@ impl IntoResponse for AppError {
                 "Invalid request body",
                 Some(e.body_text()),
             ),
+            AppError::InvalidBodyField(field) => (
+                StatusCode::BAD_REQUEST,
+                "Invalid request body",
+                Some(field.details()),
+            ),
+            AppError::InvalidQueryField(field) => (
+                StatusCode::BAD_REQUEST,
+                "Invalid query parameters",
+                Some(field.details()),
+            ),
             AppError::Query(e) => (
                 StatusCode::BAD_REQUEST,
                 "Invalid query parameters",
                 Some(e.body_text()),
             ),
+            AppError::Form(e) => (
+                StatusCode::BAD_REQUEST,
+                "Invalid form body",
+                Some(e.body_text()),
+            ),
             AppError::Path(e) => (
                 StatusCode::BAD_REQUEST,
                 "Invalid path parameters",
@@ -160,14 +336,197 @@ impl IntoResponse for AppError {
                 (StatusCode::UNAUTHORIZED, "Invalid token", Some(msg.clone()))
             }
             AppError::UserNotFound => (StatusCode::UNAUTHORIZED, "User not found", None),
+            AppError::ProductUnavailable { availability, .. } => {
+                let details = match availability {
+                    Availability::ComingSoon => msg::PRODUCT_NOT_YET_AVAILABLE,
+                    _ => msg::PRODUCT_NO_LONGER_AVAILABLE,
+                };
+                (StatusCode::BAD_REQUEST, "Bad request", Some(details.into()))
+            }
+            AppError::ProviderBusy { .. } => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Service unavailable",
+                Some(msg::PROVIDER_BUSY.into()),
+            ),
+            AppError::Payment(e) => match e {
+                PaymentError::InvalidConfig(_) => (
+                    StatusCode::BAD_REQUEST,
+                    "Bad request",
+                    Some(msg::PROVIDER_CONFIG_INVALID.into()),
+                ),
+                PaymentError::ProviderRejected { message, .. } => (
+                    StatusCode::BAD_REQUEST,
+                    "Bad request",
+                    Some(format!("{}: {}", msg::PROVIDER_REJECTED, message)),
+                ),
+                _ => (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "Service unavailable",
+                    Some(msg::PROVIDER_UNAVAILABLE.into()),
+                ),
+            },
+            AppError::ValidationThrottled { .. } => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "Too many requests",
+                Some(msg::VALIDATION_THROTTLED.into()),
+            ),
+            AppError::PayloadTooLarge => (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "Payload too large",
+                Some(msg::BODY_TOO_LARGE.into()),
+            ),
+            AppError::FreeLicenseThrottled => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "Too many requests",
+                Some(msg::FREE_LICENSE_THROTTLED.into()),
+            ),
+            AppError::SessionWaitThrottled => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "Too many requests",
+                Some(msg::SESSION_WAIT_THROTTLED.into()),
+            ),
+            AppError::QuotaExceeded {
+                limit_name,
+                hard_value,
+            } => (
+                StatusCode::FORBIDDEN,
+                "Forbidden",
+                Some(format!(
+                    "{}: {} is capped at {}",
+                    msg::QUOTA_EXCEEDED,
+                    limit_name.as_ref(),
+                    hard_value
+                )),
+            ),
+            AppError::LicenseRevoked(_) => (
+                StatusCode::FORBIDDEN,
+                "Forbidden",
+                Some(msg::LICENSE_REVOKED.into()),
+            ),
+            AppError::UpdatesExpired { .. } => (
+                StatusCode::FORBIDDEN,
+                "Forbidden",
+                Some(msg::UPDATES_EXPIRED_FOR_RELEASE.into()),
+            ),
+            AppError::AlreadyLicensed { .. } => (
+                StatusCode::CONFLICT,
+                "Conflict",
+                Some(msg::ALREADY_LICENSED.into()),
+            ),
+            AppError::TermsNotAccepted(_) => (
+                StatusCode::BAD_REQUEST,
+                "Bad request",
+                Some(msg::TERMS_NOT_ACCEPTED.into()),
+            ),
+            AppError::VersionConflict(_) => (
+                StatusCode::CONFLICT,
+                "Conflict",
+                Some(msg::VERSION_CONFLICT.into()),
+            ),
+            AppError::Maintenance { message, .. } => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Service unavailable",
+                Some(message.clone()),
+            ),
+        };
+
+        let retry_after = match &self {
+            AppError::ProviderBusy { retry_after_secs }
+            | AppError::ValidationThrottled { retry_after_secs }
+            | AppError::Maintenance {
+                retry_after_secs, ..
+            } => Some(*retry_after_secs),
+            AppError::Payment(e) => e.retry_after_secs(),
+            _ => None,
         };
 
+        let body = match self {
+            AppError::ProductUnavailable {
+                availability,
+                available_from,
+                available_until,
+            } => ErrorResponse {
+                code: Some("product_unavailable"),
+                window: Some(SaleWindow {
+                    availability,
+                    available_from,
+                    available_until,
+                }),
+                ..Default::default()
+            },
+            AppError::LicenseRevoked(revocation) => ErrorResponse {
+                code: Some("license_revoked"),
+                revocation: Some(revocation),
+                ..Default::default()
+            },
+            AppError::Payment(e) => ErrorResponse {
+                code: Some(e.code()),
+                ..Default::default()
+            },
+            AppError::QuotaExceeded { .. } => ErrorResponse {
+                code: Some("quota_exceeded"),
+                ..Default::default()
+            },
+            AppError::Maintenance { .. } => ErrorResponse {
+                code: Some("maintenance"),
+                ..Default::default()
+            },
+            AppError::InvalidBodyField(field) => ErrorResponse {
+                code: Some("invalid_body_field"),
+                field: Some(field),
+                ..Default::default()
+            },
+            AppError::InvalidQueryField(field) => ErrorResponse {
+                code: Some("invalid_query_field"),
+                field: Some(field),
+                ..Default::default()
+            },
+            AppError::AlreadyLicensed { license_created_at } => ErrorResponse {
+                code: Some("already_licensed"),
+                already_licensed: Some(AlreadyLicensed {
+                    already_licensed: true,
+                    license_created_at,
+                    recovery_hint: msg::ALREADY_LICENSED_RECOVERY_HINT,
+                }),
+                ..Default::default()
+            },
+            AppError::TermsNotAccepted(terms) => ErrorResponse {
+                code: Some("terms_not_accepted"),
+                current_terms: Some(terms),
+                ..Default::default()
+            },
+            AppError::UpdatesExpired {
+                updates_expires_at,
+                renewal_product_ids,
+            } => ErrorResponse {
+                code: Some("updates_expired"),
+                updates_expired: Some(UpdatesExpired {
+                    updates_expires_at,
+                    renewal_product_ids,
+                }),
+                ..Default::default()
+            },
+            AppError::VersionConflict(conflict) => ErrorResponse {
+                code: Some("version_conflict"),
+                version_conflict: Some(conflict),
+                ..Default::default()
+            },
+            _ => ErrorResponse::default(),
+        };
         let body = ErrorResponse {
             error: error.to_string(),
             details,
+            request_id: RequestId::current().map(|id| id.0),
+            ..body
         };
 
-        (status, Json(body)).into_response()
+        let mut response = (status, Json(body)).into_response();
+        if let Some(secs) = retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, header::HeaderValue::from(secs));
+        }
+        response
     }
 }
 
@@ -197,11 +556,24 @@ pub mod msg {
     pub const SESSION_NOT_FOUND: &str = "Session not found";
     pub const PAYMENT_CONFIG_NOT_FOUND: &str = "Payment config not found";
     pub const PROVIDER_LINK_NOT_FOUND: &str = "Provider link not found";
+    pub const SEAT_NOT_FOUND: &str = "Seat not found";
+    pub const SHARE_LINK_NOT_FOUND: &str = "Share link not found";
+    pub const PREPAID_BATCH_NOT_FOUND: &str = "Prepaid code batch not found";
+    pub const BULK_JOB_NOT_FOUND: &str = "Bulk license job not found";
 
     // Membership checks
     pub const NOT_ORG_MEMBER: &str = "User is not a member of this org";
     pub const NOT_PROJECT_MEMBER: &str = "User is not a member of this project";
     pub const NOT_OPERATOR: &str = "User is not an operator";
+    pub const OPERATOR_NOT_ORG_SCOPED: &str =
+        "Only partner operators can be scoped to organizations";
+    pub const OPERATOR_ORG_SCOPE_EXISTS: &str = "Operator already has access to this organization";
+    pub const OPERATOR_ORG_SCOPE_NOT_FOUND: &str = "Operator has no access to this organization";
+    pub const PARTNER_AUDIT_LOGS_NEED_ORG: &str =
+        "Partner operators must filter audit logs by org_id";
+    pub const AUDIT_ARCHIVE_RANGE_INVALID: &str = "from must not be after to";
+    pub const PARTNER_AUDIT_CHAIN_FORBIDDEN: &str =
+        "Partner operators cannot verify the audit log chain";
     pub const ORG_MEMBER_NOT_FOUND: &str = "Org member not found";
 
     // Soft-deleted resources
@@ -214,6 +586,10 @@ pub mod msg {
 
     // Device-specific
     pub const DEVICE_NOT_FOUND_OR_DEACTIVATED: &str = "Device not found or already deactivated";
+    pub const DEVICE_FILTER_REQUIRED: &str =
+        "Filter by activated_after, activated_before, or device_type";
+    pub const DEVICE_DEACTIVATION_UNCONFIRMED: &str =
+        "Set confirm to true to deactivate devices across a project";
 
     // Permission errors
     pub const INSUFFICIENT_PERMISSIONS: &str = "Insufficient permissions";
@@ -222,8 +598,28 @@ pub mod msg {
 
     // Self-action restrictions
     pub const CANNOT_DELETE_SELF: &str = "Cannot delete yourself";
+    pub const CANNOT_MERGE_SELF: &str = "Cannot merge yourself into another user";
     pub const CANNOT_CHANGE_OWN_ROLE: &str = "Cannot change your own role";
 
+    // User merges
+    pub const MERGE_SAME_USER: &str = "source_user_id must be a different user";
+    pub const MERGE_SOURCE_NOT_FOUND: &str = "Source user not found";
+    pub const USER_MERGED: &str = "User was merged into another user and can't be restored";
+
+    // Scheduled member removal
+    pub const GRACE_HOURS_INVALID: &str = "grace_hours must be between 0 and 720";
+    pub const MEMBER_REMOVAL_NOT_SCHEDULED: &str = "No removal is scheduled for this member";
+
+    // Temporary project role errors
+    pub const TEMPORARY_ROLE_DURATION_INVALID: &str =
+        "expires_in_minutes must be between 1 and 1440";
+    pub const TEMPORARY_ROLE_NOT_NEEDED: &str =
+        "Org owners and admins already have access to every project";
+    pub const TEMPORARY_ROLE_NOT_FOUND: &str = "Member has no active temporary role";
+    pub const CANNOT_GRANT_SELF_TEMPORARY_ROLE: &str = "Cannot grant yourself a temporary role";
+    pub const TEMPORARY_ROLE_CANNOT_MANAGE_MEMBERS: &str =
+        "A temporary role cannot manage project members";
+
     // Validation errors
     pub const EMAIL_ALREADY_EXISTS: &str = "Email already exists";
     pub const TOKEN_MISSING_JTI: &str = "Token missing JTI";
@@ -234,6 +630,41 @@ pub mod msg {
     pub const LS_NOT_CONFIGURED: &str = "LemonSqueezy not configured";
     pub const NO_PRICE_CONFIGURED: &str = "Payment config has no price_cents configured.";
     pub const NO_VARIANT_CONFIGURED: &str = "Payment config has no ls_variant_id configured.";
+    pub const PROVIDER_BUSY: &str = "Payment provider is busy, try again shortly";
+    pub const PROVIDER_CONFIG_INVALID: &str =
+        "The payment provider refused this organization's credentials";
+    pub const PROVIDER_REJECTED: &str = "The payment provider rejected the checkout";
+    pub const PROVIDER_UNAVAILABLE: &str = "Payment provider is unavailable, try again shortly";
+
+    // Subscription reconciliation errors
+    pub const RECONCILIATION_RUN_NOT_FOUND: &str = "Reconciliation run not found";
+
+    // Database backup errors
+    pub const BACKUPS_NOT_CONFIGURED: &str =
+        "Backups are not configured (set PAYCHECK_BACKUP_DIR)";
+    pub const BACKUP_NOT_FOUND: &str = "Backup not found";
+
+    // License key import errors
+    pub const IMPORT_PRODUCTS_REQUIRED: &str =
+        "products must map at least one LemonSqueezy product ID to a product";
+
+    // Maintenance mode errors
+    pub const MAINTENANCE_MESSAGE_INVALID: &str =
+        "message must be between 1 and 500 characters";
+
+    // Webhook mirror errors
+    pub const WEBHOOK_MIRROR_DAYS_INVALID: &str = "expires_in_days must be between 1 and 30";
+
+    // Custom domain errors
+    pub const CUSTOM_DOMAIN_INVALID: &str =
+        "hostname must be a domain name, like licenses.example.com";
+    pub const CUSTOM_DOMAIN_IS_BASE_URL: &str = "hostname is Paycheck's own host (BASE_URL)";
+    pub const CUSTOM_DOMAIN_TAKEN: &str = "hostname is already bound to a project";
+    pub const CUSTOM_DOMAIN_NOT_FOUND: &str = "Custom domain not found";
+    pub const CUSTOM_DOMAIN_PROJECT_MISMATCH: &str =
+        "X-Paycheck-Project header names a different project than this domain serves";
+    pub const CUSTOM_DOMAIN_LOOKUP_FAILED: &str =
+        "Couldn't look up the TXT record, try again shortly";
 
     // Post-operation errors (for consistency in error messages after mutations)
     pub const USER_NOT_FOUND_AFTER_RESTORE: &str = "User not found after restore";
@@ -255,6 +686,194 @@ pub mod msg {
     // License state errors
     pub const LICENSE_REVOKED: &str = "License is revoked";
     pub const LICENSE_ALREADY_REVOKED: &str = "License is already revoked";
+    pub const LICENSE_SUSPENDED_FOR_DISPUTE: &str =
+        "License is suspended while a payment dispute is open";
+    pub const REVOKED_MESSAGE_TOO_LONG: &str = "message must be at most 500 characters";
+    pub const LICENSE_NOT_SEAT_BASED: &str = "License does not have seats";
+    pub const SEAT_ALREADY_ASSIGNED: &str = "Email already holds a seat on this license";
+
+    // Share link errors
+    pub const SHARE_LINK_UNAVAILABLE: &str = "Share link is invalid, expired, or revoked";
+    pub const SHARE_LINK_ALREADY_REVOKED: &str = "Share link is already revoked";
+    pub const SHARE_LINK_EMAIL_MISMATCH: &str = "email does not match the license";
+    pub const SHARE_LINK_EXPIRY_INVALID: &str = "expires_in_hours must be between 1 and 720";
+
+    // License attestation errors
+    pub const ATTESTATION_AUDIENCE_NOT_ALLOWED: &str =
+        "audience is not in the project's attestation_audiences";
+    pub const LICENSE_NOT_VALID: &str = "License is not valid";
+
+    // Prepaid code errors
+    pub const PREPAID_CODE_COUNT_INVALID: &str = "count must be between 1 and 10000";
+    pub const PREPAID_BATCH_LABEL_TOO_LONG: &str = "label must be at most 100 characters";
+    pub const PREPAID_CODE_EXPIRY_INVALID: &str = "expires_in_days must be at least 1";
+    pub const PREPAID_CODES_ALREADY_DOWNLOADED: &str =
+        "The codes in this batch have already been downloaded";
+    pub const PREPAID_BATCH_ALREADY_REVOKED: &str = "Prepaid code batch is already revoked";
+
+    // License tag errors
+    pub const TAG_INVALID: &str = "Tags must be 1-40 characters of a-z, 0-9, '-' or '_'";
+    pub const TAGS_EMPTY: &str = "tags cannot be empty";
+    pub const TOO_MANY_TAGS: &str = "A license can have at most 20 tags";
+    pub const TAG_FILTER_REQUIRED: &str = "filter must set at least one field";
+
+    // Entitlement check errors
+    pub const ENTITLEMENT_CUSTOMER_REQUIRED: &str = "Provide exactly one of customer_id or email";
+    pub const ENTITLEMENT_CHECK_EMPTY: &str = "customer_ids and features cannot be empty";
+    pub const ENTITLEMENT_CHECK_TOO_LARGE: &str =
+        "A bulk entitlement check can cover at most 100 customer_ids and 50 features";
+    pub const TOO_MANY_ENTITLEMENTS: &str = "A product can have at most 50 entitlements";
+    pub const ENTITLEMENT_NAME_INVALID: &str =
+        "Entitlement names must be 1-64 characters of a-z, 0-9, '_' and '-'";
+
+    // Publishable key errors
+    pub const PUBLIC_KEY_REQUIRED: &str =
+        "public_key is required (X-Paycheck-Project header or public_key field)";
+    pub const PROJECT_KEY_MISMATCH: &str =
+        "X-Paycheck-Project header does not match the request's public_key";
+    pub const PROJECT_KEY_HEADER_INVALID: &str = "Invalid X-Paycheck-Project header";
+
+    // Link checkout errors
+    pub const LINK_CHECKOUT_DISABLED: &str =
+        "Purchase links are not enabled for this project (allow_link_checkout)";
+    pub const LINK_CHECKOUT_SCRIPTED: &str =
+        "GET /buy only serves browser navigation; use POST from scripts";
+
+    // License validation throttling
+    pub const VALIDATION_THROTTLED: &str =
+        "This license has exceeded its hourly validation limit, try again later";
+    pub const VALIDATION_LIMIT_INVALID: &str =
+        "max_validations_per_hour_per_license must be at least 1";
+
+    // Dormant licenses
+    pub const DORMANT_AFTER_DAYS_INVALID: &str = "dormant_after_days must be at least 1";
+
+    // Email webhook signing
+    pub const EMAIL_WEBHOOK_SECRET_INVALID: &str =
+        "email_webhook_secret must be 32 to 256 characters without whitespace";
+
+    // Org compliance settings
+    pub const AUDIT_RETENTION_DAYS_INVALID: &str =
+        "public_audit_retention_days and admin_audit_retention_days must be at least 1";
+
+    // Upgrade purchase errors
+    pub const UPGRADE_LICENSE_NOT_FOUND: &str = "License to upgrade not found";
+    pub const UPGRADE_LICENSE_INACTIVE: &str = "License to upgrade is revoked or expired";
+    pub const UPGRADE_CUSTOMER_MISMATCH: &str =
+        "customer_id does not match the license to upgrade";
+    pub const UPGRADE_SAME_PRODUCT: &str = "License is already for this product";
+
+    // Free product errors
+    pub const FREE_PRODUCT_EMAIL_REQUIRED: &str =
+        "email is required to claim a free product (it's how the license is recovered)";
+    pub const FREE_PRODUCT_NO_UPGRADE: &str = "A free product can't be bought as an upgrade";
+    pub const FREE_LICENSE_ALREADY_CLAIMED: &str =
+        "This email has already claimed a license for this product";
+    pub const FREE_LICENSE_THROTTLED: &str = "Too many free license claims, try again later";
+
+    // Payment session wait and claim errors
+    pub const SESSION_WAIT_THROTTLED: &str =
+        "Too many waits on this payment session, try again later";
+    pub const CLAIM_CODE_INVALID: &str = "Claim code is invalid, expired, or already used";
+
+    // Duplicate purchase errors
+    pub const ALREADY_LICENSED: &str =
+        "An active license for this product already exists; set force_new_purchase to buy another";
+    pub const ALREADY_LICENSED_RECOVERY_HINT: &str = "an activation code can be sent to your email";
+
+    // Org limit errors
+    pub const ORG_LIMIT_NEGATIVE: &str = "soft_value and hard_value cannot be negative";
+    pub const ORG_LIMIT_SOFT_ABOVE_HARD: &str = "soft_value cannot be greater than hard_value";
+    pub const INVALID_ORG_LIMIT: &str =
+        "Invalid limit. Must be 'projects', 'products_per_project', or 'licenses_per_month'";
+    pub const QUOTA_EXCEEDED: &str = "This organization has reached its limit";
+
+    // Sale window errors
+    pub const SALE_WINDOW_INVALID: &str = "available_from must be before available_until";
+    pub const PRODUCT_NOT_YET_AVAILABLE: &str = "Product is not on sale yet";
+    pub const PRODUCT_NO_LONGER_AVAILABLE: &str = "Product is no longer on sale";
+
+    // Checkout field errors
+    pub const TOO_MANY_CHECKOUT_FIELDS: &str = "A product can have at most 10 checkout fields";
+    pub const CHECKOUT_FIELD_KEY_INVALID: &str =
+        "Checkout field keys must be 1-32 characters of a-z, 0-9 or '_'";
+    pub const CHECKOUT_FIELD_KEY_DUPLICATE: &str = "Checkout field keys must be unique";
+    pub const CHECKOUT_FIELD_LABEL_INVALID: &str = "Checkout field labels must be 1-100 characters";
+    pub const TOO_MANY_CHECKOUT_METADATA_KEYS: &str =
+        "checkout_metadata_keys can have at most 20 entries";
+    pub const CHECKOUT_METADATA_KEY_INVALID: &str =
+        "checkout_metadata_keys entries must be 1-35 characters of a-z, 0-9 or '_'";
+
+    // Updates-only renewal errors
+    pub const UPDATES_RENEWAL_NEEDS_UPDATES_DAYS: &str =
+        "An updates-only renewal needs updates_exp_days of at least 1";
+    pub const UPDATES_RENEWAL_FREE: &str = "An updates-only renewal can't be free";
+    pub const UPDATES_RENEWAL_BASE_INVALID: &str =
+        "extends_updates_for_product_id must be another product in this project that isn't itself a renewal";
+    pub const UPDATES_RENEWAL_IS_BASE: &str =
+        "Other products renew this product's updates, so it can't be a renewal itself";
+
+    // Upsell errors
+    pub const UPSELL_BLURB_TOO_LONG: &str = "marketing_blurb must be at most 280 characters";
+    pub const UPSELL_TARGET_INVALID: &str =
+        "upgrade_to_product_id must be another product in this project that isn't an updates-only renewal";
+    pub const UPSELL_HIGHLIGHTS_NOT_FEATURES: &str =
+        "highlight_features must all be features of the upgrade_to_product_id product";
+    pub const UPSELL_HIGHLIGHTS_IN_USE: &str =
+        "Another product's upsell highlights a feature this change removes; update that upsell first";
+
+    // Bulk license job errors
+    pub const LICENSE_COUNT_INVALID: &str = "Count must be between 1 and 10000";
+    pub const BULK_JOB_NOT_RUNNING: &str = "Bulk license job is no longer running";
+    pub const BULK_JOB_STILL_RUNNING: &str = "Bulk license job is still running";
+    pub const BULK_JOB_NO_CODES: &str =
+        "This job wasn't started with activation_codes; its license IDs are in the job";
+    pub const BULK_CHUNK_FAILED: &str = "Failed to create this chunk's licenses";
+    pub const BULK_JOB_INTERRUPTED: &str = "Interrupted by a server restart";
+
+    // Product template errors
+    pub const PRODUCT_TEMPLATE_NOT_FOUND: &str = "Product template not found";
+    pub const PRODUCT_TEMPLATES_EMPTY: &str = "templates must list at least one template";
+
+    // Workspace errors
+    pub const WORKSPACE_TEMPLATES_UNSUPPORTED: &str =
+        "A new org has no product templates; list the products instead";
+    pub const WORKSPACE_RENEWAL_UNSUPPORTED: &str =
+        "extends_updates_for_product_id can't be set when creating a workspace; add the renewal product afterwards";
+    pub const WORKSPACE_DUPLICATE_PROVIDER_LINK: &str =
+        "A product can only have one link per provider";
+    pub const WORKSPACE_RESIDENCY_UNSUPPORTED: &str =
+        "Workspaces can't be created in one step while data residency is enabled; create the org, then its projects";
+
+    // Terms of sale errors
+    pub const TERMS_NOT_FOUND: &str = "Terms version not found";
+    pub const TERMS_VERSION_INVALID: &str = "version must be between 1 and 50 characters";
+    pub const TERMS_VERSION_EXISTS: &str = "This project already has terms with this version";
+    pub const TERMS_IN_USE: &str =
+        "Licenses were sold under this terms version, so it can't change; publish a new version instead";
+    pub const TERMS_NOT_ACCEPTED: &str =
+        "Send the current terms version as accepted_terms_version to buy";
+
+    // Release artifact errors
+    pub const ARTIFACT_NOT_FOUND: &str = "Artifact not found";
+    pub const ARTIFACT_NAME_INVALID: &str = "name must be between 1 and 200 characters";
+    pub const UPDATES_EXPIRED_FOR_RELEASE: &str =
+        "This release came out after the license's updates ended; renew updates to download it";
+
+    // Optimistic locking errors
+    pub const VERSION_CONFLICT: &str =
+        "Changed since expected_version; review the conflicts and retry with current_version";
+
+    // Client flag errors
+    pub const MIN_CLIENT_VERSION_INVALID: &str =
+        "min_client_version must be a version like 2.1.0 (1 to 4 numbers separated by dots)";
+
+    // Trial conversion errors
+    pub const CONVERSION_STATS_RANGE_INVALID: &str = "from must be before to";
+
+    // Usage geography errors
+    pub const USAGE_GEO_RANGE_INVALID: &str = "from can't be after to";
+    pub const USAGE_GEO_RANGE_TOO_LONG: &str = "The range can be at most 366 days";
 
     // Token validation errors
     pub const INVALID_TOKEN_PRODUCT: &str = "Invalid token: product not found";
@@ -265,6 +884,7 @@ pub mod msg {
     pub const INVALID_ORG_PROVIDER: &str = "Invalid payment_provider in organization";
     pub const INVALID_DEVICE_TYPE: &str = "Invalid device_type. Must be 'uuid' or 'machine'";
     pub const DEVICE_ID_EMPTY: &str = "device_id cannot be empty";
+    pub const BODY_TOO_LARGE: &str = "Request body exceeds this endpoint's size limit";
     pub const CANNOT_HARD_DELETE_SELF: &str = "Cannot hard delete yourself";
 
     // Contextual not found
@@ -274,15 +894,20 @@ pub mod msg {
     // Model validation errors
     pub const NAME_EMPTY: &str = "name cannot be empty";
     pub const TIER_EMPTY: &str = "tier cannot be empty";
+    pub const SEAT_COUNT_INVALID: &str = "seat_count must be at least 1";
     pub const EMAIL_EMPTY: &str = "email cannot be empty";
     pub const INVALID_EMAIL_FORMAT: &str = "invalid email format";
     pub const EMAIL_FROM_REQUIRES_ORG_RESEND_KEY: &str =
         "email_from requires the organization to have a resend_api_key configured";
+    pub const RESEND_API_KEY_EMPTY: &str = "resend_api_key cannot be empty";
+    pub const ORG_RESEND_KEY_NOT_CONFIGURED: &str =
+        "No Resend API key is configured for this organization";
 
     // JWT/Token errors
     pub const INVALID_TOKEN_FORMAT: &str = "Invalid token format";
     pub const INVALID_TOKEN_ENCODING: &str = "Invalid token encoding";
     pub const INVALID_TOKEN_PAYLOAD: &str = "Invalid token payload";
+    pub const INVALID_TOKEN_HEADER: &str = "Invalid token header";
     pub const INVALID_PRIVATE_KEY_LENGTH: &str = "Invalid private key length";
     pub const INVALID_PUBLIC_KEY_LENGTH: &str = "Invalid public key length";
     pub const FAILED_TO_CONVERT_KEY_BYTES: &str = "Failed to convert key bytes";
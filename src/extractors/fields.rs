@@ -0,0 +1,308 @@
+//! Field-level errors for request bodies and query strings.
+//!
+//! Bodies are parsed to a `serde_json::Value` first, so malformed JSON keeps
+//! axum's rejection, then deserialized through `serde_path_to_error` to find
+//! the field that failed: its path (`scopes[1].access`), what serde expected,
+//! and a snippet of the value that was sent.
+
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, MapAccess, SeqAccess, Visitor,
+    value::BorrowedStrDeserializer,
+};
+use serde_json::Value;
+use serde_path_to_error::{Path, Segment};
+
+use crate::error::{AppError, FieldError};
+
+/// Longest value snippet echoed back, in characters
+const MAX_VALUE_CHARS: usize = 60;
+
+/// Deserialize a parsed body. With `strict`, object keys the target struct
+/// doesn't declare are an error instead of being ignored.
+pub(crate) fn from_body<T: DeserializeOwned>(body: &Value, strict: bool) -> Result<T, AppError> {
+    let result = if strict {
+        serde_path_to_error::deserialize(Strict(body))
+    } else {
+        serde_path_to_error::deserialize(body)
+    };
+    result.map_err(|e| {
+        let message = e.inner().to_string();
+        let steps = steps(e.path(), &message);
+        let value = lookup(body, &steps).map(|value| snippet(value.to_string()));
+        AppError::InvalidBodyField(field_error(&steps, message, value))
+    })
+}
+
+/// Deserialize a query string. Unknown parameters are always ignored.
+pub(crate) fn from_query<T: DeserializeOwned>(query: &str) -> Result<T, AppError> {
+    let deserializer =
+        serde_urlencoded::Deserializer::new(form_urlencoded::parse(query.as_bytes()));
+    serde_path_to_error::deserialize(deserializer).map_err(|e| {
+        let message = e.inner().to_string();
+        let steps = steps(e.path(), &message);
+        let value = match steps.first() {
+            Some(Step::Key(name)) => form_urlencoded::parse(query.as_bytes())
+                .find(|(key, _)| &**key == name.as_str())
+                .map(|(_, value)| snippet(Value::from(value.into_owned()).to_string())),
+            _ => None,
+        };
+        AppError::InvalidQueryField(field_error(&steps, message, value))
+    })
+}
+
+#[derive(Debug, PartialEq)]
+enum Step {
+    Key(String),
+    Index(usize),
+}
+
+fn steps(path: &Path, message: &str) -> Vec<Step> {
+    let mut steps: Vec<Step> = path
+        .iter()
+        .filter_map(|segment| match segment {
+            Segment::Seq { index } => Some(Step::Index(*index)),
+            Segment::Map { key } => Some(Step::Key(key.clone())),
+            Segment::Enum { variant } => Some(Step::Key(variant.clone())),
+            Segment::Unknown => None,
+        })
+        .collect();
+
+    // Missing and unknown fields are reported by the struct that has them,
+    // so the path stops one level short
+    if let Some(name) = named_field(message) {
+        let step = Step::Key(name.to_string());
+        if steps.last() != Some(&step) {
+            steps.push(step);
+        }
+    }
+    steps
+}
+
+fn named_field(message: &str) -> Option<&str> {
+    ["missing field `", "unknown field `"]
+        .iter()
+        .find_map(|prefix| message.strip_prefix(prefix))
+        .and_then(|rest| rest.split('`').next())
+}
+
+fn lookup<'a>(body: &'a Value, steps: &[Step]) -> Option<&'a Value> {
+    steps.iter().try_fold(body, |value, step| match step {
+        Step::Key(key) => value.get(key),
+        Step::Index(index) => value.get(index),
+    })
+}
+
+fn snippet(json: String) -> String {
+    if json.chars().count() <= MAX_VALUE_CHARS {
+        return json;
+    }
+    let mut truncated: String = json.chars().take(MAX_VALUE_CHARS).collect();
+    truncated.push('…');
+    truncated
+}
+
+fn field_error(steps: &[Step], message: String, value: Option<String>) -> FieldError {
+    let mut path = String::new();
+    for step in steps {
+        match step {
+            Step::Key(key) if path.is_empty() => path.push_str(key),
+            Step::Key(key) => {
+                path.push('.');
+                path.push_str(key);
+            }
+            Step::Index(index) => path.push_str(&format!("[{}]", index)),
+        }
+    }
+    if path.is_empty() {
+        path.push('.');
+    }
+
+    let expected = message
+        .split_once(", expected ")
+        .map(|(_, expected)| expected.to_string());
+
+    FieldError {
+        path,
+        message,
+        expected,
+        value,
+    }
+}
+
+/// Deserializer over a `Value` that rejects object keys the target struct
+/// doesn't declare, at any depth. Maps, enums, and structs with flattened
+/// fields (which serde reads as maps) stay lenient.
+struct Strict<'de>(&'de Value);
+
+macro_rules! forward_to_value {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                self.0.$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Strict<'de> {
+    type Error = serde_json::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Value::Array(items) => visitor.visit_seq(StrictSeq(items.iter())),
+            Value::Object(map) => visitor.visit_map(StrictMap::new(map)),
+            other => other.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Value::Array(items) => visitor.visit_seq(StrictSeq(items.iter())),
+            other => other.deserialize_seq(visitor),
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Value::Object(map) => visitor.visit_map(StrictMap::new(map)),
+            other => other.deserialize_map(visitor),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Value::Object(map) => {
+                if let Some(key) = map.keys().find(|key| !fields.contains(&key.as_str())) {
+                    return Err(de::Error::unknown_field(key, fields));
+                }
+                visitor.visit_map(StrictMap::new(map))
+            }
+            other => other.deserialize_struct(name, fields, visitor),
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.0.deserialize_unit_struct(name, visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.0.deserialize_enum(name, variants, visitor)
+    }
+
+    forward_to_value! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_i128 deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+        deserialize_u128 deserialize_f32 deserialize_f64 deserialize_char deserialize_str deserialize_string deserialize_bytes
+        deserialize_byte_buf deserialize_unit deserialize_identifier deserialize_ignored_any
+    }
+}
+
+struct StrictSeq<'de>(std::slice::Iter<'de, Value>);
+
+impl<'de> SeqAccess<'de> for StrictSeq<'de> {
+    type Error = serde_json::Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Self::Error> {
+        self.0
+            .next()
+            .map(|value| seed.deserialize(Strict(value)))
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.0.len())
+    }
+}
+
+struct StrictMap<'de> {
+    entries: serde_json::map::Iter<'de>,
+    value: Option<&'de Value>,
+}
+
+impl<'de> StrictMap<'de> {
+    fn new(map: &'de serde_json::Map<String, Value>) -> Self {
+        StrictMap {
+            entries: map.iter(),
+            value: None,
+        }
+    }
+}
+
+impl<'de> MapAccess<'de> for StrictMap<'de> {
+    type Error = serde_json::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        let Some((key, value)) = self.entries.next() else {
+            return Ok(None);
+        };
+        self.value = Some(value);
+        seed.deserialize(BorrowedStrDeserializer::new(key))
+            .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.value.take() {
+            Some(value) => seed.deserialize(Strict(value)),
+            None => Err(de::Error::custom("value is missing")),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
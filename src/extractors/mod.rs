@@ -0,0 +1,244 @@
+//! Custom extractors that return JSON errors instead of plain text.
+//!
+//! These wrap Axum's built-in extractors to ensure all error responses
+//! are consistent JSON format. A body or query string that parses but
+//! doesn't fit the handler's type is rejected with the path of the field at
+//! fault (`invalid_body_field` / `invalid_query_field`), see [`fields`].
+
+mod fields;
+
+use axum::{
+    extract::{
+        FromRequest, FromRequestParts, OptionalFromRequest, Request, rejection::JsonRejection,
+    },
+    http::{Method, StatusCode, header, request::Parts},
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::Value;
+
+use crate::error::AppError;
+use crate::models::TimestampFormat;
+
+/// JSON extractor that returns `AppError` on failure.
+///
+/// Use this instead of `axum::Json` to get JSON error responses. A body over
+/// the route's `DefaultBodyLimit` becomes `AppError::PayloadTooLarge` (413).
+///
+/// Fields the type doesn't declare are rejected, so a typo in an admin
+/// request fails loudly instead of being dropped. Public endpoints use
+/// [`PublicJson`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json<T>(pub T);
+
+impl<S, T> FromRequest<S> for Json<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let axum::Json(body) = axum::Json::<Value>::from_request(req, state)
+            .await
+            .map_err(json_rejection)?;
+        fields::from_body(&body, true).map(Json)
+    }
+}
+
+/// `Option<Json<T>>` is `None` when the request has no `Content-Type`, for
+/// endpoints whose body is optional.
+impl<S, T> OptionalFromRequest<S> for Json<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Option<Self>, Self::Rejection> {
+        let result = <axum::Json<Value> as OptionalFromRequest<S>>::from_request(req, state)
+            .await
+            .map_err(json_rejection)?;
+        result
+            .map(|axum::Json(body)| fields::from_body(&body, true).map(Json))
+            .transpose()
+    }
+}
+
+/// JSON extractor for public endpoints, which ignores fields the type doesn't
+/// declare so older servers accept requests from newer client SDKs.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PublicJson<T>(pub T);
+
+impl<S, T> FromRequest<S> for PublicJson<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let axum::Json(body) = axum::Json::<Value>::from_request(req, state)
+            .await
+            .map_err(json_rejection)?;
+        fields::from_body(&body, false).map(PublicJson)
+    }
+}
+
+fn json_rejection(rejection: JsonRejection) -> AppError {
+    if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE {
+        AppError::PayloadTooLarge
+    } else {
+        AppError::JsonBody(rejection)
+    }
+}
+
+impl<T> std::ops::Deref for Json<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> std::ops::DerefMut for Json<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// Timestamps in the body are written in the format the request asked for
+/// (see [`TimestampFormat`]).
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        match TimestampFormat::requested().apply(|| serde_json::to_value(&self.0)) {
+            Ok(body) => axum::Json(body).into_response(),
+            Err(e) => AppError::from(e).into_response(),
+        }
+    }
+}
+
+/// Query extractor that returns `AppError` on failure.
+///
+/// Use this instead of `axum::extract::Query` to get JSON error responses.
+/// Unknown parameters are ignored, as with `axum::extract::Query`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Query<T>(pub T);
+
+impl<S, T> FromRequestParts<S> for Query<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        fields::from_query(parts.uri.query().unwrap_or_default()).map(Query)
+    }
+}
+
+impl<T> std::ops::Deref for Query<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> std::ops::DerefMut for Query<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// Path extractor that returns `AppError` on failure.
+///
+/// Use this instead of `axum::extract::Path` to get JSON error responses.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Path<T>(pub T);
+
+impl<S, T> FromRequestParts<S> for Path<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned + Send,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let result = axum::extract::Path::<T>::from_request_parts(parts, state).await?;
+        Ok(Path(result.0))
+    }
+}
+
+impl<T> std::ops::Deref for Path<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> std::ops::DerefMut for Path<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// Where a [`Payload`] was read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadSource {
+    Json,
+    Form,
+    Query,
+}
+
+/// Request data from a JSON body, a form-encoded body, or (for GET) the query
+/// string, for endpoints that are also reached from plain HTML forms and links.
+///
+/// GET and HEAD read the query string. Other methods read a form body when
+/// the content type is `application/x-www-form-urlencoded`, and JSON
+/// otherwise, so a missing or unknown content type gets the usual JSON error.
+/// Unknown fields are ignored, as with [`PublicJson`].
+#[derive(Debug, Clone, Copy)]
+pub struct Payload<T>(pub T, pub PayloadSource);
+
+impl<S, T> FromRequest<S> for Payload<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        if req.method() == Method::GET || req.method() == Method::HEAD {
+            let (mut parts, _) = req.into_parts();
+            let Query(value) = Query::<T>::from_request_parts(&mut parts, state).await?;
+            return Ok(Payload(value, PayloadSource::Query));
+        }
+
+        let is_form = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .is_some_and(|mime| {
+                mime.trim()
+                    .eq_ignore_ascii_case("application/x-www-form-urlencoded")
+            });
+        if is_form {
+            let axum::Form(value) = axum::Form::<T>::from_request(req, state).await?;
+            return Ok(Payload(value, PayloadSource::Form));
+        }
+
+        let PublicJson(value) = PublicJson::<T>::from_request(req, state).await?;
+        Ok(Payload(value, PayloadSource::Json))
+    }
+}
+
+/// Request body for restore operations on soft-deleted entities.
+#[derive(Debug, Deserialize)]
+pub struct RestoreRequest {
+    /// If true, restore even if item was cascade-deleted (otherwise requires parent to be restored first)
+    #[serde(default)]
+    pub force: bool,
+}
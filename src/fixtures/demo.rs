@@ -0,0 +1,556 @@
+//! Demo dataset for local development: one org with two projects, products
+//! across tiers, licenses in assorted states, devices, payment sessions,
+//! and the audit history that goes with them.
+//!
+//! Everything you'd see in the console (names, emails, tiers, license
+//! states, dates relative to now, device counts) follows from the seed, so
+//! the same seed gives the same dataset for tests and screenshots. IDs, API
+//! keys, and signing keys are still random.
+
+use axum::http::HeaderMap;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rusqlite::Connection;
+
+use crate::db::{AppState, queries};
+use crate::error::Result;
+use crate::models::{
+    ActorType, AuditAction, AuditLogNames, CreateLicense, CreateOrgMember, CreateOrganization,
+    CreatePaymentSession, CreateProduct, CreateProviderLink, DeviceType, EmailAddress,
+    LemonSqueezyConfig, OperatorRole, OrgMemberRole, Organization, Product, Project,
+    RevocationReason, RevokeLicense, ServiceProvider, StripeConfig,
+};
+use crate::util::AuditLogBuilder;
+
+/// Seed used when none is given
+pub const DEFAULT_DEMO_SEED: u64 = 42;
+/// Licenses spread across the demo products
+pub const DEMO_LICENSE_COUNT: usize = 200;
+/// Checkouts started but never paid
+const ABANDONED_CHECKOUTS: usize = 12;
+
+const DAY: i64 = 86_400;
+
+const FIRST_NAMES: &[&str] = &[
+    "ada", "ben", "carla", "dev", "elif", "femi", "grace", "hiro", "ines", "jonas", "kai", "lena",
+    "mateo", "nadia", "omar", "priya", "quinn", "rosa", "sven", "tara", "uma", "victor", "wen",
+    "yusuf", "zoe",
+];
+
+const LAST_NAMES: &[&str] = &[
+    "alvarez", "brooks", "chen", "dubois", "eriksen", "fischer", "garcia", "haddad", "ito",
+    "jensen", "kowalski", "larsen", "moreau", "nakamura", "okafor", "patel", "rossi", "silva",
+    "tanaka", "novak",
+];
+
+const DEVICE_NAMES: &[&str] = &[
+    "MacBook Pro",
+    "MacBook Air",
+    "iMac",
+    "Mac mini",
+    "ThinkPad X1",
+    "Dell XPS 13",
+    "Surface Laptop",
+    "Framework 13",
+    "Linux Desktop",
+    "Office PC",
+];
+
+struct DemoProduct {
+    name: &'static str,
+    tier: &'static str,
+    price_cents: i64,
+    /// Subscription period in days (None = one-time purchase)
+    period_days: Option<i32>,
+    device_limit: i32,
+    features: &'static [&'static str],
+}
+
+struct DemoProject {
+    name: &'static str,
+    prefix: &'static str,
+    products: &'static [DemoProduct],
+}
+
+const PROJECTS: &[DemoProject] = &[
+    DemoProject {
+        name: "Acme Notes",
+        prefix: "NOTES",
+        products: &[
+            DemoProduct {
+                name: "Notes Basic",
+                tier: "basic",
+                price_cents: 1900,
+                period_days: None,
+                device_limit: 2,
+                features: &["markdown-export"],
+            },
+            DemoProduct {
+                name: "Notes Pro",
+                tier: "pro",
+                price_cents: 4900,
+                period_days: None,
+                device_limit: 5,
+                features: &["markdown-export", "pdf-export", "cloud-sync"],
+            },
+            DemoProduct {
+                name: "Notes Pro Monthly",
+                tier: "pro",
+                price_cents: 700,
+                period_days: Some(30),
+                device_limit: 5,
+                features: &["markdown-export", "pdf-export", "cloud-sync"],
+            },
+        ],
+    },
+    DemoProject {
+        name: "Acme Sync",
+        prefix: "SYNC",
+        products: &[
+            DemoProduct {
+                name: "Sync Personal",
+                tier: "personal",
+                price_cents: 2900,
+                period_days: Some(365),
+                device_limit: 3,
+                features: &["sync"],
+            },
+            DemoProduct {
+                name: "Sync Business",
+                tier: "business",
+                price_cents: 9900,
+                period_days: Some(365),
+                device_limit: 10,
+                features: &["sync", "sso", "audit-export", "priority-support"],
+            },
+        ],
+    },
+];
+
+/// What the demo seed created
+pub struct DemoData {
+    pub operator_api_key: String,
+    pub member_api_key: String,
+    pub org: Organization,
+    pub projects: Vec<Project>,
+    pub products: usize,
+    pub licenses: usize,
+    pub devices: usize,
+    pub payment_sessions: usize,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum DemoState {
+    Active,
+    Expired,
+    Revoked,
+}
+
+/// Create the demo dataset. Callers decide whether the database may already
+/// hold data; demo users that already exist are reused.
+pub fn seed_demo(state: &AppState, seed: u64) -> Result<DemoData> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let emails = state.emails();
+    let headers = HeaderMap::new();
+    let mut conn = state.db.get()?;
+    let audit_conn = state.audit.get()?;
+
+    // Operator
+    let operator = match queries::get_user_by_email(&conn, "operator@acme.example", &emails)? {
+        Some(user) => user,
+        None => super::create_user(&conn, "operator@acme.example", "Morgan Operator", &emails)?,
+    };
+    let operator = queries::grant_operator_role(&conn, &operator.id, OperatorRole::Owner, &emails)?;
+    let (_, operator_api_key) =
+        queries::create_api_key(&mut conn, &operator.id, "Demo", None, true, None)?;
+    AuditLogBuilder::for_state(&audit_conn, state, &headers)
+        .actor(ActorType::System, None)
+        .action(AuditAction::SeedOperator)
+        .resource("operator", &operator.id)
+        .names(&AuditLogNames::default().resource_user(&operator.name, &operator.email))
+        .save()?;
+
+    // Org, with payment providers in test mode
+    let org = queries::create_organization(
+        &conn,
+        &CreateOrganization {
+            name: "Acme Software".to_string(),
+            owner_user_id: None,
+        },
+        &state.master_key,
+    )?;
+    AuditLogBuilder::for_state(&audit_conn, state, &headers)
+        .actor(ActorType::System, None)
+        .action(AuditAction::SeedOrg)
+        .resource("org", &org.id)
+        .names(&AuditLogNames::default().resource(org.name.clone()))
+        .save()?;
+
+    super::set_service_config(
+        &conn,
+        &org.id,
+        ServiceProvider::Stripe,
+        &StripeConfig {
+            secret_key: "sk_test_demo".to_string(),
+            publishable_key: "pk_test_demo".to_string(),
+            webhook_secret: "whsec_demo".to_string(),
+        },
+        &state.master_key,
+    )?;
+    super::set_service_config(
+        &conn,
+        &org.id,
+        ServiceProvider::LemonSqueezy,
+        &LemonSqueezyConfig {
+            api_key: "ls_test_demo".to_string(),
+            store_id: "demo-store".to_string(),
+            webhook_secret: "ls_whsec_demo".to_string(),
+            verify_imported_keys: false,
+        },
+        &state.master_key,
+    )?;
+
+    // Members: the owner's API key is the one to develop with
+    let mut member_api_key = String::new();
+    let mut owner_id = String::new();
+    for (email, name, role) in [
+        ("avery@acme.example", "Avery Owner", OrgMemberRole::Owner),
+        ("blake@acme.example", "Blake Admin", OrgMemberRole::Admin),
+        ("casey@acme.example", "Casey Member", OrgMemberRole::Member),
+    ] {
+        let user = match queries::get_user_by_email(&conn, email, &emails)? {
+            Some(user) => user,
+            None => super::create_user(&conn, email, name, &emails)?,
+        };
+        let member = queries::create_org_member(
+            &conn,
+            &org.id,
+            &CreateOrgMember {
+                user_id: user.id.clone(),
+                role,
+            },
+        )?;
+        let (_, api_key) = queries::create_api_key(&mut conn, &user.id, "Demo", None, true, None)?;
+        AuditLogBuilder::for_state(&audit_conn, state, &headers)
+            .actor(ActorType::System, None)
+            .action(AuditAction::SeedOrgMember)
+            .resource("org_member", &member.id)
+            .org(&org.id)
+            .names(
+                &AuditLogNames::default()
+                    .resource_user(&user.name, &user.email)
+                    .org(org.name.clone()),
+            )
+            .save()?;
+        if role == OrgMemberRole::Owner {
+            member_api_key = api_key;
+            owner_id = user.id;
+        }
+    }
+    drop(conn);
+
+    // Projects and products live in the org's tenant database
+    let tenant_conn = state.org_db(&org.id).get()?;
+    let mut projects = Vec::new();
+    let mut catalog: Vec<(Project, Product, &DemoProduct)> = Vec::new();
+    for spec in PROJECTS {
+        let project = super::create_project(
+            &tenant_conn,
+            &org.id,
+            &super::project_input(spec.name, spec.prefix),
+            &state.master_key,
+        )?;
+        AuditLogBuilder::for_state(&audit_conn, state, &headers)
+            .actor(ActorType::System, None)
+            .action(AuditAction::SeedProject)
+            .resource("project", &project.id)
+            .org(&org.id)
+            .project(&project.id)
+            .names(
+                &AuditLogNames::default()
+                    .resource(project.name.clone())
+                    .org(org.name.clone())
+                    .project(project.name.clone()),
+            )
+            .save()?;
+
+        for product_spec in spec.products {
+            let product = queries::create_product(
+                &tenant_conn,
+                &project.id,
+                &CreateProduct {
+                    license_exp_days: product_spec.period_days,
+                    updates_exp_days: product_spec.period_days.or(Some(365)),
+                    device_limit: Some(product_spec.device_limit),
+                    features: product_spec
+                        .features
+                        .iter()
+                        .map(|f| f.to_string())
+                        .collect(),
+                    entitlements: Default::default(),
+                    price_cents: Some(product_spec.price_cents),
+                    currency: Some("usd".to_string()),
+                    ..super::product_input(product_spec.name, product_spec.tier)
+                },
+            )?;
+            queries::create_provider_link(
+                &tenant_conn,
+                &product.id,
+                &CreateProviderLink {
+                    provider: "stripe".to_string(),
+                    linked_id: format!(
+                        "price_demo_{}",
+                        product.name.to_lowercase().replace(' ', "_")
+                    ),
+                },
+            )?;
+            AuditLogBuilder::for_state(&audit_conn, state, &headers)
+                .actor(ActorType::System, None)
+                .action(AuditAction::SeedProduct)
+                .resource("product", &product.id)
+                .org(&org.id)
+                .project(&project.id)
+                .names(
+                    &AuditLogNames::default()
+                        .resource(product.name.clone())
+                        .org(org.name.clone())
+                        .project(project.name.clone()),
+                )
+                .save()?;
+            catalog.push((project.clone(), product, product_spec));
+        }
+        projects.push(project);
+    }
+
+    let now = state.clock.now();
+    let mut devices = 0;
+    let mut payment_sessions = 0;
+    for n in 0..DEMO_LICENSE_COUNT {
+        let (project, product, spec) = catalog.choose(&mut rng).expect("catalog is not empty");
+        let first = FIRST_NAMES.choose(&mut rng).expect("names are not empty");
+        let last = LAST_NAMES.choose(&mut rng).expect("names are not empty");
+        let email = EmailAddress::parse(&format!("{}.{}{}@example.com", first, last, n))?;
+
+        let roll = rng.gen_range(0..100);
+        let demo_state = match roll {
+            0..=9 => DemoState::Revoked,
+            10..=29 if spec.period_days.is_some() => DemoState::Expired,
+            _ => DemoState::Active,
+        };
+
+        // Subscriptions are always bought through a provider; one-time
+        // purchases sometimes come from the console instead
+        let provider = match (spec.period_days, rng.gen_range(0..100)) {
+            (None, 0..=14) => None,
+            (_, 0..=29) => Some("lemonsqueezy"),
+            _ => Some("stripe"),
+        };
+
+        let expires_at = spec.period_days.map(|days| match demo_state {
+            DemoState::Expired => now - rng.gen_range(1..=90) * DAY,
+            _ => now + rng.gen_range(1..=i64::from(days)) * DAY,
+        });
+        let updates_expires_at = expires_at.or_else(|| Some(now + rng.gen_range(-180..=365) * DAY));
+
+        let license = queries::create_license(
+            &tenant_conn,
+            &project.id,
+            &product.id,
+            &CreateLicense {
+                customer_id: Some(format!("customer-{:04}", n)),
+                expires_at,
+                updates_expires_at,
+                payment_provider: provider.map(str::to_string),
+                payment_provider_customer_id: provider.map(|_| format!("cus_demo_{:04}", n)),
+                payment_provider_subscription_id: spec
+                    .period_days
+                    .and(provider)
+                    .map(|_| format!("sub_demo_{:04}", n)),
+                payment_provider_order_id: provider.map(|_| format!("order_demo_{:04}", n)),
+                ..super::license_input(Some(state.email_hasher.hash(&email)))
+            },
+        )?;
+
+        if let Some(provider) = provider {
+            let session = queries::create_payment_session(
+                &tenant_conn,
+                &CreatePaymentSession {
+                    product_id: product.id.clone(),
+                    customer_id: license.customer_id.clone(),
+                    upgrade_from_license_id: None,
+                    upgrade_days_remaining: None,
+                    upgrade_credit_cents: None,
+                    checkout_fields: Default::default(),
+                    accepted_terms_version: None,
+                    metadata: Default::default(),
+                },
+            )?;
+            queries::try_claim_payment_session(&tenant_conn, &session.id)?;
+            queries::set_payment_session_license(
+                &tenant_conn,
+                &session.id,
+                &license.id,
+                Some(&format!("pay_demo_{:04}", n)),
+            )?;
+            payment_sessions += 1;
+
+            AuditLogBuilder::for_state(&audit_conn, state, &headers)
+                .actor(ActorType::Public, None)
+                .action(AuditAction::ReceiveCheckoutWebhook)
+                .resource("license", &license.id)
+                .details(&serde_json::json!({
+                    "provider": provider,
+                    "session_id": session.id,
+                    "product_id": product.id,
+                    "subscription_id": license.payment_provider_subscription_id,
+                    "order_id": license.payment_provider_order_id,
+                }))
+                .org(&org.id)
+                .project(&project.id)
+                .names(
+                    &AuditLogNames::default()
+                        .org(org.name.clone())
+                        .project(project.name.clone()),
+                )
+                .save()?;
+        } else {
+            AuditLogBuilder::for_state(&audit_conn, state, &headers)
+                .actor(ActorType::User, Some(&owner_id))
+                .action(AuditAction::CreateLicense)
+                .resource("license", &license.id)
+                .details(&serde_json::json!({ "product_id": product.id }))
+                .org(&org.id)
+                .project(&project.id)
+                .names(
+                    &AuditLogNames::default()
+                        .org(org.name.clone())
+                        .project(project.name.clone()),
+                )
+                .save()?;
+        }
+
+        // Active licenses are in use; lapsed ones were used before they lapsed
+        let device_count = match demo_state {
+            DemoState::Active => rng.gen_range(0..=spec.device_limit.min(3)),
+            DemoState::Expired | DemoState::Revoked => rng.gen_range(0..=1),
+        };
+        for _ in 0..device_count {
+            add_device(
+                state,
+                &audit_conn,
+                &tenant_conn,
+                &mut rng,
+                &org,
+                project,
+                &license.id,
+            )?;
+            devices += 1;
+        }
+
+        if demo_state == DemoState::Revoked {
+            let reason = *[
+                RevocationReason::Refund,
+                RevocationReason::Chargeback,
+                RevocationReason::Abuse,
+                RevocationReason::AdminOther,
+            ]
+            .choose(&mut rng)
+            .expect("reasons are not empty");
+            queries::revoke_license(
+                &tenant_conn,
+                &license.id,
+                &RevokeLicense::new(reason),
+                Some(&owner_id),
+            )?;
+            AuditLogBuilder::for_state(&audit_conn, state, &headers)
+                .actor(ActorType::User, Some(&owner_id))
+                .action(AuditAction::RevokeLicense)
+                .resource("license", &license.id)
+                .details(&serde_json::json!({ "reason": reason }))
+                .org(&org.id)
+                .project(&project.id)
+                .names(
+                    &AuditLogNames::default()
+                        .org(org.name.clone())
+                        .project(project.name.clone()),
+                )
+                .save()?;
+        }
+    }
+
+    for _ in 0..ABANDONED_CHECKOUTS {
+        let (_, product, _) = catalog.choose(&mut rng).expect("catalog is not empty");
+        queries::create_payment_session(
+            &tenant_conn,
+            &CreatePaymentSession {
+                product_id: product.id.clone(),
+                customer_id: None,
+                upgrade_from_license_id: None,
+                upgrade_days_remaining: None,
+                upgrade_credit_cents: None,
+                checkout_fields: Default::default(),
+                accepted_terms_version: None,
+                metadata: Default::default(),
+            },
+        )?;
+        payment_sessions += 1;
+    }
+
+    Ok(DemoData {
+        operator_api_key,
+        member_api_key,
+        org,
+        projects,
+        products: catalog.len(),
+        licenses: DEMO_LICENSE_COUNT,
+        devices,
+        payment_sessions,
+    })
+}
+
+/// Activate a device on a license, as /redeem would.
+fn add_device(
+    state: &AppState,
+    audit_conn: &Connection,
+    conn: &Connection,
+    rng: &mut StdRng,
+    org: &Organization,
+    project: &Project,
+    license_id: &str,
+) -> Result<()> {
+    let device_type = if rng.gen_bool(0.5) {
+        DeviceType::Uuid
+    } else {
+        DeviceType::Machine
+    };
+    let device_id = format!("demo-{:016x}", rng.r#gen::<u64>());
+    let jti = format!("demo-jti-{:016x}", rng.r#gen::<u64>());
+    let name = DEVICE_NAMES
+        .choose(rng)
+        .expect("device names are not empty");
+
+    queries::create_device(conn, license_id, &device_id, device_type, &jti, Some(name))?;
+    queries::increment_activation_count(conn, license_id)?;
+
+    let headers = HeaderMap::new();
+    AuditLogBuilder::for_state(audit_conn, state, &headers)
+        .actor(ActorType::Public, None)
+        .action(AuditAction::ActivateDevice)
+        .resource("device", &device_id)
+        .details(&serde_json::json!({
+            "license_id": license_id,
+            "device_type": device_type,
+            "device_name": name,
+        }))
+        .org(&org.id)
+        .project(&project.id)
+        .names(&AuditLogNames {
+            resource_name: Some(name.to_string()),
+            org_name: Some(org.name.clone()),
+            project_name: Some(project.name.clone()),
+            ..Default::default()
+        })
+        .save()?;
+    Ok(())
+}
@@ -0,0 +1,164 @@
+//! Builders for users, orgs, projects, products, and licenses with sensible
+//! defaults, shared by the integration tests and the demo dataset
+//! (`--seed-demo`, see [`demo`]).
+//!
+//! The `*_input` functions return a complete input to adjust with struct
+//! update syntax; the `create_*` functions also do the bookkeeping around
+//! the insert (keypairs, API keys, encrypted configs).
+
+pub mod demo;
+
+use rusqlite::Connection;
+use serde::Serialize;
+
+use crate::crypto::MasterKey;
+use crate::db::{EmailColumn, queries};
+use crate::error::Result;
+use crate::jwt;
+use crate::models::{
+    ConvertedTrialAction, CreateLicense, CreateOrgMember, CreateProduct, CreateProject, CreateUser,
+    OperatorRole, OrgMember, OrgMemberRole, Project, RenewalFallback, ServiceProvider,
+    UpgradeOldLicense, User,
+};
+
+/// Project input with every optional setting off.
+pub fn project_input(name: &str, license_key_prefix: &str) -> CreateProject {
+    CreateProject {
+        name: name.to_string(),
+        license_key_prefix: license_key_prefix.to_string(),
+        redirect_url: None,
+        email_from: None,
+        email_enabled: true,
+        email_webhook_url: None,
+        email_webhook_secret: None,
+        jwt_issuer: None,
+        jwt_audience: None,
+        upgrade_auto_discount: false,
+        upgrade_old_license: UpgradeOldLicense::Revoke,
+        allow_project_id_auth: true,
+        allow_link_checkout: false,
+        max_validations_per_hour_per_license: None,
+        statement_descriptor_suffix: None,
+        receipt_email_enabled: false,
+        checkout_message: None,
+        attestation_audiences: vec![],
+        duplicate_purchase_check: true,
+        converted_trial_action: ConvertedTrialAction::Keep,
+        require_terms_acceptance: false,
+        checkout_metadata_keys: vec![],
+        templates: vec![],
+    }
+}
+
+/// Product input for a perpetual license with no limits, features, or price.
+pub fn product_input(name: &str, tier: &str) -> CreateProduct {
+    CreateProduct {
+        name: name.to_string(),
+        tier: tier.to_string(),
+        license_exp_days: None,
+        updates_exp_days: None,
+        activation_limit: None,
+        device_limit: None,
+        device_inactive_days: None,
+        features: vec![],
+        entitlements: Default::default(),
+        price_cents: None,
+        currency: None,
+        seat_count: None,
+        available_from: None,
+        available_until: None,
+        checkout_fields: vec![],
+        extends_updates_for_product_id: None,
+        renewal_fallback: RenewalFallback::CreateLicense,
+        revoke_on_partial_refund: false,
+    }
+}
+
+/// License input for a perpetual, directly created license.
+pub fn license_input(email_hash: Option<String>) -> CreateLicense {
+    CreateLicense {
+        email_hash,
+        customer_id: None,
+        expires_at: None,
+        updates_expires_at: None,
+        payment_provider: None,
+        payment_provider_customer_id: None,
+        payment_provider_subscription_id: None,
+        payment_provider_order_id: None,
+        seats: None,
+    }
+}
+
+/// Create a user.
+pub fn create_user(
+    conn: &Connection,
+    email: &str,
+    name: &str,
+    emails: &EmailColumn,
+) -> Result<User> {
+    let input = CreateUser {
+        email: email.to_string(),
+        name: name.to_string(),
+    };
+    queries::create_user(conn, &input, emails)
+}
+
+/// Create a user with an operator role and an API key (returned raw).
+pub fn create_operator(
+    conn: &mut Connection,
+    email: &str,
+    name: &str,
+    role: OperatorRole,
+    emails: &EmailColumn,
+) -> Result<(User, String)> {
+    let user = create_user(conn, email, name, emails)?;
+    let user = queries::grant_operator_role(conn, &user.id, role, emails)?;
+    let (_, api_key) = queries::create_api_key(conn, &user.id, "Default", None, true, None)?;
+    Ok((user, api_key))
+}
+
+/// Create a user, add them to an org, and give them an API key (returned raw).
+pub fn create_org_member(
+    conn: &mut Connection,
+    org_id: &str,
+    email: &str,
+    name: &str,
+    role: OrgMemberRole,
+    emails: &EmailColumn,
+) -> Result<(User, OrgMember, String)> {
+    let user = create_user(conn, email, name, emails)?;
+    let input = CreateOrgMember {
+        user_id: user.id.clone(),
+        role,
+    };
+    let member = queries::create_org_member(conn, org_id, &input)?;
+    let (_, api_key) = queries::create_api_key(conn, &user.id, "Default", None, true, None)?;
+    Ok((user, member, api_key))
+}
+
+/// Create a project with a fresh signing keypair.
+pub fn create_project(
+    conn: &Connection,
+    org_id: &str,
+    input: &CreateProject,
+    master_key: &MasterKey,
+) -> Result<Project> {
+    let (private_key, public_key) = jwt::generate_keypair();
+    queries::create_project(conn, org_id, input, &private_key, &public_key, master_key)
+}
+
+/// Store an org's config for a payment or email provider, encrypted as the
+/// org settings endpoint does.
+pub fn set_service_config<T: Serialize>(
+    conn: &Connection,
+    org_id: &str,
+    provider: ServiceProvider,
+    config: &T,
+    master_key: &MasterKey,
+) -> Result<()> {
+    let config_json = serde_json::to_vec(config)?;
+    let encrypted =
+        queries::org_data_key(conn, org_id, master_key)?.encrypt_private_key(org_id, &config_json)?;
+    queries::upsert_org_service_config(conn, org_id, provider, &encrypted)?;
+    Ok(())
+}
@@ -0,0 +1,99 @@
+//! Coarse client geography for usage heatmaps: an address resolved to its
+//! country code and nothing finer.
+//!
+//! Lookups read a MaxMind-format database (GeoLite2 or GeoIP2, Country or
+//! City) the operator provides at `PAYCHECK_GEOIP_DATABASE`, in builds with
+//! the `geoip` feature. No database is bundled: MaxMind's license doesn't
+//! allow redistributing it. Without one, every lookup comes back empty and
+//! usage simply isn't mapped.
+//!
+//! Addresses are only held for the lookup. Callers keep the country code.
+
+use std::net::IpAddr;
+
+/// Country lookups against the configured database, if any.
+pub struct GeoIp {
+    #[cfg(feature = "geoip")]
+    reader: Option<maxminddb::Reader<Vec<u8>>>,
+}
+
+impl GeoIp {
+    /// No database: every lookup comes back empty.
+    pub fn disabled() -> Self {
+        Self {
+            #[cfg(feature = "geoip")]
+            reader: None,
+        }
+    }
+
+    /// Load the database at `path`. A file that can't be read is logged and
+    /// leaves lookups disabled rather than stopping the server.
+    pub fn open(path: &str) -> Self {
+        #[cfg(feature = "geoip")]
+        {
+            match maxminddb::Reader::open_readfile(path) {
+                Ok(reader) => {
+                    tracing::info!(
+                        path,
+                        database_type = %reader.metadata.database_type,
+                        "Loaded GeoIP database"
+                    );
+                    Self {
+                        reader: Some(reader),
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        path,
+                        "Couldn't load GeoIP database, usage won't be mapped: {}",
+                        e
+                    );
+                    Self::disabled()
+                }
+            }
+        }
+        #[cfg(not(feature = "geoip"))]
+        {
+            tracing::warn!(
+                path,
+                "Built without the geoip feature, usage won't be mapped"
+            );
+            Self::disabled()
+        }
+    }
+
+    /// `open` the configured database, or a disabled lookup if none is set.
+    pub fn from_config(path: Option<&str>) -> Self {
+        path.map_or_else(Self::disabled, Self::open)
+    }
+
+    /// Whether lookups can find anything.
+    pub fn is_enabled(&self) -> bool {
+        #[cfg(feature = "geoip")]
+        {
+            self.reader.is_some()
+        }
+        #[cfg(not(feature = "geoip"))]
+        {
+            false
+        }
+    }
+
+    /// ISO 3166-1 alpha-2 code of the country `ip` is in, if the database
+    /// knows it.
+    pub fn country(&self, ip: IpAddr) -> Option<String> {
+        #[cfg(feature = "geoip")]
+        {
+            let found: maxminddb::geoip2::Country = self.reader.as_ref()?.lookup(ip).ok()?;
+            found
+                .country
+                .and_then(|country| country.iso_code)
+                .map(str::to_string)
+        }
+        #[cfg(not(feature = "geoip"))]
+        {
+            let _ = ip;
+            None
+        }
+    }
+}
@@ -2,3 +2,98 @@ pub mod operators;
 pub mod orgs;
 pub mod public;
 pub mod webhooks;
+
+use std::sync::Arc;
+
+use axum::{Extension, Router, extract::DefaultBodyLimit};
+use tower_http::compression::{
+    CompressionLayer,
+    predicate::{DefaultPredicate, NotForContentType, Predicate},
+};
+use tower_http::trace::TraceLayer;
+
+use crate::config::{BodyLimitConfig, Config};
+use crate::custom_domains::DnsResolver;
+use crate::db::AppState;
+use crate::geoip::GeoIp;
+use crate::middleware::{
+    RequestIdConfig, custom_domain_host, maintenance_gate, request_id, timestamp_format,
+};
+
+/// Build the application router: every API with its CORS policy, plus the
+/// layers all requests pass through.
+pub fn app(state: AppState, config: &Config) -> Router {
+    let console_cors = config.console_cors_layer();
+    let mut router = Router::new()
+        // Public endpoints (no auth, permissive CORS for customer websites, small bodies,
+        // project resolved from the Host header on verified custom domains, country
+        // lookups for usage geography)
+        .merge(
+            public::router(config.rate_limit)
+                .layer(Extension(Arc::new(GeoIp::from_config(
+                    config.geoip_database.as_deref(),
+                ))))
+                .layer(DefaultBodyLimit::max(config.body_limits.public_bytes))
+                .layer(axum::middleware::from_fn_with_state(
+                    state.clone(),
+                    custom_domain_host,
+                )),
+        )
+        // Webhook endpoints (provider-specific auth, no CORS needed - server-to-server)
+        .merge(webhooks::router())
+        // Operator API (operator key auth, console CORS only)
+        .merge(
+            operators::router(state.clone())
+                .merge(operators::config_router(state.clone(), config))
+                .merge(operators::custom_domains_router(
+                    state.clone(),
+                    DnsResolver::new(&config.dns_resolver_url),
+                ))
+                .layer(console_cors.clone()),
+        )
+        // Organization API (org member key auth, console CORS only, high rate limit)
+        .merge(orgs::router(state.clone(), config.rate_limit).layer(console_cors));
+    // Embedded status page (same-origin HTML, no CORS)
+    if config.status_page {
+        router = router.merge(operators::status_page_router());
+    }
+    // Maintenance mode sits outside every API so it can hold back writes anywhere
+    router = router.layer(axum::middleware::from_fn_with_state(
+        state.clone(),
+        maintenance_gate,
+    ));
+    // Timestamp format negotiation (`?ts=iso` or the `iso-timestamps` Accept profile)
+    router = router.layer(axum::middleware::from_fn(timestamp_format));
+
+    with_http_layers(router, config.body_limits)
+        .layer(TraceLayer::new_for_http())
+        .layer(axum::middleware::from_fn_with_state(
+            RequestIdConfig {
+                trust_incoming: config.trust_request_id,
+            },
+            request_id,
+        ))
+        .with_state(state)
+}
+
+/// Request body limit and response compression.
+///
+/// The limit is axum's `DefaultBodyLimit`, enforced by the body extractors,
+/// so routes can raise it with their own `DefaultBodyLimit` (the audit archive
+/// upload does) and `Json` reports it as a 413 error envelope. Responses are
+/// compressed with Brotli or gzip when the client accepts it, except archives
+/// that are already gzipped.
+pub fn with_http_layers<S>(router: Router<S>, limits: BodyLimitConfig) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    let compress = DefaultPredicate::new().and(NotForContentType::const_new("application/gzip"));
+    router
+        .layer(DefaultBodyLimit::max(limits.default_bytes))
+        .layer(
+            CompressionLayer::new()
+                .br(true)
+                .gzip(true)
+                .compress_when(compress),
+        )
+}
@@ -4,7 +4,7 @@ use axum::{
 };
 use serde::Deserialize;
 
-use crate::db::{AppState, queries};
+use crate::db::{AppState, outbox, queries};
 use crate::error::{AppError, OptionExt, Result, msg};
 use crate::extractors::{Json, Path};
 use crate::middleware::OperatorContext;
@@ -35,19 +35,44 @@ pub async fn create_api_key(
     let audit_conn = state.audit.get()?;
 
     // Verify the target user exists
-    let target_user =
-        queries::get_user_by_id(&conn, &path.user_id)?.or_not_found(msg::USER_NOT_FOUND)?;
+    let target_user = queries::get_user_by_id(&conn, &path.user_id, &state.emails())?
+        .or_not_found(msg::USER_NOT_FOUND)?;
 
     // Create API key for the user
     let user_manageable = input.user_manageable.unwrap_or(true);
-    let (key_record, full_key) = queries::create_api_key(
+    let details = serde_json::json!({
+        "target_user_id": path.user_id,
+        "target_email": target_user.email,
+        "name": input.name
+    });
+    let (key_record, full_key) = outbox::try_with_audited_tx(
         &mut conn,
-        &path.user_id,
-        &input.name,
-        input.expires_in_days,
-        user_manageable,
-        input.scopes.as_deref(),
+        |tx| {
+            queries::insert_api_key(
+                tx,
+                &path.user_id,
+                &input.name,
+                input.expires_in_days,
+                user_manageable,
+                input.scopes.as_deref(),
+            )
+        },
+        |(key_record, _)| {
+            AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+                .actor(ActorType::User, Some(&ctx.user.id))
+                .action(AuditAction::CreateApiKey)
+                .resource("api_key", &key_record.id)
+                .details(&details)
+                .names(
+                    &ctx.audit_names()
+                        .resource_user(&target_user.name, &target_user.email)
+                        .resource(input.name.clone()),
+                )
+                .auth_method(&ctx.auth_method)
+                .checked_entry()
+        },
     )?;
+    outbox::relay(&conn, &audit_conn);
 
     // Get scopes from the created key
     let scopes = if input.scopes.is_some() {
@@ -56,23 +81,6 @@ pub async fn create_api_key(
         None
     };
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
-        .actor(ActorType::User, Some(&ctx.user.id))
-        .action(AuditAction::CreateApiKey)
-        .resource("api_key", &key_record.id)
-        .details(&serde_json::json!({
-            "target_user_id": path.user_id,
-            "target_email": target_user.email,
-            "name": input.name
-        }))
-        .names(
-            &ctx.audit_names()
-                .resource_user(&target_user.name, &target_user.email)
-                .resource(input.name.clone()),
-        )
-        .auth_method(&ctx.auth_method)
-        .save()?;
-
     Ok(Json(ApiKeyCreated {
         id: key_record.id,
         name: key_record.name,
@@ -94,8 +102,8 @@ pub async fn list_api_keys(
     let conn = state.db.get()?;
 
     // Verify the target user exists
-    let _target_user =
-        queries::get_user_by_id(&conn, &path.user_id)?.or_not_found(msg::USER_NOT_FOUND)?;
+    let _target_user = queries::get_user_by_id(&conn, &path.user_id, &state.emails())?
+        .or_not_found(msg::USER_NOT_FOUND)?;
 
     let limit = query.limit();
     let offset = query.offset();
@@ -128,12 +136,12 @@ pub async fn revoke_api_key(
     Path(path): Path<UserApiKeyIdPath>,
     headers: HeaderMap,
 ) -> Result<Json<serde_json::Value>> {
-    let conn = state.db.get()?;
+    let mut conn = state.db.get()?;
     let audit_conn = state.audit.get()?;
 
     // Verify the target user exists
-    let target_user =
-        queries::get_user_by_id(&conn, &path.user_id)?.or_not_found(msg::USER_NOT_FOUND)?;
+    let target_user = queries::get_user_by_id(&conn, &path.user_id, &state.emails())?
+        .or_not_found(msg::USER_NOT_FOUND)?;
 
     // Verify key exists and belongs to the right user
     let key =
@@ -143,24 +151,30 @@ pub async fn revoke_api_key(
         return Err(AppError::NotFound(msg::API_KEY_NOT_FOUND.into()));
     }
 
-    queries::revoke_api_key(&conn, &path.key_id)?;
-
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
-        .actor(ActorType::User, Some(&ctx.user.id))
-        .action(AuditAction::RevokeApiKey)
-        .resource("api_key", &path.key_id)
-        .details(&serde_json::json!({
-            "target_user_id": path.user_id,
-            "target_email": target_user.email,
-            "key_name": key.name
-        }))
-        .names(
-            &ctx.audit_names()
-                .resource_user(&target_user.name, &target_user.email)
-                .resource(key.name.clone()),
-        )
-        .auth_method(&ctx.auth_method)
-        .save()?;
+    let details = serde_json::json!({
+        "target_user_id": path.user_id,
+        "target_email": target_user.email,
+        "key_name": key.name
+    });
+    outbox::try_with_audited_tx(
+        &mut conn,
+        |tx| queries::revoke_api_key(tx, &path.key_id),
+        |_| {
+            AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+                .actor(ActorType::User, Some(&ctx.user.id))
+                .action(AuditAction::RevokeApiKey)
+                .resource("api_key", &path.key_id)
+                .details(&details)
+                .names(
+                    &ctx.audit_names()
+                        .resource_user(&target_user.name, &target_user.email)
+                        .resource(key.name.clone()),
+                )
+                .auth_method(&ctx.auth_method)
+                .checked_entry()
+        },
+    )?;
+    outbox::relay(&conn, &audit_conn);
 
     Ok(Json(serde_json::json!({ "success": true })))
 }
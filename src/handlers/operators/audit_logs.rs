@@ -1,19 +1,34 @@
-use axum::extract::State;
+use std::collections::HashMap;
 
-use crate::db::{AppState, queries};
-use crate::error::Result;
+use axum::body::Bytes;
+use axum::extract::{Extension, State};
+use axum::http::{HeaderMap, header};
+use axum::response::{IntoResponse, Response};
+use rusqlite::Connection;
+use serde::Deserialize;
+
+use crate::audit_archive::{self, ArchiveVerification};
+use crate::audit_csv;
+use crate::db::audit_chain::{self, ChainVerification};
+use crate::db::{AppState, EmailColumn, queries};
+use crate::error::{AppError, Result, msg};
 use crate::extractors::{Json, Query};
-use crate::models::{AuditLogQuery, AuditLogResponse};
+use crate::middleware::OperatorContext;
+use crate::models::{ActorType, AuditAction, AuditLog, AuditLogQuery, AuditLogResponse};
 use crate::pagination::Paginated;
+use crate::util::{AuditLogBuilder, blocking_stream_body};
 
 pub async fn query_audit_logs(
     State(state): State<AppState>,
+    Extension(ctx): Extension<OperatorContext>,
     Query(query): Query<AuditLogQuery>,
 ) -> Result<Json<Paginated<AuditLogResponse>>> {
+    check_org_scope(&state, &ctx, &query)?;
     let limit = query.limit();
     let offset = query.offset();
     let conn = state.audit.get()?;
-    let (logs, total) = queries::query_audit_logs(&conn, &query)?;
+    let (mut logs, total) = queries::query_audit_logs(&conn, &query)?;
+    resolve_user_names(&state, &mut logs)?;
     let responses: Vec<AuditLogResponse> = logs.into_iter().map(Into::into).collect();
     Ok(Json(Paginated::new(responses, total, limit, offset)))
 }
@@ -23,10 +38,13 @@ pub async fn query_audit_logs(
 /// Supports the same filtering and pagination as the JSON endpoint.
 pub async fn query_audit_logs_text(
     State(state): State<AppState>,
+    Extension(ctx): Extension<OperatorContext>,
     Query(query): Query<AuditLogQuery>,
 ) -> Result<String> {
+    check_org_scope(&state, &ctx, &query)?;
     let conn = state.audit.get()?;
-    let (logs, _total) = queries::query_audit_logs(&conn, &query)?;
+    let (mut logs, _total) = queries::query_audit_logs(&conn, &query)?;
+    resolve_user_names(&state, &mut logs)?;
 
     Ok(logs
         .iter()
@@ -34,3 +52,201 @@ pub async fn query_audit_logs_text(
         .collect::<Vec<_>>()
         .join("\n"))
 }
+
+/// Download audit logs as CSV: every entry matching the same filters as the
+/// JSON endpoint, newest first, streamed as it's read. The export itself is
+/// audit logged.
+pub async fn export_audit_logs(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OperatorContext>,
+    headers: HeaderMap,
+    Query(query): Query<AuditLogQuery>,
+) -> Result<Response> {
+    check_org_scope(&state, &ctx, &query)?;
+
+    let scope = query.org_id.clone().unwrap_or_else(|| "all".to_string());
+    let details = serde_json::json!({
+        "format": "csv",
+        "from": query.from_timestamp,
+        "to": query.to_timestamp,
+    });
+    let audit_conn = state.audit.get()?;
+    let mut entry = AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.user.id))
+        .action(AuditAction::ExportAuditLogs)
+        .resource("audit_logs", &scope)
+        .details(&details)
+        .names(&ctx.audit_names())
+        .auth_method(&ctx.auth_method);
+    if let Some(org_id) = &query.org_id {
+        entry = entry.org(org_id);
+    }
+    entry.save()?;
+
+    let filename = audit_csv::filename(&scope, state.clock.now());
+    let body = blocking_stream_body("Audit log export".to_string(), move |out| {
+        audit_csv::write_csv(
+            &audit_conn,
+            &query,
+            |logs| resolve_user_names(&state, logs),
+            out,
+        )
+        .map(|_| ())
+    });
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyChainQuery {
+    /// Unix timestamp bounds; the whole chain when omitted
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+}
+
+/// Re-walk the audit log hash chains and report the first break in each.
+/// Covers every org, so partner operators can't use it.
+pub async fn verify_audit_chain(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OperatorContext>,
+    Query(query): Query<VerifyChainQuery>,
+) -> Result<Json<ChainVerification>> {
+    if ctx.role().is_org_scoped() {
+        return Err(AppError::Forbidden(
+            msg::PARTNER_AUDIT_CHAIN_FORBIDDEN.into(),
+        ));
+    }
+    let verification = tokio::task::spawn_blocking(move || -> Result<ChainVerification> {
+        let conn = state.audit.get()?;
+        audit_chain::verify(&conn, query.from, query.to)
+    })
+    .await
+    .map_err(|e| AppError::Internal(format!("Audit chain verification failed: {}", e)))??;
+    Ok(Json(verification))
+}
+
+/// Largest archive accepted for verification.
+pub const MAX_AUDIT_ARCHIVE_UPLOAD_BYTES: usize = 64 * 1024 * 1024;
+
+/// Check an archive downloaded from `POST /orgs/{org_id}/audit-logs/archive`
+/// (raw gzip body). Tampering is reported as `valid: false` with a reason,
+/// not as an error status.
+pub async fn verify_audit_archive(
+    State(state): State<AppState>,
+    body: Bytes,
+) -> Result<Json<ArchiveVerification>> {
+    let verification = tokio::task::spawn_blocking(move || {
+        audit_archive::verify_archive(body.as_ref(), &state.master_key)
+    })
+    .await
+    .map_err(|e| AppError::Internal(format!("Archive verification failed: {}", e)))?;
+    Ok(Json(verification))
+}
+
+/// Partner operators may only read one of their scoped orgs' logs at a time.
+fn check_org_scope(state: &AppState, ctx: &OperatorContext, query: &AuditLogQuery) -> Result<()> {
+    if !ctx.role().is_org_scoped() {
+        return Ok(());
+    }
+    let org_id = query
+        .org_id
+        .as_deref()
+        .ok_or_else(|| AppError::Forbidden(msg::PARTNER_AUDIT_LOGS_NEED_ORG.into()))?;
+    ctx.require_org_access(&state.db.get()?, org_id)
+}
+
+/// Fill in user names and emails left out of entries written under
+/// PII minimization, from the current user records (deleted users included).
+/// Users that were hard deleted stay as IDs.
+fn resolve_user_names(state: &AppState, logs: &mut [AuditLog]) -> Result<()> {
+    let conn = state.db.get()?;
+    let mut users = UserLookup {
+        conn: &conn,
+        emails: state.emails(),
+        cache: HashMap::new(),
+    };
+
+    for log in logs.iter_mut() {
+        if log.user_email.is_none()
+            && let Some(user_id) = &log.user_id
+            && let Some((name, email)) = users.get(user_id)?
+        {
+            log.user_name.get_or_insert(name);
+            log.user_email = Some(email);
+        }
+
+        if log.resource_email.is_none() {
+            let user_id = match log.resource_type.as_str() {
+                "user" | "operator" => Some(log.resource_id.clone()),
+                "org_member" => match queries::get_org_member_by_id(&conn, &log.resource_id)? {
+                    Some(member) => Some(member.user_id),
+                    None => queries::get_deleted_org_member_by_id(&conn, &log.resource_id)?
+                        .map(|m| m.user_id),
+                },
+                "project_member" => match &log.project_id {
+                    Some(project_id) => queries::get_project_member_user_id(
+                        &*state.project_db(project_id)?.get()?,
+                        &log.resource_id,
+                    )?,
+                    None => None,
+                },
+                "api_key" => {
+                    queries::get_api_key_by_id(&conn, &log.resource_id)?.map(|k| k.user_id)
+                }
+                _ => None,
+            };
+            if let Some(user_id) = user_id
+                && let Some((name, email)) = users.get(&user_id)?
+            {
+                log.resource_name.get_or_insert(name);
+                log.resource_email = Some(email);
+            }
+        }
+
+        // Impersonating operator, recorded in details as {user_id, name, email}
+        if let Some(impersonator) = log
+            .details
+            .as_mut()
+            .and_then(|d| d.get_mut("impersonator"))
+            .and_then(|i| i.as_object_mut())
+            && !impersonator.contains_key("email")
+            && let Some(user_id) = impersonator.get("user_id").and_then(|u| u.as_str())
+            && let Some((name, email)) = users.get(user_id)?
+        {
+            impersonator.insert("name".into(), name.into());
+            impersonator.insert("email".into(), email.into());
+        }
+    }
+    Ok(())
+}
+
+/// Cached (name, email) lookups by user ID.
+struct UserLookup<'a> {
+    conn: &'a Connection,
+    emails: EmailColumn,
+    cache: HashMap<String, Option<(String, String)>>,
+}
+
+impl UserLookup<'_> {
+    fn get(&mut self, user_id: &str) -> Result<Option<(String, String)>> {
+        if let Some(cached) = self.cache.get(user_id) {
+            return Ok(cached.clone());
+        }
+        let user = match queries::get_user_by_id(self.conn, user_id, &self.emails)? {
+            Some(user) => Some(user),
+            None => queries::get_deleted_user_by_id(self.conn, user_id, &self.emails)?,
+        };
+        let resolved = user.map(|u| (u.name, u.email));
+        self.cache.insert(user_id.to_string(), resolved.clone());
+        Ok(resolved)
+    }
+}
@@ -0,0 +1,18 @@
+//! Operator view of the server's configuration.
+
+use std::sync::Arc;
+
+use axum::extract::Extension;
+
+use crate::extractors::Json;
+
+/// [`crate::config::Config::redacted`], computed once at startup
+#[derive(Clone)]
+pub struct RedactedConfig(pub Arc<serde_json::Value>);
+
+/// GET /operators/config
+/// The settings the server started with, after file and environment are
+/// merged and defaults applied. Keys and API keys read "[redacted]".
+pub async fn get_config(Extension(config): Extension<RedactedConfig>) -> Json<serde_json::Value> {
+    Json(config.0.as_ref().clone())
+}
@@ -0,0 +1,263 @@
+//! Custom domains: vendor hostnames serving a project's public endpoints
+//! (see [`crate::custom_domains`]). Adding one hands out the TXT record to
+//! publish; verifying looks it up; only verified domains route requests.
+
+use axum::{
+    extract::{Extension, State},
+    http::HeaderMap,
+};
+use reqwest::Url;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::custom_domains::{self, DnsResolver};
+use crate::db::{AppState, queries};
+use crate::error::{AppError, OptionExt, Result, msg};
+use crate::extractors::{Json, Path};
+use crate::middleware::OperatorContext;
+use crate::models::{
+    ActorType, AuditAction, CustomDomain, Project, serialize_optional_timestamp,
+    serialize_timestamp,
+};
+use crate::util::AuditLogBuilder;
+
+#[derive(Debug, Deserialize)]
+pub struct CustomDomainsPath {
+    pub org_id: String,
+    pub project_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CustomDomainPath {
+    pub org_id: String,
+    pub project_id: String,
+    pub hostname: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddCustomDomain {
+    /// e.g. `licenses.example.com`
+    pub hostname: String,
+}
+
+/// The TXT record that proves the vendor controls the hostname.
+#[derive(Debug, Serialize)]
+pub struct TxtRecord {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CustomDomainStatus {
+    pub hostname: String,
+    pub project_id: String,
+    /// Whether requests to the hostname are routed to the project
+    pub verified: bool,
+    #[serde(serialize_with = "serialize_optional_timestamp")]
+    pub verified_at: Option<i64>,
+    pub txt_record: TxtRecord,
+    #[serde(serialize_with = "serialize_timestamp")]
+    pub created_at: i64,
+    /// Why the last verification attempt didn't succeed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verification_error: Option<String>,
+}
+
+impl From<CustomDomain> for CustomDomainStatus {
+    fn from(domain: CustomDomain) -> Self {
+        Self {
+            txt_record: TxtRecord {
+                name: custom_domains::challenge_name(&domain.hostname),
+                value: domain.verification_token,
+            },
+            hostname: domain.hostname,
+            project_id: domain.project_id,
+            verified: domain.verified_at.is_some(),
+            verified_at: domain.verified_at,
+            created_at: domain.created_at,
+            verification_error: None,
+        }
+    }
+}
+
+/// The project, if it belongs to the org in the path.
+fn get_project(state: &AppState, org_id: &str, project_id: &str) -> Result<Project> {
+    let conn = state.org_db(org_id).get()?;
+    queries::get_project_by_id(&conn, project_id)?
+        .filter(|project| project.org_id == org_id)
+        .or_not_found(msg::PROJECT_NOT_FOUND)
+}
+
+/// The custom domain, if it's bound to the project in the path.
+fn get_domain(conn: &Connection, path: &CustomDomainPath) -> Result<CustomDomain> {
+    let hostname = custom_domains::normalize_hostname(&path.hostname)
+        .or_not_found(msg::CUSTOM_DOMAIN_NOT_FOUND)?;
+    queries::get_custom_domain(conn, &hostname)?
+        .filter(|domain| domain.project_id == path.project_id)
+        .or_not_found(msg::CUSTOM_DOMAIN_NOT_FOUND)
+}
+
+/// GET /operators/organizations/{org_id}/projects/{project_id}/custom-domains
+pub async fn list_custom_domains(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OperatorContext>,
+    Path(path): Path<CustomDomainsPath>,
+) -> Result<Json<Vec<CustomDomainStatus>>> {
+    let conn = state.db.get()?;
+    ctx.require_org_access(&conn, &path.org_id)?;
+    let project = get_project(&state, &path.org_id, &path.project_id)?;
+    let domains = queries::list_custom_domains_for_project(&conn, &project.id)?;
+    Ok(Json(domains.into_iter().map(Into::into).collect()))
+}
+
+/// POST /operators/organizations/{org_id}/projects/{project_id}/custom-domains
+/// Bind a hostname to the project, unverified. The response has the TXT
+/// record the vendor must publish before verifying.
+pub async fn add_custom_domain(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OperatorContext>,
+    headers: HeaderMap,
+    Path(path): Path<CustomDomainsPath>,
+    Json(input): Json<AddCustomDomain>,
+) -> Result<Json<CustomDomainStatus>> {
+    let hostname = custom_domains::normalize_hostname(&input.hostname)
+        .ok_or_else(|| AppError::BadRequest(msg::CUSTOM_DOMAIN_INVALID.into()))?;
+    let base_host = Url::parse(&state.base_url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_ascii_lowercase));
+    if base_host.as_deref() == Some(hostname.as_str()) {
+        return Err(AppError::BadRequest(msg::CUSTOM_DOMAIN_IS_BASE_URL.into()));
+    }
+
+    let conn = state.db.get()?;
+    ctx.require_org_access(&conn, &path.org_id)?;
+    let audit_conn = state.audit.get()?;
+    let project = get_project(&state, &path.org_id, &path.project_id)?;
+
+    if queries::get_custom_domain(&conn, &hostname)?.is_some() {
+        return Err(AppError::Conflict(msg::CUSTOM_DOMAIN_TAKEN.into()));
+    }
+    let domain = queries::create_custom_domain(
+        &conn,
+        &path.org_id,
+        &project.id,
+        &hostname,
+        &custom_domains::generate_verification_token(),
+        Some(&ctx.user.id),
+    )?;
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.user.id))
+        .action(AuditAction::AddCustomDomain)
+        .resource("project", &project.id)
+        .details(&serde_json::json!({ "hostname": hostname }))
+        .org(&path.org_id)
+        .project(&project.id)
+        .names(&ctx.audit_names().resource(project.name.clone()))
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    Ok(Json(domain.into()))
+}
+
+/// POST /operators/organizations/{org_id}/projects/{project_id}/custom-domains/{hostname}/verify
+/// Look up the hostname's `_paycheck-challenge` TXT record and, if it has the
+/// verification token, start routing the hostname to the project. When it
+/// doesn't, the domain stays unverified and `verification_error` says why.
+pub async fn verify_custom_domain(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OperatorContext>,
+    Extension(resolver): Extension<DnsResolver>,
+    headers: HeaderMap,
+    Path(path): Path<CustomDomainPath>,
+) -> Result<Json<CustomDomainStatus>> {
+    let conn = state.db.get()?;
+    ctx.require_org_access(&conn, &path.org_id)?;
+    let audit_conn = state.audit.get()?;
+    let project = get_project(&state, &path.org_id, &path.project_id)?;
+    let domain = get_domain(&conn, &path)?;
+    if domain.verified_at.is_some() {
+        return Ok(Json(domain.into()));
+    }
+
+    let record_name = custom_domains::challenge_name(&domain.hostname);
+    let verification_error = match resolver.txt_records(&record_name).await {
+        Ok(records) if records.contains(&domain.verification_token) => None,
+        Ok(_) => Some(format!(
+            "No TXT record at {} has the verification token",
+            record_name
+        )),
+        Err(e) => {
+            tracing::warn!(hostname = %domain.hostname, "TXT lookup failed: {}", e);
+            Some(msg::CUSTOM_DOMAIN_LOOKUP_FAILED.to_string())
+        }
+    };
+    if let Some(verification_error) = verification_error {
+        return Ok(Json(CustomDomainStatus {
+            verification_error: Some(verification_error),
+            ..domain.into()
+        }));
+    }
+
+    let now = state.clock.now();
+    queries::mark_custom_domain_verified(&conn, &domain.hostname, now)?;
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.user.id))
+        .action(AuditAction::VerifyCustomDomain)
+        .resource("project", &project.id)
+        .details(&serde_json::json!({ "hostname": domain.hostname }))
+        .org(&path.org_id)
+        .project(&project.id)
+        .names(&ctx.audit_names().resource(project.name.clone()))
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    tracing::info!(
+        "Verified custom domain {} (project: {})",
+        domain.hostname,
+        project.id
+    );
+
+    Ok(Json(
+        CustomDomain {
+            verified_at: Some(now),
+            ..domain
+        }
+        .into(),
+    ))
+}
+
+/// DELETE /operators/organizations/{org_id}/projects/{project_id}/custom-domains/{hostname}
+/// Stop routing the hostname to the project. Adding it again issues a new
+/// verification token.
+pub async fn remove_custom_domain(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OperatorContext>,
+    headers: HeaderMap,
+    Path(path): Path<CustomDomainPath>,
+) -> Result<Json<CustomDomainStatus>> {
+    let conn = state.db.get()?;
+    ctx.require_org_access(&conn, &path.org_id)?;
+    let audit_conn = state.audit.get()?;
+    let project = get_project(&state, &path.org_id, &path.project_id)?;
+    let domain = get_domain(&conn, &path)?;
+
+    queries::delete_custom_domain(&conn, &domain.hostname)?;
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.user.id))
+        .action(AuditAction::RemoveCustomDomain)
+        .resource("project", &project.id)
+        .details(&serde_json::json!({
+            "hostname": domain.hostname,
+            "was_verified": domain.verified_at.is_some(),
+        }))
+        .org(&path.org_id)
+        .project(&project.id)
+        .names(&ctx.audit_names().resource(project.name.clone()))
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    Ok(Json(domain.into()))
+}
@@ -0,0 +1,49 @@
+use axum::{
+    extract::{Extension, State},
+    http::HeaderMap,
+};
+use serde::Serialize;
+
+use crate::db::AppState;
+use crate::error::Result;
+use crate::extractors::Json;
+use crate::middleware::OperatorContext;
+use crate::models::{ActorType, AuditAction};
+use crate::util::AuditLogBuilder;
+
+#[derive(Debug, Serialize)]
+pub struct RefreshJwksResponse {
+    /// Cached key sets dropped (one per trusted issuer JWKS URL)
+    pub cleared: usize,
+}
+
+/// POST /operators/jwks/refresh
+/// Drop every cached trusted issuer JWKS so the next first-party token from
+/// each issuer refetches its keys. Use after an issuer rotates its signing
+/// key, instead of waiting out the cache TTL.
+pub async fn refresh_jwks(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OperatorContext>,
+    headers: HeaderMap,
+) -> Result<Json<RefreshJwksResponse>> {
+    let audit_conn = state.audit.get()?;
+
+    let cleared = state.jwks_cache.clear();
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.user.id))
+        .action(AuditAction::RefreshJwksCache)
+        .resource("jwks_cache", "trusted_issuers")
+        .details(&serde_json::json!({ "cleared": cleared }))
+        .names(&ctx.audit_names())
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    tracing::info!(
+        "JWKS cache cleared by operator {} ({} key set(s))",
+        ctx.user.id,
+        cleared
+    );
+
+    Ok(Json(RefreshJwksResponse { cleared }))
+}
@@ -0,0 +1,122 @@
+use axum::{
+    extract::{Extension, State},
+    http::HeaderMap,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::db::AppState;
+use crate::error::{AppError, Result, msg};
+use crate::extractors::Json;
+use crate::middleware::{DEFAULT_MAINTENANCE_MESSAGE, MaintenanceSettings, OperatorContext};
+use crate::models::{ActorType, AuditAction};
+use crate::util::AuditLogBuilder;
+
+/// Longest message accepted for the 503 responses.
+const MAX_MESSAGE_CHARS: usize = 500;
+
+#[derive(Debug, Deserialize)]
+pub struct SetMaintenanceMode {
+    pub enabled: bool,
+    /// Shown to clients while maintenance mode is on
+    #[serde(default)]
+    pub message: Option<String>,
+    /// Keep serving GET requests and `/validate` (default true)
+    #[serde(default = "default_allow_reads")]
+    pub allow_reads: bool,
+}
+
+fn default_allow_reads() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize)]
+pub struct MaintenanceModeStatus {
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub settings: Option<MaintenanceSettings>,
+}
+
+impl From<Option<MaintenanceSettings>> for MaintenanceModeStatus {
+    fn from(settings: Option<MaintenanceSettings>) -> Self {
+        Self {
+            enabled: settings.is_some(),
+            settings,
+        }
+    }
+}
+
+/// GET /operators/maintenance-mode
+pub async fn get_maintenance_mode(State(state): State<AppState>) -> Json<MaintenanceModeStatus> {
+    Json(state.maintenance.current().into())
+}
+
+/// POST /operators/maintenance-mode
+/// Turn maintenance mode on or off. While it's on, writes everywhere get a
+/// 503 with the message, and reads too unless `allow_reads` is set.
+pub async fn set_maintenance_mode(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OperatorContext>,
+    headers: HeaderMap,
+    Json(input): Json<SetMaintenanceMode>,
+) -> Result<Json<MaintenanceModeStatus>> {
+    let settings = if input.enabled {
+        let message = match input.message {
+            Some(message) => {
+                let message = message.trim().to_string();
+                if message.is_empty() || message.chars().count() > MAX_MESSAGE_CHARS {
+                    return Err(AppError::BadRequest(
+                        msg::MAINTENANCE_MESSAGE_INVALID.into(),
+                    ));
+                }
+                message
+            }
+            None => DEFAULT_MAINTENANCE_MESSAGE.to_string(),
+        };
+        Some(MaintenanceSettings {
+            message,
+            allow_reads: input.allow_reads,
+            enabled_at: state.clock.now(),
+            enabled_by: ctx.user.id.clone(),
+        })
+    } else {
+        None
+    };
+
+    let conn = state.db.get()?;
+    let audit_conn = state.audit.get()?;
+
+    state.maintenance.set(&conn, settings.clone())?;
+
+    let action = if input.enabled {
+        AuditAction::EnableMaintenanceMode
+    } else {
+        AuditAction::DisableMaintenanceMode
+    };
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.user.id))
+        .action(action)
+        .resource("system_config", "maintenance_mode")
+        .details(&serde_json::json!({
+            "message": settings.as_ref().map(|s| &s.message),
+            "allow_reads": settings.as_ref().map(|s| s.allow_reads),
+        }))
+        .names(&ctx.audit_names())
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    if input.enabled {
+        tracing::warn!(
+            "Maintenance mode enabled by operator {} (reads {})",
+            ctx.user.id,
+            if input.allow_reads {
+                "allowed"
+            } else {
+                "blocked"
+            }
+        );
+    } else {
+        tracing::info!("Maintenance mode disabled by operator {}", ctx.user.id);
+    }
+
+    Ok(Json(settings.into()))
+}
@@ -3,11 +3,17 @@ use axum::{
     http::HeaderMap,
 };
 
+use rusqlite::Connection;
+use serde::Deserialize;
+
 use crate::db::{AppState, queries};
 use crate::error::{AppError, OptionExt, Result, msg};
 use crate::extractors::{Json, Path};
 use crate::middleware::OperatorContext;
-use crate::models::{ActorType, AuditAction, CreateOperator, UpdateOperator, User};
+use crate::models::{
+    ActorType, AuditAction, CreateOperator, CreateOperatorOrgScope, OperatorOrgScope,
+    UpdateOperator, User,
+};
 use crate::pagination::{Paginated, PaginationQuery};
 use crate::util::AuditLogBuilder;
 
@@ -24,16 +30,16 @@ pub async fn create_operator(
     let audit_conn = state.audit.get()?;
 
     // Verify the user exists and doesn't already have an operator role
-    let user = queries::get_user_by_id(&conn, &input.user_id)?
+    let user = queries::get_user_by_id(&conn, &input.user_id, &state.emails())?
         .ok_or_else(|| AppError::BadRequest(msg::USER_NOT_FOUND.into()))?;
 
     if user.operator_role.is_some() {
         return Err(AppError::BadRequest("User is already an operator".into()));
     }
 
-    let updated_user = queries::grant_operator_role(&conn, &input.user_id, input.role)?;
+    let updated_user = queries::grant_operator_role(&conn, &input.user_id, input.role, &state.emails())?;
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::User, Some(&ctx.user.id))
         .action(AuditAction::CreateOperator)
         .resource("operator", &input.user_id)
@@ -57,7 +63,7 @@ pub async fn list_operators(
     let conn = state.db.get()?;
     let limit = pagination.limit();
     let offset = pagination.offset();
-    let (operators, total) = queries::list_operators_paginated(&conn, limit, offset)?;
+    let (operators, total) = queries::list_operators_paginated(&conn, limit, offset, &state.emails())?;
     Ok(Json(Paginated::new(operators, total, limit, offset)))
 }
 
@@ -67,7 +73,7 @@ pub async fn get_operator(
     Path(user_id): Path<String>,
 ) -> Result<Json<User>> {
     let conn = state.db.get()?;
-    let user = queries::get_user_by_id(&conn, &user_id)?
+    let user = queries::get_user_by_id(&conn, &user_id, &state.emails())?
         .or_not_found(msg::USER_NOT_FOUND)?;
 
     if user.operator_role.is_none() {
@@ -92,7 +98,7 @@ pub async fn update_operator(
         return Err(AppError::BadRequest(msg::CANNOT_CHANGE_OWN_ROLE.into()));
     }
 
-    let existing = queries::get_user_by_id(&conn, &user_id)?
+    let existing = queries::get_user_by_id(&conn, &user_id, &state.emails())?
         .or_not_found(msg::USER_NOT_FOUND)?;
 
     if existing.operator_role.is_none() {
@@ -100,13 +106,13 @@ pub async fn update_operator(
     }
 
     let updated_user = if let Some(role) = input.role {
-        queries::update_operator_role(&conn, &user_id, role)?
+        queries::update_operator_role(&conn, &user_id, role, &state.emails())?
             .ok_or_else(|| AppError::Internal(msg::USER_NOT_FOUND_AFTER_UPDATE.into()))?
     } else {
         existing.clone()
     };
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::User, Some(&ctx.user.id))
         .action(AuditAction::UpdateOperator)
         .resource("operator", &user_id)
@@ -135,7 +141,7 @@ pub async fn delete_operator(
         return Err(AppError::BadRequest(msg::CANNOT_DELETE_SELF.into()));
     }
 
-    let existing = queries::get_user_by_id(&conn, &user_id)?
+    let existing = queries::get_user_by_id(&conn, &user_id, &state.emails())?
         .or_not_found(msg::USER_NOT_FOUND)?;
 
     if existing.operator_role.is_none() {
@@ -144,7 +150,7 @@ pub async fn delete_operator(
 
     queries::revoke_operator_role(&conn, &user_id)?;
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::User, Some(&ctx.user.id))
         .action(AuditAction::DeleteOperator)
         .resource("operator", &user_id)
@@ -161,3 +167,104 @@ pub async fn delete_operator(
 
     Ok(Json(serde_json::json!({ "success": true })))
 }
+
+/// Load an operator that can be given org scopes (partners only)
+fn get_scoped_operator(state: &AppState, conn: &Connection, user_id: &str) -> Result<User> {
+    let user = queries::get_user_by_id(conn, user_id, &state.emails())?
+        .or_not_found(msg::USER_NOT_FOUND)?;
+
+    match user.operator_role {
+        None => Err(AppError::NotFound(msg::NOT_OPERATOR.into())),
+        Some(role) if !role.is_org_scoped() => {
+            Err(AppError::BadRequest(msg::OPERATOR_NOT_ORG_SCOPED.into()))
+        }
+        Some(_) => Ok(user),
+    }
+}
+
+/// List the orgs a partner operator can access
+pub async fn list_operator_org_scopes(
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+) -> Result<Json<Vec<OperatorOrgScope>>> {
+    let conn = state.db.get()?;
+    get_scoped_operator(&state, &conn, &user_id)?;
+    Ok(Json(queries::list_operator_org_scopes(&conn, &user_id)?))
+}
+
+/// Give a partner operator access to an org
+pub async fn add_operator_org_scope(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OperatorContext>,
+    headers: HeaderMap,
+    Path(user_id): Path<String>,
+    Json(input): Json<CreateOperatorOrgScope>,
+) -> Result<Json<OperatorOrgScope>> {
+    let conn = state.db.get()?;
+    let audit_conn = state.audit.get()?;
+
+    let operator = get_scoped_operator(&state, &conn, &user_id)?;
+    let org = queries::get_organization_by_id(&conn, &input.org_id)?
+        .ok_or_else(|| AppError::BadRequest(msg::ORG_NOT_FOUND.into()))?;
+
+    let scope = queries::add_operator_org_scope(&conn, &user_id, &org.id)?
+        .ok_or_else(|| AppError::Conflict(msg::OPERATOR_ORG_SCOPE_EXISTS.into()))?;
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.user.id))
+        .action(AuditAction::AddOperatorOrgScope)
+        .resource("operator", &user_id)
+        .org(&org.id)
+        .details(&serde_json::json!({ "org_id": org.id }))
+        .names(
+            &ctx.audit_names()
+                .resource_user(&operator.name, &operator.email)
+                .org(org.name.clone()),
+        )
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    Ok(Json(scope))
+}
+
+#[derive(Deserialize)]
+pub struct OperatorOrgScopePath {
+    pub user_id: String,
+    pub org_id: String,
+}
+
+/// Take away a partner operator's access to an org
+pub async fn remove_operator_org_scope(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OperatorContext>,
+    headers: HeaderMap,
+    Path(path): Path<OperatorOrgScopePath>,
+) -> Result<Json<serde_json::Value>> {
+    let OperatorOrgScopePath { user_id, org_id } = path;
+    let conn = state.db.get()?;
+    let audit_conn = state.audit.get()?;
+
+    let operator = get_scoped_operator(&state, &conn, &user_id)?;
+
+    if !queries::remove_operator_org_scope(&conn, &user_id, &org_id)? {
+        return Err(AppError::NotFound(msg::OPERATOR_ORG_SCOPE_NOT_FOUND.into()));
+    }
+
+    let org_name = queries::get_organization_by_id(&conn, &org_id)?.map(|org| org.name);
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.user.id))
+        .action(AuditAction::RemoveOperatorOrgScope)
+        .resource("operator", &user_id)
+        .org(&org_id)
+        .details(&serde_json::json!({ "org_id": org_id }))
+        .names(
+            &ctx.audit_names()
+                .resource_user(&operator.name, &operator.email)
+                .org(org_name),
+        )
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    Ok(Json(serde_json::json!({ "success": true })))
+}
@@ -1,24 +1,52 @@
 mod api_keys;
 mod audit_logs;
+mod backups;
+mod config;
+mod custom_domains;
+mod jwks;
+mod maintenance;
 mod management;
 mod organizations;
+mod reconciliation;
+mod status_page;
+mod storage_health;
 mod support;
 mod users;
+mod webhook_mirror;
+mod workspaces;
 
 pub use api_keys::*;
 pub use audit_logs::*;
+pub use backups::*;
+pub use config::*;
+pub use custom_domains::*;
+pub use jwks::*;
+pub use maintenance::*;
 pub use management::*;
 pub use organizations::*;
+pub use reconciliation::*;
+pub use status_page::*;
+pub use storage_health::*;
 pub use support::*;
 pub use users::*;
+pub use webhook_mirror::*;
+pub use workspaces::*;
+
+use std::sync::Arc;
 
 use axum::{
-    Router, middleware,
+    Extension, Router,
+    extract::DefaultBodyLimit,
+    middleware,
     routing::{delete, get, post, put},
 };
 
+use crate::config::Config;
+use crate::custom_domains::DnsResolver;
 use crate::db::AppState;
-use crate::middleware::{operator_auth, require_admin_role, require_owner_role};
+use crate::middleware::{
+    operator_auth, require_admin_role, require_org_operator_role, require_owner_role,
+};
 
 pub fn router(state: AppState) -> Router<AppState> {
     Router::new()
@@ -28,6 +56,26 @@ pub fn router(state: AppState) -> Router<AppState> {
         .route("/operators/{user_id}", get(get_operator))
         .route("/operators/{user_id}", put(update_operator))
         .route("/operators/{user_id}", delete(delete_operator))
+        // Partner org scopes (owner only)
+        .route(
+            "/operators/{user_id}/org-scopes",
+            get(list_operator_org_scopes),
+        )
+        .route(
+            "/operators/{user_id}/org-scopes",
+            post(add_operator_org_scope),
+        )
+        .route(
+            "/operators/{user_id}/org-scopes/{org_id}",
+            delete(remove_operator_org_scope),
+        )
+        // Maintenance mode (owner only)
+        .route("/operators/maintenance-mode", post(set_maintenance_mode))
+        // Merging duplicate users (owner only)
+        .route("/operators/users/{user_id}/merge", post(merge_user))
+        // Database backups (owner only)
+        .route("/operators/backups", post(create_backup))
+        .route("/operators/backups/{backup_id}/verify", post(verify_backup))
         .layer(middleware::from_fn_with_state(
             state.clone(),
             require_owner_role,
@@ -48,7 +96,35 @@ pub fn router(state: AppState) -> Router<AppState> {
                     "/operators/users/{user_id}/hard-delete",
                     post(users::hard_delete_user),
                 )
-                // Organization management (admin+)
+                // User API keys (admin+)
+                .route(
+                    "/operators/users/{user_id}/api-keys",
+                    post(api_keys::create_api_key),
+                )
+                .route(
+                    "/operators/users/{user_id}/api-keys",
+                    get(api_keys::list_api_keys),
+                )
+                .route(
+                    "/operators/users/{user_id}/api-keys/{key_id}",
+                    delete(api_keys::revoke_api_key),
+                )
+                // Trusted issuer key cache (admin+)
+                .route("/operators/jwks/refresh", post(refresh_jwks))
+                // Backup list (admin+)
+                .route("/operators/backups", get(list_backups))
+                // Storage heartbeats, disk space, and clocks (admin+)
+                .route("/operators/storage-health", get(get_storage_health))
+                // Org, owner, projects, and products in one transaction (admin+)
+                .route("/operators/workspaces", post(create_workspace))
+                .layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    require_admin_role,
+                )),
+        )
+        .merge(
+            Router::new()
+                // Organization management (admin+, partners within their orgs)
                 .route("/operators/organizations", post(create_organization))
                 .route("/operators/organizations", get(list_organizations))
                 .route("/operators/organizations/{org_id}", get(get_organization))
@@ -68,7 +144,15 @@ pub fn router(state: AppState) -> Router<AppState> {
                     "/operators/organizations/{org_id}/hard-delete",
                     post(hard_delete_organization),
                 )
-                // Support endpoints (admin+)
+                .route(
+                    "/operators/organizations/{org_id}/limits",
+                    get(get_org_limits),
+                )
+                .route(
+                    "/operators/organizations/{org_id}/limits/{limit_name}",
+                    put(set_org_limit),
+                )
+                // Support endpoints (admin+, partners within their orgs)
                 .route(
                     "/operators/organizations/{org_id}/payment-provider",
                     get(get_org_payment_config),
@@ -77,29 +161,92 @@ pub fn router(state: AppState) -> Router<AppState> {
                     "/operators/organizations/{org_id}/projects/{project_id}/licenses/lookup",
                     get(lookup_licenses_by_email),
                 )
-                // User API keys (admin+)
+                // Webhook mirrors (admin+, partners within their orgs)
                 .route(
-                    "/operators/users/{user_id}/api-keys",
-                    post(api_keys::create_api_key),
+                    "/operators/organizations/{org_id}/projects/{project_id}/webhook-mirror",
+                    get(get_webhook_mirror),
                 )
                 .route(
-                    "/operators/users/{user_id}/api-keys",
-                    get(api_keys::list_api_keys),
+                    "/operators/organizations/{org_id}/projects/{project_id}/webhook-mirror",
+                    put(set_webhook_mirror),
                 )
                 .route(
-                    "/operators/users/{user_id}/api-keys/{key_id}",
-                    delete(api_keys::revoke_api_key),
+                    "/operators/organizations/{org_id}/projects/{project_id}/webhook-mirror",
+                    delete(clear_webhook_mirror),
+                )
+                // Subscription reconciliation (admin+, partners within their orgs)
+                .route("/operators/reconcile", post(reconcile_subscriptions))
+                .route(
+                    "/operators/reconciliation-runs/{run_id}",
+                    get(get_reconciliation_run),
                 )
                 .layer(middleware::from_fn_with_state(
                     state.clone(),
-                    require_admin_role,
+                    require_org_operator_role,
                 )),
         )
         .merge(
             Router::new()
-                // Audit logs (view+)
+                // Maintenance mode status (view+)
+                .route("/operators/maintenance-mode", get(get_maintenance_mode))
+                // Audit logs and their verification (view+; partners must
+                // filter logs to one of their orgs and can't verify the chain)
                 .route("/operators/audit-logs", get(query_audit_logs))
                 .route("/operators/audit-logs/text", get(query_audit_logs_text))
+                .route("/operators/audit-logs/export", get(export_audit_logs))
+                .route(
+                    "/operators/audit-logs/verify-chain",
+                    get(verify_audit_chain),
+                )
+                .route(
+                    "/operators/audit-archives/verify",
+                    post(verify_audit_archive)
+                        .layer(DefaultBodyLimit::max(MAX_AUDIT_ARCHIVE_UPLOAD_BYTES)),
+                )
                 .layer(middleware::from_fn_with_state(state.clone(), operator_auth)),
         )
 }
+
+/// Embedded status page and its login form. Authenticates with its own
+/// session cookie rather than the operator middleware, and is only mounted
+/// when `PAYCHECK_STATUS_PAGE` is enabled.
+pub fn status_page_router() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/operators/login-page",
+            get(status_login_page).post(status_login),
+        )
+        .route("/operators/status-page", get(status_page))
+}
+
+/// Running configuration (admin+). Separate from [`router`] because it
+/// serves the config the server started with, which `AppState` doesn't keep.
+pub fn config_router(state: AppState, config: &Config) -> Router<AppState> {
+    Router::new()
+        .route("/operators/config", get(get_config))
+        .layer(Extension(RedactedConfig(Arc::new(config.redacted()))))
+        .layer(middleware::from_fn_with_state(state, require_admin_role))
+}
+
+/// Custom domain management (admin+, partners within their orgs). Separate
+/// from [`router`] so tests can point verification at a mock resolver.
+pub fn custom_domains_router(state: AppState, resolver: DnsResolver) -> Router<AppState> {
+    Router::new()
+        .route(
+            "/operators/organizations/{org_id}/projects/{project_id}/custom-domains",
+            get(list_custom_domains).post(add_custom_domain),
+        )
+        .route(
+            "/operators/organizations/{org_id}/projects/{project_id}/custom-domains/{hostname}",
+            delete(remove_custom_domain),
+        )
+        .route(
+            "/operators/organizations/{org_id}/projects/{project_id}/custom-domains/{hostname}/verify",
+            post(verify_custom_domain),
+        )
+        .layer(Extension(resolver))
+        .layer(middleware::from_fn_with_state(
+            state,
+            require_org_operator_role,
+        ))
+}
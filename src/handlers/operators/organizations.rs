@@ -5,20 +5,22 @@ use axum::{
 use rusqlite::Connection;
 use serde::Deserialize;
 
-use crate::db::{AppState, queries};
+use crate::db::{AppState, outbox, queries};
 use crate::error::{AppError, OptionExt, Result, msg};
 use crate::extractors::{Json, Path};
 use crate::middleware::OperatorContext;
 use crate::models::{
-    ActorType, AuditAction, CreateOrgMember, CreateOrganization, OrgMemberRole, Organization,
-    OrganizationPublic, ServiceProvider, UpdateOrganization,
+    ActorType, AuditAction, CreateOrgMember, CreateOrganization, OrgLimitName, OrgLimitsResponse,
+    OrgMemberRole, OrgStats, Organization, OrganizationPublic, OrganizationWithStats,
+    ServiceProvider, SetOrgLimit, UpdateOrganization, check_expected_version,
 };
-use crate::pagination::Paginated;
+use crate::pagination::{Paginated, clamp_limit, clamp_offset};
+use crate::quota;
 use crate::util::AuditLogBuilder;
 use std::collections::HashMap;
 
 /// Helper to convert Organization to OrganizationPublic by querying service configs
-fn org_to_public(conn: &Connection, org: Organization) -> Result<OrganizationPublic> {
+pub(super) fn org_to_public(conn: &Connection, org: Organization) -> Result<OrganizationPublic> {
     let configs = queries::get_org_service_configs(conn, &org.id)?;
 
     // Group providers by category
@@ -39,9 +41,29 @@ fn org_to_public(conn: &Connection, org: Organization) -> Result<OrganizationPub
     Ok(OrganizationPublic::from_with_configs(org, configured_services, defaults))
 }
 
-/// Helper to convert multiple Organizations to OrganizationPublic
-fn orgs_to_public(conn: &Connection, orgs: Vec<Organization>) -> Result<Vec<OrganizationPublic>> {
-    orgs.into_iter().map(|org| org_to_public(conn, org)).collect()
+/// Helper to convert Organizations with their shared-database stats to
+/// OrganizationWithStats. Adds the latest audit log activity, and project and
+/// license counts for orgs whose tenant data is in a dedicated database.
+fn orgs_with_stats(
+    state: &AppState,
+    conn: &Connection,
+    orgs: Vec<(Organization, OrgStats)>,
+) -> Result<Vec<OrganizationWithStats>> {
+    let org_ids: Vec<&str> = orgs.iter().map(|(org, _)| org.id.as_str()).collect();
+    let last_audit_at = queries::get_orgs_last_audit_at(&state.audit.get()?, &org_ids)?;
+
+    orgs.into_iter()
+        .map(|(org, mut stats)| {
+            stats.record_activity(last_audit_at.get(&org.id).copied());
+            if let Some(pool) = state.org_dbs.get(&org.id) {
+                stats.add_tenant_stats(&queries::get_org_tenant_stats(&pool.get()?, &org.id)?);
+            }
+            Ok(OrganizationWithStats {
+                org: org_to_public(conn, org)?,
+                stats,
+            })
+        })
+        .collect()
 }
 
 pub async fn create_organization(
@@ -54,13 +76,21 @@ pub async fn create_organization(
 
     let conn = state.db.get()?;
     let audit_conn = state.audit.get()?;
-    let organization = queries::create_organization(&conn, &input)?;
+    let organization = queries::create_organization(&conn, &input, &state.master_key)?;
+
+    // Data residency mode: give the new org its own database file
+    state.org_dbs.provision(&conn, &organization.id)?;
+
+    // A partner keeps access to the orgs it creates
+    if ctx.role().is_org_scoped() {
+        queries::add_operator_org_scope(&conn, &ctx.user.id, &organization.id)?;
+    }
 
     // If owner_user_id is provided, create the first org member as owner
     // The user must already exist in the users table
     // No API key is created - owner uses Console (impersonation) or creates a key later
     let audit_details = if let Some(owner_user_id) = &input.owner_user_id {
-        let user = queries::get_user_by_id(&conn, owner_user_id)?
+        let user = queries::get_user_by_id(&conn, owner_user_id, &state.emails())?
             .ok_or_else(|| AppError::BadRequest(msg::OWNER_USER_NOT_FOUND.into()))?;
 
         queries::create_org_member(
@@ -81,7 +111,7 @@ pub async fn create_organization(
         serde_json::json!({ "name": input.name })
     };
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::User, Some(&ctx.user.id))
         .action(AuditAction::CreateOrg)
         .resource("org", &organization.id)
@@ -109,85 +139,90 @@ pub struct ListOrgsQuery {
 
 impl ListOrgsQuery {
     fn limit(&self) -> i64 {
-        self.limit.unwrap_or(50).clamp(1, 100)
+        clamp_limit(self.limit)
     }
 
     fn offset(&self) -> i64 {
-        self.offset.unwrap_or(0).max(0)
+        clamp_offset(self.offset)
     }
 }
 
 pub async fn list_organizations(
     State(state): State<AppState>,
+    Extension(ctx): Extension<OperatorContext>,
     Query(query): Query<ListOrgsQuery>,
-) -> Result<Json<Paginated<OrganizationPublic>>> {
+) -> Result<Json<Paginated<OrganizationWithStats>>> {
     let conn = state.db.get()?;
     let limit = query.limit();
     let offset = query.offset();
 
-    let (organizations, total) = if let Some(user_id) = &query.user_id {
-        // Filter by user ID - returns orgs where user is a member
-        // Note: include_deleted is not supported for this filter
-        queries::list_orgs_by_user_id_paginated(&conn, user_id, limit, offset)?
-    } else {
-        queries::list_organizations_paginated(&conn, limit, offset, query.include_deleted)?
-    };
-
-    let organizations_public = orgs_to_public(&conn, organizations)?;
-
-    Ok(Json(Paginated::new(
-        organizations_public,
-        total,
+    // Partners only see their scoped orgs
+    let scoped_operator_id = ctx.role().is_org_scoped().then_some(ctx.user.id.as_str());
+    let (organizations, total) = queries::list_organizations_with_stats_paginated(
+        &conn,
+        scoped_operator_id,
+        query.user_id.as_deref(),
+        query.include_deleted,
         limit,
         offset,
-    )))
+    )?;
+
+    let organizations = orgs_with_stats(&state, &conn, organizations)?;
+
+    Ok(Json(Paginated::new(organizations, total, limit, offset)))
 }
 
 pub async fn get_organization(
     State(state): State<AppState>,
+    Extension(ctx): Extension<OperatorContext>,
     Path(id): Path<String>,
-) -> Result<Json<OrganizationPublic>> {
+) -> Result<Json<OrganizationWithStats>> {
     let conn = state.db.get()?;
+    ctx.require_org_access(&conn, &id)?;
     let organization =
-        queries::get_organization_by_id(&conn, &id)?.or_not_found(msg::ORG_NOT_FOUND)?;
-    Ok(Json(org_to_public(&conn, organization)?))
+        queries::get_organization_with_stats(&conn, &id)?.or_not_found(msg::ORG_NOT_FOUND)?;
+    let mut organizations = orgs_with_stats(&state, &conn, vec![organization])?;
+    Ok(Json(organizations.remove(0)))
 }
 
-pub async fn update_organization(
-    State(state): State<AppState>,
-    Extension(ctx): Extension<OperatorContext>,
-    headers: HeaderMap,
-    Path(id): Path<String>,
-    Json(input): Json<UpdateOrganization>,
-) -> Result<Json<OrganizationPublic>> {
-    input.validate()?;
-
-    let conn = state.db.get()?;
-    let audit_conn = state.audit.get()?;
-
-    // Verify organization exists
-    let existing = queries::get_organization_by_id(&conn, &id)?.or_not_found(msg::ORG_NOT_FOUND)?;
+/// Apply an org update's service config changes and field updates. Returns
+/// which configs changed, as (stripe, lemonsqueezy, resend), for the audit entry.
+pub(super) fn apply_organization_update(
+    conn: &Connection,
+    state: &AppState,
+    id: &str,
+    existing: &Organization,
+    input: &UpdateOrganization,
+) -> Result<(bool, bool, bool)> {
+    // Claim the org's version before changing anything, so an update made
+    // against a stale expected_version changes nothing
+    if !queries::claim_organization_version(conn, id, input.expected_version)? {
+        let current =
+            queries::get_organization_by_id(conn, id)?.or_not_found(msg::ORG_NOT_FOUND)?;
+        check_expected_version(input.expected_version, current.version, &current, input)?;
+    }
 
     // Track what configs are being updated for audit
     let mut stripe_updated = false;
     let mut ls_updated = false;
     let mut resend_updated = false;
+    let org_key = queries::org_data_key(conn, id, &state.master_key)?;
 
     // Handle Stripe config: Some(Some(config)) = set, Some(None) = clear, None = unchanged
     if let Some(ref stripe_config_opt) = input.stripe_config {
         match stripe_config_opt {
             Some(config) => {
                 let json = serde_json::to_string(config)?;
-                let encrypted = state.master_key.encrypt_private_key(&id, json.as_bytes())?;
-                queries::upsert_org_service_config(&conn, &id, ServiceProvider::Stripe, &encrypted)?;
+                let encrypted = org_key.encrypt_private_key(id, json.as_bytes())?;
+                queries::upsert_org_service_config(conn, id, ServiceProvider::Stripe, &encrypted)?;
                 stripe_updated = true;
             }
             None => {
                 // Clear the config - also clear payment_provider if it was stripe
-                if queries::delete_org_service_config(&conn, &id, ServiceProvider::Stripe)? {
+                if queries::delete_org_service_config(conn, id, ServiceProvider::Stripe)? {
                     stripe_updated = true;
                     if existing.payment_provider.as_deref() == Some("stripe") {
-                        queries::clear_org_payment_provider(&conn, &id)?;
+                        queries::clear_org_payment_provider(conn, id)?;
                     }
                 }
             }
@@ -199,16 +234,16 @@ pub async fn update_organization(
         match ls_config_opt {
             Some(config) => {
                 let json = serde_json::to_string(config)?;
-                let encrypted = state.master_key.encrypt_private_key(&id, json.as_bytes())?;
-                queries::upsert_org_service_config(&conn, &id, ServiceProvider::LemonSqueezy, &encrypted)?;
+                let encrypted = org_key.encrypt_private_key(id, json.as_bytes())?;
+                queries::upsert_org_service_config(conn, id, ServiceProvider::LemonSqueezy, &encrypted)?;
                 ls_updated = true;
             }
             None => {
                 // Clear the config - also clear payment_provider if it was lemonsqueezy
-                if queries::delete_org_service_config(&conn, &id, ServiceProvider::LemonSqueezy)? {
+                if queries::delete_org_service_config(conn, id, ServiceProvider::LemonSqueezy)? {
                     ls_updated = true;
                     if existing.payment_provider.as_deref() == Some("lemonsqueezy") {
-                        queries::clear_org_payment_provider(&conn, &id)?;
+                        queries::clear_org_payment_provider(conn, id)?;
                     }
                 }
             }
@@ -219,12 +254,12 @@ pub async fn update_organization(
     if let Some(ref resend_opt) = input.resend_api_key {
         match resend_opt {
             Some(api_key) => {
-                let encrypted = state.master_key.encrypt_private_key(&id, api_key.as_bytes())?;
-                queries::upsert_org_service_config(&conn, &id, ServiceProvider::Resend, &encrypted)?;
+                let encrypted = org_key.encrypt_private_key(id, api_key.as_bytes())?;
+                queries::upsert_org_service_config(conn, id, ServiceProvider::Resend, &encrypted)?;
                 resend_updated = true;
             }
             None => {
-                if queries::delete_org_service_config(&conn, &id, ServiceProvider::Resend)? {
+                if queries::delete_org_service_config(conn, id, ServiceProvider::Resend)? {
                     resend_updated = true;
                 }
             }
@@ -243,11 +278,11 @@ pub async fn update_organization(
         let has_config = match provider_enum {
             ServiceProvider::Stripe => {
                 input.stripe_config.as_ref().map(|o| o.is_some()).unwrap_or(false)
-                    || queries::org_has_service_config(&conn, &id, ServiceProvider::Stripe)?
+                    || queries::org_has_service_config(conn, id, ServiceProvider::Stripe)?
             }
             ServiceProvider::LemonSqueezy => {
                 input.ls_config.as_ref().map(|o| o.is_some()).unwrap_or(false)
-                    || queries::org_has_service_config(&conn, &id, ServiceProvider::LemonSqueezy)?
+                    || queries::org_has_service_config(conn, id, ServiceProvider::LemonSqueezy)?
             }
             _ => false,
         };
@@ -261,53 +296,127 @@ pub async fn update_organization(
     }
 
     // Update basic org fields (name, payment_provider)
-    queries::update_organization(&conn, &id, &input)?;
+    queries::update_organization(conn, id, input)?;
 
-    // Fetch updated organization
-    let organization = queries::get_organization_by_id(&conn, &id)?
-        .ok_or_else(|| AppError::Internal(msg::ORG_NOT_FOUND_AFTER_UPDATE.into()))?;
+    Ok((stripe_updated, ls_updated, resend_updated))
+}
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
-        .actor(ActorType::User, Some(&ctx.user.id))
-        .action(AuditAction::UpdateOrg)
-        .resource("org", &id)
-        .details(&serde_json::json!({
-            "old_name": existing.name,
-            "new_name": input.name,
-            "stripe_updated": stripe_updated,
-            "ls_updated": ls_updated,
-            "resend_updated": resend_updated
-        }))
-        .names(&ctx.audit_names().resource(organization.name.clone()))
-        .auth_method(&ctx.auth_method)
-        .save()?;
+pub async fn update_organization(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OperatorContext>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Json(input): Json<UpdateOrganization>,
+) -> Result<Json<OrganizationPublic>> {
+    input.validate()?;
+
+    let mut conn = state.db.get()?;
+    let audit_conn = state.audit.get()?;
+    ctx.require_org_access(&conn, &id)?;
+
+    // Verify organization exists
+    let existing = queries::get_organization_by_id(&conn, &id)?.or_not_found(msg::ORG_NOT_FOUND)?;
+
+    // Config changes, the org update, and the audit entry commit together
+    let (organization, ..) = outbox::try_with_audited_tx(
+        &mut conn,
+        |tx| {
+            let updated = apply_organization_update(tx, &state, &id, &existing, &input)?;
+            let organization = queries::get_organization_by_id(tx, &id)?
+                .ok_or_else(|| AppError::Internal(msg::ORG_NOT_FOUND_AFTER_UPDATE.into()))?;
+            Ok((organization, updated))
+        },
+        |(organization, (stripe_updated, ls_updated, resend_updated))| {
+            let details = serde_json::json!({
+                "old_name": existing.name,
+                "new_name": input.name,
+                "stripe_updated": stripe_updated,
+                "ls_updated": ls_updated,
+                "resend_updated": resend_updated,
+                "audit_retention_updated": input.public_audit_retention_days.is_some()
+                    || input.admin_audit_retention_days.is_some()
+            });
+            AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+                .actor(ActorType::User, Some(&ctx.user.id))
+                .action(AuditAction::UpdateOrg)
+                .resource("org", &id)
+                .details(&details)
+                .names(&ctx.audit_names().resource(organization.name.clone()))
+                .auth_method(&ctx.auth_method)
+                .checked_entry()
+        },
+    )?;
+    outbox::relay(&conn, &audit_conn);
 
     Ok(Json(org_to_public(&conn, organization)?))
 }
 
+#[derive(Debug, Default, Deserialize)]
+pub struct DeleteOrganizationQuery {
+    /// Also soft-delete the org's projects, products, and licenses.
+    /// Without it, an org that still has projects is refused with 409.
+    #[serde(default)]
+    pub cascade: bool,
+}
+
+/// Soft delete an organization. Everything cascaded is restorable with the
+/// org until the soft-delete purge removes it after the retention window.
 pub async fn delete_organization(
     State(state): State<AppState>,
     Extension(ctx): Extension<OperatorContext>,
     headers: HeaderMap,
     Path(id): Path<String>,
+    Query(query): Query<DeleteOrganizationQuery>,
 ) -> Result<Json<serde_json::Value>> {
-    let conn = state.db.get()?;
+    ctx.require_org_access(&state.db.get()?, &id)?;
+    let mut conn = state.org_db(&id).get()?;
     let audit_conn = state.audit.get()?;
 
     let existing = queries::get_organization_by_id(&conn, &id)?.or_not_found(msg::ORG_NOT_FOUND)?;
 
-    queries::soft_delete_organization(&conn, &id)?;
+    if !query.cascade {
+        let project_ids: Vec<String> = queries::list_projects_for_org(&conn, &id)?
+            .into_iter()
+            .map(|p| p.id)
+            .collect();
+        if !project_ids.is_empty() {
+            return Err(AppError::Conflict(format!(
+                "Organization still has {} project(s): {}. Delete them first or pass cascade=true.",
+                project_ids.len(),
+                project_ids.join(", ")
+            )));
+        }
+    }
+
+    let counts =
+        queries::soft_delete_organization(&mut conn, &id)?.or_not_found(msg::ORG_NOT_FOUND)?;
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::User, Some(&ctx.user.id))
         .action(AuditAction::DeleteOrg)
         .resource("org", &id)
-        .details(&serde_json::json!({ "name": existing.name }))
+        .details(&serde_json::json!({
+            "name": existing.name,
+            "cascade": query.cascade,
+            "org_members": counts.org_members,
+            "projects": counts.projects,
+            "products": counts.products,
+            "licenses": counts.licenses
+        }))
         .names(&ctx.audit_names().resource(existing.name.clone()))
         .auth_method(&ctx.auth_method)
         .save()?;
 
-    Ok(Json(serde_json::json!({ "success": true })))
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "deleted_at": counts.deleted_at,
+        "deleted": {
+            "org_members": counts.org_members,
+            "projects": counts.projects,
+            "products": counts.products,
+            "licenses": counts.licenses
+        }
+    })))
 }
 
 /// Restore a soft-deleted organization and its cascade-deleted children
@@ -317,7 +426,8 @@ pub async fn restore_organization(
     headers: HeaderMap,
     Path(id): Path<String>,
 ) -> Result<Json<OrganizationPublic>> {
-    let conn = state.db.get()?;
+    ctx.require_org_access(&state.db.get()?, &id)?;
+    let conn = state.org_db(&id).get()?;
     let audit_conn = state.audit.get()?;
 
     // Get the deleted organization (need to check it exists and was deleted)
@@ -326,12 +436,14 @@ pub async fn restore_organization(
 
     // Restore the organization and cascade-deleted children
     queries::restore_organization(&conn, &id)?;
+    // Restored projects would otherwise stay cached as unknown until the TTL
+    state.project_misses.clear();
 
     // Get the restored organization
     let organization = queries::get_organization_by_id(&conn, &id)?
         .ok_or_else(|| AppError::Internal(msg::ORG_NOT_FOUND_AFTER_RESTORE.into()))?;
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::User, Some(&ctx.user.id))
         .action(AuditAction::RestoreOrg)
         .resource("org", &id)
@@ -356,6 +468,7 @@ pub async fn hard_delete_organization(
 ) -> Result<Json<serde_json::Value>> {
     let conn = state.db.get()?;
     let audit_conn = state.audit.get()?;
+    ctx.require_org_access(&conn, &id)?;
 
     // Get org info for audit log (may be soft-deleted already)
     let existing = queries::get_organization_by_id(&conn, &id)?
@@ -369,7 +482,10 @@ pub async fn hard_delete_organization(
     // Perform hard delete (CASCADE removes all related data)
     queries::delete_organization(&conn, &id)?;
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    // Dedicated org database: tenant data goes with the file
+    state.org_dbs.release(&id)?;
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::User, Some(&ctx.user.id))
         .action(AuditAction::HardDeleteOrg)
         .resource("org", &id)
@@ -392,3 +508,65 @@ pub async fn hard_delete_organization(
         serde_json::json!({ "success": true, "permanently_deleted": true }),
     ))
 }
+
+#[derive(Deserialize)]
+pub struct OrgLimitPath {
+    pub org_id: String,
+    pub limit_name: String,
+}
+
+/// GET /operators/organizations/{org_id}/limits
+/// The org's limits next to its current usage.
+pub async fn get_org_limits(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OperatorContext>,
+    Path(id): Path<String>,
+) -> Result<Json<OrgLimitsResponse>> {
+    let conn = state.db.get()?;
+    ctx.require_org_access(&conn, &id)?;
+    queries::get_organization_by_id(&conn, &id)?.or_not_found(msg::ORG_NOT_FOUND)?;
+
+    let limits = quota::org_usage(&state.org_db(&id).get()?, &id, state.clock.now())?;
+    Ok(Json(OrgLimitsResponse { limits }))
+}
+
+/// PUT /operators/organizations/{org_id}/limits/{limit_name}
+/// Set one of an org's limits. Null for both values makes it unlimited again.
+pub async fn set_org_limit(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OperatorContext>,
+    headers: HeaderMap,
+    Path(path): Path<OrgLimitPath>,
+    Json(input): Json<SetOrgLimit>,
+) -> Result<Json<OrgLimitsResponse>> {
+    let limit_name: OrgLimitName = path
+        .limit_name
+        .parse()
+        .map_err(|_| AppError::BadRequest(msg::INVALID_ORG_LIMIT.into()))?;
+    input.validate()?;
+
+    let conn = state.db.get()?;
+    let audit_conn = state.audit.get()?;
+    ctx.require_org_access(&conn, &path.org_id)?;
+    let org =
+        queries::get_organization_by_id(&conn, &path.org_id)?.or_not_found(msg::ORG_NOT_FOUND)?;
+
+    queries::set_org_limit(&conn, &org.id, limit_name, &input)?;
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.user.id))
+        .action(AuditAction::UpdateOrgLimit)
+        .resource("org", &org.id)
+        .details(&serde_json::json!({
+            "limit_name": limit_name,
+            "soft_value": input.soft_value,
+            "hard_value": input.hard_value
+        }))
+        .org(&org.id)
+        .names(&ctx.audit_names().resource(org.name.clone()))
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    let limits = quota::org_usage(&state.org_db(&org.id).get()?, &org.id, state.clock.now())?;
+    Ok(Json(OrgLimitsResponse { limits }))
+}
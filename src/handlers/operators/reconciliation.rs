@@ -0,0 +1,100 @@
+//! Operator endpoints for reconciling licenses with provider subscriptions.
+
+use axum::{
+    extract::{Extension, Query, State},
+    http::HeaderMap,
+};
+use serde::Deserialize;
+
+use crate::db::{AppState, queries};
+use crate::error::{AppError, OptionExt, Result, msg};
+use crate::extractors::{Json, Path};
+use crate::middleware::OperatorContext;
+use crate::models::{ActorType, AuditAction, ReconciliationRun};
+use crate::payments::PaymentProvider;
+use crate::reconcile::{self, RunOptions, SubscriptionClient};
+use crate::util::AuditLogBuilder;
+
+#[derive(Debug, Deserialize)]
+pub struct ReconcileQuery {
+    pub org_id: String,
+    /// "stripe" or "lemonsqueezy"
+    pub provider: String,
+    /// Fix discrepancies instead of only reporting them
+    #[serde(default)]
+    pub apply: bool,
+}
+
+/// POST /operators/reconcile?org_id=&provider=&apply=
+/// Compare an org's subscription licenses with the provider and report (or,
+/// with `apply=true`, fix) the ones that disagree. Runs to completion, paced
+/// to the provider's rate limit.
+pub async fn reconcile_subscriptions(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OperatorContext>,
+    headers: HeaderMap,
+    Query(query): Query<ReconcileQuery>,
+) -> Result<Json<ReconciliationRun>> {
+    let provider: PaymentProvider = query
+        .provider
+        .parse()
+        .map_err(|_| AppError::BadRequest(msg::INVALID_PROVIDER.into()))?;
+
+    let (org, client) = {
+        let conn = state.db.get()?;
+        ctx.require_org_access(&conn, &query.org_id)?;
+        let org = queries::get_organization_by_id(&conn, &query.org_id)?
+            .or_not_found(msg::ORG_NOT_FOUND)?;
+        let client = SubscriptionClient::for_org(&conn, &org.id, provider, &state.master_key)?
+            .ok_or_else(|| {
+                AppError::BadRequest(
+                    match provider {
+                        PaymentProvider::Stripe => msg::STRIPE_NOT_CONFIGURED,
+                        PaymentProvider::LemonSqueezy => msg::LS_NOT_CONFIGURED,
+                    }
+                    .into(),
+                )
+            })?;
+        (org, client)
+    };
+
+    let options = RunOptions {
+        apply: query.apply,
+        started_by: Some(&ctx.user.id),
+        pace: client.pace(),
+    };
+    let run = reconcile::run(&state, &org.id, &client, &options).await?;
+
+    let audit_conn = state.audit.get()?;
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.user.id))
+        .action(AuditAction::ReconcileSubscriptions)
+        .resource("reconciliation_run", &run.id)
+        .details(&serde_json::json!({
+            "provider": run.provider,
+            "applied": run.applied,
+            "checked": run.checked,
+            "discrepancies": run.discrepancies.len(),
+            "fixed": run.discrepancies.iter().filter(|d| d.fixed).count(),
+        }))
+        .org(&org.id)
+        .names(&ctx.audit_names().org(org.name.clone()))
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    Ok(Json(run))
+}
+
+/// GET /operators/reconciliation-runs/{run_id}
+/// A saved reconciliation run and its report.
+pub async fn get_reconciliation_run(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OperatorContext>,
+    Path(run_id): Path<String>,
+) -> Result<Json<ReconciliationRun>> {
+    let conn = state.db.get()?;
+    let run = queries::get_reconciliation_run(&conn, &run_id)?
+        .or_not_found(msg::RECONCILIATION_RUN_NOT_FOUND)?;
+    ctx.require_org_access(&conn, &run.org_id)?;
+    Ok(Json(run))
+}
@@ -0,0 +1,402 @@
+//! Embedded status page for deployments that don't run the console.
+//!
+//! Operators sign in with an API key at `/operators/login-page` and get a
+//! short-lived session cookie for `/operators/status-page`. The page is
+//! read-only: it has no forms, so the cookie can't be used to change anything
+//! and needs no CSRF token. Only served when `PAYCHECK_STATUS_PAGE` is set.
+
+use axum::{
+    Form,
+    extract::State,
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::db::{AppState, migrations, outbox, queries};
+use crate::error::Result;
+use crate::models::{ApiKey, AuditLog, AuditLogQuery, ReconciliationRun, User};
+
+/// Name of the session cookie set by the login form.
+pub const STATUS_SESSION_COOKIE: &str = "paycheck_status_session";
+
+/// How long a status page session lasts (15 minutes).
+const SESSION_TTL_SECS: i64 = 15 * 60;
+
+const LOGIN_PATH: &str = "/operators/login-page";
+const STATUS_PATH: &str = "/operators/status-page";
+
+const RECENT_AUDIT_ENTRIES: i64 = 20;
+const RECENT_RECONCILIATION_RUNS: i64 = 10;
+
+#[derive(Debug, Deserialize)]
+pub struct StatusLoginForm {
+    pub api_key: String,
+}
+
+/// GET /operators/login-page
+pub async fn status_login_page() -> Response {
+    page_response(StatusCode::OK, render_login(None))
+}
+
+/// POST /operators/login-page
+/// Exchange an operator API key for a status page session cookie.
+pub async fn status_login(
+    State(state): State<AppState>,
+    Form(form): Form<StatusLoginForm>,
+) -> Result<Response> {
+    let conn = state.db.get()?;
+    let found = queries::get_user_by_api_key(&conn, form.api_key.trim(), &state.emails())?;
+    let Some((_, api_key)) = found.filter(|(user, _)| can_view_status(user)) else {
+        return Ok(page_response(
+            StatusCode::UNAUTHORIZED,
+            render_login(Some("That key isn't an operator API key.")),
+        ));
+    };
+
+    let expires_at = Utc::now().timestamp() + SESSION_TTL_SECS;
+    let message = session_message(&api_key.id, expires_at);
+    let signature = state.master_key.sign_status_session(message.as_bytes());
+    let secure = if state.base_url.starts_with("https://") {
+        "; Secure"
+    } else {
+        ""
+    };
+    let cookie = format!(
+        "{}={}.{}; Max-Age={}; Path=/operators; HttpOnly; SameSite=Strict{}",
+        STATUS_SESSION_COOKIE, message, signature, SESSION_TTL_SECS, secure
+    );
+
+    let mut response = Redirect::to(STATUS_PATH).into_response();
+    // Set-Cookie values built here are always valid header values
+    if let Ok(value) = HeaderValue::from_str(&cookie) {
+        response.headers_mut().insert(header::SET_COOKIE, value);
+    }
+    Ok(response)
+}
+
+/// GET /operators/status-page
+/// Without a valid session, redirects to the login page.
+pub async fn status_page(State(state): State<AppState>, headers: HeaderMap) -> Result<Response> {
+    let Some(user) = session_user(&state, &headers)? else {
+        return Ok(Redirect::to(LOGIN_PATH).into_response());
+    };
+    let status = collect_status(&state)?;
+    Ok(page_response(StatusCode::OK, render_status(&user, &status)))
+}
+
+/// Operators other than partners: the page shows every org's counts and
+/// activity, which partners aren't allowed to see.
+fn can_view_status(user: &User) -> bool {
+    user.operator_role.is_some_and(|role| !role.is_org_scoped())
+}
+
+fn session_message(key_id: &str, expires_at: i64) -> String {
+    format!("{}.{}", key_id, expires_at)
+}
+
+/// The operator behind the request's session cookie. The key and the role
+/// are checked again on every load, so revoking the key or demoting the
+/// operator ends the session before the cookie expires.
+fn session_user(state: &AppState, headers: &HeaderMap) -> Result<Option<User>> {
+    let Some(value) = session_cookie(headers) else {
+        return Ok(None);
+    };
+    let mut parts = value.splitn(3, '.');
+    let (Some(key_id), Some(expires_at), Some(signature)) =
+        (parts.next(), parts.next(), parts.next())
+    else {
+        return Ok(None);
+    };
+    let Ok(expires_at) = expires_at.parse::<i64>() else {
+        return Ok(None);
+    };
+    let message = session_message(key_id, expires_at);
+    if !state
+        .master_key
+        .verify_status_session(message.as_bytes(), signature)
+    {
+        return Ok(None);
+    }
+
+    let now = Utc::now().timestamp();
+    if expires_at <= now {
+        return Ok(None);
+    }
+
+    let conn = state.db.get()?;
+    let Some(api_key) = queries::get_api_key_by_id(&conn, key_id)? else {
+        return Ok(None);
+    };
+    if !key_is_usable(&api_key, now) {
+        return Ok(None);
+    }
+    Ok(queries::get_user_by_id(&conn, &api_key.user_id, &state.emails())?.filter(can_view_status))
+}
+
+fn key_is_usable(api_key: &ApiKey, now: i64) -> bool {
+    api_key.revoked_at.is_none() && api_key.expires_at.is_none_or(|exp| exp > now)
+}
+
+fn session_cookie(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(name, _)| *name == STATUS_SESSION_COOKIE)
+        .map(|(_, value)| value)
+}
+
+// ============ Data ============
+
+struct StatusData {
+    main_db_version: i32,
+    audit_db_version: i32,
+    audit_log_enabled: bool,
+    pii_minimization: bool,
+    dedicated_org_dbs: usize,
+    organizations: i64,
+    projects: i64,
+    licenses: i64,
+    /// Audit entries waiting in tenant outboxes to be relayed to the audit database
+    audit_outbox_pending: i64,
+    webhook_events_24h: i64,
+    audit_entries: Vec<AuditLog>,
+    reconciliation_runs: Vec<ReconciliationRun>,
+}
+
+fn collect_status(state: &AppState) -> Result<StatusData> {
+    let conn = state.db.get()?;
+    let audit_conn = state.audit.get()?;
+    let tenant_pools = state.tenant_pools();
+
+    // Projects, licenses, and outboxes live in whichever database holds the org
+    let (mut projects, mut licenses, mut audit_outbox_pending) = (0, 0, 0);
+    for pool in &tenant_pools {
+        let tenant_conn = pool.get()?;
+        projects += queries::count_live_rows(&tenant_conn, "projects")?;
+        licenses += queries::count_live_rows(&tenant_conn, "licenses")?;
+        audit_outbox_pending += outbox::pending_count(&tenant_conn)?;
+    }
+
+    let (audit_entries, _) = queries::query_audit_logs(
+        &audit_conn,
+        &AuditLogQuery {
+            limit: Some(RECENT_AUDIT_ENTRIES),
+            ..Default::default()
+        },
+    )?;
+
+    Ok(StatusData {
+        main_db_version: migrations::get_version(&conn)?,
+        audit_db_version: migrations::get_version(&audit_conn)?,
+        audit_log_enabled: state.audit_log_enabled,
+        pii_minimization: state.pii_minimization,
+        dedicated_org_dbs: tenant_pools.len() - 1,
+        organizations: queries::count_live_rows(&conn, "organizations")?,
+        projects,
+        licenses,
+        audit_outbox_pending,
+        webhook_events_24h: queries::count_webhook_events_since(
+            &conn,
+            Utc::now().timestamp() - 24 * 60 * 60,
+        )?,
+        audit_entries,
+        reconciliation_runs: queries::list_recent_reconciliation_runs(
+            &conn,
+            RECENT_RECONCILIATION_RUNS,
+        )?,
+    })
+}
+
+// ============ Rendering ============
+
+/// HTML response that stays out of caches and Referer headers.
+fn page_response(status: StatusCode, html: String) -> Response {
+    let mut response = (status, Html(html)).into_response();
+    let headers = response.headers_mut();
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    headers.insert(
+        header::REFERRER_POLICY,
+        HeaderValue::from_static("no-referrer"),
+    );
+    response
+}
+
+const STYLE: &str = r#"<style>
+body { font-family: system-ui, sans-serif; max-width: 60rem; margin: 2rem auto; padding: 0 1rem; color: #222; }
+h2 { margin-top: 2rem; font-size: 1.125rem; }
+dl { display: grid; grid-template-columns: max-content 1fr; gap: 0.5rem 1.5rem; }
+dt { color: #666; }
+dd { margin: 0; }
+table { border-collapse: collapse; width: 100%; font-size: 0.875rem; }
+th, td { text-align: left; padding: 0.25rem 0.75rem 0.25rem 0; border-bottom: 1px solid #eee; }
+th { color: #666; font-weight: normal; }
+.error { color: #b00; }
+footer { margin-top: 2rem; color: #888; font-size: 0.875rem; }
+</style>"#;
+
+fn render_login(error: Option<&str>) -> String {
+    let error = error
+        .map(|e| format!("<p class=\"error\">{}</p>\n", escape_html(e)))
+        .unwrap_or_default();
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<meta name="robots" content="noindex">
+<title>Paycheck status</title>
+{style}
+</head>
+<body>
+<h1>Paycheck status</h1>
+{error}<form method="post" action="{login}">
+<label for="api_key">Operator API key</label>
+<input type="password" id="api_key" name="api_key" autocomplete="off" required>
+<button type="submit">Sign in</button>
+</form>
+</body>
+</html>
+"#,
+        style = STYLE,
+        error = error,
+        login = LOGIN_PATH,
+    )
+}
+
+fn render_status(user: &User, status: &StatusData) -> String {
+    let audit_rows: String = status
+        .audit_entries
+        .iter()
+        .map(|entry| {
+            let actor = entry
+                .user_email
+                .as_deref()
+                .or(entry.user_id.as_deref())
+                .unwrap_or(entry.actor_type.as_ref());
+            let resource = entry.resource_name.as_deref().unwrap_or(&entry.resource_id);
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{} {}</td></tr>\n",
+                format_timestamp(Some(entry.timestamp)),
+                escape_html(actor),
+                escape_html(&entry.action),
+                escape_html(&entry.resource_type),
+                escape_html(resource),
+            )
+        })
+        .collect();
+    let run_rows: String = status
+        .reconciliation_runs
+        .iter()
+        .map(|run| {
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                format_timestamp(Some(run.started_at)),
+                escape_html(&run.org_id),
+                escape_html(&run.provider),
+                if run.applied { "applied" } else { "dry run" },
+                run.checked,
+                run.discrepancies.len(),
+            )
+        })
+        .collect();
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<meta name="robots" content="noindex">
+<title>Paycheck status</title>
+{style}
+</head>
+<body>
+<h1>Paycheck status</h1>
+
+<h2>Health</h2>
+<dl>
+<dt>Version</dt><dd>{version}</dd>
+<dt>Main database schema</dt><dd>v{main_db_version}</dd>
+<dt>Audit database schema</dt><dd>v{audit_db_version}</dd>
+<dt>Dedicated org databases</dt><dd>{dedicated_org_dbs}</dd>
+<dt>Audit logging</dt><dd>{audit_log_enabled}</dd>
+<dt>PII minimization</dt><dd>{pii_minimization}</dd>
+</dl>
+
+<h2>Totals</h2>
+<dl>
+<dt>Organizations</dt><dd id="organizations">{organizations}</dd>
+<dt>Projects</dt><dd id="projects">{projects}</dd>
+<dt>Licenses</dt><dd id="licenses">{licenses}</dd>
+</dl>
+
+<h2>Queues</h2>
+<dl>
+<dt>Audit entries awaiting relay</dt><dd id="audit-outbox">{audit_outbox_pending}</dd>
+<dt>Webhook events (last 24h)</dt><dd id="webhook-events">{webhook_events_24h}</dd>
+</dl>
+
+<h2>Recent audit entries</h2>
+<table>
+<tr><th>Time</th><th>Actor</th><th>Action</th><th>Resource</th></tr>
+{audit_rows}</table>
+
+<h2>Reconciliation runs</h2>
+<table>
+<tr><th>Started</th><th>Org</th><th>Provider</th><th>Mode</th><th>Checked</th><th>Discrepancies</th></tr>
+{run_rows}</table>
+
+<footer>Signed in as {email}. Generated {generated}.</footer>
+</body>
+</html>
+"#,
+        style = STYLE,
+        version = env!("CARGO_PKG_VERSION"),
+        main_db_version = status.main_db_version,
+        audit_db_version = status.audit_db_version,
+        dedicated_org_dbs = status.dedicated_org_dbs,
+        audit_log_enabled = on_off(status.audit_log_enabled),
+        pii_minimization = on_off(status.pii_minimization),
+        organizations = status.organizations,
+        projects = status.projects,
+        licenses = status.licenses,
+        audit_outbox_pending = status.audit_outbox_pending,
+        webhook_events_24h = status.webhook_events_24h,
+        audit_rows = audit_rows,
+        run_rows = run_rows,
+        email = escape_html(&user.email),
+        generated = format_timestamp(Some(Utc::now().timestamp())),
+    )
+}
+
+fn on_off(enabled: bool) -> &'static str {
+    if enabled { "on" } else { "off" }
+}
+
+fn format_timestamp(timestamp: Option<i64>) -> String {
+    match timestamp.and_then(|ts| DateTime::from_timestamp(ts, 0)) {
+        Some(dt) => dt.format("%Y-%m-%d %H:%M UTC").to_string(),
+        None => "Never".to_string(),
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,14 @@
+//! Operator view of database storage: heartbeats, disk space, and clocks.
+
+use axum::extract::State;
+
+use crate::db::AppState;
+use crate::error::Result;
+use crate::extractors::Json;
+use crate::storage_health::{self, StorageHealth};
+
+/// GET /operators/storage-health
+/// Checked on each request, not read from the last maintenance tick.
+pub async fn get_storage_health(State(state): State<AppState>) -> Result<Json<StorageHealth>> {
+    Ok(Json(storage_health::check(&state)?))
+}
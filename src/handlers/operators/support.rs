@@ -1,12 +1,13 @@
 //! Operator support endpoints for debugging customer issues.
 
-use axum::extract::{Query, State};
+use axum::extract::{Extension, Query, State};
 use serde::{Deserialize, Serialize};
 
 use crate::db::{AppState, queries};
 use crate::error::{AppError, OptionExt, Result, msg};
 use crate::extractors::{Json, Path};
-use crate::models::{LemonSqueezyConfig, LicenseWithProduct, StripeConfig};
+use crate::middleware::OperatorContext;
+use crate::models::{EmailAddress, LemonSqueezyConfig, LicenseWithProduct, StripeConfig};
 
 #[derive(Debug, Serialize)]
 pub struct FullPaymentConfigResponse {
@@ -14,15 +15,19 @@ pub struct FullPaymentConfigResponse {
     pub org_name: String,
     pub stripe_config: Option<StripeConfig>,
     pub ls_config: Option<LemonSqueezyConfig>,
+    /// The org's version, for `expected_version` when changing the config
+    pub version: i64,
 }
 
 /// Get full (unmasked) payment provider configuration for an organization.
 /// This is for operator support staff to debug customer payment issues.
 pub async fn get_org_payment_config(
     State(state): State<AppState>,
+    Extension(ctx): Extension<OperatorContext>,
     Path(org_id): Path<String>,
 ) -> Result<Json<FullPaymentConfigResponse>> {
     let conn = state.db.get()?;
+    ctx.require_org_access(&conn, &org_id)?;
 
     let org = queries::get_organization_by_id(&conn, &org_id)?.or_not_found(msg::ORG_NOT_FOUND)?;
 
@@ -40,6 +45,7 @@ pub async fn get_org_payment_config(
         org_name: org.name,
         stripe_config,
         ls_config,
+        version: org.version,
     }))
 }
 
@@ -69,10 +75,12 @@ pub struct LicenseLookupResponse {
 /// Returns ALL licenses including expired and revoked.
 pub async fn lookup_licenses_by_email(
     State(state): State<AppState>,
+    Extension(ctx): Extension<OperatorContext>,
     Path(path): Path<LicenseLookupPath>,
     Query(query): Query<LicenseLookupQuery>,
 ) -> Result<Json<LicenseLookupResponse>> {
-    let conn = state.db.get()?;
+    ctx.require_org_access(&state.db.get()?, &path.org_id)?;
+    let conn = state.org_db(&path.org_id).get()?;
 
     // Verify org exists
     let org =
@@ -89,14 +97,22 @@ pub async fn lookup_licenses_by_email(
     }
 
     // Look up all licenses by email hash (use high limit since filtered by email)
-    let email_hash = state.email_hasher.hash(&query.email);
-    let (licenses, _total) = queries::get_all_licenses_by_email_hash_for_admin_paginated(
-        &conn,
-        &path.project_id,
-        &email_hash,
-        100, // Max 100 licenses per email lookup
-        0,
+    let email = EmailAddress::parse(&query.email)?;
+    let (mut licenses, _total) = state.email_hasher.find_by_hash(
+        &email,
+        "operator_license_lookup",
+        |hash| {
+            queries::get_all_licenses_by_email_hash_for_admin_paginated(
+                &conn,
+                &path.project_id,
+                hash,
+                100, // Max 100 licenses per email lookup
+                0,
+            )
+        },
+        |(licenses, _)| !licenses.is_empty(),
     )?;
+    queries::attach_license_tags(&conn, &mut licenses)?;
 
     tracing::info!(
         "OPERATOR: License lookup by email for org {} project {} ({} results)",
@@ -8,14 +8,20 @@ use crate::db::{AppState, queries};
 use crate::error::{AppError, OptionExt, Result, msg};
 use crate::extractors::{Json, Path, RestoreRequest};
 use crate::middleware::OperatorContext;
-use crate::models::{ActorType, AuditAction, CreateUser, UpdateUser, User, UserWithRoles};
-use crate::pagination::{Paginated, PaginationQuery};
+use crate::models::{
+    ActorType, AuditAction, CreateUser, MergeUsers, MergeUsersResponse, UpdateUser, User,
+    UserWithRoles,
+};
+use crate::pagination::{Paginated, clamp_limit, clamp_offset};
 use crate::util::AuditLogBuilder;
 
 #[derive(Deserialize)]
 pub struct UserQuery {
-    #[serde(flatten)]
-    pub pagination: PaginationQuery,
+    /// Pagination: max items to return (default: 50, max: 100)
+    /// (not a flattened PaginationQuery: serde(flatten) can't parse numbers from query strings)
+    pub limit: Option<i64>,
+    /// Pagination: items to skip (default: 0)
+    pub offset: Option<i64>,
     /// Filter by email (exact match)
     pub email: Option<String>,
     /// Include soft-deleted users (default: false)
@@ -23,6 +29,16 @@ pub struct UserQuery {
     pub include_deleted: bool,
 }
 
+impl UserQuery {
+    fn limit(&self) -> i64 {
+        clamp_limit(self.limit)
+    }
+
+    fn offset(&self) -> i64 {
+        clamp_offset(self.offset)
+    }
+}
+
 /// Create a new user.
 pub async fn create_user(
     State(state): State<AppState>,
@@ -36,18 +52,18 @@ pub async fn create_user(
     let audit_conn = state.audit.get()?;
 
     // Check if email already exists
-    if queries::get_user_by_email(&conn, &input.email)?.is_some() {
+    if queries::get_user_by_email(&conn, &input.email, &state.emails())?.is_some() {
         return Err(AppError::BadRequest(msg::EMAIL_ALREADY_EXISTS.into()));
     }
 
-    let user = queries::create_user(&conn, &input)?;
+    let user = queries::create_user(&conn, &input, &state.emails())?;
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::User, Some(&ctx.user.id))
         .action(AuditAction::CreateUser)
         .resource("user", &user.id)
         .details(&serde_json::json!({
-            "email": input.email,
+            "email": user.email,
             "name": input.name
         }))
         .names(&ctx.audit_names().resource_user(&user.name, &user.email))
@@ -55,7 +71,7 @@ pub async fn create_user(
         .save()?;
 
     // Return user with roles (will be empty for new user)
-    let user_with_roles = queries::get_user_with_roles(&conn, &user.id)?
+    let user_with_roles = queries::get_user_with_roles(&conn, &user.id, &state.emails())?
         .ok_or_else(|| AppError::Internal(msg::FAILED_TO_FETCH_CREATED_USER.into()))?;
 
     Ok(Json(user_with_roles))
@@ -70,20 +86,26 @@ pub async fn list_users(
 
     // If email filter provided, return single result
     if let Some(email) = &query.email {
-        let user = queries::get_user_by_email(&conn, email)?;
+        let user = queries::get_user_by_email(&conn, email, &state.emails())?;
         if let Some(user) = user {
-            let user_with_roles = queries::get_user_with_roles(&conn, &user.id)?
-                .ok_or_else(|| AppError::Internal(msg::FAILED_TO_FETCH_USER.into()))?;
+            let user_with_roles =
+                queries::get_user_with_roles(&conn, &user.id, &state.emails())?
+                    .ok_or_else(|| AppError::Internal(msg::FAILED_TO_FETCH_USER.into()))?;
             return Ok(Json(Paginated::new(vec![user_with_roles], 1, 1, 0)));
         } else {
             return Ok(Json(Paginated::new(vec![], 0, 1, 0)));
         }
     }
 
-    let limit = query.pagination.limit();
-    let offset = query.pagination.offset();
-    let (users, total) =
-        queries::list_users_with_roles_paginated(&conn, limit, offset, query.include_deleted)?;
+    let limit = query.limit();
+    let offset = query.offset();
+    let (users, total) = queries::list_users_with_roles_paginated(
+        &conn,
+        limit,
+        offset,
+        query.include_deleted,
+        &state.emails(),
+    )?;
 
     Ok(Json(Paginated::new(users, total, limit, offset)))
 }
@@ -94,7 +116,8 @@ pub async fn get_user(
     Path(id): Path<String>,
 ) -> Result<Json<UserWithRoles>> {
     let conn = state.db.get()?;
-    let user = queries::get_user_with_roles(&conn, &id)?.or_not_found(msg::USER_NOT_FOUND)?;
+    let user = queries::get_user_with_roles(&conn, &id, &state.emails())?
+        .or_not_found(msg::USER_NOT_FOUND)?;
     Ok(Json(user))
 }
 
@@ -111,19 +134,21 @@ pub async fn update_user(
     let conn = state.db.get()?;
     let audit_conn = state.audit.get()?;
 
-    let existing = queries::get_user_by_id(&conn, &id)?.or_not_found(msg::USER_NOT_FOUND)?;
+    let existing =
+        queries::get_user_by_id(&conn, &id, &state.emails())?.or_not_found(msg::USER_NOT_FOUND)?;
 
-    // If changing email, check it doesn't conflict
+    // If changing email, check it doesn't conflict (a respelling of the
+    // user's own address finds the user themselves)
     if let Some(ref new_email) = input.email
-        && new_email != &existing.email
-        && queries::get_user_by_email(&conn, new_email)?.is_some()
+        && queries::get_user_by_email(&conn, new_email, &state.emails())?
+            .is_some_and(|other| other.id != existing.id)
     {
         return Err(AppError::BadRequest(msg::EMAIL_ALREADY_EXISTS.into()));
     }
 
-    queries::update_user(&conn, &id, &input)?.or_not_found(msg::USER_NOT_FOUND)?;
+    queries::update_user(&conn, &id, &input, &state.emails())?.or_not_found(msg::USER_NOT_FOUND)?;
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::User, Some(&ctx.user.id))
         .action(AuditAction::UpdateUser)
         .resource("user", &id)
@@ -138,7 +163,8 @@ pub async fn update_user(
         .auth_method(&ctx.auth_method)
         .save()?;
 
-    let user = queries::get_user_with_roles(&conn, &id)?.or_not_found(msg::USER_NOT_FOUND)?;
+    let user = queries::get_user_with_roles(&conn, &id, &state.emails())?
+        .or_not_found(msg::USER_NOT_FOUND)?;
 
     Ok(Json(user))
 }
@@ -159,11 +185,21 @@ pub async fn delete_user(
         return Err(AppError::BadRequest(msg::CANNOT_DELETE_SELF.into()));
     }
 
-    let existing = queries::get_user_by_id(&conn, &id)?.or_not_found(msg::USER_NOT_FOUND)?;
+    let existing =
+        queries::get_user_by_id(&conn, &id, &state.emails())?.or_not_found(msg::USER_NOT_FOUND)?;
 
     queries::soft_delete_user(&conn, &id)?;
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    // Project memberships in dedicated org databases aren't reached by the cascade above
+    let deleted_at =
+        queries::get_deleted_user_by_id(&conn, &id, &state.emails())?.and_then(|u| u.deleted_at);
+    if let Some(deleted_at) = deleted_at {
+        for (_, pool) in state.org_dbs.dedicated_pools() {
+            queries::cascade_delete_user_project_members(&*pool.get()?, &id, deleted_at)?;
+        }
+    }
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::User, Some(&ctx.user.id))
         .action(AuditAction::DeleteUser)
         .resource("user", &id)
@@ -193,17 +229,21 @@ pub async fn restore_user(
     let audit_conn = state.audit.get()?;
 
     // Get the deleted user
-    let existing =
-        queries::get_deleted_user_by_id(&conn, &id)?.or_not_found(msg::DELETED_USER_NOT_FOUND)?;
+    let existing = queries::get_deleted_user_by_id(&conn, &id, &state.emails())?
+        .or_not_found(msg::DELETED_USER_NOT_FOUND)?;
+    // Its memberships and keys belong to another user now
+    if existing.merged_into.is_some() {
+        return Err(AppError::BadRequest(msg::USER_MERGED.into()));
+    }
 
     // Restore the user and cascade-deleted children
     queries::restore_user(&conn, &id, input.force)?;
 
     // Get the restored user
-    let user = queries::get_user_by_id(&conn, &id)?
+    let user = queries::get_user_by_id(&conn, &id, &state.emails())?
         .ok_or_else(|| AppError::Internal(msg::USER_NOT_FOUND_AFTER_RESTORE.into()))?;
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::User, Some(&ctx.user.id))
         .action(AuditAction::RestoreUser)
         .resource("user", &id)
@@ -219,6 +259,75 @@ pub async fn restore_user(
     Ok(Json(user))
 }
 
+/// Merge a duplicate user (`source_user_id`) into the user in the path.
+/// Owner only.
+///
+/// The source's org memberships, project memberships, API keys, and operator
+/// role move to the target in one transaction, and the source is soft-deleted
+/// with `merged_into` set. In orgs both users belong to, the target keeps the
+/// higher of the two roles (owner > admin > member) and each conflict is
+/// listed in the response. The source's API keys keep working, as the target.
+pub async fn merge_user(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OperatorContext>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Json(input): Json<MergeUsers>,
+) -> Result<Json<MergeUsersResponse>> {
+    if input.source_user_id == id {
+        return Err(AppError::BadRequest(msg::MERGE_SAME_USER.into()));
+    }
+    if input.source_user_id == ctx.user.id {
+        return Err(AppError::BadRequest(msg::CANNOT_MERGE_SELF.into()));
+    }
+
+    let mut conn = state.db.get()?;
+    let audit_conn = state.audit.get()?;
+
+    let target =
+        queries::get_user_by_id(&conn, &id, &state.emails())?.or_not_found(msg::USER_NOT_FOUND)?;
+    let source = queries::get_user_by_id(&conn, &input.source_user_id, &state.emails())?
+        .or_not_found(msg::MERGE_SOURCE_NOT_FOUND)?;
+    let before = serde_json::json!({
+        "source": queries::get_user_with_roles(&conn, &source.id, &state.emails())?,
+        "target": queries::get_user_with_roles(&conn, &target.id, &state.emails())?,
+    });
+
+    let tx = conn.transaction()?;
+    let merge = queries::merge_users(&tx, &source, &target)?;
+    tx.commit()?;
+
+    // Project memberships in dedicated org databases aren't reached above
+    for conflict in &merge.conflicts {
+        if let Some(pool) = state.org_dbs.get(&conflict.org_id) {
+            queries::merge_project_memberships(
+                &*pool.get()?,
+                &conflict.source_member_id,
+                &conflict.target_member_id,
+            )?;
+        }
+    }
+
+    let user = queries::get_user_with_roles(&conn, &target.id, &state.emails())?
+        .or_not_found(msg::USER_NOT_FOUND)?;
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.user.id))
+        .action(AuditAction::MergeUser)
+        .resource("user", &target.id)
+        .details(&serde_json::json!({
+            "source_user_id": source.id,
+            "before": before,
+            "after": user,
+            "merge": merge,
+        }))
+        .names(&ctx.audit_names().resource_user(&target.name, &target.email))
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    Ok(Json(MergeUsersResponse { user, merge }))
+}
+
 /// Hard delete a user (GDPR compliance - permanently removes all data).
 /// This is irreversible and removes all associated data including:
 /// - Operator record (if any)
@@ -239,14 +348,18 @@ pub async fn hard_delete_user(
     }
 
     // Get user info for audit log (may be soft-deleted already)
-    let existing = queries::get_user_by_id(&conn, &id)?
-        .or_else(|| queries::get_deleted_user_by_id(&conn, &id).ok().flatten())
+    let existing = queries::get_user_by_id(&conn, &id, &state.emails())?
+        .or_else(|| {
+            queries::get_deleted_user_by_id(&conn, &id, &state.emails())
+                .ok()
+                .flatten()
+        })
         .or_not_found(msg::USER_NOT_FOUND)?;
 
     // Perform hard delete (CASCADE removes all related data)
     queries::delete_user(&conn, &id)?;
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::User, Some(&ctx.user.id))
         .action(AuditAction::HardDeleteUser)
         .resource("user", &id)
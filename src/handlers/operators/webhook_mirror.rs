@@ -0,0 +1,149 @@
+//! Webhook mirrors: copies of a project's webhooks sent to a developer's
+//! tunnel for a while (see [`crate::webhook_mirror`]).
+
+use axum::{
+    extract::{Extension, State},
+    http::HeaderMap,
+};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::db::{AppState, queries};
+use crate::error::{AppError, OptionExt, Result, msg};
+use crate::external_url;
+use crate::extractors::{Json, Path};
+use crate::middleware::OperatorContext;
+use crate::models::{ActorType, AuditAction, Project, serialize_optional_timestamp};
+use crate::util::AuditLogBuilder;
+use crate::webhook_mirror::{DEFAULT_MIRROR_DAYS, MAX_MIRROR_DAYS};
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookMirrorPath {
+    pub org_id: String,
+    pub project_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetWebhookMirror {
+    /// Where copies go, e.g. an ngrok tunnel
+    pub url: String,
+    /// Days until the mirror turns itself off (default 7, at most 30)
+    #[serde(default)]
+    pub expires_in_days: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WebhookMirrorStatus {
+    pub project_id: String,
+    /// Whether webhooks are being mirrored now
+    pub active: bool,
+    pub url: Option<String>,
+    #[serde(serialize_with = "serialize_optional_timestamp")]
+    pub expires_at: Option<i64>,
+}
+
+impl WebhookMirrorStatus {
+    fn of(project: &Project, now: i64) -> Self {
+        Self {
+            project_id: project.id.clone(),
+            active: project.webhook_mirror(now).is_some(),
+            url: project.webhook_mirror_url.clone(),
+            expires_at: project.webhook_mirror_expires_at,
+        }
+    }
+}
+
+/// The project, if it belongs to the org in the path.
+fn get_project(conn: &Connection, path: &WebhookMirrorPath) -> Result<Project> {
+    queries::get_project_by_id(conn, &path.project_id)?
+        .filter(|project| project.org_id == path.org_id)
+        .or_not_found(msg::PROJECT_NOT_FOUND)
+}
+
+/// GET /operators/organizations/{org_id}/projects/{project_id}/webhook-mirror
+pub async fn get_webhook_mirror(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OperatorContext>,
+    Path(path): Path<WebhookMirrorPath>,
+) -> Result<Json<WebhookMirrorStatus>> {
+    ctx.require_org_access(&state.db.get()?, &path.org_id)?;
+    let conn = state.org_db(&path.org_id).get()?;
+    let project = get_project(&conn, &path)?;
+    Ok(Json(WebhookMirrorStatus::of(&project, state.clock.now())))
+}
+
+/// PUT /operators/organizations/{org_id}/projects/{project_id}/webhook-mirror
+/// Copy the project's webhooks, sent and received, to `url` until the
+/// mirror expires. Replaces any mirror already set.
+pub async fn set_webhook_mirror(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OperatorContext>,
+    headers: HeaderMap,
+    Path(path): Path<WebhookMirrorPath>,
+    Json(input): Json<SetWebhookMirror>,
+) -> Result<Json<WebhookMirrorStatus>> {
+    let days = input.expires_in_days.unwrap_or(DEFAULT_MIRROR_DAYS);
+    if !(1..=MAX_MIRROR_DAYS).contains(&days) {
+        return Err(AppError::BadRequest(
+            msg::WEBHOOK_MIRROR_DAYS_INVALID.into(),
+        ));
+    }
+    external_url::validate_external_url("url", Some(&input.url), state.allow_localhost_urls)
+        .await?;
+
+    ctx.require_org_access(&state.db.get()?, &path.org_id)?;
+    let conn = state.org_db(&path.org_id).get()?;
+    let audit_conn = state.audit.get()?;
+    let project = get_project(&conn, &path)?;
+
+    let now = state.clock.now();
+    let expires_at = now + days * 86400;
+    queries::set_project_webhook_mirror(&conn, &project.id, Some((&input.url, expires_at)))?;
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.user.id))
+        .action(AuditAction::SetWebhookMirror)
+        .resource("project", &project.id)
+        .details(&serde_json::json!({
+            "url": input.url,
+            "expires_at": expires_at,
+        }))
+        .org(&path.org_id)
+        .project(&project.id)
+        .names(&ctx.audit_names().resource(project.name.clone()))
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    let project = get_project(&conn, &path)?;
+    Ok(Json(WebhookMirrorStatus::of(&project, now)))
+}
+
+/// DELETE /operators/organizations/{org_id}/projects/{project_id}/webhook-mirror
+/// Stop mirroring the project's webhooks now, before the mirror expires.
+pub async fn clear_webhook_mirror(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OperatorContext>,
+    headers: HeaderMap,
+    Path(path): Path<WebhookMirrorPath>,
+) -> Result<Json<WebhookMirrorStatus>> {
+    ctx.require_org_access(&state.db.get()?, &path.org_id)?;
+    let conn = state.org_db(&path.org_id).get()?;
+    let audit_conn = state.audit.get()?;
+    let project = get_project(&conn, &path)?;
+
+    queries::set_project_webhook_mirror(&conn, &project.id, None)?;
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.user.id))
+        .action(AuditAction::ClearWebhookMirror)
+        .resource("project", &project.id)
+        .details(&serde_json::json!({ "url": project.webhook_mirror_url }))
+        .org(&path.org_id)
+        .project(&project.id)
+        .names(&ctx.audit_names().resource(project.name.clone()))
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    let project = get_project(&conn, &path)?;
+    Ok(Json(WebhookMirrorStatus::of(&project, state.clock.now())))
+}
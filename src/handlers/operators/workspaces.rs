@@ -0,0 +1,247 @@
+//! Onboarding a customer in one request: the org, its owner, its projects and
+//! their products, created in a single transaction so a failure partway
+//! leaves nothing behind. Everything goes through the same queries as the
+//! individual create endpoints.
+
+use axum::{
+    extract::{Extension, State},
+    http::HeaderMap,
+};
+use rusqlite::Connection;
+use serde::Serialize;
+
+use crate::db::queries::ProductWithProviderLinks;
+use crate::db::{AppState, outbox, queries};
+use crate::error::{AppError, FieldError, Result, msg};
+use crate::external_url::validate_external_url;
+use crate::extractors::Json;
+use crate::jwt;
+use crate::middleware::OperatorContext;
+use crate::models::{
+    ActorType, AuditAction, CreateOrgMember, CreateWorkspace, OrgMemberRole, OrganizationPublic,
+    ProjectPublic, User, WorkspaceProject,
+};
+use crate::util::AuditLogBuilder;
+
+use super::organizations::{apply_organization_update, org_to_public};
+
+/// Response of `POST /operators/workspaces`. `owner_api_key` is only ever
+/// shown here.
+#[derive(Debug, Serialize)]
+pub struct WorkspaceCreated {
+    pub org: OrganizationPublic,
+    pub owner: User,
+    pub owner_api_key: String,
+    pub projects: Vec<WorkspaceProjectCreated>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WorkspaceProjectCreated {
+    #[serde(flatten)]
+    pub project: ProjectPublic,
+    pub products: Vec<ProductWithProviderLinks>,
+}
+
+/// Report a validation failure at `path` in the body. Other errors pass
+/// through unchanged.
+fn at(path: impl Into<String>) -> impl FnOnce(AppError) -> AppError {
+    let path = path.into();
+    move |err| match err {
+        AppError::BadRequest(message) => AppError::InvalidBodyField(FieldError {
+            path,
+            message,
+            expected: None,
+            value: None,
+        }),
+        err => err,
+    }
+}
+
+/// Everything that can be checked before writing, so most mistakes fail
+/// without opening a transaction.
+async fn validate_workspace(
+    state: &AppState,
+    conn: &Connection,
+    input: &CreateWorkspace,
+) -> Result<()> {
+    input.org.create_input().validate().map_err(at("org"))?;
+    input.org.payment_update().validate().map_err(at("org"))?;
+    input.owner.validate().map_err(at("owner"))?;
+    if queries::get_user_by_email(conn, &input.owner.email, &state.emails())?.is_some() {
+        return Err(at("owner.email")(AppError::BadRequest(
+            msg::EMAIL_ALREADY_EXISTS.into(),
+        )));
+    }
+
+    for (i, WorkspaceProject { project, products }) in input.projects.iter().enumerate() {
+        let path = format!("projects[{}]", i);
+        project.validate().map_err(at(&path))?;
+        if !project.templates.is_empty() {
+            return Err(at(format!("{}.templates", path))(AppError::BadRequest(
+                msg::WORKSPACE_TEMPLATES_UNSUPPORTED.into(),
+            )));
+        }
+        // A new org has no Resend key for email_from to send with
+        if project.email_from.is_some() {
+            return Err(at(format!("{}.email_from", path))(AppError::BadRequest(
+                msg::EMAIL_FROM_REQUIRES_ORG_RESEND_KEY.into(),
+            )));
+        }
+        validate_external_url(
+            "redirect_url",
+            project.redirect_url.as_deref(),
+            state.allow_localhost_urls,
+        )
+        .await
+        .map_err(at(&path))?;
+        validate_external_url(
+            "email_webhook_url",
+            project.email_webhook_url.as_deref(),
+            state.allow_localhost_urls,
+        )
+        .await
+        .map_err(at(&path))?;
+
+        for (j, product) in products.iter().enumerate() {
+            let path = format!("{}.products[{}]", path, j);
+            product.product.validate().map_err(at(&path))?;
+            if product.product.extends_updates_for_product_id.is_some() {
+                return Err(at(format!("{}.extends_updates_for_product_id", path))(
+                    AppError::BadRequest(msg::WORKSPACE_RENEWAL_UNSUPPORTED.into()),
+                ));
+            }
+            for (k, link) in product.provider_links.iter().enumerate() {
+                let path = format!("{}.provider_links[{}]", path, k);
+                link.validate().map_err(at(&path))?;
+                if product.provider_links[..k]
+                    .iter()
+                    .any(|earlier| earlier.provider == link.provider)
+                {
+                    return Err(at(path)(AppError::BadRequest(
+                        msg::WORKSPACE_DUPLICATE_PROVIDER_LINK.into(),
+                    )));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// POST /operators/workspaces
+/// Create an org with its payment config, a new owner with an API key, and
+/// the org's projects (each with a fresh keypair) and products. Nothing is
+/// created unless all of it is. A validation failure is reported at its
+/// path in the body, like `projects[0].products[1]`.
+pub async fn create_workspace(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OperatorContext>,
+    headers: HeaderMap,
+    Json(input): Json<CreateWorkspace>,
+) -> Result<Json<WorkspaceCreated>> {
+    // Projects in a dedicated org file can't share a transaction with the org
+    if state.org_dbs.is_enabled() {
+        return Err(AppError::BadRequest(
+            msg::WORKSPACE_RESIDENCY_UNSUPPORTED.into(),
+        ));
+    }
+
+    let mut conn = state.db.get()?;
+    let audit_conn = state.audit.get()?;
+    validate_workspace(&state, &conn, &input).await?;
+
+    let (org, owner, owner_api_key, projects) = outbox::with_audited_tx(
+        &mut conn,
+        |tx| {
+            let org =
+                queries::create_organization(tx, &input.org.create_input(), &state.master_key)?;
+            apply_organization_update(tx, &state, &org.id, &org, &input.org.payment_update())
+                .map_err(at("org"))?;
+            let org = queries::get_organization_by_id(tx, &org.id)?
+                .ok_or_else(|| AppError::Internal(msg::ORG_NOT_FOUND_AFTER_UPDATE.into()))?;
+
+            let owner = queries::create_user(tx, &input.owner, &state.emails())?;
+            queries::create_org_member(
+                tx,
+                &org.id,
+                &CreateOrgMember {
+                    user_id: owner.id.clone(),
+                    role: OrgMemberRole::Owner,
+                },
+            )?;
+            let (_, owner_api_key) =
+                queries::insert_api_key(tx, &owner.id, "Default", None, true, None)?;
+
+            let mut projects = Vec::with_capacity(input.projects.len());
+            for WorkspaceProject { project, products } in &input.projects {
+                let (private_key, public_key) = jwt::generate_keypair();
+                let project = queries::create_project(
+                    tx,
+                    &org.id,
+                    project,
+                    &private_key,
+                    &public_key,
+                    &state.master_key,
+                )?;
+
+                let mut created = Vec::with_capacity(products.len());
+                for product in products {
+                    let created_product =
+                        queries::create_product(tx, &project.id, &product.product)?;
+                    let provider_links = product
+                        .provider_links
+                        .iter()
+                        .map(|link| queries::create_provider_link(tx, &created_product.id, link))
+                        .collect::<Result<Vec<_>>>()?;
+                    created.push(ProductWithProviderLinks {
+                        product: created_product,
+                        provider_links,
+                    });
+                }
+                projects.push((project, created));
+            }
+
+            Ok((org, owner, owner_api_key, projects))
+        },
+        |(org, owner, _, projects)| {
+            let details = serde_json::json!({
+                "name": org.name,
+                "owner_user_id": owner.id,
+                "owner_email": owner.email,
+                "payment_provider": org.payment_provider,
+                "projects": projects
+                    .iter()
+                    .map(|(project, products)| serde_json::json!({
+                        "project_id": project.id,
+                        "name": project.name,
+                        "product_ids": products.iter().map(|p| &p.product.id).collect::<Vec<_>>()
+                    }))
+                    .collect::<Vec<_>>()
+            });
+            AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+                .actor(ActorType::User, Some(&ctx.user.id))
+                .action(AuditAction::CreateWorkspace)
+                .resource("org", &org.id)
+                .details(&details)
+                .org(&org.id)
+                .names(&ctx.audit_names().resource(org.name.clone()))
+                .auth_method(&ctx.auth_method)
+                .entry()
+        },
+    )?;
+    outbox::relay(&conn, &audit_conn);
+    // New public keys mustn't be answered from the unknown-project cache
+    state.project_misses.clear();
+
+    Ok(Json(WorkspaceCreated {
+        org: org_to_public(&conn, org)?,
+        owner,
+        owner_api_key,
+        projects: projects
+            .into_iter()
+            .map(|(project, products)| WorkspaceProjectCreated {
+                project: project.into(),
+                products,
+            })
+            .collect(),
+    }))
+}
@@ -0,0 +1,37 @@
+use axum::extract::State;
+use serde::Deserialize;
+
+use crate::db::{AppState, queries};
+use crate::error::Result;
+use crate::extractors::{Json, Path, Query};
+use crate::middleware::OrgProjectPath;
+use crate::models::ProjectActivity;
+
+/// Entries returned when `limit` isn't given
+const DEFAULT_ACTIVITY_LIMIT: i64 = 20;
+const MAX_ACTIVITY_LIMIT: i64 = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct ActivityQuery {
+    /// Max entries to return (default 20, max 100)
+    pub limit: Option<i64>,
+}
+
+/// GET /orgs/{org_id}/projects/{project_id}/activity
+/// The project's latest changes by org members, newest first, each with a
+/// one-line `summary` for the console's recent changes feed. The full audit
+/// log (`/orgs/{org_id}/audit-logs`) has the details and the public entries.
+pub async fn list_project_activity(
+    State(state): State<AppState>,
+    Path(path): Path<OrgProjectPath>,
+    Query(query): Query<ActivityQuery>,
+) -> Result<Json<Vec<ProjectActivity>>> {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_ACTIVITY_LIMIT)
+        .clamp(1, MAX_ACTIVITY_LIMIT);
+
+    let conn = state.audit.get()?;
+    let activity = queries::list_recent_project_activity(&conn, &path.project_id, limit)?;
+    Ok(Json(activity))
+}
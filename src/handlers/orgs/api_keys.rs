@@ -4,7 +4,7 @@ use axum::{
 };
 use serde::Deserialize;
 
-use crate::db::{AppState, queries};
+use crate::db::{AppState, outbox, queries};
 use crate::error::{AppError, OptionExt, Result, msg};
 use crate::extractors::{Json, Path};
 use crate::middleware::OrgMemberContext;
@@ -35,17 +35,24 @@ pub async fn create_api_key(
     Json(input): Json<CreateApiKey>,
 ) -> Result<Json<ApiKeyCreated>> {
     // Only owner can manage other members' keys, or member can manage their own
+    // (but a view-only key can't mint itself a broader one)
     if path.user_id != ctx.member.user_id {
         ctx.require_owner()?;
+    } else {
+        ctx.require_write()?;
     }
 
     let mut conn = state.db.get()?;
     let audit_conn = state.audit.get()?;
 
     // Get the target member with user details
-    let target_member =
-        queries::get_org_member_with_user_by_user_and_org(&conn, &path.user_id, &path.org_id)?
-            .or_not_found(msg::NOT_ORG_MEMBER)?;
+    let target_member = queries::get_org_member_with_user_by_user_and_org(
+        &conn,
+        &path.user_id,
+        &path.org_id,
+        &state.emails(),
+    )?
+    .or_not_found(msg::NOT_ORG_MEMBER)?;
 
     // Validate that all scopes are for the current org (security boundary)
     // Users should only be able to create scopes for orgs they're managing keys within
@@ -62,14 +69,37 @@ pub async fn create_api_key(
     // Create API key for the member's user identity
     // Note: org member keys default to user_manageable=true unless explicitly set
     let user_manageable = input.user_manageable.unwrap_or(true);
-    let (key_record, full_key) = queries::create_api_key(
+    let details = serde_json::json!({
+        "target_user_id": path.user_id,
+        "target_email": target_member.email,
+        "name": input.name,
+        "impersonator": ctx.impersonator_json()
+    });
+    let (key_record, full_key) = outbox::try_with_audited_tx(
         &mut conn,
-        &path.user_id,
-        &input.name,
-        input.expires_in_days,
-        user_manageable,
-        input.scopes.as_deref(),
+        |tx| {
+            queries::insert_api_key(
+                tx,
+                &path.user_id,
+                &input.name,
+                input.expires_in_days,
+                user_manageable,
+                input.scopes.as_deref(),
+            )
+        },
+        |(key_record, _)| {
+            AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+                .actor(ActorType::User, Some(&ctx.member.user_id))
+                .action(AuditAction::CreateApiKey)
+                .resource("api_key", &key_record.id)
+                .details(&details)
+                .org(&path.org_id)
+                .names(&ctx.audit_names().resource(key_record.name.clone()))
+                .auth_method(&ctx.auth_method)
+                .checked_entry()
+        },
     )?;
+    outbox::relay(&conn, &audit_conn);
 
     // Get scopes from the created key
     let scopes = if input.scopes.is_some() {
@@ -78,21 +108,6 @@ pub async fn create_api_key(
         None
     };
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
-        .actor(ActorType::User, Some(&ctx.member.user_id))
-        .action(AuditAction::CreateApiKey)
-        .resource("api_key", &key_record.id)
-        .details(&serde_json::json!({
-            "target_user_id": path.user_id,
-            "target_email": target_member.email,
-            "name": input.name,
-            "impersonator": ctx.impersonator_json()
-        }))
-        .org(&path.org_id)
-        .names(&ctx.audit_names().resource(key_record.name.clone()))
-        .auth_method(&ctx.auth_method)
-        .save()?;
-
     Ok(Json(ApiKeyCreated {
         id: key_record.id,
         name: key_record.name,
@@ -120,9 +135,13 @@ pub async fn list_api_keys(
     let conn = state.db.get()?;
 
     // Verify the user is a member of this org
-    let _target_member =
-        queries::get_org_member_with_user_by_user_and_org(&conn, &path.user_id, &path.org_id)?
-            .or_not_found(msg::NOT_ORG_MEMBER)?;
+    let _target_member = queries::get_org_member_with_user_by_user_and_org(
+        &conn,
+        &path.user_id,
+        &path.org_id,
+        &state.emails(),
+    )?
+    .or_not_found(msg::NOT_ORG_MEMBER)?;
 
     let limit = query.limit();
     let offset = query.offset();
@@ -158,15 +177,21 @@ pub async fn revoke_api_key(
     // Only owner can revoke other members' keys, or member can revoke their own
     if path.user_id != ctx.member.user_id {
         ctx.require_owner()?;
+    } else {
+        ctx.require_write()?;
     }
 
-    let conn = state.db.get()?;
+    let mut conn = state.db.get()?;
     let audit_conn = state.audit.get()?;
 
     // Verify the user is a member of this org
-    let target_member =
-        queries::get_org_member_with_user_by_user_and_org(&conn, &path.user_id, &path.org_id)?
-            .or_not_found(msg::NOT_ORG_MEMBER)?;
+    let target_member = queries::get_org_member_with_user_by_user_and_org(
+        &conn,
+        &path.user_id,
+        &path.org_id,
+        &state.emails(),
+    )?
+    .or_not_found(msg::NOT_ORG_MEMBER)?;
 
     // Verify key exists and belongs to the right user
     let key =
@@ -176,22 +201,28 @@ pub async fn revoke_api_key(
         return Err(AppError::NotFound(msg::API_KEY_NOT_FOUND.into()));
     }
 
-    queries::revoke_api_key(&conn, &path.key_id)?;
-
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
-        .actor(ActorType::User, Some(&ctx.member.user_id))
-        .action(AuditAction::RevokeApiKey)
-        .resource("api_key", &path.key_id)
-        .details(&serde_json::json!({
-            "target_user_id": path.user_id,
-            "target_email": target_member.email,
-            "key_name": key.name,
-            "impersonator": ctx.impersonator_json()
-        }))
-        .org(&path.org_id)
-        .names(&ctx.audit_names().resource(key.name.clone()))
-        .auth_method(&ctx.auth_method)
-        .save()?;
+    let details = serde_json::json!({
+        "target_user_id": path.user_id,
+        "target_email": target_member.email,
+        "key_name": key.name,
+        "impersonator": ctx.impersonator_json()
+    });
+    outbox::try_with_audited_tx(
+        &mut conn,
+        |tx| queries::revoke_api_key(tx, &path.key_id),
+        |_| {
+            AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+                .actor(ActorType::User, Some(&ctx.member.user_id))
+                .action(AuditAction::RevokeApiKey)
+                .resource("api_key", &path.key_id)
+                .details(&details)
+                .org(&path.org_id)
+                .names(&ctx.audit_names().resource(key.name.clone()))
+                .auth_method(&ctx.auth_method)
+                .checked_entry()
+        },
+    )?;
+    outbox::relay(&conn, &audit_conn);
 
     Ok(Json(serde_json::json!({ "success": true })))
 }
@@ -0,0 +1,173 @@
+//! Release artifacts: files a project hands out through `/download/{id}` to
+//! licenses whose updates window covers the release. Paycheck stores only
+//! the URL; the vendor's CDN checks the signature on each redirect with the
+//! project's download signing key.
+
+use axum::{
+    extract::{Extension, State},
+    http::HeaderMap,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::artifacts::DOWNLOAD_URL_LIFETIME_SECS;
+use crate::db::{AppState, queries};
+use crate::error::{AppError, OptionExt, Result, msg};
+use crate::external_url::validate_external_url;
+use crate::extractors::{Json, Path};
+use crate::middleware::{OrgMemberContext, OrgProjectPath};
+use crate::models::{ActorType, Artifact, ArtifactDownload, AuditAction, CreateArtifact};
+use crate::util::AuditLogBuilder;
+
+#[derive(Deserialize)]
+pub struct ArtifactPath {
+    pub org_id: String,
+    pub project_id: String,
+    pub artifact_id: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DownloadSigningKey {
+    /// Hex-encoded HMAC-SHA256 key
+    pub key: String,
+    pub algorithm: &'static str,
+    /// How long each signed URL is valid for
+    pub url_lifetime_secs: i64,
+}
+
+/// POST /orgs/{org_id}/projects/{project_id}/artifacts
+/// Register an artifact released at `released_at` (default: now).
+pub async fn create_artifact(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(path): Path<OrgProjectPath>,
+    headers: HeaderMap,
+    Json(input): Json<CreateArtifact>,
+) -> Result<Json<Artifact>> {
+    if !ctx.can_write_project() {
+        return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
+    }
+    input.validate()?;
+    validate_external_url("url", Some(&input.url), state.allow_localhost_urls).await?;
+
+    let conn = state.org_db(&path.org_id).get()?;
+    let audit_conn = state.audit.get()?;
+
+    let released_at = input.released_at.unwrap_or_else(|| state.clock.now());
+    let artifact = queries::create_artifact(
+        &conn,
+        &path.project_id,
+        &input,
+        released_at,
+        Some(&ctx.member.user_id),
+    )?;
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.member.user_id))
+        .action(AuditAction::CreateArtifact)
+        .resource("artifact", &artifact.id)
+        .details(&serde_json::json!({
+            "name": artifact.name,
+            "url": artifact.url,
+            "released_at": artifact.released_at,
+            "impersonator": ctx.impersonator_json()
+        }))
+        .org(&path.org_id)
+        .project(&path.project_id)
+        .names(&ctx.audit_names().resource(artifact.name.clone()))
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    Ok(Json(artifact))
+}
+
+/// GET /orgs/{org_id}/projects/{project_id}/artifacts
+/// The project's artifacts with their download counts, newest release first.
+pub async fn list_artifacts(
+    State(state): State<AppState>,
+    Path(path): Path<OrgProjectPath>,
+) -> Result<Json<Vec<Artifact>>> {
+    let conn = state.org_db(&path.org_id).get()?;
+    Ok(Json(queries::list_artifacts(&conn, &path.project_id)?))
+}
+
+/// DELETE /orgs/{org_id}/projects/{project_id}/artifacts/{artifact_id}
+/// Stop handing out the artifact. Its download counts go with it.
+pub async fn delete_artifact(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(path): Path<ArtifactPath>,
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>> {
+    if !ctx.can_write_project() {
+        return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
+    }
+
+    let conn = state.org_db(&path.org_id).get()?;
+    let audit_conn = state.audit.get()?;
+
+    let existing = queries::get_artifact(&conn, &path.project_id, &path.artifact_id)?
+        .or_not_found(msg::ARTIFACT_NOT_FOUND)?;
+    queries::delete_artifact(&conn, &path.project_id, &existing.id)?;
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.member.user_id))
+        .action(AuditAction::DeleteArtifact)
+        .resource("artifact", &existing.id)
+        .details(&serde_json::json!({
+            "name": existing.name,
+            "download_count": existing.download_count,
+            "impersonator": ctx.impersonator_json()
+        }))
+        .org(&path.org_id)
+        .project(&path.project_id)
+        .names(&ctx.audit_names().resource(existing.name.clone()))
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    Ok(Json(serde_json::json!({ "success": true })))
+}
+
+/// GET /orgs/{org_id}/projects/{project_id}/artifacts/{artifact_id}/downloads
+/// Download counts per license, most recently downloaded first.
+pub async fn list_artifact_downloads(
+    State(state): State<AppState>,
+    Path(path): Path<ArtifactPath>,
+) -> Result<Json<Vec<ArtifactDownload>>> {
+    let conn = state.org_db(&path.org_id).get()?;
+    let artifact = queries::get_artifact(&conn, &path.project_id, &path.artifact_id)?
+        .or_not_found(msg::ARTIFACT_NOT_FOUND)?;
+    Ok(Json(queries::list_artifact_downloads(&conn, &artifact.id)?))
+}
+
+/// GET /orgs/{org_id}/projects/{project_id}/download-signing-key
+/// The key the vendor's CDN checks download signatures with. Org owners and
+/// admins only, since it lets the holder sign download URLs for any file
+/// behind the CDN.
+pub async fn get_download_signing_key(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(path): Path<OrgProjectPath>,
+    headers: HeaderMap,
+) -> Result<Json<DownloadSigningKey>> {
+    ctx.require_admin()?;
+
+    let audit_conn = state.audit.get()?;
+    let key = state.master_key.download_signing_key(&path.project_id);
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.member.user_id))
+        .action(AuditAction::ViewDownloadSigningKey)
+        .resource("project", &path.project_id)
+        .details(&serde_json::json!({ "impersonator": ctx.impersonator_json() }))
+        .org(&path.org_id)
+        .project(&path.project_id)
+        .names(&ctx.audit_names())
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    Ok(Json(DownloadSigningKey {
+        key: hex::encode(key),
+        algorithm: "HMAC-SHA256",
+        url_lifetime_secs: DOWNLOAD_URL_LIFETIME_SECS,
+    }))
+}
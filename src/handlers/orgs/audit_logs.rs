@@ -1,18 +1,35 @@
-use axum::extract::State;
+use axum::{
+    extract::{Extension, State},
+    http::{HeaderMap, header},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
 
+use crate::audit_archive;
+use crate::audit_csv;
 use crate::db::{AppState, queries};
-use crate::error::Result;
+use crate::error::{AppError, Result, msg};
 use crate::extractors::{Json, Path, Query};
-use crate::models::{AuditLogQuery, AuditLogResponse};
+use crate::middleware::{OrgMemberContext, VisibleProjects, visible_project_ids};
+use crate::models::{ActorType, AuditAction, AuditLogQuery, AuditLogResponse};
 use crate::pagination::Paginated;
+use crate::util::{AuditLogBuilder, blocking_stream_body};
 
 /// Query audit logs scoped to the authenticated org.
 /// The org_id from the path is always enforced - query params cannot override it.
+/// `member`-role users only see entries for the projects they were added to.
 pub async fn query_org_audit_logs(
     State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
     Path(org_id): Path<String>,
     Query(mut query): Query<AuditLogQuery>,
 ) -> Result<Json<Paginated<AuditLogResponse>>> {
+    // Org-level entries have no project_id, so they're left out too
+    let conn = state.org_db(&org_id).get()?;
+    if let VisibleProjects::Only(ids) = visible_project_ids(&conn, &ctx, state.clock.now())? {
+        query.project_ids = Some(ids.into_iter().collect());
+    }
+
     // Force org_id from path - ignore any org_id in query params
     query.org_id = Some(org_id);
 
@@ -23,3 +40,123 @@ pub async fn query_org_audit_logs(
     let responses: Vec<AuditLogResponse> = logs.into_iter().map(Into::into).collect();
     Ok(Json(Paginated::new(responses, total, limit, offset)))
 }
+
+/// Download the org's audit logs as CSV: every entry matching the same
+/// filters and project scoping as `query_org_audit_logs`, newest first,
+/// streamed as it's read. The export itself is audit logged.
+pub async fn export_org_audit_logs(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(org_id): Path<String>,
+    headers: HeaderMap,
+    Query(mut query): Query<AuditLogQuery>,
+) -> Result<Response> {
+    let conn = state.org_db(&org_id).get()?;
+    if let VisibleProjects::Only(ids) = visible_project_ids(&conn, &ctx, state.clock.now())? {
+        query.project_ids = Some(ids.into_iter().collect());
+    }
+    query.org_id = Some(org_id.clone());
+
+    let audit_conn = state.audit.get()?;
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.member.user_id))
+        .action(AuditAction::ExportAuditLogs)
+        .resource("org", &org_id)
+        .details(&serde_json::json!({
+            "format": "csv",
+            "from": query.from_timestamp,
+            "to": query.to_timestamp,
+            "impersonator": ctx.impersonator_json()
+        }))
+        .org(&org_id)
+        .names(&ctx.audit_names())
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    let filename = audit_csv::filename(&org_id, state.clock.now());
+    let body = blocking_stream_body(format!("Audit log export for org {}", org_id), move |out| {
+        audit_csv::write_csv(&audit_conn, &query, |_| Ok(()), out).map(|_| ())
+    });
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditArchiveRequest {
+    /// Inclusive Unix timestamp range
+    pub from: i64,
+    pub to: i64,
+}
+
+/// Download the org's audit logs in a time range as a signed archive
+/// (gzip'd NDJSON, see `audit_archive`). Owner only.
+///
+/// The archive is streamed as it's written. If the audit database fails
+/// partway through, the response body ends with an error and the truncated
+/// archive won't verify.
+pub async fn archive_org_audit_logs(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(org_id): Path<String>,
+    headers: HeaderMap,
+    Json(input): Json<AuditArchiveRequest>,
+) -> Result<Response> {
+    ctx.require_owner()?;
+    if input.from > input.to {
+        return Err(AppError::BadRequest(
+            msg::AUDIT_ARCHIVE_RANGE_INVALID.into(),
+        ));
+    }
+
+    let audit_conn = state.audit.get()?;
+    let generated_at = state.clock.now();
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.member.user_id))
+        .action(AuditAction::GenerateAuditArchive)
+        .resource("org", &org_id)
+        .details(&serde_json::json!({
+            "from": input.from,
+            "to": input.to,
+            "impersonator": ctx.impersonator_json()
+        }))
+        .org(&org_id)
+        .names(&ctx.audit_names())
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    let filename = format!("audit-{}-{}-{}.ndjson.gz", org_id, input.from, input.to);
+    let body = blocking_stream_body(format!("Audit archive for org {}", org_id), move |out| {
+        audit_archive::write_archive(
+            &audit_conn,
+            &state.master_key,
+            &org_id,
+            input.from,
+            input.to,
+            generated_at,
+            out,
+        )
+        .map(|_| ())
+    });
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/gzip".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
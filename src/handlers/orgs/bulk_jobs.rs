@@ -0,0 +1,214 @@
+//! Bulk license jobs: `POST .../licenses` requests too large to create
+//! within the request (see [`crate::bulk_jobs`]). These endpoints report a
+//! job's progress, cancel it, and hand out its activation codes.
+
+use axum::{
+    extract::{Extension, State},
+    http::{HeaderMap, header},
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::bulk_jobs;
+use crate::db::{AppState, outbox, queries};
+use crate::error::{AppError, OptionExt, Result, msg};
+use crate::extractors::{Json, Path};
+use crate::middleware::OrgMemberContext;
+use crate::models::{ActorType, AuditAction, BulkJob, BulkJobChunk, BulkJobStatus};
+use crate::util::AuditLogBuilder;
+
+#[derive(Deserialize)]
+pub struct BulkJobPath {
+    pub org_id: String,
+    pub project_id: String,
+    pub job_id: String,
+}
+
+/// A chunk that failed, with nothing created
+#[derive(Debug, Serialize)]
+pub struct BulkJobChunkError {
+    pub chunk: i64,
+    pub error: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BulkJobResponse {
+    #[serde(flatten)]
+    pub job: BulkJob,
+    /// Chunks the job is split into
+    pub chunk_count: i64,
+    /// Chunks attempted so far
+    pub chunks_done: i64,
+    pub errors: Vec<BulkJobChunkError>,
+    /// Once the job is over, the licenses it created (jobs without activation codes)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license_ids: Option<Vec<String>>,
+    /// Once the job is over, where to download its activation codes (jobs with activation codes)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub codes_url: Option<String>,
+}
+
+impl BulkJobResponse {
+    pub fn new(org_id: &str, job: BulkJob, chunks: Vec<BulkJobChunk>) -> Self {
+        let finished = job.status != BulkJobStatus::Running;
+        let chunks_done = chunks.len() as i64;
+        let errors = chunks
+            .iter()
+            .filter_map(|chunk| {
+                chunk.error.clone().map(|error| BulkJobChunkError {
+                    chunk: chunk.chunk,
+                    error,
+                })
+            })
+            .collect();
+        let codes_url = (finished && job.activation_codes).then(|| {
+            format!(
+                "/orgs/{}/projects/{}/bulk-jobs/{}/csv",
+                org_id, job.project_id, job.id
+            )
+        });
+        let license_ids = (finished && !job.activation_codes).then(|| {
+            chunks
+                .into_iter()
+                .flat_map(|chunk| chunk.license_ids)
+                .collect()
+        });
+        Self {
+            chunk_count: bulk_jobs::chunk_count(job.requested),
+            chunks_done,
+            errors,
+            license_ids,
+            codes_url,
+            job,
+        }
+    }
+}
+
+/// The job in the path, checked against the project in the path.
+fn get_job(conn: &rusqlite::Connection, path: &BulkJobPath) -> Result<BulkJob> {
+    queries::get_bulk_job(conn, &path.project_id, &path.job_id)?
+        .or_not_found(msg::BULK_JOB_NOT_FOUND)
+}
+
+/// GET /orgs/{org_id}/projects/{project_id}/bulk-jobs/{job_id}
+/// A job's status and progress: licenses created so far and chunks that
+/// failed. Once it's over, also the license IDs or the CSV download link.
+pub async fn get_bulk_job(
+    State(state): State<AppState>,
+    Path(path): Path<BulkJobPath>,
+) -> Result<Json<BulkJobResponse>> {
+    let conn = state.org_db(&path.org_id).get()?;
+    let job = get_job(&conn, &path)?;
+    let chunks = queries::list_bulk_job_chunks(&conn, &job.id)?;
+    Ok(Json(BulkJobResponse::new(&path.org_id, job, chunks)))
+}
+
+/// POST /orgs/{org_id}/projects/{project_id}/bulk-jobs/{job_id}/cancel
+/// Stop a running job. The chunk in flight is rolled back; licenses from
+/// chunks already created are kept.
+pub async fn cancel_bulk_job(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(path): Path<BulkJobPath>,
+    headers: HeaderMap,
+) -> Result<Json<BulkJobResponse>> {
+    if !ctx.can_write_project() {
+        return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
+    }
+
+    let conn = state.org_db(&path.org_id).get()?;
+    let audit_conn = state.audit.get()?;
+    let job = get_job(&conn, &path)?;
+
+    if !queries::cancel_bulk_job(&conn, &job.id)? {
+        return Err(AppError::Conflict(msg::BULK_JOB_NOT_RUNNING.into()));
+    }
+
+    let job = get_job(&conn, &path)?;
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.member.user_id))
+        .action(AuditAction::CancelBulkLicenseJob)
+        .resource("bulk_job", &job.id)
+        .details(&serde_json::json!({
+            "product_id": job.product_id,
+            "requested": job.requested,
+            "created_count": job.created_count,
+            "impersonator": ctx.impersonator_json()
+        }))
+        .org(&path.org_id)
+        .project(&path.project_id)
+        .names(&ctx.audit_names())
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    let chunks = queries::list_bulk_job_chunks(&conn, &job.id)?;
+    Ok(Json(BulkJobResponse::new(&path.org_id, job, chunks)))
+}
+
+/// GET /orgs/{org_id}/projects/{project_id}/bulk-jobs/{job_id}/csv
+/// Download a finished job's licenses as CSV (`license_id,activation_code`),
+/// for jobs started with `activation_codes`. Codes are issued as the CSV is
+/// downloaded, so each download gets fresh codes with the usual 30 minute TTL.
+pub async fn download_bulk_job_codes(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(path): Path<BulkJobPath>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    if !ctx.can_write_project() {
+        return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
+    }
+
+    let mut conn = state.org_db(&path.org_id).get()?;
+    let audit_conn = state.audit.get()?;
+    let job = get_job(&conn, &path)?;
+
+    if !job.activation_codes {
+        return Err(AppError::BadRequest(msg::BULK_JOB_NO_CODES.into()));
+    }
+    if job.status == BulkJobStatus::Running {
+        return Err(AppError::Conflict(msg::BULK_JOB_STILL_RUNNING.into()));
+    }
+
+    let project = queries::get_project_by_id(&conn, &path.project_id)?
+        .or_not_found(msg::PROJECT_NOT_FOUND)?;
+    let license_ids: Vec<String> = queries::list_bulk_job_chunks(&conn, &job.id)?
+        .into_iter()
+        .flat_map(|chunk| chunk.license_ids)
+        .collect();
+
+    // The codes and the audit entry commit together
+    let csv = outbox::with_audited_tx(
+        &mut conn,
+        |tx| bulk_jobs::activation_codes_csv(tx, &project.license_key_prefix, &license_ids),
+        |_| {
+            AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+                .actor(ActorType::User, Some(&ctx.member.user_id))
+                .action(AuditAction::DownloadBulkLicenseCodes)
+                .resource("bulk_job", &job.id)
+                .details(&serde_json::json!({
+                    "product_id": job.product_id,
+                    "count": license_ids.len(),
+                    "impersonator": ctx.impersonator_json()
+                }))
+                .org(&path.org_id)
+                .project(&path.project_id)
+                .names(&ctx.audit_names().project(project.name.clone()))
+                .auth_method(&ctx.auth_method)
+                .entry()
+        },
+    )?;
+    outbox::relay(&conn, &audit_conn);
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"licenses-{}.csv\"", job.id),
+            ),
+        ],
+        csv,
+    )
+        .into_response())
+}
@@ -0,0 +1,92 @@
+//! Claims preview: the token payload /redeem would issue for a license,
+//! without activating a device or signing anything.
+
+use axum::extract::State;
+use serde::Serialize;
+
+use crate::db::{AppState, queries};
+use crate::error::{AppError, OptionExt, Result, msg};
+use crate::extractors::{Json, Path};
+use crate::jwt::{self, LicenseClaims};
+use crate::models::DeviceType;
+
+use super::LicensePath;
+
+/// Stand-in for the device a real redemption would activate
+const PREVIEW_DEVICE_ID: &str = "preview-device";
+
+/// Unsigned token payload: registered claims plus the license claims.
+/// `jti` is left out since it's generated per device.
+#[derive(Debug, Serialize)]
+pub struct PreviewClaims {
+    pub iss: String,
+    pub sub: String,
+    pub aud: String,
+    pub iat: i64,
+    pub nbf: i64,
+    pub exp: i64,
+    #[serde(flatten)]
+    pub license: LicenseClaims,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ClaimsPreview {
+    pub claims: PreviewClaims,
+    pub license_exp: Option<i64>,
+    pub updates_exp: Option<i64>,
+    /// False if /redeem would currently refuse the license (revoked or expired)
+    pub redeemable: bool,
+}
+
+/// GET /orgs/{org_id}/projects/{project_id}/licenses/{license_id}/claims-preview
+pub async fn get_license_claims_preview(
+    State(state): State<AppState>,
+    Path(mut path): Path<LicensePath>,
+) -> Result<Json<ClaimsPreview>> {
+    let conn = state.org_db(&path.org_id).get()?;
+    path.license_id = queries::resolve_admin_license_id(&conn, &path.project_id, &path.license_id)?;
+
+    let license = queries::get_license_by_id(&conn, &path.license_id)?
+        .or_not_found(msg::LICENSE_NOT_FOUND)?;
+
+    // Verify license belongs to a product in this project
+    let product = queries::get_product_by_id(&conn, &license.product_id)?
+        .or_not_found(msg::LICENSE_NOT_FOUND)?;
+
+    if product.project_id != path.project_id {
+        return Err(AppError::NotFound(msg::LICENSE_NOT_FOUND.into()));
+    }
+
+    let project = queries::get_project_by_id(&conn, &path.project_id)?
+        .or_not_found(msg::PROJECT_NOT_FOUND)?;
+
+    // Expirations are based on activation time, so preview an activation now
+    let now = state.clock.now();
+    let token = jwt::build_license_claims(
+        &license,
+        &product,
+        &project,
+        &jwt::DeviceInfo {
+            device_id: PREVIEW_DEVICE_ID,
+            device_type: DeviceType::Uuid,
+            activated_at: now,
+        },
+    );
+
+    let redeemable = !license.revoked && !license.expires_at.is_some_and(|exp| now > exp);
+
+    Ok(Json(ClaimsPreview {
+        license_exp: token.claims.license_exp,
+        updates_exp: token.claims.updates_exp,
+        redeemable,
+        claims: PreviewClaims {
+            iss: token.issuer,
+            sub: token.subject,
+            aud: token.audience,
+            iat: now,
+            nbf: now,
+            exp: now + jwt::TOKEN_LIFETIME_SECS as i64,
+            license: token.claims,
+        },
+    }))
+}
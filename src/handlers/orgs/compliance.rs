@@ -0,0 +1,98 @@
+//! Org compliance settings: how long the org's audit log entries are kept
+//! before the startup purge removes them.
+
+use axum::{
+    extract::{Extension, State},
+    http::HeaderMap,
+};
+use serde::Serialize;
+
+use crate::db::{AppState, queries};
+use crate::error::{AppError, OptionExt, Result, msg};
+use crate::extractors::{Json, Path};
+use crate::middleware::OrgMemberContext;
+use crate::models::{
+    ActorType, AuditAction, AuditRetentionPolicy, Organization, UpdateOrgComplianceSettings,
+};
+use crate::util::AuditLogBuilder;
+
+#[derive(Debug, Serialize)]
+pub struct ComplianceSettingsResponse {
+    pub org_id: String,
+    /// Days public (end-user) audit entries are kept (None = instance default)
+    pub public_audit_retention_days: Option<i64>,
+    /// Days internal audit entries are kept (None = forever)
+    pub admin_audit_retention_days: Option<i64>,
+    /// What the purge applies, with the instance default filled in
+    pub effective: AuditRetentionPolicy,
+}
+
+impl ComplianceSettingsResponse {
+    fn new(state: &AppState, org: Organization) -> Self {
+        Self {
+            effective: AuditRetentionPolicy::effective(&org, state.public_audit_retention_days),
+            org_id: org.id,
+            public_audit_retention_days: org.public_audit_retention_days,
+            admin_audit_retention_days: org.admin_audit_retention_days,
+        }
+    }
+}
+
+/// GET /orgs/{org_id}/compliance-settings
+pub async fn get_compliance_settings(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(org_id): Path<String>,
+) -> Result<Json<ComplianceSettingsResponse>> {
+    ctx.require_owner()?;
+
+    let conn = state.org_db(&org_id).get()?;
+    let org = queries::get_organization_by_id(&conn, &org_id)?.or_not_found(msg::ORG_NOT_FOUND)?;
+
+    Ok(Json(ComplianceSettingsResponse::new(&state, org)))
+}
+
+/// PUT /orgs/{org_id}/compliance-settings
+/// Set or clear the org's audit log retention.
+pub async fn update_compliance_settings(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(org_id): Path<String>,
+    headers: HeaderMap,
+    Json(input): Json<UpdateOrgComplianceSettings>,
+) -> Result<Json<ComplianceSettingsResponse>> {
+    ctx.require_owner()?;
+    input.validate()?;
+
+    let conn = state.org_db(&org_id).get()?;
+    let audit_conn = state.audit.get()?;
+    let existing =
+        queries::get_organization_by_id(&conn, &org_id)?.or_not_found(msg::ORG_NOT_FOUND)?;
+
+    queries::update_org_audit_retention(
+        &conn,
+        &org_id,
+        input.public_audit_retention_days,
+        input.admin_audit_retention_days,
+    )?;
+    let org = queries::get_organization_by_id(&conn, &org_id)?
+        .ok_or_else(|| AppError::Internal(msg::ORG_NOT_FOUND_AFTER_UPDATE.into()))?;
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.member.user_id))
+        .action(AuditAction::UpdateOrgComplianceSettings)
+        .resource("org", &org_id)
+        .details(&serde_json::json!({
+            "old_public_audit_retention_days": existing.public_audit_retention_days,
+            "old_admin_audit_retention_days": existing.admin_audit_retention_days,
+            "public_audit_retention_days": org.public_audit_retention_days,
+            "admin_audit_retention_days": org.admin_audit_retention_days,
+            "impersonator": ctx.impersonator_json()
+        }))
+        .org(&org_id)
+        .names(&ctx.audit_names().org(org.name.clone()))
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    Ok(Json(ComplianceSettingsResponse::new(&state, org)))
+}
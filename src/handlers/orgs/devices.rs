@@ -0,0 +1,190 @@
+//! Bulk device deactivation: every device on a license, or every device in a
+//! project matching a filter. Each device's token is revoked and its row
+//! deleted in the same transaction as the audit entry.
+//!
+//! Like single-device deactivation, this doesn't give back activations:
+//! `activation_count` counts every activation a license has used, and
+//! `activation_limit` caps that lifetime total. Only `device_limit`, which
+//! counts current devices, frees up.
+
+use axum::{
+    extract::{Extension, State},
+    http::HeaderMap,
+};
+use rusqlite::Connection;
+use serde::Serialize;
+
+use crate::db::{AppState, outbox, queries};
+use crate::error::{AppError, OptionExt, Result, msg};
+use crate::extractors::{Json, Path};
+use crate::middleware::{OrgMemberContext, OrgProjectPath};
+use crate::models::{
+    ActorType, AuditAction, DeactivateDevicesByFilter, Device, DeviceFilter, DeviceType,
+};
+use crate::util::AuditLogBuilder;
+
+use super::LicensePath;
+
+#[derive(Debug, Serialize)]
+pub struct DeactivatedDevice {
+    pub license_id: String,
+    pub device_id: String,
+    pub device_type: DeviceType,
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DevicesDeactivated {
+    pub deactivated: usize,
+    pub devices: Vec<DeactivatedDevice>,
+}
+
+/// Revoke each device's token and delete it
+fn deactivate(
+    conn: &Connection,
+    devices: Vec<Device>,
+    details: &str,
+) -> Result<DevicesDeactivated> {
+    for device in &devices {
+        queries::add_revoked_jti(conn, &device.license_id, &device.jti, Some(details))?;
+        queries::delete_device(conn, &device.id)?;
+    }
+    Ok(DevicesDeactivated {
+        deactivated: devices.len(),
+        devices: devices
+            .into_iter()
+            .map(|d| DeactivatedDevice {
+                license_id: d.license_id,
+                device_id: d.device_id,
+                device_type: d.device_type,
+                name: d.name,
+            })
+            .collect(),
+    })
+}
+
+/// POST /orgs/{org_id}/projects/{project_id}/licenses/{license_id}/devices/deactivate-all
+///
+/// Deactivates the license's devices matching the (optional) filter, e.g.
+/// everything but the customer's current machine.
+pub async fn deactivate_all_license_devices(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(mut path): Path<LicensePath>,
+    headers: HeaderMap,
+    Json(filter): Json<DeviceFilter>,
+) -> Result<Json<DevicesDeactivated>> {
+    if !ctx.can_write_project() {
+        return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
+    }
+
+    let mut conn = state.org_db(&path.org_id).get()?;
+    path.license_id = queries::resolve_admin_license_id(&conn, &path.project_id, &path.license_id)?;
+    let audit_conn = state.audit.get()?;
+
+    let license = queries::get_license_by_id(&conn, &path.license_id)?
+        .or_not_found(msg::LICENSE_NOT_FOUND)?;
+    if license.project_id != path.project_id {
+        return Err(AppError::NotFound(msg::LICENSE_NOT_FOUND.into()));
+    }
+
+    let details = format!("admin bulk deactivation by user {}", ctx.member.user_id);
+    let result = outbox::with_audited_tx(
+        &mut conn,
+        |tx| {
+            let devices =
+                queries::list_devices_by_filter(tx, &path.project_id, Some(&license.id), &filter)?;
+            deactivate(tx, devices, &details)
+        },
+        |result| {
+            AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+                .actor(ActorType::User, Some(&ctx.member.user_id))
+                .action(AuditAction::BulkDeactivateDevices)
+                .resource("license", &license.id)
+                .details(&serde_json::json!({
+                    "filter": filter,
+                    "deactivated": result.deactivated,
+                    "device_ids": result.devices.iter().map(|d| &d.device_id).collect::<Vec<_>>(),
+                    "impersonator": ctx.impersonator_json()
+                }))
+                .org(&path.org_id)
+                .project(&path.project_id)
+                .names(&ctx.audit_names())
+                .auth_method(&ctx.auth_method)
+                .entry()
+        },
+    )?;
+    outbox::relay(&conn, &audit_conn);
+
+    tracing::info!(
+        "Deactivated {} devices on license {} (project: {})",
+        result.deactivated,
+        license.id,
+        path.project_id
+    );
+
+    Ok(Json(result))
+}
+
+/// POST /orgs/{org_id}/projects/{project_id}/devices/deactivate-by-filter
+///
+/// Deactivates matching devices across every license in the project, e.g.
+/// all devices activated during a leaked-key window. Admins only, and the
+/// filter must narrow by time or device type.
+pub async fn deactivate_devices_by_filter(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(path): Path<OrgProjectPath>,
+    headers: HeaderMap,
+    Json(body): Json<DeactivateDevicesByFilter>,
+) -> Result<Json<DevicesDeactivated>> {
+    ctx.require_admin()?;
+    if body.filter.is_empty() {
+        return Err(AppError::BadRequest(msg::DEVICE_FILTER_REQUIRED.into()));
+    }
+    if !body.confirm {
+        return Err(AppError::BadRequest(
+            msg::DEVICE_DEACTIVATION_UNCONFIRMED.into(),
+        ));
+    }
+
+    let mut conn = state.org_db(&path.org_id).get()?;
+    let audit_conn = state.audit.get()?;
+    let project = queries::get_project_by_id(&conn, &path.project_id)?
+        .or_not_found(msg::PROJECT_NOT_FOUND)?;
+
+    let details = format!("admin bulk deactivation by user {}", ctx.member.user_id);
+    let result = outbox::with_audited_tx(
+        &mut conn,
+        |tx| {
+            let devices = queries::list_devices_by_filter(tx, &project.id, None, &body.filter)?;
+            deactivate(tx, devices, &details)
+        },
+        |result| {
+            AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+                .actor(ActorType::User, Some(&ctx.member.user_id))
+                .action(AuditAction::BulkDeactivateDevices)
+                .resource("project", &project.id)
+                .details(&serde_json::json!({
+                    "filter": body.filter,
+                    "deactivated": result.deactivated,
+                    "device_ids": result.devices.iter().map(|d| &d.device_id).collect::<Vec<_>>(),
+                    "impersonator": ctx.impersonator_json()
+                }))
+                .org(&path.org_id)
+                .project(&path.project_id)
+                .names(&ctx.audit_names().project(project.name.clone()))
+                .auth_method(&ctx.auth_method)
+                .entry()
+        },
+    )?;
+    outbox::relay(&conn, &audit_conn);
+
+    tracing::info!(
+        "Deactivated {} devices by filter (project: {})",
+        result.deactivated,
+        path.project_id
+    );
+
+    Ok(Json(result))
+}
@@ -0,0 +1,43 @@
+use axum::extract::State;
+use serde::Deserialize;
+
+use crate::db::{AppState, queries};
+use crate::error::Result;
+use crate::extractors::{Json, Path, Query};
+use crate::middleware::OrgProjectPath;
+use crate::models::{Dispute, DisputeStatus};
+use crate::pagination::{Paginated, clamp_limit, clamp_offset};
+
+#[derive(Debug, Deserialize)]
+pub struct DisputeQuery {
+    /// Only disputes with this status (e.g. `open`)
+    pub status: Option<DisputeStatus>,
+    /// Max results to return (default 50, max 100)
+    pub limit: Option<i64>,
+    /// Offset for pagination (default 0)
+    pub offset: Option<i64>,
+}
+
+/// GET /orgs/{org_id}/projects/{project_id}/disputes
+/// Payment disputes across the project, newest first.
+/// `?status=open` lists the licenses currently suspended by one.
+pub async fn list_disputes(
+    State(state): State<AppState>,
+    Path(path): Path<OrgProjectPath>,
+    Query(query): Query<DisputeQuery>,
+) -> Result<Json<Paginated<Dispute>>> {
+    let conn = state.org_db(&path.org_id).get()?;
+
+    let limit = clamp_limit(query.limit);
+    let offset = clamp_offset(query.offset);
+
+    let (disputes, total) = queries::list_project_disputes_paginated(
+        &conn,
+        &path.project_id,
+        query.status,
+        limit,
+        offset,
+    )?;
+
+    Ok(Json(Paginated::new(disputes, total, limit, offset)))
+}
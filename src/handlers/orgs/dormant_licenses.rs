@@ -0,0 +1,167 @@
+use std::collections::{HashMap, hash_map::Entry};
+
+use axum::http::HeaderMap;
+
+use crate::db::{AppState, DbPool, queries};
+use crate::email::{EmailSendConfig, EmailTrigger};
+use crate::error::Result;
+use crate::models::{ActorType, AuditAction, AuditLogNames, License, Project};
+use crate::util::AuditLogBuilder;
+use crate::webhook_payloads::LicenseDormant;
+
+/// Mark licenses dormant once their project's `dormant_after_days` pass with
+/// no activation, and tell each project's email webhook about them. Called
+/// by the background maintenance task; returns the number of licenses marked.
+pub async fn process_dormant_licenses(state: &AppState, now: i64) -> Result<usize> {
+    let audit_conn = state.audit.get()?;
+    let headers = HeaderMap::new();
+
+    let mut marked = 0;
+    for pool in state.tenant_pools() {
+        let conn = pool.get()?;
+        let licenses = queries::mark_dormant_licenses(&conn, now)?;
+        marked += licenses.len();
+
+        let mut projects: HashMap<String, Option<Project>> = HashMap::new();
+        for license in licenses {
+            if let Entry::Vacant(entry) = projects.entry(license.project_id.clone()) {
+                entry.insert(queries::get_project_by_id(&conn, &license.project_id)?);
+            }
+            let Some(project) = &projects[&license.project_id] else {
+                continue;
+            };
+            let product_name = queries::get_product_by_id(&conn, &license.product_id)?
+                .map(|product| product.name)
+                .unwrap_or_default();
+
+            // The license is already marked, so a failed write here shouldn't stop the run
+            if let Err(e) = AuditLogBuilder::for_state(&audit_conn, state, &headers)
+                .actor(ActorType::System, None)
+                .action(AuditAction::LicenseDormant)
+                .resource("license", &license.id)
+                .details(&serde_json::json!({
+                    "purchased_at": license.created_at,
+                    "dormant_after_days": project.dormant_after_days,
+                }))
+                .org(&project.org_id)
+                .project(&project.id)
+                .names(&AuditLogNames {
+                    project_name: Some(project.name.clone()),
+                    ..Default::default()
+                })
+                .save()
+            {
+                tracing::warn!(
+                    license_id = %license.id,
+                    "Failed to write dormant license audit log: {}",
+                    e
+                );
+            }
+
+            notify_dormant_license(state, &pool, project, &product_name, &license).await;
+        }
+    }
+
+    Ok(marked)
+}
+
+/// Send the `license_dormant` event and, if the project opted in, a fresh
+/// activation code for the developer to pass on.
+async fn notify_dormant_license(
+    state: &AppState,
+    pool: &DbPool,
+    project: &Project,
+    product_name: &str,
+    license: &License,
+) {
+    let dormant_since = license.dormant_since.unwrap_or_default();
+    // Nothing is sent if the secret can't be decrypted: a webhook sent unsigned
+    // would look like the project never set one
+    let webhook_secret =
+        match pool.get().map_err(Into::into).and_then(|conn| {
+            queries::decrypt_project_webhook_secret(&conn, project, &state.master_key)
+        }) {
+            Ok(secret) => secret,
+            Err(e) => {
+                tracing::error!(
+                    license_id = %license.id,
+                    "Failed to decrypt email webhook secret, not notifying: {}",
+                    e
+                );
+                return;
+            }
+        };
+    state
+        .email_service
+        .notify_license_dormant(
+            project,
+            &LicenseDormant {
+                project_id: &project.id,
+                project_name: &project.name,
+                license_id: &license.id,
+                product_name,
+                customer_id: license.customer_id.as_deref(),
+                purchased_at: license.created_at,
+                dormant_since,
+                metadata: &license.metadata,
+            },
+            webhook_secret.as_deref(),
+        )
+        .await;
+
+    // Nudges go through the webhook: it's the only way to reach a customer
+    // whose address isn't stored
+    if !project.dormant_nudge_email || project.email_webhook_url.is_none() {
+        return;
+    }
+    let Some(email_hash) = license.email_hash.as_deref() else {
+        return;
+    };
+
+    let code = pool.get().map_err(Into::into).and_then(|conn| {
+        queries::create_activation_code(&conn, &license.id, &project.license_key_prefix)
+    });
+    let code = match code {
+        Ok(code) => code,
+        Err(e) => {
+            tracing::warn!(
+                license_id = %license.id,
+                "Failed to create dormant nudge activation code: {}",
+                e
+            );
+            return;
+        }
+    };
+    let result = state
+        .email_service
+        .send_activation_code(EmailSendConfig {
+            to_email: "",
+            code: &code.code,
+            expires_in_minutes: 30,
+            product_name,
+            project_name: &project.name,
+            project,
+            license_id: &license.id,
+            purchased_at: license.created_at,
+            metadata: &license.metadata,
+            org_resend_key: None,
+            webhook_secret: webhook_secret.as_deref(),
+            org_from_email: None,
+            trigger: EmailTrigger::DormantNudge,
+        })
+        .await;
+
+    let recorded = pool.get().map_err(Into::into).and_then(|conn| {
+        queries::record_email_attempt(
+            &conn,
+            &project.id,
+            std::slice::from_ref(&license.id),
+            email_hash,
+            EmailTrigger::DormantNudge,
+            &result,
+        )
+    });
+    if let Err(e) = recorded {
+        tracing::warn!("Failed to record dormant nudge email attempt: {}", e);
+    }
+}
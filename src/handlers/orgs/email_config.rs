@@ -0,0 +1,163 @@
+//! Org email config: the org's own Resend API key and default sender, and a
+//! test send to check them before customers depend on them.
+
+use axum::{
+    extract::{Extension, State},
+    http::HeaderMap,
+};
+use serde::Serialize;
+
+use crate::db::{AppState, queries};
+use crate::email::TestEmailResult;
+use crate::error::{AppError, OptionExt, Result, msg};
+use crate::extractors::{Json, Path};
+use crate::middleware::OrgMemberContext;
+use crate::models::{ActorType, AuditAction, ServiceProvider, UpdateOrgEmailConfig, mask_secret};
+use crate::util::AuditLogBuilder;
+
+#[derive(Debug, Serialize)]
+pub struct EmailConfigResponse {
+    pub org_id: String,
+    /// Masked key (None = the system default key is used)
+    pub resend_api_key: Option<String>,
+    pub email_from: Option<String>,
+}
+
+/// GET /orgs/{org_id}/email-config
+pub async fn get_email_config(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(org_id): Path<String>,
+) -> Result<Json<EmailConfigResponse>> {
+    ctx.require_owner()?;
+
+    let conn = state.org_db(&org_id).get()?;
+    let org = queries::get_organization_by_id(&conn, &org_id)?.or_not_found(msg::ORG_NOT_FOUND)?;
+    let api_key = queries::get_org_resend_api_key(&conn, &org_id, &state.master_key)?;
+
+    Ok(Json(EmailConfigResponse {
+        org_id,
+        resend_api_key: api_key.as_deref().map(mask_secret),
+        email_from: org.email_from,
+    }))
+}
+
+/// PUT /orgs/{org_id}/email-config
+/// Set or clear the org's Resend API key and default "from" address.
+pub async fn update_email_config(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(org_id): Path<String>,
+    headers: HeaderMap,
+    Json(input): Json<UpdateOrgEmailConfig>,
+) -> Result<Json<EmailConfigResponse>> {
+    ctx.require_owner()?;
+    input.validate()?;
+
+    let conn = state.org_db(&org_id).get()?;
+    let audit_conn = state.audit.get()?;
+    let org = queries::get_organization_by_id(&conn, &org_id)?.or_not_found(msg::ORG_NOT_FOUND)?;
+
+    let mut key_updated = false;
+    if let Some(ref key_opt) = input.resend_api_key {
+        match key_opt {
+            Some(api_key) => {
+                let encrypted = queries::org_data_key(&conn, &org_id, &state.master_key)?
+                    .encrypt_private_key(&org_id, api_key.trim().as_bytes())?;
+                queries::upsert_org_service_config(
+                    &conn,
+                    &org_id,
+                    ServiceProvider::Resend,
+                    &encrypted,
+                )?;
+                key_updated = true;
+            }
+            None => {
+                key_updated =
+                    queries::delete_org_service_config(&conn, &org_id, ServiceProvider::Resend)?;
+            }
+        }
+    }
+
+    let email_from = match input.email_from {
+        Some(ref from) => {
+            let from = from.as_deref().map(str::trim);
+            queries::set_org_email_from(&conn, &org_id, from)?;
+            from.map(String::from)
+        }
+        None => org.email_from,
+    };
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.member.user_id))
+        .action(AuditAction::UpdateOrgEmailConfig)
+        .resource("org", &org_id)
+        .details(&serde_json::json!({
+            "key_updated": key_updated,
+            "key_cleared": matches!(input.resend_api_key, Some(None)),
+            "email_from": email_from,
+            "impersonator": ctx.impersonator_json()
+        }))
+        .org(&org_id)
+        .names(&ctx.audit_names().org(org.name))
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    let api_key = queries::get_org_resend_api_key(&conn, &org_id, &state.master_key)?;
+    Ok(Json(EmailConfigResponse {
+        org_id,
+        resend_api_key: api_key.as_deref().map(mask_secret),
+        email_from,
+    }))
+}
+
+/// POST /orgs/{org_id}/email-config/test
+/// Send a test email to the calling member with the org's Resend API key.
+/// Resend's answer is returned as-is: a rejected key is `sent: false` with
+/// Resend's status and message, not an error response.
+pub async fn send_test_email(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(org_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<TestEmailResult>> {
+    ctx.require_owner()?;
+
+    let (org, api_key) = {
+        let conn = state.org_db(&org_id).get()?;
+        let org =
+            queries::get_organization_by_id(&conn, &org_id)?.or_not_found(msg::ORG_NOT_FOUND)?;
+        let api_key = queries::get_org_resend_api_key(&conn, &org_id, &state.master_key)?
+            .ok_or_else(|| AppError::BadRequest(msg::ORG_RESEND_KEY_NOT_CONFIGURED.into()))?;
+        (org, api_key)
+    };
+
+    let result = state
+        .email_service
+        .send_test_email(
+            &api_key,
+            org.email_from.as_deref(),
+            &ctx.member.email,
+            &org.name,
+        )
+        .await;
+
+    let audit_conn = state.audit.get()?;
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.member.user_id))
+        .action(AuditAction::SendTestEmail)
+        .resource("org", &org_id)
+        .details(&serde_json::json!({
+            "sent": result.sent,
+            "from": result.from,
+            "status": result.status,
+            "error": result.error,
+            "impersonator": ctx.impersonator_json()
+        }))
+        .org(&org_id)
+        .names(&ctx.audit_names().org(org.name))
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    Ok(Json(result))
+}
@@ -0,0 +1,43 @@
+use axum::extract::State;
+use serde::Deserialize;
+
+use crate::db::{AppState, queries};
+use crate::error::Result;
+use crate::extractors::{Json, Path, Query};
+use crate::middleware::OrgProjectPath;
+use crate::models::{EmailLogEntry, EmailLogResult};
+use crate::pagination::{Paginated, clamp_limit, clamp_offset};
+
+#[derive(Debug, Deserialize)]
+pub struct EmailLogQuery {
+    /// Only attempts with this result (e.g. `failed`)
+    pub result: Option<EmailLogResult>,
+    /// Max results to return (default 50, max 100)
+    pub limit: Option<i64>,
+    /// Offset for pagination (default 0)
+    pub offset: Option<i64>,
+}
+
+/// GET /orgs/{org_id}/projects/{project_id}/email-log
+/// Activation code email attempts across the project, newest first.
+/// `?result=failed` lists the emails Resend rejected or never received.
+pub async fn list_email_log(
+    State(state): State<AppState>,
+    Path(path): Path<OrgProjectPath>,
+    Query(query): Query<EmailLogQuery>,
+) -> Result<Json<Paginated<EmailLogEntry>>> {
+    let conn = state.org_db(&path.org_id).get()?;
+
+    let limit = clamp_limit(query.limit);
+    let offset = clamp_offset(query.offset);
+
+    let (entries, total) = queries::list_project_email_log_paginated(
+        &conn,
+        &path.project_id,
+        query.result,
+        limit,
+        offset,
+    )?;
+
+    Ok(Json(Paginated::new(entries, total, limit, offset)))
+}
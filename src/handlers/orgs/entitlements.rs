@@ -0,0 +1,174 @@
+//! Entitlement checks: whether a customer's active licenses grant a feature,
+//! so backends can gate features server-side without handling license tokens.
+//!
+//! A license counts while it is not revoked, deleted, or expired (paused
+//! licenses keep counting, as in /validate). Features come from the
+//! license's product: its `features` list or its structured `entitlements`.
+//! Unknown customers are reported as not entitled.
+
+use std::collections::HashMap;
+
+use axum::extract::State;
+use serde::{Deserialize, Serialize};
+
+use crate::db::{AppState, queries};
+use crate::error::{AppError, Result, msg};
+use crate::extractors::{Json, Path, Query};
+use crate::middleware::OrgProjectPath;
+use crate::models::{
+    EmailAddress, Entitlement, EntitlementSuggestion, LicenseGrant, MAX_ENTITLEMENT_CUSTOMERS,
+    MAX_ENTITLEMENT_FEATURES, suggest_entitlements,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct EntitlementQuery {
+    pub customer_id: Option<String>,
+    /// Customer email (hashed before matching)
+    pub email: Option<String>,
+    pub feature: String,
+    /// Only a numeric entitlement of at least this much counts, e.g.
+    /// `feature=seats&min=3`
+    pub min: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BulkEntitlementBody {
+    pub customer_ids: Vec<String>,
+    pub features: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct BulkEntitlementResponse {
+    /// One result per customer and feature, in request order
+    pub results: Vec<Entitlement>,
+}
+
+#[derive(Serialize)]
+pub struct EntitlementSuggestionsResponse {
+    /// Products with `name_<number>` features to turn into entitlements
+    pub products: Vec<EntitlementSuggestion>,
+}
+
+/// GET /orgs/{org_id}/projects/{project_id}/entitlements
+/// Check one feature for a customer, by `customer_id` or `email`, optionally
+/// against a numeric minimum.
+pub async fn check_entitlement(
+    State(state): State<AppState>,
+    Path(path): Path<OrgProjectPath>,
+    Query(query): Query<EntitlementQuery>,
+) -> Result<Json<Entitlement>> {
+    let conn = state.org_db(&path.org_id).get()?;
+    let now = state.clock.now();
+
+    let grants = match (&query.customer_id, &query.email) {
+        (Some(customer_id), None) => queries::get_license_grants_by_customer_ids(
+            &conn,
+            &path.project_id,
+            std::slice::from_ref(customer_id),
+            now,
+        )?,
+        (None, Some(email)) => {
+            let email = EmailAddress::parse(email)?;
+            state.email_hasher.find_by_hash(
+                &email,
+                "entitlement_check",
+                |hash| {
+                    queries::get_license_grants_by_email_hash(&conn, &path.project_id, hash, now)
+                },
+                |grants| !grants.is_empty(),
+            )?
+        }
+        _ => {
+            return Err(AppError::BadRequest(
+                msg::ENTITLEMENT_CUSTOMER_REQUIRED.into(),
+            ));
+        }
+    };
+
+    Ok(Json(Entitlement::resolve(
+        query.customer_id.as_deref(),
+        &query.feature,
+        query.min,
+        &grants,
+    )))
+}
+
+/// POST /orgs/{org_id}/projects/{project_id}/entitlements/check
+/// Check every feature for every customer with a single query, for batch jobs.
+pub async fn check_entitlements_bulk(
+    State(state): State<AppState>,
+    Path(path): Path<OrgProjectPath>,
+    Json(body): Json<BulkEntitlementBody>,
+) -> Result<Json<BulkEntitlementResponse>> {
+    if body.customer_ids.is_empty() || body.features.is_empty() {
+        return Err(AppError::BadRequest(msg::ENTITLEMENT_CHECK_EMPTY.into()));
+    }
+    if body.customer_ids.len() > MAX_ENTITLEMENT_CUSTOMERS
+        || body.features.len() > MAX_ENTITLEMENT_FEATURES
+    {
+        return Err(AppError::BadRequest(
+            msg::ENTITLEMENT_CHECK_TOO_LARGE.into(),
+        ));
+    }
+
+    let conn = state.org_db(&path.org_id).get()?;
+    let grants = queries::get_license_grants_by_customer_ids(
+        &conn,
+        &path.project_id,
+        &body.customer_ids,
+        state.clock.now(),
+    )?;
+
+    let mut by_customer: HashMap<&str, Vec<&LicenseGrant>> = HashMap::new();
+    for grant in &grants {
+        if let Some(customer_id) = grant.customer_id.as_deref() {
+            by_customer.entry(customer_id).or_default().push(grant);
+        }
+    }
+
+    let results = body
+        .customer_ids
+        .iter()
+        .flat_map(|customer_id| {
+            let grants = by_customer
+                .get(customer_id.as_str())
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            body.features.iter().map(move |feature| {
+                Entitlement::resolve(
+                    Some(customer_id.as_str()),
+                    feature,
+                    None,
+                    grants.iter().copied(),
+                )
+            })
+        })
+        .collect();
+
+    Ok(Json(BulkEntitlementResponse { results }))
+}
+
+/// GET /orgs/{org_id}/projects/{project_id}/entitlements/suggestions
+/// Propose entitlements from feature strings like `team_5`, for an admin to
+/// review and apply with a product update. Nothing is changed.
+pub async fn suggest_product_entitlements(
+    State(state): State<AppState>,
+    Path(path): Path<OrgProjectPath>,
+) -> Result<Json<EntitlementSuggestionsResponse>> {
+    let conn = state.org_db(&path.org_id).get()?;
+    let products = queries::list_products_for_project(&conn, &path.project_id)?
+        .into_iter()
+        .filter_map(|product| {
+            let (entitlements, from_features) =
+                suggest_entitlements(&product.features, &product.entitlements);
+            (!entitlements.is_empty()).then_some(EntitlementSuggestion {
+                product_id: product.id,
+                product_name: product.name,
+                entitlements,
+                from_features,
+            })
+        })
+        .collect();
+
+    Ok(Json(EntitlementSuggestionsResponse { products }))
+}
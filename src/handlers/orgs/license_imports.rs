@@ -0,0 +1,91 @@
+//! Importing license keys a payment provider issued before the project moved
+//! to Paycheck (see [`crate::license_import`]).
+
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Extension, State},
+    http::HeaderMap,
+};
+use serde::Deserialize;
+
+use crate::db::{AppState, queries};
+use crate::error::{AppError, OptionExt, Result, msg};
+use crate::extractors::{Json, Path};
+use crate::license_import::{self, ImportResult};
+use crate::middleware::{OrgMemberContext, OrgProjectPath};
+use crate::models::{ActorType, AuditAction};
+use crate::payments::LemonSqueezyClient;
+use crate::reconcile::LEMONSQUEEZY_PACE;
+use crate::util::AuditLogBuilder;
+
+#[derive(Debug, Deserialize)]
+pub struct ImportLemonSqueezyKeys {
+    /// LemonSqueezy product ID -> ID of the project product its keys become
+    pub products: HashMap<String, String>,
+}
+
+/// POST /orgs/{org_id}/projects/{project_id}/license-imports/lemonsqueezy
+/// Create a license for each of the org's LemonSqueezy license keys whose
+/// product is mapped, skipping keys imported before. Runs to completion,
+/// paced to LemonSqueezy's rate limit.
+pub async fn import_lemonsqueezy_keys(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(path): Path<OrgProjectPath>,
+    headers: HeaderMap,
+    Json(input): Json<ImportLemonSqueezyKeys>,
+) -> Result<Json<ImportResult>> {
+    if !ctx.can_write_project() {
+        return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
+    }
+
+    let (project, client) = {
+        let conn = state.org_db(&path.org_id).get()?;
+        let project = queries::get_project_by_id(&conn, &path.project_id)?
+            .or_not_found(msg::PROJECT_NOT_FOUND)?;
+        let config = queries::get_org_ls_config(&conn, &path.org_id, &state.master_key)?
+            .ok_or_else(|| AppError::BadRequest(msg::LS_NOT_CONFIGURED.into()))?;
+        (project, LemonSqueezyClient::new(&config))
+    };
+
+    let result = license_import::import_lemonsqueezy(
+        &state,
+        &path.org_id,
+        &project.id,
+        &client,
+        &input.products,
+        LEMONSQUEEZY_PACE,
+    )
+    .await?;
+
+    let audit_conn = state.audit.get()?;
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.member.user_id))
+        .action(AuditAction::ImportLicenses)
+        .resource("project", &project.id)
+        .details(&serde_json::json!({
+            "provider": license_import::LEMONSQUEEZY,
+            "products": input.products,
+            "fetched": result.fetched,
+            "imported": result.imported,
+            "already_imported": result.already_imported,
+            "unmapped": result.unmapped,
+            "disabled": result.disabled,
+            "impersonator": ctx.impersonator_json()
+        }))
+        .org(&path.org_id)
+        .project(&project.id)
+        .names(&ctx.audit_names().resource(project.name.clone()))
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    tracing::info!(
+        "Imported {} LemonSqueezy license key(s) into project {} ({} already imported)",
+        result.imported,
+        project.id,
+        result.already_imported
+    );
+
+    Ok(Json(result))
+}
@@ -0,0 +1,204 @@
+//! Seat management for team licenses.
+//!
+//! A license with `seats` set can be assigned to that many email addresses.
+//! Each seat holder requests activation codes with their own email, and the
+//! product's device_limit applies per seat.
+
+use axum::{
+    extract::{Extension, State},
+    http::HeaderMap,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::db::{AppState, queries};
+use crate::error::{AppError, OptionExt, Result, msg};
+use crate::extractors::{Json, Path};
+use crate::middleware::OrgMemberContext;
+use crate::models::{ActorType, AuditAction, EmailAddress, License, LicenseSeat};
+use crate::util::AuditLogBuilder;
+
+use super::LicensePath;
+
+#[derive(Deserialize)]
+pub struct LicenseSeatPath {
+    pub org_id: String,
+    pub project_id: String,
+    pub license_id: String,
+    pub seat_id: String,
+}
+
+#[derive(Serialize)]
+pub struct LicenseSeatWithDevices {
+    #[serde(flatten)]
+    pub seat: LicenseSeat,
+    /// Devices activated under this seat
+    pub device_count: i32,
+}
+
+#[derive(Serialize)]
+pub struct LicenseSeatsResponse {
+    /// Total seats on the license
+    pub seats: i32,
+    /// Currently assigned seats, oldest first
+    pub items: Vec<LicenseSeatWithDevices>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AssignSeatBody {
+    /// Seat holder's email (hashed, never stored)
+    pub email: String,
+}
+
+#[derive(Serialize)]
+pub struct RemoveSeatResponse {
+    pub removed: bool,
+    pub seat_id: String,
+    /// Devices deactivated because they were activated under this seat
+    pub devices_deactivated: usize,
+}
+
+/// Load a license, verifying it belongs to a product in this project.
+fn get_project_license(
+    conn: &rusqlite::Connection,
+    project_id: &str,
+    license_id: &str,
+) -> Result<License> {
+    let license =
+        queries::get_license_by_id(conn, license_id)?.or_not_found(msg::LICENSE_NOT_FOUND)?;
+
+    let product = queries::get_product_by_id(conn, &license.product_id)?
+        .or_not_found(msg::LICENSE_NOT_FOUND)?;
+
+    if product.project_id != project_id {
+        return Err(AppError::NotFound(msg::LICENSE_NOT_FOUND.into()));
+    }
+
+    Ok(license)
+}
+
+/// GET /orgs/{org_id}/projects/{project_id}/licenses/{license_id}/seats
+pub async fn list_license_seats(
+    State(state): State<AppState>,
+    Path(mut path): Path<LicensePath>,
+) -> Result<Json<LicenseSeatsResponse>> {
+    let conn = state.org_db(&path.org_id).get()?;
+    path.license_id = queries::resolve_admin_license_id(&conn, &path.project_id, &path.license_id)?;
+
+    let license = get_project_license(&conn, &path.project_id, &path.license_id)?;
+    let seats = license
+        .seats
+        .ok_or_else(|| AppError::BadRequest(msg::LICENSE_NOT_SEAT_BASED.into()))?;
+
+    let items = queries::list_license_seats(&conn, &license.id)?
+        .into_iter()
+        .map(|seat| {
+            let device_count = queries::count_devices_for_seat(&conn, &seat.id)?;
+            Ok(LicenseSeatWithDevices { seat, device_count })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Json(LicenseSeatsResponse { seats, items }))
+}
+
+/// POST /orgs/{org_id}/projects/{project_id}/licenses/{license_id}/seats
+/// Assign a seat to an email. The seat holder can then request activation codes.
+pub async fn assign_license_seat(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(mut path): Path<LicensePath>,
+    headers: HeaderMap,
+    Json(body): Json<AssignSeatBody>,
+) -> Result<Json<LicenseSeat>> {
+    if !ctx.can_write_project() {
+        return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
+    }
+
+    let email = EmailAddress::parse(&body.email)?;
+
+    let conn = state.org_db(&path.org_id).get()?;
+    path.license_id = queries::resolve_admin_license_id(&conn, &path.project_id, &path.license_id)?;
+    let audit_conn = state.audit.get()?;
+
+    let license = get_project_license(&conn, &path.project_id, &path.license_id)?;
+    if license.revoked {
+        return Err(AppError::BadRequest(msg::LICENSE_REVOKED.into()));
+    }
+
+    let project = queries::get_project_by_id(&conn, &path.project_id)?
+        .or_not_found(msg::PROJECT_NOT_FOUND)?;
+
+    let seat_email_hash = state.email_hasher.hash(&email);
+    let seat = queries::assign_license_seat(&conn, &license, &seat_email_hash)?;
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.member.user_id))
+        .action(AuditAction::AssignLicenseSeat)
+        .resource("license", &license.id)
+        .details(&serde_json::json!({
+            "seat_id": seat.id,
+            "seat_email_hash": seat.seat_email_hash,
+            "impersonator": ctx.impersonator_json()
+        }))
+        .org(&path.org_id)
+        .project(&path.project_id)
+        .names(&ctx.audit_names().project(project.name.clone()))
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    Ok(Json(seat))
+}
+
+/// DELETE /orgs/{org_id}/projects/{project_id}/licenses/{license_id}/seats/{seat_id}
+/// Unassign a seat. Devices activated under it are deactivated and their tokens revoked.
+pub async fn remove_license_seat(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(mut path): Path<LicenseSeatPath>,
+    headers: HeaderMap,
+) -> Result<Json<RemoveSeatResponse>> {
+    if !ctx.can_write_project() {
+        return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
+    }
+
+    let mut conn = state.org_db(&path.org_id).get()?;
+    path.license_id = queries::resolve_admin_license_id(&conn, &path.project_id, &path.license_id)?;
+    let audit_conn = state.audit.get()?;
+
+    let license = get_project_license(&conn, &path.project_id, &path.license_id)?;
+    let seat = queries::get_active_license_seat(&conn, &license.id, &path.seat_id)?
+        .or_not_found(msg::SEAT_NOT_FOUND)?;
+
+    let project = queries::get_project_by_id(&conn, &path.project_id)?
+        .or_not_found(msg::PROJECT_NOT_FOUND)?;
+
+    let details = format!("seat removed by user {}", ctx.member.user_id);
+    let devices_deactivated = queries::remove_license_seat(&mut conn, &seat, Some(&details))?;
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.member.user_id))
+        .action(AuditAction::RemoveLicenseSeat)
+        .resource("license", &license.id)
+        .details(&serde_json::json!({
+            "seat_id": seat.id,
+            "devices_deactivated": devices_deactivated,
+            "impersonator": ctx.impersonator_json()
+        }))
+        .org(&path.org_id)
+        .project(&path.project_id)
+        .names(&ctx.audit_names().project(project.name.clone()))
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    tracing::info!(
+        "Seat {} removed from license {} ({} device(s) deactivated)",
+        seat.id,
+        license.id,
+        devices_deactivated
+    );
+
+    Ok(Json(RemoveSeatResponse {
+        removed: true,
+        seat_id: seat.id,
+        devices_deactivated,
+    }))
+}
@@ -0,0 +1,294 @@
+//! License tags: free-form labels for grouping licenses ("beta-cohort",
+//! "enterprise-pilot") and filtering the license list by them.
+//!
+//! Tags are normalized to lowercase and must be 1-40 characters of `a-z`,
+//! `0-9`, `-` and `_`. A license carries at most 20.
+
+use axum::{
+    extract::{Extension, State},
+    http::HeaderMap,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::db::{AppState, queries};
+use crate::error::{AppError, OptionExt, Result, msg};
+use crate::extractors::{Json, Path};
+use crate::middleware::{OrgMemberContext, OrgProjectPath};
+use crate::models::{
+    ActorType, AuditAction, EmailAddress, License, LicenseFilter, LicenseTagUsage,
+    MAX_TAGS_PER_LICENSE, validate_tag,
+};
+use crate::util::AuditLogBuilder;
+
+use super::LicensePath;
+
+#[derive(Debug, Deserialize)]
+pub struct LicenseTagsBody {
+    pub tags: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct LicenseTagsResponse {
+    pub license_id: String,
+    /// All tags on the license after the change, sorted
+    pub tags: Vec<String>,
+}
+
+/// Which licenses a bulk tag applies to. Every field that is set must match.
+#[derive(Debug, Default, Deserialize)]
+pub struct BulkTagFilter {
+    pub product_id: Option<String>,
+    pub customer_id: Option<String>,
+    /// Customer email (hashed before matching)
+    pub email: Option<String>,
+    /// Licenses that already carry this tag
+    pub tag: Option<String>,
+    pub revoked: Option<bool>,
+    /// Created at or after this Unix timestamp
+    pub created_after: Option<i64>,
+    /// Created before this Unix timestamp
+    pub created_before: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BulkTagBody {
+    pub tags: Vec<String>,
+    pub filter: BulkTagFilter,
+}
+
+#[derive(Serialize)]
+pub struct BulkTagResponse {
+    /// Licenses matching the filter
+    pub matched: usize,
+    /// License/tag pairs newly added (existing tags and full licenses are skipped)
+    pub added: usize,
+}
+
+/// Lowercase, validate and dedupe the tags in a request body.
+fn normalize_tags(tags: Vec<String>) -> Result<Vec<String>> {
+    if tags.is_empty() {
+        return Err(AppError::BadRequest(msg::TAGS_EMPTY.into()));
+    }
+    let mut normalized = tags
+        .into_iter()
+        .map(|tag| {
+            let tag = tag.trim().to_lowercase();
+            validate_tag(&tag)?;
+            Ok(tag)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    normalized.sort();
+    normalized.dedup();
+    if normalized.len() > MAX_TAGS_PER_LICENSE {
+        return Err(AppError::BadRequest(msg::TOO_MANY_TAGS.into()));
+    }
+    Ok(normalized)
+}
+
+/// Load a license, verifying it belongs to a product in this project.
+fn get_project_license(
+    conn: &rusqlite::Connection,
+    project_id: &str,
+    license_id: &str,
+) -> Result<License> {
+    let license =
+        queries::get_license_by_id(conn, license_id)?.or_not_found(msg::LICENSE_NOT_FOUND)?;
+
+    let product = queries::get_product_by_id(conn, &license.product_id)?
+        .or_not_found(msg::LICENSE_NOT_FOUND)?;
+
+    if product.project_id != project_id {
+        return Err(AppError::NotFound(msg::LICENSE_NOT_FOUND.into()));
+    }
+
+    Ok(license)
+}
+
+/// POST /orgs/{org_id}/projects/{project_id}/licenses/{license_id}/tags
+/// Add tags to a license. Tags it already has are ignored.
+pub async fn add_license_tags(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(mut path): Path<LicensePath>,
+    headers: HeaderMap,
+    Json(body): Json<LicenseTagsBody>,
+) -> Result<Json<LicenseTagsResponse>> {
+    if !ctx.can_write_project() {
+        return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
+    }
+
+    let tags = normalize_tags(body.tags)?;
+
+    let conn = state.org_db(&path.org_id).get()?;
+    path.license_id = queries::resolve_admin_license_id(&conn, &path.project_id, &path.license_id)?;
+    let audit_conn = state.audit.get()?;
+
+    let license = get_project_license(&conn, &path.project_id, &path.license_id)?;
+    let project = queries::get_project_by_id(&conn, &path.project_id)?
+        .or_not_found(msg::PROJECT_NOT_FOUND)?;
+
+    let existing = queries::list_license_tags(&conn, &license.id)?;
+    let new_count = tags.iter().filter(|tag| !existing.contains(tag)).count();
+    if existing.len() + new_count > MAX_TAGS_PER_LICENSE {
+        return Err(AppError::BadRequest(msg::TOO_MANY_TAGS.into()));
+    }
+
+    queries::add_license_tags(&conn, &license.id, &tags)?;
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.member.user_id))
+        .action(AuditAction::AddLicenseTags)
+        .resource("license", &license.id)
+        .details(&serde_json::json!({
+            "tags": tags,
+            "impersonator": ctx.impersonator_json()
+        }))
+        .org(&path.org_id)
+        .project(&path.project_id)
+        .names(&ctx.audit_names().project(project.name.clone()))
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    let tags = queries::list_license_tags(&conn, &license.id)?;
+    Ok(Json(LicenseTagsResponse {
+        license_id: license.id,
+        tags,
+    }))
+}
+
+/// DELETE /orgs/{org_id}/projects/{project_id}/licenses/{license_id}/tags
+/// Remove tags from a license. Tags it doesn't have are ignored.
+pub async fn remove_license_tags(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(mut path): Path<LicensePath>,
+    headers: HeaderMap,
+    Json(body): Json<LicenseTagsBody>,
+) -> Result<Json<LicenseTagsResponse>> {
+    if !ctx.can_write_project() {
+        return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
+    }
+
+    let tags = normalize_tags(body.tags)?;
+
+    let conn = state.org_db(&path.org_id).get()?;
+    path.license_id = queries::resolve_admin_license_id(&conn, &path.project_id, &path.license_id)?;
+    let audit_conn = state.audit.get()?;
+
+    let license = get_project_license(&conn, &path.project_id, &path.license_id)?;
+    let project = queries::get_project_by_id(&conn, &path.project_id)?
+        .or_not_found(msg::PROJECT_NOT_FOUND)?;
+
+    let removed = queries::remove_license_tags(&conn, &license.id, &tags)?;
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.member.user_id))
+        .action(AuditAction::RemoveLicenseTags)
+        .resource("license", &license.id)
+        .details(&serde_json::json!({
+            "tags": tags,
+            "removed": removed,
+            "impersonator": ctx.impersonator_json()
+        }))
+        .org(&path.org_id)
+        .project(&path.project_id)
+        .names(&ctx.audit_names().project(project.name.clone()))
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    let tags = queries::list_license_tags(&conn, &license.id)?;
+    Ok(Json(LicenseTagsResponse {
+        license_id: license.id,
+        tags,
+    }))
+}
+
+/// GET /orgs/{org_id}/projects/{project_id}/licenses/tags
+/// Tags used in the project with license counts, most used first (for autocomplete).
+pub async fn list_project_license_tags(
+    State(state): State<AppState>,
+    Path(path): Path<OrgProjectPath>,
+) -> Result<Json<Vec<LicenseTagUsage>>> {
+    let conn = state.org_db(&path.org_id).get()?;
+    let tags = queries::list_project_license_tags(&conn, &path.project_id)?;
+    Ok(Json(tags))
+}
+
+/// POST /orgs/{org_id}/projects/{project_id}/licenses/tags/bulk
+/// Add tags to every license matching a filter (e.g. all licenses of a
+/// product bought in a date range). Licenses already at the tag limit are skipped.
+pub async fn bulk_tag_licenses(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(path): Path<OrgProjectPath>,
+    headers: HeaderMap,
+    Json(body): Json<BulkTagBody>,
+) -> Result<Json<BulkTagResponse>> {
+    if !ctx.can_write_project() {
+        return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
+    }
+
+    let tags = normalize_tags(body.tags)?;
+    let email = body
+        .filter
+        .email
+        .as_deref()
+        .map(EmailAddress::parse)
+        .transpose()?;
+    let filter = LicenseFilter {
+        product_id: body.filter.product_id,
+        customer_id: body.filter.customer_id,
+        email_hash: email.map(|e| state.email_hasher.hash(&e)),
+        tag: body.filter.tag.map(|t| t.trim().to_lowercase()),
+        revoked: body.filter.revoked,
+        created_after: body.filter.created_after,
+        created_before: body.filter.created_before,
+    };
+    // Tagging a whole project by accident is hard to undo
+    if filter.is_empty() {
+        return Err(AppError::BadRequest(msg::TAG_FILTER_REQUIRED.into()));
+    }
+
+    let mut conn = state.org_db(&path.org_id).get()?;
+    let audit_conn = state.audit.get()?;
+
+    let project = queries::get_project_by_id(&conn, &path.project_id)?
+        .or_not_found(msg::PROJECT_NOT_FOUND)?;
+
+    let (matched, added) =
+        queries::bulk_add_license_tags(&mut conn, &path.project_id, &filter, &tags)?;
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.member.user_id))
+        .action(AuditAction::BulkTagLicenses)
+        .resource("project", &path.project_id)
+        .details(&serde_json::json!({
+            "tags": tags,
+            "filter": {
+                "product_id": filter.product_id,
+                "customer_id": filter.customer_id,
+                "email_hash": filter.email_hash,
+                "tag": filter.tag,
+                "revoked": filter.revoked,
+                "created_after": filter.created_after,
+                "created_before": filter.created_before,
+            },
+            "matched": matched,
+            "added": added,
+            "impersonator": ctx.impersonator_json()
+        }))
+        .org(&path.org_id)
+        .project(&path.project_id)
+        .names(&ctx.audit_names().project(project.name.clone()))
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    tracing::info!(
+        "Bulk tagged {} license(s) in project {} ({} tag(s) added)",
+        matched,
+        path.project_id,
+        added
+    );
+
+    Ok(Json(BulkTagResponse { matched, added }))
+}
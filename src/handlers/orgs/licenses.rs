@@ -1,17 +1,26 @@
 use axum::{
     extract::{Extension, Query, State},
-    http::HeaderMap,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
 };
 use serde::{Deserialize, Serialize};
 
-use crate::db::{AppState, queries};
+use crate::bulk_jobs::{self, JobActor};
+use crate::db::{AppState, outbox, queries};
 use crate::error::{AppError, OptionExt, Result, msg};
 use crate::extractors::{Json, Path, RestoreRequest};
 use crate::middleware::OrgMemberContext;
-use crate::models::{ActorType, AuditAction, CreateLicense, Device, LicenseWithProduct};
-use crate::pagination::Paginated;
+use crate::models::{
+    ActorType, AuditAction, BulkLicenseParams, CreateLicense, Device, Dispute, EmailAddress,
+    EmailLogEntry, LicenseUpdateRenewal, LicenseUpgrade, LicenseWithProduct, OrgLimitName,
+    QuotaWarning, RECENT_EMAILS_PER_LICENSE, RevokeLicense, validate_seat_count,
+};
+use crate::pagination::{Paginated, clamp_limit, clamp_offset};
+use crate::quota;
 use crate::util::{AuditLogBuilder, LicenseExpirations};
 
+use super::BulkJobResponse;
+
 #[derive(serde::Deserialize)]
 pub struct LicensePath {
     pub org_id: String,
@@ -37,6 +46,22 @@ pub struct LicenseWithDevices {
     pub active_device_count: i32,
     /// Total device count regardless of activity
     pub total_device_count: i32,
+    /// Whether the provider subscription is currently paused
+    pub paused: bool,
+    /// Whole days spent paused (completed pauses plus any pause in progress)
+    pub paused_days: i64,
+    /// Upgrade this license was bought through
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upgraded_from: Option<LicenseUpgrade>,
+    /// Upgrade that replaced this license
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upgraded_to: Option<LicenseUpgrade>,
+    /// Updates-only renewals applied to the license, oldest first
+    pub update_renewals: Vec<LicenseUpdateRenewal>,
+    /// Latest activation code email attempts, newest first
+    pub recent_emails: Vec<EmailLogEntry>,
+    /// Payment disputes against the license's purchase, newest first
+    pub disputes: Vec<Dispute>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -47,6 +72,16 @@ pub struct ListLicensesQuery {
     pub payment_provider_order_id: Option<String>,
     /// Filter by developer-managed customer ID (for linking to your own user system)
     pub customer_id: Option<String>,
+    /// Filter by reference number, e.g. PRO-000123 (for support lookups by phone)
+    pub reference: Option<String>,
+    /// Filter by license tag
+    pub tag: Option<String>,
+    /// Only licenses throttled for exceeding the project's hourly validation limit
+    #[serde(default)]
+    pub flagged: bool,
+    /// Only licenses marked dormant (bought but never activated)
+    #[serde(default)]
+    pub dormant: bool,
     /// Max results to return (default 50, max 100)
     pub limit: Option<i64>,
     /// Offset for pagination (default 0)
@@ -55,36 +90,43 @@ pub struct ListLicensesQuery {
 
 impl ListLicensesQuery {
     fn limit(&self) -> i64 {
-        self.limit.unwrap_or(50).clamp(1, 100)
+        clamp_limit(self.limit)
     }
 
     fn offset(&self) -> i64 {
-        self.offset.unwrap_or(0).max(0)
+        clamp_offset(self.offset)
     }
 }
 
 /// GET /orgs/{org_id}/projects/{project_id}/licenses
-/// List licenses for a project with pagination, optionally filtered by email, payment order ID, or customer ID.
+/// List licenses for a project with pagination, optionally filtered by email, payment order ID, customer ID, reference number, tag, abuse flag, or dormancy.
 /// When filtering, returns ALL licenses including expired/revoked (for support lookups).
 pub async fn list_licenses(
     State(state): State<AppState>,
     Path(path): Path<crate::middleware::OrgProjectPath>,
     Query(query): Query<ListLicensesQuery>,
 ) -> Result<Json<Paginated<LicenseWithProduct>>> {
-    let conn = state.db.get()?;
+    let conn = state.org_db(&path.org_id).get()?;
 
     let limit = query.limit();
     let offset = query.offset();
 
-    let (licenses, total) = if let Some(email) = query.email {
+    let (mut licenses, total) = if let Some(email) = query.email {
         // Support lookup by email - includes expired/revoked
-        let email_hash = state.email_hasher.hash(&email);
-        queries::get_all_licenses_by_email_hash_for_admin_paginated(
-            &conn,
-            &path.project_id,
-            &email_hash,
-            limit,
-            offset,
+        let email = EmailAddress::parse(&email)?;
+        state.email_hasher.find_by_hash(
+            &email,
+            "license_list",
+            |hash| {
+                queries::get_all_licenses_by_email_hash_for_admin_paginated(
+                    &conn,
+                    &path.project_id,
+                    hash,
+                    limit,
+                    offset,
+                )
+            },
+            |(_, total)| *total > 0,
         )?
     } else if let Some(ref order_id) = query.payment_provider_order_id {
         // Support lookup by payment provider order ID (e.g., from receipt) - includes expired/revoked
@@ -104,10 +146,29 @@ pub async fn list_licenses(
             limit,
             offset,
         )?
+    } else if let Some(ref reference) = query.reference {
+        // Support lookup by reference number - includes expired/revoked
+        queries::get_licenses_by_reference_number_paginated(
+            &conn,
+            &path.project_id,
+            reference,
+            limit,
+            offset,
+        )?
+    } else if let Some(ref tag) = query.tag {
+        // Ad-hoc groups (e.g. a beta cohort) - includes expired/revoked
+        queries::list_licenses_by_tag_paginated(&conn, &path.project_id, tag, limit, offset)?
+    } else if query.flagged {
+        // Suspected cracked/shared licenses, most recently throttled first
+        queries::list_flagged_licenses_paginated(&conn, &path.project_id, limit, offset)?
+    } else if query.dormant {
+        // Never activated within the project's dormant_after_days, most recently marked first
+        queries::list_dormant_licenses_paginated(&conn, &path.project_id, limit, offset)?
     } else {
         // Default: list all licenses for project
         queries::list_licenses_for_project_paginated(&conn, &path.project_id, limit, offset)?
     };
+    queries::attach_license_tags(&conn, &mut licenses)?;
 
     Ok(Json(Paginated::new(licenses, total, limit, offset)))
 }
@@ -132,9 +193,22 @@ pub struct CreateLicenseBody {
     /// If not specified, uses product's updates_exp_days
     #[serde(default)]
     pub updates_exp_days: Option<Option<i32>>,
-    /// Number of licenses to create (default: 1, max: 100)
+    /// Number of licenses to create (default: 1, max: 10,000)
+    /// Above 100 they're created by a background job: see `activation_codes`
     #[serde(default = "default_count")]
     pub count: i32,
+    /// Seats per license for team licenses (e.g. purchase quantity)
+    /// If not specified, uses product's seat_count
+    #[serde(default)]
+    pub seats: Option<i32>,
+    /// Issue trial licenses: a later purchase by the same email or
+    /// customer_id is linked to the trial as its conversion
+    #[serde(default)]
+    pub is_trial: bool,
+    /// For counts above 100: offer the finished job's licenses as a CSV of
+    /// activation codes rather than a list of license IDs
+    #[serde(default)]
+    pub activation_codes: bool,
 }
 
 fn default_count() -> i32 {
@@ -158,22 +232,24 @@ pub struct CreatedLicenseWithDetails {
 /// POST /orgs/{org_id}/projects/{project_id}/licenses
 /// Create one or more licenses directly (for bulk/trial licenses)
 /// Useful for gift cards, bulk purchases, or trial generation
+///
+/// Up to 100 licenses are created within the request. Larger counts start a
+/// background job instead: the response is 202 with the job, whose progress
+/// is at `GET .../bulk-jobs/{job_id}`.
 pub async fn create_license(
     State(state): State<AppState>,
     Extension(ctx): Extension<OrgMemberContext>,
     Path(path): Path<crate::middleware::OrgProjectPath>,
     headers: HeaderMap,
     Json(body): Json<CreateLicenseBody>,
-) -> Result<Json<CreateLicenseResponse>> {
+) -> Result<(Option<QuotaWarning>, Response)> {
     if !ctx.can_write_project() {
         return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
     }
 
     // Validate count
-    if body.count < 1 || body.count > 100 {
-        return Err(AppError::BadRequest(
-            "Count must be between 1 and 100".into(),
-        ));
+    if body.count < 1 || body.count > bulk_jobs::MAX_COUNT {
+        return Err(AppError::BadRequest(msg::LICENSE_COUNT_INVALID.into()));
     }
 
     // Validate expiration days are non-negative (prevents creating already-expired licenses)
@@ -191,8 +267,10 @@ pub async fn create_license(
             "updates_exp_days must be non-negative".into(),
         ));
     }
+    validate_seat_count(body.seats)?;
+    let email = body.email.as_deref().map(EmailAddress::parse).transpose()?;
 
-    let conn = state.db.get()?;
+    let mut conn = state.org_db(&path.org_id).get()?;
     let audit_conn = state.audit.get()?;
 
     // Verify product exists and belongs to this project
@@ -209,65 +287,177 @@ pub async fn create_license(
     let project = queries::get_project_by_id(&conn, &path.project_id)?
         .or_not_found(msg::PROJECT_NOT_FOUND)?;
 
+    let quota = quota::check(
+        &conn,
+        &path.org_id,
+        OrgLimitName::LicensesPerMonth,
+        None,
+        body.count as i64,
+        state.clock.now(),
+    )?;
+
     // Compute email hash if email provided
-    let email_hash = body.email.as_ref().map(|e| state.email_hasher.hash(e));
+    let email_hash = email.as_ref().map(|e| state.email_hasher.hash(e));
 
     // Compute expirations (use override if provided, otherwise use product defaults)
     let now = chrono::Utc::now().timestamp();
     let license_exp_days = body.license_exp_days.unwrap_or(product.license_exp_days);
     let updates_exp_days = body.updates_exp_days.unwrap_or(product.updates_exp_days);
     let exps = LicenseExpirations::from_days(license_exp_days, updates_exp_days, now);
-
-    let mut created_licenses = Vec::with_capacity(body.count as usize);
-
-    for _ in 0..body.count {
-        let license = queries::create_license(
-            &conn,
-            &project.id,
-            &body.product_id,
-            &CreateLicense {
-                email_hash: email_hash.clone(),
-                customer_id: body.customer_id.clone(),
-                expires_at: exps.license_exp,
-                updates_expires_at: exps.updates_exp,
-                payment_provider: None,
-                payment_provider_customer_id: None,
-                payment_provider_subscription_id: None,
-                payment_provider_order_id: None,
+    let seats = body.seats.or(product.seat_count);
+
+    let details = serde_json::json!({
+        "product_id": body.product_id,
+        "expires_at": exps.license_exp,
+        "has_email": email_hash.is_some(),
+        "seats": seats,
+        "is_trial": body.is_trial,
+        "impersonator": ctx.impersonator_json()
+    });
+
+    if body.count > bulk_jobs::SYNC_MAX_COUNT {
+        let params = BulkLicenseParams {
+            email_hash,
+            customer_id: body.customer_id.clone(),
+            expires_at: exps.license_exp,
+            updates_expires_at: exps.updates_exp,
+            seats,
+            is_trial: body.is_trial,
+        };
+        let names = ctx.audit_names().project(project.name.clone());
+
+        // The job and its audit entry commit together
+        let job = outbox::with_audited_tx(
+            &mut conn,
+            |tx| {
+                queries::create_bulk_job(
+                    tx,
+                    &project.id,
+                    &product.id,
+                    body.count as i64,
+                    body.activation_codes,
+                    &params,
+                    Some(&ctx.member.user_id),
+                )
+            },
+            |job| {
+                AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+                    .actor(ActorType::User, Some(&ctx.member.user_id))
+                    .action(AuditAction::StartBulkLicenseJob)
+                    .resource("bulk_job", &job.id)
+                    .details(&serde_json::json!({
+                        "count": body.count,
+                        "activation_codes": body.activation_codes,
+                        "license": details,
+                    }))
+                    .org(&path.org_id)
+                    .project(&path.project_id)
+                    .names(&names)
+                    .auth_method(&ctx.auth_method)
+                    .entry()
             },
         )?;
+        outbox::relay(&conn, &audit_conn);
+        if let Some(ref warning) = quota {
+            quota::audit_crossing(
+                AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+                    .actor(ActorType::User, Some(&ctx.member.user_id))
+                    .org(&path.org_id)
+                    .project(&path.project_id)
+                    .names(&names)
+                    .auth_method(&ctx.auth_method),
+                &path.org_id,
+                warning,
+            );
+        }
 
-        // Generate activation code for immediate use
-        let code =
-            queries::create_activation_code(&conn, &license.id, &project.license_key_prefix)?;
-
-        created_licenses.push(CreatedLicenseWithDetails {
-            license: LicenseWithProduct {
-                license,
-                product_name: product.name.clone(),
-            },
-            activation_code: code.code,
-            activation_code_expires_at: code.expires_at,
-        });
+        tracing::info!(
+            "Started bulk license job {} for {} license(s) of product {} (project: {})",
+            job.id,
+            body.count,
+            body.product_id,
+            path.project_id
+        );
 
-        // Audit log for each license
-        AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
-            .actor(ActorType::User, Some(&ctx.member.user_id))
-            .action(AuditAction::CreateLicense)
-            .resource("license", &created_licenses.last().unwrap().license.license.id)
-            .details(&serde_json::json!({
-                "product_id": body.product_id,
-                "expires_at": exps.license_exp,
-                "has_email": email_hash.is_some(),
-                "impersonator": ctx.impersonator_json()
-            }))
-            .org(&path.org_id)
-            .project(&path.project_id)
-            .names(&ctx.audit_names().project(project.name.clone()))
-            .auth_method(&ctx.auth_method)
-            .save()?;
+        let actor = JobActor {
+            user_id: ctx.member.user_id.clone(),
+            auth_method: ctx.auth_method.clone(),
+            names,
+            headers,
+            impersonator: ctx.impersonator_json(),
+        };
+        bulk_jobs::spawn(state.clone(), path.org_id.clone(), job.clone(), actor);
+
+        let response = BulkJobResponse::new(&path.org_id, job, Vec::new());
+        return Ok((
+            quota,
+            (StatusCode::ACCEPTED, Json(response)).into_response(),
+        ));
     }
 
+    // Licenses and their audit entries commit together
+    let created_licenses = outbox::with_audited_tx(
+        &mut conn,
+        |tx| {
+            let mut created_licenses = Vec::with_capacity(body.count as usize);
+            for _ in 0..body.count {
+                let mut license = queries::create_license(
+                    tx,
+                    &project.id,
+                    &body.product_id,
+                    &CreateLicense {
+                        email_hash: email_hash.clone(),
+                        customer_id: body.customer_id.clone(),
+                        expires_at: exps.license_exp,
+                        updates_expires_at: exps.updates_exp,
+                        payment_provider: None,
+                        payment_provider_customer_id: None,
+                        payment_provider_subscription_id: None,
+                        payment_provider_order_id: None,
+                        seats,
+                    },
+                )?;
+                if body.is_trial {
+                    queries::mark_license_trial(tx, &license.id)?;
+                    license.is_trial = true;
+                }
+
+                // Generate activation code for immediate use
+                let code =
+                    queries::create_activation_code(tx, &license.id, &project.license_key_prefix)?;
+
+                created_licenses.push(CreatedLicenseWithDetails {
+                    license: LicenseWithProduct {
+                        license,
+                        product_name: product.name.clone(),
+                        tags: Vec::new(),
+                    },
+                    activation_code: code.code,
+                    activation_code_expires_at: code.expires_at,
+                });
+            }
+            Ok(created_licenses)
+        },
+        |created_licenses| {
+            created_licenses
+                .iter()
+                .filter_map(|created| {
+                    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+                        .actor(ActorType::User, Some(&ctx.member.user_id))
+                        .action(AuditAction::CreateLicense)
+                        .resource("license", &created.license.license.id)
+                        .details(&details)
+                        .org(&path.org_id)
+                        .project(&path.project_id)
+                        .names(&ctx.audit_names().project(project.name.clone()))
+                        .auth_method(&ctx.auth_method)
+                        .entry()
+                })
+                .collect::<Vec<_>>()
+        },
+    )?;
+    outbox::relay(&conn, &audit_conn);
+
     tracing::info!(
         "Created {} license(s) for product {} (project: {})",
         created_licenses.len(),
@@ -275,9 +465,26 @@ pub async fn create_license(
         path.project_id
     );
 
-    Ok(Json(CreateLicenseResponse {
-        items: created_licenses,
-    }))
+    if let Some(ref warning) = quota {
+        quota::audit_crossing(
+            AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+                .actor(ActorType::User, Some(&ctx.member.user_id))
+                .org(&path.org_id)
+                .project(&path.project_id)
+                .names(&ctx.audit_names().project(project.name.clone()))
+                .auth_method(&ctx.auth_method),
+            &path.org_id,
+            warning,
+        );
+    }
+
+    Ok((
+        quota,
+        Json(CreateLicenseResponse {
+            items: created_licenses,
+        })
+        .into_response(),
+    ))
 }
 
 /// Request body for updating a license (email correction)
@@ -293,7 +500,7 @@ pub struct UpdateLicenseBody {
 pub async fn update_license(
     State(state): State<AppState>,
     Extension(ctx): Extension<OrgMemberContext>,
-    Path(path): Path<LicensePath>,
+    Path(mut path): Path<LicensePath>,
     headers: HeaderMap,
     Json(body): Json<UpdateLicenseBody>,
 ) -> Result<Json<LicenseWithProduct>> {
@@ -301,7 +508,8 @@ pub async fn update_license(
         return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
     }
 
-    let conn = state.db.get()?;
+    let conn = state.org_db(&path.org_id).get()?;
+    path.license_id = queries::resolve_admin_license_id(&conn, &path.project_id, &path.license_id)?;
     let audit_conn = state.audit.get()?;
 
     // Get the license
@@ -322,7 +530,7 @@ pub async fn update_license(
 
     // Update email hash if provided
     if let Some(ref email) = body.email {
-        let new_email_hash = state.email_hasher.hash(email);
+        let new_email_hash = state.email_hasher.hash(&EmailAddress::parse(email)?);
         let old_email_hash = license.email_hash.clone();
         queries::update_license_email_hash(&conn, &license.id, &new_email_hash)?;
 
@@ -330,7 +538,7 @@ pub async fn update_license(
         license.email_hash = Some(new_email_hash);
 
         // Audit log the email change (log old hash for investigation, not new email for privacy)
-        AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+        AuditLogBuilder::for_state(&audit_conn, &state, &headers)
             .actor(ActorType::User, Some(&ctx.member.user_id))
             .action(AuditAction::UpdateLicenseEmail)
             .resource("license", &license.id)
@@ -352,17 +560,21 @@ pub async fn update_license(
         );
     }
 
+    let tags = queries::list_license_tags(&conn, &license.id)?;
+
     Ok(Json(LicenseWithProduct {
         license,
         product_name: product.name,
+        tags,
     }))
 }
 
 pub async fn get_license(
     State(state): State<AppState>,
-    Path(path): Path<LicensePath>,
+    Path(mut path): Path<LicensePath>,
 ) -> Result<Json<LicenseWithDevices>> {
-    let conn = state.db.get()?;
+    let conn = state.org_db(&path.org_id).get()?;
+    path.license_id = queries::resolve_admin_license_id(&conn, &path.project_id, &path.license_id)?;
 
     let license = queries::get_license_by_id(&conn, &path.license_id)?
         .or_not_found(msg::LICENSE_NOT_FOUND)?;
@@ -380,28 +592,53 @@ pub async fn get_license(
     let active_device_count =
         queries::count_active_devices_for_license(&conn, &license.id, product.device_inactive_days)?;
 
+    let paused = license.paused_at.is_some();
+    let paused_days = license.total_paused_seconds(chrono::Utc::now().timestamp()) / 86400;
+    let upgraded_from = queries::get_upgrade_creating_license(&conn, &license.id)?;
+    let upgraded_to = queries::get_upgrade_replacing_license(&conn, &license.id)?;
+    let update_renewals = queries::list_license_update_renewals(&conn, &license.id)?;
+    let tags = queries::list_license_tags(&conn, &license.id)?;
+    let recent_emails =
+        queries::list_recent_emails_for_license(&conn, &license.id, RECENT_EMAILS_PER_LICENSE)?;
+    let disputes = queries::list_disputes_for_license(&conn, &license.id)?;
+
     Ok(Json(LicenseWithDevices {
         license: LicenseWithProduct {
             license,
             product_name: product.name,
+            tags,
         },
         devices,
         active_device_count,
         total_device_count,
+        paused,
+        paused_days,
+        upgraded_from,
+        upgraded_to,
+        update_renewals,
+        recent_emails,
+        disputes,
     }))
 }
 
+/// POST /orgs/{org_id}/projects/{project_id}/licenses/{license_id}/revoke
+/// Revoke a license. The body is optional; without one the reason is `admin_other`.
 pub async fn revoke_license(
     State(state): State<AppState>,
     Extension(ctx): Extension<OrgMemberContext>,
-    Path(path): Path<LicensePath>,
+    Path(mut path): Path<LicensePath>,
     headers: HeaderMap,
+    input: Option<Json<RevokeLicense>>,
 ) -> Result<Json<serde_json::Value>> {
     if !ctx.can_write_project() {
         return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
     }
 
-    let conn = state.db.get()?;
+    let input = input.map(|Json(input)| input).unwrap_or_default();
+    input.validate()?;
+
+    let mut conn = state.org_db(&path.org_id).get()?;
+    path.license_id = queries::resolve_admin_license_id(&conn, &path.project_id, &path.license_id)?;
     let audit_conn = state.audit.get()?;
 
     let license = queries::get_license_by_id(&conn, &path.license_id)?
@@ -423,20 +660,28 @@ pub async fn revoke_license(
     let project = queries::get_project_by_id(&conn, &path.project_id)?
         .or_not_found(msg::PROJECT_NOT_FOUND)?;
 
-    queries::revoke_license(&conn, &license.id)?;
-
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
-        .actor(ActorType::User, Some(&ctx.member.user_id))
-        .action(AuditAction::RevokeLicense)
-        .resource("license", &license.id)
-        .details(&serde_json::json!({
-            "impersonator": ctx.impersonator_json()
-        }))
-        .org(&path.org_id)
-        .project(&path.project_id)
-        .names(&ctx.audit_names().project(project.name.clone()))
-        .auth_method(&ctx.auth_method)
-        .save()?;
+    let details = serde_json::json!({
+        "reason": input.reason,
+        "message": input.message(),
+        "impersonator": ctx.impersonator_json()
+    });
+    outbox::try_with_audited_tx(
+        &mut conn,
+        |tx| queries::revoke_license(tx, &license.id, &input, Some(&ctx.member.user_id)),
+        |_| {
+            AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+                .actor(ActorType::User, Some(&ctx.member.user_id))
+                .action(AuditAction::RevokeLicense)
+                .resource("license", &license.id)
+                .details(&details)
+                .org(&path.org_id)
+                .project(&path.project_id)
+                .names(&ctx.audit_names().project(project.name.clone()))
+                .auth_method(&ctx.auth_method)
+                .checked_entry()
+        },
+    )?;
+    outbox::relay(&conn, &audit_conn);
 
     Ok(Json(serde_json::json!({ "success": true })))
 }
@@ -453,14 +698,15 @@ pub struct SendActivationCodeResponse {
 pub async fn send_activation_code(
     State(state): State<AppState>,
     Extension(ctx): Extension<OrgMemberContext>,
-    Path(path): Path<LicensePath>,
+    Path(mut path): Path<LicensePath>,
     headers: HeaderMap,
 ) -> Result<Json<SendActivationCodeResponse>> {
     if !ctx.can_write_project() {
         return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
     }
 
-    let conn = state.db.get()?;
+    let conn = state.org_db(&path.org_id).get()?;
+    path.license_id = queries::resolve_admin_license_id(&conn, &path.project_id, &path.license_id)?;
     let audit_conn = state.audit.get()?;
 
     let license = queries::get_license_by_id(&conn, &path.license_id)?
@@ -485,7 +731,7 @@ pub async fn send_activation_code(
     // Create activation code
     let code = queries::create_activation_code(&conn, &license.id, &project.license_key_prefix)?;
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::User, Some(&ctx.member.user_id))
         .action(AuditAction::GenerateActivationCode)
         .resource("license", &license.id)
@@ -518,14 +764,15 @@ pub struct DeactivateDeviceResponse {
 pub async fn deactivate_device_admin(
     State(state): State<AppState>,
     Extension(ctx): Extension<OrgMemberContext>,
-    Path(path): Path<LicenseDevicePath>,
+    Path(mut path): Path<LicenseDevicePath>,
     headers: HeaderMap,
 ) -> Result<Json<DeactivateDeviceResponse>> {
     if !ctx.can_write_project() {
         return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
     }
 
-    let conn = state.db.get()?;
+    let conn = state.org_db(&path.org_id).get()?;
+    path.license_id = queries::resolve_admin_license_id(&conn, &path.project_id, &path.license_id)?;
     let audit_conn = state.audit.get()?;
 
     // Get the license
@@ -555,7 +802,7 @@ pub async fn deactivate_device_admin(
     let remaining = queries::count_devices_for_license(&conn, &license.id)?;
 
     // Audit log
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::User, Some(&ctx.member.user_id))
         .action(AuditAction::DeactivateDevice)
         .resource("device", &device.id)
@@ -590,7 +837,7 @@ pub async fn deactivate_device_admin(
 pub async fn restore_license(
     State(state): State<AppState>,
     Extension(ctx): Extension<OrgMemberContext>,
-    Path(path): Path<LicensePath>,
+    Path(mut path): Path<LicensePath>,
     headers: HeaderMap,
     Json(input): Json<RestoreRequest>,
 ) -> Result<Json<LicenseWithDevices>> {
@@ -598,7 +845,8 @@ pub async fn restore_license(
         return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
     }
 
-    let conn = state.db.get()?;
+    let conn = state.org_db(&path.org_id).get()?;
+    path.license_id = queries::resolve_admin_license_id(&conn, &path.project_id, &path.license_id)?;
     let audit_conn = state.audit.get()?;
 
     let existing = queries::get_deleted_license_by_id(&conn, &path.license_id)?
@@ -622,7 +870,7 @@ pub async fn restore_license(
     let active_device_count =
         queries::count_active_devices_for_license(&conn, &license.id, product.device_inactive_days)?;
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::User, Some(&ctx.member.user_id))
         .action(AuditAction::RestoreLicense)
         .resource("license", &path.license_id)
@@ -636,13 +884,31 @@ pub async fn restore_license(
         .auth_method(&ctx.auth_method)
         .save()?;
 
+    let paused = license.paused_at.is_some();
+    let paused_days = license.total_paused_seconds(chrono::Utc::now().timestamp()) / 86400;
+    let upgraded_from = queries::get_upgrade_creating_license(&conn, &license.id)?;
+    let upgraded_to = queries::get_upgrade_replacing_license(&conn, &license.id)?;
+    let update_renewals = queries::list_license_update_renewals(&conn, &license.id)?;
+    let tags = queries::list_license_tags(&conn, &license.id)?;
+    let recent_emails =
+        queries::list_recent_emails_for_license(&conn, &license.id, RECENT_EMAILS_PER_LICENSE)?;
+    let disputes = queries::list_disputes_for_license(&conn, &license.id)?;
+
     Ok(Json(LicenseWithDevices {
         license: LicenseWithProduct {
             license,
             product_name: product.name,
+            tags,
         },
         devices,
         active_device_count,
         total_device_count,
+        paused,
+        paused_days,
+        upgraded_from,
+        upgraded_to,
+        update_renewals,
+        recent_emails,
+        disputes,
     }))
 }
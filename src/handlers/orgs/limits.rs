@@ -0,0 +1,32 @@
+use axum::extract::{Extension, State};
+
+use crate::db::AppState;
+use crate::error::Result;
+use crate::extractors::{Json, Path};
+use crate::middleware::{OrgMemberContext, visible_project_ids};
+use crate::models::OrgLimitsResponse;
+use crate::quota;
+
+/// GET /orgs/{org_id}/limits
+/// The org's operator-set limits next to its current usage. The project with
+/// the most products is left out for members who can't see it.
+pub async fn get_limits(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(org_id): Path<String>,
+) -> Result<Json<OrgLimitsResponse>> {
+    let conn = state.org_db(&org_id).get()?;
+    let mut limits = quota::org_usage(&conn, &org_id, state.clock.now())?;
+
+    let visible = visible_project_ids(&conn, &ctx, state.clock.now())?;
+    for usage in &mut limits {
+        if usage
+            .project_id
+            .as_deref()
+            .is_some_and(|id| !visible.contains(id))
+        {
+            usage.project_id = None;
+        }
+    }
+    Ok(Json(OrgLimitsResponse { limits }))
+}
@@ -3,7 +3,7 @@ use axum::{
     http::HeaderMap,
 };
 
-use crate::db::{AppState, queries};
+use crate::db::{AppState, outbox, queries};
 use crate::error::{AppError, OptionExt, Result, msg};
 use crate::extractors::{Json, Path, RestoreRequest};
 use crate::middleware::OrgMemberContext;
@@ -25,16 +25,16 @@ pub async fn create_org_member(
 ) -> Result<Json<OrgMemberWithUser>> {
     ctx.require_owner()?;
 
-    let conn = state.db.get()?;
+    let conn = state.org_db(&org_id).get()?;
     let audit_conn = state.audit.get()?;
 
     // Verify the user exists
-    let user = queries::get_user_by_id(&conn, &input.user_id)?
+    let user = queries::get_user_by_id(&conn, &input.user_id, &state.emails())?
         .ok_or_else(|| AppError::BadRequest(msg::USER_NOT_FOUND.into()))?;
 
     let member = queries::create_org_member(&conn, &org_id, &input)?;
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::User, Some(&ctx.member.user_id))
         .action(AuditAction::CreateOrgMember)
         .resource("org_member", &member.id)
@@ -50,9 +50,13 @@ pub async fn create_org_member(
         .save()?;
 
     // Return enriched member with user details
-    let member_with_user =
-        queries::get_org_member_with_user_by_user_and_org(&conn, &input.user_id, &org_id)?
-            .ok_or_else(|| AppError::Internal("Failed to fetch created member".into()))?;
+    let member_with_user = queries::get_org_member_with_user_by_user_and_org(
+        &conn,
+        &input.user_id,
+        &org_id,
+        &state.emails(),
+    )?
+    .ok_or_else(|| AppError::Internal("Failed to fetch created member".into()))?;
 
     Ok(Json(member_with_user))
 }
@@ -63,11 +67,16 @@ pub async fn list_org_members(
     Path(org_id): Path<String>,
     Query(pagination): Query<PaginationQuery>,
 ) -> Result<Json<Paginated<OrgMemberWithUser>>> {
-    let conn = state.db.get()?;
+    let conn = state.org_db(&org_id).get()?;
     let limit = pagination.limit();
     let offset = pagination.offset();
-    let (members, total) =
-        queries::list_org_members_with_user_paginated(&conn, &org_id, limit, offset)?;
+    let (members, total) = queries::list_org_members_with_user_paginated(
+        &conn,
+        &org_id,
+        limit,
+        offset,
+        &state.emails(),
+    )?;
     Ok(Json(Paginated::new(members, total, limit, offset)))
 }
 
@@ -77,15 +86,28 @@ pub struct OrgMemberPath {
     pub user_id: String,
 }
 
+/// Longest grace period for a scheduled member removal (30 days)
+const MAX_REMOVAL_GRACE_HOURS: i64 = 720;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct DeleteOrgMemberQuery {
+    /// Hours the member keeps access before removal (default 0 = immediately)
+    pub grace_hours: Option<i64>,
+}
+
 /// Get an org member with user details by user_id
 pub async fn get_org_member(
     State(state): State<AppState>,
     Path(path): Path<OrgMemberPath>,
 ) -> Result<Json<OrgMemberWithUser>> {
-    let conn = state.db.get()?;
-    let member =
-        queries::get_org_member_with_user_by_user_and_org(&conn, &path.user_id, &path.org_id)?
-            .or_not_found(msg::NOT_ORG_MEMBER)?;
+    let conn = state.org_db(&path.org_id).get()?;
+    let member = queries::get_org_member_with_user_by_user_and_org(
+        &conn,
+        &path.user_id,
+        &path.org_id,
+        &state.emails(),
+    )?
+    .or_not_found(msg::NOT_ORG_MEMBER)?;
 
     Ok(Json(member))
 }
@@ -99,7 +121,7 @@ pub async fn update_org_member(
 ) -> Result<Json<OrgMemberWithUser>> {
     ctx.require_owner()?;
 
-    let conn = state.db.get()?;
+    let mut conn = state.org_db(&path.org_id).get()?;
     let audit_conn = state.audit.get()?;
 
     // Prevent changing your own role
@@ -107,33 +129,39 @@ pub async fn update_org_member(
         return Err(AppError::BadRequest(msg::CANNOT_CHANGE_OWN_ROLE.into()));
     }
 
-    let mut member =
-        queries::get_org_member_with_user_by_user_and_org(&conn, &path.user_id, &path.org_id)?
-            .or_not_found(msg::NOT_ORG_MEMBER)?;
-
-    let updated = queries::update_org_member(&conn, &member.id, &input)?
-        .or_not_found(msg::NOT_ORG_MEMBER)?;
+    let mut member = queries::get_org_member_with_user_by_user_and_org(
+        &conn,
+        &path.user_id,
+        &path.org_id,
+        &state.emails(),
+    )?
+    .or_not_found(msg::NOT_ORG_MEMBER)?;
+
+    let details = serde_json::json!({
+        "role": input.role,
+        "impersonator": ctx.impersonator_json()
+    });
+    let updated = outbox::try_with_audited_tx(
+        &mut conn,
+        |tx| queries::update_org_member(tx, &member.id, &input)?.or_not_found(msg::NOT_ORG_MEMBER),
+        |_| {
+            AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+                .actor(ActorType::User, Some(&ctx.member.user_id))
+                .action(AuditAction::UpdateOrgMember)
+                .resource("org_member", &member.id)
+                .details(&details)
+                .org(&path.org_id)
+                .names(&ctx.audit_names().resource_user(&member.name, &member.email))
+                .auth_method(&ctx.auth_method)
+                .checked_entry()
+        },
+    )?;
+    outbox::relay(&conn, &audit_conn);
 
     // Apply known changes to avoid re-fetching
     member.role = updated.role;
     member.updated_at = updated.updated_at;
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
-        .actor(ActorType::User, Some(&ctx.member.user_id))
-        .action(AuditAction::UpdateOrgMember)
-        .resource("org_member", &member.id)
-        .details(&serde_json::json!({
-            "role": input.role,
-            "impersonator": ctx.impersonator_json()
-        }))
-        .org(&path.org_id)
-        .names(
-            &ctx.audit_names()
-                .resource_user(&member.name, &member.email),
-        )
-        .auth_method(&ctx.auth_method)
-        .save()?;
-
     Ok(Json(member))
 }
 
@@ -141,11 +169,17 @@ pub async fn delete_org_member(
     State(state): State<AppState>,
     Extension(ctx): Extension<OrgMemberContext>,
     Path(path): Path<OrgMemberPath>,
+    Query(query): Query<DeleteOrgMemberQuery>,
     headers: HeaderMap,
 ) -> Result<Json<serde_json::Value>> {
     ctx.require_owner()?;
 
-    let conn = state.db.get()?;
+    let grace_hours = query.grace_hours.unwrap_or(0);
+    if !(0..=MAX_REMOVAL_GRACE_HOURS).contains(&grace_hours) {
+        return Err(AppError::BadRequest(msg::GRACE_HOURS_INVALID.into()));
+    }
+
+    let conn = state.org_db(&path.org_id).get()?;
     let audit_conn = state.audit.get()?;
 
     // Prevent self-deletion
@@ -153,13 +187,45 @@ pub async fn delete_org_member(
         return Err(AppError::BadRequest(msg::CANNOT_DELETE_SELF.into()));
     }
 
-    let existing =
-        queries::get_org_member_with_user_by_user_and_org(&conn, &path.user_id, &path.org_id)?
-            .or_not_found(msg::NOT_ORG_MEMBER)?;
+    let existing = queries::get_org_member_with_user_by_user_and_org(
+        &conn,
+        &path.user_id,
+        &path.org_id,
+        &state.emails(),
+    )?
+    .or_not_found(msg::NOT_ORG_MEMBER)?;
+
+    if grace_hours > 0 {
+        let removal_at = chrono::Utc::now().timestamp() + grace_hours * 3600;
+        queries::schedule_org_member_removal(&conn, &existing.id, removal_at)?;
+
+        AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+            .actor(ActorType::User, Some(&ctx.member.user_id))
+            .action(AuditAction::ScheduleOrgMemberRemoval)
+            .resource("org_member", &existing.id)
+            .details(&serde_json::json!({
+                "user_id": path.user_id,
+                "email": existing.email,
+                "removal_scheduled_at": removal_at,
+                "impersonator": ctx.impersonator_json()
+            }))
+            .org(&path.org_id)
+            .names(
+                &ctx.audit_names()
+                    .resource_user(&existing.name, &existing.email),
+            )
+            .auth_method(&ctx.auth_method)
+            .save()?;
+
+        return Ok(Json(serde_json::json!({
+            "success": true,
+            "removal_scheduled_at": removal_at
+        })));
+    }
 
     queries::soft_delete_org_member(&conn, &existing.id)?;
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::User, Some(&ctx.member.user_id))
         .action(AuditAction::DeleteOrgMember)
         .resource("org_member", &existing.id)
@@ -179,6 +245,82 @@ pub async fn delete_org_member(
     Ok(Json(serde_json::json!({ "success": true })))
 }
 
+/// Cancel a member's scheduled removal (see `grace_hours` on delete)
+pub async fn cancel_org_member_removal(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(path): Path<OrgMemberPath>,
+    headers: HeaderMap,
+) -> Result<Json<OrgMemberWithUser>> {
+    ctx.require_owner()?;
+
+    let conn = state.org_db(&path.org_id).get()?;
+    let audit_conn = state.audit.get()?;
+
+    let mut member = queries::get_org_member_with_user_by_user_and_org(
+        &conn,
+        &path.user_id,
+        &path.org_id,
+        &state.emails(),
+    )?
+    .or_not_found(msg::NOT_ORG_MEMBER)?;
+
+    if !queries::cancel_org_member_removal(&conn, &member.id)? {
+        return Err(AppError::BadRequest(msg::MEMBER_REMOVAL_NOT_SCHEDULED.into()));
+    }
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.member.user_id))
+        .action(AuditAction::CancelOrgMemberRemoval)
+        .resource("org_member", &member.id)
+        .details(&serde_json::json!({
+            "user_id": path.user_id,
+            "removal_scheduled_at": member.removal_scheduled_at,
+            "impersonator": ctx.impersonator_json()
+        }))
+        .org(&path.org_id)
+        .names(
+            &ctx.audit_names()
+                .resource_user(&member.name, &member.email),
+        )
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    member.removal_scheduled_at = None;
+    Ok(Json(member))
+}
+
+/// Remove org members whose grace period has ended. Called by the background
+/// maintenance task; returns the number of members removed.
+pub fn process_scheduled_member_removals(state: &AppState, now: i64) -> Result<usize> {
+    let due = queries::list_due_org_member_removals(&*state.db.get()?, now)?;
+    let audit_conn = state.audit.get()?;
+    let headers = HeaderMap::new();
+
+    let mut removed = 0;
+    for member in due {
+        // Project memberships cascade in the org's own database
+        let conn = state.org_db(&member.org_id).get()?;
+        if !queries::soft_delete_org_member(&conn, &member.id)? {
+            continue;
+        }
+        removed += 1;
+
+        AuditLogBuilder::for_state(&audit_conn, state, &headers)
+            .actor(ActorType::System, None)
+            .action(AuditAction::DeleteOrgMember)
+            .resource("org_member", &member.id)
+            .details(&serde_json::json!({
+                "user_id": member.user_id,
+                "removal_scheduled_at": member.removal_scheduled_at
+            }))
+            .org(&member.org_id)
+            .save()?;
+    }
+
+    Ok(removed)
+}
+
 /// Restore a soft-deleted org member
 pub async fn restore_org_member(
     State(state): State<AppState>,
@@ -189,7 +331,7 @@ pub async fn restore_org_member(
 ) -> Result<Json<OrgMemberWithUser>> {
     ctx.require_owner()?;
 
-    let conn = state.db.get()?;
+    let conn = state.org_db(&path.org_id).get()?;
     let audit_conn = state.audit.get()?;
 
     let existing =
@@ -199,10 +341,10 @@ pub async fn restore_org_member(
     queries::restore_org_member(&conn, &existing.id, input.force)?;
 
     // Get user info for audit log
-    let user = queries::get_user_by_id(&conn, &path.user_id)?
+    let user = queries::get_user_by_id(&conn, &path.user_id, &state.emails())?
         .ok_or_else(|| AppError::Internal(msg::USER_NOT_FOUND.into()))?;
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::User, Some(&ctx.member.user_id))
         .action(AuditAction::RestoreOrgMember)
         .resource("org_member", &existing.id)
@@ -216,9 +358,13 @@ pub async fn restore_org_member(
         .auth_method(&ctx.auth_method)
         .save()?;
 
-    let member =
-        queries::get_org_member_with_user_by_user_and_org(&conn, &path.user_id, &path.org_id)?
-            .ok_or_else(|| AppError::Internal(msg::MEMBER_NOT_FOUND_AFTER_RESTORE.into()))?;
+    let member = queries::get_org_member_with_user_by_user_and_org(
+        &conn,
+        &path.user_id,
+        &path.org_id,
+        &state.emails(),
+    )?
+    .ok_or_else(|| AppError::Internal(msg::MEMBER_NOT_FOUND_AFTER_RESTORE.into()))?;
 
     Ok(Json(member))
 }
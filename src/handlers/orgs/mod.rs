@@ -1,20 +1,70 @@
+mod activity;
 mod api_keys;
+mod artifacts;
 mod audit_logs;
+mod bulk_jobs;
+mod claims_preview;
+mod compliance;
+mod devices;
+mod disputes;
+mod dormant_licenses;
+mod email_config;
+mod email_log;
+mod entitlements;
+mod license_imports;
+mod license_seats;
+mod license_tags;
 mod licenses;
+mod limits;
 mod members;
+mod prepaid_codes;
 mod product_provider_link;
+mod product_templates;
 mod products;
+mod project_config;
 mod project_members;
 mod projects;
+mod share_links;
+mod temporary_roles;
+mod terms;
+mod token_diagnostics;
+mod trial_conversions;
+mod usage_geo;
+mod usage_versions;
 
+pub use activity::*;
 pub use api_keys::*;
+pub use artifacts::*;
 pub use audit_logs::*;
+pub use bulk_jobs::*;
+pub use claims_preview::*;
+pub use compliance::*;
+pub use devices::*;
+pub use disputes::*;
+pub use dormant_licenses::*;
+pub use email_config::*;
+pub use email_log::*;
+pub use entitlements::*;
+pub use license_imports::*;
+pub use license_seats::*;
+pub use license_tags::*;
 pub use licenses::*;
+pub use limits::*;
 pub use members::*;
+pub use prepaid_codes::*;
 pub use product_provider_link::*;
+pub use product_templates::*;
 pub use products::*;
+pub use project_config::*;
 pub use project_members::*;
 pub use projects::*;
+pub use share_links::*;
+pub use temporary_roles::*;
+pub use terms::*;
+pub use token_diagnostics::*;
+pub use trial_conversions::*;
+pub use usage_geo::*;
+pub use usage_versions::*;
 
 use axum::{
     Router, middleware,
@@ -41,6 +91,10 @@ pub fn router(state: AppState, rate_limit_config: RateLimitConfig) -> Router<App
             "/orgs/{org_id}/members/{user_id}/restore",
             post(restore_org_member),
         )
+        .route(
+            "/orgs/{org_id}/members/{user_id}/cancel-removal",
+            post(cancel_org_member_removal),
+        )
         // Member API keys
         .route(
             "/orgs/{org_id}/members/{user_id}/api-keys",
@@ -56,10 +110,46 @@ pub fn router(state: AppState, rate_limit_config: RateLimitConfig) -> Router<App
         )
         .route("/orgs/{org_id}/projects", post(create_project))
         .route("/orgs/{org_id}/projects", get(list_projects))
+        // Org-level: the project middleware doesn't find deleted projects
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/restore",
+            post(restore_project),
+        )
+        // Product templates new projects are seeded from (owner/admin)
+        .route(
+            "/orgs/{org_id}/product-templates",
+            post(create_product_template),
+        )
+        .route(
+            "/orgs/{org_id}/product-templates",
+            get(list_product_templates),
+        )
+        .route(
+            "/orgs/{org_id}/product-templates/{template_id}",
+            put(update_product_template),
+        )
+        .route(
+            "/orgs/{org_id}/product-templates/{template_id}",
+            delete(delete_product_template),
+        )
+        // Operator-set limits and current usage
+        .route("/orgs/{org_id}/limits", get(get_limits))
         // Payment provider config (at org level, masked for customers to verify their settings)
         .route("/orgs/{org_id}/payment-provider", get(get_payment_config))
+        // Org Resend API key and default sender (owner only)
+        .route("/orgs/{org_id}/email-config", get(get_email_config))
+        .route("/orgs/{org_id}/email-config", put(update_email_config))
+        .route("/orgs/{org_id}/email-config/test", post(send_test_email))
+        // Audit log retention (owner only)
+        .route("/orgs/{org_id}/compliance-settings", get(get_compliance_settings))
+        .route("/orgs/{org_id}/compliance-settings", put(update_compliance_settings))
         // Audit logs (org-scoped, any org member can view their org's logs)
         .route("/orgs/{org_id}/audit-logs", get(query_org_audit_logs))
+        .route("/orgs/{org_id}/audit-logs/export", get(export_org_audit_logs))
+        .route(
+            "/orgs/{org_id}/audit-logs/archive",
+            post(archive_org_audit_logs),
+        )
         .layer(middleware::from_fn_with_state(
             state.clone(),
             org_member_auth,
@@ -74,8 +164,35 @@ pub fn router(state: AppState, rate_limit_config: RateLimitConfig) -> Router<App
             delete(delete_project),
         )
         .route(
-            "/orgs/{org_id}/projects/{project_id}/restore",
-            post(restore_project),
+            "/orgs/{org_id}/projects/{project_id}/client-flags",
+            put(update_client_flags),
+        )
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/webhook-schema",
+            get(get_webhook_schema),
+        )
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/email-webhook",
+            get(get_email_webhook),
+        )
+        // Token diagnostics (why a customer's token is rejected)
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/diagnose-token",
+            post(diagnose_token),
+        )
+        // Project config as code (settings, products, provider links)
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/config-export",
+            get(export_project_config),
+        )
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/config-import",
+            put(import_project_config),
+        )
+        // Products from org templates (owner/admin)
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/apply-template",
+            post(apply_product_templates),
         )
         // Project members
         .route(
@@ -98,6 +215,50 @@ pub fn router(state: AppState, rate_limit_config: RateLimitConfig) -> Router<App
             "/orgs/{org_id}/projects/{project_id}/members/{user_id}",
             delete(delete_project_member),
         )
+        // Temporary project roles (break-glass access)
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/members/{user_id}/temporary-role",
+            post(grant_temporary_role),
+        )
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/members/{user_id}/temporary-role",
+            delete(revoke_temporary_role),
+        )
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/temporary-roles",
+            get(list_temporary_roles),
+        )
+        // Terms of sale (versions buyers accept at /buy)
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/terms",
+            post(create_terms),
+        )
+        .route("/orgs/{org_id}/projects/{project_id}/terms", get(list_terms))
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/terms/{terms_id}",
+            put(update_terms),
+        )
+        // Release artifacts (downloads gated by the updates window)
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/artifacts",
+            post(create_artifact),
+        )
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/artifacts",
+            get(list_artifacts),
+        )
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/artifacts/{artifact_id}",
+            delete(delete_artifact),
+        )
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/artifacts/{artifact_id}/downloads",
+            get(list_artifact_downloads),
+        )
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/download-signing-key",
+            get(get_download_signing_key),
+        )
         // Products
         .route(
             "/orgs/{org_id}/projects/{project_id}/products",
@@ -144,6 +305,28 @@ pub fn router(state: AppState, rate_limit_config: RateLimitConfig) -> Router<App
             "/orgs/{org_id}/projects/{project_id}/products/{product_id}/provider-links/{link_id}",
             delete(delete_provider_link_handler),
         )
+        // Prepaid codes (single-use codes for offline distribution)
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/products/{product_id}/prepaid-codes",
+            post(create_prepaid_codes),
+        )
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/products/{product_id}/prepaid-codes",
+            get(list_prepaid_code_batches),
+        )
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/products/{product_id}/prepaid-codes/{batch_id}/csv",
+            get(download_prepaid_codes),
+        )
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/products/{product_id}/prepaid-codes/{batch_id}/revoke",
+            post(revoke_prepaid_codes),
+        )
+        // License key imports (keys a payment provider issued before Paycheck)
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/license-imports/lemonsqueezy",
+            post(import_lemonsqueezy_keys),
+        )
         // Licenses
         .route(
             "/orgs/{org_id}/projects/{project_id}/licenses",
@@ -153,6 +336,40 @@ pub fn router(state: AppState, rate_limit_config: RateLimitConfig) -> Router<App
             "/orgs/{org_id}/projects/{project_id}/licenses",
             post(create_license),
         )
+        // Bulk license jobs (counts too large to create within the request)
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/bulk-jobs/{job_id}",
+            get(get_bulk_job),
+        )
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/bulk-jobs/{job_id}/cancel",
+            post(cancel_bulk_job),
+        )
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/bulk-jobs/{job_id}/csv",
+            get(download_bulk_job_codes),
+        )
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/licenses/tags",
+            get(list_project_license_tags),
+        )
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/licenses/tags/bulk",
+            post(bulk_tag_licenses),
+        )
+        // Entitlements (server-side feature checks by customer)
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/entitlements",
+            get(check_entitlement),
+        )
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/entitlements/check",
+            post(check_entitlements_bulk),
+        )
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/entitlements/suggestions",
+            get(suggest_product_entitlements),
+        )
         .route(
             "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}",
             get(get_license),
@@ -173,11 +390,78 @@ pub fn router(state: AppState, rate_limit_config: RateLimitConfig) -> Router<App
             "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/send-code",
             post(send_activation_code),
         )
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/claims-preview",
+            get(get_license_claims_preview),
+        )
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/email-log",
+            get(list_email_log),
+        )
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/disputes",
+            get(list_disputes),
+        )
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/activity",
+            get(list_project_activity),
+        )
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/conversion-stats",
+            get(get_conversion_stats),
+        )
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/usage/geo",
+            get(get_usage_geo),
+        )
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/usage/versions",
+            get(get_usage_versions),
+        )
+        // Seat management (team licenses)
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/seats",
+            get(list_license_seats),
+        )
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/seats",
+            post(assign_license_seat),
+        )
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/seats/{seat_id}",
+            delete(remove_license_seat),
+        )
+        // Tags (ad-hoc license grouping)
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/tags",
+            post(add_license_tags),
+        )
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/tags",
+            delete(remove_license_tags),
+        )
+        // Customer-facing share links
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/share-link",
+            post(create_share_link),
+        )
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/share-link/{share_link_id}/revoke",
+            post(revoke_share_link),
+        )
         // Device management (for remote deactivation of lost devices)
         .route(
             "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/devices/{device_id}",
             delete(deactivate_device_admin),
         )
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/devices/deactivate-all",
+            post(deactivate_all_license_devices),
+        )
+        .route(
+            "/orgs/{org_id}/projects/{project_id}/devices/deactivate-by-filter",
+            post(deactivate_devices_by_filter),
+        )
         .layer(middleware::from_fn_with_state(
             state.clone(),
             org_member_project_auth,
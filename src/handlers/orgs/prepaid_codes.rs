@@ -0,0 +1,237 @@
+//! Prepaid codes: batches of single-use codes for offline distribution, such
+//! as activation cards in boxed retail copies.
+//!
+//! Each code is redeemed once through `POST /redeem/prepaid`, which creates
+//! the license on the spot. Only code hashes are stored: the codes themselves
+//! can be downloaded once as CSV, and a batch can be revoked as a whole if a
+//! shipment goes missing.
+
+use axum::{
+    extract::{Extension, State},
+    http::{HeaderMap, header},
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::db::{AppState, outbox, queries};
+use crate::error::{AppError, OptionExt, Result, msg};
+use crate::extractors::{Json, Path};
+use crate::middleware::OrgMemberContext;
+use crate::models::{ActorType, AuditAction, CreatePrepaidCodes, PrepaidCodeBatch, Product};
+use crate::util::AuditLogBuilder;
+
+use super::ProductPath;
+
+#[derive(Deserialize)]
+pub struct PrepaidBatchPath {
+    pub org_id: String,
+    pub project_id: String,
+    pub product_id: String,
+    pub batch_id: String,
+}
+
+#[derive(Serialize)]
+pub struct PrepaidCodeBatchList {
+    pub items: Vec<PrepaidCodeBatch>,
+}
+
+/// Product in the path, checked against the project in the path.
+fn product_in_project(
+    conn: &rusqlite::Connection,
+    project_id: &str,
+    product_id: &str,
+) -> Result<Product> {
+    let product =
+        queries::get_product_by_id(conn, product_id)?.or_not_found(msg::PRODUCT_NOT_FOUND)?;
+    if product.project_id != project_id {
+        return Err(AppError::NotFound(msg::PRODUCT_NOT_FOUND.into()));
+    }
+    Ok(product)
+}
+
+/// POST /orgs/{org_id}/projects/{project_id}/products/{product_id}/prepaid-codes
+/// Generate a batch of prepaid codes. The codes aren't in the response:
+/// download them once from `.../prepaid-codes/{batch_id}/csv`.
+pub async fn create_prepaid_codes(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(path): Path<ProductPath>,
+    headers: HeaderMap,
+    Json(input): Json<CreatePrepaidCodes>,
+) -> Result<Json<PrepaidCodeBatch>> {
+    if !ctx.can_write_project() {
+        return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
+    }
+    input.validate()?;
+
+    let mut conn = state.org_db(&path.org_id).get()?;
+    let audit_conn = state.audit.get()?;
+
+    let product = product_in_project(&conn, &path.project_id, &path.product_id)?;
+    let project = queries::get_project_by_id(&conn, &path.project_id)?
+        .or_not_found(msg::PROJECT_NOT_FOUND)?;
+
+    // The batch, its code hashes, and the audit entry commit together
+    let batch = outbox::with_audited_tx(
+        &mut conn,
+        |tx| {
+            queries::create_prepaid_code_batch(
+                tx,
+                &state.master_key,
+                &project,
+                &product.id,
+                &input,
+                Some(&ctx.member.user_id),
+            )
+        },
+        |batch| {
+            AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+                .actor(ActorType::User, Some(&ctx.member.user_id))
+                .action(AuditAction::CreatePrepaidCodes)
+                .resource("prepaid_code_batch", &batch.id)
+                .details(&serde_json::json!({
+                    "product_id": product.id,
+                    "count": batch.code_count,
+                    "label": batch.label,
+                    "expires_at": batch.expires_at,
+                    "impersonator": ctx.impersonator_json()
+                }))
+                .org(&path.org_id)
+                .project(&path.project_id)
+                .names(&ctx.audit_names().resource(product.name.clone()))
+                .auth_method(&ctx.auth_method)
+                .entry()
+        },
+    )?;
+    outbox::relay(&conn, &audit_conn);
+
+    tracing::info!(
+        "Created {} prepaid code(s) for product {} (batch: {})",
+        batch.code_count,
+        product.id,
+        batch.id
+    );
+
+    Ok(Json(batch))
+}
+
+/// GET /orgs/{org_id}/projects/{project_id}/products/{product_id}/prepaid-codes
+/// List a product's prepaid code batches with how many codes were redeemed.
+pub async fn list_prepaid_code_batches(
+    State(state): State<AppState>,
+    Path(path): Path<ProductPath>,
+) -> Result<Json<PrepaidCodeBatchList>> {
+    let conn = state.org_db(&path.org_id).get()?;
+    product_in_project(&conn, &path.project_id, &path.product_id)?;
+
+    let items = queries::list_prepaid_code_batches(&conn, &path.product_id)?;
+    Ok(Json(PrepaidCodeBatchList { items }))
+}
+
+/// GET /orgs/{org_id}/projects/{project_id}/products/{product_id}/prepaid-codes/{batch_id}/csv
+/// Download a batch's codes as CSV (`serial,code`). This works once: the
+/// stored copy is deleted as it's handed out, and later calls get 409.
+pub async fn download_prepaid_codes(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(path): Path<PrepaidBatchPath>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    if !ctx.can_write_project() {
+        return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
+    }
+
+    let mut conn = state.org_db(&path.org_id).get()?;
+    let audit_conn = state.audit.get()?;
+
+    let product = product_in_project(&conn, &path.project_id, &path.product_id)?;
+    let batch = queries::get_prepaid_code_batch(&conn, &path.product_id, &path.batch_id)?
+        .or_not_found(msg::PREPAID_BATCH_NOT_FOUND)?;
+
+    // Decrypt inside the transaction so a failure leaves the codes downloadable
+    let csv = outbox::with_audited_tx(
+        &mut conn,
+        |tx| {
+            queries::take_prepaid_codes(tx, &state.master_key, &path.org_id, &batch.id)?
+                .ok_or_else(|| AppError::Conflict(msg::PREPAID_CODES_ALREADY_DOWNLOADED.into()))
+        },
+        |_| {
+            AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+                .actor(ActorType::User, Some(&ctx.member.user_id))
+                .action(AuditAction::DownloadPrepaidCodes)
+                .resource("prepaid_code_batch", &batch.id)
+                .details(&serde_json::json!({
+                    "product_id": product.id,
+                    "count": batch.code_count,
+                    "impersonator": ctx.impersonator_json()
+                }))
+                .org(&path.org_id)
+                .project(&path.project_id)
+                .names(&ctx.audit_names().resource(product.name.clone()))
+                .auth_method(&ctx.auth_method)
+                .entry()
+        },
+    )?;
+    outbox::relay(&conn, &audit_conn);
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"prepaid-codes-{}.csv\"", batch.id),
+            ),
+        ],
+        csv,
+    )
+        .into_response())
+}
+
+/// POST /orgs/{org_id}/projects/{project_id}/products/{product_id}/prepaid-codes/{batch_id}/revoke
+/// Revoke a whole batch (e.g. a stolen shipment). Its unredeemed codes stop
+/// working at once; licenses already redeemed from it are kept, and can be
+/// found with `GET .../licenses?payment_provider_order_id={batch_id}`.
+pub async fn revoke_prepaid_codes(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(path): Path<PrepaidBatchPath>,
+    headers: HeaderMap,
+) -> Result<Json<PrepaidCodeBatch>> {
+    if !ctx.can_write_project() {
+        return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
+    }
+
+    let conn = state.org_db(&path.org_id).get()?;
+    let audit_conn = state.audit.get()?;
+
+    let product = product_in_project(&conn, &path.project_id, &path.product_id)?;
+    let batch = queries::get_prepaid_code_batch(&conn, &path.product_id, &path.batch_id)?
+        .or_not_found(msg::PREPAID_BATCH_NOT_FOUND)?;
+
+    if !queries::revoke_prepaid_code_batch(&conn, &batch.id)? {
+        return Err(AppError::BadRequest(
+            msg::PREPAID_BATCH_ALREADY_REVOKED.into(),
+        ));
+    }
+
+    let batch = queries::get_prepaid_code_batch(&conn, &path.product_id, &batch.id)?
+        .or_not_found(msg::PREPAID_BATCH_NOT_FOUND)?;
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.member.user_id))
+        .action(AuditAction::RevokePrepaidCodes)
+        .resource("prepaid_code_batch", &batch.id)
+        .details(&serde_json::json!({
+            "product_id": product.id,
+            "codes_revoked": batch.code_count - batch.redeemed_count,
+            "already_redeemed": batch.redeemed_count,
+            "impersonator": ctx.impersonator_json()
+        }))
+        .org(&path.org_id)
+        .project(&path.project_id)
+        .names(&ctx.audit_names().resource(product.name.clone()))
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    Ok(Json(batch))
+}
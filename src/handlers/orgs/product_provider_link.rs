@@ -40,7 +40,7 @@ pub async fn create_provider_link(
 
     input.validate()?;
 
-    let conn = state.db.get()?;
+    let conn = state.org_db(&path.org_id).get()?;
     let audit_conn = state.audit.get()?;
 
     // Verify product exists and belongs to this project
@@ -61,7 +61,7 @@ pub async fn create_provider_link(
 
     let link = queries::create_provider_link(&conn, &path.product_id, &input)?;
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::User, Some(&ctx.member.user_id))
         .action(AuditAction::CreateProviderLink)
         .resource("provider_link", &link.id)
@@ -79,7 +79,7 @@ pub async fn list_provider_links(
     State(state): State<AppState>,
     Path(path): Path<ProviderLinkPath>,
 ) -> Result<Json<Vec<ProductProviderLink>>> {
-    let conn = state.db.get()?;
+    let conn = state.org_db(&path.org_id).get()?;
 
     // Verify product exists and belongs to this project
     let product = queries::get_product_by_id(&conn, &path.product_id)?
@@ -97,7 +97,7 @@ pub async fn get_provider_link_handler(
     State(state): State<AppState>,
     Path(path): Path<ProviderLinkItemPath>,
 ) -> Result<Json<ProductProviderLink>> {
-    let conn = state.db.get()?;
+    let conn = state.org_db(&path.org_id).get()?;
 
     let link = queries::get_provider_link_by_id(&conn, &path.link_id)?
         .or_not_found(msg::PROVIDER_LINK_NOT_FOUND)?;
@@ -131,7 +131,7 @@ pub async fn update_provider_link_handler(
 
     input.validate()?;
 
-    let conn = state.db.get()?;
+    let conn = state.org_db(&path.org_id).get()?;
     let audit_conn = state.audit.get()?;
 
     let existing = queries::get_provider_link_by_id(&conn, &path.link_id)?
@@ -152,7 +152,7 @@ pub async fn update_provider_link_handler(
 
     queries::update_provider_link(&conn, &path.link_id, &input)?;
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::User, Some(&ctx.member.user_id))
         .action(AuditAction::UpdateProviderLink)
         .resource("provider_link", &path.link_id)
@@ -181,7 +181,7 @@ pub async fn delete_provider_link_handler(
         return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
     }
 
-    let conn = state.db.get()?;
+    let conn = state.org_db(&path.org_id).get()?;
     let audit_conn = state.audit.get()?;
 
     let existing = queries::get_provider_link_by_id(&conn, &path.link_id)?
@@ -202,7 +202,7 @@ pub async fn delete_provider_link_handler(
 
     queries::delete_provider_link(&conn, &path.link_id)?;
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::User, Some(&ctx.member.user_id))
         .action(AuditAction::DeleteProviderLink)
         .resource("provider_link", &path.link_id)
@@ -0,0 +1,229 @@
+//! Org product templates: product definitions kept at the org level so a new
+//! project can start with its usual products instead of recreating them by
+//! hand. Applying a template copies it into a product; changing or deleting
+//! the template later leaves those products alone.
+
+use axum::{
+    extract::{Extension, State},
+    http::HeaderMap,
+};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::db::{AppState, queries};
+use crate::error::{AppError, OptionExt, Result, msg};
+use crate::extractors::{Json, Path};
+use crate::middleware::{OrgMemberContext, OrgProjectPath};
+use crate::models::{
+    ActorType, ApplyProductTemplates, AuditAction, CreateProductTemplate, OrgLimitName,
+    ProductTemplate, QuotaWarning, UpdateProductTemplate,
+};
+use crate::quota;
+use crate::util::AuditLogBuilder;
+
+#[derive(Deserialize)]
+pub struct ProductTemplatePath {
+    pub org_id: String,
+    pub template_id: String,
+}
+
+/// Products created from templates, in template order
+#[derive(Debug, Serialize)]
+pub struct AppliedTemplates {
+    pub product_ids: Vec<String>,
+}
+
+/// Look up `template_ids` in the org, in the order given. Any ID that isn't
+/// one of the org's templates is a 404.
+pub(super) fn org_templates(
+    conn: &Connection,
+    org_id: &str,
+    template_ids: &[String],
+) -> Result<Vec<ProductTemplate>> {
+    template_ids
+        .iter()
+        .map(|id| {
+            queries::get_product_template(conn, org_id, id)?
+                .or_not_found(msg::PRODUCT_TEMPLATE_NOT_FOUND)
+        })
+        .collect()
+}
+
+/// POST /orgs/{org_id}/product-templates
+pub async fn create_product_template(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(org_id): Path<String>,
+    headers: HeaderMap,
+    Json(input): Json<CreateProductTemplate>,
+) -> Result<Json<ProductTemplate>> {
+    ctx.require_admin()?;
+    input.validate()?;
+
+    let conn = state.db.get()?;
+    let audit_conn = state.audit.get()?;
+
+    let template = queries::create_product_template(&conn, &org_id, &input)?;
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.member.user_id))
+        .action(AuditAction::CreateProductTemplate)
+        .resource("product_template", &template.id)
+        .details(&serde_json::json!({
+            "name": template.name,
+            "tier": template.tier,
+            "impersonator": ctx.impersonator_json()
+        }))
+        .org(&org_id)
+        .names(&ctx.audit_names().resource(template.name.clone()))
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    Ok(Json(template))
+}
+
+/// GET /orgs/{org_id}/product-templates
+pub async fn list_product_templates(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(org_id): Path<String>,
+) -> Result<Json<Vec<ProductTemplate>>> {
+    ctx.require_admin()?;
+    let conn = state.db.get()?;
+    Ok(Json(queries::list_product_templates(&conn, &org_id)?))
+}
+
+/// PUT /orgs/{org_id}/product-templates/{template_id}
+/// Only products created from the template afterwards see the change.
+pub async fn update_product_template(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(path): Path<ProductTemplatePath>,
+    headers: HeaderMap,
+    Json(input): Json<UpdateProductTemplate>,
+) -> Result<Json<ProductTemplate>> {
+    ctx.require_admin()?;
+    input.validate()?;
+
+    let conn = state.db.get()?;
+    let audit_conn = state.audit.get()?;
+
+    let existing = queries::get_product_template(&conn, &path.org_id, &path.template_id)?
+        .or_not_found(msg::PRODUCT_TEMPLATE_NOT_FOUND)?;
+    queries::update_product_template(&conn, &existing.id, &input)?;
+    let template = queries::get_product_template(&conn, &path.org_id, &existing.id)?
+        .or_not_found(msg::PRODUCT_TEMPLATE_NOT_FOUND)?;
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.member.user_id))
+        .action(AuditAction::UpdateProductTemplate)
+        .resource("product_template", &template.id)
+        .details(&serde_json::json!({
+            "name": input.name,
+            "tier": input.tier,
+            "impersonator": ctx.impersonator_json()
+        }))
+        .org(&path.org_id)
+        .names(&ctx.audit_names().resource(existing.name))
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    Ok(Json(template))
+}
+
+/// DELETE /orgs/{org_id}/product-templates/{template_id}
+/// Products already created from the template stay as they are.
+pub async fn delete_product_template(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(path): Path<ProductTemplatePath>,
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>> {
+    ctx.require_admin()?;
+
+    let conn = state.db.get()?;
+    let audit_conn = state.audit.get()?;
+
+    let existing = queries::get_product_template(&conn, &path.org_id, &path.template_id)?
+        .or_not_found(msg::PRODUCT_TEMPLATE_NOT_FOUND)?;
+    queries::delete_product_template(&conn, &path.org_id, &existing.id)?;
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.member.user_id))
+        .action(AuditAction::DeleteProductTemplate)
+        .resource("product_template", &existing.id)
+        .details(&serde_json::json!({
+            "name": existing.name,
+            "impersonator": ctx.impersonator_json()
+        }))
+        .org(&path.org_id)
+        .names(&ctx.audit_names().resource(existing.name.clone()))
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    Ok(Json(serde_json::json!({ "success": true })))
+}
+
+/// POST /orgs/{org_id}/projects/{project_id}/apply-template
+/// Create a product in the project from each template, all in one
+/// transaction: if any template is missing or over quota, none are created.
+pub async fn apply_product_templates(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(path): Path<OrgProjectPath>,
+    headers: HeaderMap,
+    Json(input): Json<ApplyProductTemplates>,
+) -> Result<(Option<QuotaWarning>, Json<AppliedTemplates>)> {
+    ctx.require_admin()?;
+    if input.templates.is_empty() {
+        return Err(AppError::BadRequest(msg::PRODUCT_TEMPLATES_EMPTY.into()));
+    }
+
+    let mut conn = state.org_db(&path.org_id).get()?;
+    let audit_conn = state.audit.get()?;
+
+    let templates = org_templates(&conn, &path.org_id, &input.templates)?;
+    let quota = quota::check(
+        &conn,
+        &path.org_id,
+        OrgLimitName::ProductsPerProject,
+        Some(&path.project_id),
+        templates.len() as i64,
+        state.clock.now(),
+    )?;
+
+    let tx = conn.transaction()?;
+    let products = queries::create_products_from_templates(&tx, &path.project_id, &templates)?;
+    tx.commit()?;
+    let product_ids: Vec<String> = products.into_iter().map(|p| p.id).collect();
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.member.user_id))
+        .action(AuditAction::ApplyProductTemplates)
+        .resource("project", &path.project_id)
+        .details(&serde_json::json!({
+            "templates": input.templates,
+            "product_ids": product_ids,
+            "impersonator": ctx.impersonator_json()
+        }))
+        .org(&path.org_id)
+        .project(&path.project_id)
+        .names(&ctx.audit_names())
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    if let Some(ref warning) = quota {
+        quota::audit_crossing(
+            AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+                .actor(ActorType::User, Some(&ctx.member.user_id))
+                .org(&path.org_id)
+                .project(&path.project_id)
+                .names(&ctx.audit_names())
+                .auth_method(&ctx.auth_method),
+            &path.org_id,
+            warning,
+        );
+    }
+
+    Ok((quota, Json(AppliedTemplates { product_ids })))
+}
@@ -8,8 +8,12 @@ use crate::db::{AppState, queries};
 use crate::error::{AppError, OptionExt, Result, msg};
 use crate::extractors::{Json, Path, RestoreRequest};
 use crate::middleware::OrgMemberContext;
-use crate::models::{ActorType, AuditAction, CreateProduct, UpdateProduct};
+use crate::models::{
+    ActorType, AuditAction, CreateProduct, OrgLimitName, ProductUpsell, QuotaWarning,
+    UpdateProduct, check_expected_version,
+};
 use crate::pagination::{Paginated, PaginationQuery};
+use crate::quota;
 use crate::util::AuditLogBuilder;
 
 #[derive(serde::Deserialize)]
@@ -19,23 +23,114 @@ pub struct ProductPath {
     pub product_id: String,
 }
 
+/// Check the base product an updates-only renewal extends: it has to be a
+/// different live product in the same project and not a renewal itself, and
+/// a product that others renew can't become a renewal.
+fn check_renewal_base(
+    conn: &rusqlite::Connection,
+    project_id: &str,
+    product_id: Option<&str>,
+    base_product_id: Option<&str>,
+) -> Result<()> {
+    let Some(base_product_id) = base_product_id else {
+        return Ok(());
+    };
+    let base = queries::get_product_by_id(conn, base_product_id)?;
+    let valid = base.is_some_and(|base| {
+        base.project_id == project_id
+            && Some(base.id.as_str()) != product_id
+            && !base.is_updates_renewal()
+    });
+    if !valid {
+        return Err(AppError::BadRequest(
+            msg::UPDATES_RENEWAL_BASE_INVALID.into(),
+        ));
+    }
+    if let Some(product_id) = product_id
+        && queries::product_has_updates_renewals(conn, product_id)?
+    {
+        return Err(AppError::BadRequest(msg::UPDATES_RENEWAL_IS_BASE.into()));
+    }
+    Ok(())
+}
+
+/// Check an upsell against the project: the target has to be a different
+/// live product in the same project that isn't an updates-only renewal, and
+/// every highlighted feature has to be one of the target's.
+fn check_upsell(
+    conn: &rusqlite::Connection,
+    project_id: &str,
+    product_id: &str,
+    upsell: &ProductUpsell,
+) -> Result<()> {
+    let target = queries::get_product_by_id(conn, &upsell.upgrade_to_product_id)?
+        .filter(|target| {
+            target.project_id == project_id
+                && target.id != product_id
+                && !target.is_updates_renewal()
+        })
+        .ok_or_else(|| AppError::BadRequest(msg::UPSELL_TARGET_INVALID.into()))?;
+    if !upsell
+        .highlight_features
+        .iter()
+        .all(|feature| target.features.contains(feature))
+    {
+        return Err(AppError::BadRequest(
+            msg::UPSELL_HIGHLIGHTS_NOT_FEATURES.into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Check that a product's new feature list keeps every feature other
+/// products' upsells highlight for it.
+fn check_upsell_highlights_kept(
+    conn: &rusqlite::Connection,
+    product_id: &str,
+    features: &[String],
+) -> Result<()> {
+    let stale = queries::list_upsells_to_product(conn, product_id)?
+        .iter()
+        .filter_map(|product| product.upsell.as_ref())
+        .flat_map(|upsell| &upsell.highlight_features)
+        .any(|feature| !features.contains(feature));
+    if stale {
+        return Err(AppError::BadRequest(msg::UPSELL_HIGHLIGHTS_IN_USE.into()));
+    }
+    Ok(())
+}
+
 pub async fn create_product(
     State(state): State<AppState>,
     Extension(ctx): Extension<OrgMemberContext>,
     Path(path): Path<crate::middleware::OrgProjectPath>,
     headers: HeaderMap,
     Json(input): Json<CreateProduct>,
-) -> Result<Json<ProductWithProviderLinks>> {
+) -> Result<(Option<QuotaWarning>, Json<ProductWithProviderLinks>)> {
     if !ctx.can_write_project() {
         return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
     }
     input.validate()?;
 
-    let conn = state.db.get()?;
+    let conn = state.org_db(&path.org_id).get()?;
     let audit_conn = state.audit.get()?;
+    let quota = quota::check(
+        &conn,
+        &path.org_id,
+        OrgLimitName::ProductsPerProject,
+        Some(&path.project_id),
+        1,
+        state.clock.now(),
+    )?;
+    check_renewal_base(
+        &conn,
+        &path.project_id,
+        None,
+        input.extends_updates_for_product_id.as_deref(),
+    )?;
     let product = queries::create_product(&conn, &path.project_id, &input)?;
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::User, Some(&ctx.member.user_id))
         .action(AuditAction::CreateProduct)
         .resource("product", &product.id)
@@ -50,11 +145,27 @@ pub async fn create_product(
         .auth_method(&ctx.auth_method)
         .save()?;
 
+    if let Some(ref warning) = quota {
+        quota::audit_crossing(
+            AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+                .actor(ActorType::User, Some(&ctx.member.user_id))
+                .org(&path.org_id)
+                .project(&path.project_id)
+                .names(&ctx.audit_names())
+                .auth_method(&ctx.auth_method),
+            &path.org_id,
+            warning,
+        );
+    }
+
     // Return with empty payment config (none configured yet)
-    Ok(Json(ProductWithProviderLinks {
-        product,
-        provider_links: vec![],
-    }))
+    Ok((
+        quota,
+        Json(ProductWithProviderLinks {
+            product,
+            provider_links: vec![],
+        }),
+    ))
 }
 
 pub async fn list_products(
@@ -62,7 +173,7 @@ pub async fn list_products(
     Path(path): Path<crate::middleware::OrgProjectPath>,
     Query(pagination): Query<PaginationQuery>,
 ) -> Result<Json<Paginated<ProductWithProviderLinks>>> {
-    let conn = state.db.get()?;
+    let conn = state.org_db(&path.org_id).get()?;
     let limit = pagination.limit();
     let offset = pagination.offset();
     let (products, total) =
@@ -74,7 +185,7 @@ pub async fn get_product(
     State(state): State<AppState>,
     Path(path): Path<ProductPath>,
 ) -> Result<Json<ProductWithProviderLinks>> {
-    let conn = state.db.get()?;
+    let conn = state.org_db(&path.org_id).get()?;
     let product = queries::get_product_with_links(&conn, &path.product_id)?
         .or_not_found(msg::PRODUCT_NOT_FOUND)?;
 
@@ -97,7 +208,7 @@ pub async fn update_product(
     }
     input.validate()?;
 
-    let conn = state.db.get()?;
+    let conn = state.org_db(&path.org_id).get()?;
     let audit_conn = state.audit.get()?;
 
     let existing = queries::get_product_by_id(&conn, &path.product_id)?
@@ -106,11 +217,32 @@ pub async fn update_product(
     if existing.project_id != path.project_id {
         return Err(AppError::NotFound(msg::PRODUCT_NOT_FOUND.into()));
     }
+    input.validate_against(&existing)?;
+    if let Some(ref extends) = input.extends_updates_for_product_id {
+        check_renewal_base(
+            &conn,
+            &path.project_id,
+            Some(&existing.id),
+            extends.as_deref(),
+        )?;
+    }
+    if let Some(Some(ref upsell)) = input.upsell {
+        check_upsell(&conn, &path.project_id, &existing.id, upsell)?;
+    }
+    if let Some(ref features) = input.features {
+        check_upsell_highlights_kept(&conn, &existing.id, features)?;
+    }
 
-    queries::update_product(&conn, &path.product_id, &input)?
-        .or_not_found(msg::PRODUCT_NOT_FOUND)?;
+    let updated = queries::update_product(&conn, &path.product_id, &input)?;
+    if updated.is_none() {
+        // Another update may have moved it past expected_version since `existing`
+        let current = queries::get_product_by_id(&conn, &path.product_id)?
+            .or_not_found(msg::PRODUCT_NOT_FOUND)?;
+        check_expected_version(input.expected_version, current.version, &current, &input)?;
+    }
+    updated.or_not_found(msg::PRODUCT_NOT_FOUND)?;
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::User, Some(&ctx.member.user_id))
         .action(AuditAction::UpdateProduct)
         .resource("product", &path.product_id)
@@ -141,7 +273,7 @@ pub async fn delete_product(
         return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
     }
 
-    let conn = state.db.get()?;
+    let conn = state.org_db(&path.org_id).get()?;
     let audit_conn = state.audit.get()?;
 
     let existing = queries::get_product_by_id(&conn, &path.product_id)?
@@ -153,7 +285,7 @@ pub async fn delete_product(
 
     queries::soft_delete_product(&conn, &path.product_id)?;
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::User, Some(&ctx.member.user_id))
         .action(AuditAction::DeleteProduct)
         .resource("product", &path.product_id)
@@ -182,7 +314,7 @@ pub async fn restore_product(
         return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
     }
 
-    let conn = state.db.get()?;
+    let conn = state.org_db(&path.org_id).get()?;
     let audit_conn = state.audit.get()?;
 
     let existing = queries::get_deleted_product_by_id(&conn, &path.product_id)?
@@ -197,7 +329,7 @@ pub async fn restore_product(
     let product = queries::get_product_with_links(&conn, &path.product_id)?
         .ok_or_else(|| AppError::Internal(msg::PRODUCT_NOT_FOUND_AFTER_RESTORE.into()))?;
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::User, Some(&ctx.member.user_id))
         .action(AuditAction::RestoreProduct)
         .resource("product", &path.product_id)
@@ -0,0 +1,238 @@
+use axum::{
+    body::Bytes,
+    extract::{Extension, State},
+    http::{HeaderMap, header},
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::db::{AppState, outbox, queries};
+use crate::error::{AppError, OptionExt, Result, msg};
+use crate::external_url::validate_external_url;
+use crate::extractors::{Json, Path, Query};
+use crate::middleware::{OrgMemberContext, OrgProjectPath};
+use crate::models::{ActorType, AuditAction, OrgLimitName, Project, QuotaWarning};
+use crate::project_config::{self, ChangeAction, ChangeResource, ConfigChange, ConfigFormat};
+use crate::quota;
+use crate::util::AuditLogBuilder;
+
+#[derive(Debug, Deserialize)]
+pub struct ConfigExportQuery {
+    /// `json` (default) or `yaml`
+    #[serde(default)]
+    pub format: ConfigFormat,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConfigImportQuery {
+    /// Return the changes without making them
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConfigImportResponse {
+    pub dry_run: bool,
+    /// Planned changes for a dry run, otherwise the changes made
+    pub changes: Vec<ConfigChange>,
+}
+
+fn get_org_project(conn: &rusqlite::Connection, path: &OrgProjectPath) -> Result<Project> {
+    let project =
+        queries::get_project_by_id(conn, &path.project_id)?.or_not_found(msg::PROJECT_NOT_FOUND)?;
+    if project.org_id != path.org_id {
+        return Err(AppError::NotFound(msg::PROJECT_NOT_FOUND.into()));
+    }
+    Ok(project)
+}
+
+/// Export the project's settings, products, and provider links as a config
+/// document (no IDs, keys, or secrets).
+pub async fn export_project_config(
+    State(state): State<AppState>,
+    Path(path): Path<OrgProjectPath>,
+    Query(query): Query<ConfigExportQuery>,
+) -> Result<Response> {
+    let conn = state.org_db(&path.org_id).get()?;
+    let project = get_org_project(&conn, &path)?;
+
+    let config = project_config::export(&conn, &project)?;
+    Ok((
+        [(header::CONTENT_TYPE, query.format.content_type())],
+        query.format.write(&config)?,
+    )
+        .into_response())
+}
+
+/// Bring the project in line with a config document: update its settings and
+/// create, update, or delete products and provider links, all in one
+/// transaction. Send YAML with `Content-Type: application/yaml`.
+pub async fn import_project_config(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(path): Path<OrgProjectPath>,
+    Query(query): Query<ConfigImportQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<(Option<QuotaWarning>, Json<ConfigImportResponse>)> {
+    if !ctx.can_write_project() {
+        return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
+    }
+
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok());
+    let config = ConfigFormat::from_content_type(content_type).parse(&body)?;
+
+    // URLs the import changes get the same checks as a project update;
+    // the connection isn't held across the DNS lookups
+    let project = get_org_project(&state.org_db(&path.org_id).get()?, &path)?;
+    for (field, url, current) in [
+        (
+            "redirect_url",
+            &config.project.redirect_url,
+            &project.redirect_url,
+        ),
+        (
+            "email_webhook_url",
+            &config.project.email_webhook_url,
+            &project.email_webhook_url,
+        ),
+    ] {
+        if url != current {
+            validate_external_url(field, url.as_deref(), state.allow_localhost_urls).await?;
+        }
+    }
+
+    let mut conn = state.org_db(&path.org_id).get()?;
+    let audit_conn = state.audit.get()?;
+
+    // Same rule as updating the project directly
+    if config.project.email_from.is_some()
+        && config.project.email_from != project.email_from
+        && queries::get_org_resend_api_key(&conn, &path.org_id, &state.master_key)?.is_none()
+    {
+        return Err(AppError::BadRequest(
+            msg::EMAIL_FROM_REQUIRES_ORG_RESEND_KEY.into(),
+        ));
+    }
+
+    // Products the import adds count against the org's per-project limit
+    let check_quota = |conn: &rusqlite::Connection, changes: &[ConfigChange]| {
+        let added = products_added(changes);
+        if added <= 0 {
+            return Ok(None);
+        }
+        quota::check(
+            conn,
+            &path.org_id,
+            OrgLimitName::ProductsPerProject,
+            Some(&project.id),
+            added,
+            state.clock.now(),
+        )
+    };
+
+    if query.dry_run {
+        let changes = project_config::plan(&conn, &project, &config)?;
+        let quota = check_quota(&conn, &changes)?;
+        return Ok((
+            quota,
+            Json(ConfigImportResponse {
+                dry_run: true,
+                changes,
+            }),
+        ));
+    }
+
+    // Changes and their audit entries commit together
+    let (changes, quota) = outbox::with_audited_tx(
+        &mut conn,
+        |tx| {
+            let mut changes = project_config::plan(tx, &project, &config)?;
+            let quota = check_quota(tx, &changes)?;
+            project_config::apply(tx, &project.id, &mut changes, &state.master_key)?;
+            Ok((changes, quota))
+        },
+        |(changes, _)| {
+            changes
+                .iter()
+                .filter_map(|change| {
+                    let (action, resource_type) = audit_action(change);
+                    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+                        .actor(ActorType::User, Some(&ctx.member.user_id))
+                        .action(action)
+                        .resource(resource_type, change.id.as_deref().unwrap_or_default())
+                        .details(&serde_json::json!({
+                            "source": "config_import",
+                            "name": change.name,
+                            "product": change.product,
+                            "fields": change.fields,
+                            "impersonator": ctx.impersonator_json()
+                        }))
+                        .org(&path.org_id)
+                        .project(&path.project_id)
+                        .names(
+                            &ctx.audit_names()
+                                .resource(change.product.clone().unwrap_or(change.name.clone())),
+                        )
+                        .auth_method(&ctx.auth_method)
+                        .entry()
+                })
+                .collect::<Vec<_>>()
+        },
+    )?;
+    outbox::relay(&conn, &audit_conn);
+
+    if let Some(ref warning) = quota {
+        quota::audit_crossing(
+            AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+                .actor(ActorType::User, Some(&ctx.member.user_id))
+                .org(&path.org_id)
+                .project(&path.project_id)
+                .names(&ctx.audit_names())
+                .auth_method(&ctx.auth_method),
+            &path.org_id,
+            warning,
+        );
+    }
+
+    Ok((
+        quota,
+        Json(ConfigImportResponse {
+            dry_run: false,
+            changes,
+        }),
+    ))
+}
+
+/// Net number of products a set of changes creates (negative if it deletes more).
+fn products_added(changes: &[ConfigChange]) -> i64 {
+    changes
+        .iter()
+        .map(|change| match (change.resource, change.action) {
+            (ChangeResource::Product, ChangeAction::Create) => 1,
+            (ChangeResource::Product, ChangeAction::Delete) => -1,
+            _ => 0,
+        })
+        .sum()
+}
+
+/// The audit action and resource type the equivalent API call would log.
+fn audit_action(change: &ConfigChange) -> (AuditAction, &'static str) {
+    match (change.resource, change.action) {
+        (ChangeResource::Project, _) => (AuditAction::UpdateProject, "project"),
+        (ChangeResource::Product, ChangeAction::Create) => (AuditAction::CreateProduct, "product"),
+        (ChangeResource::Product, ChangeAction::Update) => (AuditAction::UpdateProduct, "product"),
+        (ChangeResource::Product, ChangeAction::Delete) => (AuditAction::DeleteProduct, "product"),
+        (ChangeResource::ProviderLink, ChangeAction::Create) => {
+            (AuditAction::CreateProviderLink, "provider_link")
+        }
+        (ChangeResource::ProviderLink, ChangeAction::Update) => {
+            (AuditAction::UpdateProviderLink, "provider_link")
+        }
+        (ChangeResource::ProviderLink, ChangeAction::Delete) => {
+            (AuditAction::DeleteProviderLink, "provider_link")
+        }
+    }
+}
@@ -3,7 +3,7 @@ use axum::{
     http::HeaderMap,
 };
 
-use crate::db::{AppState, queries};
+use crate::db::{AppState, outbox, queries};
 use crate::error::{AppError, OptionExt, Result, msg};
 use crate::extractors::{Json, Path};
 use crate::middleware::OrgMemberContext;
@@ -30,14 +30,24 @@ pub async fn create_project_member(
     if !ctx.can_write_project() {
         return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
     }
+    // Otherwise a temporary admin could make their access permanent
+    if ctx.is_temporary_elevation() {
+        return Err(AppError::Forbidden(
+            msg::TEMPORARY_ROLE_CANNOT_MANAGE_MEMBERS.into(),
+        ));
+    }
 
-    let conn = state.db.get()?;
+    let conn = state.org_db(&path.org_id).get()?;
     let audit_conn = state.audit.get()?;
 
     // Look up the org member by user_id and org_id
-    let target_member =
-        queries::get_org_member_with_user_by_user_and_org(&conn, &input.user_id, &path.org_id)?
-            .or_not_found(msg::ORG_MEMBER_NOT_FOUND)?;
+    let target_member = queries::get_org_member_with_user_by_user_and_org(
+        &conn,
+        &input.user_id,
+        &path.org_id,
+        &state.emails(),
+    )?
+    .or_not_found(msg::ORG_MEMBER_NOT_FOUND)?;
 
     // Check if already a project member
     if queries::get_project_member(&conn, &target_member.id, &path.project_id)?.is_some() {
@@ -49,7 +59,7 @@ pub async fn create_project_member(
     let project_member =
         queries::create_project_member(&conn, &target_member.id, &path.project_id, input.role)?;
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::User, Some(&ctx.member.user_id))
         .action(AuditAction::CreateProjectMember)
         .resource("project_member", &project_member.id)
@@ -85,11 +95,16 @@ pub async fn list_project_members(
     Path(path): Path<crate::middleware::OrgProjectPath>,
     Query(pagination): Query<PaginationQuery>,
 ) -> Result<Json<Paginated<ProjectMemberWithDetails>>> {
-    let conn = state.db.get()?;
+    let conn = state.org_db(&path.org_id).get()?;
     let limit = pagination.limit();
     let offset = pagination.offset();
-    let (members, total) =
-        queries::list_project_members_paginated(&conn, &path.project_id, limit, offset)?;
+    let (members, total) = queries::list_project_members_paginated(
+        &conn,
+        &path.project_id,
+        limit,
+        offset,
+        &state.emails(),
+    )?;
     Ok(Json(Paginated::new(members, total, limit, offset)))
 }
 
@@ -97,13 +112,14 @@ pub async fn get_project_member(
     State(state): State<AppState>,
     Path(path): Path<ProjectMemberPath>,
 ) -> Result<Json<ProjectMemberWithDetails>> {
-    let conn = state.db.get()?;
+    let conn = state.org_db(&path.org_id).get()?;
 
     let member = queries::get_project_member_by_user_and_project(
         &conn,
         &path.user_id,
         &path.org_id,
         &path.project_id,
+        &state.emails(),
     )?
     .or_not_found(msg::NOT_PROJECT_MEMBER)?;
 
@@ -120,8 +136,14 @@ pub async fn update_project_member(
     if !ctx.can_write_project() {
         return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
     }
+    // Otherwise a temporary admin could make their access permanent
+    if ctx.is_temporary_elevation() {
+        return Err(AppError::Forbidden(
+            msg::TEMPORARY_ROLE_CANNOT_MANAGE_MEMBERS.into(),
+        ));
+    }
 
-    let conn = state.db.get()?;
+    let mut conn = state.org_db(&path.org_id).get()?;
     let audit_conn = state.audit.get()?;
 
     let mut member = queries::get_project_member_by_user_and_project(
@@ -129,33 +151,39 @@ pub async fn update_project_member(
         &path.user_id,
         &path.org_id,
         &path.project_id,
+        &state.emails(),
     )?
     .or_not_found(msg::NOT_PROJECT_MEMBER)?;
 
-    let updated = queries::update_project_member(&conn, &member.id, &path.project_id, &input)?
-        .or_not_found(msg::NOT_PROJECT_MEMBER)?;
+    let details = serde_json::json!({
+        "role": input.role,
+        "impersonator": ctx.impersonator_json()
+    });
+    let updated = outbox::try_with_audited_tx(
+        &mut conn,
+        |tx| {
+            queries::update_project_member(tx, &member.id, &path.project_id, &input)?
+                .or_not_found(msg::NOT_PROJECT_MEMBER)
+        },
+        |_| {
+            AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+                .actor(ActorType::User, Some(&ctx.member.user_id))
+                .action(AuditAction::UpdateProjectMember)
+                .resource("project_member", &member.id)
+                .details(&details)
+                .org(&path.org_id)
+                .project(&path.project_id)
+                .names(&ctx.audit_names().resource_user(&member.name, &member.email))
+                .auth_method(&ctx.auth_method)
+                .checked_entry()
+        },
+    )?;
+    outbox::relay(&conn, &audit_conn);
 
     // Apply known changes to avoid re-fetching
     member.role = updated.role;
     member.updated_at = updated.updated_at;
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
-        .actor(ActorType::User, Some(&ctx.member.user_id))
-        .action(AuditAction::UpdateProjectMember)
-        .resource("project_member", &member.id)
-        .details(&serde_json::json!({
-            "role": input.role,
-            "impersonator": ctx.impersonator_json()
-        }))
-        .org(&path.org_id)
-        .project(&path.project_id)
-        .names(
-            &ctx.audit_names()
-                .resource_user(&member.name, &member.email),
-        )
-        .auth_method(&ctx.auth_method)
-        .save()?;
-
     Ok(Json(member))
 }
 
@@ -168,8 +196,14 @@ pub async fn delete_project_member(
     if !ctx.can_write_project() {
         return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
     }
+    // Otherwise a temporary admin could make their access permanent
+    if ctx.is_temporary_elevation() {
+        return Err(AppError::Forbidden(
+            msg::TEMPORARY_ROLE_CANNOT_MANAGE_MEMBERS.into(),
+        ));
+    }
 
-    let conn = state.db.get()?;
+    let conn = state.org_db(&path.org_id).get()?;
     let audit_conn = state.audit.get()?;
 
     // Fetch member first for audit log (before delete)
@@ -178,6 +212,7 @@ pub async fn delete_project_member(
         &path.user_id,
         &path.org_id,
         &path.project_id,
+        &state.emails(),
     )?
     .or_not_found(msg::NOT_PROJECT_MEMBER)?;
 
@@ -188,7 +223,7 @@ pub async fn delete_project_member(
         ));
     }
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::User, Some(&ctx.member.user_id))
         .action(AuditAction::DeleteProjectMember)
         .resource("project_member", &existing.id)
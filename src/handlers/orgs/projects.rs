@@ -2,18 +2,36 @@ use axum::{
     extract::{Extension, Query, State},
     http::HeaderMap,
 };
+use serde::Serialize;
+use serde_json::Value;
 
 use crate::db::{AppState, queries};
 use crate::error::{AppError, OptionExt, Result, msg};
+use crate::external_url::validate_external_url;
 use crate::extractors::{Json, Path, RestoreRequest};
 use crate::jwt;
 use crate::middleware::OrgMemberContext;
 use crate::models::{
-    ActorType, AuditAction, CreateProject, LemonSqueezyConfigMasked, ProjectPublic,
-    StripeConfigMasked, UpdateProject,
+    ActorType, AuditAction, ClientFlags, CreateProject, LemonSqueezyConfigMasked, OrgLimitName,
+    ProjectPublic, QuotaWarning, StripeConfigMasked, UpdateProject, check_expected_version,
+    mask_secret,
 };
 use crate::pagination::{Paginated, PaginationQuery};
+use crate::quota;
 use crate::util::AuditLogBuilder;
+use crate::webhook_payloads;
+
+use super::product_templates::org_templates;
+
+/// A new project, with the products created from its templates
+#[derive(Debug, Serialize)]
+pub struct ProjectCreated {
+    #[serde(flatten)]
+    pub project: ProjectPublic,
+    /// In the order the templates were given
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub product_ids: Vec<String>,
+}
 
 pub async fn create_project(
     State(state): State<AppState>,
@@ -21,15 +39,37 @@ pub async fn create_project(
     Path(org_id): Path<String>,
     headers: HeaderMap,
     Json(input): Json<CreateProject>,
-) -> Result<Json<ProjectPublic>> {
+) -> Result<(Option<QuotaWarning>, Json<ProjectCreated>)> {
     ctx.require_admin()?;
     input.validate()?;
-
-    let conn = state.db.get()?;
+    validate_external_url(
+        "redirect_url",
+        input.redirect_url.as_deref(),
+        state.allow_localhost_urls,
+    )
+    .await?;
+    validate_external_url(
+        "email_webhook_url",
+        input.email_webhook_url.as_deref(),
+        state.allow_localhost_urls,
+    )
+    .await?;
+
+    let mut conn = state.org_db(&org_id).get()?;
     let audit_conn = state.audit.get()?;
 
     // Look up org for audit log
     let org = queries::get_organization_by_id(&conn, &org_id)?.or_not_found(msg::ORG_NOT_FOUND)?;
+    let templates = org_templates(&conn, &org_id, &input.templates)?;
+
+    let quota = quota::check(
+        &conn,
+        &org_id,
+        OrgLimitName::Projects,
+        None,
+        1,
+        state.clock.now(),
+    )?;
 
     // Validate email_from requires org to have resend_api_key
     if input.email_from.is_some() {
@@ -45,21 +85,44 @@ pub async fn create_project(
     // Generate Ed25519 key pair
     let (private_key, public_key) = jwt::generate_keypair();
 
+    // The project and its template products are created together or not at all
+    let tx = conn.transaction()?;
     let project = queries::create_project(
-        &conn,
+        &tx,
         &org_id,
         &input,
         &private_key,
         &public_key,
         &state.master_key,
     )?;
-
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    let product_quota = if templates.is_empty() {
+        None
+    } else {
+        quota::check(
+            &tx,
+            &org_id,
+            OrgLimitName::ProductsPerProject,
+            Some(&project.id),
+            templates.len() as i64,
+            state.clock.now(),
+        )?
+    };
+    let products = queries::create_products_from_templates(&tx, &project.id, &templates)?;
+    tx.commit()?;
+    let product_ids: Vec<String> = products.into_iter().map(|p| p.id).collect();
+    state.org_dbs.index_project(&conn, &project)?;
+    // Creation is rare, so drop every cached miss rather than risk a new key
+    // (or a newly routed one) being answered as unknown for the TTL
+    state.project_misses.clear();
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::User, Some(&ctx.member.user_id))
         .action(AuditAction::CreateProject)
         .resource("project", &project.id)
         .details(&serde_json::json!({
             "name": input.name,
+            "templates": input.templates,
+            "product_ids": product_ids,
             "impersonator": ctx.impersonator_json()
         }))
         .org(&org_id)
@@ -67,12 +130,30 @@ pub async fn create_project(
         .names(
             &ctx.audit_names()
                 .resource(project.name.clone())
-                .org(org.name),
+                .org(org.name.clone()),
         )
         .auth_method(&ctx.auth_method)
         .save()?;
 
-    Ok(Json(project.into()))
+    for warning in [&quota, &product_quota].into_iter().flatten() {
+        quota::audit_crossing(
+            AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+                .actor(ActorType::User, Some(&ctx.member.user_id))
+                .org(&org_id)
+                .names(&ctx.audit_names().org(org.name.clone()))
+                .auth_method(&ctx.auth_method),
+            &org_id,
+            warning,
+        );
+    }
+
+    Ok((
+        quota.or(product_quota),
+        Json(ProjectCreated {
+            project: project.into(),
+            product_ids,
+        }),
+    ))
 }
 
 pub async fn list_projects(
@@ -81,7 +162,7 @@ pub async fn list_projects(
     Path(org_id): Path<String>,
     Query(pagination): Query<PaginationQuery>,
 ) -> Result<Json<Paginated<ProjectPublic>>> {
-    let conn = state.db.get()?;
+    let conn = state.org_db(&org_id).get()?;
     let limit = pagination.limit();
     let offset = pagination.offset();
 
@@ -107,7 +188,7 @@ pub async fn get_project(
     State(state): State<AppState>,
     Path(path): Path<crate::middleware::OrgProjectPath>,
 ) -> Result<Json<ProjectPublic>> {
-    let conn = state.db.get()?;
+    let conn = state.org_db(&path.org_id).get()?;
     let project = queries::get_project_by_id(&conn, &path.project_id)?
         .or_not_found(msg::PROJECT_NOT_FOUND)?;
 
@@ -118,6 +199,65 @@ pub async fn get_project(
     Ok(Json(project.into()))
 }
 
+/// GET /orgs/{org_id}/projects/{project_id}/webhook-schema
+/// JSON Schema for the webhook payloads the project receives, in its
+/// `webhook_schema_version` (the latest if it hasn't pinned one).
+pub async fn get_webhook_schema(
+    State(state): State<AppState>,
+    Path(path): Path<crate::middleware::OrgProjectPath>,
+) -> Result<Json<Value>> {
+    let conn = state.org_db(&path.org_id).get()?;
+    let project = queries::get_project_by_id(&conn, &path.project_id)?
+        .or_not_found(msg::PROJECT_NOT_FOUND)?;
+
+    if project.org_id != path.org_id {
+        return Err(AppError::NotFound(msg::PROJECT_NOT_FOUND.into()));
+    }
+
+    Ok(Json(webhook_payloads::schema_document(
+        project.webhook_schema(),
+    )))
+}
+
+/// Email webhook settings, with the signing secret masked
+#[derive(Debug, Serialize)]
+pub struct EmailWebhookResponse {
+    pub project_id: String,
+    pub email_webhook_url: Option<String>,
+    /// None when the project's webhooks are unsigned
+    pub email_webhook_secret: Option<String>,
+    /// The project's version, for `expected_version` when changing them
+    pub version: i64,
+}
+
+/// GET /orgs/{org_id}/projects/{project_id}/email-webhook
+/// The project's email webhook settings (signing secret masked).
+pub async fn get_email_webhook(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(path): Path<crate::middleware::OrgProjectPath>,
+) -> Result<Json<EmailWebhookResponse>> {
+    // Only admins can view the signing secret, even masked
+    ctx.require_admin()?;
+
+    let conn = state.org_db(&path.org_id).get()?;
+    let project = queries::get_project_by_id(&conn, &path.project_id)?
+        .or_not_found(msg::PROJECT_NOT_FOUND)?;
+
+    if project.org_id != path.org_id {
+        return Err(AppError::NotFound(msg::PROJECT_NOT_FOUND.into()));
+    }
+
+    let secret = queries::decrypt_project_webhook_secret(&conn, &project, &state.master_key)?;
+
+    Ok(Json(EmailWebhookResponse {
+        email_webhook_secret: secret.as_deref().map(mask_secret),
+        project_id: project.id,
+        email_webhook_url: project.email_webhook_url,
+        version: project.version,
+    }))
+}
+
 pub async fn update_project(
     State(state): State<AppState>,
     Extension(ctx): Extension<OrgMemberContext>,
@@ -129,8 +269,20 @@ pub async fn update_project(
         return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
     }
     input.validate()?;
-
-    let conn = state.db.get()?;
+    validate_external_url(
+        "redirect_url",
+        input.redirect_url.as_ref().and_then(Option::as_deref),
+        state.allow_localhost_urls,
+    )
+    .await?;
+    validate_external_url(
+        "email_webhook_url",
+        input.email_webhook_url.as_ref().and_then(Option::as_deref),
+        state.allow_localhost_urls,
+    )
+    .await?;
+
+    let conn = state.org_db(&path.org_id).get()?;
     let audit_conn = state.audit.get()?;
 
     // Look up org and project for audit log
@@ -151,15 +303,30 @@ pub async fn update_project(
         }
     }
 
-    let project = queries::update_project(&conn, &path.project_id, &input)?
-        .or_not_found(msg::PROJECT_NOT_FOUND)?;
+    let updated = queries::update_project(&conn, &path.project_id, &input, &state.master_key)?;
+    if updated.is_none() {
+        // Another update may have moved it past expected_version since `existing`
+        let current = ProjectPublic::from(
+            queries::get_project_by_id(&conn, &path.project_id)?
+                .or_not_found(msg::PROJECT_NOT_FOUND)?,
+        );
+        check_expected_version(input.expected_version, current.version, &current, &input)?;
+    }
+    let project = updated.or_not_found(msg::PROJECT_NOT_FOUND)?;
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::User, Some(&ctx.member.user_id))
         .action(AuditAction::UpdateProject)
         .resource("project", &path.project_id)
         .details(&serde_json::json!({
             "name": input.name,
+            "jwt_issuer": input.jwt_issuer,
+            "jwt_audience": input.jwt_audience,
+            "client_flags": input.client_flags,
+            "min_client_version": input.min_client_version,
+            "webhook_schema_version": input.webhook_schema_version,
+            // Whether a secret was set (true) or removed (false), never the secret
+            "email_webhook_secret": input.email_webhook_secret.as_ref().map(Option::is_some),
             "impersonator": ctx.impersonator_json()
         }))
         .org(&path.org_id)
@@ -171,6 +338,49 @@ pub async fn update_project(
     Ok(Json(project.into()))
 }
 
+/// PUT /orgs/{org_id}/projects/{project_id}/client-flags
+/// Replace the flags `/validate` and `/refresh` return, and the minimum app
+/// version. Apps see the change on their next call.
+pub async fn update_client_flags(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(path): Path<crate::middleware::OrgProjectPath>,
+    headers: HeaderMap,
+    Json(input): Json<ClientFlags>,
+) -> Result<Json<ClientFlags>> {
+    if !ctx.can_write_project() {
+        return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
+    }
+    input.validate()?;
+
+    let conn = state.org_db(&path.org_id).get()?;
+    let audit_conn = state.audit.get()?;
+
+    let existing = queries::get_project_by_id(&conn, &path.project_id)?
+        .or_not_found(msg::PROJECT_NOT_FOUND)?;
+    let project = queries::set_project_client_flags(&conn, &path.project_id, &input)?
+        .or_not_found(msg::PROJECT_NOT_FOUND)?;
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.member.user_id))
+        .action(AuditAction::UpdateClientFlags)
+        .resource("project", &path.project_id)
+        .details(&serde_json::json!({
+            "client_flags": project.client_flags,
+            "min_client_version": project.min_client_version,
+            "previous_client_flags": existing.client_flags,
+            "previous_min_client_version": existing.min_client_version,
+            "impersonator": ctx.impersonator_json()
+        }))
+        .org(&path.org_id)
+        .project(&path.project_id)
+        .names(&ctx.audit_names().resource(existing.name))
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    Ok(Json(ClientFlags::from(&project)))
+}
+
 pub async fn delete_project(
     State(state): State<AppState>,
     Extension(ctx): Extension<OrgMemberContext>,
@@ -179,7 +389,7 @@ pub async fn delete_project(
 ) -> Result<Json<serde_json::Value>> {
     ctx.require_admin()?;
 
-    let conn = state.db.get()?;
+    let conn = state.org_db(&path.org_id).get()?;
     let audit_conn = state.audit.get()?;
 
     // Look up org and project for audit log
@@ -190,7 +400,7 @@ pub async fn delete_project(
 
     queries::soft_delete_project(&conn, &path.project_id)?;
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::User, Some(&ctx.member.user_id))
         .action(AuditAction::DeleteProject)
         .resource("project", &path.project_id)
@@ -213,6 +423,8 @@ pub struct PaymentConfigResponse {
     pub stripe_config: Option<StripeConfigMasked>,
     pub ls_config: Option<LemonSqueezyConfigMasked>,
     pub payment_provider: Option<String>,
+    /// The org's version, for `expected_version` when changing the config
+    pub version: i64,
 }
 
 /// Get payment provider configuration for the organization (masked for security)
@@ -224,7 +436,7 @@ pub async fn get_payment_config(
     // Only admins can view payment config
     ctx.require_admin()?;
 
-    let conn = state.db.get()?;
+    let conn = state.org_db(&org_id).get()?;
     let org = queries::get_organization_by_id(&conn, &org_id)?.or_not_found(msg::ORG_NOT_FOUND)?;
 
     let stripe_config = queries::get_org_stripe_config(&conn, &org_id, &state.master_key)?
@@ -240,6 +452,7 @@ pub async fn get_payment_config(
         stripe_config,
         ls_config,
         payment_provider: org.payment_provider,
+        version: org.version,
     }))
 }
 
@@ -253,7 +466,7 @@ pub async fn restore_project(
 ) -> Result<Json<ProjectPublic>> {
     ctx.require_admin()?;
 
-    let conn = state.db.get()?;
+    let conn = state.org_db(&path.org_id).get()?;
     let audit_conn = state.audit.get()?;
 
     // Look up org for audit log
@@ -271,8 +484,9 @@ pub async fn restore_project(
 
     let project = queries::get_project_by_id(&conn, &path.project_id)?
         .ok_or_else(|| AppError::Internal(msg::PROJECT_NOT_FOUND_AFTER_RESTORE.into()))?;
+    state.project_misses.invalidate(&project.public_key);
 
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::User, Some(&ctx.member.user_id))
         .action(AuditAction::RestoreProject)
         .resource("project", &path.project_id)
@@ -0,0 +1,203 @@
+//! Share links: expiring, revocable URLs that show a customer their license
+//! details without an account or API key.
+//!
+//! The token in the URL is the only credential. It is scoped to one license,
+//! and only its hash is stored.
+
+use axum::{
+    extract::{Extension, State},
+    http::HeaderMap,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::db::{AppState, queries};
+use crate::error::{AppError, OptionExt, Result, msg};
+use crate::extractors::{Json, Path};
+use crate::middleware::OrgMemberContext;
+use crate::models::{ActorType, AuditAction, EmailAddress, ShareLink};
+use crate::util::AuditLogBuilder;
+
+use super::LicensePath;
+
+/// Default share link lifetime
+const DEFAULT_EXPIRES_IN_HOURS: i64 = 24;
+/// Longest lifetime a share link can be created with (30 days)
+const MAX_EXPIRES_IN_HOURS: i64 = 720;
+
+#[derive(Deserialize)]
+pub struct ShareLinkPath {
+    pub org_id: String,
+    pub project_id: String,
+    pub license_id: String,
+    pub share_link_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateShareLinkBody {
+    /// Customer email to show (masked) on the page. Must match the license.
+    pub email: Option<String>,
+    /// Link lifetime in hours (default 24, max 720)
+    pub expires_in_hours: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct CreateShareLinkResponse {
+    #[serde(flatten)]
+    pub share_link: ShareLink,
+    /// Token in the URL (returned once, not stored)
+    pub token: String,
+    /// Public page for the customer
+    pub url: String,
+}
+
+/// Mask an email for display: `jane@example.com` becomes `j***@example.com`.
+fn mask_email(email: &str) -> String {
+    let email = email.trim();
+    match email.split_once('@') {
+        Some((local, domain)) => {
+            let first: String = local.chars().take(1).collect();
+            format!("{}***@{}", first, domain)
+        }
+        None => "***".to_string(),
+    }
+}
+
+/// POST /orgs/{org_id}/projects/{project_id}/licenses/{license_id}/share-link
+/// Create an expiring link to a customer-facing page for this license.
+pub async fn create_share_link(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(mut path): Path<LicensePath>,
+    headers: HeaderMap,
+    Json(body): Json<CreateShareLinkBody>,
+) -> Result<Json<CreateShareLinkResponse>> {
+    if !ctx.can_write_project() {
+        return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
+    }
+
+    let expires_in_hours = body.expires_in_hours.unwrap_or(DEFAULT_EXPIRES_IN_HOURS);
+    if !(1..=MAX_EXPIRES_IN_HOURS).contains(&expires_in_hours) {
+        return Err(AppError::BadRequest(msg::SHARE_LINK_EXPIRY_INVALID.into()));
+    }
+
+    let conn = state.org_db(&path.org_id).get()?;
+    path.license_id = queries::resolve_admin_license_id(&conn, &path.project_id, &path.license_id)?;
+    let audit_conn = state.audit.get()?;
+
+    let license = queries::get_license_by_id(&conn, &path.license_id)?
+        .or_not_found(msg::LICENSE_NOT_FOUND)?;
+
+    // Verify license belongs to a product in this project
+    let product = queries::get_product_by_id(&conn, &license.product_id)?
+        .or_not_found(msg::LICENSE_NOT_FOUND)?;
+
+    if product.project_id != path.project_id {
+        return Err(AppError::NotFound(msg::LICENSE_NOT_FOUND.into()));
+    }
+
+    // Only the hash of the purchase email is stored, so the caller supplies the
+    // address and it must match before a masked copy goes on the page
+    let masked_email = match body.email.as_deref() {
+        Some(email) => {
+            let email = EmailAddress::parse(email)?;
+            let matches = state.email_hasher.find_by_hash(
+                &email,
+                "share_link",
+                |hash| Ok::<_, AppError>(license.email_hash.as_deref() == Some(hash)),
+                |matched| *matched,
+            )?;
+            if !matches {
+                return Err(AppError::BadRequest(msg::SHARE_LINK_EMAIL_MISMATCH.into()));
+            }
+            Some(mask_email(email.as_str()))
+        }
+        None => None,
+    };
+
+    let project = queries::get_project_by_id(&conn, &path.project_id)?
+        .or_not_found(msg::PROJECT_NOT_FOUND)?;
+
+    let expires_at = chrono::Utc::now().timestamp() + expires_in_hours * 3600;
+    let (share_link, token) = queries::create_share_link(
+        &conn,
+        &license.id,
+        masked_email.as_deref(),
+        Some(&ctx.member.user_id),
+        expires_at,
+    )?;
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.member.user_id))
+        .action(AuditAction::CreateShareLink)
+        .resource("license", &license.id)
+        .details(&serde_json::json!({
+            "share_link_id": share_link.id,
+            "expires_at": share_link.expires_at,
+            "impersonator": ctx.impersonator_json()
+        }))
+        .org(&path.org_id)
+        .project(&path.project_id)
+        .names(&ctx.audit_names().project(project.name.clone()))
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    let url = format!("{}/shared/license/{}", state.base_url, token);
+    Ok(Json(CreateShareLinkResponse {
+        share_link,
+        token,
+        url,
+    }))
+}
+
+/// POST /orgs/{org_id}/projects/{project_id}/licenses/{license_id}/share-link/{share_link_id}/revoke
+/// Revoke a share link. The page stops working immediately.
+pub async fn revoke_share_link(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(mut path): Path<ShareLinkPath>,
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>> {
+    if !ctx.can_write_project() {
+        return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
+    }
+
+    let conn = state.org_db(&path.org_id).get()?;
+    path.license_id = queries::resolve_admin_license_id(&conn, &path.project_id, &path.license_id)?;
+    let audit_conn = state.audit.get()?;
+
+    let license = queries::get_license_by_id(&conn, &path.license_id)?
+        .or_not_found(msg::LICENSE_NOT_FOUND)?;
+
+    let product = queries::get_product_by_id(&conn, &license.product_id)?
+        .or_not_found(msg::LICENSE_NOT_FOUND)?;
+
+    if product.project_id != path.project_id {
+        return Err(AppError::NotFound(msg::LICENSE_NOT_FOUND.into()));
+    }
+
+    let share_link = queries::get_share_link(&conn, &license.id, &path.share_link_id)?
+        .or_not_found(msg::SHARE_LINK_NOT_FOUND)?;
+
+    if !queries::revoke_share_link(&conn, &share_link.id)? {
+        return Err(AppError::BadRequest(msg::SHARE_LINK_ALREADY_REVOKED.into()));
+    }
+
+    let project = queries::get_project_by_id(&conn, &path.project_id)?
+        .or_not_found(msg::PROJECT_NOT_FOUND)?;
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.member.user_id))
+        .action(AuditAction::RevokeShareLink)
+        .resource("license", &license.id)
+        .details(&serde_json::json!({
+            "share_link_id": share_link.id,
+            "impersonator": ctx.impersonator_json()
+        }))
+        .org(&path.org_id)
+        .project(&path.project_id)
+        .names(&ctx.audit_names().project(project.name.clone()))
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    Ok(Json(serde_json::json!({ "success": true })))
+}
@@ -0,0 +1,192 @@
+use axum::{
+    extract::{Extension, State},
+    http::HeaderMap,
+};
+use serde::Deserialize;
+
+use crate::db::{AppState, queries};
+use crate::error::{AppError, OptionExt, Result, msg};
+use crate::extractors::{Json, Path, Query};
+use crate::middleware::{OrgMemberContext, OrgProjectPath};
+use crate::models::{
+    ActorType, AuditAction, GrantTemporaryRole, MAX_TEMPORARY_ROLE_MINUTES, TemporaryRoleGrant,
+};
+use crate::pagination::{Paginated, clamp_limit, clamp_offset};
+use crate::util::AuditLogBuilder;
+
+use super::ProjectMemberPath;
+
+#[derive(Debug, Deserialize)]
+pub struct TemporaryRoleQuery {
+    /// Include expired and revoked grants (default false)
+    #[serde(default)]
+    pub include_inactive: bool,
+    /// Max results to return (default 50, max 100)
+    pub limit: Option<i64>,
+    /// Offset for pagination (default 0)
+    pub offset: Option<i64>,
+}
+
+/// POST /orgs/{org_id}/projects/{project_id}/members/{user_id}/temporary-role
+/// Give an org member a project role for a limited time (break-glass access).
+/// Replaces any grant the member already has on the project.
+pub async fn grant_temporary_role(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(path): Path<ProjectMemberPath>,
+    headers: HeaderMap,
+    Json(input): Json<GrantTemporaryRole>,
+) -> Result<Json<TemporaryRoleGrant>> {
+    if !ctx.can_write_project() {
+        return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
+    }
+    if ctx.is_temporary_elevation() {
+        return Err(AppError::Forbidden(
+            msg::TEMPORARY_ROLE_CANNOT_MANAGE_MEMBERS.into(),
+        ));
+    }
+    if path.user_id == ctx.member.user_id {
+        return Err(AppError::BadRequest(
+            msg::CANNOT_GRANT_SELF_TEMPORARY_ROLE.into(),
+        ));
+    }
+    if !(1..=MAX_TEMPORARY_ROLE_MINUTES).contains(&input.expires_in_minutes) {
+        return Err(AppError::BadRequest(
+            msg::TEMPORARY_ROLE_DURATION_INVALID.into(),
+        ));
+    }
+
+    let mut conn = state.org_db(&path.org_id).get()?;
+    let audit_conn = state.audit.get()?;
+
+    let target_member = queries::get_org_member_with_user_by_user_and_org(
+        &conn,
+        &path.user_id,
+        &path.org_id,
+        &state.emails(),
+    )?
+    .or_not_found(msg::ORG_MEMBER_NOT_FOUND)?;
+
+    if target_member.role.has_implicit_project_access() {
+        return Err(AppError::BadRequest(msg::TEMPORARY_ROLE_NOT_NEEDED.into()));
+    }
+
+    let grant = queries::create_temporary_role_grant(
+        &mut conn,
+        &target_member.id,
+        &path.project_id,
+        &input,
+        &ctx.member.user_id,
+        state.clock.now(),
+    )?;
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.member.user_id))
+        .action(AuditAction::GrantTemporaryRole)
+        .resource("temporary_role_grant", &grant.id)
+        .details(&serde_json::json!({
+            "user_id": path.user_id,
+            "project_id": path.project_id,
+            "role": grant.role,
+            "expires_at": grant.expires_at,
+            "reason": grant.reason,
+            "impersonator": ctx.impersonator_json()
+        }))
+        .org(&path.org_id)
+        .project(&path.project_id)
+        .names(
+            &ctx.audit_names()
+                .resource_user(&target_member.name, &target_member.email),
+        )
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    Ok(Json(grant))
+}
+
+/// DELETE /orgs/{org_id}/projects/{project_id}/members/{user_id}/temporary-role
+/// End a member's temporary role early. Members can always end their own.
+pub async fn revoke_temporary_role(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(path): Path<ProjectMemberPath>,
+    headers: HeaderMap,
+) -> Result<Json<TemporaryRoleGrant>> {
+    let is_self = path.user_id == ctx.member.user_id;
+    if !is_self {
+        if !ctx.can_write_project() {
+            return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
+        }
+        if ctx.is_temporary_elevation() {
+            return Err(AppError::Forbidden(
+                msg::TEMPORARY_ROLE_CANNOT_MANAGE_MEMBERS.into(),
+            ));
+        }
+    }
+
+    let conn = state.org_db(&path.org_id).get()?;
+    let audit_conn = state.audit.get()?;
+    let now = state.clock.now();
+
+    let target_member = queries::get_org_member_with_user_by_user_and_org(
+        &conn,
+        &path.user_id,
+        &path.org_id,
+        &state.emails(),
+    )?
+    .or_not_found(msg::ORG_MEMBER_NOT_FOUND)?;
+
+    let active =
+        queries::get_active_temporary_role_grant(&conn, &target_member.id, &path.project_id, now)?
+            .or_not_found(msg::TEMPORARY_ROLE_NOT_FOUND)?;
+
+    let grant = queries::revoke_temporary_role_grant(&conn, &active.id, &ctx.member.user_id, now)?
+        .or_not_found(msg::TEMPORARY_ROLE_NOT_FOUND)?;
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.member.user_id))
+        .action(AuditAction::RevokeTemporaryRole)
+        .resource("temporary_role_grant", &grant.id)
+        .details(&serde_json::json!({
+            "user_id": path.user_id,
+            "project_id": path.project_id,
+            "role": grant.role,
+            "expires_at": grant.expires_at,
+            "impersonator": ctx.impersonator_json()
+        }))
+        .org(&path.org_id)
+        .project(&path.project_id)
+        .names(
+            &ctx.audit_names()
+                .resource_user(&target_member.name, &target_member.email),
+        )
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    Ok(Json(grant))
+}
+
+/// GET /orgs/{org_id}/projects/{project_id}/temporary-roles
+/// Temporary roles on the project, newest first. Only grants still in effect
+/// unless `?include_inactive=true`.
+pub async fn list_temporary_roles(
+    State(state): State<AppState>,
+    Path(path): Path<OrgProjectPath>,
+    Query(query): Query<TemporaryRoleQuery>,
+) -> Result<Json<Paginated<TemporaryRoleGrant>>> {
+    let conn = state.org_db(&path.org_id).get()?;
+
+    let limit = clamp_limit(query.limit);
+    let offset = clamp_offset(query.offset);
+    let active_at = (!query.include_inactive).then(|| state.clock.now());
+
+    let (grants, total) = queries::list_temporary_role_grants_paginated(
+        &conn,
+        &path.project_id,
+        active_at,
+        limit,
+        offset,
+    )?;
+
+    Ok(Json(Paginated::new(grants, total, limit, offset)))
+}
@@ -0,0 +1,137 @@
+//! Terms of sale: the versioned EULA / refund policy buyers accept at /buy.
+//! A version is published once and then only corrected while no license has
+//! been sold under it; after that, changes mean publishing a new version.
+
+use axum::{
+    extract::{Extension, State},
+    http::HeaderMap,
+};
+use serde::Deserialize;
+
+use crate::db::{AppState, queries};
+use crate::error::{AppError, OptionExt, Result, msg};
+use crate::external_url::validate_external_url;
+use crate::extractors::{Json, Path};
+use crate::middleware::{OrgMemberContext, OrgProjectPath};
+use crate::models::{ActorType, AuditAction, CreateTerms, Terms, UpdateTerms};
+use crate::util::AuditLogBuilder;
+
+#[derive(Deserialize)]
+pub struct TermsPath {
+    pub org_id: String,
+    pub project_id: String,
+    pub terms_id: String,
+}
+
+/// POST /orgs/{org_id}/projects/{project_id}/terms
+/// Publish a terms version. It becomes the current one at `effective_at`
+/// (default: now), until a later version takes effect.
+pub async fn create_terms(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(path): Path<OrgProjectPath>,
+    headers: HeaderMap,
+    Json(input): Json<CreateTerms>,
+) -> Result<Json<Terms>> {
+    if !ctx.can_write_project() {
+        return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
+    }
+    input.validate()?;
+    validate_external_url("url", Some(&input.url), state.allow_localhost_urls).await?;
+
+    let conn = state.org_db(&path.org_id).get()?;
+    let audit_conn = state.audit.get()?;
+
+    let effective_at = input.effective_at.unwrap_or_else(|| state.clock.now());
+    // The idx_terms_project_version index refuses a version published twice
+    let terms = queries::create_terms(
+        &conn,
+        &path.project_id,
+        &input,
+        effective_at,
+        Some(&ctx.member.user_id),
+    )
+    .map_err(|e| match e {
+        AppError::Database(rusqlite::Error::SqliteFailure(err, _))
+            if err.code == rusqlite::ErrorCode::ConstraintViolation =>
+        {
+            AppError::Conflict(msg::TERMS_VERSION_EXISTS.into())
+        }
+        e => e,
+    })?;
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.member.user_id))
+        .action(AuditAction::CreateTerms)
+        .resource("terms", &terms.id)
+        .details(&serde_json::json!({
+            "version": terms.version,
+            "url": terms.url,
+            "effective_at": terms.effective_at,
+            "impersonator": ctx.impersonator_json()
+        }))
+        .org(&path.org_id)
+        .project(&path.project_id)
+        .names(&ctx.audit_names())
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    Ok(Json(terms))
+}
+
+/// GET /orgs/{org_id}/projects/{project_id}/terms
+/// The project's terms versions, newest effective first.
+pub async fn list_terms(
+    State(state): State<AppState>,
+    Path(path): Path<OrgProjectPath>,
+) -> Result<Json<Vec<Terms>>> {
+    let conn = state.org_db(&path.org_id).get()?;
+    Ok(Json(queries::list_terms(&conn, &path.project_id)?))
+}
+
+/// PUT /orgs/{org_id}/projects/{project_id}/terms/{terms_id}
+/// Correct a version's `url` or `effective_at`. Refused with 409 once a
+/// license has been sold under the version.
+pub async fn update_terms(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<OrgMemberContext>,
+    Path(path): Path<TermsPath>,
+    headers: HeaderMap,
+    Json(input): Json<UpdateTerms>,
+) -> Result<Json<Terms>> {
+    if !ctx.can_write_project() {
+        return Err(AppError::Forbidden(msg::INSUFFICIENT_PERMISSIONS.into()));
+    }
+    validate_external_url("url", input.url.as_deref(), state.allow_localhost_urls).await?;
+
+    let conn = state.org_db(&path.org_id).get()?;
+    let audit_conn = state.audit.get()?;
+
+    let existing = queries::get_terms(&conn, &path.project_id, &path.terms_id)?
+        .or_not_found(msg::TERMS_NOT_FOUND)?;
+    if queries::terms_accepted_by_license(&conn, &path.project_id, &existing.version)? {
+        return Err(AppError::Conflict(msg::TERMS_IN_USE.into()));
+    }
+
+    queries::update_terms(&conn, &existing.id, &input)?;
+    let terms = queries::get_terms(&conn, &path.project_id, &existing.id)?
+        .or_not_found(msg::TERMS_NOT_FOUND)?;
+
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::User, Some(&ctx.member.user_id))
+        .action(AuditAction::UpdateTerms)
+        .resource("terms", &terms.id)
+        .details(&serde_json::json!({
+            "version": terms.version,
+            "url": input.url,
+            "effective_at": input.effective_at,
+            "impersonator": ctx.impersonator_json()
+        }))
+        .org(&path.org_id)
+        .project(&path.project_id)
+        .names(&ctx.audit_names())
+        .auth_method(&ctx.auth_method)
+        .save()?;
+
+    Ok(Json(terms))
+}
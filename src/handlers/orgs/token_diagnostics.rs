@@ -0,0 +1,252 @@
+//! Token diagnostics: explain why a customer's license token is rejected.
+//!
+//! Each verification step runs separately, so instead of "signature
+//! verification failed" the response names the first step that failed and
+//! still reports what the later steps found.
+
+use axum::extract::State;
+use serde::{Deserialize, Serialize};
+
+use crate::db::{AppState, queries};
+use crate::error::{AppError, OptionExt, Result, msg};
+use crate::extractors::{Json, Path};
+use crate::jwt::{self, TokenHeader};
+use crate::middleware::OrgProjectPath;
+
+#[derive(Debug, Deserialize)]
+pub struct DiagnoseTokenBody {
+    pub token: String,
+}
+
+/// Verification steps, in the order they're checked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosisStage {
+    /// Not a JWT, or the header isn't readable
+    Header,
+    /// Header is fine but the license claims don't parse
+    Payload,
+    /// `kid` doesn't name any signing key in the org
+    Kid,
+    /// Signature doesn't verify with this project's key
+    Signature,
+    /// `iss` or `aud` isn't accepted by this project
+    Issuer,
+    /// `exp` has passed (refresh still accepts these)
+    Expired,
+    /// The token's JTI has been revoked
+    Revoked,
+    /// No device holds the token's JTI
+    Device,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenDiagnosis {
+    /// True if every check passed
+    pub valid: bool,
+    /// First check that failed
+    pub failed_stage: Option<DiagnosisStage>,
+    pub reason: Option<String>,
+    pub header: Option<TokenHeader>,
+    /// `kid` of this project's signing key
+    pub project_kid: String,
+    /// Whether the token's `kid` names a key in this org (None if it has no `kid`)
+    pub kid_known: Option<bool>,
+    pub signature_valid: bool,
+    /// Project whose key verifies the signature - another project in the org
+    /// if the token was sent to the wrong one
+    pub signed_by_project_id: Option<String>,
+    pub product_id: Option<String>,
+    pub license_id: Option<String>,
+    /// Remaining fields need a verified signature
+    pub claims_accepted: Option<bool>,
+    pub expires_at: Option<i64>,
+    pub expired: Option<bool>,
+    pub jti: Option<String>,
+    pub jti_revoked: Option<bool>,
+    pub device_exists: Option<bool>,
+    /// `kid` recorded when the device was activated
+    pub device_signed_with_kid: Option<String>,
+}
+
+impl TokenDiagnosis {
+    fn new(project_kid: String) -> Self {
+        Self {
+            valid: false,
+            failed_stage: None,
+            reason: None,
+            header: None,
+            project_kid,
+            kid_known: None,
+            signature_valid: false,
+            signed_by_project_id: None,
+            product_id: None,
+            license_id: None,
+            claims_accepted: None,
+            expires_at: None,
+            expired: None,
+            jti: None,
+            jti_revoked: None,
+            device_exists: None,
+            device_signed_with_kid: None,
+        }
+    }
+
+    /// Record a failed check. Only the first one is kept.
+    fn fail(&mut self, stage: DiagnosisStage, reason: impl Into<String>) {
+        if self.failed_stage.is_none() {
+            self.failed_stage = Some(stage);
+            self.reason = Some(reason.into());
+        }
+    }
+
+    fn finish(mut self) -> Json<Self> {
+        self.valid = self.failed_stage.is_none();
+        Json(self)
+    }
+}
+
+/// Message from a verification error, without the error kind prefix
+fn reason(err: AppError) -> String {
+    match err {
+        AppError::BadRequest(msg) | AppError::Internal(msg) => msg,
+        other => other.to_string(),
+    }
+}
+
+/// POST /orgs/{org_id}/projects/{project_id}/diagnose-token
+/// Check a license token step by step against this project, without
+/// requiring it to be valid.
+pub async fn diagnose_token(
+    State(state): State<AppState>,
+    Path(path): Path<OrgProjectPath>,
+    Json(body): Json<DiagnoseTokenBody>,
+) -> Result<Json<TokenDiagnosis>> {
+    let conn = state.org_db(&path.org_id).get()?;
+    let project = queries::get_project_by_id(&conn, &path.project_id)?
+        .filter(|project| project.org_id == path.org_id)
+        .or_not_found(msg::PROJECT_NOT_FOUND)?;
+
+    let token = body.token.trim();
+    let mut diagnosis = TokenDiagnosis::new(jwt::key_id(&project.public_key)?);
+
+    let header = match jwt::decode_header(token) {
+        Ok(header) => header,
+        Err(e) => {
+            diagnosis.fail(DiagnosisStage::Header, reason(e));
+            return Ok(diagnosis.finish());
+        }
+    };
+    let kid = header.kid.clone();
+    diagnosis.header = Some(header);
+
+    match jwt::decode_unverified(token) {
+        Ok(claims) => diagnosis.product_id = Some(claims.product_id),
+        Err(e) => {
+            diagnosis.fail(DiagnosisStage::Payload, reason(e));
+            return Ok(diagnosis.finish());
+        }
+    }
+
+    // Keys of every project in the org, to name the one a stray token belongs to
+    let org_keys = queries::list_projects_for_org(&conn, &path.org_id)?
+        .into_iter()
+        .map(|p| Ok((jwt::key_id(&p.public_key)?, p)))
+        .collect::<Result<Vec<_>>>()?;
+
+    if let Some(ref kid) = kid {
+        let known = org_keys.iter().any(|(key_id, _)| key_id == kid);
+        diagnosis.kid_known = Some(known);
+        if !known {
+            diagnosis.fail(
+                DiagnosisStage::Kid,
+                "kid does not match any signing key in this organization",
+            );
+        }
+    }
+
+    let verified = match jwt::verify_signature(token, &project.public_key) {
+        Ok(verified) => {
+            diagnosis.signature_valid = true;
+            diagnosis.signed_by_project_id = Some(project.id.clone());
+            Some(verified)
+        }
+        Err(e) => {
+            let other = org_keys
+                .iter()
+                .filter(|(_, p)| p.id != project.id)
+                .find_map(|(_, p)| {
+                    jwt::verify_signature(token, &p.public_key)
+                        .ok()
+                        .map(|verified| (p, verified))
+                });
+            match other {
+                Some((other, verified)) => {
+                    diagnosis.signed_by_project_id = Some(other.id.clone());
+                    diagnosis.fail(
+                        DiagnosisStage::Signature,
+                        format!("Token was signed by project {}, not this one", other.id),
+                    );
+                    Some(verified)
+                }
+                None => {
+                    diagnosis.fail(DiagnosisStage::Signature, reason(e));
+                    None
+                }
+            }
+        }
+    };
+    let Some(verified) = verified else {
+        return Ok(diagnosis.finish());
+    };
+    // `sub` is the license ref; report the ID the license endpoints show
+    diagnosis.license_id = match (&verified.subject, &diagnosis.signed_by_project_id) {
+        (Some(sub), Some(project_id)) => Some(queries::resolve_license_id(&conn, project_id, sub)?),
+        _ => verified.subject.clone(),
+    };
+
+    let now = chrono::Utc::now().timestamp();
+    if diagnosis.signature_valid {
+        let expected = project.expected_token_claims(now);
+        match jwt::verify_token_expecting_allow_expired(token, &project.public_key, &expected) {
+            Ok(_) => diagnosis.claims_accepted = Some(true),
+            Err(e) => {
+                diagnosis.claims_accepted = Some(false);
+                diagnosis.fail(DiagnosisStage::Issuer, reason(e));
+            }
+        }
+    }
+
+    diagnosis.expires_at = verified.expires_at.map(|exp| exp.as_secs() as i64);
+    diagnosis.expired = diagnosis.expires_at.map(|exp| exp < now);
+    if let Some(exp) = diagnosis.expires_at.filter(|exp| *exp < now) {
+        diagnosis.fail(
+            DiagnosisStage::Expired,
+            format!("Token expired at {} (refresh still accepts it)", exp),
+        );
+    }
+
+    let Some(jti) = verified.jwt_id else {
+        diagnosis.fail(DiagnosisStage::Payload, msg::INVALID_TOKEN_MISSING_JTI);
+        return Ok(diagnosis.finish());
+    };
+
+    let revoked = queries::is_jti_revoked(&conn, &jti)?;
+    diagnosis.jti_revoked = Some(revoked);
+    if revoked {
+        diagnosis.fail(DiagnosisStage::Revoked, "Token's JTI has been revoked");
+    }
+
+    let device = queries::get_device_by_jti(&conn, &jti)?;
+    diagnosis.device_exists = Some(device.is_some());
+    match device {
+        Some(device) => diagnosis.device_signed_with_kid = device.signed_with_kid,
+        None => diagnosis.fail(
+            DiagnosisStage::Device,
+            "No device holds this token's JTI (deactivated, or replaced by a newer activation)",
+        ),
+    }
+    diagnosis.jti = Some(jti);
+
+    Ok(diagnosis.finish())
+}
@@ -0,0 +1,38 @@
+use axum::extract::State;
+use serde::Deserialize;
+
+use crate::db::{AppState, queries};
+use crate::error::{AppError, Result, msg};
+use crate::extractors::{Json, Path, Query};
+use crate::middleware::OrgProjectPath;
+use crate::models::TrialConversionStats;
+
+#[derive(Debug, Deserialize)]
+pub struct ConversionStatsQuery {
+    /// Only count trials started at or after this time (Unix seconds)
+    pub from: Option<i64>,
+    /// Only count trials started before this time (Unix seconds)
+    pub to: Option<i64>,
+}
+
+/// GET /orgs/{org_id}/projects/{project_id}/conversion-stats
+/// How many trials started in the range, how many a purchase converted, and
+/// the median days from trial to purchase. A trial converted after `to` still
+/// counts as converted: the range picks trials by when they started.
+pub async fn get_conversion_stats(
+    State(state): State<AppState>,
+    Path(path): Path<OrgProjectPath>,
+    Query(query): Query<ConversionStatsQuery>,
+) -> Result<Json<TrialConversionStats>> {
+    if let (Some(from), Some(to)) = (query.from, query.to)
+        && from >= to
+    {
+        return Err(AppError::BadRequest(
+            msg::CONVERSION_STATS_RANGE_INVALID.into(),
+        ));
+    }
+
+    let conn = state.org_db(&path.org_id).get()?;
+    let stats = queries::get_trial_conversion_stats(&conn, &path.project_id, query.from, query.to)?;
+    Ok(Json(stats))
+}
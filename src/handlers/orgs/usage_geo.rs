@@ -0,0 +1,59 @@
+use axum::extract::State;
+use chrono::{Days, NaiveDate};
+use serde::Deserialize;
+
+use crate::db::{AppState, queries};
+use crate::error::{AppError, Result, msg};
+use crate::extractors::{Json, Path, Query};
+use crate::middleware::OrgProjectPath;
+use crate::models::UsageGeo;
+
+/// Longest range one request can cover, in days
+const MAX_RANGE_DAYS: u64 = 366;
+
+/// Days covered when `from` isn't given
+const DEFAULT_RANGE_DAYS: u64 = 30;
+
+#[derive(Debug, Deserialize)]
+pub struct UsageGeoQuery {
+    /// First day (YYYY-MM-DD, UTC). Default: 30 days up to `to`
+    pub from: Option<NaiveDate>,
+    /// Last day (YYYY-MM-DD, UTC, inclusive). Default: today
+    pub to: Option<NaiveDate>,
+    /// Only count this product
+    pub product_id: Option<String>,
+}
+
+/// GET /orgs/{org_id}/projects/{project_id}/usage/geo?from=&to=
+/// Valid `/validate` calls by client country and UTC day, with per-country
+/// totals, for a usage heatmap. Empty until the project sets
+/// `usage_geo_enabled` and the server has a GeoIP database.
+pub async fn get_usage_geo(
+    State(state): State<AppState>,
+    Path(path): Path<OrgProjectPath>,
+    Query(query): Query<UsageGeoQuery>,
+) -> Result<Json<UsageGeo>> {
+    let to = query
+        .to
+        .or_else(|| chrono::DateTime::from_timestamp(state.clock.now(), 0).map(|t| t.date_naive()))
+        .ok_or_else(|| AppError::Internal("Clock is out of range".into()))?;
+    let from = query
+        .from
+        .unwrap_or_else(|| to - Days::new(DEFAULT_RANGE_DAYS - 1));
+    if from > to {
+        return Err(AppError::BadRequest(msg::USAGE_GEO_RANGE_INVALID.into()));
+    }
+    if to.signed_duration_since(from).num_days() >= MAX_RANGE_DAYS as i64 {
+        return Err(AppError::BadRequest(msg::USAGE_GEO_RANGE_TOO_LONG.into()));
+    }
+
+    let conn = state.org_db(&path.org_id).get()?;
+    let days = queries::get_usage_geo_days(
+        &conn,
+        &path.project_id,
+        &from.to_string(),
+        &to.to_string(),
+        query.product_id.as_deref(),
+    )?;
+    Ok(Json(UsageGeo::from_days(from, to, days)))
+}
@@ -0,0 +1,27 @@
+use axum::extract::State;
+use serde::Serialize;
+
+use crate::db::{AppState, queries};
+use crate::error::Result;
+use crate::extractors::{Json, Path};
+use crate::middleware::OrgProjectPath;
+use crate::models::LicenseVersionCount;
+
+#[derive(Debug, Serialize)]
+pub struct UsageVersionsResponse {
+    /// Licenses by last reported app version, most licenses first
+    pub versions: Vec<LicenseVersionCount>,
+}
+
+/// GET /orgs/{org_id}/projects/{project_id}/usage/versions
+/// Licenses grouped by the app version they were last validated or refreshed
+/// with, for tracking adoption of a release. Licenses whose app never sent
+/// `X-Paycheck-Client-Version` are counted under a null version.
+pub async fn get_usage_versions(
+    State(state): State<AppState>,
+    Path(path): Path<OrgProjectPath>,
+) -> Result<Json<UsageVersionsResponse>> {
+    let conn = state.org_db(&path.org_id).get()?;
+    let versions = queries::list_license_version_counts(&conn, &path.project_id)?;
+    Ok(Json(UsageVersionsResponse { versions }))
+}
@@ -3,6 +3,7 @@
 //! Allows users to request activation codes sent to their purchase email.
 //! This enables activation on new devices without needing a permanent license key.
 //! If the user has multiple licenses, all codes are sent in a single email.
+//! Seat holders on team licenses get codes scoped to their seat.
 
 use std::collections::HashMap;
 
@@ -10,10 +11,12 @@ use axum::{extract::State, http::HeaderMap};
 use serde::{Deserialize, Serialize};
 
 use crate::db::{AppState, queries};
-use crate::email::{EmailSendConfig, EmailTrigger, LicenseCodeInfo, MultiLicenseEmailConfig};
-use crate::error::Result;
-use crate::extractors::Json;
-use crate::models::{ActorType, AuditAction, AuditLogNames};
+use crate::email::{
+    EmailSendConfig, EmailSendResult, EmailTrigger, LicenseCodeInfo, MultiLicenseEmailConfig,
+};
+use crate::error::{AppError, Result};
+use crate::extractors::{Json, PublicJson};
+use crate::models::{ActorType, AuditAction, AuditLogNames, EmailAddress, License};
 use crate::util::AuditLogBuilder;
 
 #[derive(Debug, Deserialize)]
@@ -40,12 +43,13 @@ pub struct RequestCodeResponse {
 pub async fn request_activation_code(
     State(state): State<AppState>,
     headers: HeaderMap,
-    Json(body): Json<RequestCodeBody>,
+    PublicJson(body): PublicJson<RequestCodeBody>,
 ) -> Result<Json<RequestCodeResponse>> {
-    let conn = state.db.get()?;
+    // A malformed address can't hold a license; rejecting it reveals nothing
+    let email = EmailAddress::parse(&body.email)?;
 
     // Compute email hash for rate limiting and lookup
-    let email_hash = state.email_hasher.hash(&body.email);
+    let email_hash = state.email_hasher.hash(&email);
 
     // Rate limit check (by email hash)
     if let Err(_msg) = state.activation_rate_limiter.check(&email_hash) {
@@ -58,8 +62,8 @@ pub async fn request_activation_code(
     }
 
     // Look up project by public key
-    let project = match queries::get_project_by_public_key(&conn, &body.public_key)? {
-        Some(p) => p,
+    let (conn, project) = match state.project_by_public_key(&body.public_key)? {
+        Some(found) => found,
         None => {
             // Don't reveal project doesn't exist - return same response
             tracing::debug!("Project not found for public key");
@@ -69,11 +73,36 @@ pub async fn request_activation_code(
         }
     };
 
-    // Look up ALL licenses by email hash and project (user may have multiple)
-    let licenses = queries::get_licenses_by_email_hash(&conn, &project.id, &email_hash)?;
+    // Look up ALL licenses by email hash and project (user may have multiple),
+    // plus seats held on team licenses, matched against the seat's email rather
+    // than the purchaser's. A seat supersedes the purchaser match on the same license.
+    let (licenses, seats) = state.email_hasher.find_by_hash(
+        &email,
+        "activation_code_request",
+        |hash| {
+            Ok::<_, AppError>((
+                queries::get_licenses_by_email_hash(&conn, &project.id, hash)?,
+                queries::get_active_seats_by_email_hash(&conn, &project.id, hash)?,
+            ))
+        },
+        |(licenses, seats)| !licenses.is_empty() || !seats.is_empty(),
+    )?;
+    let mut grants: Vec<(License, Option<String>)> =
+        Vec::with_capacity(licenses.len() + seats.len());
+    for seat in seats {
+        if let Some(license) = queries::get_license_by_id(&conn, &seat.license_id)? {
+            grants.push((license, Some(seat.id)));
+        }
+    }
+    for license in licenses {
+        if !grants.iter().any(|(l, _)| l.id == license.id) {
+            grants.push((license, None));
+        }
+    }
 
     // Filter to non-revoked licenses only (query already does this, but be explicit)
-    let active_licenses: Vec<_> = licenses.into_iter().filter(|l| !l.revoked).collect();
+    grants.retain(|(l, _)| !l.revoked);
+    let active_licenses: Vec<&License> = grants.iter().map(|(l, _)| l).collect();
 
     if active_licenses.is_empty() {
         tracing::debug!(
@@ -91,6 +120,11 @@ pub async fn request_activation_code(
     let org_resend_key = queries::get_org_resend_api_key(&conn, &project.org_id, &state.master_key)
         .ok()
         .flatten();
+    // Nothing is sent if the secret can't be decrypted: a webhook sent unsigned
+    // would look like the project never set one. The response stays the same,
+    // so a broken secret doesn't reveal which emails hold licenses.
+    let webhook_secret =
+        queries::decrypt_project_webhook_secret(&conn, &project, &state.master_key);
 
     // Batch fetch all products for the licenses (avoids N+1 queries)
     let product_ids: Vec<&str> = active_licenses
@@ -106,9 +140,13 @@ pub async fn request_activation_code(
     // Create activation codes for all licenses
     let mut license_codes: Vec<LicenseCodeInfo> = Vec::with_capacity(active_licenses.len());
 
-    for license in &active_licenses {
-        let code =
-            queries::create_activation_code(&conn, &license.id, &project.license_key_prefix)?;
+    for (license, seat_id) in &grants {
+        let code = queries::create_activation_code_for_seat(
+            &conn,
+            &license.id,
+            seat_id.as_deref(),
+            &project.license_key_prefix,
+        )?;
 
         let product_name = product_names
             .get(license.product_id.as_str())
@@ -120,20 +158,22 @@ pub async fn request_activation_code(
             code: code.code,
             license_id: license.id.clone(),
             purchased_at: license.created_at,
+            metadata: license.metadata.clone(),
         });
     }
 
     // Audit log the activation code request (only when we actually found licenses)
     let audit_conn = state.audit.get()?;
     let org_name = org.as_ref().map(|o| o.name.clone());
-    if let Err(e) = AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    if let Err(e) = AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::Public, None)
         .action(AuditAction::RequestActivationCode)
         .resource("license", &active_licenses[0].id) // Use first license as resource
         .details(&serde_json::json!({
-            "email": body.email,
+            "email": email.as_str(),
             "licenses_found": active_licenses.len(),
             "license_ids": active_licenses.iter().map(|l| &l.id).collect::<Vec<_>>(),
+            "seats_found": grants.iter().filter(|(_, seat)| seat.is_some()).count(),
         }))
         .org(&project.org_id)
         .project(&project.id)
@@ -148,57 +188,83 @@ pub async fn request_activation_code(
     }
 
     // Send email - use single-license format for 1, multi-license for 2+
-    let email_result = if license_codes.len() == 1 {
-        let info = &license_codes[0];
-        let email_config = EmailSendConfig {
-            to_email: &body.email,
-            code: &info.code,
-            expires_in_minutes: 30,
-            product_name: &info.product_name,
-            project_name: &project.name,
-            project: &project,
-            license_id: &info.license_id,
-            purchased_at: info.purchased_at,
-            org_resend_key: org_resend_key.as_deref(),
-            trigger: EmailTrigger::RecoveryRequest,
-        };
-        state.email_service.send_activation_code(email_config).await
-    } else {
-        let email_config = MultiLicenseEmailConfig {
-            to_email: &body.email,
-            expires_in_minutes: 30,
-            project_name: &project.name,
-            project: &project,
-            licenses: license_codes,
-            org_resend_key: org_resend_key.as_deref(),
-            trigger: EmailTrigger::RecoveryRequest,
-        };
-        state
-            .email_service
-            .send_multi_license_activation_codes(email_config)
-            .await
-    };
-
-    match email_result {
-        Ok(result) => {
-            tracing::info!(
-                result = ?result,
-                email_hash_prefix = &email_hash[..8],
-                project_id = %project.id,
-                license_count = active_licenses.len(),
-                "Activation code email processed"
-            );
-        }
+    let license_ids: Vec<String> = license_codes.iter().map(|l| l.license_id.clone()).collect();
+    let email_result = match webhook_secret {
         Err(e) => {
-            // Log error but don't expose it to user
             tracing::error!(
-                error = %e,
-                email_hash_prefix = &email_hash[..8],
                 project_id = %project.id,
-                license_count = active_licenses.len(),
-                "Failed to send activation code email"
+                "Failed to decrypt email webhook secret: {}",
+                e
             );
+            EmailSendResult::Failed { status: None }
         }
+        Ok(webhook_secret) if license_codes.len() == 1 => {
+            let info = &license_codes[0];
+            let email_config = EmailSendConfig {
+                to_email: email.as_str(),
+                code: &info.code,
+                expires_in_minutes: 30,
+                product_name: &info.product_name,
+                project_name: &project.name,
+                project: &project,
+                license_id: &info.license_id,
+                purchased_at: info.purchased_at,
+                metadata: &info.metadata,
+                org_resend_key: org_resend_key.as_deref(),
+                webhook_secret: webhook_secret.as_deref(),
+                org_from_email: org.as_ref().and_then(|o| o.email_from.as_deref()),
+                trigger: EmailTrigger::RecoveryRequest,
+            };
+            state.email_service.send_activation_code(email_config).await
+        }
+        Ok(webhook_secret) => {
+            let email_config = MultiLicenseEmailConfig {
+                to_email: email.as_str(),
+                expires_in_minutes: 30,
+                project_name: &project.name,
+                project: &project,
+                licenses: license_codes,
+                org_resend_key: org_resend_key.as_deref(),
+                webhook_secret: webhook_secret.as_deref(),
+                org_from_email: org.as_ref().and_then(|o| o.email_from.as_deref()),
+                trigger: EmailTrigger::RecoveryRequest,
+            };
+            state
+                .email_service
+                .send_multi_license_activation_codes(email_config)
+                .await
+        }
+    };
+
+    if let EmailSendResult::Failed { status } = email_result {
+        // Log error but don't expose it to user
+        tracing::error!(
+            status = ?status,
+            email_hash_prefix = &email_hash[..8],
+            project_id = %project.id,
+            license_count = active_licenses.len(),
+            "Failed to send activation code email"
+        );
+    } else {
+        tracing::info!(
+            result = ?email_result,
+            email_hash_prefix = &email_hash[..8],
+            project_id = %project.id,
+            license_count = active_licenses.len(),
+            "Activation code email processed"
+        );
+    }
+
+    // The codes are already issued, so a failed write here shouldn't fail the request
+    if let Err(e) = queries::record_email_attempt(
+        &conn,
+        &project.id,
+        &license_ids,
+        &email_hash,
+        EmailTrigger::RecoveryRequest,
+        &email_result,
+    ) {
+        tracing::warn!("Failed to record activation code email attempt: {}", e);
     }
 
     // Always return same response (email enumeration protection)
@@ -1,18 +1,40 @@
-use axum::extract::State;
+use std::collections::BTreeMap;
+use std::net::{IpAddr, SocketAddr};
+
+use axum::{
+    Extension,
+    extract::{ConnectInfo, State},
+    http::{HeaderMap, header},
+    response::{IntoResponse, Redirect, Response},
+};
+use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
 
-use crate::db::{AppState, queries};
+use crate::db::{AppState, outbox, queries};
+use crate::email::{EmailSendConfig, EmailSendResult, EmailTrigger};
 use crate::error::{AppError, OptionExt, Result, msg};
-use crate::extractors::Json;
-use crate::models::{CreatePaymentSession, ServiceProvider};
-use crate::payments::{LemonSqueezyClient, PaymentProvider, StripeClient};
+use crate::extractors::{Json, Payload, PayloadSource};
+use crate::models::{
+    ActorType, AuditAction, AuditLogNames, CreateLicense, CreatePaymentSession, CurrentTerms,
+    EmailAddress, License, OrgLimitName, Product, Project, ServiceProvider,
+};
+use crate::payments::{
+    CheckoutSettings, CheckoutUpgrade, LemonSqueezyClient, PaymentProvider, StripeClient,
+};
+use crate::quota;
+use crate::util::{AuditLogBuilder, LicenseExpirations};
 
 /// Simplified BuyRequest - Paycheck knows the product pricing details.
 /// Device info is NOT required here - purchase ≠ activation.
 /// Redirect URL is configured per-project, not per-request.
+///
+/// Sent as JSON, as a form body, or as the query string of a `GET /buy`
+/// purchase link.
 #[derive(Debug, Deserialize)]
 pub struct BuyRequest {
-    /// Public key - identifies the project (preferred over product_id lookup)
+    /// Public key - identifies the project (or send the X-Paycheck-Project header).
+    /// Without either, the project is found from product_id, which is
+    /// deprecated and only allowed while the project has `allow_project_id_auth`.
     #[serde(default)]
     pub public_key: Option<String>,
     /// Product ID - Paycheck looks up project and pricing from this
@@ -23,40 +45,619 @@ pub struct BuyRequest {
     /// Optional: developer-managed customer identifier (flows through to license)
     #[serde(default)]
     pub customer_id: Option<String>,
+    /// Buyer's email. Required for free products, whose license is issued
+    /// here and recovered by this address. For paid products it only gets
+    /// the provider's receipt, if the project has `receipt_email_enabled`.
+    #[serde(default)]
+    pub email: Option<String>,
+    /// Optional: existing license this purchase upgrades, by license ref (the
+    /// token's `sub`) or ID. Must be active, in the same project, and issued to
+    /// `customer_id`.
+    #[serde(default)]
+    pub upgrade_from_license_id: Option<String>,
+    /// Values for the product's checkout fields, by key (JSON bodies only)
+    #[serde(default)]
+    pub fields: BTreeMap<String, String>,
+    /// Developer metadata such as `utm_*` parameters, by key (JSON bodies
+    /// only). Keys must be in the project's `checkout_metadata_keys`. Passed to
+    /// the provider and kept on the license; never shown to the buyer.
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    /// Buy another license even though the buyer already has one for this
+    /// product (projects with `duplicate_purchase_check` refuse otherwise)
+    #[serde(default)]
+    pub force_new_purchase: bool,
+    /// Version of the project's current terms the buyer accepted. Required
+    /// when the project has `require_terms_acceptance`.
+    #[serde(default)]
+    pub accepted_terms_version: Option<String>,
+    /// Optional: true to answer with a 303 to the checkout page, false for the
+    /// JSON `BuyResponse`. Defaults to JSON for JSON requests and to a
+    /// redirect for forms and links, unless `Accept` asks for JSON.
+    #[serde(default)]
+    pub redirect: Option<bool>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct BuyResponse {
+    /// Provider checkout page, or for a free product the callback that
+    /// carries its activation code
     pub checkout_url: String,
     pub session_id: String,
+    /// The project's current terms, if it has published any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub terms: Option<CurrentTerms>,
+}
+
+/// Remaining value of a license being upgraded, computed at checkout.
+#[derive(Debug)]
+pub struct UpgradeQuote {
+    pub license: License,
+    pub from_product: Product,
+    /// Whole days left on the old license (None = perpetual)
+    pub days_remaining: Option<i64>,
+    /// Prorated value of the remaining time in cents, capped at the new
+    /// product's price. None if it can't be priced (no price, currency
+    /// mismatch, or no fixed term to prorate against).
+    pub credit_cents: Option<i64>,
+}
+
+impl UpgradeQuote {
+    /// Verify `license_id` (or license ref) can be upgraded to `product` by
+    /// `customer_id` and compute its remaining value.
+    pub fn for_license(
+        conn: &Connection,
+        project: &Project,
+        product: &Product,
+        license_id: &str,
+        customer_id: Option<&str>,
+        now: i64,
+    ) -> Result<Self> {
+        // A license in another project is reported the same as a missing one
+        let license_id = queries::resolve_license_id(conn, &project.id, license_id)?;
+        let license = queries::get_license_by_id(conn, &license_id)?
+            .filter(|license| license.project_id == project.id)
+            .or_not_found(msg::UPGRADE_LICENSE_NOT_FOUND)?;
+
+        if license.revoked || license.expires_at.is_some_and(|exp| exp < now) {
+            return Err(AppError::BadRequest(msg::UPGRADE_LICENSE_INACTIVE.into()));
+        }
+        if customer_id.is_none() || license.customer_id.as_deref() != customer_id {
+            return Err(AppError::BadRequest(msg::UPGRADE_CUSTOMER_MISMATCH.into()));
+        }
+        if license.product_id == product.id {
+            return Err(AppError::BadRequest(msg::UPGRADE_SAME_PRODUCT.into()));
+        }
+
+        let from_product = queries::get_product_by_id(conn, &license.product_id)?
+            .or_not_found(msg::UPGRADE_LICENSE_NOT_FOUND)?;
+
+        let days_remaining = license.expires_at.map(|exp| (exp - now) / 86400);
+        let credit_cents = Self::credit(&license, &from_product, product, now);
+
+        Ok(Self {
+            license,
+            from_product,
+            days_remaining,
+            credit_cents,
+        })
+    }
+
+    /// Old price prorated by the fraction of its term left. A perpetual license
+    /// keeps its full value. Same currency only.
+    fn credit(
+        license: &License,
+        from_product: &Product,
+        to_product: &Product,
+        now: i64,
+    ) -> Option<i64> {
+        let price = from_product.price_cents?;
+        if from_product.currency != to_product.currency {
+            return None;
+        }
+        let credit = match license.expires_at {
+            None => price,
+            Some(expires_at) => {
+                let term_days = from_product.license_exp_days.filter(|days| *days > 0)?;
+                let term_secs = i64::from(term_days) * 86400;
+                let remaining_secs = (expires_at - now).clamp(0, term_secs);
+                (i128::from(price) * i128::from(remaining_secs) / i128::from(term_secs)) as i64
+            }
+        };
+        Some(match to_product.price_cents {
+            Some(new_price) => credit.min(new_price),
+            None => credit,
+        })
+    }
+}
+
+/// The terms version a purchase accepted. A version that's sent must be the
+/// current one, and projects with `require_terms_acceptance` refuse purchases
+/// that don't send one. Nothing is checked until the project publishes terms.
+fn accepted_terms_version(
+    project: &Project,
+    current: Option<&CurrentTerms>,
+    accepted: Option<&str>,
+) -> Result<Option<String>> {
+    let Some(current) = current else {
+        return Ok(None);
+    };
+    match accepted {
+        Some(version) if version == current.version => Ok(Some(version.to_string())),
+        None if !project.require_terms_acceptance => Ok(None),
+        _ => Err(AppError::TermsNotAccepted(current.clone())),
+    }
+}
+
+/// How long an unpaid checkout is handed back to a repeat /buy instead of
+/// opening another
+const PENDING_CHECKOUT_REUSE_SECS: i64 = 30 * 60;
+
+/// Catch a buyer paying twice because they missed the first confirmation:
+/// refuse if they already hold an active license for the product, and hand
+/// back a checkout they opened in the last 30 minutes (Some) instead of a new
+/// one. The buyer is matched by `customer_id` or email, so a request with
+/// neither is never checked.
+#[allow(clippy::too_many_arguments)]
+fn check_existing_purchase(
+    state: &AppState,
+    conn: &Connection,
+    product: &Product,
+    request: &BuyRequest,
+    email: Option<&EmailAddress>,
+    checkout_fields: &BTreeMap<String, String>,
+    accepted_terms_version: Option<&str>,
+    now: i64,
+) -> Result<Option<BuyResponse>> {
+    let customer_id = request.customer_id.as_deref();
+    if customer_id.is_none() && email.is_none() {
+        return Ok(None);
+    }
+
+    let find_license = |email_hash: Option<&str>| {
+        queries::find_active_license_for_buyer(conn, &product.id, customer_id, email_hash, now)
+    };
+    let license = match email {
+        Some(email) => state.email_hasher.find_by_hash(
+            email,
+            "duplicate_purchase",
+            |hash| find_license(Some(hash)),
+            Option::is_some,
+        )?,
+        None => find_license(None)?,
+    };
+    if let Some(license) = license {
+        return Err(AppError::AlreadyLicensed {
+            license_created_at: license.created_at,
+        });
+    }
+
+    if request.upgrade_from_license_id.is_some() {
+        return Ok(None);
+    }
+    let email_hash = email.map(|email| state.email_hasher.hash(email));
+    let pending = queries::find_pending_payment_session(
+        conn,
+        &product.id,
+        customer_id,
+        email_hash.as_deref(),
+        now - PENDING_CHECKOUT_REUSE_SECS,
+    )?;
+    // A checkout opened with other field values would sell something else,
+    // and one opened under other terms would record the wrong version
+    Ok(pending
+        .filter(|session| {
+            session.checkout_fields == *checkout_fields
+                && session.accepted_terms_version.as_deref() == accepted_terms_version
+        })
+        .and_then(|session| {
+            Some(BuyResponse {
+                checkout_url: session.checkout_url?,
+                session_id: session.id,
+                terms: None,
+            })
+        }))
+}
+
+/// Whether the `Accept` header lists `application/json`.
+fn accepts_json(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|range| range.split(';').next())
+        .any(|mime| mime.trim().eq_ignore_ascii_case("application/json"))
+}
+
+/// Answer a successful purchase: the JSON `BuyResponse` for scripts, or a 303
+/// straight to the provider's checkout page for browser navigation.
+pub fn buy_response(
+    buy: BuyResponse,
+    request: &BuyRequest,
+    source: PayloadSource,
+    headers: &HeaderMap,
+) -> Response {
+    let redirect = request
+        .redirect
+        .unwrap_or_else(|| source != PayloadSource::Json && !accepts_json(headers));
+    if redirect {
+        Redirect::to(&buy.checkout_url).into_response()
+    } else {
+        Json(buy).into_response()
+    }
+}
+
+/// A `GET /buy` link is for browser navigation. Browsers mark `fetch()`,
+/// `<img>` and other subresource requests with a `Sec-Fetch-Mode` other than
+/// `navigate`; those are refused so embedding a link can't open sessions.
+fn check_link_navigation(headers: &HeaderMap) -> Result<()> {
+    match headers.get("sec-fetch-mode") {
+        Some(mode) if mode.as_bytes() != b"navigate" => {
+            Err(AppError::Forbidden(msg::LINK_CHECKOUT_SCRIPTED.into()))
+        }
+        _ => Ok(()),
+    }
 }
 
+/// Issue a free product's license straight away: no provider, but a payment
+/// session completed on the spot so /callback works as for a paid purchase.
+///
+/// Each email gets one license per free product, and claims are rate limited
+/// per client IP and per email.
+#[allow(clippy::too_many_arguments)]
+async fn claim_free_license(
+    state: &AppState,
+    headers: &HeaderMap,
+    conn: &mut Connection,
+    project: &Project,
+    product: &Product,
+    request: &BuyRequest,
+    client_ip: Option<IpAddr>,
+    checkout_fields: BTreeMap<String, String>,
+    metadata: BTreeMap<String, String>,
+    accepted_terms_version: Option<String>,
+) -> Result<BuyResponse> {
+    if request.upgrade_from_license_id.is_some() {
+        return Err(AppError::BadRequest(msg::FREE_PRODUCT_NO_UPGRADE.into()));
+    }
+    let email = request
+        .email
+        .as_deref()
+        .ok_or_else(|| AppError::BadRequest(msg::FREE_PRODUCT_EMAIL_REQUIRED.into()))?;
+    let email = EmailAddress::parse(email)?;
+    let email_hash = state.email_hasher.hash(&email);
+
+    let limiter = &state.free_license_limiter;
+    if let Some(ip) = client_ip
+        && limiter.check(&format!("ip:{}", ip)).is_err()
+    {
+        return Err(AppError::FreeLicenseThrottled);
+    }
+    if limiter.check(&format!("email:{}", email_hash)).is_err() {
+        return Err(AppError::FreeLicenseThrottled);
+    }
+
+    let claimed = state.email_hasher.find_by_hash(
+        &email,
+        "free_license_claim",
+        |hash| queries::free_license_claimed(conn, &product.id, hash),
+        |claimed| *claimed,
+    )?;
+    if claimed {
+        return Err(AppError::Conflict(msg::FREE_LICENSE_ALREADY_CLAIMED.into()));
+    }
+
+    let quota = quota::check(
+        conn,
+        &project.org_id,
+        OrgLimitName::LicensesPerMonth,
+        None,
+        1,
+        state.clock.now(),
+    )?;
+
+    let exps = LicenseExpirations::from_product(product, state.clock.now());
+    let audit_conn = state.audit.get()?;
+    let (session, license, code) = outbox::with_audited_tx(
+        conn,
+        |tx| {
+            let session = queries::create_payment_session(
+                tx,
+                &CreatePaymentSession {
+                    product_id: product.id.clone(),
+                    customer_id: request.customer_id.clone(),
+                    upgrade_from_license_id: None,
+                    upgrade_days_remaining: None,
+                    upgrade_credit_cents: None,
+                    checkout_fields,
+                    accepted_terms_version,
+                    metadata,
+                },
+            )?;
+            queries::try_claim_payment_session(tx, &session.id)?;
+            // The idx_licenses_free_claim index settles concurrent claims
+            let license = queries::create_license(
+                tx,
+                &project.id,
+                &product.id,
+                &CreateLicense {
+                    email_hash: Some(email_hash.clone()),
+                    customer_id: request.customer_id.clone(),
+                    expires_at: exps.license_exp,
+                    updates_expires_at: exps.updates_exp,
+                    payment_provider: Some("free".to_string()),
+                    payment_provider_customer_id: None,
+                    payment_provider_subscription_id: None,
+                    payment_provider_order_id: None,
+                    seats: product.seat_count,
+                },
+            )
+            .map_err(|e| match e {
+                AppError::Database(rusqlite::Error::SqliteFailure(err, _))
+                    if err.code == rusqlite::ErrorCode::ConstraintViolation =>
+                {
+                    AppError::Conflict(msg::FREE_LICENSE_ALREADY_CLAIMED.into())
+                }
+                e => e,
+            })?;
+            queries::set_payment_session_license(tx, &session.id, &license.id, None)?;
+            if !session.checkout_fields.is_empty() {
+                queries::set_license_checkout_fields(tx, &license.id, &session.checkout_fields)?;
+            }
+            if !session.metadata.is_empty() {
+                queries::set_license_metadata(tx, &license.id, &session.metadata)?;
+            }
+            if let Some(ref version) = session.accepted_terms_version {
+                queries::set_license_accepted_terms(tx, &license.id, version)?;
+            }
+            let code =
+                queries::create_activation_code(tx, &license.id, &project.license_key_prefix)?;
+            Ok((session, license, code))
+        },
+        |(_, license, _)| {
+            AuditLogBuilder::for_state(&audit_conn, state, headers)
+                .actor(ActorType::Public, None)
+                .action(AuditAction::ClaimFreeLicense)
+                .resource("license", &license.id)
+                .details(&serde_json::json!({
+                    "product_id": product.id,
+                    "email": email.as_str(),
+                }))
+                .org(&project.org_id)
+                .project(&project.id)
+                .names(&AuditLogNames {
+                    project_name: Some(project.name.clone()),
+                    ..Default::default()
+                })
+                .entry()
+        },
+    )?;
+    outbox::relay(conn, &audit_conn);
+
+    if let Some(ref warning) = quota {
+        quota::audit_crossing(
+            AuditLogBuilder::for_state(&audit_conn, state, headers)
+                .actor(ActorType::Public, None)
+                .org(&project.org_id)
+                .project(&project.id)
+                .names(&AuditLogNames {
+                    project_name: Some(project.name.clone()),
+                    ..Default::default()
+                }),
+            &project.org_id,
+            warning,
+        );
+    }
+
+    let org_resend_key = queries::get_org_resend_api_key(conn, &project.org_id, &state.master_key)
+        .ok()
+        .flatten();
+    let org_from_email = queries::get_organization_by_id(conn, &project.org_id)
+        .ok()
+        .flatten()
+        .and_then(|org| org.email_from);
+    // Nothing is sent if the secret can't be decrypted: a webhook sent unsigned
+    // would look like the project never set one
+    let email_result =
+        match queries::decrypt_project_webhook_secret(conn, project, &state.master_key) {
+            Ok(webhook_secret) => {
+                state
+                    .email_service
+                    .send_activation_code(EmailSendConfig {
+                        to_email: email.as_str(),
+                        code: &code.code,
+                        expires_in_minutes: 30,
+                        product_name: &product.name,
+                        project_name: &project.name,
+                        project,
+                        license_id: &license.id,
+                        purchased_at: license.created_at,
+                        metadata: &session.metadata,
+                        org_resend_key: org_resend_key.as_deref(),
+                        webhook_secret: webhook_secret.as_deref(),
+                        org_from_email: org_from_email.as_deref(),
+                        trigger: EmailTrigger::Purchase,
+                    })
+                    .await
+            }
+            Err(e) => {
+                tracing::error!(
+                    project_id = %project.id,
+                    "Failed to decrypt email webhook secret: {}",
+                    e
+                );
+                EmailSendResult::Failed { status: None }
+            }
+        };
+    if let EmailSendResult::Failed { status } = email_result {
+        // The license is issued either way; /activation/request-code recovers it
+        tracing::error!(
+            status = ?status,
+            project_id = %project.id,
+            license_id = %license.id,
+            "Failed to send free license activation email"
+        );
+    }
+    if let Err(e) = queries::record_email_attempt(
+        conn,
+        &project.id,
+        std::slice::from_ref(&license.id),
+        &email_hash,
+        EmailTrigger::Purchase,
+        &email_result,
+    ) {
+        tracing::warn!("Failed to record free license email attempt: {}", e);
+    }
+
+    Ok(BuyResponse {
+        checkout_url: format!("{}/callback?session={}", state.base_url, session.id),
+        session_id: session.id,
+        terms: None,
+    })
+}
+
+/// POST /buy (JSON or form body) and GET /buy (purchase link).
+/// GET requires the project's `allow_link_checkout`.
+///
+/// Free products (price 0) skip the provider: see [`claim_free_license`].
 pub async fn initiate_buy(
     State(state): State<AppState>,
-    Json(request): Json<BuyRequest>,
-) -> Result<Json<BuyResponse>> {
-    let conn = state.db.get()?;
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+    headers: HeaderMap,
+    Payload(request, source): Payload<BuyRequest>,
+) -> Result<Response> {
+    let is_link = source == PayloadSource::Query;
+    if is_link {
+        check_link_navigation(&headers)?;
+    }
 
-    // Get product - this gives us project_id and payment config
-    let product = queries::get_product_by_id(&conn, &request.product_id)?
-        .or_not_found(msg::PRODUCT_NOT_FOUND)?;
+    let public_key = super::publishable_key(&headers, request.public_key.as_deref())?;
 
-    // Get project - prefer public_key lookup if provided, otherwise use product's project_id
-    let project = if let Some(ref public_key) = request.public_key {
-        let project = queries::get_project_by_public_key(&conn, public_key)?
+    // Resolve the project first when a public_key is given, so an unknown key
+    // is always a 404 for the project rather than whichever lookup fails first
+    let (mut conn, project, product) = if let Some(ref public_key) = public_key {
+        let (conn, project) = state
+            .project_by_public_key(public_key)?
             .or_not_found(msg::PROJECT_NOT_FOUND)?;
+        let product = queries::get_product_by_id(&conn, &request.product_id)?
+            .or_not_found(msg::PRODUCT_NOT_FOUND)?;
         // Verify the product belongs to this project
         if product.project_id != project.id {
             return Err(AppError::BadRequest(
                 "Product does not belong to this project".into(),
             ));
         }
-        project
+        (conn, project, product)
     } else {
-        queries::get_project_by_id(&conn, &product.project_id)?
-            .or_not_found(msg::PROJECT_NOT_FOUND)?
+        let conn = state.product_db(&request.product_id)?.get()?;
+        // Get product - this gives us project_id and payment config
+        let product = queries::get_product_by_id(&conn, &request.product_id)?
+            .or_not_found(msg::PRODUCT_NOT_FOUND)?;
+        let project = queries::get_project_by_id(&conn, &product.project_id)?
+            .or_not_found(msg::PROJECT_NOT_FOUND)?;
+        if !project.allow_project_id_auth {
+            return Err(AppError::BadRequest(msg::PUBLIC_KEY_REQUIRED.into()));
+        }
+        tracing::warn!(
+            "Deprecated /buy without a public key: project={}, product={}",
+            project.id,
+            product.id
+        );
+        (conn, project, product)
+    };
+
+    if is_link && !project.allow_link_checkout {
+        return Err(AppError::Forbidden(msg::LINK_CHECKOUT_DISABLED.into()));
+    }
+
+    let now = state.clock.now();
+    product.check_available(now)?;
+    let checkout_fields = product.checkout_values(&request.fields)?;
+    let metadata = project.checkout_metadata(&request.metadata)?;
+    let current_terms =
+        queries::get_current_terms(&conn, &project.id, now)?.map(CurrentTerms::from);
+    let accepted_terms_version = accepted_terms_version(
+        &project,
+        current_terms.as_ref(),
+        request.accepted_terms_version.as_deref(),
+    )?;
+    // Every answer shows the terms the purchase was made under
+    let respond = |mut buy: BuyResponse| {
+        buy.terms = current_terms.clone();
+        buy_response(buy, &request, source, &headers)
+    };
+
+    if product.is_free() {
+        let client_ip = connect_info.map(|Extension(ConnectInfo(addr))| addr.ip());
+        let buy = claim_free_license(
+            &state,
+            &headers,
+            &mut conn,
+            &project,
+            &product,
+            &request,
+            client_ip,
+            checkout_fields,
+            metadata,
+            accepted_terms_version,
+        )
+        .await?;
+        return Ok(respond(buy));
+    }
+
+    // The webhook couldn't issue a license past the org's hard limit, so don't
+    // take payment for one. Soft limit warnings wait for the license itself.
+    quota::check(
+        &conn,
+        &project.org_id,
+        OrgLimitName::LicensesPerMonth,
+        None,
+        1,
+        now,
+    )?;
+
+    // Paid checkouts only use the email for the provider's receipt
+    let receipt_email = match request.email.as_deref() {
+        Some(email) if project.receipt_email_enabled => Some(EmailAddress::parse(email)?),
+        _ => None,
+    };
+    let settings = CheckoutSettings::for_project(&project, receipt_email.as_ref());
+
+    let upgrade = match request.upgrade_from_license_id {
+        Some(ref license_id) => Some(UpgradeQuote::for_license(
+            &conn,
+            &project,
+            &product,
+            license_id,
+            request.customer_id.as_deref(),
+            now,
+        )?),
+        None => None,
     };
 
+    // Matched against licenses and pending checkouts, and saved on the new
+    // session. An address that doesn't parse just isn't matched.
+    let buyer_email = request
+        .email
+        .as_deref()
+        .and_then(|email| EmailAddress::parse(email).ok());
+    if project.duplicate_purchase_check && !request.force_new_purchase {
+        let pending = check_existing_purchase(
+            &state,
+            &conn,
+            &product,
+            &request,
+            buyer_email.as_ref(),
+            &checkout_fields,
+            accepted_terms_version.as_deref(),
+            now,
+        )?;
+        if let Some(buy) = pending {
+            return Ok(respond(buy));
+        }
+    }
+
     // Get organization (payment config is at org level)
     let org = queries::get_organization_by_id(&conn, &project.org_id)?
         .or_not_found(msg::ORG_NOT_FOUND)?;
@@ -112,6 +713,12 @@ pub async fn initiate_buy(
         &CreatePaymentSession {
             product_id: request.product_id.clone(),
             customer_id: request.customer_id.clone(),
+            upgrade_from_license_id: upgrade.as_ref().map(|u| u.license.id.clone()),
+            upgrade_days_remaining: upgrade.as_ref().and_then(|u| u.days_remaining),
+            upgrade_credit_cents: upgrade.as_ref().and_then(|u| u.credit_cents),
+            checkout_fields,
+            accepted_terms_version,
+            metadata,
         },
     )?;
 
@@ -126,14 +733,53 @@ pub async fn initiate_buy(
                 .ok_or_else(|| AppError::BadRequest(msg::STRIPE_NOT_CONFIGURED.into()))?;
 
             let client = StripeClient::new(&config);
-            let (_, url) = client
-                .create_checkout_session(
-                    &session.id,
-                    &product.project_id,
-                    &product.id,
-                    &provider_link.linked_id, // Stripe Price ID (e.g., "price_1ABC...")
-                    &callback_url,
-                    &cancel_url,
+
+            let checkout_upgrade = match upgrade {
+                Some(upgrade) => {
+                    // Coupons need a positive amount and a currency to be denominated in
+                    let coupon_id = match (upgrade.credit_cents, product.currency.as_deref()) {
+                        (Some(credit), Some(currency))
+                            if project.upgrade_auto_discount && credit > 0 =>
+                        {
+                            Some(
+                                state
+                                    .provider_calls
+                                    .run(
+                                        &org.id,
+                                        client.create_upgrade_coupon(&session.id, credit, currency),
+                                    )
+                                    .await?,
+                            )
+                        }
+                        _ => None,
+                    };
+                    Some(CheckoutUpgrade {
+                        from_license_id: upgrade.license.id,
+                        from_product_id: upgrade.from_product.id,
+                        days_remaining: upgrade.days_remaining,
+                        credit_cents: upgrade.credit_cents,
+                        coupon_id,
+                    })
+                }
+                None => None,
+            };
+
+            let (_, url) = state
+                .provider_calls
+                .run(
+                    &org.id,
+                    client.create_checkout_session(
+                        &session.id,
+                        &product.project_id,
+                        &product.id,
+                        &provider_link.linked_id, // Stripe Price ID (e.g., "price_1ABC...")
+                        &callback_url,
+                        &cancel_url,
+                        checkout_upgrade.as_ref(),
+                        &session.checkout_fields,
+                        &session.metadata,
+                        &settings,
+                    ),
                 )
                 .await?;
             url
@@ -143,21 +789,38 @@ pub async fn initiate_buy(
                 .ok_or_else(|| AppError::BadRequest(msg::LS_NOT_CONFIGURED.into()))?;
 
             let client = LemonSqueezyClient::new(&config);
-            let (_, url) = client
-                .create_checkout(
-                    &session.id,
-                    &product.project_id,
-                    &product.id,
-                    &provider_link.linked_id, // LemonSqueezy Variant ID
-                    &callback_url,
+            let (_, url) = state
+                .provider_calls
+                .run(
+                    &org.id,
+                    client.create_checkout(
+                        &session.id,
+                        &product.project_id,
+                        &product.id,
+                        &provider_link.linked_id, // LemonSqueezy Variant ID
+                        &callback_url,
+                        &session.metadata,
+                        &settings,
+                    ),
                 )
                 .await?;
             url
         }
     };
 
-    Ok(Json(BuyResponse {
+    let buyer_email_hash = buyer_email
+        .as_ref()
+        .map(|email| state.email_hasher.hash(email));
+    queries::set_payment_session_checkout(
+        &conn,
+        &session.id,
+        &checkout_url,
+        buyer_email_hash.as_deref(),
+    )?;
+
+    Ok(respond(BuyResponse {
         checkout_url,
         session_id: session.id,
+        terms: None,
     }))
 }
@@ -17,7 +17,8 @@ pub struct CallbackQuery {
 ///
 /// Query params appended to redirect:
 /// - code: A short-lived activation code (PREFIX-XXXX-XXXX format)
-/// - status: "success" or "pending"
+/// - status: "success", "pending", or "review" (an updates-only renewal with
+///   no license to extend, held for the seller to sort out)
 ///
 /// Note: No JWT or license key is returned here. The user must call /redeem
 /// with the activation code and device info to get a JWT.
@@ -25,7 +26,9 @@ pub async fn payment_callback(
     State(state): State<AppState>,
     Query(query): Query<CallbackQuery>,
 ) -> Result<Redirect> {
-    let conn = state.db.get()?;
+    let conn = state
+        .find_db(|conn| Ok(queries::get_payment_session(conn, &query.session)?.is_some()))?
+        .get()?;
 
     // Get payment session
     let session = queries::get_payment_session(&conn, &query.session)?
@@ -37,7 +40,7 @@ pub async fn payment_callback(
 
     // Get project for redirect URL and activation code prefix
     let project = queries::get_project_by_id(&conn, &product.project_id)?
-        .ok_or_else(|| AppError::Internal(msg::PROJECT_NOT_FOUND.into()))?;
+        .or_not_found(msg::PROJECT_NOT_FOUND)?;
 
     // Determine base redirect URL (from project config or fallback to Paycheck success page)
     let base_redirect = project
@@ -55,6 +58,14 @@ pub async fn payment_callback(
         return Ok(Redirect::temporary(&redirect_url));
     }
 
+    if session.needs_review {
+        let redirect_url = append_query_params(
+            base_redirect,
+            &[("session", &query.session), ("status", "review")],
+        );
+        return Ok(Redirect::temporary(&redirect_url));
+    }
+
     // Get license directly via stored ID (set by webhook when license was created)
     let license_id = session
         .license_id
@@ -0,0 +1,115 @@
+use std::collections::HashSet;
+
+use axum::{extract::State, http::HeaderMap};
+use serde::{Deserialize, Serialize};
+
+use crate::db::{AppState, queries};
+use crate::error::{OptionExt, Result, msg};
+use crate::extractors::{Json, Query};
+use crate::models::{Availability, CurrentTerms, Entitlements, Product, ProductUpsell};
+
+/// Query parameters for GET /products
+#[derive(Debug, Deserialize)]
+pub struct CatalogQuery {
+    /// Public key - identifies the project (or send the X-Paycheck-Project header)
+    #[serde(default)]
+    pub public_key: Option<String>,
+    /// Also list products outside their sale window, marked `coming_soon` or
+    /// `ended`. By default only products on sale now are listed.
+    #[serde(default)]
+    pub include_unavailable: bool,
+}
+
+/// A product as shown to customers (no limits or internal settings)
+#[derive(Debug, Serialize)]
+pub struct CatalogProduct {
+    pub id: String,
+    pub name: String,
+    pub tier: String,
+    pub features: Vec<String>,
+    /// Structured entitlements, e.g. `{"seats": 5}` for "up to 5 seats"
+    #[serde(skip_serializing_if = "Entitlements::is_empty")]
+    pub entitlements: Entitlements,
+    pub price_cents: Option<i64>,
+    pub currency: Option<String>,
+    pub seat_count: Option<i32>,
+    pub available_from: Option<i64>,
+    pub available_until: Option<i64>,
+    pub availability: Availability,
+    /// Set on updates-only renewals: the product whose licenses they extend.
+    /// Offer these to existing customers rather than on a general pricing page.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extends_updates_for_product_id: Option<String>,
+    /// The upgrade to pitch to this product's customers. Only set when the
+    /// target product is in the same listing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upsell: Option<ProductUpsell>,
+}
+
+impl CatalogProduct {
+    fn new(product: Product, availability: Availability) -> Self {
+        Self {
+            id: product.id,
+            name: product.name,
+            tier: product.tier,
+            features: product.features,
+            entitlements: product.entitlements,
+            price_cents: product.price_cents,
+            currency: product.currency,
+            seat_count: product.seat_count,
+            available_from: product.available_from,
+            available_until: product.available_until,
+            availability,
+            extends_updates_for_product_id: product.extends_updates_for_product_id,
+            upsell: product.upsell,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CatalogResponse {
+    pub products: Vec<CatalogProduct>,
+    /// The terms buyers accept at /buy, if the project has published any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub terms: Option<CurrentTerms>,
+}
+
+/// GET /products - Products a project sells, for building pricing pages
+pub async fn get_catalog(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<CatalogQuery>,
+) -> Result<Json<CatalogResponse>> {
+    let public_key = super::require_publishable_key(&headers, query.public_key.as_deref())?;
+    let (conn, project) = state
+        .project_by_public_key(&public_key)?
+        .or_not_found(msg::PROJECT_NOT_FOUND)?;
+
+    let now = state.clock.now();
+    let mut products: Vec<CatalogProduct> = queries::list_products_for_project(&conn, &project.id)?
+        .into_iter()
+        .map(|product| {
+            let availability = product.availability_at(now);
+            (product, availability)
+        })
+        .filter(|(_, availability)| {
+            query.include_unavailable || *availability == Availability::Available
+        })
+        .map(|(product, availability)| CatalogProduct::new(product, availability))
+        .collect();
+
+    // Don't point customers at an upgrade they can't see
+    let listed: HashSet<String> = products.iter().map(|p| p.id.clone()).collect();
+    for product in &mut products {
+        if product
+            .upsell
+            .as_ref()
+            .is_some_and(|upsell| !listed.contains(&upsell.upgrade_to_product_id))
+        {
+            product.upsell = None;
+        }
+    }
+
+    let terms = queries::get_current_terms(&conn, &project.id, now)?.map(CurrentTerms::from);
+    Ok(Json(CatalogResponse { products, terms }))
+}
@@ -26,12 +26,12 @@ pub async fn deactivate_device(
     headers: HeaderMap,
     TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
 ) -> Result<Json<DeactivateResponse>> {
-    let conn = state.db.get()?;
     let token = auth.token();
 
     // First, decode the token without verification to get the product_id
     // We need this to look up the project and its public key
     let unverified_claims = jwt::decode_unverified(token)?;
+    let conn = state.product_db(&unverified_claims.product_id)?.get()?;
 
     // Look up the product to get the project
     let product = queries::get_product_by_id(&conn, &unverified_claims.product_id)?
@@ -39,15 +39,22 @@ pub async fn deactivate_device(
 
     // Get the project to get the public key
     let project = queries::get_project_by_id(&conn, &product.project_id)?
-        .ok_or_else(|| AppError::Internal(msg::PROJECT_NOT_FOUND.into()))?;
+        .or_not_found(msg::PROJECT_NOT_FOUND)?;
+
+    // A publishable key header, if sent, must name the token's project
+    super::publishable_key(&headers, Some(&project.public_key))?;
 
     // Get org for audit logging
     let org = queries::get_organization_by_id(&conn, &project.org_id)?
         .ok_or_else(|| AppError::Internal(msg::ORG_NOT_FOUND.into()))?;
 
     // Now verify the JWT signature with the project's public key
-    // Also validates issuer ("paycheck")
-    let verified_claims = jwt::verify_token(token, &project.public_key)?;
+    // Also validates issuer (and audience, if the project overrides it)
+    let verified_claims = jwt::verify_token_expecting(
+        token,
+        &project.public_key,
+        &project.expected_token_claims(chrono::Utc::now().timestamp()),
+    )?;
 
     // Extract JTI from verified claims
     let jti = verified_claims
@@ -82,7 +89,7 @@ pub async fn deactivate_device(
 
     // Audit log the self-deactivation
     let audit_conn = state.audit.get()?;
-    if let Err(e) = AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    if let Err(e) = AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::Public, None)
         .action(AuditAction::DeactivateDevice)
         .resource("device", &device_id)
@@ -0,0 +1,177 @@
+use axum::{
+    extract::State,
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD as BASE64, URL_SAFE_NO_PAD as BASE64_URL};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::db::AppState;
+use crate::error::{AppError, OptionExt, Result, msg};
+use crate::extractors::Query;
+use crate::jwt;
+use crate::models::Project;
+
+/// How long clients may cache the discovery document before revalidating.
+/// Issuer/audience changes keep the old values valid for a grace window of
+/// days, so a few minutes of staleness is harmless.
+pub const DISCOVERY_MAX_AGE_SECS: u64 = 300;
+
+/// Query parameters for GET /discovery and GET /.well-known/jwks.json
+#[derive(Debug, Deserialize)]
+pub struct DiscoveryQuery {
+    /// Public key - identifies the project (or send the X-Paycheck-Project
+    /// header, or ask the project's custom domain)
+    #[serde(default)]
+    pub public_key: Option<String>,
+}
+
+/// Token validation settings for a project, so SDKs and standard JWT
+/// libraries can configure their validators.
+#[derive(Debug, Serialize)]
+pub struct DiscoveryResponse {
+    /// `iss` claim on newly issued tokens
+    pub issuer: String,
+    /// `aud` claim on newly issued tokens
+    pub audience: String,
+    /// Whether Paycheck verifies `aud` (only when the project overrides it)
+    pub audience_verified: bool,
+    /// Previous issuer/audience, still accepted until `previous_until`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_issuer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_audience: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_until: Option<i64>,
+    pub jwks: Jwks,
+    /// Header that can carry the public key instead of a `public_key` field
+    pub project_key_header: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Jwks {
+    pub keys: Vec<Jwk>,
+}
+
+/// Ed25519 public key in JWK form (RFC 8037)
+#[derive(Debug, Serialize)]
+pub struct Jwk {
+    pub kty: &'static str,
+    pub crv: &'static str,
+    pub alg: &'static str,
+    #[serde(rename = "use")]
+    pub key_use: &'static str,
+    /// Matches the `kid` header of tokens signed with this key
+    pub kid: String,
+    pub x: String,
+}
+
+/// The project's signing key as a JWK set
+fn project_jwks(project: &Project) -> Result<Jwks> {
+    // Stored as standard base64; JWK wants base64url without padding
+    let key_bytes = BASE64
+        .decode(&project.public_key)
+        .map_err(|e| AppError::Internal(format!("Invalid public key encoding: {}", e)))?;
+
+    Ok(Jwks {
+        keys: vec![Jwk {
+            kty: "OKP",
+            crv: "Ed25519",
+            alg: "EdDSA",
+            key_use: "sig",
+            kid: jwt::key_id(&project.public_key)?,
+            x: BASE64_URL.encode(key_bytes),
+        }],
+    })
+}
+
+/// The project a discovery request is for
+fn discovery_project(
+    state: &AppState,
+    headers: &HeaderMap,
+    query: &DiscoveryQuery,
+) -> Result<Project> {
+    let public_key = super::require_publishable_key(headers, query.public_key.as_deref())?;
+    let (_conn, project) = state
+        .project_by_public_key(&public_key)?
+        .or_not_found(msg::PROJECT_NOT_FOUND)?;
+    Ok(project)
+}
+
+/// GET /discovery - Issuer, audience, and signing key for a project
+///
+/// Responses carry `Cache-Control: max-age` and an `ETag` hashed from the
+/// document (key set, issuer, and audience); `If-None-Match` gets a 304.
+pub async fn get_discovery(
+    State(state): State<AppState>,
+    Query(query): Query<DiscoveryQuery>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    let project = discovery_project(&state, &headers, &query)?;
+    let in_grace = project.in_jwt_grace_window(Utc::now().timestamp());
+
+    let document = DiscoveryResponse {
+        issuer: project.token_issuer().to_string(),
+        audience: project.token_audience().to_string(),
+        audience_verified: project.jwt_audience.is_some(),
+        previous_issuer: project.jwt_previous_issuer.clone().filter(|_| in_grace),
+        previous_audience: project.jwt_previous_audience.clone().filter(|_| in_grace),
+        previous_until: project.jwt_previous_until.filter(|_| in_grace),
+        jwks: project_jwks(&project)?,
+        project_key_header: "X-Paycheck-Project",
+    };
+    cacheable_json(&document, &headers)
+}
+
+/// GET /.well-known/jwks.json - The project's signing key set alone, for JWT
+/// libraries that fetch keys from a JWKS URL. Cached like `/discovery`.
+pub async fn get_jwks(
+    State(state): State<AppState>,
+    Query(query): Query<DiscoveryQuery>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    let project = discovery_project(&state, &headers, &query)?;
+    cacheable_json(&project_jwks(&project)?, &headers)
+}
+
+/// `document` as JSON with `Cache-Control` and an `ETag` of its bytes, or a
+/// 304 when `If-None-Match` already has it.
+fn cacheable_json<T: Serialize>(document: &T, headers: &HeaderMap) -> Result<Response> {
+    let body = serde_json::to_vec(document)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize discovery: {}", e)))?;
+    let etag = format!("\"{}\"", hex::encode(&Sha256::digest(&body)[..16]));
+
+    let mut response = if etag_matches(headers, &etag) {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        let content_type = HeaderValue::from_static("application/json");
+        ([(header::CONTENT_TYPE, content_type)], body).into_response()
+    };
+
+    let response_headers = response.headers_mut();
+    response_headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_str(&format!("public, max-age={}", DISCOVERY_MAX_AGE_SECS))
+            .expect("valid header value"),
+    );
+    response_headers.insert(
+        header::ETAG,
+        HeaderValue::from_str(&etag).expect("hex is a valid header value"),
+    );
+    Ok(response)
+}
+
+/// Whether `If-None-Match` lists `etag` (or `*`)
+fn etag_matches(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|tags| {
+            tags.split(',')
+                .map(str::trim)
+                .any(|tag| tag == etag || tag == "*")
+        })
+}
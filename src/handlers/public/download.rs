@@ -0,0 +1,80 @@
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use axum_extra::{
+    TypedHeader,
+    headers::{Authorization, authorization::Bearer},
+};
+use chrono::Utc;
+use serde::Deserialize;
+
+use super::validate::check_bearer_license;
+use crate::artifacts::{DOWNLOAD_URL_LIFETIME_SECS, signed_download_url};
+use crate::db::{AppState, queries};
+use crate::error::{AppError, OptionExt, Result, msg};
+use crate::extractors::Query;
+
+#[derive(Debug, Deserialize)]
+pub struct DownloadQuery {
+    /// Public key - identifies the project (or send the X-Paycheck-Project header)
+    #[serde(default)]
+    pub public_key: Option<String>,
+}
+
+/// GET /download/{artifact_id}
+/// Validate the license token in the Authorization header like `/validate`,
+/// check that the license's updates window covers the artifact's release,
+/// then redirect (302) to a short-lived signed URL for it. The bytes come
+/// from the vendor's own storage; see [`crate::artifacts`] for the scheme.
+///
+/// A release newer than the updates window is refused with
+/// `updates_expired`, listing the renewal products that would extend it.
+pub async fn download_artifact(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Path(artifact_id): Path<String>,
+    Query(query): Query<DownloadQuery>,
+) -> Result<Response> {
+    let public_key = super::require_publishable_key(&headers, query.public_key.as_deref())?;
+    let (conn, project) = state
+        .project_by_public_key(&public_key)?
+        .or_not_found(msg::PROJECT_NOT_FOUND)?;
+
+    let now = Utc::now().timestamp();
+    let (license, _, _) =
+        check_bearer_license(&state, &headers, &conn, &project, auth.token(), now)?;
+
+    let artifact = queries::get_artifact(&conn, &project.id, &artifact_id)?
+        .or_not_found(msg::ARTIFACT_NOT_FOUND)?;
+
+    if !license.covers_release(artifact.released_at) {
+        let renewal_product_ids =
+            queries::list_updates_renewals_for_product(&conn, &license.product_id, now)?
+                .into_iter()
+                .map(|product| product.id)
+                .collect();
+        return Err(AppError::UpdatesExpired {
+            updates_expires_at: license.updates_expires_at.unwrap_or_default(),
+            renewal_product_ids,
+        });
+    }
+
+    let url = signed_download_url(
+        &artifact.url,
+        &state.master_key.download_signing_key(&project.id),
+        now + DOWNLOAD_URL_LIFETIME_SECS,
+    )?;
+    queries::record_artifact_download(&conn, &artifact.id, &license.id, now)?;
+
+    Ok((
+        StatusCode::FOUND,
+        [
+            (header::LOCATION, url),
+            (header::CACHE_CONTROL, "no-store".to_string()),
+        ],
+    )
+        .into_response())
+}
@@ -47,6 +47,8 @@ pub enum LicenseStatus {
     Active,
     Expired,
     Revoked,
+    /// A dispute over the purchase is open
+    SuspendedForDispute,
 }
 
 /// GET /license - Get license info
@@ -56,15 +58,19 @@ pub async fn get_license_info(
     TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
     Query(query): Query<LicenseQuery>,
 ) -> Result<Json<LicenseResponse>> {
-    let conn = state.db.get()?;
     let token = auth.token();
 
     // Look up project by public key (validates project exists)
-    let _project = queries::get_project_by_public_key(&conn, &query.public_key)?
+    let (conn, project) = state
+        .project_by_public_key(&query.public_key)?
         .or_not_found(msg::PROJECT_NOT_FOUND)?;
 
     // Verify JWT signature (allow expired JWTs - we just need identity)
-    let claims = jwt::verify_token_allow_expired(token, &query.public_key)?;
+    let claims = jwt::verify_token_expecting_allow_expired(
+        token,
+        &query.public_key,
+        &project.expected_token_claims(chrono::Utc::now().timestamp()),
+    )?;
 
     // Extract JTI from verified token
     let jti = claims
@@ -91,6 +97,8 @@ pub async fn get_license_info(
     let now = chrono::Utc::now().timestamp();
     let status = if license.revoked {
         LicenseStatus::Revoked
+    } else if license.suspended_for_dispute {
+        LicenseStatus::SuspendedForDispute
     } else if license.expires_at.map(|exp| exp < now).unwrap_or(false) {
         LicenseStatus::Expired
     } else {
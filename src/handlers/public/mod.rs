@@ -1,32 +1,120 @@
 mod activation;
 mod buy;
 mod callback;
+mod catalog;
 mod devices;
+mod discovery;
+mod download;
 mod license;
+mod project_health;
 mod redeem;
 mod refresh;
+mod shared;
 mod validate;
+mod wait;
 
 pub use activation::*;
 pub use buy::*;
 pub use callback::*;
+pub use catalog::*;
 pub use devices::*;
+pub use discovery::*;
+pub use download::*;
 pub use license::*;
+pub use project_health::*;
 pub use redeem::*;
 pub use refresh::*;
+pub use shared::*;
 pub use validate::*;
+pub use wait::*;
 
 use axum::Router;
-use axum::http::{HeaderName, Method};
+use axum::http::{HeaderMap, HeaderName, Method};
 use axum::routing::{get, post};
+use rusqlite::Connection;
 use serde::Serialize;
 use tower_http::cors::{Any, CorsLayer};
 
 use crate::config::RateLimitConfig;
-use crate::db::AppState;
+use crate::db::{AppState, queries};
+use crate::error::{AppError, Result, msg};
 use crate::extractors::Json;
+use crate::middleware::REQUEST_ID_HEADER;
+use crate::models::{License, reported_client_version};
 use crate::rate_limit;
 
+/// Header carrying the project's publishable (public) key, so clients don't
+/// have to put it in every request body.
+pub const PROJECT_KEY_HEADER: &str = "x-paycheck-project";
+
+/// Header carrying the version of the app making the request, checked
+/// against the project's `min_client_version`.
+pub const CLIENT_VERSION_HEADER: &str = "x-paycheck-client-version";
+
+/// The publishable key for a request, from the `X-Paycheck-Project` header or
+/// the request's own `public_key` field. If both are given they must match:
+/// neither is trusted over the other.
+fn publishable_key(headers: &HeaderMap, public_key: Option<&str>) -> Result<Option<String>> {
+    let header = match headers.get(PROJECT_KEY_HEADER) {
+        Some(value) => Some(
+            value
+                .to_str()
+                .map_err(|_| AppError::BadRequest(msg::PROJECT_KEY_HEADER_INVALID.into()))?,
+        ),
+        None => None,
+    };
+    match (header, public_key) {
+        (Some(header), Some(body)) if header != body => {
+            Err(AppError::BadRequest(msg::PROJECT_KEY_MISMATCH.into()))
+        }
+        (Some(key), _) | (None, Some(key)) => Ok(Some(key.to_string())),
+        (None, None) => Ok(None),
+    }
+}
+
+/// Like `publishable_key`, for endpoints where a key is required.
+fn require_publishable_key(headers: &HeaderMap, public_key: Option<&str>) -> Result<String> {
+    publishable_key(headers, public_key)?
+        .ok_or_else(|| AppError::BadRequest(msg::PUBLIC_KEY_REQUIRED.into()))
+}
+
+/// The app version the client reported, from the `X-Paycheck-Client-Version`
+/// header or else the `client_version` query parameter. Only used for the
+/// `update_required` hint and the license's seen versions, so an unreadable
+/// header counts as none.
+fn client_version<'a>(headers: &'a HeaderMap, query: Option<&'a str>) -> Option<&'a str> {
+    headers
+        .get(CLIENT_VERSION_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .or(query)
+}
+
+/// Note the app version a valid license was just used with, for support.
+/// Versions that don't read as one are ignored, writes are throttled (see
+/// [`License::seen_version_due`]), and a failed write is logged rather than
+/// failing the request.
+fn record_seen_version(
+    state: &AppState,
+    conn: &Connection,
+    license: &License,
+    client_version: Option<&str>,
+) {
+    let Some(version) = client_version.and_then(reported_client_version) else {
+        return;
+    };
+    let now = state.clock.now();
+    if !license.seen_version_due(now) {
+        return;
+    }
+    if let Err(e) = queries::record_license_seen_version(conn, &license.id, version, now) {
+        tracing::warn!(
+            license_id = %license.id,
+            "Failed to record app version: {}",
+            e
+        );
+    }
+}
+
 #[derive(Serialize)]
 struct HealthResponse {
     status: &'static str,
@@ -43,24 +131,46 @@ async fn health() -> Json<HealthResponse> {
 pub fn router(rate_limit_config: RateLimitConfig) -> Router<AppState> {
     // Strict tier: external API calls + activation requests
     let strict_routes = Router::new()
-        .route("/buy", post(initiate_buy))
+        .route("/buy", get(initiate_buy).post(initiate_buy))
         .route("/activation/request-code", post(request_activation_code))
-        .layer(rate_limit::strict_layer(rate_limit_config.strict_rpm));
+        .route("/redeem/prepaid", post(redeem_prepaid_code))
+        .route("/redeem/lemonsqueezy", post(redeem_lemonsqueezy_key));
+    let strict_routes = rate_limited(
+        strict_routes,
+        rate_limit_config.strict_rpm,
+        rate_limit::strict_layer,
+    );
 
     // Standard tier: crypto + DB operations
     let standard_routes = Router::new()
         .route("/callback", get(payment_callback))
+        .route("/buy/wait", get(wait_for_payment))
         .route("/redeem", post(redeem_with_code))
+        .route("/redeem/claim", post(redeem_claim_code))
         .route("/refresh", post(refresh_token))
         .route("/validate", post(validate_license))
+        .route("/validate/attest", get(attest_license))
+        .route("/updates/check", get(check_updates))
+        .route("/download/{artifact_id}", get(download_artifact))
         .route("/license", get(get_license_info))
+        .route("/discovery", get(get_discovery))
+        .route("/.well-known/jwks.json", get(get_jwks))
+        .route("/products", get(get_catalog))
         .route("/devices/deactivate", post(deactivate_device))
-        .layer(rate_limit::standard_layer(rate_limit_config.standard_rpm));
+        .route("/shared/license/{token}", get(view_shared_license))
+        .route("/projects/{project_id}/health", get(get_project_health));
+    let standard_routes = rate_limited(
+        standard_routes,
+        rate_limit_config.standard_rpm,
+        rate_limit::standard_layer,
+    );
 
     // Relaxed tier: lightweight operations
-    let relaxed_routes = Router::new()
-        .route("/health", get(health))
-        .layer(rate_limit::relaxed_layer(rate_limit_config.relaxed_rpm));
+    let relaxed_routes = rate_limited(
+        Router::new().route("/health", get(health)),
+        rate_limit_config.relaxed_rpm,
+        rate_limit::relaxed_layer,
+    );
 
     // CORS: Allow any origin since public endpoints are called from customer websites
     let cors = CorsLayer::new()
@@ -69,7 +179,10 @@ pub fn router(rate_limit_config: RateLimitConfig) -> Router<AppState> {
         .allow_headers([
             HeaderName::from_static("authorization"),
             HeaderName::from_static("content-type"),
-        ]);
+            HeaderName::from_static(PROJECT_KEY_HEADER),
+            HeaderName::from_static(CLIENT_VERSION_HEADER),
+        ])
+        .expose_headers([HeaderName::from_static(REQUEST_ID_HEADER)]);
 
     Router::new()
         .merge(strict_routes)
@@ -77,3 +190,16 @@ pub fn router(rate_limit_config: RateLimitConfig) -> Router<AppState> {
         .merge(relaxed_routes)
         .layer(cors)
 }
+
+/// Apply a rate limit tier, or skip it if its rpm is 0 (as the org router does)
+fn rate_limited(
+    routes: Router<AppState>,
+    rpm: u32,
+    layer: fn(u32) -> rate_limit::RateLimitLayer,
+) -> Router<AppState> {
+    if rpm > 0 {
+        routes.layer(layer(rpm))
+    } else {
+        routes
+    }
+}
@@ -0,0 +1,76 @@
+use axum::{
+    extract::State,
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+
+use crate::db::{AppState, queries};
+use crate::error::{OptionExt, Result, msg};
+use crate::extractors::{Json, Path};
+use crate::jwt;
+
+/// How long monitors and caches in front of Paycheck may reuse a result.
+/// Each uncached check decrypts the project's key and signs a token.
+pub const PROJECT_HEALTH_MAX_AGE_SECS: u64 = 60;
+
+/// Response for GET /projects/{project_id}/health. Deliberately says nothing
+/// about the project beyond whether it's being served.
+#[derive(Debug, Serialize)]
+pub struct ProjectHealth {
+    /// "ok", or "unavailable" (503)
+    pub status: &'static str,
+    /// The project's signing key decrypts and signs tokens its public key verifies
+    pub can_sign: bool,
+    /// The public key `/discovery` publishes is readable
+    pub jwks_available: bool,
+}
+
+/// GET /projects/{project_id}/health - Whether Paycheck can issue tokens for
+/// this project right now, for uptime monitoring.
+///
+/// An unknown or deleted project is the same 404. Failures are 503 with no
+/// detail; the cause is only logged.
+pub async fn get_project_health(
+    State(state): State<AppState>,
+    Path(project_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    let conn = state.project_db(&project_id)?.get()?;
+    let project =
+        queries::get_project_by_id(&conn, &project_id)?.or_not_found(msg::PROJECT_NOT_FOUND)?;
+    // On a custom domain (or with X-Paycheck-Project) the path must name that project
+    super::publishable_key(&headers, Some(&project.public_key))?;
+
+    let can_sign = queries::decrypt_project_private_key(&conn, &project, &state.master_key)
+        .and_then(|private_key| jwt::check_signing(&private_key, &project.public_key))
+        .inspect_err(|e| tracing::error!("Project {} can't sign tokens: {}", project.id, e))
+        .is_ok();
+    let jwks_available = jwt::key_id(&project.public_key)
+        .inspect_err(|e| {
+            tracing::error!("Project {} has an unusable public key: {}", project.id, e)
+        })
+        .is_ok();
+
+    let healthy = can_sign && jwks_available;
+    let status = if healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    let mut response = (
+        status,
+        Json(ProjectHealth {
+            status: if healthy { "ok" } else { "unavailable" },
+            can_sign,
+            jwks_available,
+        }),
+    )
+        .into_response();
+    response.headers_mut().insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_str(&format!("public, max-age={}", PROJECT_HEALTH_MAX_AGE_SECS))
+            .expect("valid header value"),
+    );
+    Ok(response)
+}
@@ -2,16 +2,21 @@ use axum::{
     extract::State,
     http::HeaderMap,
 };
-use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::crypto::MasterKey;
-use crate::db::{AppState, queries};
+use crate::db::{AppState, outbox, queries};
 use crate::error::{AppError, OptionExt, Result, msg};
-use crate::extractors::Json;
-use crate::jwt::{self, LicenseClaims};
-use crate::models::{ActorType, AuditAction, AuditLogNames, DeviceType};
+use crate::extractors::{Json, PublicJson};
+use crate::jwt;
+use crate::license_import;
+use crate::models::{
+    ActorType, AuditAction, AuditLogNames, CreateLicense, DeviceType, EmailAddress, Entitlements,
+    OrgLimitName,
+};
+use crate::payments::LemonSqueezyClient;
+use crate::quota;
 use crate::util::{AuditLogBuilder, LicenseExpirations};
 
 // Input length limits to prevent storage exhaustion and oversized JWTs
@@ -51,8 +56,9 @@ fn normalize_activation_code(code: &str) -> String {
 /// Request body for POST /redeem (using short-lived activation code)
 #[derive(Debug, Deserialize)]
 pub struct RedeemRequest {
-    /// Public key - identifies the project
-    pub public_key: String,
+    /// Public key - identifies the project (or send the X-Paycheck-Project header)
+    #[serde(default)]
+    pub public_key: Option<String>,
     /// Short-lived activation code (PREFIX-XXXX-XXXX format)
     pub code: String,
     pub device_id: String,
@@ -63,8 +69,9 @@ pub struct RedeemRequest {
 
 impl RedeemRequest {
     /// Validate input lengths to prevent storage exhaustion attacks.
-    fn validate(&self) -> Result<()> {
-        if self.public_key.len() > MAX_PUBLIC_KEY_LEN {
+    /// `public_key` is the resolved key (header or body).
+    fn validate(&self, public_key: &str) -> Result<()> {
+        if public_key.len() > MAX_PUBLIC_KEY_LEN {
             return Err(AppError::BadRequest(format!(
                 "public_key too long (max {} chars)",
                 MAX_PUBLIC_KEY_LEN
@@ -104,6 +111,8 @@ pub struct RedeemResponse {
     pub updates_exp: Option<i64>,
     pub tier: String,
     pub features: Vec<String>,
+    #[serde(skip_serializing_if = "Entitlements::is_empty")]
+    pub entitlements: Entitlements,
     /// Short-lived activation code for future activations
     pub activation_code: String,
     /// Expiration time of the activation code
@@ -117,15 +126,15 @@ pub struct RedeemResponse {
 pub async fn redeem_with_code(
     State(state): State<AppState>,
     headers: HeaderMap,
-    Json(req): Json<RedeemRequest>,
+    PublicJson(req): PublicJson<RedeemRequest>,
 ) -> Result<Json<RedeemResponse>> {
     // Validate input lengths first (cheap check before any DB operations)
-    req.validate()?;
-
-    let mut conn = state.db.get()?;
+    let public_key = super::require_publishable_key(&headers, req.public_key.as_deref())?;
+    req.validate(&public_key)?;
 
     // Look up project by public key
-    let project = queries::get_project_by_public_key(&conn, &req.public_key)?
+    let (mut conn, project) = state
+        .project_by_public_key(&public_key)?
         .or_not_found(msg::PROJECT_NOT_FOUND)?;
     let project_id = project.id.clone();
     let project_name = project.name.clone();
@@ -156,20 +165,30 @@ pub async fn redeem_with_code(
     let license_id = license.id.clone();
     let product_id = license.product_id.clone();
 
+    // Seat codes only work while the seat is still assigned
+    let seat_id = activation_code.seat_id.as_deref();
+    if let Some(seat_id) = seat_id
+        && queries::get_active_license_seat(&conn, &license.id, seat_id)?.is_none()
+    {
+        return Err(AppError::Forbidden(msg::CANNOT_BE_REDEEMED.into()));
+    }
+
     // Proceed with normal redemption logic
     let result = redeem_license_internal(
         &mut conn,
         &state.master_key,
         &license,
         &project_id,
+        seat_id,
         &req.device_id,
         device_type,
         req.device_name.as_deref(),
+        state.clock.now(),
     )?;
 
     // Audit log successful device activation
     let audit_conn = state.audit.get()?;
-    if let Err(e) = AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    if let Err(e) = AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::Public, None)
         .action(AuditAction::ActivateDevice)
         .resource("device", &req.device_id)
@@ -178,6 +197,7 @@ pub async fn redeem_with_code(
             "product_id": product_id,
             "device_type": req.device_type,
             "device_name": req.device_name,
+            "seat_id": seat_id,
         }))
         .org(&org_id)
         .project(&project_id)
@@ -195,21 +215,395 @@ pub async fn redeem_with_code(
     Ok(result)
 }
 
+/// Normalize a prepaid code to canonical format (PREFIX-XXXX-XXXX-XXXX-XXXX).
+///
+/// Prepaid codes are typed in from printed cards, so besides spaces for
+/// dashes this also accepts lowercase in the random groups.
+fn normalize_prepaid_code(code: &str) -> String {
+    let trimmed = code.trim();
+    let parts: Vec<&str> = trimmed
+        .split(|c: char| c.is_whitespace() || c == '-')
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    if parts.len() == 5 {
+        let groups: Vec<String> = parts[1..].iter().map(|p| p.to_ascii_uppercase()).collect();
+        format!("{}-{}", parts[0], groups.join("-"))
+    } else {
+        trimmed.to_string()
+    }
+}
+
+/// Request body for POST /redeem/prepaid
+#[derive(Debug, Deserialize)]
+pub struct RedeemPrepaidRequest {
+    /// Email for the new license, so it can be recovered later
+    /// (/activation/request-code)
+    pub email: String,
+    /// Project, code, and device, as for POST /redeem (`code` is the prepaid code)
+    #[serde(flatten)]
+    pub redeem: RedeemRequest,
+}
+
+/// POST /redeem/prepaid - Redeem a prepaid code (e.g. from a retail card)
+///
+/// Creates a license to the code's product for the given email, marks the
+/// code used, and activates this device as POST /redeem does. A code works
+/// once, and not after its batch is revoked or expires.
+pub async fn redeem_prepaid_code(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    PublicJson(req): PublicJson<RedeemPrepaidRequest>,
+) -> Result<Json<RedeemResponse>> {
+    let public_key = super::require_publishable_key(&headers, req.redeem.public_key.as_deref())?;
+    req.redeem.validate(&public_key)?;
+    let email = EmailAddress::parse(&req.email)?;
+    let device_type = req
+        .redeem
+        .device_type
+        .parse::<DeviceType>()
+        .ok()
+        .ok_or_else(|| AppError::BadRequest(msg::INVALID_DEVICE_TYPE.into()))?;
+
+    let (mut conn, project) = state
+        .project_by_public_key(&public_key)?
+        .or_not_found(msg::PROJECT_NOT_FOUND)?;
+    let org = queries::get_organization_by_id(&conn, &project.org_id)?
+        .ok_or_else(|| AppError::Internal(msg::ORG_NOT_FOUND.into()))?;
+
+    let now = state.clock.now();
+    let quota = quota::check(
+        &conn,
+        &project.org_id,
+        OrgLimitName::LicensesPerMonth,
+        None,
+        1,
+        now,
+    )?;
+
+    let code = normalize_prepaid_code(&req.redeem.code);
+    let email_hash = state.email_hasher.hash(&email);
+    let names = AuditLogNames {
+        org_name: Some(org.name),
+        project_name: Some(project.name.clone()),
+        ..Default::default()
+    };
+
+    // Claiming the code and creating the license commit together, so a
+    // failure after the claim leaves the code redeemable
+    let audit_conn = state.audit.get()?;
+    let (batch, license) = outbox::with_audited_tx(
+        &mut conn,
+        |tx| {
+            let batch = queries::try_claim_prepaid_code(tx, &project.id, &code, now)?
+                .ok_or_else(|| AppError::Forbidden(msg::CANNOT_BE_REDEEMED.into()))?;
+            let product = queries::get_product_by_id(tx, &batch.product_id)?
+                .ok_or_else(|| AppError::Forbidden(msg::CANNOT_BE_REDEEMED.into()))?;
+            let exps = LicenseExpirations::from_product(&product, now);
+            // The batch ID stands in for an order ID, so support can list
+            // every license redeemed from a batch
+            let license = queries::create_license(
+                tx,
+                &project.id,
+                &product.id,
+                &CreateLicense {
+                    email_hash: Some(email_hash.clone()),
+                    customer_id: None,
+                    expires_at: exps.license_exp,
+                    updates_expires_at: exps.updates_exp,
+                    payment_provider: Some("prepaid".to_string()),
+                    payment_provider_customer_id: None,
+                    payment_provider_subscription_id: None,
+                    payment_provider_order_id: Some(batch.id.clone()),
+                    seats: product.seat_count,
+                },
+            )?;
+            queries::set_prepaid_code_license(tx, &code, &license.id)?;
+            Ok((batch, license))
+        },
+        |(batch, license)| {
+            AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+                .actor(ActorType::Public, None)
+                .action(AuditAction::RedeemPrepaidCode)
+                .resource("license", &license.id)
+                .details(&serde_json::json!({
+                    "batch_id": batch.id,
+                    "product_id": license.product_id,
+                    "email": email.as_str(),
+                    "device_id": req.redeem.device_id,
+                    "device_type": req.redeem.device_type,
+                }))
+                .org(&project.org_id)
+                .project(&project.id)
+                .names(&names)
+                .entry()
+        },
+    )?;
+    outbox::relay(&conn, &audit_conn);
+
+    if let Some(ref warning) = quota {
+        quota::audit_crossing(
+            AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+                .actor(ActorType::Public, None)
+                .org(&project.org_id)
+                .project(&project.id)
+                .names(&names),
+            &project.org_id,
+            warning,
+        );
+    }
+
+    tracing::info!(
+        "Redeemed prepaid code from batch {} as license {} (project: {})",
+        batch.id,
+        license.id,
+        project.id
+    );
+
+    // The license exists from here on: if activation fails, the customer
+    // recovers it by email like any other license
+    redeem_license_internal(
+        &mut conn,
+        &state.master_key,
+        &license,
+        &project.id,
+        None,
+        &req.redeem.device_id,
+        device_type,
+        req.redeem.device_name.as_deref(),
+        now,
+    )
+}
+
+/// Request body for POST /redeem/claim
+#[derive(Debug, Deserialize)]
+pub struct RedeemClaimRequest {
+    /// Payment session the claim code was issued for (the `session_id` from /buy)
+    pub session_id: String,
+    /// Project, code, and device, as for POST /redeem (`code` is the claim
+    /// code from GET /buy/wait)
+    #[serde(flatten)]
+    pub redeem: RedeemRequest,
+}
+
+/// POST /redeem/claim - Redeem a payment session's claim code
+///
+/// Closes the purchase loop for apps that wait on their checkout with
+/// GET /buy/wait: the claim code it hands out activates this device on the
+/// session's license, as POST /redeem does. A code works once, only for its
+/// own session, and for 5 minutes. If activation fails (say, the device limit
+/// is reached) the code stays usable until it expires.
+pub async fn redeem_claim_code(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    PublicJson(req): PublicJson<RedeemClaimRequest>,
+) -> Result<Json<RedeemResponse>> {
+    let public_key = super::require_publishable_key(&headers, req.redeem.public_key.as_deref())?;
+    req.redeem.validate(&public_key)?;
+    if req.session_id.len() > MAX_CODE_LEN {
+        return Err(AppError::BadRequest(format!(
+            "session_id too long (max {} chars)",
+            MAX_CODE_LEN
+        )));
+    }
+    let device_type = req
+        .redeem
+        .device_type
+        .parse::<DeviceType>()
+        .ok()
+        .ok_or_else(|| AppError::BadRequest(msg::INVALID_DEVICE_TYPE.into()))?;
+
+    let (mut conn, project) = state
+        .project_by_public_key(&public_key)?
+        .or_not_found(msg::PROJECT_NOT_FOUND)?;
+    let org = queries::get_organization_by_id(&conn, &project.org_id)?
+        .ok_or_else(|| AppError::Internal(msg::ORG_NOT_FOUND.into()))?;
+
+    // A session of another project is refused like a wrong code, before its
+    // code is touched
+    let in_project = match queries::get_payment_session(&conn, &req.session_id)? {
+        Some(session) => queries::get_product_by_id(&conn, &session.product_id)?
+            .is_some_and(|product| product.project_id == project.id),
+        None => false,
+    };
+    if !in_project {
+        return Err(AppError::Forbidden(msg::CLAIM_CODE_INVALID.into()));
+    }
+
+    // Atomically use the code, so concurrent requests can't both activate with it
+    let session =
+        queries::try_claim_payment_session_code(&conn, &req.session_id, req.redeem.code.trim())?
+            .ok_or_else(|| AppError::Forbidden(msg::CLAIM_CODE_INVALID.into()))?;
+
+    let license = session
+        .license_id
+        .as_deref()
+        .map(|license_id| queries::get_license_by_id(&conn, license_id))
+        .transpose()?
+        .flatten()
+        .ok_or_else(|| AppError::Internal(msg::LICENSE_NOT_FOUND.into()));
+    let result = license.and_then(|license| {
+        redeem_license_internal(
+            &mut conn,
+            &state.master_key,
+            &license,
+            &project.id,
+            None,
+            &req.redeem.device_id,
+            device_type,
+            req.redeem.device_name.as_deref(),
+            state.clock.now(),
+        )
+        .map(|result| (license, result))
+    });
+    let (license, result) = match result {
+        Ok(redeemed) => redeemed,
+        Err(e) => {
+            queries::release_payment_session_claim(&conn, &session.id)?;
+            return Err(e);
+        }
+    };
+
+    let audit_conn = state.audit.get()?;
+    if let Err(e) = AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::Public, None)
+        .action(AuditAction::ActivateDevice)
+        .resource("device", &req.redeem.device_id)
+        .details(&serde_json::json!({
+            "license_id": license.id,
+            "product_id": license.product_id,
+            "device_type": req.redeem.device_type,
+            "device_name": req.redeem.device_name,
+            "payment_session_id": session.id,
+        }))
+        .org(&project.org_id)
+        .project(&project.id)
+        .names(&AuditLogNames {
+            resource_name: req.redeem.device_name.clone(),
+            org_name: Some(org.name),
+            project_name: Some(project.name.clone()),
+            ..Default::default()
+        })
+        .save()
+    {
+        tracing::warn!("Failed to write activation audit log: {}", e);
+    }
+
+    Ok(result)
+}
+
+/// POST /redeem/lemonsqueezy - Redeem a license key LemonSqueezy issued
+///
+/// For customers of a project that moved to Paycheck: `code` is their old
+/// LemonSqueezy key, imported beforehand by the project. Activates this device
+/// on the imported license as POST /redeem does. If the org turned on
+/// `verify_imported_keys`, LemonSqueezy must also still consider the key valid.
+pub async fn redeem_lemonsqueezy_key(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    PublicJson(req): PublicJson<RedeemRequest>,
+) -> Result<Json<RedeemResponse>> {
+    let public_key = super::require_publishable_key(&headers, req.public_key.as_deref())?;
+    req.validate(&public_key)?;
+    let device_type = req
+        .device_type
+        .parse::<DeviceType>()
+        .ok()
+        .ok_or_else(|| AppError::BadRequest(msg::INVALID_DEVICE_TYPE.into()))?;
+
+    let (mut conn, project) = state
+        .project_by_public_key(&public_key)?
+        .or_not_found(msg::PROJECT_NOT_FOUND)?;
+    let org = queries::get_organization_by_id(&conn, &project.org_id)?
+        .ok_or_else(|| AppError::Internal(msg::ORG_NOT_FOUND.into()))?;
+
+    // Unknown and never-imported keys get the same answer as a used code
+    let license = queries::get_license_by_external_key(
+        &conn,
+        &project.id,
+        license_import::LEMONSQUEEZY,
+        &license_import::external_key_hash(&req.code),
+    )?
+    .ok_or_else(|| AppError::Forbidden(msg::CANNOT_BE_REDEEMED.into()))?;
+
+    if let Some(config) = queries::get_org_ls_config(&conn, &org.id, &state.master_key)?
+        && config.verify_imported_keys
+    {
+        let client = LemonSqueezyClient::new(&config);
+        let key = license_import::normalize_external_key(&req.code);
+        let valid = state
+            .provider_calls
+            .run(&org.id, client.validate_license_key(&key))
+            .await?;
+        if !valid {
+            return Err(AppError::Forbidden(msg::CANNOT_BE_REDEEMED.into()));
+        }
+    }
+
+    let result = redeem_license_internal(
+        &mut conn,
+        &state.master_key,
+        &license,
+        &project.id,
+        None,
+        &req.device_id,
+        device_type,
+        req.device_name.as_deref(),
+        state.clock.now(),
+    )?;
+
+    let audit_conn = state.audit.get()?;
+    if let Err(e) = AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::Public, None)
+        .action(AuditAction::ActivateDevice)
+        .resource("device", &req.device_id)
+        .details(&serde_json::json!({
+            "license_id": license.id,
+            "product_id": license.product_id,
+            "device_type": req.device_type,
+            "device_name": req.device_name,
+            "external_key_provider": license_import::LEMONSQUEEZY,
+        }))
+        .org(&org.id)
+        .project(&project.id)
+        .names(&AuditLogNames {
+            resource_name: req.device_name.clone(),
+            org_name: Some(org.name),
+            project_name: Some(project.name),
+            ..Default::default()
+        })
+        .save()
+    {
+        tracing::warn!("Failed to write activation audit log: {}", e);
+    }
+
+    Ok(result)
+}
+
 /// Internal function that handles the actual license redemption logic
+#[allow(clippy::too_many_arguments)]
 fn redeem_license_internal(
     conn: &mut r2d2::PooledConnection<r2d2_sqlite::SqliteConnectionManager>,
     master_key: &MasterKey,
     license: &crate::models::License,
     project_id: &str,
+    seat_id: Option<&str>,
     device_id: &str,
     device_type: DeviceType,
     device_name: Option<&str>,
+    now: i64,
 ) -> Result<Json<RedeemResponse>> {
-    // Check if revoked or expired (generic message to prevent enumeration)
-    let is_expired = license
-        .expires_at
-        .is_some_and(|exp| Utc::now().timestamp() > exp);
-    if license.revoked || is_expired {
+    // The code holder already proved they own the license, so a revocation
+    // carries its reason; expiry keeps the generic message
+    if let Some(revocation) = license.revocation() {
+        return Err(AppError::LicenseRevoked(revocation));
+    }
+    if license.suspended_for_dispute {
+        return Err(AppError::Forbidden(
+            msg::LICENSE_SUSPENDED_FOR_DISPUTE.into(),
+        ));
+    }
+    if license.expires_at.is_some_and(|exp| now > exp) {
         return Err(AppError::Forbidden(msg::CANNOT_BE_REDEEMED.into()));
     }
 
@@ -228,7 +622,11 @@ fn redeem_license_internal(
 
     // Generate JTI for the new token
     let jti = Uuid::new_v4().to_string();
-    let now = Utc::now().timestamp();
+
+    // Team licenses: device_limit applies per seat, activation_limit to the whole team
+    let activation_limit = product
+        .activation_limit
+        .map(|limit| limit.saturating_mul(license.seats.unwrap_or(1)));
 
     // Atomically acquire device (handles limit checks + creation in a transaction)
     // This prevents race conditions where concurrent requests could bypass device limits
@@ -239,42 +637,40 @@ fn redeem_license_internal(
         device_type,
         &jti,
         device_name,
+        seat_id,
+        Some(&jwt::key_id(&project.public_key)?),
         product.device_limit,
-        product.activation_limit,
+        activation_limit,
         product.device_inactive_days,
     )?;
 
-    // Calculate expirations
-    let exps = LicenseExpirations::from_product(&product, now);
-
-    // Build claims
-    let claims = LicenseClaims {
-        license_exp: exps.license_exp,
-        updates_exp: exps.updates_exp,
-        tier: product.tier.clone(),
-        features: product.features.clone(),
-        device_id: device_id.to_string(),
-        device_type: match device_type {
-            DeviceType::Uuid => "uuid".to_string(),
-            DeviceType::Machine => "machine".to_string(),
-        },
-        product_id: product.id.clone(),
+    let device = jwt::DeviceInfo {
+        device_id,
+        device_type,
+        activated_at: now,
     };
+    let license_token = jwt::build_license_claims(license, &product, &project, &device);
 
     // Decrypt the private key and sign the JWT
-    let private_key = master_key.decrypt_private_key(&project.id, &project.private_key)?;
-    let token = jwt::sign_claims(&claims, &private_key, &license.id, &project.name, &jti)?;
+    let private_key = queries::decrypt_project_private_key(conn, &project, master_key)?;
+    let token = jwt::sign_license_token(&license_token, &private_key, &jti)?;
 
     // Create a fresh activation code for future activations (e.g., on new device)
-    let new_activation_code =
-        queries::create_activation_code(conn, &license.id, &project.license_key_prefix)?;
+    let new_activation_code = queries::create_activation_code_for_seat(
+        conn,
+        &license.id,
+        seat_id,
+        &project.license_key_prefix,
+    )?;
 
+    let claims = license_token.claims;
     Ok(Json(RedeemResponse {
         token,
-        license_exp: exps.license_exp,
-        updates_exp: exps.updates_exp,
-        tier: product.tier,
-        features: product.features,
+        license_exp: claims.license_exp,
+        updates_exp: claims.updates_exp,
+        tier: claims.tier,
+        features: claims.features,
+        entitlements: claims.entitlements,
         activation_code: new_activation_code.code,
         activation_code_expires_at: new_activation_code.expires_at,
     }))
@@ -326,4 +722,21 @@ mod tests {
         assert_eq!(normalize_activation_code("invalid"), "invalid");
         assert_eq!(normalize_activation_code("  invalid  "), "invalid");
     }
+
+    #[test]
+    fn test_normalize_prepaid_code() {
+        assert_eq!(
+            normalize_prepaid_code("MYAPP-AB3D-EF5G-HJ6K-LM7N"),
+            "MYAPP-AB3D-EF5G-HJ6K-LM7N"
+        );
+
+        // Typed from a card: spaces and lowercase groups
+        assert_eq!(
+            normalize_prepaid_code(" MYAPP ab3d ef5g hj6k lm7n "),
+            "MYAPP-AB3D-EF5G-HJ6K-LM7N"
+        );
+
+        // An activation code isn't rewritten into a prepaid one
+        assert_eq!(normalize_prepaid_code("MYAPP-AB3D-EF5G"), "MYAPP-AB3D-EF5G");
+    }
 }
@@ -4,11 +4,13 @@ use chrono::Utc;
 use serde::Serialize;
 
 use crate::db::{AppState, queries};
-use crate::error::{AppError, Result};
-use crate::extractors::Json;
-use crate::jwt::{self, LicenseClaims};
+use crate::error::{AppError, Result, msg};
+use crate::extractors::{Json, Query};
+use crate::jwt;
 use crate::models::{ActorType, AuditAction, AuditLogNames};
-use crate::util::{AuditLogBuilder, LicenseExpirations, extract_bearer_token};
+use crate::util::{AuditLogBuilder, extract_bearer_token};
+
+use super::{ClientHints, ClientVersionQuery};
 
 /// Validate that a string is a valid UUID format.
 /// This is a cheap check to reject garbage before hitting the database.
@@ -19,6 +21,8 @@ fn is_valid_uuid(s: &str) -> bool {
 #[derive(Debug, Serialize)]
 pub struct RefreshResponse {
     pub token: String,
+    #[serde(flatten)]
+    pub hints: ClientHints,
 }
 
 /// Refresh an existing JWT to get a new token with updated expiration.
@@ -28,15 +32,14 @@ pub struct RefreshResponse {
 /// with updated license_exp/updates_exp from the database.
 ///
 /// This allows apps to get fresh tokens without storing the license key.
+/// The response also carries the project's client flags, like `/validate`.
 pub async fn refresh_token(
     State(state): State<AppState>,
     headers: HeaderMap,
+    Query(query): Query<ClientVersionQuery>,
 ) -> Result<Json<RefreshResponse>> {
     let token = extract_bearer_token(&headers).ok_or(AppError::Unauthorized)?;
 
-    let conn = state.db.get()?;
-    let audit_conn = state.audit.get()?;
-
     // Decode without verification to get product_id for key lookup
     let unverified_claims = jwt::decode_unverified(token)?;
 
@@ -45,6 +48,9 @@ pub async fn refresh_token(
         return Err(AppError::Unauthorized);
     }
 
+    let conn = state.product_db(&unverified_claims.product_id)?.get()?;
+    let audit_conn = state.audit.get()?;
+
     // Look up the product and project
     let product = queries::get_product_by_id(&conn, &unverified_claims.product_id)?
         .ok_or(AppError::Unauthorized)?;
@@ -53,8 +59,12 @@ pub async fn refresh_token(
         queries::get_project_by_id(&conn, &product.project_id)?.ok_or(AppError::Unauthorized)?;
 
     // Now verify the token signature (allowing expired tokens)
-    let verified = jwt::verify_token_allow_expired(token, &project.public_key)
-        .map_err(|_| AppError::Unauthorized)?;
+    let verified = jwt::verify_token_expecting_allow_expired(
+        token,
+        &project.public_key,
+        &project.expected_token_claims(Utc::now().timestamp()),
+    )
+    .map_err(|_| AppError::Unauthorized)?;
 
     let jti = verified.jwt_id.ok_or(AppError::Unauthorized)?;
 
@@ -80,49 +90,49 @@ pub async fn refresh_token(
         return Err(AppError::Unauthorized);
     }
 
-    // Check if license has expired (database-level expiration, not JWT exp)
-    if let Some(expires_at) = license.expires_at
-        && Utc::now().timestamp() > expires_at
-    {
+    if license.suspended_for_dispute {
+        return Err(AppError::Forbidden(
+            msg::LICENSE_SUSPENDED_FOR_DISPUTE.into(),
+        ));
+    }
+
+    // Check if license has expired (database-level expiration, not JWT exp;
+    // paused licenses keep working)
+    if license.is_expired_for_validation(Utc::now().timestamp()) {
         return Err(AppError::Unauthorized);
     }
 
     // Update last_seen_at
     queries::update_device_last_seen(&conn, &device.id)?;
-
-    // Calculate fresh expirations from current database values
-    let now = Utc::now().timestamp();
-    let exps = LicenseExpirations::from_product(&product, device.activated_at);
+    let client_version = super::client_version(&headers, query.client_version.as_deref());
+    super::record_seen_version(&state, &conn, &license, client_version);
+
+    // Build claims with fresh expirations from current database values
+    let license_token = jwt::build_license_claims(
+        &license,
+        &product,
+        &project,
+        &jwt::DeviceInfo {
+            device_id: &device.device_id,
+            device_type: device.device_type,
+            activated_at: device.activated_at,
+        },
+    );
 
     // Check if license_exp has passed
-    if let Some(exp) = exps.license_exp
-        && now > exp
+    if license_token
+        .claims
+        .is_license_expired(Utc::now().timestamp())
     {
         return Err(AppError::Unauthorized);
     }
 
-    // Build new claims with updated expirations
-    let claims = LicenseClaims {
-        license_exp: exps.license_exp,
-        updates_exp: exps.updates_exp,
-        tier: product.tier.clone(),
-        features: product.features.clone(),
-        device_id: device.device_id.clone(),
-        device_type: match device.device_type {
-            crate::models::DeviceType::Uuid => "uuid".to_string(),
-            crate::models::DeviceType::Machine => "machine".to_string(),
-        },
-        product_id: product.id.clone(),
-    };
-
     // Sign new JWT
-    let private_key = state
-        .master_key
-        .decrypt_private_key(&project.id, &project.private_key)?;
-    let new_token = jwt::sign_claims(&claims, &private_key, &license.id, &project.name, &jti)?;
+    let private_key = queries::decrypt_project_private_key(&conn, &project, &state.master_key)?;
+    let new_token = jwt::sign_license_token(&license_token, &private_key, &jti)?;
 
     // Audit log the refresh
-    AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+    AuditLogBuilder::for_state(&audit_conn, &state, &headers)
         .actor(ActorType::Public, None)
         .action(AuditAction::RefreshToken)
         .resource("device", &device.id)
@@ -138,5 +148,8 @@ pub async fn refresh_token(
         })
         .save()?;
 
-    Ok(Json(RefreshResponse { token: new_token }))
+    Ok(Json(RefreshResponse {
+        token: new_token,
+        hints: ClientHints::for_project(&project, client_version),
+    }))
 }
@@ -0,0 +1,206 @@
+use axum::{
+    extract::State,
+    http::{HeaderMap, HeaderValue, header},
+    response::{Html, IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::crypto::hash_secret;
+use crate::db::{AppState, queries};
+use crate::error::{AppError, OptionExt, Result, msg};
+use crate::extractors::{Json, Path};
+use crate::models::{ActorType, AuditAction, AuditLogNames};
+use crate::util::AuditLogBuilder;
+
+use super::LicenseStatus;
+
+/// License details shown on a share link page.
+/// Deliberately limited: no email hash, payment IDs, or activation codes.
+#[derive(Debug, Serialize)]
+pub struct SharedLicenseResponse {
+    pub product_name: String,
+    /// What to quote to support, e.g. PRO-000123
+    pub reference_number: Option<String>,
+    pub status: LicenseStatus,
+    pub expires_at: Option<i64>,
+    pub updates_expires_at: Option<i64>,
+    pub device_count: i32,
+    /// None = unlimited
+    pub device_limit: Option<i32>,
+    /// Masked email (e.g. j***@example.com), if the link was created with one
+    pub email: Option<String>,
+    /// When this link stops working
+    pub link_expires_at: i64,
+}
+
+/// GET /shared/license/{token} - View license details through a share link
+/// Returns JSON when the client accepts application/json, otherwise an HTML page.
+pub async fn view_shared_license(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    let token_hash = hash_secret(&token);
+    let pool = state
+        .find_db(|conn| Ok(queries::get_share_link_by_token_hash(conn, &token_hash)?.is_some()))?;
+    let conn = pool.get()?;
+
+    // Unknown, revoked, and expired links are indistinguishable to the viewer
+    let now = Utc::now().timestamp();
+    let share_link = queries::get_share_link_by_token_hash(&conn, &token_hash)?
+        .filter(|link| !link.revoked && link.expires_at > now)
+        .or_not_found(msg::SHARE_LINK_UNAVAILABLE)?;
+
+    let license = queries::get_license_by_id(&conn, &share_link.license_id)?
+        .or_not_found(msg::SHARE_LINK_UNAVAILABLE)?;
+    let product = queries::get_product_by_id(&conn, &license.product_id)?
+        .ok_or_else(|| AppError::Internal(msg::PRODUCT_NOT_FOUND.into()))?;
+    let project = queries::get_project_by_id(&conn, &license.project_id)?
+        .ok_or_else(|| AppError::Internal(msg::PROJECT_NOT_FOUND.into()))?;
+
+    let status = if license.revoked {
+        LicenseStatus::Revoked
+    } else if license.suspended_for_dispute {
+        LicenseStatus::SuspendedForDispute
+    } else if license.expires_at.map(|exp| exp < now).unwrap_or(false) {
+        LicenseStatus::Expired
+    } else {
+        LicenseStatus::Active
+    };
+
+    let details = SharedLicenseResponse {
+        product_name: product.name,
+        reference_number: license.reference_number,
+        status,
+        expires_at: license.expires_at,
+        updates_expires_at: license.updates_expires_at,
+        device_count: queries::count_devices_for_license(&conn, &license.id)?,
+        device_limit: product.device_limit,
+        email: share_link.masked_email,
+        link_expires_at: share_link.expires_at,
+    };
+
+    let audit_conn = state.audit.get()?;
+    if let Err(e) = AuditLogBuilder::for_state(&audit_conn, &state, &headers)
+        .actor(ActorType::Public, None)
+        .action(AuditAction::ViewShareLink)
+        .resource("license", &license.id)
+        .details(&serde_json::json!({
+            "share_link_id": share_link.id,
+        }))
+        .org(&project.org_id)
+        .project(&project.id)
+        .names(&AuditLogNames {
+            project_name: Some(project.name),
+            ..Default::default()
+        })
+        .save()
+    {
+        tracing::warn!("Failed to write share link audit log: {}", e);
+    }
+
+    let wants_json = headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|accept| accept.contains("application/json"));
+
+    let mut response = if wants_json {
+        Json(details).into_response()
+    } else {
+        Html(render_page(&details)).into_response()
+    };
+
+    // The URL is the credential: keep it out of caches and Referer headers
+    let response_headers = response.headers_mut();
+    response_headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    response_headers.insert(
+        header::REFERRER_POLICY,
+        HeaderValue::from_static("no-referrer"),
+    );
+    Ok(response)
+}
+
+fn render_page(details: &SharedLicenseResponse) -> String {
+    let status = match details.status {
+        LicenseStatus::Active => "Active",
+        LicenseStatus::Expired => "Expired",
+        LicenseStatus::Revoked => "Revoked",
+        LicenseStatus::SuspendedForDispute => "Suspended (payment disputed)",
+    };
+    let device_limit = details
+        .device_limit
+        .map(|limit| limit.to_string())
+        .unwrap_or_else(|| "unlimited".to_string());
+    let reference_row = details
+        .reference_number
+        .as_deref()
+        .map(|number| format!("<dt>Reference</dt><dd>{}</dd>\n", escape_html(number)))
+        .unwrap_or_default();
+    let email_row = details
+        .email
+        .as_deref()
+        .map(|email| format!("<dt>Email</dt><dd>{}</dd>\n", escape_html(email)))
+        .unwrap_or_default();
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<meta name="robots" content="noindex">
+<title>{product} license</title>
+<style>
+body {{ font-family: system-ui, sans-serif; max-width: 32rem; margin: 3rem auto; padding: 0 1rem; color: #222; }}
+dl {{ display: grid; grid-template-columns: max-content 1fr; gap: 0.5rem 1.5rem; }}
+dt {{ color: #666; }}
+dd {{ margin: 0; }}
+footer {{ margin-top: 2rem; color: #888; font-size: 0.875rem; }}
+</style>
+</head>
+<body>
+<h1>{product}</h1>
+<dl>
+{reference_row}<dt>Status</dt><dd>{status}</dd>
+{email_row}<dt>License expires</dt><dd>{expires}</dd>
+<dt>Updates until</dt><dd>{updates}</dd>
+<dt>Devices</dt><dd>{devices} of {device_limit}</dd>
+</dl>
+<footer>This link expires {link_expires}.</footer>
+</body>
+</html>
+"#,
+        product = escape_html(&details.product_name),
+        reference_row = reference_row,
+        status = status,
+        email_row = email_row,
+        expires = format_timestamp(details.expires_at),
+        updates = format_timestamp(details.updates_expires_at),
+        devices = details.device_count,
+        device_limit = device_limit,
+        link_expires = format_timestamp(Some(details.link_expires_at)),
+    )
+}
+
+fn format_timestamp(timestamp: Option<i64>) -> String {
+    match timestamp.and_then(|ts| DateTime::from_timestamp(ts, 0)) {
+        Some(dt) => dt.format("%Y-%m-%d %H:%M UTC").to_string(),
+        None => "Never".to_string(),
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
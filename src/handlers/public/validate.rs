@@ -1,92 +1,368 @@
-use axum::extract::State;
-use chrono::Utc;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use axum::{
+    Extension,
+    extract::{ConnectInfo, State},
+    http::HeaderMap,
+};
+use axum_extra::{
+    TypedHeader,
+    headers::{Authorization, authorization::Bearer},
+};
+use chrono::{DateTime, Utc};
+use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
 use crate::db::{AppState, queries};
-use crate::error::{AppError, Result, msg};
-use crate::extractors::Json;
-use crate::util::LicenseExpirations;
+use crate::error::{AppError, OptionExt, Result, msg};
+use crate::extractors::{Json, PublicJson, Query};
+use crate::geoip::GeoIp;
+use crate::jwt::{self, AttestationClaims};
+use crate::models::{Availability, Entitlements, License, Product, Project, Revocation};
+use crate::rate_limit::ValidationCheck;
+use crate::util::{LicenseExpirations, client_ip, extract_request_info};
 
 #[derive(Debug, Deserialize)]
 pub struct ValidateRequest {
-    /// Public key - identifies the project
-    pub public_key: String,
+    /// Public key - identifies the project (or send the X-Paycheck-Project header)
+    #[serde(default)]
+    pub public_key: Option<String>,
     pub jti: String,
 }
 
+/// Query parameters `/validate` and `/refresh` accept alongside their body
+#[derive(Debug, Default, Deserialize)]
+pub struct ClientVersionQuery {
+    /// The app's version (or send the X-Paycheck-Client-Version header)
+    #[serde(default)]
+    pub client_version: Option<String>,
+}
+
+/// Query parameters `/validate` accepts alongside its body
+#[derive(Debug, Default, Deserialize)]
+pub struct ValidateQuery {
+    /// The app's version (or send the X-Paycheck-Client-Version header)
+    #[serde(default)]
+    pub client_version: Option<String>,
+    /// Also list the token IDs revoked on the license
+    #[serde(default)]
+    pub include_revocations: bool,
+}
+
+/// The project's hints for deployed apps, in `/validate` and `/refresh`
+/// responses. Read from the project on each call rather than signed into
+/// tokens, so changes apply at once.
+#[derive(Debug, Default, Serialize)]
+pub struct ClientHints {
+    /// The project's `client_flags`, as set
+    pub client_flags: Map<String, Value>,
+    /// The app reported a version older than `min_client_version`
+    pub update_required: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_client_version: Option<String>,
+}
+
+impl ClientHints {
+    pub fn for_project(project: &Project, client_version: Option<&str>) -> Self {
+        Self {
+            client_flags: project.client_flags.clone(),
+            update_required: project.update_required(client_version),
+            min_client_version: project.min_client_version.clone(),
+        }
+    }
+}
+
+/// A token revoked on the license, e.g. by deactivating a sibling device.
+/// Apps that validate offline can drop a cached token whose `jti` is listed.
+#[derive(Debug, Serialize)]
+pub struct RevokedToken {
+    pub jti: String,
+    pub revoked_at: i64,
+}
+
 #[derive(Debug, Serialize)]
 pub struct ValidateResponse {
     pub valid: bool,
+    /// Only set for revoked licenses ("revoked") and licenses suspended during
+    /// a payment dispute ("suspended_for_dispute"); other failures stay generic
     pub reason: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub license_exp: Option<i64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub updates_exp: Option<i64>,
+    /// Whether the license's update window is still open, per `updates_expires_at`
+    pub updates_valid: bool,
+    /// When update access ends (None = perpetual), from the license itself:
+    /// renewal webhooks move it, while a token's `updates_exp` claim stays
+    /// as it was when the token was issued
+    pub updates_expires_at: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revocation: Option<Revocation>,
+    /// The product's upsell target, while it's on sale: pass it to /buy to
+    /// start the upgrade
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upgrade_to_product_id: Option<String>,
+    /// The product's structured entitlements as they are now (a token's
+    /// `entitlements` claim is as of when it was issued). Valid licenses only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entitlements: Option<Entitlements>,
+    /// Tokens revoked on the license, oldest first. Only with
+    /// `include_revocations=true`, and only for valid licenses.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revoked_jtis: Option<Vec<RevokedToken>>,
+    /// Sent whether or not the license is valid, so a killswitch or required
+    /// update still reaches apps whose license lapsed
+    #[serde(flatten)]
+    pub hints: ClientHints,
 }
 
 pub async fn validate_license(
     State(state): State<AppState>,
-    Json(req): Json<ValidateRequest>,
+    headers: HeaderMap,
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+    geoip: Option<Extension<Arc<GeoIp>>>,
+    Query(query): Query<ValidateQuery>,
+    PublicJson(req): PublicJson<ValidateRequest>,
 ) -> Result<Json<ValidateResponse>> {
-    let conn = state.db.get()?;
+    let public_key = super::require_publishable_key(&headers, req.public_key.as_deref())?;
 
     // Helper for invalid responses - no reason given to prevent information disclosure
-    let invalid_response = || {
+    let invalid_response = |hints: ClientHints| {
         Json(ValidateResponse {
             valid: false,
             reason: None,
             license_exp: None,
             updates_exp: None,
+            updates_valid: false,
+            updates_expires_at: None,
+            revocation: None,
+            upgrade_to_product_id: None,
+            entitlements: None,
+            revoked_jtis: None,
+            hints,
         })
     };
 
     // Look up project by public key
-    let project = match queries::get_project_by_public_key(&conn, &req.public_key)? {
-        Some(p) => p,
-        None => return Ok(invalid_response()),
+    let (conn, project) = match state.project_by_public_key(&public_key)? {
+        Some(found) => found,
+        None => return Ok(invalid_response(ClientHints::default())),
     };
-    let project_id = project.id;
+    let client_version = super::client_version(&headers, query.client_version.as_deref());
+    let hints = ClientHints::for_project(&project, client_version);
+
+    match check_license(&state, &headers, &conn, &project, &req.jti)? {
+        // A revoked license tells its own token holder why, so the app can show it
+        Validation::Revoked(revocation) => Ok(Json(ValidateResponse {
+            valid: false,
+            reason: Some("revoked".into()),
+            license_exp: None,
+            updates_exp: None,
+            updates_valid: false,
+            updates_expires_at: None,
+            revocation: Some(revocation),
+            upgrade_to_product_id: None,
+            entitlements: None,
+            revoked_jtis: None,
+            hints,
+        })),
+        Validation::Suspended => Ok(Json(ValidateResponse {
+            valid: false,
+            reason: Some("suspended_for_dispute".into()),
+            license_exp: None,
+            updates_exp: None,
+            updates_valid: false,
+            updates_expires_at: None,
+            revocation: None,
+            upgrade_to_product_id: None,
+            entitlements: None,
+            revoked_jtis: None,
+            hints,
+        })),
+        Validation::Invalid => Ok(invalid_response(hints)),
+        Validation::Valid {
+            license,
+            product,
+            exps,
+        } => {
+            if project.usage_geo_enabled
+                && let Some(Extension(geoip)) = geoip
+            {
+                let peer = connect_info.map(|Extension(ConnectInfo(addr))| addr.ip());
+                record_usage_geo(&state, &conn, &geoip, &product, client_ip(&headers, peer));
+            }
+            super::record_seen_version(&state, &conn, &license, client_version);
+            let revoked_jtis = if query.include_revocations {
+                let revoked = queries::list_revoked_jtis(&conn, &license.id)?;
+                Some(
+                    revoked
+                        .into_iter()
+                        .map(|r| RevokedToken {
+                            jti: r.jti,
+                            revoked_at: r.revoked_at,
+                        })
+                        .collect(),
+                )
+            } else {
+                None
+            };
+            Ok(Json(ValidateResponse {
+                valid: true,
+                reason: None,
+                license_exp: exps.license_exp,
+                updates_exp: exps.updates_exp,
+                updates_valid: license.covers_release(Utc::now().timestamp()),
+                updates_expires_at: license.updates_expires_at,
+                revocation: None,
+                upgrade_to_product_id: upgrade_target(&conn, &product, state.clock.now())?,
+                entitlements: Some(product.entitlements),
+                revoked_jtis,
+                hints,
+            }))
+        }
+    }
+}
+
+/// Count a valid check in the project's usage geography. Only the country the
+/// client's address resolves to is stored, and a failure to store it is
+/// logged rather than failing the check.
+fn record_usage_geo(
+    state: &AppState,
+    conn: &Connection,
+    geoip: &GeoIp,
+    product: &Product,
+    ip: Option<IpAddr>,
+) {
+    let Some(country) = ip.and_then(|ip| geoip.country(ip)) else {
+        return;
+    };
+    let Some(today) = DateTime::from_timestamp(state.clock.now(), 0) else {
+        return;
+    };
+    let date = today.format("%Y-%m-%d").to_string();
+    if let Err(e) =
+        queries::record_usage_geo(conn, &date, &product.project_id, &product.id, &country)
+    {
+        tracing::warn!(
+            project_id = %product.project_id,
+            "Failed to record usage geography: {}",
+            e
+        );
+    }
+}
+
+/// The product's upsell target, if it's still a product of the project that
+/// can be bought at `now`.
+fn upgrade_target(conn: &Connection, product: &Product, now: i64) -> Result<Option<String>> {
+    let Some(ref upsell) = product.upsell else {
+        return Ok(None);
+    };
+    let target = queries::get_product_by_id(conn, &upsell.upgrade_to_product_id)?;
+    Ok(target
+        .filter(|target| {
+            target.project_id == product.project_id
+                && target.availability_at(now) == Availability::Available
+        })
+        .map(|target| target.id))
+}
 
+/// Outcome of validating a token's `jti` against a project
+enum Validation {
+    Valid {
+        license: License,
+        product: Product,
+        exps: LicenseExpirations,
+    },
+    Revoked(Revocation),
+    /// A dispute over the purchase is open
+    Suspended,
+    Invalid,
+}
+
+/// The checks behind `/validate`: the device and license behind a `jti` exist,
+/// belong to the project, and are neither revoked, suspended nor expired. Counts against
+/// the license's hourly validation limit and records the device as seen.
+fn check_license(
+    state: &AppState,
+    headers: &HeaderMap,
+    conn: &Connection,
+    project: &Project,
+    jti: &str,
+) -> Result<Validation> {
     // Find the device by JTI
-    let device = match queries::get_device_by_jti(&conn, &req.jti)? {
+    let device = match queries::get_device_by_jti(conn, jti)? {
         Some(d) => d,
-        None => return Ok(invalid_response()),
+        None => return Ok(Validation::Invalid),
     };
 
     // Get the license
-    let license = match queries::get_license_by_id(&conn, &device.license_id)? {
+    let license = match queries::get_license_by_id(conn, &device.license_id)? {
         Some(l) => l,
-        None => return Ok(invalid_response()),
+        None => return Ok(Validation::Invalid),
     };
 
-    // Check if license is revoked
-    if license.revoked {
-        return Ok(invalid_response());
+    if let Some(revocation) = license.revocation() {
+        return Ok(Validation::Revoked(revocation));
     }
 
     // Check if this specific JTI is revoked
-    if queries::is_jti_revoked(&conn, &req.jti)? {
-        return Ok(invalid_response());
+    if queries::is_jti_revoked(conn, jti)? {
+        return Ok(Validation::Invalid);
     }
 
-    // Check if license has expired
-    if let Some(expires_at) = license.expires_at
-        && Utc::now().timestamp() > expires_at
-    {
-        return Ok(invalid_response());
+    if license.suspended_for_dispute {
+        return Ok(Validation::Suspended);
+    }
+
+    // Check if license has expired (paused licenses keep validating)
+    if license.is_expired_for_validation(Utc::now().timestamp()) {
+        return Ok(Validation::Invalid);
     }
 
     // Get the product for expiration info
-    let product = queries::get_product_by_id(&conn, &license.product_id)?
+    let product = queries::get_product_by_id(conn, &license.product_id)?
         .ok_or_else(|| AppError::Internal(msg::PRODUCT_NOT_FOUND.into()))?;
 
     // Verify project matches
-    if product.project_id != project_id {
-        return Ok(invalid_response());
+    if product.project_id != project.id {
+        return Ok(Validation::Invalid);
+    }
+
+    // Per-license hourly limit: one license validating far more often than a
+    // real install would is usually a cracked build or a shared token
+    if let Some(limit) = project.max_validations_per_hour_per_license {
+        let (ip, _) = extract_request_info(headers);
+        // X-Forwarded-For may list proxies after the client
+        let client_ip = ip
+            .as_deref()
+            .and_then(|v| v.split(',').next())
+            .map(str::trim);
+        let now = state.clock.now();
+        if let ValidationCheck::Throttled {
+            retry_after_secs,
+            first_in_window,
+            distinct_ips,
+        } = state
+            .validation_limiter
+            .check(&license.id, client_ip, limit, now)
+        {
+            if first_in_window {
+                queries::flag_license_abuse(conn, &license.id, distinct_ips, now)?;
+                tracing::warn!(
+                    license_id = %license.id,
+                    project_id = %project.id,
+                    distinct_ips,
+                    "License exceeded its hourly validation limit"
+                );
+            }
+            return Err(AppError::ValidationThrottled { retry_after_secs });
+        }
     }
 
     // Update last seen
-    queries::update_device_last_seen(&conn, &device.id)?;
+    queries::update_device_last_seen(conn, &device.id)?;
 
     // Calculate current expirations based on activation time
     let exps = LicenseExpirations::from_product(&product, device.activated_at);
@@ -95,13 +371,154 @@ pub async fn validate_license(
     if let Some(exp) = exps.license_exp
         && Utc::now().timestamp() > exp
     {
-        return Ok(invalid_response());
+        return Ok(Validation::Invalid);
+    }
+
+    Ok(Validation::Valid {
+        license,
+        product,
+        exps,
+    })
+}
+
+/// `check_license` for the token in an Authorization header, which must be
+/// signed with the project's key. A license that doesn't pass is an error.
+pub(super) fn check_bearer_license(
+    state: &AppState,
+    headers: &HeaderMap,
+    conn: &Connection,
+    project: &Project,
+    token: &str,
+    now: i64,
+) -> Result<(License, Product, LicenseExpirations)> {
+    let claims = jwt::verify_token_expecting(
+        token,
+        &project.public_key,
+        &project.expected_token_claims(now),
+    )?;
+    let jti = claims
+        .jwt_id
+        .ok_or_else(|| AppError::BadRequest(msg::TOKEN_MISSING_JTI.into()))?;
+
+    match check_license(state, headers, conn, project, &jti)? {
+        Validation::Valid {
+            license,
+            product,
+            exps,
+        } => Ok((license, product, exps)),
+        Validation::Revoked(revocation) => Err(AppError::LicenseRevoked(revocation)),
+        Validation::Suspended => Err(AppError::Forbidden(
+            msg::LICENSE_SUSPENDED_FOR_DISPUTE.into(),
+        )),
+        Validation::Invalid => Err(AppError::Forbidden(msg::LICENSE_NOT_VALID.into())),
+    }
+}
+
+/// Query parameters for GET /validate/attest
+#[derive(Debug, Deserialize)]
+pub struct AttestQuery {
+    /// Third party the attestation is for; must be in the project's
+    /// `attestation_audiences`
+    pub audience: String,
+    /// Public key - identifies the project (or send the X-Paycheck-Project header)
+    #[serde(default)]
+    pub public_key: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AttestResponse {
+    /// JWT signed with the project key, verifiable against its JWKS
+    pub attestation: String,
+    pub expires_at: i64,
+}
+
+/// GET /validate/attest?audience=...
+/// Validate the license token in the Authorization header like `/validate`,
+/// then issue a short-lived attestation of it for a third party.
+pub async fn attest_license(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Query(query): Query<AttestQuery>,
+) -> Result<Json<AttestResponse>> {
+    let public_key = super::require_publishable_key(&headers, query.public_key.as_deref())?;
+    let (conn, project) = state
+        .project_by_public_key(&public_key)?
+        .or_not_found(msg::PROJECT_NOT_FOUND)?;
+
+    if !project.attestation_audiences.contains(&query.audience) {
+        return Err(AppError::Forbidden(
+            msg::ATTESTATION_AUDIENCE_NOT_ALLOWED.into(),
+        ));
     }
 
-    Ok(Json(ValidateResponse {
-        valid: true,
-        reason: None,
-        license_exp: exps.license_exp,
-        updates_exp: exps.updates_exp,
+    let now = Utc::now().timestamp();
+    let (license, product, exps) =
+        check_bearer_license(&state, &headers, &conn, &project, auth.token(), now)?;
+
+    let attestation = AttestationClaims {
+        license_hash: jwt::license_hash(&license.id, &query.audience),
+        tier: product.tier,
+        product_id: product.id,
+        valid_until: exps.license_exp,
+    };
+    let private_key = queries::decrypt_project_private_key(&conn, &project, &state.master_key)?;
+    let token = jwt::sign_attestation(
+        &attestation,
+        &private_key,
+        project.token_issuer(),
+        &query.audience,
+    )?;
+
+    Ok(Json(AttestResponse {
+        attestation: token,
+        expires_at: now + jwt::ATTESTATION_LIFETIME_SECS as i64,
+    }))
+}
+
+/// Query parameters for GET /updates/check
+#[derive(Debug, Deserialize)]
+pub struct UpdatesCheckQuery {
+    /// When the build asking was released (Unix seconds): its build
+    /// timestamp or its version's release date
+    pub released_at: i64,
+    /// Public key - identifies the project (or send the X-Paycheck-Project header)
+    #[serde(default)]
+    pub public_key: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UpdatesCheckResponse {
+    /// Whether the license's update window covers the build
+    pub covered: bool,
+    pub released_at: i64,
+    /// When update access ends (None = perpetual), from the license itself
+    pub updates_expires_at: Option<i64>,
+}
+
+/// GET /updates/check?released_at=...
+/// Validate the license token in the Authorization header like `/validate`,
+/// then say whether a build released at `released_at` is within the
+/// license's updates window. Answers from the license's current
+/// `updates_expires_at`, so a renewal counts before the token is refreshed.
+pub async fn check_updates(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Query(query): Query<UpdatesCheckQuery>,
+) -> Result<Json<UpdatesCheckResponse>> {
+    let public_key = super::require_publishable_key(&headers, query.public_key.as_deref())?;
+    let (conn, project) = state
+        .project_by_public_key(&public_key)?
+        .or_not_found(msg::PROJECT_NOT_FOUND)?;
+
+    let now = Utc::now().timestamp();
+    let (license, _, _) =
+        check_bearer_license(&state, &headers, &conn, &project, auth.token(), now)?;
+
+    Ok(Json(UpdatesCheckResponse {
+        covered: license.covers_release(query.released_at),
+        released_at: query.released_at,
+        updates_expires_at: license.updates_expires_at,
     }))
 }
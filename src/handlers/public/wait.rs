@@ -0,0 +1,176 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use axum::{
+    Extension,
+    extract::{ConnectInfo, State},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::db::{AppState, queries};
+use crate::error::{AppError, OptionExt, Result, msg};
+use crate::extractors::{Json, Query};
+use crate::models::PaymentSession;
+
+/// Longest a wait is held open, in seconds (and the default)
+pub const MAX_WAIT_SECS: u64 = 25;
+
+/// How often a held wait re-reads the session
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Age past which an uncompleted checkout is reported expired. Provider
+/// checkout pages stop taking payment after 24 hours.
+pub const PAYMENT_SESSION_EXPIRY_SECS: i64 = 24 * 60 * 60;
+
+#[derive(Debug, Deserialize)]
+pub struct WaitQuery {
+    pub session: String,
+    /// Seconds to hold the request while the checkout is pending (0 to
+    /// answer right away; capped at 25, the default)
+    #[serde(default)]
+    pub timeout: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WaitStatus {
+    /// Payment hasn't completed yet
+    Pending,
+    /// Payment completed
+    Completed,
+    /// The checkout was abandoned; payment won't complete any more
+    Expired,
+}
+
+/// What the app should do next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivationHint {
+    /// Wait again
+    Wait,
+    /// Exchange `claim_code` at POST /redeem/claim
+    Claim,
+    /// The license was already claimed; activate with the emailed code
+    AlreadyClaimed,
+    /// Nothing was issued; the seller is looking into the purchase
+    Review,
+    /// Start a new purchase
+    StartOver,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WaitResponse {
+    pub status: WaitStatus,
+    pub activation_hint: ActivationHint,
+    /// One-time code for POST /redeem/claim, when `activation_hint` is `claim`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub claim_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub claim_expires_at: Option<i64>,
+}
+
+/// GET /buy/wait - Wait for a checkout to complete
+///
+/// For apps that open the /buy checkout in a browser: the /callback redirect
+/// lands there, not in the app. This long-polls instead, holding the request
+/// for up to 25 seconds and answering as soon as the session completes. A
+/// completed session answers with a one-time claim code, good for 5 minutes,
+/// that the app exchanges at POST /redeem/claim for the license. Each wait
+/// on a completed session issues a new code and voids the previous one.
+///
+/// Rate limited per session and per client IP.
+pub async fn wait_for_payment(
+    State(state): State<AppState>,
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+    Query(query): Query<WaitQuery>,
+) -> Result<Json<WaitResponse>> {
+    let limiter = &state.session_wait_limiter;
+    if limiter
+        .check(&format!("session:{}", query.session))
+        .is_err()
+    {
+        return Err(AppError::SessionWaitThrottled);
+    }
+    if let Some(Extension(ConnectInfo(addr))) = connect_info
+        && limiter.check(&format!("ip:{}", addr.ip())).is_err()
+    {
+        return Err(AppError::SessionWaitThrottled);
+    }
+
+    let pool =
+        state.find_db(|conn| Ok(queries::get_payment_session(conn, &query.session)?.is_some()))?;
+    let timeout = query.timeout.unwrap_or(MAX_WAIT_SECS).min(MAX_WAIT_SECS);
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(timeout);
+
+    loop {
+        // Only hold a connection while reading, not across the sleep
+        let session = queries::get_payment_session(&*pool.get()?, &query.session)?
+            .or_not_found(msg::SESSION_NOT_FOUND)?;
+
+        if session.completed {
+            let conn = pool.get()?;
+            return Ok(Json(completed_response(&conn, &session)?));
+        }
+        if state.clock.now() - session.created_at > PAYMENT_SESSION_EXPIRY_SECS {
+            return Ok(Json(WaitResponse::without_claim(
+                WaitStatus::Expired,
+                ActivationHint::StartOver,
+            )));
+        }
+        if tokio::time::Instant::now() + WAIT_POLL_INTERVAL > deadline {
+            return Ok(Json(WaitResponse::without_claim(
+                WaitStatus::Pending,
+                ActivationHint::Wait,
+            )));
+        }
+        tokio::time::sleep(WAIT_POLL_INTERVAL).await;
+    }
+}
+
+impl WaitResponse {
+    fn without_claim(status: WaitStatus, activation_hint: ActivationHint) -> Self {
+        Self {
+            status,
+            activation_hint,
+            claim_code: None,
+            claim_expires_at: None,
+        }
+    }
+}
+
+/// The answer for a completed session, issuing its claim code if the license
+/// is still there to claim.
+fn completed_response(
+    conn: &rusqlite::Connection,
+    session: &PaymentSession,
+) -> Result<WaitResponse> {
+    if session.needs_review {
+        return Ok(WaitResponse::without_claim(
+            WaitStatus::Completed,
+            ActivationHint::Review,
+        ));
+    }
+    if session.claimed_at.is_some() {
+        return Ok(WaitResponse::without_claim(
+            WaitStatus::Completed,
+            ActivationHint::AlreadyClaimed,
+        ));
+    }
+    match queries::issue_payment_session_claim(conn, &session.id)? {
+        Some((code, expires_at)) => Ok(WaitResponse {
+            status: WaitStatus::Completed,
+            activation_hint: ActivationHint::Claim,
+            claim_code: Some(code),
+            claim_expires_at: Some(expires_at),
+        }),
+        // Claimed since it was read, or the webhook hasn't linked the license yet
+        None => Ok(WaitResponse::without_claim(
+            WaitStatus::Completed,
+            if session.license_id.is_some() {
+                ActivationHint::AlreadyClaimed
+            } else {
+                ActivationHint::Wait
+            },
+        )),
+    }
+}
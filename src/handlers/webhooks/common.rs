@@ -3,6 +3,8 @@
 //! This module provides a trait-based approach to unify Stripe and LemonSqueezy
 //! webhook handlers, reducing code duplication while preserving provider-specific logic.
 
+use std::collections::BTreeMap;
+
 use axum::{
     body::Bytes,
     http::{HeaderMap, StatusCode},
@@ -13,10 +15,38 @@ use crate::crypto::{EmailHasher, MasterKey};
 use crate::db::{AppState, queries};
 use crate::error::AppError;
 use crate::models::{
-    ActorType, AuditAction, AuditLogNames, CreateLicense, License, Organization, PaymentSession,
-    Product, Project,
+    ActorType, AuditAction, AuditLogNames, Availability, CreateDispute, CreateLicense, Dispute,
+    DisputeStatus, EmailAddress, License, LicenseUpdateRenewal, LicenseUpgrade, OrgLimitName,
+    Organization, PaymentSession, Product, Project, RenewalFallback, RevocationReason,
+    RevokeLicense, UpgradeOldLicense,
 };
+use crate::payments::PaymentError;
+use crate::quota;
 use crate::util::{AuditLogBuilder, LicenseExpirations};
+use crate::webhook_mirror::WebhookMirror;
+
+/// Response for a webhook signature that couldn't be checked. An unusable
+/// webhook secret gets 200, like a missing config, so the provider doesn't
+/// retry until the org fixes it; an unreadable signature header is a 400.
+pub fn verification_failure(provider: &str, err: &PaymentError) -> WebhookResult {
+    match err {
+        PaymentError::InvalidConfig(_) => {
+            tracing::error!(provider, "Webhook signature not checked: {}", err);
+            (StatusCode::OK, "Webhook secret unusable")
+        }
+        PaymentError::ProviderRejected { .. } => {
+            tracing::warn!(provider, "Webhook signature rejected: {}", err);
+            (StatusCode::BAD_REQUEST, "Invalid signature format")
+        }
+        _ => {
+            tracing::warn!(provider, "Webhook signature not checked: {}", err);
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Signature verification unavailable",
+            )
+        }
+    }
+}
 
 /// Helper to unwrap DB query results with consistent error handling.
 fn db_lookup<T>(
@@ -70,6 +100,11 @@ pub struct CheckoutData {
     pub subscription_id: Option<String>,
     /// Provider's order/checkout session ID (Stripe: cs_xxx, LemonSqueezy: order ID)
     pub order_id: Option<String>,
+    /// Payment that refunds and chargebacks refer back to (Stripe: PaymentIntent,
+    /// LemonSqueezy: order ID). Stripe subscription checkouts have none.
+    pub payment_id: Option<String>,
+    /// `/buy` metadata echoed back by the provider
+    pub metadata: BTreeMap<String, String>,
 }
 
 /// Data extracted from a subscription renewal event.
@@ -92,6 +127,52 @@ pub struct CancellationData {
     pub subscription_id: String,
 }
 
+/// Data extracted from a subscription pause or resume event.
+#[derive(Debug)]
+pub struct PauseData {
+    pub subscription_id: String,
+    /// When the provider paused or resumed the subscription (Unix timestamp).
+    /// A delayed or retried webhook still counts the pause from this time.
+    pub occurred_at: i64,
+}
+
+/// Data extracted from a refund event. The license is found by
+/// `payment_id`, then `order_id`, then `subscription_id`.
+#[derive(Debug)]
+pub struct RefundData {
+    /// Matches `CheckoutData::payment_id` of the original checkout
+    pub payment_id: Option<String>,
+    /// Matches `CheckoutData::order_id` of the original checkout
+    pub order_id: Option<String>,
+    pub subscription_id: Option<String>,
+    /// Only part of the payment was refunded, which revokes only for
+    /// products with `revoke_on_partial_refund`
+    pub partial: bool,
+    /// Recorded as the license's revocation reason
+    pub reason: RevocationReason,
+}
+
+/// Data extracted from a dispute (chargeback) opening event.
+#[derive(Debug)]
+pub struct DisputeData {
+    /// Provider's dispute ID, which the closing event refers back to
+    pub dispute_id: String,
+    /// Matches `CheckoutData::payment_id` of the original checkout
+    pub payment_id: String,
+    pub amount_cents: Option<i64>,
+    pub currency: Option<String>,
+    /// Provider's reason code
+    pub reason: Option<String>,
+}
+
+/// Data extracted from a dispute closing event.
+#[derive(Debug)]
+pub struct DisputeClosedData {
+    pub dispute_id: String,
+    /// Whether the dispute was decided in the merchant's favor
+    pub won: bool,
+}
+
 /// Parsed webhook event with provider-agnostic data.
 #[derive(Debug)]
 pub enum WebhookEvent {
@@ -101,6 +182,16 @@ pub enum WebhookEvent {
     SubscriptionRenewed(RenewalData),
     /// Subscription cancelled - license expires naturally
     SubscriptionCancelled(CancellationData),
+    /// Subscription paused - license keeps validating, pause time is tracked
+    SubscriptionPaused(PauseData),
+    /// Subscription resumed - license expiration extended by the paused duration
+    SubscriptionResumed(PauseData),
+    /// Payment refunded - revokes the license
+    Refunded(RefundData),
+    /// Payment disputed - suspends the license until the dispute closes
+    DisputeOpened(DisputeData),
+    /// Dispute decided - reinstates the license if won, revokes it if lost
+    DisputeClosed(DisputeClosedData),
     /// Event type not relevant to license management
     Ignored,
 }
@@ -136,6 +227,7 @@ pub trait WebhookProvider: Send + Sync {
 /// Device creation is deferred to activation time (/redeem endpoint).
 /// Uses atomic compare-and-swap to prevent race conditions where multiple concurrent
 /// webhook deliveries could create multiple licenses from a single payment.
+#[allow(clippy::too_many_arguments)]
 pub fn process_checkout(
     conn: &mut Connection,
     email_hasher: &EmailHasher,
@@ -144,7 +236,19 @@ pub fn process_checkout(
     payment_session: &PaymentSession,
     product: &Product,
     data: &CheckoutData,
+    now: i64,
 ) -> WebhookResult {
+    // The sale window is checked against when checkout started, not now, so a
+    // purchase begun just before the window closed still completes
+    if product.availability_at(payment_session.created_at) != Availability::Available {
+        tracing::warn!(
+            "Checkout for session {} started outside product {}'s sale window",
+            data.session_id,
+            product.id
+        );
+        return (StatusCode::OK, "Product was not on sale when checkout started");
+    }
+
     // Atomically claim this payment session BEFORE creating any resources.
     // This prevents race conditions where concurrent webhooks could all create licenses.
     match queries::try_claim_payment_session(conn, &data.session_id) {
@@ -161,8 +265,23 @@ pub fn process_checkout(
         }
     }
 
-    // Compute email hash for license recovery via email
-    let email_hash = data.customer_email.as_ref().map(|e| email_hasher.hash(e));
+    // Compute email hash for license recovery via email. The payment already
+    // went through, so a malformed provider email costs recovery, not the license.
+    let email = data
+        .customer_email
+        .as_deref()
+        .and_then(|e| match EmailAddress::parse(e) {
+            Ok(email) => Some(email),
+            Err(err) => {
+                tracing::warn!(
+                    "Unusable email in checkout for session {}: {}",
+                    data.session_id,
+                    err
+                );
+                None
+            }
+        });
+    let email_hash = email.as_ref().map(|e| email_hasher.hash(e));
 
     if email_hash.is_none() {
         tracing::warn!(
@@ -171,8 +290,73 @@ pub fn process_checkout(
         );
     }
 
+    // Updates-only renewals extend the buyer's existing license instead of
+    // issuing a new one
+    if let Some(ref base_product_id) = product.extends_updates_for_product_id {
+        let base_license = match queries::find_active_license_for_buyer(
+            conn,
+            base_product_id,
+            payment_session.customer_id.as_deref(),
+            email_hash.as_deref(),
+            now,
+        ) {
+            Ok(license) => license,
+            Err(e) => {
+                tracing::error!("Failed to look up license to renew: {}", e);
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Database error");
+            }
+        };
+
+        match (base_license, product.renewal_fallback) {
+            (Some(license), _) => {
+                return match apply_updates_renewal(conn, payment_session, product, &license, now) {
+                    Ok(renewal) => {
+                        tracing::info!(
+                            "{} updates renewal applied: session={}, license_id={}, updates_expires_at={:?}",
+                            provider,
+                            data.session_id,
+                            license.id,
+                            renewal.updates_expires_at
+                        );
+                        (StatusCode::OK, "Updates extended")
+                    }
+                    Err(e) => {
+                        tracing::error!(
+                            "Failed to extend updates for license {}: {}",
+                            license.id,
+                            e
+                        );
+                        (
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            "Failed to extend updates",
+                        )
+                    }
+                };
+            }
+            (None, RenewalFallback::ManualReview) => {
+                if let Err(e) = queries::mark_payment_session_for_review(conn, &data.session_id) {
+                    tracing::error!("Failed to flag session for review: {}", e);
+                    return (StatusCode::INTERNAL_SERVER_ERROR, "Database error");
+                }
+                tracing::warn!(
+                    "{} updates renewal held for review: session={} has no {} license to extend",
+                    provider,
+                    data.session_id,
+                    base_product_id
+                );
+                return (StatusCode::OK, "Held for review");
+            }
+            (None, RenewalFallback::CreateLicense) => {
+                tracing::info!(
+                    "No {} license to extend for session {}, issuing a new license",
+                    base_product_id,
+                    data.session_id
+                );
+            }
+        }
+    }
+
     // Compute expirations from product settings
-    let now = chrono::Utc::now().timestamp();
     let exps = LicenseExpirations::from_product(product, now);
 
     // Create license (no user-facing key - email hash is the identity)
@@ -189,6 +373,7 @@ pub fn process_checkout(
             payment_provider_customer_id: data.customer_id.clone(),
             payment_provider_subscription_id: data.subscription_id.clone(),
             payment_provider_order_id: data.order_id.clone(),
+            seats: product.seat_count,
         },
     ) {
         Ok(l) => l,
@@ -202,11 +387,101 @@ pub fn process_checkout(
     };
 
     // Link license to payment session for efficient callback lookup
-    if let Err(e) = queries::set_payment_session_license(conn, &data.session_id, &license.id) {
+    if let Err(e) = queries::set_payment_session_license(
+        conn,
+        &data.session_id,
+        &license.id,
+        data.payment_id.as_deref(),
+    ) {
         tracing::error!("Failed to link license to session: {}", e);
         // Non-fatal - callback will fall back to search
     }
 
+    if !payment_session.checkout_fields.is_empty()
+        && let Err(e) = queries::set_license_checkout_fields(
+            conn,
+            &license.id,
+            &payment_session.checkout_fields,
+        )
+    {
+        // Non-fatal - the values stay on the payment session
+        tracing::error!(
+            "Failed to copy checkout fields to license {}: {}",
+            license.id,
+            e
+        );
+    }
+
+    // The session's copy was validated by /buy. A session without one takes
+    // what the provider echoed, if the project accepts those keys.
+    let metadata = if payment_session.metadata.is_empty() {
+        project
+            .checkout_metadata(&data.metadata)
+            .unwrap_or_default()
+    } else {
+        payment_session.metadata.clone()
+    };
+    if !metadata.is_empty()
+        && let Err(e) = queries::set_license_metadata(conn, &license.id, &metadata)
+    {
+        // Non-fatal - it's for the developer's analytics, not the license
+        tracing::error!("Failed to copy metadata to license {}: {}", license.id, e);
+    }
+
+    if let Some(ref version) = payment_session.accepted_terms_version
+        && let Err(e) = queries::set_license_accepted_terms(conn, &license.id, version)
+    {
+        // Non-fatal - the version stays on the payment session
+        tracing::error!(
+            "Failed to copy accepted terms version to license {}: {}",
+            license.id,
+            e
+        );
+    }
+
+    // A purchase by someone on a trial converts it
+    match convert_trial(conn, provider, project, &license, now) {
+        Ok(Some(trial)) => tracing::info!(
+            "Trial converted: trial_license={}, new_license={}, converted_trial_action={}",
+            trial.id,
+            license.id,
+            project.converted_trial_action.as_ref()
+        ),
+        Ok(None) => {}
+        Err(e) => {
+            // Non-fatal - the new license is paid for and stays valid
+            tracing::error!("Failed to link license {} to a trial: {}", license.id, e);
+        }
+    }
+
+    // Upgrade checkouts replace the old license
+    if let Some(ref old_license_id) = payment_session.upgrade_from_license_id {
+        match complete_upgrade(
+            conn,
+            provider,
+            project,
+            payment_session,
+            old_license_id,
+            &license.id,
+            now,
+        ) {
+            Ok(upgrade) => tracing::info!(
+                "Upgrade completed: old_license={}, new_license={}, old_license_action={}",
+                upgrade.from_license_id,
+                upgrade.to_license_id,
+                upgrade.old_license_action.as_ref()
+            ),
+            Err(e) => {
+                // Non-fatal - the new license is paid for and stays valid
+                tracing::error!(
+                    "Failed to complete upgrade from license {}: {}",
+                    old_license_id,
+                    e
+                );
+            }
+        }
+    }
+
     // NOTE: Device creation is deferred to activation time (/redeem endpoint).
     // This separates purchase from activation - user may buy on phone, activate on desktop.
 
@@ -221,6 +496,103 @@ pub fn process_checkout(
     (StatusCode::OK, "OK")
 }
 
+/// Apply the project's old-license policy to the license being upgraded and
+/// record which license replaced it.
+fn complete_upgrade(
+    conn: &mut Connection,
+    provider: &str,
+    project: &Project,
+    payment_session: &PaymentSession,
+    old_license_id: &str,
+    new_license_id: &str,
+    now: i64,
+) -> Result<LicenseUpgrade, AppError> {
+    let tx = conn.transaction()?;
+    match project.upgrade_old_license {
+        UpgradeOldLicense::Revoke => {
+            queries::revoke_license(
+                &tx,
+                old_license_id,
+                &RevokeLicense::new(RevocationReason::Superseded),
+                Some(provider),
+            )?;
+        }
+        UpgradeOldLicense::UpdatesOnly => {
+            queries::end_license_updates(&tx, old_license_id, now)?;
+        }
+    }
+    let upgrade = queries::create_license_upgrade(
+        &tx,
+        old_license_id,
+        new_license_id,
+        payment_session,
+        project.upgrade_old_license,
+    )?;
+    tx.commit()?;
+    Ok(upgrade)
+}
+
+/// Link a new paid license to the buyer's unconverted trial in the project,
+/// if they have one, and apply the project's `converted_trial_action` to it.
+fn convert_trial(
+    conn: &mut Connection,
+    provider: &str,
+    project: &Project,
+    license: &License,
+    now: i64,
+) -> Result<Option<License>, AppError> {
+    let Some(trial) = queries::find_unconverted_trial_for_buyer(
+        conn,
+        &project.id,
+        license.customer_id.as_deref(),
+        license.email_hash.as_deref(),
+    )?
+    else {
+        return Ok(None);
+    };
+    let tx = conn.transaction()?;
+    queries::convert_trial_license(
+        &tx,
+        &trial.id,
+        &license.id,
+        project.converted_trial_action,
+        Some(provider),
+        now,
+    )?;
+    tx.commit()?;
+    Ok(Some(trial))
+}
+
+/// Extend a license's update window by an updates-only renewal product and
+/// link the checkout to it, so `/callback` can hand out an activation code.
+fn apply_updates_renewal(
+    conn: &mut Connection,
+    payment_session: &PaymentSession,
+    product: &Product,
+    license: &License,
+    now: i64,
+) -> Result<LicenseUpdateRenewal, AppError> {
+    let updates_expires_at = LicenseExpirations::extend_updates(
+        license.updates_expires_at,
+        product.updates_exp_days.unwrap_or(0),
+        now,
+    );
+    let tx = conn.transaction()?;
+    let renewal = queries::apply_license_update_renewal(
+        &tx,
+        license,
+        &product.id,
+        &payment_session.id,
+        updates_expires_at,
+        now,
+    )?;
+    // No provider payment ID: refunding the renewal must not find (and
+    // revoke) the license it extended
+    queries::set_payment_session_license(&tx, &payment_session.id, &license.id, None)?;
+    tx.commit()?;
+    Ok(renewal)
+}
+
 /// Process a subscription renewal event - extends license expiration.
 ///
 /// The `event_id` parameter is used for replay attack prevention - if the same
@@ -229,6 +601,7 @@ pub fn process_checkout(
 /// The `period_end` parameter is the billing period end from the payment provider,
 /// which is more accurate than calculating from product settings. If not available,
 /// falls back to `now + license_exp_days`.
+#[allow(clippy::too_many_arguments)]
 pub fn process_renewal(
     conn: &Connection,
     provider: &str,
@@ -237,6 +610,7 @@ pub fn process_renewal(
     subscription_id: &str,
     event_id: Option<&str>,
     period_end: Option<i64>,
+    now: i64,
 ) -> WebhookResult {
     // Replay attack prevention: check if we've already processed this event
     if let Some(eid) = event_id {
@@ -255,24 +629,10 @@ pub fn process_renewal(
         }
     }
 
-    // Use provider's period_end if available, otherwise calculate from product settings.
-    // Provider's date is more accurate (handles prorations, billing date changes, etc.)
-    let now = chrono::Utc::now().timestamp();
-    let fallback_exps = LicenseExpirations::from_product(product, now);
-
-    let license_exp = period_end.or(fallback_exps.license_exp);
-    // For updates_exp, calculate relative offset from license_exp if provider gave period_end
-    let updates_exp = match (period_end, product.license_exp_days, product.updates_exp_days) {
-        // Provider period_end available and product has both expiration settings
-        (Some(pe), Some(_), Some(upd_days)) => Some(pe + (upd_days as i64 * 86400)),
-        // Provider period_end available but updates follows license (same duration)
-        (Some(pe), Some(lic_days), None) if lic_days > 0 => {
-            // If updates_exp_days not set, assume same as license
-            fallback_exps.updates_exp.map(|_| pe)
-        }
-        // Fall back to calculated value
-        _ => fallback_exps.updates_exp,
-    };
+    let LicenseExpirations {
+        license_exp,
+        updates_exp,
+    } = LicenseExpirations::for_renewal(product, period_end, now);
 
     if let Err(e) = queries::extend_license_expiration(conn, license_id, license_exp, updates_exp) {
         tracing::error!("Failed to extend license: {}", e);
@@ -312,6 +672,236 @@ pub fn process_cancellation(
     (StatusCode::OK, "OK")
 }
 
+/// Process a subscription pause event - records when the pause started.
+///
+/// The license keeps validating while paused; expiration is pushed out on resume.
+pub fn process_pause(
+    conn: &Connection,
+    provider: &str,
+    license_id: &str,
+    data: &PauseData,
+) -> WebhookResult {
+    match queries::pause_license(conn, license_id, data.occurred_at) {
+        Ok(true) => {}
+        Ok(false) => return (StatusCode::OK, "Already paused"),
+        Err(e) => {
+            tracing::error!("Failed to pause license: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to pause license");
+        }
+    }
+
+    tracing::info!(
+        "{} subscription paused: subscription={}, license_id={}",
+        provider,
+        data.subscription_id,
+        license_id
+    );
+
+    (StatusCode::OK, "OK")
+}
+
+/// Process a subscription resume event - extends expiration by the paused duration.
+pub fn process_resume(
+    conn: &Connection,
+    provider: &str,
+    license_id: &str,
+    data: &PauseData,
+) -> WebhookResult {
+    match queries::resume_license(conn, license_id, data.occurred_at) {
+        Ok(true) => {}
+        Ok(false) => return (StatusCode::OK, "Not paused"),
+        Err(e) => {
+            tracing::error!("Failed to resume license: {}", e);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to resume license",
+            );
+        }
+    }
+
+    tracing::info!(
+        "{} subscription resumed: subscription={}, license_id={}",
+        provider,
+        data.subscription_id,
+        license_id
+    );
+
+    (StatusCode::OK, "OK")
+}
+
+/// Process a refund - revokes the license with the event's reason, and the
+/// tokens of its devices.
+pub fn process_refund(
+    conn: &Connection,
+    provider: &str,
+    license: &License,
+    data: &RefundData,
+) -> WebhookResult {
+    if license.revoked {
+        return (StatusCode::OK, "Already revoked");
+    }
+
+    // Tokens first: a retry after a failed revoke adds them again harmlessly
+    let details = format!("license revoked: {} via {}", data.reason.as_ref(), provider);
+    if let Err(e) = queries::revoke_device_jtis(conn, &license.id, &details) {
+        tracing::error!("Failed to revoke device tokens: {}", e);
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to revoke license",
+        );
+    }
+
+    if let Err(e) = queries::revoke_license(
+        conn,
+        &license.id,
+        &RevokeLicense::new(data.reason),
+        Some(provider),
+    ) {
+        tracing::error!("Failed to revoke license: {}", e);
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to revoke license",
+        );
+    }
+
+    tracing::info!(
+        "{} payment {}: payment={:?}, order={:?}, license_id={} revoked",
+        provider,
+        data.reason.as_ref(),
+        data.payment_id,
+        data.order_id,
+        license.id
+    );
+
+    (StatusCode::OK, "OK")
+}
+
+/// Process a dispute opening - records the dispute and suspends the license.
+///
+/// A suspended license fails validation but keeps its devices, so winning the
+/// dispute restores it as it was. Returns the dispute when one was recorded.
+pub fn process_dispute_opened(
+    conn: &Connection,
+    provider: &str,
+    license: &License,
+    data: &DisputeData,
+    now: i64,
+) -> (WebhookResult, Option<Dispute>) {
+    let input = CreateDispute {
+        provider_dispute_id: data.dispute_id.clone(),
+        payment_id: data.payment_id.clone(),
+        amount_cents: data.amount_cents,
+        currency: data.currency.clone(),
+        reason: data.reason.clone(),
+    };
+    let dispute = match queries::create_dispute(conn, license, provider, &input, now) {
+        Ok(Some(d)) => d,
+        Ok(None) => return ((StatusCode::OK, "Dispute already recorded"), None),
+        Err(e) => {
+            tracing::error!("Failed to record dispute: {}", e);
+            return (
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to record dispute",
+                ),
+                None,
+            );
+        }
+    };
+
+    // A revoked license has nothing left to suspend; the dispute is still kept
+    // for the history.
+    if !license.revoked
+        && let Err(e) = queries::set_license_suspended_for_dispute(conn, &license.id, true)
+    {
+        tracing::error!("Failed to suspend license: {}", e);
+        return (
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to suspend license",
+            ),
+            None,
+        );
+    }
+
+    tracing::info!(
+        "{} dispute opened: dispute={}, payment={}, license_id={} suspended",
+        provider,
+        data.dispute_id,
+        data.payment_id,
+        license.id
+    );
+
+    ((StatusCode::OK, "OK"), Some(dispute))
+}
+
+/// Process a dispute closing. A lost dispute revokes the license as a
+/// chargeback; a won one lifts the suspension unless another dispute on the
+/// license is still open.
+pub fn process_dispute_closed(
+    conn: &Connection,
+    provider: &str,
+    license: &License,
+    dispute: &Dispute,
+    won: bool,
+    now: i64,
+) -> WebhookResult {
+    let status = if won {
+        DisputeStatus::Won
+    } else {
+        DisputeStatus::Lost
+    };
+    match queries::close_dispute(conn, &dispute.id, status, now) {
+        Ok(true) => {}
+        Ok(false) => return (StatusCode::OK, "Dispute already closed"),
+        Err(e) => {
+            tracing::error!("Failed to close dispute: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to close dispute");
+        }
+    }
+
+    let outcome = if won {
+        queries::count_open_disputes_for_license(conn, &license.id).and_then(|open| {
+            if open == 0 {
+                queries::set_license_suspended_for_dispute(conn, &license.id, false)
+            } else {
+                Ok(())
+            }
+        })
+    } else {
+        // Already-revoked licenses keep their original reason
+        let revoked = if license.revoked {
+            Ok(false)
+        } else {
+            queries::revoke_license(
+                conn,
+                &license.id,
+                &RevokeLicense::new(RevocationReason::Chargeback),
+                Some(provider),
+            )
+        };
+        revoked.and_then(|_| queries::set_license_suspended_for_dispute(conn, &license.id, false))
+    };
+    if let Err(e) = outcome {
+        tracing::error!("Failed to update license after dispute: {}", e);
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to update license",
+        );
+    }
+
+    tracing::info!(
+        "{} dispute {}: dispute={}, license_id={} {}",
+        provider,
+        status.as_ref(),
+        dispute.provider_dispute_id,
+        license.id,
+        if won { "reinstated" } else { "revoked" }
+    );
+
+    (StatusCode::OK, "OK")
+}
+
 /// Generic webhook handler that delegates to provider-specific implementations.
 pub async fn handle_webhook<P: WebhookProvider>(
     provider: &P,
@@ -332,24 +922,69 @@ pub async fn handle_webhook<P: WebhookProvider>(
     };
 
     // Handle based on event type
-    match event {
+    // Set by the handler once the signature checks out
+    let mut mirror_url = None;
+    let mirror = &mut mirror_url;
+    let result = match event {
         WebhookEvent::CheckoutCompleted(data) => {
-            handle_checkout(provider, state, &headers, &body, &signature, data)
+            handle_checkout(provider, state, &headers, &body, &signature, data, mirror)
                 .await
                 .unwrap_or_else(|e| e)
         }
         WebhookEvent::SubscriptionRenewed(data) => {
-            handle_renewal(provider, state, &headers, &body, &signature, data)
+            handle_renewal(provider, state, &headers, &body, &signature, data, mirror)
                 .await
                 .unwrap_or_else(|e| e)
         }
         WebhookEvent::SubscriptionCancelled(data) => {
-            handle_cancellation(provider, state, &headers, &body, &signature, data)
+            handle_cancellation(provider, state, &headers, &body, &signature, data, mirror)
+                .await
+                .unwrap_or_else(|e| e)
+        }
+        WebhookEvent::SubscriptionPaused(data) => handle_pause(
+            provider, state, &headers, &body, &signature, data, true, mirror,
+        )
+        .await
+        .unwrap_or_else(|e| e),
+        WebhookEvent::SubscriptionResumed(data) => handle_pause(
+            provider, state, &headers, &body, &signature, data, false, mirror,
+        )
+        .await
+        .unwrap_or_else(|e| e),
+        WebhookEvent::Refunded(data) => {
+            handle_refund(provider, state, &headers, &body, &signature, data, mirror)
+                .await
+                .unwrap_or_else(|e| e)
+        }
+        WebhookEvent::DisputeOpened(data) => {
+            handle_dispute_opened(provider, state, &headers, &body, &signature, data, mirror)
+                .await
+                .unwrap_or_else(|e| e)
+        }
+        WebhookEvent::DisputeClosed(data) => {
+            handle_dispute_closed(provider, state, &headers, &body, &signature, data, mirror)
                 .await
                 .unwrap_or_else(|e| e)
         }
         WebhookEvent::Ignored => (StatusCode::OK, "Event ignored"),
+    };
+
+    if let Some(mirror_url) = mirror_url {
+        WebhookMirror::new(state.allow_localhost_urls).forward_provider_webhook(
+            &mirror_url,
+            provider.provider_name(),
+            &headers,
+            body,
+        );
     }
+    result
+}
+
+/// The project's webhook mirror URL, if it has one on.
+fn webhook_mirror_url(state: &AppState, project: &Project) -> Option<String> {
+    project
+        .webhook_mirror(state.clock.now())
+        .map(str::to_string)
 }
 
 async fn handle_checkout<P: WebhookProvider>(
@@ -359,11 +994,15 @@ async fn handle_checkout<P: WebhookProvider>(
     body: &Bytes,
     signature: &str,
     data: CheckoutData,
+    mirror_url: &mut Option<String>,
 ) -> Result<WebhookResult, WebhookResult> {
-    let mut conn = state.db.get().map_err(|e| {
-        tracing::error!("DB connection error: {}", e);
-        (StatusCode::INTERNAL_SERVER_ERROR, "Database error")
-    })?;
+    let mut conn = state
+        .project_db(&data.project_id)
+        .and_then(|pool| Ok(pool.get()?))
+        .map_err(|e| {
+            tracing::error!("DB connection error: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error")
+        })?;
 
     let project = db_lookup(
         queries::get_project_by_id(&conn, &data.project_id),
@@ -381,6 +1020,7 @@ async fn handle_checkout<P: WebhookProvider>(
         Ok(false) => return Err((StatusCode::UNAUTHORIZED, "Invalid signature")),
         Err(e) => return Err(e),
     }
+    *mirror_url = webhook_mirror_url(state, &project);
 
     let payment_session = db_lookup(
         queries::get_payment_session(&conn, &data.session_id),
@@ -392,6 +1032,37 @@ async fn handle_checkout<P: WebhookProvider>(
         "Product not found",
     )?;
 
+    // Past the org's hard license limit the provider is asked to retry, so the
+    // license is issued once an operator raises the limit. /buy refuses new
+    // checkouts at the limit, so this only catches ones already under way.
+    let quota = if payment_session.completed {
+        None
+    } else {
+        match quota::check(
+            &conn,
+            &org.id,
+            OrgLimitName::LicensesPerMonth,
+            None,
+            1,
+            state.clock.now(),
+        ) {
+            Ok(quota) => quota,
+            Err(AppError::QuotaExceeded { hard_value, .. }) => {
+                tracing::error!(
+                    "Checkout {} deferred: org {} is at its monthly license limit ({})",
+                    data.session_id,
+                    org.id,
+                    hard_value
+                );
+                return Err((StatusCode::SERVICE_UNAVAILABLE, "License limit reached"));
+            }
+            Err(e) => {
+                tracing::error!("Failed to check license limit: {}", e);
+                return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error"));
+            }
+        }
+    };
+
     let result = process_checkout(
         &mut conn,
         &state.email_hasher,
@@ -400,23 +1071,35 @@ async fn handle_checkout<P: WebhookProvider>(
         &payment_session,
         &product,
         &data,
+        state.clock.now(),
     );
 
-    // Audit log on successful checkout (license created)
-    if result.0 == StatusCode::OK && result.1 == "OK" {
+    // Audit log on successful checkout (license created or extended, or the
+    // payment held for review)
+    let outcome = match result {
+        (StatusCode::OK, "OK") => Some("license_created"),
+        (StatusCode::OK, "Updates extended") => Some("updates_extended"),
+        (StatusCode::OK, "Held for review") => Some("held_for_review"),
+        _ => None,
+    };
+    if let Some(outcome) = outcome {
         // Re-fetch session to get the linked license_id
         if let Ok(Some(updated_session)) = queries::get_payment_session(&conn, &data.session_id)
-            && let Some(license_id) = updated_session.license_id
+            && (updated_session.license_id.is_some() || updated_session.needs_review)
         {
             let audit_conn = state.audit.get().map_err(|e| {
                 tracing::error!("Audit DB connection error: {}", e);
                 (StatusCode::INTERNAL_SERVER_ERROR, "Database error")
             })?;
+            let (resource_type, resource_id) = match updated_session.license_id {
+                Some(ref license_id) => ("license", license_id.as_str()),
+                None => ("payment_session", data.session_id.as_str()),
+            };
 
-            if let Err(e) = AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, headers)
+            if let Err(e) = AuditLogBuilder::for_state(&audit_conn, state, headers)
                 .actor(ActorType::Public, None)
                 .action(AuditAction::ReceiveCheckoutWebhook)
-                .resource("license", &license_id)
+                .resource(resource_type, resource_id)
                 .details(&serde_json::json!({
                     "provider": provider.provider_name(),
                     "session_id": data.session_id,
@@ -424,6 +1107,8 @@ async fn handle_checkout<P: WebhookProvider>(
                     "customer_email": data.customer_email,
                     "subscription_id": data.subscription_id,
                     "order_id": data.order_id,
+                    "upgrade_from_license_id": payment_session.upgrade_from_license_id,
+                    "outcome": outcome,
                 }))
                 .org(&org.id)
                 .project(&project.id)
@@ -436,6 +1121,50 @@ async fn handle_checkout<P: WebhookProvider>(
             {
                 tracing::warn!("Failed to write checkout audit log: {}", e);
             }
+
+            // The new license converted the buyer's trial
+            if outcome == "license_created"
+                && let Some(ref license_id) = updated_session.license_id
+                && let Ok(Some(license)) = queries::get_license_by_id(&conn, license_id)
+                && let Some(ref trial_id) = license.converted_from_license_id
+                && let Err(e) = AuditLogBuilder::for_state(&audit_conn, state, headers)
+                    .actor(ActorType::Public, None)
+                    .action(AuditAction::TrialConverted)
+                    .resource("license", license_id)
+                    .details(&serde_json::json!({
+                        "provider": provider.provider_name(),
+                        "trial_license_id": trial_id,
+                        "converted_trial_action": project.converted_trial_action.as_ref(),
+                    }))
+                    .org(&org.id)
+                    .project(&project.id)
+                    .names(&AuditLogNames {
+                        org_name: Some(org.name.clone()),
+                        project_name: Some(project.name.clone()),
+                        ..Default::default()
+                    })
+                    .save()
+            {
+                tracing::warn!("Failed to write trial conversion audit log: {}", e);
+            }
+
+            if outcome == "license_created"
+                && let Some(ref warning) = quota
+            {
+                quota::audit_crossing(
+                    AuditLogBuilder::for_state(&audit_conn, state, headers)
+                        .actor(ActorType::Public, None)
+                        .org(&org.id)
+                        .project(&project.id)
+                        .names(&AuditLogNames {
+                            org_name: Some(org.name.clone()),
+                            project_name: Some(project.name.clone()),
+                            ..Default::default()
+                        }),
+                    &org.id,
+                    warning,
+                );
+            }
         }
     }
 
@@ -449,6 +1178,7 @@ async fn handle_renewal<P: WebhookProvider>(
     body: &Bytes,
     signature: &str,
     data: RenewalData,
+    mirror_url: &mut Option<String>,
 ) -> Result<WebhookResult, WebhookResult> {
     // Skip if not a renewal (initial subscription handled by checkout)
     if !data.is_renewal {
@@ -459,10 +1189,20 @@ async fn handle_renewal<P: WebhookProvider>(
         return Ok((StatusCode::OK, "Invoice not paid"));
     }
 
-    let conn = state.db.get().map_err(|e| {
-        tracing::error!("DB connection error: {}", e);
-        (StatusCode::INTERNAL_SERVER_ERROR, "Database error")
-    })?;
+    let conn = state
+        .find_db(|conn| {
+            Ok(queries::get_license_by_subscription(
+                conn,
+                provider.provider_name(),
+                &data.subscription_id,
+            )?
+            .is_some())
+        })
+        .and_then(|pool| Ok(pool.get()?))
+        .map_err(|e| {
+            tracing::error!("DB connection error: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error")
+        })?;
 
     let license = lookup_license_by_subscription(provider, &conn, &data.subscription_id)?;
     let product = db_lookup(
@@ -484,7 +1224,9 @@ async fn handle_renewal<P: WebhookProvider>(
         Ok(false) => return Err((StatusCode::UNAUTHORIZED, "Invalid signature")),
         Err(e) => return Err(e),
     }
+    *mirror_url = webhook_mirror_url(state, &project);
 
+    let now = state.clock.now();
     let result = process_renewal(
         &conn,
         provider.provider_name(),
@@ -493,6 +1235,7 @@ async fn handle_renewal<P: WebhookProvider>(
         &data.subscription_id,
         data.event_id.as_deref(),
         data.period_end,
+        now,
     );
 
     // Audit log on successful renewal
@@ -503,11 +1246,10 @@ async fn handle_renewal<P: WebhookProvider>(
         })?;
 
         // Compute new expirations for logging (same logic as process_renewal)
-        let now = chrono::Utc::now().timestamp();
-        let fallback_exps = LicenseExpirations::from_product(&product, now);
-        let license_exp = data.period_end.or(fallback_exps.license_exp);
+        let license_exp =
+            LicenseExpirations::for_renewal(&product, data.period_end, now).license_exp;
 
-        if let Err(e) = AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, headers)
+        if let Err(e) = AuditLogBuilder::for_state(&audit_conn, state, headers)
             .actor(ActorType::Public, None)
             .action(AuditAction::ReceiveRenewalWebhook)
             .resource("license", &license.id)
@@ -542,11 +1284,22 @@ async fn handle_cancellation<P: WebhookProvider>(
     body: &Bytes,
     signature: &str,
     data: CancellationData,
+    mirror_url: &mut Option<String>,
 ) -> Result<WebhookResult, WebhookResult> {
-    let conn = state.db.get().map_err(|e| {
-        tracing::error!("DB connection error: {}", e);
-        (StatusCode::INTERNAL_SERVER_ERROR, "Database error")
-    })?;
+    let conn = state
+        .find_db(|conn| {
+            Ok(queries::get_license_by_subscription(
+                conn,
+                provider.provider_name(),
+                &data.subscription_id,
+            )?
+            .is_some())
+        })
+        .and_then(|pool| Ok(pool.get()?))
+        .map_err(|e| {
+            tracing::error!("DB connection error: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error")
+        })?;
 
     let license = lookup_license_by_subscription(provider, &conn, &data.subscription_id)?;
     let product = db_lookup(
@@ -568,6 +1321,7 @@ async fn handle_cancellation<P: WebhookProvider>(
         Ok(false) => return Err((StatusCode::UNAUTHORIZED, "Invalid signature")),
         Err(e) => return Err(e),
     }
+    *mirror_url = webhook_mirror_url(state, &project);
 
     let result = process_cancellation(
         provider.provider_name(),
@@ -583,7 +1337,7 @@ async fn handle_cancellation<P: WebhookProvider>(
             (StatusCode::INTERNAL_SERVER_ERROR, "Database error")
         })?;
 
-        if let Err(e) = AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, headers)
+        if let Err(e) = AuditLogBuilder::for_state(&audit_conn, state, headers)
             .actor(ActorType::Public, None)
             .action(AuditAction::ReceiveCancellationWebhook)
             .resource("license", &license.id)
@@ -608,3 +1362,450 @@ async fn handle_cancellation<P: WebhookProvider>(
 
     Ok(result)
 }
+
+/// Handle a pause (`paused = true`) or resume (`paused = false`) event.
+#[allow(clippy::too_many_arguments)]
+async fn handle_pause<P: WebhookProvider>(
+    provider: &P,
+    state: &AppState,
+    headers: &HeaderMap,
+    body: &Bytes,
+    signature: &str,
+    data: PauseData,
+    paused: bool,
+    mirror_url: &mut Option<String>,
+) -> Result<WebhookResult, WebhookResult> {
+    let conn = state
+        .find_db(|conn| {
+            Ok(queries::get_license_by_subscription(
+                conn,
+                provider.provider_name(),
+                &data.subscription_id,
+            )?
+            .is_some())
+        })
+        .and_then(|pool| Ok(pool.get()?))
+        .map_err(|e| {
+            tracing::error!("DB connection error: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error")
+        })?;
+
+    let license = lookup_license_by_subscription(provider, &conn, &data.subscription_id)?;
+    let product = db_lookup(
+        queries::get_product_by_id(&conn, &license.product_id),
+        "Product not found",
+    )?;
+    let project = db_lookup(
+        queries::get_project_by_id(&conn, &product.project_id),
+        "Project not found",
+    )?;
+    let org = db_lookup(
+        queries::get_organization_by_id(&conn, &project.org_id),
+        "Organization not found",
+    )?;
+
+    // Verify signature
+    match provider.verify_signature(&conn, &org, &state.master_key, body, signature) {
+        Ok(true) => {}
+        Ok(false) => return Err((StatusCode::UNAUTHORIZED, "Invalid signature")),
+        Err(e) => return Err(e),
+    }
+    *mirror_url = webhook_mirror_url(state, &project);
+
+    let (result, action) = if paused {
+        (
+            process_pause(&conn, provider.provider_name(), &license.id, &data),
+            AuditAction::ReceivePauseWebhook,
+        )
+    } else {
+        (
+            process_resume(&conn, provider.provider_name(), &license.id, &data),
+            AuditAction::ReceiveResumeWebhook,
+        )
+    };
+
+    // Audit log only when the pause state actually changed
+    if result.0 == StatusCode::OK && result.1 == "OK" {
+        let updated = db_lookup(
+            queries::get_license_by_id(&conn, &license.id),
+            "License not found",
+        )?;
+
+        let audit_conn = state.audit.get().map_err(|e| {
+            tracing::error!("Audit DB connection error: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error")
+        })?;
+
+        if let Err(e) = AuditLogBuilder::for_state(&audit_conn, state, headers)
+            .actor(ActorType::Public, None)
+            .action(action)
+            .resource("license", &license.id)
+            .details(&serde_json::json!({
+                "provider": provider.provider_name(),
+                "subscription_id": data.subscription_id,
+                "product_id": product.id,
+                "expires_at": updated.expires_at,
+                "paused_seconds": updated.paused_seconds,
+            }))
+            .org(&org.id)
+            .project(&project.id)
+            .names(&AuditLogNames {
+                org_name: Some(org.name.clone()),
+                project_name: Some(project.name.clone()),
+                ..Default::default()
+            })
+            .save()
+        {
+            tracing::warn!("Failed to write pause audit log: {}", e);
+        }
+    }
+
+    Ok(result)
+}
+
+async fn handle_refund<P: WebhookProvider>(
+    provider: &P,
+    state: &AppState,
+    headers: &HeaderMap,
+    body: &Bytes,
+    signature: &str,
+    data: RefundData,
+    mirror_url: &mut Option<String>,
+) -> Result<WebhookResult, WebhookResult> {
+    let conn = state
+        .find_db(|conn| Ok(find_refunded_license(conn, provider.provider_name(), &data)?.is_some()))
+        .and_then(|pool| Ok(pool.get()?))
+        .map_err(|e| {
+            tracing::error!("DB connection error: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error")
+        })?;
+
+    let license = match find_refunded_license(&conn, provider.provider_name(), &data) {
+        Ok(Some(l)) => l,
+        Ok(None) => {
+            tracing::warn!(
+                "No license found for {} refund: payment={:?}, order={:?}, subscription={:?}",
+                provider.provider_name(),
+                data.payment_id,
+                data.order_id,
+                data.subscription_id
+            );
+            return Err((StatusCode::OK, "License not found for payment"));
+        }
+        Err(e) => {
+            tracing::error!("DB error: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error"));
+        }
+    };
+    let product = db_lookup(
+        queries::get_product_by_id(&conn, &license.product_id),
+        "Product not found",
+    )?;
+    let project = db_lookup(
+        queries::get_project_by_id(&conn, &product.project_id),
+        "Project not found",
+    )?;
+    let org = db_lookup(
+        queries::get_organization_by_id(&conn, &project.org_id),
+        "Organization not found",
+    )?;
+
+    // Verify signature
+    match provider.verify_signature(&conn, &org, &state.master_key, body, signature) {
+        Ok(true) => {}
+        Ok(false) => return Err((StatusCode::UNAUTHORIZED, "Invalid signature")),
+        Err(e) => return Err(e),
+    }
+    *mirror_url = webhook_mirror_url(state, &project);
+
+    if data.partial && !product.revoke_on_partial_refund {
+        tracing::info!(
+            "{} partial refund left license {} active (product {} doesn't revoke on partial refunds)",
+            provider.provider_name(),
+            license.id,
+            product.id
+        );
+        return Ok((StatusCode::OK, "Partial refund ignored"));
+    }
+
+    let result = process_refund(&conn, provider.provider_name(), &license, &data);
+
+    // Audit log only when the license was actually revoked
+    if result.0 == StatusCode::OK && result.1 == "OK" {
+        let audit_conn = state.audit.get().map_err(|e| {
+            tracing::error!("Audit DB connection error: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error")
+        })?;
+
+        if let Err(e) = AuditLogBuilder::for_state(&audit_conn, state, headers)
+            .actor(ActorType::System, None)
+            .action(AuditAction::ReceiveRefundWebhook)
+            .resource("license", &license.id)
+            .details(&serde_json::json!({
+                "provider": provider.provider_name(),
+                "payment_id": data.payment_id,
+                "order_id": data.order_id,
+                "subscription_id": data.subscription_id,
+                "partial": data.partial,
+                "reason": data.reason,
+                "product_id": product.id,
+            }))
+            .org(&org.id)
+            .project(&project.id)
+            .names(&AuditLogNames {
+                org_name: Some(org.name.clone()),
+                project_name: Some(project.name.clone()),
+                ..Default::default()
+            })
+            .save()
+        {
+            tracing::warn!("Failed to write refund audit log: {}", e);
+        }
+    }
+
+    Ok(result)
+}
+
+/// The license a refund is for: the one bought with the refunded payment,
+/// else the one from the checkout order, else the subscription's.
+fn find_refunded_license(
+    conn: &Connection,
+    provider: &str,
+    data: &RefundData,
+) -> Result<Option<License>, AppError> {
+    if let Some(payment_id) = &data.payment_id
+        && let Some(license) = queries::get_license_by_provider_payment(conn, provider, payment_id)?
+    {
+        return Ok(Some(license));
+    }
+    if let Some(order_id) = &data.order_id
+        && let Some(license) = queries::get_license_by_provider_order(conn, provider, order_id)?
+    {
+        return Ok(Some(license));
+    }
+    match &data.subscription_id {
+        Some(subscription_id) => {
+            queries::get_license_by_subscription(conn, provider, subscription_id)
+        }
+        None => Ok(None),
+    }
+}
+
+/// Look up the product, project and org a license belongs to and check the
+/// webhook signature against the org's provider config.
+fn verify_for_license<P: WebhookProvider>(
+    provider: &P,
+    state: &AppState,
+    conn: &Connection,
+    license: &License,
+    body: &Bytes,
+    signature: &str,
+) -> Result<(Product, Project, Organization), WebhookResult> {
+    let product = db_lookup(
+        queries::get_product_by_id(conn, &license.product_id),
+        "Product not found",
+    )?;
+    let project = db_lookup(
+        queries::get_project_by_id(conn, &product.project_id),
+        "Project not found",
+    )?;
+    let org = db_lookup(
+        queries::get_organization_by_id(conn, &project.org_id),
+        "Organization not found",
+    )?;
+
+    match provider.verify_signature(conn, &org, &state.master_key, body, signature) {
+        Ok(true) => Ok((product, project, org)),
+        Ok(false) => Err((StatusCode::UNAUTHORIZED, "Invalid signature")),
+        Err(e) => Err(e),
+    }
+}
+
+async fn handle_dispute_opened<P: WebhookProvider>(
+    provider: &P,
+    state: &AppState,
+    headers: &HeaderMap,
+    body: &Bytes,
+    signature: &str,
+    data: DisputeData,
+    mirror_url: &mut Option<String>,
+) -> Result<WebhookResult, WebhookResult> {
+    let conn = state
+        .find_db(|conn| {
+            Ok(queries::get_license_by_provider_payment(
+                conn,
+                provider.provider_name(),
+                &data.payment_id,
+            )?
+            .is_some())
+        })
+        .and_then(|pool| Ok(pool.get()?))
+        .map_err(|e| {
+            tracing::error!("DB connection error: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error")
+        })?;
+
+    let license = match queries::get_license_by_provider_payment(
+        &conn,
+        provider.provider_name(),
+        &data.payment_id,
+    ) {
+        Ok(Some(l)) => l,
+        Ok(None) => {
+            tracing::warn!(
+                "No license found for {} disputed payment: {}",
+                provider.provider_name(),
+                data.payment_id
+            );
+            return Err((StatusCode::OK, "License not found for payment"));
+        }
+        Err(e) => {
+            tracing::error!("DB error: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error"));
+        }
+    };
+    let (product, project, org) =
+        verify_for_license(provider, state, &conn, &license, body, signature)?;
+    *mirror_url = webhook_mirror_url(state, &project);
+
+    let (result, dispute) = process_dispute_opened(
+        &conn,
+        provider.provider_name(),
+        &license,
+        &data,
+        state.clock.now(),
+    );
+
+    if let Some(dispute) = dispute {
+        let audit_conn = state.audit.get().map_err(|e| {
+            tracing::error!("Audit DB connection error: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error")
+        })?;
+
+        if let Err(e) = AuditLogBuilder::for_state(&audit_conn, state, headers)
+            .actor(ActorType::Public, None)
+            .action(AuditAction::ReceiveDisputeOpenedWebhook)
+            .resource("license", &license.id)
+            .details(&serde_json::json!({
+                "provider": provider.provider_name(),
+                "dispute_id": dispute.id,
+                "provider_dispute_id": dispute.provider_dispute_id,
+                "payment_id": dispute.payment_id,
+                "amount_cents": dispute.amount_cents,
+                "currency": dispute.currency,
+                "reason": dispute.reason,
+                "suspended": !license.revoked,
+                "product_id": product.id,
+            }))
+            .org(&org.id)
+            .project(&project.id)
+            .names(&AuditLogNames {
+                org_name: Some(org.name.clone()),
+                project_name: Some(project.name.clone()),
+                ..Default::default()
+            })
+            .save()
+        {
+            tracing::warn!("Failed to write dispute audit log: {}", e);
+        }
+    }
+
+    Ok(result)
+}
+
+async fn handle_dispute_closed<P: WebhookProvider>(
+    provider: &P,
+    state: &AppState,
+    headers: &HeaderMap,
+    body: &Bytes,
+    signature: &str,
+    data: DisputeClosedData,
+    mirror_url: &mut Option<String>,
+) -> Result<WebhookResult, WebhookResult> {
+    let conn = state
+        .find_db(|conn| {
+            Ok(queries::get_dispute_by_provider_id(
+                conn,
+                provider.provider_name(),
+                &data.dispute_id,
+            )?
+            .is_some())
+        })
+        .and_then(|pool| Ok(pool.get()?))
+        .map_err(|e| {
+            tracing::error!("DB connection error: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error")
+        })?;
+
+    let dispute = match queries::get_dispute_by_provider_id(
+        &conn,
+        provider.provider_name(),
+        &data.dispute_id,
+    ) {
+        Ok(Some(d)) => d,
+        Ok(None) => {
+            // Disputes opened before the license existed here (or before
+            // dispute tracking) have nothing to close
+            tracing::warn!(
+                "No dispute found for {} dispute: {}",
+                provider.provider_name(),
+                data.dispute_id
+            );
+            return Err((StatusCode::OK, "Dispute not found"));
+        }
+        Err(e) => {
+            tracing::error!("DB error: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error"));
+        }
+    };
+    let license = db_lookup(
+        queries::get_license_by_id(&conn, &dispute.license_id),
+        "License not found",
+    )?;
+    let (product, project, org) =
+        verify_for_license(provider, state, &conn, &license, body, signature)?;
+    *mirror_url = webhook_mirror_url(state, &project);
+
+    let result = process_dispute_closed(
+        &conn,
+        provider.provider_name(),
+        &license,
+        &dispute,
+        data.won,
+        state.clock.now(),
+    );
+
+    if result.0 == StatusCode::OK && result.1 == "OK" {
+        let audit_conn = state.audit.get().map_err(|e| {
+            tracing::error!("Audit DB connection error: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error")
+        })?;
+
+        if let Err(e) = AuditLogBuilder::for_state(&audit_conn, state, headers)
+            .actor(ActorType::Public, None)
+            .action(AuditAction::ReceiveDisputeClosedWebhook)
+            .resource("license", &license.id)
+            .details(&serde_json::json!({
+                "provider": provider.provider_name(),
+                "dispute_id": dispute.id,
+                "provider_dispute_id": dispute.provider_dispute_id,
+                "payment_id": dispute.payment_id,
+                "status": if data.won { DisputeStatus::Won } else { DisputeStatus::Lost },
+                "revoked": !data.won && !license.revoked,
+                "product_id": product.id,
+            }))
+            .org(&org.id)
+            .project(&project.id)
+            .names(&AuditLogNames {
+                org_name: Some(org.name.clone()),
+                project_name: Some(project.name.clone()),
+                ..Default::default()
+            })
+            .save()
+        {
+            tracing::warn!("Failed to write dispute audit log: {}", e);
+        }
+    }
+
+    Ok(result)
+}
@@ -8,15 +8,15 @@ use rusqlite::Connection;
 
 use crate::crypto::MasterKey;
 use crate::db::{AppState, queries};
-use crate::models::Organization;
+use crate::models::{Organization, RevocationReason};
 use crate::payments::{
     LemonSqueezyClient, LemonSqueezyOrderAttributes, LemonSqueezySubscriptionInvoiceAttributes,
-    LemonSqueezyWebhookEvent,
+    LemonSqueezySubscriptionPauseAttributes, LemonSqueezyWebhookEvent, echoed_metadata,
 };
 
 use super::common::{
-    CancellationData, CheckoutData, RenewalData, WebhookEvent, WebhookProvider, WebhookResult,
-    handle_webhook,
+    CancellationData, CheckoutData, PauseData, RefundData, RenewalData, WebhookEvent,
+    WebhookProvider, WebhookResult, handle_webhook, verification_failure,
 };
 
 /// LemonSqueezy webhook provider implementation.
@@ -64,13 +64,7 @@ impl WebhookProvider for LemonSqueezyWebhookProvider {
         let client = LemonSqueezyClient::new(&ls_config);
         client
             .verify_webhook_signature(body, signature)
-            .map_err(|e| {
-                tracing::error!("Signature verification error: {}", e);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "Signature verification failed",
-                )
-            })
+            .map_err(|e| verification_failure(self.provider_name(), &e))
     }
 
     fn parse_event(&self, body: &Bytes) -> Result<WebhookEvent, WebhookResult> {
@@ -81,8 +75,17 @@ impl WebhookProvider for LemonSqueezyWebhookProvider {
 
         match event.meta.event_name.as_str() {
             "order_created" => parse_order_created(&event),
+            "order_refunded" => parse_order_refunded(&event),
             "subscription_payment_success" => parse_subscription_payment(&event),
             "subscription_cancelled" => parse_subscription_cancelled(&event),
+            "subscription_paused" => Ok(WebhookEvent::SubscriptionPaused(
+                parse_subscription_pause(&event)?,
+            )),
+            "subscription_unpaused" => Ok(WebhookEvent::SubscriptionResumed(
+                parse_subscription_pause(&event)?,
+            )),
+            // No dispute events: as merchant of record, LemonSqueezy fights
+            // chargebacks itself and only reports a lost one as a refund
             _ => Ok(WebhookEvent::Ignored),
         }
     }
@@ -129,6 +132,34 @@ fn parse_order_created(event: &LemonSqueezyWebhookEvent) -> Result<WebhookEvent,
         customer_email: order.user_email,
         subscription_id,
         order_id: Some(event.data.id.clone()),
+        payment_id: Some(event.data.id.clone()),
+        metadata: echoed_metadata(&custom_data.other),
+    }))
+}
+
+/// `status` is `refunded` for a full refund and `partial_refund` otherwise.
+fn parse_order_refunded(event: &LemonSqueezyWebhookEvent) -> Result<WebhookEvent, WebhookResult> {
+    let order: LemonSqueezyOrderAttributes = serde_json::from_value(event.data.attributes.clone())
+        .map_err(|e| {
+            tracing::error!("Failed to parse order attributes: {}", e);
+            (StatusCode::BAD_REQUEST, "Invalid order attributes")
+        })?;
+
+    let partial = match order.status.as_str() {
+        "refunded" => false,
+        "partial_refund" => true,
+        _ => return Ok(WebhookEvent::Ignored),
+    };
+
+    Ok(WebhookEvent::Refunded(RefundData {
+        payment_id: Some(event.data.id.clone()),
+        order_id: Some(event.data.id.clone()),
+        subscription_id: order
+            .first_order_item
+            .and_then(|item| item.subscription_id)
+            .map(|id| id.to_string()),
+        partial,
+        reason: RevocationReason::Refund,
     }))
 }
 
@@ -163,6 +194,25 @@ fn parse_subscription_cancelled(
     }))
 }
 
+/// The paused time is counted from the subscription's `updated_at`, not from
+/// when the webhook arrived.
+fn parse_subscription_pause(event: &LemonSqueezyWebhookEvent) -> Result<PauseData, WebhookResult> {
+    let attributes: LemonSqueezySubscriptionPauseAttributes =
+        serde_json::from_value(event.data.attributes.clone()).map_err(|e| {
+            tracing::error!("Failed to parse subscription attributes: {}", e);
+            (StatusCode::BAD_REQUEST, "Invalid subscription attributes")
+        })?;
+    let occurred_at = attributes.updated_at_timestamp().ok_or_else(|| {
+        tracing::error!("Invalid subscription updated_at: {}", attributes.updated_at);
+        (StatusCode::BAD_REQUEST, "Invalid subscription attributes")
+    })?;
+
+    Ok(PauseData {
+        subscription_id: event.data.id.clone(),
+        occurred_at,
+    })
+}
+
 /// Axum handler for LemonSqueezy webhooks.
 pub async fn handle_lemonsqueezy_webhook(
     State(state): State<AppState>,
@@ -8,14 +8,16 @@ use rusqlite::Connection;
 
 use crate::crypto::MasterKey;
 use crate::db::{AppState, queries};
-use crate::models::Organization;
+use crate::models::{Organization, RevocationReason};
 use crate::payments::{
-    StripeCheckoutSession, StripeClient, StripeInvoice, StripeSubscription, StripeWebhookEvent,
+    StripeCharge, StripeCheckoutSession, StripeClient, StripeDispute, StripeInvoice,
+    StripeSubscription, StripeWebhookEvent, echoed_metadata,
 };
 
 use super::common::{
-    CancellationData, CheckoutData, RenewalData, WebhookEvent, WebhookProvider, WebhookResult,
-    handle_webhook,
+    CancellationData, CheckoutData, DisputeClosedData, DisputeData, PauseData, RefundData,
+    RenewalData, WebhookEvent, WebhookProvider, WebhookResult, handle_webhook,
+    verification_failure,
 };
 
 /// Stripe webhook provider implementation.
@@ -59,13 +61,7 @@ impl WebhookProvider for StripeWebhookProvider {
         let client = StripeClient::new(&stripe_config);
         client
             .verify_webhook_signature(body, signature)
-            .map_err(|e| {
-                tracing::error!("Signature verification error: {}", e);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "Signature verification failed",
-                )
-            })
+            .map_err(|e| verification_failure(self.provider_name(), &e))
     }
 
     fn parse_event(&self, body: &Bytes) -> Result<WebhookEvent, WebhookResult> {
@@ -76,8 +72,13 @@ impl WebhookProvider for StripeWebhookProvider {
 
         match event.event_type.as_str() {
             "checkout.session.completed" => parse_checkout_completed(&event),
+            "checkout.session.async_payment_failed" => parse_async_payment_failed(&event),
             "invoice.paid" => parse_invoice_paid(&event),
             "customer.subscription.deleted" => parse_subscription_deleted(&event),
+            "customer.subscription.updated" => parse_subscription_updated(&event),
+            "charge.refunded" => parse_charge_refunded(&event),
+            "charge.dispute.created" => parse_dispute_created(&event),
+            "charge.dispute.closed" => parse_dispute_closed(&event),
             _ => Ok(WebhookEvent::Ignored),
         }
     }
@@ -114,6 +115,28 @@ fn parse_checkout_completed(event: &StripeWebhookEvent) -> Result<WebhookEvent,
         customer_email,
         subscription_id: session.subscription,
         order_id: Some(session.id),
+        payment_id: session.payment_intent,
+        metadata: echoed_metadata(&session.metadata.other),
+    }))
+}
+
+/// A delayed payment method (bank debit, etc.) failed after checkout. Only
+/// sessions paid at completion create licenses, so this revokes one only if
+/// it was issued anyway; it is matched like a full refund, but recorded as
+/// `payment_failed` since nothing was refunded.
+fn parse_async_payment_failed(event: &StripeWebhookEvent) -> Result<WebhookEvent, WebhookResult> {
+    let session: StripeCheckoutSession = serde_json::from_value(event.data.object.clone())
+        .map_err(|e| {
+            tracing::error!("Failed to parse checkout session: {}", e);
+            (StatusCode::BAD_REQUEST, "Invalid checkout session")
+        })?;
+
+    Ok(WebhookEvent::Refunded(RefundData {
+        payment_id: session.payment_intent,
+        order_id: Some(session.id),
+        subscription_id: session.subscription,
+        partial: false,
+        reason: RevocationReason::PaymentFailed,
     }))
 }
 
@@ -162,6 +185,105 @@ fn parse_subscription_deleted(event: &StripeWebhookEvent) -> Result<WebhookEvent
     }))
 }
 
+/// Stripe signals pause/resume by setting or clearing `pause_collection`.
+/// Other subscription updates are ignored.
+fn parse_subscription_updated(event: &StripeWebhookEvent) -> Result<WebhookEvent, WebhookResult> {
+    let subscription: StripeSubscription = serde_json::from_value(event.data.object.clone())
+        .map_err(|e| {
+            tracing::error!("Failed to parse subscription: {}", e);
+            (StatusCode::BAD_REQUEST, "Invalid subscription")
+        })?;
+
+    let is_paused = subscription
+        .pause_collection
+        .as_ref()
+        .is_some_and(|p| !p.is_null());
+    // Only a change to pause_collection counts (previous_attributes lists changed fields)
+    let pause_changed = event
+        .data
+        .previous_attributes
+        .as_ref()
+        .is_some_and(|prev| prev.get("pause_collection").is_some());
+
+    if !pause_changed {
+        return Ok(WebhookEvent::Ignored);
+    }
+
+    // The paused time is counted from the event, not from when it arrived
+    let occurred_at = event.created.ok_or_else(|| {
+        tracing::error!("Subscription update without an event timestamp");
+        (StatusCode::BAD_REQUEST, "Invalid subscription")
+    })?;
+    let data = PauseData {
+        subscription_id: subscription.id,
+        occurred_at,
+    };
+    if is_paused {
+        Ok(WebhookEvent::SubscriptionPaused(data))
+    } else {
+        Ok(WebhookEvent::SubscriptionResumed(data))
+    }
+}
+
+/// `refunded` is only set once the whole charge is refunded; anything less
+/// is a partial refund.
+fn parse_charge_refunded(event: &StripeWebhookEvent) -> Result<WebhookEvent, WebhookResult> {
+    let charge: StripeCharge = serde_json::from_value(event.data.object.clone()).map_err(|e| {
+        tracing::error!("Failed to parse charge: {}", e);
+        (StatusCode::BAD_REQUEST, "Invalid charge")
+    })?;
+
+    match charge.payment_intent {
+        Some(payment_id) => Ok(WebhookEvent::Refunded(RefundData {
+            payment_id: Some(payment_id),
+            // Charges don't name their checkout session or subscription
+            order_id: None,
+            subscription_id: None,
+            partial: !charge.refunded,
+            reason: RevocationReason::Refund,
+        })),
+        None => Ok(WebhookEvent::Ignored),
+    }
+}
+
+fn parse_dispute(event: &StripeWebhookEvent) -> Result<StripeDispute, WebhookResult> {
+    serde_json::from_value(event.data.object.clone()).map_err(|e| {
+        tracing::error!("Failed to parse dispute: {}", e);
+        (StatusCode::BAD_REQUEST, "Invalid dispute")
+    })
+}
+
+fn parse_dispute_created(event: &StripeWebhookEvent) -> Result<WebhookEvent, WebhookResult> {
+    let dispute = parse_dispute(event)?;
+
+    match dispute.payment_intent {
+        Some(payment_id) => Ok(WebhookEvent::DisputeOpened(DisputeData {
+            dispute_id: dispute.id,
+            payment_id,
+            amount_cents: dispute.amount,
+            currency: dispute.currency,
+            reason: dispute.reason,
+        })),
+        None => Ok(WebhookEvent::Ignored),
+    }
+}
+
+/// `warning_closed` is an inquiry that closed without becoming a chargeback,
+/// which the merchant keeps the money for just like a won dispute.
+fn parse_dispute_closed(event: &StripeWebhookEvent) -> Result<WebhookEvent, WebhookResult> {
+    let dispute = parse_dispute(event)?;
+
+    let won = match dispute.status.as_str() {
+        "won" | "warning_closed" => true,
+        "lost" => false,
+        _ => return Ok(WebhookEvent::Ignored),
+    };
+    Ok(WebhookEvent::DisputeClosed(DisputeClosedData {
+        dispute_id: dispute.id,
+        won,
+    }))
+}
+
 /// Axum handler for Stripe webhooks.
 pub async fn handle_stripe_webhook(
     State(state): State<AppState>,
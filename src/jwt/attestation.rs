@@ -0,0 +1,83 @@
+//! Short-lived license attestations for third parties.
+//!
+//! A third party that a client wants to prove its license to (a plugin host,
+//! a companion web service) gets an attestation instead of the license token
+//! itself. It is signed with the same project key, so it verifies against the
+//! project's JWKS, but carries only what the third party needs: no subject,
+//! email, device, or license key. The license is identified by a hash scoped
+//! to the audience, so two third parties can't correlate their users.
+
+use std::collections::HashSet;
+
+use jwt_simple::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::signing::{key_pair, public_key};
+use crate::error::{AppError, Result};
+
+/// Lifetime of an attestation (`exp` - `iat`)
+pub const ATTESTATION_LIFETIME_SECS: u64 = 300;
+
+/// Clock skew allowed when verifying an attestation's timestamps
+pub const ATTESTATION_LEEWAY_SECS: u64 = 30;
+
+/// Claims in an attestation, besides `iss`, `aud`, `iat`, and `exp`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttestationClaims {
+    /// `license_hash(license_id, audience)`
+    pub license_hash: String,
+    pub tier: String,
+    pub product_id: String,
+    /// When license access ends (None = perpetual)
+    pub valid_until: Option<i64>,
+}
+
+/// Stable per-audience identifier for a license. The same license hashes
+/// differently for each audience.
+pub fn license_hash(license_id: &str, audience: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(audience.as_bytes());
+    hasher.update([0]);
+    hasher.update(license_id.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+/// Sign an attestation for `audience` with a project's private key
+pub fn sign_attestation(
+    claims: &AttestationClaims,
+    private_key: &[u8],
+    issuer: &str,
+    audience: &str,
+) -> Result<String> {
+    let key_pair = key_pair(private_key)?;
+    let jwt_claims = Claims::with_custom_claims(
+        claims.clone(),
+        Duration::from_secs(ATTESTATION_LIFETIME_SECS),
+    )
+    .with_issuer(issuer)
+    .with_audience(audience);
+
+    key_pair
+        .sign(jwt_claims)
+        .map_err(|e| AppError::Internal(format!("Failed to sign attestation: {}", e)))
+}
+
+/// Verify an attestation the way a third party would, as of `now`:
+/// signature, that it was issued for `audience`, and that it hasn't expired
+pub fn verify_attestation(
+    token: &str,
+    public_key_b64: &str,
+    audience: &str,
+    now: i64,
+) -> Result<JWTClaims<AttestationClaims>> {
+    let options = VerificationOptions {
+        allowed_audiences: Some(HashSet::from([audience.to_string()])),
+        artificial_time: Some(UnixTimeStamp::from_secs(now.max(0) as u64)),
+        time_tolerance: Some(Duration::from_secs(ATTESTATION_LEEWAY_SECS)),
+        ..Default::default()
+    };
+    public_key(public_key_b64)?
+        .verify_token::<AttestationClaims>(token, Some(options))
+        .map_err(|e| AppError::BadRequest(format!("Invalid attestation: {}", e)))
+}
@@ -1,5 +1,8 @@
 use serde::{Deserialize, Serialize};
 
+use crate::models::{DeviceType, EntitlementValue, Entitlements, License, Product, Project};
+use crate::util::LicenseExpirations;
+
 /// Custom claims for Paycheck licenses (non-standard JWT claims)
 /// Standard claims (iss, sub, aud, jti, iat, exp) are handled by jwt-simple
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -9,10 +12,14 @@ pub struct LicenseClaims {
     pub updates_exp: Option<i64>, // When new version access ends
     pub tier: String,             // Product tier
     pub features: Vec<String>,    // Enabled features
+    #[serde(default, skip_serializing_if = "Entitlements::is_empty")]
+    pub entitlements: Entitlements, // Structured entitlements, e.g. {"seats": 5}
 
     // Identity
     pub device_id: String,   // Device identifier
     pub device_type: String, // "uuid" or "machine"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license_ref: Option<String>, // Public license handle, same as sub
 
     // Metadata
     pub product_id: String, // Product ID
@@ -33,4 +40,58 @@ impl LicenseClaims {
     pub fn has_feature(&self, feature: &str) -> bool {
         self.features.iter().any(|f| f == feature)
     }
+
+    pub fn entitlement(&self, name: &str) -> Option<&EntitlementValue> {
+        self.entitlements.get(name)
+    }
+}
+
+/// The device a license token is issued to
+#[derive(Debug, Clone, Copy)]
+pub struct DeviceInfo<'a> {
+    pub device_id: &'a str,
+    pub device_type: DeviceType,
+    /// Base time for `license_exp`/`updates_exp` (now on redemption, the
+    /// device's activation time on refresh)
+    pub activated_at: i64,
+}
+
+/// Everything that goes into a license token except `jti` and the timestamps
+/// jwt-simple sets when signing
+#[derive(Debug, Clone, Serialize)]
+pub struct LicenseToken {
+    pub issuer: String,
+    /// License ref, or the license ID for a license without one
+    pub subject: String,
+    pub audience: String,
+    pub claims: LicenseClaims,
+}
+
+/// Build the claims for a license token. Redemption, refresh and the claims
+/// preview all go through here so they can't drift apart.
+pub fn build_license_claims(
+    license: &License,
+    product: &Product,
+    project: &Project,
+    device: &DeviceInfo,
+) -> LicenseToken {
+    let exps = LicenseExpirations::from_product(product, device.activated_at);
+    let subject = license.license_ref.as_ref().unwrap_or(&license.id);
+
+    LicenseToken {
+        issuer: project.token_issuer().to_string(),
+        subject: subject.clone(),
+        audience: project.token_audience().to_string(),
+        claims: LicenseClaims {
+            license_exp: exps.license_exp,
+            updates_exp: exps.updates_exp,
+            tier: product.tier.clone(),
+            features: product.features.clone(),
+            entitlements: product.entitlements.clone(),
+            device_id: device.device_id.to_string(),
+            device_type: device.device_type.as_ref().to_string(),
+            license_ref: license.license_ref.clone(),
+            product_id: product.id.clone(),
+        },
+    }
 }
@@ -7,6 +7,8 @@
 //! - Automatic caching with 1-hour TTL
 //! - Retry with exponential backoff on fetch failures
 //! - Stale cache fallback when all retries are exhausted
+//! - Manual invalidation (`POST /operators/jwks/refresh`) for issuers that
+//!   rotate keys before the TTL runs out
 
 use std::collections::HashMap;
 use std::sync::RwLock;
@@ -71,6 +73,7 @@ impl JwksCache {
             cache: RwLock::new(HashMap::new()),
             client: reqwest::Client::builder()
                 .timeout(Duration::from_secs(10))
+                .redirect(reqwest::redirect::Policy::limited(3))
                 .build()
                 .expect("Failed to create HTTP client"),
         }
@@ -143,6 +146,23 @@ impl JwksCache {
         }
     }
 
+    /// Drop the cached keys for one JWKS URL so the next lookup refetches.
+    /// Returns whether anything was cached.
+    pub fn invalidate(&self, jwks_url: &str) -> bool {
+        self.cache.write().unwrap().remove(jwks_url).is_some()
+    }
+
+    /// Drop every cached JWKS. Returns how many were cleared.
+    ///
+    /// This also discards the stale fallback, so an issuer that's down when
+    /// the next token arrives fails validation until it's reachable again.
+    pub fn clear(&self) -> usize {
+        let mut cache = self.cache.write().unwrap();
+        let cleared = cache.len();
+        cache.clear();
+        cleared
+    }
+
     /// Fetch JWKS with retry and exponential backoff.
     async fn fetch_jwks_with_retry(&self, url: &str) -> Result<HashMap<String, RS256PublicKey>> {
         let mut last_error = None;
@@ -343,6 +363,35 @@ mod tests {
         assert!(!cached.is_expired());
     }
 
+    fn seeded_cache(urls: &[&str]) -> JwksCache {
+        let cache = JwksCache::new();
+        for url in urls {
+            cache.seed_cache_for_testing(url, HashMap::new(), Duration::ZERO);
+        }
+        cache
+    }
+
+    #[test]
+    fn test_invalidate_removes_only_that_url() {
+        let cache = seeded_cache(&["https://a.example/jwks", "https://b.example/jwks"]);
+
+        assert!(cache.invalidate("https://a.example/jwks"));
+        assert!(!cache.invalidate("https://a.example/jwks"));
+
+        let cached = cache.cache.read().unwrap();
+        assert!(!cached.contains_key("https://a.example/jwks"));
+        assert!(cached.contains_key("https://b.example/jwks"));
+    }
+
+    #[test]
+    fn test_clear_removes_everything() {
+        let cache = seeded_cache(&["https://a.example/jwks", "https://b.example/jwks"]);
+
+        assert_eq!(cache.clear(), 2);
+        assert!(cache.cache.read().unwrap().is_empty());
+        assert_eq!(cache.clear(), 0);
+    }
+
     #[test]
     fn test_retry_constants_are_reasonable() {
         // Sanity check that retry settings are reasonable
@@ -1,8 +1,10 @@
+mod attestation;
 mod claims;
 pub mod first_party;
 pub mod jwks;
 mod signing;
 
+pub use attestation::*;
 pub use claims::*;
 pub use first_party::{
     FirstPartyTokenClaims, ValidatedFirstPartyToken, validate_first_party_token,
@@ -1,13 +1,40 @@
+use std::collections::HashSet;
+
 use base64::engine::general_purpose::STANDARD as BASE64;
 use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL};
 use ed25519_dalek::{SigningKey, VerifyingKey};
 use jwt_simple::prelude::*;
 use rand::rngs::OsRng;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
-use super::LicenseClaims;
+use super::{LicenseClaims, LicenseToken};
 use crate::error::{AppError, Result, msg};
 
+/// Default `iss` claim for projects without an issuer override
+pub const DEFAULT_ISSUER: &str = "paycheck";
+
+/// Lifetime of a license token (`exp` - `iat`)
+pub const TOKEN_LIFETIME_SECS: u64 = 3600;
+
+/// Registered claims a verified token must carry.
+#[derive(Debug, Clone)]
+pub struct ExpectedClaims {
+    /// Accepted `iss` values
+    pub issuers: HashSet<String>,
+    /// Accepted `aud` values (None = audience not checked)
+    pub audiences: Option<HashSet<String>>,
+}
+
+impl Default for ExpectedClaims {
+    fn default() -> Self {
+        Self {
+            issuers: HashSet::from([DEFAULT_ISSUER.to_string()]),
+            audiences: None,
+        }
+    }
+}
+
 /// Generate a new Ed25519 key pair
 /// Returns (private_key_bytes, public_key_base64)
 pub fn generate_keypair() -> (Vec<u8>, String) {
@@ -20,6 +47,24 @@ pub fn generate_keypair() -> (Vec<u8>, String) {
     (private_bytes, public_b64)
 }
 
+/// Key ID (`kid`) for a project's public key: its RFC 7638 JWK thumbprint.
+/// Every token carries the `kid` of the key that signed it.
+pub fn key_id(public_key_b64: &str) -> Result<String> {
+    let public_bytes = BASE64
+        .decode(public_key_b64)
+        .map_err(|e| AppError::Internal(format!("Invalid public key encoding: {}", e)))?;
+    Ok(thumbprint(&public_bytes))
+}
+
+fn thumbprint(public_bytes: &[u8]) -> String {
+    // Required members in lexicographic order, no whitespace (RFC 7638 section 3)
+    let jwk = format!(
+        r#"{{"crv":"Ed25519","kty":"OKP","x":"{}"}}"#,
+        BASE64_URL.encode(public_bytes)
+    );
+    BASE64_URL.encode(Sha256::digest(jwk.as_bytes()))
+}
+
 /// Sign claims with an Ed25519 private key
 /// The `audience` parameter is included in the JWT for debugging purposes only
 /// (e.g., to identify which project a token belongs to). It is NOT verified.
@@ -30,6 +75,37 @@ pub fn sign_claims(
     audience: &str,
     jti: &str,
 ) -> Result<String> {
+    sign_claims_with_issuer(claims, private_key, subject, DEFAULT_ISSUER, audience, jti)
+}
+
+/// Sign claims with an explicit issuer (projects with a `jwt_issuer` override)
+pub fn sign_claims_with_issuer(
+    claims: &LicenseClaims,
+    private_key: &[u8],
+    subject: &str,
+    issuer: &str,
+    audience: &str,
+    jti: &str,
+) -> Result<String> {
+    let key_pair = key_pair(private_key)?;
+
+    // Create claims with standard fields handled by jwt-simple
+    let jwt_claims =
+        Claims::with_custom_claims(claims.clone(), Duration::from_secs(TOKEN_LIFETIME_SECS))
+            .with_issuer(issuer)
+            .with_subject(subject)
+            .with_audience(audience)
+            .with_jwt_id(jti);
+
+    let token = key_pair
+        .sign(jwt_claims)
+        .map_err(|e| AppError::Internal(format!("Failed to sign token: {}", e)))?;
+
+    Ok(token)
+}
+
+/// A project's signing key pair, tagged with its `kid`
+pub(super) fn key_pair(private_key: &[u8]) -> Result<Ed25519KeyPair> {
     if private_key.len() != 32 {
         return Err(AppError::Internal(msg::INVALID_PRIVATE_KEY_LENGTH.into()));
     }
@@ -39,21 +115,53 @@ pub fn sign_claims(
         .map_err(|_| AppError::Internal(msg::FAILED_TO_CONVERT_KEY_BYTES.into()))?;
 
     let signing_key = SigningKey::from_bytes(&key_bytes);
-    let key_pair = Ed25519KeyPair::from_bytes(&signing_key.to_keypair_bytes())
-        .map_err(|e| AppError::Internal(format!("Failed to create key pair: {}", e)))?;
+    let kid = thumbprint(&signing_key.verifying_key().to_bytes());
+    Ok(Ed25519KeyPair::from_bytes(&signing_key.to_keypair_bytes())
+        .map_err(|e| AppError::Internal(format!("Failed to create key pair: {}", e)))?
+        .with_key_id(&kid))
+}
 
-    // Create claims with standard fields handled by jwt-simple
-    let jwt_claims = Claims::with_custom_claims(claims.clone(), Duration::from_secs(3600))
-        .with_issuer("paycheck")
-        .with_subject(subject)
-        .with_audience(audience)
-        .with_jwt_id(jti);
+/// Sign a token built by `build_license_claims`
+pub fn sign_license_token(token: &LicenseToken, private_key: &[u8], jti: &str) -> Result<String> {
+    sign_claims_with_issuer(
+        &token.claims,
+        private_key,
+        &token.subject,
+        &token.issuer,
+        &token.audience,
+        jti,
+    )
+}
 
-    let token = key_pair
-        .sign(jwt_claims)
+/// Sign a throwaway token with no license claims and check it against the
+/// project's public key, as a health probe. Nothing is stored, and the
+/// token is dropped here.
+pub fn check_signing(private_key: &[u8], public_key_b64: &str) -> Result<()> {
+    let token = key_pair(private_key)?
+        .sign(Claims::create(Duration::from_secs(60)).with_subject("health-check"))
         .map_err(|e| AppError::Internal(format!("Failed to sign token: {}", e)))?;
+    public_key(public_key_b64)?
+        .verify_token::<NoCustomClaims>(&token, None)
+        .map_err(|e| AppError::Internal(format!("Signature check failed: {}", e)))?;
+    Ok(())
+}
 
-    Ok(token)
+/// JOSE header of a token, decoded without verification
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenHeader {
+    pub alg: String,
+    /// Absent on tokens issued before key IDs were added
+    pub kid: Option<String>,
+}
+
+/// Decode a JWT's header without verification (for diagnostics)
+pub fn decode_header(token: &str) -> Result<TokenHeader> {
+    let metadata = Token::decode_metadata(token)
+        .map_err(|_| AppError::BadRequest(msg::INVALID_TOKEN_HEADER.into()))?;
+    Ok(TokenHeader {
+        alg: metadata.algorithm().to_string(),
+        kid: metadata.key_id().map(String::from),
+    })
 }
 
 /// Decode a JWT without verification to extract claims
@@ -86,7 +194,7 @@ pub fn decode_unverified(token: &str) -> Result<LicenseClaims> {
 /// Note: Audience is NOT verified - signature verification with the project's
 /// public key is sufficient to prove the token was issued for that project.
 pub fn verify_token(token: &str, public_key_b64: &str) -> Result<JWTClaims<LicenseClaims>> {
-    verify_token_internal(token, public_key_b64, false)
+    verify_token_internal(token, public_key_b64, Some(&ExpectedClaims::default()), false)
 }
 
 /// Verify a JWT signature but allow expired tokens (for refresh flow)
@@ -95,35 +203,48 @@ pub fn verify_token_allow_expired(
     token: &str,
     public_key_b64: &str,
 ) -> Result<JWTClaims<LicenseClaims>> {
-    verify_token_internal(token, public_key_b64, true)
+    verify_token_internal(token, public_key_b64, Some(&ExpectedClaims::default()), true)
 }
 
-fn verify_token_internal(
+/// Verify a JWT against a project's accepted issuers/audiences
+/// (see `Project::expected_token_claims`)
+pub fn verify_token_expecting(
     token: &str,
     public_key_b64: &str,
-    allow_expired: bool,
+    expected: &ExpectedClaims,
 ) -> Result<JWTClaims<LicenseClaims>> {
-    let public_bytes = BASE64
-        .decode(public_key_b64)
-        .map_err(|e| AppError::Internal(format!("Invalid public key encoding: {}", e)))?;
-
-    if public_bytes.len() != 32 {
-        return Err(AppError::Internal(msg::INVALID_PUBLIC_KEY_LENGTH.into()));
-    }
+    verify_token_internal(token, public_key_b64, Some(expected), false)
+}
 
-    let key_bytes: [u8; 32] = public_bytes
-        .try_into()
-        .map_err(|_| AppError::Internal(msg::FAILED_TO_CONVERT_KEY_BYTES.into()))?;
+/// Like `verify_token_expecting`, but allows expired tokens (for refresh flow)
+pub fn verify_token_expecting_allow_expired(
+    token: &str,
+    public_key_b64: &str,
+    expected: &ExpectedClaims,
+) -> Result<JWTClaims<LicenseClaims>> {
+    verify_token_internal(token, public_key_b64, Some(expected), true)
+}
 
-    let verifying_key = VerifyingKey::from_bytes(&key_bytes)
-        .map_err(|e| AppError::Internal(format!("Invalid public key: {}", e)))?;
+/// Verify only a JWT's signature: any issuer, audience, or expiration is
+/// accepted. For diagnosing tokens, never for authorizing requests.
+pub fn verify_signature(token: &str, public_key_b64: &str) -> Result<JWTClaims<LicenseClaims>> {
+    verify_token_internal(token, public_key_b64, None, true)
+}
 
-    let public_key = Ed25519PublicKey::from_bytes(&verifying_key.to_bytes())
-        .map_err(|e| AppError::Internal(format!("Failed to create public key: {}", e)))?;
+/// `expected: None` skips the issuer and audience checks
+fn verify_token_internal(
+    token: &str,
+    public_key_b64: &str,
+    expected: Option<&ExpectedClaims>,
+    allow_expired: bool,
+) -> Result<JWTClaims<LicenseClaims>> {
+    let public_key = public_key(public_key_b64)?;
 
     let mut options = VerificationOptions {
-        allowed_issuers: Some(std::collections::HashSet::from(["paycheck".to_string()])),
-        // Audience not verified - signature with project's key is sufficient
+        allowed_issuers: expected.map(|e| e.issuers.clone()),
+        // Audience only checked for projects with an explicit override -
+        // otherwise signature with project's key is sufficient
+        allowed_audiences: expected.and_then(|e| e.audiences.clone()),
         ..Default::default()
     };
 
@@ -139,3 +260,24 @@ fn verify_token_internal(
 
     Ok(claims)
 }
+
+/// A project's public key (base64) as a verifier
+pub(super) fn public_key(public_key_b64: &str) -> Result<Ed25519PublicKey> {
+    let public_bytes = BASE64
+        .decode(public_key_b64)
+        .map_err(|e| AppError::Internal(format!("Invalid public key encoding: {}", e)))?;
+
+    if public_bytes.len() != 32 {
+        return Err(AppError::Internal(msg::INVALID_PUBLIC_KEY_LENGTH.into()));
+    }
+
+    let key_bytes: [u8; 32] = public_bytes
+        .try_into()
+        .map_err(|_| AppError::Internal(msg::FAILED_TO_CONVERT_KEY_BYTES.into()))?;
+
+    let verifying_key = VerifyingKey::from_bytes(&key_bytes)
+        .map_err(|e| AppError::Internal(format!("Invalid public key: {}", e)))?;
+
+    Ed25519PublicKey::from_bytes(&verifying_key.to_bytes())
+        .map_err(|e| AppError::Internal(format!("Failed to create public key: {}", e)))
+}
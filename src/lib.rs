@@ -3,17 +3,34 @@
 //! This library provides the core functionality for the Paycheck licensing system,
 //! including database operations, JWT handling, payment provider integration, and API handlers.
 
+pub mod artifacts;
+pub mod audit_archive;
+pub mod audit_csv;
+pub mod backup;
+pub mod bulk_jobs;
 pub mod config;
 pub mod crypto;
+pub mod custom_domains;
 pub mod db;
 pub mod email;
 pub mod error;
+pub mod external_url;
 pub mod extractors;
+pub mod fixtures;
+pub mod geoip;
 pub mod handlers;
 pub mod jwt;
+pub mod license_import;
 pub mod middleware;
 pub mod models;
 pub mod pagination;
 pub mod payments;
+pub mod project_config;
+pub mod quota;
 pub mod rate_limit;
+pub mod reconcile;
+pub mod startup;
+pub mod storage_health;
 pub mod util;
+pub mod webhook_mirror;
+pub mod webhook_payloads;
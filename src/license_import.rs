@@ -0,0 +1,152 @@
+//! Importing license keys issued by LemonSqueezy.
+//!
+//! Projects that sold through LemonSqueezy's built-in license keys have
+//! customers holding those keys. An import pages through the store's keys and
+//! creates a Paycheck license for each one whose LemonSqueezy product is
+//! mapped to a project product, storing only a hash of the key. The customer
+//! then redeems the old key once at `/redeem/lemonsqueezy` and gets a Paycheck
+//! token; from there on, validation never involves LemonSqueezy.
+//!
+//! Keys already imported into the project are skipped, so an import can be
+//! re-run to pick up keys sold since the last one.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::crypto::hash_secret;
+use crate::db::{AppState, queries};
+use crate::error::{AppError, Result, msg};
+use crate::models::{CreateLicense, EmailAddress, Product};
+use crate::payments::{LemonSqueezyClient, LemonSqueezyLicenseKey};
+
+/// `external_key_provider` of licenses imported from LemonSqueezy
+pub const LEMONSQUEEZY: &str = "lemonsqueezy";
+
+/// Canonical form of a provider license key as a customer might type it
+/// (LemonSqueezy keys are uppercase UUIDs).
+pub fn normalize_external_key(key: &str) -> String {
+    key.trim().to_ascii_uppercase()
+}
+
+/// Hash stored in `licenses.external_key_hash`.
+pub fn external_key_hash(key: &str) -> String {
+    hash_secret(&normalize_external_key(key))
+}
+
+/// What an import did with the keys it fetched.
+#[derive(Debug, Default, Serialize)]
+pub struct ImportResult {
+    /// Keys LemonSqueezy returned
+    pub fetched: usize,
+    /// Licenses created
+    pub imported: usize,
+    /// Keys imported by an earlier run
+    pub already_imported: usize,
+    /// Keys for LemonSqueezy products not in the product map
+    pub unmapped: usize,
+    /// Keys disabled in LemonSqueezy
+    pub disabled: usize,
+}
+
+/// Import the store's license keys into a project. `products` maps
+/// LemonSqueezy product IDs to the project's product IDs; keys for any other
+/// LemonSqueezy product are counted and skipped.
+pub async fn import_lemonsqueezy(
+    state: &AppState,
+    org_id: &str,
+    project_id: &str,
+    client: &LemonSqueezyClient,
+    products: &HashMap<String, String>,
+    pace: Duration,
+) -> Result<ImportResult> {
+    if products.is_empty() {
+        return Err(AppError::BadRequest(msg::IMPORT_PRODUCTS_REQUIRED.into()));
+    }
+    let products = {
+        let conn = state.org_db(org_id).get()?;
+        let mut resolved = HashMap::with_capacity(products.len());
+        for (ls_product_id, product_id) in products {
+            let product = queries::get_product_by_id(&conn, product_id)?
+                .filter(|product| product.project_id == project_id)
+                .ok_or_else(|| AppError::NotFound(msg::PRODUCT_NOT_FOUND.into()))?;
+            resolved.insert(ls_product_id.clone(), product);
+        }
+        resolved
+    };
+
+    let mut result = ImportResult::default();
+    let mut page = Some(1);
+    while let Some(number) = page {
+        if number > 1 && !pace.is_zero() {
+            tokio::time::sleep(pace).await;
+        }
+        let (keys, next) = state
+            .provider_calls
+            .run(org_id, client.list_license_keys(number))
+            .await?;
+        result.fetched += keys.len();
+        for key in &keys {
+            import_key(state, org_id, project_id, &products, key, &mut result)?;
+        }
+        page = next;
+    }
+    Ok(result)
+}
+
+fn import_key(
+    state: &AppState,
+    org_id: &str,
+    project_id: &str,
+    products: &HashMap<String, Product>,
+    key: &LemonSqueezyLicenseKey,
+    result: &mut ImportResult,
+) -> Result<()> {
+    if key.disabled {
+        result.disabled += 1;
+        return Ok(());
+    }
+    let Some(product) = products.get(&key.product_id) else {
+        result.unmapped += 1;
+        return Ok(());
+    };
+
+    let key_hash = external_key_hash(&key.key);
+    let mut conn = state.org_db(org_id).get()?;
+    if queries::external_key_imported(&conn, project_id, LEMONSQUEEZY, &key_hash)? {
+        result.already_imported += 1;
+        return Ok(());
+    }
+
+    // A malformed email still imports: the order ID identifies the license
+    let email_hash = key
+        .user_email
+        .as_deref()
+        .and_then(|email| EmailAddress::parse(email).ok())
+        .map(|email| state.email_hasher.hash(&email));
+
+    let tx = conn.transaction()?;
+    let license = queries::create_license(
+        &tx,
+        project_id,
+        &product.id,
+        &CreateLicense {
+            email_hash,
+            customer_id: None,
+            // LemonSqueezy keys have one expiration, for use and updates alike
+            expires_at: key.expires_at,
+            updates_expires_at: key.expires_at,
+            payment_provider: Some(LEMONSQUEEZY.to_string()),
+            payment_provider_customer_id: key.customer_id.clone(),
+            payment_provider_subscription_id: None,
+            payment_provider_order_id: Some(key.order_id.clone()),
+            seats: product.seat_count,
+        },
+    )?;
+    queries::set_license_external_key(&tx, &license.id, LEMONSQUEEZY, &key_hash)?;
+    tx.commit()?;
+
+    result.imported += 1;
+    Ok(())
+}
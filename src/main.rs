@@ -1,22 +1,33 @@
-use axum::Router;
 use clap::Parser;
-use tower_http::trace::TraceLayer;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::time::Duration;
 
-use paycheck::config::Config;
+use paycheck::backup::Backups;
+use paycheck::bulk_jobs;
+use paycheck::config::{Config, ConfigSource};
 use paycheck::crypto::{EmailHasher, MasterKey};
-use paycheck::db::{AppState, MigrationTarget, create_pool, init_audit_db, init_db, queries, run_migrations};
+use paycheck::db::{
+    AppState, MigrationTarget, OrgDbRegistry, ProjectMissCache, create_pool, init_audit_db,
+    init_db, outbox, queries, run_migrations,
+};
 use paycheck::email::EmailService;
+use paycheck::fixtures;
 use paycheck::handlers;
-use paycheck::jwt::{self, JwksCache};
+use paycheck::jwt::JwksCache;
+use paycheck::middleware::MaintenanceMode;
 use paycheck::models::{
-    self, ActorType, AuditAction, AuditLogNames, CreateOrgMember, CreateProduct, CreateProject,
-    CreateProviderLink, CreateUser, OperatorRole, OrgMemberRole,
+    self, ActorType, AuditAction, AuditLogNames, CreateOrgMember, CreateProduct,
+    CreateProviderLink, CreateUser, OperatorRole, OrgMemberRole, redact_pii_details,
 };
-use paycheck::rate_limit::ActivationRateLimiter;
+use paycheck::payments::{PaymentProvider, ProviderCallGovernor};
+use paycheck::rate_limit::{ActivationRateLimiter, ValidationRateLimiter};
+use paycheck::reconcile::{self, RunOptions, SubscriptionClient};
+use paycheck::startup;
+use paycheck::storage_health;
+use paycheck::util::Clock;
 
 #[derive(Parser, Debug)]
 #[command(name = "paycheck")]
@@ -26,6 +37,19 @@ struct Cli {
     #[arg(long)]
     seed: bool,
 
+    /// Seed a demo dataset (org, projects, products, licenses, devices, audit
+    /// history) for local development. Dev mode only.
+    #[arg(long, conflicts_with = "seed")]
+    seed_demo: bool,
+
+    /// Seed for the demo dataset; the same seed gives the same data (for --seed-demo)
+    #[arg(long, requires = "seed_demo", default_value_t = fixtures::demo::DEFAULT_DEMO_SEED)]
+    demo_seed: u64,
+
+    /// Seed the demo dataset even if the database already has organizations (for --seed-demo)
+    #[arg(long, requires = "seed_demo")]
+    force: bool,
+
     /// Delete databases on exit (dev mode only, useful for fresh starts)
     #[arg(long)]
     ephemeral: bool,
@@ -41,6 +65,33 @@ struct Cli {
     /// Path to the new master key file (for --rotate-key)
     #[arg(long, requires = "rotate_key")]
     new_key_file: Option<String>,
+
+    /// Move an existing organization's data into its own database file.
+    /// Requires PAYCHECK_ORG_DATA_DIR. Run while the server is stopped.
+    #[arg(long, value_name = "ORG_ID")]
+    isolate_org: Option<String>,
+
+    /// Encrypt existing plaintext user emails in place (for PII_MINIMIZATION).
+    /// Uses the configured master key. Run while the server is stopped.
+    #[arg(long)]
+    encrypt_user_emails: bool,
+
+    /// Give organizations created before per-org data keys their own key and
+    /// re-encrypt their secrets under it. Uses the configured master key.
+    /// Run while the server is stopped.
+    #[arg(long)]
+    migrate_org_keys: bool,
+
+    /// Restore a backup taken with POST /operators/backups into DATABASE_PATH
+    /// and AUDIT_DATABASE_PATH, which must not exist yet. Requires
+    /// PAYCHECK_BACKUP_DIR and the key the backup was encrypted with.
+    #[arg(long, value_name = "BACKUP_ID")]
+    restore_backup: Option<String>,
+
+    /// Run the startup self-check and exit, non-zero if it fails. Starts up as
+    /// the server would (pending migrations are applied) but doesn't listen.
+    #[arg(long)]
+    check: bool,
 }
 
 fn bootstrap_first_operator(state: &AppState, email: &str) {
@@ -59,6 +110,8 @@ fn bootstrap_first_operator(state: &AppState, email: &str) {
         return;
     }
 
+    let emails = state.emails();
+
     // Create user first
     let user = queries::create_user(
         &conn,
@@ -66,11 +119,12 @@ fn bootstrap_first_operator(state: &AppState, email: &str) {
             email: email.to_string(),
             name: "Bootstrap Operator".to_string(),
         },
+        &emails,
     )
     .expect("Failed to create bootstrap user");
 
     // Grant operator role to user
-    let user = queries::grant_operator_role(&conn, &user.id, OperatorRole::Owner)
+    let user = queries::grant_operator_role(&conn, &user.id, OperatorRole::Owner, &emails)
         .expect("Failed to grant operator role");
 
     // Create API key for the operator's user
@@ -86,17 +140,25 @@ fn bootstrap_first_operator(state: &AppState, email: &str) {
         AuditAction::BootstrapOperator.as_ref(),
         "operator",
         &user.id,
-        Some(&serde_json::json!({
-            "email": email,
-            "role": "owner",
-        })),
+        Some(&system_audit_details(
+            state,
+            serde_json::json!({
+                "email": email,
+                "role": "owner",
+            }),
+        )),
         None,
         None,
         None,
         None,
-        &AuditLogNames::default().resource(user.name.clone()),
+        &system_audit_names(
+            state,
+            "operator",
+            AuditLogNames::default().resource_user(&user.name, &user.email),
+        ),
         None, // auth_type (system action)
         None, // auth_credential
+        None, // request_id
     )
     .expect("Failed to create audit log for bootstrap");
 
@@ -109,6 +171,28 @@ fn bootstrap_first_operator(state: &AppState, email: &str) {
     tracing::info!("============================================");
 }
 
+/// Audit names for a system action, without user names/emails under PII minimization.
+fn system_audit_names(
+    state: &AppState,
+    resource_type: &str,
+    names: AuditLogNames,
+) -> AuditLogNames {
+    if state.pii_minimization {
+        names.redact_pii(resource_type)
+    } else {
+        names
+    }
+}
+
+/// Audit details for a system action, without emails under PII minimization.
+fn system_audit_details(state: &AppState, details: serde_json::Value) -> serde_json::Value {
+    if state.pii_minimization {
+        redact_pii_details(&details)
+    } else {
+        details
+    }
+}
+
 /// Seeds the database with dev data for testing.
 /// Creates: operator, organization, org member, project, and product.
 /// Only runs in dev mode and when database is empty.
@@ -129,6 +213,8 @@ fn seed_dev_data(state: &AppState) {
         return;
     }
 
+    let emails = state.emails();
+
     // 1. Create operator user and grant operator role
     let operator_user = queries::create_user(
         &conn,
@@ -136,11 +222,13 @@ fn seed_dev_data(state: &AppState) {
             email: "dev@paycheck.local".to_string(),
             name: "Dev Operator".to_string(),
         },
+        &emails,
     )
     .expect("Failed to create operator user");
 
-    let operator_user = queries::grant_operator_role(&conn, &operator_user.id, OperatorRole::Owner)
-        .expect("Failed to grant operator role");
+    let operator_user =
+        queries::grant_operator_role(&conn, &operator_user.id, OperatorRole::Owner, &emails)
+            .expect("Failed to grant operator role");
 
     // Create API key for operator
     let (_, operator_api_key) =
@@ -160,9 +248,14 @@ fn seed_dev_data(state: &AppState) {
         None,
         None,
         None,
-        &AuditLogNames::default().resource_user(&operator_user.name, &operator_user.email),
+        &system_audit_names(
+            state,
+            "operator",
+            AuditLogNames::default().resource_user(&operator_user.name, &operator_user.email),
+        ),
         None, // auth_type (system action)
         None, // auth_credential
+        None, // request_id
     )
     .expect("Failed to create audit log");
 
@@ -173,6 +266,7 @@ fn seed_dev_data(state: &AppState) {
             name: "Dev Org".to_string(),
             owner_user_id: None,
         },
+        &state.master_key,
     )
     .expect("Failed to create dev organization");
 
@@ -192,6 +286,7 @@ fn seed_dev_data(state: &AppState) {
         &AuditLogNames::default().resource(org.name.clone()),
         None, // auth_type (system action)
         None, // auth_credential
+        None, // request_id
     )
     .expect("Failed to create audit log");
 
@@ -202,6 +297,7 @@ fn seed_dev_data(state: &AppState) {
             email: "dev@devorg.local".to_string(),
             name: "Dev Member".to_string(),
         },
+        &emails,
     )
     .expect("Failed to create member user");
 
@@ -233,30 +329,24 @@ fn seed_dev_data(state: &AppState) {
         None,
         None,
         None,
-        &AuditLogNames::default()
-            .resource_user(&member_user.name, &member_user.email)
-            .org(org.name.clone()),
+        &system_audit_names(
+            state,
+            "org_member",
+            AuditLogNames::default()
+                .resource_user(&member_user.name, &member_user.email)
+                .org(org.name.clone()),
+        ),
         None, // auth_type (system action)
         None, // auth_credential
+        None, // request_id
     )
     .expect("Failed to create audit log");
 
     // 4. Create project
-    let (private_key, public_key) = jwt::generate_keypair();
-    let project_input = CreateProject {
-        name: "Dev Project".to_string(),
-        license_key_prefix: "PC".to_string(),
-        redirect_url: None,
-        email_from: None,
-        email_enabled: true,
-        email_webhook_url: None,
-    };
-    let project = queries::create_project(
+    let project = fixtures::create_project(
         &conn,
         &org.id,
-        &project_input,
-        &private_key,
-        &public_key,
+        &fixtures::project_input("Dev Project", "PC"),
         &state.master_key,
     )
     .expect("Failed to create dev project");
@@ -280,25 +370,24 @@ fn seed_dev_data(state: &AppState) {
             .project(project.name.clone()),
         None, // auth_type (system action)
         None, // auth_credential
+        None, // request_id
     )
     .expect("Failed to create audit log");
 
     // 5. Create product
     let product_input = CreateProduct {
-        name: "Pro License".to_string(),
-        tier: "pro".to_string(),
         license_exp_days: Some(365),
         updates_exp_days: Some(365),
-        activation_limit: None, // unlimited
         device_limit: Some(5),
-        device_inactive_days: None,
         features: vec![
             "advanced-export".to_string(),
             "cloud-sync".to_string(),
             "priority-support".to_string(),
         ],
+        entitlements: Default::default(),
         price_cents: Some(4999),
         currency: Some("usd".to_string()),
+        ..fixtures::product_input("Pro License", "pro")
     };
     let product = queries::create_product(&conn, &project.id, &product_input)
         .expect("Failed to create dev product");
@@ -322,6 +411,7 @@ fn seed_dev_data(state: &AppState) {
             .project(project.name.clone()),
         None, // auth_type (system action)
         None, // auth_credential
+        None, // request_id
     )
     .expect("Failed to create audit log");
 
@@ -348,7 +438,7 @@ fn seed_dev_data(state: &AppState) {
     println!("  org_id: {}", org.id);
     println!("  project_id: {}", project.id);
     println!("  product_id: {}", product.id);
-    println!("  project_pub_key: {}", public_key);
+    println!("  project_pub_key: {}", project.public_key);
     println!("  user_id: {}", operator_user.id);
     println!();
     println!("──────────────────────────────────────────────────────────────────");
@@ -383,8 +473,55 @@ fn seed_dev_data(state: &AppState) {
     println!();
 }
 
+/// Seeds the demo dataset (see `fixtures::demo`) and prints what was created.
+/// Exits if the database already has organizations, unless `force` is set.
+fn seed_demo_data(state: &AppState, seed: u64, force: bool) {
+    let conn = state
+        .db
+        .get()
+        .expect("Failed to get db connection for seeding");
+    let org_count = queries::count_organizations(&conn).expect("Failed to count organizations");
+    drop(conn);
+    if org_count > 0 && !force {
+        eprintln!(
+            "ERROR: database already has {} organization(s); pass --force to seed the demo dataset anyway",
+            org_count
+        );
+        std::process::exit(1);
+    }
+
+    let demo = fixtures::demo::seed_demo(state, seed).unwrap_or_else(|e| {
+        eprintln!("ERROR: failed to seed demo dataset: {}", e);
+        std::process::exit(1);
+    });
+
+    println!();
+    println!("══════════════════════════════════════════════════════════════════");
+    println!("                    DEMO DATASET READY (seed {})", seed);
+    println!("══════════════════════════════════════════════════════════════════");
+    println!();
+    println!("  operator_api_key: {}", demo.operator_api_key);
+    println!("  org_member_api_key: {}", demo.member_api_key);
+    println!("  org_id: {}", demo.org.id);
+    for project in &demo.projects {
+        println!(
+            "  project: {} ({}, public key {})",
+            project.name, project.id, project.public_key
+        );
+    }
+    println!();
+    println!(
+        "  {} products, {} licenses, {} devices, {} payment sessions",
+        demo.products, demo.licenses, demo.devices, demo.payment_sessions
+    );
+    println!("══════════════════════════════════════════════════════════════════");
+    println!();
+}
+
 /// Rotate the master encryption key.
-/// Decrypts all project private keys with the old key and re-encrypts with the new key.
+/// Re-wraps org data keys with the new key; the secrets under them don't change.
+/// Secrets of orgs without a data key, and everything else under the master key
+/// itself, are decrypted with the old key and re-encrypted with the new key.
 /// Uses a transaction to ensure all-or-nothing semantics.
 fn rotate_master_key(
     db_path: &str,
@@ -395,44 +532,61 @@ fn rotate_master_key(
 
     let mut conn =
         Connection::open(db_path).map_err(|e| format!("Failed to open database: {}", e))?;
+    init_db(&conn).map_err(|e| format!("Failed to initialize database: {}", e))?;
 
     // Start transaction - any error will cause automatic rollback when conn is dropped
     let tx = conn
         .transaction()
         .map_err(|e| format!("Failed to start transaction: {}", e))?;
 
-    // Get all projects
-    let projects =
-        queries::list_all_projects(&tx).map_err(|e| format!("Failed to list projects: {}", e))?;
-
-    if projects.is_empty() {
-        println!("No projects found. Nothing to rotate.");
-        return Ok(());
+    // Orgs with their own data key only need it re-wrapped
+    let rewrapped = queries::rewrap_org_data_keys(&tx, old_key, new_key)
+        .map_err(|e| format!("Failed to re-wrap org data keys: {}", e))?;
+    for org_id in &rewrapped {
+        println!("  [OK] Org data key: {}", org_id);
+    }
+    let keyed_orgs: HashSet<String> = rewrapped.into_iter().collect();
+
+    let project_count = rotate_project_keys(&tx, old_key, new_key, &keyed_orgs)?;
+
+    // Dedicated org databases (data residency mode) hold their own projects.
+    // Each gets its own transaction, committed only after the shared database commits.
+    let org_databases = queries::list_org_databases(&tx)
+        .map_err(|e| format!("Failed to list org databases: {}", e))?;
+    let org_conns = org_databases
+        .iter()
+        .map(|(org_id, path)| {
+            Connection::open(path)
+                .map(|c| (org_id, c))
+                .map_err(|e| format!("Failed to open org database {}: {}", path, e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut org_txs = Vec::with_capacity(org_conns.len());
+    let mut org_project_count = 0;
+    for (org_id, org_conn) in &org_conns {
+        let org_tx = org_conn
+            .unchecked_transaction()
+            .map_err(|e| format!("Failed to start transaction for org {}: {}", org_id, e))?;
+        println!("Org database: {}", org_id);
+        org_project_count += rotate_project_keys(&org_tx, old_key, new_key, &keyed_orgs)?;
+        org_txs.push(org_tx);
     }
 
-    println!("Found {} project(s) to rotate.", projects.len());
-
-    for project in &projects {
-        // Decrypt with old key
-        let plaintext = old_key
-            .decrypt_private_key(&project.id, &project.private_key)
-            .map_err(|e| format!("Failed to decrypt project {}: {}", project.id, e))?;
-
-        // Re-encrypt with new key
-        let new_ciphertext = new_key
-            .encrypt_private_key(&project.id, &plaintext)
-            .map_err(|e| format!("Failed to re-encrypt project {}: {}", project.id, e))?;
-
-        // Update private key in database
-        queries::update_project_private_key(&tx, &project.id, &new_ciphertext)
-            .map_err(|e| format!("Failed to update project {} in database: {}", project.id, e))?;
+    // Encrypted user emails (PII minimization)
+    let user_email_count = queries::rotate_user_emails(&tx, old_key, new_key)
+        .map_err(|e| format!("Failed to rotate user emails: {}", e))?;
 
-        println!("  [OK] Project: {} ({})", project.name, project.id);
+    if keyed_orgs.len() + project_count + org_project_count + user_email_count == 0 {
+        println!("No org data keys, projects or encrypted user emails found. Nothing to rotate.");
+        return Ok(());
     }
 
-    // Rotate organization service configs (stripe, lemonsqueezy, resend)
-    let service_configs = queries::list_all_org_service_configs(&tx)
-        .map_err(|e| format!("Failed to list org service configs: {}", e))?;
+    // Rotate service configs (stripe, lemonsqueezy, resend) of orgs without a data key
+    let service_configs: Vec<_> = queries::list_all_org_service_configs(&tx)
+        .map_err(|e| format!("Failed to list org service configs: {}", e))?
+        .into_iter()
+        .filter(|config| !keyed_orgs.contains(&config.org_id))
+        .collect();
 
     if !service_configs.is_empty() {
         println!();
@@ -507,9 +661,18 @@ fn rotate_master_key(
         println!("  [OK] Email HMAC key");
     }
 
+    // The startup check expects the sentinel under the new key
+    startup::store_key_sentinel(&tx, new_key)
+        .map_err(|e| format!("Failed to update master key sentinel: {}", e))?;
+
     // Commit the transaction
     tx.commit()
         .map_err(|e| format!("Failed to commit transaction: {}", e))?;
+    for org_tx in org_txs {
+        org_tx
+            .commit()
+            .map_err(|e| format!("Failed to commit org database transaction: {}", e))?;
+    }
 
     // Track if email HMAC key was rotated
     let email_key_rotated = queries::get_system_config(&conn, EmailHasher::CONFIG_KEY)
@@ -518,13 +681,22 @@ fn rotate_master_key(
 
     println!();
     println!("SUCCESS: All keys rotated to new master key.");
-    println!("  {} project(s)", projects.len());
+    if !keyed_orgs.is_empty() {
+        println!("  {} org data key(s)", keyed_orgs.len());
+    }
+    println!("  {} project(s)", project_count + org_project_count);
+    if !org_databases.is_empty() {
+        println!("  {} dedicated org database(s)", org_databases.len());
+    }
     if !service_configs.is_empty() {
         println!(
             "  {} organization service config(s)",
             service_configs.len()
         );
     }
+    if user_email_count > 0 {
+        println!("  {} encrypted user email(s)", user_email_count);
+    }
     if email_key_rotated {
         println!("  1 email HMAC key");
     }
@@ -537,12 +709,192 @@ fn rotate_master_key(
     Ok(())
 }
 
+/// Re-encrypt the project private keys, email webhook secrets, and prepaid
+/// codes awaiting download in one database, skipping projects of `keyed_orgs`
+/// (they're under their org's data key).
+/// Returns the number of projects rotated.
+fn rotate_project_keys(
+    conn: &rusqlite::Connection,
+    old_key: &MasterKey,
+    new_key: &MasterKey,
+    keyed_orgs: &HashSet<String>,
+) -> Result<usize, String> {
+    let projects: Vec<_> = queries::list_all_projects(conn)
+        .map_err(|e| format!("Failed to list projects: {}", e))?
+        .into_iter()
+        .filter(|project| !keyed_orgs.contains(&project.org_id))
+        .collect();
+
+    if projects.is_empty() {
+        return Ok(0);
+    }
+
+    println!("Found {} project(s) to rotate.", projects.len());
+
+    for project in &projects {
+        // Decrypt with old key
+        let plaintext = old_key
+            .decrypt_private_key(&project.id, &project.private_key)
+            .map_err(|e| format!("Failed to decrypt project {}: {}", project.id, e))?;
+
+        // Re-encrypt with new key
+        let new_ciphertext = new_key
+            .encrypt_private_key(&project.id, &plaintext)
+            .map_err(|e| format!("Failed to re-encrypt project {}: {}", project.id, e))?;
+
+        // Update private key in database
+        queries::update_project_private_key(conn, &project.id, &new_ciphertext)
+            .map_err(|e| format!("Failed to update project {} in database: {}", project.id, e))?;
+
+        // The email webhook signing secret is under the same key
+        if let Some(ref encrypted) = project.email_webhook_secret {
+            let secret = old_key
+                .decrypt_webhook_secret(&project.id, encrypted)
+                .map_err(|e| {
+                    format!(
+                        "Failed to decrypt webhook secret of project {}: {}",
+                        project.id, e
+                    )
+                })?;
+            let new_ciphertext = new_key
+                .encrypt_webhook_secret(&project.id, &secret)
+                .map_err(|e| {
+                    format!(
+                        "Failed to re-encrypt webhook secret of project {}: {}",
+                        project.id, e
+                    )
+                })?;
+            queries::update_project_webhook_secret(conn, &project.id, &new_ciphertext).map_err(
+                |e| {
+                    format!(
+                        "Failed to update webhook secret of project {} in database: {}",
+                        project.id, e
+                    )
+                },
+            )?;
+        }
+
+        // So are the codes of prepaid batches not downloaded yet
+        let pending = queries::list_pending_prepaid_codes(conn, &project.id)
+            .map_err(|e| format!("Failed to list prepaid code batches: {}", e))?;
+        for (batch_id, encrypted) in &pending {
+            let plaintext = old_key
+                .decrypt_private_key(batch_id, encrypted)
+                .map_err(|e| format!("Failed to decrypt prepaid codes {}: {}", batch_id, e))?;
+            let new_ciphertext = new_key
+                .encrypt_private_key(batch_id, &plaintext)
+                .map_err(|e| format!("Failed to re-encrypt prepaid codes {}: {}", batch_id, e))?;
+            queries::update_pending_prepaid_codes(conn, batch_id, &new_ciphertext)
+                .map_err(|e| format!("Failed to update prepaid codes {}: {}", batch_id, e))?;
+        }
+
+        println!("  [OK] Project: {} ({})", project.name, project.id);
+    }
+
+    Ok(projects.len())
+}
+
+/// Give each org without a data key its own, re-encrypting its service configs
+/// and project secrets under it. Orgs that already have one are skipped,
+/// so this can be re-run. Uses a transaction per database, like key rotation.
+fn migrate_org_keys(db_path: &str, master_key: &MasterKey) -> Result<(), String> {
+    use rusqlite::Connection;
+
+    let mut conn =
+        Connection::open(db_path).map_err(|e| format!("Failed to open database: {}", e))?;
+    init_db(&conn).map_err(|e| format!("Failed to initialize database: {}", e))?;
+
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+    let org_ids = queries::list_orgs_without_data_key(&tx)
+        .map_err(|e| format!("Failed to list organizations: {}", e))?;
+    if org_ids.is_empty() {
+        println!("Every organization has its own data key. Nothing to migrate.");
+        return Ok(());
+    }
+
+    // Orgs with a dedicated database (data residency mode) keep their projects there
+    let org_conns = queries::list_org_databases(&tx)
+        .map_err(|e| format!("Failed to list org databases: {}", e))?
+        .into_iter()
+        .filter(|(org_id, _)| org_ids.contains(org_id))
+        .map(|(org_id, path)| {
+            Connection::open(&path)
+                .map(|c| (org_id, c))
+                .map_err(|e| format!("Failed to open org database {}: {}", path, e))
+        })
+        .collect::<Result<HashMap<_, _>, _>>()?;
+
+    let mut org_txs = Vec::with_capacity(org_conns.len());
+    let mut migrated = 0;
+    for org_id in &org_ids {
+        let result = match org_conns.get(org_id) {
+            Some(org_conn) => {
+                let org_tx = org_conn.unchecked_transaction().map_err(|e| {
+                    format!("Failed to start transaction for org {}: {}", org_id, e)
+                })?;
+                let result = queries::migrate_org_data_key(&tx, &org_tx, org_id, master_key);
+                org_txs.push(org_tx);
+                result
+            }
+            None => queries::migrate_org_data_key(&tx, &tx, org_id, master_key),
+        };
+        if result.map_err(|e| format!("Failed to migrate org {}: {}", org_id, e))? {
+            migrated += 1;
+            println!("  [OK] Organization: {}", org_id);
+        }
+    }
+
+    tx.commit()
+        .map_err(|e| format!("Failed to commit transaction: {}", e))?;
+    for org_tx in org_txs {
+        org_tx
+            .commit()
+            .map_err(|e| format!("Failed to commit org database transaction: {}", e))?;
+    }
+
+    println!();
+    println!(
+        "SUCCESS: {} organization(s) moved to their own data key.",
+        migrated
+    );
+
+    Ok(())
+}
+
+/// Move an organization's tenant data from the shared database into a dedicated file.
+fn isolate_org(db_path: &str, data_dir: &str, org_id: &str) -> Result<(), String> {
+    let mut conn = rusqlite::Connection::open(db_path)
+        .map_err(|e| format!("Failed to open database: {}", e))?;
+    init_db(&conn).map_err(|e| format!("Failed to initialize database: {}", e))?;
+
+    let registry = OrgDbRegistry::new(data_dir, db_path);
+    let result = registry
+        .isolate_org(&mut conn, org_id)
+        .map_err(|e| e.to_string())?;
+
+    for (table, count) in &result.tables {
+        println!("  [OK] {}: {} row(s)", table, count);
+    }
+    println!();
+    println!("SUCCESS: Organization {} moved to {}", org_id, result.db_path);
+    println!("Restart the server to pick up the new database.");
+
+    Ok(())
+}
+
 /// Spawns a background task that periodically runs maintenance routines.
 /// Different routines run at offset intervals to spread the load:
 /// - Activation codes: every 5 minutes (every tick)
 /// - Rate limiter: every 5 minutes (every tick)
+/// - Scheduled member removals: every 5 minutes (every tick)
+/// - Storage heartbeats and health alerts: every 5 minutes (every tick)
+/// - Dormant licenses: every hour, offset by 5 min (iteration % 12 == 1)
 /// - Webhook events: every hour, offset by 15 min (iteration % 12 == 3)
 /// - Payment sessions: every hour, offset by 30 min (iteration % 12 == 6)
+/// - Backups beyond BACKUP_RETENTION_COUNT: every hour, offset by 45 min (iteration % 12 == 9)
 fn spawn_cleanup_task(
     state: AppState,
     webhook_event_retention_days: i64,
@@ -557,24 +909,70 @@ fn spawn_cleanup_task(
             iteration += 1;
 
             // Clean up expired activation codes (every tick = 5 min)
-            match state.db.get() {
-                Ok(conn) => match queries::cleanup_expired_activation_codes(&conn) {
-                    Ok(count) => {
-                        if count > 0 {
-                            tracing::debug!("Cleaned up {} expired activation codes", count);
+            for pool in state.tenant_pools() {
+                match pool.get() {
+                    Ok(conn) => match queries::cleanup_expired_activation_codes(&conn) {
+                        Ok(count) => {
+                            if count > 0 {
+                                tracing::debug!("Cleaned up {} expired activation codes", count);
+                            }
                         }
-                    }
+                        Err(e) => {
+                            tracing::warn!("Failed to cleanup activation codes: {}", e);
+                        }
+                    },
                     Err(e) => {
-                        tracing::warn!("Failed to cleanup activation codes: {}", e);
+                        tracing::warn!("Failed to get db connection for cleanup: {}", e);
                     }
-                },
-                Err(e) => {
-                    tracing::warn!("Failed to get db connection for cleanup: {}", e);
                 }
             }
 
             // Clean up rate limiter expired entries (every tick = 5 min)
             state.activation_rate_limiter.cleanup();
+            state.free_license_limiter.cleanup();
+            state.session_wait_limiter.cleanup();
+            state.validation_limiter.cleanup(state.clock.now());
+            state.project_misses.cleanup();
+
+            // Storage heartbeats, then alert on any storage problem (every tick = 5 min)
+            match storage_health::heartbeat(&state) {
+                Ok(health) => health.log_problems(),
+                Err(e) => tracing::error!("Failed to write storage heartbeats: {}", e),
+            }
+
+            // Remove org members whose grace period has ended (every tick = 5 min)
+            match handlers::orgs::process_scheduled_member_removals(
+                &state,
+                chrono::Utc::now().timestamp(),
+            ) {
+                Ok(count) => {
+                    if count > 0 {
+                        tracing::info!("Removed {} org members after their grace period", count);
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to process scheduled member removals: {}", e);
+                }
+            }
+
+            // Mark never-activated licenses dormant (every 12 ticks = 1 hour, offset by 1 tick = 5 min)
+            if iteration % 12 == 1 {
+                match handlers::orgs::process_dormant_licenses(
+                    &state,
+                    chrono::Utc::now().timestamp(),
+                )
+                .await
+                {
+                    Ok(count) => {
+                        if count > 0 {
+                            tracing::info!("Marked {} licenses dormant", count);
+                        }
+                    }
+                    Err(e) => {
+                        tracing::warn!("Failed to process dormant licenses: {}", e);
+                    }
+                }
+            }
 
             // Clean up old webhook events (every 12 ticks = 1 hour, offset by 3 ticks = 15 min)
             // Only runs if retention is configured (> 0)
@@ -606,27 +1004,110 @@ fn spawn_cleanup_task(
             // Clean up old incomplete payment sessions (every 12 ticks = 1 hour, offset by 6 ticks = 30 min)
             // Only runs if retention is configured (> 0)
             if payment_session_retention_days > 0 && iteration % 12 == 6 {
-                match state.db.get() {
-                    Ok(conn) => match queries::purge_old_payment_sessions(
-                        &conn,
-                        payment_session_retention_days,
-                    ) {
-                        Ok(count) => {
-                            if count > 0 {
-                                tracing::info!(
-                                    "Purged {} abandoned payment sessions older than {} days",
-                                    count,
-                                    payment_session_retention_days
-                                );
+                for pool in state.tenant_pools() {
+                    match pool.get() {
+                        Ok(conn) => match queries::purge_old_payment_sessions(
+                            &conn,
+                            payment_session_retention_days,
+                        ) {
+                            Ok(count) => {
+                                if count > 0 {
+                                    tracing::info!(
+                                        "Purged {} abandoned payment sessions older than {} days",
+                                        count,
+                                        payment_session_retention_days
+                                    );
+                                }
                             }
-                        }
+                            Err(e) => {
+                                tracing::warn!("Failed to purge old payment sessions: {}", e);
+                            }
+                        },
                         Err(e) => {
-                            tracing::warn!("Failed to purge old payment sessions: {}", e);
+                            tracing::warn!(
+                                "Failed to get db connection for payment session cleanup: {}",
+                                e
+                            );
                         }
-                    },
-                    Err(e) => {
+                    }
+                }
+            }
+
+            // Prune old backups (every 12 ticks = 1 hour, offset by 9 ticks = 45 min)
+            // Only runs if backups are configured
+            if let Some(backups) = state.backups.clone()
+                && iteration % 12 == 9
+            {
+                let pool = state.db.clone();
+                let result = tokio::task::spawn_blocking(move || {
+                    let conn = pool.get()?;
+                    backups.prune(&conn)
+                })
+                .await;
+                match result {
+                    Ok(Ok(count)) => {
+                        if count > 0 {
+                            tracing::info!("Pruned {} backups beyond the retention count", count);
+                        }
+                    }
+                    Ok(Err(e)) => tracing::warn!("Failed to prune old backups: {}", e),
+                    Err(e) => tracing::warn!("Backup pruning task failed: {}", e),
+                }
+            }
+        }
+    });
+
+    tracing::info!(
+        "Background maintenance task started (activation codes: 5min, hourly: dormant licenses, webhook events, payment sessions, backups)"
+    );
+}
+
+/// Spawns a background task that relays audit outbox entries handlers couldn't
+/// relay right after committing (audit database busy or unavailable).
+fn spawn_audit_relay_task(state: AppState) {
+    tokio::spawn(async move {
+        let interval = Duration::from_secs(30);
+        loop {
+            tokio::time::sleep(interval).await;
+            match outbox::relay_all(&state) {
+                Ok(count) => {
+                    if count > 0 {
+                        tracing::info!("Relayed {} audit log entries from the outbox", count);
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to relay audit outbox: {}", e);
+                }
+            }
+        }
+    });
+}
+
+/// Spawns a background task that runs report-only subscription reconciliation
+/// for every org and configured payment provider, one org at a time.
+fn spawn_reconciliation_task(state: AppState, interval_hours: u64) {
+    tokio::spawn(async move {
+        let interval = Duration::from_secs(interval_hours * 60 * 60);
+        loop {
+            tokio::time::sleep(interval).await;
+            let orgs = match state.db.get() {
+                Ok(conn) => queries::list_organizations(&conn),
+                Err(e) => Err(e.into()),
+            };
+            let orgs = match orgs {
+                Ok(orgs) => orgs,
+                Err(e) => {
+                    tracing::warn!("Failed to list organizations for reconciliation: {}", e);
+                    continue;
+                }
+            };
+            for org in orgs {
+                for provider in [PaymentProvider::Stripe, PaymentProvider::LemonSqueezy] {
+                    if let Err(e) = reconcile_org(&state, &org.id, provider).await {
                         tracing::warn!(
-                            "Failed to get db connection for payment session cleanup: {}",
+                            org_id = %org.id,
+                            "Failed to reconcile {} subscriptions: {}",
+                            provider.as_ref(),
                             e
                         );
                     }
@@ -636,10 +1117,96 @@ fn spawn_cleanup_task(
     });
 
     tracing::info!(
-        "Background maintenance task started (activation codes: 5min, hourly: webhook events, payment sessions)"
+        "Scheduled subscription reconciliation started (every {}h, report only)",
+        interval_hours
     );
 }
 
+/// One scheduled, report-only reconciliation run (skipped if the org hasn't
+/// configured `provider`).
+async fn reconcile_org(
+    state: &AppState,
+    org_id: &str,
+    provider: PaymentProvider,
+) -> paycheck::error::Result<()> {
+    let client = {
+        let conn = state.db.get()?;
+        SubscriptionClient::for_org(&conn, org_id, provider, &state.master_key)?
+    };
+    let Some(client) = client else {
+        return Ok(());
+    };
+    let options = RunOptions {
+        apply: false,
+        started_by: None,
+        pace: client.pace(),
+    };
+    let run = reconcile::run(state, org_id, &client, &options).await?;
+    if !run.discrepancies.is_empty() {
+        tracing::warn!(
+            org_id,
+            run_id = %run.id,
+            "Reconciliation found {} of {} {} licenses out of sync",
+            run.discrepancies.len(),
+            run.checked,
+            run.provider
+        );
+    }
+    Ok(())
+}
+
+/// Load the email HMAC key from system_config, generating and storing one on first run.
+fn init_email_hasher(conn: &rusqlite::Connection, master_key: &MasterKey) -> EmailHasher {
+    // Try to load existing encrypted HMAC key
+    match queries::get_system_config(conn, EmailHasher::CONFIG_KEY) {
+        Ok(Some(encrypted)) => {
+            // Decrypt the HMAC key using the master key
+            // We use a fixed entity ID for system config encryption
+            let hmac_key_bytes = master_key
+                .decrypt_private_key("system-config", &encrypted)
+                .expect(
+                    "Failed to decrypt email HMAC key - was master key rotated without migration?",
+                );
+
+            if hmac_key_bytes.len() != 32 {
+                panic!(
+                    "Invalid email HMAC key length: expected 32, got {}",
+                    hmac_key_bytes.len()
+                );
+            }
+
+            let mut key = [0u8; 32];
+            key.copy_from_slice(&hmac_key_bytes);
+            tracing::debug!("Loaded existing email HMAC key from database");
+            EmailHasher::from_bytes(key)
+        }
+        Ok(None) => {
+            // Generate new HMAC key, encrypt, and store
+            let hmac_key = EmailHasher::generate_key();
+            let encrypted = master_key
+                .encrypt_private_key("system-config", &hmac_key)
+                .expect("Failed to encrypt email HMAC key");
+
+            queries::set_system_config(conn, EmailHasher::CONFIG_KEY, &encrypted)
+                .expect("Failed to store email HMAC key");
+
+            tracing::info!("Generated and stored new email HMAC key");
+            EmailHasher::from_bytes(hmac_key)
+        }
+        Err(e) => {
+            panic!("Failed to check for existing email HMAC key: {}", e);
+        }
+    }
+}
+
+/// Raw settings for CLI commands that run without a master key
+fn load_config_source() -> ConfigSource {
+    ConfigSource::load().unwrap_or_else(|errors| {
+        eprintln!("ERROR: {}", errors.join("\n"));
+        std::process::exit(1);
+    })
+}
+
 #[tokio::main]
 async fn main() {
     // Parse CLI arguments
@@ -678,9 +1245,8 @@ async fn main() {
 
         println!();
 
-        // Get database path from env or default
-        dotenvy::dotenv().ok();
-        let db_path = std::env::var("DATABASE_PATH").unwrap_or_else(|_| "paycheck.db".to_string());
+        // Get database path from config or default
+        let db_path = load_config_source().database_path();
 
         println!("Using database: {}", db_path);
         println!();
@@ -696,6 +1262,67 @@ async fn main() {
         return;
     }
 
+    // Handle org isolation command (before normal startup)
+    if let Some(ref org_id) = cli.isolate_org {
+        let source = load_config_source();
+        let db_path = source.database_path();
+        let Some(data_dir) = source.org_data_dir() else {
+            eprintln!("ERROR: --isolate-org requires PAYCHECK_ORG_DATA_DIR");
+            std::process::exit(1);
+        };
+
+        println!("Org Isolation");
+        println!("=============");
+        println!();
+        println!("Using database: {}", db_path);
+        println!("Org data directory: {}", data_dir);
+        println!();
+
+        if let Err(e) = isolate_org(&db_path, &data_dir, org_id) {
+            eprintln!();
+            eprintln!("ERROR: {}", e);
+            std::process::exit(1);
+        }
+
+        return;
+    }
+
+    // Handle backup restore command (before normal startup)
+    if let Some(ref backup_id) = cli.restore_backup {
+        let config = Config::from_env();
+        let backups = match Backups::from_config(&config) {
+            Ok(Some(backups)) => backups,
+            Ok(None) => {
+                eprintln!("ERROR: --restore-backup requires PAYCHECK_BACKUP_DIR");
+                std::process::exit(1);
+            }
+            Err(e) => {
+                eprintln!("ERROR: {}", e);
+                std::process::exit(1);
+            }
+        };
+
+        println!("Backup Restore");
+        println!("==============");
+        println!();
+        println!("Restoring backup: {}", backup_id);
+        println!("Main database: {}", config.database_path);
+        println!("Audit database: {}", config.audit_database_path);
+        println!();
+
+        if let Err(e) = backups.restore(
+            backup_id,
+            std::path::Path::new(&config.database_path),
+            std::path::Path::new(&config.audit_database_path),
+        ) {
+            eprintln!("ERROR: {}", e);
+            std::process::exit(1);
+        }
+
+        println!("Restored. Start the server normally to use the restored databases.");
+        return;
+    }
+
     // Initialize tracing
     tracing_subscriber::registry()
         .with(
@@ -757,11 +1384,30 @@ async fn main() {
         init_audit_db(&conn).expect("Failed to initialize audit database");
     }
 
+    // Open dedicated org databases (data residency mode)
+    let org_dbs = match config.org_data_dir {
+        Some(ref dir) => {
+            let registry = OrgDbRegistry::new(dir, &config.database_path);
+            let conn = db_pool.get().expect("Failed to get connection");
+            let count = registry
+                .load(&conn, config.migration_backup_count)
+                .expect("Failed to open org databases");
+            tracing::info!(
+                "Data residency enabled: {} dedicated org database(s) in {}",
+                count,
+                dir
+            );
+            registry
+        }
+        None => OrgDbRegistry::disabled(),
+    };
+
     // Initialize email service with system-level Resend API key
     let email_service = EmailService::new(
         config.resend_api_key.clone(),
         config.default_from_email.clone(),
-    );
+    )
+    .with_localhost_urls(config.allow_localhost_urls);
 
     // Initialize JWKS cache for first-party JWT authentication
     let jwks_cache = Arc::new(JwksCache::new());
@@ -778,114 +1424,208 @@ async fn main() {
         );
     }
 
-    // Initialize email hasher (stable HMAC key stored encrypted in DB)
+    // Initialize email hasher (stable HMAC key stored encrypted in DB).
+    // The self-check checks the master key again, but the hasher needs it first.
     let email_hasher = {
         let conn = db_pool
             .get()
             .expect("Failed to get connection for email hasher init");
+        if let Err(e) = startup::check_master_key(&conn, &config.master_key) {
+            eprintln!("ERROR: {}", e);
+            std::process::exit(1);
+        }
+        init_email_hasher(&conn, &config.master_key)
+    };
 
-        // Try to load existing encrypted HMAC key
-        match queries::get_system_config(&conn, EmailHasher::CONFIG_KEY) {
-            Ok(Some(encrypted)) => {
-                // Decrypt the HMAC key using the master key
-                // We use a fixed entity ID for system config encryption
-                let hmac_key_bytes = config
-                    .master_key
-                    .decrypt_private_key("system-config", &encrypted)
-                    .expect("Failed to decrypt email HMAC key - was master key rotated without migration?");
-
-                if hmac_key_bytes.len() != 32 {
-                    panic!(
-                        "Invalid email HMAC key length: expected 32, got {}",
-                        hmac_key_bytes.len()
-                    );
-                }
-
-                let mut key = [0u8; 32];
-                key.copy_from_slice(&hmac_key_bytes);
-                tracing::debug!("Loaded existing email HMAC key from database");
-                EmailHasher::from_bytes(key)
-            }
-            Ok(None) => {
-                // Generate new HMAC key, encrypt, and store
-                let hmac_key = EmailHasher::generate_key();
-                let encrypted = config
-                    .master_key
-                    .encrypt_private_key("system-config", &hmac_key)
-                    .expect("Failed to encrypt email HMAC key");
-
-                queries::set_system_config(&conn, EmailHasher::CONFIG_KEY, &encrypted)
-                    .expect("Failed to store email HMAC key");
-
-                tracing::info!("Generated and stored new email HMAC key");
-                EmailHasher::from_bytes(hmac_key)
-            }
-            Err(e) => {
-                panic!("Failed to check for existing email HMAC key: {}", e);
+    let backups = Backups::from_config(&config)
+        .expect("Failed to open backup directory")
+        .map(Arc::new);
+    if let Some(ref dir) = config.backup_dir {
+        tracing::info!(
+            "Backups enabled in {} (encrypted with the {}, keeping {})",
+            dir,
+            if config.backup_key.is_some() {
+                "backup key"
+            } else {
+                "master key"
+            },
+            match config.backup_retention_count {
+                count if count > 0 => count.to_string(),
+                _ => "all".to_string(),
             }
-        }
+        );
+    }
+
+    // Restore maintenance mode if it was left on before a restart
+    let maintenance = {
+        let conn = db_pool
+            .get()
+            .expect("Failed to get connection for maintenance mode");
+        MaintenanceMode::load(&conn).expect("Failed to load maintenance mode")
     };
+    if let Some(settings) = maintenance.current() {
+        tracing::warn!(
+            "Maintenance mode is on (enabled by {}, reads {}): {}",
+            settings.enabled_by,
+            if settings.allow_reads {
+                "allowed"
+            } else {
+                "blocked"
+            },
+            settings.message
+        );
+    }
 
     let state = AppState {
         db: db_pool,
         audit: audit_pool,
         base_url: config.base_url.clone(),
         audit_log_enabled: config.audit_log_enabled,
+        audit_failure_mode: config.audit_failure_mode,
+        public_audit_retention_days: config.public_audit_log_retention_days,
         master_key: config.master_key.clone(),
         email_hasher,
+        pii_minimization: config.pii_minimization,
         success_page_url: config.success_page_url.clone(),
         activation_rate_limiter: Arc::new(ActivationRateLimiter::default()),
         email_service: Arc::new(email_service),
         jwks_cache,
         trusted_issuers: config.trusted_issuers.clone(),
+        org_dbs: Arc::new(org_dbs),
+        project_misses: Arc::new(ProjectMissCache::default()),
+        clock: Clock::system(),
+        provider_calls: Arc::new(ProviderCallGovernor::new(config.provider_calls)),
+        validation_limiter: Arc::new(ValidationRateLimiter::new()),
+        free_license_limiter: Arc::new(ActivationRateLimiter::for_free_licenses()),
+        session_wait_limiter: Arc::new(ActivationRateLimiter::for_session_waits()),
+        allow_localhost_urls: config.allow_localhost_urls,
+        maintenance: Arc::new(maintenance),
+        backups,
     };
 
-    // Purge old public audit logs on startup (0 = never purge)
-    // Only public (end-user) logs are purged; internal actions are kept forever.
-    if config.public_audit_log_retention_days > 0 {
+    // Check configuration and encrypted data before doing anything with them
+    let report = startup::self_check(&state).await;
+    report.log();
+    if !report.passed() {
+        eprintln!(
+            "ERROR: Startup self-check failed ({} problem(s), see above)",
+            report.failures.len()
+        );
+        std::process::exit(1);
+    }
+    if cli.check {
+        println!("Self-check passed ({} warning(s)).", report.warnings.len());
+        return;
+    }
+
+    // Handle email encryption command (needs the master key and email HMAC key)
+    if cli.encrypt_user_emails {
+        let mut conn = state.db.get().expect("Failed to get connection");
+        match queries::encrypt_user_emails(&mut conn, &state.emails()) {
+            Ok(count) => println!("Encrypted {} user email(s).", count),
+            Err(e) => {
+                eprintln!("ERROR: {}", e);
+                std::process::exit(1);
+            }
+        }
+        if !config.pii_minimization {
+            println!("Set PII_MINIMIZATION=true so new users are stored encrypted as well.");
+        }
+        return;
+    }
+
+    // Handle org data key migration (needs the master key)
+    if cli.migrate_org_keys {
+        if let Err(e) = migrate_org_keys(&config.database_path, &config.master_key) {
+            eprintln!();
+            eprintln!("ERROR: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // Hash emails of users created before the email_hash column existed
+    {
+        let conn = state
+            .db
+            .get()
+            .expect("Failed to get connection for email hash backfill");
+        match queries::backfill_user_email_hashes(&conn, &state.emails()) {
+            Ok(0) => {}
+            Ok(count) => tracing::info!("Backfilled email hashes for {} user(s)", count),
+            Err(e) => tracing::warn!("Failed to backfill user email hashes: {}", e),
+        }
+    }
+
+    // Purge old audit logs on startup. Public (end-user) logs follow each org's
+    // public_audit_retention_days, falling back to PUBLIC_AUDIT_LOG_RETENTION_DAYS
+    // (0 = never purge); internal actions are only purged for orgs that set
+    // admin_audit_retention_days.
+    {
+        let retention = state
+            .db
+            .get()
+            .map_err(Into::into)
+            .and_then(|conn| queries::list_org_audit_retention(&conn));
         let conn = state
             .audit
             .get()
             .expect("Failed to get audit connection for purge");
-        match queries::purge_old_public_audit_logs(&conn, config.public_audit_log_retention_days) {
+        match retention.and_then(|orgs| {
+            queries::purge_old_audit_logs(&conn, config.public_audit_log_retention_days, &orgs)
+        }) {
             Ok(count) if count > 0 => {
-                tracing::info!(
-                    "Purged {} public audit log entries older than {} days",
-                    count,
-                    config.public_audit_log_retention_days
-                );
+                tracing::info!("Purged {} audit log entries past retention", count);
             }
             Ok(_) => {}
             Err(e) => {
-                tracing::warn!("Failed to purge old public audit logs: {}", e);
+                tracing::warn!("Failed to purge old audit logs: {}", e);
             }
         }
     }
 
     // Purge soft-deleted records on startup (0 = never auto-purge)
     // Records can still be manually hard-deleted via operator API.
+    // Dedicated org databases go first: purging an org from the shared database
+    // drops its file registration.
     if config.soft_delete_retention_days > 0 {
+        for pool in state.tenant_pools().into_iter().rev() {
+            let conn = pool
+                .get()
+                .expect("Failed to get db connection for soft delete purge");
+            match queries::purge_soft_deleted_records(&conn, config.soft_delete_retention_days) {
+                Ok(result) if result.total() > 0 => {
+                    tracing::info!(
+                        "Purged {} soft-deleted records older than {} days (users: {}, orgs: {}, members: {}, projects: {}, products: {}, licenses: {})",
+                        result.total(),
+                        config.soft_delete_retention_days,
+                        result.users,
+                        result.organizations,
+                        result.org_members,
+                        result.projects,
+                        result.products,
+                        result.licenses
+                    );
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::warn!("Failed to purge soft-deleted records: {}", e);
+                }
+            }
+        }
+
+        // Remove files for dedicated orgs that were purged
         let conn = state
             .db
             .get()
             .expect("Failed to get db connection for soft delete purge");
-        match queries::purge_soft_deleted_records(&conn, config.soft_delete_retention_days) {
-            Ok(result) if result.total() > 0 => {
-                tracing::info!(
-                    "Purged {} soft-deleted records older than {} days (users: {}, orgs: {}, members: {}, projects: {}, products: {}, licenses: {})",
-                    result.total(),
-                    config.soft_delete_retention_days,
-                    result.users,
-                    result.organizations,
-                    result.org_members,
-                    result.projects,
-                    result.products,
-                    result.licenses
-                );
-            }
-            Ok(_) => {}
-            Err(e) => {
-                tracing::warn!("Failed to purge soft-deleted records: {}", e);
+        let registered = queries::list_org_databases(&conn).unwrap_or_default();
+        for (org_id, _) in state.org_dbs.dedicated_pools() {
+            if !registered.iter().any(|(id, _)| *id == org_id) {
+                match state.org_dbs.release(&org_id) {
+                    Ok(_) => tracing::info!("Removed database for purged organization {}", org_id),
+                    Err(e) => tracing::warn!("Failed to remove purged org database: {}", e),
+                }
             }
         }
     }
@@ -903,11 +1643,46 @@ async fn main() {
         }
     }
 
+    // Seed the demo dataset if --seed-demo is passed (only in dev mode)
+    if cli.seed_demo {
+        if !config.dev_mode {
+            tracing::warn!("--seed-demo flag ignored: not in dev mode (set PAYCHECK_ENV=dev)");
+        } else {
+            seed_demo_data(&state, cli.demo_seed, cli.force);
+        }
+    }
+
     // Bootstrap first operator if configured (fallback for non-seed usage)
     if let Some(ref email) = config.bootstrap_operator_email {
         bootstrap_first_operator(&state, email);
     }
 
+    // Relay audit entries left in the outbox by a crash before serving requests
+    match outbox::relay_all(&state) {
+        Ok(0) => {}
+        Ok(count) => tracing::info!(
+            "Relayed {} pending audit log entries from the outbox",
+            count
+        ),
+        Err(e) => tracing::warn!("Failed to relay audit outbox at startup: {}", e),
+    }
+    spawn_audit_relay_task(state.clone());
+
+    // Jobs running when the server stopped won't resume: report them as failed
+    match bulk_jobs::fail_interrupted(&state) {
+        Ok(0) => {}
+        Ok(count) => tracing::warn!(
+            "Marked {} bulk license job(s) interrupted by the restart as failed",
+            count
+        ),
+        Err(e) => tracing::warn!("Failed to check for interrupted bulk license jobs: {}", e),
+    }
+
+    // The start counts as a heartbeat; the next start's self-check compares against it
+    if let Err(e) = storage_health::record_server_start(&state) {
+        tracing::error!("Failed to record storage heartbeats: {}", e);
+    }
+
     // Start background maintenance task (activation codes, webhook events, payment sessions, rate limiter)
     spawn_cleanup_task(
         state.clone(),
@@ -915,19 +1690,12 @@ async fn main() {
         config.payment_session_retention_days,
     );
 
+    if config.reconcile_interval_hours > 0 {
+        spawn_reconciliation_task(state.clone(), config.reconcile_interval_hours);
+    }
+
     // Build the application router
-    let console_cors = config.console_cors_layer();
-    let app = Router::new()
-        // Public endpoints (no auth, permissive CORS for customer websites)
-        .merge(handlers::public::router(config.rate_limit))
-        // Webhook endpoints (provider-specific auth, no CORS needed - server-to-server)
-        .merge(handlers::webhooks::router())
-        // Operator API (operator key auth, console CORS only)
-        .merge(handlers::operators::router(state.clone()).layer(console_cors.clone()))
-        // Organization API (org member key auth, console CORS only, high rate limit)
-        .merge(handlers::orgs::router(state.clone(), config.rate_limit).layer(console_cors))
-        .layer(TraceLayer::new_for_http())
-        .with_state(state);
+    let app = handlers::app(state, &config);
 
     // Start the server
     // In dev mode, try successive ports if the default is taken
@@ -0,0 +1,67 @@
+//! Host-based project resolution for the public endpoints (see
+//! [`crate::custom_domains`]).
+//!
+//! A request to a verified custom domain is handled as if it carried the
+//! domain's project in `X-Paycheck-Project`, so every public endpoint picks
+//! the project up the way it already reads that header: a `public_key` for
+//! another project is rejected as a mismatch, and so is an
+//! `X-Paycheck-Project` header naming another project. Requests to any other
+//! host pass through untouched.
+
+use axum::{
+    extract::{Request, State},
+    http::HeaderValue,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::custom_domains;
+use crate::db::{AppState, queries};
+use crate::error::{AppError, OptionExt, Result, msg};
+use crate::handlers::public::PROJECT_KEY_HEADER;
+
+/// Public key of the project served at the request's host, if that host is a
+/// verified custom domain.
+fn custom_domain_key(state: &AppState, request: &Request) -> Result<Option<String>> {
+    let Some(hostname) = custom_domains::request_hostname(request.headers(), request.uri()) else {
+        return Ok(None);
+    };
+    let conn = state.db.get()?;
+    let Some(project_id) = queries::get_verified_custom_domain_project_id(&conn, &hostname)? else {
+        return Ok(None);
+    };
+    let conn = state.project_db(&project_id)?.get()?;
+    let project =
+        queries::get_project_by_id(&conn, &project_id)?.or_not_found(msg::PROJECT_NOT_FOUND)?;
+    Ok(Some(project.public_key))
+}
+
+/// Resolve the project of a public request from its `Host` header, when the
+/// host is a verified custom domain.
+pub async fn custom_domain_host(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let public_key = match custom_domain_key(&state, &request) {
+        Ok(Some(public_key)) => public_key,
+        Ok(None) => return next.run(request).await,
+        Err(e) => return e.into_response(),
+    };
+
+    if let Some(sent) = request.headers().get(PROJECT_KEY_HEADER)
+        && sent.as_bytes() != public_key.as_bytes()
+    {
+        return AppError::BadRequest(msg::CUSTOM_DOMAIN_PROJECT_MISMATCH.into()).into_response();
+    }
+    match HeaderValue::from_str(&public_key) {
+        Ok(value) => {
+            request.headers_mut().insert(PROJECT_KEY_HEADER, value);
+        }
+        Err(_) => {
+            return AppError::Internal("Project public key is not a valid header value".into())
+                .into_response();
+        }
+    }
+    next.run(request).await
+}
@@ -0,0 +1,130 @@
+//! Maintenance mode: a switch operators flip with
+//! `POST /operators/maintenance-mode` to quiet the database for backups and
+//! migrations.
+//!
+//! While it's on, [`maintenance_gate`] answers 503 with the operator's
+//! message and `Retry-After` for everything that writes: mutating API calls,
+//! purchases and activations (`/buy` and `/callback` included, though they're
+//! GETs), and payment provider webhooks, which providers retry on 5xx. With
+//! `allow_reads`, GET requests and `/validate` keep working; without it they
+//! get the 503 too. `/health` and the maintenance mode endpoints always pass,
+//! so the switch can be flipped back.
+//!
+//! The setting is stored in `system_config`, so a restart keeps it.
+
+use std::sync::RwLock;
+
+use axum::{
+    extract::{Request, State},
+    http::Method,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::db::{AppState, queries};
+use crate::error::{AppError, Result};
+
+/// Seconds clients are told to wait before retrying.
+pub const MAINTENANCE_RETRY_AFTER_SECS: u64 = 120;
+
+/// Shown to clients when the operator doesn't give a message.
+pub const DEFAULT_MAINTENANCE_MESSAGE: &str = "Paycheck is down for maintenance, try again shortly";
+
+/// `system_config` key the setting is persisted under.
+const CONFIG_KEY: &str = "maintenance_mode";
+
+/// Paths that work in maintenance mode regardless of `allow_reads`.
+const ALWAYS_ALLOWED: &[&str] = &["/health", "/operators/maintenance-mode"];
+
+/// POSTs that only read, served when `allow_reads` is set.
+const READ_ONLY_POSTS: &[&str] = &["/validate"];
+
+/// GETs that write: the checkout redirect and the post-payment callback.
+const WRITING_GETS: &[&str] = &["/buy", "/callback"];
+
+/// Maintenance mode as an operator turned it on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaintenanceSettings {
+    /// Shown to clients in 503 responses
+    pub message: String,
+    /// Keep serving GET requests and `/validate`
+    pub allow_reads: bool,
+    pub enabled_at: i64,
+    /// Operator who turned it on
+    pub enabled_by: String,
+}
+
+/// Current maintenance mode setting, shared by every request.
+#[derive(Debug, Default)]
+pub struct MaintenanceMode {
+    current: RwLock<Option<MaintenanceSettings>>,
+}
+
+impl MaintenanceMode {
+    /// Load the persisted setting (off if none was saved).
+    pub fn load(conn: &Connection) -> Result<Self> {
+        let current = queries::get_system_config(conn, CONFIG_KEY)?
+            .map(|value| serde_json::from_slice(&value))
+            .transpose()?;
+        Ok(Self {
+            current: RwLock::new(current),
+        })
+    }
+
+    /// The active setting, or None when maintenance mode is off.
+    pub fn current(&self) -> Option<MaintenanceSettings> {
+        self.current
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Turn maintenance mode on (`Some`) or off (`None`), persisting the
+    /// change before it takes effect.
+    pub fn set(&self, conn: &Connection, settings: Option<MaintenanceSettings>) -> Result<()> {
+        match &settings {
+            Some(settings) => {
+                queries::set_system_config(conn, CONFIG_KEY, &serde_json::to_vec(settings)?)?
+            }
+            None => {
+                queries::delete_system_config(conn, CONFIG_KEY)?;
+            }
+        }
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = settings;
+        Ok(())
+    }
+}
+
+/// Whether a request may run under the given maintenance settings.
+fn is_allowed(method: &Method, path: &str, settings: &MaintenanceSettings) -> bool {
+    if ALWAYS_ALLOWED.contains(&path) || method == Method::OPTIONS {
+        return true;
+    }
+    let reads = match *method {
+        Method::GET | Method::HEAD => !WRITING_GETS.contains(&path),
+        Method::POST => READ_ONLY_POSTS.contains(&path),
+        _ => false,
+    };
+    reads && settings.allow_reads
+}
+
+/// Refuse requests that maintenance mode holds back. Install outside the
+/// routers so it covers every API.
+pub async fn maintenance_gate(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Some(settings) = state.maintenance.current()
+        && !is_allowed(request.method(), request.uri().path(), &settings)
+    {
+        return AppError::Maintenance {
+            message: settings.message,
+            retry_after_secs: MAINTENANCE_RETRY_AFTER_SECS,
+        }
+        .into_response();
+    }
+    next.run(request).await
+}
@@ -1,8 +1,16 @@
+mod custom_domain;
+mod maintenance;
 mod operator_auth;
 mod org_auth;
+mod request_id;
+mod timestamps;
 
+pub use custom_domain::*;
+pub use maintenance::*;
 pub use operator_auth::*;
 pub use org_auth::*;
+pub use request_id::*;
+pub use timestamps::*;
 
 /// Tracks how a request was authenticated.
 /// Useful for audit logging to distinguish API key vs JWT auth.
@@ -5,7 +5,10 @@ use axum::{
     response::Response,
 };
 
+use rusqlite::Connection;
+
 use crate::db::{AppState, queries};
+use crate::error::{AppError, Result as AppResult, msg};
 use crate::jwt::validate_first_party_token;
 use crate::models::{AuditLogNames, OperatorRole, User};
 use crate::util::extract_bearer_token;
@@ -27,6 +30,16 @@ impl OperatorContext {
             .expect("OperatorContext should only be created for users with operator_role")
     }
 
+    /// Check the operator may act on `org_id`. Partners outside their scopes
+    /// get the same 404 as a missing org, so they can't probe for org IDs.
+    pub fn require_org_access(&self, conn: &Connection, org_id: &str) -> AppResult<()> {
+        if operator_can_manage_org(conn, &self.user, org_id)? {
+            Ok(())
+        } else {
+            Err(AppError::NotFound(msg::ORG_NOT_FOUND.into()))
+        }
+    }
+
     /// Get audit log names pre-populated with the user's name and email.
     /// Chain with `.resource()`, `.org()`, `.project()` to add more context.
     pub fn audit_names(&self) -> AuditLogNames {
@@ -38,6 +51,22 @@ impl OperatorContext {
     }
 }
 
+/// Whether `user` has admin-level operator access to `org_id`: owners and
+/// admins reach every org, partners only the orgs scoped to them.
+pub(crate) fn operator_can_manage_org(
+    conn: &Connection,
+    user: &User,
+    org_id: &str,
+) -> AppResult<bool> {
+    match user.operator_role {
+        Some(role) if role.is_org_scoped() => {
+            queries::operator_has_org_scope(conn, &user.id, org_id)
+        }
+        Some(role) => Ok(role.can_manage_orgs()),
+        None => Ok(false),
+    }
+}
+
 /// Authenticate operator from bearer token (API key).
 /// Returns (User, AuthMethod) if authentication succeeds.
 fn authenticate_operator_api_key(
@@ -57,7 +86,7 @@ fn authenticate_operator_api_key(
     }
 
     // API key path (default)
-    let (user, api_key_record) = queries::get_user_by_api_key(&conn, token)
+    let (user, api_key_record) = queries::get_user_by_api_key(&conn, token, &state.emails())
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
         .ok_or(StatusCode::UNAUTHORIZED)?;
 
@@ -94,7 +123,7 @@ async fn authenticate_operator_jwt(
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     // Look up user by email
-    let user = queries::get_user_by_email(&conn, &validated.claims.email)
+    let user = queries::get_user_by_email(&conn, &validated.claims.email, &state.emails())
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
         .ok_or(StatusCode::UNAUTHORIZED)?;
 
@@ -173,3 +202,25 @@ pub async fn require_admin_role(
         .insert(OperatorContext { user, auth_method });
     Ok(next.run(request).await)
 }
+
+/// Organization management: admin+ operators, plus partners. Handlers must
+/// call `OperatorContext::require_org_access` for the org they touch.
+pub async fn require_org_operator_role(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let (user, auth_method) = authenticate_from_request(&state, request.headers()).await?;
+
+    if !user
+        .operator_role
+        .is_some_and(|role| role.can_manage_orgs())
+    {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    request
+        .extensions_mut()
+        .insert(OperatorContext { user, auth_method });
+    Ok(next.run(request).await)
+}
@@ -9,7 +9,8 @@
 //!
 //! **Trigger:** `X-On-Behalf-Of: {target_user_id}` header present
 //!
-//! - User must be an `admin+` operator (owner or admin role)
+//! - User must be an `admin+` operator (owner or admin role), or a partner
+//!   operator scoped to the org
 //! - Target user must be a member of the specified org
 //! - Request executes with **target member's actual role** in that org
 //! - Useful for: Admin support, testing member workflows, member-initiated actions
@@ -23,7 +24,8 @@
 //! The `user_id` in the audit log is the **impersonator's** ID, not the target's.
 //!
 //! **Errors:**
-//! - `403 Forbidden`: Header present but user is not an admin+ operator
+//! - `403 Forbidden`: Header present but user is not an admin+ operator, or is
+//!   a partner without a scope for this org
 //! - `404 Not Found`: Target user is not a member of the specified org
 //!
 //! ## Path 2: Normal Org Member Authentication
@@ -45,7 +47,8 @@
 //!
 //! **Trigger:** User is NOT an org member, but IS an admin+ operator
 //!
-//! - User must be an `admin+` operator (owner or admin role)
+//! - User must be an `admin+` operator (owner or admin role), or a partner
+//!   operator scoped to the org
 //! - A synthetic `OrgMemberWithUser` is created with `Owner` role
 //! - Synthetic member ID format: `operator:{operator.id}`
 //! - Request executes with **owner-level access** to all org operations
@@ -56,6 +59,7 @@
 //!
 //! **Errors:**
 //! - `403 Forbidden`: User is an operator but role is less than admin
+//! - `403 Forbidden`: User is a partner operator without a scope for this org
 //! - `403 Forbidden`: User is neither an org member nor an admin+ operator
 //!
 //! # Security Properties
@@ -63,6 +67,8 @@
 //! - **Path precedence:** Impersonation is checked first, preventing accidental
 //!   fallthrough to synthetic access
 //! - **Role requirements:** Impersonation and synthetic access require admin+ role
+//! - **Partner scopes:** Partner operators get paths 1 and 3 only for orgs in
+//!   `operator_org_scopes`; elsewhere they are treated like any non-member
 //! - **404 not 403:** Non-member lookups return 404, preventing org enumeration
 //! - **API key scopes:** Checked only for normal member auth (Path 2)
 //! - **Audit logs record the acting user**, enabling traceability of all actions
@@ -73,7 +79,8 @@
 //! - Project existence and ownership validation
 //! - Project-level role resolution (for `Member` org role users)
 //! - Owner/Admin org members get implicit project access
-//! - `Member` role users need explicit `project_members` entries
+//! - `Member` role users need explicit `project_members` entries, or an
+//!   unexpired `temporary_role_grants` entry (the higher role applies)
 //!
 //! # Usage
 //!
@@ -89,29 +96,45 @@
 //!     .layer(middleware::from_fn_with_state(state.clone(), org_member_project_auth))
 //! ```
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use axum::{
     extract::{Path, Request, State},
-    http::StatusCode,
+    http::{HeaderValue, Method, StatusCode},
     middleware::Next,
     response::Response,
 };
+use rusqlite::Connection;
 
 use crate::db::{AppState, queries};
 use crate::jwt::validate_first_party_token;
 use crate::models::{
-    AccessLevel, AuditLogNames, OperatorRole, OrgMemberRole, OrgMemberWithUser, ProjectMemberRole,
-    User,
+    AccessLevel, ActorType, AuditAction, AuditLogNames, OrgMemberRole, OrgMemberWithUser,
+    ProjectMemberRole, TemporaryRoleGrant, User,
 };
-use crate::util::extract_bearer_token;
+use crate::util::{AuditLogBuilder, extract_bearer_token};
 
 use super::AuthMethod;
+use super::operator_auth::operator_can_manage_org;
 
 /// Header name for operator impersonation.
 /// Value should be a `user_id` (not member_id).
 const ON_BEHALF_OF_HEADER: &str = "x-on-behalf-of";
 
+/// Response header set while the caller's org membership is scheduled for
+/// removal. Value is the Unix timestamp when access ends.
+pub const ACCESS_ENDING_HEADER: &str = "x-paycheck-access-ending";
+
+/// Add `ACCESS_ENDING_HEADER` if the member has a removal scheduled.
+fn with_access_ending(mut response: Response, removal_scheduled_at: Option<i64>) -> Response {
+    if let Some(ts) = removal_scheduled_at {
+        response
+            .headers_mut()
+            .insert(ACCESS_ENDING_HEADER, HeaderValue::from(ts));
+    }
+    response
+}
+
 #[derive(Clone)]
 pub struct OrgMemberContext {
     /// The org member (with user details joined)
@@ -125,6 +148,9 @@ pub struct OrgMemberContext {
     pub auth_method: AuthMethod,
     /// API key access level (None for JWT auth, Some for scoped API key auth)
     pub api_key_access: Option<AccessLevel>,
+    /// Temporary grant that raised `project_role` above the member's
+    /// permanent role for this request, if any
+    pub temporary_grant: Option<TemporaryRoleGrant>,
 }
 
 #[derive(Clone)]
@@ -164,6 +190,15 @@ impl OrgMemberContext {
         }
     }
 
+    /// Reject view-only API keys. For writes that touch only the caller's
+    /// own data (e.g. their API keys), where no role check applies.
+    pub fn require_write(&self) -> Result<(), StatusCode> {
+        if let Some(AccessLevel::View) = self.api_key_access {
+            return Err(StatusCode::FORBIDDEN);
+        }
+        Ok(())
+    }
+
     pub fn can_write_project(&self) -> bool {
         // Check API key access level first - View-only keys cannot write
         if let Some(AccessLevel::View) = self.api_key_access {
@@ -182,6 +217,12 @@ impl OrgMemberContext {
         self.impersonator.is_some()
     }
 
+    /// Returns true if `project_role` comes from a temporary grant rather
+    /// than the member's permanent access
+    pub fn is_temporary_elevation(&self) -> bool {
+        self.temporary_grant.is_some()
+    }
+
     /// Returns impersonator info as JSON for audit log details
     pub fn impersonator_json(&self) -> Option<serde_json::Value> {
         self.impersonator.as_ref().map(|i| {
@@ -204,6 +245,44 @@ impl OrgMemberContext {
     }
 }
 
+/// Projects an org member can learn about through org-level endpoints.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VisibleProjects {
+    /// Owners and admins see every project in the org
+    All,
+    /// `member`-role users see only the projects they were added to
+    Only(HashSet<String>),
+}
+
+impl VisibleProjects {
+    pub fn contains(&self, project_id: &str) -> bool {
+        match self {
+            VisibleProjects::All => true,
+            VisibleProjects::Only(ids) => ids.contains(project_id),
+        }
+    }
+}
+
+/// The projects `ctx`'s member may see, including those they hold an active
+/// temporary role on at `now`. Org-level endpoints that return project IDs or
+/// names filter by this, or redact what they can't filter.
+pub fn visible_project_ids(
+    conn: &Connection,
+    ctx: &OrgMemberContext,
+    now: i64,
+) -> crate::error::Result<VisibleProjects> {
+    if ctx.member.role.has_implicit_project_access() {
+        return Ok(VisibleProjects::All);
+    }
+    let ids = queries::list_accessible_project_ids_for_member(
+        conn,
+        &ctx.member.org_id,
+        &ctx.member.id,
+        now,
+    )?;
+    Ok(VisibleProjects::Only(ids.into_iter().collect()))
+}
+
 /// Attempt to authenticate as an operator impersonating an org member.
 /// Returns Some((member_with_user, impersonator_info)) if impersonation is valid.
 fn try_operator_impersonation(
@@ -218,26 +297,28 @@ fn try_operator_impersonation(
         None => return Ok(None), // No impersonation header - not an impersonation attempt
     };
 
-    // Check if user is an operator with admin+ role
-    let operator_role = match user.operator_role {
-        Some(role) => role,
-        None => return Err(StatusCode::FORBIDDEN), // Has impersonation header but not an operator
-    };
-
-    // Only admin+ operators can impersonate
-    if !matches!(operator_role, OperatorRole::Owner | OperatorRole::Admin) {
-        return Err(StatusCode::FORBIDDEN);
-    }
-
     let conn = state
         .db
         .get()
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    // Load the target org member by user_id and org_id
-    let member = queries::get_org_member_with_user_by_user_and_org(&conn, target_user_id, org_id)
+    // Only admin+ operators can impersonate (partners only within their orgs).
+    // Also rejects a header sent by a non-operator.
+    if !operator_can_manage_org(&conn, user, org_id)
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-        .ok_or(StatusCode::NOT_FOUND)?;
+    {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // Load the target org member by user_id and org_id
+    let member = queries::get_org_member_with_user_by_user_and_org(
+        &conn,
+        target_user_id,
+        org_id,
+        &state.emails(),
+    )
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .ok_or(StatusCode::NOT_FOUND)?;
 
     let impersonator = ImpersonatorInfo {
         user_id: user.id.clone(),
@@ -331,7 +412,7 @@ async fn authenticate_user_jwt(
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     // Look up user by email
-    let user = queries::get_user_by_email(&conn, &validated.claims.email)
+    let user = queries::get_user_by_email(&conn, &validated.claims.email, &state.emails())
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
         .ok_or(StatusCode::UNAUTHORIZED)?;
 
@@ -387,7 +468,7 @@ pub async fn org_member_auth(
             .db
             .get()
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-        let (user, api_key_record) = queries::get_user_by_api_key(&conn, token)
+        let (user, api_key_record) = queries::get_user_by_api_key(&conn, token, &state.emails())
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
             .ok_or(StatusCode::UNAUTHORIZED)?;
         let auth_method = AuthMethod::ApiKey {
@@ -413,6 +494,7 @@ pub async fn org_member_auth(
             impersonator: Some(impersonator),
             auth_method,
             api_key_access: None, // Operators bypass scope checks
+            temporary_grant: None,
         });
         return Ok(next.run(request).await);
     }
@@ -426,11 +508,13 @@ pub async fn org_member_auth(
     };
 
     // Try normal org member authentication first
-    let member = queries::get_org_member_with_user_by_user_and_org(&conn, &user.id, org_id)
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let member =
+        queries::get_org_member_with_user_by_user_and_org(&conn, &user.id, org_id, &state.emails())
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     if let Some(member) = member {
         // User is an org member
+        let removal_scheduled_at = member.removal_scheduled_at;
         request.extensions_mut().insert(OrgMemberContext {
             member,
             user,
@@ -438,15 +522,18 @@ pub async fn org_member_auth(
             impersonator: None,
             auth_method,
             api_key_access,
+            temporary_grant: None,
         });
-        return Ok(next.run(request).await);
+        return Ok(with_access_ending(
+            next.run(request).await,
+            removal_scheduled_at,
+        ));
     }
 
-    // Not an org member - check if they're an operator with admin+ role
-    if matches!(
-        user.operator_role,
-        Some(OperatorRole::Owner) | Some(OperatorRole::Admin)
-    ) {
+    // Not an org member - check if they're an operator with admin+ access to this org
+    if operator_can_manage_org(&conn, &user, org_id)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    {
         // Operator with admin+ role gets synthetic owner access
         let synthetic_member = OrgMemberWithUser {
             id: format!("operator:{}", user.id),
@@ -459,6 +546,7 @@ pub async fn org_member_auth(
             updated_at: user.updated_at,
             deleted_at: None,
             deleted_cascade_depth: None,
+            removal_scheduled_at: None,
         };
         request.extensions_mut().insert(OrgMemberContext {
             member: synthetic_member,
@@ -467,6 +555,7 @@ pub async fn org_member_auth(
             impersonator: None,
             auth_method,
             api_key_access: None, // Operators bypass scope checks
+            temporary_grant: None,
         });
         return Ok(next.run(request).await);
     }
@@ -494,8 +583,10 @@ pub struct OrgProjectPath {
 /// 1. Validates the project exists and belongs to the org
 /// 2. Resolves project-level role:
 ///    - **Owner/Admin** org members: Implicit access (no project member entry needed)
-///    - **Member** org role: Must have explicit `project_members` entry
+///    - **Member** org role: Must have explicit `project_members` entry or an
+///      unexpired temporary grant; with both, the higher role applies
 /// 3. Returns 404 if user has no access (prevents project enumeration)
+/// 4. Audit logs writes (non-GET requests) made under a temporary grant
 ///
 /// # Request Extensions
 ///
@@ -531,7 +622,7 @@ pub async fn org_member_project_auth(
             .db
             .get()
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-        let (user, api_key_record) = queries::get_user_by_api_key(&conn, token)
+        let (user, api_key_record) = queries::get_user_by_api_key(&conn, token, &state.emails())
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
             .ok_or(StatusCode::UNAUTHORIZED)?;
         let auth_method = AuthMethod::ApiKey {
@@ -561,17 +652,21 @@ pub async fn org_member_project_auth(
         };
 
         // Try normal org member authentication
-        let member = queries::get_org_member_with_user_by_user_and_org(&conn, &user.id, org_id)
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let member = queries::get_org_member_with_user_by_user_and_org(
+            &conn,
+            &user.id,
+            org_id,
+            &state.emails(),
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
         if let Some(member) = member {
             (member, None, api_key_access)
         } else {
-            // Not an org member - check if they're an operator with admin+ role
-            if matches!(
-                user.operator_role,
-                Some(OperatorRole::Owner) | Some(OperatorRole::Admin)
-            ) {
+            // Not an org member - check if they're an operator with admin+ access to this org
+            if operator_can_manage_org(&conn, &user, org_id)
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            {
                 // Operator with admin+ role gets synthetic owner access
                 let synthetic_member = OrgMemberWithUser {
                     id: format!("operator:{}", user.id),
@@ -584,6 +679,7 @@ pub async fn org_member_project_auth(
                     updated_at: user.updated_at,
                     deleted_at: None,
                     deleted_cascade_depth: None,
+                    removal_scheduled_at: None,
                 };
                 (synthetic_member, None, None) // Operators bypass scope checks
             } else {
@@ -592,6 +688,12 @@ pub async fn org_member_project_auth(
         }
     };
 
+    // Project data lives in the org's database (dedicated file or shared)
+    let conn = state
+        .org_db(org_id)
+        .get()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
     // Check project exists and belongs to org
     let project = queries::get_project_by_id(&conn, project_id)
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
@@ -602,12 +704,27 @@ pub async fn org_member_project_auth(
     }
 
     // Get project-level role if exists
-    let project_role = if member.role.has_implicit_project_access() {
-        None // Owner/admin have implicit access, no need for project_members entry
+    let (project_role, temporary_grant) = if member.role.has_implicit_project_access() {
+        (None, None) // Owner/admin have implicit access, no need for project_members entry
     } else {
-        queries::get_project_member(&conn, &member.id, project_id)
+        let permanent = queries::get_project_member(&conn, &member.id, project_id)
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-            .map(|pm| pm.role)
+            .map(|pm| pm.role);
+        // Expiry is checked here, against the grant's timestamp, so an expired
+        // or revoked grant stops working on the very next request. A grant
+        // only counts if it raises the member's permanent role.
+        let grant = queries::get_active_temporary_role_grant(
+            &conn,
+            &member.id,
+            project_id,
+            state.clock.now(),
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .filter(|g| permanent.is_none_or(|role| g.role.higher(role) != role));
+        match grant {
+            Some(grant) => (Some(grant.role), Some(grant)),
+            None => (permanent, None),
+        }
     };
 
     // Check if member has any access to this project
@@ -616,14 +733,55 @@ pub async fn org_member_project_auth(
         return Err(StatusCode::NOT_FOUND);
     }
 
-    request.extensions_mut().insert(OrgMemberContext {
+    // Impersonating operators keep their own access; the header is for the member
+    let removal_scheduled_at = impersonator
+        .is_none()
+        .then_some(member.removal_scheduled_at)
+        .flatten();
+    let ctx = OrgMemberContext {
         member,
         user,
         project_role,
         impersonator,
         auth_method,
         api_key_access,
-    });
+        temporary_grant,
+    };
+
+    if let Some(grant) = &ctx.temporary_grant
+        && !matches!(
+            *request.method(),
+            Method::GET | Method::HEAD | Method::OPTIONS
+        )
+    {
+        let audit_conn = state
+            .audit
+            .get()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        AuditLogBuilder::for_state(&audit_conn, &state, request.headers())
+            .actor(ActorType::User, Some(&ctx.member.user_id))
+            .action(AuditAction::UseTemporaryRole)
+            .resource("temporary_role_grant", &grant.id)
+            .details(&serde_json::json!({
+                "role": grant.role,
+                "expires_at": grant.expires_at,
+                "granted_by": grant.granted_by,
+                "method": request.method().as_str(),
+                "path": request.uri().path(),
+                "impersonator": ctx.impersonator_json()
+            }))
+            .org(org_id)
+            .project(project_id)
+            .names(&ctx.audit_names())
+            .auth_method(&ctx.auth_method)
+            .save()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    }
+
+    request.extensions_mut().insert(ctx);
 
-    Ok(next.run(request).await)
+    Ok(with_access_ending(
+        next.run(request).await,
+        removal_scheduled_at,
+    ))
 }
@@ -0,0 +1,105 @@
+//! Request IDs for correlating a customer's report with server logs.
+//!
+//! Every request gets an ID, either a fresh UUID or (when
+//! `PAYCHECK_TRUST_REQUEST_ID` is set, for deployments behind a proxy that
+//! assigns them) the incoming `X-Request-Id`. The ID is:
+//!
+//! - recorded on the tracing span wrapping the request,
+//! - returned in the `X-Request-Id` response header,
+//! - included as `request_id` in error response bodies,
+//! - stored on audit log entries written while handling the request.
+//!
+//! Handlers can extract it as [`RequestId`] from request extensions. Error
+//! responses and the audit log builder have no access to the request, so they
+//! read it with [`RequestId::current`].
+
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use tracing::Instrument;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest incoming request ID that is honored; longer ones are replaced.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    static CURRENT_REQUEST_ID: RequestId;
+}
+
+/// The ID assigned to the request being handled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+    /// The ID of the request this task is handling, if any (None outside the
+    /// [`request_id`] middleware, e.g. in CLI commands and background tasks).
+    pub fn current() -> Option<RequestId> {
+        CURRENT_REQUEST_ID.try_with(|id| id.clone()).ok()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Settings for the [`request_id`] middleware.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequestIdConfig {
+    /// Keep a well-formed incoming `X-Request-Id` instead of generating one.
+    /// Only safe when a proxy in front of the server sets or strips the header.
+    pub trust_incoming: bool,
+}
+
+/// Assign the request its ID. Install as the outermost layer so every other
+/// layer's logs and errors carry it.
+pub async fn request_id(
+    State(config): State<RequestIdConfig>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let id = config
+        .trust_incoming
+        .then(|| incoming_request_id(request.headers()))
+        .flatten()
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let header_value =
+        HeaderValue::from_str(&id).expect("request IDs are generated or validated as ASCII");
+
+    // Handlers that read headers see the ID in use, not a client's untrusted one
+    request
+        .headers_mut()
+        .insert(REQUEST_ID_HEADER, header_value.clone());
+    request.extensions_mut().insert(RequestId(id.clone()));
+
+    let span = tracing::info_span!(
+        "request",
+        request_id = %id,
+        method = %request.method(),
+        path = %request.uri().path(),
+    );
+    let mut response = CURRENT_REQUEST_ID
+        .scope(RequestId(id), next.run(request))
+        .instrument(span)
+        .await;
+
+    response
+        .headers_mut()
+        .insert(REQUEST_ID_HEADER, header_value);
+    response
+}
+
+/// An incoming `X-Request-Id` that is safe to log and echo back: 1-128
+/// characters of letters, digits, `-`, `_`, `.` and `:`.
+fn incoming_request_id(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get(REQUEST_ID_HEADER)?.to_str().ok()?;
+    let valid = !value.is_empty()
+        && value.len() <= MAX_REQUEST_ID_LEN
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'));
+    valid.then(|| value.to_string())
+}
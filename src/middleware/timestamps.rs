@@ -0,0 +1,58 @@
+//! Timestamp format negotiation.
+//!
+//! Response timestamps are Unix seconds unless the client asks for ISO ones,
+//! with `?ts=iso` on any request or an `Accept` header naming the
+//! `iso-timestamps` profile (`Accept: application/json;
+//! profile="iso-timestamps"`). See [`TimestampFormat`] for the wire format.
+
+use axum::{
+    extract::Request,
+    http::{HeaderMap, header},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::models::TimestampFormat;
+
+/// `Accept` profile that selects ISO timestamps.
+pub const ISO_TIMESTAMPS_PROFILE: &str = "iso-timestamps";
+
+/// Serve the request with the timestamp format it asked for. Install outside
+/// the routers so every API honors it.
+pub async fn timestamp_format(request: Request, next: Next) -> Response {
+    let format = requested_format(request.uri().query(), request.headers());
+    format.scope(next.run(request)).await
+}
+
+fn requested_format(query: Option<&str>, headers: &HeaderMap) -> TimestampFormat {
+    let in_query = query
+        .unwrap_or_default()
+        .split('&')
+        .any(|pair| pair.eq_ignore_ascii_case("ts=iso"));
+    if in_query || accepts_iso_profile(headers) {
+        TimestampFormat::Iso
+    } else {
+        TimestampFormat::Unix
+    }
+}
+
+/// Whether any `Accept` media range has a `profile` parameter listing
+/// [`ISO_TIMESTAMPS_PROFILE`] (profiles are a space-separated list).
+fn accepts_iso_profile(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .flat_map(|range| range.split(';').skip(1))
+        .filter_map(|param| param.split_once('='))
+        .filter(|(name, _)| name.trim().eq_ignore_ascii_case("profile"))
+        .any(|(_, value)| {
+            value
+                .trim()
+                .trim_matches('"')
+                .split_whitespace()
+                .any(|profile| profile == ISO_TIMESTAMPS_PROFILE)
+        })
+}
+
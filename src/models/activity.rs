@@ -0,0 +1,353 @@
+use serde::Serialize;
+
+use super::{ActorType, AuditAction, AuditLog};
+
+/// One change in a project's recent activity feed: the audit log columns the
+/// console's "recent changes" widget shows, plus a one-line summary.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProjectActivity {
+    pub id: String,
+    pub timestamp: i64,
+    pub actor_type: ActorType,
+    pub user_id: Option<String>,
+    /// None under PII minimization
+    pub user_name: Option<String>,
+    /// None under PII minimization
+    pub user_email: Option<String>,
+    pub action: String,
+    pub resource_type: String,
+    pub resource_id: String,
+    pub resource_name: Option<String>,
+    /// e.g. "Alice revoked license 3f2a9c1b…"
+    pub summary: String,
+}
+
+impl ProjectActivity {
+    /// Who made the change, for the summary: their name, else their email.
+    fn actor(&self) -> &str {
+        self.user_name
+            .as_deref()
+            .or(self.user_email.as_deref())
+            .unwrap_or("A team member")
+    }
+
+    /// The resource's name, else the start of its ID.
+    fn resource(&self) -> String {
+        match &self.resource_name {
+            Some(name) => name.clone(),
+            None if self.resource_id.chars().count() > 8 => {
+                let short: String = self.resource_id.chars().take(8).collect();
+                format!("{}…", short)
+            }
+            None => self.resource_id.clone(),
+        }
+    }
+
+    /// One-line description of the change, from a template per action.
+    /// Actions without one fall back to the audit log's verb phrasing
+    /// ("Alice created api key CI key").
+    pub fn summarize(&self) -> String {
+        let actor = self.actor();
+        let res = self.resource();
+        let fallback = || {
+            format!(
+                "{} {} {}",
+                actor,
+                AuditLog::action_to_verb_phrase(&self.action, &self.resource_type),
+                res
+            )
+        };
+        let Ok(action) = self.action.parse::<AuditAction>() else {
+            return fallback();
+        };
+
+        match action {
+            AuditAction::UpdateProject => format!("{} updated project {}", actor, res),
+            AuditAction::RestoreProject => format!("{} restored project {}", actor, res),
+            AuditAction::UpdateClientFlags => {
+                format!("{} changed the client flags of project {}", actor, res)
+            }
+
+            AuditAction::CreateProduct => format!("{} created product {}", actor, res),
+            AuditAction::UpdateProduct => format!("{} updated product {}", actor, res),
+            AuditAction::DeleteProduct => format!("{} deleted product {}", actor, res),
+            AuditAction::RestoreProduct => format!("{} restored product {}", actor, res),
+
+            AuditAction::CreateProviderLink => {
+                format!("{} linked product {} to a payment provider", actor, res)
+            }
+            AuditAction::UpdateProviderLink => {
+                format!("{} updated the payment link for product {}", actor, res)
+            }
+            AuditAction::DeleteProviderLink => {
+                format!(
+                    "{} unlinked product {} from its payment provider",
+                    actor, res
+                )
+            }
+
+            AuditAction::CreateProjectMember => {
+                format!("{} added {} to the project", actor, res)
+            }
+            AuditAction::UpdateProjectMember => {
+                format!("{} changed the project role of {}", actor, res)
+            }
+            AuditAction::DeleteProjectMember => {
+                format!("{} removed {} from the project", actor, res)
+            }
+            AuditAction::GrantTemporaryRole => {
+                format!("{} granted a temporary role to {}", actor, res)
+            }
+            AuditAction::RevokeTemporaryRole => {
+                format!("{} revoked the temporary role of {}", actor, res)
+            }
+            AuditAction::UseTemporaryRole => format!("{} used a temporary role", actor),
+
+            AuditAction::CreateLicense => format!("{} created license {}", actor, res),
+            AuditAction::UpdateLicenseEmail => {
+                format!("{} changed the email on license {}", actor, res)
+            }
+            AuditAction::RevokeLicense => format!("{} revoked license {}", actor, res),
+            AuditAction::RestoreLicense => format!("{} restored license {}", actor, res),
+            AuditAction::AssignLicenseSeat => {
+                format!("{} assigned a seat on license {}", actor, res)
+            }
+            AuditAction::RemoveLicenseSeat => {
+                format!("{} removed a seat from license {}", actor, res)
+            }
+            AuditAction::CreateShareLink => {
+                format!("{} created a share link for license {}", actor, res)
+            }
+            AuditAction::RevokeShareLink => {
+                format!("{} revoked a share link for license {}", actor, res)
+            }
+            AuditAction::AddLicenseTags => format!("{} tagged license {}", actor, res),
+            AuditAction::RemoveLicenseTags => format!("{} untagged license {}", actor, res),
+            AuditAction::BulkTagLicenses => format!("{} bulk-tagged licenses", actor),
+            AuditAction::ImportLicenses => format!("{} imported licenses", actor),
+            AuditAction::GenerateActivationCode => {
+                format!("{} generated an activation code for license {}", actor, res)
+            }
+            AuditAction::DeactivateDevice => format!("{} deactivated device {}", actor, res),
+            AuditAction::BulkDeactivateDevices => format!("{} bulk-deactivated devices", actor),
+
+            AuditAction::CreatePrepaidCodes => {
+                format!("{} created prepaid code batch {}", actor, res)
+            }
+            AuditAction::DownloadPrepaidCodes => {
+                format!("{} downloaded prepaid code batch {}", actor, res)
+            }
+            AuditAction::RevokePrepaidCodes => {
+                format!("{} revoked prepaid code batch {}", actor, res)
+            }
+
+            _ => fallback(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn activity(action: AuditAction, resource_name: Option<&str>) -> ProjectActivity {
+        ProjectActivity {
+            id: "log1".to_string(),
+            timestamp: 1_767_225_600,
+            actor_type: ActorType::User,
+            user_id: Some("user1".to_string()),
+            user_name: Some("Alice".to_string()),
+            user_email: Some("alice@example.com".to_string()),
+            action: action.as_ref().to_string(),
+            resource_type: "license".to_string(),
+            resource_id: "3f2a9c1b-77e0-4d1e-9a52-0c6b1f4e8d21".to_string(),
+            resource_name: resource_name.map(String::from),
+            summary: String::new(),
+        }
+    }
+
+    fn summary(action: AuditAction, resource_name: Option<&str>) -> String {
+        activity(action, resource_name).summarize()
+    }
+
+    #[test]
+    fn test_project_phrasing() {
+        assert_eq!(
+            summary(AuditAction::UpdateProject, Some("My App")),
+            "Alice updated project My App"
+        );
+        assert_eq!(
+            summary(AuditAction::RestoreProject, Some("My App")),
+            "Alice restored project My App"
+        );
+    }
+
+    #[test]
+    fn test_product_phrasing() {
+        let cases = [
+            (AuditAction::CreateProduct, "Alice created product Pro Plan"),
+            (AuditAction::UpdateProduct, "Alice updated product Pro Plan"),
+            (AuditAction::DeleteProduct, "Alice deleted product Pro Plan"),
+            (
+                AuditAction::RestoreProduct,
+                "Alice restored product Pro Plan",
+            ),
+            (
+                AuditAction::CreateProviderLink,
+                "Alice linked product Pro Plan to a payment provider",
+            ),
+            (
+                AuditAction::UpdateProviderLink,
+                "Alice updated the payment link for product Pro Plan",
+            ),
+            (
+                AuditAction::DeleteProviderLink,
+                "Alice unlinked product Pro Plan from its payment provider",
+            ),
+        ];
+        for (action, expected) in cases {
+            assert_eq!(summary(action, Some("Pro Plan")), expected);
+        }
+    }
+
+    #[test]
+    fn test_project_member_phrasing() {
+        let cases = [
+            (
+                AuditAction::CreateProjectMember,
+                "Alice added Bob to the project",
+            ),
+            (
+                AuditAction::UpdateProjectMember,
+                "Alice changed the project role of Bob",
+            ),
+            (
+                AuditAction::DeleteProjectMember,
+                "Alice removed Bob from the project",
+            ),
+            (
+                AuditAction::GrantTemporaryRole,
+                "Alice granted a temporary role to Bob",
+            ),
+            (
+                AuditAction::RevokeTemporaryRole,
+                "Alice revoked the temporary role of Bob",
+            ),
+            (AuditAction::UseTemporaryRole, "Alice used a temporary role"),
+        ];
+        for (action, expected) in cases {
+            assert_eq!(summary(action, Some("Bob")), expected);
+        }
+    }
+
+    #[test]
+    fn test_license_phrasing() {
+        let cases = [
+            (
+                AuditAction::CreateLicense,
+                "Alice created license 3f2a9c1b…",
+            ),
+            (
+                AuditAction::UpdateLicenseEmail,
+                "Alice changed the email on license 3f2a9c1b…",
+            ),
+            (
+                AuditAction::RevokeLicense,
+                "Alice revoked license 3f2a9c1b…",
+            ),
+            (
+                AuditAction::RestoreLicense,
+                "Alice restored license 3f2a9c1b…",
+            ),
+            (
+                AuditAction::AssignLicenseSeat,
+                "Alice assigned a seat on license 3f2a9c1b…",
+            ),
+            (
+                AuditAction::RemoveLicenseSeat,
+                "Alice removed a seat from license 3f2a9c1b…",
+            ),
+            (
+                AuditAction::CreateShareLink,
+                "Alice created a share link for license 3f2a9c1b…",
+            ),
+            (
+                AuditAction::RevokeShareLink,
+                "Alice revoked a share link for license 3f2a9c1b…",
+            ),
+            (
+                AuditAction::AddLicenseTags,
+                "Alice tagged license 3f2a9c1b…",
+            ),
+            (
+                AuditAction::RemoveLicenseTags,
+                "Alice untagged license 3f2a9c1b…",
+            ),
+            (AuditAction::BulkTagLicenses, "Alice bulk-tagged licenses"),
+            (AuditAction::ImportLicenses, "Alice imported licenses"),
+            (
+                AuditAction::GenerateActivationCode,
+                "Alice generated an activation code for license 3f2a9c1b…",
+            ),
+            (
+                AuditAction::DeactivateDevice,
+                "Alice deactivated device 3f2a9c1b…",
+            ),
+            (
+                AuditAction::BulkDeactivateDevices,
+                "Alice bulk-deactivated devices",
+            ),
+        ];
+        for (action, expected) in cases {
+            assert_eq!(summary(action, None), expected);
+        }
+    }
+
+    #[test]
+    fn test_prepaid_code_phrasing() {
+        let cases = [
+            (
+                AuditAction::CreatePrepaidCodes,
+                "Alice created prepaid code batch Launch promo",
+            ),
+            (
+                AuditAction::DownloadPrepaidCodes,
+                "Alice downloaded prepaid code batch Launch promo",
+            ),
+            (
+                AuditAction::RevokePrepaidCodes,
+                "Alice revoked prepaid code batch Launch promo",
+            ),
+        ];
+        for (action, expected) in cases {
+            assert_eq!(summary(action, Some("Launch promo")), expected);
+        }
+    }
+
+    #[test]
+    fn test_actor_falls_back_to_email_then_generic() {
+        let mut entry = activity(AuditAction::UpdateProduct, Some("Pro Plan"));
+        entry.user_name = None;
+        assert_eq!(
+            entry.summarize(),
+            "alice@example.com updated product Pro Plan"
+        );
+
+        // PII minimization stores neither
+        entry.user_email = None;
+        assert_eq!(entry.summarize(), "A team member updated product Pro Plan");
+    }
+
+    #[test]
+    fn test_untemplated_action_uses_action_words() {
+        assert_eq!(
+            summary(AuditAction::CreateApiKey, Some("CI key")),
+            "Alice created api key CI key"
+        );
+
+        // Actions from a newer version this one doesn't know
+        let mut entry = activity(AuditAction::CreateProduct, Some("Pro Plan"));
+        entry.action = "archive_product".to_string();
+        assert_eq!(entry.summarize(), "Alice archive product Pro Plan");
+    }
+}
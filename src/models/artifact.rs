@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, Result, msg};
+
+/// Longest artifact name
+pub const MAX_ARTIFACT_NAME_LEN: usize = 200;
+
+/// A release file a project gates behind its licenses (an installer, an
+/// archive). Paycheck never serves the bytes: `/download/{id}` checks the
+/// license and redirects to a signed copy of `url` that the vendor's CDN
+/// verifies.
+#[derive(Debug, Clone, Serialize)]
+pub struct Artifact {
+    pub id: String,
+    pub project_id: String,
+    /// Display name, e.g. "MyApp-2.4.0.dmg"
+    pub name: String,
+    /// Licenses whose updates ended before this can't download it
+    pub released_at: i64,
+    /// Where the file lives; the signature is appended to it
+    pub url: String,
+    /// Downloads across all licenses
+    pub download_count: i64,
+    /// User who registered the artifact
+    pub created_by: Option<String>,
+    pub created_at: i64,
+}
+
+/// Downloads of one artifact by one license.
+#[derive(Debug, Clone, Serialize)]
+pub struct ArtifactDownload {
+    pub artifact_id: String,
+    pub license_id: String,
+    pub count: i64,
+    pub first_downloaded_at: i64,
+    pub last_downloaded_at: i64,
+}
+
+/// Body for registering an artifact
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CreateArtifact {
+    pub name: String,
+    pub url: String,
+    /// When the release came out (default: now)
+    #[serde(default)]
+    pub released_at: Option<i64>,
+}
+
+impl CreateArtifact {
+    pub fn validate(&self) -> Result<()> {
+        let name = self.name.trim();
+        if name.is_empty() || name.chars().count() > MAX_ARTIFACT_NAME_LEN {
+            return Err(AppError::BadRequest(msg::ARTIFACT_NAME_INVALID.into()));
+        }
+        Ok(())
+    }
+}
@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
 use strum::{AsRefStr, EnumString};
 
+use super::serialize_timestamp;
+use crate::pagination::{clamp_limit, clamp_offset};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, AsRefStr, EnumString)]
 #[serde(rename_all = "snake_case")]
 #[strum(serialize_all = "snake_case")]
@@ -20,65 +23,135 @@ pub enum AuditAction {
     CreateUser,
     UpdateUser,
     DeleteUser,
+    MergeUser,
 
     // Operator management
     CreateOperator,
     UpdateOperator,
     DeleteOperator,
     BootstrapOperator,
+    AddOperatorOrgScope,
+    RemoveOperatorOrgScope,
+    EnableMaintenanceMode,
+    DisableMaintenanceMode,
 
     // Organization management
     CreateOrg,
+    CreateWorkspace,
     UpdateOrg,
     DeleteOrg,
+    UpdateOrgLimit,
+    ReachOrgSoftLimit,
+    UpdateOrgEmailConfig,
+    SendTestEmail,
+    UpdateOrgComplianceSettings,
 
     // Org member management
     CreateOrgMember,
     UpdateOrgMember,
     DeleteOrgMember,
+    ScheduleOrgMemberRemoval,
+    CancelOrgMemberRemoval,
 
     // Project management
     CreateProject,
     UpdateProject,
     DeleteProject,
+    SetWebhookMirror,
+    ClearWebhookMirror,
+    AddCustomDomain,
+    VerifyCustomDomain,
+    RemoveCustomDomain,
+    UpdateClientFlags,
 
     // Project member management
     CreateProjectMember,
     UpdateProjectMember,
     DeleteProjectMember,
+    GrantTemporaryRole,
+    RevokeTemporaryRole,
+    UseTemporaryRole,
 
     // Product management
     CreateProduct,
     UpdateProduct,
     DeleteProduct,
 
+    // Product templates
+    CreateProductTemplate,
+    UpdateProductTemplate,
+    DeleteProductTemplate,
+    ApplyProductTemplates,
+
     // Provider link management
     CreateProviderLink,
     UpdateProviderLink,
     DeleteProviderLink,
 
+    // Terms of sale
+    CreateTerms,
+    UpdateTerms,
+
+    // Release artifacts
+    CreateArtifact,
+    DeleteArtifact,
+    ViewDownloadSigningKey,
+
     // License management
     CreateLicense,
     UpdateLicenseEmail,
     RevokeLicense,
+    AssignLicenseSeat,
+    RemoveLicenseSeat,
+    CreateShareLink,
+    RevokeShareLink,
+    AddLicenseTags,
+    RemoveLicenseTags,
+    BulkTagLicenses,
+    CreatePrepaidCodes,
+    DownloadPrepaidCodes,
+    RevokePrepaidCodes,
+    StartBulkLicenseJob,
+    CancelBulkLicenseJob,
+    DownloadBulkLicenseCodes,
+    ImportLicenses,
+    TrialConverted,
+    LicenseDormant,
 
     // Activation
     GenerateActivationCode,
 
     // Device management
     DeactivateDevice,
+    BulkDeactivateDevices,
 
     // Token operations
     RefreshToken,
+    RefreshJwksCache,
 
     // Public activation actions
     ActivateDevice,
     RequestActivationCode,
+    ViewShareLink,
+    ClaimFreeLicense,
+    RedeemPrepaidCode,
 
     // Webhook events
     ReceiveCheckoutWebhook,
     ReceiveRenewalWebhook,
     ReceiveCancellationWebhook,
+    ReceivePauseWebhook,
+    ReceiveResumeWebhook,
+    ReceiveRefundWebhook,
+    ReceiveDisputeOpenedWebhook,
+    ReceiveDisputeClosedWebhook,
+
+    // Subscription reconciliation
+    ReconcileSubscriptions,
+
+    // Database backups
+    CreateBackup,
+    VerifyBackup,
 
     // API key management
     CreateApiKey,
@@ -103,11 +176,65 @@ pub enum AuditAction {
     // Hard delete (GDPR)
     HardDeleteUser,
     HardDeleteOrg,
+
+    // Audit trail export
+    GenerateAuditArchive,
+    ExportAuditLogs,
+}
+
+/// How much an action's audit entry matters when it can't be written
+/// (see [`AuditFailureMode`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditSensitivity {
+    /// Changes to who gets paid, who holds which role, and which licenses and
+    /// credentials are valid. Must not happen unrecorded under `fail_request`.
+    Sensitive,
+    /// Everything else, reads included. A failed audit write is logged and
+    /// the request carries on.
+    Routine,
+}
+
+impl AuditAction {
+    /// Whether this action fails closed under `fail_request`. Handlers for
+    /// sensitive actions write their audit entry before committing
+    /// (`AuditLogBuilder::checked_entry`), so a failed write rolls them back.
+    pub fn sensitivity(self) -> AuditSensitivity {
+        match self {
+            // Payment config: the org's Stripe and LemonSqueezy credentials
+            AuditAction::UpdateOrg
+            // Member role changes
+            | AuditAction::UpdateOrgMember
+            | AuditAction::UpdateProjectMember
+            // License revocation
+            | AuditAction::RevokeLicense
+            // API key rotation
+            | AuditAction::CreateApiKey
+            | AuditAction::RevokeApiKey => AuditSensitivity::Sensitive,
+            _ => AuditSensitivity::Routine,
+        }
+    }
+}
+
+/// What happens to a request whose audit entry can't be written, e.g. because
+/// the audit database is full or locked. Set via AUDIT_FAILURE_MODE.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, AsRefStr, EnumString,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum AuditFailureMode {
+    /// Log the failure and carry on (default)
+    #[default]
+    LogAndContinue,
+    /// Sensitive actions ([`AuditAction::sensitivity`]) roll back and return
+    /// 500; routine ones log and carry on
+    FailRequest,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuditLog {
     pub id: String,
+    #[serde(serialize_with = "serialize_timestamp")]
     pub timestamp: i64,
     pub actor_type: ActorType,
     /// User ID (references users.id, null for public/system)
@@ -136,6 +263,9 @@ pub struct AuditLog {
     pub auth_type: Option<String>,
     /// Auth credential (API key prefix or JWT issuer URL)
     pub auth_credential: Option<String>,
+    /// ID of the HTTP request that made the change (also in the `X-Request-Id`
+    /// response header and error bodies). None for CLI and background actions.
+    pub request_id: Option<String>,
 }
 
 /// Names to include in an audit log entry for human-readable display.
@@ -181,9 +311,53 @@ impl AuditLogNames {
         self.project_name = name.into();
         self
     }
+
+    /// Drop user names and emails (PII minimization). The log keeps user IDs;
+    /// operators resolve them back to names on read.
+    ///
+    /// The resource name is dropped for resources that are people; other
+    /// resources (e.g. an API key named after its purpose) keep theirs.
+    pub fn redact_pii(mut self, resource_type: &str) -> Self {
+        self.user_name = None;
+        self.user_email = None;
+        self.resource_email = None;
+        if PERSON_RESOURCE_TYPES.contains(&resource_type) {
+            self.resource_name = None;
+        }
+        self
+    }
+}
+
+/// Audit resource types whose resource name is a person's name.
+pub const PERSON_RESOURCE_TYPES: &[&str] = &["user", "operator", "org_member", "project_member"];
+
+/// Strip email addresses from audit details (PII minimization).
+///
+/// Removes every `email` / `*_email` key, and the `name` key of any object
+/// describing a person (one with an `email` key, like `impersonator`).
+pub fn redact_pii_details(details: &serde_json::Value) -> serde_json::Value {
+    use serde_json::Value;
+
+    match details {
+        Value::Object(map) => {
+            let is_person = map.contains_key("email");
+            Value::Object(
+                map.iter()
+                    .filter(|(k, _)| {
+                        !(k.as_str() == "email"
+                            || k.ends_with("_email")
+                            || (is_person && k.as_str() == "name"))
+                    })
+                    .map(|(k, v)| (k.clone(), redact_pii_details(v)))
+                    .collect(),
+            )
+        }
+        Value::Array(items) => Value::Array(items.iter().map(redact_pii_details).collect()),
+        other => other.clone(),
+    }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 pub struct AuditLogQuery {
     pub actor_type: Option<ActorType>,
     pub user_id: Option<String>,
@@ -198,21 +372,27 @@ pub struct AuditLogQuery {
     pub auth_type: Option<String>,
     /// Filter by auth credential (API key prefix or JWT issuer)
     pub auth_credential: Option<String>,
+    /// Filter by the request that wrote the entry
+    pub request_id: Option<String>,
     /// Maximum number of items to return (default: 50, max: 100)
     pub limit: Option<i64>,
     /// Number of items to skip (default: 0)
     pub offset: Option<i64>,
+    /// Only entries for these projects. Set by handlers for callers limited
+    /// to some projects, never from the query string.
+    #[serde(skip)]
+    pub project_ids: Option<Vec<String>>,
 }
 
 impl AuditLogQuery {
     /// Get the limit, clamped to valid range
     pub fn limit(&self) -> i64 {
-        self.limit.unwrap_or(50).clamp(1, 100)
+        clamp_limit(self.limit)
     }
 
     /// Get the offset, minimum 0
     pub fn offset(&self) -> i64 {
-        self.offset.unwrap_or(0).max(0)
+        clamp_offset(self.offset)
     }
 }
 
@@ -363,7 +543,7 @@ impl AuditLog {
 
     /// Convert an action string to a past-tense verb phrase.
     /// e.g., "create_organization" -> "created organization"
-    fn action_to_verb_phrase(action: &str, resource_type: &str) -> String {
+    pub(crate) fn action_to_verb_phrase(action: &str, resource_type: &str) -> String {
         let parts: Vec<&str> = action.split('_').collect();
         if parts.is_empty() {
             return action.to_string();
@@ -389,6 +569,7 @@ impl AuditLog {
             "delete" => "deleted",
             "revoke" => "revoked",
             "generate" => "generated",
+            "export" => "exported",
             "refresh" => "refreshed",
             "send" => "sent",
             "deactivate" => "deactivated",
@@ -407,6 +588,10 @@ impl AuditLog {
             "increment" => "incremented",
             "purge" => "purged",
             "restore" => "restored",
+            "grant" => "granted",
+            "enable" => "enabled",
+            "disable" => "disabled",
+            "use" => "used",
             "hard" => "hard", // hard_delete -> hard deleted
             other => other,   // Unknown verbs pass through unchanged
         }
@@ -458,6 +643,7 @@ mod tests {
             user_agent: Some("test-agent".to_string()),
             auth_type: None,
             auth_credential: None,
+            request_id: None,
         };
 
         let formatted = log.formatted();
@@ -492,6 +678,7 @@ mod tests {
             user_agent: None,
             auth_type: None,
             auth_credential: None,
+            request_id: None,
         };
 
         let formatted = log.formatted();
@@ -525,6 +712,7 @@ mod tests {
             user_agent: None,
             auth_type: None,
             auth_credential: None,
+            request_id: None,
         };
 
         let formatted = log.formatted();
@@ -559,6 +747,7 @@ mod tests {
             user_agent: None,
             auth_type: None,
             auth_credential: None,
+            request_id: None,
         };
 
         let formatted = log.formatted();
@@ -598,6 +787,7 @@ mod tests {
             user_agent: Some("test-agent".to_string()),
             auth_type: None,
             auth_credential: None,
+            request_id: None,
         };
 
         let formatted = log.formatted();
@@ -661,6 +851,7 @@ mod tests {
             user_agent: None,
             auth_type: None,
             auth_credential: None,
+            request_id: None,
         };
 
         let formatted = log.formatted();
@@ -723,6 +914,7 @@ mod tests {
             user_agent: None,
             auth_type: None,
             auth_credential: None,
+            request_id: None,
         };
 
         let response: AuditLogResponse = log.into();
@@ -735,4 +927,53 @@ mod tests {
         assert!(response.formatted.contains("created organization"));
         assert_eq!(response.log.id, "log12345678");
     }
+
+    #[test]
+    fn test_redact_pii_names() {
+        let names = AuditLogNames {
+            user_name: Some("John Smith".to_string()),
+            user_email: Some("john@example.com".to_string()),
+            org_name: Some("Acme Corp".to_string()),
+            ..Default::default()
+        };
+
+        let member = names
+            .clone()
+            .resource_user("Jane Doe", "jane@example.com")
+            .redact_pii("org_member");
+        assert_eq!(member.user_name, None);
+        assert_eq!(member.user_email, None);
+        assert_eq!(member.resource_name, None);
+        assert_eq!(member.resource_email, None);
+        assert_eq!(member.org_name.as_deref(), Some("Acme Corp"));
+
+        // API keys are named after their purpose, not their owner
+        let api_key = names
+            .resource_user("Jane Doe", "jane@example.com")
+            .resource("CI key".to_string())
+            .redact_pii("api_key");
+        assert_eq!(api_key.resource_name.as_deref(), Some("CI key"));
+        assert_eq!(api_key.resource_email, None);
+    }
+
+    #[test]
+    fn test_redact_pii_details() {
+        let details = serde_json::json!({
+            "email": "jane@example.com",
+            "name": "Jane Doe",
+            "target_email": "bob@example.com",
+            "role": "admin",
+            "key": { "name": "CI key", "old_email": "x@example.com" },
+            "impersonator": { "user_id": "u1", "name": "Ops", "email": "ops@example.com" },
+        });
+
+        assert_eq!(
+            redact_pii_details(&details),
+            serde_json::json!({
+                "role": "admin",
+                "key": { "name": "CI key" },
+                "impersonator": { "user_id": "u1" },
+            })
+        );
+    }
 }
@@ -0,0 +1,25 @@
+use serde::Serialize;
+
+/// An encrypted snapshot of the main and audit databases. The row records
+/// what was taken; the file itself is in the backup storage under
+/// `file_name`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Backup {
+    pub id: String,
+    pub file_name: String,
+    /// Size of the encrypted file
+    pub size_bytes: i64,
+    /// Time taken to snapshot, encrypt, and store both databases
+    pub duration_ms: i64,
+    /// Hex SHA-256 of the encrypted file
+    pub sha256: String,
+    /// "backup_key" (PAYCHECK_BACKUP_KEY_FILE) or "master_key"
+    pub encrypted_with: String,
+    /// Operator who took the backup
+    pub created_by: Option<String>,
+    pub created_at: i64,
+    /// When the backup was last verified
+    pub verified_at: Option<i64>,
+    /// Whether the last verification passed (None = never verified)
+    pub verified_ok: Option<bool>,
+}
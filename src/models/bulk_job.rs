@@ -0,0 +1,65 @@
+use serde::{Deserialize, Serialize};
+use strum::{AsRefStr, EnumString};
+
+/// Where a bulk license job stands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, AsRefStr, EnumString)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum BulkJobStatus {
+    /// Chunks are still being created
+    Running,
+    /// Every chunk was created
+    Completed,
+    /// Finished, but at least one chunk failed (or the server stopped mid-run)
+    Failed,
+    /// Stopped on request; chunks created before that are kept
+    Cancelled,
+}
+
+/// A background job creating a large number of licenses for one product, a
+/// chunk at a time.
+#[derive(Debug, Clone, Serialize)]
+pub struct BulkJob {
+    pub id: String,
+    pub project_id: String,
+    pub product_id: String,
+    pub status: BulkJobStatus,
+    /// Licenses asked for
+    pub requested: i64,
+    /// Licenses created so far
+    pub created_count: i64,
+    /// Whether the finished job offers a CSV of activation codes
+    pub activation_codes: bool,
+    /// User who started the job
+    pub created_by: Option<String>,
+    /// Why the job stopped early, other than a failed chunk
+    pub error: Option<String>,
+    /// What each license is created with
+    #[serde(skip)]
+    pub params: BulkLicenseParams,
+    pub created_at: i64,
+    pub finished_at: Option<i64>,
+}
+
+/// License settings shared by every license a bulk job creates, resolved
+/// from the request and the product when the job starts.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BulkLicenseParams {
+    pub email_hash: Option<String>,
+    pub customer_id: Option<String>,
+    pub expires_at: Option<i64>,
+    pub updates_expires_at: Option<i64>,
+    pub seats: Option<i32>,
+    pub is_trial: bool,
+}
+
+/// One chunk a bulk job attempted: the licenses it created, or why none were.
+#[derive(Debug, Clone, Serialize)]
+pub struct BulkJobChunk {
+    /// Position in the job, from 0
+    pub chunk: i64,
+    pub license_ids: Vec<String>,
+    /// Set when the chunk failed; its licenses were rolled back
+    pub error: Option<String>,
+    pub created_at: i64,
+}
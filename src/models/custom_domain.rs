@@ -0,0 +1,18 @@
+use serde::Serialize;
+
+/// A vendor hostname bound to a project (see `custom_domains`). Requests to
+/// it only resolve the project once `verified_at` is set.
+#[derive(Debug, Clone, Serialize)]
+pub struct CustomDomain {
+    /// Lowercase, without a trailing dot
+    pub hostname: String,
+    pub org_id: String,
+    pub project_id: String,
+    /// Value the vendor publishes in the `_paycheck-challenge` TXT record
+    pub verification_token: String,
+    /// When the TXT record was found (None = not verified yet)
+    pub verified_at: Option<i64>,
+    /// Operator who added the domain
+    pub created_by: Option<String>,
+    pub created_at: i64,
+}
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use strum::{AsRefStr, EnumString};
 
+use super::serialize_timestamp;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, AsRefStr, EnumString)]
 #[serde(rename_all = "lowercase")]
 #[strum(serialize_all = "lowercase")]
@@ -17,6 +19,48 @@ pub struct Device {
     pub device_type: DeviceType,
     pub name: Option<String>,
     pub jti: String,
+    #[serde(serialize_with = "serialize_timestamp")]
     pub activated_at: i64,
+    #[serde(serialize_with = "serialize_timestamp")]
     pub last_seen_at: i64,
+    /// Seat this device was activated under (team licenses only)
+    pub seat_id: Option<String>,
+    /// `kid` of the key that signed the device's token at activation
+    pub signed_with_kid: Option<String>,
+}
+
+/// Selects devices for bulk deactivation. Every field that is set must match.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DeviceFilter {
+    /// Activated at or after this time
+    #[serde(default)]
+    pub activated_after: Option<i64>,
+    /// Activated before this time
+    #[serde(default)]
+    pub activated_before: Option<i64>,
+    #[serde(default)]
+    pub device_type: Option<DeviceType>,
+    /// `device_id`s to leave activated
+    #[serde(default)]
+    pub except_device_ids: Vec<String>,
+}
+
+impl DeviceFilter {
+    /// No field narrows the devices down. `except_device_ids` doesn't count:
+    /// on its own it still matches nearly everything.
+    pub fn is_empty(&self) -> bool {
+        self.activated_after.is_none()
+            && self.activated_before.is_none()
+            && self.device_type.is_none()
+    }
+}
+
+/// Body for POST /orgs/{org_id}/projects/{project_id}/devices/deactivate-by-filter
+#[derive(Debug, Deserialize)]
+pub struct DeactivateDevicesByFilter {
+    #[serde(flatten)]
+    pub filter: DeviceFilter,
+    /// Must be true: the filter can match every license in the project
+    #[serde(default)]
+    pub confirm: bool,
 }
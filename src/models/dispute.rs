@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+use strum::{AsRefStr, EnumString};
+
+/// Where a payment dispute stands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, AsRefStr, EnumString)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum DisputeStatus {
+    /// Under review; the license is suspended meanwhile
+    Open,
+    /// Decided for the merchant; the license was reinstated
+    Won,
+    /// Decided for the customer; the license was revoked as a chargeback
+    Lost,
+}
+
+/// A chargeback against the payment a license was bought with, as reported
+/// by the payment provider's dispute webhooks.
+#[derive(Debug, Clone, Serialize)]
+pub struct Dispute {
+    pub id: String,
+    pub license_id: String,
+    pub project_id: String,
+    /// "stripe" or "lemonsqueezy"
+    pub provider: String,
+    /// The provider's dispute ID (Stripe: du_...)
+    pub provider_dispute_id: String,
+    /// Payment the dispute is against (matches the checkout's payment ID)
+    pub payment_id: String,
+    /// Disputed amount in the currency's smallest unit
+    pub amount_cents: Option<i64>,
+    pub currency: Option<String>,
+    /// The provider's reason code (e.g. "fraudulent", "product_not_received")
+    pub reason: Option<String>,
+    pub status: DisputeStatus,
+    pub created_at: i64,
+    /// When the dispute was decided (None while open)
+    pub closed_at: Option<i64>,
+}
+
+/// A dispute as it arrives in an opening webhook.
+#[derive(Debug, Clone)]
+pub struct CreateDispute {
+    pub provider_dispute_id: String,
+    pub payment_id: String,
+    pub amount_cents: Option<i64>,
+    pub currency: Option<String>,
+    pub reason: Option<String>,
+}
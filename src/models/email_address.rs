@@ -0,0 +1,336 @@
+use std::fmt;
+
+use serde::Serialize;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::error::{AppError, Result, msg};
+
+/// Longest address accepted (RFC 5321 forward-path limit less the brackets)
+const MAX_EMAIL_LEN: usize = 254;
+const MAX_LOCAL_LEN: usize = 64;
+
+/// Characters that can't appear in an unquoted local part. Quoted local parts
+/// (`"john doe"@example.com`) are valid RFC 5322 but rejected: no real
+/// customer has one, and they make normalization ambiguous.
+const LOCAL_PART_SPECIALS: &[char] = &['"', '(', ')', ',', ':', ';', '<', '>', '@', '[', '\\', ']'];
+
+/// An email address in canonical form, the only input `EmailHasher` accepts.
+///
+/// [`EmailAddress::parse`] validates the syntax and normalizes: Unicode NFC,
+/// trimmed, lowercased, display name and `mailto:` removed
+/// (`"Bob" <Bob@Example.com>` becomes `bob@example.com`), and an
+/// internationalized domain converted to punycode. Two spellings of the same
+/// mailbox parse to the same address and so to the same hash.
+#[derive(Debug, Clone, Serialize)]
+#[serde(into = "String")]
+pub struct EmailAddress {
+    normalized: String,
+    /// The input as hashes were normalized before this type existed (NFC,
+    /// lowercase, trim, nothing else), for [`EmailAddress::legacy_form`]
+    legacy: String,
+}
+
+impl EmailAddress {
+    /// Validate and normalize an email address.
+    pub fn parse(input: &str) -> Result<Self> {
+        let nfc: String = input.nfc().collect();
+        let legacy = nfc.to_lowercase().trim().to_string();
+        if legacy.is_empty() {
+            return Err(AppError::BadRequest(msg::EMAIL_EMPTY.into()));
+        }
+        let normalized = normalize(&legacy)
+            .ok_or_else(|| AppError::BadRequest(msg::INVALID_EMAIL_FORMAT.into()))?;
+        Ok(Self { normalized, legacy })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.normalized
+    }
+
+    /// The input as hashes were computed before addresses were parsed, if
+    /// that differs from the normalized form (a display name, `mailto:`, or
+    /// a Unicode domain). Rows hashed back then only match this form.
+    pub fn legacy_form(&self) -> Option<&str> {
+        (self.legacy != self.normalized).then_some(self.legacy.as_str())
+    }
+}
+
+impl PartialEq for EmailAddress {
+    fn eq(&self, other: &Self) -> bool {
+        self.normalized == other.normalized
+    }
+}
+
+impl Eq for EmailAddress {}
+
+impl fmt::Display for EmailAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.normalized)
+    }
+}
+
+impl From<EmailAddress> for String {
+    fn from(email: EmailAddress) -> Self {
+        email.normalized
+    }
+}
+
+/// Canonical form of an already NFC, lowercased, trimmed input, or None if
+/// it isn't a valid address.
+fn normalize(input: &str) -> Option<String> {
+    let address = strip_display_name(input)?;
+    let address = address.strip_prefix("mailto:").unwrap_or(address).trim();
+
+    let (local, domain) = address.rsplit_once('@')?;
+    if !is_valid_local_part(local) {
+        return None;
+    }
+    let domain = to_ascii_domain(domain)?;
+
+    let email = format!("{}@{}", local, domain);
+    (email.len() <= MAX_EMAIL_LEN).then_some(email)
+}
+
+/// `Name <addr>` to `addr`, and `addr (comment)` to `addr`. Anything else
+/// with angle brackets is malformed.
+fn strip_display_name(input: &str) -> Option<&str> {
+    if let Some(rest) = input.strip_suffix('>') {
+        let start = rest.rfind('<')?;
+        return Some(&rest[start + 1..]);
+    }
+    if input.contains(['<', '>']) {
+        return None;
+    }
+    if let Some(rest) = input.strip_suffix(')') {
+        let start = rest.rfind('(')?;
+        return Some(rest[..start].trim_end());
+    }
+    Some(input)
+}
+
+/// Unquoted dot-atom local part. Non-ASCII letters are allowed (SMTPUTF8).
+fn is_valid_local_part(local: &str) -> bool {
+    !local.is_empty()
+        && local.len() <= MAX_LOCAL_LEN
+        && !local.starts_with('.')
+        && !local.ends_with('.')
+        && !local.contains("..")
+        && !local
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || LOCAL_PART_SPECIALS.contains(&c))
+}
+
+/// Domain in ASCII (punycode for internationalized names), with at least two
+/// labels and a non-numeric TLD. IP literals (`user@[192.0.2.1]`) are rejected.
+fn to_ascii_domain(domain: &str) -> Option<String> {
+    let domain = domain.strip_suffix('.').unwrap_or(domain);
+    if domain.is_empty() || domain.starts_with('[') {
+        return None;
+    }
+    // STD3 rules: labels of letters, digits and inner hyphens, within DNS
+    // length limits
+    let ascii = idna::domain_to_ascii_strict(domain).ok()?;
+    let labels: Vec<&str> = ascii.split('.').collect();
+    let tld = labels.last()?;
+    if labels.len() < 2
+        || labels.iter().any(|l| l.is_empty())
+        || tld.bytes().all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+    Some(ascii)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalized(input: &str) -> String {
+        EmailAddress::parse(input)
+            .unwrap_or_else(|e| panic!("{:?} should parse: {}", input, e))
+            .to_string()
+    }
+
+    fn assert_rejected(input: &str) {
+        assert!(
+            EmailAddress::parse(input).is_err(),
+            "{:?} should be rejected",
+            input
+        );
+    }
+
+    #[test]
+    fn test_case_and_whitespace() {
+        assert_eq!(normalized("Bob@Example.COM"), "bob@example.com");
+        assert_eq!(normalized("  bob@example.com \t\n"), "bob@example.com");
+    }
+
+    #[test]
+    fn test_display_name_forms() {
+        for input in [
+            "Bob <bob@example.com>",
+            "\"Bob Smith\" <bob@example.com>",
+            "\"Smith, Bob\" <Bob@Example.com>",
+            "<bob@example.com>",
+            "Bob<bob@example.com>",
+            "\"Bob <boss>\" <bob@example.com>",
+            "  Bob Smith  < bob@example.com >  ",
+            "bob@example.com (Bob Smith)",
+            "mailto:bob@example.com",
+            "Bob <mailto:bob@example.com>",
+        ] {
+            assert_eq!(normalized(input), "bob@example.com", "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_unbalanced_brackets_rejected() {
+        for input in [
+            "Bob <bob@example.com",
+            "bob@example.com>",
+            "Bob bob@example.com>",
+            "<>",
+            "Bob <>",
+            "bob@example.com)",
+        ] {
+            assert_rejected(input);
+        }
+    }
+
+    #[test]
+    fn test_internationalized_domain_uses_punycode() {
+        assert_eq!(
+            normalized("bob@bücher.example"),
+            "bob@xn--bcher-kva.example"
+        );
+        assert_eq!(
+            normalized("bob@BÜCHER.example"),
+            "bob@xn--bcher-kva.example"
+        );
+        // Already-encoded domains are left alone
+        assert_eq!(
+            normalized("bob@xn--bcher-kva.example"),
+            "bob@xn--bcher-kva.example"
+        );
+        // Fullwidth characters map to ASCII under UTS #46
+        assert_eq!(normalized("bob@ｅｘａｍｐｌｅ.com"), "bob@example.com");
+    }
+
+    #[test]
+    fn test_unicode_local_part_is_nfc() {
+        let composed = "jos\u{00e9}@example.com";
+        let decomposed = "jose\u{0301}@example.com";
+        assert_eq!(normalized(decomposed), normalized(composed));
+        assert_eq!(normalized(composed), composed);
+    }
+
+    #[test]
+    fn test_valid_unusual_addresses() {
+        for input in [
+            "user+tag@example.com",
+            "first.last@example.com",
+            "o'brien@example.ie",
+            "user!#$%&'*+/=?^_`{|}~-@example.com",
+            "u@e.co",
+            "user@sub.domain.example.com",
+            "user@my-domain.example",
+            "1234@example.com",
+        ] {
+            assert_eq!(normalized(input), input.to_lowercase(), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_trailing_dot_on_domain() {
+        assert_eq!(normalized("bob@example.com."), "bob@example.com");
+    }
+
+    #[test]
+    fn test_invalid_syntax_rejected() {
+        for input in [
+            "notanemail",
+            "user@",
+            "@example.com",
+            "user@nodot",
+            "@@@@",
+            "user@@example.com",
+            "user@.com",
+            "user@com.",
+            "user@example..com",
+            "user@-example.com",
+            "user@example-.com",
+            "user@exa_mple.com",
+            "user name@example.com",
+            ".user@example.com",
+            "user.@example.com",
+            "us..er@example.com",
+            "\"quoted\"@example.com",
+            "user,other@example.com",
+            "user@[192.0.2.1]",
+            "user@192.0.2.1",
+            "user\u{0}@example.com",
+        ] {
+            assert_rejected(input);
+        }
+    }
+
+    #[test]
+    fn test_empty_input() {
+        for input in ["", "   "] {
+            let err = EmailAddress::parse(input).unwrap_err();
+            assert!(matches!(err, AppError::BadRequest(m) if m == msg::EMAIL_EMPTY));
+        }
+    }
+
+    #[test]
+    fn test_length_limits() {
+        let local = "a".repeat(MAX_LOCAL_LEN);
+        assert!(EmailAddress::parse(&format!("{}@example.com", local)).is_ok());
+        assert_rejected(&format!("a{}@example.com", local));
+
+        let label = "a".repeat(63);
+        let domain = format!("{0}.{0}.{0}.com", label);
+        assert!(EmailAddress::parse(&format!("bob@{}", domain)).is_ok());
+        assert_rejected(&format!("bob@a{}.com", label));
+        assert_rejected(&format!("{}@{}", local, domain));
+    }
+
+    #[test]
+    fn test_legacy_form() {
+        assert_eq!(
+            EmailAddress::parse(" Bob@Example.com ")
+                .unwrap()
+                .legacy_form(),
+            None
+        );
+        assert_eq!(
+            EmailAddress::parse("Bob <Bob@Example.com>")
+                .unwrap()
+                .legacy_form(),
+            Some("bob <bob@example.com>")
+        );
+        assert_eq!(
+            EmailAddress::parse("bob@Bücher.example")
+                .unwrap()
+                .legacy_form(),
+            Some("bob@bücher.example")
+        );
+    }
+
+    #[test]
+    fn test_equality_ignores_input_form() {
+        assert_eq!(
+            EmailAddress::parse("Bob <bob@example.com>").unwrap(),
+            EmailAddress::parse("BOB@EXAMPLE.COM").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_serializes_normalized() {
+        let email = EmailAddress::parse("Bob <Bob@Bücher.example>").unwrap();
+        assert_eq!(
+            serde_json::to_value(&email).unwrap(),
+            "bob@xn--bcher-kva.example"
+        );
+    }
+}
@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+use strum::{AsRefStr, EnumString};
+
+use crate::email::{EmailSendResult, EmailTrigger};
+
+/// Activation code emails kept on the admin license detail
+pub const RECENT_EMAILS_PER_LICENSE: i64 = 10;
+
+/// Stored outcome of an email attempt ([`EmailSendResult`] without its data).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, AsRefStr, EnumString)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum EmailLogResult {
+    Sent,
+    WebhookCalled,
+    Disabled,
+    NoApiKey,
+    Failed,
+}
+
+impl From<&EmailSendResult> for EmailLogResult {
+    fn from(result: &EmailSendResult) -> Self {
+        match result {
+            EmailSendResult::Sent { .. } => Self::Sent,
+            EmailSendResult::WebhookCalled => Self::WebhookCalled,
+            EmailSendResult::Disabled => Self::Disabled,
+            EmailSendResult::NoApiKey => Self::NoApiKey,
+            EmailSendResult::Failed { .. } => Self::Failed,
+        }
+    }
+}
+
+/// One activation code email attempt for a license.
+#[derive(Debug, Clone, Serialize)]
+pub struct EmailLogEntry {
+    pub id: String,
+    pub license_id: String,
+    pub project_id: String,
+    /// Hash of the recipient address (no PII stored)
+    pub to_email_hash: String,
+    pub trigger: EmailTrigger,
+    pub result: EmailLogResult,
+    /// HTTP status from Resend for failed sends (None if there was no response)
+    pub error_status: Option<u16>,
+    /// Resend's id for sent emails, for looking them up in the Resend dashboard
+    pub provider_message_id: Option<String>,
+    pub created_at: i64,
+}
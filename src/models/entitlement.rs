@@ -0,0 +1,253 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, Result, msg};
+
+/// Most customers one bulk entitlement check can cover
+pub const MAX_ENTITLEMENT_CUSTOMERS: usize = 100;
+/// Most features one bulk entitlement check can cover
+pub const MAX_ENTITLEMENT_FEATURES: usize = 50;
+/// Most structured entitlements a product can define
+pub const MAX_PRODUCT_ENTITLEMENTS: usize = 50;
+const MAX_ENTITLEMENT_NAME_LEN: usize = 64;
+const MAX_ENTITLEMENT_TEXT_LEN: usize = 200;
+
+/// A structured entitlement: a numeric limit (`"seats": 5`), a flag
+/// (`"sso": true`), or a string (`"support": "priority"`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum EntitlementValue {
+    Bool(bool),
+    Number(i64),
+    Text(String),
+}
+
+impl EntitlementValue {
+    /// Whether this value grants its entitlement. Without `min`, `false` is
+    /// the only value that doesn't; with it, only a number of at least `min`
+    /// does.
+    pub fn satisfies(&self, min: Option<i64>) -> bool {
+        match (self, min) {
+            (Self::Number(n), Some(min)) => *n >= min,
+            (_, Some(_)) => false,
+            (Self::Bool(granted), None) => *granted,
+            (Self::Number(_) | Self::Text(_), None) => true,
+        }
+    }
+}
+
+/// A product's structured entitlements by name. Products keep their flat
+/// `features` list alongside: a check for a name passes if either grants it.
+pub type Entitlements = BTreeMap<String, EntitlementValue>;
+
+/// Entitlement definitions: at most [`MAX_PRODUCT_ENTITLEMENTS`], names of
+/// 1-64 characters of `a-z`, `0-9`, `_` and `-`, non-negative numbers, and
+/// strings of at most 200 characters.
+pub fn validate_entitlements(entitlements: &Entitlements) -> Result<()> {
+    if entitlements.len() > MAX_PRODUCT_ENTITLEMENTS {
+        return Err(AppError::BadRequest(msg::TOO_MANY_ENTITLEMENTS.into()));
+    }
+    for (name, value) in entitlements {
+        if !is_valid_name(name) {
+            return Err(AppError::BadRequest(msg::ENTITLEMENT_NAME_INVALID.into()));
+        }
+        let value_valid = match value {
+            EntitlementValue::Bool(_) => true,
+            EntitlementValue::Number(n) => *n >= 0,
+            EntitlementValue::Text(text) => text.chars().count() <= MAX_ENTITLEMENT_TEXT_LEN,
+        };
+        if !value_valid {
+            return Err(AppError::BadRequest(format!(
+                "Entitlement '{}' must be true/false, a non-negative integer, or a string of at most {} characters",
+                name, MAX_ENTITLEMENT_TEXT_LEN
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn is_valid_name(name: &str) -> bool {
+    (1..=MAX_ENTITLEMENT_NAME_LEN).contains(&name.len())
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_' || b == b'-')
+}
+
+/// Entitlements proposed for a product from its `name_<number>` feature
+/// strings, for an admin to review and apply with a product update.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EntitlementSuggestion {
+    pub product_id: String,
+    pub product_name: String,
+    /// Entitlements to add, e.g. `{"team": 10}` for `team_5` and `team_10`
+    pub entitlements: Entitlements,
+    /// The features they were derived from, which can be dropped once
+    /// every app reads the entitlements
+    pub from_features: Vec<String>,
+}
+
+/// Derive numeric entitlements from feature strings ending in `_<number>`.
+/// When several share a name the largest number wins, since a product
+/// listing `team_5` and `team_10` allows 10. Names in `existing` are left
+/// out. Returns the entitlements and the features they came from.
+pub fn suggest_entitlements(
+    features: &[String],
+    existing: &Entitlements,
+) -> (Entitlements, Vec<String>) {
+    let mut suggested = Entitlements::new();
+    let mut from_features = Vec::new();
+    for feature in features {
+        let Some((name, number)) = feature.rsplit_once('_') else {
+            continue;
+        };
+        let digits = !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit());
+        if !digits || !is_valid_name(name) || existing.contains_key(name) {
+            continue;
+        }
+        let Ok(number) = number.parse::<i64>() else {
+            continue;
+        };
+        let entry = suggested
+            .entry(name.to_string())
+            .or_insert(EntitlementValue::Number(number));
+        if let EntitlementValue::Number(current) = entry
+            && number > *current
+        {
+            *current = number;
+        }
+        from_features.push(feature.clone());
+    }
+    (suggested, from_features)
+}
+
+/// An active license and what its product grants.
+#[derive(Debug, Clone)]
+pub struct LicenseGrant {
+    pub license_id: String,
+    pub customer_id: Option<String>,
+    pub product_id: String,
+    pub tier: String,
+    pub features: Vec<String>,
+    pub entitlements: Entitlements,
+    pub expires_at: Option<i64>,
+}
+
+impl LicenseGrant {
+    /// The value this grant gives `feature`, if it grants it: the product's
+    /// entitlement of that name, or `true` for a matching feature string.
+    /// With `min`, only a numeric entitlement of at least `min` counts.
+    fn value_for(&self, feature: &str, min: Option<i64>) -> Option<EntitlementValue> {
+        match self.entitlements.get(feature) {
+            Some(value) => value.satisfies(min).then(|| value.clone()),
+            None if min.is_none() && self.features.iter().any(|f| f == feature) => {
+                Some(EntitlementValue::Bool(true))
+            }
+            None => None,
+        }
+    }
+}
+
+/// Whether a customer has a feature, and which license grants it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Entitlement {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub customer_id: Option<String>,
+    pub feature: String,
+    pub entitled: bool,
+    pub license_id: Option<String>,
+    pub product_id: Option<String>,
+    pub tier: Option<String>,
+    /// Expiration of the granting license (None = perpetual or not entitled)
+    pub expires_at: Option<i64>,
+    /// The granting product's structured entitlement of this name, when it
+    /// has one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<EntitlementValue>,
+}
+
+impl Entitlement {
+    /// Check `feature` against a customer's active licenses. When several
+    /// grant it, the one that lasts longest wins, perpetual licenses first.
+    /// With `min`, only numeric entitlements of at least `min` grant it.
+    pub fn resolve<'a>(
+        customer_id: Option<&str>,
+        feature: &str,
+        min: Option<i64>,
+        grants: impl IntoIterator<Item = &'a LicenseGrant>,
+    ) -> Self {
+        let grant = grants
+            .into_iter()
+            .filter_map(|g| g.value_for(feature, min).map(|value| (g, value)))
+            .max_by_key(|(g, _)| (g.expires_at.is_none(), g.expires_at));
+        let value = grant
+            .as_ref()
+            .filter(|(g, _)| g.entitlements.contains_key(feature))
+            .map(|(_, value)| value.clone());
+        let grant = grant.map(|(g, _)| g);
+        Self {
+            customer_id: customer_id.map(str::to_string),
+            feature: feature.to_string(),
+            entitled: grant.is_some(),
+            license_id: grant.map(|g| g.license_id.clone()),
+            product_id: grant.map(|g| g.product_id.clone()),
+            tier: grant.map(|g| g.tier.clone()),
+            expires_at: grant.and_then(|g| g.expires_at),
+            value,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn features(list: &[&str]) -> Vec<String> {
+        list.iter().map(|f| f.to_string()).collect()
+    }
+
+    #[test]
+    fn test_suggests_numbers_from_feature_suffixes() {
+        let (suggested, from) = suggest_entitlements(
+            &features(&["team_5", "team_10", "max_projects_3", "sso", "export_v2"]),
+            &Entitlements::new(),
+        );
+        assert_eq!(
+            suggested,
+            Entitlements::from([
+                ("team".to_string(), EntitlementValue::Number(10)),
+                ("max_projects".to_string(), EntitlementValue::Number(3)),
+            ])
+        );
+        assert_eq!(from, features(&["team_5", "team_10", "max_projects_3"]));
+    }
+
+    #[test]
+    fn test_suggestions_skip_defined_names_and_non_numbers() {
+        let existing = Entitlements::from([("team".to_string(), EntitlementValue::Number(25))]);
+        let (suggested, from) = suggest_entitlements(
+            &features(&[
+                "team_5",
+                "Seats_5",
+                "_5",
+                "seats_",
+                "seats_1x",
+                "seats_99999999999999999999",
+            ]),
+            &existing,
+        );
+        assert!(suggested.is_empty());
+        assert!(from.is_empty());
+    }
+
+    #[test]
+    fn test_numeric_minimum() {
+        let seats = EntitlementValue::Number(5);
+        assert!(seats.satisfies(None));
+        assert!(seats.satisfies(Some(5)));
+        assert!(!seats.satisfies(Some(6)));
+        assert!(!EntitlementValue::Bool(true).satisfies(Some(1)));
+        assert!(!EntitlementValue::Text("10".into()).satisfies(Some(1)));
+        assert!(!EntitlementValue::Bool(false).satisfies(None));
+    }
+}
@@ -1,4 +1,43 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
+use strum::{AsRefStr, EnumString};
+
+use super::{UpgradeOldLicense, serialize_optional_timestamp, serialize_timestamp};
+use crate::error::{AppError, Result, msg};
+
+/// Most tags a single license can carry
+pub const MAX_TAGS_PER_LICENSE: usize = 20;
+const MAX_TAG_LEN: usize = 40;
+
+/// Max length of the customer-visible message on a revoked license
+pub const MAX_REVOKED_MESSAGE_LEN: usize = 500;
+
+/// Shortest time between writes of a license's last seen app version, so
+/// `/validate` doesn't write on every call
+pub const SEEN_VERSION_INTERVAL_SECS: i64 = 3600;
+
+/// Why a license was revoked.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, AsRefStr, EnumString,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum RevocationReason {
+    /// The purchase was refunded
+    Refund,
+    /// The customer disputed the payment with their bank
+    Chargeback,
+    /// A delayed payment method (bank debit, etc.) failed after checkout
+    PaymentFailed,
+    /// Terms-of-service violation, key sharing, etc.
+    Abuse,
+    /// Replaced by an upgrade purchase
+    Superseded,
+    /// Revoked by an admin for any other reason
+    #[default]
+    AdminOther,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct License {
@@ -12,8 +51,11 @@ pub struct License {
     pub customer_id: Option<String>,
     pub activation_count: i32,
     pub revoked: bool,
+    #[serde(serialize_with = "serialize_timestamp")]
     pub created_at: i64,
+    #[serde(serialize_with = "serialize_optional_timestamp")]
     pub expires_at: Option<i64>,
+    #[serde(serialize_with = "serialize_optional_timestamp")]
     pub updates_expires_at: Option<i64>,
     pub payment_provider: Option<String>,
     pub payment_provider_customer_id: Option<String>,
@@ -21,10 +63,157 @@ pub struct License {
     pub payment_provider_order_id: Option<String>,
     /// Soft delete timestamp (None = active, Some = deleted at this time)
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "serialize_optional_timestamp")]
     pub deleted_at: Option<i64>,
     /// Cascade depth (0 = directly deleted, >0 = cascaded from parent)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub deleted_cascade_depth: Option<i32>,
+    /// When the provider subscription was paused (None = not paused)
+    #[serde(serialize_with = "serialize_optional_timestamp")]
+    pub paused_at: Option<i64>,
+    /// Total seconds spent paused across completed pauses
+    pub paused_seconds: i64,
+    /// Assignable seats for team licenses (None = single-user license)
+    pub seats: Option<i32>,
+    /// Hourly windows in which validation was throttled for exceeding the
+    /// project's `max_validations_per_hour_per_license`
+    pub abuse_flags: i64,
+    /// When validation was last throttled (None = never flagged)
+    #[serde(serialize_with = "serialize_optional_timestamp")]
+    pub abuse_flagged_at: Option<i64>,
+    /// Approximate distinct IPs that validated in the most recently flagged window
+    pub abuse_distinct_ips: Option<i64>,
+    /// Why the license was revoked (None = not revoked)
+    pub revoked_reason: Option<RevocationReason>,
+    /// Customer-visible explanation returned by /validate and /redeem
+    pub revoked_message: Option<String>,
+    #[serde(serialize_with = "serialize_optional_timestamp")]
+    pub revoked_at: Option<i64>,
+    /// User ID of the member who revoked the license, or the payment provider
+    /// name when a webhook did
+    pub revoked_by: Option<String>,
+    /// Values the buyer entered for the product's checkout fields, by key
+    #[serde(default)]
+    pub checkout_fields: BTreeMap<String, String>,
+    /// Validation is refused while a dispute over the purchase is open;
+    /// devices are kept so they work again if the dispute is won
+    #[serde(default)]
+    pub suspended_for_dispute: bool,
+    /// Issued as a trial; a later purchase by the same buyer converts it
+    #[serde(default)]
+    pub is_trial: bool,
+    /// Trial license this paid license converted from
+    #[serde(default)]
+    pub converted_from_license_id: Option<String>,
+    /// Version of the project's terms the buyer accepted at checkout
+    #[serde(default)]
+    pub accepted_terms_version: Option<String>,
+    /// Public handle for the license, used in tokens in place of `id`
+    #[serde(default)]
+    pub license_ref: Option<String>,
+    /// Metadata sent to /buy with the purchase (e.g. utm_* parameters), for
+    /// the developer's analytics. Never shown to the customer.
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    /// When the license was marked dormant: never activated within the
+    /// project's `dormant_after_days`. Cleared by the first activation.
+    #[serde(default, serialize_with = "serialize_optional_timestamp")]
+    pub dormant_since: Option<i64>,
+    /// Sequential number for support conversations, e.g. `PRO-000123`.
+    /// Accepted by admin endpoints in place of `id`, never by public ones.
+    #[serde(default)]
+    pub reference_number: Option<String>,
+    /// App version the license was first validated or refreshed with, from
+    /// `X-Paycheck-Client-Version`
+    #[serde(default)]
+    pub first_seen_version: Option<String>,
+    /// Most recent app version reported, updated at most once an hour
+    #[serde(default)]
+    pub last_seen_version: Option<String>,
+    #[serde(default, serialize_with = "serialize_optional_timestamp")]
+    pub last_seen_version_at: Option<i64>,
+}
+
+impl License {
+    /// Whether the license has passed `expires_at`. A paused license never counts
+    /// as expired: its expiration is pushed out by the paused time on resume.
+    pub fn is_expired_for_validation(&self, now: i64) -> bool {
+        self.paused_at.is_none() && self.expires_at.is_some_and(|exp| now > exp)
+    }
+
+    /// Whether the updates window covers a build released at `released_at`
+    /// (always, for perpetual updates).
+    pub fn covers_release(&self, released_at: i64) -> bool {
+        self.updates_expires_at.is_none_or(|exp| released_at <= exp)
+    }
+
+    /// Total time spent paused, including a pause still in progress.
+    pub fn total_paused_seconds(&self, now: i64) -> i64 {
+        let current = self.paused_at.map_or(0, |at| (now - at).max(0));
+        self.paused_seconds + current
+    }
+
+    /// Whether a reported app version should be written at `now`: the
+    /// license's seen versions are updated at most once per
+    /// [`SEEN_VERSION_INTERVAL_SECS`].
+    pub fn seen_version_due(&self, now: i64) -> bool {
+        self.last_seen_version_at
+            .is_none_or(|at| now - at >= SEEN_VERSION_INTERVAL_SECS)
+    }
+
+    /// What the customer is told about a revoked license (None = not revoked).
+    pub fn revocation(&self) -> Option<Revocation> {
+        self.revoked.then(|| Revocation {
+            reason: self.revoked_reason.unwrap_or_default(),
+            message: self.revoked_message.clone(),
+            revoked_at: self.revoked_at,
+        })
+    }
+}
+
+/// Customer-facing revocation details, included in /validate and /redeem responses.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Revocation {
+    pub reason: RevocationReason,
+    pub message: Option<String>,
+    #[serde(serialize_with = "serialize_optional_timestamp")]
+    pub revoked_at: Option<i64>,
+}
+
+/// Body of `POST .../licenses/{id}/revoke` (optional; defaults to `admin_other`).
+#[derive(Debug, Default, Deserialize)]
+pub struct RevokeLicense {
+    #[serde(default)]
+    pub reason: RevocationReason,
+    /// Shown to the customer, so keep it free of internal notes
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+impl RevokeLicense {
+    pub fn new(reason: RevocationReason) -> Self {
+        Self {
+            reason,
+            message: None,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if let Some(message) = &self.message
+            && message.chars().count() > MAX_REVOKED_MESSAGE_LEN
+        {
+            return Err(AppError::BadRequest(msg::REVOKED_MESSAGE_TOO_LONG.into()));
+        }
+        Ok(())
+    }
+
+    /// Message to store: trimmed, with blank treated as none.
+    pub fn message(&self) -> Option<&str> {
+        self.message
+            .as_deref()
+            .map(str::trim)
+            .filter(|m| !m.is_empty())
+    }
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -32,6 +221,63 @@ pub struct LicenseWithProduct {
     #[serde(flatten)]
     pub license: License,
     pub product_name: String,
+    /// Sorted tag names (list queries leave this empty; see `queries::attach_license_tags`)
+    pub tags: Vec<String>,
+}
+
+/// Tags are lowercase slugs: 1-40 characters of `a-z`, `0-9`, `-` and `_`.
+pub fn validate_tag(tag: &str) -> Result<()> {
+    let valid = (1..=MAX_TAG_LEN).contains(&tag.len())
+        && tag
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-' || b == b'_');
+    if !valid {
+        return Err(AppError::BadRequest(msg::TAG_INVALID.into()));
+    }
+    Ok(())
+}
+
+/// Licenses in a project whose last reported app version is `version`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LicenseVersionCount {
+    /// None = licenses that never reported a version
+    pub version: Option<String>,
+    /// Licenses (not deleted) last seen on the version
+    pub license_count: i64,
+}
+
+/// A tag in use in a project, for autocomplete
+#[derive(Debug, Clone, Serialize)]
+pub struct LicenseTagUsage {
+    pub tag: String,
+    /// Licenses (not deleted) carrying the tag
+    pub license_count: i64,
+}
+
+/// Selects licenses in a project for bulk tagging. Every field that is set
+/// must match.
+#[derive(Debug, Default)]
+pub struct LicenseFilter {
+    pub product_id: Option<String>,
+    pub customer_id: Option<String>,
+    pub email_hash: Option<String>,
+    /// Licenses that already carry this tag
+    pub tag: Option<String>,
+    pub revoked: Option<bool>,
+    pub created_after: Option<i64>,
+    pub created_before: Option<i64>,
+}
+
+impl LicenseFilter {
+    pub fn is_empty(&self) -> bool {
+        self.product_id.is_none()
+            && self.customer_id.is_none()
+            && self.email_hash.is_none()
+            && self.tag.is_none()
+            && self.revoked.is_none()
+            && self.created_after.is_none()
+            && self.created_before.is_none()
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -52,6 +298,9 @@ pub struct CreateLicense {
     pub payment_provider_subscription_id: Option<String>,
     #[serde(default)]
     pub payment_provider_order_id: Option<String>,
+    /// Seat count for team licenses (None = single-user license)
+    #[serde(default)]
+    pub seats: Option<i32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -61,6 +310,83 @@ pub struct ActivationCode {
     pub expires_at: i64,
     pub used: bool,
     pub created_at: i64,
+    /// Seat the code was issued to (team licenses only)
+    pub seat_id: Option<String>,
+}
+
+/// A seat on a team license, held by one email address.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LicenseSeat {
+    pub id: String,
+    pub license_id: String,
+    /// Hash of the seat holder's email (no PII stored)
+    pub seat_email_hash: String,
+    pub assigned_at: i64,
+    /// When the seat was unassigned (None = active)
+    pub removed_at: Option<i64>,
+}
+
+/// A customer-facing link to a license's details page.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShareLink {
+    pub id: String,
+    pub license_id: String,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    /// Display-only email shown on the page (e.g. j***@example.com)
+    pub masked_email: Option<String>,
+    /// User who created the link
+    pub created_by: Option<String>,
+    pub expires_at: i64,
+    pub revoked: bool,
+    pub created_at: i64,
+}
+
+/// Link between an old license and the one bought to upgrade it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LicenseUpgrade {
+    pub id: String,
+    pub from_license_id: String,
+    pub to_license_id: String,
+    pub payment_session_id: Option<String>,
+    /// Days left on the old license at checkout (None = perpetual)
+    pub days_remaining: Option<i64>,
+    /// Remaining value of the old license in cents, if its product has a price
+    pub credit_cents: Option<i64>,
+    /// What was done to the old license
+    pub old_license_action: UpgradeOldLicense,
+    pub created_at: i64,
+}
+
+/// An updates-only renewal purchase applied to a license.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LicenseUpdateRenewal {
+    pub id: String,
+    pub license_id: String,
+    /// Renewal product that was bought
+    pub product_id: String,
+    pub payment_session_id: Option<String>,
+    /// Update window end before the renewal (None = perpetual)
+    pub previous_updates_expires_at: Option<i64>,
+    /// Update window end after the renewal
+    pub updates_expires_at: Option<i64>,
+    pub created_at: i64,
+}
+
+/// Trial-to-paid conversion for trials started in a time range.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TrialConversionStats {
+    /// Start of the range (inclusive, None = from the beginning)
+    pub from: Option<i64>,
+    /// End of the range (exclusive, None = up to now)
+    pub to: Option<i64>,
+    pub trials_started: i64,
+    /// Trials started in the range that a purchase has converted, whenever it happened
+    pub trials_converted: i64,
+    /// `trials_converted / trials_started` (None with no trials)
+    pub conversion_rate: Option<f64>,
+    /// Median days from trial to purchase (None with no conversions)
+    pub median_days_to_convert: Option<f64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -1,29 +1,65 @@
+mod activity;
 mod api_key;
+mod artifact;
 mod audit_log;
+mod backup;
+mod bulk_job;
+mod custom_domain;
 mod device;
+mod dispute;
+mod email_address;
+mod email_log;
+mod entitlement;
 mod license;
 mod operator;
+mod org_limit;
 mod org_member;
 mod org_service_config;
 mod organization;
 mod payment_session;
+mod prepaid_code;
 mod product;
 mod product_provider_link;
 mod project;
 mod project_member;
+mod reconciliation;
+mod storage_heartbeat;
+mod terms;
+mod timestamp;
+mod usage_geo;
 mod user;
+mod version_conflict;
+mod workspace;
 
+pub use activity::*;
 pub use api_key::*;
+pub use artifact::*;
 pub use audit_log::*;
+pub use backup::*;
+pub use bulk_job::*;
+pub use custom_domain::*;
 pub use device::*;
+pub use dispute::*;
+pub use email_address::*;
+pub use email_log::*;
+pub use entitlement::*;
 pub use license::*;
 pub use operator::*;
+pub use org_limit::*;
 pub use org_member::*;
 pub use org_service_config::*;
 pub use organization::*;
 pub use payment_session::*;
+pub use prepaid_code::*;
 pub use product::*;
 pub use product_provider_link::*;
 pub use project::*;
 pub use project_member::*;
+pub use reconciliation::*;
+pub use storage_heartbeat::*;
+pub use terms::*;
+pub use timestamp::*;
+pub use usage_geo::*;
 pub use user::*;
+pub use version_conflict::*;
+pub use workspace::*;
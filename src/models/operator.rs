@@ -8,13 +8,45 @@ pub enum OperatorRole {
     Owner,
     Admin,
     View,
+    /// Admin access limited to the orgs in `operator_org_scopes`
+    Partner,
 }
 
 impl OperatorRole {
+    /// The more privileged of two roles (owner > admin > partner > view). A
+    /// partner can write in its orgs, so it outranks a read-only view.
+    pub fn higher(self, other: OperatorRole) -> OperatorRole {
+        let rank = |role: OperatorRole| match role {
+            OperatorRole::Owner => 3,
+            OperatorRole::Admin => 2,
+            OperatorRole::Partner => 1,
+            OperatorRole::View => 0,
+        };
+        if rank(other) > rank(self) {
+            other
+        } else {
+            self
+        }
+    }
+
     /// Returns true if this role can manage other operators (create, update, delete)
     pub fn can_manage_operators(&self) -> bool {
         matches!(self, OperatorRole::Owner)
     }
+
+    /// Returns true if this role can manage organizations. Partners only reach
+    /// the orgs scoped to them; see `is_org_scoped`.
+    pub fn can_manage_orgs(&self) -> bool {
+        matches!(
+            self,
+            OperatorRole::Owner | OperatorRole::Admin | OperatorRole::Partner
+        )
+    }
+
+    /// Returns true if this role is restricted to the orgs assigned to it
+    pub fn is_org_scoped(&self) -> bool {
+        matches!(self, OperatorRole::Partner)
+    }
 }
 
 /// Request to grant operator role to a user
@@ -29,3 +61,17 @@ pub struct CreateOperator {
 pub struct UpdateOperator {
     pub role: Option<OperatorRole>,
 }
+
+/// An organization a partner operator is allowed to access
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperatorOrgScope {
+    pub operator_id: String,
+    pub org_id: String,
+    pub created_at: i64,
+}
+
+/// Request to give a partner operator access to an organization
+#[derive(Debug, Deserialize)]
+pub struct CreateOperatorOrgScope {
+    pub org_id: String,
+}
@@ -0,0 +1,128 @@
+use chrono::{DateTime, Datelike, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use strum::{AsRefStr, EnumString};
+
+use crate::error::{AppError, Result, msg};
+
+/// What an org limit caps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, AsRefStr, EnumString)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum OrgLimitName {
+    /// Projects that aren't soft-deleted
+    Projects,
+    /// Products that aren't soft-deleted, in any one project
+    ProductsPerProject,
+    /// Licenses created since the start of the calendar month (UTC),
+    /// including ones deleted since
+    LicensesPerMonth,
+}
+
+impl OrgLimitName {
+    pub const ALL: [Self; 3] = [
+        Self::Projects,
+        Self::ProductsPerProject,
+        Self::LicensesPerMonth,
+    ];
+}
+
+/// Operator-set limit for one org. Reaching `soft_value` only warns; creates
+/// that would go past `hard_value` are refused. A missing row or value means
+/// unlimited.
+#[derive(Debug, Clone, Serialize)]
+pub struct OrgLimit {
+    pub org_id: String,
+    pub limit_name: OrgLimitName,
+    pub soft_value: Option<i64>,
+    pub hard_value: Option<i64>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// Body for setting an org limit (both values null removes it)
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SetOrgLimit {
+    #[serde(default)]
+    pub soft_value: Option<i64>,
+    #[serde(default)]
+    pub hard_value: Option<i64>,
+}
+
+impl SetOrgLimit {
+    pub fn validate(&self) -> Result<()> {
+        if self.soft_value.is_some_and(|v| v < 0) || self.hard_value.is_some_and(|v| v < 0) {
+            return Err(AppError::BadRequest(msg::ORG_LIMIT_NEGATIVE.into()));
+        }
+        if let (Some(soft), Some(hard)) = (self.soft_value, self.hard_value)
+            && soft > hard
+        {
+            return Err(AppError::BadRequest(msg::ORG_LIMIT_SOFT_ABOVE_HARD.into()));
+        }
+        Ok(())
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.soft_value.is_none() && self.hard_value.is_none()
+    }
+}
+
+/// A limit next to the org's current usage (GET /orgs/{org_id}/limits)
+#[derive(Debug, Clone, Serialize)]
+pub struct OrgLimitUsage {
+    pub limit_name: OrgLimitName,
+    pub soft_value: Option<i64>,
+    pub hard_value: Option<i64>,
+    pub used: i64,
+    /// For `products_per_project`, the project with the most products
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project_id: Option<String>,
+    /// For `licenses_per_month`, when the current month started
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub period_start: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OrgLimitsResponse {
+    /// Every limit, unset ones (null values) included
+    pub limits: Vec<OrgLimitUsage>,
+}
+
+/// Usage after a create that reached a limit's soft value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuotaWarning {
+    pub limit_name: OrgLimitName,
+    /// Usage including what was just created
+    pub used: i64,
+    pub soft_value: i64,
+    pub hard_value: Option<i64>,
+    /// Whether this create took usage from below the soft value to it or past
+    /// it (later creates keep warning but aren't audited again)
+    pub crossed: bool,
+}
+
+impl QuotaWarning {
+    /// Value for the `X-Paycheck-Quota-Warning` header, e.g.
+    /// `licenses_per_month; used=81; soft=80; hard=100`.
+    pub fn header_value(&self) -> String {
+        let mut value = format!(
+            "{}; used={}; soft={}",
+            self.limit_name.as_ref(),
+            self.used,
+            self.soft_value
+        );
+        if let Some(hard) = self.hard_value {
+            value.push_str(&format!("; hard={}", hard));
+        }
+        value
+    }
+}
+
+/// Start of the calendar month (UTC) containing `now`.
+pub fn month_start(now: i64) -> i64 {
+    let date = DateTime::from_timestamp(now, 0).unwrap_or_default();
+    Utc.with_ymd_and_hms(date.year(), date.month(), 1, 0, 0, 0)
+        .single()
+        .map(|start| start.timestamp())
+        .unwrap_or(now)
+}
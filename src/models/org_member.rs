@@ -11,6 +11,15 @@ pub enum OrgMemberRole {
 }
 
 impl OrgMemberRole {
+    /// The more privileged of two roles (owner > admin > member).
+    pub fn higher(self, other: OrgMemberRole) -> OrgMemberRole {
+        match (self, other) {
+            (OrgMemberRole::Owner, _) | (_, OrgMemberRole::Owner) => OrgMemberRole::Owner,
+            (OrgMemberRole::Admin, _) | (_, OrgMemberRole::Admin) => OrgMemberRole::Admin,
+            _ => OrgMemberRole::Member,
+        }
+    }
+
     pub fn can_manage_members(&self) -> bool {
         matches!(self, OrgMemberRole::Owner)
     }
@@ -35,6 +44,9 @@ pub struct OrgMember {
     /// Cascade depth (0 = directly deleted, >0 = cascaded from parent)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub deleted_cascade_depth: Option<i32>,
+    /// When a scheduled removal takes effect (access continues until then)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub removal_scheduled_at: Option<i64>,
 }
 
 /// Org member with user info joined (for API responses)
@@ -56,6 +68,9 @@ pub struct OrgMemberWithUser {
     /// Cascade depth (0 = directly deleted, >0 = cascaded from parent)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub deleted_cascade_depth: Option<i32>,
+    /// When a scheduled removal takes effect (access continues until then)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub removal_scheduled_at: Option<i64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -107,7 +107,8 @@ impl std::fmt::Display for ServiceProvider {
     }
 }
 
-/// Organization service configuration (encrypted external service credentials)
+/// Organization service configuration (encrypted external service credentials).
+/// `config_encrypted` is sealed with the org's data key, not the master key.
 #[derive(Debug, Clone, Serialize)]
 pub struct OrgServiceConfig {
     pub id: String,
@@ -122,9 +123,9 @@ pub struct OrgServiceConfig {
 
 impl OrgServiceConfig {
     /// Decrypt as Stripe config. Panics if provider is not Stripe.
-    pub fn decrypt_stripe_config(&self, master_key: &MasterKey) -> Result<StripeConfig> {
+    pub fn decrypt_stripe_config(&self, org_key: &MasterKey) -> Result<StripeConfig> {
         debug_assert_eq!(self.provider, ServiceProvider::Stripe);
-        let decrypted = master_key.decrypt_private_key(&self.org_id, &self.config_encrypted)?;
+        let decrypted = org_key.decrypt_private_key(&self.org_id, &self.config_encrypted)?;
         let json = String::from_utf8(decrypted)
             .map_err(|_| AppError::Internal("Invalid UTF-8 in Stripe config".into()))?;
         let config: StripeConfig = serde_json::from_str(&json)?;
@@ -132,9 +133,9 @@ impl OrgServiceConfig {
     }
 
     /// Decrypt as LemonSqueezy config. Panics if provider is not LemonSqueezy.
-    pub fn decrypt_ls_config(&self, master_key: &MasterKey) -> Result<LemonSqueezyConfig> {
+    pub fn decrypt_ls_config(&self, org_key: &MasterKey) -> Result<LemonSqueezyConfig> {
         debug_assert_eq!(self.provider, ServiceProvider::LemonSqueezy);
-        let decrypted = master_key.decrypt_private_key(&self.org_id, &self.config_encrypted)?;
+        let decrypted = org_key.decrypt_private_key(&self.org_id, &self.config_encrypted)?;
         let json = String::from_utf8(decrypted)
             .map_err(|_| AppError::Internal("Invalid UTF-8 in LemonSqueezy config".into()))?;
         let config: LemonSqueezyConfig = serde_json::from_str(&json)?;
@@ -142,9 +143,9 @@ impl OrgServiceConfig {
     }
 
     /// Decrypt as Resend API key. Panics if provider is not Resend.
-    pub fn decrypt_resend_api_key(&self, master_key: &MasterKey) -> Result<String> {
+    pub fn decrypt_resend_api_key(&self, org_key: &MasterKey) -> Result<String> {
         debug_assert_eq!(self.provider, ServiceProvider::Resend);
-        let decrypted = master_key.decrypt_private_key(&self.org_id, &self.config_encrypted)?;
+        let decrypted = org_key.decrypt_private_key(&self.org_id, &self.config_encrypted)?;
         let api_key = String::from_utf8(decrypted)
             .map_err(|_| AppError::Internal("Invalid UTF-8 in Resend API key".into()))?;
         Ok(api_key)
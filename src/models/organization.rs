@@ -2,20 +2,37 @@ use serde::{Deserialize, Serialize};
 
 use crate::error::{AppError, Result, msg};
 use crate::models::project::{LemonSqueezyConfig, StripeConfig};
+use crate::models::{EmailAddress, serialize_optional_timestamp, serialize_timestamp};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Organization {
     pub id: String,
     pub name: String,
     pub payment_provider: Option<String>,
+    #[serde(serialize_with = "serialize_timestamp")]
     pub created_at: i64,
+    #[serde(serialize_with = "serialize_timestamp")]
     pub updated_at: i64,
     /// Soft delete timestamp (None = active, Some = deleted at this time)
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "serialize_optional_timestamp")]
     pub deleted_at: Option<i64>,
     /// Cascade depth (0 = directly deleted, >0 = cascaded from parent)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub deleted_cascade_depth: Option<i32>,
+    /// Default "from" address for email sent with the org's Resend API key
+    /// (projects can override it with their own `email_from`)
+    pub email_from: Option<String>,
+    /// Days public (end-user) audit entries are kept
+    /// (None = the instance's PUBLIC_AUDIT_LOG_RETENTION_DAYS)
+    pub public_audit_retention_days: Option<i64>,
+    /// Days internal audit entries (members, operators, system) are kept
+    /// (None = forever)
+    pub admin_audit_retention_days: Option<i64>,
+    /// Bumped by each update, including payment config changes; send it
+    /// back as `expected_version` to refuse an update if someone else
+    /// changed the org first
+    pub version: i64,
 }
 
 #[derive(Debug, Deserialize)]
@@ -35,23 +52,42 @@ impl CreateOrganization {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct UpdateOrganization {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
     /// Stripe config - use Some(config) to set, Some(None) to clear, None to leave unchanged
     #[serde(default, deserialize_with = "deserialize_optional_stripe_config")]
+    #[serde(skip_serializing)]
     pub stripe_config: Option<Option<StripeConfig>>,
     /// LemonSqueezy config - use Some(config) to set, Some(None) to clear, None to leave unchanged
     #[serde(default, deserialize_with = "deserialize_optional_ls_config")]
+    #[serde(skip_serializing)]
     pub ls_config: Option<Option<LemonSqueezyConfig>>,
     /// Resend API key for email delivery (overrides system default)
     /// Use Some(None) to clear and fall back to system default, None to leave unchanged
     #[serde(default, deserialize_with = "deserialize_optional_field")]
+    #[serde(skip_serializing)]
     pub resend_api_key: Option<Option<String>>,
     /// Payment provider ("stripe" or "lemonsqueezy")
     /// Use Some(None) to clear, None to leave unchanged
     #[serde(default, deserialize_with = "deserialize_optional_field")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub payment_provider: Option<Option<String>>,
+    /// Days public audit entries are kept
+    /// Use Some(None) to follow the instance default, None to leave unchanged
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_audit_retention_days: Option<Option<i64>>,
+    /// Days internal audit entries are kept
+    /// Use Some(None) to keep them forever, None to leave unchanged
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub admin_audit_retention_days: Option<Option<i64>>,
+    /// Refuse the update with 409 unless the org is still at this
+    /// `version`. Omitted, the update applies whatever changed in between.
+    #[serde(default, skip_serializing)]
+    pub expected_version: Option<i64>,
 }
 
 impl UpdateOrganization {
@@ -69,19 +105,84 @@ impl UpdateOrganization {
                 "payment_provider cannot be empty".into(),
             ));
         }
+        validate_audit_retention(
+            self.public_audit_retention_days,
+            self.admin_audit_retention_days,
+        )
+    }
+}
+
+/// Body of `PUT /orgs/{org_id}/email-config`.
+#[derive(Debug, Deserialize)]
+pub struct UpdateOrgEmailConfig {
+    /// Resend API key (stored encrypted)
+    /// Use Some(None) to clear and fall back to system default, None to leave unchanged
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    pub resend_api_key: Option<Option<String>>,
+    /// Default "from" address, e.g. `Acme <billing@acme.com>`
+    /// Use Some(None) to clear, None to leave unchanged
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    pub email_from: Option<Option<String>>,
+}
+
+impl UpdateOrgEmailConfig {
+    pub fn validate(&self) -> Result<()> {
+        if let Some(Some(ref key)) = self.resend_api_key
+            && key.trim().is_empty()
+        {
+            return Err(AppError::BadRequest(msg::RESEND_API_KEY_EMPTY.into()));
+        }
+        if let Some(Some(ref from)) = self.email_from {
+            EmailAddress::parse(from)?;
+        }
         Ok(())
     }
 }
 
+/// Body of `PUT /orgs/{org_id}/compliance-settings`.
+#[derive(Debug, Deserialize)]
+pub struct UpdateOrgComplianceSettings {
+    /// Days public (end-user) audit entries are kept
+    /// Use Some(None) to follow the instance default, None to leave unchanged
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    pub public_audit_retention_days: Option<Option<i64>>,
+    /// Days internal audit entries are kept
+    /// Use Some(None) to keep them forever, None to leave unchanged
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    pub admin_audit_retention_days: Option<Option<i64>>,
+}
+
+impl UpdateOrgComplianceSettings {
+    pub fn validate(&self) -> Result<()> {
+        validate_audit_retention(
+            self.public_audit_retention_days,
+            self.admin_audit_retention_days,
+        )
+    }
+}
+
+fn validate_audit_retention(public: Option<Option<i64>>, admin: Option<Option<i64>>) -> Result<()> {
+    if [public, admin]
+        .into_iter()
+        .any(|days| matches!(days, Some(Some(d)) if d < 1))
+    {
+        return Err(AppError::BadRequest(
+            msg::AUDIT_RETENTION_DAYS_INVALID.into(),
+        ));
+    }
+    Ok(())
+}
+
 /// Deserialize a field that can be:
 /// - absent (None) - leave unchanged
 /// - null (Some(None)) - clear the value
 /// - present (Some(Some(value))) - set to value
-fn deserialize_optional_field<'de, D>(
+fn deserialize_optional_field<'de, D, T>(
     deserializer: D,
-) -> std::result::Result<Option<Option<String>>, D::Error>
+) -> std::result::Result<Option<Option<T>>, D::Error>
 where
     D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
 {
     Ok(Some(Option::deserialize(deserializer)?))
 }
@@ -115,14 +216,24 @@ pub struct OrganizationPublic {
     /// Default providers by category
     /// e.g. { "payment": "stripe" }
     pub defaults: std::collections::HashMap<String, String>,
+    /// Default "from" address for the org's Resend API key
+    pub email_from: Option<String>,
+    /// Days public audit entries are kept (None = instance default)
+    pub public_audit_retention_days: Option<i64>,
+    /// Days internal audit entries are kept (None = forever)
+    pub admin_audit_retention_days: Option<i64>,
+    #[serde(serialize_with = "serialize_timestamp")]
     pub created_at: i64,
+    #[serde(serialize_with = "serialize_timestamp")]
     pub updated_at: i64,
     /// Soft delete timestamp (None = active, Some = deleted at this time)
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "serialize_optional_timestamp")]
     pub deleted_at: Option<i64>,
     /// Cascade depth (0 = directly deleted, >0 = cascaded from parent)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub deleted_cascade_depth: Option<i32>,
+    pub version: i64,
 }
 
 impl OrganizationPublic {
@@ -137,10 +248,85 @@ impl OrganizationPublic {
             name: org.name,
             configured_services,
             defaults,
+            email_from: org.email_from,
+            public_audit_retention_days: org.public_audit_retention_days,
+            admin_audit_retention_days: org.admin_audit_retention_days,
             created_at: org.created_at,
             updated_at: org.updated_at,
             deleted_at: org.deleted_at,
             deleted_cascade_depth: org.deleted_cascade_depth,
+            version: org.version,
+        }
+    }
+}
+
+/// Audit log retention in effect for an org, after instance defaults
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct AuditRetentionPolicy {
+    /// Days public audit entries are kept (None = forever)
+    pub public_audit_retention_days: Option<i64>,
+    /// Days internal audit entries are kept (None = forever)
+    pub admin_audit_retention_days: Option<i64>,
+}
+
+impl AuditRetentionPolicy {
+    /// The policy for `org`, given the instance's public retention
+    /// (PUBLIC_AUDIT_LOG_RETENTION_DAYS, 0 = forever).
+    pub fn effective(org: &Organization, default_public_days: i64) -> Self {
+        Self {
+            public_audit_retention_days: org
+                .public_audit_retention_days
+                .or((default_public_days > 0).then_some(default_public_days)),
+            admin_audit_retention_days: org.admin_audit_retention_days,
         }
     }
 }
+
+/// An org's own audit retention, as the purge applies it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrgAuditRetention {
+    pub org_id: String,
+    /// Days public entries are kept (None = instance default)
+    pub public_days: Option<i64>,
+    /// Days internal entries are kept (None = forever)
+    pub admin_days: Option<i64>,
+}
+
+/// Aggregate counts for an organization, shown on the operator org list
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct OrgStats {
+    /// Projects that aren't soft-deleted
+    pub project_count: i64,
+    /// Licenses that aren't soft-deleted, in projects that aren't either
+    pub license_count: i64,
+    /// Org members that aren't soft-deleted
+    pub member_count: i64,
+    /// Latest audit log entry or license creation for the org
+    pub last_activity_at: Option<i64>,
+    /// Of `license_count`, those marked dormant (bought but never activated)
+    pub dormant_license_count: i64,
+}
+
+impl OrgStats {
+    /// Fold in tenant counts read from an org's dedicated database.
+    pub fn add_tenant_stats(&mut self, tenant: &OrgStats) {
+        self.project_count += tenant.project_count;
+        self.license_count += tenant.license_count;
+        self.dormant_license_count += tenant.dormant_license_count;
+        self.record_activity(tenant.last_activity_at);
+    }
+
+    /// Move `last_activity_at` forward to `at` if it is later.
+    pub fn record_activity(&mut self, at: Option<i64>) {
+        self.last_activity_at = self.last_activity_at.max(at);
+    }
+}
+
+/// Organization with its aggregate counts (operator list and get endpoints)
+#[derive(Debug, Clone, Serialize)]
+pub struct OrganizationWithStats {
+    #[serde(flatten)]
+    pub org: OrganizationPublic,
+    #[serde(flatten)]
+    pub stats: OrgStats,
+}
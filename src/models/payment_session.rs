@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
 /// Payment session tracks a purchase flow from /buy to webhook completion.
@@ -14,6 +16,28 @@ pub struct PaymentSession {
     pub completed: bool,
     /// License ID created by webhook (set when checkout completes)
     pub license_id: Option<String>,
+    /// License being replaced, for upgrade checkouts
+    pub upgrade_from_license_id: Option<String>,
+    /// Days left on the old license when checkout started (None = perpetual)
+    pub upgrade_days_remaining: Option<i64>,
+    /// Remaining value of the old license in cents
+    pub upgrade_credit_cents: Option<i64>,
+    /// Validated values of the product's checkout fields, copied to the license
+    pub checkout_fields: BTreeMap<String, String>,
+    /// Provider checkout page (set once the checkout is created)
+    pub checkout_url: Option<String>,
+    /// The checkout completed without issuing anything and needs someone to
+    /// look at it (an updates-only renewal with no license to extend)
+    pub needs_review: bool,
+    /// When the buyer's app exchanged the session's claim code for a license
+    /// (POST /redeem/claim); a session's license is claimed at most once
+    pub claimed_at: Option<i64>,
+    /// Version of the project's terms the buyer accepted in /buy, copied to
+    /// the license
+    pub accepted_terms_version: Option<String>,
+    /// Metadata sent to /buy (keys in the project's `checkout_metadata_keys`),
+    /// passed to the provider and copied to the license
+    pub metadata: BTreeMap<String, String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -22,4 +46,17 @@ pub struct CreatePaymentSession {
     /// Developer-managed customer identifier (flows through to license)
     #[serde(default)]
     pub customer_id: Option<String>,
+    /// License being replaced, for upgrade checkouts
+    #[serde(default)]
+    pub upgrade_from_license_id: Option<String>,
+    #[serde(default)]
+    pub upgrade_days_remaining: Option<i64>,
+    #[serde(default)]
+    pub upgrade_credit_cents: Option<i64>,
+    #[serde(default)]
+    pub checkout_fields: BTreeMap<String, String>,
+    #[serde(default)]
+    pub accepted_terms_version: Option<String>,
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
 }
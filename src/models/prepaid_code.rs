@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, Result, msg};
+
+/// Most codes one batch can hold
+pub const MAX_PREPAID_CODES_PER_BATCH: i64 = 10_000;
+/// Longest batch label
+pub const MAX_PREPAID_BATCH_LABEL_LEN: usize = 100;
+
+/// A batch of prepaid codes (e.g. one retail shipment of printed cards).
+/// Each code can be redeemed once for a new license to `product_id`; only
+/// code hashes are stored.
+#[derive(Debug, Clone, Serialize)]
+pub struct PrepaidCodeBatch {
+    pub id: String,
+    pub project_id: String,
+    pub product_id: String,
+    pub label: Option<String>,
+    pub code_count: i64,
+    /// Codes redeemed so far
+    pub redeemed_count: i64,
+    /// User who generated the batch
+    pub created_by: Option<String>,
+    /// Codes stop working after this (None = never)
+    pub expires_at: Option<i64>,
+    /// When the codes were downloaded (they can only be downloaded once)
+    pub downloaded_at: Option<i64>,
+    /// When the batch was revoked (unredeemed codes stop working)
+    pub revoked_at: Option<i64>,
+    pub created_at: i64,
+}
+
+/// Body for generating a batch of prepaid codes
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CreatePrepaidCodes {
+    /// Number of codes (1 to 10,000)
+    pub count: i64,
+    /// Free-form note, e.g. the retailer or shipment
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Days until unredeemed codes stop working (None = never)
+    #[serde(default)]
+    pub expires_in_days: Option<i64>,
+}
+
+impl CreatePrepaidCodes {
+    pub fn validate(&self) -> Result<()> {
+        if !(1..=MAX_PREPAID_CODES_PER_BATCH).contains(&self.count) {
+            return Err(AppError::BadRequest(msg::PREPAID_CODE_COUNT_INVALID.into()));
+        }
+        if let Some(ref label) = self.label
+            && label.chars().count() > MAX_PREPAID_BATCH_LABEL_LEN
+        {
+            return Err(AppError::BadRequest(
+                msg::PREPAID_BATCH_LABEL_TOO_LONG.into(),
+            ));
+        }
+        if self.expires_in_days.is_some_and(|days| days < 1) {
+            return Err(AppError::BadRequest(
+                msg::PREPAID_CODE_EXPIRY_INVALID.into(),
+            ));
+        }
+        Ok(())
+    }
+}
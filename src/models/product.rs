@@ -1,7 +1,25 @@
+use std::collections::{BTreeMap, HashSet};
+
 use serde::{Deserialize, Deserializer, Serialize};
+use strum::{AsRefStr, EnumString};
 
+use super::{
+    EmailAddress, Entitlements, serialize_optional_timestamp, serialize_timestamp,
+    validate_entitlements,
+};
 use crate::error::{AppError, Result, msg};
 
+/// Most checkout fields a product can define. Each becomes a Stripe metadata
+/// key, and Stripe allows 50 per checkout session.
+pub const MAX_CHECKOUT_FIELDS: usize = 10;
+/// Stripe metadata keys are limited to 40 characters, including the `field_` prefix
+const MAX_CHECKOUT_FIELD_KEY_LEN: usize = 32;
+const MAX_CHECKOUT_FIELD_LABEL_LEN: usize = 100;
+/// Longest checkout field value (Stripe's metadata value limit)
+pub const MAX_CHECKOUT_FIELD_VALUE_LEN: usize = 500;
+/// Longest upsell marketing blurb
+pub const MAX_UPSELL_BLURB_LEN: usize = 280;
+
 /// Deserialize a double Option field where:
 /// - Field absent in JSON → None (don't update)
 /// - Field present with null → Some(None) (set to NULL in DB)
@@ -34,17 +52,212 @@ pub struct Product {
     /// None = disabled (all devices count regardless of activity).
     pub device_inactive_days: Option<i32>,
     pub features: Vec<String>,
-    /// Canonical price in cents (for display and future provider sync)
+    /// Structured entitlements, e.g. `{"seats": 5, "sso": true}`
+    #[serde(default)]
+    pub entitlements: Entitlements,
+    /// Canonical price in cents (for display and future provider sync).
+    /// 0 makes the product free: /buy issues its license without a provider.
     pub price_cents: Option<i64>,
     /// Currency code (e.g., "usd")
     pub currency: Option<String>,
+    #[serde(serialize_with = "serialize_timestamp")]
     pub created_at: i64,
     /// Soft delete timestamp (None = active, Some = deleted at this time)
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "serialize_optional_timestamp")]
     pub deleted_at: Option<i64>,
     /// Cascade depth (0 = directly deleted, >0 = cascaded from parent)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub deleted_cascade_depth: Option<i32>,
+    /// Seats on each license sold (team licenses). None = single-user license.
+    /// device_limit then applies per seat.
+    pub seat_count: Option<i32>,
+    /// Start of the sale window (inclusive). None = on sale from creation.
+    #[serde(serialize_with = "serialize_optional_timestamp")]
+    pub available_from: Option<i64>,
+    /// End of the sale window (exclusive). None = no end.
+    #[serde(serialize_with = "serialize_optional_timestamp")]
+    pub available_until: Option<i64>,
+    /// Custom fields the buyer fills in at checkout, stored on the license
+    pub checkout_fields: Vec<CheckoutField>,
+    /// Makes this an updates-only renewal: buying it extends the update
+    /// window of the buyer's license for this base product instead of
+    /// creating a license
+    pub extends_updates_for_product_id: Option<String>,
+    /// What a renewal checkout does when the buyer has no license for the
+    /// base product
+    pub renewal_fallback: RenewalFallback,
+    /// The upgrade to offer this product's customers in the app
+    pub upsell: Option<ProductUpsell>,
+    /// Bumped by each update; send it back as `expected_version` to refuse
+    /// an update if someone else changed the product first
+    pub version: i64,
+    /// Revoke licenses when a payment is partly refunded, not only when it
+    /// is refunded in full
+    pub revoke_on_partial_refund: bool,
+}
+
+/// An upgrade offer for in-app upsell screens ("You're on Basic - upgrade to
+/// Pro for X and Y").
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProductUpsell {
+    /// Product to upgrade to, in the same project
+    pub upgrade_to_product_id: String,
+    /// Features of the target product to call out (a subset of its features)
+    #[serde(default)]
+    pub highlight_features: Vec<String>,
+    /// Short pitch, at most [`MAX_UPSELL_BLURB_LEN`] characters
+    #[serde(default)]
+    pub marketing_blurb: Option<String>,
+}
+
+/// What an updates-only renewal does when the buyer has no active license
+/// for the base product.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, AsRefStr, EnumString,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum RenewalFallback {
+    /// Issue a license for the renewal product, as for any other purchase
+    #[default]
+    CreateLicense,
+    /// Issue nothing and flag the payment session for someone to sort out
+    ManualReview,
+}
+
+/// What a checkout field accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckoutFieldType {
+    Text,
+    /// Normalized like purchase emails
+    Email,
+    /// Any finite decimal number, stored as submitted
+    Number,
+}
+
+/// A custom field collected at checkout (company name, VAT ID, ...).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CheckoutField {
+    /// Key in the `fields` map sent to /buy and on the license:
+    /// 1-32 characters of `a-z`, `0-9` and `_`
+    pub key: String,
+    /// Display name for checkout forms and the admin UI
+    pub label: String,
+    #[serde(rename = "type")]
+    pub field_type: CheckoutFieldType,
+    #[serde(default)]
+    pub required: bool,
+}
+
+/// Where a product stands relative to its sale window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Availability {
+    Available,
+    /// Before `available_from`
+    ComingSoon,
+    /// At or after `available_until`
+    Ended,
+}
+
+impl Product {
+    /// Whether the product can be bought at `at`. Only purchases are limited by
+    /// the window - licenses created by an admin ignore it.
+    pub fn availability_at(&self, at: i64) -> Availability {
+        if self.available_from.is_some_and(|from| at < from) {
+            Availability::ComingSoon
+        } else if self.available_until.is_some_and(|until| at >= until) {
+            Availability::Ended
+        } else {
+            Availability::Available
+        }
+    }
+
+    /// Error for a purchase attempted outside the sale window, if it is.
+    pub fn check_available(&self, at: i64) -> Result<()> {
+        match self.availability_at(at) {
+            Availability::Available => Ok(()),
+            availability => Err(AppError::ProductUnavailable {
+                availability,
+                available_from: self.available_from,
+                available_until: self.available_until,
+            }),
+        }
+    }
+
+    /// Whether /buy hands out this product's license directly, one per email.
+    pub fn is_free(&self) -> bool {
+        self.price_cents == Some(0)
+    }
+
+    /// Whether buying this product extends another product's licenses.
+    pub fn is_updates_renewal(&self) -> bool {
+        self.extends_updates_for_product_id.is_some()
+    }
+
+    /// Check the `fields` submitted to /buy against this product's checkout
+    /// fields and return the values to store: trimmed, blanks dropped, and
+    /// emails normalized. Unknown keys are rejected.
+    pub fn checkout_values(
+        &self,
+        values: &BTreeMap<String, String>,
+    ) -> Result<BTreeMap<String, String>> {
+        if let Some(key) = values
+            .keys()
+            .find(|key| !self.checkout_fields.iter().any(|f| f.key == **key))
+        {
+            return Err(AppError::BadRequest(format!(
+                "Unknown checkout field '{}'",
+                key
+            )));
+        }
+
+        let mut accepted = BTreeMap::new();
+        for field in &self.checkout_fields {
+            let value = values.get(&field.key).map(|v| v.trim()).unwrap_or("");
+            if value.is_empty() {
+                if field.required {
+                    return Err(AppError::BadRequest(format!(
+                        "Missing required checkout field '{}'",
+                        field.key
+                    )));
+                }
+                continue;
+            }
+            if value.chars().count() > MAX_CHECKOUT_FIELD_VALUE_LEN {
+                return Err(AppError::BadRequest(format!(
+                    "Checkout field '{}' must be at most {} characters",
+                    field.key, MAX_CHECKOUT_FIELD_VALUE_LEN
+                )));
+            }
+            let value = match field.field_type {
+                CheckoutFieldType::Text => value.to_string(),
+                CheckoutFieldType::Email => EmailAddress::parse(value)
+                    .map_err(|_| {
+                        AppError::BadRequest(format!(
+                            "Checkout field '{}' must be an email address",
+                            field.key
+                        ))
+                    })?
+                    .into(),
+                CheckoutFieldType::Number => {
+                    if !value.parse::<f64>().is_ok_and(f64::is_finite) {
+                        return Err(AppError::BadRequest(format!(
+                            "Checkout field '{}' must be a number",
+                            field.key
+                        )));
+                    }
+                    value.to_string()
+                }
+            };
+            accepted.insert(field.key.clone(), value);
+        }
+        Ok(accepted)
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -66,9 +279,29 @@ pub struct CreateProduct {
     #[serde(default)]
     pub features: Vec<String>,
     #[serde(default)]
+    pub entitlements: Entitlements,
+    #[serde(default)]
     pub price_cents: Option<i64>,
     #[serde(default)]
     pub currency: Option<String>,
+    /// Seats on each license sold. None = single-user license.
+    #[serde(default)]
+    pub seat_count: Option<i32>,
+    /// Sale window start (Unix timestamp). None = on sale immediately.
+    #[serde(default)]
+    pub available_from: Option<i64>,
+    /// Sale window end (Unix timestamp). None = no end.
+    #[serde(default)]
+    pub available_until: Option<i64>,
+    #[serde(default)]
+    pub checkout_fields: Vec<CheckoutField>,
+    /// Base product whose licenses this product renews updates for
+    #[serde(default)]
+    pub extends_updates_for_product_id: Option<String>,
+    #[serde(default)]
+    pub renewal_fallback: RenewalFallback,
+    #[serde(default)]
+    pub revoke_on_partial_refund: bool,
 }
 
 impl CreateProduct {
@@ -79,29 +312,79 @@ impl CreateProduct {
         if self.tier.trim().is_empty() {
             return Err(AppError::BadRequest(msg::TIER_EMPTY.into()));
         }
+        validate_seat_count(self.seat_count)?;
+        validate_sale_window(self.available_from, self.available_until)?;
+        validate_checkout_fields(&self.checkout_fields)?;
+        validate_entitlements(&self.entitlements)?;
+        validate_updates_renewal(
+            self.extends_updates_for_product_id.as_deref(),
+            self.updates_exp_days,
+            self.price_cents,
+        )?;
         Ok(())
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct UpdateProduct {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub tier: Option<String>,
     #[serde(default, deserialize_with = "deserialize_optional_nullable")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub license_exp_days: Option<Option<i32>>,
     #[serde(default, deserialize_with = "deserialize_optional_nullable")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub updates_exp_days: Option<Option<i32>>,
     #[serde(default, deserialize_with = "deserialize_optional_nullable")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub activation_limit: Option<Option<i32>>,
     #[serde(default, deserialize_with = "deserialize_optional_nullable")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub device_limit: Option<Option<i32>>,
     #[serde(default, deserialize_with = "deserialize_optional_nullable")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub device_inactive_days: Option<Option<i32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub features: Option<Vec<String>>,
+    /// Replaces the whole map
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entitlements: Option<Entitlements>,
     #[serde(default, deserialize_with = "deserialize_optional_nullable")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub price_cents: Option<Option<i64>>,
     #[serde(default, deserialize_with = "deserialize_optional_nullable")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub currency: Option<Option<String>>,
+    /// Only affects licenses created afterwards
+    #[serde(default, deserialize_with = "deserialize_optional_nullable")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seat_count: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "deserialize_optional_nullable")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub available_from: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "deserialize_optional_nullable")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub available_until: Option<Option<i64>>,
+    /// Replaces the whole list. Only affects checkouts started afterwards.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checkout_fields: Option<Vec<CheckoutField>>,
+    #[serde(default, deserialize_with = "deserialize_optional_nullable")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extends_updates_for_product_id: Option<Option<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub renewal_fallback: Option<RenewalFallback>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revoke_on_partial_refund: Option<bool>,
+    /// Replaces the whole upsell; null removes it
+    #[serde(default, deserialize_with = "deserialize_optional_nullable")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upsell: Option<Option<ProductUpsell>>,
+    /// Refuse the update with 409 unless the product is still at this
+    /// `version`. Omitted, the update applies whatever changed in between.
+    #[serde(default, skip_serializing)]
+    pub expected_version: Option<i64>,
 }
 
 impl UpdateProduct {
@@ -116,6 +399,272 @@ impl UpdateProduct {
         {
             return Err(AppError::BadRequest(msg::TIER_EMPTY.into()));
         }
+        if let Some(seat_count) = self.seat_count {
+            validate_seat_count(seat_count)?;
+        }
+        if let Some(ref fields) = self.checkout_fields {
+            validate_checkout_fields(fields)?;
+        }
+        if let Some(ref entitlements) = self.entitlements {
+            validate_entitlements(entitlements)?;
+        }
+        if let Some(Some(ref upsell)) = self.upsell {
+            validate_upsell(upsell)?;
+        }
+        Ok(())
+    }
+
+    /// Validate the sale window and renewal settings that result from
+    /// applying this update to `existing` (only one bound may be changing).
+    pub fn validate_against(&self, existing: &Product) -> Result<()> {
+        validate_sale_window(
+            self.available_from.unwrap_or(existing.available_from),
+            self.available_until.unwrap_or(existing.available_until),
+        )?;
+        validate_updates_renewal(
+            self.extends_updates_for_product_id
+                .as_ref()
+                .unwrap_or(&existing.extends_updates_for_product_id)
+                .as_deref(),
+            self.updates_exp_days.unwrap_or(existing.updates_exp_days),
+            self.price_cents.unwrap_or(existing.price_cents),
+        )
+    }
+}
+
+/// An org-level product definition that projects are seeded from. Applying a
+/// template copies it into a new product, so later edits to the template (or
+/// deleting it) don't reach products already made from it.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProductTemplate {
+    pub id: String,
+    pub org_id: String,
+    pub name: String,
+    pub tier: String,
+    pub license_exp_days: Option<i32>,
+    pub updates_exp_days: Option<i32>,
+    pub activation_limit: Option<i32>,
+    pub device_limit: Option<i32>,
+    pub device_inactive_days: Option<i32>,
+    pub features: Vec<String>,
+    /// Placeholder price for the products made from this template. Provider
+    /// links are still set up per product.
+    pub price_cents: Option<i64>,
+    pub currency: Option<String>,
+    pub seat_count: Option<i32>,
+    pub checkout_fields: Vec<CheckoutField>,
+    #[serde(serialize_with = "serialize_timestamp")]
+    pub created_at: i64,
+    #[serde(serialize_with = "serialize_timestamp")]
+    pub updated_at: i64,
+}
+
+impl ProductTemplate {
+    /// The product this template creates in a project.
+    pub fn product(&self) -> CreateProduct {
+        CreateProduct {
+            name: self.name.clone(),
+            tier: self.tier.clone(),
+            license_exp_days: self.license_exp_days,
+            updates_exp_days: self.updates_exp_days,
+            activation_limit: self.activation_limit,
+            device_limit: self.device_limit,
+            device_inactive_days: self.device_inactive_days,
+            features: self.features.clone(),
+            entitlements: Entitlements::new(),
+            price_cents: self.price_cents,
+            currency: self.currency.clone(),
+            seat_count: self.seat_count,
+            available_from: None,
+            available_until: None,
+            checkout_fields: self.checkout_fields.clone(),
+            extends_updates_for_product_id: None,
+            renewal_fallback: RenewalFallback::default(),
+            revoke_on_partial_refund: false,
+        }
+    }
+}
+
+/// Body for creating a product template. Sale windows, renewals and upsells
+/// refer to a particular project, so they're set on the product afterwards.
+#[derive(Debug, Deserialize)]
+pub struct CreateProductTemplate {
+    pub name: String,
+    pub tier: String,
+    #[serde(default)]
+    pub license_exp_days: Option<i32>,
+    #[serde(default)]
+    pub updates_exp_days: Option<i32>,
+    #[serde(default)]
+    pub activation_limit: Option<i32>,
+    #[serde(default)]
+    pub device_limit: Option<i32>,
+    #[serde(default)]
+    pub device_inactive_days: Option<i32>,
+    #[serde(default)]
+    pub features: Vec<String>,
+    #[serde(default)]
+    pub price_cents: Option<i64>,
+    #[serde(default)]
+    pub currency: Option<String>,
+    #[serde(default)]
+    pub seat_count: Option<i32>,
+    #[serde(default)]
+    pub checkout_fields: Vec<CheckoutField>,
+}
+
+impl CreateProductTemplate {
+    /// The rules `CreateProduct` enforces, so every product made from the
+    /// template passes them too.
+    pub fn validate(&self) -> Result<()> {
+        if self.name.trim().is_empty() {
+            return Err(AppError::BadRequest(msg::NAME_EMPTY.into()));
+        }
+        if self.tier.trim().is_empty() {
+            return Err(AppError::BadRequest(msg::TIER_EMPTY.into()));
+        }
+        validate_seat_count(self.seat_count)?;
+        validate_checkout_fields(&self.checkout_fields)?;
+        Ok(())
+    }
+}
+
+/// Body for updating a product template. Products already made from it
+/// keep their settings.
+#[derive(Debug, Deserialize)]
+pub struct UpdateProductTemplate {
+    pub name: Option<String>,
+    pub tier: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_optional_nullable")]
+    pub license_exp_days: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "deserialize_optional_nullable")]
+    pub updates_exp_days: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "deserialize_optional_nullable")]
+    pub activation_limit: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "deserialize_optional_nullable")]
+    pub device_limit: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "deserialize_optional_nullable")]
+    pub device_inactive_days: Option<Option<i32>>,
+    pub features: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "deserialize_optional_nullable")]
+    pub price_cents: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "deserialize_optional_nullable")]
+    pub currency: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_optional_nullable")]
+    pub seat_count: Option<Option<i32>>,
+    /// Replaces the whole list
+    pub checkout_fields: Option<Vec<CheckoutField>>,
+}
+
+impl UpdateProductTemplate {
+    pub fn validate(&self) -> Result<()> {
+        if let Some(ref name) = self.name
+            && name.trim().is_empty()
+        {
+            return Err(AppError::BadRequest(msg::NAME_EMPTY.into()));
+        }
+        if let Some(ref tier) = self.tier
+            && tier.trim().is_empty()
+        {
+            return Err(AppError::BadRequest(msg::TIER_EMPTY.into()));
+        }
+        if let Some(seat_count) = self.seat_count {
+            validate_seat_count(seat_count)?;
+        }
+        if let Some(ref fields) = self.checkout_fields {
+            validate_checkout_fields(fields)?;
+        }
         Ok(())
     }
 }
+
+/// Body for POST /orgs/{org_id}/projects/{project_id}/apply-template
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApplyProductTemplates {
+    /// Template IDs, one product each, created in this order
+    pub templates: Vec<String>,
+}
+
+/// Seat counts must be positive (None disables seats).
+pub fn validate_seat_count(seat_count: Option<i32>) -> Result<()> {
+    if seat_count.is_some_and(|n| n < 1) {
+        return Err(AppError::BadRequest(msg::SEAT_COUNT_INVALID.into()));
+    }
+    Ok(())
+}
+
+/// A sale window must end after it starts (either bound may be open).
+pub fn validate_sale_window(from: Option<i64>, until: Option<i64>) -> Result<()> {
+    if let (Some(from), Some(until)) = (from, until)
+        && from >= until
+    {
+        return Err(AppError::BadRequest(msg::SALE_WINDOW_INVALID.into()));
+    }
+    Ok(())
+}
+
+/// An updates-only renewal needs an update period to add and a price (free
+/// products skip the webhook that applies renewals).
+pub fn validate_updates_renewal(
+    base_product_id: Option<&str>,
+    updates_exp_days: Option<i32>,
+    price_cents: Option<i64>,
+) -> Result<()> {
+    if base_product_id.is_none() {
+        return Ok(());
+    }
+    if !updates_exp_days.is_some_and(|days| days > 0) {
+        return Err(AppError::BadRequest(
+            msg::UPDATES_RENEWAL_NEEDS_UPDATES_DAYS.into(),
+        ));
+    }
+    if price_cents == Some(0) {
+        return Err(AppError::BadRequest(msg::UPDATES_RENEWAL_FREE.into()));
+    }
+    Ok(())
+}
+
+/// An upsell's own fields: a blurb of at most [`MAX_UPSELL_BLURB_LEN`]
+/// characters. The target and its features are checked against the project.
+pub fn validate_upsell(upsell: &ProductUpsell) -> Result<()> {
+    if upsell
+        .marketing_blurb
+        .as_ref()
+        .is_some_and(|blurb| blurb.chars().count() > MAX_UPSELL_BLURB_LEN)
+    {
+        return Err(AppError::BadRequest(msg::UPSELL_BLURB_TOO_LONG.into()));
+    }
+    Ok(())
+}
+
+/// Checkout field definitions: at most [`MAX_CHECKOUT_FIELDS`], unique
+/// lowercase keys, and non-empty labels.
+pub fn validate_checkout_fields(fields: &[CheckoutField]) -> Result<()> {
+    if fields.len() > MAX_CHECKOUT_FIELDS {
+        return Err(AppError::BadRequest(msg::TOO_MANY_CHECKOUT_FIELDS.into()));
+    }
+    let mut keys = HashSet::new();
+    for field in fields {
+        let key_valid = (1..=MAX_CHECKOUT_FIELD_KEY_LEN).contains(&field.key.len())
+            && field
+                .key
+                .bytes()
+                .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_');
+        if !key_valid {
+            return Err(AppError::BadRequest(msg::CHECKOUT_FIELD_KEY_INVALID.into()));
+        }
+        if !keys.insert(field.key.as_str()) {
+            return Err(AppError::BadRequest(
+                msg::CHECKOUT_FIELD_KEY_DUPLICATE.into(),
+            ));
+        }
+        let label = field.label.trim();
+        if label.is_empty() || label.chars().count() > MAX_CHECKOUT_FIELD_LABEL_LEN {
+            return Err(AppError::BadRequest(
+                msg::CHECKOUT_FIELD_LABEL_INVALID.into(),
+            ));
+        }
+    }
+    Ok(())
+}
@@ -1,6 +1,75 @@
+use std::collections::{BTreeMap, HashSet};
+
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use strum::{AsRefStr, EnumString};
 
 use crate::error::{AppError, Result, msg};
+use crate::jwt::{DEFAULT_ISSUER, ExpectedClaims};
+use crate::models::{serialize_optional_timestamp, serialize_timestamp};
+use crate::webhook_payloads::WebhookSchemaVersion;
+
+/// Default grace window for tokens carrying the previous `iss`/`aud` after an override change
+pub const DEFAULT_JWT_GRACE_DAYS: i64 = 7;
+
+/// Max length of a `jwt_issuer` / `jwt_audience` override
+const MAX_JWT_CLAIM_LEN: usize = 255;
+
+/// Stripe's limit for a statement descriptor, and so for any suffix
+const MAX_STATEMENT_DESCRIPTOR_LEN: usize = 22;
+
+/// Max length of a `checkout_message` (Stripe's limit for custom text)
+const MAX_CHECKOUT_MESSAGE_LEN: usize = 1200;
+
+/// Most audiences a project can allow attestations for
+const MAX_ATTESTATION_AUDIENCES: usize = 20;
+
+/// Largest `client_flags` object, as serialized JSON
+pub const MAX_CLIENT_FLAGS_BYTES: usize = 4096;
+
+/// Max length of a `min_client_version`
+const MAX_CLIENT_VERSION_LEN: usize = 50;
+
+/// Most keys a project can accept in `/buy` metadata
+const MAX_CHECKOUT_METADATA_KEYS: usize = 20;
+/// Stripe metadata keys are limited to 40 characters, including the `meta_` prefix
+const MAX_CHECKOUT_METADATA_KEY_LEN: usize = 35;
+/// Longest `/buy` metadata value (Stripe's metadata value limit)
+const MAX_CHECKOUT_METADATA_VALUE_LEN: usize = 500;
+
+/// Length bounds of an `email_webhook_secret`
+const MIN_EMAIL_WEBHOOK_SECRET_LEN: usize = 32;
+const MAX_EMAIL_WEBHOOK_SECRET_LEN: usize = 256;
+
+/// What happens to the old license when an upgrade purchase completes.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, AsRefStr, EnumString,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum UpgradeOldLicense {
+    /// Revoke the old license
+    #[default]
+    Revoke,
+    /// Keep the old license valid for the versions it covers, but end its update window
+    UpdatesOnly,
+}
+
+/// What a purchase does to the buyer's trial license when it converts it.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, AsRefStr, EnumString,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum ConvertedTrialAction {
+    /// Leave the trial as it is; it runs out on its own
+    #[default]
+    Keep,
+    /// End the trial now
+    Expire,
+    /// Revoke the trial as superseded
+    Revoke,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StripeConfig {
@@ -14,6 +83,10 @@ pub struct LemonSqueezyConfig {
     pub api_key: String,
     pub store_id: String,
     pub webhook_secret: String,
+    /// Check imported LemonSqueezy license keys with LemonSqueezy's validate
+    /// endpoint when they're redeemed, so keys disabled there stop working
+    #[serde(default)]
+    pub verify_imported_keys: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -36,14 +109,193 @@ pub struct Project {
     /// Webhook URL to POST activation data to (instead of sending email)
     /// If set, Paycheck calls this URL and dev handles email delivery themselves
     pub email_webhook_url: Option<String>,
+    #[serde(serialize_with = "serialize_timestamp")]
     pub created_at: i64,
+    #[serde(serialize_with = "serialize_timestamp")]
     pub updated_at: i64,
     /// Soft delete timestamp (None = active, Some = deleted at this time)
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "serialize_optional_timestamp")]
     pub deleted_at: Option<i64>,
     /// Cascade depth (0 = directly deleted, >0 = cascaded from parent)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub deleted_cascade_depth: Option<i32>,
+    /// JWT `iss` override (None = "paycheck")
+    pub jwt_issuer: Option<String>,
+    /// JWT `aud` override (None = project name, not verified)
+    pub jwt_audience: Option<String>,
+    /// Issuer in effect before the last override change
+    pub jwt_previous_issuer: Option<String>,
+    /// Audience in effect before the last override change
+    pub jwt_previous_audience: Option<String>,
+    /// Tokens with the previous issuer/audience are accepted until this time
+    #[serde(serialize_with = "serialize_optional_timestamp")]
+    pub jwt_previous_until: Option<i64>,
+    /// Apply a Stripe coupon for the old license's remaining value on upgrade purchases
+    pub upgrade_auto_discount: bool,
+    /// What happens to the old license when an upgrade purchase completes
+    pub upgrade_old_license: UpgradeOldLicense,
+    /// Deprecated: let `/buy` find the project from `product_id` alone, without
+    /// a public key. Will be removed in the next release.
+    pub allow_project_id_auth: bool,
+    /// Accept `GET /buy` purchase links (plain `<a href>` checkout, no JavaScript)
+    pub allow_link_checkout: bool,
+    /// `/validate` calls allowed per license per hour before it is throttled
+    /// and flagged (None = unlimited)
+    pub max_validations_per_hour_per_license: Option<i64>,
+    /// Shown after the Stripe account's prefix on card statements, e.g.
+    /// "MYAPP PRO" (None = the account's default descriptor)
+    pub statement_descriptor_suffix: Option<String>,
+    /// Have the provider email a receipt to the address given to `/buy`
+    pub receipt_email_enabled: bool,
+    /// Text on the provider's checkout page, e.g. "You will receive your
+    /// license code by email"
+    pub checkout_message: Option<String>,
+    /// `aud` values `/validate/attest` will sign attestations for, e.g. a
+    /// marketplace's verifier (empty = attestations off)
+    pub attestation_audiences: Vec<String>,
+    /// Have `/buy` refuse a second license for a buyer who already has an
+    /// active one for the product, and hand back their pending checkout
+    /// instead of opening another. Off for vendors who sell multiples.
+    pub duplicate_purchase_check: bool,
+    /// What a purchase does to the buyer's trial license
+    pub converted_trial_action: ConvertedTrialAction,
+    /// URL that gets a copy of the project's webhooks, for developing against
+    /// real traffic. Set by operators, and off after `webhook_mirror_expires_at`.
+    pub webhook_mirror_url: Option<String>,
+    #[serde(serialize_with = "serialize_optional_timestamp")]
+    pub webhook_mirror_expires_at: Option<i64>,
+    /// Have `/buy` refuse purchases that don't send the current terms version
+    /// as `accepted_terms_version` (see [`crate::models::Terms`])
+    pub require_terms_acceptance: bool,
+    /// Returned as-is by `/validate` and `/refresh`, e.g. announcements or
+    /// feature killswitches for deployed apps. Not in tokens, so a change
+    /// reaches apps on their next call.
+    pub client_flags: Map<String, Value>,
+    /// Apps reporting an older version get `update_required: true` from
+    /// `/validate` and `/refresh` (None = no minimum)
+    pub min_client_version: Option<String>,
+    /// Keys `/buy` accepts in `metadata` (e.g. `utm_source`), which are passed
+    /// to the provider and kept on the license for the developer's analytics
+    /// (empty = no metadata accepted)
+    pub checkout_metadata_keys: Vec<String>,
+    /// Count valid `/validate` calls by the client's country (see
+    /// [`crate::geoip`]). Only the country code is kept.
+    pub usage_geo_enabled: bool,
+    /// Days after purchase a license that was never activated is marked
+    /// dormant (None = never)
+    pub dormant_after_days: Option<i64>,
+    /// Send owners of newly dormant licenses a fresh activation code through
+    /// the project's email webhook
+    pub dormant_nudge_email: bool,
+    /// Payload schema version of the webhooks sent to `email_webhook_url`
+    /// (None = the latest, see [`crate::webhook_payloads`])
+    pub webhook_schema_version: Option<WebhookSchemaVersion>,
+    /// Encrypted secret that payloads POSTed to `email_webhook_url` are
+    /// signed with (None = unsigned). Only ever shown masked.
+    #[serde(skip_serializing)]
+    pub email_webhook_secret: Option<Vec<u8>>,
+    /// Bumped by each update; send it back as `expected_version` to refuse
+    /// an update if someone else changed the project first
+    pub version: i64,
+}
+
+impl Project {
+    /// Validate the `metadata` sent to `/buy`: every key must be in
+    /// `checkout_metadata_keys`, and values must fit in Stripe metadata.
+    /// Blank values are dropped.
+    pub fn checkout_metadata(
+        &self,
+        values: &BTreeMap<String, String>,
+    ) -> Result<BTreeMap<String, String>> {
+        let mut accepted = BTreeMap::new();
+        for (key, value) in values {
+            if !self.checkout_metadata_keys.contains(key) {
+                return Err(AppError::BadRequest(format!(
+                    "Metadata key '{}' is not allowed for this project",
+                    key
+                )));
+            }
+            let value = value.trim();
+            if value.is_empty() {
+                continue;
+            }
+            if value.chars().count() > MAX_CHECKOUT_METADATA_VALUE_LEN {
+                return Err(AppError::BadRequest(format!(
+                    "Metadata '{}' must be at most {} characters",
+                    key, MAX_CHECKOUT_METADATA_VALUE_LEN
+                )));
+            }
+            accepted.insert(key.clone(), value.to_string());
+        }
+        Ok(accepted)
+    }
+
+    /// Whether an app reporting `client_version` is older than
+    /// `min_client_version`. False when either is missing or isn't a version.
+    pub fn update_required(&self, client_version: Option<&str>) -> bool {
+        let (Some(min), Some(client)) = (
+            self.min_client_version
+                .as_deref()
+                .and_then(parse_client_version),
+            client_version.and_then(parse_client_version),
+        ) else {
+            return false;
+        };
+        let len = min.len().max(client.len());
+        let padded = |v: Vec<u64>| v.into_iter().chain(std::iter::repeat(0)).take(len);
+        padded(client).lt(padded(min))
+    }
+
+    /// The webhook mirror URL, if one is set and hasn't expired.
+    pub fn webhook_mirror(&self, now: i64) -> Option<&str> {
+        match self.webhook_mirror_expires_at {
+            Some(expires_at) if now < expires_at => self.webhook_mirror_url.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Schema version its webhooks are sent in.
+    pub fn webhook_schema(&self) -> WebhookSchemaVersion {
+        self.webhook_schema_version
+            .unwrap_or(WebhookSchemaVersion::LATEST)
+    }
+
+    /// `iss` claim for newly issued tokens.
+    pub fn token_issuer(&self) -> &str {
+        self.jwt_issuer.as_deref().unwrap_or(DEFAULT_ISSUER)
+    }
+
+    /// `aud` claim for newly issued tokens.
+    pub fn token_audience(&self) -> &str {
+        self.jwt_audience.as_deref().unwrap_or(&self.name)
+    }
+
+    /// Whether tokens with the previous issuer/audience are still accepted.
+    pub fn in_jwt_grace_window(&self, now: i64) -> bool {
+        self.jwt_previous_until.is_some_and(|until| now <= until)
+    }
+
+    /// Issuers/audiences accepted when verifying this project's tokens.
+    /// Audience is only checked when the project has an explicit override.
+    pub fn expected_token_claims(&self, now: i64) -> ExpectedClaims {
+        let in_grace = self.in_jwt_grace_window(now);
+
+        let mut issuers = HashSet::from([self.token_issuer().to_string()]);
+        if in_grace && let Some(ref previous) = self.jwt_previous_issuer {
+            issuers.insert(previous.clone());
+        }
+
+        let audiences = self.jwt_audience.as_ref().map(|audience| {
+            let mut audiences = HashSet::from([audience.clone()]);
+            if in_grace && let Some(ref previous) = self.jwt_previous_audience {
+                audiences.insert(previous.clone());
+            }
+            audiences
+        });
+
+        ExpectedClaims { issuers, audiences }
+    }
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -57,14 +309,51 @@ pub struct ProjectPublic {
     pub email_from: Option<String>,
     pub email_enabled: bool,
     pub email_webhook_url: Option<String>,
+    #[serde(serialize_with = "serialize_timestamp")]
     pub created_at: i64,
+    #[serde(serialize_with = "serialize_timestamp")]
     pub updated_at: i64,
     /// Soft delete timestamp (None = active, Some = deleted at this time)
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "serialize_optional_timestamp")]
     pub deleted_at: Option<i64>,
     /// Cascade depth (0 = directly deleted, >0 = cascaded from parent)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub deleted_cascade_depth: Option<i32>,
+    pub jwt_issuer: Option<String>,
+    pub jwt_audience: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jwt_previous_issuer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jwt_previous_audience: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "serialize_optional_timestamp")]
+    pub jwt_previous_until: Option<i64>,
+    pub upgrade_auto_discount: bool,
+    pub upgrade_old_license: UpgradeOldLicense,
+    pub allow_project_id_auth: bool,
+    pub allow_link_checkout: bool,
+    pub max_validations_per_hour_per_license: Option<i64>,
+    pub statement_descriptor_suffix: Option<String>,
+    pub receipt_email_enabled: bool,
+    pub checkout_message: Option<String>,
+    pub attestation_audiences: Vec<String>,
+    pub duplicate_purchase_check: bool,
+    pub converted_trial_action: ConvertedTrialAction,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub webhook_mirror_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "serialize_optional_timestamp")]
+    pub webhook_mirror_expires_at: Option<i64>,
+    pub require_terms_acceptance: bool,
+    pub client_flags: Map<String, Value>,
+    pub min_client_version: Option<String>,
+    pub checkout_metadata_keys: Vec<String>,
+    pub usage_geo_enabled: bool,
+    pub dormant_after_days: Option<i64>,
+    pub dormant_nudge_email: bool,
+    pub webhook_schema_version: Option<WebhookSchemaVersion>,
+    pub version: i64,
 }
 
 impl From<Project> for ProjectPublic {
@@ -83,6 +372,33 @@ impl From<Project> for ProjectPublic {
             updated_at: p.updated_at,
             deleted_at: p.deleted_at,
             deleted_cascade_depth: p.deleted_cascade_depth,
+            jwt_issuer: p.jwt_issuer,
+            jwt_audience: p.jwt_audience,
+            jwt_previous_issuer: p.jwt_previous_issuer,
+            jwt_previous_audience: p.jwt_previous_audience,
+            jwt_previous_until: p.jwt_previous_until,
+            upgrade_auto_discount: p.upgrade_auto_discount,
+            upgrade_old_license: p.upgrade_old_license,
+            allow_project_id_auth: p.allow_project_id_auth,
+            allow_link_checkout: p.allow_link_checkout,
+            max_validations_per_hour_per_license: p.max_validations_per_hour_per_license,
+            statement_descriptor_suffix: p.statement_descriptor_suffix,
+            receipt_email_enabled: p.receipt_email_enabled,
+            checkout_message: p.checkout_message,
+            attestation_audiences: p.attestation_audiences,
+            duplicate_purchase_check: p.duplicate_purchase_check,
+            converted_trial_action: p.converted_trial_action,
+            webhook_mirror_url: p.webhook_mirror_url,
+            webhook_mirror_expires_at: p.webhook_mirror_expires_at,
+            require_terms_acceptance: p.require_terms_acceptance,
+            client_flags: p.client_flags,
+            min_client_version: p.min_client_version,
+            checkout_metadata_keys: p.checkout_metadata_keys,
+            usage_geo_enabled: p.usage_geo_enabled,
+            dormant_after_days: p.dormant_after_days,
+            dormant_nudge_email: p.dormant_nudge_email,
+            webhook_schema_version: p.webhook_schema_version,
+            version: p.version,
         }
     }
 }
@@ -104,6 +420,57 @@ pub struct CreateProject {
     /// Webhook URL to POST activation data to (instead of sending email)
     #[serde(default)]
     pub email_webhook_url: Option<String>,
+    /// Secret to sign webhook payloads with (default: unsigned)
+    #[serde(default)]
+    pub email_webhook_secret: Option<String>,
+    /// JWT `iss` override (default: "paycheck")
+    #[serde(default)]
+    pub jwt_issuer: Option<String>,
+    /// JWT `aud` override (default: project name)
+    #[serde(default)]
+    pub jwt_audience: Option<String>,
+    /// Discount upgrade purchases by the old license's remaining value (Stripe only, default: false)
+    #[serde(default)]
+    pub upgrade_auto_discount: bool,
+    /// What happens to the old license after an upgrade (default: revoke)
+    #[serde(default)]
+    pub upgrade_old_license: UpgradeOldLicense,
+    /// Deprecated: accept `/buy` requests without a public key (default: true)
+    #[serde(default = "default_allow_project_id_auth")]
+    pub allow_project_id_auth: bool,
+    /// Accept `GET /buy` purchase links (default: false)
+    #[serde(default)]
+    pub allow_link_checkout: bool,
+    /// Hourly `/validate` limit per license (default: unlimited)
+    #[serde(default)]
+    pub max_validations_per_hour_per_license: Option<i64>,
+    /// Card statement descriptor suffix (Stripe only, default: none)
+    #[serde(default)]
+    pub statement_descriptor_suffix: Option<String>,
+    /// Email a receipt to the buyer's `/buy` address (default: false)
+    #[serde(default)]
+    pub receipt_email_enabled: bool,
+    /// Text on the checkout page (default: none)
+    #[serde(default)]
+    pub checkout_message: Option<String>,
+    /// Audiences `/validate/attest` signs attestations for (default: none)
+    #[serde(default)]
+    pub attestation_audiences: Vec<String>,
+    /// Refuse duplicate purchases and reuse pending checkouts in `/buy` (default: true)
+    #[serde(default = "default_duplicate_purchase_check")]
+    pub duplicate_purchase_check: bool,
+    /// What a purchase does to the buyer's trial license (default: keep)
+    #[serde(default)]
+    pub converted_trial_action: ConvertedTrialAction,
+    /// Require buyers to accept the current terms in `/buy` (default: false)
+    #[serde(default)]
+    pub require_terms_acceptance: bool,
+    /// Keys `/buy` accepts in `metadata` (default: none)
+    #[serde(default)]
+    pub checkout_metadata_keys: Vec<String>,
+    /// Org product templates to create the project's first products from
+    #[serde(default)]
+    pub templates: Vec<String>,
 }
 
 impl CreateProject {
@@ -116,10 +483,160 @@ impl CreateProject {
                 "license_key_prefix cannot be empty".into(),
             ));
         }
+        validate_email_webhook_secret(self.email_webhook_secret.as_deref())?;
+        validate_jwt_claim("jwt_issuer", self.jwt_issuer.as_deref())?;
+        validate_jwt_claim("jwt_audience", self.jwt_audience.as_deref())?;
+        validate_validation_limit(self.max_validations_per_hour_per_license)?;
+        validate_statement_descriptor_suffix(self.statement_descriptor_suffix.as_deref())?;
+        validate_checkout_message(self.checkout_message.as_deref())?;
+        validate_attestation_audiences(&self.attestation_audiences)?;
+        validate_checkout_metadata_keys(&self.checkout_metadata_keys)?;
         Ok(())
     }
 }
 
+/// Check a statement descriptor suffix against Stripe's rules: at most 22
+/// Latin characters, at least one letter, and none of `< > \ ' " *`.
+fn validate_statement_descriptor_suffix(value: Option<&str>) -> Result<()> {
+    const FIELD: &str = "statement_descriptor_suffix";
+    let Some(value) = value else {
+        return Ok(());
+    };
+    if value.trim().is_empty() {
+        return Err(AppError::BadRequest(format!("{} cannot be empty", FIELD)));
+    }
+    if value.chars().count() > MAX_STATEMENT_DESCRIPTOR_LEN {
+        return Err(AppError::BadRequest(format!(
+            "{} must be at most {} characters",
+            FIELD, MAX_STATEMENT_DESCRIPTOR_LEN
+        )));
+    }
+    if !value
+        .chars()
+        .all(|c| (c.is_ascii_graphic() || c == ' ') && !"<>\\'\"*".contains(c))
+    {
+        return Err(AppError::BadRequest(format!(
+            "{} can only contain letters, digits, spaces, and punctuation other than < > \\ ' \" *",
+            FIELD
+        )));
+    }
+    if !value.chars().any(|c| c.is_ascii_alphabetic()) {
+        return Err(AppError::BadRequest(format!(
+            "{} must contain at least one letter",
+            FIELD
+        )));
+    }
+    Ok(())
+}
+
+fn validate_checkout_message(value: Option<&str>) -> Result<()> {
+    const FIELD: &str = "checkout_message";
+    let Some(value) = value else {
+        return Ok(());
+    };
+    if value.trim().is_empty() {
+        return Err(AppError::BadRequest(format!("{} cannot be empty", FIELD)));
+    }
+    if value.chars().count() > MAX_CHECKOUT_MESSAGE_LEN {
+        return Err(AppError::BadRequest(format!(
+            "{} must be at most {} characters",
+            FIELD, MAX_CHECKOUT_MESSAGE_LEN
+        )));
+    }
+    Ok(())
+}
+
+/// Attestation audiences are `aud` values, so each follows the same rules as
+/// a `jwt_audience` override.
+fn validate_attestation_audiences(audiences: &[String]) -> Result<()> {
+    if audiences.len() > MAX_ATTESTATION_AUDIENCES {
+        return Err(AppError::BadRequest(format!(
+            "attestation_audiences can have at most {} entries",
+            MAX_ATTESTATION_AUDIENCES
+        )));
+    }
+    for audience in audiences {
+        validate_jwt_claim("attestation_audiences", Some(audience))?;
+    }
+    Ok(())
+}
+
+/// Metadata keys are sent to Stripe behind a `meta_` prefix, so they follow
+/// the same rules as checkout field keys, with 5 characters less.
+fn validate_checkout_metadata_keys(keys: &[String]) -> Result<()> {
+    if keys.len() > MAX_CHECKOUT_METADATA_KEYS {
+        return Err(AppError::BadRequest(
+            msg::TOO_MANY_CHECKOUT_METADATA_KEYS.into(),
+        ));
+    }
+    for key in keys {
+        let valid = (1..=MAX_CHECKOUT_METADATA_KEY_LEN).contains(&key.len())
+            && key
+                .bytes()
+                .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_');
+        if !valid {
+            return Err(AppError::BadRequest(
+                msg::CHECKOUT_METADATA_KEY_INVALID.into(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// A webhook signing secret must be long enough that masking it still
+/// hides most of it.
+fn validate_email_webhook_secret(value: Option<&str>) -> Result<()> {
+    let Some(value) = value else {
+        return Ok(());
+    };
+    if !(MIN_EMAIL_WEBHOOK_SECRET_LEN..=MAX_EMAIL_WEBHOOK_SECRET_LEN).contains(&value.len())
+        || value.chars().any(|c| c.is_whitespace() || c.is_control())
+    {
+        return Err(AppError::BadRequest(
+            msg::EMAIL_WEBHOOK_SECRET_INVALID.into(),
+        ));
+    }
+    Ok(())
+}
+
+/// A per-license validation limit must allow at least one call an hour.
+fn validate_validation_limit(limit: Option<i64>) -> Result<()> {
+    if limit.is_some_and(|n| n < 1) {
+        return Err(AppError::BadRequest(msg::VALIDATION_LIMIT_INVALID.into()));
+    }
+    Ok(())
+}
+
+/// Validate an `iss`/`aud` override as a JWT StringOrURI (RFC 7519):
+/// any value containing ':' must be a valid URI.
+fn validate_jwt_claim(field: &str, value: Option<&str>) -> Result<()> {
+    let Some(value) = value else {
+        return Ok(());
+    };
+    if value.trim().is_empty() {
+        return Err(AppError::BadRequest(format!("{} cannot be empty", field)));
+    }
+    if value.len() > MAX_JWT_CLAIM_LEN {
+        return Err(AppError::BadRequest(format!(
+            "{} must be at most {} characters",
+            field, MAX_JWT_CLAIM_LEN
+        )));
+    }
+    if value.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err(AppError::BadRequest(format!(
+            "{} cannot contain whitespace",
+            field
+        )));
+    }
+    if value.contains(':') && reqwest::Url::parse(value).is_err() {
+        return Err(AppError::BadRequest(format!(
+            "{} must be a valid URI or a plain string without ':'",
+            field
+        )));
+    }
+    Ok(())
+}
+
 fn default_prefix() -> String {
     "PC".to_string()
 }
@@ -128,6 +645,14 @@ fn default_email_enabled() -> bool {
     true
 }
 
+fn default_allow_project_id_auth() -> bool {
+    true
+}
+
+pub(crate) fn default_duplicate_purchase_check() -> bool {
+    true
+}
+
 /// Masked Stripe config for display (hides sensitive parts of keys)
 #[derive(Debug, Clone, Serialize)]
 pub struct StripeConfigMasked {
@@ -152,6 +677,7 @@ pub struct LemonSqueezyConfigMasked {
     pub api_key: String,
     pub store_id: String,
     pub webhook_secret: String,
+    pub verify_imported_keys: bool,
 }
 
 impl From<&LemonSqueezyConfig> for LemonSqueezyConfigMasked {
@@ -160,13 +686,14 @@ impl From<&LemonSqueezyConfig> for LemonSqueezyConfigMasked {
             api_key: mask_secret(&config.api_key),
             store_id: config.store_id.clone(), // Store ID is not sensitive
             webhook_secret: mask_secret(&config.webhook_secret),
+            verify_imported_keys: config.verify_imported_keys,
         }
     }
 }
 
 /// Mask a secret string, showing first 8 and last 4 characters
 /// e.g., "sk_test_abc123xyz789" -> "sk_test_...9789"
-fn mask_secret(s: &str) -> String {
+pub(crate) fn mask_secret(s: &str) -> String {
     if s.len() <= 12 {
         // Too short to meaningfully mask
         return "*".repeat(s.len().min(8));
@@ -174,21 +701,111 @@ fn mask_secret(s: &str) -> String {
     format!("{}...{}", &s[..8], &s[s.len() - 4..])
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct UpdateProject {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub license_key_prefix: Option<String>,
     /// Redirect URL (use Some(None) to clear, None to leave unchanged)
     #[serde(default, deserialize_with = "deserialize_optional_field")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub redirect_url: Option<Option<String>>,
     /// Email "from" address (use Some(None) to clear, None to leave unchanged)
     #[serde(default, deserialize_with = "deserialize_optional_field")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub email_from: Option<Option<String>>,
     /// Whether email delivery is enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub email_enabled: Option<bool>,
     /// Webhook URL (use Some(None) to clear, None to leave unchanged)
     #[serde(default, deserialize_with = "deserialize_optional_field")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub email_webhook_url: Option<Option<String>>,
+    /// Secret to sign webhook payloads with (use Some(None) to stop signing,
+    /// None to leave unchanged). Never echoed back.
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    #[serde(skip_serializing)]
+    pub email_webhook_secret: Option<Option<String>>,
+    /// JWT `iss` override (use Some(None) to reset to "paycheck", None to leave unchanged)
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jwt_issuer: Option<Option<String>>,
+    /// JWT `aud` override (use Some(None) to reset to project name, None to leave unchanged)
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jwt_audience: Option<Option<String>>,
+    /// How long tokens with the old issuer/audience stay valid after changing
+    /// either override (default: 7 days, 0 = reject immediately)
+    #[serde(skip_serializing)]
+    pub jwt_grace_days: Option<i64>,
+    /// Discount upgrade purchases by the old license's remaining value (Stripe only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upgrade_auto_discount: Option<bool>,
+    /// What happens to the old license after an upgrade
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upgrade_old_license: Option<UpgradeOldLicense>,
+    /// Deprecated: accept `/buy` requests without a public key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_project_id_auth: Option<bool>,
+    /// Accept `GET /buy` purchase links
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_link_checkout: Option<bool>,
+    /// Hourly `/validate` limit per license (use Some(None) to remove, None to leave unchanged)
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_validations_per_hour_per_license: Option<Option<i64>>,
+    /// Card statement descriptor suffix (use Some(None) to clear, None to leave unchanged)
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub statement_descriptor_suffix: Option<Option<String>>,
+    /// Email a receipt to the buyer's `/buy` address
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub receipt_email_enabled: Option<bool>,
+    /// Text on the checkout page (use Some(None) to clear, None to leave unchanged)
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checkout_message: Option<Option<String>>,
+    /// Audiences `/validate/attest` signs attestations for (replaces the list)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attestation_audiences: Option<Vec<String>>,
+    /// Refuse duplicate purchases and reuse pending checkouts in `/buy`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duplicate_purchase_check: Option<bool>,
+    /// What a purchase does to the buyer's trial license
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub converted_trial_action: Option<ConvertedTrialAction>,
+    /// Require buyers to accept the current terms in `/buy`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub require_terms_acceptance: Option<bool>,
+    /// Flags returned by `/validate` and `/refresh` (replaces the object)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_flags: Option<Map<String, Value>>,
+    /// Oldest app version not told to update (use Some(None) to clear, None to leave unchanged)
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_client_version: Option<Option<String>>,
+    /// Keys `/buy` accepts in `metadata` (replaces the list)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checkout_metadata_keys: Option<Vec<String>>,
+    /// Count valid `/validate` calls by the client's country
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage_geo_enabled: Option<bool>,
+    /// Days before a never-activated license is marked dormant (use Some(None) to stop, None to leave unchanged)
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dormant_after_days: Option<Option<i64>>,
+    /// Send owners of newly dormant licenses a fresh activation code
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dormant_nudge_email: Option<bool>,
+    /// Webhook payload schema version (use Some(None) to follow the latest, None to leave unchanged)
+    #[serde(default, deserialize_with = "deserialize_optional_field")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub webhook_schema_version: Option<Option<WebhookSchemaVersion>>,
+    /// Refuse the update with 409 unless the project is still at this
+    /// `version`. Omitted, the update applies whatever changed in between.
+    #[serde(default, skip_serializing)]
+    pub expected_version: Option<i64>,
 }
 
 impl UpdateProject {
@@ -205,19 +822,141 @@ impl UpdateProject {
                 "license_key_prefix cannot be empty".into(),
             ));
         }
+        validate_email_webhook_secret(
+            self.email_webhook_secret
+                .as_ref()
+                .and_then(Option::as_deref),
+        )?;
+        validate_jwt_claim(
+            "jwt_issuer",
+            self.jwt_issuer.as_ref().and_then(Option::as_deref),
+        )?;
+        validate_jwt_claim(
+            "jwt_audience",
+            self.jwt_audience.as_ref().and_then(Option::as_deref),
+        )?;
+        if let Some(days) = self.jwt_grace_days
+            && !(0..=365).contains(&days)
+        {
+            return Err(AppError::BadRequest(
+                "jwt_grace_days must be between 0 and 365".into(),
+            ));
+        }
+        validate_validation_limit(self.max_validations_per_hour_per_license.flatten())?;
+        validate_statement_descriptor_suffix(
+            self.statement_descriptor_suffix
+                .as_ref()
+                .and_then(Option::as_deref),
+        )?;
+        validate_checkout_message(self.checkout_message.as_ref().and_then(Option::as_deref))?;
+        if let Some(ref audiences) = self.attestation_audiences {
+            validate_attestation_audiences(audiences)?;
+        }
+        if let Some(ref flags) = self.client_flags {
+            validate_client_flags(flags)?;
+        }
+        validate_min_client_version(self.min_client_version.as_ref().and_then(Option::as_deref))?;
+        if let Some(ref keys) = self.checkout_metadata_keys {
+            validate_checkout_metadata_keys(keys)?;
+        }
+        if let Some(Some(days)) = self.dormant_after_days
+            && days < 1
+        {
+            return Err(AppError::BadRequest(msg::DORMANT_AFTER_DAYS_INVALID.into()));
+        }
         Ok(())
     }
 }
 
+/// Body for `PUT /orgs/{org_id}/projects/{project_id}/client-flags`, and its
+/// response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClientFlags {
+    /// Replaces the project's flags
+    pub client_flags: Map<String, Value>,
+    /// Oldest app version not told to update (omit or null for no minimum)
+    #[serde(default)]
+    pub min_client_version: Option<String>,
+}
+
+impl ClientFlags {
+    pub fn validate(&self) -> Result<()> {
+        validate_client_flags(&self.client_flags)?;
+        validate_min_client_version(self.min_client_version.as_deref())
+    }
+}
+
+impl From<&Project> for ClientFlags {
+    fn from(p: &Project) -> Self {
+        Self {
+            client_flags: p.client_flags.clone(),
+            min_client_version: p.min_client_version.clone(),
+        }
+    }
+}
+
+/// Flags ride along on every `/validate` call, so they're kept small.
+fn validate_client_flags(flags: &Map<String, Value>) -> Result<()> {
+    if serde_json::to_string(flags)?.len() > MAX_CLIENT_FLAGS_BYTES {
+        return Err(AppError::BadRequest(format!(
+            "client_flags must be at most {} bytes as JSON",
+            MAX_CLIENT_FLAGS_BYTES
+        )));
+    }
+    Ok(())
+}
+
+fn validate_min_client_version(value: Option<&str>) -> Result<()> {
+    let Some(value) = value else {
+        return Ok(());
+    };
+    if value.len() > MAX_CLIENT_VERSION_LEN || parse_client_version(value).is_none() {
+        return Err(AppError::BadRequest(msg::MIN_CLIENT_VERSION_INVALID.into()));
+    }
+    Ok(())
+}
+
+/// `version` as an app reported it, trimmed, if it reads as a version (see
+/// [`parse_client_version`]) made of letters, digits, `.`, `-` and `+`.
+/// Anything else is ignored rather than recorded.
+pub fn reported_client_version(version: &str) -> Option<&str> {
+    let version = version.trim();
+    let valid = version.len() <= MAX_CLIENT_VERSION_LEN
+        && version
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'-' | b'+'))
+        && parse_client_version(version).is_some();
+    valid.then_some(version)
+}
+
+/// Numeric parts of a version like "2.1.0", "v2.1" or "2.1.0-beta.1"
+/// (the pre-release or build suffix is ignored). None unless it's 1 to 4
+/// dot-separated numbers.
+fn parse_client_version(version: &str) -> Option<Vec<u64>> {
+    let version = version.trim();
+    let version = version
+        .strip_prefix(['v', 'V'])
+        .unwrap_or(version)
+        .split(['-', '+'])
+        .next()?;
+    let parts = version
+        .split('.')
+        .map(|part| part.parse().ok())
+        .collect::<Option<Vec<u64>>>()?;
+    (1..=4).contains(&parts.len()).then_some(parts)
+}
+
 /// Deserialize a field that can be:
 /// - absent (None) - leave unchanged
 /// - null (Some(None)) - clear the value
 /// - present (Some(Some(value))) - set to value
-fn deserialize_optional_field<'de, D>(
+fn deserialize_optional_field<'de, D, T>(
     deserializer: D,
-) -> std::result::Result<Option<Option<String>>, D::Error>
+) -> std::result::Result<Option<Option<T>>, D::Error>
 where
     D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
 {
     Ok(Some(Option::deserialize(deserializer)?))
 }
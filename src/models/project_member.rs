@@ -9,6 +9,17 @@ pub enum ProjectMemberRole {
     View,
 }
 
+impl ProjectMemberRole {
+    /// The more privileged of two roles.
+    pub fn higher(self, other: ProjectMemberRole) -> ProjectMemberRole {
+        if self == ProjectMemberRole::Admin || other == ProjectMemberRole::Admin {
+            ProjectMemberRole::Admin
+        } else {
+            ProjectMemberRole::View
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProjectMember {
     pub id: String,
@@ -58,3 +69,48 @@ pub struct CreateProjectMember {
 pub struct UpdateProjectMember {
     pub role: ProjectMemberRole,
 }
+
+/// Longest a temporary role can be granted for (24 hours).
+pub const MAX_TEMPORARY_ROLE_MINUTES: i64 = 24 * 60;
+
+/// Time-limited project role ("break-glass" access). Applies until
+/// `expires_at` or until revoked, whichever comes first; expiry is checked
+/// at auth time so nothing needs to clean up after it.
+#[derive(Debug, Clone, Serialize)]
+pub struct TemporaryRoleGrant {
+    pub id: String,
+    /// Internal ID - not exposed in API responses (use user_id instead)
+    #[serde(skip_serializing)]
+    pub org_member_id: String,
+    pub user_id: String,
+    pub project_id: String,
+    pub role: ProjectMemberRole,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// User ID of the member who granted the role
+    pub granted_by: String,
+    pub created_at: i64,
+    pub expires_at: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revoked_at: Option<i64>,
+    /// User ID of the member who revoked the role early
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revoked_by: Option<String>,
+}
+
+impl TemporaryRoleGrant {
+    /// Whether the grant still applies at `now`.
+    pub fn is_active(&self, now: i64) -> bool {
+        self.revoked_at.is_none() && self.expires_at > now
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GrantTemporaryRole {
+    pub role: ProjectMemberRole,
+    /// How long the role lasts, 1 to `MAX_TEMPORARY_ROLE_MINUTES`
+    pub expires_in_minutes: i64,
+    /// Why access is needed (e.g. an incident number), kept for the audit trail
+    #[serde(default)]
+    pub reason: Option<String>,
+}
@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+
+/// One pass over an org's subscription-linked licenses for one payment
+/// provider, comparing each license with the subscription the provider has.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReconciliationRun {
+    pub id: String,
+    pub org_id: String,
+    /// "stripe" or "lemonsqueezy"
+    pub provider: String,
+    /// Whether discrepancies were fixed, or only reported
+    pub applied: bool,
+    /// Operator who started the run (None = scheduled)
+    pub started_by: Option<String>,
+    pub started_at: i64,
+    pub finished_at: i64,
+    /// Licenses checked against the provider
+    pub checked: i64,
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+/// A license that disagrees with its provider subscription, or couldn't be
+/// checked.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Discrepancy {
+    pub license_id: String,
+    pub project_id: String,
+    pub subscription_id: String,
+    pub kind: DiscrepancyKind,
+    /// Status name from the provider (None when the lookup failed)
+    pub provider_status: Option<String>,
+    /// End of the paid period the provider reports
+    pub provider_period_end: Option<i64>,
+    pub license_expires_at: Option<i64>,
+    /// What the fix sets `expires_at` to (None = nothing to fix)
+    pub expected_expires_at: Option<i64>,
+    /// Whether the fix was applied in this run
+    pub fixed: bool,
+    /// Why the provider lookup failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscrepancyKind {
+    /// The subscription is paid past the license's expiration (a renewal
+    /// webhook was missed). Fixed by extending the license.
+    MissedRenewal,
+    /// The subscription is past due or ended, but the license runs past the
+    /// last paid period (or never expires). Fixed by ending the license at
+    /// the provider's period end.
+    NotRenewed,
+    /// The provider doesn't know the subscription
+    NotFound,
+    /// The provider lookup failed (rate limit, outage, bad credentials)
+    ProviderError,
+}
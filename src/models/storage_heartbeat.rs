@@ -0,0 +1,16 @@
+use serde::Serialize;
+
+/// Liveness marker for a database, kept in the database itself (see
+/// `storage_health`). The main database also keeps a copy of the audit
+/// database's row, so a replaced audit file shows up as a lower `beat`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StorageHeartbeat {
+    /// `main` or `audit`
+    pub db: String,
+    /// Database file when the server last started
+    pub path: String,
+    /// Incremented on every heartbeat and never reset
+    pub beat: i64,
+    pub started_at: i64,
+    pub beat_at: i64,
+}
@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, Result, msg};
+
+/// Longest terms version string
+pub const MAX_TERMS_VERSION_LEN: usize = 50;
+
+/// One version of a project's terms of sale (EULA, refund policy). The
+/// current version is the latest one in effect; buyers accept it at /buy.
+/// A version can't be edited once a license has accepted it: publish a new
+/// version instead.
+#[derive(Debug, Clone, Serialize)]
+pub struct Terms {
+    pub id: String,
+    pub project_id: String,
+    /// Version string buyers send as `accepted_terms_version`, e.g. "2026-01"
+    pub version: String,
+    /// Where the terms are published
+    pub url: String,
+    /// When this version takes over as the current one
+    pub effective_at: i64,
+    /// User who published the version
+    pub created_by: Option<String>,
+    pub created_at: i64,
+}
+
+/// The current terms, as shown to buyers in the catalog and /buy responses.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CurrentTerms {
+    pub version: String,
+    pub url: String,
+}
+
+impl From<Terms> for CurrentTerms {
+    fn from(terms: Terms) -> Self {
+        Self {
+            version: terms.version,
+            url: terms.url,
+        }
+    }
+}
+
+/// Body for publishing a terms version
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CreateTerms {
+    pub version: String,
+    pub url: String,
+    /// When it takes effect (default: now)
+    #[serde(default)]
+    pub effective_at: Option<i64>,
+}
+
+impl CreateTerms {
+    pub fn validate(&self) -> Result<()> {
+        let version = self.version.trim();
+        if version.is_empty() || version.chars().count() > MAX_TERMS_VERSION_LEN {
+            return Err(AppError::BadRequest(msg::TERMS_VERSION_INVALID.into()));
+        }
+        Ok(())
+    }
+}
+
+/// Body for correcting a terms version no license has accepted yet. The
+/// version string itself can't change.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateTerms {
+    pub url: Option<String>,
+    pub effective_at: Option<i64>,
+}
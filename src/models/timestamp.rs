@@ -0,0 +1,129 @@
+//! Timestamps in API responses.
+//!
+//! Models store timestamps as Unix seconds and serialize them as integers by
+//! default. A client that asks for ISO timestamps (`?ts=iso`, or an `Accept`
+//! header with `profile="iso-timestamps"`, see
+//! [`timestamp_format`](crate::middleware::timestamp_format)) gets each one as
+//! `{"unix": 1704067200, "iso": "2024-01-01T00:00:00Z"}` instead, always in UTC.
+//!
+//! Only response bodies change: the format applies while
+//! [`Json`](crate::extractors::Json) serializes a response, so audit log
+//! details, webhooks and anything else written with serde stay integers.
+
+use std::cell::Cell;
+use std::future::Future;
+
+use chrono::{DateTime, SecondsFormat};
+use serde::{Serialize, Serializer, ser::SerializeStruct};
+
+/// How timestamps are written in a response body.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimestampFormat {
+    /// Unix seconds, as integers (the default)
+    #[default]
+    Unix,
+    /// `{"unix": ..., "iso": ...}` objects with an RFC 3339 UTC string
+    Iso,
+}
+
+tokio::task_local! {
+    static REQUESTED_FORMAT: TimestampFormat;
+}
+
+thread_local! {
+    static ACTIVE_FORMAT: Cell<TimestampFormat> = const { Cell::new(TimestampFormat::Unix) };
+}
+
+impl TimestampFormat {
+    /// The format the request this task is handling asked for (Unix outside
+    /// the `timestamp_format` middleware).
+    pub fn requested() -> Self {
+        REQUESTED_FORMAT
+            .try_with(|format| *format)
+            .unwrap_or_default()
+    }
+
+    /// Run a request's future with this as its requested format.
+    pub async fn scope<F: Future>(self, future: F) -> F::Output {
+        REQUESTED_FORMAT.scope(self, future).await
+    }
+
+    /// Serialize with this format: timestamps serialized inside `f` use it.
+    pub fn apply<R>(self, f: impl FnOnce() -> R) -> R {
+        let previous = ACTIVE_FORMAT.with(|active| active.replace(self));
+        let result = f();
+        ACTIVE_FORMAT.with(|active| active.set(previous));
+        result
+    }
+}
+
+/// Unix seconds that serialize in the active [`TimestampFormat`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Timestamp(pub i64);
+
+impl Timestamp {
+    /// RFC 3339 in UTC with second precision (None if out of chrono's range).
+    pub fn to_rfc3339(self) -> Option<String> {
+        DateTime::from_timestamp(self.0, 0).map(|dt| dt.to_rfc3339_opts(SecondsFormat::Secs, true))
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match ACTIVE_FORMAT.with(Cell::get) {
+            TimestampFormat::Unix => serializer.serialize_i64(self.0),
+            TimestampFormat::Iso => {
+                let mut state = serializer.serialize_struct("Timestamp", 2)?;
+                state.serialize_field("unix", &self.0)?;
+                state.serialize_field("iso", &self.to_rfc3339())?;
+                state.end()
+            }
+        }
+    }
+}
+
+/// `serialize_with` for `i64` timestamp fields.
+pub fn serialize_timestamp<S: Serializer>(at: &i64, serializer: S) -> Result<S::Ok, S::Error> {
+    Timestamp(*at).serialize(serializer)
+}
+
+/// `serialize_with` for `Option<i64>` timestamp fields (None stays null).
+pub fn serialize_optional_timestamp<S: Serializer>(
+    at: &Option<i64>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    at.map(Timestamp).serialize(serializer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unix_by_default() {
+        assert_eq!(
+            serde_json::to_value(Timestamp(1704067200)).unwrap(),
+            serde_json::json!(1704067200)
+        );
+    }
+
+    #[test]
+    fn test_iso_agrees_with_unix() {
+        let value = TimestampFormat::Iso.apply(|| serde_json::to_value(Timestamp(1704067200)));
+        assert_eq!(
+            value.unwrap(),
+            serde_json::json!({ "unix": 1704067200, "iso": "2024-01-01T00:00:00Z" })
+        );
+    }
+
+    #[test]
+    fn test_apply_restores_previous_format() {
+        TimestampFormat::Iso.apply(|| {
+            TimestampFormat::Unix.apply(|| {
+                assert_eq!(serde_json::to_value(Timestamp(0)).unwrap(), 0);
+            });
+            assert!(serde_json::to_value(Timestamp(0)).unwrap().is_object());
+        });
+        assert_eq!(serde_json::to_value(Timestamp(0)).unwrap(), 0);
+    }
+}
@@ -0,0 +1,60 @@
+use std::collections::BTreeMap;
+
+use chrono::NaiveDate;
+use serde::Serialize;
+
+/// Valid `/validate` calls for one product from one country on one UTC day.
+/// Rows of the `usage_geo_daily` rollup; no addresses or finer locations are
+/// kept.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UsageGeoDay {
+    /// YYYY-MM-DD
+    pub date: String,
+    pub product_id: String,
+    /// ISO 3166-1 alpha-2 code
+    pub country: String,
+    pub count: i64,
+}
+
+/// Valid `/validate` calls from one country over a date range.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UsageGeoCountry {
+    pub country: String,
+    pub count: i64,
+}
+
+/// A project's usage by country over a date range, for a heatmap.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UsageGeo {
+    /// First day of the range (inclusive)
+    pub from: NaiveDate,
+    /// Last day of the range (inclusive)
+    pub to: NaiveDate,
+    /// Totals per country, most calls first
+    pub countries: Vec<UsageGeoCountry>,
+    /// The daily rollup behind the totals, oldest first
+    pub days: Vec<UsageGeoDay>,
+}
+
+impl UsageGeo {
+    pub fn from_days(from: NaiveDate, to: NaiveDate, days: Vec<UsageGeoDay>) -> Self {
+        let mut totals: BTreeMap<&str, i64> = BTreeMap::new();
+        for day in &days {
+            *totals.entry(&day.country).or_default() += day.count;
+        }
+        let mut countries: Vec<UsageGeoCountry> = totals
+            .into_iter()
+            .map(|(country, count)| UsageGeoCountry {
+                country: country.to_string(),
+                count,
+            })
+            .collect();
+        countries.sort_by(|a, b| b.count.cmp(&a.count));
+        Self {
+            from,
+            to,
+            countries,
+            days,
+        }
+    }
+}
@@ -1,56 +1,8 @@
 use serde::{Deserialize, Serialize};
 
+use super::EmailAddress;
 use crate::error::{AppError, Result, msg};
 
-/// Basic email format validation.
-///
-/// Validates that email has:
-/// - Exactly one @ symbol
-/// - Non-empty local part (before @)
-/// - Non-empty domain part (after @)
-/// - At least one dot in the domain
-///
-/// This is intentionally permissive to avoid rejecting valid but unusual emails.
-/// It's not meant to be RFC 5322 compliant - just a basic sanity check.
-fn validate_email_format(email: &str) -> Result<()> {
-    let email = email.trim();
-
-    if email.is_empty() {
-        return Err(AppError::BadRequest(msg::EMAIL_EMPTY.into()));
-    }
-
-    // Check for exactly one @
-    let parts: Vec<&str> = email.split('@').collect();
-    if parts.len() != 2 {
-        return Err(AppError::BadRequest(msg::INVALID_EMAIL_FORMAT.into()));
-    }
-
-    let local_part = parts[0];
-    let domain_part = parts[1];
-
-    // Local part cannot be empty
-    if local_part.is_empty() {
-        return Err(AppError::BadRequest(msg::INVALID_EMAIL_FORMAT.into()));
-    }
-
-    // Domain cannot be empty and must have at least one dot
-    if domain_part.is_empty() || !domain_part.contains('.') {
-        return Err(AppError::BadRequest(msg::INVALID_EMAIL_FORMAT.into()));
-    }
-
-    // Domain cannot start or end with a dot
-    if domain_part.starts_with('.') || domain_part.ends_with('.') {
-        return Err(AppError::BadRequest(msg::INVALID_EMAIL_FORMAT.into()));
-    }
-
-    // Local part cannot have spaces
-    if local_part.contains(' ') {
-        return Err(AppError::BadRequest(msg::INVALID_EMAIL_FORMAT.into()));
-    }
-
-    Ok(())
-}
-
 /// User identity - source of truth for name/email
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
@@ -67,6 +19,9 @@ pub struct User {
     /// Cascade depth (0 = directly deleted, >0 = cascaded from parent)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub deleted_cascade_depth: Option<i32>,
+    /// The user this one was merged into (merged users are soft-deleted)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merged_into: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -77,7 +32,7 @@ pub struct CreateUser {
 
 impl CreateUser {
     pub fn validate(&self) -> Result<()> {
-        validate_email_format(&self.email)?;
+        EmailAddress::parse(&self.email)?;
         if self.name.trim().is_empty() {
             return Err(AppError::BadRequest(msg::NAME_EMPTY.into()));
         }
@@ -94,7 +49,7 @@ pub struct UpdateUser {
 impl UpdateUser {
     pub fn validate(&self) -> Result<()> {
         if let Some(ref email) = self.email {
-            validate_email_format(email)?;
+            EmailAddress::parse(email)?;
         }
         if let Some(ref name) = self.name
             && name.trim().is_empty()
@@ -128,3 +83,49 @@ pub struct UserWithRoles {
     /// Org memberships
     pub memberships: Vec<UserOrgMembership>,
 }
+
+/// Body for merging a duplicate user into the user in the path
+#[derive(Debug, Deserialize)]
+pub struct MergeUsers {
+    /// The duplicate, which is soft-deleted once merged
+    pub source_user_id: String,
+}
+
+/// An org both users belonged to: the target keeps its membership with the
+/// higher of the two roles, and takes over the source's project memberships.
+#[derive(Debug, Clone, Serialize)]
+pub struct MembershipConflict {
+    pub org_id: String,
+    pub source_role: crate::models::OrgMemberRole,
+    pub target_role: crate::models::OrgMemberRole,
+    pub kept_role: crate::models::OrgMemberRole,
+    #[serde(skip_serializing)]
+    pub source_member_id: String,
+    #[serde(skip_serializing)]
+    pub target_member_id: String,
+}
+
+/// What merging one user into another moved
+#[derive(Debug, Clone, Serialize)]
+pub struct UserMerge {
+    pub source_user_id: String,
+    /// Orgs whose membership moved to the target as it was
+    pub moved_org_ids: Vec<String>,
+    /// Orgs both users belonged to
+    pub conflicts: Vec<MembershipConflict>,
+    /// The source's API keys, which now authenticate as the target
+    pub moved_api_key_ids: Vec<String>,
+    /// Moved keys renamed with their prefix because the target already had a
+    /// key by that name
+    pub renamed_api_key_ids: Vec<String>,
+    /// The target's operator role after the merge (the higher of the two)
+    pub operator_role: Option<crate::models::OperatorRole>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MergeUsersResponse {
+    /// The target user after the merge
+    pub user: UserWithRoles,
+    #[serde(flatten)]
+    pub merge: UserMerge,
+}
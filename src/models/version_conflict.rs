@@ -0,0 +1,118 @@
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::error::{AppError, Result};
+
+/// Why an update sent with a stale `expected_version` was refused: the
+/// row's version now, and how the row differs from what the update asked for.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VersionConflict {
+    pub current_version: i64,
+    /// Fields the update sets that now hold something else. Empty when the
+    /// other change already made the same edits.
+    pub conflicts: Vec<FieldConflict>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldConflict {
+    pub field: String,
+    /// The field's value now
+    pub current: Value,
+    /// The value the update would have written
+    pub requested: Value,
+}
+
+impl VersionConflict {
+    /// Compare each field `requested` sets (its serialized form, so unset
+    /// fields must be skipped) against the same field of `current`.
+    pub fn new(
+        current_version: i64,
+        current: &impl Serialize,
+        requested: &impl Serialize,
+    ) -> Result<Self> {
+        let current = to_object(current)?;
+        let conflicts = to_object(requested)?
+            .into_iter()
+            .filter_map(|(field, requested)| {
+                let current = current.get(&field).cloned().unwrap_or(Value::Null);
+                (current != requested).then_some(FieldConflict {
+                    field,
+                    current,
+                    requested,
+                })
+            })
+            .collect();
+        Ok(Self {
+            current_version,
+            conflicts,
+        })
+    }
+}
+
+/// Refuse an update made against `expected` when the row is at `current_version`.
+/// No `expected` means the caller doesn't care, so anything goes.
+pub fn check_expected_version(
+    expected: Option<i64>,
+    current_version: i64,
+    current: &impl Serialize,
+    requested: &impl Serialize,
+) -> Result<()> {
+    match expected {
+        Some(expected) if expected != current_version => Err(AppError::VersionConflict(
+            VersionConflict::new(current_version, current, requested)?,
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn to_object(value: &impl Serialize) -> Result<serde_json::Map<String, Value>> {
+    match serde_json::to_value(value)? {
+        Value::Object(fields) => Ok(fields),
+        _ => Err(AppError::Internal("Versioned row is not an object".into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_conflicts_list_only_fields_that_differ() {
+        let current = json!({"name": "Pro", "price_cents": 1900, "currency": "usd"});
+        let requested = json!({"name": "Pro", "price_cents": 2900, "tier": "pro"});
+
+        let conflict = VersionConflict::new(3, &current, &requested).unwrap();
+
+        assert_eq!(conflict.current_version, 3);
+        assert_eq!(
+            conflict.conflicts,
+            vec![
+                FieldConflict {
+                    field: "price_cents".into(),
+                    current: json!(1900),
+                    requested: json!(2900),
+                },
+                FieldConflict {
+                    field: "tier".into(),
+                    current: Value::Null,
+                    requested: json!("pro"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_missing_expected_version_never_conflicts() {
+        let row = json!({"name": "Pro"});
+        assert!(check_expected_version(None, 5, &row, &row).is_ok());
+        assert!(check_expected_version(Some(5), 5, &row, &row).is_ok());
+        assert!(matches!(
+            check_expected_version(Some(4), 5, &row, &row),
+            Err(AppError::VersionConflict(VersionConflict {
+                current_version: 5,
+                ..
+            }))
+        ));
+    }
+}
@@ -0,0 +1,71 @@
+use serde::Deserialize;
+
+use super::{
+    CreateOrganization, CreateProduct, CreateProject, CreateProviderLink, CreateUser,
+    LemonSqueezyConfig, StripeConfig, UpdateOrganization,
+};
+
+/// Body of `POST /operators/workspaces`: an org, its owner, and its projects
+/// and products, created together or not at all.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CreateWorkspace {
+    pub org: WorkspaceOrg,
+    /// Created as a new user and the org's owner, with an API key
+    pub owner: CreateUser,
+    #[serde(default)]
+    pub projects: Vec<WorkspaceProject>,
+}
+
+/// The org and its payment config
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorkspaceOrg {
+    pub name: String,
+    #[serde(default)]
+    pub stripe_config: Option<StripeConfig>,
+    #[serde(default)]
+    pub ls_config: Option<LemonSqueezyConfig>,
+    /// "stripe" or "lemonsqueezy"; its config must be given too
+    #[serde(default)]
+    pub payment_provider: Option<String>,
+}
+
+impl WorkspaceOrg {
+    pub fn create_input(&self) -> CreateOrganization {
+        CreateOrganization {
+            name: self.name.clone(),
+            owner_user_id: None,
+        }
+    }
+
+    /// The payment config as the update the org settings endpoint applies
+    pub fn payment_update(&self) -> UpdateOrganization {
+        UpdateOrganization {
+            name: None,
+            stripe_config: self.stripe_config.clone().map(Some),
+            ls_config: self.ls_config.clone().map(Some),
+            resend_api_key: None,
+            payment_provider: self.payment_provider.clone().map(Some),
+            public_audit_retention_days: None,
+            admin_audit_retention_days: None,
+            expected_version: None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WorkspaceProject {
+    #[serde(flatten)]
+    pub project: CreateProject,
+    #[serde(default)]
+    pub products: Vec<WorkspaceProduct>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WorkspaceProduct {
+    #[serde(flatten)]
+    pub product: CreateProduct,
+    #[serde(default)]
+    pub provider_links: Vec<CreateProviderLink>,
+}
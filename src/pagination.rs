@@ -4,6 +4,23 @@
 
 use serde::{Deserialize, Serialize};
 
+/// Page size when the request doesn't specify one
+pub const DEFAULT_LIMIT: i64 = 50;
+
+/// Largest page size any list endpoint returns
+pub const MAX_LIMIT: i64 = 100;
+
+/// Clamp a requested page size to 1..=MAX_LIMIT (DEFAULT_LIMIT if absent).
+/// Shared by every list query type so endpoints clamp identically.
+pub fn clamp_limit(limit: Option<i64>) -> i64 {
+    limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+}
+
+/// Clamp a requested offset to a minimum of 0.
+pub fn clamp_offset(offset: Option<i64>) -> i64 {
+    offset.unwrap_or(0).max(0)
+}
+
 /// Query parameters for paginated list endpoints.
 #[derive(Debug, Deserialize, Default)]
 pub struct PaginationQuery {
@@ -18,12 +35,12 @@ pub struct PaginationQuery {
 impl PaginationQuery {
     /// Get the limit, clamped to valid range
     pub fn limit(&self) -> i64 {
-        self.limit.unwrap_or(50).clamp(1, 100)
+        clamp_limit(self.limit)
     }
 
     /// Get the offset, minimum 0
     pub fn offset(&self) -> i64 {
-        self.offset.unwrap_or(0).max(0)
+        clamp_offset(self.offset)
     }
 }
 
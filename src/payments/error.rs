@@ -0,0 +1,155 @@
+//! Payment provider failures, classified by what fixes them.
+//!
+//! - `InvalidConfig` and `ProviderRejected` are permanent: the org's provider
+//!   settings or the product's provider link need changing. /buy answers 400.
+//! - `RateLimited`, `Timeout`, and `Network` are transient: the same request
+//!   may work later. /buy answers 503 with `Retry-After`.
+
+use reqwest::{Response, StatusCode, header};
+use serde_json::Value;
+use thiserror::Error;
+
+use super::PaymentProvider;
+
+/// Retry hint for transient failures that didn't come with a `Retry-After`
+pub const DEFAULT_RETRY_AFTER_SECS: u64 = 5;
+
+#[derive(Debug, Error)]
+pub enum PaymentError {
+    /// Our credentials or webhook secret are unusable
+    #[error("Invalid payment provider config: {0}")]
+    InvalidConfig(String),
+
+    /// The provider refused the request (unknown price ID, bad parameter), or
+    /// sent a webhook whose signature can't be read. Retrying won't help.
+    #[error("Payment provider rejected the request: {message}")]
+    ProviderRejected {
+        /// Provider's machine-readable code (e.g. Stripe's `resource_missing`)
+        code: Option<String>,
+        message: String,
+    },
+
+    /// The provider is rate limiting us
+    #[error("Payment provider rate limited the request")]
+    RateLimited { retry_after_secs: u64 },
+
+    #[error("Payment provider timed out")]
+    Timeout,
+
+    /// Couldn't reach the provider, it failed on its side (5xx), or it sent a
+    /// response we can't read
+    #[error("Payment provider unavailable: {0}")]
+    Network(String),
+}
+
+impl PaymentError {
+    /// Classify a non-2xx response from `provider`'s API.
+    pub async fn from_response(provider: PaymentProvider, response: Response) -> Self {
+        let status = response.status();
+        let retry_after = response
+            .headers()
+            .get(header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok());
+        let body = response.text().await.unwrap_or_default();
+        Self::from_status(provider, status, retry_after, &body).logged(provider)
+    }
+
+    /// Classify a response by status, `Retry-After` seconds, and error body.
+    pub fn from_status(
+        provider: PaymentProvider,
+        status: StatusCode,
+        retry_after: Option<u64>,
+        body: &str,
+    ) -> Self {
+        let (code, message) = error_details(provider, body);
+        let message = message.unwrap_or_else(|| format!("HTTP {}", status.as_u16()));
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Self::InvalidConfig(message),
+            StatusCode::TOO_MANY_REQUESTS => Self::RateLimited {
+                retry_after_secs: retry_after.unwrap_or(DEFAULT_RETRY_AFTER_SECS).max(1),
+            },
+            StatusCode::REQUEST_TIMEOUT | StatusCode::GATEWAY_TIMEOUT => Self::Timeout,
+            status if status.is_server_error() => {
+                Self::Network(format!("HTTP {}: {}", status.as_u16(), message))
+            }
+            _ => Self::ProviderRejected { code, message },
+        }
+    }
+
+    /// Classify a request that got no usable response.
+    pub fn from_request(provider: PaymentProvider, err: reqwest::Error) -> Self {
+        let error = if err.is_timeout() {
+            Self::Timeout
+        } else if err.is_decode() {
+            Self::Network(format!("Unreadable response: {}", err))
+        } else {
+            Self::Network(err.to_string())
+        };
+        error.logged(provider)
+    }
+
+    /// Whether the same request may succeed later.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            Self::RateLimited { .. } | Self::Timeout | Self::Network(_)
+        )
+    }
+
+    /// Machine-readable `code` for API error responses.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidConfig(_) => "provider_config_invalid",
+            Self::ProviderRejected { .. } => "provider_rejected",
+            Self::RateLimited { .. } => "provider_rate_limited",
+            Self::Timeout => "provider_timeout",
+            Self::Network(_) => "provider_unavailable",
+        }
+    }
+
+    /// How long callers should wait before retrying, for transient failures.
+    pub fn retry_after_secs(&self) -> Option<u64> {
+        match self {
+            Self::RateLimited { retry_after_secs } => Some(*retry_after_secs),
+            Self::Timeout | Self::Network(_) => Some(DEFAULT_RETRY_AFTER_SECS),
+            Self::InvalidConfig(_) | Self::ProviderRejected { .. } => None,
+        }
+    }
+
+    /// Log once where the failure is classified: config problems need an
+    /// operator, rejections point at a product or request, and transient
+    /// failures only matter if they keep happening.
+    fn logged(self, provider: PaymentProvider) -> Self {
+        let provider = provider.as_ref();
+        match &self {
+            Self::InvalidConfig(_) => tracing::error!(provider, "{}", self),
+            Self::ProviderRejected { code, .. } => {
+                tracing::warn!(provider, code = code.as_deref(), "{}", self)
+            }
+            Self::RateLimited { retry_after_secs } => {
+                tracing::warn!(provider, retry_after_secs, "{}", self)
+            }
+            Self::Timeout | Self::Network(_) => tracing::warn!(provider, "{}", self),
+        }
+        self
+    }
+}
+
+/// Code and message from a provider's error body: Stripe's
+/// `{"error": {"code", "message"}}` or LemonSqueezy's JSON:API
+/// `{"errors": [{"code", "detail", "title"}]}`.
+fn error_details(provider: PaymentProvider, body: &str) -> (Option<String>, Option<String>) {
+    let Ok(json) = serde_json::from_str::<Value>(body) else {
+        return (None, None);
+    };
+    let error = match provider {
+        PaymentProvider::Stripe => &json["error"],
+        PaymentProvider::LemonSqueezy => &json["errors"][0],
+    };
+    let field = |key: &str| error[key].as_str().map(str::to_string);
+    let message = field("message")
+        .or_else(|| field("detail"))
+        .or_else(|| field("title"));
+    (field("code"), message)
+}
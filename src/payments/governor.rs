@@ -0,0 +1,105 @@
+//! Concurrency limits for outbound payment provider API calls.
+//!
+//! Each org gets its own semaphore, so one tenant retrying against bad keys
+//! or running a flash sale can't take every slot, and a global semaphore caps
+//! total outbound load. A call that can't get both slots within the wait
+//! timeout fails with 503 and `Retry-After`.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::Instrument;
+
+use super::PaymentError;
+use crate::config::ProviderCallLimits;
+use crate::error::{AppError, Result};
+
+pub struct ProviderCallGovernor {
+    limits: ProviderCallLimits,
+    global: Arc<Semaphore>,
+    /// Semaphores for orgs with calls in flight or waiting
+    orgs: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+/// An org slot and a global slot, released on drop
+pub struct ProviderCallPermit {
+    _org: OwnedSemaphorePermit,
+    _global: OwnedSemaphorePermit,
+}
+
+impl ProviderCallGovernor {
+    pub fn new(limits: ProviderCallLimits) -> Self {
+        Self {
+            limits,
+            global: Arc::new(Semaphore::new(limits.global)),
+            orgs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn org_semaphore(&self, org_id: &str) -> Arc<Semaphore> {
+        let mut orgs = self.orgs.lock().unwrap();
+        // Idle orgs hold the only reference to their semaphore; permits and
+        // waiters keep a clone, so this only drops fully released ones.
+        orgs.retain(|_, semaphore| Arc::strong_count(semaphore) > 1);
+        orgs.entry(org_id.to_string())
+            .or_insert_with(|| Arc::new(Semaphore::new(self.limits.per_org)))
+            .clone()
+    }
+
+    /// Wait for a slot for `org_id`. The org slot is taken first so an org at
+    /// its limit queues without holding global slots other orgs could use.
+    pub async fn acquire(&self, org_id: &str) -> Result<ProviderCallPermit> {
+        let org = self.org_semaphore(org_id);
+        let global = self.global.clone();
+        let wait = Duration::from_millis(self.limits.wait_timeout_ms);
+
+        let acquired = tokio::time::timeout(wait, async move {
+            let org = org.acquire_owned().await?;
+            let global = global.acquire_owned().await?;
+            Ok::<_, tokio::sync::AcquireError>((org, global))
+        })
+        .await;
+
+        match acquired {
+            Ok(Ok((org, global))) => Ok(ProviderCallPermit {
+                _org: org,
+                _global: global,
+            }),
+            Ok(Err(_)) => Err(AppError::Internal("Provider call semaphore closed".into())),
+            Err(_) => {
+                tracing::warn!(org_id, "Timed out waiting for a provider call slot");
+                Err(AppError::ProviderBusy {
+                    retry_after_secs: self.limits.wait_timeout_ms.div_ceil(1000).max(1),
+                })
+            }
+        }
+    }
+
+    /// Run a provider call while holding a slot for `org_id`. The
+    /// `provider_call` span records the org and how long the call queued.
+    pub async fn run<T>(
+        &self,
+        org_id: &str,
+        call: impl Future<Output = std::result::Result<T, PaymentError>>,
+    ) -> Result<T> {
+        let span = tracing::info_span!("provider_call", org_id, wait_ms = tracing::field::Empty);
+        async move {
+            let started = Instant::now();
+            let permit = self.acquire(org_id).await;
+            tracing::Span::current().record("wait_ms", started.elapsed().as_millis() as u64);
+            let _permit = permit?;
+            Ok(call.await?)
+        }
+        .instrument(span)
+        .await
+    }
+}
+
+impl Default for ProviderCallGovernor {
+    fn default() -> Self {
+        Self::new(ProviderCallLimits::default())
+    }
+}
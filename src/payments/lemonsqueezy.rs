@@ -1,14 +1,25 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
 use hmac::{Hmac, Mac};
-use reqwest::Client;
+use reqwest::{Client, StatusCode};
 use serde::{Deserialize, Serialize};
 use sha2::Sha256;
 use subtle::ConstantTimeEq;
 
-use crate::error::{AppError, Result, msg};
+use super::{
+    CheckoutSettings, PROVIDER_REQUEST_TIMEOUT, PaymentError, PaymentProvider,
+    ProviderSubscription, SubscriptionStatus, http_client, provider_metadata,
+};
+use crate::error::msg;
 use crate::models::LemonSqueezyConfig;
 
 type HmacSha256 = Hmac<Sha256>;
 
+type Result<T> = std::result::Result<T, PaymentError>;
+
+const LEMONSQUEEZY_API_BASE: &str = "https://api.lemonsqueezy.com";
+
 #[derive(Debug, Serialize)]
 struct CreateCheckoutRequest {
     data: CheckoutData,
@@ -33,6 +44,8 @@ struct CheckoutAttributes {
 #[derive(Debug, Serialize)]
 struct ProductOptions {
     redirect_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    receipt_thank_you_note: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -42,6 +55,9 @@ struct CheckoutOptions {
 
 #[derive(Debug, Serialize)]
 struct CheckoutDataPayload {
+    /// Prefills the checkout, so the receipt goes to this address
+    #[serde(skip_serializing_if = "Option::is_none")]
+    email: Option<String>,
     custom: CustomData,
 }
 
@@ -50,6 +66,9 @@ struct CustomData {
     paycheck_session_id: String,
     project_id: String,
     product_id: String,
+    /// `/buy` metadata, under prefixed keys
+    #[serde(flatten)]
+    metadata: BTreeMap<String, String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -86,9 +105,89 @@ struct CheckoutResponseAttributes {
     url: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct RetrieveSubscriptionResponse {
+    data: SubscriptionResponseData,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubscriptionResponseData {
+    id: String,
+    attributes: SubscriptionResponseAttributes,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubscriptionResponseAttributes {
+    status: String,
+    /// Next renewal (ISO 8601)
+    renews_at: Option<String>,
+    /// When a cancelled or expired subscription ends (ISO 8601)
+    ends_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListLicenseKeysResponse {
+    meta: ListMeta,
+    data: Vec<LicenseKeyData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListMeta {
+    page: PageMeta,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PageMeta {
+    current_page: u32,
+    last_page: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct LicenseKeyData {
+    id: String,
+    attributes: LicenseKeyAttributes,
+}
+
+#[derive(Debug, Deserialize)]
+struct LicenseKeyAttributes {
+    key: String,
+    /// "inactive", "active", "expired", or "disabled"
+    status: String,
+    #[serde(default)]
+    disabled: bool,
+    product_id: i64,
+    order_id: i64,
+    customer_id: Option<i64>,
+    user_email: Option<String>,
+    /// ISO 8601 (None = never expires)
+    expires_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ValidateLicenseKeyResponse {
+    valid: bool,
+}
+
+/// A license key issued by LemonSqueezy's built-in license key feature.
+#[derive(Debug, Clone)]
+pub struct LemonSqueezyLicenseKey {
+    pub id: String,
+    pub key: String,
+    pub status: String,
+    /// Disabled in the LemonSqueezy dashboard (or by a refund)
+    pub disabled: bool,
+    pub product_id: String,
+    pub order_id: String,
+    pub customer_id: Option<String>,
+    pub user_email: Option<String>,
+    pub expires_at: Option<i64>,
+}
+
 #[derive(Debug, Clone)]
 pub struct LemonSqueezyClient {
     client: Client,
+    api_base: String,
     api_key: String,
     store_id: String,
     webhook_secret: String,
@@ -96,14 +195,25 @@ pub struct LemonSqueezyClient {
 
 impl LemonSqueezyClient {
     pub fn new(config: &LemonSqueezyConfig) -> Self {
+        Self::with_endpoint(config, LEMONSQUEEZY_API_BASE, PROVIDER_REQUEST_TIMEOUT)
+    }
+
+    /// Client for a LemonSqueezy-compatible API at `api_base` (a mock in tests).
+    pub fn with_endpoint(config: &LemonSqueezyConfig, api_base: &str, timeout: Duration) -> Self {
         Self {
-            client: Client::new(),
+            client: http_client(timeout),
+            api_base: api_base.trim_end_matches('/').to_string(),
             api_key: config.api_key.clone(),
             store_id: config.store_id.clone(),
             webhook_secret: config.webhook_secret.clone(),
         }
     }
 
+    /// Create a checkout for a variant. LemonSqueezy has no statement
+    /// descriptor per checkout, so `settings` only prefills the buyer's email
+    /// and adds the message to the receipt. `metadata` from `/buy` goes in the
+    /// custom data and comes back in the webhook.
+    #[allow(clippy::too_many_arguments)]
     pub async fn create_checkout(
         &self,
         session_id: &str,
@@ -111,6 +221,8 @@ impl LemonSqueezyClient {
         product_id: &str,
         variant_id: &str,
         redirect_url: &str,
+        metadata: &BTreeMap<String, String>,
+        settings: &CheckoutSettings,
     ) -> Result<(String, String)> {
         let request = CreateCheckoutRequest {
             data: CheckoutData {
@@ -119,15 +231,18 @@ impl LemonSqueezyClient {
                     custom_price: None,
                     product_options: ProductOptions {
                         redirect_url: redirect_url.to_string(),
+                        receipt_thank_you_note: settings.message.clone(),
                     },
                     checkout_options: CheckoutOptions {
                         button_color: "#7c3aed".to_string(),
                     },
                     checkout_data: CheckoutDataPayload {
+                        email: settings.receipt_email.clone(),
                         custom: CustomData {
                             paycheck_session_id: session_id.to_string(),
                             project_id: project_id.to_string(),
                             product_id: product_id.to_string(),
+                            metadata: provider_metadata(metadata),
                         },
                     },
                 },
@@ -150,33 +265,164 @@ impl LemonSqueezyClient {
 
         let response = self
             .client
-            .post("https://api.lemonsqueezy.com/v1/checkouts")
+            .post(format!("{}/v1/checkouts", self.api_base))
             .header("Authorization", format!("Bearer {}", self.api_key))
             .header("Accept", "application/vnd.api+json")
             .header("Content-Type", "application/vnd.api+json")
             .json(&request)
             .send()
             .await
-            .map_err(|e| AppError::Internal(format!("LemonSqueezy API error: {}", e)))?;
+            .map_err(|e| PaymentError::from_request(PaymentProvider::LemonSqueezy, e))?;
 
         if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            return Err(AppError::Internal(format!(
-                "LemonSqueezy API error: {}",
-                error_text
-            )));
+            return Err(PaymentError::from_response(PaymentProvider::LemonSqueezy, response).await);
         }
 
-        let checkout: CreateCheckoutResponse = response.json().await.map_err(|e| {
-            AppError::Internal(format!("Failed to parse LemonSqueezy response: {}", e))
-        })?;
+        let checkout: CreateCheckoutResponse = response
+            .json()
+            .await
+            .map_err(|e| PaymentError::from_request(PaymentProvider::LemonSqueezy, e))?;
 
         Ok((checkout.data.id, checkout.data.attributes.url))
     }
 
+    /// Fetch a subscription's current status and period end (None if
+    /// LemonSqueezy doesn't know it).
+    pub async fn get_subscription(
+        &self,
+        subscription_id: &str,
+    ) -> Result<Option<ProviderSubscription>> {
+        let response = self
+            .client
+            .get(format!(
+                "{}/v1/subscriptions/{}",
+                self.api_base, subscription_id
+            ))
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .header("Accept", "application/vnd.api+json")
+            .send()
+            .await
+            .map_err(|e| PaymentError::from_request(PaymentProvider::LemonSqueezy, e))?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(PaymentError::from_response(PaymentProvider::LemonSqueezy, response).await);
+        }
+
+        let subscription: RetrieveSubscriptionResponse = response
+            .json()
+            .await
+            .map_err(|e| PaymentError::from_request(PaymentProvider::LemonSqueezy, e))?;
+        let attributes = subscription.data.attributes;
+
+        let status = match attributes.status.as_str() {
+            "active" | "on_trial" => SubscriptionStatus::Active,
+            "past_due" | "unpaid" => SubscriptionStatus::PastDue,
+            "paused" => SubscriptionStatus::Paused,
+            _ => SubscriptionStatus::Ended,
+        };
+        let timestamp = |value: &Option<String>| {
+            value
+                .as_deref()
+                .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
+                .map(|dt| dt.timestamp())
+        };
+
+        Ok(Some(ProviderSubscription {
+            id: subscription.data.id,
+            status,
+            period_end: timestamp(&attributes.ends_at).or(timestamp(&attributes.renews_at)),
+            provider_status: attributes.status,
+        }))
+    }
+
+    /// One page of the store's license keys, oldest first, and the number of
+    /// the next page (None after the last one).
+    pub async fn list_license_keys(
+        &self,
+        page: u32,
+    ) -> Result<(Vec<LemonSqueezyLicenseKey>, Option<u32>)> {
+        let response = self
+            .client
+            .get(format!("{}/v1/license-keys", self.api_base))
+            .query(&[
+                ("filter[store_id]", self.store_id.as_str()),
+                ("page[number]", &page.to_string()),
+                ("page[size]", "100"),
+            ])
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .header("Accept", "application/vnd.api+json")
+            .send()
+            .await
+            .map_err(|e| PaymentError::from_request(PaymentProvider::LemonSqueezy, e))?;
+
+        if !response.status().is_success() {
+            return Err(PaymentError::from_response(PaymentProvider::LemonSqueezy, response).await);
+        }
+
+        let list: ListLicenseKeysResponse = response
+            .json()
+            .await
+            .map_err(|e| PaymentError::from_request(PaymentProvider::LemonSqueezy, e))?;
+
+        let keys = list
+            .data
+            .into_iter()
+            .map(|data| {
+                let attributes = data.attributes;
+                LemonSqueezyLicenseKey {
+                    id: data.id,
+                    key: attributes.key,
+                    disabled: attributes.disabled || attributes.status == "disabled",
+                    status: attributes.status,
+                    product_id: attributes.product_id.to_string(),
+                    order_id: attributes.order_id.to_string(),
+                    customer_id: attributes.customer_id.map(|id| id.to_string()),
+                    user_email: attributes.user_email,
+                    expires_at: attributes
+                        .expires_at
+                        .as_deref()
+                        .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
+                        .map(|dt| dt.timestamp()),
+                }
+            })
+            .collect();
+        let page = list.meta.page;
+        let next = (page.current_page < page.last_page).then_some(page.current_page + 1);
+        Ok((keys, next))
+    }
+
+    /// Ask LemonSqueezy whether a license key is still valid. Unknown keys
+    /// are invalid, not an error.
+    pub async fn validate_license_key(&self, key: &str) -> Result<bool> {
+        let response = self
+            .client
+            .post(format!("{}/v1/licenses/validate", self.api_base))
+            .header("Accept", "application/json")
+            .form(&[("license_key", key)])
+            .send()
+            .await
+            .map_err(|e| PaymentError::from_request(PaymentProvider::LemonSqueezy, e))?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+        if !response.status().is_success() {
+            return Err(PaymentError::from_response(PaymentProvider::LemonSqueezy, response).await);
+        }
+
+        let validation: ValidateLicenseKeyResponse = response
+            .json()
+            .await
+            .map_err(|e| PaymentError::from_request(PaymentProvider::LemonSqueezy, e))?;
+        Ok(validation.valid)
+    }
+
     pub fn verify_webhook_signature(&self, payload: &[u8], signature: &str) -> Result<bool> {
         let mut mac = HmacSha256::new_from_slice(self.webhook_secret.as_bytes())
-            .map_err(|_| AppError::Internal(msg::INVALID_WEBHOOK_SECRET.into()))?;
+            .map_err(|_| PaymentError::InvalidConfig(msg::INVALID_WEBHOOK_SECRET.into()))?;
         mac.update(payload);
         let expected = hex::encode(mac.finalize().into_bytes());
 
@@ -214,6 +460,9 @@ pub struct LemonSqueezyCustomData {
     pub paycheck_session_id: Option<String>,
     pub project_id: Option<String>,
     pub product_id: Option<String>,
+    /// Everything else, including `/buy` metadata (see [`super::echoed_metadata`])
+    #[serde(flatten)]
+    pub other: BTreeMap<String, serde_json::Value>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -266,3 +515,20 @@ pub struct LemonSqueezySubscriptionAttributes {
     pub customer_id: i64,
     pub status: String, // "cancelled", "active", etc.
 }
+
+// ============ subscription_paused / subscription_unpaused ============
+
+#[derive(Debug, Deserialize)]
+pub struct LemonSqueezySubscriptionPauseAttributes {
+    /// When the subscription was last changed (ISO 8601 datetime string)
+    pub updated_at: String,
+}
+
+impl LemonSqueezySubscriptionPauseAttributes {
+    /// Get the time of the pause or resume as a Unix timestamp.
+    pub fn updated_at_timestamp(&self) -> Option<i64> {
+        chrono::DateTime::parse_from_rfc3339(&self.updated_at)
+            .ok()
+            .map(|dt| dt.timestamp())
+    }
+}
@@ -1,11 +1,25 @@
+mod error;
+mod governor;
 mod lemonsqueezy;
 mod stripe;
 
+pub use error::*;
+pub use governor::*;
 pub use lemonsqueezy::*;
 pub use stripe::*;
 
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use serde::Serialize;
+use serde_json::Value;
 use strum::{AsRefStr, EnumString};
 
+use crate::models::{EmailAddress, Project};
+
+/// How long a provider API call may take before it fails as a timeout
+pub const PROVIDER_REQUEST_TIMEOUT: Duration = Duration::from_secs(20);
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, AsRefStr, EnumString)]
 #[strum(ascii_case_insensitive)]
 pub enum PaymentProvider {
@@ -14,3 +28,89 @@ pub enum PaymentProvider {
     #[strum(serialize = "lemonsqueezy", serialize = "ls")]
     LemonSqueezy,
 }
+
+/// Prefix of `/buy` metadata keys in provider metadata (Stripe session
+/// metadata, LemonSqueezy custom data), keeping them apart from Paycheck's own
+pub const METADATA_KEY_PREFIX: &str = "meta_";
+
+/// `/buy` metadata under the keys it's sent to the provider with.
+pub fn provider_metadata(metadata: &BTreeMap<String, String>) -> BTreeMap<String, String> {
+    metadata
+        .iter()
+        .map(|(key, value)| (format!("{}{}", METADATA_KEY_PREFIX, key), value.clone()))
+        .collect()
+}
+
+/// `/buy` metadata echoed back in a provider webhook, picked out of the
+/// checkout's metadata or custom data and unprefixed.
+pub fn echoed_metadata(values: &BTreeMap<String, Value>) -> BTreeMap<String, String> {
+    values
+        .iter()
+        .filter_map(|(key, value)| {
+            let key = key.strip_prefix(METADATA_KEY_PREFIX)?;
+            Some((key.to_string(), value.as_str()?.to_string()))
+        })
+        .collect()
+}
+
+/// What the buyer sees besides the product: the project's card statement
+/// text, receipt, and checkout page message.
+#[derive(Debug, Clone, Default)]
+pub struct CheckoutSettings {
+    /// Shown after the account's statement descriptor prefix (Stripe only)
+    pub statement_descriptor_suffix: Option<String>,
+    /// Address the provider emails the receipt to
+    pub receipt_email: Option<String>,
+    /// Shown above the pay button on Stripe, and on the receipt and
+    /// confirmation page on LemonSqueezy
+    pub message: Option<String>,
+}
+
+impl CheckoutSettings {
+    /// Settings for a checkout in `project`. `buyer_email` gets the receipt
+    /// only if the project has `receipt_email_enabled`.
+    pub fn for_project(project: &Project, buyer_email: Option<&EmailAddress>) -> Self {
+        Self {
+            statement_descriptor_suffix: project.statement_descriptor_suffix.clone(),
+            receipt_email: buyer_email
+                .filter(|_| project.receipt_email_enabled)
+                .map(|email| email.as_str().to_string()),
+            message: project.checkout_message.clone(),
+        }
+    }
+}
+
+/// Where a subscription stands, across providers' status names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, AsRefStr)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum SubscriptionStatus {
+    /// Paid up (including trials)
+    Active,
+    /// A renewal payment failed and the provider is still retrying
+    PastDue,
+    /// Payment collection is paused
+    Paused,
+    /// Cancelled or expired; it won't renew
+    Ended,
+}
+
+/// A subscription as the provider sees it now, for reconciliation.
+#[derive(Debug, Clone)]
+pub struct ProviderSubscription {
+    pub id: String,
+    pub status: SubscriptionStatus,
+    /// The provider's own status name (e.g. Stripe's `unpaid`)
+    pub provider_status: String,
+    /// End of the current paid period, or when an ended subscription stopped
+    pub period_end: Option<i64>,
+}
+
+fn http_client(timeout: Duration) -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(timeout)
+        // Provider APIs answer directly; a redirect is never expected
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .expect("Failed to create HTTP client")
+}
@@ -1,14 +1,25 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
 use hmac::{Hmac, Mac};
-use reqwest::Client;
+use reqwest::{Client, StatusCode};
 use serde::Deserialize;
 use sha2::Sha256;
 use subtle::ConstantTimeEq;
 
-use crate::error::{AppError, Result, msg};
+use super::{
+    CheckoutSettings, PROVIDER_REQUEST_TIMEOUT, PaymentError, PaymentProvider,
+    ProviderSubscription, SubscriptionStatus, http_client, provider_metadata,
+};
+use crate::error::msg;
 use crate::models::StripeConfig;
 
 type HmacSha256 = Hmac<Sha256>;
 
+type Result<T> = std::result::Result<T, PaymentError>;
+
+const STRIPE_API_BASE: &str = "https://api.stripe.com";
+
 // Note: We use Stripe's pre-configured prices (linked_id = price_xxx)
 // instead of ad-hoc price_data. This keeps all payment products
 // organized in the Stripe dashboard.
@@ -19,27 +30,170 @@ struct CreateCheckoutSessionResponse {
     url: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct CreateCouponResponse {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RetrieveSubscriptionResponse {
+    id: String,
+    status: String,
+    /// Top-level on older API versions, per item on newer ones
+    #[serde(default)]
+    current_period_end: Option<i64>,
+    #[serde(default)]
+    ended_at: Option<i64>,
+    #[serde(default)]
+    items: Option<SubscriptionItems>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubscriptionItems {
+    data: Vec<SubscriptionItem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubscriptionItem {
+    #[serde(default)]
+    current_period_end: Option<i64>,
+}
+
+/// Upgrade details attached to a checkout as metadata.
+#[derive(Debug, Clone)]
+pub struct CheckoutUpgrade {
+    pub from_license_id: String,
+    pub from_product_id: String,
+    /// Days left on the old license (None = perpetual)
+    pub days_remaining: Option<i64>,
+    pub credit_cents: Option<i64>,
+    /// One-time coupon for the credit, applied to the checkout
+    pub coupon_id: Option<String>,
+}
+
 #[derive(Debug, Clone)]
 pub struct StripeClient {
     client: Client,
+    api_base: String,
     secret_key: String,
     webhook_secret: String,
 }
 
 impl StripeClient {
     pub fn new(config: &StripeConfig) -> Self {
+        Self::with_endpoint(config, STRIPE_API_BASE, PROVIDER_REQUEST_TIMEOUT)
+    }
+
+    /// Client for a Stripe-compatible API at `api_base` (a mock in tests).
+    pub fn with_endpoint(config: &StripeConfig, api_base: &str, timeout: Duration) -> Self {
         Self {
-            client: Client::new(),
+            client: http_client(timeout),
+            api_base: api_base.trim_end_matches('/').to_string(),
             secret_key: config.secret_key.clone(),
             webhook_secret: config.webhook_secret.clone(),
         }
     }
 
+    /// POST a form to `path` and parse the JSON response.
+    async fn post<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        form: &[(&str, String)],
+    ) -> Result<T> {
+        let response = self
+            .client
+            .post(format!("{}{}", self.api_base, path))
+            .basic_auth(&self.secret_key, None::<&str>)
+            .form(form)
+            .send()
+            .await
+            .map_err(|e| PaymentError::from_request(PaymentProvider::Stripe, e))?;
+
+        if !response.status().is_success() {
+            return Err(PaymentError::from_response(PaymentProvider::Stripe, response).await);
+        }
+
+        response
+            .json()
+            .await
+            .map_err(|e| PaymentError::from_request(PaymentProvider::Stripe, e))
+    }
+
+    /// GET `path` and parse the JSON response (None on 404).
+    async fn get<T: serde::de::DeserializeOwned>(&self, path: &str) -> Result<Option<T>> {
+        let response = self
+            .client
+            .get(format!("{}{}", self.api_base, path))
+            .basic_auth(&self.secret_key, None::<&str>)
+            .send()
+            .await
+            .map_err(|e| PaymentError::from_request(PaymentProvider::Stripe, e))?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(PaymentError::from_response(PaymentProvider::Stripe, response).await);
+        }
+
+        response
+            .json()
+            .await
+            .map(Some)
+            .map_err(|e| PaymentError::from_request(PaymentProvider::Stripe, e))
+    }
+
+    /// Fetch a subscription's current status and period end (None if Stripe
+    /// doesn't know it).
+    pub async fn get_subscription(
+        &self,
+        subscription_id: &str,
+    ) -> Result<Option<ProviderSubscription>> {
+        let Some(subscription) = self
+            .get::<RetrieveSubscriptionResponse>(&format!("/v1/subscriptions/{}", subscription_id))
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let status = match subscription.status.as_str() {
+            "active" | "trialing" => SubscriptionStatus::Active,
+            "past_due" | "unpaid" | "incomplete" => SubscriptionStatus::PastDue,
+            "paused" => SubscriptionStatus::Paused,
+            _ => SubscriptionStatus::Ended,
+        };
+        let current_period_end = subscription.current_period_end.or_else(|| {
+            subscription
+                .items
+                .as_ref()
+                .and_then(|items| items.data.first())
+                .and_then(|item| item.current_period_end)
+        });
+        let period_end = match status {
+            SubscriptionStatus::Ended => subscription.ended_at.or(current_period_end),
+            _ => current_period_end,
+        };
+
+        Ok(Some(ProviderSubscription {
+            id: subscription.id,
+            status,
+            provider_status: subscription.status,
+            period_end,
+        }))
+    }
+
     /// Create a Stripe checkout session using a pre-configured price.
     ///
     /// `price_id` is the Stripe Price ID (e.g., "price_1ABC...") configured in
     /// your Stripe dashboard. This creates organized payments in Stripe instead
     /// of ad-hoc "one-time" charges scattered across the dashboard.
+    ///
+    /// For upgrade purchases, `upgrade` adds the old license details to the
+    /// session metadata and applies its coupon, if any. `settings` sets the
+    /// statement descriptor suffix and receipt address on the payment, and
+    /// the message above the pay button. `metadata` from `/buy` is added to
+    /// the session metadata and comes back in the webhook.
+    #[allow(clippy::too_many_arguments)]
     pub async fn create_checkout_session(
         &self,
         session_id: &str,
@@ -48,41 +202,95 @@ impl StripeClient {
         price_id: &str,
         success_url: &str,
         cancel_url: &str,
+        upgrade: Option<&CheckoutUpgrade>,
+        checkout_fields: &BTreeMap<String, String>,
+        metadata: &BTreeMap<String, String>,
+        settings: &CheckoutSettings,
     ) -> Result<(String, String)> {
-        let response = self
-            .client
-            .post("https://api.stripe.com/v1/checkout/sessions")
-            .basic_auth(&self.secret_key, None::<&str>)
-            .form(&[
-                ("mode", "payment"),
-                ("success_url", success_url),
-                ("cancel_url", cancel_url),
-                ("line_items[0][price]", price_id),
-                ("line_items[0][quantity]", "1"),
-                ("metadata[paycheck_session_id]", session_id),
-                ("metadata[project_id]", project_id),
-                ("metadata[product_id]", product_id),
-            ])
-            .send()
-            .await
-            .map_err(|e| AppError::Internal(format!("Stripe API error: {}", e)))?;
-
-        if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            return Err(AppError::Internal(format!(
-                "Stripe API error: {}",
-                error_text
-            )));
+        // Shown with the payment in the Stripe dashboard
+        let field_metadata: Vec<(String, &String)> = checkout_fields
+            .iter()
+            .map(|(key, value)| (format!("metadata[field_{}]", key), value))
+            .collect();
+
+        let mut form = vec![
+            ("mode", "payment".to_string()),
+            ("success_url", success_url.to_string()),
+            ("cancel_url", cancel_url.to_string()),
+            ("line_items[0][price]", price_id.to_string()),
+            ("line_items[0][quantity]", "1".to_string()),
+            ("metadata[paycheck_session_id]", session_id.to_string()),
+            ("metadata[project_id]", project_id.to_string()),
+            ("metadata[product_id]", product_id.to_string()),
+        ];
+        if let Some(upgrade) = upgrade {
+            form.push((
+                "metadata[upgrade_from_license_id]",
+                upgrade.from_license_id.clone(),
+            ));
+            form.push((
+                "metadata[upgrade_from_product_id]",
+                upgrade.from_product_id.clone(),
+            ));
+            if let Some(days) = upgrade.days_remaining {
+                form.push(("metadata[upgrade_days_remaining]", days.to_string()));
+            }
+            if let Some(credit) = upgrade.credit_cents {
+                form.push(("metadata[upgrade_credit_cents]", credit.to_string()));
+            }
+            if let Some(ref coupon_id) = upgrade.coupon_id {
+                form.push(("discounts[0][coupon]", coupon_id.clone()));
+            }
+        }
+        for (key, value) in &field_metadata {
+            form.push((key.as_str(), value.to_string()));
+        }
+        let buy_metadata: Vec<(String, String)> = provider_metadata(metadata)
+            .into_iter()
+            .map(|(key, value)| (format!("metadata[{}]", key), value))
+            .collect();
+        for (key, value) in &buy_metadata {
+            form.push((key.as_str(), value.clone()));
+        }
+        if let Some(ref suffix) = settings.statement_descriptor_suffix {
+            form.push((
+                "payment_intent_data[statement_descriptor_suffix]",
+                suffix.clone(),
+            ));
+        }
+        if let Some(ref email) = settings.receipt_email {
+            form.push(("payment_intent_data[receipt_email]", email.clone()));
+        }
+        if let Some(ref message) = settings.message {
+            form.push(("custom_text[submit][message]", message.clone()));
         }
 
-        let session: CreateCheckoutSessionResponse = response
-            .json()
-            .await
-            .map_err(|e| AppError::Internal(format!("Failed to parse Stripe response: {}", e)))?;
+        let session: CreateCheckoutSessionResponse =
+            self.post("/v1/checkout/sessions", &form).await?;
 
         Ok((session.id, session.url))
     }
 
+    /// Create a single-use coupon for an upgrade credit. Returns the coupon ID.
+    pub async fn create_upgrade_coupon(
+        &self,
+        session_id: &str,
+        amount_off_cents: i64,
+        currency: &str,
+    ) -> Result<String> {
+        let form = [
+            ("amount_off", amount_off_cents.to_string()),
+            ("currency", currency.to_string()),
+            ("duration", "once".to_string()),
+            ("max_redemptions", "1".to_string()),
+            ("name", "Upgrade credit".to_string()),
+            ("metadata[paycheck_session_id]", session_id.to_string()),
+        ];
+        let coupon: CreateCouponResponse = self.post("/v1/coupons", &form).await?;
+
+        Ok(coupon.id)
+    }
+
     /// Maximum age of a webhook timestamp before it's rejected (in seconds).
     /// Stripe recommends 300 seconds (5 minutes).
     const WEBHOOK_TIMESTAMP_TOLERANCE_SECS: i64 = 300;
@@ -102,16 +310,14 @@ impl StripeClient {
             }
         }
 
-        let timestamp_str =
-            timestamp.ok_or_else(|| AppError::BadRequest(msg::INVALID_SIGNATURE_FORMAT.into()))?;
-        let sig_v1 =
-            sig_v1.ok_or_else(|| AppError::BadRequest(msg::INVALID_SIGNATURE_FORMAT.into()))?;
+        let timestamp_str = timestamp.ok_or_else(|| unreadable(msg::INVALID_SIGNATURE_FORMAT))?;
+        let sig_v1 = sig_v1.ok_or_else(|| unreadable(msg::INVALID_SIGNATURE_FORMAT))?;
 
         // Parse and validate timestamp to prevent replay attacks.
         // Reject webhooks older than WEBHOOK_TIMESTAMP_TOLERANCE_SECS.
         let timestamp: i64 = timestamp_str
             .parse()
-            .map_err(|_| AppError::BadRequest(msg::INVALID_TIMESTAMP_IN_SIGNATURE.into()))?;
+            .map_err(|_| unreadable(msg::INVALID_TIMESTAMP_IN_SIGNATURE))?;
 
         let now = chrono::Utc::now().timestamp();
         let age = now - timestamp;
@@ -139,7 +345,7 @@ impl StripeClient {
 
         // Compute expected signature
         let mut mac = HmacSha256::new_from_slice(self.webhook_secret.as_bytes())
-            .map_err(|_| AppError::Internal(msg::INVALID_WEBHOOK_SECRET.into()))?;
+            .map_err(|_| PaymentError::InvalidConfig(msg::INVALID_WEBHOOK_SECRET.into()))?;
         mac.update(signed_payload.as_bytes());
         let expected = hex::encode(mac.finalize().into_bytes());
 
@@ -159,17 +365,31 @@ impl StripeClient {
     }
 }
 
+/// A `Stripe-Signature` header that can't be parsed
+fn unreadable(message: &str) -> PaymentError {
+    PaymentError::ProviderRejected {
+        code: None,
+        message: message.into(),
+    }
+}
+
 /// Generic Stripe webhook event - object is parsed based on event_type
 #[derive(Debug, Deserialize)]
 pub struct StripeWebhookEvent {
     #[serde(rename = "type")]
     pub event_type: String,
+    /// When Stripe created the event (Unix timestamp)
+    #[serde(default)]
+    pub created: Option<i64>,
     pub data: StripeEventData,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct StripeEventData {
     pub object: serde_json::Value,
+    /// Fields changed by a `*.updated` event, with their previous values
+    #[serde(default)]
+    pub previous_attributes: Option<serde_json::Value>,
 }
 
 // ============ checkout.session.completed ============
@@ -185,6 +405,9 @@ pub struct StripeCheckoutSession {
     /// Customer details collected during checkout (contains the actual email entered)
     pub customer_details: Option<StripeCustomerDetails>,
     pub subscription: Option<String>, // Present for subscription mode
+    /// PaymentIntent for payment mode (refunds and disputes refer to it)
+    #[serde(default)]
+    pub payment_intent: Option<String>,
     pub metadata: StripeMetadata,
 }
 
@@ -199,6 +422,9 @@ pub struct StripeMetadata {
     pub paycheck_session_id: Option<String>,
     pub project_id: Option<String>,
     pub product_id: Option<String>,
+    /// Everything else, including `/buy` metadata (see [`super::echoed_metadata`])
+    #[serde(flatten)]
+    pub other: BTreeMap<String, serde_json::Value>,
 }
 
 // ============ invoice.paid ============
@@ -238,6 +464,32 @@ impl StripeInvoice {
     }
 }
 
+// ============ charge.refunded / charge.dispute.created / charge.dispute.closed ============
+
+#[derive(Debug, Deserialize)]
+pub struct StripeCharge {
+    pub id: String,
+    pub payment_intent: Option<String>,
+    /// True once the full amount has been refunded (partial refunds leave it false)
+    pub refunded: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StripeDispute {
+    pub id: String,
+    pub payment_intent: Option<String>,
+    /// Disputed amount in cents
+    #[serde(default)]
+    pub amount: Option<i64>,
+    #[serde(default)]
+    pub currency: Option<String>,
+    /// "fraudulent", "product_not_received", etc.
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// "needs_response", "under_review", "won", "lost", "warning_closed", etc.
+    pub status: String,
+}
+
 // ============ customer.subscription.deleted ============
 
 #[derive(Debug, Deserialize)]
@@ -245,4 +497,7 @@ pub struct StripeSubscription {
     pub id: String,
     pub customer: Option<String>,
     pub status: String, // "active", "canceled", etc.
+    /// Set while payment collection is paused (null otherwise)
+    #[serde(default)]
+    pub pause_collection: Option<serde_json::Value>,
 }
@@ -0,0 +1,557 @@
+//! Project configuration as a document, for keeping it in version control.
+//!
+//! [`export`] describes a project's settings and products, with each product's
+//! provider links as the Stripe price or LemonSqueezy variant it sells as. It
+//! leaves out IDs, keys, secrets, and licenses. [`plan`] compares a document
+//! against the project and lists the changes that would make them match, and
+//! [`apply`] makes them.
+//!
+//! Products are matched by name, so renaming one deletes it and creates a new
+//! one. Importing an unchanged export plans no changes.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::crypto::MasterKey;
+use crate::db::queries::{self, ProductWithProviderLinks};
+use crate::error::{AppError, OptionExt, Result, msg};
+use crate::models::{
+    CheckoutField, ConvertedTrialAction, CreateProduct, CreateProviderLink, Entitlements, Product,
+    ProductProviderLink, Project, UpdateProduct, UpdateProject, UpdateProviderLink,
+    UpgradeOldLicense, default_duplicate_purchase_check,
+};
+use crate::webhook_payloads::WebhookSchemaVersion;
+
+/// Document format version. Documents with any other version are rejected.
+pub const CONFIG_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectConfig {
+    pub version: u32,
+    pub project: ProjectSettings,
+    /// Sorted by name on export
+    #[serde(default)]
+    pub products: Vec<ProductConfig>,
+}
+
+/// Project settings, named as in `PUT /orgs/{org_id}/projects/{project_id}`.
+/// Omitted optional settings are cleared.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectSettings {
+    pub name: String,
+    pub license_key_prefix: String,
+    pub redirect_url: Option<String>,
+    pub email_from: Option<String>,
+    pub email_enabled: bool,
+    pub email_webhook_url: Option<String>,
+    pub jwt_issuer: Option<String>,
+    pub jwt_audience: Option<String>,
+    pub upgrade_auto_discount: bool,
+    pub upgrade_old_license: UpgradeOldLicense,
+    pub allow_project_id_auth: bool,
+    pub allow_link_checkout: bool,
+    pub max_validations_per_hour_per_license: Option<i64>,
+    pub statement_descriptor_suffix: Option<String>,
+    #[serde(default)]
+    pub receipt_email_enabled: bool,
+    pub checkout_message: Option<String>,
+    #[serde(default)]
+    pub attestation_audiences: Vec<String>,
+    #[serde(default = "default_duplicate_purchase_check")]
+    pub duplicate_purchase_check: bool,
+    #[serde(default)]
+    pub converted_trial_action: ConvertedTrialAction,
+    #[serde(default)]
+    pub require_terms_acceptance: bool,
+    #[serde(default)]
+    pub client_flags: Map<String, Value>,
+    #[serde(default)]
+    pub min_client_version: Option<String>,
+    #[serde(default)]
+    pub checkout_metadata_keys: Vec<String>,
+    #[serde(default)]
+    pub usage_geo_enabled: bool,
+    #[serde(default)]
+    pub dormant_after_days: Option<i64>,
+    #[serde(default)]
+    pub dormant_nudge_email: bool,
+    #[serde(default)]
+    pub webhook_schema_version: Option<WebhookSchemaVersion>,
+}
+
+impl From<&Project> for ProjectSettings {
+    fn from(p: &Project) -> Self {
+        Self {
+            name: p.name.clone(),
+            license_key_prefix: p.license_key_prefix.clone(),
+            redirect_url: p.redirect_url.clone(),
+            email_from: p.email_from.clone(),
+            email_enabled: p.email_enabled,
+            email_webhook_url: p.email_webhook_url.clone(),
+            jwt_issuer: p.jwt_issuer.clone(),
+            jwt_audience: p.jwt_audience.clone(),
+            upgrade_auto_discount: p.upgrade_auto_discount,
+            upgrade_old_license: p.upgrade_old_license,
+            allow_project_id_auth: p.allow_project_id_auth,
+            allow_link_checkout: p.allow_link_checkout,
+            max_validations_per_hour_per_license: p.max_validations_per_hour_per_license,
+            statement_descriptor_suffix: p.statement_descriptor_suffix.clone(),
+            receipt_email_enabled: p.receipt_email_enabled,
+            checkout_message: p.checkout_message.clone(),
+            attestation_audiences: p.attestation_audiences.clone(),
+            duplicate_purchase_check: p.duplicate_purchase_check,
+            converted_trial_action: p.converted_trial_action,
+            require_terms_acceptance: p.require_terms_acceptance,
+            client_flags: p.client_flags.clone(),
+            min_client_version: p.min_client_version.clone(),
+            checkout_metadata_keys: p.checkout_metadata_keys.clone(),
+            usage_geo_enabled: p.usage_geo_enabled,
+            dormant_after_days: p.dormant_after_days,
+            dormant_nudge_email: p.dormant_nudge_email,
+            webhook_schema_version: p.webhook_schema_version,
+        }
+    }
+}
+
+/// A product, named as in the product endpoints. Omitted optional fields are
+/// cleared.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProductConfig {
+    pub name: String,
+    pub tier: String,
+    pub license_exp_days: Option<i32>,
+    pub updates_exp_days: Option<i32>,
+    pub activation_limit: Option<i32>,
+    pub device_limit: Option<i32>,
+    pub device_inactive_days: Option<i32>,
+    #[serde(default)]
+    pub features: Vec<String>,
+    #[serde(default)]
+    pub entitlements: Entitlements,
+    pub price_cents: Option<i64>,
+    pub currency: Option<String>,
+    pub seat_count: Option<i32>,
+    pub available_from: Option<i64>,
+    pub available_until: Option<i64>,
+    #[serde(default)]
+    pub checkout_fields: Vec<CheckoutField>,
+    /// Provider ("stripe" or "lemonsqueezy") to its price/variant ID
+    #[serde(default)]
+    pub provider_links: BTreeMap<String, String>,
+}
+
+impl From<&ProductWithProviderLinks> for ProductConfig {
+    fn from(p: &ProductWithProviderLinks) -> Self {
+        let product = &p.product;
+        Self {
+            name: product.name.clone(),
+            tier: product.tier.clone(),
+            license_exp_days: product.license_exp_days,
+            updates_exp_days: product.updates_exp_days,
+            activation_limit: product.activation_limit,
+            device_limit: product.device_limit,
+            device_inactive_days: product.device_inactive_days,
+            features: product.features.clone(),
+            entitlements: product.entitlements.clone(),
+            price_cents: product.price_cents,
+            currency: product.currency.clone(),
+            seat_count: product.seat_count,
+            available_from: product.available_from,
+            available_until: product.available_until,
+            checkout_fields: product.checkout_fields.clone(),
+            provider_links: p
+                .provider_links
+                .iter()
+                .map(|link| (link.provider.clone(), link.linked_id.clone()))
+                .collect(),
+        }
+    }
+}
+
+/// Wire format of a config document
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigFormat {
+    #[default]
+    Json,
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// YAML for `application/yaml` and similar, JSON otherwise.
+    pub fn from_content_type(content_type: Option<&str>) -> Self {
+        let mime = content_type
+            .and_then(|ct| ct.split(';').next())
+            .map(|ct| ct.trim().to_ascii_lowercase());
+        match mime.as_deref() {
+            Some("application/yaml" | "application/x-yaml" | "text/yaml") => Self::Yaml,
+            _ => Self::Json,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Yaml => "application/yaml",
+        }
+    }
+
+    pub fn parse(self, body: &[u8]) -> Result<ProjectConfig> {
+        let parsed = match self {
+            Self::Json => serde_json::from_slice(body).map_err(|e| e.to_string()),
+            Self::Yaml => serde_yaml::from_slice(body).map_err(|e| e.to_string()),
+        };
+        parsed.map_err(|e| AppError::BadRequest(format!("Invalid config document: {}", e)))
+    }
+
+    pub fn write(self, config: &ProjectConfig) -> Result<String> {
+        match self {
+            Self::Json => Ok(serde_json::to_string_pretty(config)?),
+            Self::Yaml => serde_yaml::to_string(config)
+                .map_err(|e| AppError::Internal(format!("Failed to write config: {}", e))),
+        }
+    }
+}
+
+/// The document describing `project` as it is now.
+pub fn export(conn: &Connection, project: &Project) -> Result<ProjectConfig> {
+    let mut products: Vec<ProductConfig> = queries::list_products_with_links(conn, &project.id)?
+        .iter()
+        .map(ProductConfig::from)
+        .collect();
+    products.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(ProjectConfig {
+        version: CONFIG_VERSION,
+        project: ProjectSettings::from(project),
+        products,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeAction {
+    Create,
+    Update,
+    Delete,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeResource {
+    Project,
+    Product,
+    ProviderLink,
+}
+
+/// One create, update, or delete needed to bring a project in line with a
+/// document.
+#[derive(Debug, Serialize)]
+pub struct ConfigChange {
+    pub action: ChangeAction,
+    pub resource: ChangeResource,
+    /// The resource's ID; only known for created resources once applied
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Project or product name, or the provider of a provider link
+    pub name: String,
+    /// Product a provider link belongs to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub product: Option<String>,
+    /// Fields an update changes
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<String>,
+    #[serde(skip)]
+    step: Step,
+}
+
+#[derive(Debug)]
+enum Step {
+    UpdateProject(UpdateProject),
+    CreateProduct(CreateProduct),
+    UpdateProduct(UpdateProduct),
+    DeleteProduct,
+    CreateLink(CreateProviderLink),
+    UpdateLink(UpdateProviderLink),
+    DeleteLink,
+}
+
+/// List the changes that would make `project` match `config`, in the order
+/// [`apply`] makes them. Fails if `config` is invalid.
+pub fn plan(
+    conn: &Connection,
+    project: &Project,
+    config: &ProjectConfig,
+) -> Result<Vec<ConfigChange>> {
+    validate(config)?;
+    let mut changes = Vec::new();
+
+    let fields = changed_fields(&ProjectSettings::from(project), &config.project)?;
+    if !fields.is_empty() {
+        changes.push(ConfigChange {
+            action: ChangeAction::Update,
+            resource: ChangeResource::Project,
+            id: Some(project.id.clone()),
+            name: project.name.clone(),
+            product: None,
+            fields: fields.keys().cloned().collect(),
+            step: Step::UpdateProject(serde_json::from_value(Value::Object(fields))?),
+        });
+    }
+
+    let mut current: HashMap<String, ProductWithProviderLinks> =
+        queries::list_products_with_links(conn, &project.id)?
+            .into_iter()
+            .map(|p| (p.product.name.clone(), p))
+            .collect();
+
+    let mut desired: Vec<&ProductConfig> = config.products.iter().collect();
+    desired.sort_by(|a, b| a.name.cmp(&b.name));
+
+    for product in desired {
+        match current.remove(&product.name) {
+            Some(existing) => plan_product_update(&mut changes, existing, product)?,
+            None => {
+                if queries::get_deleted_product_by_name(conn, &project.id, &product.name)?.is_some()
+                {
+                    return Err(AppError::Conflict(format!(
+                        "Product '{}' is deleted; restore it or use another name",
+                        product.name
+                    )));
+                }
+                changes.push(ConfigChange {
+                    action: ChangeAction::Create,
+                    resource: ChangeResource::Product,
+                    id: None,
+                    name: product.name.clone(),
+                    product: None,
+                    fields: Vec::new(),
+                    step: Step::CreateProduct(serde_json::from_value(product_fields(product)?)?),
+                });
+                plan_link_changes(&mut changes, &product.name, &[], &product.provider_links);
+            }
+        }
+    }
+
+    let mut removed: Vec<Product> = current.into_values().map(|p| p.product).collect();
+    removed.sort_by(|a, b| a.name.cmp(&b.name));
+    for product in removed {
+        changes.push(ConfigChange {
+            action: ChangeAction::Delete,
+            resource: ChangeResource::Product,
+            id: Some(product.id),
+            name: product.name,
+            product: None,
+            fields: Vec::new(),
+            step: Step::DeleteProduct,
+        });
+    }
+
+    Ok(changes)
+}
+
+fn plan_product_update(
+    changes: &mut Vec<ConfigChange>,
+    existing: ProductWithProviderLinks,
+    desired: &ProductConfig,
+) -> Result<()> {
+    let mut fields = changed_fields(&ProductConfig::from(&existing), desired)?;
+    fields.remove("provider_links");
+    if !fields.is_empty() {
+        let input: UpdateProduct = serde_json::from_value(Value::Object(fields.clone()))?;
+        input.validate_against(&existing.product)?;
+        changes.push(ConfigChange {
+            action: ChangeAction::Update,
+            resource: ChangeResource::Product,
+            id: Some(existing.product.id.clone()),
+            name: desired.name.clone(),
+            product: None,
+            fields: fields.keys().cloned().collect(),
+            step: Step::UpdateProduct(input),
+        });
+    }
+    plan_link_changes(
+        changes,
+        &desired.name,
+        &existing.provider_links,
+        &desired.provider_links,
+    );
+    Ok(())
+}
+
+fn plan_link_changes(
+    changes: &mut Vec<ConfigChange>,
+    product: &str,
+    current: &[ProductProviderLink],
+    desired: &BTreeMap<String, String>,
+) {
+    let change =
+        |action: ChangeAction, id: Option<String>, provider: &str, step: Step| ConfigChange {
+            action,
+            resource: ChangeResource::ProviderLink,
+            id,
+            name: provider.to_string(),
+            product: Some(product.to_string()),
+            fields: if action == ChangeAction::Update {
+                vec!["linked_id".to_string()]
+            } else {
+                Vec::new()
+            },
+            step,
+        };
+
+    for (provider, linked_id) in desired {
+        match current.iter().find(|link| link.provider == *provider) {
+            None => changes.push(change(
+                ChangeAction::Create,
+                None,
+                provider,
+                Step::CreateLink(CreateProviderLink {
+                    provider: provider.clone(),
+                    linked_id: linked_id.clone(),
+                }),
+            )),
+            Some(link) if link.linked_id != *linked_id => changes.push(change(
+                ChangeAction::Update,
+                Some(link.id.clone()),
+                provider,
+                Step::UpdateLink(UpdateProviderLink {
+                    linked_id: Some(linked_id.clone()),
+                }),
+            )),
+            Some(_) => {}
+        }
+    }
+
+    let mut removed: Vec<&ProductProviderLink> = current
+        .iter()
+        .filter(|link| !desired.contains_key(&link.provider))
+        .collect();
+    removed.sort_by(|a, b| a.provider.cmp(&b.provider));
+    for link in removed {
+        changes.push(change(
+            ChangeAction::Delete,
+            Some(link.id.clone()),
+            &link.provider,
+            Step::DeleteLink,
+        ));
+    }
+}
+
+/// Make the planned changes, filling in the IDs of created resources. Run
+/// inside a transaction so a failure part way leaves the project unchanged.
+pub fn apply(
+    conn: &Connection,
+    project_id: &str,
+    changes: &mut [ConfigChange],
+    master_key: &MasterKey,
+) -> Result<()> {
+    let mut product_ids: HashMap<String, String> =
+        queries::list_products_for_project(conn, project_id)?
+            .into_iter()
+            .map(|p| (p.name, p.id))
+            .collect();
+
+    for change in changes.iter_mut() {
+        let existing_id = change.id.as_deref().unwrap_or_default();
+        match &change.step {
+            Step::UpdateProject(input) => {
+                queries::update_project(conn, project_id, input, master_key)?
+                    .or_not_found(msg::PROJECT_NOT_FOUND)?;
+            }
+            Step::CreateProduct(input) => {
+                let product = queries::create_product(conn, project_id, input)?;
+                product_ids.insert(product.name, product.id.clone());
+                change.id = Some(product.id);
+            }
+            Step::UpdateProduct(input) => {
+                queries::update_product(conn, existing_id, input)?
+                    .or_not_found(msg::PRODUCT_NOT_FOUND)?;
+            }
+            Step::DeleteProduct => {
+                queries::soft_delete_product(conn, existing_id)?;
+            }
+            Step::CreateLink(input) => {
+                let product = change.product.as_deref().unwrap_or_default();
+                let product_id = product_ids
+                    .get(product)
+                    .or_not_found(msg::PRODUCT_NOT_FOUND)?;
+                change.id = Some(queries::create_provider_link(conn, product_id, input)?.id);
+            }
+            Step::UpdateLink(input) => {
+                queries::update_provider_link(conn, existing_id, input)?;
+            }
+            Step::DeleteLink => {
+                queries::delete_provider_link(conn, existing_id)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check everything the product and project endpoints would, plus that
+/// product names are unique.
+fn validate(config: &ProjectConfig) -> Result<()> {
+    if config.version != CONFIG_VERSION {
+        return Err(AppError::BadRequest(format!(
+            "Unsupported config version {} (expected {})",
+            config.version, CONFIG_VERSION
+        )));
+    }
+
+    let settings: UpdateProject = serde_json::from_value(serde_json::to_value(&config.project)?)?;
+    settings.validate()?;
+
+    let mut names = HashSet::new();
+    for product in &config.products {
+        if !names.insert(product.name.as_str()) {
+            return Err(AppError::BadRequest(format!(
+                "Product '{}' appears more than once",
+                product.name
+            )));
+        }
+        let input: CreateProduct = serde_json::from_value(product_fields(product)?)?;
+        input.validate()?;
+
+        for (provider, linked_id) in &product.provider_links {
+            if !matches!(provider.as_str(), "stripe" | "lemonsqueezy") {
+                return Err(AppError::BadRequest(msg::INVALID_PROVIDER.into()));
+            }
+            CreateProviderLink {
+                provider: provider.clone(),
+                linked_id: linked_id.clone(),
+            }
+            .validate()?;
+        }
+    }
+    Ok(())
+}
+
+/// A product's own fields, without its provider links.
+fn product_fields(product: &ProductConfig) -> Result<Value> {
+    let mut fields = to_object(product)?;
+    fields.remove("provider_links");
+    Ok(Value::Object(fields))
+}
+
+/// Fields of `desired` that differ from `current`, with their desired values.
+fn changed_fields<T: Serialize>(current: &T, desired: &T) -> Result<Map<String, Value>> {
+    let current = to_object(current)?;
+    Ok(to_object(desired)?
+        .into_iter()
+        .filter(|(field, value)| current.get(field) != Some(value))
+        .collect())
+}
+
+fn to_object<T: Serialize>(value: &T) -> Result<Map<String, Value>> {
+    match serde_json::to_value(value)? {
+        Value::Object(fields) => Ok(fields),
+        _ => Err(AppError::Internal("Config section is not an object".into())),
+    }
+}
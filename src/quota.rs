@@ -0,0 +1,157 @@
+//! Operator-set org limits (`org_limits`).
+//!
+//! Creates call [`check`] before writing. A create that would go past a
+//! limit's hard value fails with `AppError::QuotaExceeded` (403,
+//! `quota_exceeded`). One that reaches the soft value succeeds with a
+//! [`QuotaWarning`]: org endpoints return it as the `X-Paycheck-Quota-Warning`
+//! header, and the create that first reaches it is audit logged.
+//!
+//! Orgs without limits are unlimited. Checks read usage and then write, so
+//! concurrent creates can overshoot a hard value by a few.
+
+use std::convert::Infallible;
+
+use axum::http::HeaderValue;
+use axum::response::{IntoResponseParts, ResponseParts};
+use rusqlite::Connection;
+
+use crate::db::queries;
+use crate::error::{AppError, Result};
+use crate::models::{
+    AuditAction, OrgLimit, OrgLimitName, OrgLimitUsage, QuotaWarning, month_start,
+};
+use crate::util::AuditLogBuilder;
+
+/// Response header sent while a create is at or past a soft limit
+pub const QUOTA_WARNING_HEADER: &str = "x-paycheck-quota-warning";
+
+/// Check that `adding` more of what `limit_name` counts fits the org's limit.
+/// `project_id` is the project a product is created in (`products_per_project`).
+pub fn check(
+    conn: &Connection,
+    org_id: &str,
+    limit_name: OrgLimitName,
+    project_id: Option<&str>,
+    adding: i64,
+    now: i64,
+) -> Result<Option<QuotaWarning>> {
+    let Some(limit) = queries::get_org_limit(conn, org_id, limit_name)? else {
+        return Ok(None);
+    };
+    let before = match (limit_name, project_id) {
+        (OrgLimitName::ProductsPerProject, Some(project_id)) => {
+            queries::count_products_for_project(conn, project_id)?
+        }
+        _ => usage(conn, org_id, &limit, now)?.used,
+    };
+    let after = before + adding;
+
+    if let Some(hard_value) = limit.hard_value
+        && after > hard_value
+    {
+        return Err(AppError::QuotaExceeded {
+            limit_name,
+            hard_value,
+        });
+    }
+
+    Ok(limit
+        .soft_value
+        .filter(|&soft| after >= soft)
+        .map(|soft_value| QuotaWarning {
+            limit_name,
+            used: after,
+            soft_value,
+            hard_value: limit.hard_value,
+            crossed: before < soft_value,
+        }))
+}
+
+/// Every limit with the org's current usage, unset ones included.
+pub fn org_usage(conn: &Connection, org_id: &str, now: i64) -> Result<Vec<OrgLimitUsage>> {
+    let limits = queries::get_org_limits(conn, org_id)?;
+    OrgLimitName::ALL
+        .into_iter()
+        .map(|limit_name| {
+            let limit = limits
+                .iter()
+                .find(|l| l.limit_name == limit_name)
+                .cloned()
+                .unwrap_or_else(|| OrgLimit {
+                    org_id: org_id.to_string(),
+                    limit_name,
+                    soft_value: None,
+                    hard_value: None,
+                    created_at: now,
+                    updated_at: now,
+                });
+            usage(conn, org_id, &limit, now)
+        })
+        .collect()
+}
+
+fn usage(conn: &Connection, org_id: &str, limit: &OrgLimit, now: i64) -> Result<OrgLimitUsage> {
+    let (used, project_id, period_start) = match limit.limit_name {
+        OrgLimitName::Projects => (
+            queries::get_org_tenant_stats(conn, org_id)?.project_count,
+            None,
+            None,
+        ),
+        OrgLimitName::ProductsPerProject => {
+            let (used, project_id) = queries::get_max_products_per_project(conn, org_id)?;
+            (used, project_id, None)
+        }
+        OrgLimitName::LicensesPerMonth => {
+            let since = month_start(now);
+            (
+                queries::count_org_licenses_created_since(conn, org_id, since)?,
+                None,
+                Some(since),
+            )
+        }
+    };
+    Ok(OrgLimitUsage {
+        limit_name: limit.limit_name,
+        soft_value: limit.soft_value,
+        hard_value: limit.hard_value,
+        used,
+        project_id,
+        period_start,
+    })
+}
+
+/// Audit log the create that reached a soft limit. `audit` carries the
+/// caller's actor, org, and names; creates after the first only warn.
+pub fn audit_crossing(audit: AuditLogBuilder<'_>, org_id: &str, warning: &QuotaWarning) {
+    if !warning.crossed {
+        return;
+    }
+    let details = serde_json::json!({
+        "limit_name": warning.limit_name,
+        "used": warning.used,
+        "soft_value": warning.soft_value,
+        "hard_value": warning.hard_value,
+    });
+    if let Err(e) = audit
+        .action(AuditAction::ReachOrgSoftLimit)
+        .resource("org", org_id)
+        .details(&details)
+        .save()
+    {
+        tracing::warn!("Failed to write soft limit audit log: {}", e);
+    }
+}
+
+impl IntoResponseParts for QuotaWarning {
+    type Error = Infallible;
+
+    fn into_response_parts(
+        self,
+        mut res: ResponseParts,
+    ) -> std::result::Result<ResponseParts, Self::Error> {
+        if let Ok(value) = HeaderValue::from_str(&self.header_value()) {
+            res.headers_mut().insert(QUOTA_WARNING_HEADER, value);
+        }
+        Ok(res)
+    }
+}
@@ -14,6 +14,9 @@
 //! - RATE_LIMIT_STANDARD_RPM (default: 30)
 //! - RATE_LIMIT_RELAXED_RPM (default: 60)
 //! - RATE_LIMIT_ORG_OPS_RPM (default: 3000)
+//!
+//! Separately, projects can cap `/validate` calls per license
+//! (`max_validations_per_hour_per_license`), enforced by [`ValidationRateLimiter`].
 
 use std::sync::Arc;
 use std::time::Duration;
@@ -121,9 +124,135 @@ impl ActivationRateLimiter {
     }
 }
 
+impl ActivationRateLimiter {
+    /// Limiter for free license claims on /buy, checked per client IP and
+    /// per email hash: 5 claims per hour.
+    pub fn for_free_licenses() -> Self {
+        Self::new(5, 3600)
+    }
+
+    /// Limiter for /buy/wait long polls, checked per payment session and per
+    /// client IP: 60 polls per 10 minutes, about one every 10 seconds.
+    pub fn for_session_waits() -> Self {
+        Self::new(60, 600)
+    }
+}
+
 impl Default for ActivationRateLimiter {
     fn default() -> Self {
         // 3 requests per email per hour
         Self::new(3, 3600)
     }
 }
+
+// ============ License Validation Rate Limiter ============
+
+use std::collections::HashSet;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// Length of a per-license validation window (counters reset after this).
+const VALIDATION_WINDOW_SECS: i64 = 3600;
+
+/// Distinct IPs remembered per license per window. Beyond this the
+/// distinct-IP count stops growing, which is plenty to tell a handful of
+/// devices from a shared token.
+const MAX_TRACKED_IPS: usize = 256;
+
+/// One license's validations in the current window.
+struct ValidationWindow {
+    started_at: i64,
+    count: i64,
+    /// Hashes of the client IPs seen (bounded by MAX_TRACKED_IPS)
+    ips: HashSet<u64>,
+    /// Whether a validation has already been rejected in this window
+    throttled: bool,
+}
+
+impl ValidationWindow {
+    fn new(started_at: i64) -> Self {
+        Self {
+            started_at,
+            count: 0,
+            ips: HashSet::new(),
+            throttled: false,
+        }
+    }
+
+    fn ends_at(&self) -> i64 {
+        self.started_at + VALIDATION_WINDOW_SECS
+    }
+}
+
+/// Result of counting a validation against a license's hourly limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationCheck {
+    Allowed,
+    Throttled {
+        /// Seconds until the window resets
+        retry_after_secs: u64,
+        /// First rejection in this window (flag the license once per window)
+        first_in_window: bool,
+        /// Approximate number of distinct IPs that validated this window
+        distinct_ips: i64,
+    },
+}
+
+/// In-memory per-license validation counter.
+/// A license's window opens at its first validation and resets an hour later.
+/// Nothing survives a restart, which only ever errs toward allowing calls.
+#[derive(Default)]
+pub struct ValidationRateLimiter {
+    windows: Mutex<HashMap<String, ValidationWindow>>,
+}
+
+impl ValidationRateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a validation of `license_id` from `ip` against `limit` per hour.
+    /// Rejected calls still record their IP, so the distinct-IP count keeps
+    /// growing while a shared token is hammering the endpoint.
+    pub fn check(
+        &self,
+        license_id: &str,
+        ip: Option<&str>,
+        limit: i64,
+        now: i64,
+    ) -> ValidationCheck {
+        let mut map = self.windows.lock().unwrap();
+        let window = map
+            .entry(license_id.to_string())
+            .or_insert_with(|| ValidationWindow::new(now));
+        if now >= window.ends_at() {
+            *window = ValidationWindow::new(now);
+        }
+
+        if let Some(ip) = ip
+            && window.ips.len() < MAX_TRACKED_IPS
+        {
+            let mut hasher = DefaultHasher::new();
+            ip.hash(&mut hasher);
+            window.ips.insert(hasher.finish());
+        }
+
+        if window.count >= limit {
+            let first_in_window = !window.throttled;
+            window.throttled = true;
+            return ValidationCheck::Throttled {
+                retry_after_secs: (window.ends_at() - now) as u64,
+                first_in_window,
+                distinct_ips: window.ips.len() as i64,
+            };
+        }
+
+        window.count += 1;
+        ValidationCheck::Allowed
+    }
+
+    /// Drop windows that have ended. Call periodically.
+    pub fn cleanup(&self, now: i64) {
+        let mut map = self.windows.lock().unwrap();
+        map.retain(|_, window| now < window.ends_at());
+    }
+}
@@ -0,0 +1,250 @@
+//! Subscription reconciliation against the payment providers.
+//!
+//! Licenses follow subscriptions through webhooks, so a missed or failed
+//! delivery leaves a license out of date: a renewal that never extended it,
+//! or a cancellation that never ended it. A run walks an org's
+//! subscription-linked licenses for one provider, fetches each subscription,
+//! and records every license that disagrees. With `apply`, each discrepancy is
+//! fixed the way the missed webhook would have fixed it.
+//!
+//! Lookups are made one at a time with a pause between them, and go through
+//! the org's provider call limits, so a run over thousands of licenses stays
+//! under the provider's API rate limit.
+
+use std::time::Duration;
+
+use rusqlite::Connection;
+
+use crate::crypto::MasterKey;
+use crate::db::{AppState, queries};
+use crate::error::{AppError, OptionExt, Result, msg};
+use crate::models::{Discrepancy, DiscrepancyKind, License, ReconciliationRun};
+use crate::payments::{
+    LemonSqueezyClient, PaymentError, PaymentProvider, ProviderSubscription, StripeClient,
+    SubscriptionStatus,
+};
+use crate::util::LicenseExpirations;
+
+/// Pause between Stripe lookups (its read limit is 100 requests/second)
+pub const STRIPE_PACE: Duration = Duration::from_millis(50);
+
+/// Pause between LemonSqueezy lookups (its limit is 300 requests/minute)
+pub const LEMONSQUEEZY_PACE: Duration = Duration::from_millis(250);
+
+/// Longest wait honored from a rate-limited lookup before it's retried once
+const MAX_RETRY_WAIT: Duration = Duration::from_secs(60);
+
+/// An org's API client for the provider being reconciled.
+pub enum SubscriptionClient {
+    Stripe(StripeClient),
+    LemonSqueezy(LemonSqueezyClient),
+}
+
+impl SubscriptionClient {
+    /// Client from the org's stored provider config (None if not configured).
+    pub fn for_org(
+        conn: &Connection,
+        org_id: &str,
+        provider: PaymentProvider,
+        master_key: &MasterKey,
+    ) -> Result<Option<Self>> {
+        Ok(match provider {
+            PaymentProvider::Stripe => queries::get_org_stripe_config(conn, org_id, master_key)?
+                .map(|config| Self::Stripe(StripeClient::new(&config))),
+            PaymentProvider::LemonSqueezy => queries::get_org_ls_config(conn, org_id, master_key)?
+                .map(|config| Self::LemonSqueezy(LemonSqueezyClient::new(&config))),
+        })
+    }
+
+    pub fn provider(&self) -> PaymentProvider {
+        match self {
+            Self::Stripe(_) => PaymentProvider::Stripe,
+            Self::LemonSqueezy(_) => PaymentProvider::LemonSqueezy,
+        }
+    }
+
+    /// Pause between lookups that keeps a run under the provider's rate limit
+    pub fn pace(&self) -> Duration {
+        match self {
+            Self::Stripe(_) => STRIPE_PACE,
+            Self::LemonSqueezy(_) => LEMONSQUEEZY_PACE,
+        }
+    }
+
+    async fn get_subscription(
+        &self,
+        subscription_id: &str,
+    ) -> std::result::Result<Option<ProviderSubscription>, PaymentError> {
+        match self {
+            Self::Stripe(client) => client.get_subscription(subscription_id).await,
+            Self::LemonSqueezy(client) => client.get_subscription(subscription_id).await,
+        }
+    }
+}
+
+pub struct RunOptions<'a> {
+    /// Fix discrepancies instead of only reporting them
+    pub apply: bool,
+    /// Operator who started the run (None = scheduled)
+    pub started_by: Option<&'a str>,
+    /// Pause between provider lookups
+    pub pace: Duration,
+}
+
+/// Reconcile an org's licenses against `client`'s provider and save the run.
+pub async fn run(
+    state: &AppState,
+    org_id: &str,
+    client: &SubscriptionClient,
+    options: &RunOptions<'_>,
+) -> Result<ReconciliationRun> {
+    let provider = client.provider();
+    let started_at = state.clock.now();
+    let licenses = {
+        let conn = state.org_db(org_id).get()?;
+        queries::list_subscription_licenses(&conn, org_id, provider.as_ref())?
+    };
+
+    let mut discrepancies = Vec::new();
+    for (i, license) in licenses.iter().enumerate() {
+        if i > 0 && !options.pace.is_zero() {
+            tokio::time::sleep(options.pace).await;
+        }
+        let Some(subscription_id) = license.payment_provider_subscription_id.as_deref() else {
+            continue;
+        };
+        let lookup = fetch(state, org_id, client, subscription_id).await;
+        let Some(mut discrepancy) = compare(license, subscription_id, lookup) else {
+            continue;
+        };
+        if options.apply && discrepancy.expected_expires_at.is_some() {
+            match fix(state, org_id, license, &discrepancy) {
+                Ok(()) => discrepancy.fixed = true,
+                Err(e) => tracing::error!(
+                    license_id = %license.id,
+                    "Failed to apply reconciliation fix: {}",
+                    e
+                ),
+            }
+        }
+        discrepancies.push(discrepancy);
+    }
+
+    let run = ReconciliationRun {
+        id: uuid::Uuid::new_v4().to_string(),
+        org_id: org_id.to_string(),
+        provider: provider.as_ref().to_string(),
+        applied: options.apply,
+        started_by: options.started_by.map(String::from),
+        started_at,
+        finished_at: state.clock.now(),
+        checked: licenses.len() as i64,
+        discrepancies,
+    };
+    queries::create_reconciliation_run(&state.db.get()?, &run)?;
+    Ok(run)
+}
+
+/// Look up a subscription, retrying once if the provider rate limits us.
+async fn fetch(
+    state: &AppState,
+    org_id: &str,
+    client: &SubscriptionClient,
+    subscription_id: &str,
+) -> Result<Option<ProviderSubscription>> {
+    let lookup = || {
+        state
+            .provider_calls
+            .run(org_id, client.get_subscription(subscription_id))
+    };
+    match lookup().await {
+        Err(AppError::Payment(PaymentError::RateLimited { retry_after_secs }))
+        | Err(AppError::ProviderBusy { retry_after_secs }) => {
+            tokio::time::sleep(Duration::from_secs(retry_after_secs).min(MAX_RETRY_WAIT)).await;
+            lookup().await
+        }
+        result => result,
+    }
+}
+
+/// The discrepancy between a license and its subscription, if any.
+fn compare(
+    license: &License,
+    subscription_id: &str,
+    lookup: Result<Option<ProviderSubscription>>,
+) -> Option<Discrepancy> {
+    let discrepancy = |kind| Discrepancy {
+        license_id: license.id.clone(),
+        project_id: license.project_id.clone(),
+        subscription_id: subscription_id.to_string(),
+        kind,
+        provider_status: None,
+        provider_period_end: None,
+        license_expires_at: license.expires_at,
+        expected_expires_at: None,
+        fixed: false,
+        error: None,
+    };
+
+    let subscription = match lookup {
+        Ok(Some(subscription)) => subscription,
+        Ok(None) => return Some(discrepancy(DiscrepancyKind::NotFound)),
+        Err(e) => {
+            return Some(Discrepancy {
+                error: Some(e.to_string()),
+                ..discrepancy(DiscrepancyKind::ProviderError)
+            });
+        }
+    };
+
+    // Paused licenses are resumed by webhook with their paused time credited;
+    // their expiration isn't comparable to the provider's period end
+    if license.paused_at.is_some() || subscription.status == SubscriptionStatus::Paused {
+        return None;
+    }
+    let period_end = subscription.period_end?;
+    let kind = match subscription.status {
+        SubscriptionStatus::Active if license.expires_at.is_some_and(|exp| exp < period_end) => {
+            DiscrepancyKind::MissedRenewal
+        }
+        SubscriptionStatus::PastDue | SubscriptionStatus::Ended
+            if license.expires_at.is_none_or(|exp| exp > period_end) =>
+        {
+            DiscrepancyKind::NotRenewed
+        }
+        _ => return None,
+    };
+
+    Some(Discrepancy {
+        provider_status: Some(subscription.provider_status),
+        provider_period_end: Some(period_end),
+        expected_expires_at: Some(period_end),
+        ..discrepancy(kind)
+    })
+}
+
+/// Apply a discrepancy's fix through the same expiration update renewal
+/// webhooks use.
+fn fix(state: &AppState, org_id: &str, license: &License, discrepancy: &Discrepancy) -> Result<()> {
+    let Some(period_end) = discrepancy.expected_expires_at else {
+        return Ok(());
+    };
+    let conn = state.org_db(org_id).get()?;
+    let (expires_at, updates_expires_at) = match discrepancy.kind {
+        DiscrepancyKind::MissedRenewal => {
+            let product = queries::get_product_by_id(&conn, &license.product_id)?
+                .or_not_found(msg::PRODUCT_NOT_FOUND)?;
+            let exps =
+                LicenseExpirations::for_renewal(&product, Some(period_end), state.clock.now());
+            (exps.license_exp, exps.updates_exp)
+        }
+        // Updates the customer already paid for stay, up to the period end
+        _ => (
+            Some(period_end),
+            license
+                .updates_expires_at
+                .map(|updates| updates.min(period_end)),
+        ),
+    };
+    queries::extend_license_expiration(&conn, &license.id, expires_at, updates_expires_at)
+}
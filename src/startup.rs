@@ -0,0 +1,284 @@
+//! Startup self-check.
+//!
+//! Runs before the server binds its listener, and on its own with `--check`,
+//! so a misconfiguration stops the deploy with a specific error instead of
+//! surfacing as confusing failures hours later. Failures stop startup;
+//! warnings are logged and startup continues.
+//!
+//! The master key is checked against a sentinel value encrypted with it at
+//! first boot. A wrong key (a restore without the matching key file, a typo in
+//! `PAYCHECK_MASTER_KEY_FILE`) fails the check before anything is decrypted.
+
+use rusqlite::Connection;
+
+use crate::crypto::{EmailHasher, MasterKey};
+use crate::db::migrations::{self, MigrationTarget};
+use crate::db::{AppState, DbPool, queries};
+use crate::email::ResendKeyCheck;
+use crate::error::Result;
+use crate::storage_health;
+
+/// Key of the master key sentinel in `system_config`
+pub const KEY_SENTINEL_CONFIG_KEY: &str = "master_key_sentinel";
+
+/// Encryption context shared by everything in `system_config`
+const SYSTEM_CONFIG_CONTEXT: &str = "system-config";
+
+const KEY_SENTINEL: &[u8] = b"paycheck master key sentinel v1";
+
+/// What [`check_master_key`] found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeySentinel {
+    /// The key decrypts the stored sentinel
+    Verified,
+    /// There was no sentinel yet; one was stored under the key
+    Created,
+}
+
+/// Failures and warnings from [`self_check`].
+#[derive(Debug, Default)]
+pub struct SelfCheckReport {
+    pub failures: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl SelfCheckReport {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    fn fail(&mut self, message: impl Into<String>) {
+        self.failures.push(message.into());
+    }
+
+    fn warn(&mut self, message: impl Into<String>) {
+        self.warnings.push(message.into());
+    }
+
+    /// Log every failure as an error and every warning as a warning.
+    pub fn log(&self) {
+        for warning in &self.warnings {
+            tracing::warn!("Self-check: {}", warning);
+        }
+        for failure in &self.failures {
+            tracing::error!("Self-check failed: {}", failure);
+        }
+    }
+}
+
+/// Store the sentinel encrypted under `master_key`, replacing any old one.
+/// Key rotation calls this with the new key.
+pub fn store_key_sentinel(conn: &Connection, master_key: &MasterKey) -> Result<()> {
+    let encrypted = master_key.encrypt_private_key(SYSTEM_CONFIG_CONTEXT, KEY_SENTINEL)?;
+    queries::set_system_config(conn, KEY_SENTINEL_CONFIG_KEY, &encrypted)
+}
+
+/// Check that `master_key` is the key this database was encrypted with.
+///
+/// The first time, the sentinel is stored. A database from before sentinels
+/// must first show the key decrypts its email HMAC key, so a wrong key isn't
+/// written down as the right one.
+pub fn check_master_key(
+    conn: &Connection,
+    master_key: &MasterKey,
+) -> std::result::Result<KeySentinel, String> {
+    let wrong_key = || {
+        "The master key can't decrypt this database's encrypted data. \
+         PAYCHECK_MASTER_KEY_FILE must hold the key the database was created with \
+         (after a restore, the key file from the same server). \
+         To change keys, use --rotate-key."
+            .to_string()
+    };
+    let read = |key: &str| {
+        queries::get_system_config(conn, key)
+            .map_err(|e| format!("Failed to read system config: {}", e))
+    };
+
+    if let Some(sentinel) = read(KEY_SENTINEL_CONFIG_KEY)? {
+        return match master_key.decrypt_private_key(SYSTEM_CONFIG_CONTEXT, &sentinel) {
+            Ok(plaintext) if plaintext == KEY_SENTINEL => Ok(KeySentinel::Verified),
+            _ => Err(wrong_key()),
+        };
+    }
+
+    if let Some(hmac_key) = read(EmailHasher::CONFIG_KEY)?
+        && master_key
+            .decrypt_private_key(SYSTEM_CONFIG_CONTEXT, &hmac_key)
+            .is_err()
+    {
+        return Err(wrong_key());
+    }
+    store_key_sentinel(conn, master_key)
+        .map_err(|e| format!("Failed to store master key sentinel: {}", e))?;
+    Ok(KeySentinel::Created)
+}
+
+/// Run every startup check against `state`.
+pub async fn self_check(state: &AppState) -> SelfCheckReport {
+    let mut report = SelfCheckReport::default();
+
+    match state.db.get() {
+        Ok(conn) => {
+            if let Err(e) = check_master_key(&conn, &state.master_key) {
+                report.fail(e);
+            } else {
+                check_secrets(&mut report, state, &conn);
+            }
+        }
+        Err(e) => report.fail(format!("Can't open the main database: {}", e)),
+    }
+
+    check_base_url(&mut report, &state.base_url);
+
+    check_writable(&mut report, "main database", &state.db);
+    check_writable(&mut report, "audit database", &state.audit);
+    for (org_id, pool) in state.org_dbs.dedicated_pools() {
+        check_writable(&mut report, &format!("database of org {}", org_id), &pool);
+    }
+
+    check_schema_version(&mut report, "main", &state.db, MigrationTarget::Main);
+    check_schema_version(&mut report, "audit", &state.audit, MigrationTarget::Audit);
+    check_storage_continuity(&mut report, state);
+
+    if !state.audit_log_enabled {
+        report.warn("AUDIT_LOG_ENABLED=false: nothing is being audit-logged");
+    }
+    match state.email_service.check_system_key().await {
+        None => report.warn(
+            "PAYCHECK_RESEND_API_KEY is not set: only orgs with their own Resend key can send email",
+        ),
+        Some(ResendKeyCheck::Accepted) => {}
+        Some(ResendKeyCheck::Rejected(status)) => report.fail(format!(
+            "Resend refused PAYCHECK_RESEND_API_KEY (HTTP {}). Create a new key in Resend \
+             or unset the variable.",
+            status
+        )),
+        Some(ResendKeyCheck::Unknown(reason)) => report.warn(format!(
+            "Couldn't check PAYCHECK_RESEND_API_KEY with Resend: {}",
+            reason
+        )),
+    }
+
+    report
+}
+
+/// Test-decrypt one project signing key and one org service config, so data
+/// encrypted under another key (or damaged) shows up now.
+fn check_secrets(report: &mut SelfCheckReport, state: &AppState, conn: &Connection) {
+    let project = state.tenant_pools().into_iter().find_map(|pool| {
+        let conn = pool.get().ok()?;
+        let project = queries::list_all_projects(&conn)
+            .ok()?
+            .into_iter()
+            .find(|p| p.deleted_at.is_none())?;
+        Some((conn, project))
+    });
+    if let Some((project_conn, project)) = project
+        && let Err(e) =
+            queries::decrypt_project_private_key(&project_conn, &project, &state.master_key)
+    {
+        report.fail(format!(
+            "Can't decrypt the signing key of project {}: {}. Its org's data key or \
+             the key itself was encrypted under a different master key.",
+            project.id, e
+        ));
+    }
+
+    let config = match queries::list_all_org_service_configs(conn) {
+        Ok(configs) => configs.into_iter().next(),
+        Err(e) => {
+            report.fail(format!("Can't read org service configs: {}", e));
+            return;
+        }
+    };
+    if let Some(config) = config {
+        let decrypted = queries::org_data_key(conn, &config.org_id, &state.master_key)
+            .and_then(|key| key.decrypt_private_key(&config.org_id, &config.config_encrypted));
+        if let Err(e) = decrypted {
+            report.fail(format!(
+                "Can't decrypt the {} config of org {}: {}",
+                config.provider.as_str(),
+                config.org_id,
+                e
+            ));
+        }
+    }
+}
+
+fn check_base_url(report: &mut SelfCheckReport, base_url: &str) {
+    let valid = reqwest::Url::parse(base_url)
+        .is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.host().is_some());
+    if !valid {
+        report.fail(format!(
+            "BASE_URL must be an absolute http(s) URL such as https://paycheck.example.com \
+             (got {:?})",
+            base_url
+        ));
+    }
+}
+
+/// Write inside a savepoint that's rolled back, so nothing is left behind.
+fn check_writable(report: &mut SelfCheckReport, name: &str, pool: &DbPool) {
+    let result = pool.get().map_err(|e| e.to_string()).and_then(|conn| {
+        conn.execute_batch(
+            "SAVEPOINT self_check;
+             CREATE TABLE self_check_probe (id INTEGER);
+             ROLLBACK TO self_check;
+             RELEASE self_check;",
+        )
+        .map_err(|e| e.to_string())
+    });
+    if let Err(e) = result {
+        report.fail(format!(
+            "Can't write to the {}: {}. Check the file and directory permissions.",
+            name, e
+        ));
+    }
+}
+
+/// An audit database that lost heartbeats, or a database that moved, since
+/// the last start. Warnings, since the server can run on either; they're
+/// recorded as seen once the server starts.
+fn check_storage_continuity(report: &mut SelfCheckReport, state: &AppState) {
+    let result = match (state.db.get(), state.audit.get()) {
+        (Ok(main), Ok(audit)) => storage_health::check_continuity(&main, &audit),
+        (Err(e), _) | (_, Err(e)) => Err(e.into()),
+    };
+    match result {
+        Ok(warnings) => {
+            for warning in warnings {
+                report.warn(warning);
+            }
+        }
+        Err(e) => report.fail(format!("Can't read the storage heartbeats: {}", e)),
+    }
+}
+
+fn check_schema_version(
+    report: &mut SelfCheckReport,
+    name: &str,
+    pool: &DbPool,
+    target: MigrationTarget,
+) {
+    let version = match pool.get() {
+        Ok(conn) => migrations::get_version(&conn),
+        Err(e) => {
+            report.fail(format!("Can't open the {} database: {}", name, e));
+            return;
+        }
+    };
+    let latest = migrations::latest_version(target);
+    match version {
+        Ok(version) if version > latest => report.fail(format!(
+            "The {} database is at schema version {}, newer than this build supports ({}). \
+             It was written by a newer Paycheck; run that version or restore a backup.",
+            name, version, latest
+        )),
+        Ok(version) if version < latest => report.fail(format!(
+            "The {} database is at schema version {}, expected {}. Migrations didn't run.",
+            name, version, latest
+        )),
+        Ok(_) => {}
+        Err(e) => report.fail(format!("Can't read the {} schema version: {}", name, e)),
+    }
+}
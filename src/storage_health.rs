@@ -0,0 +1,294 @@
+//! Storage health: heartbeats in the main and audit databases, and the
+//! checks behind `GET /operators/storage-health`.
+//!
+//! The failures this watches for don't break anything when they happen. An
+//! audit database on an ephemeral disk is replaced by an empty one along
+//! with the node, and requests carry on writing to it. A host clock that's
+//! off stamps audit entries out of order with the license changes they
+//! describe.
+//!
+//! Each database keeps a heartbeat row that the maintenance task bumps every
+//! tick, and the main database keeps a copy of the audit database's. At
+//! startup, an audit database whose beat is behind that copy has been
+//! replaced or restored from an older file, and a database whose path
+//! changed since the last start has moved. While running, a stalled
+//! heartbeat, low free space on a database's filesystem, or a database clock
+//! that disagrees with the process clock is logged as an error.
+
+use std::fs;
+use std::path::Path;
+
+use rusqlite::Connection;
+use serde::Serialize;
+
+use crate::backup::{AUDIT_DATABASE, MAIN_DATABASE};
+use crate::db::{AppState, queries};
+use crate::error::Result;
+use crate::models::StorageHeartbeat;
+
+/// A heartbeat older than this (three maintenance ticks) has stalled
+pub const HEARTBEAT_STALL_SECS: i64 = 15 * 60;
+
+/// Less free space than this on a database's filesystem is a problem
+pub const LOW_FREE_SPACE_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// How far a database's clock may be from the process clock
+pub const MAX_CLOCK_DRIFT_MS: i64 = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageProblem {
+    /// No heartbeat for `HEARTBEAT_STALL_SECS`, or none ever
+    HeartbeatStalled,
+    LowFreeSpace,
+    ClockDrift,
+}
+
+/// Response of `GET /operators/storage-health`.
+#[derive(Debug, Serialize)]
+pub struct StorageHealth {
+    /// No database has a problem
+    pub healthy: bool,
+    pub databases: Vec<DatabaseHealth>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DatabaseHealth {
+    /// `main` or `audit`
+    pub db: &'static str,
+    /// None for an in-memory database
+    pub path: Option<String>,
+    /// The database file and its write-ahead log
+    pub size_bytes: Option<u64>,
+    /// Space available on the filesystem holding the database. None off
+    /// Unix, or when it can't be read.
+    pub free_bytes: Option<u64>,
+    pub heartbeat: Option<StorageHeartbeat>,
+    /// How far the database's `unixepoch()` is ahead of the process clock
+    /// (negative when behind)
+    pub clock_drift_ms: i64,
+    pub problems: Vec<StorageProblem>,
+}
+
+impl StorageHealth {
+    /// Log every problem as an error.
+    pub fn log_problems(&self) {
+        for database in &self.databases {
+            let path = database.path.as_deref().unwrap_or(":memory:");
+            for problem in &database.problems {
+                match problem {
+                    StorageProblem::HeartbeatStalled => match &database.heartbeat {
+                        Some(heartbeat) => tracing::error!(
+                            "The {} database ({}) has had no heartbeat since {}",
+                            database.db,
+                            path,
+                            heartbeat.beat_at
+                        ),
+                        None => tracing::error!(
+                            "The {} database ({}) has never had a heartbeat",
+                            database.db,
+                            path
+                        ),
+                    },
+                    StorageProblem::LowFreeSpace => tracing::error!(
+                        "Only {} MiB free on the filesystem holding the {} database ({})",
+                        database.free_bytes.unwrap_or(0) / (1024 * 1024),
+                        database.db,
+                        path
+                    ),
+                    StorageProblem::ClockDrift => tracing::error!(
+                        "The {} database's clock is {} ms off the process clock: \
+                         timestamps written by SQLite and by Paycheck will disagree",
+                        database.db,
+                        database.clock_drift_ms
+                    ),
+                }
+            }
+        }
+    }
+}
+
+/// File behind `conn`, or None for an in-memory database.
+pub fn database_path(conn: &Connection) -> Option<String> {
+    conn.path()
+        .filter(|path| !path.is_empty())
+        .map(str::to_string)
+}
+
+/// Milliseconds `db_ms` is ahead of the process clock, read at `before_ms`
+/// and `after_ms` either side of the database's. The midpoint is used, so a
+/// slow query doesn't show up as drift.
+pub fn clock_drift_ms(db_ms: i64, before_ms: i64, after_ms: i64) -> i64 {
+    db_ms - (before_ms + (after_ms - before_ms) / 2)
+}
+
+pub fn is_clock_drifting(drift_ms: i64) -> bool {
+    drift_ms.abs() > MAX_CLOCK_DRIFT_MS
+}
+
+pub fn is_heartbeat_stalled(heartbeat: Option<&StorageHeartbeat>, now: i64) -> bool {
+    heartbeat.is_none_or(|heartbeat| now - heartbeat.beat_at > HEARTBEAT_STALL_SECS)
+}
+
+/// Compare `conn`'s `unixepoch()` with the process clock.
+pub fn measure_clock_drift(conn: &Connection) -> Result<i64> {
+    let before = chrono::Utc::now().timestamp_millis();
+    let db_ms: i64 = conn.query_row(
+        "SELECT CAST(unixepoch('subsec') * 1000 AS INTEGER)",
+        [],
+        |row| row.get(0),
+    )?;
+    let after = chrono::Utc::now().timestamp_millis();
+    Ok(clock_drift_ms(db_ms, before, after))
+}
+
+/// Bump both databases' heartbeats and copy the audit database's into the
+/// main one.
+pub fn beat(main: &Connection, audit: &Connection, now: i64) -> Result<()> {
+    let audit_path = database_path(audit).unwrap_or_default();
+    let audit_beat = queries::beat_storage_heartbeat(audit, AUDIT_DATABASE, &audit_path, now)?;
+    let main_path = database_path(main).unwrap_or_default();
+    queries::beat_storage_heartbeat(main, MAIN_DATABASE, &main_path, now)?;
+    queries::save_storage_heartbeat(main, &audit_beat)
+}
+
+/// Record a server start in both databases, counting it as a beat.
+pub fn record_start(main: &Connection, audit: &Connection, now: i64) -> Result<()> {
+    let audit_path = database_path(audit).unwrap_or_default();
+    let audit_beat = queries::record_storage_start(audit, AUDIT_DATABASE, &audit_path, now)?;
+    let main_path = database_path(main).unwrap_or_default();
+    queries::record_storage_start(main, MAIN_DATABASE, &main_path, now)?;
+    queries::save_storage_heartbeat(main, &audit_beat)
+}
+
+/// [`record_start`] on the server's databases.
+pub fn record_server_start(state: &AppState) -> Result<()> {
+    record_start(&state.db.get()?, &state.audit.get()?, state.clock.now())
+}
+
+/// What the heartbeats say happened since the last run, as warnings for the
+/// startup self-check. Reads only, so `--check` can run it.
+pub fn check_continuity(main: &Connection, audit: &Connection) -> Result<Vec<String>> {
+    let mut warnings = Vec::new();
+
+    let audit_beat = queries::get_storage_heartbeat(audit, AUDIT_DATABASE)?;
+    let audit_copy = queries::get_storage_heartbeat(main, AUDIT_DATABASE)?;
+    if let Some(copy) = &audit_copy
+        && audit_beat.as_ref().is_none_or(|beat| beat.beat < copy.beat)
+    {
+        warnings.push(format!(
+            "The audit database is at heartbeat {}, but the main database saw it \
+             reach {} (at {}, as {}). It was replaced, lost, or restored from an \
+             older copy, so audit entries may be missing. Keep AUDIT_DATABASE_PATH \
+             on persistent storage.",
+            audit_beat.as_ref().map_or(0, |beat| beat.beat),
+            copy.beat,
+            copy.beat_at,
+            copy.path
+        ));
+    }
+
+    for (name, conn, recorded) in [
+        (
+            MAIN_DATABASE,
+            main,
+            queries::get_storage_heartbeat(main, MAIN_DATABASE)?,
+        ),
+        (AUDIT_DATABASE, audit, audit_beat),
+    ] {
+        let path = database_path(conn).unwrap_or_default();
+        if let Some(recorded) = recorded
+            && recorded.path != path
+        {
+            warnings.push(format!(
+                "The {} database moved from {} to {} since the last start",
+                name, recorded.path, path
+            ));
+        }
+    }
+
+    Ok(warnings)
+}
+
+/// Check one database against the process clock; `now` is
+/// `AppState::clock`'s time, for the heartbeat age.
+pub fn check_database(conn: &Connection, db: &'static str, now: i64) -> Result<DatabaseHealth> {
+    let path = database_path(conn);
+    let heartbeat = queries::get_storage_heartbeat(conn, db)?;
+    let clock_drift_ms = measure_clock_drift(conn)?;
+    let size_bytes = path.as_deref().and_then(|path| file_size(Path::new(path)));
+    let free_bytes = path.as_deref().and_then(|path| free_space(Path::new(path)));
+
+    let mut problems = Vec::new();
+    if is_heartbeat_stalled(heartbeat.as_ref(), now) {
+        problems.push(StorageProblem::HeartbeatStalled);
+    }
+    if free_bytes.is_some_and(|free| free < LOW_FREE_SPACE_BYTES) {
+        problems.push(StorageProblem::LowFreeSpace);
+    }
+    if is_clock_drifting(clock_drift_ms) {
+        problems.push(StorageProblem::ClockDrift);
+    }
+
+    Ok(DatabaseHealth {
+        db,
+        path,
+        size_bytes,
+        free_bytes,
+        heartbeat,
+        clock_drift_ms,
+        problems,
+    })
+}
+
+/// Check the main and audit databases.
+pub fn check(state: &AppState) -> Result<StorageHealth> {
+    let now = state.clock.now();
+    let databases = vec![
+        check_database(&state.db.get()?, MAIN_DATABASE, now)?,
+        check_database(&state.audit.get()?, AUDIT_DATABASE, now)?,
+    ];
+    Ok(StorageHealth {
+        healthy: databases.iter().all(|db| db.problems.is_empty()),
+        databases,
+    })
+}
+
+/// One maintenance tick: beat both heartbeats, then check both databases.
+pub fn heartbeat(state: &AppState) -> Result<StorageHealth> {
+    beat(&state.db.get()?, &state.audit.get()?, state.clock.now())?;
+    check(state)
+}
+
+fn file_size(path: &Path) -> Option<u64> {
+    let size = fs::metadata(path).ok()?.len();
+    let mut wal = path.as_os_str().to_owned();
+    wal.push("-wal");
+    Some(size + fs::metadata(wal).map_or(0, |meta| meta.len()))
+}
+
+/// `statvfs` field widths differ between platforms, so the casts are only
+/// unnecessary on some of them.
+#[cfg(unix)]
+#[allow(clippy::unnecessary_cast)]
+fn free_space(path: &Path) -> Option<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let dir = CString::new(dir.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `dir` is a NUL-terminated path and `stat` is a valid buffer
+    if unsafe { libc::statvfs(dir.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+fn free_space(_path: &Path) -> Option<u64> {
+    None
+}
@@ -1,11 +1,21 @@
 //! Shared utility functions for the Paycheck application.
 
+use std::io::{self, Write};
+use std::net::IpAddr;
+
+use axum::body::{Body, Bytes};
 use axum::http::HeaderMap;
 use rusqlite::Connection;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
 
-use crate::db::queries;
+use crate::db::{AppState, queries};
 use crate::error::Result;
-use crate::models::{ActorType, AuditAction, AuditLog, AuditLogNames, Product};
+use crate::middleware::RequestId;
+use crate::models::{
+    ActorType, AuditAction, AuditFailureMode, AuditLog, AuditLogNames, AuditSensitivity, Product,
+    redact_pii_details,
+};
 
 const SECONDS_PER_DAY: i64 = 86400;
 
@@ -40,6 +50,65 @@ impl LicenseExpirations {
             updates_exp: updates_days.map(|days| base_time + (days as i64) * SECONDS_PER_DAY),
         }
     }
+
+    /// Expirations after a subscription renewal.
+    ///
+    /// `period_end` is the billing period end from the payment provider,
+    /// which is more accurate than calculating from product settings
+    /// (prorations, billing date changes). Without it, falls back to
+    /// `now + license_exp_days`.
+    pub fn for_renewal(product: &Product, period_end: Option<i64>, now: i64) -> Self {
+        let fallback = Self::from_product(product, now);
+        // With a provider period_end, updates keep their offset from the license
+        let updates_exp = match (
+            period_end,
+            product.license_exp_days,
+            product.updates_exp_days,
+        ) {
+            (Some(pe), Some(_), Some(upd_days)) => Some(pe + (upd_days as i64) * SECONDS_PER_DAY),
+            // updates_exp_days not set: updates follow the license
+            (Some(pe), Some(lic_days), None) if lic_days > 0 => fallback.updates_exp.map(|_| pe),
+            _ => fallback.updates_exp,
+        };
+        Self {
+            license_exp: period_end.or(fallback.license_exp),
+            updates_exp,
+        }
+    }
+
+    /// Update window end after an updates-only renewal of `days`.
+    ///
+    /// Time still left on the window carries over; a lapsed window restarts
+    /// from `now`. Perpetual updates (None) stay perpetual.
+    pub fn extend_updates(current: Option<i64>, days: i32, now: i64) -> Option<i64> {
+        current.map(|exp| exp.max(now) + (days as i64) * SECONDS_PER_DAY)
+    }
+}
+
+/// Source of the current Unix timestamp for time-dependent handlers.
+///
+/// The system clock in production; tests pin it with [`Clock::fixed`] so
+/// computed expirations can be asserted exactly.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Clock {
+    fixed: Option<i64>,
+}
+
+impl Clock {
+    pub fn system() -> Self {
+        Self { fixed: None }
+    }
+
+    /// A clock that always reports `timestamp`.
+    pub fn fixed(timestamp: i64) -> Self {
+        Self {
+            fixed: Some(timestamp),
+        }
+    }
+
+    pub fn now(&self) -> i64 {
+        self.fixed.unwrap_or_else(|| chrono::Utc::now().timestamp())
+    }
 }
 
 /// Extract client IP address and user-agent from request headers.
@@ -61,6 +130,17 @@ pub fn extract_request_info(headers: &HeaderMap) -> (Option<String>, Option<Stri
     (ip, user_agent)
 }
 
+/// The client's address: the first `x-forwarded-for` entry (later ones are
+/// proxies), else `x-real-ip`, else the connection's peer address.
+pub fn client_ip(headers: &HeaderMap, peer: Option<IpAddr>) -> Option<IpAddr> {
+    let (forwarded, _) = extract_request_info(headers);
+    forwarded
+        .as_deref()
+        .and_then(|v| v.split(',').next())
+        .and_then(|v| v.trim().parse().ok())
+        .or(peer)
+}
+
 /// Extract a Bearer token from the Authorization header.
 ///
 /// Returns the token string without the "Bearer " prefix, or None if
@@ -74,6 +154,45 @@ pub fn extract_bearer_token(headers: &HeaderMap) -> Option<&str> {
         .filter(|s| !s.is_empty())
 }
 
+/// A response body written on a blocking thread by `write` and sent as it's
+/// produced, for downloads too large to build in memory. If `write` fails
+/// partway, the error is logged under `what` and the body ends with an
+/// error, so the client sees a truncated download rather than a short one.
+pub fn blocking_stream_body<F>(what: String, write: F) -> Body
+where
+    F: FnOnce(&mut dyn Write) -> Result<()> + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel(8);
+    tokio::task::spawn_blocking(move || {
+        let mut out = io::BufWriter::new(ChannelWriter { tx: tx.clone() });
+        if let Err(e) = write(&mut out) {
+            drop(out);
+            tracing::error!("{} failed: {}", what, e);
+            let _ = tx.blocking_send(Err(io::Error::other(e.to_string())));
+        }
+    });
+    Body::from_stream(ReceiverStream::new(rx))
+}
+
+/// Forwards written bytes to the response body. Fails once the client has
+/// gone away, which stops the writer.
+struct ChannelWriter {
+    tx: mpsc::Sender<io::Result<Bytes>>,
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.tx
+            .blocking_send(Ok(Bytes::copy_from_slice(buf)))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "client disconnected"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
 /// Builder for creating audit log entries.
 ///
 /// Provides a fluent API for constructing audit logs with named methods
@@ -81,7 +200,7 @@ pub fn extract_bearer_token(headers: &HeaderMap) -> Option<&str> {
 ///
 /// # Example
 /// ```ignore
-/// AuditLogBuilder::new(&audit_conn, state.audit_log_enabled, &headers)
+/// AuditLogBuilder::for_state(&audit_conn, &state, &headers)
 ///     .actor(ActorType::User, Some(&user_id))
 ///     .action(AuditAction::CreateOrg)
 ///     .resource("org", &org.id)
@@ -104,6 +223,8 @@ pub struct AuditLogBuilder<'a> {
     names: AuditLogNames,
     auth_type: Option<&'a str>,
     auth_credential: Option<&'a str>,
+    redact_pii: bool,
+    failure_mode: AuditFailureMode,
 }
 
 impl<'a> AuditLogBuilder<'a> {
@@ -124,9 +245,31 @@ impl<'a> AuditLogBuilder<'a> {
             names: AuditLogNames::default(),
             auth_type: None,
             auth_credential: None,
+            redact_pii: false,
+            failure_mode: AuditFailureMode::LogAndContinue,
         }
     }
 
+    /// Create a builder using the app's audit settings (enabled, PII
+    /// minimization, failure mode).
+    pub fn for_state(conn: &'a Connection, state: &AppState, headers: &'a HeaderMap) -> Self {
+        Self::new(conn, state.audit_log_enabled, headers)
+            .redact_pii(state.pii_minimization)
+            .failure_mode(state.audit_failure_mode)
+    }
+
+    /// Strip user names and emails from names and details before saving.
+    pub fn redact_pii(mut self, redact: bool) -> Self {
+        self.redact_pii = redact;
+        self
+    }
+
+    /// What to do when the entry can't be written (see [`AuditFailureMode`]).
+    pub fn failure_mode(mut self, mode: AuditFailureMode) -> Self {
+        self.failure_mode = mode;
+        self
+    }
+
     /// Set the actor type and optional user ID.
     pub fn actor(mut self, actor_type: ActorType, user_id: Option<&'a str>) -> Self {
         self.actor_type = actor_type;
@@ -184,25 +327,86 @@ impl<'a> AuditLogBuilder<'a> {
         self.auth(method.auth_type(), method.auth_credential())
     }
 
-    /// Save the audit log entry to the database.
+    /// Save the audit log entry to the database, tagged with the current
+    /// request's ID.
+    ///
+    /// A failed write is only an error when the action fails closed (see
+    /// [`AuditFailureMode`]); otherwise it's logged and the entry returned.
     pub fn save(self) -> Result<AuditLog> {
+        let (conn, enabled, fails_closed) = (self.conn, self.enabled, self.fails_closed());
+        let entry = self.build();
+        // Skip database insert if audit logging is disabled
+        if enabled && let Err(e) = queries::insert_audit_log(conn, &entry) {
+            if fails_closed {
+                return Err(e);
+            }
+            tracing::error!(
+                action = %entry.action,
+                resource_id = %entry.resource_id,
+                "Failed to write audit log entry: {}",
+                e
+            );
+        }
+        Ok(entry)
+    }
+
+    /// Build the entry without saving it, for writing through the audit
+    /// outbox (`db::outbox::with_audited_tx`). None when audit logging is disabled.
+    pub fn entry(self) -> Option<AuditLog> {
+        self.enabled.then(|| self.build())
+    }
+
+    /// [`entry`](Self::entry) for sensitive actions, built inside the
+    /// transaction (`db::outbox::try_with_audited_tx`). When the action fails
+    /// closed the entry is also saved now, before the commit, so a failed
+    /// write rolls the change back. The relay skips the saved copy.
+    pub fn checked_entry(self) -> Result<Option<AuditLog>> {
+        if self.fails_closed() {
+            self.save().map(Some)
+        } else {
+            Ok(self.entry())
+        }
+    }
+
+    /// Whether a failed write should fail the request: a sensitive action
+    /// under `fail_request`.
+    fn fails_closed(&self) -> bool {
+        self.enabled
+            && self.failure_mode == AuditFailureMode::FailRequest
+            && self.action.sensitivity() == AuditSensitivity::Sensitive
+    }
+
+    fn build(self) -> AuditLog {
         let (ip, ua) = extract_request_info(self.headers);
-        queries::create_audit_log(
-            self.conn,
-            self.enabled,
+        let request_id = RequestId::current();
+        let (names, redacted) = if self.redact_pii {
+            (
+                self.names.redact_pii(self.resource_type),
+                self.details.map(redact_pii_details),
+            )
+        } else {
+            (self.names, None)
+        };
+        let details = if self.redact_pii {
+            redacted.as_ref()
+        } else {
+            self.details
+        };
+        queries::new_audit_log(
             self.actor_type,
             self.user_id,
             self.action.as_ref(),
             self.resource_type,
             self.resource_id,
-            self.details,
+            details,
             self.org_id,
             self.project_id,
             ip.as_deref(),
             ua.as_deref(),
-            &self.names,
+            &names,
             self.auth_type,
             self.auth_credential,
+            request_id.as_ref().map(RequestId::as_str),
         )
     }
 }
@@ -0,0 +1,126 @@
+//! Webhook mirroring, for developing integrations against real traffic.
+//!
+//! An operator can point a project's `webhook_mirror_url` at a developer's
+//! tunnel. Until the mirror expires, every webhook Paycheck sends for the
+//! project (activation code webhooks) is also POSTed there, and every
+//! payment provider webhook it receives for the project is forwarded there
+//! verbatim after it has been processed. Mirrored requests carry
+//! `X-Paycheck-Mirror: true`.
+//!
+//! Mirroring never gets in the way of the real delivery: each copy is sent
+//! once, in the background, with a short timeout, and failures are only
+//! logged.
+
+use std::time::Duration;
+
+use axum::body::Bytes;
+use axum::http::HeaderMap;
+use reqwest::{Client, RequestBuilder};
+
+use crate::external_url;
+use crate::webhook_payloads::WebhookBody;
+
+/// Header set on every mirrored request.
+pub const MIRROR_HEADER: &str = "X-Paycheck-Mirror";
+
+/// How long a mirror stays on when the operator doesn't say.
+pub const DEFAULT_MIRROR_DAYS: i64 = 7;
+
+/// Longest a mirror can be set for.
+pub const MAX_MIRROR_DAYS: i64 = 30;
+
+/// Mirrored requests give up after this long.
+const MIRROR_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Provider webhook headers copied to the mirror (signatures included, so
+/// the developer can verify them with their own test secrets).
+const FORWARDED_HEADERS: &[&str] = &[
+    "content-type",
+    "stripe-signature",
+    "x-signature",
+    "x-event-name",
+];
+
+/// Sends copies of webhooks to mirror URLs.
+#[derive(Clone)]
+pub struct WebhookMirror {
+    /// HTTP client for mirror URLs (refuses internal addresses)
+    client: Client,
+    /// Accept localhost mirror URLs (PAYCHECK_ALLOW_LOCALHOST_URLS)
+    allow_localhost_urls: bool,
+}
+
+impl WebhookMirror {
+    pub fn new(allow_localhost_urls: bool) -> Self {
+        Self {
+            client: external_url::guarded_client(allow_localhost_urls),
+            allow_localhost_urls,
+        }
+    }
+
+    /// Mirror a webhook Paycheck sends, exactly as it went out.
+    pub fn send_event(&self, mirror_url: &str, body: &WebhookBody) {
+        let request = self
+            .client
+            .post(mirror_url)
+            .header("Content-Type", "application/json")
+            .header("X-Paycheck-Event", &body.event_header)
+            .body(body.json.clone());
+        self.spawn(mirror_url, request);
+    }
+
+    /// Forward a provider webhook Paycheck received: the same body, with the
+    /// provider's content type and signature headers.
+    pub fn forward_provider_webhook(
+        &self,
+        mirror_url: &str,
+        provider: &str,
+        headers: &HeaderMap,
+        body: Bytes,
+    ) {
+        let mut request = self
+            .client
+            .post(mirror_url)
+            .header("X-Paycheck-Provider", provider)
+            .body(body);
+        for name in FORWARDED_HEADERS {
+            if let Some(value) = headers.get(*name) {
+                request = request.header(*name, value.clone());
+            }
+        }
+        self.spawn(mirror_url, request);
+    }
+
+    /// Send once in the background. Nothing waits on the result.
+    fn spawn(&self, mirror_url: &str, request: RequestBuilder) {
+        // Saved URLs are checked too, but may predate the rules
+        if let Err(rejection) = external_url::check_url(mirror_url, self.allow_localhost_urls) {
+            tracing::warn!(mirror_url = %mirror_url, "Not mirroring webhook: URL {}", rejection);
+            return;
+        }
+
+        let mirror_url = mirror_url.to_string();
+        let request = request
+            .header(MIRROR_HEADER, "true")
+            .timeout(MIRROR_TIMEOUT);
+        tokio::spawn(async move {
+            match request.send().await {
+                Ok(response) if !response.status().is_success() => {
+                    tracing::debug!(
+                        mirror_url = %mirror_url,
+                        status = %response.status(),
+                        "Webhook mirror returned an error"
+                    );
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::debug!(
+                        mirror_url = %mirror_url,
+                        error = %e,
+                        "Failed to reach webhook mirror"
+                    );
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,327 @@
+//! Payloads of the webhooks Paycheck sends to a project's `email_webhook_url`.
+//!
+//! Each payload shape has a schema version. A project pins the version it
+//! receives with `webhook_schema_version` (unset = [`WebhookSchemaVersion::LATEST`]),
+//! so a developer's parser only sees new fields or shapes when they move it
+//! forward. Every version has its own serializer structs ([`v1`], [`v2`]),
+//! built from the event data here; a released version's structs never
+//! change, and the golden files in `tests/webhooks/golden/` hold them to it.
+//! [`SCHEMA_CHANGELOG`] lists what each version changed.
+//!
+//! Version 1 is the format from before versioning, request included: no
+//! `schema_version` field and a bare event name in `X-Paycheck-Event`. Later
+//! versions add `schema_version` to the body and a `.v<N>` suffix to the
+//! header (`activation_code_created.v2`).
+//!
+//! A project with an `email_webhook_secret` gets every webhook signed: the
+//! `X-Paycheck-Signature` header holds `v1=` and the hex HMAC-SHA256 of
+//! `{X-Paycheck-Timestamp}.{body}` under the secret (see [`WebhookBody::signature`]).
+
+mod schema;
+pub mod v1;
+pub mod v2;
+
+use std::collections::BTreeMap;
+
+use axum::body::Bytes;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+use strum::{AsRefStr, EnumString};
+
+use crate::email::{EmailTrigger, LicenseCodeInfo};
+
+use schema::SchemaType;
+
+pub use schema::schema_document;
+
+/// A webhook payload schema version, sent as a string (`"2"`).
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, AsRefStr, EnumString,
+)]
+pub enum WebhookSchemaVersion {
+    /// The format from before versioning
+    #[serde(rename = "1")]
+    #[strum(serialize = "1")]
+    V1,
+    #[serde(rename = "2")]
+    #[strum(serialize = "2")]
+    V2,
+}
+
+impl WebhookSchemaVersion {
+    /// Sent to projects that haven't pinned a version
+    pub const LATEST: Self = Self::V2;
+
+    pub const ALL: [Self; 2] = [Self::V1, Self::V2];
+
+    /// The `X-Paycheck-Event` header for `event` in this version.
+    pub fn event_header(self, event: &str) -> String {
+        match self {
+            Self::V1 => event.to_string(),
+            _ => format!("{}.v{}", event, self.as_ref()),
+        }
+    }
+}
+
+/// What changed in each version, relative to the one before.
+pub const SCHEMA_CHANGELOG: &[(WebhookSchemaVersion, &[&str])] = &[
+    (
+        WebhookSchemaVersion::V1,
+        &["Payloads as sent before schema versioning"],
+    ),
+    (
+        WebhookSchemaVersion::V2,
+        &[
+            "Every payload starts with `schema_version`",
+            "`X-Paycheck-Event` carries the version as a suffix (`activation_code_created.v2`)",
+            "`activation_code_created`: `email` is null when there's no address to send to (v1 leaves it out)",
+            "`metadata` is always sent, `{}` when the license has none (v1 leaves it out)",
+        ],
+    ),
+];
+
+/// An event Paycheck sends webhooks for.
+pub trait WebhookEvent {
+    /// The payload's `event`, and `X-Paycheck-Event` before any version suffix
+    const NAME: &'static str;
+
+    /// The payload as JSON, in `version`'s shape.
+    fn to_json(&self, version: WebhookSchemaVersion) -> serde_json::Result<Vec<u8>>;
+
+    /// JSON Schema for the payload in `version`'s shape.
+    fn json_schema(version: WebhookSchemaVersion) -> Value;
+}
+
+/// A rendered webhook, ready to send (and mirror) as is.
+#[derive(Debug, Clone)]
+pub struct WebhookBody {
+    /// `X-Paycheck-Event` header value
+    pub event_header: String,
+    pub json: Bytes,
+}
+
+impl WebhookBody {
+    pub fn render<E: WebhookEvent>(
+        event: &E,
+        version: WebhookSchemaVersion,
+    ) -> serde_json::Result<Self> {
+        Ok(Self {
+            event_header: version.event_header(E::NAME),
+            json: Bytes::from(event.to_json(version)?),
+        })
+    }
+
+    /// `X-Paycheck-Signature` for this body sent at `timestamp` (Unix
+    /// seconds, also sent as `X-Paycheck-Timestamp`): `v1=` and the hex
+    /// HMAC-SHA256 of `{timestamp}.{json}` under `secret`.
+    pub fn signature(&self, secret: &str, timestamp: i64) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+            .expect("HMAC can take key of any size");
+        mac.update(timestamp.to_string().as_bytes());
+        mac.update(b".");
+        mac.update(&self.json);
+        format!("v1={}", hex::encode(mac.finalize().into_bytes()))
+    }
+}
+
+/// An activation code for one license, for the customer to enter in the app.
+#[derive(Debug)]
+pub struct ActivationCodeCreated<'a> {
+    /// Empty for dormant nudges, which have no stored address to send to
+    pub email: &'a str,
+    pub code: &'a str,
+    pub expires_at: i64,
+    pub expires_in_minutes: i32,
+    pub product_name: &'a str,
+    pub project_id: &'a str,
+    pub project_name: &'a str,
+    pub license_id: &'a str,
+    pub trigger: EmailTrigger,
+    /// `/buy` metadata the license was bought with
+    pub metadata: &'a BTreeMap<String, String>,
+}
+
+/// Activation codes for several licenses bought with the same address.
+#[derive(Debug)]
+pub struct ActivationCodesCreated<'a> {
+    pub email: &'a str,
+    pub expires_at: i64,
+    pub expires_in_minutes: i32,
+    pub project_id: &'a str,
+    pub project_name: &'a str,
+    pub licenses: &'a [LicenseCodeInfo],
+    pub trigger: EmailTrigger,
+}
+
+/// A license was marked dormant (bought but never activated), so the
+/// developer can follow up with the customer.
+#[derive(Debug)]
+pub struct LicenseDormant<'a> {
+    pub project_id: &'a str,
+    pub project_name: &'a str,
+    pub license_id: &'a str,
+    pub product_name: &'a str,
+    pub customer_id: Option<&'a str>,
+    /// When the license was purchased (Unix timestamp)
+    pub purchased_at: i64,
+    pub dormant_since: i64,
+    pub metadata: &'a BTreeMap<String, String>,
+}
+
+impl WebhookEvent for ActivationCodeCreated<'_> {
+    const NAME: &'static str = "activation_code_created";
+
+    fn to_json(&self, version: WebhookSchemaVersion) -> serde_json::Result<Vec<u8>> {
+        match version {
+            WebhookSchemaVersion::V1 => serde_json::to_vec(&v1::ActivationCodeCreated::from(self)),
+            WebhookSchemaVersion::V2 => serde_json::to_vec(&v2::ActivationCodeCreated::from(self)),
+        }
+    }
+
+    fn json_schema(version: WebhookSchemaVersion) -> Value {
+        match version {
+            WebhookSchemaVersion::V1 => v1::ActivationCodeCreated::json_schema(),
+            WebhookSchemaVersion::V2 => v2::ActivationCodeCreated::json_schema(),
+        }
+    }
+}
+
+impl WebhookEvent for ActivationCodesCreated<'_> {
+    const NAME: &'static str = "activation_codes_created";
+
+    fn to_json(&self, version: WebhookSchemaVersion) -> serde_json::Result<Vec<u8>> {
+        match version {
+            WebhookSchemaVersion::V1 => serde_json::to_vec(&v1::ActivationCodesCreated::from(self)),
+            WebhookSchemaVersion::V2 => serde_json::to_vec(&v2::ActivationCodesCreated::from(self)),
+        }
+    }
+
+    fn json_schema(version: WebhookSchemaVersion) -> Value {
+        match version {
+            WebhookSchemaVersion::V1 => v1::ActivationCodesCreated::json_schema(),
+            WebhookSchemaVersion::V2 => v2::ActivationCodesCreated::json_schema(),
+        }
+    }
+}
+
+impl WebhookEvent for LicenseDormant<'_> {
+    const NAME: &'static str = "license_dormant";
+
+    fn to_json(&self, version: WebhookSchemaVersion) -> serde_json::Result<Vec<u8>> {
+        match version {
+            WebhookSchemaVersion::V1 => serde_json::to_vec(&v1::LicenseDormant::from(self)),
+            WebhookSchemaVersion::V2 => serde_json::to_vec(&v2::LicenseDormant::from(self)),
+        }
+    }
+
+    fn json_schema(version: WebhookSchemaVersion) -> Value {
+        match version {
+            WebhookSchemaVersion::V1 => v1::LicenseDormant::json_schema(),
+            WebhookSchemaVersion::V2 => v2::LicenseDormant::json_schema(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_codes() -> Vec<LicenseCodeInfo> {
+        vec![LicenseCodeInfo {
+            product_name: "Pro".into(),
+            code: "PC-AAAA-BBBB".into(),
+            license_id: "lic_1".into(),
+            purchased_at: 1_700_000_000,
+            metadata: BTreeMap::new(),
+        }]
+    }
+
+    /// Every field a payload sends is in its schema, and every field the
+    /// schema requires is sent, even with the optional ones left empty.
+    fn assert_matches_schema<E: WebhookEvent>(event: &E) {
+        for version in WebhookSchemaVersion::ALL {
+            let payload: Value = serde_json::from_slice(&event.to_json(version).unwrap()).unwrap();
+            let schema = E::json_schema(version);
+            let properties = schema["properties"].as_object().unwrap();
+            for field in payload.as_object().unwrap().keys() {
+                assert!(
+                    properties.contains_key(field),
+                    "{} v{}: `{}` is sent but not in the schema",
+                    E::NAME,
+                    version.as_ref(),
+                    field
+                );
+            }
+            for field in schema["required"].as_array().unwrap() {
+                assert!(
+                    payload.get(field.as_str().unwrap()).is_some(),
+                    "{} v{}: `{}` is required but not sent",
+                    E::NAME,
+                    version.as_ref(),
+                    field
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_schemas_match_payloads() {
+        let metadata = BTreeMap::new();
+        let licenses = sample_codes();
+        assert_matches_schema(&ActivationCodeCreated {
+            email: "",
+            code: "PC-AAAA-BBBB",
+            expires_at: 1_700_001_800,
+            expires_in_minutes: 30,
+            product_name: "Pro",
+            project_id: "proj_1",
+            project_name: "App",
+            license_id: "lic_1",
+            trigger: EmailTrigger::DormantNudge,
+            metadata: &metadata,
+        });
+        assert_matches_schema(&ActivationCodesCreated {
+            email: "buyer@example.com",
+            expires_at: 1_700_001_800,
+            expires_in_minutes: 30,
+            project_id: "proj_1",
+            project_name: "App",
+            licenses: &licenses,
+            trigger: EmailTrigger::Purchase,
+        });
+        assert_matches_schema(&LicenseDormant {
+            project_id: "proj_1",
+            project_name: "App",
+            license_id: "lic_1",
+            product_name: "Pro",
+            customer_id: None,
+            purchased_at: 1_700_000_000,
+            dormant_since: 1_702_592_000,
+            metadata: &metadata,
+        });
+    }
+
+    #[test]
+    fn test_every_version_is_in_the_changelog() {
+        let listed: Vec<_> = SCHEMA_CHANGELOG.iter().map(|(v, _)| *v).collect();
+        assert_eq!(listed, WebhookSchemaVersion::ALL);
+        assert_eq!(
+            WebhookSchemaVersion::ALL.last(),
+            Some(&WebhookSchemaVersion::LATEST)
+        );
+    }
+
+    #[test]
+    fn test_event_header_suffix() {
+        assert_eq!(
+            WebhookSchemaVersion::V1.event_header("license_dormant"),
+            "license_dormant"
+        );
+        assert_eq!(
+            WebhookSchemaVersion::V2.event_header("license_dormant"),
+            "license_dormant.v2"
+        );
+    }
+}
@@ -0,0 +1,155 @@
+//! JSON Schema (draft 2020-12) for webhook payloads, built from the field
+//! types of each version's serializer structs.
+
+use std::collections::BTreeMap;
+
+use serde_json::{Map, Value, json};
+
+use super::{
+    ActivationCodeCreated, ActivationCodesCreated, LicenseDormant, WebhookEvent,
+    WebhookSchemaVersion,
+};
+use crate::email::EmailTrigger;
+
+/// A type that appears in a webhook payload.
+pub trait SchemaType {
+    fn json_schema() -> Value;
+}
+
+impl SchemaType for &str {
+    fn json_schema() -> Value {
+        json!({ "type": "string" })
+    }
+}
+
+impl SchemaType for i64 {
+    fn json_schema() -> Value {
+        json!({ "type": "integer" })
+    }
+}
+
+impl SchemaType for i32 {
+    fn json_schema() -> Value {
+        json!({ "type": "integer" })
+    }
+}
+
+impl<T: SchemaType> SchemaType for Option<T> {
+    fn json_schema() -> Value {
+        let mut schema = T::json_schema();
+        match schema.get("type").and_then(Value::as_str) {
+            Some(ty) => {
+                schema["type"] = json!([ty, "null"]);
+                schema
+            }
+            None => json!({ "anyOf": [schema, { "type": "null" }] }),
+        }
+    }
+}
+
+impl<T: SchemaType> SchemaType for Vec<T> {
+    fn json_schema() -> Value {
+        json!({ "type": "array", "items": T::json_schema() })
+    }
+}
+
+impl SchemaType for &BTreeMap<String, String> {
+    fn json_schema() -> Value {
+        json!({ "type": "object", "additionalProperties": { "type": "string" } })
+    }
+}
+
+impl SchemaType for EmailTrigger {
+    fn json_schema() -> Value {
+        let triggers = [
+            EmailTrigger::Purchase,
+            EmailTrigger::RecoveryRequest,
+            EmailTrigger::AdminGenerated,
+            EmailTrigger::DormantNudge,
+        ];
+        let names: Vec<&str> = triggers.iter().map(AsRef::as_ref).collect();
+        json!({ "type": "string", "enum": names })
+    }
+}
+
+/// One field of a payload object.
+pub struct Property {
+    name: &'static str,
+    schema: Value,
+    required: bool,
+}
+
+impl Property {
+    /// A field of type `T`, always sent.
+    pub fn of<T: SchemaType>(name: &'static str) -> Self {
+        Self {
+            name,
+            schema: T::json_schema(),
+            required: true,
+        }
+    }
+
+    /// A field that always holds `value` (`event`, `schema_version`).
+    pub fn constant(name: &'static str, value: &str) -> Self {
+        Self {
+            name,
+            schema: json!({ "type": "string", "const": value }),
+            required: true,
+        }
+    }
+
+    /// Left out of the payload when empty.
+    pub fn optional(mut self) -> Self {
+        self.required = false;
+        self
+    }
+}
+
+/// Schema for an object with exactly these fields.
+pub fn object(properties: Vec<Property>) -> Value {
+    let required: Vec<&str> = properties
+        .iter()
+        .filter(|p| p.required)
+        .map(|p| p.name)
+        .collect();
+    let properties: Map<String, Value> = properties
+        .into_iter()
+        .map(|p| (p.name.to_string(), p.schema))
+        .collect();
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    })
+}
+
+/// The schema of every payload in `version`: one of the events under `$defs`,
+/// keyed by event name.
+pub fn schema_document(version: WebhookSchemaVersion) -> Value {
+    let events = [
+        (
+            ActivationCodeCreated::NAME,
+            ActivationCodeCreated::json_schema(version),
+        ),
+        (
+            ActivationCodesCreated::NAME,
+            ActivationCodesCreated::json_schema(version),
+        ),
+        (LicenseDormant::NAME, LicenseDormant::json_schema(version)),
+    ];
+    let one_of: Vec<Value> = events
+        .iter()
+        .map(|(name, _)| json!({ "$ref": format!("#/$defs/{}", name) }))
+        .collect();
+    let defs: Map<String, Value> = events
+        .into_iter()
+        .map(|(name, schema)| (name.to_string(), schema))
+        .collect();
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": format!("Paycheck webhook payloads, schema version {}", version.as_ref()),
+        "oneOf": one_of,
+        "$defs": defs,
+    })
+}
@@ -0,0 +1,186 @@
+//! Schema version 1: the payloads as sent before versioning. Don't change
+//! these; add a version instead.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::WebhookEvent;
+use super::schema::{Property, SchemaType, object};
+use crate::email::{EmailTrigger, LicenseCodeInfo};
+
+#[derive(Debug, Serialize)]
+pub struct ActivationCodeCreated<'a> {
+    pub event: &'static str,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    pub email: &'a str,
+    pub code: &'a str,
+    pub expires_at: i64,
+    pub expires_in_minutes: i32,
+    pub product_name: &'a str,
+    pub project_id: &'a str,
+    pub project_name: &'a str,
+    pub license_id: &'a str,
+    pub trigger: EmailTrigger,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: &'a BTreeMap<String, String>,
+}
+
+impl<'a> From<&super::ActivationCodeCreated<'a>> for ActivationCodeCreated<'a> {
+    fn from(e: &super::ActivationCodeCreated<'a>) -> Self {
+        Self {
+            event: super::ActivationCodeCreated::NAME,
+            email: e.email,
+            code: e.code,
+            expires_at: e.expires_at,
+            expires_in_minutes: e.expires_in_minutes,
+            product_name: e.product_name,
+            project_id: e.project_id,
+            project_name: e.project_name,
+            license_id: e.license_id,
+            trigger: e.trigger,
+            metadata: e.metadata,
+        }
+    }
+}
+
+impl SchemaType for ActivationCodeCreated<'_> {
+    fn json_schema() -> Value {
+        object(vec![
+            Property::constant("event", super::ActivationCodeCreated::NAME),
+            Property::of::<&str>("email").optional(),
+            Property::of::<&str>("code"),
+            Property::of::<i64>("expires_at"),
+            Property::of::<i32>("expires_in_minutes"),
+            Property::of::<&str>("product_name"),
+            Property::of::<&str>("project_id"),
+            Property::of::<&str>("project_name"),
+            Property::of::<&str>("license_id"),
+            Property::of::<EmailTrigger>("trigger"),
+            Property::of::<&BTreeMap<String, String>>("metadata").optional(),
+        ])
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct LicenseCode<'a> {
+    pub product_name: &'a str,
+    pub code: &'a str,
+    pub license_id: &'a str,
+    pub purchased_at: i64,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: &'a BTreeMap<String, String>,
+}
+
+impl<'a> From<&'a LicenseCodeInfo> for LicenseCode<'a> {
+    fn from(l: &'a LicenseCodeInfo) -> Self {
+        Self {
+            product_name: &l.product_name,
+            code: &l.code,
+            license_id: &l.license_id,
+            purchased_at: l.purchased_at,
+            metadata: &l.metadata,
+        }
+    }
+}
+
+impl SchemaType for LicenseCode<'_> {
+    fn json_schema() -> Value {
+        object(vec![
+            Property::of::<&str>("product_name"),
+            Property::of::<&str>("code"),
+            Property::of::<&str>("license_id"),
+            Property::of::<i64>("purchased_at"),
+            Property::of::<&BTreeMap<String, String>>("metadata").optional(),
+        ])
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ActivationCodesCreated<'a> {
+    pub event: &'static str,
+    pub email: &'a str,
+    pub expires_at: i64,
+    pub expires_in_minutes: i32,
+    pub project_id: &'a str,
+    pub project_name: &'a str,
+    pub licenses: Vec<LicenseCode<'a>>,
+    pub trigger: EmailTrigger,
+}
+
+impl<'a> From<&super::ActivationCodesCreated<'a>> for ActivationCodesCreated<'a> {
+    fn from(e: &super::ActivationCodesCreated<'a>) -> Self {
+        Self {
+            event: super::ActivationCodesCreated::NAME,
+            email: e.email,
+            expires_at: e.expires_at,
+            expires_in_minutes: e.expires_in_minutes,
+            project_id: e.project_id,
+            project_name: e.project_name,
+            licenses: e.licenses.iter().map(LicenseCode::from).collect(),
+            trigger: e.trigger,
+        }
+    }
+}
+
+impl SchemaType for ActivationCodesCreated<'_> {
+    fn json_schema() -> Value {
+        object(vec![
+            Property::constant("event", super::ActivationCodesCreated::NAME),
+            Property::of::<&str>("email"),
+            Property::of::<i64>("expires_at"),
+            Property::of::<i32>("expires_in_minutes"),
+            Property::of::<&str>("project_id"),
+            Property::of::<&str>("project_name"),
+            Property::of::<Vec<LicenseCode<'_>>>("licenses"),
+            Property::of::<EmailTrigger>("trigger"),
+        ])
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct LicenseDormant<'a> {
+    pub event: &'static str,
+    pub project_id: &'a str,
+    pub project_name: &'a str,
+    pub license_id: &'a str,
+    pub product_name: &'a str,
+    pub customer_id: Option<&'a str>,
+    pub purchased_at: i64,
+    pub dormant_since: i64,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: &'a BTreeMap<String, String>,
+}
+
+impl<'a> From<&super::LicenseDormant<'a>> for LicenseDormant<'a> {
+    fn from(e: &super::LicenseDormant<'a>) -> Self {
+        Self {
+            event: super::LicenseDormant::NAME,
+            project_id: e.project_id,
+            project_name: e.project_name,
+            license_id: e.license_id,
+            product_name: e.product_name,
+            customer_id: e.customer_id,
+            purchased_at: e.purchased_at,
+            dormant_since: e.dormant_since,
+            metadata: e.metadata,
+        }
+    }
+}
+
+impl SchemaType for LicenseDormant<'_> {
+    fn json_schema() -> Value {
+        object(vec![
+            Property::constant("event", super::LicenseDormant::NAME),
+            Property::of::<&str>("project_id"),
+            Property::of::<&str>("project_name"),
+            Property::of::<&str>("license_id"),
+            Property::of::<&str>("product_name"),
+            Property::of::<Option<&str>>("customer_id"),
+            Property::of::<i64>("purchased_at"),
+            Property::of::<i64>("dormant_since"),
+            Property::of::<&BTreeMap<String, String>>("metadata").optional(),
+        ])
+    }
+}
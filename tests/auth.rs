@@ -35,3 +35,12 @@ mod org_audit_log_isolation;
 
 #[path = "auth/impersonation.rs"]
 mod operator_impersonation;
+
+#[path = "auth/partner_operators.rs"]
+mod partner_operator_scopes;
+
+#[path = "auth/permission_matrix.rs"]
+mod permission_matrix;
+
+#[path = "auth/project_visibility.rs"]
+mod project_visibility;
@@ -23,8 +23,11 @@ fn org_app_with_audit() -> (Router, AppState) {
         audit: audit_pool,
         base_url: "http://localhost:3000".to_string(),
         audit_log_enabled: true, // Enable audit logging
+        audit_failure_mode: paycheck::models::AuditFailureMode::default(),
+        public_audit_retention_days: 0,
         master_key,
         email_hasher: paycheck::crypto::EmailHasher::from_bytes([0xAA; 32]),
+        pii_minimization: false,
         success_page_url: "http://localhost:3000/success".to_string(),
         activation_rate_limiter: std::sync::Arc::new(
             paycheck::rate_limit::ActivationRateLimiter::default(),
@@ -35,6 +38,20 @@ fn org_app_with_audit() -> (Router, AppState) {
         )),
         jwks_cache: std::sync::Arc::new(paycheck::jwt::JwksCache::new()),
         trusted_issuers: vec![],
+        org_dbs: std::sync::Arc::new(paycheck::db::OrgDbRegistry::disabled()),
+        project_misses: std::sync::Arc::new(paycheck::db::ProjectMissCache::default()),
+        clock: paycheck::util::Clock::system(),
+        provider_calls: std::sync::Arc::new(paycheck::payments::ProviderCallGovernor::default()),
+        validation_limiter: std::sync::Arc::new(paycheck::rate_limit::ValidationRateLimiter::new()),
+        free_license_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_free_licenses(),
+        ),
+        session_wait_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_session_waits(),
+        ),
+        allow_localhost_urls: false,
+        maintenance: std::sync::Arc::new(paycheck::middleware::MaintenanceMode::default()),
+        backups: None,
     };
 
     let app = handlers::orgs::router(state.clone(), paycheck::config::RateLimitConfig::disabled())
@@ -138,6 +155,7 @@ async fn audit_logs_only_return_own_org_data() {
         &AuditLogNames::default(),
         None, // auth_type
         None, // auth_credential
+        None, // request_id
     )
     .unwrap();
 
@@ -157,6 +175,7 @@ async fn audit_logs_only_return_own_org_data() {
         &AuditLogNames::default(),
         None, // auth_type
         None, // auth_credential
+        None, // request_id
     )
     .unwrap();
 
@@ -231,6 +250,7 @@ async fn query_param_org_id_cannot_override_path_org_id() {
         &AuditLogNames::default(),
         None, // auth_type
         None, // auth_credential
+        None, // request_id
     )
     .unwrap();
 
@@ -250,6 +270,7 @@ async fn query_param_org_id_cannot_override_path_org_id() {
         &AuditLogNames::default(),
         None, // auth_type
         None, // auth_credential
+        None, // request_id
     )
     .unwrap();
 
@@ -293,6 +314,76 @@ async fn query_param_org_id_cannot_override_path_org_id() {
     );
 }
 
+#[tokio::test]
+async fn member_role_only_sees_audit_logs_for_their_projects() {
+    use paycheck::models::{ActorType, AuditLogNames};
+
+    let (app, state) = org_app_with_audit();
+    let mut conn = state.db.get().unwrap();
+    let audit_conn = state.audit.get().unwrap();
+
+    let org = create_test_org(&mut conn, "Test Org");
+    let mine = create_test_project(&conn, &org.id, "Mine", &state.master_key);
+    let other = create_test_project(&conn, &org.id, "Other", &state.master_key);
+    let (_user, member, key) =
+        create_test_org_member(&mut conn, &org.id, "member@org.com", OrgMemberRole::Member);
+    create_test_project_member(&conn, &member.id, &mine.id, ProjectMemberRole::View);
+
+    for (action, project_id) in [
+        ("mine_action", Some(mine.id.as_str())),
+        ("other_action", Some(other.id.as_str())),
+        ("org_action", None),
+    ] {
+        queries::create_audit_log(
+            &audit_conn,
+            true,
+            ActorType::User,
+            Some("someone"),
+            action,
+            "test_resource",
+            "resource",
+            None,
+            Some(&org.id),
+            project_id,
+            None,
+            None,
+            &AuditLogNames::default(),
+            None, // auth_type
+            None, // auth_credential
+            None, // request_id
+        )
+        .unwrap();
+    }
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("GET")
+                .uri(format!("/orgs/{}/audit-logs", org.id))
+                .header("Authorization", format!("Bearer {}", key))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let result: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let actions: Vec<&str> = result["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|log| log["action"].as_str().unwrap())
+        .collect();
+
+    assert_eq!(
+        actions,
+        vec!["mine_action"],
+        "a member-role user should only see entries for projects they belong to"
+    );
+}
+
 #[tokio::test]
 async fn missing_token_cannot_access_org_audit_logs() {
     let (app, state) = org_app_with_audit();
@@ -40,8 +40,11 @@ pub fn operator_app() -> (Router, AppState) {
         audit: audit_pool,
         base_url: "http://localhost:3000".to_string(),
         audit_log_enabled: false,
+        audit_failure_mode: paycheck::models::AuditFailureMode::default(),
+        public_audit_retention_days: 0,
         master_key,
         email_hasher: paycheck::crypto::EmailHasher::from_bytes([0xAA; 32]),
+        pii_minimization: false,
         success_page_url: "http://localhost:3000/success".to_string(),
         activation_rate_limiter: std::sync::Arc::new(
             paycheck::rate_limit::ActivationRateLimiter::default(),
@@ -52,6 +55,20 @@ pub fn operator_app() -> (Router, AppState) {
         )),
         jwks_cache: std::sync::Arc::new(paycheck::jwt::JwksCache::new()),
         trusted_issuers: vec![],
+        org_dbs: std::sync::Arc::new(paycheck::db::OrgDbRegistry::disabled()),
+        project_misses: std::sync::Arc::new(paycheck::db::ProjectMissCache::default()),
+        clock: paycheck::util::Clock::system(),
+        provider_calls: std::sync::Arc::new(paycheck::payments::ProviderCallGovernor::default()),
+        validation_limiter: std::sync::Arc::new(paycheck::rate_limit::ValidationRateLimiter::new()),
+        free_license_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_free_licenses(),
+        ),
+        session_wait_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_session_waits(),
+        ),
+        allow_localhost_urls: false,
+        maintenance: std::sync::Arc::new(paycheck::middleware::MaintenanceMode::default()),
+        backups: None,
     };
 
     let app = handlers::operators::router(state.clone()).with_state(state.clone());
@@ -82,8 +99,11 @@ pub fn org_app() -> (Router, AppState) {
         audit: audit_pool,
         base_url: "http://localhost:3000".to_string(),
         audit_log_enabled: false,
+        audit_failure_mode: paycheck::models::AuditFailureMode::default(),
+        public_audit_retention_days: 0,
         master_key,
         email_hasher: paycheck::crypto::EmailHasher::from_bytes([0xAA; 32]),
+        pii_minimization: false,
         success_page_url: "http://localhost:3000/success".to_string(),
         activation_rate_limiter: std::sync::Arc::new(
             paycheck::rate_limit::ActivationRateLimiter::default(),
@@ -94,6 +114,20 @@ pub fn org_app() -> (Router, AppState) {
         )),
         jwks_cache: std::sync::Arc::new(paycheck::jwt::JwksCache::new()),
         trusted_issuers: vec![],
+        org_dbs: std::sync::Arc::new(paycheck::db::OrgDbRegistry::disabled()),
+        project_misses: std::sync::Arc::new(paycheck::db::ProjectMissCache::default()),
+        clock: paycheck::util::Clock::system(),
+        provider_calls: std::sync::Arc::new(paycheck::payments::ProviderCallGovernor::default()),
+        validation_limiter: std::sync::Arc::new(paycheck::rate_limit::ValidationRateLimiter::new()),
+        free_license_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_free_licenses(),
+        ),
+        session_wait_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_session_waits(),
+        ),
+        allow_localhost_urls: false,
+        maintenance: std::sync::Arc::new(paycheck::middleware::MaintenanceMode::default()),
+        backups: None,
     };
 
     let app = handlers::orgs::router(state.clone(), RateLimitConfig::disabled())
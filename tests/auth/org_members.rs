@@ -317,7 +317,7 @@ async fn member_role_cannot_create_project() {
                     .header("Authorization", format!("Bearer {}", member_key))
                     .header("Content-Type", "application/json")
                     .body(Body::from(
-                        r#"{"name": "New Project", "domain": "test.example.com", "license_key_prefix": "TEST"}"#,
+                        r#"{"name": "New Project", "license_key_prefix": "TEST"}"#,
                     ))
                     .unwrap(),
             )
@@ -348,7 +348,7 @@ async fn admin_role_can_create_project() {
                     .header("Authorization", format!("Bearer {}", admin_key))
                     .header("Content-Type", "application/json")
                     .body(Body::from(
-                        r#"{"name": "New Project", "domain": "test.example.com", "license_key_prefix": "TEST"}"#,
+                        r#"{"name": "New Project", "license_key_prefix": "TEST"}"#,
                     ))
                     .unwrap(),
             )
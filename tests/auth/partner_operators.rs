@@ -0,0 +1,456 @@
+//! Partner operators are admin-level, but only inside the orgs scoped to them.
+//!
+//! Out-of-scope orgs look missing (404) on operator routes, and the partner is
+//! treated like any non-member (403) on `/orgs/{org_id}/*` routes, with or
+//! without impersonation.
+
+use super::helpers::*;
+
+struct PartnerFixture {
+    state: AppState,
+    partner: User,
+    partner_key: String,
+    owner_key: String,
+    scoped: Organization,
+    scoped_project: Project,
+    scoped_member: User,
+    other: Organization,
+    other_project: Project,
+    other_member: User,
+}
+
+/// A partner scoped to one of two orgs, each with a project and an owner member
+fn setup() -> PartnerFixture {
+    let (_, state) = operator_app();
+    let mut conn = state.db.get().unwrap();
+
+    let (_, owner_key) = create_test_operator(&mut conn, "owner@platform.com", OperatorRole::Owner);
+    let (partner, partner_key) =
+        create_test_operator(&mut conn, "partner@reseller.com", OperatorRole::Partner);
+
+    let scoped = create_test_org(&conn, "Scoped Org");
+    let scoped_project = create_test_project(&conn, &scoped.id, "Scoped App", &state.master_key);
+    let (scoped_member, _, _) = create_test_org_member(
+        &mut conn,
+        &scoped.id,
+        "owner@scoped.com",
+        OrgMemberRole::Owner,
+    );
+
+    let other = create_test_org(&conn, "Other Org");
+    let other_project = create_test_project(&conn, &other.id, "Other App", &state.master_key);
+    let (other_member, _, _) = create_test_org_member(
+        &mut conn,
+        &other.id,
+        "owner@other.com",
+        OrgMemberRole::Owner,
+    );
+
+    queries::add_operator_org_scope(&conn, &partner.id, &scoped.id).unwrap();
+    drop(conn);
+
+    PartnerFixture {
+        state,
+        partner,
+        partner_key,
+        owner_key,
+        scoped,
+        scoped_project,
+        scoped_member,
+        other,
+        other_project,
+        other_member,
+    }
+}
+
+impl PartnerFixture {
+    fn operators(&self) -> Router {
+        handlers::operators::router(self.state.clone()).with_state(self.state.clone())
+    }
+
+    fn orgs(&self) -> Router {
+        handlers::orgs::router(self.state.clone(), RateLimitConfig::disabled())
+            .with_state(self.state.clone())
+    }
+}
+
+async fn send(
+    app: Router,
+    key: &str,
+    method: &str,
+    uri: &str,
+    body: Option<serde_json::Value>,
+    on_behalf_of: Option<&str>,
+) -> (StatusCode, serde_json::Value) {
+    let mut builder = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("Authorization", format!("Bearer {}", key))
+        .header("Content-Type", "application/json");
+    if let Some(user_id) = on_behalf_of {
+        builder = builder.header("X-On-Behalf-Of", user_id);
+    }
+    let body = match body {
+        Some(json) => Body::from(json.to_string()),
+        None => Body::empty(),
+    };
+
+    let response = app.oneshot(builder.body(body).unwrap()).await.unwrap();
+    let status = response.status();
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (
+        status,
+        serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null),
+    )
+}
+
+#[tokio::test]
+async fn partner_lists_only_scoped_orgs() {
+    let f = setup();
+
+    let (status, json) = send(
+        f.operators(),
+        &f.partner_key,
+        "GET",
+        "/operators/organizations",
+        None,
+        None,
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["total"], 1);
+    assert_eq!(json["items"][0]["id"], f.scoped.id.as_str());
+}
+
+#[tokio::test]
+async fn partner_gets_404_for_out_of_scope_org_on_operator_routes() {
+    let f = setup();
+
+    let requests = [
+        (
+            "GET",
+            format!("/operators/organizations/{}", f.other.id),
+            None,
+        ),
+        (
+            "PUT",
+            format!("/operators/organizations/{}", f.other.id),
+            Some(serde_json::json!({ "name": "Renamed" })),
+        ),
+        (
+            "DELETE",
+            format!("/operators/organizations/{}", f.other.id),
+            None,
+        ),
+        (
+            "POST",
+            format!("/operators/organizations/{}/hard-delete", f.other.id),
+            None,
+        ),
+        (
+            "GET",
+            format!("/operators/organizations/{}/payment-provider", f.other.id),
+            None,
+        ),
+        (
+            "GET",
+            format!(
+                "/operators/organizations/{}/projects/{}/licenses/lookup?email=a@b.com",
+                f.other.id, f.other_project.id
+            ),
+            None,
+        ),
+    ];
+
+    for (method, uri, body) in requests {
+        let (status, _) = send(f.operators(), &f.partner_key, method, &uri, body, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{} {}", method, uri);
+    }
+
+    let conn = f.state.db.get().unwrap();
+    let other = queries::get_organization_by_id(&conn, &f.other.id).unwrap();
+    assert_eq!(
+        other.unwrap().name,
+        "Other Org",
+        "out-of-scope org untouched"
+    );
+}
+
+#[tokio::test]
+async fn partner_can_manage_scoped_org_on_operator_routes() {
+    let f = setup();
+
+    let (status, json) = send(
+        f.operators(),
+        &f.partner_key,
+        "PUT",
+        &format!("/operators/organizations/{}", f.scoped.id),
+        Some(serde_json::json!({ "name": "Renamed" })),
+        None,
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["name"], "Renamed");
+}
+
+#[tokio::test]
+async fn partner_keeps_access_to_orgs_it_creates() {
+    let f = setup();
+
+    let (status, json) = send(
+        f.operators(),
+        &f.partner_key,
+        "POST",
+        "/operators/organizations",
+        Some(serde_json::json!({ "name": "Partner Customer" })),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let org_id = json["id"].as_str().unwrap().to_string();
+
+    let (status, _) = send(
+        f.operators(),
+        &f.partner_key,
+        "GET",
+        &format!("/operators/organizations/{}", org_id),
+        None,
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn partner_cannot_manage_users_or_operators() {
+    let f = setup();
+
+    let requests = [
+        ("GET", "/operators/users".to_string(), None),
+        ("GET", "/operators".to_string(), None),
+        (
+            "POST",
+            "/operators".to_string(),
+            Some(serde_json::json!({ "user_id": f.other_member.id, "role": "admin" })),
+        ),
+        (
+            "POST",
+            format!("/operators/{}/org-scopes", f.partner.id),
+            Some(serde_json::json!({ "org_id": f.other.id })),
+        ),
+    ];
+
+    for (method, uri, body) in requests {
+        let (status, _) = send(f.operators(), &f.partner_key, method, &uri, body, None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{} {}", method, uri);
+    }
+}
+
+#[tokio::test]
+async fn partner_audit_logs_limited_to_scoped_orgs() {
+    let f = setup();
+
+    let cases = [
+        ("/operators/audit-logs".to_string(), StatusCode::FORBIDDEN),
+        (
+            "/operators/audit-logs/verify-chain".to_string(),
+            StatusCode::FORBIDDEN,
+        ),
+        (
+            format!("/operators/audit-logs?org_id={}", f.other.id),
+            StatusCode::NOT_FOUND,
+        ),
+        (
+            format!("/operators/audit-logs/text?org_id={}", f.other.id),
+            StatusCode::NOT_FOUND,
+        ),
+        (
+            format!("/operators/audit-logs?org_id={}", f.scoped.id),
+            StatusCode::OK,
+        ),
+    ];
+
+    for (uri, expected) in cases {
+        let (status, _) = send(f.operators(), &f.partner_key, "GET", &uri, None, None).await;
+        assert_eq!(status, expected, "{}", uri);
+    }
+}
+
+#[tokio::test]
+async fn partner_direct_org_access_respects_scope() {
+    let f = setup();
+
+    let (status, _) = send(
+        f.orgs(),
+        &f.partner_key,
+        "GET",
+        &format!("/orgs/{}/members", f.scoped.id),
+        None,
+        None,
+    )
+    .await;
+    assert_eq!(
+        status,
+        StatusCode::OK,
+        "scoped org gets synthetic owner access"
+    );
+
+    let (status, _) = send(
+        f.orgs(),
+        &f.partner_key,
+        "GET",
+        &format!("/orgs/{}/members", f.other.id),
+        None,
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn partner_project_access_respects_scope() {
+    let f = setup();
+
+    let (status, _) = send(
+        f.orgs(),
+        &f.partner_key,
+        "GET",
+        &format!(
+            "/orgs/{}/projects/{}/products",
+            f.scoped.id, f.scoped_project.id
+        ),
+        None,
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = send(
+        f.orgs(),
+        &f.partner_key,
+        "POST",
+        &format!(
+            "/orgs/{}/projects/{}/products",
+            f.other.id, f.other_project.id
+        ),
+        Some(serde_json::json!({ "name": "Sneaky", "tier": "pro" })),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn partner_impersonation_respects_scope() {
+    let f = setup();
+
+    let (status, _) = send(
+        f.orgs(),
+        &f.partner_key,
+        "GET",
+        &format!("/orgs/{}/members", f.scoped.id),
+        None,
+        Some(&f.scoped_member.id),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = send(
+        f.orgs(),
+        &f.partner_key,
+        "GET",
+        &format!("/orgs/{}/members", f.other.id),
+        None,
+        Some(&f.other_member.id),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn owner_assigns_and_removes_partner_scopes() {
+    let f = setup();
+    let scopes_uri = format!("/operators/{}/org-scopes", f.partner.id);
+
+    let (status, json) = send(
+        f.operators(),
+        &f.owner_key,
+        "POST",
+        &scopes_uri,
+        Some(serde_json::json!({ "org_id": f.other.id })),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["org_id"], f.other.id.as_str());
+
+    let (status, _) = send(
+        f.operators(),
+        &f.owner_key,
+        "POST",
+        &scopes_uri,
+        Some(serde_json::json!({ "org_id": f.other.id })),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, json) = send(f.operators(), &f.owner_key, "GET", &scopes_uri, None, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json.as_array().unwrap().len(), 2);
+
+    let (status, _) = send(
+        f.operators(),
+        &f.partner_key,
+        "GET",
+        &format!("/operators/organizations/{}", f.other.id),
+        None,
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "new scope takes effect");
+
+    let (status, _) = send(
+        f.operators(),
+        &f.owner_key,
+        "DELETE",
+        &format!("{}/{}", scopes_uri, f.scoped.id),
+        None,
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = send(
+        f.orgs(),
+        &f.partner_key,
+        "GET",
+        &format!("/orgs/{}/members", f.scoped.id),
+        None,
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN, "removed scope takes effect");
+}
+
+#[tokio::test]
+async fn only_partners_can_be_scoped() {
+    let f = setup();
+    let mut conn = f.state.db.get().unwrap();
+    let (admin, _) = create_test_operator(&mut conn, "admin@platform.com", OperatorRole::Admin);
+    drop(conn);
+
+    let (status, _) = send(
+        f.operators(),
+        &f.owner_key,
+        "POST",
+        &format!("/operators/{}/org-scopes", admin.id),
+        Some(serde_json::json!({ "org_id": f.scoped.id })),
+        None,
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,1005 @@
+//! Permission matrix for the org API: every route in `handlers::orgs::router`
+//! against every kind of caller, with the status each should get.
+//!
+//! Each cell runs against a fresh fixture so a write in one cell can't change
+//! the outcome of another. A route added to the router without a row here
+//! fails `matrix_covers_every_org_route`.
+
+use std::collections::BTreeSet;
+
+use super::helpers::*;
+
+#[derive(Debug, Clone, Copy)]
+enum Caller {
+    OperatorOwner,
+    OperatorAdmin,
+    OperatorView,
+    OrgOwner,
+    OrgAdmin,
+    /// `member` role, not on the project
+    OrgMember,
+    /// `member` role with project admin
+    ProjectAdmin,
+    /// `member` role with project view
+    ProjectView,
+    /// The owner's org-scoped view-only key
+    ViewKey,
+    /// The owner's org-scoped admin key
+    AdminKey,
+    /// Owner of another org
+    Outsider,
+}
+
+const CALLERS: [Caller; 11] = [
+    Caller::OperatorOwner,
+    Caller::OperatorAdmin,
+    Caller::OperatorView,
+    Caller::OrgOwner,
+    Caller::OrgAdmin,
+    Caller::OrgMember,
+    Caller::ProjectAdmin,
+    Caller::ProjectView,
+    Caller::ViewKey,
+    Caller::AdminKey,
+    Caller::Outsider,
+];
+
+#[derive(Debug, Clone, Copy)]
+enum Expect {
+    /// Gets past authorization: anything but 401/403/404/405 or a 5xx
+    Allow,
+    /// 403
+    Deny,
+    /// 404: the project is hidden from members who weren't added to it
+    Hide,
+}
+
+use Expect::{Allow as A, Deny as D, Hide as H};
+
+impl Expect {
+    fn matches(self, status: StatusCode) -> bool {
+        match self {
+            Expect::Allow => {
+                !matches!(status.as_u16(), 401 | 403 | 404 | 405) && !status.is_server_error()
+            }
+            Expect::Deny => status == StatusCode::FORBIDDEN,
+            Expect::Hide => status == StatusCode::NOT_FOUND,
+        }
+    }
+}
+
+/// Expected outcome per caller, in `CALLERS` order
+type Class = [Expect; 11];
+
+// Callers:            OpOwn OpAdm OpView Owner Admin Member PrjAdm PrjView ViewKey AdmKey Outsider
+const ORG_READ: Class = [A, A, D, A, A, A, A, A, A, A, D];
+const ORG_ADMIN: Class = [A, A, D, A, A, D, D, D, D, A, D];
+const ORG_OWNER: Class = [A, A, D, A, D, D, D, D, D, A, D];
+/// The caller's own API keys (operators aren't org members, so they have none)
+const OWN_READ: Class = [H, H, D, A, A, A, A, A, A, A, D];
+const OWN_WRITE: Class = [H, H, D, A, A, A, A, A, D, A, D];
+const PROJECT_READ: Class = [A, A, D, A, A, H, A, A, A, A, D];
+const PROJECT_WRITE: Class = [A, A, D, A, A, H, A, D, D, A, D];
+/// Project routes that need an org owner or admin, whatever the project role
+const PROJECT_ORG_ADMIN: Class = [A, A, D, A, A, H, D, D, D, A, D];
+
+struct Route {
+    method: &'static str,
+    /// Path as registered in the router
+    path: &'static str,
+    query: &'static str,
+    body: Option<&'static str>,
+    expect: Class,
+    /// Runs on the fixture before the request
+    before: Option<fn(&Fixture)>,
+    /// `{user_id}` and `{key_id}` are the caller's own, not the target's
+    own: bool,
+}
+
+fn route(method: &'static str, path: &'static str, expect: Class) -> Route {
+    Route {
+        method,
+        path,
+        query: "",
+        body: None,
+        expect,
+        before: None,
+        own: false,
+    }
+}
+
+impl Route {
+    fn query(self, query: &'static str) -> Self {
+        Route { query, ..self }
+    }
+
+    fn body(self, body: &'static str) -> Self {
+        Route {
+            body: Some(body),
+            ..self
+        }
+    }
+
+    fn before(self, before: fn(&Fixture)) -> Self {
+        Route {
+            before: Some(before),
+            ..self
+        }
+    }
+
+    fn own(self) -> Self {
+        Route { own: true, ..self }
+    }
+}
+
+fn routes() -> Vec<Route> {
+    vec![
+        // Org members
+        route("POST", "/orgs/{org_id}/members", ORG_OWNER)
+            .body(r#"{"user_id":"{new_user_id}","role":"member"}"#),
+        route("GET", "/orgs/{org_id}/members", ORG_READ),
+        route("GET", "/orgs/{org_id}/members/{user_id}", ORG_READ),
+        route("PUT", "/orgs/{org_id}/members/{user_id}", ORG_OWNER).body(r#"{"role":"admin"}"#),
+        route("DELETE", "/orgs/{org_id}/members/{user_id}", ORG_OWNER),
+        route(
+            "POST",
+            "/orgs/{org_id}/members/{user_id}/restore",
+            ORG_OWNER,
+        )
+        .body("{}")
+        .before(|f| {
+            let conn = f.state.db.get().unwrap();
+            queries::soft_delete_org_member(&conn, &f.target_member_id).unwrap();
+        }),
+        route(
+            "POST",
+            "/orgs/{org_id}/members/{user_id}/cancel-removal",
+            ORG_OWNER,
+        )
+        .before(|f| {
+            let conn = f.state.db.get().unwrap();
+            queries::schedule_org_member_removal(
+                &conn,
+                &f.target_member_id,
+                future_timestamp(ONE_WEEK),
+            )
+            .unwrap();
+        }),
+        // Member API keys
+        route(
+            "POST",
+            "/orgs/{org_id}/members/{user_id}/api-keys",
+            ORG_OWNER,
+        )
+        .body(r#"{"name":"Matrix"}"#),
+        route(
+            "GET",
+            "/orgs/{org_id}/members/{user_id}/api-keys",
+            ORG_OWNER,
+        ),
+        route(
+            "DELETE",
+            "/orgs/{org_id}/members/{user_id}/api-keys/{key_id}",
+            ORG_OWNER,
+        ),
+        route(
+            "POST",
+            "/orgs/{org_id}/members/{user_id}/api-keys",
+            OWN_WRITE,
+        )
+        .body(r#"{"name":"Matrix"}"#)
+        .own(),
+        route("GET", "/orgs/{org_id}/members/{user_id}/api-keys", OWN_READ).own(),
+        route(
+            "DELETE",
+            "/orgs/{org_id}/members/{user_id}/api-keys/{key_id}",
+            OWN_WRITE,
+        )
+        .own(),
+        // Org-level
+        route("POST", "/orgs/{org_id}/projects", ORG_ADMIN).body(r#"{"name":"New Project"}"#),
+        route("GET", "/orgs/{org_id}/projects", ORG_READ),
+        route(
+            "POST",
+            "/orgs/{org_id}/projects/{project_id}/restore",
+            ORG_ADMIN,
+        )
+        .body("{}")
+        .before(|f| {
+            let conn = f.state.db.get().unwrap();
+            queries::soft_delete_project(&conn, &f.project_id).unwrap();
+        }),
+        route("GET", "/orgs/{org_id}/limits", ORG_READ),
+        route("POST", "/orgs/{org_id}/product-templates", ORG_ADMIN)
+            .body(r#"{"name":"Basic","tier":"basic"}"#),
+        route("GET", "/orgs/{org_id}/product-templates", ORG_ADMIN),
+        route(
+            "PUT",
+            "/orgs/{org_id}/product-templates/{template_id}",
+            ORG_ADMIN,
+        )
+        .body("{}"),
+        route(
+            "DELETE",
+            "/orgs/{org_id}/product-templates/{template_id}",
+            ORG_ADMIN,
+        ),
+        route("GET", "/orgs/{org_id}/payment-provider", ORG_ADMIN),
+        route("GET", "/orgs/{org_id}/email-config", ORG_OWNER),
+        route("PUT", "/orgs/{org_id}/email-config", ORG_OWNER).body("{}"),
+        route("POST", "/orgs/{org_id}/email-config/test", ORG_OWNER),
+        route("GET", "/orgs/{org_id}/compliance-settings", ORG_OWNER),
+        route("PUT", "/orgs/{org_id}/compliance-settings", ORG_OWNER).body("{}"),
+        route("GET", "/orgs/{org_id}/audit-logs", ORG_READ),
+        route("GET", "/orgs/{org_id}/audit-logs/export", ORG_READ),
+        route("POST", "/orgs/{org_id}/audit-logs/archive", ORG_OWNER)
+            .body(r#"{"from":0,"to":4102444800}"#),
+        // Project
+        route("GET", "/orgs/{org_id}/projects/{project_id}", PROJECT_READ),
+        route("PUT", "/orgs/{org_id}/projects/{project_id}", PROJECT_WRITE).body("{}"),
+        route(
+            "PUT",
+            "/orgs/{org_id}/projects/{project_id}/client-flags",
+            PROJECT_WRITE,
+        )
+        .body(r#"{"client_flags":{"banner":"Scheduled maintenance"}}"#),
+        route(
+            "POST",
+            "/orgs/{org_id}/projects/{project_id}/apply-template",
+            PROJECT_ORG_ADMIN,
+        )
+        .body(r#"{"templates":["{template_id}"]}"#),
+        route(
+            "DELETE",
+            "/orgs/{org_id}/projects/{project_id}",
+            PROJECT_ORG_ADMIN,
+        ),
+        route(
+            "POST",
+            "/orgs/{org_id}/projects/{project_id}/diagnose-token",
+            PROJECT_READ,
+        )
+        .body(r#"{"token":"not-a-token"}"#),
+        route(
+            "GET",
+            "/orgs/{org_id}/projects/{project_id}/config-export",
+            PROJECT_READ,
+        ),
+        route(
+            "PUT",
+            "/orgs/{org_id}/projects/{project_id}/config-import",
+            PROJECT_WRITE,
+        )
+        .query("?dry_run=true")
+        .body("{}"),
+        // Project members
+        route(
+            "POST",
+            "/orgs/{org_id}/projects/{project_id}/members",
+            PROJECT_WRITE,
+        )
+        .body(r#"{"user_id":"{spare_user_id}","role":"view"}"#),
+        route(
+            "GET",
+            "/orgs/{org_id}/projects/{project_id}/members",
+            PROJECT_READ,
+        ),
+        route(
+            "GET",
+            "/orgs/{org_id}/projects/{project_id}/members/{user_id}",
+            PROJECT_READ,
+        ),
+        route(
+            "PUT",
+            "/orgs/{org_id}/projects/{project_id}/members/{user_id}",
+            PROJECT_WRITE,
+        )
+        .body(r#"{"role":"admin"}"#),
+        route(
+            "DELETE",
+            "/orgs/{org_id}/projects/{project_id}/members/{user_id}",
+            PROJECT_WRITE,
+        ),
+        // Temporary roles
+        route(
+            "POST",
+            "/orgs/{org_id}/projects/{project_id}/members/{user_id}/temporary-role",
+            PROJECT_WRITE,
+        )
+        .body(r#"{"role":"admin","expires_in_minutes":60}"#),
+        route(
+            "DELETE",
+            "/orgs/{org_id}/projects/{project_id}/members/{user_id}/temporary-role",
+            PROJECT_WRITE,
+        )
+        .before(|f| {
+            let mut conn = f.state.db.get().unwrap();
+            let grant = GrantTemporaryRole {
+                role: ProjectMemberRole::Admin,
+                expires_in_minutes: 60,
+                reason: None,
+            };
+            queries::create_temporary_role_grant(
+                &mut conn,
+                &f.target_member_id,
+                &f.project_id,
+                &grant,
+                &f.owner_user_id,
+                now(),
+            )
+            .unwrap();
+        }),
+        route(
+            "GET",
+            "/orgs/{org_id}/projects/{project_id}/temporary-roles",
+            PROJECT_READ,
+        ),
+        // Terms of sale
+        route(
+            "POST",
+            "/orgs/{org_id}/projects/{project_id}/terms",
+            PROJECT_WRITE,
+        )
+        .body(r#"{"version":"2","url":"https://example.com/terms/2"}"#),
+        route(
+            "GET",
+            "/orgs/{org_id}/projects/{project_id}/terms",
+            PROJECT_READ,
+        ),
+        route(
+            "PUT",
+            "/orgs/{org_id}/projects/{project_id}/terms/{terms_id}",
+            PROJECT_WRITE,
+        )
+        .body("{}"),
+        // Release artifacts
+        route(
+            "POST",
+            "/orgs/{org_id}/projects/{project_id}/artifacts",
+            PROJECT_WRITE,
+        )
+        .body(r#"{"name":"MyApp-2.0.dmg","url":"https://cdn.example.com/MyApp-2.0.dmg"}"#),
+        route(
+            "GET",
+            "/orgs/{org_id}/projects/{project_id}/artifacts",
+            PROJECT_READ,
+        ),
+        route(
+            "DELETE",
+            "/orgs/{org_id}/projects/{project_id}/artifacts/{artifact_id}",
+            PROJECT_WRITE,
+        ),
+        route(
+            "GET",
+            "/orgs/{org_id}/projects/{project_id}/artifacts/{artifact_id}/downloads",
+            PROJECT_READ,
+        ),
+        route(
+            "GET",
+            "/orgs/{org_id}/projects/{project_id}/download-signing-key",
+            PROJECT_ORG_ADMIN,
+        ),
+        // Products
+        route(
+            "POST",
+            "/orgs/{org_id}/projects/{project_id}/products",
+            PROJECT_WRITE,
+        )
+        .body(r#"{"name":"New Plan","tier":"new"}"#),
+        route(
+            "GET",
+            "/orgs/{org_id}/projects/{project_id}/products",
+            PROJECT_READ,
+        ),
+        route(
+            "GET",
+            "/orgs/{org_id}/projects/{project_id}/products/{product_id}",
+            PROJECT_READ,
+        ),
+        route(
+            "PUT",
+            "/orgs/{org_id}/projects/{project_id}/products/{product_id}",
+            PROJECT_WRITE,
+        )
+        .body("{}"),
+        route(
+            "DELETE",
+            "/orgs/{org_id}/projects/{project_id}/products/{product_id}",
+            PROJECT_WRITE,
+        ),
+        route(
+            "POST",
+            "/orgs/{org_id}/projects/{project_id}/products/{product_id}/restore",
+            PROJECT_WRITE,
+        )
+        .body("{}")
+        .before(|f| {
+            let conn = f.state.db.get().unwrap();
+            queries::soft_delete_product(&conn, &f.product_id).unwrap();
+        }),
+        // Provider links
+        route(
+            "POST",
+            "/orgs/{org_id}/projects/{project_id}/products/{product_id}/provider-links",
+            PROJECT_WRITE,
+        )
+        .body(r#"{"provider":"lemonsqueezy","linked_id":"var_1"}"#),
+        route(
+            "GET",
+            "/orgs/{org_id}/projects/{project_id}/products/{product_id}/provider-links",
+            PROJECT_READ,
+        ),
+        route(
+            "GET",
+            "/orgs/{org_id}/projects/{project_id}/products/{product_id}/provider-links/{link_id}",
+            PROJECT_READ,
+        ),
+        route(
+            "PUT",
+            "/orgs/{org_id}/projects/{project_id}/products/{product_id}/provider-links/{link_id}",
+            PROJECT_WRITE,
+        )
+        .body(r#"{"linked_id":"price_456"}"#),
+        route(
+            "DELETE",
+            "/orgs/{org_id}/projects/{project_id}/products/{product_id}/provider-links/{link_id}",
+            PROJECT_WRITE,
+        ),
+        // Prepaid codes
+        route(
+            "POST",
+            "/orgs/{org_id}/projects/{project_id}/products/{product_id}/prepaid-codes",
+            PROJECT_WRITE,
+        )
+        .body(r#"{"count":2}"#),
+        route(
+            "GET",
+            "/orgs/{org_id}/projects/{project_id}/products/{product_id}/prepaid-codes",
+            PROJECT_READ,
+        ),
+        // Downloading hands out the codes, so it counts as a write
+        route(
+            "GET",
+            "/orgs/{org_id}/projects/{project_id}/products/{product_id}/prepaid-codes/{batch_id}/csv",
+            PROJECT_WRITE,
+        ),
+        route(
+            "POST",
+            "/orgs/{org_id}/projects/{project_id}/products/{product_id}/prepaid-codes/{batch_id}/revoke",
+            PROJECT_WRITE,
+        ),
+        // License key imports
+        route(
+            "POST",
+            "/orgs/{org_id}/projects/{project_id}/license-imports/lemonsqueezy",
+            PROJECT_WRITE,
+        )
+        .body(r#"{"products":{}}"#),
+        // Licenses
+        route(
+            "GET",
+            "/orgs/{org_id}/projects/{project_id}/licenses",
+            PROJECT_READ,
+        ),
+        route(
+            "POST",
+            "/orgs/{org_id}/projects/{project_id}/licenses",
+            PROJECT_WRITE,
+        )
+        .body(r#"{"product_id":"{product_id}"}"#),
+        // Bulk license jobs
+        route(
+            "GET",
+            "/orgs/{org_id}/projects/{project_id}/bulk-jobs/{job_id}",
+            PROJECT_READ,
+        ),
+        route(
+            "POST",
+            "/orgs/{org_id}/projects/{project_id}/bulk-jobs/{job_id}/cancel",
+            PROJECT_WRITE,
+        ),
+        // Downloading issues activation codes, so it counts as a write
+        route(
+            "GET",
+            "/orgs/{org_id}/projects/{project_id}/bulk-jobs/{job_id}/csv",
+            PROJECT_WRITE,
+        ),
+        route(
+            "GET",
+            "/orgs/{org_id}/projects/{project_id}/licenses/tags",
+            PROJECT_READ,
+        ),
+        route(
+            "POST",
+            "/orgs/{org_id}/projects/{project_id}/licenses/tags/bulk",
+            PROJECT_WRITE,
+        )
+        .body(r#"{"tags":["vip"],"filter":{"customer_id":"test-customer"}}"#),
+        route(
+            "GET",
+            "/orgs/{org_id}/projects/{project_id}/entitlements",
+            PROJECT_READ,
+        )
+        .query("?customer_id=test-customer&feature=feature1"),
+        route(
+            "POST",
+            "/orgs/{org_id}/projects/{project_id}/entitlements/check",
+            PROJECT_READ,
+        )
+        .body(r#"{"customer_ids":["test-customer"],"features":["feature1"]}"#),
+        route(
+            "GET",
+            "/orgs/{org_id}/projects/{project_id}/entitlements/suggestions",
+            PROJECT_READ,
+        ),
+        route(
+            "GET",
+            "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}",
+            PROJECT_READ,
+        ),
+        route(
+            "PATCH",
+            "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}",
+            PROJECT_WRITE,
+        )
+        .body(r#"{"email":"new@example.com"}"#),
+        route(
+            "POST",
+            "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/revoke",
+            PROJECT_WRITE,
+        ),
+        route(
+            "POST",
+            "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/restore",
+            PROJECT_WRITE,
+        )
+        .body("{}")
+        .before(|f| {
+            let conn = f.state.db.get().unwrap();
+            queries::soft_delete_license(&conn, &f.license_id).unwrap();
+        }),
+        route(
+            "POST",
+            "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/send-code",
+            PROJECT_WRITE,
+        ),
+        route(
+            "GET",
+            "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/claims-preview",
+            PROJECT_READ,
+        ),
+        route(
+            "GET",
+            "/orgs/{org_id}/projects/{project_id}/email-log",
+            PROJECT_READ,
+        ),
+        route(
+            "GET",
+            "/orgs/{org_id}/projects/{project_id}/disputes",
+            PROJECT_READ,
+        ),
+        route(
+            "GET",
+            "/orgs/{org_id}/projects/{project_id}/activity",
+            PROJECT_READ,
+        ),
+        route(
+            "GET",
+            "/orgs/{org_id}/projects/{project_id}/conversion-stats",
+            PROJECT_READ,
+        ),
+        route(
+            "GET",
+            "/orgs/{org_id}/projects/{project_id}/usage/geo",
+            PROJECT_READ,
+        ),
+        route(
+            "GET",
+            "/orgs/{org_id}/projects/{project_id}/usage/versions",
+            PROJECT_READ,
+        ),
+        // Seats
+        route(
+            "GET",
+            "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/seats",
+            PROJECT_READ,
+        ),
+        route(
+            "POST",
+            "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/seats",
+            PROJECT_WRITE,
+        )
+        .body(r#"{"email":"seat2@example.com"}"#),
+        route(
+            "DELETE",
+            "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/seats/{seat_id}",
+            PROJECT_WRITE,
+        ),
+        // Tags
+        route(
+            "POST",
+            "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/tags",
+            PROJECT_WRITE,
+        )
+        .body(r#"{"tags":["vip"]}"#),
+        route(
+            "DELETE",
+            "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/tags",
+            PROJECT_WRITE,
+        )
+        .body(r#"{"tags":["vip"]}"#),
+        // Share links
+        route(
+            "POST",
+            "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/share-link",
+            PROJECT_WRITE,
+        )
+        .body("{}"),
+        route(
+            "POST",
+            "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/share-link/{share_link_id}/revoke",
+            PROJECT_WRITE,
+        ),
+        // Devices
+        route(
+            "DELETE",
+            "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/devices/{device_id}",
+            PROJECT_WRITE,
+        ),
+        route(
+            "POST",
+            "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/devices/deactivate-all",
+            PROJECT_WRITE,
+        )
+        .body("{}"),
+        route(
+            "POST",
+            "/orgs/{org_id}/projects/{project_id}/devices/deactivate-by-filter",
+            PROJECT_ORG_ADMIN,
+        )
+        .body(r#"{"device_type":"uuid","confirm":true}"#),
+    ]
+}
+
+struct Credentials {
+    user_id: String,
+    key: String,
+    key_id: String,
+}
+
+struct Fixture {
+    app: Router,
+    state: AppState,
+    org_id: String,
+    project_id: String,
+    product_id: String,
+    link_id: String,
+    batch_id: String,
+    job_id: String,
+    terms_id: String,
+    artifact_id: String,
+    template_id: String,
+    license_id: String,
+    seat_id: String,
+    share_link_id: String,
+    owner_user_id: String,
+    /// `member`-role user with view access to the project, the subject of
+    /// member, API key and temporary role routes
+    target: Credentials,
+    target_member_id: String,
+    /// Org member who isn't on the project yet
+    spare_user_id: String,
+    /// User outside the org
+    new_user_id: String,
+    /// In `CALLERS` order
+    callers: Vec<Credentials>,
+}
+
+fn credentials(state: &AppState, key: String) -> Credentials {
+    let conn = state.db.get().unwrap();
+    let (user, api_key) = queries::get_user_by_api_key(&conn, &key, &state.emails())
+        .unwrap()
+        .expect("key should authenticate");
+    Credentials {
+        user_id: user.id,
+        key,
+        key_id: api_key.id,
+    }
+}
+
+fn fixture() -> Fixture {
+    let (app, state) = org_app();
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Matrix Org");
+    let project = create_test_project(&conn, &org.id, "Matrix Project", &state.master_key);
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+    let link = create_test_provider_link(&conn, &product.id, "stripe", "price_123");
+    let license = queries::create_license(
+        &conn,
+        &project.id,
+        &product.id,
+        &CreateLicense {
+            customer_id: Some("test-customer".to_string()),
+            expires_at: Some(future_timestamp(ONE_YEAR)),
+            seats: Some(5),
+            ..fixtures::license_input(Some(
+                state.email_hasher.hash(&parse_email("buyer@example.com")),
+            ))
+        },
+    )
+    .unwrap();
+    let seat = queries::assign_license_seat(
+        &conn,
+        &license,
+        &state.email_hasher.hash(&parse_email("seat@example.com")),
+    )
+    .unwrap();
+    create_test_device(&conn, &license.id, "matrix-device", DeviceType::Uuid);
+    let (share_link, _) =
+        queries::create_share_link(&conn, &license.id, None, None, future_timestamp(ONE_DAY))
+            .unwrap();
+    let batch = queries::create_prepaid_code_batch(
+        &conn,
+        &state.master_key,
+        &project,
+        &product.id,
+        &CreatePrepaidCodes {
+            count: 2,
+            label: None,
+            expires_in_days: None,
+        },
+        None,
+    )
+    .unwrap();
+    let job = queries::create_bulk_job(
+        &conn,
+        &project.id,
+        &product.id,
+        500,
+        true,
+        &BulkLicenseParams::default(),
+        None,
+    )
+    .unwrap();
+    let terms = queries::create_terms(
+        &conn,
+        &project.id,
+        &CreateTerms {
+            version: "1".to_string(),
+            url: "https://example.com/terms/1".to_string(),
+            effective_at: None,
+        },
+        past_timestamp(ONE_DAY),
+        None,
+    )
+    .unwrap();
+    let artifact = queries::create_artifact(
+        &conn,
+        &project.id,
+        &CreateArtifact {
+            name: "MyApp-1.0.dmg".to_string(),
+            url: "https://cdn.example.com/MyApp-1.0.dmg".to_string(),
+            released_at: None,
+        },
+        past_timestamp(ONE_DAY),
+        None,
+    )
+    .unwrap();
+    let template = queries::create_product_template(
+        &conn,
+        &org.id,
+        &serde_json::from_value(serde_json::json!({"name": "Starter", "tier": "starter"})).unwrap(),
+    )
+    .unwrap();
+
+    let (_, target_member, target_key) =
+        create_test_org_member(&mut conn, &org.id, "target@test.com", OrgMemberRole::Member);
+    create_test_project_member(
+        &conn,
+        &target_member.id,
+        &project.id,
+        ProjectMemberRole::View,
+    );
+    let (spare, _, _) =
+        create_test_org_member(&mut conn, &org.id, "spare@test.com", OrgMemberRole::Member);
+    let new_user = create_test_user(&conn, "new@test.com", "New User");
+
+    let (_, op_owner_key) =
+        create_test_operator(&mut conn, "op-owner@test.com", OperatorRole::Owner);
+    let (_, op_admin_key) =
+        create_test_operator(&mut conn, "op-admin@test.com", OperatorRole::Admin);
+    let (_, op_view_key) = create_test_operator(&mut conn, "op-view@test.com", OperatorRole::View);
+    let (owner, _, owner_key) =
+        create_test_org_member(&mut conn, &org.id, "owner@test.com", OrgMemberRole::Owner);
+    let (_, _, admin_key) =
+        create_test_org_member(&mut conn, &org.id, "admin@test.com", OrgMemberRole::Admin);
+    let (_, _, member_key) =
+        create_test_org_member(&mut conn, &org.id, "member@test.com", OrgMemberRole::Member);
+    let (_, project_admin, project_admin_key) = create_test_org_member(
+        &mut conn,
+        &org.id,
+        "project-admin@test.com",
+        OrgMemberRole::Member,
+    );
+    create_test_project_member(
+        &conn,
+        &project_admin.id,
+        &project.id,
+        ProjectMemberRole::Admin,
+    );
+    let (_, project_view, project_view_key) = create_test_org_member(
+        &mut conn,
+        &org.id,
+        "project-view@test.com",
+        OrgMemberRole::Member,
+    );
+    create_test_project_member(
+        &conn,
+        &project_view.id,
+        &project.id,
+        ProjectMemberRole::View,
+    );
+    let view_key = create_api_key_with_org_scope(&mut conn, &owner.id, &org.id, AccessLevel::View);
+    let scoped_admin_key =
+        create_api_key_with_org_scope(&mut conn, &owner.id, &org.id, AccessLevel::Admin);
+    let other_org = create_test_org(&conn, "Other Org");
+    let (_, _, outsider_key) = create_test_org_member(
+        &mut conn,
+        &other_org.id,
+        "outsider@test.com",
+        OrgMemberRole::Owner,
+    );
+    drop(conn);
+
+    let callers = [
+        op_owner_key,
+        op_admin_key,
+        op_view_key,
+        owner_key,
+        admin_key,
+        member_key,
+        project_admin_key,
+        project_view_key,
+        view_key,
+        scoped_admin_key,
+        outsider_key,
+    ]
+    .into_iter()
+    .map(|key| credentials(&state, key))
+    .collect();
+
+    Fixture {
+        target: credentials(&state, target_key),
+        app,
+        state,
+        org_id: org.id,
+        project_id: project.id,
+        product_id: product.id,
+        link_id: link.id,
+        batch_id: batch.id,
+        job_id: job.id,
+        terms_id: terms.id,
+        artifact_id: artifact.id,
+        template_id: template.id,
+        license_id: license.id,
+        seat_id: seat.id,
+        share_link_id: share_link.id,
+        owner_user_id: owner.id,
+        target_member_id: target_member.id,
+        spare_user_id: spare.id,
+        new_user_id: new_user.id,
+        callers,
+    }
+}
+
+impl Fixture {
+    fn fill(&self, template: &str, caller: &Credentials, own: bool) -> String {
+        let subject = if own { caller } else { &self.target };
+        [
+            ("{org_id}", &self.org_id),
+            ("{project_id}", &self.project_id),
+            ("{product_id}", &self.product_id),
+            ("{link_id}", &self.link_id),
+            ("{batch_id}", &self.batch_id),
+            ("{job_id}", &self.job_id),
+            ("{terms_id}", &self.terms_id),
+            ("{artifact_id}", &self.artifact_id),
+            ("{template_id}", &self.template_id),
+            ("{license_id}", &self.license_id),
+            ("{seat_id}", &self.seat_id),
+            ("{share_link_id}", &self.share_link_id),
+            ("{user_id}", &subject.user_id),
+            ("{key_id}", &subject.key_id),
+            ("{spare_user_id}", &self.spare_user_id),
+            ("{new_user_id}", &self.new_user_id),
+        ]
+        .into_iter()
+        .fold(template.to_string(), |filled, (placeholder, value)| {
+            filled.replace(placeholder, value)
+        })
+        .replace("{device_id}", "matrix-device")
+    }
+
+    async fn send(&self, route: &Route, caller: &Credentials) -> StatusCode {
+        let uri = self.fill(&format!("{}{}", route.path, route.query), caller, route.own);
+        let mut request = Request::builder()
+            .method(route.method)
+            .uri(uri)
+            .header("Authorization", format!("Bearer {}", caller.key));
+        let body = match route.body {
+            Some(body) => {
+                request = request.header("content-type", "application/json");
+                Body::from(self.fill(body, caller, route.own))
+            }
+            None => Body::empty(),
+        };
+        self.app
+            .clone()
+            .oneshot(request.body(body).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+}
+
+#[tokio::test]
+async fn every_caller_gets_expected_status_on_every_route() {
+    let mut mismatches = Vec::new();
+
+    for route in routes() {
+        for (i, caller) in CALLERS.iter().enumerate() {
+            let f = fixture();
+            if let Some(before) = route.before {
+                before(&f);
+            }
+            let status = f.send(&route, &f.callers[i]).await;
+            let expected = route.expect[i];
+            if !expected.matches(status) {
+                mismatches.push(format!(
+                    "{} {}{} as {:?}: expected {:?}, got {}",
+                    route.method,
+                    route.path,
+                    if route.own { " (own)" } else { "" },
+                    caller,
+                    expected,
+                    status
+                ));
+            }
+        }
+    }
+
+    assert!(
+        mismatches.is_empty(),
+        "{} cells don't match the permission matrix:\n{}",
+        mismatches.len(),
+        mismatches.join("\n")
+    );
+}
+
+/// Method and path of every `.route(...)` in the org router's source
+fn registered_routes() -> BTreeSet<(String, String)> {
+    routes_in_source(include_str!("../../src/handlers/orgs/mod.rs"))
+}
+
+#[test]
+fn matrix_covers_every_org_route() {
+    let registered = registered_routes();
+    let covered: BTreeSet<(String, String)> = routes()
+        .into_iter()
+        .map(|route| (route.method.to_string(), route.path.to_string()))
+        .collect();
+
+    let missing: Vec<_> = registered.difference(&covered).collect();
+    let stale: Vec<_> = covered.difference(&registered).collect();
+    assert!(
+        missing.is_empty(),
+        "routes without a row in the permission matrix: {:?}",
+        missing
+    );
+    assert!(
+        stale.is_empty(),
+        "matrix rows for routes that aren't registered: {:?}",
+        stale
+    );
+}
@@ -0,0 +1,199 @@
+//! A `member`-role user can't learn the IDs or names of projects they weren't
+//! added to through any org-level endpoint.
+
+use super::helpers::*;
+use paycheck::models::{ActorType, AuditLogNames, GrantTemporaryRole};
+
+const HIDDEN_NAME: &str = "Hidden Project";
+
+struct VisibilityFixture {
+    app: Router,
+    state: AppState,
+    org_id: String,
+    assigned: Project,
+    hidden: Project,
+    member_id: String,
+    member_user_id: String,
+    member_key: String,
+    owner_key: String,
+}
+
+/// An org with an assigned and a hidden project, each with audit entries;
+/// the hidden project has the most products.
+fn setup() -> VisibilityFixture {
+    let (app, state) = org_app();
+    let mut conn = state.db.get().unwrap();
+    let audit_conn = state.audit.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let assigned = create_test_project(&conn, &org.id, "Assigned Project", &state.master_key);
+    let hidden = create_test_project(&conn, &org.id, HIDDEN_NAME, &state.master_key);
+    create_test_product(&conn, &assigned.id, "Basic", "basic");
+    create_test_product(&conn, &hidden.id, "Basic", "basic");
+    create_test_product(&conn, &hidden.id, "Pro", "pro");
+
+    let (_, _, owner_key) =
+        create_test_org_member(&mut conn, &org.id, "owner@org.com", OrgMemberRole::Owner);
+    let (member_user, member, member_key) =
+        create_test_org_member(&mut conn, &org.id, "member@org.com", OrgMemberRole::Member);
+    create_test_project_member(&conn, &member.id, &assigned.id, ProjectMemberRole::View);
+
+    for (action, project) in [
+        ("assigned_action", Some(&assigned)),
+        ("hidden_action", Some(&hidden)),
+        ("org_action", None),
+    ] {
+        queries::create_audit_log(
+            &audit_conn,
+            true,
+            ActorType::User,
+            Some("someone"),
+            action,
+            "project",
+            &project.map_or(hidden.id.clone(), |p| p.id.clone()),
+            None,
+            Some(&org.id),
+            project.map(|p| p.id.as_str()),
+            None,
+            None,
+            &AuditLogNames {
+                project_name: Some(project.map_or(HIDDEN_NAME, |p| p.name.as_str()).into()),
+                ..Default::default()
+            },
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    }
+
+    VisibilityFixture {
+        app,
+        state,
+        org_id: org.id,
+        assigned,
+        hidden,
+        member_id: member.id,
+        member_user_id: member_user.id,
+        member_key,
+        owner_key,
+    }
+}
+
+impl VisibilityFixture {
+    async fn get(&self, path: &str, key: &str) -> (StatusCode, String) {
+        let response = self
+            .app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri(path)
+                    .header("Authorization", format!("Bearer {}", key))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    fn assert_hidden(&self, path: &str, body: &str) {
+        assert!(
+            !body.contains(&self.hidden.id),
+            "{path} leaked the hidden project's ID: {body}"
+        );
+        assert!(
+            !body.contains(HIDDEN_NAME),
+            "{path} leaked the hidden project's name: {body}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn member_cannot_learn_unassigned_projects_through_org_endpoints() {
+    let f = setup();
+    let paths = [
+        format!("/orgs/{}/projects", f.org_id),
+        format!("/orgs/{}/members", f.org_id),
+        format!("/orgs/{}/members/{}", f.org_id, f.member_user_id),
+        format!("/orgs/{}/members/{}/api-keys", f.org_id, f.member_user_id),
+        format!("/orgs/{}/limits", f.org_id),
+        format!("/orgs/{}/audit-logs", f.org_id),
+        format!("/orgs/{}/payment-provider", f.org_id),
+        format!("/orgs/{}/email-config", f.org_id),
+    ];
+
+    for path in &paths {
+        let (status, body) = f.get(path, &f.member_key).await;
+        if status == StatusCode::OK {
+            f.assert_hidden(path, &body);
+        }
+    }
+}
+
+#[tokio::test]
+async fn member_sees_usage_without_hidden_project() {
+    let f = setup();
+    let path = format!("/orgs/{}/limits", f.org_id);
+
+    let (status, body) = f.get(&path, &f.member_key).await;
+    assert_eq!(status, StatusCode::OK);
+    f.assert_hidden(&path, &body);
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let products = body["limits"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|l| l["limit_name"] == "products_per_project")
+        .unwrap();
+    assert_eq!(products["used"], 2, "usage still counts the whole org");
+    assert!(products.get("project_id").is_none());
+
+    // Owners see which project it is
+    let (_, body) = f.get(&path, &f.owner_key).await;
+    assert!(body.contains(&f.hidden.id));
+}
+
+#[tokio::test]
+async fn member_sees_assigned_project_in_audit_logs() {
+    let f = setup();
+    let path = format!("/orgs/{}/audit-logs", f.org_id);
+
+    let (status, body) = f.get(&path, &f.member_key).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(&f.assigned.id));
+    assert!(body.contains("assigned_action"));
+
+    let (_, body) = f.get(&path, &f.owner_key).await;
+    assert!(body.contains("hidden_action"));
+    assert!(body.contains(HIDDEN_NAME));
+}
+
+#[tokio::test]
+async fn member_sees_project_with_temporary_role_in_audit_logs() {
+    let f = setup();
+    let path = format!("/orgs/{}/audit-logs", f.org_id);
+    let mut conn = f.state.db.get().unwrap();
+    let grant = GrantTemporaryRole {
+        role: ProjectMemberRole::View,
+        expires_in_minutes: 60,
+        reason: None,
+    };
+    queries::create_temporary_role_grant(
+        &mut conn,
+        &f.member_id,
+        &f.hidden.id,
+        &grant,
+        "owner",
+        f.state.clock.now(),
+    )
+    .unwrap();
+
+    let (status, body) = f.get(&path, &f.member_key).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("hidden_action"));
+    assert!(!body.contains("org_action"));
+}
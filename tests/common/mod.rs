@@ -29,19 +29,28 @@ use axum::routing::{get, post};
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
 use rusqlite::Connection;
+use std::collections::BTreeSet;
 use std::sync::Arc;
 
 // Re-export the main library crate
 pub use paycheck::crypto::{EmailHasher, MasterKey};
-pub use paycheck::db::{AppState, init_audit_db, init_db, queries};
+pub use paycheck::db::{
+    AppState, EmailColumn, OrgDbRegistry, ProjectMissCache, init_audit_db, init_db, queries,
+};
 pub use paycheck::email::EmailService;
+pub use paycheck::fixtures;
 pub use paycheck::handlers::public::{
-    deactivate_device, get_license_info, initiate_buy, payment_callback, redeem_with_code,
-    request_activation_code, validate_license,
+    deactivate_device, get_catalog, get_discovery, get_license_info, initiate_buy,
+    payment_callback, redeem_claim_code, redeem_lemonsqueezy_key, redeem_prepaid_code,
+    redeem_with_code, refresh_token, request_activation_code, validate_license,
+    view_shared_license, wait_for_payment,
 };
 pub use paycheck::jwt::{self, JwksCache};
+pub use paycheck::middleware::MaintenanceMode;
 pub use paycheck::models::*;
-pub use paycheck::rate_limit::ActivationRateLimiter;
+pub use paycheck::payments::ProviderCallGovernor;
+pub use paycheck::rate_limit::{ActivationRateLimiter, ValidationRateLimiter};
+pub use paycheck::util::Clock;
 
 /// Create a test master key (deterministic for testing)
 pub fn test_master_key() -> MasterKey {
@@ -56,6 +65,21 @@ pub fn test_email_hasher() -> EmailHasher {
     EmailHasher::from_bytes([0xAA; 32])
 }
 
+/// Parse an email known to be valid (for hashing in fixtures and assertions)
+pub fn parse_email(email: &str) -> EmailAddress {
+    EmailAddress::parse(email).expect("test email should be valid")
+}
+
+/// Create a test email column codec (plaintext storage, the default mode)
+pub fn test_email_column() -> EmailColumn {
+    EmailColumn::new(test_master_key(), test_email_hasher(), false)
+}
+
+/// Create a test email column codec with PII minimization (encrypted storage)
+pub fn test_minimized_email_column() -> EmailColumn {
+    EmailColumn::new(test_master_key(), test_email_hasher(), true)
+}
+
 /// Create an in-memory test database with schema initialized
 pub fn setup_test_db() -> Connection {
     let conn = Connection::open_in_memory().expect("Failed to create in-memory database");
@@ -72,27 +96,15 @@ pub fn setup_test_audit_db() -> Connection {
 
 /// Create a test user
 pub fn create_test_user(conn: &Connection, email: &str, name: &str) -> User {
-    let input = CreateUser {
-        email: email.to_string(),
-        name: name.to_string(),
-    };
-    queries::create_user(conn, &input).expect("Failed to create test user")
+    fixtures::create_user(conn, email, name, &test_email_column())
+        .expect("Failed to create test user")
 }
 
 /// Create a test operator with default values (returns User with operator_role and API key)
 pub fn create_test_operator(conn: &mut Connection, email: &str, role: OperatorRole) -> (User, String) {
-    // Create user first
-    let user = create_test_user(conn, email, &format!("Test Operator {}", email));
-
-    // Grant operator role to user
-    let user = queries::grant_operator_role(conn, &user.id, role)
-        .expect("Failed to grant operator role");
-
-    // Create API key for the user
-    let (_, api_key) = queries::create_api_key(conn, &user.id, "Default", None, true, None)
-        .expect("Failed to create test operator API key");
-
-    (user, api_key)
+    let name = format!("Test Operator {}", email);
+    fixtures::create_operator(conn, email, &name, role, &test_email_column())
+        .expect("Failed to create test operator")
 }
 
 /// Create a test organization
@@ -101,7 +113,8 @@ pub fn create_test_org(conn: &Connection, name: &str) -> Organization {
         name: name.to_string(),
         owner_user_id: None,
     };
-    queries::create_organization(conn, &input).expect("Failed to create test organization")
+    queries::create_organization(conn, &input, &test_master_key())
+        .expect("Failed to create test organization")
 }
 
 /// Create a test org member with default values (returns User, OrgMember, and API key)
@@ -111,22 +124,9 @@ pub fn create_test_org_member(
     email: &str,
     role: OrgMemberRole,
 ) -> (User, OrgMember, String) {
-    // Create user first
-    let user = create_test_user(conn, email, &format!("Test Member {}", email));
-
-    // Create org member linked to user
-    let input = CreateOrgMember {
-        user_id: user.id.clone(),
-        role,
-    };
-    let member =
-        queries::create_org_member(conn, org_id, &input).expect("Failed to create test org member");
-
-    // Create an API key for the user
-    let (_, api_key) = queries::create_api_key(conn, &member.user_id, "Default", None, true, None)
-        .expect("Failed to create test org member API key");
-
-    (user, member, api_key)
+    let name = format!("Test Member {}", email);
+    fixtures::create_org_member(conn, org_id, email, &name, role, &test_email_column())
+        .expect("Failed to create test org member")
 }
 
 /// Create a test project with auto-generated keypair and encrypted private key
@@ -136,32 +136,23 @@ pub fn create_test_project(
     name: &str,
     master_key: &MasterKey,
 ) -> Project {
-    let input = CreateProject {
-        name: name.to_string(),
-        license_key_prefix: "TEST".to_string(),
-        redirect_url: None,
-        email_from: None,
-        email_enabled: true,
-        email_webhook_url: None,
-    };
-    let (private_key, public_key) = jwt::generate_keypair();
-    queries::create_project(conn, org_id, &input, &private_key, &public_key, master_key)
+    let input = fixtures::project_input(name, "TEST");
+    fixtures::create_project(conn, org_id, &input, master_key)
         .expect("Failed to create test project")
 }
 
 /// Create a test product
 pub fn create_test_product(conn: &Connection, project_id: &str, name: &str, tier: &str) -> Product {
     let input = CreateProduct {
-        name: name.to_string(),
-        tier: tier.to_string(),
         license_exp_days: Some(365),
         updates_exp_days: Some(365),
         activation_limit: Some(5),
         device_limit: Some(3),
-        device_inactive_days: None,
         features: vec!["feature1".to_string(), "feature2".to_string()],
+        entitlements: Default::default(),
         price_cents: Some(4999),
         currency: Some("usd".to_string()),
+        ..fixtures::product_input(name, tier)
     };
     queries::create_product(conn, project_id, &input).expect("Failed to create test product")
 }
@@ -188,15 +179,12 @@ pub fn create_test_license(
     product_id: &str,
     expires_at: Option<i64>,
 ) -> License {
+    let email_hash = test_email_hasher().hash(&parse_email("test@example.com"));
     let input = CreateLicense {
-        email_hash: Some(test_email_hasher().hash("test@example.com")),
         customer_id: Some("test-customer".to_string()),
         expires_at,
         updates_expires_at: expires_at,
-        payment_provider: None,
-        payment_provider_customer_id: None,
-        payment_provider_subscription_id: None,
-        payment_provider_order_id: None,
+        ..fixtures::license_input(Some(email_hash))
     };
     queries::create_license(conn, project_id, product_id, &input)
         .expect("Failed to create test license")
@@ -260,29 +248,120 @@ pub fn create_test_app_state() -> AppState {
         audit: audit_pool,
         base_url: "http://localhost:3000".to_string(),
         audit_log_enabled: false,
+        audit_failure_mode: AuditFailureMode::default(),
+        public_audit_retention_days: 0,
         master_key,
         email_hasher,
+        pii_minimization: false,
         success_page_url: "http://localhost:3000/success".to_string(),
         activation_rate_limiter: Arc::new(ActivationRateLimiter::default()),
         email_service: Arc::new(EmailService::new(None, "test@example.com".to_string())),
         jwks_cache: Arc::new(JwksCache::new()),
         trusted_issuers: vec![],
+        org_dbs: Arc::new(OrgDbRegistry::disabled()),
+        project_misses: Arc::new(ProjectMissCache::default()),
+        clock: Clock::system(),
+        provider_calls: Arc::new(ProviderCallGovernor::default()),
+        validation_limiter: Arc::new(ValidationRateLimiter::new()),
+        free_license_limiter: Arc::new(ActivationRateLimiter::for_free_licenses()),
+        session_wait_limiter: Arc::new(ActivationRateLimiter::for_session_waits()),
+        allow_localhost_urls: false,
+        maintenance: Arc::new(MaintenanceMode::default()),
+        backups: None,
     }
 }
 
 /// Create a Router with all public endpoints (without rate limiting for tests)
 pub fn public_app(state: AppState) -> Router {
     Router::new()
-        .route("/buy", post(initiate_buy))
+        .route("/buy", get(initiate_buy).post(initiate_buy))
+        .route("/buy/wait", get(wait_for_payment))
         .route("/callback", get(payment_callback))
         .route("/redeem", post(redeem_with_code))
+        .route("/redeem/claim", post(redeem_claim_code))
+        .route("/redeem/prepaid", post(redeem_prepaid_code))
+        .route("/redeem/lemonsqueezy", post(redeem_lemonsqueezy_key))
         .route("/activation/request-code", post(request_activation_code))
         .route("/validate", post(validate_license))
         .route("/license", get(get_license_info))
         .route("/devices/deactivate", post(deactivate_device))
+        .route("/refresh", post(refresh_token))
+        .route("/discovery", get(get_discovery))
+        .route("/.well-known/jwks.json", get(get_jwks))
+        .route("/products", get(get_catalog))
+        .route("/shared/license/{token}", get(view_shared_license))
         .with_state(state)
 }
 
+/// Configuration for the full application router: dev mode, rate limiting
+/// off (requests in tests have no peer address), and the status page on so
+/// every route is mounted.
+pub fn test_config() -> paycheck::config::Config {
+    let source = paycheck::config::ConfigSource::from_parts(
+        None,
+        [
+            ("PAYCHECK_ENV", "dev"),
+            ("PAYCHECK_STATUS_PAGE", "true"),
+            ("RATE_LIMIT_STRICT_RPM", "0"),
+            ("RATE_LIMIT_STANDARD_RPM", "0"),
+            ("RATE_LIMIT_RELAXED_RPM", "0"),
+            ("RATE_LIMIT_ORG_OPS_RPM", "0"),
+        ],
+    )
+    .unwrap();
+    paycheck::config::Config::from_source(&source).unwrap()
+}
+
+/// The complete production router (public, webhooks, operators, and orgs,
+/// with every layer `main` serves), without binding a socket.
+pub fn full_app(state: AppState) -> Router {
+    paycheck::handlers::app(state, &test_config())
+}
+
+const HTTP_METHODS: [&str; 7] = ["get", "post", "put", "patch", "delete", "head", "options"];
+
+/// Method and path of every `.route(...)` in a router's source, one entry per
+/// method for chains like `get(list).post(create)`.
+pub fn routes_in_source(source: &str) -> BTreeSet<(String, String)> {
+    let mut routes = BTreeSet::new();
+    for call in source.split(".route(").skip(1) {
+        let mut parts = call.splitn(3, '"');
+        parts.next();
+        let path = parts.next().expect("route path");
+        let mut rest = parts
+            .next()
+            .expect("route handler")
+            .trim_start_matches(',')
+            .trim_start();
+        while let Some(open) = rest.find('(') {
+            let method = rest[..open].trim();
+            if !HTTP_METHODS.contains(&method) {
+                break;
+            }
+            routes.insert((method.to_uppercase(), path.to_string()));
+
+            // Skip the handler, then carry on if another method is chained
+            let mut depth = 0;
+            let close = rest[open..]
+                .char_indices()
+                .find_map(|(i, c)| {
+                    match c {
+                        '(' => depth += 1,
+                        ')' => depth -= 1,
+                        _ => {}
+                    }
+                    (depth == 0).then_some(open + i)
+                })
+                .expect("unbalanced route handler");
+            match rest[close + 1..].trim_start().strip_prefix('.') {
+                Some(next) => rest = next.trim_start(),
+                None => break,
+            }
+        }
+    }
+    routes
+}
+
 /// Create a test payment session.
 /// Note: Device info is NOT stored in payment sessions - purchase ≠ activation.
 /// Redirect URL is configured per-project, not per-session.
@@ -294,6 +373,12 @@ pub fn create_test_payment_session(
     let input = CreatePaymentSession {
         product_id: product_id.to_string(),
         customer_id: customer_id.map(|s| s.to_string()),
+        upgrade_from_license_id: None,
+        upgrade_days_remaining: None,
+        upgrade_credit_cents: None,
+        checkout_fields: Default::default(),
+        accepted_terms_version: None,
+        metadata: Default::default(),
     };
     queries::create_payment_session(conn, &input).expect("Failed to create test payment session")
 }
@@ -301,7 +386,7 @@ pub fn create_test_payment_session(
 /// Mark a payment session as completed and associate it with a license
 pub fn complete_payment_session(conn: &Connection, session_id: &str, license_id: &str) {
     queries::try_claim_payment_session(conn, session_id).expect("Failed to claim payment session");
-    queries::set_payment_session_license(conn, session_id, license_id)
+    queries::set_payment_session_license(conn, session_id, license_id, None)
         .expect("Failed to set payment session license");
 }
 
@@ -312,11 +397,7 @@ pub fn setup_stripe_config(conn: &Connection, org_id: &str, master_key: &MasterK
         publishable_key: "pk_test_abc123xyz789".to_string(),
         webhook_secret: "whsec_test123secret456".to_string(),
     };
-    let config_json = serde_json::to_vec(&config).expect("Failed to serialize Stripe config");
-    let encrypted = master_key
-        .encrypt_private_key(org_id, &config_json)
-        .expect("Failed to encrypt Stripe config");
-    queries::upsert_org_service_config(conn, org_id, ServiceProvider::Stripe, &encrypted)
+    fixtures::set_service_config(conn, org_id, ServiceProvider::Stripe, &config, master_key)
         .expect("Failed to set Stripe config");
 }
 
@@ -326,13 +407,16 @@ pub fn setup_lemonsqueezy_config(conn: &Connection, org_id: &str, master_key: &M
         api_key: "ls_test_key_abcdefghij".to_string(),
         store_id: "store_123".to_string(),
         webhook_secret: "ls_whsec_test_secret".to_string(),
+        verify_imported_keys: false,
     };
-    let config_json = serde_json::to_vec(&config).expect("Failed to serialize LS config");
-    let encrypted = master_key
-        .encrypt_private_key(org_id, &config_json)
-        .expect("Failed to encrypt LS config");
-    queries::upsert_org_service_config(conn, org_id, ServiceProvider::LemonSqueezy, &encrypted)
-        .expect("Failed to set LemonSqueezy config");
+    fixtures::set_service_config(
+        conn,
+        org_id,
+        ServiceProvider::LemonSqueezy,
+        &config,
+        master_key,
+    )
+    .expect("Failed to set LemonSqueezy config");
 }
 
 /// Set up both Stripe and LemonSqueezy configs for an organization
@@ -350,8 +434,8 @@ pub fn create_test_license_with_subscription(
     provider: &str,
     subscription_id: &str,
 ) -> License {
+    let email_hash = test_email_hasher().hash(&parse_email("test@example.com"));
     let input = CreateLicense {
-        email_hash: Some(test_email_hasher().hash("test@example.com")),
         customer_id: Some("test-customer".to_string()),
         expires_at,
         updates_expires_at: expires_at,
@@ -359,6 +443,7 @@ pub fn create_test_license_with_subscription(
         payment_provider_customer_id: Some("cust_test".to_string()),
         payment_provider_subscription_id: Some(subscription_id.to_string()),
         payment_provider_order_id: Some("order_test".to_string()),
+        ..fixtures::license_input(Some(email_hash))
     };
     queries::create_license(conn, project_id, product_id, &input)
         .expect("Failed to create test license with subscription")
@@ -8,3 +8,9 @@ mod jwt;
 
 #[path = "crypto/key_rotation.rs"]
 mod key_rotation;
+
+#[path = "crypto/org_data_keys.rs"]
+mod org_data_keys;
+
+#[path = "crypto/startup_check.rs"]
+mod startup_check;
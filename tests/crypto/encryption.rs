@@ -1,4 +1,4 @@
-//! Crypto module tests (envelope encryption, secret hashing)
+//! Crypto module tests (envelope encryption, key wrapping, secret hashing)
 
 use base64::Engine;
 use base64::engine::general_purpose::STANDARD as BASE64;
@@ -104,6 +104,52 @@ fn test_wrong_project_id_fails() {
     );
 }
 
+#[test]
+fn test_wrap_unwrap_key_roundtrip() {
+    let master_key = MasterKey::from_base64(&MasterKey::generate()).unwrap();
+    let org_key = MasterKey::random();
+    let encrypted = org_key
+        .encrypt_private_key("project-1", &[42u8; MASTER_KEY_SIZE])
+        .unwrap();
+
+    let wrapped = master_key.wrap_key("org-1", &org_key).unwrap();
+    let unwrapped = master_key.unwrap_key("org-1", &wrapped).unwrap();
+
+    assert_eq!(
+        unwrapped
+            .decrypt_private_key("project-1", &encrypted)
+            .unwrap(),
+        [42u8; MASTER_KEY_SIZE],
+        "unwrapped key should decrypt what the original key encrypted"
+    );
+}
+
+#[test]
+fn test_unwrap_key_bound_to_org_and_master_key() {
+    let master_key = MasterKey::from_base64(&MasterKey::generate()).unwrap();
+    let wrapped = master_key.wrap_key("org-1", &MasterKey::random()).unwrap();
+
+    assert!(
+        master_key.unwrap_key("org-2", &wrapped).is_err(),
+        "a key wrapped for one org should not unwrap as another org's"
+    );
+    let other_master = MasterKey::from_base64(&MasterKey::generate()).unwrap();
+    assert!(
+        other_master.unwrap_key("org-1", &wrapped).is_err(),
+        "a wrapped key should only unwrap with the master key that wrapped it"
+    );
+}
+
+#[test]
+fn test_wrapped_key_is_not_a_private_key_ciphertext() {
+    // Wrapping uses its own context, so a wrapped key can't be passed off as
+    // an entity's ciphertext with the same ID
+    let master_key = MasterKey::from_base64(&MasterKey::generate()).unwrap();
+    let wrapped = master_key.wrap_key("org-1", &MasterKey::random()).unwrap();
+
+    assert!(master_key.decrypt_private_key("org-1", &wrapped).is_err());
+}
+
 #[test]
 fn test_hash_secret_produces_deterministic_hex_output() {
     let key = "PC-ABCD-1234-WXYZ-5678";
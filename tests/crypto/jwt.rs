@@ -16,8 +16,10 @@ fn create_test_claims() -> LicenseClaims {
         updates_exp: Some(now + SECONDS_PER_DAY * UPDATES_VALID_DAYS),
         tier: "pro".to_string(),
         features: vec!["export".to_string(), "api".to_string()],
+        entitlements: Default::default(),
         device_id: "device-123".to_string(),
         device_type: "uuid".to_string(),
+        license_ref: None,
         product_id: "product-abc".to_string(),
     }
 }
@@ -170,6 +172,46 @@ fn test_verify_truncated_token_fails() {
     );
 }
 
+#[test]
+fn test_sign_sets_kid_from_public_key() {
+    let (private_key, public_key) = jwt::generate_keypair();
+    let token = jwt::sign_claims(&create_test_claims(), &private_key, "sub", "aud", "jti").unwrap();
+
+    let header = jwt::decode_header(&token).expect("Header should decode");
+    assert_eq!(header.alg, "EdDSA");
+    assert_eq!(header.kid, Some(jwt::key_id(&public_key).unwrap()));
+
+    let (_, other_public_key) = jwt::generate_keypair();
+    assert_ne!(
+        jwt::key_id(&public_key).unwrap(),
+        jwt::key_id(&other_public_key).unwrap(),
+        "kid should differ per key"
+    );
+}
+
+#[test]
+fn test_verify_signature_ignores_issuer_but_not_key() {
+    let (private_key, public_key) = jwt::generate_keypair();
+    let token = jwt::sign_claims_with_issuer(
+        &create_test_claims(),
+        &private_key,
+        "sub",
+        "someone-else",
+        "aud",
+        "jti",
+    )
+    .unwrap();
+
+    assert!(
+        jwt::verify_token(&token, &public_key).is_err(),
+        "full verification checks the issuer"
+    );
+    assert!(jwt::verify_signature(&token, &public_key).is_ok());
+
+    let (_, other_public_key) = jwt::generate_keypair();
+    assert!(jwt::verify_signature(&token, &other_public_key).is_err());
+}
+
 // ============ Decode Unverified Tests ============
 
 #[test]
@@ -285,8 +327,10 @@ fn test_is_license_expired_future() {
         updates_exp: None,
         tier: "pro".to_string(),
         features: vec![],
+        entitlements: Default::default(),
         device_id: "".to_string(),
         device_type: "uuid".to_string(),
+        license_ref: None,
         product_id: "".to_string(),
     };
 
@@ -304,8 +348,10 @@ fn test_is_license_expired_past() {
         updates_exp: None,
         tier: "pro".to_string(),
         features: vec![],
+        entitlements: Default::default(),
         device_id: "".to_string(),
         device_type: "uuid".to_string(),
+        license_ref: None,
         product_id: "".to_string(),
     };
 
@@ -323,8 +369,10 @@ fn test_is_license_expired_perpetual() {
         updates_exp: None,
         tier: "pro".to_string(),
         features: vec![],
+        entitlements: Default::default(),
         device_id: "".to_string(),
         device_type: "uuid".to_string(),
+        license_ref: None,
         product_id: "".to_string(),
     };
 
@@ -342,8 +390,10 @@ fn test_covers_version_with_updates_exp() {
         updates_exp: Some(now + SECONDS_PER_DAY * ONE_DAY), // Updates expire tomorrow
         tier: "pro".to_string(),
         features: vec![],
+        entitlements: Default::default(),
         device_id: "".to_string(),
         device_type: "uuid".to_string(),
+        license_ref: None,
         product_id: "".to_string(),
     };
 
@@ -367,8 +417,10 @@ fn test_covers_version_perpetual_updates() {
         updates_exp: None, // Perpetual updates
         tier: "pro".to_string(),
         features: vec![],
+        entitlements: Default::default(),
         device_id: "".to_string(),
         device_type: "uuid".to_string(),
+        license_ref: None,
         product_id: "".to_string(),
     };
 
@@ -390,8 +442,10 @@ fn test_has_feature_returns_true_for_existing_feature() {
             "api".to_string(),
             "analytics".to_string(),
         ],
+        entitlements: Default::default(),
         device_id: "".to_string(),
         device_type: "uuid".to_string(),
+        license_ref: None,
         product_id: "".to_string(),
     };
 
@@ -410,8 +464,10 @@ fn test_has_feature_returns_false_for_empty_features() {
         updates_exp: None,
         tier: "free".to_string(),
         features: vec![],
+        entitlements: Default::default(),
         device_id: "".to_string(),
         device_type: "uuid".to_string(),
+        license_ref: None,
         product_id: "".to_string(),
     };
 
@@ -431,8 +487,10 @@ fn test_sign_with_unicode_claims() {
         updates_exp: None,
         tier: "プロ".to_string(), // Japanese
         features: vec!["日本語".to_string(), "한국어".to_string()], // Japanese and Korean
+        entitlements: Default::default(),
         device_id: "デバイス".to_string(),
         device_type: "uuid".to_string(),
+        license_ref: None,
         product_id: "商品".to_string(),
     };
 
@@ -462,8 +520,10 @@ fn test_sign_with_special_characters() {
             "feat:with:colons".to_string(),
             "feat/with/slashes".to_string(),
         ],
+        entitlements: Default::default(),
         device_id: "device<>&id".to_string(),
         device_type: "uuid".to_string(),
+        license_ref: None,
         product_id: "product@#$%".to_string(),
     };
 
@@ -490,8 +550,10 @@ fn test_sign_with_empty_features() {
         updates_exp: None,
         tier: "free".to_string(),
         features: vec![],
+        entitlements: Default::default(),
         device_id: "device".to_string(),
         device_type: "uuid".to_string(),
+        license_ref: None,
         product_id: "product".to_string(),
     };
 
@@ -516,8 +578,10 @@ fn test_sign_with_many_features() {
         updates_exp: None,
         tier: "enterprise".to_string(),
         features: features.clone(),
+        entitlements: Default::default(),
         device_id: "device".to_string(),
         device_type: "uuid".to_string(),
+        license_ref: None,
         product_id: "product".to_string(),
     };
 
@@ -3,8 +3,11 @@
 //! These tests verify that all encrypted data is properly re-encrypted
 //! when the master key is rotated.
 //!
-//! Note: Licenses are no longer encrypted, so only project private keys
-//! and organization payment configs need rotation.
+//! Note: Licenses are no longer encrypted, so only project private keys,
+//! email webhook secrets, pending prepaid codes and organization payment
+//! configs need rotation. Orgs with their own data key only need that key
+//! re-wrapped; the re-encryption tests below use orgs created before per-org
+//! keys, whose secrets are under the master key.
 
 #[path = "../common/mod.rs"]
 mod common;
@@ -19,6 +22,17 @@ const OLD_KEY_BYTES: [u8; 32] = [1u8; 32];
 /// Bytes for the "new" master key used after rotation
 const NEW_KEY_BYTES: [u8; 32] = [2u8; 32];
 
+/// An org without a data key, as created before per-org keys existed.
+fn create_legacy_org(conn: &Connection, name: &str) -> Organization {
+    let org = create_test_org(conn, name);
+    conn.execute(
+        "UPDATE organizations SET org_data_key = NULL WHERE id = ?1",
+        [&org.id],
+    )
+    .unwrap();
+    org
+}
+
 /// Simulate key rotation for a project's private key
 fn rotate_project_key(
     conn: &Connection,
@@ -45,6 +59,32 @@ fn rotate_project_key(
     queries::update_project_private_key(conn, &project.id, &new_ciphertext)
         .map_err(|e| format!("Failed to update: {}", e))?;
 
+    // The email webhook signing secret is under the same key
+    if let Some(ref encrypted) = project.email_webhook_secret {
+        let secret = old_key
+            .decrypt_webhook_secret(&project.id, encrypted)
+            .map_err(|e| format!("Failed to decrypt webhook secret: {}", e))?;
+        let new_ciphertext = new_key
+            .encrypt_webhook_secret(&project.id, &secret)
+            .map_err(|e| format!("Failed to encrypt webhook secret: {}", e))?;
+        queries::update_project_webhook_secret(conn, &project.id, &new_ciphertext)
+            .map_err(|e| format!("Failed to update webhook secret: {}", e))?;
+    }
+
+    // So are the codes of prepaid batches not downloaded yet
+    let pending = queries::list_pending_prepaid_codes(conn, &project.id)
+        .map_err(|e| format!("Failed to list prepaid codes: {}", e))?;
+    for (batch_id, encrypted) in &pending {
+        let plaintext = old_key
+            .decrypt_private_key(batch_id, encrypted)
+            .map_err(|e| format!("Failed to decrypt prepaid codes: {}", e))?;
+        let new_ciphertext = new_key
+            .encrypt_private_key(batch_id, &plaintext)
+            .map_err(|e| format!("Failed to encrypt prepaid codes: {}", e))?;
+        queries::update_pending_prepaid_codes(conn, batch_id, &new_ciphertext)
+            .map_err(|e| format!("Failed to update prepaid codes: {}", e))?;
+    }
+
     Ok(())
 }
 
@@ -90,7 +130,7 @@ fn test_project_private_key_reencrypts_with_new_master_key() {
     let new_key = MasterKey::from_bytes(NEW_KEY_BYTES);
 
     // Create org and project with old key
-    let org = create_test_org(&mut conn, "Test Org");
+    let org = create_legacy_org(&conn, "Test Org");
 
     // Create project - encryption happens internally with correct project ID
     let (private_key_bytes, public_key) = jwt::generate_keypair();
@@ -101,6 +141,23 @@ fn test_project_private_key_reencrypts_with_new_master_key() {
         email_from: None,
         email_enabled: true,
         email_webhook_url: None,
+        email_webhook_secret: None,
+        jwt_issuer: None,
+        jwt_audience: None,
+        upgrade_auto_discount: false,
+        upgrade_old_license: UpgradeOldLicense::Revoke,
+        allow_project_id_auth: true,
+        allow_link_checkout: false,
+        max_validations_per_hour_per_license: None,
+        statement_descriptor_suffix: None,
+        receipt_email_enabled: false,
+        checkout_message: None,
+        attestation_audiences: vec![],
+        duplicate_purchase_check: true,
+        converted_trial_action: ConvertedTrialAction::Keep,
+        require_terms_acceptance: false,
+        checkout_metadata_keys: vec![],
+        templates: vec![],
     };
     let project = queries::create_project(
         &conn,
@@ -147,6 +204,77 @@ fn test_project_private_key_reencrypts_with_new_master_key() {
     );
 }
 
+#[test]
+fn test_email_webhook_secret_reencrypts_with_new_master_key() {
+    let conn = setup_test_db();
+    let old_key = MasterKey::from_bytes(OLD_KEY_BYTES);
+    let new_key = MasterKey::from_bytes(NEW_KEY_BYTES);
+    let secret = "whsec_paycheck_0123456789abcdefghijklmnop";
+
+    let org = create_legacy_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "Test Project", &old_key);
+    let input: UpdateProject =
+        serde_json::from_value(serde_json::json!({ "email_webhook_secret": secret })).unwrap();
+    queries::update_project(&conn, &project.id, &input, &old_key)
+        .unwrap()
+        .unwrap();
+
+    rotate_project_key(&conn, &project.id, &old_key, &new_key).expect("Rotation should succeed");
+
+    let fetched = queries::get_project_by_id(&conn, &project.id)
+        .unwrap()
+        .unwrap();
+    assert!(
+        queries::decrypt_project_webhook_secret(&conn, &fetched, &old_key).is_err(),
+        "old key should fail to decrypt the webhook secret after rotation"
+    );
+    assert_eq!(
+        queries::decrypt_project_webhook_secret(&conn, &fetched, &new_key)
+            .expect("New key should decrypt")
+            .as_deref(),
+        Some(secret)
+    );
+}
+
+#[test]
+fn test_pending_prepaid_codes_reencrypt_with_new_master_key() {
+    let conn = setup_test_db();
+    let old_key = MasterKey::from_bytes(OLD_KEY_BYTES);
+    let new_key = MasterKey::from_bytes(NEW_KEY_BYTES);
+
+    let org = create_legacy_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "Test Project", &old_key);
+    let product = create_test_product(&conn, &project.id, "Boxed Edition", "pro");
+    let batch = queries::create_prepaid_code_batch(
+        &conn,
+        &old_key,
+        &project,
+        &product.id,
+        &CreatePrepaidCodes {
+            count: 3,
+            label: None,
+            expires_in_days: None,
+        },
+        None,
+    )
+    .unwrap();
+
+    rotate_project_key(&conn, &project.id, &old_key, &new_key).expect("Rotation should succeed");
+
+    let (_, encrypted) = queries::list_pending_prepaid_codes(&conn, &project.id)
+        .unwrap()
+        .pop()
+        .unwrap();
+    assert!(
+        old_key.decrypt_private_key(&batch.id, &encrypted).is_err(),
+        "old key should fail to decrypt pending prepaid codes after rotation"
+    );
+    let csv = queries::take_prepaid_codes(&conn, &new_key, &org.id, &batch.id)
+        .expect("New key should decrypt")
+        .unwrap();
+    assert_eq!(String::from_utf8(csv).unwrap().lines().count(), 4);
+}
+
 // ============ Organization Payment Config Rotation ============
 
 #[test]
@@ -156,7 +284,7 @@ fn test_org_stripe_config_reencrypts_with_new_master_key() {
     let new_key = MasterKey::from_bytes(NEW_KEY_BYTES);
 
     // Create org
-    let org = create_test_org(&mut conn, "Test Org");
+    let org = create_legacy_org(&conn, "Test Org");
 
     // Set up Stripe config with old key
     let stripe_config = r#"{"secret_key":"sk_test_123","publishable_key":"pk_test_123","webhook_secret":"whsec_123"}"#;
@@ -212,7 +340,7 @@ fn test_org_lemonsqueezy_config_reencrypts_with_new_master_key() {
     let new_key = MasterKey::from_bytes(NEW_KEY_BYTES);
 
     // Create org
-    let org = create_test_org(&mut conn, "Test Org");
+    let org = create_legacy_org(&conn, "Test Org");
 
     // Set up LemonSqueezy config with old key
     let ls_config = r#"{"api_key":"ls_test_123","store_id":"12345","webhook_secret":"lswhsec_123"}"#;
@@ -261,6 +389,78 @@ fn test_org_lemonsqueezy_config_reencrypts_with_new_master_key() {
     );
 }
 
+// ============ Org Data Key Re-wrapping ============
+
+#[test]
+fn test_rotation_rewraps_org_data_key_without_touching_secrets() {
+    let conn = setup_test_db();
+    let old_key = MasterKey::from_bytes(OLD_KEY_BYTES);
+    let new_key = MasterKey::from_bytes(NEW_KEY_BYTES);
+
+    let org = queries::create_organization(
+        &conn,
+        &CreateOrganization {
+            name: "Test Org".to_string(),
+            owner_user_id: None,
+        },
+        &old_key,
+    )
+    .unwrap();
+    let project = create_test_project(&conn, &org.id, "Test Project", &old_key);
+    let signing_key = queries::decrypt_project_private_key(&conn, &project, &old_key).unwrap();
+    let stripe = StripeConfig {
+        secret_key: "sk_test_123".to_string(),
+        publishable_key: "pk_test_123".to_string(),
+        webhook_secret: "whsec_123".to_string(),
+    };
+    fixtures::set_service_config(&conn, &org.id, ServiceProvider::Stripe, &stripe, &old_key)
+        .unwrap();
+    let config_before = queries::get_org_service_config(&conn, &org.id, ServiceProvider::Stripe)
+        .unwrap()
+        .unwrap();
+
+    let rewrapped = queries::rewrap_org_data_keys(&conn, &old_key, &new_key).unwrap();
+    assert_eq!(rewrapped, vec![org.id.clone()]);
+
+    // The blobs under the org key are untouched
+    let fetched = queries::get_project_by_id(&conn, &project.id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(fetched.private_key, project.private_key);
+    let config_after = queries::get_org_service_config(&conn, &org.id, ServiceProvider::Stripe)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        config_after.config_encrypted,
+        config_before.config_encrypted
+    );
+
+    // ...and readable through the new master key only
+    assert_eq!(
+        queries::decrypt_project_private_key(&conn, &fetched, &new_key).unwrap(),
+        signing_key
+    );
+    assert!(queries::decrypt_project_private_key(&conn, &fetched, &old_key).is_err());
+    let config = queries::get_org_stripe_config(&conn, &org.id, &new_key)
+        .unwrap()
+        .unwrap();
+    assert_eq!(config.secret_key, "sk_test_123");
+}
+
+#[test]
+fn test_rewrap_skips_orgs_without_data_key() {
+    let conn = setup_test_db();
+    let old_key = MasterKey::from_bytes(OLD_KEY_BYTES);
+    let new_key = MasterKey::from_bytes(NEW_KEY_BYTES);
+    create_legacy_org(&conn, "Legacy Org");
+
+    let rewrapped = queries::rewrap_org_data_keys(&conn, &old_key, &new_key).unwrap();
+    assert!(
+        rewrapped.is_empty(),
+        "legacy orgs are re-encrypted, not re-wrapped"
+    );
+}
+
 // ============ Email HMAC Key Rotation (Issue 15 from security audit) ============
 
 /// Simulate rotation of the email HMAC key (stored in system_config).
@@ -316,7 +516,7 @@ fn test_email_hash_survives_master_key_rotation() {
     // Create email hasher and hash a test email
     let hasher_before = EmailHasher::from_bytes(hmac_key);
     let test_email = "customer@example.com";
-    let hash_before = hasher_before.hash(test_email);
+    let hash_before = hasher_before.hash(&parse_email(test_email));
 
     // Perform master key rotation
     rotate_email_hmac_key(&mut conn, &old_key, &new_key).expect("Rotation should succeed");
@@ -342,7 +542,7 @@ fn test_email_hash_survives_master_key_rotation() {
         .try_into()
         .expect("HMAC key should be 32 bytes");
     let hasher_after = EmailHasher::from_bytes(hmac_key_array);
-    let hash_after = hasher_after.hash(test_email);
+    let hash_after = hasher_after.hash(&parse_email(test_email));
 
     assert_eq!(
         hash_before, hash_after,
@@ -0,0 +1,234 @@
+//! Tests for per-org data keys: each org's secrets are encrypted under its own
+//! key, so one org's key can't read another's, and orgs created before the
+//! keys existed are moved onto one by `--migrate-org-keys`.
+
+#[path = "../common/mod.rs"]
+mod common;
+
+use common::*;
+use rusqlite::Connection;
+
+fn stripe_config() -> StripeConfig {
+    StripeConfig {
+        secret_key: "sk_test_123".to_string(),
+        publishable_key: "pk_test_123".to_string(),
+        webhook_secret: "whsec_123".to_string(),
+    }
+}
+
+/// An org without a data key, with a project and Stripe config encrypted
+/// under the master key as they were before per-org keys.
+fn legacy_org_with_secrets(conn: &Connection) -> (Organization, Project) {
+    let org = create_test_org(conn, "Legacy Org");
+    conn.execute(
+        "UPDATE organizations SET org_data_key = NULL WHERE id = ?1",
+        [&org.id],
+    )
+    .unwrap();
+    let project = create_test_project(conn, &org.id, "Legacy Project", &test_master_key());
+    fixtures::set_service_config(
+        conn,
+        &org.id,
+        ServiceProvider::Stripe,
+        &stripe_config(),
+        &test_master_key(),
+    )
+    .unwrap();
+    (org, project)
+}
+
+fn reload(conn: &Connection, project: &Project) -> Project {
+    queries::get_project_by_id(conn, &project.id)
+        .unwrap()
+        .unwrap()
+}
+
+#[test]
+fn test_new_org_secrets_not_readable_with_master_key_alone() {
+    let conn = setup_test_db();
+    let master_key = test_master_key();
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "Test Project", &master_key);
+
+    assert!(
+        master_key
+            .decrypt_private_key(&project.id, &project.private_key)
+            .is_err(),
+        "project keys are encrypted under the org's data key"
+    );
+    assert!(queries::decrypt_project_private_key(&conn, &project, &master_key).is_ok());
+}
+
+#[test]
+fn test_org_key_cannot_decrypt_other_orgs_secrets() {
+    let conn = setup_test_db();
+    let master_key = test_master_key();
+    let org_a = create_test_org(&conn, "Org A");
+    let org_b = create_test_org(&conn, "Org B");
+    let project_b = create_test_project(&conn, &org_b.id, "Project B", &master_key);
+    fixtures::set_service_config(
+        &conn,
+        &org_b.id,
+        ServiceProvider::Stripe,
+        &stripe_config(),
+        &master_key,
+    )
+    .unwrap();
+
+    let key_a = queries::org_data_key(&conn, &org_a.id, &master_key).unwrap();
+    assert!(
+        key_a
+            .decrypt_private_key(&project_b.id, &project_b.private_key)
+            .is_err(),
+        "org A's data key should not decrypt org B's project key"
+    );
+    let config_b = queries::get_org_service_config(&conn, &org_b.id, ServiceProvider::Stripe)
+        .unwrap()
+        .unwrap();
+    assert!(
+        config_b.decrypt_stripe_config(&key_a).is_err(),
+        "org A's data key should not decrypt org B's Stripe config"
+    );
+
+    // A project claiming to belong to org A still doesn't decrypt
+    let mut moved = project_b.clone();
+    moved.org_id = org_a.id.clone();
+    assert!(queries::decrypt_project_private_key(&conn, &moved, &master_key).is_err());
+}
+
+#[test]
+fn test_migrate_moves_legacy_secrets_to_org_key() {
+    let conn = setup_test_db();
+    let master_key = test_master_key();
+    let (org, project) = legacy_org_with_secrets(&conn);
+    let signing_key = master_key
+        .decrypt_private_key(&project.id, &project.private_key)
+        .unwrap();
+
+    assert!(queries::migrate_org_data_key(&conn, &conn, &org.id, &master_key).unwrap());
+
+    let migrated = reload(&conn, &project);
+    assert!(
+        master_key
+            .decrypt_private_key(&project.id, &migrated.private_key)
+            .is_err(),
+        "the project key should be re-encrypted under the org's data key"
+    );
+    assert_eq!(
+        queries::decrypt_project_private_key(&conn, &migrated, &master_key).unwrap(),
+        signing_key
+    );
+    let config = queries::get_org_stripe_config(&conn, &org.id, &master_key)
+        .unwrap()
+        .unwrap();
+    assert_eq!(config.secret_key, "sk_test_123");
+    assert!(
+        queries::list_orgs_without_data_key(&conn)
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn test_migrate_is_idempotent() {
+    let conn = setup_test_db();
+    let master_key = test_master_key();
+    let (org, project) = legacy_org_with_secrets(&conn);
+    let keyed = create_test_org(&conn, "Keyed Org");
+
+    assert!(queries::migrate_org_data_key(&conn, &conn, &org.id, &master_key).unwrap());
+    let migrated = reload(&conn, &project);
+    let wrapped = queries::list_org_data_keys(&conn).unwrap();
+
+    // Running again changes nothing, for migrated and new orgs alike
+    assert!(!queries::migrate_org_data_key(&conn, &conn, &org.id, &master_key).unwrap());
+    assert!(!queries::migrate_org_data_key(&conn, &conn, &keyed.id, &master_key).unwrap());
+    assert_eq!(reload(&conn, &project).private_key, migrated.private_key);
+    assert_eq!(queries::list_org_data_keys(&conn).unwrap(), wrapped);
+    assert!(queries::decrypt_project_private_key(&conn, &migrated, &master_key).is_ok());
+}
+
+fn create_prepaid_batch(conn: &Connection, project: &Project) -> PrepaidCodeBatch {
+    let product = create_test_product(conn, &project.id, "Boxed Edition", "pro");
+    queries::create_prepaid_code_batch(
+        conn,
+        &test_master_key(),
+        project,
+        &product.id,
+        &CreatePrepaidCodes {
+            count: 2,
+            label: None,
+            expires_in_days: None,
+        },
+        None,
+    )
+    .unwrap()
+}
+
+fn pending_prepaid_codes(conn: &Connection, batch: &PrepaidCodeBatch) -> Vec<u8> {
+    conn.query_row(
+        "SELECT pending_codes FROM prepaid_code_batches WHERE id = ?1",
+        [&batch.id],
+        |row| row.get(0),
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_prepaid_codes_encrypted_under_org_key() {
+    let conn = setup_test_db();
+    let master_key = test_master_key();
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "Test Project", &master_key);
+    let batch = create_prepaid_batch(&conn, &project);
+
+    assert!(
+        master_key
+            .decrypt_private_key(&batch.id, &pending_prepaid_codes(&conn, &batch))
+            .is_err(),
+        "pending prepaid codes are encrypted under the org's data key"
+    );
+    let csv = queries::take_prepaid_codes(&conn, &master_key, &org.id, &batch.id)
+        .unwrap()
+        .unwrap();
+    assert!(String::from_utf8(csv).unwrap().starts_with("serial,code\n"));
+}
+
+#[test]
+fn test_migrate_moves_pending_prepaid_codes_to_org_key() {
+    let conn = setup_test_db();
+    let master_key = test_master_key();
+    let (org, project) = legacy_org_with_secrets(&conn);
+    let batch = create_prepaid_batch(&conn, &project);
+    let csv = master_key
+        .decrypt_private_key(&batch.id, &pending_prepaid_codes(&conn, &batch))
+        .unwrap();
+
+    assert!(queries::migrate_org_data_key(&conn, &conn, &org.id, &master_key).unwrap());
+
+    assert!(
+        master_key
+            .decrypt_private_key(&batch.id, &pending_prepaid_codes(&conn, &batch))
+            .is_err(),
+        "pending prepaid codes should be re-encrypted under the org's data key"
+    );
+    assert_eq!(
+        queries::take_prepaid_codes(&conn, &master_key, &org.id, &batch.id)
+            .unwrap()
+            .unwrap(),
+        csv
+    );
+}
+
+#[test]
+fn test_legacy_org_secrets_readable_before_migration() {
+    let conn = setup_test_db();
+    let (org, project) = legacy_org_with_secrets(&conn);
+
+    assert!(queries::decrypt_project_private_key(&conn, &project, &test_master_key()).is_ok());
+    assert!(
+        queries::get_org_stripe_config(&conn, &org.id, &test_master_key())
+            .unwrap()
+            .is_some()
+    );
+}
@@ -0,0 +1,202 @@
+//! Tests for the startup self-check: the master key sentinel stored at first
+//! boot catches a wrong key, and configuration problems fail the check.
+
+#[path = "../common/mod.rs"]
+mod common;
+
+use common::*;
+
+use paycheck::db::migrations::{self, MigrationTarget};
+use paycheck::startup::{self, KEY_SENTINEL_CONFIG_KEY, KeySentinel};
+use paycheck::storage_health;
+
+fn other_key() -> MasterKey {
+    MasterKey::from_bytes([7u8; 32])
+}
+
+/// Test state with both databases at the current schema version, as after
+/// the startup migrations.
+fn migrated_state() -> AppState {
+    let state = create_test_app_state();
+    for (pool, target) in [
+        (&state.db, MigrationTarget::Main),
+        (&state.audit, MigrationTarget::Audit),
+    ] {
+        pool.get()
+            .unwrap()
+            .pragma_update(None, "user_version", migrations::latest_version(target))
+            .unwrap();
+    }
+    state
+}
+
+#[test]
+fn test_sentinel_created_on_first_boot() {
+    let state = create_test_app_state();
+    let conn = state.db.get().unwrap();
+    assert!(
+        queries::get_system_config(&conn, KEY_SENTINEL_CONFIG_KEY)
+            .unwrap()
+            .is_none()
+    );
+
+    assert_eq!(
+        startup::check_master_key(&conn, &test_master_key()),
+        Ok(KeySentinel::Created)
+    );
+    assert!(
+        queries::get_system_config(&conn, KEY_SENTINEL_CONFIG_KEY)
+            .unwrap()
+            .is_some()
+    );
+    assert_eq!(
+        startup::check_master_key(&conn, &test_master_key()),
+        Ok(KeySentinel::Verified)
+    );
+}
+
+#[test]
+fn test_wrong_master_key_detected() {
+    let state = create_test_app_state();
+    let conn = state.db.get().unwrap();
+    startup::check_master_key(&conn, &test_master_key()).unwrap();
+
+    let error = startup::check_master_key(&conn, &other_key()).unwrap_err();
+    assert!(error.contains("PAYCHECK_MASTER_KEY_FILE"), "{}", error);
+}
+
+#[test]
+fn test_wrong_key_not_recorded_on_database_from_before_sentinels() {
+    let state = create_test_app_state();
+    let conn = state.db.get().unwrap();
+    let hmac_key = test_master_key()
+        .encrypt_private_key("system-config", &EmailHasher::generate_key())
+        .unwrap();
+    queries::set_system_config(&conn, EmailHasher::CONFIG_KEY, &hmac_key).unwrap();
+
+    assert!(startup::check_master_key(&conn, &other_key()).is_err());
+    assert!(
+        queries::get_system_config(&conn, KEY_SENTINEL_CONFIG_KEY)
+            .unwrap()
+            .is_none()
+    );
+    assert_eq!(
+        startup::check_master_key(&conn, &test_master_key()),
+        Ok(KeySentinel::Created)
+    );
+}
+
+#[test]
+fn test_rotated_sentinel_follows_new_key() {
+    let state = create_test_app_state();
+    let conn = state.db.get().unwrap();
+    startup::check_master_key(&conn, &test_master_key()).unwrap();
+
+    startup::store_key_sentinel(&conn, &other_key()).unwrap();
+    assert_eq!(
+        startup::check_master_key(&conn, &other_key()),
+        Ok(KeySentinel::Verified)
+    );
+    assert!(startup::check_master_key(&conn, &test_master_key()).is_err());
+}
+
+#[tokio::test]
+async fn test_self_check_passes_with_warnings() {
+    let state = migrated_state();
+    let conn = state.db.get().unwrap();
+    let org = create_test_org(&conn, "Test Org");
+    create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    drop(conn);
+
+    let report = startup::self_check(&state).await;
+    assert!(report.passed(), "{:?}", report.failures);
+    // No system Resend key, and audit logging is off in tests
+    assert_eq!(report.warnings.len(), 2, "{:?}", report.warnings);
+}
+
+#[tokio::test]
+async fn test_self_check_fails_on_wrong_master_key() {
+    let mut state = migrated_state();
+    startup::check_master_key(&state.db.get().unwrap(), &test_master_key()).unwrap();
+
+    state.master_key = other_key();
+    let report = startup::self_check(&state).await;
+    assert!(!report.passed());
+    assert!(
+        report.failures[0].contains("master key"),
+        "{:?}",
+        report.failures
+    );
+}
+
+#[tokio::test]
+async fn test_self_check_fails_on_undecryptable_project_key() {
+    let state = migrated_state();
+    let conn = state.db.get().unwrap();
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    conn.execute(
+        "UPDATE projects SET private_key = ?1 WHERE id = ?2",
+        rusqlite::params![vec![0u8; 64], project.id],
+    )
+    .unwrap();
+    drop(conn);
+
+    let report = startup::self_check(&state).await;
+    assert!(
+        report.failures.iter().any(|f| f.contains(&project.id)),
+        "{:?}",
+        report.failures
+    );
+}
+
+#[tokio::test]
+async fn test_self_check_fails_on_base_url_without_scheme() {
+    let mut state = migrated_state();
+    state.base_url = "paycheck.example.com".to_string();
+    let report = startup::self_check(&state).await;
+    assert!(
+        report.failures.iter().any(|f| f.contains("BASE_URL")),
+        "{:?}",
+        report.failures
+    );
+}
+
+#[tokio::test]
+async fn test_self_check_fails_on_schema_version_mismatch() {
+    let state = migrated_state();
+    let newer = migrations::latest_version(MigrationTarget::Audit) + 1;
+    state
+        .audit
+        .get()
+        .unwrap()
+        .pragma_update(None, "user_version", newer)
+        .unwrap();
+
+    let report = startup::self_check(&state).await;
+    assert!(
+        report.failures.iter().any(|f| f.contains("audit database")),
+        "{:?}",
+        report.failures
+    );
+}
+
+#[tokio::test]
+async fn test_self_check_warns_on_replaced_audit_database() {
+    let mut state = migrated_state();
+    storage_health::record_server_start(&state).unwrap();
+    assert_eq!(startup::self_check(&state).await.warnings.len(), 2);
+
+    let fresh = migrated_state();
+    state.audit = fresh.audit;
+    let report = startup::self_check(&state).await;
+    assert!(report.passed(), "{:?}", report.failures);
+    assert!(
+        report
+            .warnings
+            .iter()
+            .any(|w| w.contains("AUDIT_DATABASE_PATH")),
+        "{:?}",
+        report.warnings
+    );
+}
@@ -17,3 +17,21 @@ mod from_row_panic;
 
 #[path = "db/api_key_atomicity.rs"]
 mod api_key_atomicity;
+
+#[path = "db/pii_minimization.rs"]
+mod pii_minimization;
+
+#[path = "db/pagination.rs"]
+mod pagination;
+
+#[path = "db/audit_outbox.rs"]
+mod audit_outbox;
+
+#[path = "db/audit_chain.rs"]
+mod audit_chain;
+
+#[path = "db/demo_seed.rs"]
+mod demo_seed;
+
+#[path = "db/query_paths.rs"]
+mod query_paths;
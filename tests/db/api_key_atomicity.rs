@@ -68,9 +68,13 @@ fn test_api_key_scopes_become_orphaned_after_membership_deletion() {
     );
 
     // Verify the user is no longer a member of the org
-    let is_still_member =
-        queries::get_org_member_with_user_by_user_and_org(&mut conn, &user.id, &org.id)
-            .expect("Query failed");
+    let is_still_member = queries::get_org_member_with_user_by_user_and_org(
+        &mut conn,
+        &user.id,
+        &org.id,
+        &test_email_column(),
+    )
+    .expect("Query failed");
     assert!(
         is_still_member.is_none(),
         "user should not be a member of the org anymore"
@@ -291,8 +295,13 @@ fn test_documents_toctou_window_in_api_key_creation() {
     let scopes = queries::get_api_key_scopes(&mut conn, &api_key.id).expect("Get scopes failed");
     assert_eq!(scopes.len(), 1, "scope still exists");
 
-    let membership = queries::get_org_member_with_user_by_user_and_org(&mut conn, &user.id, &org.id)
-        .expect("Query failed");
+    let membership = queries::get_org_member_with_user_by_user_and_org(
+        &mut conn,
+        &user.id,
+        &org.id,
+        &test_email_column(),
+    )
+    .expect("Query failed");
     assert!(membership.is_none(), "but membership is gone");
 
     // This inconsistent state is what the TOCTOU vulnerability allows.
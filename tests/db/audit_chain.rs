@@ -0,0 +1,464 @@
+//! Tests for audit log hash chaining: every insert links to the one before it,
+//! and edits, deletions, and purges are told apart by the verifier.
+
+#[path = "../common/mod.rs"]
+mod common;
+
+use common::*;
+use paycheck::db::audit_chain::{self, GENESIS_HASH, INTERNAL_CHAIN, PUBLIC_CHAIN};
+use paycheck::util::AuditLogBuilder;
+use rusqlite::{Connection, params};
+
+const DAY: i64 = 86400;
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+/// Save an entry through the normal insert path.
+fn log(conn: &Connection, actor_type: ActorType, resource_id: &str) -> AuditLog {
+    AuditLogBuilder::new(conn, true, &Default::default())
+        .actor(actor_type, None)
+        .action(AuditAction::ActivateDevice)
+        .resource("device", resource_id)
+        .save()
+        .unwrap()
+}
+
+/// Save an entry with a chosen timestamp.
+fn log_at(conn: &Connection, actor_type: ActorType, resource_id: &str, timestamp: i64) {
+    let mut entry = AuditLogBuilder::new(conn, true, &Default::default())
+        .actor(actor_type, None)
+        .action(AuditAction::ActivateDevice)
+        .resource("device", resource_id)
+        .entry()
+        .unwrap();
+    entry.timestamp = timestamp;
+    queries::insert_audit_log(conn, &entry).unwrap();
+}
+
+fn chain_row(conn: &Connection, id: &str) -> (String, i64, String, String) {
+    conn.query_row(
+        "SELECT chain, chain_seq, prev_hash, row_hash FROM audit_logs WHERE id = ?1",
+        [id],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+    )
+    .unwrap()
+}
+
+fn report<'a>(
+    verification: &'a audit_chain::ChainVerification,
+    chain: &str,
+) -> &'a audit_chain::ChainReport {
+    verification
+        .chains
+        .iter()
+        .find(|c| c.chain == chain)
+        .unwrap()
+}
+
+#[test]
+fn test_entries_link_to_the_previous_entry_in_their_chain() {
+    let conn = setup_test_audit_db();
+    let first = log(&conn, ActorType::User, "d1");
+    let public = log(&conn, ActorType::Public, "d2");
+    let second = log(&conn, ActorType::System, "d3");
+
+    let (chain, seq, prev, first_hash) = chain_row(&conn, &first.id);
+    assert_eq!(
+        (chain.as_str(), seq, prev.as_str()),
+        (INTERNAL_CHAIN, 1, GENESIS_HASH)
+    );
+
+    let (chain, seq, prev, _) = chain_row(&conn, &public.id);
+    assert_eq!(
+        (chain.as_str(), seq, prev.as_str()),
+        (PUBLIC_CHAIN, 1, GENESIS_HASH)
+    );
+
+    let (chain, seq, prev, second_hash) = chain_row(&conn, &second.id);
+    assert_eq!((chain.as_str(), seq), (INTERNAL_CHAIN, 2));
+    assert_eq!(prev, first_hash);
+
+    let verification = audit_chain::verify(&conn, None, None).unwrap();
+    assert!(verification.valid);
+    let internal = report(&verification, INTERNAL_CHAIN);
+    assert_eq!(internal.checked, 2);
+    assert_eq!(
+        (internal.head_seq, internal.head_hash.as_str()),
+        (2, second_hash.as_str())
+    );
+    assert_eq!(report(&verification, PUBLIC_CHAIN).checked, 1);
+}
+
+#[test]
+fn test_duplicate_id_is_not_chained_twice() {
+    let conn = setup_test_audit_db();
+    let entry = log(&conn, ActorType::User, "d1");
+
+    assert!(!queries::insert_audit_log(&conn, &entry).unwrap());
+
+    let verification = audit_chain::verify(&conn, None, None).unwrap();
+    assert!(verification.valid);
+    assert_eq!(report(&verification, INTERNAL_CHAIN).head_seq, 1);
+}
+
+#[test]
+fn test_edited_entry_breaks_the_chain() {
+    let conn = setup_test_audit_db();
+    log(&conn, ActorType::User, "d1");
+    let tampered = log(&conn, ActorType::User, "d2");
+    log(&conn, ActorType::User, "d3");
+
+    conn.execute(
+        "UPDATE audit_logs SET resource_id = 'someone-else' WHERE id = ?1",
+        [&tampered.id],
+    )
+    .unwrap();
+
+    let verification = audit_chain::verify(&conn, None, None).unwrap();
+    assert!(!verification.valid);
+    let internal = report(&verification, INTERNAL_CHAIN);
+    assert!(!internal.valid);
+    assert_eq!(internal.checked, 1);
+    let first_break = internal.first_break.as_ref().unwrap();
+    assert_eq!(first_break.chain_seq, 2);
+    assert_eq!(first_break.entry_id.as_deref(), Some(tampered.id.as_str()));
+    assert_eq!(first_break.reason, "Entry does not match its row_hash");
+    // The other chain is unaffected
+    assert!(report(&verification, PUBLIC_CHAIN).valid);
+}
+
+#[test]
+fn test_edited_entry_with_recomputed_hash_breaks_the_next_link() {
+    let conn = setup_test_audit_db();
+    log(&conn, ActorType::User, "d1");
+    let tampered = log(&conn, ActorType::User, "d2");
+    let next = log(&conn, ActorType::User, "d3");
+
+    // Someone who knows the scheme rewrites the entry and its own hash
+    conn.execute(
+        "UPDATE audit_logs SET resource_id = 'someone-else' WHERE id = ?1",
+        [&tampered.id],
+    )
+    .unwrap();
+    let (_, _, prev_hash, _) = chain_row(&conn, &tampered.id);
+    let values: Vec<rusqlite::types::Value> = conn
+        .query_row(
+            "SELECT chain, chain_seq, id, timestamp, actor_type, user_id, user_email, user_name, action, resource_type, resource_id, resource_name, resource_email, details, org_id, org_name, project_id, project_name, ip_address, user_agent, auth_type, auth_credential, request_id
+             FROM audit_logs WHERE id = ?1",
+            [&tampered.id],
+            |row| (0..23).map(|i| row.get(i)).collect(),
+        )
+        .unwrap();
+    conn.execute(
+        "UPDATE audit_logs SET row_hash = ?1 WHERE id = ?2",
+        params![audit_chain::row_hash(&prev_hash, &values), &tampered.id],
+    )
+    .unwrap();
+
+    let verification = audit_chain::verify(&conn, None, None).unwrap();
+    let first_break = report(&verification, INTERNAL_CHAIN)
+        .first_break
+        .as_ref()
+        .unwrap();
+    assert_eq!(first_break.chain_seq, 3);
+    assert_eq!(first_break.entry_id.as_deref(), Some(next.id.as_str()));
+    assert_eq!(
+        first_break.reason,
+        "prev_hash does not match the previous entry"
+    );
+}
+
+#[test]
+fn test_deleted_entry_breaks_the_chain() {
+    let conn = setup_test_audit_db();
+    log(&conn, ActorType::User, "d1");
+    let deleted = log(&conn, ActorType::User, "d2");
+    log(&conn, ActorType::User, "d3");
+
+    conn.execute("DELETE FROM audit_logs WHERE id = ?1", [&deleted.id])
+        .unwrap();
+
+    let verification = audit_chain::verify(&conn, None, None).unwrap();
+    let first_break = report(&verification, INTERNAL_CHAIN)
+        .first_break
+        .as_ref()
+        .unwrap();
+    assert_eq!(first_break.chain_seq, 2);
+    assert_eq!(first_break.entry_id, None);
+    assert_eq!(first_break.reason, "Entry is missing");
+}
+
+#[test]
+fn test_deleted_oldest_entry_without_anchor_breaks_the_chain() {
+    let conn = setup_test_audit_db();
+    let oldest = log(&conn, ActorType::User, "d1");
+    log(&conn, ActorType::User, "d2");
+
+    conn.execute("DELETE FROM audit_logs WHERE id = ?1", [&oldest.id])
+        .unwrap();
+
+    let verification = audit_chain::verify(&conn, None, None).unwrap();
+    let first_break = report(&verification, INTERNAL_CHAIN)
+        .first_break
+        .as_ref()
+        .unwrap();
+    assert_eq!(first_break.chain_seq, 1);
+    assert_eq!(
+        first_break.reason,
+        "Entry is missing and the chain has no anchor for it"
+    );
+}
+
+#[test]
+fn test_purge_anchors_the_public_chain() {
+    let conn = setup_test_audit_db();
+    log_at(&conn, ActorType::Public, "old-1", now() - 100 * DAY);
+    log_at(&conn, ActorType::Public, "old-2", now() - 90 * DAY);
+    log_at(&conn, ActorType::User, "internal", now() - 90 * DAY);
+    log_at(&conn, ActorType::Public, "recent", now() - DAY);
+
+    let deleted = queries::purge_old_audit_logs(&conn, 30, &[]).unwrap();
+    assert_eq!(deleted, 2);
+
+    let anchor_seq: i64 = conn
+        .query_row(
+            "SELECT chain_seq FROM audit_chain_anchors WHERE chain = ?1",
+            [PUBLIC_CHAIN],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(anchor_seq, 2);
+
+    let verification = audit_chain::verify(&conn, None, None).unwrap();
+    assert!(verification.valid);
+    assert_eq!(report(&verification, PUBLIC_CHAIN).checked, 1);
+    assert_eq!(report(&verification, INTERNAL_CHAIN).checked, 1);
+
+    // New entries keep extending the chain after a purge
+    log(&conn, ActorType::Public, "after-purge");
+    let verification = audit_chain::verify(&conn, None, None).unwrap();
+    assert!(verification.valid);
+    assert_eq!(report(&verification, PUBLIC_CHAIN).head_seq, 4);
+}
+
+#[test]
+fn test_purge_of_whole_chain_links_next_entry_to_anchor() {
+    let conn = setup_test_audit_db();
+    log_at(&conn, ActorType::Public, "old-1", now() - 100 * DAY);
+    log_at(&conn, ActorType::Public, "old-2", now() - 90 * DAY);
+    let (_, _, _, last_hash) = conn
+        .query_row(
+            "SELECT id FROM audit_logs WHERE resource_id = 'old-2'",
+            [],
+            |row| row.get::<_, String>(0),
+        )
+        .map(|id| chain_row(&conn, &id))
+        .unwrap();
+
+    assert_eq!(queries::purge_old_audit_logs(&conn, 30, &[]).unwrap(), 2);
+    let verification = audit_chain::verify(&conn, None, None).unwrap();
+    assert!(verification.valid);
+    assert_eq!(report(&verification, PUBLIC_CHAIN).head_seq, 2);
+
+    let next = log(&conn, ActorType::Public, "after-purge");
+    let (_, seq, prev, _) = chain_row(&conn, &next.id);
+    assert_eq!((seq, prev), (3, last_hash));
+    assert!(audit_chain::verify(&conn, None, None).unwrap().valid);
+}
+
+#[test]
+fn test_purge_removes_old_entries_appended_after_newer_ones() {
+    let conn = setup_test_audit_db();
+    log_at(&conn, ActorType::Public, "old-1", now() - 100 * DAY);
+    log_at(&conn, ActorType::Public, "recent", now() - DAY);
+    // Relayed late, so it sits after a recent entry in the chain
+    log_at(&conn, ActorType::Public, "old-2", now() - 90 * DAY);
+
+    assert_eq!(queries::purge_old_audit_logs(&conn, 30, &[]).unwrap(), 2);
+    let verification = audit_chain::verify(&conn, None, None).unwrap();
+    assert!(verification.valid);
+    assert_eq!(report(&verification, PUBLIC_CHAIN).checked, 1);
+
+    // The purged head is still what the next entry links to
+    log(&conn, ActorType::Public, "after-purge");
+    let verification = audit_chain::verify(&conn, None, None).unwrap();
+    assert!(verification.valid);
+    assert_eq!(report(&verification, PUBLIC_CHAIN).head_seq, 4);
+}
+
+/// Save an entry for `org_id` (or no org) with a chosen timestamp.
+fn log_for_org(
+    conn: &Connection,
+    actor_type: ActorType,
+    org_id: Option<&str>,
+    resource_id: &str,
+    timestamp: i64,
+) {
+    let mut builder = AuditLogBuilder::new(conn, true, &Default::default())
+        .actor(actor_type, None)
+        .action(AuditAction::ActivateDevice)
+        .resource("device", resource_id);
+    if let Some(org_id) = org_id {
+        builder = builder.org(org_id);
+    }
+    let mut entry = builder.entry().unwrap();
+    entry.timestamp = timestamp;
+    queries::insert_audit_log(conn, &entry).unwrap();
+}
+
+fn remaining(conn: &Connection) -> Vec<String> {
+    let mut stmt = conn
+        .prepare("SELECT resource_id FROM audit_logs ORDER BY resource_id")
+        .unwrap();
+    stmt.query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+#[test]
+fn test_purge_applies_each_orgs_retention() {
+    let conn = setup_test_audit_db();
+    for (org, prefix) in [
+        (Some("org-short"), "short"),
+        (Some("org-long"), "long"),
+        (Some("org-default"), "default"),
+        (None, "unscoped"),
+    ] {
+        for (age, suffix) in [(5, "5d"), (20, "20d"), (60, "60d"), (200, "200d")] {
+            log_for_org(
+                &conn,
+                ActorType::Public,
+                org,
+                &format!("{}-public-{}", prefix, suffix),
+                now() - age * DAY,
+            );
+            log_for_org(
+                &conn,
+                ActorType::User,
+                org,
+                &format!("{}-admin-{}", prefix, suffix),
+                now() - age * DAY,
+            );
+        }
+    }
+
+    let orgs = [
+        OrgAuditRetention {
+            org_id: "org-short".into(),
+            public_days: Some(10),
+            admin_days: Some(30),
+        },
+        OrgAuditRetention {
+            org_id: "org-long".into(),
+            public_days: Some(100),
+            admin_days: None,
+        },
+        OrgAuditRetention {
+            org_id: "org-default".into(),
+            public_days: None,
+            admin_days: None,
+        },
+    ];
+    assert_eq!(queries::purge_old_audit_logs(&conn, 50, &orgs).unwrap(), 10);
+
+    let mut expected = vec![
+        // 10 days public, 30 days admin
+        "short-admin-20d",
+        "short-admin-5d",
+        "short-public-5d",
+        // 100 days public, admin kept forever
+        "long-admin-200d",
+        "long-admin-20d",
+        "long-admin-5d",
+        "long-admin-60d",
+        "long-public-20d",
+        "long-public-5d",
+        "long-public-60d",
+        // The 50 day instance default, admin kept forever
+        "default-admin-200d",
+        "default-admin-20d",
+        "default-admin-5d",
+        "default-admin-60d",
+        "default-public-20d",
+        "default-public-5d",
+        // Entries without an org follow the default too
+        "unscoped-admin-200d",
+        "unscoped-admin-20d",
+        "unscoped-admin-5d",
+        "unscoped-admin-60d",
+        "unscoped-public-20d",
+        "unscoped-public-5d",
+    ];
+    expected.sort();
+    assert_eq!(remaining(&conn), expected);
+
+    // Entries were removed from the middle of both chains
+    let verification = audit_chain::verify(&conn, None, None).unwrap();
+    assert!(verification.valid);
+    log(&conn, ActorType::Public, "after-purge");
+    log(&conn, ActorType::User, "after-purge");
+    assert!(audit_chain::verify(&conn, None, None).unwrap().valid);
+}
+
+#[test]
+fn test_purge_without_default_only_applies_org_policies() {
+    let conn = setup_test_audit_db();
+    log_for_org(
+        &conn,
+        ActorType::Public,
+        Some("org-a"),
+        "a-old",
+        now() - 60 * DAY,
+    );
+    log_for_org(
+        &conn,
+        ActorType::Public,
+        Some("org-b"),
+        "b-old",
+        now() - 60 * DAY,
+    );
+    log_for_org(
+        &conn,
+        ActorType::Public,
+        None,
+        "unscoped-old",
+        now() - 60 * DAY,
+    );
+
+    let orgs = [OrgAuditRetention {
+        org_id: "org-a".into(),
+        public_days: Some(30),
+        admin_days: None,
+    }];
+    assert_eq!(queries::purge_old_audit_logs(&conn, 0, &orgs).unwrap(), 1);
+    assert_eq!(remaining(&conn), ["b-old", "unscoped-old"]);
+    assert!(audit_chain::verify(&conn, None, None).unwrap().valid);
+}
+
+#[test]
+fn test_verify_limits_to_time_range() {
+    let conn = setup_test_audit_db();
+    log_at(&conn, ActorType::User, "d1", 1_000);
+    log_at(&conn, ActorType::User, "d2", 2_000);
+    log_at(&conn, ActorType::User, "d3", 3_000);
+    log_at(&conn, ActorType::User, "d4", 4_000);
+
+    let verification = audit_chain::verify(&conn, Some(2_000), Some(3_000)).unwrap();
+    assert!(verification.valid);
+    assert_eq!(report(&verification, INTERNAL_CHAIN).checked, 2);
+
+    // Entries outside the range aren't checked, but the first one in range
+    // still needs its predecessor
+    conn.execute("DELETE FROM audit_logs WHERE resource_id = 'd1'", [])
+        .unwrap();
+    let verification = audit_chain::verify(&conn, Some(2_000), Some(3_000)).unwrap();
+    let first_break = report(&verification, INTERNAL_CHAIN)
+        .first_break
+        .as_ref()
+        .unwrap();
+    assert_eq!(first_break.chain_seq, 1);
+    assert!(audit_chain::verify(&conn, Some(3_000), None).unwrap().valid);
+}
@@ -0,0 +1,212 @@
+//! Tests for the audit outbox: entries commit with the change they record and
+//! reach the audit database exactly once, even when the relay dies partway.
+//!
+//! "Restart" here means dropping every connection and reopening the database
+//! files, so only what was committed to disk survives.
+
+#[path = "../common/mod.rs"]
+mod common;
+
+use axum::http::HeaderMap;
+use common::*;
+use paycheck::db::outbox;
+use paycheck::error::{AppError, Result};
+use paycheck::util::AuditLogBuilder;
+use rusqlite::Connection;
+use tempfile::TempDir;
+
+struct OutboxFixture {
+    dir: TempDir,
+    license_id: String,
+}
+
+impl OutboxFixture {
+    fn new() -> Self {
+        let mut fixture = Self {
+            dir: tempfile::tempdir().unwrap(),
+            license_id: String::new(),
+        };
+        let conn = fixture.open_main();
+        init_audit_db(&fixture.open_audit()).unwrap();
+
+        let org = create_test_org(&conn, "Test Org");
+        let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+        let product = create_test_product(&conn, &project.id, "Pro", "pro");
+        fixture.license_id = create_test_license(&conn, &project.id, &product.id, None).id;
+        fixture
+    }
+
+    fn open_main(&self) -> Connection {
+        let conn = Connection::open(self.dir.path().join("paycheck.db")).unwrap();
+        init_db(&conn).unwrap();
+        conn
+    }
+
+    fn open_audit(&self) -> Connection {
+        Connection::open(self.dir.path().join("paycheck_audit.db")).unwrap()
+    }
+
+    fn entry(&self, action: AuditAction) -> AuditLog {
+        let headers = HeaderMap::new();
+        AuditLogBuilder::new(&self.open_audit(), true, &headers)
+            .action(action)
+            .resource("license", &self.license_id)
+            .entry()
+            .expect("audit logging is enabled")
+    }
+
+    /// Revoke the fixture's license through the outbox, without relaying.
+    fn revoke(&self, conn: &mut Connection) -> AuditLog {
+        let entry = self.entry(AuditAction::RevokeLicense);
+        outbox::with_audited_tx(
+            conn,
+            |tx| queries::revoke_license(tx, &self.license_id, &RevokeLicense::default(), None),
+            |_| Some(entry.clone()),
+        )
+        .unwrap();
+        entry
+    }
+
+    /// (id, timestamp, action) of every stored audit entry, oldest first.
+    fn audit_entries(&self) -> Vec<(String, i64, String)> {
+        audit_entries(&self.open_audit())
+    }
+}
+
+fn audit_entries(audit: &Connection) -> Vec<(String, i64, String)> {
+    let mut stmt = audit
+        .prepare("SELECT id, timestamp, action FROM audit_logs ORDER BY timestamp, rowid")
+        .unwrap();
+    stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap()
+        .collect::<rusqlite::Result<_>>()
+        .unwrap()
+}
+
+#[test]
+fn test_entry_survives_restart_before_relay() {
+    let f = OutboxFixture::new();
+    let entry = {
+        let mut conn = f.open_main();
+        f.revoke(&mut conn)
+        // Relay never runs: the process dies here
+    };
+    assert!(f.audit_entries().is_empty());
+
+    let conn = f.open_main();
+    assert_eq!(outbox::pending_count(&conn).unwrap(), 1);
+    assert_eq!(outbox::try_relay(&conn, &f.open_audit()).unwrap(), 1);
+
+    // Relayed with its original ID and time
+    assert_eq!(
+        f.audit_entries(),
+        [(entry.id, entry.timestamp, "revoke_license".to_string())]
+    );
+    assert_eq!(outbox::pending_count(&conn).unwrap(), 0);
+}
+
+#[test]
+fn test_crash_between_audit_insert_and_mark_does_not_duplicate() {
+    let f = OutboxFixture::new();
+    let entry = {
+        let mut conn = f.open_main();
+        let entry = f.revoke(&mut conn);
+        // The relay stores the entry, then dies before marking the row relayed
+        queries::insert_audit_log(&f.open_audit(), &entry).unwrap();
+        entry
+    };
+
+    let conn = f.open_main();
+    assert_eq!(outbox::pending_count(&conn).unwrap(), 1);
+    outbox::try_relay(&conn, &f.open_audit()).unwrap();
+    outbox::try_relay(&conn, &f.open_audit()).unwrap();
+
+    let ids: Vec<_> = f.audit_entries().into_iter().map(|(id, ..)| id).collect();
+    assert_eq!(ids, [entry.id]);
+    assert_eq!(outbox::pending_count(&conn).unwrap(), 0);
+}
+
+#[test]
+fn test_failed_change_queues_nothing() {
+    let f = OutboxFixture::new();
+    let mut conn = f.open_main();
+    let entry = f.entry(AuditAction::RevokeLicense);
+
+    let result: Result<()> = outbox::with_audited_tx(
+        &mut conn,
+        |tx| {
+            queries::revoke_license(tx, &f.license_id, &RevokeLicense::default(), None)?;
+            Err(AppError::BadRequest("rejected".into()))
+        },
+        |_| Some(entry.clone()),
+    );
+    assert!(result.is_err());
+
+    let license = queries::get_license_by_id(&conn, &f.license_id)
+        .unwrap()
+        .unwrap();
+    assert!(!license.revoked, "the change rolls back");
+    assert_eq!(outbox::pending_count(&conn).unwrap(), 0);
+}
+
+#[test]
+fn test_disabled_audit_logging_queues_nothing() {
+    let f = OutboxFixture::new();
+    let mut conn = f.open_main();
+    let headers = HeaderMap::new();
+    let audit = f.open_audit();
+
+    outbox::with_audited_tx(
+        &mut conn,
+        |tx| queries::revoke_license(tx, &f.license_id, &RevokeLicense::default(), None),
+        |_| {
+            AuditLogBuilder::new(&audit, false, &headers)
+                .action(AuditAction::RevokeLicense)
+                .resource("license", &f.license_id)
+                .entry()
+        },
+    )
+    .unwrap();
+
+    assert!(
+        queries::get_license_by_id(&conn, &f.license_id)
+            .unwrap()
+            .unwrap()
+            .revoked
+    );
+    assert_eq!(outbox::pending_count(&conn).unwrap(), 0);
+}
+
+#[test]
+fn test_relay_all_drains_every_tenant_database() {
+    let state = create_test_app_state();
+    let conn = state.db.get().unwrap();
+    let headers = HeaderMap::new();
+    let entries: Vec<AuditLog> = (0..3)
+        .map(|i| {
+            AuditLogBuilder::new(&conn, true, &headers)
+                .action(AuditAction::CreateLicense)
+                .resource("license", &format!("license-{}", i))
+                .entry()
+                .unwrap()
+        })
+        .collect();
+    for entry in &entries {
+        outbox::enqueue(&conn, entry).unwrap();
+    }
+    drop(conn);
+
+    assert_eq!(outbox::relay_all(&state).unwrap(), 3);
+    assert_eq!(
+        outbox::relay_all(&state).unwrap(),
+        0,
+        "nothing left to relay"
+    );
+
+    let stored: Vec<_> = audit_entries(&state.audit.get().unwrap())
+        .into_iter()
+        .map(|(id, ..)| id)
+        .collect();
+    let queued: Vec<_> = entries.into_iter().map(|e| e.id).collect();
+    assert_eq!(stored, queued, "relayed in the order they were queued");
+}
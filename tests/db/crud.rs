@@ -4,6 +4,7 @@
 mod common;
 
 use common::*;
+use rusqlite::Connection;
 
 // ============ Operator Tests ============
 
@@ -31,7 +32,7 @@ fn test_get_operator_by_user_id() {
     let mut conn = setup_test_db();
     let (created, _) = create_test_operator(&mut conn, "test@example.com", OperatorRole::Owner);
 
-    let fetched = queries::get_user_by_id(&mut conn, &created.id)
+    let fetched = queries::get_user_by_id(&mut conn, &created.id, &test_email_column())
         .expect("Query failed")
         .expect("User not found");
 
@@ -51,9 +52,10 @@ fn test_get_user_by_api_key() {
     let (created_user, api_key) =
         create_test_operator(&mut conn, "test@example.com", OperatorRole::View);
 
-    let (fetched_user, _api_key) = queries::get_user_by_api_key(&mut conn, &api_key)
-        .expect("Query failed")
-        .expect("User not found");
+    let (fetched_user, _api_key) =
+        queries::get_user_by_api_key(&mut conn, &api_key, &test_email_column())
+            .expect("Query failed")
+            .expect("User not found");
 
     assert_eq!(
         fetched_user.id, created_user.id,
@@ -66,7 +68,8 @@ fn test_get_user_with_invalid_api_key_returns_none() {
     let mut conn = setup_test_db();
     let _ = create_test_operator(&mut conn, "test@example.com", OperatorRole::Admin);
 
-    let result = queries::get_user_by_api_key(&mut conn, "invalid_key").expect("Query failed");
+    let result = queries::get_user_by_api_key(&mut conn, "invalid_key", &test_email_column())
+        .expect("Query failed");
 
     assert!(result.is_none(), "invalid API key should return None");
 }
@@ -78,7 +81,7 @@ fn test_list_operators() {
     create_test_operator(&mut conn, "test2@example.com", OperatorRole::Admin);
     create_test_operator(&mut conn, "test3@example.com", OperatorRole::View);
 
-    let operators = queries::list_operators(&conn).expect("Query failed");
+    let operators = queries::list_operators(&conn, &test_email_column()).expect("Query failed");
 
     assert_eq!(operators.len(), 3, "should return all 3 created operators");
 }
@@ -88,9 +91,14 @@ fn test_update_operator_role() {
     let mut conn = setup_test_db();
     let (user, _) = create_test_operator(&mut conn, "test@example.com", OperatorRole::View);
 
-    let updated = queries::update_operator_role(&mut conn, &user.id, OperatorRole::Admin)
-        .expect("Update failed")
-        .expect("Should return updated user");
+    let updated = queries::update_operator_role(
+        &mut conn,
+        &user.id,
+        OperatorRole::Admin,
+        &test_email_column(),
+    )
+    .expect("Update failed")
+    .expect("Should return updated user");
 
     assert_eq!(
         updated.operator_role,
@@ -107,7 +115,7 @@ fn test_revoke_operator_role() {
     let revoked = queries::revoke_operator_role(&mut conn, &user.id).expect("Revoke failed");
     assert!(revoked, "revoke should return true for existing operator");
 
-    let result = queries::get_user_by_id(&mut conn, &user.id)
+    let result = queries::get_user_by_id(&mut conn, &user.id, &test_email_column())
         .expect("Query failed")
         .expect("User should still exist");
     assert!(
@@ -116,6 +124,43 @@ fn test_revoke_operator_role() {
     );
 }
 
+#[test]
+fn test_operator_org_scope_is_added_once_and_removed() {
+    let conn = setup_test_db();
+
+    assert!(
+        queries::add_operator_org_scope(&conn, "op-1", "org-1")
+            .unwrap()
+            .is_some()
+    );
+    assert!(
+        queries::add_operator_org_scope(&conn, "op-1", "org-1")
+            .unwrap()
+            .is_none(),
+        "a second grant for the same org is a no-op"
+    );
+    assert!(queries::operator_has_org_scope(&conn, "op-1", "org-1").unwrap());
+
+    assert!(queries::remove_operator_org_scope(&conn, "op-1", "org-1").unwrap());
+    assert!(!queries::operator_has_org_scope(&conn, "op-1", "org-1").unwrap());
+}
+
+#[test]
+fn test_create_user_round_trips_by_id_and_email() {
+    let conn = setup_test_db();
+    let emails = test_email_column();
+    let user = create_test_user(&conn, "ada@example.com", "Ada");
+
+    let by_id = queries::get_user_by_id(&conn, &user.id, &emails)
+        .unwrap()
+        .unwrap();
+    assert_eq!(by_id.name, "Ada");
+    let by_email = queries::get_user_by_email(&conn, "ada@example.com", &emails)
+        .unwrap()
+        .unwrap();
+    assert_eq!(by_email.id, user.id);
+}
+
 // ============ Organization Tests ============
 
 #[test]
@@ -174,6 +219,9 @@ fn test_update_organization() {
         ls_config: None,
         resend_api_key: None,
         payment_provider: None,
+        public_audit_retention_days: None,
+        admin_audit_retention_days: None,
+        expected_version: None,
     };
     queries::update_organization(&conn, &org.id, &update).expect("Update failed");
 
@@ -235,9 +283,10 @@ fn test_get_user_by_api_key_for_member() {
     let (created_user, _member, api_key) =
         create_test_org_member(&mut conn, &org.id, "member@test.com", OrgMemberRole::Admin);
 
-    let (fetched_user, _api_key) = queries::get_user_by_api_key(&mut conn, &api_key)
-        .expect("Query failed")
-        .expect("User not found");
+    let (fetched_user, _api_key) =
+        queries::get_user_by_api_key(&mut conn, &api_key, &test_email_column())
+            .expect("Query failed")
+            .expect("User not found");
 
     assert_eq!(
         fetched_user.id, created_user.id,
@@ -286,6 +335,68 @@ fn test_same_user_different_orgs() {
     );
 }
 
+#[test]
+fn test_paginated_user_listing_matches_single_user_lookup() {
+    let mut conn = setup_test_db();
+    let emails = test_email_column();
+    let acme = create_test_org(&conn, "Acme");
+    let zeta = create_test_org(&conn, "Zeta");
+
+    create_test_user(&conn, "plain@example.com", "Plain");
+    create_test_operator(&mut conn, "op@example.com", OperatorRole::Admin);
+    let (both, _, _) = create_test_org_member(
+        &mut conn,
+        &zeta.id,
+        "both@example.com",
+        OrgMemberRole::Owner,
+    );
+    queries::grant_operator_role(&conn, &both.id, OperatorRole::View, &emails).unwrap();
+    let input = CreateOrgMember {
+        user_id: both.id.clone(),
+        role: OrgMemberRole::Member,
+    };
+    queries::create_org_member(&conn, &acme.id, &input).unwrap();
+    create_test_org_member(
+        &mut conn,
+        &acme.id,
+        "member@example.com",
+        OrgMemberRole::Admin,
+    );
+
+    let (all, total) =
+        queries::list_users_with_roles_paginated(&conn, 100, 0, false, &emails).unwrap();
+    assert_eq!(total, 4);
+    let both_listed = all.iter().find(|u| u.id == both.id).unwrap();
+    let orgs: Vec<&str> = both_listed
+        .memberships
+        .iter()
+        .map(|m| m.org_name.as_str())
+        .collect();
+    assert_eq!(orgs, ["Acme", "Zeta"]);
+
+    // Same users, roles, and order whatever the page size
+    for limit in [1, 3, 100] {
+        let mut listed = Vec::new();
+        for offset in (0..total).step_by(limit as usize) {
+            let (page, _) =
+                queries::list_users_with_roles_paginated(&conn, limit, offset, false, &emails)
+                    .unwrap();
+            listed.extend(page);
+        }
+        assert_eq!(listed.len(), all.len());
+        for (user, expected) in listed.iter().zip(&all) {
+            let single = queries::get_user_with_roles(&conn, &user.id, &emails)
+                .unwrap()
+                .unwrap();
+            assert_eq!(user.id, expected.id);
+            assert_eq!(
+                serde_json::to_value(user).unwrap(),
+                serde_json::to_value(&single).unwrap()
+            );
+        }
+    }
+}
+
 // ============ Project Tests ============
 
 #[test]
@@ -444,6 +555,9 @@ fn test_update_org_both_payment_configs() {
         ls_config: None,
         resend_api_key: None,
         payment_provider: Some(Some("stripe".to_string())),
+        public_audit_retention_days: None,
+        admin_audit_retention_days: None,
+        expected_version: None,
     };
     queries::update_organization(&conn, &org.id, &update).expect("Update failed");
 
@@ -500,6 +614,39 @@ fn test_payment_config_wrong_key_fails() {
     assert!(result.is_err(), "Decryption with wrong key should fail");
 }
 
+#[test]
+fn test_project_is_found_by_public_key() {
+    let conn = setup_test_db();
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "My App", &test_master_key());
+
+    let found = queries::get_project_by_public_key(&conn, &project.public_key)
+        .unwrap()
+        .unwrap();
+    assert_eq!(found.id, project.id);
+}
+
+#[test]
+fn test_update_project_leaves_unset_fields() {
+    let conn = setup_test_db();
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "My App", &test_master_key());
+    let input: UpdateProject = serde_json::from_value(serde_json::json!({
+        "name": "Renamed",
+        "attestation_audiences": ["plugins.example.com"],
+    }))
+    .unwrap();
+
+    let updated = queries::update_project(&conn, &project.id, &input)
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(updated.name, "Renamed");
+    assert_eq!(updated.attestation_audiences, vec!["plugins.example.com"]);
+    assert_eq!(updated.license_key_prefix, project.license_key_prefix);
+    assert_eq!(updated.email_enabled, project.email_enabled);
+}
+
 // ============ Product Tests ============
 
 #[test]
@@ -600,6 +747,16 @@ fn test_update_product() {
             "feature2".to_string(),
             "feature3".to_string(),
         ]),
+        entitlements: None,
+        seat_count: None,
+        available_from: None,
+        available_until: None,
+        checkout_fields: None,
+        extends_updates_for_product_id: None,
+        renewal_fallback: None,
+        revoke_on_partial_refund: None,
+        upsell: None,
+        expected_version: None,
     };
 
     queries::update_product(&mut conn, &product.id, &update).expect("Update failed");
@@ -638,6 +795,16 @@ fn test_update_product() {
         device_limit: Some(None),     // Set to unlimited
         device_inactive_days: None,
         features: None,
+        entitlements: None,
+        seat_count: None,
+        available_from: None,
+        available_until: None,
+        checkout_fields: None,
+        extends_updates_for_product_id: None,
+        renewal_fallback: None,
+        revoke_on_partial_refund: None,
+        upsell: None,
+        expected_version: None,
     };
 
     queries::update_product(&mut conn, &product.id, &update_to_unlimited).expect("Update to unlimited failed");
@@ -670,6 +837,16 @@ fn test_update_product() {
         device_limit: None,
         device_inactive_days: Some(Some(30)), // Set to 30 days
         features: None,
+        entitlements: None,
+        seat_count: None,
+        available_from: None,
+        available_until: None,
+        checkout_fields: None,
+        extends_updates_for_product_id: None,
+        renewal_fallback: None,
+        revoke_on_partial_refund: None,
+        upsell: None,
+        expected_version: None,
     };
     queries::update_product(&mut conn, &product.id, &set_inactive_days)
         .expect("Setting device_inactive_days failed");
@@ -694,6 +871,16 @@ fn test_update_product() {
         device_limit: None,
         device_inactive_days: Some(None), // Clear device_inactive_days
         features: None,
+        entitlements: None,
+        seat_count: None,
+        available_from: None,
+        available_until: None,
+        checkout_fields: None,
+        extends_updates_for_product_id: None,
+        renewal_fallback: None,
+        revoke_on_partial_refund: None,
+        upsell: None,
+        expected_version: None,
     };
     queries::update_product(&mut conn, &product.id, &clear_nullable_fields)
         .expect("Clearing nullable fields failed");
@@ -733,6 +920,42 @@ fn test_delete_product() {
     assert!(result.is_none(), "deleted product should not be found");
 }
 
+#[test]
+fn test_update_product_leaves_unset_fields() {
+    let conn = setup_test_db();
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "My App", &test_master_key());
+    let product = create_test_product(&conn, &project.id, "Pro", "pro");
+    let input: UpdateProduct = serde_json::from_value(serde_json::json!({
+        "name": "Pro Plus",
+        "device_limit": 7,
+    }))
+    .unwrap();
+
+    let updated = queries::update_product(&conn, &product.id, &input)
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(updated.name, "Pro Plus");
+    assert_eq!(updated.device_limit, Some(7));
+    assert_eq!(updated.tier, "pro");
+    assert_eq!(updated.license_exp_days, product.license_exp_days);
+}
+
+#[test]
+fn test_get_products_by_ids_skips_unknown_ids() {
+    let conn = setup_test_db();
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "My App", &test_master_key());
+    let product = create_test_product(&conn, &project.id, "Pro", "pro");
+
+    let found = queries::get_products_by_ids(&conn, &[&product.id, "missing"]).unwrap();
+
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].id, product.id);
+    assert!(queries::get_products_by_ids(&conn, &[]).unwrap().is_empty());
+}
+
 // ============ Cascade Delete Tests ============
 
 #[test]
@@ -786,42 +1009,32 @@ fn test_delete_project_cascades_to_products() {
 
 // ============ Audit Log Purge Tests ============
 
+/// Save an audit entry with a chosen ID and timestamp through the normal
+/// (hash-chained) insert.
+fn insert_audit_entry(conn: &Connection, id: &str, timestamp: i64, actor_type: ActorType) {
+    let mut entry = paycheck::util::AuditLogBuilder::new(conn, true, &Default::default())
+        .actor(actor_type, None)
+        .action(AuditAction::ActivateDevice)
+        .resource("license", id)
+        .entry()
+        .unwrap();
+    entry.id = id.to_string();
+    entry.timestamp = timestamp;
+    queries::insert_audit_log(conn, &entry).unwrap();
+}
+
 #[test]
-fn test_purge_old_public_audit_logs_only_deletes_public() {
+fn test_purge_old_audit_logs_only_deletes_public() {
     let mut conn = setup_test_audit_db();
 
     // Create audit logs with different actor types, all with old timestamps
     // Using timestamp 0 (1970) to ensure they're "old"
     let old_timestamp = 0i64;
 
-    // Insert logs directly to control timestamp
-    conn.execute(
-        "INSERT INTO audit_logs (id, timestamp, actor_type, action, resource_type, resource_id)
-         VALUES ('log_public', ?1, 'public', 'redeem', 'license', 'lic1')",
-        [old_timestamp],
-    )
-    .unwrap();
-
-    conn.execute(
-        "INSERT INTO audit_logs (id, timestamp, actor_type, action, resource_type, resource_id)
-         VALUES ('log_internal', ?1, 'user', 'create', 'organization', 'org1')",
-        [old_timestamp],
-    )
-    .unwrap();
-
-    conn.execute(
-        "INSERT INTO audit_logs (id, timestamp, actor_type, action, resource_type, resource_id)
-         VALUES ('log_user', ?1, 'user', 'create', 'license', 'lic2')",
-        [old_timestamp],
-    )
-    .unwrap();
-
-    conn.execute(
-        "INSERT INTO audit_logs (id, timestamp, actor_type, action, resource_type, resource_id)
-         VALUES ('log_system', ?1, 'system', 'bootstrap', 'operator', 'op1')",
-        [old_timestamp],
-    )
-    .unwrap();
+    insert_audit_entry(&conn, "log_public", old_timestamp, ActorType::Public);
+    insert_audit_entry(&conn, "log_internal", old_timestamp, ActorType::User);
+    insert_audit_entry(&conn, "log_user", old_timestamp, ActorType::User);
+    insert_audit_entry(&conn, "log_system", old_timestamp, ActorType::System);
 
     // Verify all 4 logs exist
     let count: i64 = conn
@@ -830,7 +1043,7 @@ fn test_purge_old_public_audit_logs_only_deletes_public() {
     assert_eq!(count, 4, "should have 4 audit logs before purge");
 
     // Purge with 1 day retention (anything older than 1 day ago)
-    let deleted = queries::purge_old_public_audit_logs(&mut conn, ONE_DAY).unwrap();
+    let deleted = queries::purge_old_audit_logs(&mut conn, ONE_DAY, &[]).unwrap();
 
     // Only the public log should be deleted
     assert_eq!(deleted, 1, "should delete only 1 public audit log");
@@ -881,7 +1094,7 @@ fn test_purge_old_public_audit_logs_only_deletes_public() {
 }
 
 #[test]
-fn test_purge_old_public_audit_logs_respects_retention_period() {
+fn test_purge_old_audit_logs_respects_retention_period() {
     let mut conn = setup_test_audit_db();
 
     let now = std::time::SystemTime::now()
@@ -891,24 +1104,14 @@ fn test_purge_old_public_audit_logs_respects_retention_period() {
 
     // Create an old public log (100 days old)
     let old_timestamp = now - (100 * 86400);
-    conn.execute(
-        "INSERT INTO audit_logs (id, timestamp, actor_type, action, resource_type, resource_id)
-         VALUES ('log_old_public', ?1, 'public', 'redeem', 'license', 'lic1')",
-        [old_timestamp],
-    )
-    .unwrap();
+    insert_audit_entry(&conn, "log_old_public", old_timestamp, ActorType::Public);
 
     // Create a recent public log (1 day old)
     let recent_timestamp = now - (ONE_DAY * 86400);
-    conn.execute(
-        "INSERT INTO audit_logs (id, timestamp, actor_type, action, resource_type, resource_id)
-         VALUES ('log_recent_public', ?1, 'public', 'redeem', 'license', 'lic2')",
-        [recent_timestamp],
-    )
-    .unwrap();
+    insert_audit_entry(&conn, "log_recent_public", recent_timestamp, ActorType::Public);
 
     // Purge with 30 day retention
-    let deleted = queries::purge_old_public_audit_logs(&mut conn, ONE_MONTH).unwrap();
+    let deleted = queries::purge_old_audit_logs(&mut conn, ONE_MONTH, &[]).unwrap();
 
     // Only the old public log should be deleted
     assert_eq!(deleted, 1, "should delete only the old public log");
@@ -927,6 +1130,59 @@ fn test_purge_old_public_audit_logs_respects_retention_period() {
     );
 }
 
+// ============ Audit Log Query Tests ============
+
+#[test]
+fn test_last_audit_at_per_org() {
+    let conn = setup_test_audit_db();
+    let log = queries::new_audit_log(
+        ActorType::System,
+        None,
+        "purge",
+        "license",
+        "lic-1",
+        None,
+        Some("org-1"),
+        None,
+        None,
+        None,
+        &AuditLogNames::default(),
+        None,
+        None,
+        None,
+    );
+    queries::insert_audit_log(&conn, &log).unwrap();
+
+    let last = queries::get_orgs_last_audit_at(&conn, &["org-1", "org-2"]).unwrap();
+
+    assert_eq!(last.get("org-1"), Some(&log.timestamp));
+    assert!(!last.contains_key("org-2"));
+}
+
+#[test]
+fn test_recent_project_activity_uses_its_index() {
+    let conn = setup_test_audit_db();
+
+    // Same filter and order as `list_recent_project_activity`
+    let plan: Vec<String> = conn
+        .prepare(
+            "EXPLAIN QUERY PLAN SELECT id FROM audit_logs
+             WHERE project_id = ?1 AND actor_type = 'user'
+             ORDER BY timestamp DESC, id DESC LIMIT ?2",
+        )
+        .unwrap()
+        .query_map(rusqlite::params!["proj-1", 20], |row| row.get(3))
+        .unwrap()
+        .collect::<rusqlite::Result<_>>()
+        .unwrap();
+    let plan = plan.join("\n");
+    assert!(
+        plan.contains("idx_audit_logs_project_activity"),
+        "unexpected plan: {}",
+        plan
+    );
+}
+
 // ============ API Key Scope Validation Tests ============
 
 #[test]
@@ -1058,3 +1314,71 @@ fn test_api_key_scope_accepts_valid_project() {
         "scope project_id should match input"
     );
 }
+
+// ============ Payment Session Tests ============
+
+#[test]
+fn test_payment_session_is_claimed_once() {
+    let conn = setup_test_db();
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "My App", &test_master_key());
+    let product = create_test_product(&conn, &project.id, "Pro", "pro");
+    let session = create_test_payment_session(&conn, &product.id, None);
+
+    assert!(queries::try_claim_payment_session(&conn, &session.id).unwrap());
+    assert!(
+        !queries::try_claim_payment_session(&conn, &session.id).unwrap(),
+        "a second webhook for the same session must not claim it"
+    );
+}
+
+#[test]
+fn test_webhook_event_is_recorded_once_per_provider() {
+    let conn = setup_test_db();
+
+    assert!(queries::try_record_webhook_event(&conn, "stripe", "evt_1").unwrap());
+    assert!(!queries::try_record_webhook_event(&conn, "stripe", "evt_1").unwrap());
+    assert!(queries::try_record_webhook_event(&conn, "lemonsqueezy", "evt_1").unwrap());
+}
+
+// ============ System Config Tests ============
+
+#[test]
+fn test_system_config_upserts() {
+    let conn = setup_test_db();
+    assert_eq!(queries::get_system_config(&conn, "key").unwrap(), None);
+
+    queries::set_system_config(&conn, "key", b"one").unwrap();
+    queries::set_system_config(&conn, "key", b"two").unwrap();
+
+    assert_eq!(
+        queries::get_system_config(&conn, "key").unwrap(),
+        Some(b"two".to_vec())
+    );
+
+    assert!(queries::delete_system_config(&conn, "key").unwrap());
+    assert!(!queries::delete_system_config(&conn, "key").unwrap());
+    assert_eq!(queries::get_system_config(&conn, "key").unwrap(), None);
+}
+
+#[test]
+fn test_storage_heartbeat_counts_every_beat() {
+    let conn = setup_test_db();
+    assert_eq!(queries::get_storage_heartbeat(&conn, "main").unwrap(), None);
+
+    let started = queries::record_storage_start(&conn, "main", "/data/paycheck.db", 100).unwrap();
+    assert_eq!((started.beat, started.started_at), (1, 100));
+
+    queries::beat_storage_heartbeat(&conn, "main", "/data/paycheck.db", 400).unwrap();
+    let beat = queries::beat_storage_heartbeat(&conn, "main", "/data/paycheck.db", 700).unwrap();
+    assert_eq!((beat.beat, beat.started_at, beat.beat_at), (3, 100, 700));
+
+    // A restart moves the path and keeps counting
+    let restarted = queries::record_storage_start(&conn, "main", "/mnt/paycheck.db", 900).unwrap();
+    assert_eq!(restarted.beat, 4);
+    assert_eq!(restarted.path, "/mnt/paycheck.db");
+    assert_eq!(
+        queries::get_storage_heartbeat(&conn, "main").unwrap(),
+        Some(restarted)
+    );
+}
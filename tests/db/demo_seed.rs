@@ -0,0 +1,116 @@
+//! Tests for the demo dataset (`--seed-demo`): it has the advertised shape,
+//! and the same seed reproduces the same data.
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+use rusqlite::Connection;
+
+use paycheck::fixtures::demo::{DEMO_LICENSE_COUNT, seed_demo};
+
+/// When the demo is seeded; fixed so dates compare across runs
+const SEEDED_AT: i64 = 1_780_000_000;
+
+fn seeded_state(seed: u64) -> AppState {
+    let mut state = create_test_app_state();
+    state.clock = Clock::fixed(SEEDED_AT);
+    seed_demo(&state, seed).expect("Failed to seed demo dataset");
+    state
+}
+
+fn seeded_fingerprint(seed: u64) -> Vec<LicenseRow> {
+    let state = seeded_state(seed);
+    let conn = state.db.get().unwrap();
+    license_fingerprint(&conn)
+}
+
+/// A license as (project, product, customer, email hash, expiry,
+/// subscription, revocation reason, activations)
+type LicenseRow = (
+    String,
+    String,
+    Option<String>,
+    Option<String>,
+    Option<i64>,
+    Option<String>,
+    Option<RevocationReason>,
+    i32,
+);
+
+/// Every license, in customer order
+fn license_fingerprint(conn: &Connection) -> Vec<LicenseRow> {
+    let org = &queries::list_organizations(conn).unwrap()[0];
+    let mut rows = Vec::new();
+    for project in queries::list_projects_for_org(conn, &org.id).unwrap() {
+        for item in queries::list_licenses_for_project(conn, &project.id).unwrap() {
+            let license = item.license;
+            rows.push((
+                project.name.clone(),
+                item.product_name,
+                license.customer_id,
+                license.email_hash,
+                license.expires_at,
+                license.payment_provider_subscription_id,
+                license.revoked_reason,
+                license.activation_count,
+            ));
+        }
+    }
+    rows.sort_by(|a, b| a.2.cmp(&b.2));
+    rows
+}
+
+#[test]
+fn test_demo_dataset_shape() {
+    let state = seeded_state(42);
+    let conn = state.db.get().unwrap();
+
+    let orgs = queries::list_organizations(&conn).unwrap();
+    assert_eq!(orgs.len(), 1);
+    let projects = queries::list_projects_for_org(&conn, &orgs[0].id).unwrap();
+    assert_eq!(projects.len(), 2);
+    let products: usize = projects
+        .iter()
+        .map(|p| {
+            queries::list_products_for_project(&conn, &p.id)
+                .unwrap()
+                .len()
+        })
+        .sum();
+    assert_eq!(products, 5);
+
+    let licenses = license_fingerprint(&conn);
+    assert_eq!(licenses.len(), DEMO_LICENSE_COUNT);
+    assert!(licenses.iter().any(|l| l.6.is_some()), "some are revoked");
+    assert!(
+        licenses
+            .iter()
+            .any(|l| l.4.is_some_and(|exp| exp < SEEDED_AT)),
+        "some are expired"
+    );
+    assert!(
+        licenses
+            .iter()
+            .any(|l| l.4.is_some_and(|exp| exp > SEEDED_AT)),
+        "some are active subscriptions"
+    );
+    assert!(
+        licenses.iter().any(|l| l.5.is_some()),
+        "some are subscription-linked"
+    );
+    assert!(licenses.iter().any(|l| l.7 > 0), "some have devices");
+}
+
+#[test]
+fn test_same_seed_same_dataset() {
+    let first = seeded_fingerprint(7);
+    let second = seeded_fingerprint(7);
+    assert_eq!(first, second);
+}
+
+#[test]
+fn test_different_seed_different_dataset() {
+    let first = seeded_fingerprint(7);
+    let second = seeded_fingerprint(8);
+    assert_ne!(first, second);
+}
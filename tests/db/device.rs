@@ -4,6 +4,9 @@
 mod common;
 
 use common::*;
+use paycheck::db::queries::DeviceAcquisitionResult;
+use paycheck::error::{AppError, Result};
+use rusqlite::Connection;
 
 // ============ Device Creation Tests ============
 
@@ -420,3 +423,74 @@ fn test_count_devices_for_license() {
         "should have 2 devices after second creation"
     );
 }
+
+// ============ Device Acquisition Tests ============
+
+fn acquire(
+    conn: &mut Connection,
+    license_id: &str,
+    device_id: &str,
+    jti: &str,
+    device_limit: Option<i32>,
+) -> Result<DeviceAcquisitionResult> {
+    queries::acquire_device_atomic(
+        conn,
+        license_id,
+        device_id,
+        DeviceType::Uuid,
+        jti,
+        None,
+        None,
+        None,
+        device_limit,
+        None,
+        None,
+    )
+}
+
+#[test]
+fn test_reactivating_a_device_reuses_it_with_the_new_jti() {
+    let mut conn = setup_test_db();
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "My App", &test_master_key());
+    let product = create_test_product(&conn, &project.id, "Pro", "pro");
+    let license = create_test_license(&conn, &project.id, &product.id, None);
+
+    let first = acquire(&mut conn, &license.id, "laptop", "jti-1", Some(1)).unwrap();
+    assert!(matches!(first, DeviceAcquisitionResult::Created(_)));
+    let again = acquire(&mut conn, &license.id, "laptop", "jti-2", Some(1)).unwrap();
+    assert!(matches!(again, DeviceAcquisitionResult::Existing(_)));
+
+    assert_eq!(
+        queries::count_devices_for_license(&conn, &license.id).unwrap(),
+        1
+    );
+    assert!(
+        queries::get_device_by_jti(&conn, "jti-1")
+            .unwrap()
+            .is_none()
+    );
+    assert!(
+        queries::get_device_by_jti(&conn, "jti-2")
+            .unwrap()
+            .is_some()
+    );
+}
+
+#[test]
+fn test_device_limit_refuses_new_devices() {
+    let mut conn = setup_test_db();
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "My App", &test_master_key());
+    let product = create_test_product(&conn, &project.id, "Pro", "pro");
+    let license = create_test_license(&conn, &project.id, &product.id, None);
+    acquire(&mut conn, &license.id, "laptop", "jti-1", Some(1)).unwrap();
+
+    let result = acquire(&mut conn, &license.id, "desktop", "jti-2", Some(1));
+
+    assert!(matches!(result, Err(AppError::Forbidden(_))));
+    assert_eq!(
+        queries::count_devices_for_license(&conn, &license.id).unwrap(),
+        1
+    );
+}
@@ -176,7 +176,7 @@ fn test_invalid_operator_role_treated_as_none() {
     ).unwrap();
 
     // Attempt to read the user - should succeed but treat invalid role as None
-    let result = queries::get_user_by_id(&mut conn, "u1");
+    let result = queries::get_user_by_id(&mut conn, "u1", &test_email_column());
 
     // Should not panic, and should treat invalid role as None (not an operator)
     let user = result.expect("Should not panic on invalid operator_role").expect("User should be found");
@@ -275,7 +275,7 @@ fn test_invalid_project_member_role_returns_error() {
     ).unwrap();
 
     // Should return error, not panic
-    let result = queries::get_project_member_by_id(&mut conn, "pm1");
+    let result = queries::get_project_member_by_id(&mut conn, "pm1", &test_email_column());
     assert!(
         result.is_err(),
         "reading project member with invalid role should return error, not panic"
@@ -341,9 +341,9 @@ fn test_list_operators_filters_invalid_roles() {
     ).unwrap();
 
     // List should succeed - users with invalid roles are filtered out (only valid operators appear)
-    // Note: list_operators queries WHERE operator_role IN ('owner', 'admin', 'view'),
+    // Note: list_operators queries WHERE operator_role IN ('owner', 'admin', 'view', 'partner'),
     // so users with invalid roles like 'hacker' won't be returned
-    let result = queries::list_operators(&conn);
+    let result = queries::list_operators(&conn, &test_email_column());
     let operators = result.expect("list_operators should not panic");
 
     // Only the valid admin should appear
@@ -382,7 +382,12 @@ fn test_org_member_with_user_invalid_role_returns_error() {
     );
 
     // Also test get_org_member_with_user_by_user_and_org
-    let result2 = queries::get_org_member_with_user_by_user_and_org(&mut conn, "u1", "org1");
+    let result2 = queries::get_org_member_with_user_by_user_and_org(
+        &mut conn,
+        "u1",
+        "org1",
+        &test_email_column(),
+    );
     assert!(
         result2.is_err(),
         "reading OrgMemberWithUser with invalid role should return error, not panic"
@@ -419,7 +424,7 @@ fn test_get_user_with_roles_invalid_member_role_returns_error() {
 
     // get_user_with_roles should return an error, not panic
     // BUG: Currently this panics due to .parse().unwrap() in queries.rs:276
-    let result = queries::get_user_with_roles(&conn, "u1");
+    let result = queries::get_user_with_roles(&conn, "u1", &test_email_column());
 
     assert!(
         result.is_err(),
@@ -60,6 +60,7 @@ fn test_create_license_without_identifier_fails() {
         payment_provider_customer_id: None,
         payment_provider_subscription_id: None,
         payment_provider_order_id: None,
+        seats: None,
     };
 
     let result = queries::create_license(&mut conn, &project.id, &product.id, &input);
@@ -94,6 +95,7 @@ fn test_create_license_with_only_order_id_succeeds() {
         payment_provider_customer_id: None,
         payment_provider_subscription_id: None,
         payment_provider_order_id: Some("cs_test_123".to_string()),
+        seats: None,
     };
 
     let license = queries::create_license(&mut conn, &project.id, &product.id, &input)
@@ -119,7 +121,7 @@ fn test_create_license_with_customer_id() {
     let product = create_test_product(&mut conn, &project.id, "Pro", "pro");
 
     let input = CreateLicense {
-        email_hash: Some(test_email_hasher().hash("customer@example.com")),
+        email_hash: Some(test_email_hasher().hash(&parse_email("customer@example.com"))),
         customer_id: Some("cust_12345".to_string()),
         expires_at: None,
         updates_expires_at: None,
@@ -127,6 +129,7 @@ fn test_create_license_with_customer_id() {
         payment_provider_customer_id: None,
         payment_provider_subscription_id: None,
         payment_provider_order_id: None,
+        seats: None,
     };
 
     let license = queries::create_license(&mut conn, &project.id, &product.id, &input)
@@ -148,7 +151,7 @@ fn test_create_license_with_payment_provider() {
     let product = create_test_product(&mut conn, &project.id, "Pro", "pro");
 
     let input = CreateLicense {
-        email_hash: Some(test_email_hasher().hash("customer@example.com")),
+        email_hash: Some(test_email_hasher().hash(&parse_email("customer@example.com"))),
         customer_id: None,
         expires_at: Some(future_timestamp(ONE_MONTH)),
         updates_expires_at: Some(future_timestamp(ONE_YEAR)),
@@ -156,6 +159,7 @@ fn test_create_license_with_payment_provider() {
         payment_provider_customer_id: Some("cus_xxx".to_string()),
         payment_provider_subscription_id: Some("sub_yyy".to_string()),
         payment_provider_order_id: Some("cs_test_xxx".to_string()),
+        seats: None,
     };
 
     let license = queries::create_license(&mut conn, &project.id, &product.id, &input)
@@ -217,7 +221,7 @@ fn test_get_license_by_email_hash() {
     let product = create_test_product(&mut conn, &project.id, "Pro", "pro");
 
     let email = "unique@example.com";
-    let email_hash = test_email_hasher().hash(email);
+    let email_hash = test_email_hasher().hash(&parse_email(email));
 
     let input = CreateLicense {
         email_hash: Some(email_hash.clone()),
@@ -228,6 +232,7 @@ fn test_get_license_by_email_hash() {
         payment_provider_customer_id: None,
         payment_provider_subscription_id: None,
         payment_provider_order_id: None,
+        seats: None,
     };
 
     let created = queries::create_license(&mut conn, &project.id, &product.id, &input)
@@ -252,7 +257,7 @@ fn test_get_license_by_subscription() {
     let product = create_test_product(&mut conn, &project.id, "Pro", "pro");
 
     let input = CreateLicense {
-        email_hash: Some(test_email_hasher().hash("subscriber@example.com")),
+        email_hash: Some(test_email_hasher().hash(&parse_email("subscriber@example.com"))),
         customer_id: None,
         expires_at: None,
         updates_expires_at: None,
@@ -260,6 +265,7 @@ fn test_get_license_by_subscription() {
         payment_provider_customer_id: Some("cus_xxx".to_string()),
         payment_provider_subscription_id: Some("sub_unique_id".to_string()),
         payment_provider_order_id: None,
+        seats: None,
     };
 
     let created = queries::create_license(&mut conn, &project.id, &product.id, &input)
@@ -284,7 +290,7 @@ fn test_get_license_by_subscription_wrong_provider() {
     let product = create_test_product(&mut conn, &project.id, "Pro", "pro");
 
     let input = CreateLicense {
-        email_hash: Some(test_email_hasher().hash("subscriber@example.com")),
+        email_hash: Some(test_email_hasher().hash(&parse_email("subscriber@example.com"))),
         customer_id: None,
         expires_at: None,
         updates_expires_at: None,
@@ -292,6 +298,7 @@ fn test_get_license_by_subscription_wrong_provider() {
         payment_provider_customer_id: None,
         payment_provider_subscription_id: Some("sub_id".to_string()),
         payment_provider_order_id: None,
+        seats: None,
     };
 
     queries::create_license(&mut conn, &project.id, &product.id, &input)
@@ -380,7 +387,7 @@ fn test_revoke_license() {
 
     assert!(!license.revoked, "new license should not be revoked");
 
-    queries::revoke_license(&mut conn, &license.id).expect("Revoke failed");
+    queries::revoke_license(&mut conn, &license.id, &RevokeLicense::default(), None).expect("Revoke failed");
 
     let revoked = queries::get_license_by_id(&mut conn, &license.id)
         .expect("Query failed")
@@ -430,6 +437,116 @@ fn test_add_revoked_jti_marks_jti_as_revoked() {
         .expect("Add duplicate JTI should not fail");
 }
 
+#[test]
+fn test_concurrent_jti_revocations_keep_every_entry() {
+    use rusqlite::Connection;
+    use std::sync::{Arc, Barrier};
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("paycheck.db");
+    let license_id = {
+        let mut conn = Connection::open(&path).unwrap();
+        init_db(&conn).unwrap();
+        let org = create_test_org(&mut conn, "Test Org");
+        let project = create_test_project(&mut conn, &org.id, "My App", &test_master_key());
+        let product = create_test_product(&mut conn, &project.id, "Pro", "pro");
+        create_test_license(&conn, &project.id, &product.id, None).id
+    };
+
+    // Each round, two admins deactivate different devices of the same license at once
+    const ROUNDS: usize = 20;
+    let barrier = Arc::new(Barrier::new(2));
+    let handles: Vec<_> = ["a", "b"]
+        .into_iter()
+        .map(|side| {
+            let (path, license_id, barrier) = (path.clone(), license_id.clone(), barrier.clone());
+            std::thread::spawn(move || {
+                let conn = Connection::open(&path).unwrap();
+                for round in 0..ROUNDS {
+                    barrier.wait();
+                    let jti = format!("jti_{}_{}", side, round);
+                    queries::add_revoked_jti(&conn, &license_id, &jti, None).unwrap();
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    let conn = Connection::open(&path).unwrap();
+    for side in ["a", "b"] {
+        for round in 0..ROUNDS {
+            let jti = format!("jti_{}_{}", side, round);
+            assert!(
+                queries::is_jti_revoked(&conn, &jti).unwrap(),
+                "{} should still be revoked",
+                jti
+            );
+        }
+    }
+    let count: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM revoked_jtis WHERE license_id = ?1",
+            [&license_id],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(count, (ROUNDS * 2) as i64);
+}
+
+#[test]
+fn test_concurrent_license_creations_get_consecutive_reference_numbers() {
+    use rusqlite::Connection;
+    use std::collections::HashSet;
+    use std::sync::{Arc, Barrier};
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("paycheck.db");
+    let (project_id, product_id) = {
+        let mut conn = Connection::open(&path).unwrap();
+        init_db(&conn).unwrap();
+        let org = create_test_org(&mut conn, "Test Org");
+        let project = create_test_project(&mut conn, &org.id, "My App", &test_master_key());
+        let product = create_test_product(&mut conn, &project.id, "Pro", "pro");
+        (project.id, product.id)
+    };
+
+    const THREADS: usize = 4;
+    const PER_THREAD: usize = 10;
+    let barrier = Arc::new(Barrier::new(THREADS));
+    let handles: Vec<_> = (0..THREADS)
+        .map(|_| {
+            let (path, project_id, product_id, barrier) = (
+                path.clone(),
+                project_id.clone(),
+                product_id.clone(),
+                barrier.clone(),
+            );
+            std::thread::spawn(move || {
+                let conn = Connection::open(&path).unwrap();
+                (0..PER_THREAD)
+                    .map(|_| {
+                        barrier.wait();
+                        create_test_license(&conn, &project_id, &product_id, None)
+                            .reference_number
+                            .unwrap()
+                    })
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect();
+    let numbers: HashSet<String> = handles
+        .into_iter()
+        .flat_map(|handle| handle.join().unwrap())
+        .collect();
+
+    let expected: HashSet<String> = (1..=THREADS * PER_THREAD)
+        .map(|n| format!("PC-{:06}", n))
+        .collect();
+    assert_eq!(numbers, expected, "no number is repeated or skipped");
+}
+
 #[test]
 fn test_extend_license_expiration() {
     let mut conn = setup_test_db();
@@ -584,8 +701,8 @@ fn test_mark_activation_code_used() {
 #[test]
 fn test_email_hash_consistency() {
     // Same email should always produce the same hash
-    let hash1 = test_email_hasher().hash("test@example.com");
-    let hash2 = test_email_hasher().hash("test@example.com");
+    let hash1 = test_email_hasher().hash(&parse_email("test@example.com"));
+    let hash2 = test_email_hasher().hash(&parse_email("test@example.com"));
     assert_eq!(
         hash1, hash2,
         "same email should always produce the same hash"
@@ -595,16 +712,16 @@ fn test_email_hash_consistency() {
 #[test]
 fn test_email_hash_case_insensitive() {
     // Email hashing should be case-insensitive
-    let hash1 = test_email_hasher().hash("Test@Example.COM");
-    let hash2 = test_email_hasher().hash("test@example.com");
+    let hash1 = test_email_hasher().hash(&parse_email("Test@Example.COM"));
+    let hash2 = test_email_hasher().hash(&parse_email("test@example.com"));
     assert_eq!(hash1, hash2, "email hashing should be case-insensitive");
 }
 
 #[test]
 fn test_email_hash_trims_whitespace() {
     // Email hashing should trim whitespace
-    let hash1 = test_email_hasher().hash("  test@example.com  ");
-    let hash2 = test_email_hasher().hash("test@example.com");
+    let hash1 = test_email_hasher().hash(&parse_email("  test@example.com  "));
+    let hash2 = test_email_hasher().hash(&parse_email("test@example.com"));
     assert_eq!(
         hash1, hash2,
         "email hashing should trim leading and trailing whitespace"
@@ -628,8 +745,8 @@ fn test_email_hash_unicode_normalization() {
     // NFD form: e + combining acute accent (U+0065 U+0301)
     let email_nfd = "cafe\u{0301}@example.com";
 
-    let hash_nfc = test_email_hasher().hash(email_nfc);
-    let hash_nfd = test_email_hasher().hash(email_nfd);
+    let hash_nfc = test_email_hasher().hash(&parse_email(email_nfc));
+    let hash_nfd = test_email_hasher().hash(&parse_email(email_nfd));
 
     assert_eq!(
         hash_nfc, hash_nfd,
@@ -640,8 +757,8 @@ fn test_email_hash_unicode_normalization() {
     let email_nfc_es = "se\u{00F1}or@example.com"; // ñ as U+00F1
     let email_nfd_es = "sen\u{0303}or@example.com"; // n + combining tilde
     assert_eq!(
-        test_email_hasher().hash(email_nfc_es),
-        test_email_hasher().hash(email_nfd_es),
+        test_email_hasher().hash(&parse_email(email_nfc_es)),
+        test_email_hasher().hash(&parse_email(email_nfd_es)),
         "Unicode normalization should work for ñ"
     );
 }
@@ -0,0 +1,184 @@
+//! Pagination ordering tests: rows sharing a timestamp must appear on exactly
+//! one page when walking a paginated query.
+
+#[path = "../common/mod.rs"]
+mod common;
+
+use std::collections::HashSet;
+
+use common::*;
+use paycheck::pagination::{MAX_LIMIT, PaginationQuery};
+use rusqlite::Connection;
+
+const ROW_COUNT: usize = 25;
+const PAGE_SIZE: i64 = 10;
+const SHARED_TIMESTAMP: i64 = 1_700_000_000;
+
+/// Give every row in `table` the same timestamp (as bulk creation does).
+fn flatten_timestamps(conn: &Connection, table: &str, column: &str) {
+    conn.execute(
+        &format!("UPDATE {} SET {} = ?1", table, column),
+        [SHARED_TIMESTAMP],
+    )
+    .unwrap();
+}
+
+/// Walk every page of a paginated query and assert each expected ID is seen
+/// exactly once. `fetch(limit, offset)` returns (page IDs, total).
+fn assert_pages_cover_once(expected: &[String], fetch: impl Fn(i64, i64) -> (Vec<String>, i64)) {
+    let mut seen = Vec::new();
+    let mut offset = 0;
+    loop {
+        let (ids, total) = fetch(PAGE_SIZE, offset);
+        assert_eq!(total, expected.len() as i64, "total should count all rows");
+        if ids.is_empty() {
+            break;
+        }
+        assert!(ids.len() as i64 <= PAGE_SIZE);
+        seen.extend(ids);
+        offset += PAGE_SIZE;
+    }
+
+    let unique: HashSet<&String> = seen.iter().collect();
+    assert_eq!(
+        unique.len(),
+        seen.len(),
+        "a row appeared on more than one page"
+    );
+    assert_eq!(
+        unique,
+        expected.iter().collect::<HashSet<_>>(),
+        "every row should appear on exactly one page"
+    );
+}
+
+#[test]
+fn test_licenses_paginate_stably_with_identical_timestamps() {
+    let conn = setup_test_db();
+    let master_key = test_master_key();
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "Test Project", &master_key);
+    let product = create_test_product(&conn, &project.id, "Pro", "pro");
+
+    let expected: Vec<String> = (0..ROW_COUNT)
+        .map(|_| create_test_license(&conn, &project.id, &product.id, None).id)
+        .collect();
+    flatten_timestamps(&conn, "licenses", "created_at");
+
+    assert_pages_cover_once(&expected, |limit, offset| {
+        let (items, total) =
+            queries::list_licenses_for_project_paginated(&conn, &project.id, limit, offset)
+                .unwrap();
+        (items.into_iter().map(|l| l.license.id).collect(), total)
+    });
+}
+
+#[test]
+fn test_org_members_paginate_stably_with_identical_timestamps() {
+    let mut conn = setup_test_db();
+    let org = create_test_org(&conn, "Test Org");
+
+    let expected: Vec<String> = (0..ROW_COUNT)
+        .map(|i| {
+            let (_, member, _) = create_test_org_member(
+                &mut conn,
+                &org.id,
+                &format!("member{}@example.com", i),
+                OrgMemberRole::Member,
+            );
+            member.id
+        })
+        .collect();
+    flatten_timestamps(&conn, "org_members", "created_at");
+
+    let emails = test_email_column();
+    assert_pages_cover_once(&expected, |limit, offset| {
+        let (items, total) =
+            queries::list_org_members_with_user_paginated(&conn, &org.id, limit, offset, &emails)
+                .unwrap();
+        (items.into_iter().map(|m| m.id).collect(), total)
+    });
+    assert_pages_cover_once(&expected, |limit, offset| {
+        let (items, total) =
+            queries::list_org_members_paginated(&conn, &org.id, limit, offset).unwrap();
+        (items.into_iter().map(|m| m.id).collect(), total)
+    });
+}
+
+#[test]
+fn test_products_paginate_stably_with_identical_timestamps() {
+    let conn = setup_test_db();
+    let master_key = test_master_key();
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "Test Project", &master_key);
+
+    let expected: Vec<String> = (0..ROW_COUNT)
+        .map(|i| create_test_product(&conn, &project.id, &format!("Product {}", i), "pro").id)
+        .collect();
+    flatten_timestamps(&conn, "products", "created_at");
+
+    assert_pages_cover_once(&expected, |limit, offset| {
+        let (items, total) =
+            queries::list_products_for_project_paginated(&conn, &project.id, limit, offset)
+                .unwrap();
+        (items.into_iter().map(|p| p.id).collect(), total)
+    });
+}
+
+#[test]
+fn test_audit_logs_paginate_stably_with_identical_timestamps() {
+    let conn = setup_test_audit_db();
+
+    let expected: Vec<String> = (0..ROW_COUNT)
+        .map(|i| {
+            queries::create_audit_log(
+                &conn,
+                true,
+                ActorType::System,
+                None,
+                "test_action",
+                "license",
+                &format!("resource-{}", i),
+                None,
+                None,
+                None,
+                None,
+                None,
+                &AuditLogNames::default(),
+                None,
+                None,
+                None,
+            )
+            .unwrap()
+            .id
+        })
+        .collect();
+    flatten_timestamps(&conn, "audit_logs", "timestamp");
+
+    assert_pages_cover_once(&expected, |limit, offset| {
+        let query: AuditLogQuery =
+            serde_json::from_value(serde_json::json!({ "limit": limit, "offset": offset }))
+                .unwrap();
+        let (items, total) = queries::query_audit_logs(&conn, &query).unwrap();
+        (items.into_iter().map(|l| l.id).collect(), total)
+    });
+}
+
+#[test]
+fn test_limit_clamped_consistently() {
+    let pagination = |limit: Option<i64>| PaginationQuery {
+        limit,
+        offset: None,
+    };
+    assert_eq!(pagination(None).limit(), 50);
+    assert_eq!(pagination(Some(0)).limit(), 1);
+    assert_eq!(pagination(Some(-5)).limit(), 1);
+    assert_eq!(pagination(Some(1000)).limit(), MAX_LIMIT);
+
+    // Endpoint-specific query types share the same clamp
+    let audit: AuditLogQuery =
+        serde_json::from_value(serde_json::json!({ "limit": 1000 })).unwrap();
+    assert_eq!(audit.limit(), MAX_LIMIT);
+    let audit: AuditLogQuery = serde_json::from_value(serde_json::json!({ "offset": -3 })).unwrap();
+    assert_eq!(audit.offset(), 0);
+}
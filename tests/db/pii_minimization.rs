@@ -0,0 +1,297 @@
+//! Tests for user email storage with and without PII minimization
+
+#[path = "../common/mod.rs"]
+mod common;
+
+use common::*;
+use rusqlite::Connection;
+
+/// Raw (email, email_hash) as stored in the users table.
+fn stored_email(conn: &Connection, user_id: &str) -> (String, Option<String>) {
+    conn.query_row(
+        "SELECT email, email_hash FROM users WHERE id = ?1",
+        [user_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .expect("User row not found")
+}
+
+fn create_user_with(conn: &Connection, email: &str, emails: &EmailColumn) -> User {
+    queries::create_user(
+        conn,
+        &CreateUser {
+            email: email.to_string(),
+            name: "Test User".to_string(),
+        },
+        emails,
+    )
+    .expect("Failed to create user")
+}
+
+// ============ Plaintext Mode ============
+
+#[test]
+fn test_plaintext_mode_stores_email_and_hash() {
+    let conn = setup_test_db();
+    let emails = test_email_column();
+
+    let user = create_user_with(&conn, "Alice@Example.com", &emails);
+    assert_eq!(user.email, "alice@example.com");
+
+    let (stored, hash) = stored_email(&conn, &user.id);
+    assert_eq!(
+        stored, "alice@example.com",
+        "plaintext mode stores email as-is"
+    );
+    assert_eq!(
+        hash,
+        Some(emails.hash(&parse_email("alice@example.com"))),
+        "hash column should be written in plaintext mode too"
+    );
+}
+
+#[test]
+fn test_plaintext_mode_finds_legacy_rows_without_hash() {
+    let conn = setup_test_db();
+    let emails = test_email_column();
+    let user = create_user_with(&conn, "legacy@example.com", &emails);
+    conn.execute("UPDATE users SET email_hash = NULL", [])
+        .unwrap();
+
+    let found = queries::get_user_by_email(&conn, "legacy@example.com", &emails)
+        .unwrap()
+        .expect("legacy row should match on the plaintext column");
+    assert_eq!(found.id, user.id);
+
+    let backfilled = queries::backfill_user_email_hashes(&conn, &emails).unwrap();
+    assert_eq!(backfilled, 1);
+    assert_eq!(
+        stored_email(&conn, &user.id).1,
+        Some(emails.hash(&parse_email("legacy@example.com")))
+    );
+    assert_eq!(
+        queries::backfill_user_email_hashes(&conn, &emails).unwrap(),
+        0,
+        "backfill should be a no-op once hashes exist"
+    );
+}
+
+// ============ Minimized Mode ============
+
+#[test]
+fn test_minimized_mode_stores_ciphertext() {
+    let conn = setup_test_db();
+    let emails = test_minimized_email_column();
+
+    let user = create_user_with(&conn, "bob@example.com", &emails);
+    assert_eq!(user.email, "bob@example.com", "caller gets plaintext back");
+
+    let (stored, hash) = stored_email(&conn, &user.id);
+    assert!(
+        stored.starts_with("enc1:"),
+        "email should be encrypted at rest, got {}",
+        stored
+    );
+    assert!(!stored.contains("bob@example.com"));
+    assert_eq!(hash, Some(emails.hash(&parse_email("bob@example.com"))));
+}
+
+#[test]
+fn test_minimized_mode_lookups_return_plaintext() {
+    let conn = setup_test_db();
+    let emails = test_minimized_email_column();
+    let user = create_user_with(&conn, "carol@example.com", &emails);
+
+    let by_email = queries::get_user_by_email(&conn, "  CAROL@example.com ", &emails)
+        .unwrap()
+        .expect("hash lookup should normalize input");
+    assert_eq!(by_email.id, user.id);
+    assert_eq!(by_email.email, "carol@example.com");
+
+    let by_id = queries::get_user_by_id(&conn, &user.id, &emails)
+        .unwrap()
+        .unwrap();
+    assert_eq!(by_id.email, "carol@example.com");
+
+    let listed = queries::list_users(&conn, &emails).unwrap();
+    assert_eq!(listed[0].email, "carol@example.com");
+
+    let with_roles = queries::get_user_with_roles(&conn, &user.id, &emails)
+        .unwrap()
+        .unwrap();
+    assert_eq!(with_roles.email, "carol@example.com");
+}
+
+#[test]
+fn test_minimized_mode_rejects_duplicate_email() {
+    let conn = setup_test_db();
+    let emails = test_minimized_email_column();
+    create_user_with(&conn, "dup@example.com", &emails);
+
+    let result = queries::create_user(
+        &conn,
+        &CreateUser {
+            email: "DUP@example.com".to_string(),
+            name: "Duplicate".to_string(),
+        },
+        &emails,
+    );
+    assert!(
+        result.is_err(),
+        "unique hash index should reject the same email even though ciphertexts differ"
+    );
+}
+
+#[test]
+fn test_minimized_mode_update_email_reseals() {
+    let conn = setup_test_db();
+    let emails = test_minimized_email_column();
+    let user = create_user_with(&conn, "old@example.com", &emails);
+
+    let updated = queries::update_user(
+        &conn,
+        &user.id,
+        &UpdateUser {
+            email: Some("new@example.com".to_string()),
+            name: None,
+        },
+        &emails,
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(updated.email, "new@example.com");
+
+    let (stored, hash) = stored_email(&conn, &user.id);
+    assert!(stored.starts_with("enc1:"));
+    assert_eq!(hash, Some(emails.hash(&parse_email("new@example.com"))));
+    assert!(
+        queries::get_user_by_email(&conn, "old@example.com", &emails)
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[test]
+fn test_minimized_mode_member_listings_return_plaintext() {
+    let mut conn = setup_test_db();
+    let emails = test_minimized_email_column();
+    let org = create_test_org(&conn, "Test Org");
+    let user = create_user_with(&conn, "member@example.com", &emails);
+    queries::create_org_member(
+        &conn,
+        &org.id,
+        &CreateOrgMember {
+            user_id: user.id.clone(),
+            role: OrgMemberRole::Owner,
+        },
+    )
+    .unwrap();
+    let (operator, _) = create_test_operator(&mut conn, "ops@example.com", OperatorRole::Admin);
+
+    let members = queries::list_org_members_with_user(&conn, &org.id, &emails).unwrap();
+    assert_eq!(members.len(), 1);
+    assert_eq!(members[0].email, "member@example.com");
+
+    let modifiers = queries::get_org_modifiers(&conn, &org.id, &emails).unwrap();
+    let listed: Vec<(&str, &str)> = modifiers
+        .iter()
+        .map(|m| (m.access_type.as_str(), m.email.as_str()))
+        .collect();
+    assert_eq!(
+        listed,
+        vec![
+            ("operator", "ops@example.com"),
+            ("owner", "member@example.com")
+        ],
+        "modifiers should be sorted by access type, then decrypted email"
+    );
+    assert_eq!(modifiers[0].user_id, operator.id);
+}
+
+// ============ Migration and Rotation ============
+
+#[test]
+fn test_encrypt_user_emails_converts_plaintext_rows() {
+    let mut conn = setup_test_db();
+    let plaintext = test_email_column();
+    let minimized = test_minimized_email_column();
+    let first = create_user_with(&conn, "first@example.com", &plaintext);
+    let second = create_user_with(&conn, "second@example.com", &plaintext);
+    // Deleted users are converted too
+    queries::soft_delete_user(&conn, &second.id).unwrap();
+    conn.execute(
+        "UPDATE users SET email_hash = NULL WHERE id = ?1",
+        [&first.id],
+    )
+    .unwrap();
+
+    let count = queries::encrypt_user_emails(&mut conn, &minimized).unwrap();
+    assert_eq!(count, 2);
+
+    for user in [&first, &second] {
+        let (stored, hash) = stored_email(&conn, &user.id);
+        assert!(stored.starts_with("enc1:"), "{} not encrypted", user.email);
+        assert_eq!(hash, Some(minimized.hash(&parse_email(&user.email))));
+    }
+
+    let found = queries::get_user_by_email(&conn, "first@example.com", &minimized)
+        .unwrap()
+        .unwrap();
+    assert_eq!(found.email, "first@example.com");
+    let deleted = queries::get_deleted_user_by_id(&conn, &second.id, &minimized)
+        .unwrap()
+        .unwrap();
+    assert_eq!(deleted.email, "second@example.com");
+
+    assert_eq!(
+        queries::encrypt_user_emails(&mut conn, &minimized).unwrap(),
+        0,
+        "already-encrypted rows should be skipped"
+    );
+}
+
+#[test]
+fn test_plaintext_mode_reads_encrypted_rows() {
+    // Turning minimization off again must not break existing encrypted rows
+    let conn = setup_test_db();
+    let user = create_user_with(&conn, "sealed@example.com", &test_minimized_email_column());
+
+    let found = queries::get_user_by_email(&conn, "sealed@example.com", &test_email_column())
+        .unwrap()
+        .unwrap();
+    assert_eq!(found.id, user.id);
+    assert_eq!(found.email, "sealed@example.com");
+}
+
+#[test]
+fn test_rotate_user_emails_reencrypts_with_new_key() {
+    let conn = setup_test_db();
+    let old_key = MasterKey::from_bytes([1u8; 32]);
+    let new_key = MasterKey::from_bytes([2u8; 32]);
+    let old_emails = EmailColumn::new(old_key.clone(), test_email_hasher(), true);
+    let new_emails = EmailColumn::new(new_key.clone(), test_email_hasher(), true);
+
+    let sealed = create_user_with(&conn, "rotate@example.com", &old_emails);
+    let plain = create_user_with(&conn, "plain@example.com", &test_email_column());
+    let before = stored_email(&conn, &sealed.id);
+
+    let count = queries::rotate_user_emails(&conn, &old_key, &new_key).unwrap();
+    assert_eq!(count, 1, "only encrypted rows need rotation");
+
+    let after = stored_email(&conn, &sealed.id);
+    assert_ne!(before.0, after.0);
+    assert_eq!(
+        before.1, after.1,
+        "hash is unaffected by master key rotation"
+    );
+
+    let found = queries::get_user_by_email(&conn, "rotate@example.com", &new_emails)
+        .unwrap()
+        .unwrap();
+    assert_eq!(found.email, "rotate@example.com");
+    assert!(
+        queries::get_user_by_id(&conn, &sealed.id, &old_emails).is_err(),
+        "old key should no longer decrypt"
+    );
+    assert_eq!(stored_email(&conn, &plain.id).0, "plain@example.com");
+}
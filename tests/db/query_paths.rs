@@ -0,0 +1,445 @@
+//! Every public item of `db::queries` stays reachable at its `db::queries::*`
+//! path. The queries live in per-domain submodules that are re-exported from
+//! `db::queries`; this fails to compile if one stops being re-exported.
+
+use paycheck::db::queries::{
+    self, DeviceAcquisitionResult, OrgDeleteCounts, OrgModifier, ProductWithProviderLinks,
+    PurgeResult,
+};
+
+#[test]
+fn test_query_types_keep_their_paths() {
+    let _: Option<DeviceAcquisitionResult> = None;
+    let _: Option<OrgDeleteCounts> = None;
+    let _: Option<OrgModifier> = None;
+    let _: Option<ProductWithProviderLinks> = None;
+    let _: Option<PurgeResult> = None;
+}
+
+#[test]
+fn test_query_functions_keep_their_paths() {
+    // Users
+    let _ = queries::create_user;
+    let _ = queries::get_user_by_id;
+    let _ = queries::get_user_by_email;
+    let _ = queries::list_users;
+    let _ = queries::list_users_paginated;
+    let _ = queries::update_user;
+    let _ = queries::delete_user;
+    let _ = queries::soft_delete_user;
+    let _ = queries::cascade_delete_user_project_members;
+    let _ = queries::get_deleted_user_by_id;
+    let _ = queries::restore_user;
+    let _ = queries::backfill_user_email_hashes;
+    let _ = queries::encrypt_user_emails;
+    let _ = queries::rotate_user_emails;
+    let _ = queries::get_user_with_roles;
+    let _ = queries::list_users_with_roles_paginated;
+
+    // User merges
+    let _ = queries::merge_users;
+    let _ = queries::merge_project_memberships;
+
+    // Operators
+    let _ = queries::grant_operator_role;
+    let _ = queries::revoke_operator_role;
+    let _ = queries::update_operator_role;
+    let _ = queries::list_operators;
+    let _ = queries::list_operators_paginated;
+    let _ = queries::count_operators;
+
+    // Operator Org Scopes
+    let _ = queries::add_operator_org_scope;
+    let _ = queries::remove_operator_org_scope;
+    let _ = queries::list_operator_org_scopes;
+    let _ = queries::operator_has_org_scope;
+
+    // API Keys (Unified)
+    let _ = queries::generate_api_key;
+    let _ = queries::get_user_by_api_key;
+    let _ = queries::create_api_key;
+    let _ = queries::insert_api_key;
+    let _ = queries::list_api_keys;
+    let _ = queries::list_api_keys_paginated;
+    let _ = queries::get_api_key_by_id;
+    let _ = queries::get_api_key_scopes;
+    let _ = queries::get_api_key_scopes_batch;
+    let _ = queries::revoke_api_key;
+    let _ = queries::api_key_has_scopes;
+    let _ = queries::get_api_key_access_level;
+    let _ = queries::check_api_key_scope;
+    let _ = queries::get_api_key_org_level_access;
+
+    // Audit Logs
+    let _ = queries::create_audit_log;
+    let _ = queries::new_audit_log;
+    let _ = queries::insert_audit_log;
+    let _ = queries::query_audit_logs;
+    let _ = queries::query_audit_logs_page;
+    let _ = queries::list_org_audit_logs_page;
+    let _ = queries::get_orgs_last_audit_at;
+    let _ = queries::list_recent_project_activity;
+
+    // Organizations
+    let _ = queries::create_organization;
+    let _ = queries::get_organization_by_id;
+    let _ = queries::list_organizations;
+    let _ = queries::count_organizations;
+    let _ = queries::list_organizations_paginated;
+    let _ = queries::list_organizations_with_stats_paginated;
+    let _ = queries::get_organization_with_stats;
+    let _ = queries::get_org_tenant_stats;
+    let _ = queries::update_organization;
+    let _ = queries::clear_org_payment_provider;
+
+    // Org Data Keys
+    let _ = queries::org_data_key;
+    let _ = queries::set_org_data_key;
+    let _ = queries::list_org_data_keys;
+    let _ = queries::list_orgs_without_data_key;
+    let _ = queries::rewrap_org_data_keys;
+    let _ = queries::migrate_org_data_key;
+
+    // Organization Service Configs
+    let _ = queries::get_org_service_config;
+    let _ = queries::get_org_service_configs;
+    let _ = queries::get_org_service_configs_by_category;
+    let _ = queries::org_has_service_config;
+    let _ = queries::upsert_org_service_config;
+    let _ = queries::delete_org_service_config;
+    let _ = queries::list_all_org_service_configs;
+    let _ = queries::update_org_service_config_encrypted;
+
+    // Service Config Helper Functions
+    let _ = queries::get_org_stripe_config;
+    let _ = queries::get_org_ls_config;
+    let _ = queries::get_org_resend_api_key;
+    let _ = queries::delete_organization;
+    let _ = queries::soft_delete_organization;
+    let _ = queries::get_deleted_organization_by_id;
+    let _ = queries::restore_organization;
+
+    // Org Limits
+    let _ = queries::get_org_limits;
+    let _ = queries::get_org_limit;
+    let _ = queries::set_org_limit;
+    let _ = queries::get_max_products_per_project;
+    let _ = queries::count_products_for_project;
+    let _ = queries::count_org_licenses_created_since;
+
+    // Org Members
+    let _ = queries::create_org_member;
+    let _ = queries::get_org_member_by_id;
+    let _ = queries::get_org_member_with_user_by_id;
+    let _ = queries::get_org_member_by_user_and_org;
+    let _ = queries::get_org_member_with_user_by_user_and_org;
+    let _ = queries::list_orgs_by_user_id;
+    let _ = queries::list_org_members;
+    let _ = queries::list_org_members_with_user;
+    let _ = queries::list_org_members_paginated;
+    let _ = queries::list_org_members_with_user_paginated;
+    let _ = queries::update_org_member;
+    let _ = queries::delete_org_member;
+    let _ = queries::soft_delete_org_member;
+    let _ = queries::schedule_org_member_removal;
+    let _ = queries::cancel_org_member_removal;
+    let _ = queries::list_due_org_member_removals;
+    let _ = queries::get_deleted_org_member_by_id;
+    let _ = queries::get_deleted_org_member_by_user_and_org;
+    let _ = queries::restore_org_member;
+
+    // Org Access Helpers
+    let _ = queries::get_org_modifiers;
+    let _ = queries::can_user_modify_org;
+
+    // Projects
+    let _ = queries::create_project;
+    let _ = queries::get_project_by_id;
+    let _ = queries::list_projects_for_org;
+    let _ = queries::list_projects_for_org_paginated;
+    let _ = queries::list_accessible_projects_for_member_paginated;
+    let _ = queries::list_all_projects;
+    let _ = queries::decrypt_project_private_key;
+    let _ = queries::update_project_private_key;
+    let _ = queries::decrypt_project_webhook_secret;
+    let _ = queries::update_project_webhook_secret;
+    let _ = queries::set_project_webhook_mirror;
+    let _ = queries::update_project;
+    let _ = queries::set_project_client_flags;
+    let _ = queries::delete_project;
+    let _ = queries::soft_delete_project;
+    let _ = queries::get_deleted_project_by_id;
+    let _ = queries::restore_project;
+    let _ = queries::get_project_by_public_key;
+
+    // Project Members
+    let _ = queries::create_project_member;
+    let _ = queries::get_project_member;
+    let _ = queries::get_project_member_user_id;
+    let _ = queries::get_project_member_by_id;
+    let _ = queries::get_project_member_by_user_and_project;
+    let _ = queries::list_project_members;
+    let _ = queries::list_project_members_paginated;
+    let _ = queries::update_project_member;
+    let _ = queries::soft_delete_project_member;
+
+    // Temporary Role Grants
+    let _ = queries::create_temporary_role_grant;
+    let _ = queries::get_temporary_role_grant;
+    let _ = queries::get_active_temporary_role_grant;
+    let _ = queries::list_temporary_role_grants_paginated;
+    let _ = queries::revoke_temporary_role_grant;
+
+    // Terms
+    let _ = queries::create_terms;
+    let _ = queries::get_terms;
+    let _ = queries::list_terms;
+    let _ = queries::get_current_terms;
+    let _ = queries::terms_accepted_by_license;
+    let _ = queries::update_terms;
+    let _ = queries::create_artifact;
+    let _ = queries::get_artifact;
+    let _ = queries::list_artifacts;
+    let _ = queries::delete_artifact;
+    let _ = queries::record_artifact_download;
+    let _ = queries::list_artifact_downloads;
+
+    // Custom domains
+    let _ = queries::create_custom_domain;
+    let _ = queries::get_custom_domain;
+    let _ = queries::get_verified_custom_domain_project_id;
+    let _ = queries::list_custom_domains_for_project;
+    let _ = queries::mark_custom_domain_verified;
+    let _ = queries::delete_custom_domain;
+
+    // Products
+    let _ = queries::create_product;
+    let _ = queries::get_product_by_id;
+    let _ = queries::get_products_by_ids;
+    let _ = queries::list_products_for_project;
+    let _ = queries::list_products_for_project_paginated;
+    let _ = queries::update_product;
+    let _ = queries::product_has_updates_renewals;
+    let _ = queries::list_updates_renewals_for_product;
+    let _ = queries::list_upsells_to_product;
+    let _ = queries::delete_product;
+    let _ = queries::soft_delete_product;
+    let _ = queries::get_deleted_product_by_id;
+    let _ = queries::get_deleted_product_by_name;
+    let _ = queries::restore_product;
+
+    // Product Provider Links
+    let _ = queries::create_provider_link;
+    let _ = queries::get_provider_link;
+    let _ = queries::get_provider_link_by_id;
+    let _ = queries::get_provider_links_for_product;
+    let _ = queries::update_provider_link;
+    let _ = queries::delete_provider_link;
+    let _ = queries::get_product_with_links;
+    let _ = queries::list_products_with_links;
+    let _ = queries::list_products_with_links_paginated;
+
+    // Product Templates
+    let _ = queries::create_product_template;
+    let _ = queries::get_product_template;
+    let _ = queries::list_product_templates;
+    let _ = queries::update_product_template;
+    let _ = queries::delete_product_template;
+    let _ = queries::create_products_from_templates;
+
+    // Licenses
+    let _ = queries::generate_activation_code;
+    let _ = queries::create_license;
+    let _ = queries::resolve_license_id;
+    let _ = queries::resolve_admin_license_id;
+    let _ = queries::get_license_by_id;
+    let _ = queries::get_license_by_email_hash;
+    let _ = queries::get_licenses_by_email_hash;
+    let _ = queries::free_license_claimed;
+    let _ = queries::get_all_licenses_by_email_hash_for_admin_paginated;
+    let _ = queries::list_licenses_for_project_paginated;
+    let _ = queries::list_licenses_for_project;
+    let _ = queries::increment_activation_count;
+    let _ = queries::revoke_license;
+    let _ = queries::soft_delete_license;
+    let _ = queries::get_deleted_license_by_id;
+    let _ = queries::restore_license;
+    let _ = queries::add_revoked_jti;
+    let _ = queries::revoke_device_jtis;
+    let _ = queries::is_jti_revoked;
+    let _ = queries::list_revoked_jtis;
+    let _ = queries::get_licenses_by_payment_order_id_paginated;
+    let _ = queries::get_licenses_by_reference_number_paginated;
+    let _ = queries::get_licenses_by_customer_id_paginated;
+    let _ = queries::list_flagged_licenses_paginated;
+    let _ = queries::list_dormant_licenses_paginated;
+    let _ = queries::mark_dormant_licenses;
+    let _ = queries::flag_license_abuse;
+    let _ = queries::record_license_seen_version;
+    let _ = queries::get_license_by_subscription;
+    let _ = queries::update_license_email_hash;
+    let _ = queries::extend_license_expiration;
+    let _ = queries::pause_license;
+    let _ = queries::resume_license;
+
+    // Activation Codes
+    let _ = queries::create_activation_code;
+    let _ = queries::create_activation_code_for_seat;
+    let _ = queries::get_activation_code_by_code;
+    let _ = queries::try_claim_activation_code;
+    let _ = queries::mark_activation_code_used;
+    let _ = queries::cleanup_expired_activation_codes;
+
+    // Devices
+    let _ = queries::acquire_device_atomic;
+    let _ = queries::create_device;
+    let _ = queries::get_device_by_jti;
+    let _ = queries::get_device_for_license;
+    let _ = queries::list_devices_for_license;
+    let _ = queries::list_devices_by_filter;
+    let _ = queries::count_devices_for_license;
+    let _ = queries::count_active_devices_for_license;
+    let _ = queries::update_device_last_seen;
+    let _ = queries::update_device_jti;
+    let _ = queries::delete_device;
+
+    // Usage Geography
+    let _ = queries::record_usage_geo;
+    let _ = queries::get_usage_geo_days;
+
+    // License Seats
+    let _ = queries::assign_license_seat;
+    let _ = queries::list_license_seats;
+    let _ = queries::get_active_license_seat;
+    let _ = queries::get_active_seats_by_email_hash;
+    let _ = queries::count_devices_for_seat;
+    let _ = queries::remove_license_seat;
+
+    // Share Links
+    let _ = queries::generate_share_token;
+    let _ = queries::create_share_link;
+    let _ = queries::get_share_link_by_token_hash;
+    let _ = queries::get_share_link;
+    let _ = queries::revoke_share_link;
+
+    // Prepaid Codes
+    let _ = queries::generate_prepaid_code;
+    let _ = queries::create_prepaid_code_batch;
+    let _ = queries::get_prepaid_code_batch;
+    let _ = queries::list_prepaid_code_batches;
+    let _ = queries::take_prepaid_codes;
+    let _ = queries::revoke_prepaid_code_batch;
+    let _ = queries::try_claim_prepaid_code;
+    let _ = queries::set_prepaid_code_license;
+    let _ = queries::list_pending_prepaid_codes;
+    let _ = queries::update_pending_prepaid_codes;
+
+    // Bulk License Jobs
+    let _ = queries::create_bulk_job;
+    let _ = queries::get_bulk_job;
+    let _ = queries::list_bulk_job_chunks;
+    let _ = queries::record_bulk_job_chunk;
+    let _ = queries::record_bulk_job_chunk_error;
+    let _ = queries::cancel_bulk_job;
+    let _ = queries::finish_bulk_job;
+    let _ = queries::fail_bulk_jobs;
+
+    // License Upgrades
+    let _ = queries::create_license_upgrade;
+    let _ = queries::get_upgrade_replacing_license;
+    let _ = queries::get_upgrade_creating_license;
+    let _ = queries::end_license_updates;
+    let _ = queries::apply_license_update_renewal;
+    let _ = queries::list_license_update_renewals;
+
+    // Trial Conversions
+    let _ = queries::mark_license_trial;
+    let _ = queries::find_unconverted_trial_for_buyer;
+    let _ = queries::convert_trial_license;
+    let _ = queries::get_trial_conversion_stats;
+
+    // License Tags
+    let _ = queries::add_license_tags;
+    let _ = queries::remove_license_tags;
+    let _ = queries::list_license_tags;
+    let _ = queries::attach_license_tags;
+    let _ = queries::list_project_license_tags;
+    let _ = queries::list_license_version_counts;
+    let _ = queries::list_licenses_by_tag_paginated;
+    let _ = queries::bulk_add_license_tags;
+
+    // Entitlements
+    let _ = queries::get_license_grants_by_customer_ids;
+    let _ = queries::get_license_grants_by_email_hash;
+
+    // Email Log
+    let _ = queries::record_email_attempt;
+    let _ = queries::list_recent_emails_for_license;
+    let _ = queries::list_project_email_log_paginated;
+
+    // Payment Sessions
+    let _ = queries::create_payment_session;
+    let _ = queries::get_payment_session;
+    let _ = queries::try_claim_payment_session;
+    let _ = queries::set_payment_session_license;
+    let _ = queries::mark_payment_session_for_review;
+    let _ = queries::generate_claim_code;
+    let _ = queries::issue_payment_session_claim;
+    let _ = queries::try_claim_payment_session_code;
+    let _ = queries::release_payment_session_claim;
+    let _ = queries::set_license_checkout_fields;
+    let _ = queries::set_license_accepted_terms;
+    let _ = queries::set_license_metadata;
+    let _ = queries::get_license_by_provider_payment;
+    let _ = queries::get_license_by_provider_order;
+    let _ = queries::purge_old_payment_sessions;
+
+    // Webhook Event Deduplication
+    let _ = queries::try_record_webhook_event;
+    let _ = queries::purge_old_webhook_events;
+
+    // Audit Log Maintenance
+    let _ = queries::purge_old_audit_logs;
+    let _ = queries::list_org_audit_retention;
+    let _ = queries::update_org_audit_retention;
+
+    // Soft Delete Maintenance
+    let _ = queries::purge_soft_deleted_records;
+    let _ = queries::get_system_config;
+    let _ = queries::set_system_config;
+    let _ = queries::delete_system_config;
+    let _ = queries::create_org_database;
+    let _ = queries::list_org_databases;
+    let _ = queries::delete_org_database;
+    let _ = queries::create_project_route;
+    let _ = queries::get_project_route_org_id;
+    let _ = queries::get_public_key_route_org_id;
+
+    // Subscription Reconciliation
+    let _ = queries::list_subscription_licenses;
+    let _ = queries::create_reconciliation_run;
+    let _ = queries::get_reconciliation_run;
+
+    // Backups
+    let _ = queries::create_backup;
+    let _ = queries::get_backup;
+    let _ = queries::list_backups;
+    let _ = queries::list_backups_beyond_retention;
+    let _ = queries::record_backup_verification;
+    let _ = queries::delete_backup;
+
+    // Storage heartbeats
+    let _ = queries::get_storage_heartbeat;
+    let _ = queries::record_storage_start;
+    let _ = queries::beat_storage_heartbeat;
+    let _ = queries::save_storage_heartbeat;
+
+    // Disputes
+    let _ = queries::create_dispute;
+    let _ = queries::get_dispute_by_provider_id;
+    let _ = queries::close_dispute;
+    let _ = queries::count_open_disputes_for_license;
+    let _ = queries::set_license_suspended_for_dispute;
+    let _ = queries::list_disputes_for_license;
+    let _ = queries::list_project_disputes_paginated;
+}
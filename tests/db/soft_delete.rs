@@ -6,7 +6,8 @@ mod common;
 use common::{
     LICENSE_VALID_DAYS, ONE_MONTH, OperatorRole, OrgMemberRole, create_test_license,
     create_test_operator, create_test_org, create_test_org_member, create_test_product,
-    create_test_project, future_timestamp, now, queries, setup_test_db, test_master_key,
+    create_test_project, future_timestamp, now, queries, setup_test_db, test_email_column,
+    test_master_key,
 };
 
 // ============ Soft Delete Mechanics ============
@@ -20,14 +21,15 @@ fn test_soft_delete_user_sets_deleted_at_and_depth() {
     queries::soft_delete_user(&mut conn, &user.id).expect("Soft delete failed");
 
     // User should not be found via normal query
-    let result = queries::get_user_by_id(&mut conn, &user.id).expect("Query failed");
+    let result =
+        queries::get_user_by_id(&mut conn, &user.id, &test_email_column()).expect("Query failed");
     assert!(
         result.is_none(),
         "User should not be found after soft delete"
     );
 
     // User should be found via deleted query
-    let deleted = queries::get_deleted_user_by_id(&mut conn, &user.id)
+    let deleted = queries::get_deleted_user_by_id(&mut conn, &user.id, &test_email_column())
         .expect("Query failed")
         .expect("Deleted user should be found");
 
@@ -240,7 +242,9 @@ fn test_list_users_excludes_deleted_by_default() {
     queries::soft_delete_user(&mut conn, &user_to_delete.id).expect("Soft delete failed");
 
     // List should exclude deleted user
-    let (users, total) = queries::list_users_paginated(&mut conn, 100, 0, false).expect("Query failed");
+    let (users, total) =
+        queries::list_users_paginated(&mut conn, 100, 0, false, &test_email_column())
+            .expect("Query failed");
     assert_eq!(total, 2, "Total should be 2 (excluding deleted)");
     assert_eq!(users.len(), 2, "Should return 2 users");
     assert!(
@@ -261,7 +265,9 @@ fn test_list_users_includes_deleted_when_requested() {
     queries::soft_delete_user(&mut conn, &user_to_delete.id).expect("Soft delete failed");
 
     // List with include_deleted=true should include all users
-    let (users, total) = queries::list_users_paginated(&mut conn, 100, 0, true).expect("Query failed");
+    let (users, total) =
+        queries::list_users_paginated(&mut conn, 100, 0, true, &test_email_column())
+            .expect("Query failed");
     assert_eq!(total, 3, "Total should be 3 (including deleted)");
     assert_eq!(users.len(), 3, "Should return all 3 users");
     assert!(
@@ -330,7 +336,7 @@ fn test_restore_directly_deleted_user_succeeds_without_force() {
     );
 
     // User should be found again
-    let restored = queries::get_user_by_id(&mut conn, &user.id)
+    let restored = queries::get_user_by_id(&mut conn, &user.id, &test_email_column())
         .expect("Query failed")
         .expect("Restored user should be found");
     assert!(
@@ -653,13 +659,13 @@ fn test_hard_delete_user_completely_removes_all_data() {
 
     // User should be completely gone (not even soft deleted)
     assert!(
-        queries::get_user_by_id(&mut conn, &user.id)
+        queries::get_user_by_id(&mut conn, &user.id, &test_email_column())
             .expect("Query failed")
             .is_none(),
         "User should not be found via normal query after hard delete"
     );
     assert!(
-        queries::get_deleted_user_by_id(&mut conn, &user.id)
+        queries::get_deleted_user_by_id(&mut conn, &user.id, &test_email_column())
             .expect("Query failed")
             .is_none(),
         "User should not be found via deleted query after hard delete"
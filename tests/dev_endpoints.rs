@@ -15,6 +15,7 @@ mod common;
 use common::*;
 
 use paycheck::db::AppState;
+use paycheck::middleware::{AuthMethod, OperatorContext};
 use paycheck::models::{LemonSqueezyConfig, StripeConfig, UpdateOrganization};
 
 // ============ Operator Endpoint Tests (without auth middleware for simplicity) ============
@@ -32,10 +33,14 @@ fn operator_app_with_payment_configs() -> (Router, String) {
     let pool = Pool::builder().max_size(4).build(manager).unwrap();
 
     let org_id: String;
+    let operator: User;
     {
         let mut conn = pool.get().unwrap();
         paycheck::db::init_db(&conn).unwrap();
 
+        // Handlers still expect the context the operator middleware would add
+        operator = create_test_operator(&mut conn, "support@test.com", OperatorRole::Admin).0;
+
         // Create test data
         let org = create_test_org(&mut conn, "Test Org");
         org_id = org.id.clone();
@@ -51,6 +56,9 @@ fn operator_app_with_payment_configs() -> (Router, String) {
             ls_config: None,
             resend_api_key: None,
             payment_provider: Some(Some("stripe".to_string())),
+            public_audit_retention_days: None,
+            admin_audit_retention_days: None,
+            expected_version: None,
         };
         queries::update_organization(&conn, &org.id, &update)
             .expect("Failed to set payment provider");
@@ -68,8 +76,11 @@ fn operator_app_with_payment_configs() -> (Router, String) {
         audit: audit_pool,
         base_url: "http://localhost:3000".to_string(),
         audit_log_enabled: false,
+        audit_failure_mode: paycheck::models::AuditFailureMode::default(),
+        public_audit_retention_days: 0,
         master_key,
         email_hasher: paycheck::crypto::EmailHasher::from_bytes([0xAA; 32]),
+        pii_minimization: false,
         success_page_url: "http://localhost:3000/success".to_string(),
         activation_rate_limiter: std::sync::Arc::new(
             paycheck::rate_limit::ActivationRateLimiter::default(),
@@ -80,6 +91,20 @@ fn operator_app_with_payment_configs() -> (Router, String) {
         )),
         jwks_cache: std::sync::Arc::new(paycheck::jwt::JwksCache::new()),
         trusted_issuers: vec![],
+        org_dbs: std::sync::Arc::new(paycheck::db::OrgDbRegistry::disabled()),
+        project_misses: std::sync::Arc::new(paycheck::db::ProjectMissCache::default()),
+        clock: paycheck::util::Clock::system(),
+        provider_calls: std::sync::Arc::new(paycheck::payments::ProviderCallGovernor::default()),
+        validation_limiter: std::sync::Arc::new(paycheck::rate_limit::ValidationRateLimiter::new()),
+        free_license_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_free_licenses(),
+        ),
+        session_wait_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_session_waits(),
+        ),
+        allow_localhost_urls: false,
+        maintenance: std::sync::Arc::new(paycheck::middleware::MaintenanceMode::default()),
+        backups: None,
     };
 
     // Note: Testing without auth middleware - auth is tested separately
@@ -88,6 +113,13 @@ fn operator_app_with_payment_configs() -> (Router, String) {
             "/operators/organizations/{org_id}/payment-provider",
             get(get_org_payment_config),
         )
+        .layer(axum::Extension(OperatorContext {
+            user: operator,
+            auth_method: AuthMethod::ApiKey {
+                key_id: "test".to_string(),
+                key_prefix: "pc_test".to_string(),
+            },
+        }))
         .with_state(state);
 
     (app, org_id)
@@ -186,8 +218,11 @@ async fn test_operator_get_payment_config_no_configs() {
         audit: audit_pool,
         base_url: "http://localhost:3000".to_string(),
         audit_log_enabled: false,
+        audit_failure_mode: paycheck::models::AuditFailureMode::default(),
+        public_audit_retention_days: 0,
         master_key,
         email_hasher: paycheck::crypto::EmailHasher::from_bytes([0xAA; 32]),
+        pii_minimization: false,
         success_page_url: "http://localhost:3000/success".to_string(),
         activation_rate_limiter: std::sync::Arc::new(
             paycheck::rate_limit::ActivationRateLimiter::default(),
@@ -198,6 +233,20 @@ async fn test_operator_get_payment_config_no_configs() {
         )),
         jwks_cache: std::sync::Arc::new(paycheck::jwt::JwksCache::new()),
         trusted_issuers: vec![],
+        org_dbs: std::sync::Arc::new(paycheck::db::OrgDbRegistry::disabled()),
+        project_misses: std::sync::Arc::new(paycheck::db::ProjectMissCache::default()),
+        clock: paycheck::util::Clock::system(),
+        provider_calls: std::sync::Arc::new(paycheck::payments::ProviderCallGovernor::default()),
+        validation_limiter: std::sync::Arc::new(paycheck::rate_limit::ValidationRateLimiter::new()),
+        free_license_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_free_licenses(),
+        ),
+        session_wait_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_session_waits(),
+        ),
+        allow_localhost_urls: false,
+        maintenance: std::sync::Arc::new(paycheck::middleware::MaintenanceMode::default()),
+        backups: None,
     };
 
     let app = Router::new()
@@ -278,6 +327,7 @@ fn test_lemonsqueezy_config_masking() {
         api_key: "ls_test_key_abcdefghij".to_string(),
         store_id: "store_123".to_string(),
         webhook_secret: "ls_whsec_test_secret".to_string(),
+        verify_imported_keys: false,
     };
 
     let masked: LemonSqueezyConfigMasked = (&config).into();
@@ -45,8 +45,11 @@ fn test_app() -> Router {
         audit: audit_pool,
         base_url: "http://localhost:3000".to_string(),
         audit_log_enabled: false,
+        audit_failure_mode: paycheck::models::AuditFailureMode::default(),
+        public_audit_retention_days: 0,
         master_key,
         email_hasher: paycheck::crypto::EmailHasher::from_bytes([0xAA; 32]),
+        pii_minimization: false,
         success_page_url: "http://localhost:3000/success".to_string(),
         activation_rate_limiter: std::sync::Arc::new(
             paycheck::rate_limit::ActivationRateLimiter::default(),
@@ -57,6 +60,20 @@ fn test_app() -> Router {
         )),
         jwks_cache: std::sync::Arc::new(paycheck::jwt::JwksCache::new()),
         trusted_issuers: vec![],
+        org_dbs: std::sync::Arc::new(paycheck::db::OrgDbRegistry::disabled()),
+        project_misses: std::sync::Arc::new(paycheck::db::ProjectMissCache::default()),
+        clock: paycheck::util::Clock::system(),
+        provider_calls: std::sync::Arc::new(paycheck::payments::ProviderCallGovernor::default()),
+        validation_limiter: std::sync::Arc::new(paycheck::rate_limit::ValidationRateLimiter::new()),
+        free_license_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_free_licenses(),
+        ),
+        session_wait_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_session_waits(),
+        ),
+        allow_localhost_urls: false,
+        maintenance: std::sync::Arc::new(paycheck::middleware::MaintenanceMode::default()),
+        backups: None,
     };
 
     Router::new()
@@ -203,3 +220,174 @@ async fn test_application_error_returns_json() {
     assert!(json.get("error").is_some());
     assert_eq!(json["error"], "Not found");
 }
+
+/// Router with one admin (strict) and one public (lenient) body, and a query
+fn field_app() -> Router {
+    use axum::routing::{get, post};
+    use paycheck::extractors::{Json, PublicJson, Query};
+    use paycheck::models::CreateApiKey;
+
+    #[derive(serde::Deserialize)]
+    struct Page {
+        limit: u32,
+    }
+
+    async fn admin(Json(input): Json<CreateApiKey>) -> String {
+        input.name
+    }
+    async fn public(PublicJson(input): PublicJson<CreateApiKey>) -> String {
+        input.name
+    }
+    async fn page(Query(query): Query<Page>) -> String {
+        query.limit.to_string()
+    }
+
+    Router::new()
+        .route("/admin", post(admin))
+        .route("/public", post(public))
+        .route("/page", get(page))
+}
+
+async fn post_body(uri: &str, body: Value) -> (StatusCode, Value) {
+    let response = field_app()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+#[tokio::test]
+async fn test_body_field_error_has_nested_path() {
+    let body = serde_json::json!({
+        "name": "CI",
+        "scopes": [
+            { "org_id": "org-1", "access": "view" },
+            { "org_id": "org-2", "access": "owner" }
+        ]
+    });
+
+    let (status, json) = post_body("/admin", body).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(json["error"], "Invalid request body");
+    assert_eq!(json["code"], "invalid_body_field");
+    assert_eq!(json["field"]["path"], "scopes[1].access");
+    assert_eq!(json["field"]["value"], "\"owner\"");
+    assert!(
+        json["field"]["expected"]
+            .as_str()
+            .unwrap()
+            .contains("`view`"),
+        "{}",
+        json
+    );
+    assert!(
+        json["details"]
+            .as_str()
+            .unwrap()
+            .starts_with("scopes[1].access: unknown variant"),
+        "{}",
+        json
+    );
+}
+
+#[tokio::test]
+async fn test_body_field_error_names_missing_field() {
+    let body = serde_json::json!({
+        "name": "CI",
+        "scopes": [{ "org_id": "org-1" }]
+    });
+
+    let (status, json) = post_body("/admin", body).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(json["field"]["path"], "scopes[0].access");
+    assert!(json["field"].get("value").is_none(), "{}", json);
+}
+
+#[tokio::test]
+async fn test_body_field_error_truncates_value() {
+    let body = serde_json::json!({ "name": vec![1234; 100] });
+
+    let (status, json) = post_body("/admin", body).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(json["field"]["path"], "name");
+    assert_eq!(json["field"]["expected"], "a string");
+    let value = json["field"]["value"].as_str().unwrap();
+    assert!(value.ends_with('…'), "{}", value);
+    assert!(value.chars().count() <= 61, "{}", value);
+}
+
+#[tokio::test]
+async fn test_admin_body_rejects_unknown_fields() {
+    let body = serde_json::json!({
+        "name": "CI",
+        "scopes": [{ "org_id": "org-1", "access": "view", "acess": "admin" }]
+    });
+
+    let (status, json) = post_body("/admin", body).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(json["code"], "invalid_body_field");
+    assert_eq!(json["field"]["path"], "scopes[0].acess");
+    assert_eq!(json["field"]["value"], "\"admin\"");
+}
+
+#[tokio::test]
+async fn test_public_body_ignores_unknown_fields() {
+    let body = serde_json::json!({
+        "name": "CI",
+        "added_in_a_later_sdk": true,
+        "scopes": [{ "org_id": "org-1", "access": "view", "note": "hi" }]
+    });
+
+    let (status, _) = post_body("/public", body).await;
+
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_public_body_still_reports_field_path() {
+    let body = serde_json::json!({ "name": "CI", "expires_in_days": "soon" });
+
+    let (status, json) = post_body("/public", body).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(json["field"]["path"], "expires_in_days");
+    assert_eq!(json["field"]["value"], "\"soon\"");
+}
+
+#[tokio::test]
+async fn test_query_field_error_has_path() {
+    let response = field_app()
+        .oneshot(
+            Request::builder()
+                .uri("/page?limit=lots&extra=ignored")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["error"], "Invalid query parameters");
+    assert_eq!(json["code"], "invalid_query_field");
+    assert_eq!(json["field"]["path"], "limit");
+    assert_eq!(json["field"]["value"], "\"lots\"");
+}
@@ -0,0 +1,118 @@
+#!/usr/bin/env python3
+"""Write geoip-test.mmdb, the tiny MaxMind-format country database the
+usage geography tests look addresses up in.
+
+Records carry city and coordinates as well as the country, like a real
+City database, so the tests can check that only the country is kept.
+
+    python3 tests/fixtures/generate_geoip_test_db.py
+"""
+
+import ipaddress
+import os
+import struct
+
+NETWORKS = {
+    "81.2.69.0/24": {
+        "country": {"iso_code": "GB", "names": {"en": "United Kingdom"}},
+        "city": {"names": {"en": "London"}},
+        "location": {"latitude": 51.5142, "longitude": -0.0931},
+    },
+    "89.160.20.0/24": {
+        "country": {"iso_code": "SE", "names": {"en": "Sweden"}},
+        "city": {"names": {"en": "Linköping"}},
+        "location": {"latitude": 58.4167, "longitude": 15.6167},
+    },
+}
+
+RECORD_SIZE = 24
+
+
+def control(type_num, size):
+    if size < 29:
+        size_bits, extra = size, b""
+    elif size < 285:
+        size_bits, extra = 29, bytes([size - 29])
+    elif size < 65821:
+        size_bits, extra = 30, struct.pack(">H", size - 285)
+    else:
+        size_bits, extra = 31, struct.pack(">I", size - 65821)[1:]
+    if type_num <= 7:
+        return bytes([(type_num << 5) | size_bits]) + extra
+    return bytes([size_bits, type_num - 7]) + extra
+
+
+def uint(type_num, value):
+    raw = value.to_bytes((value.bit_length() + 7) // 8, "big") if value else b""
+    return control(type_num, len(raw)) + raw
+
+
+def encode(value):
+    if isinstance(value, str):
+        raw = value.encode("utf-8")
+        return control(2, len(raw)) + raw
+    if isinstance(value, float):
+        return control(3, 8) + struct.pack(">d", value)
+    if isinstance(value, dict):
+        out = control(7, len(value))
+        for key, item in value.items():
+            out += encode(key) + encode(item)
+        return out
+    if isinstance(value, list):
+        return control(11, len(value)) + b"".join(encode(item) for item in value)
+    raise TypeError(value)
+
+
+def main():
+    data = b""
+    nodes = [[None, None]]
+    for network, record in NETWORKS.items():
+        offset = len(data)
+        data += encode(record)
+        net = ipaddress.ip_network(network)
+        bits = int(net.network_address)
+        node = 0
+        for depth in range(net.prefixlen):
+            bit = (bits >> (31 - depth)) & 1
+            if depth == net.prefixlen - 1:
+                nodes[node][bit] = ("data", offset)
+            else:
+                if nodes[node][bit] is None:
+                    nodes.append([None, None])
+                    nodes[node][bit] = ("node", len(nodes) - 1)
+                node = nodes[node][bit][1]
+
+    node_count = len(nodes)
+
+    def record_value(entry):
+        if entry is None:
+            return node_count
+        kind, value = entry
+        return value if kind == "node" else node_count + 16 + value
+
+    tree = b"".join(
+        record_value(left).to_bytes(3, "big") + record_value(right).to_bytes(3, "big")
+        for left, right in nodes
+    )
+
+    metadata = control(7, 9)
+    for key, value in [
+        ("binary_format_major_version", uint(5, 2)),
+        ("binary_format_minor_version", uint(5, 0)),
+        ("build_epoch", uint(9, 1735689600)),
+        ("database_type", encode("GeoIP2-Country")),
+        ("description", encode({"en": "Paycheck test data"})),
+        ("ip_version", uint(5, 4)),
+        ("languages", encode(["en"])),
+        ("node_count", uint(6, node_count)),
+        ("record_size", uint(5, RECORD_SIZE)),
+    ]:
+        metadata += encode(key) + value
+
+    path = os.path.join(os.path.dirname(os.path.abspath(__file__)), "geoip-test.mmdb")
+    with open(path, "wb") as f:
+        f.write(tree + b"\x00" * 16 + data + b"\xab\xcd\xefMaxMind.com" + metadata)
+
+
+if __name__ == "__main__":
+    main()
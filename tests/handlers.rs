@@ -8,3 +8,133 @@ mod orgs;
 
 #[path = "handlers/webhooks.rs"]
 mod webhooks;
+
+#[path = "handlers/residency.rs"]
+mod residency;
+
+#[path = "handlers/license_seats.rs"]
+mod license_seats;
+
+#[path = "handlers/share_links.rs"]
+mod share_links;
+
+#[path = "handlers/member_removal.rs"]
+mod member_removal;
+
+#[path = "handlers/token_diagnostics.rs"]
+mod token_diagnostics;
+
+#[path = "handlers/claims_preview.rs"]
+mod claims_preview;
+
+#[path = "handlers/license_tags.rs"]
+mod license_tags;
+
+#[path = "handlers/email_log.rs"]
+mod email_log;
+
+#[path = "handlers/email_config.rs"]
+mod email_config;
+
+#[path = "handlers/request_id.rs"]
+mod request_id;
+
+#[path = "handlers/temporary_roles.rs"]
+mod temporary_roles;
+
+#[path = "handlers/email_normalization.rs"]
+mod email_normalization;
+
+#[path = "handlers/license_revocation.rs"]
+mod license_revocation;
+
+#[path = "handlers/audit_archive.rs"]
+mod audit_archive;
+
+#[path = "handlers/project_config.rs"]
+mod project_config;
+
+#[path = "handlers/http_layers.rs"]
+mod http_layers;
+
+#[path = "handlers/entitlements.rs"]
+mod entitlements;
+
+#[path = "handlers/org_limits.rs"]
+mod org_limits;
+
+#[path = "handlers/prepaid_codes.rs"]
+mod prepaid_codes;
+
+#[path = "handlers/reconciliation.rs"]
+mod reconciliation;
+
+#[path = "handlers/status_page.rs"]
+mod status_page;
+
+#[path = "handlers/license_imports.rs"]
+mod license_imports;
+
+#[path = "handlers/project_activity.rs"]
+mod project_activity;
+
+#[path = "handlers/maintenance_mode.rs"]
+mod maintenance_mode;
+
+#[path = "handlers/timestamps.rs"]
+mod timestamps;
+
+#[path = "handlers/user_merge.rs"]
+mod user_merge;
+
+#[path = "handlers/product_upsells.rs"]
+mod product_upsells;
+
+#[path = "handlers/bulk_license_jobs.rs"]
+mod bulk_license_jobs;
+
+#[path = "handlers/terms.rs"]
+mod terms;
+
+#[path = "handlers/backups.rs"]
+mod backups;
+
+#[path = "handlers/license_refs.rs"]
+mod license_refs;
+
+#[path = "handlers/product_templates.rs"]
+mod product_templates;
+
+#[path = "handlers/storage_health.rs"]
+mod storage_health;
+
+#[path = "handlers/workspaces.rs"]
+mod workspaces;
+
+#[path = "handlers/device_deactivation.rs"]
+mod device_deactivation;
+
+#[path = "handlers/operator_config.rs"]
+mod operator_config;
+
+#[path = "handlers/custom_domains.rs"]
+mod custom_domains;
+
+#[path = "handlers/audit_failure_mode.rs"]
+mod audit_failure_mode;
+
+#[path = "handlers/usage_geo.rs"]
+mod usage_geo;
+#[path = "handlers/dormant_licenses.rs"]
+mod dormant_licenses;
+#[path = "handlers/compliance_settings.rs"]
+mod compliance_settings;
+
+#[path = "handlers/router.rs"]
+mod router;
+#[path = "handlers/audit_export.rs"]
+mod audit_export;
+#[path = "handlers/optimistic_locking.rs"]
+mod optimistic_locking;
+#[path = "handlers/reference_numbers.rs"]
+mod reference_numbers;
@@ -0,0 +1,312 @@
+//! Tests for signed audit log archives: org owners download them, operators
+//! verify them, and any change to the bytes is caught.
+
+use std::io::{Read, Write};
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode, header},
+};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::handlers;
+use paycheck::util::AuditLogBuilder;
+
+struct ArchiveFixture {
+    state: AppState,
+    org_id: String,
+    owner_key: String,
+    member_key: String,
+    operator_key: String,
+}
+
+fn setup() -> ArchiveFixture {
+    let mut state = create_test_app_state();
+    state.audit_log_enabled = true;
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, owner_key) =
+        create_test_org_member(&mut conn, &org.id, "owner@test.com", OrgMemberRole::Owner);
+    let (_, _, member_key) =
+        create_test_org_member(&mut conn, &org.id, "member@test.com", OrgMemberRole::Member);
+    let (_, operator_key) = create_test_operator(&mut conn, "view@test.com", OperatorRole::View);
+
+    drop(conn);
+    ArchiveFixture {
+        state,
+        org_id: org.id,
+        owner_key,
+        member_key,
+        operator_key,
+    }
+}
+
+impl ArchiveFixture {
+    /// Write an audit log entry for `org_id` at `timestamp`.
+    fn seed(&self, org_id: &str, resource_id: &str, timestamp: i64) {
+        let conn = self.state.audit.get().unwrap();
+        let mut entry = AuditLogBuilder::new(&conn, true, &Default::default())
+            .action(AuditAction::UpdateProduct)
+            .resource("product", resource_id)
+            .org(org_id)
+            .entry()
+            .unwrap();
+        entry.timestamp = timestamp;
+        queries::insert_audit_log(&conn, &entry).unwrap();
+    }
+
+    async fn archive(&self, api_key: &str, from: i64, to: i64) -> (StatusCode, Vec<u8>) {
+        let app = handlers::orgs::router(
+            self.state.clone(),
+            paycheck::config::RateLimitConfig::disabled(),
+        )
+        .with_state(self.state.clone());
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(format!("/orgs/{}/audit-logs/archive", self.org_id))
+                    .header("Authorization", format!("Bearer {}", api_key))
+                    .header("content-type", "application/json")
+                    .body(Body::from(json!({ "from": from, "to": to }).to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        if status == StatusCode::OK {
+            assert_eq!(response.headers()[header::CONTENT_TYPE], "application/gzip");
+        }
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, body.to_vec())
+    }
+
+    async fn owner_archive(&self, from: i64, to: i64) -> Vec<u8> {
+        let (status, body) = self.archive(&self.owner_key, from, to).await;
+        assert_eq!(status, StatusCode::OK);
+        body
+    }
+
+    async fn verify(&self, archive: Vec<u8>) -> Value {
+        let app = handlers::operators::router(self.state.clone()).with_state(self.state.clone());
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/operators/audit-archives/verify")
+                    .header("Authorization", format!("Bearer {}", self.operator_key))
+                    .header("content-type", "application/gzip")
+                    .body(Body::from(archive))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+}
+
+fn decompress(archive: &[u8]) -> String {
+    let mut out = String::new();
+    GzDecoder::new(archive).read_to_string(&mut out).unwrap();
+    out
+}
+
+fn compress(content: &str) -> Vec<u8> {
+    let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+    gz.write_all(content.as_bytes()).unwrap();
+    gz.finish().unwrap()
+}
+
+#[tokio::test]
+async fn test_archive_contains_org_entries_in_range_and_verifies() {
+    let f = setup();
+    f.seed(&f.org_id, "prod_1", 1_000);
+    f.seed(&f.org_id, "prod_2", 2_000);
+    f.seed(&f.org_id, "prod_3", 3_000);
+    f.seed(&f.org_id, "prod_late", 5_000);
+    f.seed("other-org", "prod_other", 2_000);
+
+    let archive = f.owner_archive(1_000, 3_000).await;
+
+    let content = decompress(&archive);
+    let lines: Vec<Value> = content
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 4, "three entries plus the manifest");
+    let resources: Vec<&str> = lines[..3]
+        .iter()
+        .map(|l| l["resource_id"].as_str().unwrap())
+        .collect();
+    assert_eq!(resources, ["prod_1", "prod_2", "prod_3"]);
+
+    let manifest = &lines[3]["manifest"];
+    assert_eq!(manifest["org_id"], f.org_id.as_str());
+    assert_eq!(manifest["from"], 1_000);
+    assert_eq!(manifest["to"], 3_000);
+    assert_eq!(manifest["row_count"], 3);
+
+    let result = f.verify(archive).await;
+    assert_eq!(result["valid"], true, "{}", result);
+    assert_eq!(result["manifest"]["row_count"], 3);
+    assert!(result.get("reason").is_none());
+}
+
+#[tokio::test]
+async fn test_archive_pages_through_entries_sharing_a_timestamp() {
+    let f = setup();
+    for i in 0..1_201 {
+        f.seed(&f.org_id, &format!("prod_{}", i), 1_000);
+    }
+
+    let archive = f.owner_archive(0, 2_000).await;
+
+    let content = decompress(&archive);
+    let mut ids: Vec<String> = content
+        .lines()
+        .filter_map(|l| {
+            let line: Value = serde_json::from_str(l).unwrap();
+            line["id"].as_str().map(String::from)
+        })
+        .collect();
+    assert_eq!(ids.len(), 1_201);
+    ids.sort();
+    ids.dedup();
+    assert_eq!(ids.len(), 1_201, "no entry should repeat across pages");
+
+    assert_eq!(f.verify(archive).await["valid"], true);
+}
+
+#[tokio::test]
+async fn test_corrupted_byte_fails_verification() {
+    let f = setup();
+    f.seed(&f.org_id, "prod_1", 1_000);
+    f.seed(&f.org_id, "prod_2", 2_000);
+
+    let mut archive = f.owner_archive(0, 3_000).await;
+    let middle = archive.len() / 2;
+    archive[middle] ^= 0x01;
+
+    let result = f.verify(archive).await;
+    assert_eq!(result["valid"], false);
+    assert!(result["reason"].is_string());
+}
+
+#[tokio::test]
+async fn test_edited_entry_fails_verification() {
+    let f = setup();
+    f.seed(&f.org_id, "prod_1", 1_000);
+
+    let archive = f.owner_archive(0, 3_000).await;
+    let edited = decompress(&archive).replace("prod_1", "prod_9");
+
+    let result = f.verify(compress(&edited)).await;
+    assert_eq!(result["valid"], false);
+    assert_eq!(result["reason"], "Entries do not match the manifest digest");
+}
+
+#[tokio::test]
+async fn test_dropped_entry_fails_verification() {
+    let f = setup();
+    f.seed(&f.org_id, "prod_1", 1_000);
+    f.seed(&f.org_id, "prod_2", 2_000);
+
+    let archive = f.owner_archive(0, 3_000).await;
+    let content = decompress(&archive);
+    let without_first: String = content.split_inclusive('\n').skip(1).collect();
+
+    let result = f.verify(compress(&without_first)).await;
+    assert_eq!(result["valid"], false);
+    assert_eq!(
+        result["reason"],
+        "Archive has 1 entries but the manifest records 2"
+    );
+}
+
+#[tokio::test]
+async fn test_edited_manifest_fails_verification() {
+    let f = setup();
+    f.seed(&f.org_id, "prod_1", 1_000);
+
+    let archive = f.owner_archive(0, 3_000).await;
+    let edited = decompress(&archive).replace("\"to\":3000", "\"to\":9000");
+
+    let result = f.verify(compress(&edited)).await;
+    assert_eq!(result["valid"], false);
+    assert_eq!(result["reason"], "Manifest signature is invalid");
+}
+
+#[tokio::test]
+async fn test_archive_signed_with_another_master_key_fails_verification() {
+    let f = setup();
+    f.seed(&f.org_id, "prod_1", 1_000);
+    let archive = f.owner_archive(0, 3_000).await;
+
+    let mut rotated = setup();
+    rotated.state.master_key = MasterKey::from_bytes([0x42; 32]);
+
+    let result = rotated.verify(archive).await;
+    assert_eq!(result["valid"], false);
+    assert_eq!(result["reason"], "Manifest signature is invalid");
+}
+
+#[tokio::test]
+async fn test_garbage_upload_fails_verification() {
+    let f = setup();
+
+    let result = f.verify(b"not an archive".to_vec()).await;
+    assert_eq!(result["valid"], false);
+
+    let result = f.verify(compress("{\"id\":\"x\"}\n")).await;
+    assert_eq!(result["valid"], false);
+    assert_eq!(result["reason"], "Archive has no manifest");
+}
+
+#[tokio::test]
+async fn test_archive_generation_is_audit_logged() {
+    let f = setup();
+
+    let archive = f.owner_archive(100, 200).await;
+    assert_eq!(f.verify(archive).await["manifest"]["row_count"], 0);
+
+    let conn = f.state.audit.get().unwrap();
+    let query: AuditLogQuery =
+        serde_json::from_value(json!({ "action": "generate_audit_archive" })).unwrap();
+    let (logs, total) = queries::query_audit_logs(&conn, &query).unwrap();
+    assert_eq!(total, 1);
+    assert_eq!(logs[0].org_id.as_deref(), Some(f.org_id.as_str()));
+    let details = logs[0].details.as_ref().unwrap();
+    assert_eq!(details["from"], 100);
+    assert_eq!(details["to"], 200);
+}
+
+#[tokio::test]
+async fn test_archive_requires_owner() {
+    let f = setup();
+
+    let (status, _) = f.archive(&f.member_key, 0, 3_000).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_archive_rejects_inverted_range() {
+    let f = setup();
+
+    let (status, _) = f.archive(&f.owner_key, 3_000, 1_000).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,268 @@
+//! Tests for CSV audit log exports: filters and org scoping match the JSON
+//! endpoints, fields are escaped, and large exports page through every entry.
+
+use axum::{
+    body::Body,
+    http::{HeaderMap, HeaderValue, Request, StatusCode, header},
+};
+use serde_json::json;
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::handlers;
+use paycheck::util::AuditLogBuilder;
+
+struct ExportFixture {
+    state: AppState,
+    org_id: String,
+    owner_key: String,
+    member_key: String,
+    operator_key: String,
+}
+
+fn setup() -> ExportFixture {
+    let mut state = create_test_app_state();
+    state.audit_log_enabled = true;
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, owner_key) =
+        create_test_org_member(&mut conn, &org.id, "owner@test.com", OrgMemberRole::Owner);
+    let (_, _, member_key) =
+        create_test_org_member(&mut conn, &org.id, "member@test.com", OrgMemberRole::Member);
+    let (_, operator_key) = create_test_operator(&mut conn, "view@test.com", OperatorRole::View);
+
+    drop(conn);
+    ExportFixture {
+        state,
+        org_id: org.id,
+        owner_key,
+        member_key,
+        operator_key,
+    }
+}
+
+impl ExportFixture {
+    /// Write an audit log entry for `org_id` at `timestamp`.
+    fn seed(&self, org_id: &str, action: AuditAction, resource_id: &str, timestamp: i64) {
+        self.seed_with_headers(org_id, action, resource_id, timestamp, &HeaderMap::new());
+    }
+
+    fn seed_with_headers(
+        &self,
+        org_id: &str,
+        action: AuditAction,
+        resource_id: &str,
+        timestamp: i64,
+        headers: &HeaderMap,
+    ) {
+        let conn = self.state.audit.get().unwrap();
+        let mut entry = AuditLogBuilder::new(&conn, true, headers)
+            .action(action)
+            .resource("product", resource_id)
+            .details(&json!({ "name": "Pro, \"yearly\"" }))
+            .org(org_id)
+            .entry()
+            .unwrap();
+        entry.timestamp = timestamp;
+        queries::insert_audit_log(&conn, &entry).unwrap();
+    }
+
+    async fn get(&self, uri: &str, api_key: &str) -> (StatusCode, HeaderMap, String) {
+        let app = if uri.starts_with("/operators") {
+            handlers::operators::router(self.state.clone()).with_state(self.state.clone())
+        } else {
+            handlers::orgs::router(
+                self.state.clone(),
+                paycheck::config::RateLimitConfig::disabled(),
+            )
+            .with_state(self.state.clone())
+        };
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri(uri)
+                    .header("Authorization", format!("Bearer {}", api_key))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, headers, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    /// Export the org's logs as the owner and return the parsed rows,
+    /// header row first.
+    async fn org_export(&self, query: &str) -> Vec<Vec<String>> {
+        let uri = format!("/orgs/{}/audit-logs/export{}", self.org_id, query);
+        let (status, headers, body) = self.get(&uri, &self.owner_key).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(headers[header::CONTENT_TYPE], "text/csv; charset=utf-8");
+        parse_csv(&body)
+    }
+}
+
+/// Minimal RFC 4180 reader: quoted fields may hold commas, doubled quotes,
+/// and line breaks.
+fn parse_csv(content: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => field.push(c),
+            (false, '"') => quoted = true,
+            (false, ',') => row.push(std::mem::take(&mut field)),
+            (false, '\r') => {}
+            (false, '\n') => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            (false, c) => field.push(c),
+        }
+    }
+    rows
+}
+
+fn column(rows: &[Vec<String>], name: &str) -> Vec<String> {
+    let index = rows[0].iter().position(|h| h == name).unwrap();
+    rows[1..].iter().map(|row| row[index].clone()).collect()
+}
+
+#[tokio::test]
+async fn test_export_has_filtered_org_entries_newest_first() {
+    let f = setup();
+    f.seed(&f.org_id, AuditAction::UpdateProduct, "prod_1", 1_000);
+    f.seed(&f.org_id, AuditAction::CreateProduct, "prod_2", 2_000);
+    f.seed(&f.org_id, AuditAction::UpdateProduct, "prod_3", 3_000);
+    f.seed("other-org", AuditAction::UpdateProduct, "prod_other", 2_000);
+
+    let rows = f.org_export("?to_timestamp=5000").await;
+    assert_eq!(rows[0][0], "id");
+    assert_eq!(column(&rows, "resource_id"), ["prod_3", "prod_2", "prod_1"]);
+    assert_eq!(column(&rows, "timestamp"), ["3000", "2000", "1000"]);
+    assert_eq!(column(&rows, "time")[2], "1970-01-01T00:16:40Z");
+
+    let rows = f
+        .org_export("?action=update_product&from_timestamp=2000&to_timestamp=5000")
+        .await;
+    assert_eq!(column(&rows, "resource_id"), ["prod_3"]);
+
+    // The path's org wins over the query string
+    let rows = f.org_export("?org_id=other-org&to_timestamp=5000").await;
+    assert_eq!(rows.len(), 4);
+}
+
+#[tokio::test]
+async fn test_export_escapes_fields() {
+    let f = setup();
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::USER_AGENT,
+        HeaderValue::from_static("=cmd|' /C calc'!A0, \"quoted\""),
+    );
+    f.seed_with_headers(
+        &f.org_id,
+        AuditAction::UpdateProduct,
+        "prod_1",
+        1_000,
+        &headers,
+    );
+
+    let rows = f.org_export("?to_timestamp=5000").await;
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[1].len(), rows[0].len());
+    assert_eq!(
+        column(&rows, "user_agent"),
+        ["'=cmd|' /C calc'!A0, \"quoted\""]
+    );
+    let details: serde_json::Value = serde_json::from_str(&column(&rows, "details")[0]).unwrap();
+    assert_eq!(details["name"], "Pro, \"yearly\"");
+}
+
+#[tokio::test]
+async fn test_export_pages_through_every_entry() {
+    let f = setup();
+    for i in 0..2_501 {
+        f.seed(
+            &f.org_id,
+            AuditAction::UpdateProduct,
+            &format!("prod_{}", i),
+            1_000 + i / 2,
+        );
+    }
+
+    let rows = f.org_export("?to_timestamp=5000").await;
+    let mut ids = column(&rows, "id");
+    assert_eq!(ids.len(), 2_501);
+    ids.sort();
+    ids.dedup();
+    assert_eq!(ids.len(), 2_501, "no entry should repeat across pages");
+}
+
+#[tokio::test]
+async fn test_export_limited_to_members_projects() {
+    let f = setup();
+    f.seed(&f.org_id, AuditAction::UpdateProduct, "prod_1", 1_000);
+
+    let uri = format!("/orgs/{}/audit-logs/export", f.org_id);
+    let (status, _, body) = f.get(&uri, &f.member_key).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(parse_csv(&body).len(), 1, "only the header row");
+}
+
+#[tokio::test]
+async fn test_export_is_audit_logged() {
+    let f = setup();
+    f.org_export("?from_timestamp=100&to_timestamp=200").await;
+
+    let conn = f.state.audit.get().unwrap();
+    let query: AuditLogQuery =
+        serde_json::from_value(json!({ "action": "export_audit_logs" })).unwrap();
+    let (logs, total) = queries::query_audit_logs(&conn, &query).unwrap();
+    assert_eq!(total, 1);
+    assert_eq!(logs[0].org_id.as_deref(), Some(f.org_id.as_str()));
+    let details = logs[0].details.as_ref().unwrap();
+    assert_eq!(details["format"], "csv");
+    assert_eq!(details["from"], 100);
+    assert_eq!(details["to"], 200);
+}
+
+#[tokio::test]
+async fn test_operator_export_covers_all_orgs() {
+    let f = setup();
+    f.seed(&f.org_id, AuditAction::UpdateProduct, "prod_1", 1_000);
+    f.seed("other-org", AuditAction::UpdateProduct, "prod_other", 2_000);
+
+    let (status, headers, body) = f
+        .get(
+            "/operators/audit-logs/export?to_timestamp=5000",
+            &f.operator_key,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let disposition = headers[header::CONTENT_DISPOSITION].to_str().unwrap();
+    assert!(
+        disposition.starts_with("attachment; filename=\"audit-logs-all-"),
+        "{}",
+        disposition
+    );
+    let rows = parse_csv(&body);
+    assert_eq!(column(&rows, "resource_id"), ["prod_other", "prod_1"]);
+}
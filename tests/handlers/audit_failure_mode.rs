@@ -0,0 +1,208 @@
+//! Tests for AUDIT_FAILURE_MODE: with the audit database refusing writes,
+//! `fail_request` rolls back sensitive changes with a 500 while routine
+//! requests carry on, and `log_and_continue` lets everything through.
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode},
+};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::db::outbox;
+use paycheck::handlers;
+
+struct AuditFixture {
+    state: AppState,
+    org_id: String,
+    project_id: String,
+    owner_key: String,
+    member: OrgMember,
+    product_id: String,
+    license_id: String,
+}
+
+/// An org with an owner, a member, and a licensed product. With
+/// `broken_audit`, every audit write fails as on a full or read-only disk.
+fn setup(mode: AuditFailureMode, broken_audit: bool) -> AuditFixture {
+    let mut state = create_test_app_state();
+    state.audit_log_enabled = true;
+    state.audit_failure_mode = mode;
+    if broken_audit {
+        let manager = SqliteConnectionManager::memory()
+            .with_init(|conn| conn.execute_batch("PRAGMA query_only = ON"));
+        state.audit = Pool::builder().max_size(1).build(manager).unwrap();
+    }
+
+    let mut conn = state.db.get().unwrap();
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, owner_key) =
+        create_test_org_member(&mut conn, &org.id, "owner@test.com", OrgMemberRole::Owner);
+    let (_, member, _) =
+        create_test_org_member(&mut conn, &org.id, "member@test.com", OrgMemberRole::Member);
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    let product = create_test_product(&conn, &project.id, "Pro", "pro");
+    let license = create_test_license(&conn, &project.id, &product.id, None);
+    drop(conn);
+
+    AuditFixture {
+        state,
+        org_id: org.id,
+        project_id: project.id,
+        owner_key,
+        member,
+        product_id: product.id,
+        license_id: license.id,
+    }
+}
+
+impl AuditFixture {
+    fn app(&self) -> Router {
+        handlers::orgs::router(
+            self.state.clone(),
+            paycheck::config::RateLimitConfig::disabled(),
+        )
+        .with_state(self.state.clone())
+    }
+
+    async fn send(&self, method: &str, uri: &str, body: Option<Value>) -> StatusCode {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("Authorization", format!("Bearer {}", self.owner_key));
+        let body = match body {
+            Some(body) => {
+                request = request.header("content-type", "application/json");
+                Body::from(body.to_string())
+            }
+            None => Body::empty(),
+        };
+        let response = self
+            .app()
+            .oneshot(request.body(body).unwrap())
+            .await
+            .unwrap();
+        response.status()
+    }
+
+    async fn promote_member(&self) -> StatusCode {
+        self.send(
+            "PUT",
+            &format!("/orgs/{}/members/{}", self.org_id, self.member.user_id),
+            Some(json!({ "role": "admin" })),
+        )
+        .await
+    }
+
+    async fn revoke_license(&self) -> StatusCode {
+        self.send(
+            "POST",
+            &format!(
+                "/orgs/{}/projects/{}/licenses/{}/revoke",
+                self.org_id, self.project_id, self.license_id
+            ),
+            None,
+        )
+        .await
+    }
+
+    fn member_role(&self) -> OrgMemberRole {
+        let conn = self.state.db.get().unwrap();
+        queries::get_org_member_by_id(&conn, &self.member.id)
+            .unwrap()
+            .unwrap()
+            .role
+    }
+
+    fn license_revoked(&self) -> bool {
+        let conn = self.state.db.get().unwrap();
+        queries::get_license_by_id(&conn, &self.license_id)
+            .unwrap()
+            .unwrap()
+            .revoked
+    }
+
+    fn pending_audit_entries(&self) -> i64 {
+        outbox::pending_count(&self.state.db.get().unwrap()).unwrap()
+    }
+}
+
+#[tokio::test]
+async fn test_fail_request_rolls_back_sensitive_changes() {
+    let f = setup(AuditFailureMode::FailRequest, true);
+
+    assert_eq!(f.promote_member().await, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(f.member_role(), OrgMemberRole::Member);
+
+    assert_eq!(f.revoke_license().await, StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(!f.license_revoked());
+
+    // Nothing was queued for changes that didn't happen
+    assert_eq!(f.pending_audit_entries(), 0);
+}
+
+#[tokio::test]
+async fn test_fail_request_lets_routine_requests_through() {
+    let f = setup(AuditFailureMode::FailRequest, true);
+
+    let status = f
+        .send("GET", &format!("/orgs/{}/members", f.org_id), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let status = f
+        .send(
+            "DELETE",
+            &format!(
+                "/orgs/{}/projects/{}/products/{}",
+                f.org_id, f.project_id, f.product_id
+            ),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let conn = f.state.db.get().unwrap();
+    assert!(
+        queries::get_product_by_id(&conn, &f.product_id)
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[tokio::test]
+async fn test_log_and_continue_keeps_sensitive_changes() {
+    let f = setup(AuditFailureMode::LogAndContinue, true);
+
+    assert_eq!(f.promote_member().await, StatusCode::OK);
+    assert_eq!(f.member_role(), OrgMemberRole::Admin);
+    assert_eq!(f.revoke_license().await, StatusCode::OK);
+    assert!(f.license_revoked());
+
+    // The entries wait in the outbox for the audit database to come back
+    assert_eq!(f.pending_audit_entries(), 2);
+}
+
+#[tokio::test]
+async fn test_fail_request_records_each_change_once() {
+    let f = setup(AuditFailureMode::FailRequest, false);
+
+    assert_eq!(f.promote_member().await, StatusCode::OK);
+    assert_eq!(f.revoke_license().await, StatusCode::OK);
+
+    // Saved before the commit and relayed after it, but stored once
+    outbox::relay_all(&f.state).unwrap();
+    let (entries, _) =
+        queries::query_audit_logs(&f.state.audit.get().unwrap(), &AuditLogQuery::default())
+            .unwrap();
+    let mut actions: Vec<_> = entries.iter().map(|e| e.action.as_str()).collect();
+    actions.sort();
+    assert_eq!(actions, ["revoke_license", "update_org_member"]);
+    assert_eq!(f.pending_audit_entries(), 0);
+}
@@ -0,0 +1,344 @@
+//! Tests for encrypted database backups: owners take them, they verify, a
+//! restore brings the data back, and damaged or foreign files are caught.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use serde_json::Value;
+use tempfile::TempDir;
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::backup::{Backups, LocalBackupStorage};
+use paycheck::handlers;
+use paycheck::util::AuditLogBuilder;
+
+struct BackupFixture {
+    state: AppState,
+    dir: TempDir,
+    org: Organization,
+    owner_key: String,
+    admin_key: String,
+    view_key: String,
+}
+
+fn backups_in(dir: &TempDir, key: MasterKey, retention_count: i64) -> Backups {
+    let storage = LocalBackupStorage::new(dir.path()).unwrap();
+    Backups::new(Box::new(storage), key, false, retention_count)
+}
+
+fn setup() -> BackupFixture {
+    let dir = TempDir::new().unwrap();
+    let mut state = create_test_app_state();
+    state.audit_log_enabled = true;
+    state.backups = Some(Arc::new(backups_in(&dir, test_master_key(), 0)));
+    let mut conn = state.db.get().unwrap();
+
+    let (_, owner_key) = create_test_operator(&mut conn, "owner@test.com", OperatorRole::Owner);
+    let (_, admin_key) = create_test_operator(&mut conn, "admin@test.com", OperatorRole::Admin);
+    let (_, view_key) = create_test_operator(&mut conn, "view@test.com", OperatorRole::View);
+    let org = create_test_org(&conn, "Backed Up Org");
+
+    let audit_conn = state.audit.get().unwrap();
+    AuditLogBuilder::new(&audit_conn, true, &Default::default())
+        .action(AuditAction::UpdateOrg)
+        .resource("org", &org.id)
+        .org(&org.id)
+        .save()
+        .unwrap();
+
+    drop(conn);
+    BackupFixture {
+        state,
+        dir,
+        org,
+        owner_key,
+        admin_key,
+        view_key,
+    }
+}
+
+impl BackupFixture {
+    async fn send(&self, method: &str, path: &str, key: &str) -> (StatusCode, Value) {
+        let app = handlers::operators::router(self.state.clone()).with_state(self.state.clone());
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri(path)
+                    .header("Authorization", format!("Bearer {}", key))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    async fn create(&self) -> Value {
+        let (status, body) = self
+            .send("POST", "/operators/backups", &self.owner_key)
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        body
+    }
+
+    async fn verify(&self, backup_id: &str) -> Value {
+        let (status, body) = self
+            .send(
+                "POST",
+                &format!("/operators/backups/{}/verify", backup_id),
+                &self.owner_key,
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        body
+    }
+
+    fn file(&self, backup: &Value) -> PathBuf {
+        self.dir.path().join(backup["file_name"].as_str().unwrap())
+    }
+}
+
+fn open_pool(path: &std::path::Path) -> Pool<SqliteConnectionManager> {
+    Pool::builder()
+        .max_size(1)
+        .build(SqliteConnectionManager::file(path))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_backup_verifies_and_restores_into_fresh_pool() {
+    let f = setup();
+    let backup = f.create().await;
+    let backup_id = backup["id"].as_str().unwrap();
+    assert!(backup["size_bytes"].as_i64().unwrap() > 0);
+    assert_eq!(backup["sha256"].as_str().unwrap().len(), 64);
+    assert_eq!(backup["encrypted_with"], "master_key");
+    assert!(f.file(&backup).exists());
+
+    // Encrypted at rest: no SQLite header, no org name in the clear
+    let raw = std::fs::read(f.file(&backup)).unwrap();
+    assert!(!raw.windows(15).any(|w| w == b"SQLite format 3"));
+    assert!(!raw.windows(13).any(|w| w == b"Backed Up Org"));
+
+    let (status, list) = f.send("GET", "/operators/backups", &f.admin_key).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(list[0]["id"], backup_id);
+
+    let result = f.verify(backup_id).await;
+    assert_eq!(result["valid"], true, "{}", result);
+    let names: Vec<&str> = result["databases"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|db| db["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["main", "audit"]);
+    assert!(
+        result["databases"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|db| db["integrity"] == "ok")
+    );
+
+    let (_, list) = f.send("GET", "/operators/backups", &f.admin_key).await;
+    assert_eq!(list[0]["verified_ok"], true);
+    assert!(list[0]["verified_at"].as_i64().is_some());
+
+    // Restore into new files and read the data back through a fresh pool
+    let target = TempDir::new().unwrap();
+    let main_path = target.path().join("restored.db");
+    let audit_path = target.path().join("restored_audit.db");
+    backups_in(&f.dir, test_master_key(), 0)
+        .restore(backup_id, &main_path, &audit_path)
+        .unwrap();
+
+    let restored = open_pool(&main_path);
+    let org = queries::get_organization_by_id(&restored.get().unwrap(), &f.org.id)
+        .unwrap()
+        .expect("org survives the restore");
+    assert_eq!(org.name, "Backed Up Org");
+
+    let restored_audit = open_pool(&audit_path);
+    let entries: i64 = restored_audit
+        .get()
+        .unwrap()
+        .query_row(
+            "SELECT COUNT(*) FROM audit_logs WHERE resource_id = ?1",
+            rusqlite::params![f.org.id],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(entries, 1);
+
+    // Never over existing files
+    assert!(
+        backups_in(&f.dir, test_master_key(), 0)
+            .restore(backup_id, &main_path, &target.path().join("other.db"))
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_tampered_backup_fails_verification() {
+    let f = setup();
+    let backup = f.create().await;
+    let path = f.file(&backup);
+    let mut raw = std::fs::read(&path).unwrap();
+    let middle = raw.len() / 2;
+    raw[middle] ^= 0x01;
+    std::fs::write(&path, raw).unwrap();
+
+    let result = f.verify(backup["id"].as_str().unwrap()).await;
+    assert_eq!(result["valid"], false);
+    assert!(
+        result["reason"].as_str().unwrap().contains("digest"),
+        "{}",
+        result
+    );
+
+    let (_, list) = f.send("GET", "/operators/backups", &f.admin_key).await;
+    assert_eq!(list[0]["verified_ok"], false);
+}
+
+#[tokio::test]
+async fn test_backup_needs_the_key_it_was_encrypted_with() {
+    let f = setup();
+    let backup = f.create().await;
+    let backup_id = backup["id"].as_str().unwrap();
+    let record = queries::get_backup(&f.state.db.get().unwrap(), backup_id)
+        .unwrap()
+        .unwrap();
+
+    let other_key = backups_in(&f.dir, MasterKey::from_bytes([7u8; 32]), 0);
+    let result = other_key.verify(&record);
+    assert!(!result.valid);
+    assert!(result.reason.unwrap().contains("decrypted"));
+
+    let target = TempDir::new().unwrap();
+    assert!(
+        other_key
+            .restore(
+                backup_id,
+                &target.path().join("main.db"),
+                &target.path().join("audit.db"),
+            )
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_missing_backup_file_fails_verification() {
+    let f = setup();
+    let backup = f.create().await;
+    std::fs::remove_file(f.file(&backup)).unwrap();
+
+    let result = f.verify(backup["id"].as_str().unwrap()).await;
+    assert_eq!(result["valid"], false);
+    assert!(result["reason"].is_string());
+}
+
+#[tokio::test]
+async fn test_prune_keeps_newest_backups() {
+    let f = setup();
+    let backups = backups_in(&f.dir, test_master_key(), 2);
+    let conn = f.state.db.get().unwrap();
+    let audit_conn = f.state.audit.get().unwrap();
+    for (i, created_at) in [1_000, 2_000, 3_000].into_iter().enumerate() {
+        let backup = backups
+            .create(
+                &conn,
+                &audit_conn,
+                &format!("backup-{}", i),
+                None,
+                created_at,
+            )
+            .unwrap();
+        queries::create_backup(&conn, &backup).unwrap();
+    }
+
+    assert_eq!(backups.prune(&conn).unwrap(), 1);
+    let left: Vec<String> = queries::list_backups(&conn)
+        .unwrap()
+        .into_iter()
+        .map(|b| b.id)
+        .collect();
+    assert_eq!(left, ["backup-2", "backup-1"]);
+    assert!(!f.dir.path().join(Backups::file_name("backup-0")).exists());
+    assert!(f.dir.path().join(Backups::file_name("backup-1")).exists());
+
+    // Nothing more to prune; 0 keeps everything
+    assert_eq!(backups.prune(&conn).unwrap(), 0);
+    assert_eq!(
+        backups_in(&f.dir, test_master_key(), 0)
+            .prune(&conn)
+            .unwrap(),
+        0
+    );
+}
+
+#[tokio::test]
+async fn test_backups_are_owner_only() {
+    let f = setup();
+    let (status, _) = f.send("POST", "/operators/backups", &f.admin_key).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = f.send("GET", "/operators/backups", &f.view_key).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let backup = f.create().await;
+    let (status, _) = f
+        .send(
+            "POST",
+            &format!(
+                "/operators/backups/{}/verify",
+                backup["id"].as_str().unwrap()
+            ),
+            &f.admin_key,
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_backups_not_configured() {
+    let mut f = setup();
+    f.state.backups = None;
+    let (status, body) = f.send("POST", "/operators/backups", &f.owner_key).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+
+    let (status, _) = f
+        .send("POST", "/operators/backups/missing/verify", &f.owner_key)
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_backup_is_audit_logged() {
+    let f = setup();
+    let backup = f.create().await;
+    let conn = f.state.audit.get().unwrap();
+    let details: String = conn
+        .query_row(
+            "SELECT details FROM audit_logs WHERE action = 'create_backup' AND resource_id = ?1",
+            rusqlite::params![backup["id"].as_str().unwrap()],
+            |row| row.get(0),
+        )
+        .unwrap();
+    let details: Value = serde_json::from_str(&details).unwrap();
+    assert_eq!(details["sha256"], backup["sha256"]);
+}
@@ -0,0 +1,367 @@
+//! Tests for bulk license jobs: counts above 100 are created in the
+//! background a chunk at a time, report their progress as they go, can be
+//! cancelled mid-run, and end with the license IDs or a CSV of activation
+//! codes.
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+use axum::{
+    Router,
+    body::Body,
+    http::{HeaderMap, Request, StatusCode, header},
+    response::Response,
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::bulk_jobs::{self, JobActor};
+use paycheck::config::RateLimitConfig;
+use paycheck::handlers;
+use paycheck::middleware::AuthMethod;
+
+struct JobFixture {
+    state: AppState,
+    org_id: String,
+    project: Project,
+    product: Product,
+    api_key: String,
+}
+
+fn setup() -> JobFixture {
+    let state = create_test_app_state();
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, api_key) =
+        create_test_org_member(&mut conn, &org.id, "admin@test.com", OrgMemberRole::Owner);
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+
+    drop(conn);
+    JobFixture {
+        state,
+        org_id: org.id,
+        project,
+        product,
+        api_key,
+    }
+}
+
+impl JobFixture {
+    fn app(&self) -> Router {
+        handlers::orgs::router(self.state.clone(), RateLimitConfig::disabled())
+            .with_state(self.state.clone())
+    }
+
+    async fn send(&self, method: &str, path: &str, body: Value) -> Response {
+        let request = Request::builder()
+            .method(method)
+            .uri(format!(
+                "/orgs/{}/projects/{}{}",
+                self.org_id, self.project.id, path
+            ))
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        self.app().oneshot(request).await.unwrap()
+    }
+
+    async fn create_licenses(&self, count: i32, activation_codes: bool) -> (StatusCode, Value) {
+        let body = json!({
+            "product_id": self.product.id,
+            "count": count,
+            "activation_codes": activation_codes,
+        });
+        let response = self.send("POST", "/licenses", body).await;
+        let status = response.status();
+        (status, body_json(response).await)
+    }
+
+    async fn job(&self, job_id: &str) -> Value {
+        let response = self
+            .send("GET", &format!("/bulk-jobs/{}", job_id), Value::Null)
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        body_json(response).await
+    }
+
+    /// Poll the job until its worker is done with it.
+    async fn wait_for(&self, job_id: &str) -> Value {
+        for _ in 0..500 {
+            let job = self.job(job_id).await;
+            if job["status"] != "running" {
+                return job;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("bulk job {} still running", job_id);
+    }
+
+    async fn cancel(&self, job_id: &str) -> (StatusCode, Value) {
+        let response = self
+            .send("POST", &format!("/bulk-jobs/{}/cancel", job_id), json!({}))
+            .await;
+        let status = response.status();
+        (status, body_json(response).await)
+    }
+
+    /// A job recorded without a worker, for the test to run chunk by chunk.
+    fn recorded_job(&self, count: i64, activation_codes: bool) -> BulkJob {
+        let conn = self.state.db.get().unwrap();
+        queries::create_bulk_job(
+            &conn,
+            &self.project.id,
+            &self.product.id,
+            count,
+            activation_codes,
+            &BulkLicenseParams::default(),
+            None,
+        )
+        .unwrap()
+    }
+
+    fn actor(&self) -> JobActor {
+        JobActor {
+            user_id: "user-1".into(),
+            auth_method: AuthMethod::ApiKey {
+                key_id: "key-1".into(),
+                key_prefix: "pc_test".into(),
+            },
+            names: Default::default(),
+            headers: HeaderMap::new(),
+            impersonator: None,
+        }
+    }
+
+    fn run_chunk(&self, job: &BulkJob, chunk: i64) -> bool {
+        bulk_jobs::run_chunk(&self.state, &self.org_id, job, &self.actor(), chunk).unwrap()
+    }
+
+    fn license_count(&self) -> i64 {
+        self.state
+            .db
+            .get()
+            .unwrap()
+            .query_row(
+                "SELECT COUNT(*) FROM licenses WHERE project_id = ?1",
+                [&self.project.id],
+                |row| row.get(0),
+            )
+            .unwrap()
+    }
+}
+
+async fn body_json(response: Response) -> Value {
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    serde_json::from_slice(&bytes).unwrap_or(Value::Null)
+}
+
+#[tokio::test]
+async fn test_up_to_100_are_created_in_the_request() {
+    let f = setup();
+    let (status, body) = f.create_licenses(100, false).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["items"].as_array().unwrap().len(), 100);
+}
+
+#[tokio::test]
+async fn test_large_count_runs_as_a_job() {
+    let f = setup();
+    let (status, body) = f.create_licenses(250, false).await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{}", body);
+    assert_eq!(body["status"], "running");
+    assert_eq!(body["requested"], 250);
+    assert_eq!(body["chunk_count"], 3);
+
+    let job = f.wait_for(body["id"].as_str().unwrap()).await;
+    assert_eq!(job["status"], "completed", "{}", job);
+    assert_eq!(job["created_count"], 250);
+    assert_eq!(job["chunks_done"], 3);
+    assert_eq!(job["errors"], json!([]));
+    assert!(job.get("codes_url").is_none());
+
+    let ids: HashSet<&str> = job["license_ids"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|id| id.as_str().unwrap())
+        .collect();
+    assert_eq!(ids.len(), 250);
+    assert_eq!(f.license_count(), 250);
+}
+
+#[tokio::test]
+async fn test_progress_is_reported_chunk_by_chunk() {
+    let f = setup();
+    let job = f.recorded_job(250, false);
+
+    assert!(f.run_chunk(&job, 0));
+    let body = f.job(&job.id).await;
+    assert_eq!(body["status"], "running");
+    assert_eq!(body["created_count"], 100);
+    assert_eq!(body["chunks_done"], 1);
+    assert!(
+        body.get("license_ids").is_none(),
+        "IDs come once the job is over"
+    );
+
+    assert!(f.run_chunk(&job, 1));
+    let body = f.job(&job.id).await;
+    assert_eq!(body["created_count"], 200);
+    assert_eq!(body["chunks_done"], 2);
+}
+
+#[tokio::test]
+async fn test_cancel_mid_run_keeps_created_chunks() {
+    let f = setup();
+    let job = f.recorded_job(250, false);
+    assert!(f.run_chunk(&job, 0));
+
+    let (status, body) = f.cancel(&job.id).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["status"], "cancelled");
+    assert_eq!(body["license_ids"].as_array().unwrap().len(), 100);
+
+    // The next chunk is rolled back and the worker stops
+    assert!(!f.run_chunk(&job, 1));
+    let status = bulk_jobs::run(&f.state, &f.org_id, &job, &f.actor(), Duration::ZERO)
+        .await
+        .unwrap();
+    assert_eq!(status, BulkJobStatus::Cancelled);
+    assert_eq!(f.license_count(), 100);
+    assert_eq!(f.job(&job.id).await["created_count"], 100);
+
+    let (status, _) = f.cancel(&job.id).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn test_run_picks_up_after_attempted_chunks() {
+    let f = setup();
+    let job = f.recorded_job(150, false);
+    assert!(f.run_chunk(&job, 0));
+
+    let status = bulk_jobs::run(&f.state, &f.org_id, &job, &f.actor(), Duration::ZERO)
+        .await
+        .unwrap();
+    assert_eq!(status, BulkJobStatus::Completed);
+    assert_eq!(f.license_count(), 150);
+}
+
+#[tokio::test]
+async fn test_finished_job_offers_activation_codes_csv() {
+    let f = setup();
+    let (status, body) = f.create_licenses(150, true).await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{}", body);
+    let job_id = body["id"].as_str().unwrap();
+
+    let job = f.wait_for(job_id).await;
+    assert_eq!(job["status"], "completed", "{}", job);
+    assert!(job.get("license_ids").is_none());
+    let codes_url = job["codes_url"].as_str().expect("codes_url");
+    assert_eq!(
+        codes_url,
+        format!(
+            "/orgs/{}/projects/{}/bulk-jobs/{}/csv",
+            f.org_id, f.project.id, job_id
+        )
+    );
+
+    let response = f
+        .send("GET", &format!("/bulk-jobs/{}/csv", job_id), Value::Null)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "text/csv; charset=utf-8"
+    );
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let csv = String::from_utf8(bytes.to_vec()).unwrap();
+    let mut lines = csv.lines();
+    assert_eq!(lines.next(), Some("license_id,activation_code"));
+    let rows: Vec<(&str, &str)> = lines.map(|line| line.split_once(',').unwrap()).collect();
+    assert_eq!(rows.len(), 150);
+
+    let conn = f.state.db.get().unwrap();
+    for (license_id, code) in rows {
+        let activation = queries::get_activation_code_by_code(&conn, code)
+            .unwrap()
+            .expect("code issued");
+        assert_eq!(activation.license_id, license_id);
+    }
+}
+
+#[tokio::test]
+async fn test_csv_needs_codes_and_a_finished_job() {
+    let f = setup();
+    let with_codes = f.recorded_job(150, true);
+    let response = f
+        .send(
+            "GET",
+            &format!("/bulk-jobs/{}/csv", with_codes.id),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::CONFLICT, "still running");
+
+    let without_codes = f.recorded_job(150, false);
+    f.cancel(&without_codes.id).await;
+    let response = f
+        .send(
+            "GET",
+            &format!("/bulk-jobs/{}/csv", without_codes.id),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_interrupted_jobs_are_failed_at_startup() {
+    let f = setup();
+    let job = f.recorded_job(250, false);
+    assert!(f.run_chunk(&job, 0));
+
+    assert_eq!(bulk_jobs::fail_interrupted(&f.state).unwrap(), 1);
+    let body = f.job(&job.id).await;
+    assert_eq!(body["status"], "failed");
+    assert_eq!(body["error"], "Interrupted by a server restart");
+    assert_eq!(body["license_ids"].as_array().unwrap().len(), 100);
+}
+
+#[tokio::test]
+async fn test_job_in_another_project_is_not_found() {
+    let f = setup();
+    let other = {
+        let conn = f.state.db.get().unwrap();
+        create_test_project(&conn, &f.org_id, "Other", &test_master_key())
+    };
+    let job = {
+        let conn = f.state.db.get().unwrap();
+        queries::create_bulk_job(
+            &conn,
+            &other.id,
+            &f.product.id,
+            150,
+            false,
+            &BulkLicenseParams::default(),
+            None,
+        )
+        .unwrap()
+    };
+
+    let response = f
+        .send("GET", &format!("/bulk-jobs/{}", job.id), Value::Null)
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
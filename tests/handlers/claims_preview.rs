@@ -0,0 +1,200 @@
+//! Tests for the license claims preview: it must match what /redeem issues.
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode},
+};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::handlers;
+
+struct PreviewFixture {
+    state: AppState,
+    org_id: String,
+    project: Project,
+    license: License,
+    api_key: String,
+}
+
+fn setup() -> PreviewFixture {
+    let mut state = create_test_app_state();
+    // Pinned so the preview and the redemption compute the same expirations
+    state.clock = Clock::fixed(chrono::Utc::now().timestamp());
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, api_key) =
+        create_test_org_member(&mut conn, &org.id, "admin@test.com", OrgMemberRole::Owner);
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+    let license = create_test_license(
+        &conn,
+        &project.id,
+        &product.id,
+        Some(future_timestamp(ONE_YEAR)),
+    );
+
+    drop(conn);
+    PreviewFixture {
+        state,
+        org_id: org.id,
+        project,
+        license,
+        api_key,
+    }
+}
+
+fn app(state: &AppState) -> Router {
+    public_app(state.clone()).merge(
+        handlers::orgs::router(state.clone(), paycheck::config::RateLimitConfig::disabled())
+            .with_state(state.clone()),
+    )
+}
+
+async fn preview(f: &PreviewFixture, project_id: &str) -> (StatusCode, Value) {
+    let response = app(&f.state)
+        .oneshot(
+            Request::builder()
+                .method("GET")
+                .uri(format!(
+                    "/orgs/{}/projects/{}/licenses/{}/claims-preview",
+                    f.org_id, project_id, f.license.id
+                ))
+                .header("Authorization", format!("Bearer {}", f.api_key))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (
+        status,
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    )
+}
+
+/// Activate a device through /redeem and return the response body
+async fn redeem(f: &PreviewFixture) -> Value {
+    let code = create_test_activation_code(
+        &f.state.db.get().unwrap(),
+        &f.license.id,
+        &f.project.license_key_prefix,
+    );
+    let response = app(&f.state)
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/redeem")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    json!({
+                        "public_key": f.project.public_key,
+                        "code": code.code,
+                        "device_id": "device-1",
+                        "device_type": "machine"
+                    })
+                    .to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    serde_json::from_slice(&bytes).unwrap()
+}
+
+/// Decode a token's payload without verifying it
+fn token_payload(token: &str) -> Value {
+    let payload = token.split('.').nth(1).unwrap();
+    serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn test_preview_matches_redeemed_token() {
+    let f = setup();
+
+    let (status, preview) = preview(&f, &f.project.id).await;
+    assert_eq!(status, StatusCode::OK);
+    let redeemed = redeem(&f).await;
+    let issued = token_payload(redeemed["token"].as_str().unwrap());
+
+    let previewed = &preview["claims"];
+    for claim in [
+        "iss",
+        "sub",
+        "aud",
+        "license_exp",
+        "updates_exp",
+        "tier",
+        "features",
+        "product_id",
+    ] {
+        assert_eq!(previewed[claim], issued[claim], "claim {}", claim);
+    }
+    // Timestamps come from the pinned clock in the preview, the real one when signing
+    for claim in ["iat", "nbf", "exp"] {
+        assert!(previewed[claim].is_i64(), "claim {}", claim);
+    }
+    assert_eq!(
+        previewed["exp"].as_i64().unwrap() - previewed["iat"].as_i64().unwrap(),
+        issued["exp"].as_i64().unwrap() - issued["iat"].as_i64().unwrap(),
+    );
+    assert!(previewed.get("jti").is_none());
+    assert_eq!(previewed["device_id"], "preview-device");
+    assert_eq!(issued["device_id"], "device-1");
+
+    assert_eq!(preview["license_exp"], redeemed["license_exp"]);
+    assert_eq!(preview["updates_exp"], redeemed["updates_exp"]);
+    assert_eq!(preview["redeemable"], true);
+}
+
+#[tokio::test]
+async fn test_preview_does_not_activate_a_device() {
+    let f = setup();
+
+    let (status, _) = preview(&f, &f.project.id).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let conn = f.state.db.get().unwrap();
+    let devices = queries::list_devices_for_license(&conn, &f.license.id).unwrap();
+    assert!(devices.is_empty());
+}
+
+#[tokio::test]
+async fn test_preview_flags_revoked_license() {
+    let f = setup();
+    {
+        let conn = f.state.db.get().unwrap();
+        queries::revoke_license(&conn, &f.license.id, &RevokeLicense::default(), None).unwrap();
+    }
+
+    let (status, preview) = preview(&f, &f.project.id).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(preview["redeemable"], false);
+}
+
+#[tokio::test]
+async fn test_preview_rejects_license_from_another_project() {
+    let f = setup();
+    let other = {
+        let conn = f.state.db.get().unwrap();
+        create_test_project(&conn, &f.org_id, "Other Project", &test_master_key())
+    };
+
+    let (status, _) = preview(&f, &other.id).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,168 @@
+//! Tests for the org compliance settings: per-org audit log retention, the
+//! effective policy after the instance default, and owner-only access.
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::handlers;
+
+struct Fixture {
+    state: AppState,
+    org_id: String,
+    owner_key: String,
+    admin_key: String,
+}
+
+/// An org with an owner and an admin, on an instance that keeps public
+/// audit entries for 90 days.
+fn setup() -> Fixture {
+    let mut state = create_test_app_state();
+    state.audit_log_enabled = true;
+    state.public_audit_retention_days = 90;
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, owner_key) =
+        create_test_org_member(&mut conn, &org.id, "owner@test.com", OrgMemberRole::Owner);
+    let (_, _, admin_key) =
+        create_test_org_member(&mut conn, &org.id, "admin@test.com", OrgMemberRole::Admin);
+
+    drop(conn);
+    Fixture {
+        state,
+        org_id: org.id,
+        owner_key,
+        admin_key,
+    }
+}
+
+impl Fixture {
+    async fn send(&self, method: &str, api_key: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let app = handlers::orgs::router(
+            self.state.clone(),
+            paycheck::config::RateLimitConfig::disabled(),
+        )
+        .with_state(self.state.clone());
+        let mut request = Request::builder()
+            .method(method)
+            .uri(format!("/orgs/{}/compliance-settings", self.org_id))
+            .header("Authorization", format!("Bearer {}", api_key));
+        let body = match body {
+            Some(body) => {
+                request = request.header("content-type", "application/json");
+                Body::from(body.to_string())
+            }
+            None => Body::empty(),
+        };
+        let response = app.oneshot(request.body(body).unwrap()).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        )
+    }
+
+    async fn update(&self, body: Value) -> (StatusCode, Value) {
+        self.send("PUT", &self.owner_key, Some(body)).await
+    }
+}
+
+#[tokio::test]
+async fn test_defaults_follow_the_instance() {
+    let f = setup();
+
+    let (status, json) = f.send("GET", &f.owner_key, None).await;
+    assert_eq!(status, StatusCode::OK, "{}", json);
+    assert!(json["public_audit_retention_days"].is_null());
+    assert!(json["admin_audit_retention_days"].is_null());
+    assert_eq!(
+        json["effective"],
+        json!({ "public_audit_retention_days": 90, "admin_audit_retention_days": null })
+    );
+}
+
+#[tokio::test]
+async fn test_set_and_clear_retention() {
+    let f = setup();
+
+    let (status, json) = f
+        .update(json!({ "public_audit_retention_days": 30, "admin_audit_retention_days": 400 }))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", json);
+    assert_eq!(json["public_audit_retention_days"], 30);
+    assert_eq!(
+        json["effective"],
+        json!({ "public_audit_retention_days": 30, "admin_audit_retention_days": 400 })
+    );
+
+    // The purge sees the org's policy
+    let conn = f.state.db.get().unwrap();
+    let retention = queries::list_org_audit_retention(&conn).unwrap();
+    assert!(retention.contains(&OrgAuditRetention {
+        org_id: f.org_id.clone(),
+        public_days: Some(30),
+        admin_days: Some(400),
+    }));
+
+    // Absent fields are left alone; null goes back to the default
+    let (status, json) = f
+        .update(json!({ "public_audit_retention_days": null }))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", json);
+    assert_eq!(
+        json["effective"],
+        json!({ "public_audit_retention_days": 90, "admin_audit_retention_days": 400 })
+    );
+
+    let audit_conn = f.state.audit.get().unwrap();
+    let count: i64 = audit_conn
+        .query_row(
+            "SELECT COUNT(*) FROM audit_logs WHERE action = 'update_org_compliance_settings' AND org_id = ?1",
+            [&f.org_id],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(count, 2);
+}
+
+#[tokio::test]
+async fn test_retention_must_be_positive() {
+    let f = setup();
+    for body in [
+        json!({ "public_audit_retention_days": 0 }),
+        json!({ "admin_audit_retention_days": -5 }),
+    ] {
+        let (status, _) = f.update(body.clone()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    }
+
+    let (_, json) = f.send("GET", &f.owner_key, None).await;
+    assert!(json["public_audit_retention_days"].is_null());
+    assert!(json["admin_audit_retention_days"].is_null());
+}
+
+#[tokio::test]
+async fn test_owner_only() {
+    let f = setup();
+    let (status, _) = f.send("GET", &f.admin_key, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = f
+        .send(
+            "PUT",
+            &f.admin_key,
+            Some(json!({ "admin_audit_retention_days": 1 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
@@ -0,0 +1,273 @@
+//! Tests for custom domain management: adding a hostname hands out a TXT
+//! record, verifying looks it up on a stub DNS-over-HTTPS resolver, and only
+//! a verified domain routes requests.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use axum::{
+    Json as AxumJson, Router,
+    body::Body,
+    extract::Query,
+    http::{Request, StatusCode},
+    routing::get,
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::custom_domains::DnsResolver;
+use paycheck::handlers;
+
+/// TXT records the stub resolver serves, by name.
+type Records = Arc<Mutex<HashMap<String, Vec<String>>>>;
+
+/// Answer DNS-over-HTTPS JSON queries from `records`, or fail every lookup
+/// with a 500 when `records` is None. Returns the resolver URL.
+async fn stub_resolver(records: Option<Records>) -> String {
+    let app = Router::new().route(
+        "/dns-query",
+        get(move |Query(query): Query<HashMap<String, String>>| {
+            let records = records.clone();
+            async move {
+                let Some(records) = records else {
+                    return Err(StatusCode::INTERNAL_SERVER_ERROR);
+                };
+                assert_eq!(query["type"], "TXT");
+                let found = records.lock().unwrap().get(&query["name"]).cloned();
+                Ok(AxumJson(match found {
+                    Some(values) => json!({
+                        "Status": 0,
+                        "Answer": values
+                            .iter()
+                            .map(|v| json!({
+                                "name": query["name"],
+                                "type": 16,
+                                "data": format!("\"{}\"", v)
+                            }))
+                            .collect::<Vec<_>>()
+                    }),
+                    None => json!({ "Status": 3 }),
+                }))
+            }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    format!("http://{}/dns-query", addr)
+}
+
+struct DomainFixture {
+    state: AppState,
+    app: Router,
+    org_id: String,
+    project_id: String,
+    other_project_id: String,
+    key: String,
+}
+
+async fn setup(records: Option<Records>) -> DomainFixture {
+    let state = create_test_app_state();
+    let mut conn = state.db.get().unwrap();
+    let (_, key) = create_test_operator(&mut conn, "admin@test.com", OperatorRole::Admin);
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    let other = create_test_project(&conn, &org.id, "Other Project", &test_master_key());
+    drop(conn);
+
+    let resolver = DnsResolver::new(&stub_resolver(records).await);
+    let app = handlers::operators::custom_domains_router(state.clone(), resolver)
+        .with_state(state.clone());
+    DomainFixture {
+        state,
+        app,
+        org_id: org.id,
+        project_id: project.id,
+        other_project_id: other.id,
+        key,
+    }
+}
+
+impl DomainFixture {
+    async fn request(&self, method: &str, path: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(format!(
+                "/operators/organizations/{}/projects/{}/custom-domains{}",
+                self.org_id, self.project_id, path
+            ))
+            .header("Authorization", format!("Bearer {}", self.key));
+        let body = match body {
+            Some(body) => {
+                request = request.header("content-type", "application/json");
+                Body::from(body.to_string())
+            }
+            None => Body::empty(),
+        };
+        let response = self
+            .app
+            .clone()
+            .oneshot(request.body(body).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        )
+    }
+
+    async fn add(&self, hostname: &str) -> (StatusCode, Value) {
+        self.request("POST", "", Some(json!({ "hostname": hostname })))
+            .await
+    }
+
+    async fn verify(&self, hostname: &str) -> (StatusCode, Value) {
+        self.request("POST", &format!("/{}/verify", hostname), None)
+            .await
+    }
+
+    fn routed_project(&self, hostname: &str) -> Option<String> {
+        let conn = self.state.db.get().unwrap();
+        queries::get_verified_custom_domain_project_id(&conn, hostname).unwrap()
+    }
+}
+
+#[tokio::test]
+async fn test_domain_is_routed_once_its_txt_record_is_published() {
+    let records = Records::default();
+    let f = setup(Some(records.clone())).await;
+
+    let (status, domain) = f.add("Licenses.Example.com").await;
+    assert_eq!(status, StatusCode::OK, "{}", domain);
+    assert_eq!(domain["hostname"], "licenses.example.com");
+    assert_eq!(domain["verified"], false);
+    assert_eq!(
+        domain["txt_record"]["name"],
+        "_paycheck-challenge.licenses.example.com"
+    );
+    let token = domain["txt_record"]["value"].as_str().unwrap().to_string();
+    assert!(token.starts_with("pcv_"));
+
+    // Not published yet
+    let (status, domain) = f.verify("licenses.example.com").await;
+    assert_eq!(status, StatusCode::OK, "{}", domain);
+    assert_eq!(domain["verified"], false);
+    assert!(domain["verification_error"].is_string());
+    assert_eq!(f.routed_project("licenses.example.com"), None);
+
+    records.lock().unwrap().insert(
+        "_paycheck-challenge.licenses.example.com".into(),
+        vec!["unrelated".into(), token],
+    );
+    let (status, domain) = f.verify("licenses.example.com").await;
+    assert_eq!(status, StatusCode::OK, "{}", domain);
+    assert_eq!(domain["verified"], true);
+    assert!(domain.get("verification_error").is_none());
+    assert_eq!(
+        f.routed_project("licenses.example.com"),
+        Some(f.project_id.clone())
+    );
+
+    let entries = queries::query_audit_logs(
+        &f.state.audit.get().unwrap(),
+        &AuditLogQuery {
+            action: Some("verify_custom_domain".into()),
+            ..Default::default()
+        },
+    )
+    .unwrap()
+    .0;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].resource_id, f.project_id);
+}
+
+#[tokio::test]
+async fn test_wrong_token_does_not_verify() {
+    let records = Records::default();
+    records.lock().unwrap().insert(
+        "_paycheck-challenge.licenses.example.com".into(),
+        vec!["pcv_someone_elses".into()],
+    );
+    let f = setup(Some(records)).await;
+    f.add("licenses.example.com").await;
+
+    let (status, domain) = f.verify("licenses.example.com").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(domain["verified"], false);
+    assert_eq!(f.routed_project("licenses.example.com"), None);
+}
+
+#[tokio::test]
+async fn test_failed_lookup_leaves_domain_unverified() {
+    let f = setup(None).await;
+    f.add("licenses.example.com").await;
+
+    let (status, domain) = f.verify("licenses.example.com").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(domain["verified"], false);
+    assert_eq!(
+        domain["verification_error"],
+        "Couldn't look up the TXT record, try again shortly"
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_and_taken_hostnames_rejected() {
+    let f = setup(Some(Records::default())).await;
+    for hostname in ["localhost", "10.0.0.1", "https://licenses.example.com"] {
+        let (status, _) = f.add(hostname).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", hostname);
+    }
+
+    let conn = f.state.db.get().unwrap();
+    queries::create_custom_domain(
+        &conn,
+        &f.org_id,
+        &f.other_project_id,
+        "licenses.example.com",
+        "pcv_token",
+        None,
+    )
+    .unwrap();
+    drop(conn);
+    let (status, _) = f.add("LICENSES.example.com").await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    // Another project's domain can't be verified or removed through this one
+    let (status, _) = f.verify("licenses.example.com").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = f.request("DELETE", "/licenses.example.com", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_removed_domain_stops_routing() {
+    let records = Records::default();
+    let f = setup(Some(records.clone())).await;
+    let (_, domain) = f.add("licenses.example.com").await;
+    records.lock().unwrap().insert(
+        "_paycheck-challenge.licenses.example.com".into(),
+        vec![domain["txt_record"]["value"].as_str().unwrap().into()],
+    );
+    f.verify("licenses.example.com").await;
+
+    let (status, list) = f.request("GET", "", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(list.as_array().unwrap().len(), 1);
+
+    let (status, _) = f.request("DELETE", "/licenses.example.com", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(f.routed_project("licenses.example.com"), None);
+    let (_, list) = f.request("GET", "", None).await;
+    assert_eq!(list, json!([]));
+}
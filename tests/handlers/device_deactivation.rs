@@ -0,0 +1,236 @@
+//! Tests for bulk device deactivation: `.../devices/deactivate-all` on one
+//! license and `.../devices/deactivate-by-filter` across a project.
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::handlers;
+
+struct DeviceFixture {
+    state: AppState,
+    org_id: String,
+    project_id: String,
+    license_id: String,
+    other_license_id: String,
+    api_key: String,
+}
+
+fn setup() -> DeviceFixture {
+    let state = create_test_app_state();
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, api_key) =
+        create_test_org_member(&mut conn, &org.id, "owner@test.com", OrgMemberRole::Owner);
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+    let license = create_test_license(&conn, &project.id, &product.id, None);
+    let other_license = create_test_license(&conn, &project.id, &product.id, None);
+
+    create_test_device(&conn, &license.id, "laptop", DeviceType::Machine);
+    create_test_device(&conn, &license.id, "desktop", DeviceType::Machine);
+    create_test_device(&conn, &license.id, "install-1", DeviceType::Uuid);
+    create_test_device(
+        &conn,
+        &other_license.id,
+        "other-laptop",
+        DeviceType::Machine,
+    );
+    create_test_device(&conn, &other_license.id, "other-install", DeviceType::Uuid);
+    conn.execute(
+        "UPDATE licenses SET activation_count = 3 WHERE id = ?1",
+        [&license.id],
+    )
+    .unwrap();
+
+    drop(conn);
+    DeviceFixture {
+        state,
+        org_id: org.id,
+        project_id: project.id,
+        license_id: license.id,
+        other_license_id: other_license.id,
+        api_key,
+    }
+}
+
+impl DeviceFixture {
+    async fn post(&self, path: &str, body: Value) -> (StatusCode, Value) {
+        let app = handlers::orgs::router(
+            self.state.clone(),
+            paycheck::config::RateLimitConfig::disabled(),
+        )
+        .with_state(self.state.clone());
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(format!(
+                        "/orgs/{}/projects/{}{}",
+                        self.org_id, self.project_id, path
+                    ))
+                    .header("Authorization", format!("Bearer {}", self.api_key))
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        )
+    }
+
+    async fn deactivate_all(&self, body: Value) -> (StatusCode, Value) {
+        let path = format!("/licenses/{}/devices/deactivate-all", self.license_id);
+        self.post(&path, body).await
+    }
+
+    async fn deactivate_by_filter(&self, body: Value) -> (StatusCode, Value) {
+        self.post("/devices/deactivate-by-filter", body).await
+    }
+
+    fn device_ids(&self, license_id: &str) -> Vec<String> {
+        let conn = self.state.db.get().unwrap();
+        let mut ids: Vec<String> = queries::list_devices_for_license(&conn, license_id)
+            .unwrap()
+            .into_iter()
+            .map(|d| d.device_id)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    fn count(&self, sql: &str) -> i64 {
+        let conn = self.state.db.get().unwrap();
+        conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+}
+
+#[tokio::test]
+async fn test_deactivate_all_keeps_excepted_devices() {
+    let f = setup();
+    let (status, body) = f
+        .deactivate_all(json!({ "except_device_ids": ["laptop"] }))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["deactivated"], 2);
+
+    let mut deactivated: Vec<&str> = body["devices"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|d| d["device_id"].as_str().unwrap())
+        .collect();
+    deactivated.sort();
+    assert_eq!(deactivated, ["desktop", "install-1"]);
+
+    assert_eq!(f.device_ids(&f.license_id), ["laptop"]);
+    assert_eq!(
+        f.device_ids(&f.other_license_id).len(),
+        2,
+        "other licenses' devices are untouched"
+    );
+    assert_eq!(f.count("SELECT COUNT(*) FROM revoked_jtis"), 2);
+}
+
+#[tokio::test]
+async fn test_deactivate_all_does_not_give_back_activations() {
+    let f = setup();
+    let (status, body) = f.deactivate_all(json!({})).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["deactivated"], 3);
+    assert!(f.device_ids(&f.license_id).is_empty());
+
+    // activation_limit caps lifetime activations, so deactivating doesn't
+    // refund them; only device_limit frees up
+    let conn = f.state.db.get().unwrap();
+    let license = queries::get_license_by_id(&conn, &f.license_id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(license.activation_count, 3);
+}
+
+#[tokio::test]
+async fn test_deactivate_all_filters_by_activation_window_and_type() {
+    let f = setup();
+    {
+        let conn = f.state.db.get().unwrap();
+        conn.execute(
+            "UPDATE devices SET activated_at = 1000 WHERE device_id = 'laptop'",
+            [],
+        )
+        .unwrap();
+    }
+
+    let (status, body) = f
+        .deactivate_all(json!({ "activated_after": 2000, "device_type": "machine" }))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["deactivated"], 1);
+    assert_eq!(body["devices"][0]["device_id"], "desktop");
+    assert_eq!(f.device_ids(&f.license_id), ["install-1", "laptop"]);
+}
+
+#[tokio::test]
+async fn test_deactivate_by_filter_rejects_empty_filter() {
+    let f = setup();
+    for body in [
+        json!({ "confirm": true }),
+        json!({ "except_device_ids": ["laptop"], "confirm": true }),
+    ] {
+        let (status, response) = f.deactivate_by_filter(body).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", response);
+    }
+    assert_eq!(f.count("SELECT COUNT(*) FROM devices"), 5);
+}
+
+#[tokio::test]
+async fn test_deactivate_by_filter_requires_confirm() {
+    let f = setup();
+    let (status, response) = f
+        .deactivate_by_filter(json!({ "device_type": "uuid" }))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", response);
+    assert_eq!(f.count("SELECT COUNT(*) FROM devices"), 5);
+}
+
+#[tokio::test]
+async fn test_deactivate_by_filter_spans_licenses_and_is_audited() {
+    let f = setup();
+    let (status, body) = f
+        .deactivate_by_filter(json!({ "device_type": "uuid", "confirm": true }))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["deactivated"], 2);
+    assert_eq!(f.device_ids(&f.license_id), ["desktop", "laptop"]);
+    assert_eq!(f.device_ids(&f.other_license_id), ["other-laptop"]);
+
+    let entries = queries::query_audit_logs(
+        &f.state.audit.get().unwrap(),
+        &AuditLogQuery {
+            action: Some("bulk_deactivate_devices".into()),
+            ..Default::default()
+        },
+    )
+    .unwrap()
+    .0;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].resource_id, f.project_id);
+    let details = entries[0].details.as_ref().unwrap();
+    assert_eq!(details["filter"]["device_type"], "uuid");
+    assert_eq!(details["deactivated"], 2);
+}
@@ -0,0 +1,299 @@
+//! Tests for dormant licenses: the maintenance job that marks licenses never
+//! activated within the project's `dormant_after_days`, the `license_dormant`
+//! webhook and opt-in nudge, the list filter, and clearing on activation.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    Json, Router,
+    body::Body,
+    http::{Request, StatusCode},
+    routing::post,
+};
+use serde_json::{Value, json};
+use tokio::sync::mpsc;
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::email::EmailTrigger;
+use paycheck::handlers;
+
+const DAY: i64 = 86400;
+
+struct DormantFixture {
+    state: AppState,
+    org_id: String,
+    project: Project,
+    api_key: String,
+    never_activated: License,
+    activated: License,
+}
+
+/// A project that marks licenses dormant after 30 days, with one license
+/// that was never activated, one that was, and a revoked and a trial license
+/// that were never activated either.
+fn setup() -> DormantFixture {
+    let mut state = create_test_app_state();
+    state.audit_log_enabled = true;
+    state.email_service =
+        Arc::new(EmailService::new(None, "test@example.com".to_string()).with_localhost_urls(true));
+    let mut conn = state.db.get().unwrap();
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, api_key) =
+        create_test_org_member(&mut conn, &org.id, "owner@test.com", OrgMemberRole::Owner);
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    conn.execute(
+        "UPDATE projects SET dormant_after_days = 30 WHERE id = ?1",
+        [&project.id],
+    )
+    .unwrap();
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+
+    let never_activated = create_test_license(&conn, &project.id, &product.id, None);
+    let activated = create_test_license(&conn, &project.id, &product.id, None);
+    create_test_device(&conn, &activated.id, "device-1", DeviceType::Uuid);
+    let revoked = create_test_license(&conn, &project.id, &product.id, None);
+    queries::revoke_license(&conn, &revoked.id, &RevokeLicense::default(), None).unwrap();
+    let trial = create_test_license(&conn, &project.id, &product.id, None);
+    conn.execute(
+        "UPDATE licenses SET is_trial = 1 WHERE id = ?1",
+        [&trial.id],
+    )
+    .unwrap();
+    drop(conn);
+
+    DormantFixture {
+        state,
+        org_id: org.id,
+        project,
+        api_key,
+        never_activated,
+        activated,
+    }
+}
+
+impl DormantFixture {
+    /// Run the job as if `days` had passed since the licenses were bought.
+    async fn run_after(&self, days: i64) -> usize {
+        let now = chrono::Utc::now().timestamp() + days * DAY;
+        handlers::orgs::process_dormant_licenses(&self.state, now)
+            .await
+            .unwrap()
+    }
+
+    fn license(&self, id: &str) -> License {
+        let conn = self.state.db.get().unwrap();
+        queries::get_license_by_id(&conn, id).unwrap().unwrap()
+    }
+
+    fn set_project(&self, sql: &str, value: &str) {
+        let conn = self.state.db.get().unwrap();
+        conn.execute(sql, rusqlite::params![value, self.project.id])
+            .unwrap();
+    }
+
+    async fn list_dormant(&self) -> Value {
+        let app = handlers::orgs::router(
+            self.state.clone(),
+            paycheck::config::RateLimitConfig::disabled(),
+        )
+        .with_state(self.state.clone());
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri(format!(
+                        "/orgs/{}/projects/{}/licenses?dormant=true",
+                        self.org_id, self.project.id
+                    ))
+                    .header("Authorization", format!("Bearer {}", self.api_key))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+}
+
+/// Record every JSON body POSTed to `/hook`. Returns the URL and the bodies.
+async fn stub_webhook() -> (String, mpsc::UnboundedReceiver<Value>) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let app = Router::new().route(
+        "/hook",
+        post(move |Json(body): Json<Value>| {
+            let tx = tx.clone();
+            async move {
+                let _ = tx.send(body);
+                StatusCode::OK
+            }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    (format!("http://{}/hook", addr), rx)
+}
+
+/// Webhook bodies received so far.
+async fn received(rx: &mut mpsc::UnboundedReceiver<Value>) -> Vec<Value> {
+    let mut bodies = Vec::new();
+    while let Ok(Some(body)) = tokio::time::timeout(Duration::from_millis(200), rx.recv()).await {
+        bodies.push(body);
+    }
+    bodies
+}
+
+#[tokio::test]
+async fn test_marks_licenses_never_activated() {
+    let f = setup();
+
+    // Too early: nothing is 30 days old yet
+    assert_eq!(f.run_after(10).await, 0);
+    assert_eq!(f.license(&f.never_activated.id).dormant_since, None);
+
+    assert_eq!(f.run_after(31).await, 1);
+    let marked = f.license(&f.never_activated.id);
+    assert!(marked.dormant_since.is_some());
+    assert_eq!(f.license(&f.activated.id).dormant_since, None);
+
+    // Already marked licenses aren't marked (or announced) again
+    assert_eq!(f.run_after(32).await, 0);
+    assert_eq!(
+        f.license(&f.never_activated.id).dormant_since,
+        marked.dormant_since
+    );
+
+    let list = f.list_dormant().await;
+    assert_eq!(list["total"], 1);
+    assert_eq!(list["items"][0]["id"], f.never_activated.id);
+
+    let stats = queries::get_org_tenant_stats(&f.state.db.get().unwrap(), &f.org_id).unwrap();
+    assert_eq!((stats.license_count, stats.dormant_license_count), (4, 1));
+}
+
+#[tokio::test]
+async fn test_projects_without_the_setting_are_left_alone() {
+    let f = setup();
+    let conn = f.state.db.get().unwrap();
+    conn.execute(
+        "UPDATE projects SET dormant_after_days = NULL WHERE id = ?1",
+        [&f.project.id],
+    )
+    .unwrap();
+    drop(conn);
+
+    assert_eq!(f.run_after(365).await, 0);
+    assert_eq!(f.list_dormant().await["total"], 0);
+}
+
+#[tokio::test]
+async fn test_first_activation_clears_dormant() {
+    let f = setup();
+    f.run_after(31).await;
+    assert!(f.license(&f.never_activated.id).dormant_since.is_some());
+
+    let code = queries::create_activation_code(
+        &f.state.db.get().unwrap(),
+        &f.never_activated.id,
+        &f.project.license_key_prefix,
+    )
+    .unwrap();
+    let response = public_app(f.state.clone())
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/redeem")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    json!({
+                        "public_key": f.project.public_key,
+                        "code": code.code,
+                        "device_id": "late-device",
+                        "device_type": "uuid"
+                    })
+                    .to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let license = f.license(&f.never_activated.id);
+    assert_eq!(license.dormant_since, None);
+    assert_eq!(license.activation_count, 1);
+    assert_eq!(f.list_dormant().await["total"], 0);
+}
+
+#[tokio::test]
+async fn test_dormant_event_sent_to_email_webhook() {
+    let f = setup();
+    let (url, mut rx) = stub_webhook().await;
+    f.set_project(
+        "UPDATE projects SET email_webhook_url = ?1 WHERE id = ?2",
+        &url,
+    );
+
+    f.run_after(31).await;
+    let bodies = received(&mut rx).await;
+    assert_eq!(bodies.len(), 1, "{:?}", bodies);
+    assert_eq!(bodies[0]["event"], "license_dormant");
+    assert_eq!(bodies[0]["license_id"], f.never_activated.id);
+    assert_eq!(bodies[0]["product_name"], "Pro Plan");
+    assert_eq!(bodies[0]["purchased_at"], f.never_activated.created_at);
+
+    // No nudge without the opt-in
+    let conn = f.state.db.get().unwrap();
+    assert!(
+        queries::list_recent_emails_for_license(&conn, &f.never_activated.id, 10)
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[tokio::test]
+async fn test_opt_in_nudge_sends_fresh_code() {
+    let f = setup();
+    let (url, mut rx) = stub_webhook().await;
+    f.set_project(
+        "UPDATE projects SET email_webhook_url = ?1, dormant_nudge_email = 1 WHERE id = ?2",
+        &url,
+    );
+
+    f.run_after(31).await;
+    let bodies = received(&mut rx).await;
+    assert_eq!(bodies.len(), 2, "{:?}", bodies);
+    assert_eq!(bodies[0]["event"], "license_dormant");
+    let nudge = &bodies[1];
+    assert_eq!(nudge["event"], "activation_code_created");
+    assert_eq!(nudge["trigger"], "dormant_nudge");
+    assert_eq!(nudge["license_id"], f.never_activated.id);
+    // There's no stored address to send; the webhook finds the customer
+    assert!(nudge["email"].is_null());
+    assert!(
+        nudge["code"]
+            .as_str()
+            .unwrap()
+            .starts_with(&f.project.license_key_prefix)
+    );
+
+    let conn = f.state.db.get().unwrap();
+    let emails = queries::list_recent_emails_for_license(&conn, &f.never_activated.id, 10).unwrap();
+    assert_eq!(emails.len(), 1);
+    assert_eq!(emails[0].trigger, EmailTrigger::DormantNudge);
+    assert_eq!(
+        Some(emails[0].to_email_hash.as_str()),
+        f.never_activated.email_hash.as_deref()
+    );
+}
@@ -0,0 +1,317 @@
+//! Tests for the org email config: setting the org's Resend API key and
+//! default sender, and test sends through a stub Resend server.
+
+use std::sync::{Arc, Mutex};
+
+use axum::{
+    Router,
+    body::Body,
+    http::{HeaderMap, Request, StatusCode},
+    routing::post,
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::handlers;
+
+const ORG_KEY: &str = "re_org_live_key_1234567890";
+
+/// A request the stub Resend server received: Authorization header and body
+type Captured = Arc<Mutex<Vec<(String, Value)>>>;
+
+/// Serve `status` and `body` for every POST to `/emails`, recording each
+/// request. Returns the URL to point the email service at.
+async fn stub_resend(status: StatusCode, body: Value) -> (String, Captured) {
+    let captured: Captured = Arc::default();
+    let log = captured.clone();
+    let app = Router::new().route(
+        "/emails",
+        post(
+            move |headers: HeaderMap, axum::Json(request): axum::Json<Value>| {
+                let body = body.clone();
+                let log = log.clone();
+                async move {
+                    let auth = headers
+                        .get("authorization")
+                        .and_then(|v| v.to_str().ok())
+                        .unwrap_or_default()
+                        .to_string();
+                    log.lock().unwrap().push((auth, request));
+                    (status, axum::Json(body))
+                }
+            },
+        ),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    (format!("http://{}/emails", addr), captured)
+}
+
+struct Fixture {
+    state: AppState,
+    org_id: String,
+    owner_key: String,
+    admin_key: String,
+}
+
+/// An org with an owner and an admin, sending email through `resend_url`.
+/// The system key is set so tests can tell it apart from the org's key.
+fn setup(resend_url: Option<String>) -> Fixture {
+    let mut state = create_test_app_state();
+    if let Some(url) = resend_url {
+        state.email_service = Arc::new(
+            EmailService::new(Some("re_system_key".into()), "noreply@test.com".into())
+                .with_api_url(url),
+        );
+    }
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, owner_key) =
+        create_test_org_member(&mut conn, &org.id, "owner@test.com", OrgMemberRole::Owner);
+    let (_, _, admin_key) =
+        create_test_org_member(&mut conn, &org.id, "admin@test.com", OrgMemberRole::Admin);
+
+    drop(conn);
+    Fixture {
+        state,
+        org_id: org.id,
+        owner_key,
+        admin_key,
+    }
+}
+
+impl Fixture {
+    async fn send(
+        &self,
+        method: &str,
+        path: &str,
+        api_key: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let app = handlers::orgs::router(
+            self.state.clone(),
+            paycheck::config::RateLimitConfig::disabled(),
+        )
+        .with_state(self.state.clone());
+        let mut request = Request::builder()
+            .method(method)
+            .uri(format!("/orgs/{}/email-config{}", self.org_id, path))
+            .header("Authorization", format!("Bearer {}", api_key));
+        let body = match body {
+            Some(body) => {
+                request = request.header("content-type", "application/json");
+                Body::from(body.to_string())
+            }
+            None => Body::empty(),
+        };
+        let response = app.oneshot(request.body(body).unwrap()).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        )
+    }
+
+    async fn configure(&self, body: Value) -> (StatusCode, Value) {
+        self.send("PUT", "", &self.owner_key, Some(body)).await
+    }
+
+    async fn send_test(&self) -> (StatusCode, Value) {
+        self.send("POST", "/test", &self.owner_key, None).await
+    }
+
+    fn audit_count(&self, action: &str) -> i64 {
+        let conn = self.state.audit.get().unwrap();
+        conn.query_row(
+            "SELECT COUNT(*) FROM audit_logs WHERE action = ?1 AND org_id = ?2",
+            rusqlite::params![action, self.org_id],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+}
+
+// ============ Config ============
+
+#[tokio::test]
+async fn test_set_key_stores_it_encrypted_and_returns_it_masked() {
+    let f = setup(None);
+
+    let (status, json) = f
+        .configure(json!({ "resend_api_key": ORG_KEY, "email_from": "Acme <billing@acme.com>" }))
+        .await;
+
+    assert_eq!(status, StatusCode::OK, "{}", json);
+    assert_eq!(json["resend_api_key"], "re_org_l...7890");
+    assert_eq!(json["email_from"], "Acme <billing@acme.com>");
+
+    let conn = f.state.db.get().unwrap();
+    let stored = queries::get_org_resend_api_key(&conn, &f.org_id, &test_master_key()).unwrap();
+    assert_eq!(stored.as_deref(), Some(ORG_KEY));
+    let raw: Vec<u8> = conn
+        .query_row(
+            "SELECT config_encrypted FROM org_service_configs WHERE org_id = ?1",
+            rusqlite::params![f.org_id],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert!(
+        !String::from_utf8_lossy(&raw).contains(ORG_KEY),
+        "the key should not be stored in plaintext"
+    );
+
+    let (status, json) = f.send("GET", "", &f.owner_key, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["resend_api_key"], "re_org_l...7890");
+    assert_eq!(f.audit_count("update_org_email_config"), 1);
+}
+
+#[tokio::test]
+async fn test_null_clears_key_and_sender() {
+    let f = setup(None);
+    f.configure(json!({ "resend_api_key": ORG_KEY, "email_from": "billing@acme.com" }))
+        .await;
+
+    let (status, json) = f
+        .configure(json!({ "resend_api_key": null, "email_from": null }))
+        .await;
+
+    assert_eq!(status, StatusCode::OK, "{}", json);
+    assert!(json["resend_api_key"].is_null());
+    assert!(json["email_from"].is_null());
+    let conn = f.state.db.get().unwrap();
+    assert!(
+        queries::get_org_resend_api_key(&conn, &f.org_id, &test_master_key())
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[tokio::test]
+async fn test_omitted_fields_are_left_unchanged() {
+    let f = setup(None);
+    f.configure(json!({ "resend_api_key": ORG_KEY, "email_from": "billing@acme.com" }))
+        .await;
+
+    let (status, json) = f.configure(json!({ "email_from": "team@acme.com" })).await;
+
+    assert_eq!(status, StatusCode::OK, "{}", json);
+    assert_eq!(json["email_from"], "team@acme.com");
+    assert_eq!(
+        json["resend_api_key"], "re_org_l...7890",
+        "the key should survive a sender-only update"
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_sender_is_rejected() {
+    let f = setup(None);
+
+    let (status, _) = f.configure(json!({ "email_from": "not an address" })).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_admin_cannot_view_or_change_email_config() {
+    let f = setup(None);
+
+    let (status, _) = f.send("GET", "", &f.admin_key, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = f
+        .send(
+            "PUT",
+            "",
+            &f.admin_key,
+            Some(json!({ "resend_api_key": ORG_KEY })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = f.send("POST", "/test", &f.admin_key, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+// ============ Test send ============
+
+#[tokio::test]
+async fn test_send_with_working_key() {
+    let (url, captured) = stub_resend(StatusCode::OK, json!({ "id": "email_123" })).await;
+    let f = setup(Some(url));
+    f.configure(json!({ "resend_api_key": ORG_KEY, "email_from": "Acme <billing@acme.com>" }))
+        .await;
+
+    let (status, json) = f.send_test().await;
+
+    assert_eq!(status, StatusCode::OK, "{}", json);
+    assert_eq!(json["sent"], true);
+    assert_eq!(json["message_id"], "email_123");
+    assert_eq!(json["to"], "owner@test.com");
+    assert!(json["error"].is_null());
+
+    let captured = captured.lock().unwrap();
+    assert_eq!(captured.len(), 1);
+    let (auth, request) = &captured[0];
+    assert_eq!(
+        auth,
+        &format!("Bearer {}", ORG_KEY),
+        "the test should use the org's key, not the system key"
+    );
+    assert_eq!(request["to"], json!(["owner@test.com"]));
+    assert_eq!(request["from"], "Acme <billing@acme.com>");
+    drop(captured);
+
+    assert_eq!(f.audit_count("send_test_email"), 1);
+}
+
+#[tokio::test]
+async fn test_send_with_invalid_key_reports_resend_error() {
+    let (url, captured) = stub_resend(
+        StatusCode::UNAUTHORIZED,
+        json!({ "statusCode": 401, "message": "API key is invalid", "name": "validation_error" }),
+    )
+    .await;
+    let f = setup(Some(url));
+    f.configure(json!({ "resend_api_key": "re_revoked_key_0000000" }))
+        .await;
+
+    let (status, json) = f.send_test().await;
+
+    assert_eq!(status, StatusCode::OK, "{}", json);
+    assert_eq!(json["sent"], false);
+    assert_eq!(json["status"], 401);
+    assert_eq!(json["error"], "API key is invalid");
+    assert!(json["message_id"].is_null());
+    assert_eq!(
+        captured.lock().unwrap().len(),
+        1,
+        "a rejected key should not be retried"
+    );
+    assert_eq!(f.audit_count("send_test_email"), 1);
+}
+
+#[tokio::test]
+async fn test_send_without_org_key_is_rejected() {
+    let (url, captured) = stub_resend(StatusCode::OK, json!({ "id": "email_123" })).await;
+    let f = setup(Some(url));
+
+    let (status, json) = f.send_test().await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", json);
+    assert!(
+        captured.lock().unwrap().is_empty(),
+        "the system key should not be tested on the org's behalf"
+    );
+}
@@ -0,0 +1,256 @@
+//! Tests for the activation code email log: attempts recorded per license
+//! against a stub Resend server, the license detail's recent emails, and the
+//! project-wide failure view.
+
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode},
+    routing::post,
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::handlers;
+
+const CUSTOMER_EMAIL: &str = "test@example.com";
+
+/// Serve `status` and `body` for every POST to `/emails`. Returns the URL to
+/// point the email service at.
+async fn stub_resend(status: StatusCode, body: Value) -> String {
+    let app = Router::new().route(
+        "/emails",
+        post(move || {
+            let body = body.clone();
+            async move { (status, axum::Json(body)) }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    format!("http://{}/emails", addr)
+}
+
+struct EmailFixture {
+    state: AppState,
+    org_id: String,
+    project: Project,
+    license: License,
+    api_key: String,
+}
+
+/// A project with one license bought by `CUSTOMER_EMAIL`, sending email
+/// through `resend_url` (None = no Resend API key configured).
+fn setup(resend_url: Option<String>) -> EmailFixture {
+    let mut state = create_test_app_state();
+    if let Some(url) = resend_url {
+        state.email_service = Arc::new(
+            EmailService::new(Some("re_test_key".into()), "noreply@test.com".into())
+                .with_api_url(url),
+        );
+    }
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, api_key) =
+        create_test_org_member(&mut conn, &org.id, "admin@test.com", OrgMemberRole::Owner);
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+    let license = create_test_license(
+        &conn,
+        &project.id,
+        &product.id,
+        Some(future_timestamp(ONE_YEAR)),
+    );
+
+    drop(conn);
+    EmailFixture {
+        state,
+        org_id: org.id,
+        project,
+        license,
+        api_key,
+    }
+}
+
+impl EmailFixture {
+    async fn request_code(&self) -> StatusCode {
+        public_app(self.state.clone())
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/activation/request-code")
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        json!({
+                            "email": CUSTOMER_EMAIL,
+                            "public_key": self.project.public_key,
+                        })
+                        .to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+            .status()
+    }
+
+    async fn get(&self, path: &str) -> (StatusCode, Value) {
+        let app = handlers::orgs::router(
+            self.state.clone(),
+            paycheck::config::RateLimitConfig::disabled(),
+        )
+        .with_state(self.state.clone());
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!(
+                        "/orgs/{}/projects/{}{}",
+                        self.org_id, self.project.id, path
+                    ))
+                    .header("Authorization", format!("Bearer {}", self.api_key))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        )
+    }
+
+    fn email_log(&self) -> Vec<EmailLogEntry> {
+        let conn = self.state.db.get().unwrap();
+        queries::list_recent_emails_for_license(&conn, &self.license.id, 10).unwrap()
+    }
+}
+
+#[tokio::test]
+async fn test_rejected_email_is_logged_and_code_still_issued() {
+    let url = stub_resend(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        json!({ "name": "validation_error", "message": "Invalid `to` field" }),
+    )
+    .await;
+    let f = setup(Some(url));
+
+    assert_eq!(f.request_code().await, StatusCode::OK);
+
+    let log = f.email_log();
+    assert_eq!(log.len(), 1);
+    let entry = &log[0];
+    assert_eq!(entry.result, EmailLogResult::Failed);
+    assert_eq!(entry.error_status, Some(422));
+    assert_eq!(entry.provider_message_id, None);
+    assert_eq!(entry.project_id, f.project.id);
+    assert_eq!(entry.trigger.as_ref(), "recovery_request");
+
+    // The recipient is stored hashed, like the license's own email
+    assert_eq!(
+        entry.to_email_hash,
+        test_email_hasher().hash(&parse_email(CUSTOMER_EMAIL))
+    );
+    let conn = f.state.db.get().unwrap();
+    let plaintext: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM email_log WHERE to_email_hash = ?1",
+            [CUSTOMER_EMAIL],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(plaintext, 0);
+
+    // The activation code was issued regardless
+    let codes: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM activation_codes WHERE license_id = ?1",
+            [&f.license.id],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(codes, 1);
+}
+
+#[tokio::test]
+async fn test_sent_email_records_provider_message_id() {
+    let url = stub_resend(StatusCode::OK, json!({ "id": "msg_123" })).await;
+    let f = setup(Some(url));
+
+    assert_eq!(f.request_code().await, StatusCode::OK);
+
+    let log = f.email_log();
+    assert_eq!(log.len(), 1);
+    assert_eq!(log[0].result, EmailLogResult::Sent);
+    assert_eq!(log[0].provider_message_id.as_deref(), Some("msg_123"));
+    assert_eq!(log[0].error_status, None);
+}
+
+#[tokio::test]
+async fn test_missing_api_key_is_logged() {
+    let f = setup(None);
+
+    assert_eq!(f.request_code().await, StatusCode::OK);
+
+    let log = f.email_log();
+    assert_eq!(log.len(), 1);
+    assert_eq!(log[0].result, EmailLogResult::NoApiKey);
+}
+
+#[tokio::test]
+async fn test_license_detail_includes_recent_emails() {
+    let url = stub_resend(StatusCode::UNPROCESSABLE_ENTITY, json!({})).await;
+    let f = setup(Some(url));
+    f.request_code().await;
+
+    let (status, json) = f.get(&format!("/licenses/{}", f.license.id)).await;
+    assert_eq!(status, StatusCode::OK);
+    let emails = json["recent_emails"].as_array().unwrap();
+    assert_eq!(emails.len(), 1);
+    assert_eq!(emails[0]["result"], "failed");
+    assert_eq!(emails[0]["error_status"], 422);
+    assert_eq!(emails[0]["trigger"], "recovery_request");
+}
+
+#[tokio::test]
+async fn test_project_email_log_filters_by_result() {
+    let f = setup(None);
+    f.request_code().await;
+    {
+        let conn = f.state.db.get().unwrap();
+        queries::record_email_attempt(
+            &conn,
+            &f.project.id,
+            std::slice::from_ref(&f.license.id),
+            "hash",
+            paycheck::email::EmailTrigger::RecoveryRequest,
+            &paycheck::email::EmailSendResult::Failed { status: Some(422) },
+        )
+        .unwrap();
+    }
+
+    let (status, json) = f.get("/email-log").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["total"], 2);
+
+    let (status, json) = f.get("/email-log?result=failed").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["total"], 1);
+    assert_eq!(json["items"][0]["license_id"], f.license.id.as_str());
+    assert_eq!(json["items"][0]["error_status"], 422);
+
+    let (status, _) = f.get("/email-log?result=bounced").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
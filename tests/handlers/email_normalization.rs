@@ -0,0 +1,262 @@
+//! Tests for email normalization at the API: every spelling of an address
+//! finds the same license, malformed addresses are rejected, and licenses
+//! stored under the pre-normalization hash are still found.
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode},
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::handlers;
+
+struct EmailFixture {
+    state: AppState,
+    org_id: String,
+    project_id: String,
+    public_key: String,
+    product_id: String,
+    api_key: String,
+}
+
+fn setup() -> EmailFixture {
+    let state = create_test_app_state();
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, api_key) =
+        create_test_org_member(&mut conn, &org.id, "owner@test.com", OrgMemberRole::Owner);
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+
+    drop(conn);
+    EmailFixture {
+        state,
+        org_id: org.id,
+        project_id: project.id,
+        public_key: project.public_key,
+        product_id: product.id,
+        api_key,
+    }
+}
+
+impl EmailFixture {
+    fn org_app(&self) -> Router {
+        handlers::orgs::router(
+            self.state.clone(),
+            paycheck::config::RateLimitConfig::disabled(),
+        )
+        .with_state(self.state.clone())
+    }
+
+    async fn org_request(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(format!(
+                "/orgs/{}/projects/{}{}",
+                self.org_id, self.project_id, path
+            ))
+            .header("content-type", "application/json")
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .body(body.map_or_else(Body::empty, |b| Body::from(b.to_string())))
+            .unwrap();
+        let response = self.org_app().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        )
+    }
+
+    async fn create_license(&self, email: &str) -> (StatusCode, Value) {
+        self.org_request(
+            "POST",
+            "/licenses",
+            Some(json!({ "product_id": self.product_id, "email": email })),
+        )
+        .await
+    }
+
+    /// Licenses found by the support lookup for `email`.
+    async fn lookup(&self, email: &str) -> Value {
+        let (status, json) = self
+            .org_request(
+                "GET",
+                &format!("/licenses?email={}", urlencoding::encode(email)),
+                None,
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "lookup {:?}", email);
+        json
+    }
+
+    /// Insert a license hashed the way emails were before normalization.
+    fn create_legacy_license(&self, email: &str) -> License {
+        let legacy_hash = test_email_hasher()
+            .legacy_hash(&parse_email(email))
+            .expect("email should normalize differently from its legacy form");
+        let conn = self.state.db.get().unwrap();
+        let license = create_test_license(&conn, &self.project_id, &self.product_id, None);
+        queries::update_license_email_hash(&conn, &license.id, &legacy_hash).unwrap();
+        license
+    }
+
+    async fn request_code(&self, email: &str) -> StatusCode {
+        public_app(self.state.clone())
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/activation/request-code")
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        json!({ "email": email, "public_key": self.public_key }).to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+            .status()
+    }
+
+    fn activation_code_licenses(&self) -> Vec<String> {
+        let conn = self.state.db.get().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT license_id FROM activation_codes")
+            .unwrap();
+        stmt.query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+    }
+}
+
+#[tokio::test]
+async fn test_spellings_of_an_address_find_the_same_license() {
+    let f = setup();
+    let (status, created) = f.create_license("\"Bob Smith\" <Bob@Bücher.Example>").await;
+    assert_eq!(status, StatusCode::OK);
+    let license_id = created["items"][0]["id"].as_str().unwrap().to_string();
+
+    for spelling in [
+        "bob@bücher.example",
+        "BOB@xn--bcher-kva.example",
+        "  Bob <bob@BÜCHER.example.>  ",
+        "mailto:bob@bücher.example",
+    ] {
+        let found = f.lookup(spelling).await;
+        assert_eq!(found["total"], 1, "{:?}", spelling);
+        assert_eq!(found["items"][0]["id"], license_id.as_str());
+    }
+}
+
+#[tokio::test]
+async fn test_malformed_emails_rejected() {
+    let f = setup();
+    for email in [
+        "not-an-email",
+        "bob@",
+        "Bob <bob@example.com",
+        "bob@exa_mple.com",
+    ] {
+        let (status, _) = f.create_license(email).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "create {:?}", email);
+
+        let (status, _) = f
+            .org_request(
+                "GET",
+                &format!("/licenses?email={}", urlencoding::encode(email)),
+                None,
+            )
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "lookup {:?}", email);
+
+        assert_eq!(
+            f.request_code(email).await,
+            StatusCode::BAD_REQUEST,
+            "request-code {:?}",
+            email
+        );
+    }
+    assert!(f.activation_code_licenses().is_empty());
+}
+
+#[tokio::test]
+async fn test_legacy_hash_found_when_normalized_misses() {
+    let f = setup();
+    let license = f.create_legacy_license("bob@bücher.example");
+
+    let found = f.lookup("bob@bücher.example").await;
+    assert_eq!(found["total"], 1);
+    assert_eq!(found["items"][0]["id"], license.id.as_str());
+
+    assert_eq!(f.request_code("bob@bücher.example").await, StatusCode::OK);
+    assert_eq!(f.activation_code_licenses(), [license.id.clone()]);
+
+    // The legacy hash is the old spelling exactly; other spellings don't reach it
+    assert_eq!(f.lookup("bob@xn--bcher-kva.example").await["total"], 0);
+}
+
+#[tokio::test]
+async fn test_normalized_hash_preferred_over_legacy() {
+    let f = setup();
+    f.create_legacy_license("Bob <bob@example.com>");
+    let (_, created) = f.create_license("bob@example.com").await;
+
+    let found = f.lookup("Bob <bob@example.com>").await;
+    assert_eq!(found["total"], 1);
+    assert_eq!(found["items"][0]["id"], created["items"][0]["id"]);
+}
+
+#[tokio::test]
+async fn test_email_update_stores_normalized_hash() {
+    let f = setup();
+    let license = f.create_legacy_license("bob@bücher.example");
+
+    let (status, _) = f
+        .org_request(
+            "PATCH",
+            &format!("/licenses/{}", license.id),
+            Some(json!({ "email": "Robert <Bob@Bücher.example>" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let conn = f.state.db.get().unwrap();
+    let updated = queries::get_license_by_id(&conn, &license.id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        updated.email_hash,
+        Some(test_email_hasher().hash(&parse_email("bob@xn--bcher-kva.example")))
+    );
+}
+
+#[tokio::test]
+async fn test_share_link_accepts_any_spelling_and_masks_normalized() {
+    let f = setup();
+    let license = f.create_legacy_license("Bob <bob@example.com>");
+
+    let (status, link) = f
+        .org_request(
+            "POST",
+            &format!("/licenses/{}/share-link", license.id),
+            Some(json!({ "email": "Bob <bob@example.com>" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(link["masked_email"], "b***@example.com");
+}
@@ -0,0 +1,497 @@
+//! Tests for entitlement checks: which licenses count (revoked, expired,
+//! deleted, and paused ones), which license is reported, email lookups, the
+//! bulk form, project-scoped API keys, structured entitlements with numeric
+//! minimums, and the suggestions for migrating `name_<number>` features.
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode},
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::error::msg;
+use paycheck::handlers;
+
+struct EntitlementFixture {
+    state: AppState,
+    org_id: String,
+    project_id: String,
+    pro: Product,
+    enterprise: Product,
+    owner_key: String,
+}
+
+fn setup() -> EntitlementFixture {
+    let state = create_test_app_state();
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, owner_key) =
+        create_test_org_member(&mut conn, &org.id, "owner@test.com", OrgMemberRole::Owner);
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    let pro = create_test_product(&conn, &project.id, "Pro", "pro");
+    let enterprise = create_test_product(&conn, &project.id, "Enterprise", "enterprise");
+    queries::update_product(
+        &conn,
+        &enterprise.id,
+        &serde_json::from_value(json!({ "features": ["feature1", "sso"] })).unwrap(),
+    )
+    .unwrap();
+
+    drop(conn);
+    EntitlementFixture {
+        state,
+        org_id: org.id,
+        project_id: project.id,
+        pro,
+        enterprise,
+        owner_key,
+    }
+}
+
+impl EntitlementFixture {
+    fn app(&self) -> Router {
+        handlers::orgs::router(
+            self.state.clone(),
+            paycheck::config::RateLimitConfig::disabled(),
+        )
+        .with_state(self.state.clone())
+    }
+
+    fn license(
+        &self,
+        product: &Product,
+        customer_id: &str,
+        email: Option<&str>,
+        expires_at: Option<i64>,
+    ) -> License {
+        let conn = self.state.db.get().unwrap();
+        let input = CreateLicense {
+            email_hash: email.map(|e| test_email_hasher().hash(&parse_email(e))),
+            customer_id: Some(customer_id.to_string()),
+            expires_at,
+            updates_expires_at: expires_at,
+            payment_provider: None,
+            payment_provider_customer_id: None,
+            payment_provider_subscription_id: None,
+            payment_provider_order_id: None,
+            seats: None,
+        };
+        queries::create_license(&conn, &self.project_id, &product.id, &input).unwrap()
+    }
+
+    async fn send(&self, api_key: &str, request: Request<Body>) -> (StatusCode, Value) {
+        let mut request = request;
+        request.headers_mut().insert(
+            "Authorization",
+            format!("Bearer {}", api_key).parse().unwrap(),
+        );
+        let response = self.app().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    async fn check_as(&self, api_key: &str, query: &str) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!(
+                "/orgs/{}/projects/{}/entitlements?{}",
+                self.org_id, self.project_id, query
+            ))
+            .body(Body::empty())
+            .unwrap();
+        self.send(api_key, request).await
+    }
+
+    async fn check(&self, customer_id: &str, feature: &str) -> Value {
+        let (status, body) = self
+            .check_as(
+                &self.owner_key,
+                &format!("customer_id={}&feature={}", customer_id, feature),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        body
+    }
+
+    async fn update_product(&self, product: &Product, body: Value) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method("PUT")
+            .uri(format!(
+                "/orgs/{}/projects/{}/products/{}",
+                self.org_id, self.project_id, product.id
+            ))
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        self.send(&self.owner_key, request).await
+    }
+
+    async fn bulk(&self, body: Value) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method("POST")
+            .uri(format!(
+                "/orgs/{}/projects/{}/entitlements/check",
+                self.org_id, self.project_id
+            ))
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        self.send(&self.owner_key, request).await
+    }
+}
+
+#[tokio::test]
+async fn test_active_license_grants_its_product_features() {
+    let f = setup();
+    let expires_at = future_timestamp(30);
+    let license = f.license(&f.pro, "cust-a", None, Some(expires_at));
+
+    let result = f.check("cust-a", "feature1").await;
+    assert_eq!(
+        result,
+        json!({
+            "customer_id": "cust-a",
+            "feature": "feature1",
+            "entitled": true,
+            "license_id": license.id,
+            "product_id": f.pro.id,
+            "tier": "pro",
+            "expires_at": expires_at
+        })
+    );
+
+    let result = f.check("cust-a", "sso").await;
+    assert_eq!(result["entitled"], false);
+    assert!(result["license_id"].is_null());
+    assert!(result["tier"].is_null());
+}
+
+#[tokio::test]
+async fn test_unknown_customer_is_not_entitled() {
+    let f = setup();
+    f.license(&f.pro, "cust-a", None, None);
+
+    let result = f.check("nobody", "feature1").await;
+    assert_eq!(result["entitled"], false);
+    assert_eq!(result["customer_id"], "nobody");
+}
+
+#[tokio::test]
+async fn test_revoked_expired_and_deleted_licenses_do_not_count() {
+    let f = setup();
+    let revoked = f.license(&f.pro, "cust-revoked", None, None);
+    f.license(&f.pro, "cust-expired", None, Some(past_timestamp(1)));
+    let deleted = f.license(&f.pro, "cust-deleted", None, None);
+    {
+        let conn = f.state.db.get().unwrap();
+        queries::revoke_license(
+            &conn,
+            &revoked.id,
+            &RevokeLicense::new(RevocationReason::Refund),
+            None,
+        )
+        .unwrap();
+        queries::soft_delete_license(&conn, &deleted.id).unwrap();
+    }
+
+    for customer_id in ["cust-revoked", "cust-expired", "cust-deleted"] {
+        let result = f.check(customer_id, "feature1").await;
+        assert_eq!(result["entitled"], false, "{}", customer_id);
+    }
+}
+
+#[tokio::test]
+async fn test_paused_license_counts_past_expiry() {
+    let f = setup();
+    let license = f.license(&f.pro, "cust-paused", None, Some(past_timestamp(1)));
+    let conn = f.state.db.get().unwrap();
+    queries::pause_license(&conn, &license.id, past_timestamp(5)).unwrap();
+    drop(conn);
+
+    let result = f.check("cust-paused", "feature1").await;
+    assert_eq!(result["entitled"], true);
+    assert_eq!(result["license_id"], license.id);
+}
+
+#[tokio::test]
+async fn test_longest_lasting_license_is_reported() {
+    let f = setup();
+    f.license(&f.pro, "cust-a", None, Some(future_timestamp(10)));
+    let later = f.license(&f.pro, "cust-a", None, Some(future_timestamp(60)));
+    f.license(&f.pro, "cust-a", None, Some(future_timestamp(20)));
+
+    let result = f.check("cust-a", "feature1").await;
+    assert_eq!(result["license_id"], later.id);
+
+    // A perpetual license beats any expiring one
+    let perpetual = f.license(&f.enterprise, "cust-a", None, None);
+    let result = f.check("cust-a", "feature1").await;
+    assert_eq!(result["license_id"], perpetual.id);
+    assert_eq!(result["tier"], "enterprise");
+    assert!(result["expires_at"].is_null());
+
+    // Only Enterprise grants sso
+    let result = f.check("cust-a", "sso").await;
+    assert_eq!(result["license_id"], perpetual.id);
+}
+
+#[tokio::test]
+async fn test_check_by_email() {
+    let f = setup();
+    let license = f.license(&f.pro, "cust-a", Some("buyer@example.com"), None);
+
+    let (status, result) = f
+        .check_as(&f.owner_key, "email=Buyer%40Example.com&feature=feature2")
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", result);
+    assert_eq!(result["entitled"], true);
+    assert_eq!(result["license_id"], license.id);
+    assert!(result.get("customer_id").is_none());
+
+    let (_, result) = f
+        .check_as(&f.owner_key, "email=other%40example.com&feature=feature2")
+        .await;
+    assert_eq!(result["entitled"], false);
+}
+
+#[tokio::test]
+async fn test_check_requires_exactly_one_customer_key() {
+    let f = setup();
+
+    for query in [
+        "feature=feature1",
+        "customer_id=cust-a&email=buyer%40example.com&feature=feature1",
+    ] {
+        let (status, result) = f.check_as(&f.owner_key, query).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", query);
+        assert_eq!(result["details"], msg::ENTITLEMENT_CUSTOMER_REQUIRED);
+    }
+}
+
+#[tokio::test]
+async fn test_bulk_check_covers_every_customer_and_feature() {
+    let f = setup();
+    let pro_license = f.license(&f.pro, "cust-a", None, None);
+    let revoked = f.license(&f.enterprise, "cust-b", None, None);
+    let conn = f.state.db.get().unwrap();
+    queries::revoke_license(
+        &conn,
+        &revoked.id,
+        &RevokeLicense::new(RevocationReason::Chargeback),
+        None,
+    )
+    .unwrap();
+    drop(conn);
+
+    let (status, body) = f
+        .bulk(json!({
+            "customer_ids": ["cust-a", "cust-b", "nobody"],
+            "features": ["feature1", "sso"]
+        }))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let results: Vec<(&str, &str, bool)> = body["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| {
+            (
+                r["customer_id"].as_str().unwrap(),
+                r["feature"].as_str().unwrap(),
+                r["entitled"].as_bool().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        results,
+        [
+            ("cust-a", "feature1", true),
+            ("cust-a", "sso", false),
+            ("cust-b", "feature1", false),
+            ("cust-b", "sso", false),
+            ("nobody", "feature1", false),
+            ("nobody", "sso", false),
+        ]
+    );
+    assert_eq!(body["results"][0]["license_id"], pro_license.id);
+}
+
+#[tokio::test]
+async fn test_bulk_check_rejects_empty_and_oversized_lists() {
+    let f = setup();
+    let too_many: Vec<String> = (0..101).map(|i| format!("cust-{}", i)).collect();
+
+    let cases = [
+        (
+            json!({ "customer_ids": [], "features": ["feature1"] }),
+            msg::ENTITLEMENT_CHECK_EMPTY,
+        ),
+        (
+            json!({ "customer_ids": ["cust-a"], "features": [] }),
+            msg::ENTITLEMENT_CHECK_EMPTY,
+        ),
+        (
+            json!({ "customer_ids": too_many, "features": ["feature1"] }),
+            msg::ENTITLEMENT_CHECK_TOO_LARGE,
+        ),
+    ];
+    for (body, details) in cases {
+        let (status, result) = f.bulk(body).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(result["details"], details);
+    }
+}
+
+#[tokio::test]
+async fn test_project_scoped_api_key() {
+    let f = setup();
+    f.license(&f.pro, "cust-a", None, None);
+    let mut conn = f.state.db.get().unwrap();
+    let (user, member, _) = create_test_org_member(
+        &mut conn,
+        &f.org_id,
+        "backend@test.com",
+        OrgMemberRole::Member,
+    );
+    create_test_project_member(&conn, &member.id, &f.project_id, ProjectMemberRole::View);
+    let other = create_test_project(&conn, &f.org_id, "Other", &test_master_key());
+    let scoped_key = create_api_key_with_project_scope(
+        &mut conn,
+        &user.id,
+        &f.org_id,
+        &f.project_id,
+        AccessLevel::View,
+    );
+    let other_key = create_api_key_with_project_scope(
+        &mut conn,
+        &user.id,
+        &f.org_id,
+        &other.id,
+        AccessLevel::View,
+    );
+    drop(conn);
+
+    let query = "customer_id=cust-a&feature=feature1";
+    let (status, result) = f.check_as(&scoped_key, query).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(result["entitled"], true);
+
+    let (status, _) = f.check_as(&other_key, query).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_numeric_entitlement_compared_against_min() {
+    let f = setup();
+    let (status, body) = f
+        .update_product(
+            &f.pro,
+            json!({ "entitlements": { "seats": 5, "sso": false, "support": "priority" } }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["entitlements"]["seats"], 5);
+    f.license(&f.pro, "cust-a", None, None);
+
+    let result = f.check("cust-a", "seats&min=3").await;
+    assert_eq!(result["entitled"], true);
+    assert_eq!(result["value"], 5);
+    assert_eq!(f.check("cust-a", "seats&min=5").await["entitled"], true);
+    let result = f.check("cust-a", "seats&min=10").await;
+    assert_eq!(result["entitled"], false);
+    assert!(result.get("value").is_none());
+    // Without a minimum, any number counts
+    assert_eq!(f.check("cust-a", "seats").await["value"], 5);
+
+    // Flags count when true; text counts as present
+    assert_eq!(f.check("cust-a", "sso").await["entitled"], false);
+    assert_eq!(f.check("cust-a", "support").await["value"], "priority");
+
+    // A plain feature or a non-numeric entitlement can't meet a minimum
+    assert_eq!(f.check("cust-a", "feature1").await["entitled"], true);
+    assert_eq!(f.check("cust-a", "feature1&min=1").await["entitled"], false);
+    assert_eq!(f.check("cust-a", "support&min=1").await["entitled"], false);
+}
+
+#[tokio::test]
+async fn test_invalid_entitlements_rejected() {
+    let f = setup();
+    for entitlements in [
+        json!({ "Seats": 5 }),
+        json!({ "seats": -1 }),
+        json!({ "": true }),
+        json!({ "tier": "x".repeat(201) }),
+    ] {
+        let (status, body) = f
+            .update_product(&f.pro, json!({ "entitlements": entitlements }))
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    }
+
+    let too_many: serde_json::Map<String, Value> = (0..51)
+        .map(|i| (format!("limit_{}", i), json!(i)))
+        .collect();
+    let (status, body) = f
+        .update_product(&f.pro, json!({ "entitlements": too_many }))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["details"], msg::TOO_MANY_ENTITLEMENTS);
+}
+
+#[tokio::test]
+async fn test_suggestions_from_numbered_features() {
+    let f = setup();
+    let conn = f.state.db.get().unwrap();
+    queries::update_product(
+        &conn,
+        &f.pro.id,
+        &serde_json::from_value(json!({
+            "features": ["export", "seats_5", "seats_10", "api_calls_1000", "projects_3"],
+            "entitlements": { "projects": 20 }
+        }))
+        .unwrap(),
+    )
+    .unwrap();
+    drop(conn);
+
+    let request = Request::builder()
+        .method("GET")
+        .uri(format!(
+            "/orgs/{}/projects/{}/entitlements/suggestions",
+            f.org_id, f.project_id
+        ))
+        .body(Body::empty())
+        .unwrap();
+    let (status, body) = f.send(&f.owner_key, request).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    // Enterprise's features have no numbers, so only Pro is listed; entitlements
+    // the product already has aren't proposed again
+    assert_eq!(
+        body["products"],
+        json!([{
+            "product_id": f.pro.id,
+            "product_name": "Pro",
+            "entitlements": { "api_calls": 1000, "seats": 10 },
+            "from_features": ["seats_5", "seats_10", "api_calls_1000"]
+        }])
+    );
+
+    // Nothing was applied
+    let conn = f.state.db.get().unwrap();
+    let product = queries::get_product_by_id(&conn, &f.pro.id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(product.entitlements.len(), 1);
+}
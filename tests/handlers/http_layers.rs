@@ -0,0 +1,191 @@
+//! Tests for the layers every request passes through: request body limits
+//! and response compression.
+
+use std::io::Read;
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode, header},
+    response::Response,
+};
+use flate2::read::GzDecoder;
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::config::BodyLimitConfig;
+use paycheck::error::msg;
+use paycheck::handlers;
+
+struct LayersFixture {
+    state: AppState,
+    org_id: String,
+    project_id: String,
+    product_id: String,
+    owner_key: String,
+}
+
+fn setup() -> LayersFixture {
+    let state = create_test_app_state();
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, owner_key) =
+        create_test_org_member(&mut conn, &org.id, "owner@test.com", OrgMemberRole::Owner);
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    let product = create_test_product(&conn, &project.id, "Pro", "pro");
+
+    drop(conn);
+    LayersFixture {
+        state,
+        org_id: org.id,
+        project_id: project.id,
+        product_id: product.id,
+        owner_key,
+    }
+}
+
+impl LayersFixture {
+    fn app(&self, limits: BodyLimitConfig) -> Router {
+        let router = handlers::orgs::router(
+            self.state.clone(),
+            paycheck::config::RateLimitConfig::disabled(),
+        );
+        handlers::with_http_layers(router, limits).with_state(self.state.clone())
+    }
+
+    fn licenses_url(&self) -> String {
+        format!(
+            "/orgs/{}/projects/{}/licenses",
+            self.org_id, self.project_id
+        )
+    }
+
+    async fn create_license(&self, limits: BodyLimitConfig, customer_id: &str) -> Response {
+        self.app(limits)
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(self.licenses_url())
+                    .header("Authorization", format!("Bearer {}", self.owner_key))
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        json!({ "product_id": self.product_id, "customer_id": customer_id })
+                            .to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+    }
+
+    async fn get(&self, uri: &str, accept_encoding: Option<&str>) -> Response {
+        let mut request = Request::builder()
+            .method("GET")
+            .uri(uri)
+            .header("Authorization", format!("Bearer {}", self.owner_key));
+        if let Some(encoding) = accept_encoding {
+            request = request.header(header::ACCEPT_ENCODING, encoding);
+        }
+        self.app(BodyLimitConfig::default())
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+}
+
+async fn body_bytes(response: Response) -> Vec<u8> {
+    axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap()
+        .to_vec()
+}
+
+fn small_limits() -> BodyLimitConfig {
+    BodyLimitConfig {
+        default_bytes: 1024,
+        ..BodyLimitConfig::default()
+    }
+}
+
+#[tokio::test]
+async fn test_json_body_over_limit_is_413_with_error_envelope() {
+    let f = setup();
+
+    let response = f.create_license(small_limits(), &"x".repeat(2048)).await;
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    let body: Value = serde_json::from_slice(&body_bytes(response).await).unwrap();
+    assert_eq!(body["error"], "Payload too large");
+    assert_eq!(body["details"], msg::BODY_TOO_LARGE);
+}
+
+#[tokio::test]
+async fn test_json_body_under_limit_is_accepted() {
+    let f = setup();
+
+    let response = f.create_license(small_limits(), "customer-1").await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_license_list_is_compressed_when_accepted() {
+    let f = setup();
+    let conn = f.state.db.get().unwrap();
+    for _ in 0..50 {
+        create_test_license(&conn, &f.project_id, &f.product_id, None);
+    }
+    drop(conn);
+    let uri = format!("{}?limit=50", f.licenses_url());
+
+    let response = f.get(&uri, Some("gzip")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+    let compressed = body_bytes(response).await;
+    let mut json = String::new();
+    GzDecoder::new(compressed.as_slice())
+        .read_to_string(&mut json)
+        .unwrap();
+    assert!(compressed.len() < json.len());
+    let body: Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(body["items"].as_array().unwrap().len(), 50);
+
+    let response = f.get(&uri, Some("br")).await;
+    assert_eq!(response.headers()[header::CONTENT_ENCODING], "br");
+
+    let response = f.get(&uri, None).await;
+    assert!(response.headers().get(header::CONTENT_ENCODING).is_none());
+}
+
+#[tokio::test]
+async fn test_gzip_archive_is_not_compressed_again() {
+    let f = setup();
+
+    let response = f
+        .app(BodyLimitConfig::default())
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/orgs/{}/audit-logs/archive", f.org_id))
+                .header("Authorization", format!("Bearer {}", f.owner_key))
+                .header("content-type", "application/json")
+                .header(header::ACCEPT_ENCODING, "gzip, br")
+                .body(Body::from(json!({ "from": 0, "to": 10_000 }).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "application/gzip");
+    assert!(response.headers().get(header::CONTENT_ENCODING).is_none());
+
+    // Still a plain gzip file
+    let archive = body_bytes(response).await;
+    let mut content = String::new();
+    GzDecoder::new(archive.as_slice())
+        .read_to_string(&mut content)
+        .unwrap();
+}
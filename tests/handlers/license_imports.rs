@@ -0,0 +1,421 @@
+//! Tests for importing LemonSqueezy license keys: a mock LemonSqueezy serves
+//! two pages of keys, the import maps them to licenses (and can be re-run
+//! without duplicating any), and customers redeem their old keys.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use axum::{
+    Form, Router,
+    body::Body,
+    extract::Query,
+    http::{Request, StatusCode},
+    response::IntoResponse,
+    routing::{get, post},
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::handlers;
+use paycheck::license_import::{self, ImportResult};
+use paycheck::payments::LemonSqueezyClient;
+
+/// LemonSqueezy product whose keys are mapped in these tests
+const LS_PRODUCT: &str = "100";
+/// Active, never expires
+const KEY_ACTIVE: &str = "38B1460A-5104-4067-A91D-77B872934D51";
+/// Active, expires 2030-01-01
+const KEY_EXPIRING: &str = "9C1D5E22-7F0B-4A8E-B3C4-1E2F3A4B5C6D";
+/// For a product that isn't mapped
+const KEY_OTHER_PRODUCT: &str = "0F6E5D4C-3B2A-4190-8877-665544332211";
+/// Disabled in LemonSqueezy
+const KEY_DISABLED: &str = "D15AB1ED-0000-4000-8000-000000000001";
+
+fn license_key(
+    id: i64,
+    key: &str,
+    product_id: i64,
+    status: &str,
+    expires_at: Option<&str>,
+) -> Value {
+    json!({
+        "type": "license-keys",
+        "id": id.to_string(),
+        "attributes": {
+            "key": key,
+            "status": status,
+            "disabled": status == "disabled",
+            "product_id": product_id,
+            "order_id": 5000 + id,
+            "customer_id": 7000 + id,
+            "user_email": format!("customer{}@example.com", id),
+            "expires_at": expires_at
+        }
+    })
+}
+
+/// Serve two pages of license keys and a validate endpoint that only knows
+/// `KEY_ACTIVE`, and return the mock's base URL.
+async fn mock_lemonsqueezy() -> String {
+    let pages = [
+        vec![
+            license_key(1, KEY_ACTIVE, 100, "active", None),
+            license_key(2, KEY_OTHER_PRODUCT, 200, "active", None),
+        ],
+        vec![
+            license_key(3, KEY_DISABLED, 100, "disabled", None),
+            license_key(
+                4,
+                KEY_EXPIRING,
+                100,
+                "inactive",
+                Some("2030-01-01T00:00:00.000000Z"),
+            ),
+        ],
+    ];
+    let app = Router::new()
+        .route(
+            "/v1/license-keys",
+            get(move |Query(query): Query<HashMap<String, String>>| {
+                assert_eq!(query["filter[store_id]"], "store_123");
+                let number: usize = query["page[number]"].parse().unwrap();
+                let data = pages[number - 1].clone();
+                async move {
+                    axum::Json(json!({
+                        "meta": { "page": { "currentPage": number, "lastPage": 2, "perPage": 100 } },
+                        "data": data
+                    }))
+                }
+            }),
+        )
+        .route(
+            "/v1/licenses/validate",
+            post(|Form(form): Form<HashMap<String, String>>| async move {
+                if form["license_key"] == KEY_ACTIVE {
+                    (StatusCode::OK, axum::Json(json!({ "valid": true }))).into_response()
+                } else {
+                    (
+                        StatusCode::NOT_FOUND,
+                        axum::Json(json!({ "valid": false, "error": "license_key not found." })),
+                    )
+                        .into_response()
+                }
+            }),
+        );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}", addr)
+}
+
+struct Fixture {
+    state: AppState,
+    org_id: String,
+    project: Project,
+    product: Product,
+    api_key: String,
+}
+
+fn setup() -> Fixture {
+    let state = create_test_app_state();
+    let mut conn = state.db.get().unwrap();
+    let master_key = test_master_key();
+
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, api_key) =
+        create_test_org_member(&mut conn, &org.id, "owner@test.com", OrgMemberRole::Owner);
+    let project = create_test_project(&conn, &org.id, "Test Project", &master_key);
+    let product = create_test_product(&conn, &project.id, "Pro", "pro");
+
+    drop(conn);
+    Fixture {
+        state,
+        org_id: org.id,
+        project,
+        product,
+        api_key,
+    }
+}
+
+impl Fixture {
+    fn ls_config() -> LemonSqueezyConfig {
+        LemonSqueezyConfig {
+            api_key: "ls_test_key_abcdefghij".to_string(),
+            store_id: "store_123".to_string(),
+            webhook_secret: "ls_whsec_test_secret".to_string(),
+            verify_imported_keys: false,
+        }
+    }
+
+    async fn import(&self, api_base: &str) -> ImportResult {
+        let client =
+            LemonSqueezyClient::with_endpoint(&Self::ls_config(), api_base, Duration::from_secs(5));
+        let products = HashMap::from([(LS_PRODUCT.to_string(), self.product.id.clone())]);
+        license_import::import_lemonsqueezy(
+            &self.state,
+            &self.org_id,
+            &self.project.id,
+            &client,
+            &products,
+            Duration::ZERO,
+        )
+        .await
+        .unwrap()
+    }
+
+    fn app(&self) -> Router {
+        public_app(self.state.clone()).merge(
+            handlers::orgs::router(
+                self.state.clone(),
+                paycheck::config::RateLimitConfig::disabled(),
+            )
+            .with_state(self.state.clone()),
+        )
+    }
+
+    async fn post(&self, uri: &str, auth: bool, body: Value) -> (StatusCode, Value) {
+        let mut request = Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header("content-type", "application/json");
+        if auth {
+            request = request.header("Authorization", format!("Bearer {}", self.api_key));
+        }
+        let response = self
+            .app()
+            .oneshot(request.body(Body::from(body.to_string())).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        )
+    }
+
+    async fn redeem(&self, key: &str, device_id: &str) -> (StatusCode, Value) {
+        self.post(
+            "/redeem/lemonsqueezy",
+            false,
+            json!({
+                "public_key": self.project.public_key,
+                "code": key,
+                "device_id": device_id,
+                "device_type": "uuid"
+            }),
+        )
+        .await
+    }
+
+    fn licenses(&self) -> Vec<LicenseWithProduct> {
+        let conn = self.state.db.get().unwrap();
+        queries::list_licenses_for_project(&conn, &self.project.id).unwrap()
+    }
+}
+
+// ============ Import ============
+
+#[tokio::test]
+async fn test_import_creates_licenses_for_mapped_keys() {
+    let f = setup();
+    let api_base = mock_lemonsqueezy().await;
+
+    let result = f.import(&api_base).await;
+
+    assert_eq!(result.fetched, 4, "both pages should be fetched");
+    assert_eq!(result.imported, 2);
+    assert_eq!(result.unmapped, 1);
+    assert_eq!(result.disabled, 1);
+    assert_eq!(result.already_imported, 0);
+
+    let licenses = f.licenses();
+    assert_eq!(licenses.len(), 2);
+    let mut orders: Vec<_> = licenses
+        .iter()
+        .map(|l| l.license.payment_provider_order_id.clone().unwrap())
+        .collect();
+    orders.sort();
+    assert_eq!(orders, ["5001", "5004"]);
+    for l in &licenses {
+        assert_eq!(l.license.product_id, f.product.id);
+        assert_eq!(l.license.payment_provider.as_deref(), Some("lemonsqueezy"));
+        assert!(l.license.email_hash.is_some());
+    }
+    let expiring = licenses
+        .iter()
+        .find(|l| l.license.payment_provider_order_id.as_deref() == Some("5004"))
+        .unwrap();
+    assert_eq!(
+        expiring.license.expires_at,
+        Some(1_893_456_000),
+        "the LemonSqueezy expiry should carry over"
+    );
+}
+
+#[tokio::test]
+async fn test_import_is_idempotent() {
+    let f = setup();
+    let api_base = mock_lemonsqueezy().await;
+    f.import(&api_base).await;
+
+    let result = f.import(&api_base).await;
+
+    assert_eq!(result.imported, 0);
+    assert_eq!(result.already_imported, 2);
+    assert_eq!(
+        f.licenses().len(),
+        2,
+        "a re-run should not duplicate licenses"
+    );
+}
+
+#[tokio::test]
+async fn test_import_skips_keys_of_deleted_licenses() {
+    let f = setup();
+    let api_base = mock_lemonsqueezy().await;
+    f.import(&api_base).await;
+    {
+        let conn = f.state.db.get().unwrap();
+        conn.execute(
+            "UPDATE licenses SET deleted_at = ?1 WHERE project_id = ?2",
+            rusqlite::params![now(), f.project.id],
+        )
+        .unwrap();
+    }
+
+    let result = f.import(&api_base).await;
+
+    assert_eq!(
+        result.imported, 0,
+        "deleting an imported license should not let the next run bring it back"
+    );
+    assert_eq!(result.already_imported, 2);
+}
+
+#[tokio::test]
+async fn test_import_endpoint_requires_ls_config() {
+    let f = setup();
+    let uri = format!(
+        "/orgs/{}/projects/{}/license-imports/lemonsqueezy",
+        f.org_id, f.project.id
+    );
+
+    let (status, body) = f
+        .post(
+            &uri,
+            true,
+            json!({ "products": { LS_PRODUCT: f.product.id } }),
+        )
+        .await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["details"], "LemonSqueezy not configured");
+}
+
+#[tokio::test]
+async fn test_import_endpoint_rejects_foreign_product() {
+    let f = setup();
+    let other_product = {
+        let conn = f.state.db.get().unwrap();
+        setup_lemonsqueezy_config(&conn, &f.org_id, &test_master_key());
+        let other = create_test_project(&conn, &f.org_id, "Other", &test_master_key());
+        create_test_product(&conn, &other.id, "Other Pro", "pro")
+    };
+    let uri = format!(
+        "/orgs/{}/projects/{}/license-imports/lemonsqueezy",
+        f.org_id, f.project.id
+    );
+
+    let (status, _) = f.post(&uri, true, json!({ "products": {} })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = f
+        .post(
+            &uri,
+            true,
+            json!({ "products": { LS_PRODUCT: other_product.id } }),
+        )
+        .await;
+    assert_eq!(
+        status,
+        StatusCode::NOT_FOUND,
+        "keys can only be mapped to the project's own products"
+    );
+}
+
+// ============ Redemption ============
+
+#[tokio::test]
+async fn test_redeem_imported_key() {
+    let f = setup();
+    let api_base = mock_lemonsqueezy().await;
+    f.import(&api_base).await;
+
+    // Customers may paste the key in lowercase or with stray whitespace
+    let typed = format!("  {}\n", KEY_ACTIVE.to_lowercase());
+    let (status, body) = f.redeem(&typed, "device-1").await;
+
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert!(body["token"].as_str().is_some());
+    assert_eq!(body["tier"], "pro");
+}
+
+#[tokio::test]
+async fn test_redeem_unknown_or_unimported_key_is_refused() {
+    let f = setup();
+    let api_base = mock_lemonsqueezy().await;
+    f.import(&api_base).await;
+
+    for key in [KEY_OTHER_PRODUCT, KEY_DISABLED, "not-a-key"] {
+        let (status, _) = f.redeem(key, "device-1").await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{}", key);
+    }
+}
+
+#[tokio::test]
+async fn test_redeem_enforces_device_limit() {
+    let f = setup();
+    let api_base = mock_lemonsqueezy().await;
+    f.import(&api_base).await;
+    let limit = f.product.device_limit.unwrap();
+
+    for i in 0..limit {
+        let (status, body) = f.redeem(KEY_ACTIVE, &format!("device-{}", i)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+    }
+    // Re-activating a known device doesn't take another slot
+    let (status, _) = f.redeem(KEY_ACTIVE, "device-0").await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = f.redeem(KEY_ACTIVE, "one-too-many").await;
+
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(
+        body["details"]
+            .as_str()
+            .unwrap()
+            .contains("Device limit reached"),
+        "{}",
+        body
+    );
+}
+
+#[tokio::test]
+async fn test_validate_license_key_against_lemonsqueezy() {
+    let api_base = mock_lemonsqueezy().await;
+    let client =
+        LemonSqueezyClient::with_endpoint(&Fixture::ls_config(), &api_base, Duration::from_secs(5));
+
+    assert!(client.validate_license_key(KEY_ACTIVE).await.unwrap());
+    assert!(
+        !client.validate_license_key(KEY_DISABLED).await.unwrap(),
+        "a key LemonSqueezy doesn't know should be invalid, not an error"
+    );
+}
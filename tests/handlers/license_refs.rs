@@ -0,0 +1,307 @@
+//! Tests for license refs: tokens and public responses name licenses by ref,
+//! admin endpoints take either the ref or the ID, and colliding refs are
+//! recomputed with a counter.
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode},
+};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::db::license_refs;
+use paycheck::handlers;
+
+struct RefFixture {
+    state: AppState,
+    org_id: String,
+    project: Project,
+    product: Product,
+    license: License,
+    api_key: String,
+}
+
+fn setup() -> RefFixture {
+    let state = create_test_app_state();
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, api_key) =
+        create_test_org_member(&mut conn, &org.id, "admin@test.com", OrgMemberRole::Owner);
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+    let license = create_test_license(
+        &conn,
+        &project.id,
+        &product.id,
+        Some(future_timestamp(ONE_YEAR)),
+    );
+
+    drop(conn);
+    RefFixture {
+        state,
+        org_id: org.id,
+        project,
+        product,
+        license,
+        api_key,
+    }
+}
+
+fn app(state: &AppState) -> Router {
+    public_app(state.clone()).merge(
+        handlers::orgs::router(state.clone(), paycheck::config::RateLimitConfig::disabled())
+            .with_state(state.clone()),
+    )
+}
+
+impl RefFixture {
+    fn license_ref(&self) -> &str {
+        self.license.license_ref.as_deref().unwrap()
+    }
+
+    async fn send(&self, request: Request<Body>) -> (StatusCode, String) {
+        let response = app(&self.state).oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(bytes.to_vec()).unwrap())
+    }
+
+    async fn admin(&self, method: &str, license: &str, suffix: &str) -> (StatusCode, Value) {
+        let (status, body) = self
+            .send(
+                Request::builder()
+                    .method(method)
+                    .uri(format!(
+                        "/orgs/{}/projects/{}/licenses/{}{}",
+                        self.org_id, self.project.id, license, suffix
+                    ))
+                    .header("Authorization", format!("Bearer {}", self.api_key))
+                    .header("content-type", "application/json")
+                    .body(Body::from("{}"))
+                    .unwrap(),
+            )
+            .await;
+        (status, serde_json::from_str(&body).unwrap_or(Value::Null))
+    }
+
+    async fn public(&self, method: &str, uri: &str, token: Option<&str>, body: Value) -> String {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json");
+        if let Some(token) = token {
+            request = request.header("Authorization", format!("Bearer {}", token));
+        }
+        let (status, body) = self
+            .send(request.body(Body::from(body.to_string())).unwrap())
+            .await;
+        assert_eq!(status, StatusCode::OK, "{} {}: {}", method, uri, body);
+        body
+    }
+}
+
+/// Decode a token's payload without verifying it
+fn token_payload(token: &str) -> Value {
+    let payload = token.split('.').nth(1).unwrap();
+    serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn test_new_licenses_get_a_ref() {
+    let f = setup();
+    let license_ref = f.license_ref();
+    assert_eq!(license_ref.len(), license_refs::LICENSE_REF_LEN);
+    assert!(license_refs::is_license_ref(license_ref));
+    assert!(!license_refs::is_license_ref(&f.license.id));
+
+    let conn = f.state.db.get().unwrap();
+    let other = create_test_license(&conn, &f.project.id, &f.product.id, None);
+    assert_ne!(other.license_ref, f.license.license_ref);
+
+    let stored = queries::get_license_by_id(&conn, &f.license.id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(stored.license_ref.as_deref(), Some(license_ref));
+}
+
+#[tokio::test]
+async fn test_colliding_ref_is_recomputed_with_counter() {
+    let f = setup();
+    let conn = f.state.db.get().unwrap();
+    let key = license_refs::project_key(&conn, &f.project.id).unwrap();
+    assert_eq!(
+        license_refs::compute(&key, &f.license.id, 0),
+        f.license_ref()
+    );
+
+    // Give the existing license the ref a new license would normally get
+    let new_id = "00000000-0000-4000-8000-000000000001";
+    let first_choice = license_refs::compute(&key, new_id, 0);
+    conn.execute(
+        "UPDATE licenses SET license_ref = ?1 WHERE id = ?2",
+        rusqlite::params![first_choice, f.license.id],
+    )
+    .unwrap();
+
+    let assigned = license_refs::assign(&conn, &f.project.id, new_id).unwrap();
+    assert_eq!(assigned, license_refs::compute(&key, new_id, 1));
+    assert_ne!(assigned, first_choice);
+
+    // A license's own ref doesn't count as taken
+    assert_eq!(
+        license_refs::assign(&conn, &f.project.id, &f.license.id).unwrap(),
+        license_refs::compute(&key, &f.license.id, 0)
+    );
+}
+
+#[tokio::test]
+async fn test_refs_differ_between_projects() {
+    let f = setup();
+    let conn = f.state.db.get().unwrap();
+    let other = create_test_project(&conn, &f.org_id, "Other", &test_master_key());
+    let key = license_refs::project_key(&conn, &f.project.id).unwrap();
+    let other_key = license_refs::project_key(&conn, &other.id).unwrap();
+    assert_ne!(key, other_key);
+    assert_ne!(
+        license_refs::compute(&key, &f.license.id, 0),
+        license_refs::compute(&other_key, &f.license.id, 0)
+    );
+}
+
+#[tokio::test]
+async fn test_admin_endpoints_accept_id_or_ref() {
+    let f = setup();
+
+    for license in [f.license.id.as_str(), f.license_ref()] {
+        let (status, body) = f.admin("GET", license, "").await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["id"], f.license.id.as_str());
+        assert_eq!(body["license_ref"], f.license_ref());
+
+        let (status, body) = f.admin("GET", license, "/claims-preview").await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["claims"]["sub"], f.license_ref());
+    }
+
+    let (status, _) = f.admin("GET", "0123456789ab", "").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, body) = f.admin("POST", f.license_ref(), "/revoke").await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let conn = f.state.db.get().unwrap();
+    let revoked = queries::get_license_by_id(&conn, &f.license.id)
+        .unwrap()
+        .unwrap();
+    assert!(revoked.revoked);
+}
+
+#[tokio::test]
+async fn test_license_list_shows_ref_and_product_name() {
+    let f = setup();
+    let (status, body) = f
+        .send(
+            Request::builder()
+                .method("GET")
+                .uri(format!(
+                    "/orgs/{}/projects/{}/licenses",
+                    f.org_id, f.project.id
+                ))
+                .header("Authorization", format!("Bearer {}", f.api_key))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let body: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["items"][0]["license_ref"], f.license_ref());
+    assert_eq!(body["items"][0]["product_name"], "Pro Plan");
+}
+
+#[tokio::test]
+async fn test_ref_from_another_project_is_not_found() {
+    let f = setup();
+    let conn = f.state.db.get().unwrap();
+    let other = create_test_project(&conn, &f.org_id, "Other", &test_master_key());
+    let other_product = create_test_product(&conn, &other.id, "Basic", "basic");
+    let other_license = create_test_license(&conn, &other.id, &other_product.id, None);
+    drop(conn);
+
+    let (status, _) = f
+        .admin("GET", other_license.license_ref.as_deref().unwrap(), "")
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_public_responses_carry_no_license_id() {
+    let f = setup();
+    let code = create_test_activation_code(
+        &f.state.db.get().unwrap(),
+        &f.license.id,
+        &f.project.license_key_prefix,
+    );
+
+    let redeemed = f
+        .public(
+            "POST",
+            "/redeem",
+            None,
+            json!({
+                "public_key": f.project.public_key,
+                "code": code.code,
+                "device_id": "device-1",
+                "device_type": "uuid"
+            }),
+        )
+        .await;
+    assert!(!redeemed.contains(&f.license.id), "{}", redeemed);
+    let redeemed: Value = serde_json::from_str(&redeemed).unwrap();
+    let token = redeemed["token"].as_str().unwrap().to_string();
+
+    let claims = token_payload(&token);
+    assert_eq!(claims["sub"], f.license_ref());
+    assert_eq!(claims["license_ref"], f.license_ref());
+    assert!(!claims.to_string().contains(&f.license.id));
+
+    let jti = claims["jti"].as_str().unwrap();
+    let validated = f
+        .public(
+            "POST",
+            "/validate",
+            None,
+            json!({ "public_key": f.project.public_key, "jti": jti }),
+        )
+        .await;
+    assert!(!validated.contains(&f.license.id), "{}", validated);
+
+    let info = f
+        .public(
+            "GET",
+            &format!(
+                "/license?public_key={}",
+                urlencoding::encode(&f.project.public_key)
+            ),
+            Some(&token),
+            Value::Null,
+        )
+        .await;
+    assert!(!info.contains(&f.license.id), "{}", info);
+
+    let refreshed = f
+        .public("POST", "/refresh", Some(&token), Value::Null)
+        .await;
+    assert!(!refreshed.contains(&f.license.id), "{}", refreshed);
+    let refreshed: Value = serde_json::from_str(&refreshed).unwrap();
+    let claims = token_payload(refreshed["token"].as_str().unwrap());
+    assert_eq!(claims["sub"], f.license_ref());
+}
@@ -0,0 +1,227 @@
+//! Tests for revocation reasons: the revoke endpoint records reason, message,
+//! time, and actor, and /validate and /redeem tell the customer why.
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode},
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::handlers;
+
+struct RevocationFixture {
+    state: AppState,
+    org_id: String,
+    project_id: String,
+    public_key: String,
+    license_id: String,
+    jti: String,
+    code: String,
+    user_id: String,
+    api_key: String,
+}
+
+fn setup() -> RevocationFixture {
+    let state = create_test_app_state();
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let (user, _, api_key) =
+        create_test_org_member(&mut conn, &org.id, "owner@test.com", OrgMemberRole::Owner);
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+    let license = create_test_license(&conn, &project.id, &product.id, None);
+    let device = create_test_device(&conn, &license.id, "device-1", DeviceType::Uuid);
+    let code = create_test_activation_code(&conn, &license.id, &project.license_key_prefix);
+
+    drop(conn);
+    RevocationFixture {
+        state,
+        org_id: org.id,
+        project_id: project.id,
+        public_key: project.public_key,
+        license_id: license.id,
+        jti: device.jti,
+        code: code.code,
+        user_id: user.id,
+        api_key,
+    }
+}
+
+impl RevocationFixture {
+    async fn request(
+        &self,
+        app: Router,
+        method: &str,
+        uri: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("Authorization", format!("Bearer {}", self.api_key));
+        // Bodyless revokes must work, so only JSON requests get a content type
+        if body.is_some() {
+            request = request.header("content-type", "application/json");
+        }
+        let response = app
+            .oneshot(
+                request
+                    .body(body.map_or_else(Body::empty, |b| Body::from(b.to_string())))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        )
+    }
+
+    async fn org_request(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let app = handlers::orgs::router(
+            self.state.clone(),
+            paycheck::config::RateLimitConfig::disabled(),
+        )
+        .with_state(self.state.clone());
+        let uri = format!(
+            "/orgs/{}/projects/{}/licenses/{}{}",
+            self.org_id, self.project_id, self.license_id, path
+        );
+        self.request(app, method, &uri, body).await
+    }
+
+    async fn revoke(&self, body: Option<Value>) -> (StatusCode, Value) {
+        self.org_request("POST", "/revoke", body).await
+    }
+
+    async fn validate(&self) -> Value {
+        let (status, json) = self
+            .request(
+                public_app(self.state.clone()),
+                "POST",
+                "/validate",
+                Some(json!({ "public_key": self.public_key, "jti": self.jti })),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        json
+    }
+
+    async fn redeem(&self) -> (StatusCode, Value) {
+        self.request(
+            public_app(self.state.clone()),
+            "POST",
+            "/redeem",
+            Some(json!({
+                "public_key": self.public_key,
+                "code": self.code,
+                "device_id": "device-2",
+                "device_type": "uuid"
+            })),
+        )
+        .await
+    }
+}
+
+#[tokio::test]
+async fn test_each_reason_reaches_the_customer() {
+    for reason in [
+        "refund",
+        "chargeback",
+        "payment_failed",
+        "abuse",
+        "superseded",
+        "admin_other",
+    ] {
+        let f = setup();
+        let message = format!("Revoked for {}", reason);
+
+        let (status, _) = f
+            .revoke(Some(json!({ "reason": reason, "message": message })))
+            .await;
+        assert_eq!(status, StatusCode::OK, "revoke {}", reason);
+
+        let validated = f.validate().await;
+        assert_eq!(validated["valid"], false);
+        assert_eq!(validated["reason"], "revoked");
+        assert_eq!(validated["revocation"]["reason"], reason);
+        assert_eq!(validated["revocation"]["message"], message.as_str());
+        assert!(validated["revocation"]["revoked_at"].is_i64());
+
+        let (status, redeemed) = f.redeem().await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "redeem {}", reason);
+        assert_eq!(redeemed["code"], "license_revoked");
+        assert_eq!(redeemed["revocation"]["reason"], reason);
+        assert_eq!(redeemed["revocation"]["message"], message.as_str());
+
+        let (status, detail) = f.org_request("GET", "", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(detail["revoked"], true);
+        assert_eq!(detail["revoked_reason"], reason);
+        assert_eq!(detail["revoked_message"], message.as_str());
+        assert_eq!(detail["revoked_by"], f.user_id.as_str());
+        assert!(detail["revoked_at"].is_i64());
+    }
+}
+
+#[tokio::test]
+async fn test_revoke_without_body_defaults_to_admin_other() {
+    let f = setup();
+    let (status, _) = f.revoke(None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let revocation = &f.validate().await["revocation"];
+    assert_eq!(revocation["reason"], "admin_other");
+    assert!(revocation["message"].is_null());
+}
+
+#[tokio::test]
+async fn test_revoke_message_is_trimmed_and_capped() {
+    let f = setup();
+    let (status, _) = f.revoke(Some(json!({ "message": "x".repeat(501) }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        f.validate().await["valid"],
+        true,
+        "rejected revoke must not apply"
+    );
+
+    let (status, _) = f
+        .revoke(Some(json!({ "reason": "abuse", "message": "   " })))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let revocation = &f.validate().await["revocation"];
+    assert_eq!(revocation["reason"], "abuse");
+    assert!(revocation["message"].is_null());
+}
+
+#[tokio::test]
+async fn test_unknown_reason_rejected() {
+    let f = setup();
+    let (status, _) = f.revoke(Some(json!({ "reason": "bored" }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_unrevoked_license_has_no_revocation() {
+    let f = setup();
+    let validated = f.validate().await;
+    assert_eq!(validated["valid"], true);
+    assert!(validated.get("revocation").is_none());
+}
@@ -0,0 +1,543 @@
+//! Tests for team license seats: assignment, per-seat device limits, the
+//! seat-aware activation code flow, and seat removal.
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode},
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::handlers;
+
+const SEAT_EMAIL: &str = "seat@example.com";
+
+struct SeatFixture {
+    state: AppState,
+    org_id: String,
+    project: Project,
+    product: Product,
+    license: License,
+    api_key: String,
+}
+
+/// A team license with `seats` seats on a product allowing one device per seat.
+fn setup_seat_license(seats: i32) -> SeatFixture {
+    let state = create_test_app_state();
+    let master_key = test_master_key();
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let (_user, _member, api_key) =
+        create_test_org_member(&mut conn, &org.id, "admin@test.com", OrgMemberRole::Owner);
+    let project = create_test_project(&conn, &org.id, "Test Project", &master_key);
+    let product = queries::create_product(
+        &conn,
+        &project.id,
+        &CreateProduct {
+            name: "Team".to_string(),
+            tier: "team".to_string(),
+            license_exp_days: None,
+            updates_exp_days: None,
+            activation_limit: None,
+            device_limit: Some(1),
+            device_inactive_days: None,
+            features: vec![],
+            entitlements: Default::default(),
+            price_cents: None,
+            currency: None,
+            seat_count: Some(seats),
+            available_from: None,
+            available_until: None,
+            checkout_fields: vec![],
+            extends_updates_for_product_id: None,
+            renewal_fallback: Default::default(),
+            revoke_on_partial_refund: false,
+        },
+    )
+    .unwrap();
+    let license = queries::create_license(
+        &conn,
+        &project.id,
+        &product.id,
+        &CreateLicense {
+            email_hash: Some(test_email_hasher().hash(&parse_email("buyer@example.com"))),
+            customer_id: None,
+            expires_at: None,
+            updates_expires_at: None,
+            payment_provider: None,
+            payment_provider_customer_id: None,
+            payment_provider_subscription_id: None,
+            payment_provider_order_id: None,
+            seats: product.seat_count,
+        },
+    )
+    .unwrap();
+
+    drop(conn);
+    SeatFixture {
+        state,
+        org_id: org.id,
+        project,
+        product,
+        license,
+        api_key,
+    }
+}
+
+fn org_app(state: &AppState) -> Router {
+    handlers::orgs::router(state.clone(), paycheck::config::RateLimitConfig::disabled())
+        .with_state(state.clone())
+}
+
+fn seats_uri(f: &SeatFixture) -> String {
+    format!(
+        "/orgs/{}/projects/{}/licenses/{}/seats",
+        f.org_id, f.project.id, f.license.id
+    )
+}
+
+async fn send(
+    app: Router,
+    method: &str,
+    uri: &str,
+    api_key: &str,
+    body: Value,
+) -> (StatusCode, Value) {
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("content-type", "application/json")
+                .header("Authorization", format!("Bearer {}", api_key))
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (
+        status,
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    )
+}
+
+async fn assign_seat(f: &SeatFixture, email: &str) -> (StatusCode, Value) {
+    send(
+        org_app(&f.state),
+        "POST",
+        &seats_uri(f),
+        &f.api_key,
+        json!({ "email": email }),
+    )
+    .await
+}
+
+/// Activate a device through the seat's device limit check.
+fn activate(
+    f: &SeatFixture,
+    seat_id: Option<&str>,
+    device_id: &str,
+) -> paycheck::error::Result<queries::DeviceAcquisitionResult> {
+    let mut conn = f.state.db.get().unwrap();
+    queries::acquire_device_atomic(
+        &mut conn,
+        &f.license.id,
+        device_id,
+        DeviceType::Uuid,
+        &uuid::Uuid::new_v4().to_string(),
+        None,
+        seat_id,
+        None,
+        f.product.device_limit,
+        f.product.activation_limit,
+        f.product.device_inactive_days,
+    )
+}
+
+// ============ Assignment ============
+
+#[tokio::test]
+async fn test_assign_seats_up_to_capacity() {
+    let f = setup_seat_license(2);
+    assert_eq!(
+        f.license.seats,
+        Some(2),
+        "license inherits product seat_count"
+    );
+
+    let (status, seat) = assign_seat(&f, "one@example.com").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(seat["license_id"], f.license.id.as_str());
+    assert_eq!(
+        seat["seat_email_hash"],
+        test_email_hasher()
+            .hash(&parse_email("one@example.com"))
+            .as_str()
+    );
+
+    let (status, _) = assign_seat(&f, "ONE@example.com").await;
+    assert_eq!(
+        status,
+        StatusCode::CONFLICT,
+        "same (normalized) email can't hold two seats"
+    );
+
+    let (status, _) = assign_seat(&f, "two@example.com").await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = assign_seat(&f, "three@example.com").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "license is full");
+    assert!(
+        body["details"]
+            .as_str()
+            .unwrap_or_default()
+            .contains("2 seats")
+    );
+
+    let (status, list) = send(
+        org_app(&f.state),
+        "GET",
+        &seats_uri(&f),
+        &f.api_key,
+        json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(list["seats"], 2);
+    assert_eq!(list["items"].as_array().unwrap().len(), 2);
+    assert_eq!(list["items"][0]["device_count"], 0);
+}
+
+#[tokio::test]
+async fn test_assign_seat_rejected_for_single_user_license() {
+    let f = setup_seat_license(2);
+    let conn = f.state.db.get().unwrap();
+    let single = create_test_license(&conn, &f.project.id, &f.product.id, None);
+    drop(conn);
+
+    let uri = format!(
+        "/orgs/{}/projects/{}/licenses/{}/seats",
+        f.org_id, f.project.id, single.id
+    );
+    let (status, _) = send(
+        org_app(&f.state),
+        "POST",
+        &uri,
+        &f.api_key,
+        json!({ "email": SEAT_EMAIL }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_removed_seat_frees_capacity() {
+    let f = setup_seat_license(1);
+    let (_, seat) = assign_seat(&f, "first@example.com").await;
+    let seat_id = seat["id"].as_str().unwrap();
+
+    let (status, body) = send(
+        org_app(&f.state),
+        "DELETE",
+        &format!("{}/{}", seats_uri(&f), seat_id),
+        &f.api_key,
+        json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["removed"], true);
+
+    let (status, _) = assign_seat(&f, "second@example.com").await;
+    assert_eq!(status, StatusCode::OK, "removed seats don't count");
+
+    let (status, _) = assign_seat(&f, "first@example.com").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "still only one seat");
+}
+
+// ============ Device Limits ============
+
+#[tokio::test]
+async fn test_device_limit_applies_per_seat() {
+    let f = setup_seat_license(2);
+    let (_, a) = assign_seat(&f, "a@example.com").await;
+    let (_, b) = assign_seat(&f, "b@example.com").await;
+    let (a, b) = (a["id"].as_str().unwrap(), b["id"].as_str().unwrap());
+
+    assert!(activate(&f, Some(a), "a-laptop").is_ok());
+    assert!(
+        activate(&f, Some(a), "a-desktop").is_err(),
+        "seat A has used its one device"
+    );
+    assert!(
+        activate(&f, Some(b), "b-laptop").is_ok(),
+        "seat B has its own device allowance"
+    );
+    assert!(
+        activate(&f, Some(a), "a-laptop").is_ok(),
+        "re-activating an existing device doesn't count"
+    );
+
+    let conn = f.state.db.get().unwrap();
+    assert_eq!(queries::count_devices_for_seat(&conn, a).unwrap(), 1);
+    assert_eq!(queries::count_devices_for_seat(&conn, b).unwrap(), 1);
+    assert_eq!(
+        queries::count_devices_for_license(&conn, &f.license.id).unwrap(),
+        2
+    );
+}
+
+#[tokio::test]
+async fn test_device_moves_seat_only_within_target_limit() {
+    let f = setup_seat_license(3);
+    let (_, a) = assign_seat(&f, "a@example.com").await;
+    let (_, b) = assign_seat(&f, "b@example.com").await;
+    let (_, c) = assign_seat(&f, "c@example.com").await;
+    let (a, b, c) = (
+        a["id"].as_str().unwrap(),
+        b["id"].as_str().unwrap(),
+        c["id"].as_str().unwrap(),
+    );
+
+    assert!(activate(&f, Some(a), "a-laptop").is_ok());
+    assert!(activate(&f, Some(b), "b-laptop").is_ok());
+    assert!(
+        activate(&f, Some(b), "a-laptop").is_err(),
+        "seat B is full, so seat A's device can't move onto it"
+    );
+
+    let conn = f.state.db.get().unwrap();
+    let device = queries::get_device_for_license(&conn, &f.license.id, "a-laptop")
+        .unwrap()
+        .unwrap();
+    assert_eq!(device.seat_id.as_deref(), Some(a));
+    drop(conn);
+
+    assert!(activate(&f, Some(c), "a-laptop").is_ok(), "seat C has room");
+    let conn = f.state.db.get().unwrap();
+    assert_eq!(queries::count_devices_for_seat(&conn, a).unwrap(), 0);
+    assert_eq!(queries::count_devices_for_seat(&conn, b).unwrap(), 1);
+    assert_eq!(queries::count_devices_for_seat(&conn, c).unwrap(), 1);
+}
+
+// ============ Activation Code Flow ============
+
+#[tokio::test]
+async fn test_request_code_issues_seat_scoped_code() {
+    let f = setup_seat_license(2);
+    let (_, seat) = assign_seat(&f, SEAT_EMAIL).await;
+    let seat_id = seat["id"].as_str().unwrap().to_string();
+
+    for email in [SEAT_EMAIL, "stranger@example.com"] {
+        let response = public_app(f.state.clone())
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/activation/request-code")
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        json!({ "email": email, "public_key": f.project.public_key }).to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    let conn = f.state.db.get().unwrap();
+    let codes: Vec<(String, Option<String>)> = conn
+        .prepare("SELECT license_id, seat_id FROM activation_codes")
+        .unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        codes,
+        vec![(f.license.id.clone(), Some(seat_id))],
+        "only the seat holder gets a code, scoped to their seat"
+    );
+}
+
+#[tokio::test]
+async fn test_redeem_seat_code_records_seat_on_device() {
+    let f = setup_seat_license(2);
+    let (_, seat) = assign_seat(&f, SEAT_EMAIL).await;
+    let seat_id = seat["id"].as_str().unwrap();
+
+    let conn = f.state.db.get().unwrap();
+    let code = queries::create_activation_code_for_seat(
+        &conn,
+        &f.license.id,
+        Some(seat_id),
+        &f.project.license_key_prefix,
+    )
+    .unwrap();
+    drop(conn);
+
+    let (status, body) = redeem(&f, &code.code, "seat-device").await;
+    assert_eq!(status, StatusCode::OK);
+
+    let conn = f.state.db.get().unwrap();
+    let device = queries::get_device_for_license(&conn, &f.license.id, "seat-device")
+        .unwrap()
+        .unwrap();
+    assert_eq!(device.seat_id.as_deref(), Some(seat_id));
+
+    let next =
+        queries::get_activation_code_by_code(&conn, body["activation_code"].as_str().unwrap())
+            .unwrap()
+            .unwrap();
+    assert_eq!(
+        next.seat_id.as_deref(),
+        Some(seat_id),
+        "follow-up code stays scoped to the seat"
+    );
+}
+
+async fn redeem(f: &SeatFixture, code: &str, device_id: &str) -> (StatusCode, Value) {
+    let response = public_app(f.state.clone())
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/redeem")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    json!({
+                        "public_key": f.project.public_key,
+                        "code": code,
+                        "device_id": device_id,
+                        "device_type": "uuid"
+                    })
+                    .to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (
+        status,
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    )
+}
+
+// ============ Removal ============
+
+#[tokio::test]
+async fn test_remove_seat_revokes_only_that_seats_devices() {
+    let f = setup_seat_license(2);
+    let (_, a) = assign_seat(&f, "a@example.com").await;
+    let (_, b) = assign_seat(&f, "b@example.com").await;
+    let (a, b) = (a["id"].as_str().unwrap(), b["id"].as_str().unwrap());
+
+    activate(&f, Some(a), "a-laptop").unwrap();
+    activate(&f, Some(b), "b-laptop").unwrap();
+
+    let conn = f.state.db.get().unwrap();
+    let a_device = queries::get_device_for_license(&conn, &f.license.id, "a-laptop")
+        .unwrap()
+        .unwrap();
+    let b_device = queries::get_device_for_license(&conn, &f.license.id, "b-laptop")
+        .unwrap()
+        .unwrap();
+    let pending = queries::create_activation_code_for_seat(
+        &conn,
+        &f.license.id,
+        Some(a),
+        &f.project.license_key_prefix,
+    )
+    .unwrap();
+    drop(conn);
+
+    let (status, body) = send(
+        org_app(&f.state),
+        "DELETE",
+        &format!("{}/{}", seats_uri(&f), a),
+        &f.api_key,
+        json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["devices_deactivated"], 1);
+
+    let conn = f.state.db.get().unwrap();
+    assert!(queries::is_jti_revoked(&conn, &a_device.jti).unwrap());
+    assert!(!queries::is_jti_revoked(&conn, &b_device.jti).unwrap());
+    assert!(
+        queries::get_device_for_license(&conn, &f.license.id, "a-laptop")
+            .unwrap()
+            .is_none()
+    );
+    assert!(
+        queries::get_device_for_license(&conn, &f.license.id, "b-laptop")
+            .unwrap()
+            .is_some()
+    );
+    assert!(
+        queries::get_activation_code_by_code(&conn, &pending.code)
+            .unwrap()
+            .is_none(),
+        "pending seat codes are dropped"
+    );
+    drop(conn);
+
+    let (status, _) = send(
+        org_app(&f.state),
+        "DELETE",
+        &format!("{}/{}", seats_uri(&f), a),
+        &f.api_key,
+        json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND, "seat is already removed");
+}
+
+#[tokio::test]
+async fn test_seat_code_rejected_after_seat_removed() {
+    let f = setup_seat_license(1);
+    let (_, seat) = assign_seat(&f, SEAT_EMAIL).await;
+    let seat_id = seat["id"].as_str().unwrap();
+
+    let mut conn = f.state.db.get().unwrap();
+    let code = queries::create_activation_code_for_seat(
+        &conn,
+        &f.license.id,
+        Some(seat_id),
+        &f.project.license_key_prefix,
+    )
+    .unwrap();
+    // A code issued right as the seat is removed must not slip through either
+    let seat = queries::get_active_license_seat(&conn, &f.license.id, seat_id)
+        .unwrap()
+        .unwrap();
+    queries::remove_license_seat(&mut conn, &seat, None).unwrap();
+    let restored = queries::create_activation_code_for_seat(
+        &conn,
+        &f.license.id,
+        Some(seat_id),
+        &f.project.license_key_prefix,
+    )
+    .unwrap();
+    drop(conn);
+    assert!(
+        redeem(&f, &code.code, "dev-1").await.0.is_client_error(),
+        "code dropped on removal"
+    );
+
+    let (status, _) = redeem(&f, &restored.code, "dev-2").await;
+    assert_eq!(status, StatusCode::FORBIDDEN, "seat is no longer assigned");
+}
@@ -0,0 +1,298 @@
+//! Tests for license tags: add/remove, validation and limits, the list
+//! filter, project tag counts, and bulk assignment.
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode},
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::handlers;
+
+struct TagFixture {
+    state: AppState,
+    org_id: String,
+    project: Project,
+    product: Product,
+    licenses: Vec<License>,
+    api_key: String,
+}
+
+/// A project with three licenses on one product
+fn setup() -> TagFixture {
+    let mut state = create_test_app_state();
+    state.audit_log_enabled = true;
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, api_key) =
+        create_test_org_member(&mut conn, &org.id, "admin@test.com", OrgMemberRole::Owner);
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+    let licenses = (0..3)
+        .map(|_| {
+            create_test_license(
+                &conn,
+                &project.id,
+                &product.id,
+                Some(future_timestamp(ONE_YEAR)),
+            )
+        })
+        .collect();
+
+    drop(conn);
+    TagFixture {
+        state,
+        org_id: org.id,
+        project,
+        product,
+        licenses,
+        api_key,
+    }
+}
+
+impl TagFixture {
+    fn licenses_uri(&self) -> String {
+        format!(
+            "/orgs/{}/projects/{}/licenses",
+            self.org_id, self.project.id
+        )
+    }
+
+    fn tags_uri(&self, license: &License) -> String {
+        format!("{}/{}/tags", self.licenses_uri(), license.id)
+    }
+
+    fn app(&self) -> Router {
+        handlers::orgs::router(
+            self.state.clone(),
+            paycheck::config::RateLimitConfig::disabled(),
+        )
+        .with_state(self.state.clone())
+    }
+
+    async fn request(&self, method: &str, uri: &str, body: Body) -> (StatusCode, Value) {
+        let response = self
+            .app()
+            .oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri(uri)
+                    .header("content-type", "application/json")
+                    .header("Authorization", format!("Bearer {}", self.api_key))
+                    .body(body)
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        )
+    }
+
+    async fn send(&self, method: &str, uri: &str, body: Value) -> (StatusCode, Value) {
+        self.request(method, uri, Body::from(body.to_string()))
+            .await
+    }
+
+    async fn get(&self, uri: &str) -> (StatusCode, Value) {
+        self.request("GET", uri, Body::empty()).await
+    }
+}
+
+#[tokio::test]
+async fn test_add_and_remove_tags() {
+    let f = setup();
+    let uri = f.tags_uri(&f.licenses[0]);
+
+    let (status, json) = f
+        .send(
+            "POST",
+            &uri,
+            json!({ "tags": ["beta-cohort", "Pilot_2", "beta-cohort"] }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["tags"], json!(["beta-cohort", "pilot_2"]));
+
+    let (status, json) = f.send("DELETE", &uri, json!({ "tags": ["pilot_2"] })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["tags"], json!(["beta-cohort"]));
+
+    let (_, detail) = f
+        .get(&format!("{}/{}", f.licenses_uri(), f.licenses[0].id))
+        .await;
+    assert_eq!(detail["tags"], json!(["beta-cohort"]));
+
+    let action_count: i64 = f
+        .state
+        .audit
+        .get()
+        .unwrap()
+        .query_row(
+            "SELECT COUNT(*) FROM audit_logs WHERE action IN ('add_license_tags', 'remove_license_tags') AND resource_id = ?1",
+            [&f.licenses[0].id],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(action_count, 2);
+}
+
+#[tokio::test]
+async fn test_rejects_invalid_tags() {
+    let f = setup();
+    let uri = f.tags_uri(&f.licenses[0]);
+
+    for tags in [
+        json!([]),
+        json!([""]),
+        json!(["has space"]),
+        json!(["x".repeat(41)]),
+    ] {
+        let (status, _) = f.send("POST", &uri, json!({ "tags": tags })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "tags {}", tags);
+    }
+}
+
+#[tokio::test]
+async fn test_tag_limit_per_license() {
+    let f = setup();
+    let uri = f.tags_uri(&f.licenses[0]);
+
+    let tags: Vec<String> = (0..20).map(|i| format!("tag-{}", i)).collect();
+    let (status, _) = f.send("POST", &uri, json!({ "tags": tags })).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = f.send("POST", &uri, json!({ "tags": ["one-more"] })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Re-adding an existing tag doesn't count against the limit
+    let (status, _) = f.send("POST", &uri, json!({ "tags": ["tag-0"] })).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_list_filters_by_tag_and_includes_tags() {
+    let f = setup();
+    for license in &f.licenses[..2] {
+        f.send("POST", &f.tags_uri(license), json!({ "tags": ["beta"] }))
+            .await;
+    }
+
+    let (status, json) = f.get(&format!("{}?tag=beta", f.licenses_uri())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["total"], 2);
+    for item in json["items"].as_array().unwrap() {
+        assert_eq!(item["tags"], json!(["beta"]));
+    }
+
+    let (_, json) = f.get(&f.licenses_uri()).await;
+    let untagged = json["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|item| item["id"] == f.licenses[2].id.as_str())
+        .unwrap();
+    assert_eq!(untagged["tags"], json!([]));
+}
+
+#[tokio::test]
+async fn test_project_tag_counts() {
+    let f = setup();
+    f.send(
+        "POST",
+        &f.tags_uri(&f.licenses[0]),
+        json!({ "tags": ["beta", "vip"] }),
+    )
+    .await;
+    f.send(
+        "POST",
+        &f.tags_uri(&f.licenses[1]),
+        json!({ "tags": ["beta"] }),
+    )
+    .await;
+
+    let (status, json) = f.get(&format!("{}/tags", f.licenses_uri())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        json,
+        json!([
+            { "tag": "beta", "license_count": 2 },
+            { "tag": "vip", "license_count": 1 }
+        ])
+    );
+}
+
+#[tokio::test]
+async fn test_bulk_tag_by_filter() {
+    let f = setup();
+    {
+        let conn = f.state.db.get().unwrap();
+        queries::revoke_license(&conn, &f.licenses[2].id, &RevokeLicense::default(), None).unwrap();
+    }
+
+    let (status, json) = f
+        .send(
+            "POST",
+            &format!("{}/tags/bulk", f.licenses_uri()),
+            json!({
+                "tags": ["spring-sale"],
+                "filter": { "product_id": f.product.id, "revoked": false }
+            }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["matched"], 2);
+    assert_eq!(json["added"], 2);
+
+    let (_, json) = f
+        .get(&format!("{}?tag=spring-sale", f.licenses_uri()))
+        .await;
+    assert_eq!(json["total"], 2);
+}
+
+#[tokio::test]
+async fn test_bulk_tag_requires_filter() {
+    let f = setup();
+
+    let (status, _) = f
+        .send(
+            "POST",
+            &format!("{}/tags/bulk", f.licenses_uri()),
+            json!({ "tags": ["everyone"], "filter": {} }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_tags_on_license_from_another_project_are_not_found() {
+    let f = setup();
+    let other = {
+        let conn = f.state.db.get().unwrap();
+        create_test_project(&conn, &f.org_id, "Other Project", &test_master_key())
+    };
+
+    let (status, _) = f
+        .send(
+            "POST",
+            &format!(
+                "/orgs/{}/projects/{}/licenses/{}/tags",
+                f.org_id, other.id, f.licenses[0].id
+            ),
+            json!({ "tags": ["beta"] }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,321 @@
+//! Tests for maintenance mode: while it's on, writes in every API get a 503
+//! with the operator's message, reads and `/validate` work only with
+//! `allow_reads`, and the switch itself, `/health`, and a restart keep
+//! working.
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode, header},
+    middleware,
+    response::Response,
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::config::RateLimitConfig;
+use paycheck::handlers;
+use paycheck::middleware::{
+    DEFAULT_MAINTENANCE_MESSAGE, MAINTENANCE_RETRY_AFTER_SECS, maintenance_gate,
+};
+
+struct MaintenanceFixture {
+    state: AppState,
+    org_id: String,
+    member_key: String,
+    owner_key: String,
+    view_key: String,
+}
+
+fn setup() -> MaintenanceFixture {
+    let state = create_test_app_state();
+    let mut conn = state.db.get().unwrap();
+    let (_, owner_key) = create_test_operator(&mut conn, "owner@test.com", OperatorRole::Owner);
+    let (_, view_key) = create_test_operator(&mut conn, "view@test.com", OperatorRole::View);
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, member_key) =
+        create_test_org_member(&mut conn, &org.id, "member@test.com", OrgMemberRole::Owner);
+    drop(conn);
+    MaintenanceFixture {
+        state,
+        org_id: org.id,
+        member_key,
+        owner_key,
+        view_key,
+    }
+}
+
+impl MaintenanceFixture {
+    /// Every API behind the maintenance gate, as `handlers::app` layers it.
+    fn app(&self) -> Router {
+        Router::new()
+            .merge(handlers::public::router(RateLimitConfig::disabled()))
+            .merge(handlers::webhooks::router())
+            .merge(handlers::operators::router(self.state.clone()))
+            .merge(handlers::orgs::router(
+                self.state.clone(),
+                RateLimitConfig::disabled(),
+            ))
+            .layer(middleware::from_fn_with_state(
+                self.state.clone(),
+                maintenance_gate,
+            ))
+            .with_state(self.state.clone())
+    }
+
+    async fn send(&self, method: &str, path: &str, key: Option<&str>, body: Value) -> Response {
+        let mut request = Request::builder().method(method).uri(path);
+        if let Some(key) = key {
+            request = request.header("Authorization", format!("Bearer {}", key));
+        }
+        let body = if body.is_null() {
+            Body::empty()
+        } else {
+            request = request.header("content-type", "application/json");
+            Body::from(body.to_string())
+        };
+        self.app()
+            .oneshot(request.body(body).unwrap())
+            .await
+            .unwrap()
+    }
+
+    async fn set_mode(&self, key: &str, body: Value) -> (StatusCode, Value) {
+        let response = self
+            .send("POST", "/operators/maintenance-mode", Some(key), body)
+            .await;
+        let status = response.status();
+        (status, body_json(response).await)
+    }
+
+    async fn enable(&self, allow_reads: bool) {
+        let (status, body) = self
+            .set_mode(
+                &self.owner_key,
+                json!({
+                    "enabled": true,
+                    "message": "Database upgrade in progress",
+                    "allow_reads": allow_reads,
+                }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+    }
+
+    async fn list_projects(&self) -> Response {
+        self.send(
+            "GET",
+            &format!("/orgs/{}/projects", self.org_id),
+            Some(&self.member_key),
+            Value::Null,
+        )
+        .await
+    }
+
+    async fn create_project(&self) -> Response {
+        self.send(
+            "POST",
+            &format!("/orgs/{}/projects", self.org_id),
+            Some(&self.member_key),
+            json!({ "name": "New Project" }),
+        )
+        .await
+    }
+}
+
+async fn body_json(response: Response) -> Value {
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    serde_json::from_slice(&bytes).unwrap_or(Value::Null)
+}
+
+/// Assert the response is maintenance mode's 503.
+async fn assert_held_back(response: Response) {
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(
+        response.headers()[header::RETRY_AFTER],
+        MAINTENANCE_RETRY_AFTER_SECS.to_string()
+    );
+    let body = body_json(response).await;
+    assert_eq!(body["details"], "Database upgrade in progress");
+    assert_eq!(body["code"], "maintenance");
+}
+
+#[tokio::test]
+async fn test_writes_get_503_with_message() {
+    let f = setup();
+    f.enable(true).await;
+
+    assert_held_back(f.create_project().await).await;
+
+    let response = f.list_projects().await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Operator writes are held back too
+    let response = f
+        .send(
+            "POST",
+            "/operators/jwks/refresh",
+            Some(&f.owner_key),
+            Value::Null,
+        )
+        .await;
+    assert_held_back(response).await;
+}
+
+#[tokio::test]
+async fn test_reads_blocked_without_allow_reads() {
+    let f = setup();
+    f.enable(false).await;
+
+    assert_held_back(f.list_projects().await).await;
+    let response = f
+        .send("POST", "/validate", None, json!({ "jti": "x" }))
+        .await;
+    assert_held_back(response).await;
+}
+
+#[tokio::test]
+async fn test_validate_served_with_allow_reads() {
+    let f = setup();
+    f.enable(true).await;
+
+    let response = f
+        .send("POST", "/validate", None, json!({ "jti": "x" }))
+        .await;
+    assert_ne!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn test_purchase_and_activation_flows_get_503() {
+    let f = setup();
+    f.enable(true).await;
+
+    // GETs, but they create payment sessions and licenses
+    for path in ["/buy?product_id=p1", "/callback?session=s1"] {
+        assert_held_back(f.send("GET", path, None, Value::Null).await).await;
+    }
+    for path in ["/activation/request-code", "/redeem", "/refresh"] {
+        assert_held_back(f.send("POST", path, None, json!({})).await).await;
+    }
+}
+
+#[tokio::test]
+async fn test_webhooks_get_503_so_providers_retry() {
+    let f = setup();
+    f.enable(true).await;
+
+    for path in ["/webhook/stripe", "/webhook/lemonsqueezy"] {
+        let response = f
+            .send(
+                "POST",
+                path,
+                None,
+                json!({ "type": "checkout.session.completed" }),
+            )
+            .await;
+        assert_held_back(response).await;
+    }
+}
+
+#[tokio::test]
+async fn test_health_and_switch_always_allowed() {
+    let f = setup();
+    f.enable(false).await;
+
+    let response = f.send("GET", "/health", None, Value::Null).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = f
+        .send(
+            "GET",
+            "/operators/maintenance-mode",
+            Some(&f.view_key),
+            Value::Null,
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = body_json(response).await;
+    assert_eq!(body["enabled"], true);
+    assert_eq!(body["message"], "Database upgrade in progress");
+    assert_eq!(body["allow_reads"], false);
+
+    let (status, body) = f.set_mode(&f.owner_key, json!({ "enabled": false })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({ "enabled": false }));
+
+    let response = f.create_project().await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_setting_persists_and_is_audited() {
+    let f = setup();
+    f.enable(true).await;
+
+    let reloaded = MaintenanceMode::load(&f.state.db.get().unwrap()).unwrap();
+    assert_eq!(reloaded.current(), f.state.maintenance.current());
+    assert_eq!(
+        reloaded.current().unwrap().message,
+        "Database upgrade in progress"
+    );
+
+    f.set_mode(&f.owner_key, json!({ "enabled": false })).await;
+    let reloaded = MaintenanceMode::load(&f.state.db.get().unwrap()).unwrap();
+    assert_eq!(reloaded.current(), None);
+
+    let actions: Vec<String> = f
+        .state
+        .audit
+        .get()
+        .unwrap()
+        .prepare(
+            "SELECT action FROM audit_logs WHERE resource_type = 'system_config' ORDER BY action",
+        )
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        actions,
+        ["disable_maintenance_mode", "enable_maintenance_mode"]
+    );
+}
+
+#[tokio::test]
+async fn test_enable_defaults() {
+    let f = setup();
+    let (status, body) = f.set_mode(&f.owner_key, json!({ "enabled": true })).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["message"], DEFAULT_MAINTENANCE_MESSAGE);
+    assert_eq!(body["allow_reads"], true);
+    assert!(body["enabled_by"].is_string());
+}
+
+#[tokio::test]
+async fn test_rejects_blank_message() {
+    let f = setup();
+    let (status, body) = f
+        .set_mode(&f.owner_key, json!({ "enabled": true, "message": "  " }))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        body["details"],
+        "message must be between 1 and 500 characters"
+    );
+    assert_eq!(f.state.maintenance.current(), None);
+}
+
+#[tokio::test]
+async fn test_only_owners_can_switch() {
+    let f = setup();
+    let (status, _) = f.set_mode(&f.view_key, json!({ "enabled": true })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(f.state.maintenance.current(), None);
+}
@@ -0,0 +1,290 @@
+//! Tests for scheduled org member removal: the grace period header, cancelling,
+//! and the final removal by the maintenance task.
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode},
+};
+use serde_json::Value;
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::handlers;
+use paycheck::middleware::ACCESS_ENDING_HEADER;
+
+struct RemovalFixture {
+    state: AppState,
+    org_id: String,
+    project_id: String,
+    owner_key: String,
+    member: OrgMember,
+    member_key: String,
+}
+
+/// An org with an owner and a member who has view access to one project.
+fn setup() -> RemovalFixture {
+    let mut state = create_test_app_state();
+    state.audit_log_enabled = true;
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, owner_key) =
+        create_test_org_member(&mut conn, &org.id, "owner@test.com", OrgMemberRole::Owner);
+    let (_, member, member_key) =
+        create_test_org_member(&mut conn, &org.id, "ci@test.com", OrgMemberRole::Member);
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    create_test_project_member(&conn, &member.id, &project.id, ProjectMemberRole::View);
+
+    drop(conn);
+    RemovalFixture {
+        state,
+        org_id: org.id,
+        project_id: project.id,
+        owner_key,
+        member,
+        member_key,
+    }
+}
+
+fn org_app(state: &AppState) -> Router {
+    handlers::orgs::router(state.clone(), paycheck::config::RateLimitConfig::disabled())
+        .with_state(state.clone())
+}
+
+/// Send a request and return status, the access-ending header, and the JSON body.
+async fn send(
+    f: &RemovalFixture,
+    method: &str,
+    uri: &str,
+    api_key: &str,
+) -> (StatusCode, Option<i64>, Value) {
+    let response = org_app(&f.state)
+        .oneshot(
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("Authorization", format!("Bearer {}", api_key))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = response.status();
+    let access_ending = response
+        .headers()
+        .get(ACCESS_ENDING_HEADER)
+        .map(|v| v.to_str().unwrap().parse().unwrap());
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (
+        status,
+        access_ending,
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    )
+}
+
+fn member_uri(f: &RemovalFixture) -> String {
+    format!("/orgs/{}/members/{}", f.org_id, f.member.user_id)
+}
+
+async fn schedule_removal(f: &RemovalFixture, grace_hours: i64) -> i64 {
+    let (status, _, body) = send(
+        f,
+        "DELETE",
+        &format!("{}?grace_hours={}", member_uri(f), grace_hours),
+        &f.owner_key,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    body["removal_scheduled_at"].as_i64().unwrap()
+}
+
+#[tokio::test]
+async fn test_grace_removal_keeps_access_and_sets_header() {
+    let f = setup();
+    let before = now();
+    let removal_at = schedule_removal(&f, 24).await;
+    assert!(removal_at >= before + 24 * 3600 && removal_at <= now() + 24 * 3600);
+
+    // Org-level and project-level routes both keep working, with the header
+    let (status, access_ending, _) = send(
+        &f,
+        "GET",
+        &format!("/orgs/{}/members", f.org_id),
+        &f.member_key,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(access_ending, Some(removal_at));
+
+    let (status, access_ending, _) = send(
+        &f,
+        "GET",
+        &format!("/orgs/{}/projects/{}", f.org_id, f.project_id),
+        &f.member_key,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(access_ending, Some(removal_at));
+
+    // Other members are unaffected
+    let (_, access_ending, body) = send(&f, "GET", &member_uri(&f), &f.owner_key).await;
+    assert_eq!(access_ending, None);
+    assert_eq!(body["removal_scheduled_at"], removal_at);
+}
+
+#[tokio::test]
+async fn test_cancel_removal_clears_header() {
+    let f = setup();
+    schedule_removal(&f, 24).await;
+    let cancel_uri = format!("{}/cancel-removal", member_uri(&f));
+
+    let (status, _, _) = send(&f, "POST", &cancel_uri, &f.member_key).await;
+    assert_eq!(status, StatusCode::FORBIDDEN, "only owners can cancel");
+
+    let (status, _, body) = send(&f, "POST", &cancel_uri, &f.owner_key).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.get("removal_scheduled_at").is_none());
+
+    let (status, access_ending, _) = send(
+        &f,
+        "GET",
+        &format!("/orgs/{}/members", f.org_id),
+        &f.member_key,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(access_ending, None);
+
+    let (status, _, body) = send(&f, "POST", &cancel_uri, &f.owner_key).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["details"], "No removal is scheduled for this member");
+
+    // Nothing left for the maintenance task
+    let removed =
+        handlers::orgs::process_scheduled_member_removals(&f.state, now() + 48 * 3600).unwrap();
+    assert_eq!(removed, 0);
+}
+
+#[tokio::test]
+async fn test_maintenance_task_removes_member_after_grace_period() {
+    let f = setup();
+    let removal_at = schedule_removal(&f, 2).await;
+
+    let removed =
+        handlers::orgs::process_scheduled_member_removals(&f.state, removal_at - 1).unwrap();
+    assert_eq!(removed, 0, "grace period hasn't ended");
+
+    let removed = handlers::orgs::process_scheduled_member_removals(&f.state, removal_at).unwrap();
+    assert_eq!(removed, 1);
+
+    let (status, _, _) = send(
+        &f,
+        "GET",
+        &format!("/orgs/{}/members", f.org_id),
+        &f.member_key,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN, "access ends after removal");
+
+    let conn = f.state.db.get().unwrap();
+    let deleted = queries::get_deleted_org_member_by_id(&conn, &f.member.id)
+        .unwrap()
+        .expect("member should be soft deleted");
+    assert_eq!(deleted.removal_scheduled_at, None);
+    let project_member = queries::get_project_member(&conn, &f.member.id, &f.project_id).unwrap();
+    assert!(
+        project_member.is_none(),
+        "project membership cascades with the removal"
+    );
+
+    let actor_type: String = f
+        .state
+        .audit
+        .get()
+        .unwrap()
+        .query_row(
+            "SELECT actor_type FROM audit_logs WHERE action = 'delete_org_member' AND resource_id = ?1",
+            [&f.member.id],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(actor_type, "system");
+}
+
+#[tokio::test]
+async fn test_delete_without_grace_removes_immediately() {
+    let f = setup();
+
+    let (status, _, body) = send(&f, "DELETE", &member_uri(&f), &f.owner_key).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.get("removal_scheduled_at").is_none());
+
+    let (status, _, _) = send(
+        &f,
+        "GET",
+        &format!("/orgs/{}/members", f.org_id),
+        &f.member_key,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_immediate_delete_overrides_scheduled_removal() {
+    let f = setup();
+    schedule_removal(&f, 24).await;
+
+    let (status, _, _) = send(
+        &f,
+        "DELETE",
+        &format!("{}?grace_hours=0", member_uri(&f)),
+        &f.owner_key,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _, _) = send(
+        &f,
+        "GET",
+        &format!("/orgs/{}/members", f.org_id),
+        &f.member_key,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_invalid_grace_hours_rejected() {
+    let f = setup();
+
+    for grace_hours in [-1, 721] {
+        let (status, _, _) = send(
+            &f,
+            "DELETE",
+            &format!("{}?grace_hours={}", member_uri(&f), grace_hours),
+            &f.owner_key,
+        )
+        .await;
+        assert_eq!(
+            status,
+            StatusCode::BAD_REQUEST,
+            "grace_hours={}",
+            grace_hours
+        );
+    }
+
+    let (status, _, _) = send(
+        &f,
+        "GET",
+        &format!("/orgs/{}/members", f.org_id),
+        &f.member_key,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "member is untouched");
+}
@@ -0,0 +1,76 @@
+//! Tests for `GET /operators/config`: admins see the running configuration
+//! with its secrets redacted.
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use serde_json::Value;
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::config::{Config, ConfigSource};
+use paycheck::handlers;
+
+async fn get_config(state: &AppState, config: &Config, key: &str) -> (StatusCode, Value) {
+    let response = handlers::app(state.clone(), config)
+        .oneshot(
+            Request::builder()
+                .method("GET")
+                .uri("/operators/config")
+                .header("Authorization", format!("Bearer {}", key))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+fn config() -> Config {
+    let source = ConfigSource::from_parts(
+        None,
+        [
+            ("PAYCHECK_ENV", "dev"),
+            ("BASE_URL", "https://paycheck.example.com"),
+            ("PAYCHECK_RESEND_API_KEY", "re_secret"),
+        ],
+    )
+    .unwrap();
+    Config::from_source(&source).unwrap()
+}
+
+#[tokio::test]
+async fn test_admin_sees_redacted_config() {
+    let state = create_test_app_state();
+    let (_, key) = {
+        let mut conn = state.db.get().unwrap();
+        create_test_operator(&mut conn, "admin@test.com", OperatorRole::Admin)
+    };
+
+    let (status, body) = get_config(&state, &config(), &key).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["base_url"], "https://paycheck.example.com");
+    assert_eq!(body["master_key"], "[redacted]");
+    assert_eq!(body["resend_api_key"], "[redacted]");
+    assert!(!body.to_string().contains("re_secret"));
+}
+
+#[tokio::test]
+async fn test_view_operator_cannot_see_config() {
+    let state = create_test_app_state();
+    let (_, key) = {
+        let mut conn = state.db.get().unwrap();
+        create_test_operator(&mut conn, "view@test.com", OperatorRole::View)
+    };
+
+    let (status, _) = get_config(&state, &config(), &key).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
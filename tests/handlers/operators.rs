@@ -10,13 +10,15 @@ use tower::ServiceExt;
 #[path = "../common/mod.rs"]
 mod common;
 use common::{
-    ONE_MONTH, create_test_operator, create_test_org, create_test_user, queries,
-    setup_lemonsqueezy_config, setup_stripe_config, test_master_key,
+    ONE_MONTH, ONE_YEAR, create_test_license, create_test_operator, create_test_org,
+    create_test_org_member, create_test_product, create_test_project, create_test_user,
+    future_timestamp, queries, setup_lemonsqueezy_config, setup_stripe_config, test_email_column,
+    test_master_key,
 };
 
 use paycheck::db::AppState;
 use paycheck::handlers;
-use paycheck::models::OperatorRole;
+use paycheck::models::{OperatorRole, OrgMemberRole};
 
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
@@ -47,8 +49,11 @@ fn operator_app() -> (Router, AppState) {
         audit: audit_pool,
         base_url: "http://localhost:3000".to_string(),
         audit_log_enabled: true, // Enable for audit log tests
+        audit_failure_mode: paycheck::models::AuditFailureMode::default(),
+        public_audit_retention_days: 0,
         master_key,
         email_hasher: paycheck::crypto::EmailHasher::from_bytes([0xAA; 32]),
+        pii_minimization: false,
         success_page_url: "http://localhost:3000/success".to_string(),
         activation_rate_limiter: std::sync::Arc::new(
             paycheck::rate_limit::ActivationRateLimiter::default(),
@@ -59,6 +64,20 @@ fn operator_app() -> (Router, AppState) {
         )),
         jwks_cache: std::sync::Arc::new(paycheck::jwt::JwksCache::new()),
         trusted_issuers: vec![],
+        org_dbs: std::sync::Arc::new(paycheck::db::OrgDbRegistry::disabled()),
+        project_misses: std::sync::Arc::new(paycheck::db::ProjectMissCache::default()),
+        clock: paycheck::util::Clock::system(),
+        provider_calls: std::sync::Arc::new(paycheck::payments::ProviderCallGovernor::default()),
+        validation_limiter: std::sync::Arc::new(paycheck::rate_limit::ValidationRateLimiter::new()),
+        free_license_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_free_licenses(),
+        ),
+        session_wait_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_session_waits(),
+        ),
+        allow_localhost_urls: false,
+        maintenance: std::sync::Arc::new(paycheck::middleware::MaintenanceMode::default()),
+        backups: None,
     };
 
     let app = handlers::operators::router(state.clone()).with_state(state.clone());
@@ -394,7 +413,7 @@ mod operator_tests {
 
         // Verify operator role is revoked
         let mut conn = state.db.get().unwrap();
-        let user = queries::get_user_by_id(&mut conn, &target_user_id)
+        let user = queries::get_user_by_id(&mut conn, &target_user_id, &test_email_column())
             .unwrap()
             .expect("User should still exist");
         assert!(
@@ -590,7 +609,8 @@ mod organization_tests {
         // Verify org member was created with full user details
         let mut conn = state.db.get().unwrap();
         let org_id = json["id"].as_str().unwrap();
-        let members = queries::list_org_members_with_user(&mut conn, org_id).unwrap();
+        let members =
+            queries::list_org_members_with_user(&mut conn, org_id, &test_email_column()).unwrap();
         assert_eq!(
             members.len(),
             1,
@@ -695,6 +715,139 @@ mod organization_tests {
         );
     }
 
+    /// Two orgs with different amounts of data, some of it soft-deleted.
+    /// Returns (busy org id, empty org id, operator API key).
+    fn setup_orgs_with_children(state: &AppState) -> (String, String, String) {
+        let mut conn = state.db.get().unwrap();
+        let (_, api_key) = create_test_operator(&mut conn, "admin@test.com", OperatorRole::Admin);
+
+        let busy = create_test_org(&conn, "Busy Org");
+        let empty = create_test_org(&conn, "Empty Org");
+
+        create_test_org_member(&mut conn, &busy.id, "owner@busy.com", OrgMemberRole::Owner);
+        let (_, gone, _) =
+            create_test_org_member(&mut conn, &busy.id, "gone@busy.com", OrgMemberRole::Member);
+        queries::soft_delete_org_member(&conn, &gone.id).unwrap();
+        create_test_org_member(
+            &mut conn,
+            &empty.id,
+            "owner@empty.com",
+            OrgMemberRole::Owner,
+        );
+
+        let live = create_test_project(&conn, &busy.id, "Live", &test_master_key());
+        let product = create_test_product(&conn, &live.id, "Pro", "pro");
+        create_test_license(&conn, &live.id, &product.id, None);
+        create_test_license(&conn, &live.id, &product.id, None);
+        let revoked = create_test_license(&conn, &live.id, &product.id, None);
+        queries::soft_delete_license(&conn, &revoked.id).unwrap();
+
+        // A deleted project's licenses don't count either
+        let old = create_test_project(&conn, &busy.id, "Old", &test_master_key());
+        let old_product = create_test_product(&conn, &old.id, "Pro", "pro");
+        create_test_license(&conn, &old.id, &old_product.id, None);
+        queries::soft_delete_project(&conn, &old.id).unwrap();
+
+        conn.execute("UPDATE licenses SET created_at = 1000", [])
+            .unwrap();
+
+        (busy.id, empty.id, api_key)
+    }
+
+    async fn get_json(app: Router, uri: &str, api_key: &str) -> Value {
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri(uri)
+                    .header("Authorization", format!("Bearer {}", api_key))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_list_organizations_includes_stats() {
+        let (app, state) = operator_app();
+        let (busy_id, empty_id, api_key) = setup_orgs_with_children(&state);
+
+        let json = get_json(app, "/operators/organizations", &api_key).await;
+        assert_eq!(json["total"], 2);
+        let items = json["items"].as_array().unwrap();
+        let busy = items.iter().find(|o| o["id"] == busy_id.as_str()).unwrap();
+        let empty = items.iter().find(|o| o["id"] == empty_id.as_str()).unwrap();
+
+        assert_eq!(busy["name"], "Busy Org");
+        assert_eq!(busy["project_count"], 1);
+        assert_eq!(busy["license_count"], 2);
+        assert_eq!(busy["member_count"], 1);
+        assert_eq!(busy["last_activity_at"], 1000);
+
+        assert_eq!(empty["project_count"], 0);
+        assert_eq!(empty["license_count"], 0);
+        assert_eq!(empty["member_count"], 1);
+        assert!(empty["last_activity_at"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_organization_last_activity_includes_audit_log() {
+        let (app, state) = operator_app();
+        let (busy_id, empty_id, api_key) = setup_orgs_with_children(&state);
+        {
+            let conn = state.audit.get().unwrap();
+            for (org_id, timestamp) in [(&busy_id, 500), (&empty_id, 2000), (&empty_id, 1500)] {
+                conn.execute(
+                    "INSERT INTO audit_logs (id, timestamp, actor_type, action, resource_type, resource_id, org_id)
+                     VALUES (?1, ?2, 'system', 'test', 'org', ?3, ?3)",
+                    rusqlite::params![uuid::Uuid::new_v4().to_string(), timestamp, org_id],
+                )
+                .unwrap();
+            }
+        }
+
+        let json = get_json(app.clone(), "/operators/organizations", &api_key).await;
+        let items = json["items"].as_array().unwrap();
+        let busy = items.iter().find(|o| o["id"] == busy_id.as_str()).unwrap();
+        let empty = items.iter().find(|o| o["id"] == empty_id.as_str()).unwrap();
+        // The later of the newest license and the newest audit entry
+        assert_eq!(busy["last_activity_at"], 1000);
+        assert_eq!(empty["last_activity_at"], 2000);
+
+        let json = get_json(
+            app,
+            &format!("/operators/organizations/{}", empty_id),
+            &api_key,
+        )
+        .await;
+        assert_eq!(json["name"], "Empty Org");
+        assert_eq!(json["member_count"], 1);
+        assert_eq!(json["last_activity_at"], 2000);
+    }
+
+    #[tokio::test]
+    async fn test_get_organization_includes_stats() {
+        let (app, state) = operator_app();
+        let (busy_id, _, api_key) = setup_orgs_with_children(&state);
+
+        let json = get_json(
+            app,
+            &format!("/operators/organizations/{}", busy_id),
+            &api_key,
+        )
+        .await;
+        assert_eq!(json["project_count"], 1);
+        assert_eq!(json["license_count"], 2);
+        assert_eq!(json["member_count"], 1);
+        assert!(json["configured_services"].is_object());
+    }
+
     #[tokio::test]
     async fn test_get_nonexistent_organization_returns_not_found() {
         let (app, state) = operator_app();
@@ -775,6 +928,61 @@ mod organization_tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_update_organization_audit_retention() {
+        let (app, state) = operator_app();
+
+        let (org_id, api_key) = {
+            let mut conn = state.db.get().unwrap();
+            let (_, key) = create_test_operator(&mut conn, "admin@test.com", OperatorRole::Admin);
+            let org = create_test_org(&mut conn, "Test Org");
+            (org.id, key)
+        };
+
+        let update = |body: Value| {
+            app.clone().oneshot(
+                Request::builder()
+                    .method("PUT")
+                    .uri(format!("/operators/organizations/{}", org_id))
+                    .header("content-type", "application/json")
+                    .header("Authorization", format!("Bearer {}", api_key))
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+        };
+
+        let response = update(json!({
+            "public_audit_retention_days": 30,
+            "admin_audit_retention_days": 365
+        }))
+        .await
+        .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["public_audit_retention_days"], 30);
+        assert_eq!(json["admin_audit_retention_days"], 365);
+
+        // Null goes back to the defaults; absent fields are left alone
+        let response = update(json!({ "public_audit_retention_days": null }))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        let conn = state.db.get().unwrap();
+        let org = queries::get_organization_by_id(&conn, &org_id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(org.public_audit_retention_days, None);
+        assert_eq!(org.admin_audit_retention_days, Some(365));
+
+        let response = update(json!({ "admin_audit_retention_days": 0 }))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+    }
+
     #[tokio::test]
     async fn test_update_organization_with_stripe_config() {
         let (app, state) = operator_app();
@@ -967,6 +1175,127 @@ mod organization_tests {
             "Deleting nonexistent organization should return 404 Not Found"
         );
     }
+
+    /// An org with one project holding a product and two licenses.
+    /// Returns (admin api key, org id, project id).
+    fn setup_org_with_tree(state: &AppState) -> (String, String, String) {
+        let mut conn = state.db.get().unwrap();
+        let (_, api_key) = create_test_operator(&mut conn, "admin@test.com", OperatorRole::Admin);
+        let org = create_test_org(&conn, "Test Org");
+        let project = create_test_project(&conn, &org.id, "Test Project", &state.master_key);
+        let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+        for _ in 0..2 {
+            create_test_license(
+                &conn,
+                &project.id,
+                &product.id,
+                Some(future_timestamp(ONE_YEAR)),
+            );
+        }
+        (api_key, org.id, project.id)
+    }
+
+    async fn send(app: Router, method: &str, uri: &str, api_key: &str) -> (u16, Value) {
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri(uri)
+                    .header("Authorization", format!("Bearer {}", api_key))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status().as_u16();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_delete_organization_with_projects_requires_cascade() {
+        let (app, state) = operator_app();
+        let (api_key, org_id, project_id) = setup_org_with_tree(&state);
+
+        let (status, json) = send(
+            app,
+            "DELETE",
+            &format!("/operators/organizations/{}", org_id),
+            &api_key,
+        )
+        .await;
+
+        assert_eq!(status, 409);
+        assert!(
+            json["details"].as_str().unwrap().contains(&project_id),
+            "409 should list the remaining project: {}",
+            json
+        );
+        let conn = state.db.get().unwrap();
+        let org = queries::get_organization_by_id(&conn, &org_id).unwrap();
+        assert!(org.is_some(), "blocked delete leaves the org");
+        let project = queries::get_project_by_id(&conn, &project_id).unwrap();
+        assert!(project.is_some(), "blocked delete leaves the project");
+    }
+
+    #[tokio::test]
+    async fn test_delete_organization_cascade_reports_counts() {
+        let (app, state) = operator_app();
+        let (api_key, org_id, project_id) = setup_org_with_tree(&state);
+
+        let (status, json) = send(
+            app,
+            "DELETE",
+            &format!("/operators/organizations/{}?cascade=true", org_id),
+            &api_key,
+        )
+        .await;
+
+        assert_eq!(status, 200);
+        assert_eq!(json["deleted"]["projects"], 1);
+        assert_eq!(json["deleted"]["products"], 1);
+        assert_eq!(json["deleted"]["licenses"], 2);
+
+        let conn = state.db.get().unwrap();
+        let project = queries::get_project_by_id(&conn, &project_id).unwrap();
+        assert!(project.is_none());
+        let deleted = queries::get_deleted_project_by_id(&conn, &project_id).unwrap();
+        assert!(deleted.is_some(), "project is soft-deleted, not purged");
+    }
+
+    #[tokio::test]
+    async fn test_restore_organization_brings_back_cascaded_tree() {
+        let (app, state) = operator_app();
+        let (api_key, org_id, project_id) = setup_org_with_tree(&state);
+
+        let (status, _) = send(
+            app.clone(),
+            "DELETE",
+            &format!("/operators/organizations/{}?cascade=true", org_id),
+            &api_key,
+        )
+        .await;
+        assert_eq!(status, 200);
+
+        let (status, _) = send(
+            app,
+            "POST",
+            &format!("/operators/organizations/{}/restore", org_id),
+            &api_key,
+        )
+        .await;
+        assert_eq!(status, 200);
+
+        let conn = state.db.get().unwrap();
+        let project = queries::get_project_by_id(&conn, &project_id).unwrap();
+        assert!(project.is_some());
+        let (licenses, total) =
+            queries::list_licenses_for_project_paginated(&conn, &project_id, 50, 0).unwrap();
+        assert_eq!(total, 2);
+        assert_eq!(licenses.len(), 2);
+    }
 }
 
 // ============================================================================
@@ -1643,6 +1972,79 @@ mod audit_log_tests {
             "Limit should be capped at maximum of 100"
         );
     }
+
+    #[tokio::test]
+    async fn test_verify_audit_chain_detects_edited_entry() {
+        let (_, state) = operator_app();
+
+        let (view_key, admin_key) = {
+            let mut conn = state.db.get().unwrap();
+            let (_, view_key) =
+                create_test_operator(&mut conn, "view@test.com", OperatorRole::View);
+            let (_, admin_key) =
+                create_test_operator(&mut conn, "admin@test.com", OperatorRole::Admin);
+            (view_key, admin_key)
+        };
+
+        for name in ["Chain Org 1", "Chain Org 2"] {
+            let app = handlers::operators::router(state.clone()).with_state(state.clone());
+            app.oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/operators/organizations")
+                    .header("content-type", "application/json")
+                    .header("Authorization", format!("Bearer {}", admin_key))
+                    .body(Body::from(json!({ "name": name }).to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        }
+
+        let verify = || async {
+            let app = handlers::operators::router(state.clone()).with_state(state.clone());
+            let response = app
+                .oneshot(
+                    Request::builder()
+                        .method("GET")
+                        .uri("/operators/audit-logs/verify-chain")
+                        .header("Authorization", format!("Bearer {}", view_key))
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), axum::http::StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            serde_json::from_slice::<Value>(&body).unwrap()
+        };
+
+        let json = verify().await;
+        assert_eq!(json["valid"], true, "{}", json);
+        let internal = &json["chains"][0];
+        assert_eq!(internal["chain"], "internal");
+        assert!(internal["checked"].as_i64().unwrap() >= 2);
+
+        state
+            .audit
+            .get()
+            .unwrap()
+            .execute(
+                "UPDATE audit_logs SET resource_name = 'Renamed' WHERE action = 'create_org' AND chain_seq = 1",
+                [],
+            )
+            .unwrap();
+
+        let json = verify().await;
+        assert_eq!(json["valid"], false);
+        assert_eq!(json["chains"][0]["first_break"]["chain_seq"], 1);
+        assert_eq!(
+            json["chains"][0]["first_break"]["reason"],
+            "Entry does not match its row_hash"
+        );
+    }
 }
 
 // ============================================================================
@@ -1799,6 +2201,47 @@ mod user_tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_list_users_respects_limit() {
+        let (app, state) = operator_app();
+
+        let api_key: String;
+        {
+            let mut conn = state.db.get().unwrap();
+            let (_, key) = create_test_operator(&mut conn, "admin@test.com", OperatorRole::Admin);
+            api_key = key;
+            for i in 0..3 {
+                create_test_user(&conn, &format!("user{}@example.com", i), "User");
+            }
+        }
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri("/operators/users?limit=2&offset=1")
+                    .header("Authorization", format!("Bearer {}", api_key))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            response.status(),
+            200,
+            "limit/offset should be accepted alongside the user filters"
+        );
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["limit"], 2);
+        assert_eq!(json["offset"], 1);
+        assert_eq!(json["items"].as_array().unwrap().len(), 2);
+    }
+
     #[tokio::test]
     async fn test_list_users_filter_by_email() {
         let (app, state) = operator_app();
@@ -2031,14 +2474,15 @@ mod user_tests {
 
         // Verify user is soft-deleted (not found via normal query)
         let mut conn = state.db.get().unwrap();
-        let user = queries::get_user_by_id(&mut conn, &user_id).unwrap();
+        let user = queries::get_user_by_id(&mut conn, &user_id, &test_email_column()).unwrap();
         assert!(
             user.is_none(),
             "User should not be found via normal query after soft delete"
         );
 
         // But still exists as deleted
-        let deleted = queries::get_deleted_user_by_id(&mut conn, &user_id).unwrap();
+        let deleted =
+            queries::get_deleted_user_by_id(&mut conn, &user_id, &test_email_column()).unwrap();
         assert!(
             deleted.is_some(),
             "User should still exist in deleted state"
@@ -2120,7 +2564,7 @@ mod user_tests {
 
         // Verify user is restored
         let mut conn = state.db.get().unwrap();
-        let user = queries::get_user_by_id(&mut conn, &user_id).unwrap();
+        let user = queries::get_user_by_id(&mut conn, &user_id, &test_email_column()).unwrap();
         assert!(
             user.is_some(),
             "User should be found via normal query after restoration"
@@ -2170,12 +2614,13 @@ mod user_tests {
 
         // Verify user is completely gone
         let mut conn = state.db.get().unwrap();
-        let user = queries::get_user_by_id(&mut conn, &user_id).unwrap();
+        let user = queries::get_user_by_id(&mut conn, &user_id, &test_email_column()).unwrap();
         assert!(
             user.is_none(),
             "User should not be found via normal query after hard delete"
         );
-        let deleted = queries::get_deleted_user_by_id(&mut conn, &user_id).unwrap();
+        let deleted =
+            queries::get_deleted_user_by_id(&mut conn, &user_id, &test_email_column()).unwrap();
         assert!(
             deleted.is_none(),
             "User should not be found even in deleted state after hard delete"
@@ -2502,3 +2947,65 @@ mod operator_api_key_tests {
         );
     }
 }
+
+// ============================================================================
+// JWKS CACHE TESTS
+// ============================================================================
+
+mod jwks_cache_tests {
+    use super::*;
+
+    async fn refresh(app: Router, api_key: &str) -> axum::http::Response<Body> {
+        app.oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/operators/jwks/refresh")
+                .header("Authorization", format!("Bearer {}", api_key))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_refresh_jwks_clears_cache_and_audits() {
+        let (app, state) = operator_app();
+        let api_key = {
+            let mut conn = state.db.get().unwrap();
+            create_test_operator(&mut conn, "admin@test.com", OperatorRole::Admin).1
+        };
+
+        let response = refresh(app, &api_key).await;
+        assert_eq!(response.status(), 200);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["cleared"], 0);
+
+        let audit_count: i64 = state
+            .audit
+            .get()
+            .unwrap()
+            .query_row(
+                "SELECT COUNT(*) FROM audit_logs WHERE action = 'refresh_jwks_cache'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(audit_count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_jwks_requires_admin() {
+        let (app, state) = operator_app();
+        let api_key = {
+            let mut conn = state.db.get().unwrap();
+            create_test_operator(&mut conn, "view@test.com", OperatorRole::View).1
+        };
+
+        let response = refresh(app, &api_key).await;
+        assert_eq!(response.status(), 403);
+    }
+}
@@ -0,0 +1,280 @@
+//! Tests for `expected_version` on product, project, and org updates: the
+//! second of two writers working from the same version gets 409 with the
+//! differences, and writers that leave it out still overwrite.
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::handlers;
+
+struct Fixture {
+    state: AppState,
+    org_id: String,
+    project_id: String,
+    product_id: String,
+    owner_key: String,
+    operator_key: String,
+}
+
+fn setup() -> Fixture {
+    let state = create_test_app_state();
+    let master_key = test_master_key();
+    let mut conn = state.db.get().unwrap();
+
+    let (_, operator_key) =
+        create_test_operator(&mut conn, "operator@test.com", OperatorRole::Admin);
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, owner_key) =
+        create_test_org_member(&mut conn, &org.id, "owner@test.com", OrgMemberRole::Owner);
+    let project = create_test_project(&conn, &org.id, "Test Project", &master_key);
+    let product = create_test_product(&conn, &project.id, "Pro", "pro");
+    setup_both_payment_configs(&conn, &org.id, &master_key);
+    drop(conn);
+
+    Fixture {
+        state,
+        org_id: org.id,
+        project_id: project.id,
+        product_id: product.id,
+        owner_key,
+        operator_key,
+    }
+}
+
+impl Fixture {
+    async fn send(&self, method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let (app, key) = if uri.starts_with("/operators") {
+            (
+                handlers::operators::router(self.state.clone()).with_state(self.state.clone()),
+                &self.operator_key,
+            )
+        } else {
+            (
+                handlers::orgs::router(
+                    self.state.clone(),
+                    paycheck::config::RateLimitConfig::disabled(),
+                )
+                .with_state(self.state.clone()),
+                &self.owner_key,
+            )
+        };
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("Authorization", format!("Bearer {}", key))
+            .header("content-type", "application/json")
+            .body(body.map_or_else(Body::empty, |b| Body::from(b.to_string())))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    fn product_uri(&self) -> String {
+        format!(
+            "/orgs/{}/projects/{}/products/{}",
+            self.org_id, self.project_id, self.product_id
+        )
+    }
+
+    fn project_uri(&self) -> String {
+        format!("/orgs/{}/projects/{}", self.org_id, self.project_id)
+    }
+
+    fn org_uri(&self) -> String {
+        format!("/operators/organizations/{}", self.org_id)
+    }
+
+    /// The `version` a GET of `uri` reports, as both writers would load it
+    async fn version(&self, uri: &str) -> i64 {
+        let (status, body) = self.send("GET", uri, None).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        body["version"].as_i64().unwrap()
+    }
+}
+
+#[tokio::test]
+async fn test_second_product_writer_gets_conflict() {
+    let fixture = setup();
+    let loaded = fixture.version(&fixture.product_uri()).await;
+
+    let (status, first) = fixture
+        .send(
+            "PUT",
+            &fixture.product_uri(),
+            Some(json!({ "price_cents": 4900, "expected_version": loaded })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", first);
+    assert_eq!(first["version"], loaded + 1);
+
+    let (status, second) = fixture
+        .send(
+            "PUT",
+            &fixture.product_uri(),
+            Some(json!({
+                "name": "Pro",
+                "price_cents": 2900,
+                "expected_version": loaded
+            })),
+        )
+        .await;
+
+    assert_eq!(status, StatusCode::CONFLICT, "{}", second);
+    assert_eq!(second["code"], "version_conflict");
+    assert_eq!(second["current_version"], loaded + 1);
+    assert_eq!(
+        second["conflicts"],
+        json!([{ "field": "price_cents", "current": 4900, "requested": 2900 }]),
+        "only fields that now differ are listed"
+    );
+    let conn = fixture.state.db.get().unwrap();
+    let product = queries::get_product_by_id(&conn, &fixture.product_id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(product.price_cents, Some(4900), "the first write stands");
+}
+
+#[tokio::test]
+async fn test_product_update_without_expected_version_overwrites() {
+    let fixture = setup();
+    let loaded = fixture.version(&fixture.product_uri()).await;
+    fixture
+        .send(
+            "PUT",
+            &fixture.product_uri(),
+            Some(json!({ "price_cents": 4900, "expected_version": loaded })),
+        )
+        .await;
+
+    let (status, body) = fixture
+        .send(
+            "PUT",
+            &fixture.product_uri(),
+            Some(json!({ "price_cents": 2900 })),
+        )
+        .await;
+
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["price_cents"], 2900);
+    assert_eq!(body["version"], loaded + 2);
+}
+
+#[tokio::test]
+async fn test_second_project_writer_gets_conflict() {
+    let fixture = setup();
+    let loaded = fixture.version(&fixture.project_uri()).await;
+
+    let (status, first) = fixture
+        .send(
+            "PUT",
+            &fixture.project_uri(),
+            Some(json!({ "checkout_message": "Thanks!", "expected_version": loaded })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", first);
+
+    let (status, second) = fixture
+        .send(
+            "PUT",
+            &fixture.project_uri(),
+            Some(json!({ "checkout_message": null, "expected_version": loaded })),
+        )
+        .await;
+
+    assert_eq!(status, StatusCode::CONFLICT, "{}", second);
+    assert_eq!(second["current_version"], loaded + 1);
+    assert_eq!(
+        second["conflicts"],
+        json!([{ "field": "checkout_message", "current": "Thanks!", "requested": null }])
+    );
+
+    let (status, third) = fixture
+        .send(
+            "PUT",
+            &fixture.project_uri(),
+            Some(json!({ "checkout_message": null, "expected_version": loaded + 1 })),
+        )
+        .await;
+    assert_eq!(
+        status,
+        StatusCode::OK,
+        "retrying with the current version goes through: {}",
+        third
+    );
+    assert_eq!(third["checkout_message"], Value::Null);
+}
+
+#[tokio::test]
+async fn test_stale_org_update_leaves_payment_config_alone() {
+    let fixture = setup();
+    let payment_uri = format!("/orgs/{}/payment-provider", fixture.org_id);
+    let loaded = fixture.version(&payment_uri).await;
+
+    let (status, first) = fixture
+        .send(
+            "PUT",
+            &fixture.org_uri(),
+            Some(json!({ "payment_provider": "stripe", "expected_version": loaded })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", first);
+    assert_eq!(fixture.version(&payment_uri).await, first["version"]);
+
+    let (status, second) = fixture
+        .send(
+            "PUT",
+            &fixture.org_uri(),
+            Some(json!({
+                "payment_provider": "lemonsqueezy",
+                "stripe_config": null,
+                "expected_version": loaded
+            })),
+        )
+        .await;
+
+    assert_eq!(status, StatusCode::CONFLICT, "{}", second);
+    assert_eq!(
+        second["conflicts"],
+        json!([{ "field": "payment_provider", "current": "stripe", "requested": "lemonsqueezy" }]),
+        "secrets are never echoed back"
+    );
+    let (_, config) = fixture.send("GET", &payment_uri, None).await;
+    assert_eq!(config["payment_provider"], "stripe");
+    assert!(
+        !config["stripe_config"].is_null(),
+        "the stale update must not clear the Stripe config"
+    );
+}
+
+#[tokio::test]
+async fn test_org_update_without_expected_version_overwrites() {
+    let fixture = setup();
+    let loaded = fixture.version(&fixture.org_uri()).await;
+    fixture
+        .send(
+            "PUT",
+            &fixture.org_uri(),
+            Some(json!({ "name": "First", "expected_version": loaded })),
+        )
+        .await;
+
+    let (status, body) = fixture
+        .send("PUT", &fixture.org_uri(), Some(json!({ "name": "Second" })))
+        .await;
+
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["name"], "Second");
+    assert_eq!(body["version"], loaded + 2);
+}
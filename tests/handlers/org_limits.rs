@@ -0,0 +1,383 @@
+//! Tests for operator-set org limits: creates warn once usage reaches a soft
+//! value, are refused past a hard value, and orgs without limits are
+//! unlimited.
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode},
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::handlers;
+use paycheck::models::{OperatorRole, OrgLimitName, SetOrgLimit};
+use paycheck::quota::QUOTA_WARNING_HEADER;
+
+struct LimitsFixture {
+    state: AppState,
+    org_id: String,
+    project: Project,
+    owner_key: String,
+    operator_key: String,
+}
+
+fn setup() -> LimitsFixture {
+    let mut state = create_test_app_state();
+    state.audit_log_enabled = true;
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, owner_key) =
+        create_test_org_member(&mut conn, &org.id, "owner@test.com", OrgMemberRole::Owner);
+    let (_, operator_key) =
+        create_test_operator(&mut conn, "operator@test.com", OperatorRole::Admin);
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+
+    drop(conn);
+    LimitsFixture {
+        state,
+        org_id: org.id,
+        project,
+        owner_key,
+        operator_key,
+    }
+}
+
+impl LimitsFixture {
+    fn set_limit(
+        &self,
+        limit_name: OrgLimitName,
+        soft_value: Option<i64>,
+        hard_value: Option<i64>,
+    ) {
+        let conn = self.state.db.get().unwrap();
+        queries::set_org_limit(
+            &conn,
+            &self.org_id,
+            limit_name,
+            &SetOrgLimit {
+                soft_value,
+                hard_value,
+            },
+        )
+        .unwrap();
+    }
+
+    async fn send(
+        &self,
+        app: Router,
+        method: &str,
+        uri: String,
+        api_key: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Option<String>, Value) {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("Authorization", format!("Bearer {}", api_key));
+        let body = match body {
+            Some(body) => {
+                request = request.header("content-type", "application/json");
+                Body::from(body.to_string())
+            }
+            None => Body::empty(),
+        };
+        let response = app.oneshot(request.body(body).unwrap()).await.unwrap();
+        let status = response.status();
+        let warning = response
+            .headers()
+            .get(QUOTA_WARNING_HEADER)
+            .map(|value| value.to_str().unwrap().to_string());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            warning,
+            serde_json::from_slice(&body).unwrap_or(Value::Null),
+        )
+    }
+
+    async fn org_request(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Option<String>, Value) {
+        let app = handlers::orgs::router(
+            self.state.clone(),
+            paycheck::config::RateLimitConfig::disabled(),
+        )
+        .with_state(self.state.clone());
+        let uri = format!("/orgs/{}{}", self.org_id, path);
+        self.send(app, method, uri, &self.owner_key, body).await
+    }
+
+    async fn operator_request(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let app = handlers::operators::router(self.state.clone()).with_state(self.state.clone());
+        let uri = format!("/operators/organizations/{}{}", self.org_id, path);
+        let (status, _, body) = self.send(app, method, uri, &self.operator_key, body).await;
+        (status, body)
+    }
+
+    async fn create_project(&self, name: &str) -> (StatusCode, Option<String>, Value) {
+        self.org_request("POST", "/projects", Some(json!({ "name": name })))
+            .await
+    }
+
+    async fn create_product(&self, project_id: &str, tier: &str) -> (StatusCode, Option<String>) {
+        let path = format!("/projects/{}/products", project_id);
+        let body = json!({ "name": tier, "tier": tier });
+        let (status, warning, _) = self.org_request("POST", &path, Some(body)).await;
+        (status, warning)
+    }
+
+    async fn limits(&self) -> Value {
+        let (status, warning, body) = self.org_request("GET", "/limits", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(warning, None);
+        body
+    }
+
+    fn soft_limit_audit_count(&self) -> i64 {
+        let conn = self.state.audit.get().unwrap();
+        conn.query_row(
+            "SELECT COUNT(*) FROM audit_logs WHERE action = 'reach_org_soft_limit' AND resource_id = ?1",
+            [&self.org_id],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+}
+
+fn limit<'a>(limits: &'a Value, name: &str) -> &'a Value {
+    limits["limits"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|l| l["limit_name"] == name)
+        .unwrap_or_else(|| panic!("{} missing from {}", name, limits))
+}
+
+#[tokio::test]
+async fn test_org_without_limits_is_unlimited() {
+    let f = setup();
+
+    let limits = f.limits().await;
+    assert_eq!(limits["limits"].as_array().unwrap().len(), 3);
+    for name in ["projects", "products_per_project", "licenses_per_month"] {
+        let entry = limit(&limits, name);
+        assert_eq!(entry["soft_value"], Value::Null, "{}", name);
+        assert_eq!(entry["hard_value"], Value::Null, "{}", name);
+    }
+    assert_eq!(limit(&limits, "projects")["used"], 1);
+    assert!(limit(&limits, "licenses_per_month")["period_start"].is_i64());
+
+    for i in 0..5 {
+        let (status, warning, _) = f.create_project(&format!("Project {}", i)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(warning, None);
+    }
+    assert_eq!(f.soft_limit_audit_count(), 0);
+}
+
+#[tokio::test]
+async fn test_project_soft_limit_warns_and_audits_once() {
+    let f = setup();
+    f.set_limit(OrgLimitName::Projects, Some(2), Some(3));
+
+    let (status, warning, _) = f.create_project("Second").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(warning.as_deref(), Some("projects; used=2; soft=2; hard=3"));
+    assert_eq!(f.soft_limit_audit_count(), 1);
+
+    let (status, warning, _) = f.create_project("Third").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(warning.as_deref(), Some("projects; used=3; soft=2; hard=3"));
+    assert_eq!(
+        f.soft_limit_audit_count(),
+        1,
+        "only the create that reaches the soft value is audited"
+    );
+
+    let limits = f.limits().await;
+    let projects = limit(&limits, "projects");
+    assert_eq!(projects["used"], 3);
+    assert_eq!(projects["soft_value"], 2);
+    assert_eq!(projects["hard_value"], 3);
+}
+
+#[tokio::test]
+async fn test_project_hard_limit_rejects_create() {
+    let f = setup();
+    f.set_limit(OrgLimitName::Projects, None, Some(1));
+
+    let (status, warning, body) = f.create_project("Second").await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(warning, None);
+    assert_eq!(body["code"], "quota_exceeded");
+
+    let conn = f.state.db.get().unwrap();
+    assert_eq!(
+        queries::list_projects_for_org(&conn, &f.org_id)
+            .unwrap()
+            .len(),
+        1,
+        "no project should be created past the hard limit"
+    );
+}
+
+#[tokio::test]
+async fn test_products_per_project_limit_is_per_project() {
+    let f = setup();
+    f.set_limit(OrgLimitName::ProductsPerProject, None, Some(2));
+
+    assert_eq!(
+        f.create_product(&f.project.id, "free").await.0,
+        StatusCode::OK
+    );
+    assert_eq!(
+        f.create_product(&f.project.id, "pro").await.0,
+        StatusCode::OK
+    );
+    assert_eq!(
+        f.create_product(&f.project.id, "team").await.0,
+        StatusCode::FORBIDDEN
+    );
+
+    let (_, _, other) = f.create_project("Other").await;
+    let other_id = other["id"].as_str().unwrap();
+    assert_eq!(
+        f.create_product(other_id, "team").await.0,
+        StatusCode::OK,
+        "another project has its own allowance"
+    );
+
+    let limits = f.limits().await;
+    let products = limit(&limits, "products_per_project");
+    assert_eq!(products["used"], 2);
+    assert_eq!(products["project_id"], f.project.id.as_str());
+}
+
+#[tokio::test]
+async fn test_bulk_license_create_counts_every_license() {
+    let f = setup();
+    let product = {
+        let conn = f.state.db.get().unwrap();
+        create_test_product(&conn, &f.project.id, "Pro", "pro")
+    };
+    f.set_limit(OrgLimitName::LicensesPerMonth, Some(4), Some(5));
+    let path = format!("/projects/{}/licenses", f.project.id);
+
+    let (status, warning, _) = f
+        .org_request(
+            "POST",
+            &path,
+            Some(json!({ "product_id": product.id, "count": 3 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(warning, None);
+
+    let (status, warning, body) = f
+        .org_request(
+            "POST",
+            &path,
+            Some(json!({ "product_id": product.id, "count": 3 })),
+        )
+        .await;
+    assert_eq!(
+        status,
+        StatusCode::FORBIDDEN,
+        "3 + 3 is past the hard value"
+    );
+    assert_eq!(warning, None);
+    assert_eq!(body["code"], "quota_exceeded");
+
+    let (status, warning, _) = f
+        .org_request(
+            "POST",
+            &path,
+            Some(json!({ "product_id": product.id, "count": 2 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        warning.as_deref(),
+        Some("licenses_per_month; used=5; soft=4; hard=5")
+    );
+    assert_eq!(limit(&f.limits().await, "licenses_per_month")["used"], 5);
+}
+
+#[tokio::test]
+async fn test_operator_sets_and_removes_limit() {
+    let f = setup();
+
+    let (status, body) = f
+        .operator_request(
+            "PUT",
+            "/limits/projects",
+            Some(json!({ "soft_value": 8, "hard_value": 10 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let projects = limit(&body, "projects");
+    assert_eq!(projects["soft_value"], 8);
+    assert_eq!(projects["hard_value"], 10);
+
+    let (status, body) = f.operator_request("GET", "/limits", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(limit(&body, "projects")["hard_value"], 10);
+
+    let (status, body) = f
+        .operator_request(
+            "PUT",
+            "/limits/projects",
+            Some(json!({ "soft_value": null, "hard_value": null })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(limit(&body, "projects")["hard_value"], Value::Null);
+
+    let conn = f.state.db.get().unwrap();
+    assert!(
+        queries::get_org_limits(&conn, &f.org_id)
+            .unwrap()
+            .is_empty(),
+        "clearing both values removes the limit"
+    );
+}
+
+#[tokio::test]
+async fn test_operator_rejects_invalid_limits() {
+    let f = setup();
+
+    let (status, _) = f
+        .operator_request(
+            "PUT",
+            "/limits/projects",
+            Some(json!({ "soft_value": 10, "hard_value": 5 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "soft above hard");
+
+    let (status, _) = f
+        .operator_request("PUT", "/limits/projects", Some(json!({ "hard_value": -1 })))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "negative value");
+
+    let (status, _) = f
+        .operator_request("PUT", "/limits/seats", Some(json!({ "hard_value": 1 })))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "unknown limit name");
+}
@@ -44,8 +44,11 @@ fn org_app() -> (Router, AppState) {
         audit: audit_pool,
         base_url: "http://localhost:3000".to_string(),
         audit_log_enabled: false,
+        audit_failure_mode: paycheck::models::AuditFailureMode::default(),
+        public_audit_retention_days: 0,
         master_key,
         email_hasher: paycheck::crypto::EmailHasher::from_bytes([0xAA; 32]),
+        pii_minimization: false,
         success_page_url: "http://localhost:3000/success".to_string(),
         activation_rate_limiter: std::sync::Arc::new(
             paycheck::rate_limit::ActivationRateLimiter::default(),
@@ -56,6 +59,20 @@ fn org_app() -> (Router, AppState) {
         )),
         jwks_cache: std::sync::Arc::new(paycheck::jwt::JwksCache::new()),
         trusted_issuers: vec![],
+        org_dbs: std::sync::Arc::new(paycheck::db::OrgDbRegistry::disabled()),
+        project_misses: std::sync::Arc::new(paycheck::db::ProjectMissCache::default()),
+        clock: paycheck::util::Clock::system(),
+        provider_calls: std::sync::Arc::new(paycheck::payments::ProviderCallGovernor::default()),
+        validation_limiter: std::sync::Arc::new(paycheck::rate_limit::ValidationRateLimiter::new()),
+        free_license_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_free_licenses(),
+        ),
+        session_wait_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_session_waits(),
+        ),
+        allow_localhost_urls: false,
+        maintenance: std::sync::Arc::new(paycheck::middleware::MaintenanceMode::default()),
+        backups: None,
     };
 
     let app = handlers::orgs::router(state.clone(), paycheck::config::RateLimitConfig::disabled())
@@ -533,6 +550,329 @@ mod product_tests {
             "accessing product from wrong project should return 404"
         );
     }
+
+    #[tokio::test]
+    async fn test_product_sale_window_must_end_after_start() {
+        let (app, state) = org_app();
+        let master_key = test_master_key();
+
+        let (org_id, project_id, product_id, api_key) = {
+            let mut conn = state.db.get().unwrap();
+            let org = create_test_org(&mut conn, "Test Org");
+            let (_, _, key) =
+                create_test_org_member(&mut conn, &org.id, "admin@test.com", OrgMemberRole::Owner);
+            let project = create_test_project(&mut conn, &org.id, "Test Project", &master_key);
+            let product = create_test_product(&mut conn, &project.id, "Pro Plan", "pro");
+            (org.id, project.id, product.id, key)
+        };
+
+        let send = |method: &str, uri: String, body: Value| {
+            app.clone().oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri(uri)
+                    .header("content-type", "application/json")
+                    .header("Authorization", format!("Bearer {}", api_key))
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+        };
+        let products_uri = format!("/orgs/{}/projects/{}/products", org_id, project_id);
+        let product_uri = format!("{}/{}", products_uri, product_id);
+
+        let response = send(
+            "POST",
+            products_uri.clone(),
+            json!({
+                "name": "Launch",
+                "tier": "pro",
+                "available_from": 2000,
+                "available_until": 2000
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            response.status(),
+            axum::http::StatusCode::BAD_REQUEST,
+            "empty window should be rejected on create"
+        );
+
+        let response = send(
+            "PUT",
+            product_uri.clone(),
+            json!({ "available_from": 1000, "available_until": 2000 }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["available_from"], 1000);
+        assert_eq!(json["available_until"], 2000);
+
+        // Only one bound sent: checked against the stored other bound
+        let response = send("PUT", product_uri.clone(), json!({ "available_until": 500 }))
+            .await
+            .unwrap();
+        assert_eq!(
+            response.status(),
+            axum::http::StatusCode::BAD_REQUEST,
+            "until before the stored from should be rejected"
+        );
+
+        let response = send(
+            "PUT",
+            product_uri,
+            json!({ "available_from": null, "available_until": 500 }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            response.status(),
+            axum::http::StatusCode::OK,
+            "clearing from makes any until valid"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_product_checkout_fields_validated_on_save() {
+        let (app, state) = org_app();
+        let master_key = test_master_key();
+
+        let (org_id, project_id, product_id, api_key) = {
+            let mut conn = state.db.get().unwrap();
+            let org = create_test_org(&mut conn, "Test Org");
+            let (_, _, key) =
+                create_test_org_member(&mut conn, &org.id, "admin@test.com", OrgMemberRole::Owner);
+            let project = create_test_project(&mut conn, &org.id, "Test Project", &master_key);
+            let product = create_test_product(&mut conn, &project.id, "Pro Plan", "pro");
+            (org.id, project.id, product.id, key)
+        };
+
+        let send = |method: &str, uri: String, body: Value| {
+            app.clone().oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri(uri)
+                    .header("content-type", "application/json")
+                    .header("Authorization", format!("Bearer {}", api_key))
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+        };
+        let products_uri = format!("/orgs/{}/projects/{}/products", org_id, project_id);
+        let product_uri = format!("{}/{}", products_uri, product_id);
+        let field = |key: &str| json!({ "key": key, "label": "Company", "type": "text" });
+
+        let response = send(
+            "POST",
+            products_uri,
+            json!({
+                "name": "Business",
+                "tier": "business",
+                "checkout_fields": [field("company"), field("company")]
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            response.status(),
+            axum::http::StatusCode::BAD_REQUEST,
+            "duplicate keys should be rejected on create"
+        );
+
+        let too_many: Vec<Value> = (0..11).map(|i| field(&format!("f{}", i))).collect();
+        for (fields, case) in [
+            (json!([field("Company Name")]), "key with spaces"),
+            (
+                json!([{ "key": "company", "label": " ", "type": "text" }]),
+                "blank label",
+            ),
+            (
+                json!([{ "key": "company", "label": "Company", "type": "date" }]),
+                "unknown type",
+            ),
+            (json!(too_many), "11 fields"),
+        ] {
+            let response = send(
+                "PUT",
+                product_uri.clone(),
+                json!({ "checkout_fields": fields }),
+            )
+            .await
+            .unwrap();
+            assert!(
+                response.status().is_client_error(),
+                "{} should be rejected, got {}",
+                case,
+                response.status()
+            );
+        }
+
+        let response = send(
+            "PUT",
+            product_uri,
+            json!({
+                "checkout_fields": [
+                    { "key": "company", "label": "Company", "type": "text", "required": true },
+                    { "key": "billing_email", "label": "Billing email", "type": "email" }
+                ]
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["checkout_fields"][0]["required"], true);
+        assert_eq!(json["checkout_fields"][1]["type"], "email");
+        assert_eq!(json["checkout_fields"][1]["required"], false);
+    }
+
+    #[tokio::test]
+    async fn test_updates_renewal_product_validated_on_save() {
+        let (app, state) = org_app();
+        let master_key = test_master_key();
+
+        let (org_id, project_id, base_id, other_project_product_id, api_key) = {
+            let mut conn = state.db.get().unwrap();
+            let org = create_test_org(&mut conn, "Test Org");
+            let (_, _, key) =
+                create_test_org_member(&mut conn, &org.id, "admin@test.com", OrgMemberRole::Owner);
+            let project = create_test_project(&mut conn, &org.id, "Test Project", &master_key);
+            let other = create_test_project(&mut conn, &org.id, "Other Project", &master_key);
+            let base = create_test_product(&mut conn, &project.id, "Pro Plan", "pro");
+            let elsewhere = create_test_product(&mut conn, &other.id, "Pro Plan", "pro");
+            (org.id, project.id, base.id, elsewhere.id, key)
+        };
+
+        let send = |method: &str, uri: String, body: Value| {
+            app.clone().oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri(uri)
+                    .header("content-type", "application/json")
+                    .header("Authorization", format!("Bearer {}", api_key))
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+        };
+        let products_uri = format!("/orgs/{}/projects/{}/products", org_id, project_id);
+        let renewal = |extends: &str| {
+            json!({
+                "name": "Pro Updates",
+                "tier": "pro",
+                "updates_exp_days": 365,
+                "price_cents": 1999,
+                "extends_updates_for_product_id": extends
+            })
+        };
+
+        for (body, case) in [
+            (
+                renewal(&other_project_product_id),
+                "base in another project",
+            ),
+            (renewal("missing"), "unknown base"),
+            (
+                json!({ "name": "Pro Updates", "tier": "pro", "extends_updates_for_product_id": base_id }),
+                "no updates_exp_days",
+            ),
+        ] {
+            let response = send("POST", products_uri.clone(), body).await.unwrap();
+            assert_eq!(
+                response.status(),
+                axum::http::StatusCode::BAD_REQUEST,
+                "{} should be rejected",
+                case
+            );
+        }
+
+        let response = send("POST", products_uri.clone(), renewal(&base_id))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["extends_updates_for_product_id"], base_id.as_str());
+        assert_eq!(json["renewal_fallback"], "create_license");
+        let renewal_id = json["id"].as_str().unwrap().to_string();
+
+        // A renewal can't be renewed, and a renewed product can't become one
+        let response = send("POST", products_uri.clone(), renewal(&renewal_id))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+        let response = send(
+            "PUT",
+            format!("{}/{}", products_uri, base_id),
+            json!({ "extends_updates_for_product_id": renewal_id }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+
+        let response = send(
+            "PUT",
+            format!("{}/{}", products_uri, renewal_id),
+            json!({ "renewal_fallback": "manual_review" }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["renewal_fallback"], "manual_review");
+    }
+
+    #[tokio::test]
+    async fn test_admin_license_creation_ignores_sale_window() {
+        let (app, state) = org_app();
+        let master_key = test_master_key();
+
+        let (org_id, project_id, product_id, api_key) = {
+            let mut conn = state.db.get().unwrap();
+            let org = create_test_org(&mut conn, "Test Org");
+            let (_, _, key) =
+                create_test_org_member(&mut conn, &org.id, "admin@test.com", OrgMemberRole::Owner);
+            let project = create_test_project(&mut conn, &org.id, "Test Project", &master_key);
+            let product = create_test_product(&mut conn, &project.id, "Pro Plan", "pro");
+            conn.execute(
+                "UPDATE products SET available_from = 1000, available_until = 2000 WHERE id = ?1",
+                [&product.id],
+            )
+            .unwrap();
+            (org.id, project.id, product.id, key)
+        };
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(format!("/orgs/{}/projects/{}/licenses", org_id, project_id))
+                    .header("content-type", "application/json")
+                    .header("Authorization", format!("Bearer {}", api_key))
+                    .body(Body::from(json!({ "product_id": product_id }).to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            response.status(),
+            axum::http::StatusCode::OK,
+            "admins can issue licenses for products no longer on sale"
+        );
+    }
 }
 
 // ============================================================================
@@ -705,7 +1045,7 @@ mod license_tests {
 
         let body = json!({
             "product_id": product_id,
-            "count": 101  // Exceeds limit of 100
+            "count": 10_001  // Exceeds limit of 10,000
         });
 
         let response = app
@@ -724,7 +1064,7 @@ mod license_tests {
         assert_eq!(
             response.status(),
             axum::http::StatusCode::BAD_REQUEST,
-            "exceeding bulk limit of 100 should return 400"
+            "exceeding bulk limit of 10,000 should return 400"
         );
     }
 
@@ -840,6 +1180,14 @@ mod license_tests {
                 device_limit: Some(3),
         device_inactive_days: None,
                 features: vec![],
+                entitlements: Default::default(),
+                seat_count: None,
+                available_from: None,
+                available_until: None,
+                checkout_fields: vec![],
+                extends_updates_for_product_id: None,
+                renewal_fallback: Default::default(),
+                revoke_on_partial_refund: false,
             };
             let product = queries::create_product(&mut conn, &project.id, &input).unwrap();
 
@@ -968,6 +1316,72 @@ mod license_tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_get_license_shows_pause_state() {
+        let (app, state) = org_app();
+        let master_key = test_master_key();
+
+        let org_id: String;
+        let project_id: String;
+        let license_id: String;
+        let api_key: String;
+
+        {
+            let mut conn = state.db.get().unwrap();
+            let org = create_test_org(&conn, "Test Org");
+            let (_, _, key) =
+                create_test_org_member(&mut conn, &org.id, "admin@test.com", OrgMemberRole::Owner);
+            let project = create_test_project(&conn, &org.id, "Test Project", &master_key);
+            let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+            let license = create_test_license(
+                &conn,
+                &project.id,
+                &product.id,
+                Some(future_timestamp(ONE_YEAR)),
+            );
+
+            // One earlier day-long pause, plus a pause in progress for 3 days
+            conn.execute(
+                "UPDATE licenses SET paused_seconds = 86400, paused_at = ?1 WHERE id = ?2",
+                rusqlite::params![now() - 3 * 86400, &license.id],
+            )
+            .unwrap();
+
+            org_id = org.id;
+            project_id = project.id;
+            license_id = license.id;
+            api_key = key;
+        }
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri(format!(
+                        "/orgs/{}/projects/{}/licenses/{}",
+                        org_id, project_id, license_id
+                    ))
+                    .header("Authorization", format!("Bearer {}", api_key))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(json["paused"], true, "license should report paused state");
+        assert_eq!(
+            json["paused_days"], 4,
+            "paused days should include the pause in progress"
+        );
+    }
+
     #[tokio::test]
     async fn test_revoke_license_marks_as_revoked() {
         let (app, state) = org_app();
@@ -1065,7 +1479,7 @@ mod license_tests {
             );
 
             // Pre-revoke the license
-            queries::revoke_license(&mut conn, &license.id).unwrap();
+            queries::revoke_license(&mut conn, &license.id, &RevokeLicense::default(), None).unwrap();
 
             org_id = org.id;
             project_id = project.id;
@@ -1659,7 +2073,6 @@ mod project_tests {
 
         let body = json!({
             "name": "New Project",
-            "domain": "new.example.com",
             "license_key_prefix": "NEW"
         });
 
@@ -2878,7 +3291,8 @@ mod project_member_tests {
 
         // Verify member list is empty
         let mut conn = state.db.get().unwrap();
-        let members = queries::list_project_members(&mut conn, &project_id).unwrap();
+        let members =
+            queries::list_project_members(&mut conn, &project_id, &test_email_column()).unwrap();
         assert_eq!(
             members.len(),
             0,
@@ -0,0 +1,365 @@
+//! Tests for prepaid codes: generating a batch, the one-shot CSV download,
+//! single-use redemption through /redeem/prepaid, and batch revocation.
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode, header},
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::handlers;
+
+struct PrepaidFixture {
+    state: AppState,
+    org_id: String,
+    project: Project,
+    product: Product,
+    api_key: String,
+}
+
+fn setup() -> PrepaidFixture {
+    let mut state = create_test_app_state();
+    state.audit_log_enabled = true;
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, api_key) =
+        create_test_org_member(&mut conn, &org.id, "owner@test.com", OrgMemberRole::Owner);
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    let product = create_test_product(&conn, &project.id, "Boxed Edition", "pro");
+
+    drop(conn);
+    PrepaidFixture {
+        state,
+        org_id: org.id,
+        project,
+        product,
+        api_key,
+    }
+}
+
+impl PrepaidFixture {
+    fn app(&self) -> Router {
+        public_app(self.state.clone()).merge(
+            handlers::orgs::router(
+                self.state.clone(),
+                paycheck::config::RateLimitConfig::disabled(),
+            )
+            .with_state(self.state.clone()),
+        )
+    }
+
+    fn batches_uri(&self) -> String {
+        format!(
+            "/orgs/{}/projects/{}/products/{}/prepaid-codes",
+            self.org_id, self.project.id, self.product.id
+        )
+    }
+
+    async fn send(&self, method: &str, uri: String, body: Option<Value>) -> (StatusCode, String) {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("Authorization", format!("Bearer {}", self.api_key));
+        let body = match body {
+            Some(body) => {
+                request = request.header("content-type", "application/json");
+                Body::from(body.to_string())
+            }
+            None => Body::empty(),
+        };
+        let response = self
+            .app()
+            .oneshot(request.body(body).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    async fn create_batch(&self, body: Value) -> (StatusCode, Value) {
+        let (status, body) = self.send("POST", self.batches_uri(), Some(body)).await;
+        (status, serde_json::from_str(&body).unwrap_or(Value::Null))
+    }
+
+    async fn download(&self, batch_id: &str) -> (StatusCode, String) {
+        let uri = format!("{}/{}/csv", self.batches_uri(), batch_id);
+        self.send("GET", uri, None).await
+    }
+
+    async fn revoke(&self, batch_id: &str) -> StatusCode {
+        let uri = format!("{}/{}/revoke", self.batches_uri(), batch_id);
+        self.send("POST", uri, None).await.0
+    }
+
+    async fn batches(&self) -> Value {
+        let (status, body) = self.send("GET", self.batches_uri(), None).await;
+        assert_eq!(status, StatusCode::OK);
+        serde_json::from_str(&body).unwrap()
+    }
+
+    /// Generate and download a batch. Returns its ID and codes.
+    async fn codes(&self, count: i64) -> (String, Vec<String>) {
+        let (status, batch) = self.create_batch(json!({ "count": count })).await;
+        assert_eq!(status, StatusCode::OK);
+        let batch_id = batch["id"].as_str().unwrap().to_string();
+        let (status, csv) = self.download(&batch_id).await;
+        assert_eq!(status, StatusCode::OK);
+        let codes = csv
+            .lines()
+            .skip(1)
+            .map(|line| line.split_once(',').unwrap().1.to_string())
+            .collect();
+        (batch_id, codes)
+    }
+
+    async fn redeem(&self, public_key: &str, code: &str, device_id: &str) -> (StatusCode, Value) {
+        let response = self
+            .app()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/redeem/prepaid")
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        json!({
+                            "public_key": public_key,
+                            "code": code,
+                            "email": "buyer@example.com",
+                            "device_id": device_id,
+                            "device_type": "uuid"
+                        })
+                        .to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        )
+    }
+
+    fn audit_count(&self, action: &str) -> i64 {
+        let conn = self.state.audit.get().unwrap();
+        conn.query_row(
+            "SELECT COUNT(*) FROM audit_logs WHERE action = ?1",
+            [action],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+}
+
+#[tokio::test]
+async fn test_generate_and_download_codes_once() {
+    let f = setup();
+
+    let (status, batch) = f
+        .create_batch(json!({ "count": 3, "label": "Retailer shipment 1" }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(batch["code_count"], 3);
+    assert_eq!(batch["redeemed_count"], 0);
+    assert_eq!(batch["label"], "Retailer shipment 1");
+    assert!(
+        batch["expires_at"].is_null(),
+        "codes don't expire by default"
+    );
+    assert!(batch.get("codes").is_none(), "codes only come from the CSV");
+    let batch_id = batch["id"].as_str().unwrap();
+
+    let response = f
+        .app()
+        .oneshot(
+            Request::builder()
+                .uri(format!("{}/{}/csv", f.batches_uri(), batch_id))
+                .header("Authorization", format!("Bearer {}", f.api_key))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "text/csv; charset=utf-8"
+    );
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let csv = String::from_utf8(bytes.to_vec()).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[0], "serial,code");
+    assert_eq!(lines.len(), 4);
+    let prefix = format!("{}-", f.project.license_key_prefix);
+    for (i, line) in lines[1..].iter().enumerate() {
+        let (serial, code) = line.split_once(',').unwrap();
+        assert_eq!(serial, (i + 1).to_string());
+        assert!(code.starts_with(&prefix), "{}", code);
+        assert_eq!(code.split('-').count(), 5, "{}", code);
+    }
+
+    let (status, body) = f.download(batch_id).await;
+    assert_eq!(status, StatusCode::CONFLICT, "the CSV downloads only once");
+    assert!(!body.contains(&prefix), "no codes in the error: {}", body);
+
+    // Only hashes are stored once the CSV is gone
+    let conn = f.state.db.get().unwrap();
+    let pending: Option<Vec<u8>> = conn
+        .query_row(
+            "SELECT pending_codes FROM prepaid_code_batches WHERE id = ?1",
+            [batch_id],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert!(pending.is_none());
+    let code = lines[1].split_once(',').unwrap().1;
+    let stored: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM prepaid_codes WHERE code_hash = ?1",
+            [code],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(stored, 0, "codes are not stored in plaintext");
+
+    assert_eq!(f.audit_count("create_prepaid_codes"), 1);
+    assert_eq!(f.audit_count("download_prepaid_codes"), 1);
+}
+
+#[tokio::test]
+async fn test_prepaid_code_redeems_once() {
+    let f = setup();
+    let (batch_id, codes) = f.codes(2).await;
+
+    // Typed from the card: spaces instead of dashes, lowercase
+    let (prefix, groups) = codes[0].split_once('-').unwrap();
+    let typed = format!("{} {}", prefix, groups.replace('-', " ").to_lowercase());
+    let (status, body) = f.redeem(&f.project.public_key, &typed, "device-1").await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert!(body["token"].is_string());
+    assert_eq!(body["tier"], "pro");
+    assert!(body["activation_code"].is_string());
+
+    let (status, _) = f.redeem(&f.project.public_key, &codes[0], "device-2").await;
+    assert_eq!(status, StatusCode::FORBIDDEN, "a code redeems only once");
+
+    let conn = f.state.db.get().unwrap();
+    let (licenses, total) =
+        queries::get_licenses_by_payment_order_id_paginated(&conn, &f.project.id, &batch_id, 50, 0)
+            .unwrap();
+    assert_eq!(total, 1, "one license per redeemed code");
+    let license = &licenses[0].license;
+    assert_eq!(license.product_id, f.product.id);
+    assert_eq!(license.payment_provider.as_deref(), Some("prepaid"));
+    assert_eq!(
+        license.email_hash.as_deref(),
+        Some(
+            test_email_hasher()
+                .hash(&parse_email("buyer@example.com"))
+                .as_str()
+        ),
+        "the email recovers the license"
+    );
+
+    let batches = f.batches().await;
+    assert_eq!(batches["items"][0]["redeemed_count"], 1);
+    assert_eq!(f.audit_count("redeem_prepaid_code"), 1);
+}
+
+#[tokio::test]
+async fn test_prepaid_code_only_redeems_in_its_project() {
+    let f = setup();
+    let (_, codes) = f.codes(1).await;
+    let other = {
+        let conn = f.state.db.get().unwrap();
+        create_test_project(&conn, &f.org_id, "Other Project", &test_master_key())
+    };
+
+    let (status, _) = f.redeem(&other.public_key, &codes[0], "device-1").await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = f.redeem(&f.project.public_key, &codes[0], "device-1").await;
+    assert_eq!(
+        status,
+        StatusCode::OK,
+        "the failed attempt didn't use it up"
+    );
+}
+
+#[tokio::test]
+async fn test_revoked_batch_codes_stop_working() {
+    let f = setup();
+    let (batch_id, codes) = f.codes(3).await;
+    let (status, _) = f.redeem(&f.project.public_key, &codes[0], "device-1").await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = f
+        .send(
+            "POST",
+            format!("{}/{}/revoke", f.batches_uri(), batch_id),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let batch: Value = serde_json::from_str(&body).unwrap();
+    assert!(batch["revoked_at"].is_i64());
+    assert_eq!(batch["redeemed_count"], 1);
+
+    for code in &codes[1..] {
+        let (status, _) = f.redeem(&f.project.public_key, code, "device-2").await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{} is revoked", code);
+    }
+
+    // The license already redeemed is kept
+    let conn = f.state.db.get().unwrap();
+    let (licenses, _) =
+        queries::get_licenses_by_payment_order_id_paginated(&conn, &f.project.id, &batch_id, 50, 0)
+            .unwrap();
+    assert!(!licenses[0].license.revoked);
+
+    assert_eq!(f.revoke(&batch_id).await, StatusCode::BAD_REQUEST);
+    assert_eq!(f.audit_count("revoke_prepaid_codes"), 1);
+}
+
+#[tokio::test]
+async fn test_revoking_before_download_drops_the_codes() {
+    let f = setup();
+    let (_, batch) = f.create_batch(json!({ "count": 2 })).await;
+    let batch_id = batch["id"].as_str().unwrap();
+
+    assert_eq!(f.revoke(batch_id).await, StatusCode::OK);
+    let (status, _) = f.download(batch_id).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn test_create_batch_validates_input() {
+    let f = setup();
+
+    for body in [
+        json!({ "count": 0 }),
+        json!({ "count": 10_001 }),
+        json!({ "count": 5, "expires_in_days": 0 }),
+        json!({ "count": 5, "label": "x".repeat(101) }),
+    ] {
+        let (status, _) = f.create_batch(body.clone()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    }
+    assert!(f.batches().await["items"].as_array().unwrap().is_empty());
+}
@@ -0,0 +1,305 @@
+//! Tests for org product templates: instantiating them when a project is
+//! created or into an existing project, and that instantiated products are
+//! copies that later template changes don't touch.
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    response::Response,
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::config::RateLimitConfig;
+use paycheck::handlers;
+
+struct TemplatesFixture {
+    state: AppState,
+    org_id: String,
+    project: Project,
+    api_key: String,
+}
+
+fn setup() -> TemplatesFixture {
+    let state = create_test_app_state();
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, api_key) =
+        create_test_org_member(&mut conn, &org.id, "admin@test.com", OrgMemberRole::Owner);
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+
+    drop(conn);
+    TemplatesFixture {
+        state,
+        org_id: org.id,
+        project,
+        api_key,
+    }
+}
+
+impl TemplatesFixture {
+    async fn send(&self, method: &str, path: &str, body: Value) -> (StatusCode, Value) {
+        let app = handlers::orgs::router(self.state.clone(), RateLimitConfig::disabled())
+            .with_state(self.state.clone());
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri(format!("/orgs/{}{}", self.org_id, path))
+                    .header("Authorization", format!("Bearer {}", self.api_key))
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        (status, body_json(response).await)
+    }
+
+    async fn create_template(&self, body: Value) -> String {
+        let (status, body) = self.send("POST", "/product-templates", body).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        body["id"].as_str().unwrap().to_string()
+    }
+
+    fn products(&self, project_id: &str) -> Vec<Product> {
+        let conn = self.state.db.get().unwrap();
+        queries::list_products_for_project(&conn, project_id).unwrap()
+    }
+}
+
+async fn body_json(response: Response) -> Value {
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    serde_json::from_slice(&bytes).unwrap_or(Value::Null)
+}
+
+fn pro_template() -> Value {
+    json!({
+        "name": "Pro",
+        "tier": "pro",
+        "license_exp_days": 365,
+        "activation_limit": 3,
+        "features": ["export", "sync"],
+        "price_cents": 4900,
+        "currency": "usd",
+    })
+}
+
+#[tokio::test]
+async fn test_project_created_with_template_products() {
+    let f = setup();
+    let pro = f.create_template(pro_template()).await;
+    let basic = f
+        .create_template(json!({ "name": "Basic", "tier": "basic" }))
+        .await;
+
+    let (status, body) = f
+        .send(
+            "POST",
+            "/projects",
+            json!({ "name": "New App", "templates": [pro, basic] }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let project_id = body["id"].as_str().unwrap();
+    let product_ids = body["product_ids"].as_array().unwrap();
+    assert_eq!(product_ids.len(), 2);
+
+    let conn = f.state.db.get().unwrap();
+    let product = queries::get_product_by_id(&conn, product_ids[0].as_str().unwrap())
+        .unwrap()
+        .unwrap();
+    assert_eq!(product.project_id, project_id);
+    assert_eq!(product.name, "Pro");
+    assert_eq!(product.tier, "pro");
+    assert_eq!(product.license_exp_days, Some(365));
+    assert_eq!(product.activation_limit, Some(3));
+    assert_eq!(product.features, vec!["export", "sync"]);
+    assert_eq!(product.price_cents, Some(4900));
+}
+
+#[tokio::test]
+async fn test_project_without_templates_has_no_products() {
+    let f = setup();
+    let (status, body) = f
+        .send("POST", "/projects", json!({ "name": "New App" }))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert!(body.get("product_ids").is_none());
+    assert!(f.products(body["id"].as_str().unwrap()).is_empty());
+}
+
+#[tokio::test]
+async fn test_unknown_template_creates_no_project() {
+    let f = setup();
+    let (status, _) = f
+        .send(
+            "POST",
+            "/projects",
+            json!({ "name": "New App", "templates": ["missing"] }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let conn = f.state.db.get().unwrap();
+    let projects = queries::list_projects_for_org(&conn, &f.org_id).unwrap();
+    assert_eq!(projects.len(), 1);
+}
+
+#[tokio::test]
+async fn test_template_from_other_org_not_found() {
+    let f = setup();
+    let other = {
+        let conn = f.state.db.get().unwrap();
+        let other_org = create_test_org(&conn, "Other Org");
+        queries::create_product_template(
+            &conn,
+            &other_org.id,
+            &serde_json::from_value(json!({ "name": "Theirs", "tier": "theirs" })).unwrap(),
+        )
+        .unwrap()
+    };
+
+    let (status, _) = f
+        .send(
+            "POST",
+            &format!("/projects/{}/apply-template", f.project.id),
+            json!({ "templates": [other.id] }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(f.products(&f.project.id).is_empty());
+}
+
+#[tokio::test]
+async fn test_apply_templates_to_existing_project() {
+    let f = setup();
+    let pro = f.create_template(pro_template()).await;
+
+    let (status, body) = f
+        .send(
+            "POST",
+            &format!("/projects/{}/apply-template", f.project.id),
+            json!({ "templates": [pro] }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let products = f.products(&f.project.id);
+    assert_eq!(products.len(), 1);
+    assert_eq!(body["product_ids"], json!([products[0].id]));
+    assert_eq!(products[0].tier, "pro");
+}
+
+#[tokio::test]
+async fn test_apply_requires_templates() {
+    let f = setup();
+    let (status, _) = f
+        .send(
+            "POST",
+            &format!("/projects/{}/apply-template", f.project.id),
+            json!({ "templates": [] }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_apply_over_product_limit_creates_nothing() {
+    let f = setup();
+    {
+        let conn = f.state.db.get().unwrap();
+        queries::set_org_limit(
+            &conn,
+            &f.org_id,
+            OrgLimitName::ProductsPerProject,
+            &SetOrgLimit {
+                soft_value: None,
+                hard_value: Some(1),
+            },
+        )
+        .unwrap();
+    }
+    let pro = f.create_template(pro_template()).await;
+    let basic = f
+        .create_template(json!({ "name": "Basic", "tier": "basic" }))
+        .await;
+
+    let (status, body) = f
+        .send(
+            "POST",
+            &format!("/projects/{}/apply-template", f.project.id),
+            json!({ "templates": [pro, basic] }),
+        )
+        .await;
+    assert!(status.is_client_error(), "{}", body);
+    assert!(f.products(&f.project.id).is_empty());
+}
+
+#[tokio::test]
+async fn test_template_changes_leave_products_alone() {
+    let f = setup();
+    let pro = f.create_template(pro_template()).await;
+    let (status, body) = f
+        .send(
+            "POST",
+            &format!("/projects/{}/apply-template", f.project.id),
+            json!({ "templates": [pro] }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let (status, body) = f
+        .send(
+            "PUT",
+            &format!("/product-templates/{}", pro),
+            json!({ "price_cents": 9900, "license_exp_days": null }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["price_cents"], 9900);
+    assert!(body["license_exp_days"].is_null());
+
+    let product = &f.products(&f.project.id)[0];
+    assert_eq!(product.price_cents, Some(4900));
+    assert_eq!(product.license_exp_days, Some(365));
+
+    let (status, _) = f
+        .send("DELETE", &format!("/product-templates/{}", pro), json!({}))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, templates) = f.send("GET", "/product-templates", json!({})).await;
+    assert_eq!(templates, json!([]));
+    assert_eq!(f.products(&f.project.id).len(), 1);
+}
+
+#[tokio::test]
+async fn test_invalid_templates_rejected() {
+    let f = setup();
+    for body in [
+        json!({ "name": "Pro", "tier": " " }),
+        json!({ "name": "", "tier": "pro" }),
+        json!({ "name": "Team", "tier": "team", "seat_count": 0 }),
+    ] {
+        let (status, response) = f.send("POST", "/product-templates", body.clone()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{} -> {}", body, response);
+    }
+
+    let pro = f.create_template(pro_template()).await;
+    let (status, _) = f
+        .send(
+            "PUT",
+            &format!("/product-templates/{}", pro),
+            json!({ "tier": "" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,272 @@
+//! Tests for product upsells: the target and highlighted features are checked
+//! when an upsell is set and when the target's features change, and the
+//! catalog and /validate pass the upgrade on to apps.
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode},
+    response::Response,
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::config::RateLimitConfig;
+use paycheck::handlers;
+
+struct UpsellFixture {
+    state: AppState,
+    org_id: String,
+    project: Project,
+    api_key: String,
+    /// Features `feature1` and `feature2`
+    basic: Product,
+    /// Features `feature1` and `feature2`
+    pro: Product,
+}
+
+fn setup() -> UpsellFixture {
+    let state = create_test_app_state();
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, api_key) =
+        create_test_org_member(&mut conn, &org.id, "admin@test.com", OrgMemberRole::Owner);
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    let basic = create_test_product(&conn, &project.id, "Basic Plan", "basic");
+    let pro = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+
+    drop(conn);
+    UpsellFixture {
+        state,
+        org_id: org.id,
+        project,
+        api_key,
+        basic,
+        pro,
+    }
+}
+
+impl UpsellFixture {
+    async fn update_product(&self, product_id: &str, body: Value) -> (StatusCode, Value) {
+        let app = handlers::orgs::router(self.state.clone(), RateLimitConfig::disabled())
+            .with_state(self.state.clone());
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("PUT")
+                    .uri(format!(
+                        "/orgs/{}/projects/{}/products/{}",
+                        self.org_id, self.project.id, product_id
+                    ))
+                    .header("Authorization", format!("Bearer {}", self.api_key))
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        (status, body_json(response).await)
+    }
+
+    /// Offer Pro to Basic customers, highlighting `highlights`.
+    async fn set_upsell(&self, highlights: &[&str]) -> (StatusCode, Value) {
+        let body = json!({
+            "upsell": {
+                "upgrade_to_product_id": self.pro.id,
+                "highlight_features": highlights,
+                "marketing_blurb": "Everything in Basic, and more",
+            }
+        });
+        self.update_product(&self.basic.id, body).await
+    }
+
+    async fn public(&self, request: Request<Body>) -> Value {
+        let response = public_app(self.state.clone())
+            .oneshot(request)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        body_json(response).await
+    }
+
+    async fn catalog(&self) -> Value {
+        let uri = format!("/products?public_key={}", self.project.public_key);
+        self.public(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+    }
+
+    /// /validate for a device on a new license for `product`.
+    async fn validate(&self, product: &Product) -> Value {
+        let jti = {
+            let conn = self.state.db.get().unwrap();
+            let license = create_test_license(
+                &conn,
+                &self.project.id,
+                &product.id,
+                Some(future_timestamp(ONE_YEAR)),
+            );
+            create_test_device(&conn, &license.id, "laptop", DeviceType::Uuid).jti
+        };
+        let body = json!({ "public_key": self.project.public_key, "jti": jti });
+        let request = Request::builder()
+            .method("POST")
+            .uri("/validate")
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        self.public(request).await
+    }
+}
+
+async fn body_json(response: Response) -> Value {
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    serde_json::from_slice(&bytes).unwrap_or(Value::Null)
+}
+
+fn catalog_entry<'a>(catalog: &'a Value, product: &Product) -> &'a Value {
+    catalog["products"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|p| p["id"] == product.id.as_str())
+        .expect("product listed")
+}
+
+#[tokio::test]
+async fn test_set_and_clear_upsell() {
+    let f = setup();
+    let (status, body) = f.set_upsell(&["feature2"]).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["upsell"]["upgrade_to_product_id"], f.pro.id.as_str());
+    assert_eq!(body["upsell"]["highlight_features"], json!(["feature2"]));
+    assert_eq!(
+        body["upsell"]["marketing_blurb"],
+        "Everything in Basic, and more"
+    );
+
+    // Other updates leave it alone
+    let (_, body) = f
+        .update_product(&f.basic.id, json!({ "name": "Starter" }))
+        .await;
+    assert_eq!(body["upsell"]["upgrade_to_product_id"], f.pro.id.as_str());
+
+    let (status, body) = f
+        .update_product(&f.basic.id, json!({ "upsell": null }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["upsell"].is_null());
+}
+
+#[tokio::test]
+async fn test_upsell_target_must_be_another_product_in_the_project() {
+    let f = setup();
+    let elsewhere = {
+        let conn = f.state.db.get().unwrap();
+        let other = create_test_project(&conn, &f.org_id, "Other", &test_master_key());
+        create_test_product(&conn, &other.id, "Pro Plan", "pro")
+    };
+
+    for target in [elsewhere.id.as_str(), f.basic.id.as_str(), "missing"] {
+        let body = json!({ "upsell": { "upgrade_to_product_id": target } });
+        let (status, body) = f.update_product(&f.basic.id, body).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}: {}", target, body);
+    }
+}
+
+#[tokio::test]
+async fn test_highlights_must_be_target_features() {
+    let f = setup();
+    let (status, body) = f.set_upsell(&["feature2", "priority_support"]).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(
+        body["details"]
+            .as_str()
+            .unwrap()
+            .contains("highlight_features"),
+        "{}",
+        body
+    );
+}
+
+#[tokio::test]
+async fn test_blurb_length_is_capped() {
+    let f = setup();
+    let body = json!({
+        "upsell": {
+            "upgrade_to_product_id": f.pro.id,
+            "marketing_blurb": "a".repeat(281),
+        }
+    });
+    let (status, _) = f.update_product(&f.basic.id, body).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_target_cannot_drop_highlighted_feature() {
+    let f = setup();
+    let (status, _) = f.set_upsell(&["feature2"]).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let body = json!({ "features": ["feature1"] });
+    let (status, _) = f.update_product(&f.pro.id, body).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Features nobody highlights can go
+    let body = json!({ "features": ["feature2", "sso"] });
+    let (status, body) = f.update_product(&f.pro.id, body).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    // Once the upsell stops highlighting it, so can the rest
+    f.set_upsell(&[]).await;
+    let body = json!({ "features": ["sso"] });
+    let (status, _) = f.update_product(&f.pro.id, body).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_catalog_lists_upsell_with_its_target() {
+    let f = setup();
+    f.set_upsell(&["feature2"]).await;
+
+    let catalog = f.catalog().await;
+    let upsell = &catalog_entry(&catalog, &f.basic)["upsell"];
+    assert_eq!(upsell["upgrade_to_product_id"], f.pro.id.as_str());
+    assert_eq!(upsell["highlight_features"], json!(["feature2"]));
+    assert!(catalog_entry(&catalog, &f.pro).get("upsell").is_none());
+
+    // No upsell to a product that isn't listed
+    queries::soft_delete_product(&f.state.db.get().unwrap(), &f.pro.id).unwrap();
+    let catalog = f.catalog().await;
+    assert!(catalog_entry(&catalog, &f.basic).get("upsell").is_none());
+}
+
+#[tokio::test]
+async fn test_validate_returns_upgrade_target() {
+    let f = setup();
+    let body = f.validate(&f.basic).await;
+    assert_eq!(body["valid"], true);
+    assert!(body.get("upgrade_to_product_id").is_none());
+
+    f.set_upsell(&[]).await;
+    let body = f.validate(&f.basic).await;
+    assert_eq!(body["upgrade_to_product_id"], f.pro.id.as_str());
+
+    // Not while the target is off sale
+    let conn = f.state.db.get().unwrap();
+    conn.execute(
+        "UPDATE products SET available_until = ?1 WHERE id = ?2",
+        rusqlite::params![past_timestamp(ONE_DAY), f.pro.id],
+    )
+    .unwrap();
+    drop(conn);
+    let body = f.validate(&f.basic).await;
+    assert!(body.get("upgrade_to_product_id").is_none());
+}
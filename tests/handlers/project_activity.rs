@@ -0,0 +1,280 @@
+//! Tests for the project activity feed: member changes recorded through the
+//! API come back newest first with a summary, public entries are left out,
+//! and members only see projects they were added to.
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::handlers;
+
+struct ActivityFixture {
+    state: AppState,
+    org_id: String,
+    project_id: String,
+    product_id: String,
+    license_id: String,
+    owner_key: String,
+}
+
+fn setup() -> ActivityFixture {
+    let state = create_test_app_state();
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, owner_key) =
+        create_test_org_member(&mut conn, &org.id, "owner@test.com", OrgMemberRole::Owner);
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+    let license = create_test_license(&conn, &project.id, &product.id, None);
+
+    drop(conn);
+    ActivityFixture {
+        state,
+        org_id: org.id,
+        project_id: project.id,
+        product_id: product.id,
+        license_id: license.id,
+        owner_key,
+    }
+}
+
+impl ActivityFixture {
+    async fn request(
+        &self,
+        method: &str,
+        path: &str,
+        api_key: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let app = handlers::orgs::router(
+            self.state.clone(),
+            paycheck::config::RateLimitConfig::disabled(),
+        )
+        .with_state(self.state.clone());
+        let mut request = Request::builder()
+            .method(method)
+            .uri(format!(
+                "/orgs/{}/projects/{}{}",
+                self.org_id, self.project_id, path
+            ))
+            .header("Authorization", format!("Bearer {}", api_key));
+        if body.is_some() {
+            request = request.header("content-type", "application/json");
+        }
+        let response = app
+            .oneshot(
+                request
+                    .body(body.map_or_else(Body::empty, |b| Body::from(b.to_string())))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        )
+    }
+
+    async fn activity(&self, query: &str, api_key: &str) -> (StatusCode, Value) {
+        self.request("GET", &format!("/activity{}", query), api_key, None)
+            .await
+    }
+
+    /// Write an audit entry for this project directly.
+    fn audit(&self, actor_type: ActorType, action: AuditAction, resource_id: &str) {
+        queries::create_audit_log(
+            &self.state.audit.get().unwrap(),
+            true,
+            actor_type,
+            None,
+            action.as_ref(),
+            "license",
+            resource_id,
+            None,
+            Some(&self.org_id),
+            Some(&self.project_id),
+            None,
+            None,
+            &AuditLogNames::default(),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_activity_lists_member_changes_newest_first() {
+    let f = setup();
+
+    let (status, _) = f
+        .request(
+            "PUT",
+            &format!("/products/{}", f.product_id),
+            &f.owner_key,
+            Some(json!({ "name": "Pro Plan 2" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    // Both requests land in the same second; push the first one back so the
+    // order doesn't come down to ID tiebreaks
+    f.state
+        .audit
+        .get()
+        .unwrap()
+        .execute(
+            "UPDATE audit_logs SET timestamp = timestamp - 60 WHERE action = 'update_product'",
+            [],
+        )
+        .unwrap();
+    let (status, _) = f
+        .request(
+            "POST",
+            &format!("/licenses/{}/revoke", f.license_id),
+            &f.owner_key,
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = f.activity("", &f.owner_key).await;
+    assert_eq!(status, StatusCode::OK);
+    let entries = body.as_array().unwrap();
+    assert_eq!(entries.len(), 2);
+
+    assert_eq!(entries[0]["action"], "revoke_license");
+    assert_eq!(entries[0]["resource_id"], f.license_id.as_str());
+    assert_eq!(
+        entries[0]["summary"],
+        format!(
+            "Test Member owner@test.com revoked license {}…",
+            &f.license_id[..8]
+        )
+    );
+    assert_eq!(entries[1]["action"], "update_product");
+    assert_eq!(
+        entries[1]["summary"],
+        "Test Member owner@test.com updated product Pro Plan"
+    );
+
+    // Only the feed's columns, not the full audit entry
+    assert!(entries[0].get("ip_address").is_none());
+    assert!(entries[0].get("details").is_none());
+}
+
+#[tokio::test]
+async fn test_activity_leaves_out_public_and_system_entries() {
+    let f = setup();
+    f.audit(
+        ActorType::Public,
+        AuditAction::ActivateDevice,
+        &f.license_id,
+    );
+    f.audit(
+        ActorType::System,
+        AuditAction::ReconcileSubscriptions,
+        &f.license_id,
+    );
+    f.audit(ActorType::User, AuditAction::CreateLicense, &f.license_id);
+
+    let (status, body) = f.activity("", &f.owner_key).await;
+    assert_eq!(status, StatusCode::OK);
+    let entries = body.as_array().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["action"], "create_license");
+    assert_eq!(
+        entries[0]["summary"],
+        format!("A team member created license {}…", &f.license_id[..8])
+    );
+}
+
+#[tokio::test]
+async fn test_activity_defaults_to_twenty_entries() {
+    let f = setup();
+    for i in 0..25 {
+        f.audit(
+            ActorType::User,
+            AuditAction::AddLicenseTags,
+            &format!("license-{}", i),
+        );
+    }
+
+    let (_, body) = f.activity("", &f.owner_key).await;
+    assert_eq!(body.as_array().unwrap().len(), 20);
+
+    let (_, body) = f.activity("?limit=5", &f.owner_key).await;
+    assert_eq!(body.as_array().unwrap().len(), 5);
+}
+
+#[tokio::test]
+async fn test_activity_scoped_to_the_project() {
+    let f = setup();
+    let other = {
+        let conn = f.state.db.get().unwrap();
+        create_test_project(&conn, &f.org_id, "Other Project", &test_master_key())
+    };
+    queries::create_audit_log(
+        &f.state.audit.get().unwrap(),
+        true,
+        ActorType::User,
+        None,
+        AuditAction::UpdateProject.as_ref(),
+        "project",
+        &other.id,
+        None,
+        Some(&f.org_id),
+        Some(&other.id),
+        None,
+        None,
+        &AuditLogNames::default(),
+        None,
+        None,
+        None,
+    )
+    .unwrap();
+
+    let (_, body) = f.activity("", &f.owner_key).await;
+    assert_eq!(body.as_array().unwrap().len(), 0);
+}
+
+#[tokio::test]
+async fn test_member_only_sees_activity_for_their_projects() {
+    let f = setup();
+    f.audit(ActorType::User, AuditAction::CreateLicense, &f.license_id);
+
+    let mut conn = f.state.db.get().unwrap();
+    let (_, _, outsider_key) = create_test_org_member(
+        &mut conn,
+        &f.org_id,
+        "outsider@test.com",
+        OrgMemberRole::Member,
+    );
+    let (_, viewer, viewer_key) = create_test_org_member(
+        &mut conn,
+        &f.org_id,
+        "viewer@test.com",
+        OrgMemberRole::Member,
+    );
+    create_test_project_member(&conn, &viewer.id, &f.project_id, ProjectMemberRole::View);
+    drop(conn);
+
+    let (status, _) = f.activity("", &outsider_key).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, body) = f.activity("", &viewer_key).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.as_array().unwrap().len(), 1);
+}
@@ -0,0 +1,387 @@
+//! Tests for project config export/import: an export imports as a no-op,
+//! imports apply creates, updates, and deletes in one transaction, and
+//! malformed documents are rejected.
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode},
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::handlers;
+
+struct ConfigFixture {
+    state: AppState,
+    org_id: String,
+    project: Project,
+    owner_key: String,
+    member_key: String,
+}
+
+fn setup() -> ConfigFixture {
+    let mut state = create_test_app_state();
+    state.audit_log_enabled = true;
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, owner_key) =
+        create_test_org_member(&mut conn, &org.id, "owner@test.com", OrgMemberRole::Owner);
+    let (_, member, member_key) =
+        create_test_org_member(&mut conn, &org.id, "member@test.com", OrgMemberRole::Member);
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    create_test_project_member(&conn, &member.id, &project.id, ProjectMemberRole::View);
+
+    let pro = create_test_product(&conn, &project.id, "Pro", "pro");
+    queries::create_provider_link(
+        &conn,
+        &pro.id,
+        &CreateProviderLink {
+            provider: "stripe".into(),
+            linked_id: "price_pro".into(),
+        },
+    )
+    .unwrap();
+    let team = create_test_product(&conn, &project.id, "Team", "team");
+    queries::update_product(
+        &conn,
+        &team.id,
+        &serde_json::from_value(json!({
+            "seat_count": 5,
+            "checkout_fields": [
+                { "key": "company", "label": "Company", "type": "text", "required": true }
+            ]
+        }))
+        .unwrap(),
+    )
+    .unwrap();
+
+    drop(conn);
+    ConfigFixture {
+        state,
+        org_id: org.id,
+        project,
+        owner_key,
+        member_key,
+    }
+}
+
+impl ConfigFixture {
+    fn app(&self) -> Router {
+        handlers::orgs::router(
+            self.state.clone(),
+            paycheck::config::RateLimitConfig::disabled(),
+        )
+        .with_state(self.state.clone())
+    }
+
+    fn url(&self, action: &str) -> String {
+        format!(
+            "/orgs/{}/projects/{}/{}",
+            self.org_id, self.project.id, action
+        )
+    }
+
+    async fn export(&self, query: &str) -> String {
+        let response = self
+            .app()
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri(format!("{}{}", self.url("config-export"), query))
+                    .header("Authorization", format!("Bearer {}", self.owner_key))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    async fn export_json(&self) -> Value {
+        serde_json::from_str(&self.export("").await).unwrap()
+    }
+
+    async fn import_as(
+        &self,
+        api_key: &str,
+        content_type: &str,
+        body: String,
+        dry_run: bool,
+    ) -> (StatusCode, Value) {
+        let response = self
+            .app()
+            .oneshot(
+                Request::builder()
+                    .method("PUT")
+                    .uri(format!("{}?dry_run={}", self.url("config-import"), dry_run))
+                    .header("Authorization", format!("Bearer {}", api_key))
+                    .header("content-type", content_type)
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    async fn import(&self, config: &Value, dry_run: bool) -> (StatusCode, Value) {
+        self.import_as(
+            &self.owner_key,
+            "application/json",
+            config.to_string(),
+            dry_run,
+        )
+        .await
+    }
+
+    fn config_import_audit_count(&self) -> i64 {
+        let conn = self.state.audit.get().unwrap();
+        conn.query_row(
+            "SELECT COUNT(*) FROM audit_logs WHERE json_extract(details, '$.source') = 'config_import'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+}
+
+#[tokio::test]
+async fn test_export_excludes_ids_and_keys() {
+    let f = setup();
+
+    let config = f.export_json().await;
+
+    assert_eq!(config["version"], 1);
+    assert_eq!(config["project"]["name"], "Test Project");
+    let text = config.to_string();
+    assert!(!text.contains(&f.project.id));
+    assert!(!text.contains(&f.project.public_key));
+    assert!(!text.contains("private_key"));
+
+    let products = config["products"].as_array().unwrap();
+    let names: Vec<&str> = products
+        .iter()
+        .map(|p| p["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["Pro", "Team"], "products are sorted by name");
+    assert_eq!(
+        products[0]["provider_links"],
+        json!({ "stripe": "price_pro" })
+    );
+    assert!(products[0].get("id").is_none());
+    assert_eq!(products[1]["checkout_fields"][0]["key"], "company");
+}
+
+#[tokio::test]
+async fn test_export_round_trips_to_no_changes() {
+    let f = setup();
+
+    let config = f.export_json().await;
+    let (status, result) = f.import(&config, false).await;
+    assert_eq!(status, StatusCode::OK, "{}", result);
+    assert_eq!(result["changes"], json!([]));
+
+    let yaml = f.export("?format=yaml").await;
+    assert!(yaml.contains("name: Test Project"), "{}", yaml);
+    let (status, result) = f
+        .import_as(&f.owner_key, "application/yaml", yaml, false)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", result);
+    assert_eq!(result["changes"], json!([]));
+
+    assert_eq!(f.export_json().await, config);
+    assert_eq!(f.config_import_audit_count(), 0);
+}
+
+#[tokio::test]
+async fn test_dry_run_reports_changes_without_applying() {
+    let f = setup();
+    let before = f.export_json().await;
+
+    let mut config = before.clone();
+    config["project"]["redirect_url"] = json!("https://example.com/thanks");
+    config["products"][0]["provider_links"]["stripe"] = json!("price_pro_v2");
+
+    let (status, result) = f.import(&config, true).await;
+    assert_eq!(status, StatusCode::OK, "{}", result);
+    assert_eq!(result["dry_run"], true);
+    assert_eq!(
+        result["changes"],
+        json!([
+            {
+                "action": "update",
+                "resource": "project",
+                "id": f.project.id,
+                "name": "Test Project",
+                "fields": ["redirect_url"]
+            },
+            {
+                "action": "update",
+                "resource": "provider_link",
+                "id": result["changes"][1]["id"],
+                "name": "stripe",
+                "product": "Pro",
+                "fields": ["linked_id"]
+            }
+        ])
+    );
+
+    assert_eq!(f.export_json().await, before);
+    assert_eq!(f.config_import_audit_count(), 0);
+}
+
+#[tokio::test]
+async fn test_import_applies_creates_updates_and_deletes() {
+    let f = setup();
+
+    let mut config = f.export_json().await;
+    config["project"]["allow_link_checkout"] = json!(true);
+    // Pro: new limit, Stripe link removed, LemonSqueezy link added
+    config["products"][0]["device_limit"] = json!(10);
+    config["products"][0]["provider_links"] = json!({ "lemonsqueezy": "12345" });
+    // Team is dropped; Enterprise is new
+    config["products"][1] = json!({
+        "name": "Enterprise",
+        "tier": "enterprise",
+        "features": ["sso"],
+        "provider_links": { "stripe": "price_ent" }
+    });
+
+    let (status, result) = f.import(&config, false).await;
+    assert_eq!(status, StatusCode::OK, "{}", result);
+    assert_eq!(result["dry_run"], false);
+
+    let summary: Vec<(&str, &str, &str)> = result["changes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|c| {
+            (
+                c["action"].as_str().unwrap(),
+                c["resource"].as_str().unwrap(),
+                c["name"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("update", "project", "Test Project"),
+            ("create", "product", "Enterprise"),
+            ("create", "provider_link", "stripe"),
+            ("update", "product", "Pro"),
+            ("create", "provider_link", "lemonsqueezy"),
+            ("delete", "provider_link", "stripe"),
+            ("delete", "product", "Team"),
+        ]
+    );
+    assert_eq!(result["changes"][3]["fields"], json!(["device_limit"]));
+    for change in result["changes"].as_array().unwrap() {
+        assert!(change["id"].is_string(), "{}", change);
+    }
+
+    // The project now matches the document, so importing it again is a no-op
+    let (_, again) = f.import(&config, true).await;
+    assert_eq!(again["changes"], json!([]));
+
+    let conn = f.state.db.get().unwrap();
+    let team = queries::get_deleted_product_by_name(&conn, &f.project.id, "Team").unwrap();
+    assert!(team.is_some(), "removed products are soft-deleted");
+    drop(conn);
+
+    // One audit entry per change
+    assert_eq!(f.config_import_audit_count(), 7);
+}
+
+#[tokio::test]
+async fn test_import_rejects_unknown_fields() {
+    let f = setup();
+    let config = f.export_json().await;
+
+    let mut top_level = config.clone();
+    top_level["owner"] = json!("someone");
+    let mut in_project = config.clone();
+    in_project["project"]["private_key"] = json!("abc");
+    let mut in_product = config.clone();
+    in_product["products"][0]["price"] = json!(10);
+    let mut in_checkout_field = config.clone();
+    in_checkout_field["products"][1]["checkout_fields"][0]["placeholder"] = json!("Acme");
+
+    for doc in [top_level, in_project, in_product, in_checkout_field] {
+        let (status, result) = f.import(&doc, true).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", doc);
+        assert!(
+            result["details"]
+                .as_str()
+                .unwrap()
+                .contains("unknown field"),
+            "{}",
+            result
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_import_rejects_invalid_documents() {
+    let f = setup();
+    let config = f.export_json().await;
+
+    let mut wrong_version = config.clone();
+    wrong_version["version"] = json!(2);
+    let mut duplicate_name = config.clone();
+    duplicate_name["products"][1]["name"] = json!("Pro");
+    let mut bad_provider = config.clone();
+    bad_provider["products"][0]["provider_links"] = json!({ "paypal": "x" });
+    let mut empty_tier = config.clone();
+    empty_tier["products"][0]["tier"] = json!("");
+
+    for doc in [wrong_version, duplicate_name, bad_provider, empty_tier] {
+        let (status, _) = f.import(&doc, false).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", doc);
+    }
+    assert_eq!(f.export_json().await, config);
+}
+
+#[tokio::test]
+async fn test_import_fails_as_a_whole_on_deleted_product_name() {
+    let f = setup();
+    let before = f.export_json().await;
+
+    // Delete Team, then try to recreate it alongside other changes
+    let mut without_team = before.clone();
+    without_team["products"].as_array_mut().unwrap().pop();
+    assert_eq!(f.import(&without_team, false).await.0, StatusCode::OK);
+    let after_delete = f.export_json().await;
+
+    let mut config = before.clone();
+    config["project"]["allow_link_checkout"] = json!(true);
+    let (status, result) = f.import(&config, false).await;
+    assert_eq!(status, StatusCode::CONFLICT, "{}", result);
+
+    assert_eq!(f.export_json().await, after_delete);
+}
+
+#[tokio::test]
+async fn test_import_requires_write_access() {
+    let f = setup();
+    let config = f.export_json().await;
+
+    let (status, _) = f
+        .import_as(&f.member_key, "application/json", config.to_string(), true)
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
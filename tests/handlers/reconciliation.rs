@@ -0,0 +1,465 @@
+//! Tests for subscription reconciliation: a mock provider answers with a mix
+//! of statuses, and a run reports (then fixes) the licenses that disagree
+//! while leaving the rest untouched.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use axum::{
+    Router,
+    body::Body,
+    extract::Path,
+    http::{Request, StatusCode},
+    response::IntoResponse,
+    routing::get,
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::handlers;
+use paycheck::payments::{LemonSqueezyClient, StripeClient, SubscriptionStatus};
+use paycheck::reconcile::{self, RunOptions, SubscriptionClient};
+
+/// Pinned "now" for the run
+const NOW: i64 = 1_780_000_000;
+const DAY: i64 = 86400;
+
+/// Serve `subscriptions` (id -> status and body) on a local port, 404 for
+/// unknown ids, and return its base URL.
+async fn mock_provider(subscriptions: HashMap<&'static str, (StatusCode, Value)>) -> String {
+    let app = Router::new().route(
+        "/v1/subscriptions/{id}",
+        get(move |Path(id): Path<String>| {
+            let canned = subscriptions.get(id.as_str()).cloned();
+            async move {
+                match canned {
+                    Some((status, body)) => (status, axum::Json(body)).into_response(),
+                    None => (
+                        StatusCode::NOT_FOUND,
+                        axum::Json(json!({ "error": { "message": "No such subscription" } })),
+                    )
+                        .into_response(),
+                }
+            }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}", addr)
+}
+
+fn stripe_subscription(id: &str, status: &str, period_end: i64) -> (StatusCode, Value) {
+    let ended_at = (status == "canceled").then_some(period_end);
+    (
+        StatusCode::OK,
+        json!({
+            "id": id,
+            "status": status,
+            "current_period_end": period_end,
+            "ended_at": ended_at,
+        }),
+    )
+}
+
+fn stripe_client(api_base: &str) -> SubscriptionClient {
+    let config = StripeConfig {
+        secret_key: "sk_test_xxx".to_string(),
+        publishable_key: "pk_test_xxx".to_string(),
+        webhook_secret: "whsec_test123secret456".to_string(),
+    };
+    SubscriptionClient::Stripe(StripeClient::with_endpoint(
+        &config,
+        api_base,
+        Duration::from_secs(5),
+    ))
+}
+
+fn options(apply: bool) -> RunOptions<'static> {
+    RunOptions {
+        apply,
+        started_by: None,
+        pace: Duration::ZERO,
+    }
+}
+
+struct Fixture {
+    state: AppState,
+    org_id: String,
+    product: Product,
+    /// Licenses by subscription id
+    licenses: HashMap<&'static str, License>,
+}
+
+/// An org with Stripe licenses for each case the mock provider answers, plus
+/// licenses a Stripe run must not look at.
+fn setup() -> Fixture {
+    let mut state = create_test_app_state();
+    state.clock = Clock::fixed(NOW);
+    let conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    let product = create_test_product(&conn, &project.id, "Pro", "pro");
+
+    let mut licenses = HashMap::new();
+    for (subscription_id, expires_at) in [
+        ("sub_renewed", NOW + 2 * DAY),
+        ("sub_in_sync", NOW + 20 * DAY),
+        ("sub_canceled", NOW + 20 * DAY),
+        ("sub_past_due", NOW + 27 * DAY),
+        ("sub_missing", NOW + 10 * DAY),
+        ("sub_broken", NOW + 10 * DAY),
+    ] {
+        let license = create_test_license_with_subscription(
+            &conn,
+            &project.id,
+            &product.id,
+            Some(expires_at),
+            "stripe",
+            subscription_id,
+        );
+        licenses.insert(subscription_id, license);
+    }
+
+    // Another provider's subscription and a revoked license are skipped
+    create_test_license_with_subscription(
+        &conn,
+        &project.id,
+        &product.id,
+        Some(NOW + 2 * DAY),
+        "lemonsqueezy",
+        "sub_renewed",
+    );
+    let revoked = create_test_license_with_subscription(
+        &conn,
+        &project.id,
+        &product.id,
+        Some(NOW + 2 * DAY),
+        "stripe",
+        "sub_revoked",
+    );
+    queries::revoke_license(&conn, &revoked.id, &RevokeLicense::default(), None).unwrap();
+
+    drop(conn);
+    Fixture {
+        state,
+        org_id: org.id,
+        product,
+        licenses,
+    }
+}
+
+/// Subscriptions as the provider has them: one renewal and two cancellations
+/// the webhooks never delivered, one in sync, one unknown, one failing.
+async fn mixed_provider() -> String {
+    mock_provider(HashMap::from([
+        (
+            "sub_renewed",
+            stripe_subscription("sub_renewed", "active", NOW + 32 * DAY),
+        ),
+        (
+            "sub_in_sync",
+            stripe_subscription("sub_in_sync", "active", NOW + 20 * DAY),
+        ),
+        (
+            "sub_canceled",
+            stripe_subscription("sub_canceled", "canceled", NOW - DAY),
+        ),
+        (
+            "sub_past_due",
+            stripe_subscription("sub_past_due", "past_due", NOW - 3 * DAY),
+        ),
+        (
+            "sub_broken",
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                json!({ "error": { "message": "Internal error" } }),
+            ),
+        ),
+    ]))
+    .await
+}
+
+impl Fixture {
+    fn license(&self, subscription_id: &str) -> License {
+        let conn = self.state.db.get().unwrap();
+        queries::get_license_by_id(&conn, &self.licenses[subscription_id].id)
+            .unwrap()
+            .unwrap()
+    }
+
+    fn discrepancy<'a>(run: &'a ReconciliationRun, subscription_id: &str) -> &'a Discrepancy {
+        run.discrepancies
+            .iter()
+            .find(|d| d.subscription_id == subscription_id)
+            .unwrap_or_else(|| panic!("no discrepancy for {}", subscription_id))
+    }
+}
+
+#[tokio::test]
+async fn test_report_only_run_lists_discrepancies_without_changes() {
+    let f = setup();
+    let client = stripe_client(&mixed_provider().await);
+
+    let run = reconcile::run(&f.state, &f.org_id, &client, &options(false))
+        .await
+        .unwrap();
+
+    assert_eq!(run.provider, "stripe");
+    assert!(!run.applied);
+    assert_eq!(run.started_at, NOW);
+    assert_eq!(
+        run.checked, 6,
+        "other providers and revoked licenses are skipped"
+    );
+    assert_eq!(run.discrepancies.len(), 5, "{:?}", run.discrepancies);
+    assert!(
+        !run.discrepancies
+            .iter()
+            .any(|d| d.subscription_id == "sub_in_sync")
+    );
+
+    let renewed = Fixture::discrepancy(&run, "sub_renewed");
+    assert_eq!(renewed.kind, DiscrepancyKind::MissedRenewal);
+    assert_eq!(renewed.provider_status.as_deref(), Some("active"));
+    assert_eq!(renewed.license_expires_at, Some(NOW + 2 * DAY));
+    assert_eq!(renewed.expected_expires_at, Some(NOW + 32 * DAY));
+
+    let canceled = Fixture::discrepancy(&run, "sub_canceled");
+    assert_eq!(canceled.kind, DiscrepancyKind::NotRenewed);
+    assert_eq!(canceled.expected_expires_at, Some(NOW - DAY));
+
+    let past_due = Fixture::discrepancy(&run, "sub_past_due");
+    assert_eq!(past_due.kind, DiscrepancyKind::NotRenewed);
+    assert_eq!(past_due.provider_status.as_deref(), Some("past_due"));
+    assert_eq!(past_due.expected_expires_at, Some(NOW - 3 * DAY));
+
+    let missing = Fixture::discrepancy(&run, "sub_missing");
+    assert_eq!(missing.kind, DiscrepancyKind::NotFound);
+    assert_eq!(missing.expected_expires_at, None);
+
+    let broken = Fixture::discrepancy(&run, "sub_broken");
+    assert_eq!(broken.kind, DiscrepancyKind::ProviderError);
+    assert!(broken.error.is_some());
+
+    assert!(run.discrepancies.iter().all(|d| !d.fixed));
+    for (subscription_id, license) in &f.licenses {
+        assert_eq!(
+            f.license(subscription_id).expires_at,
+            license.expires_at,
+            "{} changed in a report-only run",
+            subscription_id
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_apply_run_fixes_discrepancies() {
+    let f = setup();
+    let client = stripe_client(&mixed_provider().await);
+
+    let run = reconcile::run(&f.state, &f.org_id, &client, &options(true))
+        .await
+        .unwrap();
+    assert!(run.applied);
+
+    // Missed renewal: extended to the provider's period end, updates keep
+    // their offset from it
+    let renewed = f.license("sub_renewed");
+    assert_eq!(renewed.expires_at, Some(NOW + 32 * DAY));
+    let updates_days = f.product.updates_exp_days.unwrap() as i64;
+    assert_eq!(
+        renewed.updates_expires_at,
+        Some(NOW + 32 * DAY + updates_days * DAY)
+    );
+    assert!(Fixture::discrepancy(&run, "sub_renewed").fixed);
+
+    // Not renewed: ended at the last paid period
+    let canceled = f.license("sub_canceled");
+    assert_eq!(canceled.expires_at, Some(NOW - DAY));
+    assert_eq!(canceled.updates_expires_at, Some(NOW - DAY));
+    assert!(Fixture::discrepancy(&run, "sub_canceled").fixed);
+    assert_eq!(f.license("sub_past_due").expires_at, Some(NOW - 3 * DAY));
+
+    // Nothing to fix without an answer from the provider
+    for subscription_id in ["sub_in_sync", "sub_missing", "sub_broken"] {
+        assert_eq!(
+            f.license(subscription_id).expires_at,
+            f.licenses[subscription_id].expires_at,
+            "{}",
+            subscription_id
+        );
+    }
+    assert!(!Fixture::discrepancy(&run, "sub_missing").fixed);
+    assert!(!Fixture::discrepancy(&run, "sub_broken").fixed);
+
+    // A second run finds only what it can't fix
+    let rerun = reconcile::run(&f.state, &f.org_id, &client, &options(true))
+        .await
+        .unwrap();
+    let mut kinds: Vec<_> = rerun.discrepancies.iter().map(|d| d.kind).collect();
+    kinds.sort_by_key(|kind| format!("{:?}", kind));
+    assert_eq!(
+        kinds,
+        vec![DiscrepancyKind::NotFound, DiscrepancyKind::ProviderError]
+    );
+}
+
+#[tokio::test]
+async fn test_run_is_saved_with_report() {
+    let f = setup();
+    let client = stripe_client(&mixed_provider().await);
+
+    let run = reconcile::run(&f.state, &f.org_id, &client, &options(false))
+        .await
+        .unwrap();
+
+    let conn = f.state.db.get().unwrap();
+    let saved = queries::get_reconciliation_run(&conn, &run.id)
+        .unwrap()
+        .expect("run should be saved");
+    assert_eq!(saved.org_id, f.org_id);
+    assert_eq!(saved.checked, run.checked);
+    assert_eq!(saved.started_by, None);
+    assert_eq!(
+        serde_json::to_value(&saved.discrepancies).unwrap(),
+        serde_json::to_value(&run.discrepancies).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_lemonsqueezy_statuses_map_to_subscription_status() {
+    let app = Router::new().route(
+        "/v1/subscriptions/{id}",
+        get(|Path(id): Path<String>| async move {
+            let (status, renews_at, ends_at) = match id.as_str() {
+                "1" => ("active", Some("2026-07-01T00:00:00.000000Z"), None),
+                "2" => ("past_due", Some("2026-05-01T00:00:00.000000Z"), None),
+                "3" => ("paused", None, None),
+                _ => ("expired", None, Some("2026-04-01T00:00:00.000000Z")),
+            };
+            axum::Json(json!({
+                "data": {
+                    "id": id,
+                    "attributes": { "status": status, "renews_at": renews_at, "ends_at": ends_at }
+                }
+            }))
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let config = LemonSqueezyConfig {
+        api_key: "ls_test_key_abcdefghij".to_string(),
+        store_id: "store_123".to_string(),
+        webhook_secret: "ls_whsec_test_secret".to_string(),
+        verify_imported_keys: false,
+    };
+    let client = LemonSqueezyClient::with_endpoint(
+        &config,
+        &format!("http://{}", addr),
+        Duration::from_secs(5),
+    );
+
+    let expected = [
+        ("1", SubscriptionStatus::Active, Some(1_782_864_000)),
+        ("2", SubscriptionStatus::PastDue, Some(1_777_593_600)),
+        ("3", SubscriptionStatus::Paused, None),
+        ("4", SubscriptionStatus::Ended, Some(1_775_001_600)),
+    ];
+    for (id, status, period_end) in expected {
+        let subscription = client.get_subscription(id).await.unwrap().unwrap();
+        assert_eq!(subscription.status, status, "{}", id);
+        assert_eq!(subscription.period_end, period_end, "{}", id);
+    }
+}
+
+// ============ Operator endpoints ============
+
+async fn operator_request(
+    state: &AppState,
+    api_key: &str,
+    method: &str,
+    uri: &str,
+) -> (StatusCode, Value) {
+    let app = handlers::operators::router(state.clone()).with_state(state.clone());
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("Authorization", format!("Bearer {}", api_key))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+#[tokio::test]
+async fn test_reconcile_endpoint_requires_configured_provider() {
+    let f = setup();
+    let (_, admin_key) = {
+        let mut conn = f.state.db.get().unwrap();
+        create_test_operator(&mut conn, "admin@test.com", OperatorRole::Admin)
+    };
+
+    let uri = format!("/operators/reconcile?org_id={}&provider=stripe", f.org_id);
+    let (status, body) = operator_request(&f.state, &admin_key, "POST", &uri).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["details"], "Stripe not configured");
+
+    let uri = format!("/operators/reconcile?org_id={}&provider=paypal", f.org_id);
+    let (status, _) = operator_request(&f.state, &admin_key, "POST", &uri).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_get_reconciliation_run_scoped_to_operator_orgs() {
+    let f = setup();
+    let client = stripe_client(&mixed_provider().await);
+    let run = reconcile::run(&f.state, &f.org_id, &client, &options(false))
+        .await
+        .unwrap();
+
+    let (admin_key, partner_key) = {
+        let mut conn = f.state.db.get().unwrap();
+        let (_, admin_key) = create_test_operator(&mut conn, "admin@test.com", OperatorRole::Admin);
+        let (_, partner_key) =
+            create_test_operator(&mut conn, "partner@test.com", OperatorRole::Partner);
+        (admin_key, partner_key)
+    };
+
+    let uri = format!("/operators/reconciliation-runs/{}", run.id);
+    let (status, body) = operator_request(&f.state, &admin_key, "GET", &uri).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["id"], run.id);
+    assert_eq!(body["discrepancies"].as_array().unwrap().len(), 5);
+    let renewed = body["discrepancies"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|d| d["subscription_id"] == "sub_renewed")
+        .unwrap();
+    assert_eq!(renewed["kind"], "missed_renewal");
+
+    // Partners without this org in scope can't see its runs
+    let (status, _) = operator_request(&f.state, &partner_key, "GET", &uri).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = operator_request(
+        &f.state,
+        &admin_key,
+        "GET",
+        "/operators/reconciliation-runs/missing",
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,188 @@
+//! Tests for license reference numbers: per-project numbering, admin
+//! endpoints and the license list taking them, and public lookups never
+//! resolving them.
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode},
+};
+use serde_json::Value;
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::handlers;
+
+struct NumberFixture {
+    state: AppState,
+    org_id: String,
+    project: Project,
+    product: Product,
+    license: License,
+    api_key: String,
+}
+
+/// A project with license key prefix `pro` and one license
+fn setup() -> NumberFixture {
+    let state = create_test_app_state();
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, api_key) =
+        create_test_org_member(&mut conn, &org.id, "admin@test.com", OrgMemberRole::Owner);
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    conn.execute(
+        "UPDATE projects SET license_key_prefix = 'pro' WHERE id = ?1",
+        [&project.id],
+    )
+    .unwrap();
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+    let license = create_test_license(&conn, &project.id, &product.id, None);
+
+    drop(conn);
+    NumberFixture {
+        state,
+        org_id: org.id,
+        project,
+        product,
+        license,
+        api_key,
+    }
+}
+
+fn app(state: &AppState) -> Router {
+    handlers::orgs::router(state.clone(), paycheck::config::RateLimitConfig::disabled())
+        .with_state(state.clone())
+}
+
+impl NumberFixture {
+    fn reference_number(&self) -> &str {
+        self.license.reference_number.as_deref().unwrap()
+    }
+
+    async fn get(&self, uri: &str) -> (StatusCode, Value) {
+        let response = app(&self.state)
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri(uri)
+                    .header("Authorization", format!("Bearer {}", self.api_key))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    fn licenses_uri(&self) -> String {
+        format!(
+            "/orgs/{}/projects/{}/licenses",
+            self.org_id, self.project.id
+        )
+    }
+}
+
+#[tokio::test]
+async fn test_licenses_are_numbered_per_project() {
+    let f = setup();
+    assert_eq!(f.reference_number(), "PRO-000001");
+
+    let conn = f.state.db.get().unwrap();
+    let second = create_test_license(&conn, &f.project.id, &f.product.id, None);
+    assert_eq!(second.reference_number.as_deref(), Some("PRO-000002"));
+
+    let other = create_test_project(&conn, &f.org_id, "Other", &test_master_key());
+    let other_product = create_test_product(&conn, &other.id, "Basic", "basic");
+    let other_license = create_test_license(&conn, &other.id, &other_product.id, None);
+    assert_eq!(
+        other_license.reference_number.as_deref(),
+        Some("PC-000001"),
+        "each project counts from one"
+    );
+
+    let stored = queries::get_license_by_id(&conn, &second.id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(stored.reference_number, second.reference_number);
+}
+
+#[tokio::test]
+async fn test_admin_endpoints_accept_reference_number() {
+    let f = setup();
+    let license_ref = f.license.license_ref.clone().unwrap();
+
+    for license in [
+        f.license.id.as_str(),
+        license_ref.as_str(),
+        f.reference_number(),
+        "pro-000001",
+    ] {
+        let (status, body) = f.get(&format!("{}/{}", f.licenses_uri(), license)).await;
+        assert_eq!(status, StatusCode::OK, "{}: {}", license, body);
+        assert_eq!(body["id"], f.license.id.as_str());
+        assert_eq!(body["reference_number"], f.reference_number());
+    }
+
+    let (status, _) = f.get(&format!("{}/PRO-000099", f.licenses_uri())).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_reference_number_from_another_project_is_not_found() {
+    let f = setup();
+    let conn = f.state.db.get().unwrap();
+    let other = create_test_project(&conn, &f.org_id, "Other", &test_master_key());
+    let other_product = create_test_product(&conn, &other.id, "Basic", "basic");
+    let other_license = create_test_license(&conn, &other.id, &other_product.id, None);
+    drop(conn);
+
+    let (status, _) = f
+        .get(&format!(
+            "{}/{}",
+            f.licenses_uri(),
+            other_license.reference_number.unwrap()
+        ))
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_license_list_filters_by_reference() {
+    let f = setup();
+    let conn = f.state.db.get().unwrap();
+    create_test_license(&conn, &f.project.id, &f.product.id, None);
+    drop(conn);
+
+    let (status, body) = f
+        .get(&format!("{}?reference=pro-000001", f.licenses_uri()))
+        .await;
+
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["total"], 1);
+    assert_eq!(body["items"][0]["id"], f.license.id.as_str());
+    assert_eq!(body["items"][0]["product_name"], "Pro Plan");
+}
+
+#[tokio::test]
+async fn test_public_lookups_ignore_reference_numbers() {
+    let f = setup();
+    let conn = f.state.db.get().unwrap();
+
+    assert_eq!(
+        queries::resolve_license_id(&conn, &f.project.id, f.reference_number()).unwrap(),
+        f.reference_number(),
+        "reference numbers are guessable, so only admin lookups resolve them"
+    );
+    assert_eq!(
+        queries::resolve_admin_license_id(&conn, &f.project.id, f.reference_number()).unwrap(),
+        f.license.id
+    );
+}
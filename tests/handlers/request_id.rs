@@ -0,0 +1,180 @@
+//! Tests for request IDs: the `X-Request-Id` response header, the
+//! `request_id` field in error bodies, and audit log entries tagged with the
+//! request that wrote them.
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode},
+    middleware,
+    response::Response,
+    routing::get,
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::error::AppError;
+use paycheck::handlers;
+use paycheck::middleware::{REQUEST_ID_HEADER, RequestIdConfig, request_id};
+
+/// Wrap `router` in the request ID middleware, as main does.
+fn with_request_id(router: Router, trust_incoming: bool) -> Router {
+    router.layer(middleware::from_fn_with_state(
+        RequestIdConfig { trust_incoming },
+        request_id,
+    ))
+}
+
+/// A route that always fails with a 500.
+fn failing_app(trust_incoming: bool) -> Router {
+    let router = Router::new().route(
+        "/fail",
+        get(|| async { Err::<(), _>(AppError::Internal("disk on fire".into())) }),
+    );
+    with_request_id(router, trust_incoming)
+}
+
+async fn send(app: Router, incoming_id: Option<&str>) -> Response {
+    let mut request = Request::builder().uri("/fail");
+    if let Some(id) = incoming_id {
+        request = request.header(REQUEST_ID_HEADER, id);
+    }
+    app.oneshot(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap()
+}
+
+fn response_id(response: &Response) -> String {
+    response
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .expect("every response carries X-Request-Id")
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+async fn body_json(response: Response) -> Value {
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    serde_json::from_slice(&bytes).unwrap()
+}
+
+#[tokio::test]
+async fn test_generated_id_in_header_and_500_body() {
+    let response = send(failing_app(false), None).await;
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+    let id = response_id(&response);
+    assert!(
+        uuid::Uuid::parse_str(&id).is_ok(),
+        "generated IDs are UUIDs"
+    );
+
+    let json = body_json(response).await;
+    assert_eq!(json["error"], "Internal server error");
+    assert_eq!(json["request_id"], id.as_str());
+}
+
+#[tokio::test]
+async fn test_each_request_gets_its_own_id() {
+    let first = response_id(&send(failing_app(false), None).await);
+    let second = response_id(&send(failing_app(false), None).await);
+    assert_ne!(first, second);
+}
+
+#[tokio::test]
+async fn test_trusted_incoming_id_round_trips() {
+    let response = send(failing_app(true), Some("edge-7f3a:42")).await;
+    assert_eq!(response_id(&response), "edge-7f3a:42");
+    assert_eq!(body_json(response).await["request_id"], "edge-7f3a:42");
+}
+
+#[tokio::test]
+async fn test_incoming_id_ignored_unless_trusted() {
+    let response = send(failing_app(false), Some("client-chosen")).await;
+    assert_ne!(response_id(&response), "client-chosen");
+}
+
+#[tokio::test]
+async fn test_malformed_incoming_id_replaced() {
+    let too_long = "a".repeat(129);
+    for incoming in ["has spaces in it", "<script>", too_long.as_str()] {
+        let response = send(failing_app(true), Some(incoming)).await;
+        let id = response_id(&response);
+        assert_ne!(id, incoming);
+        assert!(uuid::Uuid::parse_str(&id).is_ok());
+    }
+}
+
+#[tokio::test]
+async fn test_errors_outside_the_middleware_have_no_request_id() {
+    let router = Router::new().route(
+        "/fail",
+        get(|| async { Err::<(), _>(AppError::Internal("disk on fire".into())) }),
+    );
+    let response = send(router, None).await;
+    assert!(response.headers().get(REQUEST_ID_HEADER).is_none());
+    assert!(body_json(response).await.get("request_id").is_none());
+}
+
+#[tokio::test]
+async fn test_audit_log_records_request_id() {
+    let mut state = create_test_app_state();
+    state.audit_log_enabled = true;
+    let (org_id, project_id, api_key) = {
+        let mut conn = state.db.get().unwrap();
+        let org = create_test_org(&conn, "Test Org");
+        let (_, _, api_key) =
+            create_test_org_member(&mut conn, &org.id, "admin@test.com", OrgMemberRole::Owner);
+        let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+        (org.id, project.id, api_key)
+    };
+
+    let app = with_request_id(
+        handlers::orgs::router(state.clone(), paycheck::config::RateLimitConfig::disabled())
+            .with_state(state.clone()),
+        true,
+    );
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/orgs/{}/projects/{}/products", org_id, project_id))
+                .header("content-type", "application/json")
+                .header("Authorization", format!("Bearer {}", api_key))
+                .header(REQUEST_ID_HEADER, "support-ticket-1432")
+                .body(Body::from(
+                    json!({ "name": "Pro Plan", "tier": "pro", "features": [] }).to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response_id(&response), "support-ticket-1432");
+
+    let conn = state.audit.get().unwrap();
+    let query: AuditLogQuery =
+        serde_json::from_value(json!({ "request_id": "support-ticket-1432" })).unwrap();
+    let (logs, total) = queries::query_audit_logs(&conn, &query).unwrap();
+    assert_eq!(total, 1);
+    assert_eq!(logs[0].action, "create_product");
+    assert_eq!(logs[0].request_id.as_deref(), Some("support-ticket-1432"));
+}
+
+#[test]
+fn test_audit_log_outside_a_request_has_no_request_id() {
+    let conn = setup_test_audit_db();
+    let log = paycheck::util::AuditLogBuilder::new(&conn, true, &Default::default())
+        .action(AuditAction::CreateOrg)
+        .resource("org", "org_1")
+        .save()
+        .unwrap();
+    assert_eq!(log.request_id, None);
+}
@@ -0,0 +1,459 @@
+//! Integration tests for per-org data residency (dedicated SQLite files).
+//!
+//! These tests run against real files in a temp directory, since org databases
+//! attach the shared database by path.
+
+use axum::{Router, body::Body, http::Request};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::{
+    ONE_YEAR, create_test_license, create_test_operator, create_test_org, create_test_product,
+    create_test_project, public_app, queries, test_master_key,
+};
+
+use paycheck::db::{AppState, OrgDbRegistry, ProjectMissCache, create_pool};
+use paycheck::handlers;
+use paycheck::models::{BulkLicenseParams, CreateTerms, OperatorRole};
+use paycheck::payments::ProviderCallGovernor;
+use paycheck::util::Clock;
+
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::Connection;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+// ============================================================================
+// Test App Setup
+// ============================================================================
+
+struct ResidencyApp {
+    app: Router,
+    state: AppState,
+    api_key: String,
+    org_dir: PathBuf,
+    shared_path: String,
+    _dir: TempDir,
+}
+
+fn residency_app() -> ResidencyApp {
+    let dir = tempfile::tempdir().unwrap();
+    let shared_path = dir.path().join("paycheck.db").to_str().unwrap().to_string();
+    let org_dir = dir.path().join("orgs");
+
+    let pool = create_pool(&shared_path).unwrap();
+    {
+        let conn = pool.get().unwrap();
+        paycheck::db::init_db(&conn).unwrap();
+    }
+
+    let audit_manager = SqliteConnectionManager::memory();
+    let audit_pool = Pool::builder().max_size(4).build(audit_manager).unwrap();
+    {
+        let conn = audit_pool.get().unwrap();
+        paycheck::db::init_audit_db(&conn).unwrap();
+    }
+
+    let org_dbs = OrgDbRegistry::new(&org_dir, &shared_path);
+    {
+        let conn = pool.get().unwrap();
+        org_dbs.load(&conn, 0).unwrap();
+    }
+
+    let api_key = {
+        let mut conn = pool.get().unwrap();
+        let (_, key) = create_test_operator(&mut conn, "owner@test.com", OperatorRole::Owner);
+        key
+    };
+
+    let state = AppState {
+        db: pool,
+        audit: audit_pool,
+        base_url: "http://localhost:3000".to_string(),
+        audit_log_enabled: false,
+        audit_failure_mode: paycheck::models::AuditFailureMode::default(),
+        public_audit_retention_days: 0,
+        master_key: test_master_key(),
+        email_hasher: paycheck::crypto::EmailHasher::from_bytes([0xAA; 32]),
+        pii_minimization: false,
+        success_page_url: "http://localhost:3000/success".to_string(),
+        activation_rate_limiter: Arc::new(paycheck::rate_limit::ActivationRateLimiter::default()),
+        email_service: Arc::new(paycheck::email::EmailService::new(
+            None,
+            "test@example.com".to_string(),
+        )),
+        jwks_cache: Arc::new(paycheck::jwt::JwksCache::new()),
+        trusted_issuers: vec![],
+        org_dbs: Arc::new(org_dbs),
+        project_misses: Arc::new(ProjectMissCache::default()),
+        clock: Clock::system(),
+        provider_calls: Arc::new(ProviderCallGovernor::default()),
+        validation_limiter: Arc::new(paycheck::rate_limit::ValidationRateLimiter::new()),
+        free_license_limiter: Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_free_licenses(),
+        ),
+        session_wait_limiter: Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_session_waits(),
+        ),
+        allow_localhost_urls: false,
+        maintenance: std::sync::Arc::new(paycheck::middleware::MaintenanceMode::default()),
+        backups: None,
+    };
+
+    let app = handlers::operators::router(state.clone())
+        .merge(handlers::orgs::router(
+            state.clone(),
+            paycheck::config::RateLimitConfig::disabled(),
+        ))
+        .with_state(state.clone())
+        .merge(public_app(state.clone()));
+
+    ResidencyApp {
+        app,
+        state,
+        api_key,
+        org_dir,
+        shared_path,
+        _dir: dir,
+    }
+}
+
+async fn send(
+    app: &Router,
+    method: &str,
+    uri: &str,
+    api_key: Option<&str>,
+    body: Value,
+) -> (u16, Value) {
+    let mut builder = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("content-type", "application/json");
+    if let Some(key) = api_key {
+        builder = builder.header("Authorization", format!("Bearer {}", key));
+    }
+    let body = if method == "GET" {
+        Body::empty()
+    } else {
+        Body::from(serde_json::to_string(&body).unwrap())
+    };
+
+    let response = app
+        .clone()
+        .oneshot(builder.body(body).unwrap())
+        .await
+        .unwrap();
+    let status = response.status().as_u16();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+    (status, json)
+}
+
+fn count_rows(path: &Path, table: &str) -> i64 {
+    let conn = Connection::open(path).unwrap();
+    conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+        row.get(0)
+    })
+    .unwrap()
+}
+
+fn org_db_path(t: &ResidencyApp, org_id: &str) -> PathBuf {
+    t.org_dir.join(format!("org_{}.db", org_id))
+}
+
+/// Create an org, project, product, and license through the HTTP API.
+/// Returns (org_id, project_id, public_key, activation_code).
+async fn create_org_with_license(t: &ResidencyApp, name: &str) -> (String, String, String, String) {
+    let key = Some(t.api_key.as_str());
+
+    let (status, org) = send(
+        &t.app,
+        "POST",
+        "/operators/organizations",
+        key,
+        json!({ "name": name }),
+    )
+    .await;
+    assert_eq!(status, 200, "create org should succeed: {}", org);
+    let org_id = org["id"].as_str().unwrap().to_string();
+
+    let (status, project) = send(
+        &t.app,
+        "POST",
+        &format!("/orgs/{}/projects", org_id),
+        key,
+        json!({ "name": format!("{} App", name) }),
+    )
+    .await;
+    assert_eq!(status, 200, "create project should succeed: {}", project);
+    let project_id = project["id"].as_str().unwrap().to_string();
+    let public_key = project["public_key"].as_str().unwrap().to_string();
+
+    let (status, product) = send(
+        &t.app,
+        "POST",
+        &format!("/orgs/{}/projects/{}/products", org_id, project_id),
+        key,
+        json!({ "name": "Pro", "tier": "pro" }),
+    )
+    .await;
+    assert_eq!(status, 200, "create product should succeed: {}", product);
+    let product_id = product["id"].as_str().unwrap().to_string();
+
+    let (status, licenses) = send(
+        &t.app,
+        "POST",
+        &format!("/orgs/{}/projects/{}/licenses", org_id, project_id),
+        key,
+        json!({ "product_id": product_id, "customer_id": "cust-1" }),
+    )
+    .await;
+    assert_eq!(status, 200, "create license should succeed: {}", licenses);
+    let code = licenses["items"][0]["activation_code"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    (org_id, project_id, public_key, code)
+}
+
+// ============================================================================
+// Dedicated Org Databases
+// ============================================================================
+
+#[tokio::test]
+async fn test_new_orgs_get_separate_database_files() {
+    let t = residency_app();
+
+    let (org_a, _, _, _) = create_org_with_license(&t, "Org A").await;
+    let (org_b, _, _, _) = create_org_with_license(&t, "Org B").await;
+
+    let path_a = org_db_path(&t, &org_a);
+    let path_b = org_db_path(&t, &org_b);
+    assert!(path_a.exists(), "org A should have its own database file");
+    assert!(path_b.exists(), "org B should have its own database file");
+
+    // Each file holds only its own org's tenant data
+    for path in [&path_a, &path_b] {
+        assert_eq!(count_rows(path, "projects"), 1);
+        assert_eq!(count_rows(path, "products"), 1);
+        assert_eq!(count_rows(path, "licenses"), 1);
+    }
+
+    // Tenant tables in the shared database stay empty; org rows stay shared
+    let shared = Path::new(&t.shared_path);
+    assert_eq!(count_rows(shared, "projects"), 0);
+    assert_eq!(count_rows(shared, "licenses"), 0);
+    assert_eq!(count_rows(shared, "organizations"), 2);
+    assert_eq!(count_rows(shared, "org_databases"), 2);
+}
+
+#[tokio::test]
+async fn test_org_api_is_scoped_to_org_database() {
+    let t = residency_app();
+    let key = Some(t.api_key.as_str());
+
+    let (org_a, project_a, _, _) = create_org_with_license(&t, "Org A").await;
+    let (org_b, project_b, _, _) = create_org_with_license(&t, "Org B").await;
+
+    let (status, list) = send(
+        &t.app,
+        "GET",
+        &format!("/orgs/{}/projects", org_a),
+        key,
+        Value::Null,
+    )
+    .await;
+    assert_eq!(status, 200);
+    let ids: Vec<&str> = list["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        ids,
+        vec![project_a.as_str()],
+        "org A should only list its own project"
+    );
+
+    // Org B's project isn't reachable through org A's path
+    let (status, _) = send(
+        &t.app,
+        "GET",
+        &format!("/orgs/{}/projects/{}", org_a, project_b),
+        key,
+        Value::Null,
+    )
+    .await;
+    assert_eq!(status, 404, "cross-org project access should return 404");
+
+    let (status, licenses) = send(
+        &t.app,
+        "GET",
+        &format!("/orgs/{}/projects/{}/licenses", org_b, project_b),
+        key,
+        Value::Null,
+    )
+    .await;
+    assert_eq!(status, 200);
+    assert_eq!(licenses["total"], 1);
+}
+
+#[tokio::test]
+async fn test_public_endpoints_route_to_org_database() {
+    let t = residency_app();
+
+    let (org_a, _, public_key, code) = create_org_with_license(&t, "Org A").await;
+
+    let (status, redeemed) = send(
+        &t.app,
+        "POST",
+        "/redeem",
+        None,
+        json!({
+            "public_key": public_key,
+            "code": code,
+            "device_id": "device-1",
+            "device_type": "uuid"
+        }),
+    )
+    .await;
+    assert_eq!(
+        status, 200,
+        "redeem should find the license in the org file: {}",
+        redeemed
+    );
+    assert!(redeemed["token"].is_string());
+
+    let path_a = org_db_path(&t, &org_a);
+    assert_eq!(
+        count_rows(&path_a, "devices"),
+        1,
+        "device should be stored in the org file"
+    );
+    let jti: String = Connection::open(&path_a)
+        .unwrap()
+        .query_row("SELECT jti FROM devices", [], |row| row.get(0))
+        .unwrap();
+
+    let (status, validated) = send(
+        &t.app,
+        "POST",
+        "/validate",
+        None,
+        json!({ "public_key": public_key, "jti": jti }),
+    )
+    .await;
+    assert_eq!(status, 200);
+    assert_eq!(
+        validated["valid"], true,
+        "validate should resolve the org file by public key"
+    );
+}
+
+#[tokio::test]
+async fn test_hard_delete_org_removes_database_file() {
+    let t = residency_app();
+    let key = Some(t.api_key.as_str());
+
+    let (org_a, _, _, _) = create_org_with_license(&t, "Org A").await;
+    let path_a = org_db_path(&t, &org_a);
+    assert!(path_a.exists());
+
+    let (status, _) = send(
+        &t.app,
+        "POST",
+        &format!("/operators/organizations/{}/hard-delete", org_a),
+        key,
+        Value::Null,
+    )
+    .await;
+    assert_eq!(status, 200);
+
+    assert!(!path_a.exists(), "org database file should be removed");
+    assert!(t.state.org_dbs.get(&org_a).is_none());
+    assert_eq!(count_rows(Path::new(&t.shared_path), "org_databases"), 0);
+    assert_eq!(
+        count_rows(Path::new(&t.shared_path), "org_project_routes"),
+        0
+    );
+}
+
+// ============================================================================
+// Migration Tooling
+// ============================================================================
+
+#[tokio::test]
+async fn test_isolate_org_moves_legacy_org_into_dedicated_file() {
+    let t = residency_app();
+
+    // Legacy org created directly in the shared database (not provisioned)
+    let (org_id, project_id) = {
+        let conn = t.state.db.get().unwrap();
+        let org = create_test_org(&conn, "Legacy Org");
+        let project = create_test_project(&conn, &org.id, "Legacy App", &t.state.master_key);
+        let product = create_test_product(&conn, &project.id, "Pro", "pro");
+        let license = create_test_license(
+            &conn,
+            &project.id,
+            &product.id,
+            Some(common::future_timestamp(ONE_YEAR)),
+        );
+        let job = queries::create_bulk_job(
+            &conn,
+            &project.id,
+            &product.id,
+            1,
+            false,
+            &BulkLicenseParams::default(),
+            None,
+        )
+        .unwrap();
+        queries::record_bulk_job_chunk(&conn, &job.id, 0, &[license.id]).unwrap();
+        let terms = CreateTerms {
+            version: "2025-01".into(),
+            url: "https://example.com/terms".into(),
+            effective_at: None,
+        };
+        queries::create_terms(&conn, &project.id, &terms, 0, None).unwrap();
+        (org.id, project.id)
+    };
+
+    let mut conn = Connection::open(&t.shared_path).unwrap();
+    let registry = OrgDbRegistry::new(&t.org_dir, &t.shared_path);
+    let result = registry.isolate_org(&mut conn, &org_id).unwrap();
+
+    let path = org_db_path(&t, &org_id);
+    assert_eq!(Path::new(&result.db_path), path.as_path());
+    assert_eq!(count_rows(&path, "projects"), 1);
+    assert_eq!(count_rows(&path, "products"), 1);
+    assert_eq!(count_rows(&path, "licenses"), 1);
+    assert_eq!(count_rows(&path, "terms"), 1);
+    assert_eq!(count_rows(&path, "bulk_jobs"), 1);
+    assert_eq!(count_rows(&path, "bulk_job_chunks"), 1);
+
+    let shared = Path::new(&t.shared_path);
+    assert_eq!(
+        count_rows(shared, "projects"),
+        0,
+        "rows should be removed from shared"
+    );
+    assert_eq!(count_rows(shared, "licenses"), 0);
+    assert_eq!(count_rows(shared, "terms"), 0);
+    assert_eq!(count_rows(shared, "bulk_jobs"), 0);
+    assert_eq!(count_rows(shared, "bulk_job_chunks"), 0);
+    assert_eq!(
+        queries::get_project_route_org_id(&conn, &project_id).unwrap(),
+        Some(org_id.clone())
+    );
+
+    // Isolating twice is rejected
+    assert!(registry.isolate_org(&mut conn, &org_id).is_err());
+}
@@ -0,0 +1,546 @@
+//! Router self-test: the complete production router builds, its routes match
+//! the manifest below in both directions, and every route is mounted and can
+//! extract its path parameters.
+//!
+//! Adding, removing, or renaming a route means updating `ROUTES`, so route
+//! changes are deliberate. A handler whose `Path` struct doesn't match its
+//! route's segments (`license_id` against `{id}`) fails
+//! `test_every_route_extracts_its_path`.
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use serde_json::Value;
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use std::collections::BTreeSet;
+
+/// Every route the server serves, as (method, path) in router syntax.
+const ROUTES: &[(&str, &str)] = &[
+    // Public API
+    ("GET", "/buy"),
+    ("POST", "/buy"),
+    ("POST", "/activation/request-code"),
+    ("POST", "/redeem/prepaid"),
+    ("POST", "/redeem/lemonsqueezy"),
+    ("GET", "/callback"),
+    ("GET", "/buy/wait"),
+    ("POST", "/redeem"),
+    ("POST", "/redeem/claim"),
+    ("POST", "/refresh"),
+    ("POST", "/validate"),
+    ("GET", "/validate/attest"),
+    ("GET", "/updates/check"),
+    ("GET", "/download/{artifact_id}"),
+    ("GET", "/license"),
+    ("GET", "/discovery"),
+    ("GET", "/.well-known/jwks.json"),
+    ("GET", "/products"),
+    ("POST", "/devices/deactivate"),
+    ("GET", "/shared/license/{token}"),
+    ("GET", "/projects/{project_id}/health"),
+    ("GET", "/health"),
+    // Payment provider webhooks
+    ("POST", "/webhook/stripe"),
+    ("POST", "/webhook/lemonsqueezy"),
+    // Operator API (including the config, custom domain, and status page routers)
+    ("POST", "/operators"),
+    ("GET", "/operators"),
+    ("GET", "/operators/{user_id}"),
+    ("PUT", "/operators/{user_id}"),
+    ("DELETE", "/operators/{user_id}"),
+    ("GET", "/operators/{user_id}/org-scopes"),
+    ("POST", "/operators/{user_id}/org-scopes"),
+    ("DELETE", "/operators/{user_id}/org-scopes/{org_id}"),
+    ("POST", "/operators/maintenance-mode"),
+    ("POST", "/operators/users/{user_id}/merge"),
+    ("POST", "/operators/backups"),
+    ("POST", "/operators/backups/{backup_id}/verify"),
+    ("POST", "/operators/users"),
+    ("GET", "/operators/users"),
+    ("GET", "/operators/users/{user_id}"),
+    ("PUT", "/operators/users/{user_id}"),
+    ("DELETE", "/operators/users/{user_id}"),
+    ("POST", "/operators/users/{user_id}/restore"),
+    ("POST", "/operators/users/{user_id}/hard-delete"),
+    ("POST", "/operators/users/{user_id}/api-keys"),
+    ("GET", "/operators/users/{user_id}/api-keys"),
+    ("DELETE", "/operators/users/{user_id}/api-keys/{key_id}"),
+    ("POST", "/operators/jwks/refresh"),
+    ("GET", "/operators/backups"),
+    ("GET", "/operators/storage-health"),
+    ("POST", "/operators/workspaces"),
+    ("POST", "/operators/organizations"),
+    ("GET", "/operators/organizations"),
+    ("GET", "/operators/organizations/{org_id}"),
+    ("PUT", "/operators/organizations/{org_id}"),
+    ("DELETE", "/operators/organizations/{org_id}"),
+    ("POST", "/operators/organizations/{org_id}/restore"),
+    ("POST", "/operators/organizations/{org_id}/hard-delete"),
+    ("GET", "/operators/organizations/{org_id}/limits"),
+    (
+        "PUT",
+        "/operators/organizations/{org_id}/limits/{limit_name}",
+    ),
+    ("GET", "/operators/organizations/{org_id}/payment-provider"),
+    (
+        "GET",
+        "/operators/organizations/{org_id}/projects/{project_id}/licenses/lookup",
+    ),
+    (
+        "GET",
+        "/operators/organizations/{org_id}/projects/{project_id}/webhook-mirror",
+    ),
+    (
+        "PUT",
+        "/operators/organizations/{org_id}/projects/{project_id}/webhook-mirror",
+    ),
+    (
+        "DELETE",
+        "/operators/organizations/{org_id}/projects/{project_id}/webhook-mirror",
+    ),
+    ("POST", "/operators/reconcile"),
+    ("GET", "/operators/reconciliation-runs/{run_id}"),
+    ("GET", "/operators/maintenance-mode"),
+    ("GET", "/operators/audit-logs"),
+    ("GET", "/operators/audit-logs/text"),
+    ("GET", "/operators/audit-logs/export"),
+    ("GET", "/operators/audit-logs/verify-chain"),
+    ("POST", "/operators/audit-archives/verify"),
+    ("GET", "/operators/login-page"),
+    ("POST", "/operators/login-page"),
+    ("GET", "/operators/status-page"),
+    ("GET", "/operators/config"),
+    (
+        "GET",
+        "/operators/organizations/{org_id}/projects/{project_id}/custom-domains",
+    ),
+    (
+        "POST",
+        "/operators/organizations/{org_id}/projects/{project_id}/custom-domains",
+    ),
+    (
+        "DELETE",
+        "/operators/organizations/{org_id}/projects/{project_id}/custom-domains/{hostname}",
+    ),
+    (
+        "POST",
+        "/operators/organizations/{org_id}/projects/{project_id}/custom-domains/{hostname}/verify",
+    ),
+    // Organization API
+    ("POST", "/orgs/{org_id}/members"),
+    ("GET", "/orgs/{org_id}/members"),
+    ("GET", "/orgs/{org_id}/members/{user_id}"),
+    ("PUT", "/orgs/{org_id}/members/{user_id}"),
+    ("DELETE", "/orgs/{org_id}/members/{user_id}"),
+    ("POST", "/orgs/{org_id}/members/{user_id}/restore"),
+    ("POST", "/orgs/{org_id}/members/{user_id}/cancel-removal"),
+    ("POST", "/orgs/{org_id}/members/{user_id}/api-keys"),
+    ("GET", "/orgs/{org_id}/members/{user_id}/api-keys"),
+    (
+        "DELETE",
+        "/orgs/{org_id}/members/{user_id}/api-keys/{key_id}",
+    ),
+    ("POST", "/orgs/{org_id}/projects"),
+    ("GET", "/orgs/{org_id}/projects"),
+    ("POST", "/orgs/{org_id}/projects/{project_id}/restore"),
+    ("POST", "/orgs/{org_id}/product-templates"),
+    ("GET", "/orgs/{org_id}/product-templates"),
+    ("PUT", "/orgs/{org_id}/product-templates/{template_id}"),
+    ("DELETE", "/orgs/{org_id}/product-templates/{template_id}"),
+    ("GET", "/orgs/{org_id}/limits"),
+    ("GET", "/orgs/{org_id}/payment-provider"),
+    ("GET", "/orgs/{org_id}/email-config"),
+    ("PUT", "/orgs/{org_id}/email-config"),
+    ("POST", "/orgs/{org_id}/email-config/test"),
+    ("GET", "/orgs/{org_id}/compliance-settings"),
+    ("PUT", "/orgs/{org_id}/compliance-settings"),
+    ("GET", "/orgs/{org_id}/audit-logs"),
+    ("GET", "/orgs/{org_id}/audit-logs/export"),
+    ("POST", "/orgs/{org_id}/audit-logs/archive"),
+    ("GET", "/orgs/{org_id}/projects/{project_id}"),
+    ("PUT", "/orgs/{org_id}/projects/{project_id}"),
+    ("DELETE", "/orgs/{org_id}/projects/{project_id}"),
+    ("PUT", "/orgs/{org_id}/projects/{project_id}/client-flags"),
+    ("GET", "/orgs/{org_id}/projects/{project_id}/webhook-schema"),
+    ("GET", "/orgs/{org_id}/projects/{project_id}/email-webhook"),
+    (
+        "POST",
+        "/orgs/{org_id}/projects/{project_id}/diagnose-token",
+    ),
+    ("GET", "/orgs/{org_id}/projects/{project_id}/config-export"),
+    ("PUT", "/orgs/{org_id}/projects/{project_id}/config-import"),
+    (
+        "POST",
+        "/orgs/{org_id}/projects/{project_id}/apply-template",
+    ),
+    ("POST", "/orgs/{org_id}/projects/{project_id}/members"),
+    ("GET", "/orgs/{org_id}/projects/{project_id}/members"),
+    (
+        "GET",
+        "/orgs/{org_id}/projects/{project_id}/members/{user_id}",
+    ),
+    (
+        "PUT",
+        "/orgs/{org_id}/projects/{project_id}/members/{user_id}",
+    ),
+    (
+        "DELETE",
+        "/orgs/{org_id}/projects/{project_id}/members/{user_id}",
+    ),
+    (
+        "POST",
+        "/orgs/{org_id}/projects/{project_id}/members/{user_id}/temporary-role",
+    ),
+    (
+        "DELETE",
+        "/orgs/{org_id}/projects/{project_id}/members/{user_id}/temporary-role",
+    ),
+    (
+        "GET",
+        "/orgs/{org_id}/projects/{project_id}/temporary-roles",
+    ),
+    ("POST", "/orgs/{org_id}/projects/{project_id}/terms"),
+    ("GET", "/orgs/{org_id}/projects/{project_id}/terms"),
+    (
+        "PUT",
+        "/orgs/{org_id}/projects/{project_id}/terms/{terms_id}",
+    ),
+    ("POST", "/orgs/{org_id}/projects/{project_id}/artifacts"),
+    ("GET", "/orgs/{org_id}/projects/{project_id}/artifacts"),
+    (
+        "DELETE",
+        "/orgs/{org_id}/projects/{project_id}/artifacts/{artifact_id}",
+    ),
+    (
+        "GET",
+        "/orgs/{org_id}/projects/{project_id}/artifacts/{artifact_id}/downloads",
+    ),
+    (
+        "GET",
+        "/orgs/{org_id}/projects/{project_id}/download-signing-key",
+    ),
+    ("POST", "/orgs/{org_id}/projects/{project_id}/products"),
+    ("GET", "/orgs/{org_id}/projects/{project_id}/products"),
+    (
+        "GET",
+        "/orgs/{org_id}/projects/{project_id}/products/{product_id}",
+    ),
+    (
+        "PUT",
+        "/orgs/{org_id}/projects/{project_id}/products/{product_id}",
+    ),
+    (
+        "DELETE",
+        "/orgs/{org_id}/projects/{project_id}/products/{product_id}",
+    ),
+    (
+        "POST",
+        "/orgs/{org_id}/projects/{project_id}/products/{product_id}/restore",
+    ),
+    (
+        "POST",
+        "/orgs/{org_id}/projects/{project_id}/products/{product_id}/provider-links",
+    ),
+    (
+        "GET",
+        "/orgs/{org_id}/projects/{project_id}/products/{product_id}/provider-links",
+    ),
+    (
+        "GET",
+        "/orgs/{org_id}/projects/{project_id}/products/{product_id}/provider-links/{link_id}",
+    ),
+    (
+        "PUT",
+        "/orgs/{org_id}/projects/{project_id}/products/{product_id}/provider-links/{link_id}",
+    ),
+    (
+        "DELETE",
+        "/orgs/{org_id}/projects/{project_id}/products/{product_id}/provider-links/{link_id}",
+    ),
+    (
+        "POST",
+        "/orgs/{org_id}/projects/{project_id}/products/{product_id}/prepaid-codes",
+    ),
+    (
+        "GET",
+        "/orgs/{org_id}/projects/{project_id}/products/{product_id}/prepaid-codes",
+    ),
+    (
+        "GET",
+        "/orgs/{org_id}/projects/{project_id}/products/{product_id}/prepaid-codes/{batch_id}/csv",
+    ),
+    (
+        "POST",
+        "/orgs/{org_id}/projects/{project_id}/products/{product_id}/prepaid-codes/{batch_id}/revoke",
+    ),
+    (
+        "POST",
+        "/orgs/{org_id}/projects/{project_id}/license-imports/lemonsqueezy",
+    ),
+    ("GET", "/orgs/{org_id}/projects/{project_id}/licenses"),
+    ("POST", "/orgs/{org_id}/projects/{project_id}/licenses"),
+    (
+        "GET",
+        "/orgs/{org_id}/projects/{project_id}/bulk-jobs/{job_id}",
+    ),
+    (
+        "POST",
+        "/orgs/{org_id}/projects/{project_id}/bulk-jobs/{job_id}/cancel",
+    ),
+    (
+        "GET",
+        "/orgs/{org_id}/projects/{project_id}/bulk-jobs/{job_id}/csv",
+    ),
+    ("GET", "/orgs/{org_id}/projects/{project_id}/licenses/tags"),
+    (
+        "POST",
+        "/orgs/{org_id}/projects/{project_id}/licenses/tags/bulk",
+    ),
+    ("GET", "/orgs/{org_id}/projects/{project_id}/entitlements"),
+    (
+        "POST",
+        "/orgs/{org_id}/projects/{project_id}/entitlements/check",
+    ),
+    (
+        "GET",
+        "/orgs/{org_id}/projects/{project_id}/entitlements/suggestions",
+    ),
+    (
+        "GET",
+        "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}",
+    ),
+    (
+        "PATCH",
+        "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}",
+    ),
+    (
+        "POST",
+        "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/revoke",
+    ),
+    (
+        "POST",
+        "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/restore",
+    ),
+    (
+        "POST",
+        "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/send-code",
+    ),
+    (
+        "GET",
+        "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/claims-preview",
+    ),
+    ("GET", "/orgs/{org_id}/projects/{project_id}/email-log"),
+    ("GET", "/orgs/{org_id}/projects/{project_id}/disputes"),
+    ("GET", "/orgs/{org_id}/projects/{project_id}/activity"),
+    (
+        "GET",
+        "/orgs/{org_id}/projects/{project_id}/conversion-stats",
+    ),
+    ("GET", "/orgs/{org_id}/projects/{project_id}/usage/geo"),
+    ("GET", "/orgs/{org_id}/projects/{project_id}/usage/versions"),
+    (
+        "GET",
+        "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/seats",
+    ),
+    (
+        "POST",
+        "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/seats",
+    ),
+    (
+        "DELETE",
+        "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/seats/{seat_id}",
+    ),
+    (
+        "POST",
+        "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/tags",
+    ),
+    (
+        "DELETE",
+        "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/tags",
+    ),
+    (
+        "POST",
+        "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/share-link",
+    ),
+    (
+        "POST",
+        "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/share-link/{share_link_id}/revoke",
+    ),
+    (
+        "DELETE",
+        "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/devices/{device_id}",
+    ),
+    (
+        "POST",
+        "/orgs/{org_id}/projects/{project_id}/licenses/{license_id}/devices/deactivate-all",
+    ),
+    (
+        "POST",
+        "/orgs/{org_id}/projects/{project_id}/devices/deactivate-by-filter",
+    ),
+];
+
+/// The router sources `ROUTES` is checked against.
+const ROUTER_SOURCES: [&str; 4] = [
+    include_str!("../../src/handlers/public/mod.rs"),
+    include_str!("../../src/handlers/webhooks/mod.rs"),
+    include_str!("../../src/handlers/operators/mod.rs"),
+    include_str!("../../src/handlers/orgs/mod.rs"),
+];
+
+/// Stands in for IDs the request doesn't need to exist
+const DUMMY_ID: &str = "00000000-0000-4000-8000-000000000000";
+
+struct RouterFixture {
+    state: AppState,
+    org_id: String,
+    project_id: String,
+    /// The org owner's API key, for `/orgs` routes
+    owner_key: String,
+    /// An owner operator's API key, for `/operators` routes
+    operator_key: String,
+}
+
+/// An org and project that exist, so requests get past the auth middleware
+/// to the handler's own extractors.
+fn setup() -> RouterFixture {
+    let state = create_test_app_state();
+    let mut conn = state.db.get().unwrap();
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, owner_key) =
+        create_test_org_member(&mut conn, &org.id, "owner@test.com", OrgMemberRole::Owner);
+    let (_, operator_key) =
+        create_test_operator(&mut conn, "operator@test.com", OperatorRole::Owner);
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    drop(conn);
+
+    RouterFixture {
+        state,
+        org_id: org.id,
+        project_id: project.id,
+        owner_key,
+        operator_key,
+    }
+}
+
+impl RouterFixture {
+    /// `path` with its parameters filled in.
+    fn uri(&self, path: &str) -> String {
+        path.split('/')
+            .map(|segment| match segment {
+                "{org_id}" => self.org_id.as_str(),
+                "{project_id}" => self.project_id.as_str(),
+                "{hostname}" => "licenses.example.com",
+                s if s.starts_with('{') => DUMMY_ID,
+                s => s,
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Send `method path` with no body, authenticated for its API. Handlers
+    /// extract the path before the body, so writes are rejected for the
+    /// missing body after the path has been checked.
+    async fn send(&self, method: &str, path: &str) -> (StatusCode, Vec<u8>) {
+        let mut request = Request::builder().method(method).uri(self.uri(path));
+        if path.starts_with("/orgs/") {
+            request = request.header("Authorization", format!("Bearer {}", self.owner_key));
+        } else if path.starts_with("/operators") {
+            request = request.header("Authorization", format!("Bearer {}", self.operator_key));
+        }
+        let response = full_app(self.state.clone())
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, body.to_vec())
+    }
+}
+
+/// Whether the response is a failure to extract the path: our 400, or
+/// axum's own 500 for a parameter count it can't deserialize.
+fn is_path_error(status: StatusCode, body: &[u8]) -> bool {
+    let json: Value = serde_json::from_slice(body).unwrap_or(Value::Null);
+    json["error"] == "Invalid path parameters"
+        || (status.is_server_error()
+            && String::from_utf8_lossy(body)
+                .to_lowercase()
+                .contains("path"))
+}
+
+#[tokio::test]
+async fn test_full_router_builds() {
+    full_app(create_test_app_state());
+
+    // With the default rate limits too, as in production
+    let source =
+        paycheck::config::ConfigSource::from_parts(None, [("PAYCHECK_ENV", "dev")]).unwrap();
+    let config = paycheck::config::Config::from_source(&source).unwrap();
+    paycheck::handlers::app(create_test_app_state(), &config);
+}
+
+#[test]
+fn test_manifest_matches_registered_routes() {
+    let registered: BTreeSet<(String, String)> = ROUTER_SOURCES
+        .into_iter()
+        .flat_map(routes_in_source)
+        .collect();
+    let manifest: BTreeSet<(String, String)> = ROUTES
+        .iter()
+        .map(|(method, path)| (method.to_string(), path.to_string()))
+        .collect();
+    assert_eq!(manifest.len(), ROUTES.len(), "ROUTES lists a route twice");
+
+    let missing: Vec<_> = registered.difference(&manifest).collect();
+    let stale: Vec<_> = manifest.difference(&registered).collect();
+    assert!(
+        missing.is_empty(),
+        "routes registered but not in ROUTES: {:?}",
+        missing
+    );
+    assert!(
+        stale.is_empty(),
+        "routes in ROUTES that aren't registered: {:?}",
+        stale
+    );
+}
+
+#[tokio::test]
+async fn test_every_route_extracts_its_path() {
+    let mut failures = Vec::new();
+
+    for (method, path) in ROUTES {
+        // Fresh each time, since some of these requests delete things
+        let f = setup();
+        let (status, body) = f.send(method, path).await;
+
+        // The router's own 404/405 has no body; ours always do
+        if matches!(status.as_u16(), 404 | 405) && body.is_empty() {
+            failures.push(format!("{} {}: not mounted ({})", method, path, status));
+        } else if is_path_error(status, &body) {
+            failures.push(format!(
+                "{} {}: path parameters don't match the handler ({}: {})",
+                method,
+                path,
+                status,
+                String::from_utf8_lossy(&body)
+            ));
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} routes failed:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
@@ -0,0 +1,379 @@
+//! Tests for license share links: creation, the public page, expiry,
+//! revocation, and keeping sensitive license fields off the page.
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode},
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::handlers;
+
+const BUYER_EMAIL: &str = "buyer@example.com";
+const ORDER_ID: &str = "cs_test_order_8f3k2";
+const CUSTOMER_ID: &str = "cus_share_test_41z";
+
+struct ShareFixture {
+    state: AppState,
+    org_id: String,
+    project: Project,
+    license: License,
+    api_key: String,
+}
+
+fn setup() -> ShareFixture {
+    let mut state = create_test_app_state();
+    state.audit_log_enabled = true;
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let (_user, _member, api_key) =
+        create_test_org_member(&mut conn, &org.id, "admin@test.com", OrgMemberRole::Owner);
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+    let license = queries::create_license(
+        &conn,
+        &project.id,
+        &product.id,
+        &CreateLicense {
+            email_hash: Some(test_email_hasher().hash(&parse_email(BUYER_EMAIL))),
+            customer_id: Some("dev-customer-1".to_string()),
+            expires_at: Some(future_timestamp(365)),
+            updates_expires_at: Some(future_timestamp(365)),
+            payment_provider: Some("stripe".to_string()),
+            payment_provider_customer_id: Some(CUSTOMER_ID.to_string()),
+            payment_provider_subscription_id: None,
+            payment_provider_order_id: Some(ORDER_ID.to_string()),
+            seats: None,
+        },
+    )
+    .unwrap();
+    create_test_device(&conn, &license.id, "device-1", DeviceType::Uuid);
+
+    drop(conn);
+    ShareFixture {
+        state,
+        org_id: org.id,
+        project,
+        license,
+        api_key,
+    }
+}
+
+fn app(state: &AppState) -> Router {
+    public_app(state.clone()).merge(
+        handlers::orgs::router(state.clone(), paycheck::config::RateLimitConfig::disabled())
+            .with_state(state.clone()),
+    )
+}
+
+fn share_link_uri(f: &ShareFixture) -> String {
+    format!(
+        "/orgs/{}/projects/{}/licenses/{}/share-link",
+        f.org_id, f.project.id, f.license.id
+    )
+}
+
+async fn create_link(f: &ShareFixture, body: Value) -> (StatusCode, Value) {
+    let response = app(&f.state)
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(share_link_uri(f))
+                .header("content-type", "application/json")
+                .header("Authorization", format!("Bearer {}", f.api_key))
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (
+        status,
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    )
+}
+
+/// GET the public page. Returns status, content-type, cache-control, and body.
+async fn view(
+    f: &ShareFixture,
+    token: &str,
+    accept: Option<&str>,
+) -> (StatusCode, String, String, String) {
+    let mut request = Request::builder().uri(format!("/shared/license/{}", token));
+    if let Some(accept) = accept {
+        request = request.header("accept", accept);
+    }
+    let response = app(&f.state)
+        .oneshot(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let header = |name: &str| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string()
+    };
+    let (content_type, cache_control) = (header("content-type"), header("cache-control"));
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (
+        status,
+        content_type,
+        cache_control,
+        String::from_utf8_lossy(&bytes).into_owned(),
+    )
+}
+
+fn audit_actions(f: &ShareFixture) -> Vec<String> {
+    let conn = f.state.audit.get().unwrap();
+    let mut stmt = conn
+        .prepare("SELECT action FROM audit_logs WHERE resource_id = ?1 ORDER BY timestamp, rowid")
+        .unwrap();
+    stmt.query_map([&f.license.id], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_create_share_link_defaults_to_24_hours() {
+    let f = setup();
+    let before = now();
+
+    let (status, body) = create_link(&f, json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let token = body["token"].as_str().unwrap();
+    assert!(token.starts_with("psl_"));
+    assert_eq!(
+        body["url"],
+        format!("http://localhost:3000/shared/license/{}", token)
+    );
+    assert_eq!(body["license_id"], f.license.id.as_str());
+    assert_eq!(body["revoked"], false);
+    assert!(body["masked_email"].is_null());
+    assert!(body.get("token_hash").is_none(), "hash is never serialized");
+
+    let expires_at = body["expires_at"].as_i64().unwrap();
+    assert!(expires_at >= before + 24 * 3600 && expires_at <= now() + 24 * 3600);
+}
+
+#[tokio::test]
+async fn test_create_share_link_validates_input() {
+    let f = setup();
+
+    let (status, body) = create_link(&f, json!({ "email": "someone-else@example.com" })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["details"], "email does not match the license");
+
+    for hours in [0, 721] {
+        let (status, _) = create_link(&f, json!({ "expires_in_hours": hours })).await;
+        assert_eq!(
+            status,
+            StatusCode::BAD_REQUEST,
+            "{} hours should be rejected",
+            hours
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_shared_page_json_omits_sensitive_fields() {
+    let f = setup();
+    let (_, link) = create_link(&f, json!({ "email": BUYER_EMAIL })).await;
+    assert_eq!(link["masked_email"], "b***@example.com");
+
+    let (status, content_type, cache_control, body) = view(
+        &f,
+        link["token"].as_str().unwrap(),
+        Some("application/json"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(content_type.starts_with("application/json"));
+    assert_eq!(cache_control, "no-store");
+
+    let details: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(details["product_name"], "Pro Plan");
+    assert_eq!(
+        details["reference_number"],
+        f.license.reference_number.as_deref().unwrap()
+    );
+    assert_eq!(details["status"], "active");
+    assert_eq!(details["expires_at"], f.license.expires_at.unwrap());
+    assert_eq!(details["device_count"], 1);
+    assert_eq!(details["email"], "b***@example.com");
+
+    let email_hash = test_email_hasher().hash(&parse_email(BUYER_EMAIL));
+    for secret in [
+        email_hash.as_str(),
+        BUYER_EMAIL,
+        ORDER_ID,
+        CUSTOMER_ID,
+        "dev-customer-1",
+        f.license.id.as_str(),
+    ] {
+        assert!(
+            !body.contains(secret),
+            "response should not contain {}",
+            secret
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_shared_page_html_omits_sensitive_fields() {
+    let f = setup();
+    let conn = f.state.db.get().unwrap();
+    let code = queries::create_activation_code(&conn, &f.license.id, "PC").unwrap();
+    drop(conn);
+
+    let (_, link) = create_link(&f, json!({ "email": BUYER_EMAIL })).await;
+    let (status, content_type, _, body) = view(&f, link["token"].as_str().unwrap(), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(content_type.starts_with("text/html"));
+    assert!(body.contains("<h1>Pro Plan</h1>"));
+    assert!(body.contains("<dt>Reference</dt><dd>PC-000001</dd>"));
+    assert!(body.contains("b***@example.com"));
+    assert!(
+        body.contains("1 of 3"),
+        "device count against the product limit"
+    );
+
+    let email_hash = test_email_hasher().hash(&parse_email(BUYER_EMAIL));
+    for secret in [
+        email_hash.as_str(),
+        BUYER_EMAIL,
+        ORDER_ID,
+        CUSTOMER_ID,
+        code.code.as_str(),
+    ] {
+        assert!(!body.contains(secret), "page should not contain {}", secret);
+    }
+}
+
+#[tokio::test]
+async fn test_shared_page_shows_revoked_license_status() {
+    let f = setup();
+    let (_, link) = create_link(&f, json!({})).await;
+    queries::revoke_license(
+        &f.state.db.get().unwrap(),
+        &f.license.id,
+        &RevokeLicense::default(),
+        None,
+    )
+    .unwrap();
+
+    let (status, _, _, body) = view(
+        &f,
+        link["token"].as_str().unwrap(),
+        Some("application/json"),
+    )
+    .await;
+    assert_eq!(
+        status,
+        StatusCode::OK,
+        "revoking the license doesn't revoke the link"
+    );
+    assert_eq!(
+        serde_json::from_str::<Value>(&body).unwrap()["status"],
+        "revoked"
+    );
+}
+
+#[tokio::test]
+async fn test_expired_share_link_returns_404() {
+    let f = setup();
+    let (_, link) = create_link(&f, json!({ "expires_in_hours": 1 })).await;
+    f.state
+        .db
+        .get()
+        .unwrap()
+        .execute(
+            "UPDATE share_links SET expires_at = ?1 WHERE id = ?2",
+            rusqlite::params![now() - 1, link["id"].as_str().unwrap()],
+        )
+        .unwrap();
+
+    let (status, _, _, body) = view(
+        &f,
+        link["token"].as_str().unwrap(),
+        Some("application/json"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(
+        serde_json::from_str::<Value>(&body).unwrap()["details"],
+        "Share link is invalid, expired, or revoked"
+    );
+}
+
+#[tokio::test]
+async fn test_revoked_share_link_returns_404() {
+    let f = setup();
+    let (_, link) = create_link(&f, json!({})).await;
+    let token = link["token"].as_str().unwrap();
+    let revoke_uri = format!(
+        "{}/{}/revoke",
+        share_link_uri(&f),
+        link["id"].as_str().unwrap()
+    );
+
+    let (status, _, _, _) = view(&f, token, None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let revoke = || {
+        app(&f.state).oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(&revoke_uri)
+                .header("Authorization", format!("Bearer {}", f.api_key))
+                .body(Body::empty())
+                .unwrap(),
+        )
+    };
+    assert_eq!(revoke().await.unwrap().status(), StatusCode::OK);
+    assert_eq!(
+        revoke().await.unwrap().status(),
+        StatusCode::BAD_REQUEST,
+        "already revoked"
+    );
+
+    let (status, _, _, _) = view(&f, token, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_unknown_token_returns_404() {
+    let f = setup();
+    let (status, _, _, _) = view(&f, "psl_not_a_real_token", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_share_link_creation_and_views_are_audited() {
+    let f = setup();
+    let (_, link) = create_link(&f, json!({})).await;
+    let token = link["token"].as_str().unwrap();
+    view(&f, token, None).await;
+    view(&f, token, Some("application/json")).await;
+
+    assert_eq!(
+        audit_actions(&f),
+        ["create_share_link", "view_share_link", "view_share_link"]
+    );
+}
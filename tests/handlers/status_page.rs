@@ -0,0 +1,261 @@
+//! Tests for the embedded status page: API key login, the session cookie it
+//! sets, and the counts the page renders.
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode, header},
+};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::handlers;
+
+struct Fixture {
+    state: AppState,
+    operator_key: String,
+}
+
+/// Two orgs with three projects and four live licenses (plus a deleted one),
+/// and a view-only operator to log in with.
+fn setup() -> Fixture {
+    let state = create_test_app_state();
+    let mut conn = state.db.get().unwrap();
+    let master_key = test_master_key();
+
+    let org_a = create_test_org(&conn, "Org A");
+    let org_b = create_test_org(&conn, "Org B");
+    let project_a1 = create_test_project(&conn, &org_a.id, "A1", &master_key);
+    let project_a2 = create_test_project(&conn, &org_a.id, "A2", &master_key);
+    let project_b = create_test_project(&conn, &org_b.id, "B", &master_key);
+    for project in [&project_a1, &project_a2, &project_b] {
+        let product = create_test_product(&conn, &project.id, "Pro", "pro");
+        create_test_license(&conn, &project.id, &product.id, None);
+    }
+    let product = create_test_product(&conn, &project_a1.id, "Team", "team");
+    create_test_license(&conn, &project_a1.id, &product.id, None);
+    let deleted = create_test_license(&conn, &project_a1.id, &product.id, None);
+    conn.execute(
+        "UPDATE licenses SET deleted_at = ?1 WHERE id = ?2",
+        rusqlite::params![now(), deleted.id],
+    )
+    .unwrap();
+
+    let (_, operator_key) = create_test_operator(&mut conn, "viewer@test.com", OperatorRole::View);
+
+    drop(conn);
+    Fixture {
+        state,
+        operator_key,
+    }
+}
+
+impl Fixture {
+    fn app(&self) -> Router {
+        handlers::operators::status_page_router().with_state(self.state.clone())
+    }
+
+    /// POST the login form. Returns the status and the `name=value` part of
+    /// the session cookie, if one was set.
+    async fn login(&self, api_key: &str) -> (StatusCode, Option<String>) {
+        let response = self
+            .app()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/operators/login-page")
+                    .header("content-type", "application/x-www-form-urlencoded")
+                    .body(Body::from(format!("api_key={}", api_key)))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let cookie = response
+            .headers()
+            .get(header::SET_COOKIE)
+            .map(|v| v.to_str().unwrap().to_string());
+        if let Some(ref cookie) = cookie {
+            assert!(cookie.contains("HttpOnly"), "{}", cookie);
+            assert!(cookie.contains("SameSite=Strict"), "{}", cookie);
+        }
+        let cookie = cookie.map(|c| c.split(';').next().unwrap().to_string());
+        (response.status(), cookie)
+    }
+
+    /// GET the status page. Returns the status, the Location header, and the body.
+    async fn status_page(&self, cookie: Option<&str>) -> (StatusCode, Option<String>, String) {
+        let mut request = Request::builder().uri("/operators/status-page");
+        if let Some(cookie) = cookie {
+            request = request.header(header::COOKIE, cookie);
+        }
+        let response = self
+            .app()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let location = response
+            .headers()
+            .get(header::LOCATION)
+            .map(|v| v.to_str().unwrap().to_string());
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, location, String::from_utf8(bytes.to_vec()).unwrap())
+    }
+}
+
+// ============ Login ============
+
+#[tokio::test]
+async fn test_page_without_session_redirects_to_login() {
+    let f = setup();
+
+    let (status, location, _) = f.status_page(None).await;
+
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(location.as_deref(), Some("/operators/login-page"));
+}
+
+#[tokio::test]
+async fn test_login_page_renders_form() {
+    let f = setup();
+
+    let response = f
+        .app()
+        .oneshot(
+            Request::builder()
+                .uri("/operators/login-page")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CACHE_CONTROL], "no-store");
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let body = String::from_utf8(bytes.to_vec()).unwrap();
+    assert!(body.contains(r#"<form method="post" action="/operators/login-page">"#));
+    assert!(body.contains(r#"type="password""#));
+}
+
+#[tokio::test]
+async fn test_unknown_key_is_refused() {
+    let f = setup();
+
+    let (status, cookie) = f.login("pc_not_a_real_key").await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(cookie.is_none());
+}
+
+#[tokio::test]
+async fn test_org_member_key_is_refused() {
+    let f = setup();
+    let member_key = {
+        let mut conn = f.state.db.get().unwrap();
+        let org = create_test_org(&conn, "Member Org");
+        let (_, _, key) =
+            create_test_org_member(&mut conn, &org.id, "member@test.com", OrgMemberRole::Owner);
+        key
+    };
+
+    let (status, cookie) = f.login(&member_key).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(cookie.is_none());
+}
+
+#[tokio::test]
+async fn test_partner_key_is_refused() {
+    let f = setup();
+    let partner_key = {
+        let mut conn = f.state.db.get().unwrap();
+        create_test_operator(&mut conn, "partner@test.com", OperatorRole::Partner).1
+    };
+
+    let (status, cookie) = f.login(&partner_key).await;
+
+    assert_eq!(
+        status,
+        StatusCode::UNAUTHORIZED,
+        "partners only see their own orgs, so the all-org status page is off limits"
+    );
+    assert!(cookie.is_none());
+}
+
+// ============ Status page ============
+
+#[tokio::test]
+async fn test_operator_login_shows_seeded_counts() {
+    let f = setup();
+
+    let (status, cookie) = f.login(&f.operator_key).await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let cookie = cookie.expect("login should set a session cookie");
+
+    let (status, _, body) = f.status_page(Some(&cookie)).await;
+
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert!(
+        body.contains(r#"<dd id="organizations">2</dd>"#),
+        "{}",
+        body
+    );
+    assert!(body.contains(r#"<dd id="projects">3</dd>"#), "{}", body);
+    assert!(
+        body.contains(r#"<dd id="licenses">4</dd>"#),
+        "deleted licenses should not be counted: {}",
+        body
+    );
+    assert!(body.contains("viewer@test.com"));
+    assert!(
+        !body.contains("<form"),
+        "the status page should be read-only"
+    );
+}
+
+#[tokio::test]
+async fn test_tampered_cookie_is_rejected() {
+    let f = setup();
+    let (_, cookie) = f.login(&f.operator_key).await;
+    let cookie = cookie.unwrap();
+
+    // Push the expiry out without re-signing
+    let (name, value) = cookie.split_once('=').unwrap();
+    let mut parts: Vec<&str> = value.split('.').collect();
+    let later = future_timestamp(ONE_DAY).to_string();
+    parts[1] = &later;
+    let tampered = format!("{}={}", name, parts.join("."));
+
+    let (status, location, _) = f.status_page(Some(&tampered)).await;
+
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(location.as_deref(), Some("/operators/login-page"));
+}
+
+#[tokio::test]
+async fn test_revoking_key_ends_session() {
+    let f = setup();
+    let (_, cookie) = f.login(&f.operator_key).await;
+    let cookie = cookie.unwrap();
+    {
+        let conn = f.state.db.get().unwrap();
+        let (_, api_key) =
+            queries::get_user_by_api_key(&conn, &f.operator_key, &test_email_column())
+                .unwrap()
+                .unwrap();
+        queries::revoke_api_key(&conn, &api_key.id).unwrap();
+    }
+
+    let (status, location, _) = f.status_page(Some(&cookie)).await;
+
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    assert_eq!(location.as_deref(), Some("/operators/login-page"));
+}
@@ -0,0 +1,260 @@
+//! Tests for storage health: clock drift detection, heartbeat rows in the
+//! main and audit databases, noticing a replaced or moved database at
+//! startup, and the operator endpoint.
+
+use std::path::PathBuf;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use serde_json::Value;
+use tempfile::TempDir;
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::handlers;
+use paycheck::storage_health::{
+    self, HEARTBEAT_STALL_SECS, MAX_CLOCK_DRIFT_MS, clock_drift_ms, is_clock_drifting,
+    is_heartbeat_stalled,
+};
+
+struct StorageFixture {
+    state: AppState,
+    dir: TempDir,
+    admin_key: String,
+    view_key: String,
+}
+
+fn file_pool(path: &std::path::Path) -> Pool<SqliteConnectionManager> {
+    Pool::builder()
+        .max_size(2)
+        .build(SqliteConnectionManager::file(path))
+        .unwrap()
+}
+
+/// Test state on database files, so paths and sizes are real.
+fn setup() -> StorageFixture {
+    let dir = TempDir::new().unwrap();
+    let mut state = create_test_app_state();
+    state.db = file_pool(&dir.path().join("paycheck.db"));
+    state.audit = file_pool(&dir.path().join("paycheck_audit.db"));
+    init_db(&state.db.get().unwrap()).unwrap();
+    init_audit_db(&state.audit.get().unwrap()).unwrap();
+
+    let mut conn = state.db.get().unwrap();
+    let (_, admin_key) = create_test_operator(&mut conn, "admin@test.com", OperatorRole::Admin);
+    let (_, view_key) = create_test_operator(&mut conn, "view@test.com", OperatorRole::View);
+
+    drop(conn);
+    StorageFixture {
+        state,
+        dir,
+        admin_key,
+        view_key,
+    }
+}
+
+impl StorageFixture {
+    fn path(&self, file: &str) -> PathBuf {
+        self.dir.path().join(file)
+    }
+
+    async fn get_health(&self, key: &str) -> (StatusCode, Value) {
+        let app = handlers::operators::router(self.state.clone()).with_state(self.state.clone());
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri("/operators/storage-health")
+                    .header("Authorization", format!("Bearer {}", key))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+}
+
+fn problems(database: &Value) -> Vec<&str> {
+    database["problems"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p.as_str().unwrap())
+        .collect()
+}
+
+#[test]
+fn test_clock_drift_measured_from_midpoint() {
+    // The process clock read 9.0s and 9.4s either side of a database reading
+    // of 10.0s: the database is 0.8s ahead
+    assert_eq!(clock_drift_ms(10_000, 9_000, 9_400), 800);
+    assert_eq!(clock_drift_ms(6_000, 9_000, 9_000), -3_000);
+
+    assert!(!is_clock_drifting(0));
+    assert!(!is_clock_drifting(MAX_CLOCK_DRIFT_MS));
+    assert!(!is_clock_drifting(-MAX_CLOCK_DRIFT_MS));
+    assert!(is_clock_drifting(MAX_CLOCK_DRIFT_MS + 1));
+    assert!(is_clock_drifting(-(MAX_CLOCK_DRIFT_MS + 1)));
+}
+
+#[test]
+fn test_database_clock_agrees_with_process_clock() {
+    let conn = setup_test_db();
+    let drift = storage_health::measure_clock_drift(&conn).unwrap();
+    assert!(!is_clock_drifting(drift), "drift {} ms", drift);
+}
+
+#[test]
+fn test_heartbeat_stalls_after_missed_ticks() {
+    let now = now();
+    let conn = setup_test_db();
+    assert!(is_heartbeat_stalled(None, now));
+
+    let heartbeat =
+        queries::record_storage_start(&conn, "main", "", now - HEARTBEAT_STALL_SECS).unwrap();
+    assert!(!is_heartbeat_stalled(Some(&heartbeat), now));
+    assert!(is_heartbeat_stalled(Some(&heartbeat), now + 1));
+}
+
+#[test]
+fn test_beat_copies_audit_heartbeat_into_main() {
+    let f = setup();
+    let main = f.state.db.get().unwrap();
+    let audit = f.state.audit.get().unwrap();
+
+    storage_health::record_start(&main, &audit, 1000).unwrap();
+    storage_health::beat(&main, &audit, 1300).unwrap();
+    storage_health::beat(&main, &audit, 1600).unwrap();
+
+    let main_beat = queries::get_storage_heartbeat(&main, "main")
+        .unwrap()
+        .unwrap();
+    assert_eq!(main_beat.beat, 3);
+    assert_eq!(main_beat.started_at, 1000);
+    assert_eq!(main_beat.beat_at, 1600);
+    assert_eq!(main_beat.path, f.path("paycheck.db").to_str().unwrap());
+
+    let audit_beat = queries::get_storage_heartbeat(&audit, "audit")
+        .unwrap()
+        .unwrap();
+    assert_eq!(audit_beat.beat, 3);
+    assert_eq!(
+        audit_beat.path,
+        f.path("paycheck_audit.db").to_str().unwrap()
+    );
+    assert_eq!(
+        queries::get_storage_heartbeat(&main, "audit").unwrap(),
+        Some(audit_beat)
+    );
+    assert!(
+        storage_health::check_continuity(&main, &audit)
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn test_replaced_audit_database_detected() {
+    let f = setup();
+    let main = f.state.db.get().unwrap();
+    let audit = f.state.audit.get().unwrap();
+    storage_health::record_start(&main, &audit, 1000).unwrap();
+    storage_health::beat(&main, &audit, 1300).unwrap();
+
+    // The node is replaced and the audit database comes back empty
+    let fresh_audit = setup_test_audit_db();
+    let warnings = storage_health::check_continuity(&main, &fresh_audit).unwrap();
+    assert_eq!(warnings.len(), 1, "{:?}", warnings);
+    assert!(
+        warnings[0].contains("at heartbeat 0") && warnings[0].contains("reach 2"),
+        "{}",
+        warnings[0]
+    );
+
+    // Restored from a copy taken before the last beat
+    queries::record_storage_start(&fresh_audit, "audit", "", 1000).unwrap();
+    let warnings = storage_health::check_continuity(&main, &fresh_audit).unwrap();
+    assert!(
+        warnings.iter().any(|w| w.contains("at heartbeat 1")),
+        "{:?}",
+        warnings
+    );
+}
+
+#[test]
+fn test_moved_database_detected() {
+    let f = setup();
+    let main = f.state.db.get().unwrap();
+    let audit = f.state.audit.get().unwrap();
+    storage_health::record_start(&main, &audit, 1000).unwrap();
+    queries::record_storage_start(&main, "main", "/var/lib/old/paycheck.db", 1000).unwrap();
+
+    let warnings = storage_health::check_continuity(&main, &audit).unwrap();
+    assert_eq!(warnings.len(), 1, "{:?}", warnings);
+    assert!(
+        warnings[0].contains("main database moved from /var/lib/old/paycheck.db"),
+        "{}",
+        warnings[0]
+    );
+}
+
+#[tokio::test]
+async fn test_storage_health_reports_both_databases() {
+    let f = setup();
+    storage_health::record_server_start(&f.state).unwrap();
+
+    let (status, body) = f.get_health(&f.admin_key).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let databases = body["databases"].as_array().unwrap();
+    assert_eq!(databases.len(), 2);
+
+    for (database, name, file) in [
+        (&databases[0], "main", "paycheck.db"),
+        (&databases[1], "audit", "paycheck_audit.db"),
+    ] {
+        assert_eq!(database["db"], name);
+        assert_eq!(database["path"], f.path(file).to_str().unwrap());
+        assert!(database["size_bytes"].as_u64().unwrap() > 0);
+        assert!(database["free_bytes"].as_u64().unwrap() > 0);
+        assert_eq!(database["heartbeat"]["beat"], 1);
+        assert!(!problems(database).contains(&"heartbeat_stalled"));
+        assert!(!problems(database).contains(&"clock_drift"));
+    }
+}
+
+#[tokio::test]
+async fn test_storage_health_flags_stalled_heartbeat() {
+    let mut f = setup();
+    storage_health::record_server_start(&f.state).unwrap();
+    f.state.clock = Clock::fixed(now() + HEARTBEAT_STALL_SECS + 60);
+
+    let (status, body) = f.get_health(&f.admin_key).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["healthy"], false);
+    for database in body["databases"].as_array().unwrap() {
+        assert!(
+            problems(database).contains(&"heartbeat_stalled"),
+            "{}",
+            body
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_storage_health_requires_admin_operator() {
+    let f = setup();
+    let (status, _) = f.get_health(&f.view_key).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
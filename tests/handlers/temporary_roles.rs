@@ -0,0 +1,340 @@
+//! Tests for temporary project roles: elevation while a grant is active,
+//! expiry checked against the clock at auth time, early revocation, and the
+//! audit trail of grants and their use.
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode},
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::handlers;
+
+const NOW: i64 = 1_750_000_000;
+
+struct GrantFixture {
+    state: AppState,
+    org_id: String,
+    project_id: String,
+    owner_key: String,
+    /// Org member with view access to the project
+    viewer: User,
+    viewer_key: String,
+    /// Org member with no access to the project
+    outsider: User,
+    outsider_key: String,
+}
+
+fn setup() -> GrantFixture {
+    let mut state = create_test_app_state();
+    state.audit_log_enabled = true;
+    state.clock = Clock::fixed(NOW);
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, owner_key) =
+        create_test_org_member(&mut conn, &org.id, "owner@test.com", OrgMemberRole::Owner);
+    let (viewer, viewer_member, viewer_key) =
+        create_test_org_member(&mut conn, &org.id, "oncall@test.com", OrgMemberRole::Member);
+    let (outsider, _, outsider_key) = create_test_org_member(
+        &mut conn,
+        &org.id,
+        "support@test.com",
+        OrgMemberRole::Member,
+    );
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    create_test_project_member(
+        &conn,
+        &viewer_member.id,
+        &project.id,
+        ProjectMemberRole::View,
+    );
+
+    drop(conn);
+    GrantFixture {
+        state,
+        org_id: org.id,
+        project_id: project.id,
+        owner_key,
+        viewer,
+        viewer_key,
+        outsider,
+        outsider_key,
+    }
+}
+
+impl GrantFixture {
+    fn set_time(&mut self, now: i64) {
+        self.state.clock = Clock::fixed(now);
+    }
+
+    fn app(&self) -> Router {
+        handlers::orgs::router(
+            self.state.clone(),
+            paycheck::config::RateLimitConfig::disabled(),
+        )
+        .with_state(self.state.clone())
+    }
+
+    /// Send a request to a path under the project.
+    async fn send(
+        &self,
+        method: &str,
+        path: &str,
+        api_key: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(format!(
+                "/orgs/{}/projects/{}{}",
+                self.org_id, self.project_id, path
+            ))
+            .header("content-type", "application/json")
+            .header("Authorization", format!("Bearer {}", api_key))
+            .body(body.map_or_else(Body::empty, |b| Body::from(b.to_string())))
+            .unwrap();
+        let response = self.app().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        )
+    }
+
+    async fn grant(&self, user: &User, role: &str, minutes: i64) -> (StatusCode, Value) {
+        self.send(
+            "POST",
+            &format!("/members/{}/temporary-role", user.id),
+            &self.owner_key,
+            Some(json!({ "role": role, "expires_in_minutes": minutes, "reason": "INC-482" })),
+        )
+        .await
+    }
+
+    /// Creating a product requires project write access.
+    async fn create_product(&self, api_key: &str) -> StatusCode {
+        self.send(
+            "POST",
+            "/products",
+            api_key,
+            Some(json!({ "name": "Pro Plan", "tier": "pro", "features": [] })),
+        )
+        .await
+        .0
+    }
+
+    async fn list_products(&self, api_key: &str) -> StatusCode {
+        self.send("GET", "/products", api_key, None).await.0
+    }
+
+    fn audit_actions(&self) -> Vec<String> {
+        let conn = self.state.audit.get().unwrap();
+        let query: AuditLogQuery =
+            serde_json::from_value(json!({ "resource_type": "temporary_role_grant" })).unwrap();
+        let (logs, _) = queries::query_audit_logs(&conn, &query).unwrap();
+        let mut actions: Vec<String> = logs.into_iter().map(|l| l.action).collect();
+        actions.sort();
+        actions
+    }
+}
+
+#[tokio::test]
+async fn test_grant_raises_role_until_expiry() {
+    let mut f = setup();
+    assert_eq!(f.create_product(&f.viewer_key).await, StatusCode::FORBIDDEN);
+
+    let (status, grant) = f.grant(&f.viewer, "admin", 30).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(grant["user_id"], f.viewer.id.as_str());
+    assert_eq!(grant["role"], "admin");
+    assert_eq!(grant["expires_at"], NOW + 30 * 60);
+    assert_eq!(grant["reason"], "INC-482");
+    assert!(grant.get("org_member_id").is_none());
+
+    // Last second of the grant
+    f.set_time(NOW + 30 * 60 - 1);
+    assert_eq!(f.create_product(&f.viewer_key).await, StatusCode::OK);
+
+    // At expires_at the member is back to their permanent view role
+    f.set_time(NOW + 30 * 60);
+    assert_eq!(f.create_product(&f.viewer_key).await, StatusCode::FORBIDDEN);
+    assert_eq!(f.list_products(&f.viewer_key).await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_grant_gives_access_to_member_without_project_role() {
+    let mut f = setup();
+    assert_eq!(
+        f.list_products(&f.outsider_key).await,
+        StatusCode::NOT_FOUND
+    );
+
+    let (status, _) = f.grant(&f.outsider, "view", 60).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(f.list_products(&f.outsider_key).await, StatusCode::OK);
+    assert_eq!(
+        f.create_product(&f.outsider_key).await,
+        StatusCode::FORBIDDEN
+    );
+
+    f.set_time(NOW + 60 * 60);
+    assert_eq!(
+        f.list_products(&f.outsider_key).await,
+        StatusCode::NOT_FOUND
+    );
+}
+
+#[tokio::test]
+async fn test_revocation_takes_effect_immediately() {
+    let f = setup();
+    f.grant(&f.viewer, "admin", 60).await;
+    assert_eq!(f.create_product(&f.viewer_key).await, StatusCode::OK);
+
+    let path = format!("/members/{}/temporary-role", f.viewer.id);
+    let (status, revoked) = f.send("DELETE", &path, &f.owner_key, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(revoked["revoked_at"], NOW);
+
+    // Same instant, no background job: the next request is already denied
+    assert_eq!(f.create_product(&f.viewer_key).await, StatusCode::FORBIDDEN);
+
+    let (status, _) = f.send("DELETE", &path, &f.owner_key, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_member_can_end_own_grant() {
+    let f = setup();
+    f.grant(&f.viewer, "admin", 60).await;
+
+    let path = format!("/members/{}/temporary-role", f.viewer.id);
+    let (status, revoked) = f.send("DELETE", &path, &f.viewer_key, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(revoked["revoked_by"], f.viewer.id.as_str());
+    assert_eq!(f.create_product(&f.viewer_key).await, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_duration_limits() {
+    let f = setup();
+    for minutes in [0, -5, 24 * 60 + 1] {
+        let (status, _) = f.grant(&f.viewer, "admin", minutes).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{} minutes", minutes);
+    }
+
+    let (status, grant) = f.grant(&f.viewer, "admin", 24 * 60).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(grant["expires_at"], NOW + 24 * 60 * 60);
+}
+
+#[tokio::test]
+async fn test_new_grant_replaces_active_one() {
+    let f = setup();
+    f.grant(&f.viewer, "admin", 60).await;
+    f.grant(&f.viewer, "admin", 10).await;
+
+    let (_, active) = f.send("GET", "/temporary-roles", &f.owner_key, None).await;
+    assert_eq!(active["total"], 1);
+    assert_eq!(active["items"][0]["expires_at"], NOW + 10 * 60);
+
+    let (_, all) = f
+        .send(
+            "GET",
+            "/temporary-roles?include_inactive=true",
+            &f.owner_key,
+            None,
+        )
+        .await;
+    assert_eq!(all["total"], 2);
+}
+
+#[tokio::test]
+async fn test_grant_restrictions() {
+    let mut f = setup();
+    let mut conn = f.state.db.get().unwrap();
+    let (admin, _, _) =
+        create_test_org_member(&mut conn, &f.org_id, "admin@test.com", OrgMemberRole::Admin);
+    drop(conn);
+
+    // Org admins already have access to every project
+    let (status, _) = f.grant(&admin, "admin", 60).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // A temporary admin cannot hand out access or make their own permanent
+    f.grant(&f.viewer, "admin", 60).await;
+    let (status, _) = f
+        .send(
+            "POST",
+            &format!("/members/{}/temporary-role", f.outsider.id),
+            &f.viewer_key,
+            Some(json!({ "role": "view", "expires_in_minutes": 60 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = f
+        .send(
+            "PUT",
+            &format!("/members/{}", f.viewer.id),
+            &f.viewer_key,
+            Some(json!({ "role": "admin" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // Permanent project admins can grant, but not to themselves
+    f.set_time(NOW + 60 * 60);
+    {
+        let conn = f.state.db.get().unwrap();
+        conn.execute(
+            "UPDATE project_members SET role = 'admin' WHERE project_id = ?1",
+            [&f.project_id],
+        )
+        .unwrap();
+    }
+    let (status, _) = f
+        .send(
+            "POST",
+            &format!("/members/{}/temporary-role", f.viewer.id),
+            &f.viewer_key,
+            Some(json!({ "role": "admin", "expires_in_minutes": 60 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_grant_use_and_revoke_are_audited() {
+    let f = setup();
+    f.grant(&f.viewer, "admin", 60).await;
+
+    // Reads under a grant are not logged as use; writes are
+    assert_eq!(f.list_products(&f.viewer_key).await, StatusCode::OK);
+    assert_eq!(f.create_product(&f.viewer_key).await, StatusCode::OK);
+
+    f.send(
+        "DELETE",
+        &format!("/members/{}/temporary-role", f.viewer.id),
+        &f.owner_key,
+        None,
+    )
+    .await;
+
+    assert_eq!(
+        f.audit_actions(),
+        [
+            "grant_temporary_role",
+            "revoke_temporary_role",
+            "use_temporary_role"
+        ]
+    );
+}
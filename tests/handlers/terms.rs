@@ -0,0 +1,346 @@
+//! Tests for terms of sale: publishing versions, the current version in the
+//! catalog and /buy, refusing purchases that accepted stale or no terms, and
+//! freezing a version once a license is sold under it.
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    response::Response,
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::config::RateLimitConfig;
+use paycheck::handlers;
+
+struct TermsFixture {
+    state: AppState,
+    org_id: String,
+    project: Project,
+    api_key: String,
+    /// Free, so /buy issues its license without a payment provider
+    product: Product,
+}
+
+fn setup() -> TermsFixture {
+    let state = create_test_app_state();
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, api_key) =
+        create_test_org_member(&mut conn, &org.id, "admin@test.com", OrgMemberRole::Owner);
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    let product = create_test_product(&conn, &project.id, "Community", "community");
+    conn.execute(
+        "UPDATE products SET price_cents = 0 WHERE id = ?1",
+        rusqlite::params![product.id],
+    )
+    .unwrap();
+    let product = queries::get_product_by_id(&conn, &product.id)
+        .unwrap()
+        .unwrap();
+
+    drop(conn);
+    TermsFixture {
+        state,
+        org_id: org.id,
+        project,
+        api_key,
+        product,
+    }
+}
+
+impl TermsFixture {
+    async fn org(&self, method: &str, path: &str, body: Value) -> (StatusCode, Value) {
+        let app = handlers::orgs::router(self.state.clone(), RateLimitConfig::disabled())
+            .with_state(self.state.clone());
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri(format!(
+                        "/orgs/{}/projects/{}{}",
+                        self.org_id, self.project.id, path
+                    ))
+                    .header("Authorization", format!("Bearer {}", self.api_key))
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        (status, body_json(response).await)
+    }
+
+    /// Publish `version`, in effect from a day ago.
+    async fn publish(&self, version: &str) -> Value {
+        let (status, body) = self
+            .org(
+                "POST",
+                "/terms",
+                json!({
+                    "version": version,
+                    "url": format!("https://example.com/terms/{}", version),
+                    "effective_at": past_timestamp(ONE_DAY),
+                }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        body
+    }
+
+    async fn require_acceptance(&self) {
+        let (status, body) = self
+            .org("PUT", "", json!({ "require_terms_acceptance": true }))
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["require_terms_acceptance"], true);
+    }
+
+    /// Claim the free product as `email`, accepting `version` if given.
+    async fn buy(&self, email: &str, version: Option<&str>) -> (StatusCode, Value) {
+        let mut body = json!({
+            "public_key": self.project.public_key,
+            "product_id": self.product.id,
+            "email": email,
+        });
+        if let Some(version) = version {
+            body["accepted_terms_version"] = json!(version);
+        }
+        let response = public_app(self.state.clone())
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/buy")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        (status, body_json(response).await)
+    }
+
+    /// The license a successful /buy issued.
+    fn license_for(&self, buy: &Value) -> License {
+        let conn = self.state.db.get().unwrap();
+        let session = queries::get_payment_session(&conn, buy["session_id"].as_str().unwrap())
+            .unwrap()
+            .unwrap();
+        queries::get_license_by_id(&conn, &session.license_id.unwrap())
+            .unwrap()
+            .unwrap()
+    }
+}
+
+async fn body_json(response: Response) -> Value {
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    serde_json::from_slice(&bytes).unwrap_or(Value::Null)
+}
+
+#[tokio::test]
+async fn test_current_terms_shown_in_catalog_and_buy() {
+    let f = setup();
+    f.publish("2026-01").await;
+    f.publish("2026-02").await;
+
+    let response = public_app(f.state.clone())
+        .oneshot(
+            Request::builder()
+                .uri(format!("/products?public_key={}", f.project.public_key))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let catalog = body_json(response).await;
+    assert_eq!(catalog["terms"]["version"], "2026-02");
+    assert_eq!(catalog["terms"]["url"], "https://example.com/terms/2026-02");
+
+    let (status, body) = f.buy("fan@example.com", Some("2026-02")).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["terms"]["version"], "2026-02");
+}
+
+#[tokio::test]
+async fn test_stale_terms_version_rejected_with_current_version() {
+    let f = setup();
+    f.require_acceptance().await;
+    f.publish("2026-01").await;
+    f.publish("2026-02").await;
+
+    let (status, body) = f.buy("fan@example.com", Some("2026-01")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    assert_eq!(body["code"], "terms_not_accepted");
+    assert_eq!(body["current_terms"]["version"], "2026-02");
+    assert_eq!(
+        body["current_terms"]["url"],
+        "https://example.com/terms/2026-02"
+    );
+
+    let (status, body) = f.buy("fan@example.com", None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    assert_eq!(body["current_terms"]["version"], "2026-02");
+
+    let (status, body) = f.buy("fan@example.com", Some("2026-02")).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+#[tokio::test]
+async fn test_stale_version_rejected_even_when_not_required() {
+    let f = setup();
+    f.publish("2026-01").await;
+    f.publish("2026-02").await;
+
+    let (status, body) = f.buy("fan@example.com", Some("2026-01")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    assert_eq!(body["current_terms"]["version"], "2026-02");
+
+    // Not sending one is fine without require_terms_acceptance
+    let (status, body) = f.buy("fan@example.com", None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(f.license_for(&body).accepted_terms_version, None);
+}
+
+#[tokio::test]
+async fn test_required_acceptance_waits_for_published_terms() {
+    let f = setup();
+    f.require_acceptance().await;
+
+    let (status, body) = f.buy("fan@example.com", None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert!(body.get("terms").is_none());
+}
+
+#[tokio::test]
+async fn test_accepted_version_recorded_on_session_and_license() {
+    let f = setup();
+    f.require_acceptance().await;
+    f.publish("2026-01").await;
+
+    let (status, body) = f.buy("fan@example.com", Some("2026-01")).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let session = queries::get_payment_session(
+        &f.state.db.get().unwrap(),
+        body["session_id"].as_str().unwrap(),
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(session.accepted_terms_version.as_deref(), Some("2026-01"));
+    let license = f.license_for(&body);
+    assert_eq!(license.accepted_terms_version.as_deref(), Some("2026-01"));
+
+    let (status, detail) = f
+        .org("GET", &format!("/licenses/{}", license.id), Value::Null)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", detail);
+    assert_eq!(detail["accepted_terms_version"], "2026-01");
+}
+
+#[tokio::test]
+async fn test_future_terms_take_over_once_effective() {
+    let f = setup();
+    f.publish("2026-01").await;
+    let (status, body) = f
+        .org(
+            "POST",
+            "/terms",
+            json!({
+                "version": "2026-06",
+                "url": "https://example.com/terms/2026-06",
+                "effective_at": future_timestamp(ONE_DAY),
+            }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let (status, body) = f.buy("fan@example.com", Some("2026-06")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+    assert_eq!(body["current_terms"]["version"], "2026-01");
+
+    let (_, list) = f.org("GET", "/terms", Value::Null).await;
+    let versions: Vec<&str> = list
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|terms| terms["version"].as_str().unwrap())
+        .collect();
+    assert_eq!(versions, ["2026-06", "2026-01"]);
+}
+
+#[tokio::test]
+async fn test_terms_editable_until_a_license_accepts_them() {
+    let f = setup();
+    let terms = f.publish("2026-01").await;
+    let path = format!("/terms/{}", terms["id"].as_str().unwrap());
+
+    let (status, body) = f
+        .org(
+            "PUT",
+            &path,
+            json!({ "url": "https://example.com/legal/2026-01" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["url"], "https://example.com/legal/2026-01");
+    assert_eq!(body["version"], "2026-01");
+
+    let (status, _) = f.buy("fan@example.com", Some("2026-01")).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = f
+        .org("PUT", &path, json!({ "url": "https://example.com/other" }))
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT, "{}", body);
+    let (_, list) = f.org("GET", "/terms", Value::Null).await;
+    assert_eq!(list[0]["url"], "https://example.com/legal/2026-01");
+
+    // Changes go into a new version instead
+    f.publish("2026-02").await;
+}
+
+#[tokio::test]
+async fn test_terms_version_cannot_be_renamed_or_duplicated() {
+    let f = setup();
+    let terms = f.publish("2026-01").await;
+
+    let (status, body) = f
+        .org(
+            "POST",
+            "/terms",
+            json!({ "version": "2026-01", "url": "https://example.com/terms/again" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT, "{}", body);
+
+    let (status, _) = f
+        .org(
+            "PUT",
+            &format!("/terms/{}", terms["id"].as_str().unwrap()),
+            json!({ "version": "2026-02" }),
+        )
+        .await;
+    assert!(status.is_client_error(), "{}", status);
+}
+
+#[tokio::test]
+async fn test_terms_url_must_be_https() {
+    let f = setup();
+    let (status, _) = f
+        .org(
+            "POST",
+            "/terms",
+            json!({ "version": "2026-01", "url": "http://example.com/terms" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
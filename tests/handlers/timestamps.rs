@@ -0,0 +1,189 @@
+//! Tests for timestamp formats in responses: Unix seconds by default, and
+//! `{unix, iso}` objects that agree with them when the client asks with
+//! `?ts=iso` or the `iso-timestamps` Accept profile.
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode, header},
+    middleware,
+};
+use serde_json::Value;
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::config::RateLimitConfig;
+use paycheck::handlers;
+use paycheck::middleware::timestamp_format;
+
+struct TimestampFixture {
+    state: AppState,
+    org_id: String,
+    project: Project,
+    product: Product,
+    license: License,
+    api_key: String,
+}
+
+/// A project with a product and one license with a device.
+fn setup() -> TimestampFixture {
+    let state = create_test_app_state();
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, api_key) =
+        create_test_org_member(&mut conn, &org.id, "admin@test.com", OrgMemberRole::Owner);
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+    let license = create_test_license(
+        &conn,
+        &project.id,
+        &product.id,
+        Some(future_timestamp(ONE_YEAR)),
+    );
+    create_test_device(&conn, &license.id, "laptop", DeviceType::Uuid);
+
+    drop(conn);
+    TimestampFixture {
+        state,
+        org_id: org.id,
+        project,
+        product,
+        license,
+        api_key,
+    }
+}
+
+impl TimestampFixture {
+    /// The org API behind the timestamp format middleware, as `handlers::app`
+    /// layers it.
+    fn app(&self) -> Router {
+        handlers::orgs::router(self.state.clone(), RateLimitConfig::disabled())
+            .layer(middleware::from_fn(timestamp_format))
+            .with_state(self.state.clone())
+    }
+
+    async fn get(&self, path: &str, accept: Option<&str>) -> Value {
+        let mut request = Request::builder()
+            .uri(path)
+            .header("Authorization", format!("Bearer {}", self.api_key));
+        if let Some(accept) = accept {
+            request = request.header(header::ACCEPT, accept);
+        }
+        let response = self
+            .app()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    fn license_path(&self) -> String {
+        format!(
+            "/orgs/{}/projects/{}/licenses/{}",
+            self.org_id, self.project.id, self.license.id
+        )
+    }
+}
+
+/// Assert `value` is an ISO timestamp object for Unix time `unix`.
+fn assert_iso(value: &Value, unix: i64) {
+    assert_eq!(value["unix"], unix, "{value}");
+    let iso = value["iso"].as_str().expect("iso is a string");
+    let parsed = chrono::DateTime::parse_from_rfc3339(iso).unwrap();
+    assert_eq!(parsed.timestamp(), unix);
+    assert!(iso.ends_with('Z'), "ISO timestamps are UTC: {iso}");
+}
+
+#[tokio::test]
+async fn test_default_format_is_unchanged() {
+    let f = setup();
+    let body = f.get(&f.license_path(), None).await;
+
+    assert_eq!(body["created_at"], f.license.created_at);
+    assert_eq!(body["expires_at"], f.license.expires_at.unwrap());
+    assert!(body["revoked_at"].is_null());
+    assert!(body["devices"][0]["activated_at"].is_i64());
+}
+
+#[tokio::test]
+async fn test_query_parameter_returns_agreeing_representations() {
+    let f = setup();
+    let unix = f.get(&f.license_path(), None).await;
+    let iso = f.get(&format!("{}?ts=iso", f.license_path()), None).await;
+
+    assert_iso(&iso["created_at"], f.license.created_at);
+    assert_iso(&iso["expires_at"], f.license.expires_at.unwrap());
+    assert!(iso["revoked_at"].is_null(), "missing timestamps stay null");
+    let device = &iso["devices"][0];
+    assert_iso(
+        &device["activated_at"],
+        unix["devices"][0]["activated_at"].as_i64().unwrap(),
+    );
+    assert_iso(
+        &device["last_seen_at"],
+        unix["devices"][0]["last_seen_at"].as_i64().unwrap(),
+    );
+    // Everything else is the same
+    assert_eq!(iso["id"], unix["id"]);
+    assert_eq!(iso["product_name"], unix["product_name"]);
+}
+
+#[tokio::test]
+async fn test_accept_profile_selects_iso() {
+    let f = setup();
+    let path = format!("/orgs/{}/projects/{}", f.org_id, f.project.id);
+
+    let body = f.get(&path, Some("application/json")).await;
+    assert_eq!(body["created_at"], f.project.created_at);
+
+    let body = f
+        .get(&path, Some("application/json; profile=\"iso-timestamps\""))
+        .await;
+    assert_iso(&body["created_at"], f.project.created_at);
+    assert_iso(&body["updated_at"], f.project.updated_at);
+}
+
+#[tokio::test]
+async fn test_product_and_audit_log_timestamps() {
+    let f = setup();
+    let path = format!(
+        "/orgs/{}/projects/{}/products/{}?ts=iso",
+        f.org_id, f.project.id, f.product.id
+    );
+    let body = f.get(&path, None).await;
+    assert_iso(&body["created_at"], f.product.created_at);
+
+    let log = queries::create_audit_log(
+        &f.state.audit.get().unwrap(),
+        true,
+        ActorType::User,
+        Some("member1"),
+        "update_license",
+        "license",
+        &f.license.id,
+        None,
+        Some(&f.org_id),
+        Some(&f.project.id),
+        None,
+        None,
+        &AuditLogNames::default(),
+        None,
+        None,
+        None,
+    )
+    .unwrap();
+    let path = format!("/orgs/{}/audit-logs", f.org_id);
+    let body = f.get(&path, None).await;
+    assert_eq!(body["items"][0]["timestamp"], log.timestamp);
+
+    let body = f.get(&format!("{path}?ts=iso"), None).await;
+    assert_iso(&body["items"][0]["timestamp"], log.timestamp);
+}
@@ -0,0 +1,325 @@
+//! Tests for token diagnostics: each way a token can be broken is reported at
+//! the stage where verification fails.
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode},
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::handlers;
+
+struct DiagnoseFixture {
+    state: AppState,
+    org_id: String,
+    project: Project,
+    product: Product,
+    license: License,
+    /// Another project in the same org
+    other: Project,
+    api_key: String,
+}
+
+fn setup() -> DiagnoseFixture {
+    let state = create_test_app_state();
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, api_key) =
+        create_test_org_member(&mut conn, &org.id, "admin@test.com", OrgMemberRole::Owner);
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+    let license = create_test_license(
+        &conn,
+        &project.id,
+        &product.id,
+        Some(future_timestamp(ONE_YEAR)),
+    );
+    let other = create_test_project(&conn, &org.id, "Other Project", &test_master_key());
+
+    drop(conn);
+    DiagnoseFixture {
+        state,
+        org_id: org.id,
+        project,
+        product,
+        license,
+        other,
+        api_key,
+    }
+}
+
+fn app(state: &AppState) -> Router {
+    public_app(state.clone()).merge(
+        handlers::orgs::router(state.clone(), paycheck::config::RateLimitConfig::disabled())
+            .with_state(state.clone()),
+    )
+}
+
+fn claims(f: &DiagnoseFixture) -> jwt::LicenseClaims {
+    jwt::LicenseClaims {
+        license_exp: f.license.expires_at,
+        updates_exp: f.license.updates_expires_at,
+        tier: f.product.tier.clone(),
+        features: f.product.features.clone(),
+        entitlements: f.product.entitlements.clone(),
+        device_id: "device-1".to_string(),
+        device_type: "uuid".to_string(),
+        license_ref: None,
+        product_id: f.product.id.clone(),
+    }
+}
+
+fn private_key(f: &DiagnoseFixture, project: &Project) -> Vec<u8> {
+    queries::decrypt_project_private_key(&f.state.db.get().unwrap(), project, &test_master_key())
+        .unwrap()
+}
+
+/// Sign a token for the fixture's license with `project`'s key
+fn sign(f: &DiagnoseFixture, project: &Project, jti: &str) -> String {
+    jwt::sign_claims(
+        &claims(f),
+        &private_key(f, project),
+        &f.license.id,
+        &project.name,
+        jti,
+    )
+    .unwrap()
+}
+
+async fn diagnose(f: &DiagnoseFixture, token: &str) -> Value {
+    let response = app(&f.state)
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!(
+                    "/orgs/{}/projects/{}/diagnose-token",
+                    f.org_id, f.project.id
+                ))
+                .header("content-type", "application/json")
+                .header("Authorization", format!("Bearer {}", f.api_key))
+                .body(Body::from(json!({ "token": token }).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    serde_json::from_slice(&bytes).unwrap()
+}
+
+/// Activate a device through /redeem and return its token
+async fn redeem(f: &DiagnoseFixture) -> String {
+    let code = create_test_activation_code(
+        &f.state.db.get().unwrap(),
+        &f.license.id,
+        &f.project.license_key_prefix,
+    );
+    let response = app(&f.state)
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/redeem")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    json!({
+                        "public_key": f.project.public_key,
+                        "code": code.code,
+                        "device_id": "device-1",
+                        "device_type": "uuid"
+                    })
+                    .to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let body: Value = serde_json::from_slice(&bytes).unwrap();
+    body["token"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn test_activated_token_is_valid_and_device_records_kid() {
+    let f = setup();
+    let token = redeem(&f).await;
+
+    let d = diagnose(&f, &token).await;
+    let project_kid = jwt::key_id(&f.project.public_key).unwrap();
+    assert_eq!(d["valid"], true, "{}", d);
+    assert!(d["failed_stage"].is_null());
+    assert_eq!(d["project_kid"], project_kid.as_str());
+    assert_eq!(d["header"]["kid"], project_kid.as_str());
+    assert_eq!(d["kid_known"], true);
+    assert_eq!(d["signature_valid"], true);
+    assert_eq!(d["signed_by_project_id"], f.project.id.as_str());
+    assert_eq!(d["license_id"], f.license.id.as_str());
+    assert_eq!(d["jti_revoked"], false);
+    assert_eq!(d["device_exists"], true);
+    assert_eq!(d["device_signed_with_kid"], project_kid.as_str());
+}
+
+#[tokio::test]
+async fn test_garbage_token_fails_at_header() {
+    let f = setup();
+    let d = diagnose(&f, "not-a-token").await;
+    assert_eq!(d["valid"], false);
+    assert_eq!(d["failed_stage"], "header");
+    assert!(d["header"].is_null());
+}
+
+#[tokio::test]
+async fn test_unparseable_claims_fail_at_payload() {
+    let f = setup();
+    let token = sign(&f, &f.project, "jti-1");
+    let parts: Vec<&str> = token.split('.').collect();
+    let broken = format!("{}.bm90LWpzb24.{}", parts[0], parts[2]);
+
+    let d = diagnose(&f, &broken).await;
+    assert_eq!(d["failed_stage"], "payload");
+    assert_eq!(d["header"]["alg"], "EdDSA");
+}
+
+#[tokio::test]
+async fn test_foreign_key_fails_at_kid() {
+    let f = setup();
+    let (foreign_key, _) = jwt::generate_keypair();
+    let token = jwt::sign_claims(&claims(&f), &foreign_key, &f.license.id, "x", "jti-1").unwrap();
+
+    let d = diagnose(&f, &token).await;
+    assert_eq!(d["failed_stage"], "kid");
+    assert_eq!(d["kid_known"], false);
+    assert_eq!(d["signature_valid"], false);
+    assert!(d["signed_by_project_id"].is_null());
+}
+
+#[tokio::test]
+async fn test_token_from_other_project_names_signer() {
+    let f = setup();
+    let token = sign(&f, &f.other, "jti-1");
+
+    let d = diagnose(&f, &token).await;
+    assert_eq!(d["failed_stage"], "signature");
+    assert_eq!(d["kid_known"], true, "kid is another project's key");
+    assert_eq!(d["signature_valid"], false);
+    assert_eq!(d["signed_by_project_id"], f.other.id.as_str());
+    assert!(
+        d["reason"].as_str().unwrap().contains(&f.other.id),
+        "{}",
+        d["reason"]
+    );
+}
+
+#[tokio::test]
+async fn test_tampered_token_fails_at_signature() {
+    let f = setup();
+    let token = sign(&f, &f.project, "jti-1");
+    let mut parts: Vec<String> = token.split('.').map(String::from).collect();
+    let mut claims: Value = serde_json::from_slice(
+        &base64::Engine::decode(&base64::engine::general_purpose::URL_SAFE_NO_PAD, &parts[1])
+            .unwrap(),
+    )
+    .unwrap();
+    claims["tier"] = json!("enterprise");
+    parts[1] = base64::Engine::encode(
+        &base64::engine::general_purpose::URL_SAFE_NO_PAD,
+        claims.to_string(),
+    );
+
+    let d = diagnose(&f, &parts.join(".")).await;
+    assert_eq!(d["failed_stage"], "signature");
+    assert_eq!(d["kid_known"], true);
+    assert!(d["signed_by_project_id"].is_null());
+    assert!(
+        d["device_exists"].is_null(),
+        "nothing checked past the signature"
+    );
+}
+
+#[tokio::test]
+async fn test_wrong_issuer_fails_at_issuer() {
+    let f = setup();
+    let token = jwt::sign_claims_with_issuer(
+        &claims(&f),
+        &private_key(&f, &f.project),
+        &f.license.id,
+        "someone-else",
+        &f.project.name,
+        "jti-1",
+    )
+    .unwrap();
+
+    let d = diagnose(&f, &token).await;
+    assert_eq!(d["failed_stage"], "issuer");
+    assert_eq!(d["signature_valid"], true);
+    assert_eq!(d["claims_accepted"], false);
+}
+
+#[tokio::test]
+async fn test_expired_legacy_token_fails_at_expired() {
+    let f = setup();
+    let device = create_test_device(
+        &f.state.db.get().unwrap(),
+        &f.license.id,
+        "device-1",
+        DeviceType::Uuid,
+    );
+    // Signed without a kid, like tokens issued before key IDs existed
+    let token = sign_claims_with_exp_offset(
+        &claims(&f),
+        &private_key(&f, &f.project),
+        &f.license.id,
+        &f.project.name,
+        &device.jti,
+        -3600,
+    );
+
+    let d = diagnose(&f, &token).await;
+    assert_eq!(d["failed_stage"], "expired");
+    assert!(d["kid_known"].is_null());
+    assert_eq!(d["signature_valid"], true);
+    assert_eq!(d["claims_accepted"], true);
+    assert_eq!(d["expired"], true);
+    assert_eq!(
+        d["device_exists"], true,
+        "later checks still run after a failure"
+    );
+}
+
+#[tokio::test]
+async fn test_revoked_jti_fails_at_revoked() {
+    let f = setup();
+    let token = redeem(&f).await;
+    let jti = jwt::verify_token(&token, &f.project.public_key)
+        .unwrap()
+        .jwt_id
+        .unwrap();
+    queries::add_revoked_jti(&f.state.db.get().unwrap(), &f.license.id, &jti, None).unwrap();
+
+    let d = diagnose(&f, &token).await;
+    assert_eq!(d["failed_stage"], "revoked");
+    assert_eq!(d["jti"], jti.as_str());
+    assert_eq!(d["jti_revoked"], true);
+}
+
+#[tokio::test]
+async fn test_unknown_jti_fails_at_device() {
+    let f = setup();
+    let token = sign(&f, &f.project, "jti-without-device");
+
+    let d = diagnose(&f, &token).await;
+    assert_eq!(d["failed_stage"], "device");
+    assert_eq!(d["jti_revoked"], false);
+    assert_eq!(d["device_exists"], false);
+}
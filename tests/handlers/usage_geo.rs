@@ -0,0 +1,154 @@
+//! Tests for the usage geography endpoint: the daily rollup over a date
+//! range, per-country totals, the product filter, and range checks.
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::handlers;
+
+/// 2025-06-15 15:06:40 UTC
+const NOW: i64 = 1_750_000_000;
+
+struct GeoFixture {
+    state: AppState,
+    org_id: String,
+    project_id: String,
+    pro_id: String,
+    basic_id: String,
+    api_key: String,
+}
+
+/// A project with two products and a few days of usage.
+fn setup() -> GeoFixture {
+    let mut state = create_test_app_state();
+    state.clock = Clock::fixed(NOW);
+    let mut conn = state.db.get().unwrap();
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, api_key) =
+        create_test_org_member(&mut conn, &org.id, "owner@test.com", OrgMemberRole::Owner);
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    let pro = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+    let basic = create_test_product(&conn, &project.id, "Basic Plan", "basic");
+
+    for (date, product_id, country, count) in [
+        ("2025-05-01", &pro.id, "US", 4),
+        ("2025-06-10", &pro.id, "GB", 2),
+        ("2025-06-10", &basic.id, "GB", 1),
+        ("2025-06-14", &pro.id, "SE", 3),
+        ("2025-06-15", &basic.id, "US", 1),
+    ] {
+        for _ in 0..count {
+            queries::record_usage_geo(&conn, date, &project.id, product_id, country).unwrap();
+        }
+    }
+    drop(conn);
+
+    GeoFixture {
+        state,
+        org_id: org.id,
+        project_id: project.id,
+        pro_id: pro.id,
+        basic_id: basic.id,
+        api_key,
+    }
+}
+
+impl GeoFixture {
+    async fn get(&self, query: &str) -> (StatusCode, Value) {
+        let app = handlers::orgs::router(
+            self.state.clone(),
+            paycheck::config::RateLimitConfig::disabled(),
+        )
+        .with_state(self.state.clone());
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri(format!(
+                        "/orgs/{}/projects/{}/usage/geo{}",
+                        self.org_id, self.project_id, query
+                    ))
+                    .header("Authorization", format!("Bearer {}", self.api_key))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+}
+
+#[tokio::test]
+async fn test_defaults_to_the_last_30_days() {
+    let f = setup();
+    let (status, usage) = f.get("").await;
+    assert_eq!(status, StatusCode::OK, "{}", usage);
+    assert_eq!(usage["from"], "2025-05-17");
+    assert_eq!(usage["to"], "2025-06-15");
+    assert_eq!(
+        usage["countries"],
+        json!([
+            { "country": "GB", "count": 3 },
+            { "country": "SE", "count": 3 },
+            { "country": "US", "count": 1 },
+        ])
+    );
+    let days = usage["days"].as_array().unwrap();
+    assert_eq!(days.len(), 4);
+    assert!(days.contains(
+        &json!({ "date": "2025-06-10", "product_id": f.pro_id, "country": "GB", "count": 2 })
+    ));
+}
+
+#[tokio::test]
+async fn test_range_and_product_filter() {
+    let f = setup();
+    let (status, usage) = f.get("?from=2025-05-01&to=2025-06-10").await;
+    assert_eq!(status, StatusCode::OK, "{}", usage);
+    assert_eq!(
+        usage["countries"],
+        json!([
+            { "country": "US", "count": 4 },
+            { "country": "GB", "count": 3 },
+        ])
+    );
+
+    let (_, usage) = f.get(&format!("?product_id={}", f.basic_id)).await;
+    assert_eq!(
+        usage["countries"],
+        json!([
+            { "country": "GB", "count": 1 },
+            { "country": "US", "count": 1 },
+        ])
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_ranges_rejected() {
+    let f = setup();
+    for query in [
+        "?from=2025-06-10&to=2025-06-01",
+        "?from=2024-01-01&to=2025-06-01",
+        "?from=June",
+    ] {
+        let (status, _) = f.get(query).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", query);
+    }
+
+    // A single day is fine
+    let (status, usage) = f.get("?from=2025-06-14&to=2025-06-14").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(usage["countries"], json!([{ "country": "SE", "count": 3 }]));
+}
@@ -0,0 +1,345 @@
+//! Tests for merging duplicate users: the source's org and project
+//! memberships, API keys, and operator role move to the target, overlaps keep
+//! the higher role, and the source is soft-deleted for good.
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode},
+    response::Response,
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::config::RateLimitConfig;
+use paycheck::handlers;
+
+struct MergeFixture {
+    state: AppState,
+    owner_key: String,
+    source: User,
+    source_key: String,
+    target: User,
+    /// Only the source is a member (admin)
+    source_org: Organization,
+    /// Both are members: the source as owner, the target as member
+    shared_org: Organization,
+    /// Only the target is a member
+    target_org: Organization,
+    /// In the shared org: the source is a project admin, the target a viewer
+    shared_project: Project,
+    /// In the shared org: only the source is on it (viewer)
+    source_project: Project,
+}
+
+fn setup() -> MergeFixture {
+    let mut state = create_test_app_state();
+    state.audit_log_enabled = true;
+    let mut conn = state.db.get().unwrap();
+
+    let (_, owner_key) = create_test_operator(&mut conn, "owner@test.com", OperatorRole::Owner);
+    let source_org = create_test_org(&conn, "Source Org");
+    let shared_org = create_test_org(&conn, "Shared Org");
+    let target_org = create_test_org(&conn, "Target Org");
+
+    let (source, _, source_key) = create_test_org_member(
+        &mut conn,
+        &source_org.id,
+        "jo@old.com",
+        OrgMemberRole::Admin,
+    );
+    let (target, _, _) = create_test_org_member(
+        &mut conn,
+        &target_org.id,
+        "jo@new.com",
+        OrgMemberRole::Member,
+    );
+    let add = |user: &User, role| {
+        let input = CreateOrgMember {
+            user_id: user.id.clone(),
+            role,
+        };
+        queries::create_org_member(&conn, &shared_org.id, &input).unwrap()
+    };
+    let source_member = add(&source, OrgMemberRole::Owner);
+    let target_member = add(&target, OrgMemberRole::Member);
+
+    let master_key = test_master_key();
+    let shared_project = create_test_project(&conn, &shared_org.id, "Shared", &master_key);
+    let source_project = create_test_project(&conn, &shared_org.id, "Source Only", &master_key);
+    create_test_project_member(
+        &conn,
+        &source_member.id,
+        &shared_project.id,
+        ProjectMemberRole::Admin,
+    );
+    create_test_project_member(
+        &conn,
+        &target_member.id,
+        &shared_project.id,
+        ProjectMemberRole::View,
+    );
+    create_test_project_member(
+        &conn,
+        &source_member.id,
+        &source_project.id,
+        ProjectMemberRole::View,
+    );
+
+    drop(conn);
+    MergeFixture {
+        state,
+        owner_key,
+        source,
+        source_key,
+        target,
+        source_org,
+        shared_org,
+        target_org,
+        shared_project,
+        source_project,
+    }
+}
+
+impl MergeFixture {
+    fn app(&self) -> Router {
+        Router::new()
+            .merge(handlers::operators::router(self.state.clone()))
+            .merge(handlers::orgs::router(
+                self.state.clone(),
+                RateLimitConfig::disabled(),
+            ))
+            .with_state(self.state.clone())
+    }
+
+    async fn send(&self, method: &str, path: &str, key: &str, body: Value) -> Response {
+        let request = Request::builder()
+            .method(method)
+            .uri(path)
+            .header("Authorization", format!("Bearer {}", key))
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        self.app().oneshot(request).await.unwrap()
+    }
+
+    async fn merge_as(&self, key: &str, source_id: &str, target_id: &str) -> (StatusCode, Value) {
+        let response = self
+            .send(
+                "POST",
+                &format!("/operators/users/{}/merge", target_id),
+                key,
+                json!({ "source_user_id": source_id }),
+            )
+            .await;
+        let status = response.status();
+        (status, body_json(response).await)
+    }
+
+    async fn merge(&self) -> Value {
+        let (status, body) = self
+            .merge_as(&self.owner_key, &self.source.id, &self.target.id)
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        body
+    }
+
+    fn membership(&self, user: &User, org: &Organization) -> Option<OrgMember> {
+        let conn = self.state.db.get().unwrap();
+        queries::get_org_member_by_user_and_org(&conn, &user.id, &org.id).unwrap()
+    }
+
+    /// (user_id, role) for each active member of a project.
+    fn project_members(&self, project: &Project) -> Vec<(String, ProjectMemberRole)> {
+        let conn = self.state.db.get().unwrap();
+        queries::list_project_members(&conn, &project.id, &self.state.emails())
+            .unwrap()
+            .into_iter()
+            .map(|member| (member.user_id, member.role))
+            .collect()
+    }
+}
+
+async fn body_json(response: Response) -> Value {
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    serde_json::from_slice(&bytes).unwrap_or(Value::Null)
+}
+
+#[tokio::test]
+async fn test_memberships_only_the_source_had_move() {
+    let f = setup();
+    let body = f.merge().await;
+
+    assert_eq!(body["moved_org_ids"], json!([f.source_org.id]));
+    let moved = f.membership(&f.target, &f.source_org).expect("moved");
+    assert_eq!(moved.role, OrgMemberRole::Admin, "role carries over");
+    assert_eq!(
+        f.membership(&f.target, &f.target_org).unwrap().role,
+        OrgMemberRole::Member
+    );
+    let orgs: Vec<&str> = body["user"]["memberships"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m["org_id"].as_str().unwrap())
+        .collect();
+    assert_eq!(orgs.len(), 3, "{:?}", orgs);
+}
+
+#[tokio::test]
+async fn test_overlapping_membership_keeps_higher_role() {
+    let f = setup();
+    let body = f.merge().await;
+
+    assert_eq!(
+        f.membership(&f.target, &f.shared_org).unwrap().role,
+        OrgMemberRole::Owner
+    );
+    let conflicts = body["conflicts"].as_array().unwrap();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0]["org_id"], f.shared_org.id.as_str());
+    assert_eq!(conflicts[0]["source_role"], "owner");
+    assert_eq!(conflicts[0]["target_role"], "member");
+    assert_eq!(conflicts[0]["kept_role"], "owner");
+}
+
+#[tokio::test]
+async fn test_project_memberships_follow_the_merge() {
+    let f = setup();
+    f.merge().await;
+
+    assert_eq!(
+        f.project_members(&f.shared_project),
+        vec![(f.target.id.clone(), ProjectMemberRole::Admin)],
+        "one membership, with the higher role"
+    );
+    assert_eq!(
+        f.project_members(&f.source_project),
+        vec![(f.target.id.clone(), ProjectMemberRole::View)]
+    );
+}
+
+#[tokio::test]
+async fn test_source_api_key_now_acts_as_target() {
+    let f = setup();
+    f.merge().await;
+
+    let response = f
+        .send(
+            "GET",
+            &format!("/orgs/{}/projects", f.target_org.id),
+            &f.source_key,
+            Value::Null,
+        )
+        .await;
+    assert_eq!(
+        response.status(),
+        StatusCode::OK,
+        "the source's key reaches the target's orgs"
+    );
+}
+
+#[tokio::test]
+async fn test_duplicate_key_names_are_renamed() {
+    let f = setup();
+    let body = f.merge().await;
+
+    let conn = f.state.db.get().unwrap();
+    let keys = queries::list_api_keys(&conn, &f.target.id, false).unwrap();
+    assert_eq!(keys.len(), 2);
+    assert_eq!(body["moved_api_key_ids"].as_array().unwrap().len(), 1);
+    let renamed = body["renamed_api_key_ids"][0].as_str().expect("renamed");
+    let key = keys.iter().find(|k| k.id == renamed).unwrap();
+    assert_eq!(key.name, format!("Default ({})", key.prefix));
+}
+
+#[tokio::test]
+async fn test_operator_role_keeps_higher() {
+    let f = setup();
+    {
+        let conn = f.state.db.get().unwrap();
+        let emails = f.state.emails();
+        queries::grant_operator_role(&conn, &f.source.id, OperatorRole::Admin, &emails).unwrap();
+        queries::grant_operator_role(&conn, &f.target.id, OperatorRole::View, &emails).unwrap();
+    }
+
+    let body = f.merge().await;
+    assert_eq!(body["operator_role"], "admin");
+    assert_eq!(body["user"]["operator_role"], "admin");
+}
+
+#[tokio::test]
+async fn test_source_is_deleted_and_cannot_be_restored() {
+    let f = setup();
+    f.merge().await;
+
+    let conn = f.state.db.get().unwrap();
+    assert!(
+        queries::get_user_by_id(&conn, &f.source.id, &f.state.emails())
+            .unwrap()
+            .is_none()
+    );
+    let deleted = queries::get_deleted_user_by_id(&conn, &f.source.id, &f.state.emails())
+        .unwrap()
+        .unwrap();
+    assert_eq!(deleted.merged_into.as_deref(), Some(f.target.id.as_str()));
+    drop(conn);
+
+    let response = f
+        .send(
+            "POST",
+            &format!("/operators/users/{}/restore", f.source.id),
+            &f.owner_key,
+            json!({}),
+        )
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_merge_is_audited() {
+    let f = setup();
+    f.merge().await;
+
+    let details: String = f
+        .state
+        .audit
+        .get()
+        .unwrap()
+        .query_row(
+            "SELECT details FROM audit_logs WHERE action = 'merge_user' AND resource_id = ?1",
+            [&f.target.id],
+            |row| row.get(0),
+        )
+        .unwrap();
+    let details: Value = serde_json::from_str(&details).unwrap();
+    assert_eq!(details["source_user_id"], f.source.id.as_str());
+    assert_eq!(details["before"]["source"]["id"], f.source.id.as_str());
+    assert_eq!(details["merge"]["moved_org_ids"], json!([f.source_org.id]));
+}
+
+#[tokio::test]
+async fn test_merge_into_itself_rejected() {
+    let f = setup();
+    let (status, _) = f.merge_as(&f.owner_key, &f.target.id, &f.target.id).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_merge_requires_owner() {
+    let f = setup();
+    let admin_key = {
+        let mut conn = f.state.db.get().unwrap();
+        create_test_operator(&mut conn, "admin@test.com", OperatorRole::Admin).1
+    };
+
+    let (status, _) = f.merge_as(&admin_key, &f.source.id, &f.target.id).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(f.membership(&f.source, &f.source_org).is_some());
+}
@@ -5,7 +5,8 @@ mod common;
 
 use common::{ONE_DAY, ONE_MONTH, ONE_WEEK, ONE_YEAR, UPDATES_VALID_DAYS, *};
 use paycheck::handlers::webhooks::common::{
-    CheckoutData, process_cancellation, process_checkout, process_renewal,
+    CheckoutData, PauseData, process_cancellation, process_checkout, process_pause,
+    process_renewal, process_resume,
 };
 use paycheck::models::{LemonSqueezyConfig, StripeConfig};
 use paycheck::payments::{LemonSqueezyClient, StripeClient};
@@ -164,6 +165,7 @@ fn create_lemonsqueezy_test_client() -> LemonSqueezyClient {
         api_key: "lskey_test_xxx".to_string(),
         store_id: "12345".to_string(),
         webhook_secret: "ls_whsec_test_secret".to_string(),
+        verify_imported_keys: false,
     };
     LemonSqueezyClient::new(&config)
 }
@@ -377,6 +379,7 @@ fn test_renewal_webhook_replay_prevented() {
         subscription_id,
         Some(event_id),
         None, // No provider period_end - use calculated fallback
+        now(),
     );
     assert_eq!(
         status1,
@@ -408,6 +411,7 @@ fn test_renewal_webhook_replay_prevented() {
         subscription_id,
         Some(event_id), // Same event ID = replay
         None,
+        now(),
     );
 
     // Replay should be rejected (idempotent - already processed)
@@ -463,6 +467,7 @@ fn test_different_renewal_events_both_processed() {
         subscription_id,
         Some("invoice_001"),
         None,
+        now(),
     );
     assert_eq!(
         status1,
@@ -479,6 +484,7 @@ fn test_different_renewal_events_both_processed() {
         subscription_id,
         Some("invoice_002"), // Different event ID
         None,
+        now(),
     );
     assert_eq!(
         status2,
@@ -515,6 +521,8 @@ fn test_checkout_creates_license_and_device() {
         customer_email: Some("test@example.com".to_string()),
         subscription_id: Some("sub_123".to_string()),
         order_id: Some("cs_test_123".to_string()),
+        payment_id: None,
+        metadata: Default::default(),
     };
 
     let (status, msg) = process_checkout(
@@ -525,6 +533,7 @@ fn test_checkout_creates_license_and_device() {
         &session,
         &product,
         &checkout_data,
+        now(),
     );
 
     assert_eq!(
@@ -600,6 +609,8 @@ fn test_checkout_concurrent_webhooks_create_only_one_license() {
         customer_email: Some("test@example.com".to_string()),
         subscription_id: None,
         order_id: None,
+        payment_id: None,
+        metadata: Default::default(),
     };
 
     // First call should succeed
@@ -611,6 +622,7 @@ fn test_checkout_concurrent_webhooks_create_only_one_license() {
         &session,
         &product,
         &checkout_data,
+        now(),
     );
     assert_eq!(
         status1,
@@ -628,6 +640,7 @@ fn test_checkout_concurrent_webhooks_create_only_one_license() {
         &session,
         &product,
         &checkout_data,
+        now(),
     );
     assert_eq!(
         status2,
@@ -681,6 +694,14 @@ fn test_checkout_creates_license_with_product_expirations() {
         device_limit: Some(3),
         device_inactive_days: None,
         features: vec![],
+        entitlements: Default::default(),
+        seat_count: None,
+        available_from: None,
+        available_until: None,
+        checkout_fields: vec![],
+        extends_updates_for_product_id: None,
+        renewal_fallback: Default::default(),
+        revoke_on_partial_refund: false,
     };
     let product = queries::create_product(&mut conn, &project.id, &input)
         .expect("product creation should succeed");
@@ -694,6 +715,8 @@ fn test_checkout_creates_license_with_product_expirations() {
         customer_email: Some("test@example.com".to_string()),
         subscription_id: None,
         order_id: None,
+        payment_id: None,
+        metadata: Default::default(),
     };
 
     let before = now();
@@ -705,6 +728,7 @@ fn test_checkout_creates_license_with_product_expirations() {
         &session,
         &product,
         &checkout_data,
+        now(),
     );
     assert_eq!(
         status,
@@ -773,6 +797,14 @@ fn test_checkout_perpetual_license() {
         device_limit: Some(3),
         device_inactive_days: None,
         features: vec![],
+        entitlements: Default::default(),
+        seat_count: None,
+        available_from: None,
+        available_until: None,
+        checkout_fields: vec![],
+        extends_updates_for_product_id: None,
+        renewal_fallback: Default::default(),
+        revoke_on_partial_refund: false,
     };
     let product = queries::create_product(&mut conn, &project.id, &input)
         .expect("product creation should succeed");
@@ -786,6 +818,8 @@ fn test_checkout_perpetual_license() {
         customer_email: Some("test@example.com".to_string()),
         subscription_id: None,
         order_id: None,
+        payment_id: None,
+        metadata: Default::default(),
     };
 
     let (status, _) = process_checkout(
@@ -796,6 +830,7 @@ fn test_checkout_perpetual_license() {
         &session,
         &product,
         &checkout_data,
+        now(),
     );
     assert_eq!(
         status,
@@ -845,6 +880,7 @@ fn test_renewal_extends_license_expiration() {
         "sub_123",
         Some("invoice_001"),
         None,
+        now(),
     );
     assert_eq!(
         status,
@@ -897,6 +933,7 @@ fn test_renewal_without_event_id_always_processes() {
         "sub_123",
         None, // No event_id - no replay prevention
         None,
+        now(),
     );
     assert_eq!(
         status1,
@@ -909,7 +946,7 @@ fn test_renewal_without_event_id_always_processes() {
     );
 
     // Second call also processes (no replay prevention)
-    let (status2, msg2) = process_renewal(&conn, "stripe", &product, &license.id, "sub_123", None, None);
+    let (status2, msg2) = process_renewal(&conn, "stripe", &product, &license.id, "sub_123", None, None, now());
     assert_eq!(
         status2,
         StatusCode::OK,
@@ -948,6 +985,7 @@ fn test_renewal_uses_provider_period_end_when_available() {
         "sub_123",
         Some("invoice_001"),
         Some(provider_period_end), // Provider's exact period end
+        now(),
     );
     assert_eq!(status, StatusCode::OK);
 
@@ -988,6 +1026,7 @@ fn test_renewal_falls_back_to_calculated_when_no_period_end() {
         "sub_123",
         Some("invoice_002"),
         None, // No provider period_end - should fall back to calculated
+        now(),
     );
     assert_eq!(status, StatusCode::OK);
 
@@ -1034,6 +1073,7 @@ fn test_renewal_provider_period_end_handles_early_renewal() {
         "sub_123",
         Some("invoice_003"),
         Some(provider_period_end),
+        now(),
     );
     assert_eq!(status, StatusCode::OK);
 
@@ -1088,6 +1128,169 @@ fn test_cancellation_returns_ok_without_modifying_license() {
     );
 }
 
+// ============ Pause/Resume Business Logic Tests ============
+
+/// A pause or resume of `sub_123` that the provider made at `occurred_at`.
+fn pause_event(occurred_at: i64) -> PauseData {
+    PauseData {
+        subscription_id: "sub_123".to_string(),
+        occurred_at,
+    }
+}
+
+#[test]
+fn test_pause_then_resume_extends_expiration_by_paused_duration() {
+    use axum::http::StatusCode;
+
+    let conn = setup_test_db();
+    let master_key = test_master_key();
+
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "Test Project", &master_key);
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+
+    let original_exp = now() + (ONE_MONTH * 86400);
+    let license = create_test_license(&conn, &project.id, &product.id, Some(original_exp));
+
+    // Paused 10 days ago
+    let paused_at = now() - (10 * 86400);
+    let (status, msg) = process_pause(&conn, "stripe", &license.id, &pause_event(paused_at));
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(msg, "OK", "first pause should be applied");
+
+    let paused = queries::get_license_by_id(&conn, &license.id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        paused.paused_at,
+        Some(paused_at),
+        "paused_at should be the provider's pause time"
+    );
+    assert_eq!(
+        paused.expires_at,
+        Some(original_exp),
+        "pausing should not change expiration"
+    );
+
+    let resumed_at = now();
+    let (status, msg) = process_resume(&conn, "stripe", &license.id, &pause_event(resumed_at));
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(msg, "OK", "resume should be applied");
+
+    let resumed = queries::get_license_by_id(&conn, &license.id)
+        .unwrap()
+        .unwrap();
+    assert!(resumed.paused_at.is_none(), "paused_at should be cleared");
+    let extension = resumed.expires_at.unwrap() - original_exp;
+    assert_eq!(
+        extension,
+        resumed_at - paused_at,
+        "expiration should move out by the paused duration"
+    );
+    assert_eq!(
+        resumed.paused_seconds, extension,
+        "paused_seconds should match the extension"
+    );
+}
+
+#[test]
+fn test_resume_revives_license_that_expired_during_pause() {
+    let conn = setup_test_db();
+    let master_key = test_master_key();
+
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "Test Project", &master_key);
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+
+    // Paused 7 days ago with 5 days left; the old expiration passed 2 days ago
+    let original_exp = now() - (2 * 86400);
+    let license = create_test_license(&conn, &project.id, &product.id, Some(original_exp));
+    let paused_at = now() - (7 * 86400);
+    process_pause(&conn, "stripe", &license.id, &pause_event(paused_at));
+
+    let paused = queries::get_license_by_id(&conn, &license.id)
+        .unwrap()
+        .unwrap();
+    assert!(
+        !paused.is_expired_for_validation(now()),
+        "paused license should keep validating past its old expiration"
+    );
+
+    process_resume(&conn, "stripe", &license.id, &pause_event(now()));
+
+    let resumed = queries::get_license_by_id(&conn, &license.id)
+        .unwrap()
+        .unwrap();
+    let remaining = resumed.expires_at.unwrap() - now();
+    assert!(
+        (5 * 86400 - 5..=5 * 86400 + 5).contains(&remaining),
+        "the 5 days left at pause time should be restored, got {}s",
+        remaining
+    );
+    assert!(!resumed.is_expired_for_validation(now()));
+}
+
+#[test]
+fn test_pause_and_resume_are_idempotent() {
+    let conn = setup_test_db();
+    let master_key = test_master_key();
+
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "Test Project", &master_key);
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+
+    let original_exp = now() + (ONE_MONTH * 86400);
+    let license = create_test_license(&conn, &project.id, &product.id, Some(original_exp));
+
+    let (paused_at, resumed_at) = (now() - (3 * 86400), now());
+    let (_, msg) = process_resume(&conn, "stripe", &license.id, &pause_event(resumed_at));
+    assert_eq!(
+        msg, "Not paused",
+        "resume without a pause should be a no-op"
+    );
+
+    process_pause(&conn, "stripe", &license.id, &pause_event(paused_at));
+    let (_, msg) = process_pause(&conn, "stripe", &license.id, &pause_event(paused_at));
+    assert_eq!(msg, "Already paused", "redelivered pause should be a no-op");
+
+    process_resume(&conn, "stripe", &license.id, &pause_event(resumed_at));
+    let (_, msg) = process_resume(&conn, "stripe", &license.id, &pause_event(resumed_at));
+    assert_eq!(msg, "Not paused", "redelivered resume should be a no-op");
+
+    let license = queries::get_license_by_id(&conn, &license.id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        license.expires_at.unwrap() - original_exp,
+        3 * 86400,
+        "duplicate events should not extend twice"
+    );
+}
+
+#[test]
+fn test_resume_perpetual_license_tracks_paused_time_only() {
+    let conn = setup_test_db();
+    let master_key = test_master_key();
+
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "Test Project", &master_key);
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+    let license = create_test_license(&conn, &project.id, &product.id, None);
+
+    let (paused_at, resumed_at) = (now() - 86400, now());
+    process_pause(&conn, "stripe", &license.id, &pause_event(paused_at));
+    process_resume(&conn, "stripe", &license.id, &pause_event(resumed_at));
+
+    let license = queries::get_license_by_id(&conn, &license.id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        license.expires_at, None,
+        "perpetual license stays perpetual"
+    );
+    assert_eq!(license.paused_seconds, 86400);
+}
+
 // ============ Stripe HTTP Handler Tests ============
 
 use axum::{Router, body::Body, http::Request, routing::post};
@@ -1515,6 +1718,148 @@ async fn test_stripe_webhook_subscription_deleted_returns_ok() {
     );
 }
 
+async fn post_stripe_event(state: &paycheck::db::AppState, payload: serde_json::Value) -> String {
+    let payload_bytes = serde_json::to_vec(&payload).unwrap();
+    let timestamp = current_timestamp();
+    let signature = compute_stripe_signature(&payload_bytes, "whsec_test123secret456", &timestamp);
+
+    let response = webhook_app(state.clone())
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/webhook/stripe")
+                .header("content-type", "application/json")
+                .header(
+                    "stripe-signature",
+                    format!("t={},v1={}", timestamp, signature),
+                )
+                .body(Body::from(payload_bytes))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), axum::http::StatusCode::OK);
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+#[tokio::test]
+async fn test_stripe_webhook_pause_collection_pauses_and_resumes_license() {
+    let state = create_test_app_state();
+    let master_key = test_master_key();
+
+    let license_id: String;
+    let original_exp = now() + (ONE_MONTH * 86400);
+    // Paused 4 days before the resume
+    let (paused_at, resumed_at) = (now() - (4 * 86400), now());
+
+    {
+        let conn = state.db.get().unwrap();
+        let org = create_test_org(&conn, "Test Org");
+        setup_stripe_config(&conn, &org.id, &master_key);
+        let project = create_test_project(&conn, &org.id, "Test Project", &master_key);
+        let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+        let license = create_test_license_with_subscription(
+            &conn,
+            &project.id,
+            &product.id,
+            Some(original_exp),
+            "stripe",
+            "sub_pause_test",
+        );
+        license_id = license.id.clone();
+    }
+
+    let body = post_stripe_event(
+        &state,
+        json!({
+            "type": "customer.subscription.updated",
+            "created": paused_at,
+            "data": {
+                "object": {
+                    "id": "sub_pause_test",
+                    "customer": "cus_test",
+                    "status": "active",
+                    "pause_collection": { "behavior": "void", "resumes_at": null }
+                },
+                "previous_attributes": { "pause_collection": null }
+            }
+        }),
+    )
+    .await;
+    assert_eq!(body, "OK");
+
+    {
+        let conn = state.db.get().unwrap();
+        let license = queries::get_license_by_id(&conn, &license_id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            license.paused_at,
+            Some(paused_at),
+            "the pause should start when Stripe created the event"
+        );
+    }
+
+    let body = post_stripe_event(
+        &state,
+        json!({
+            "type": "customer.subscription.updated",
+            "created": resumed_at,
+            "data": {
+                "object": {
+                    "id": "sub_pause_test",
+                    "customer": "cus_test",
+                    "status": "active",
+                    "pause_collection": null
+                },
+                "previous_attributes": {
+                    "pause_collection": { "behavior": "void", "resumes_at": null }
+                }
+            }
+        }),
+    )
+    .await;
+    assert_eq!(body, "OK");
+
+    let conn = state.db.get().unwrap();
+    let license = queries::get_license_by_id(&conn, &license_id)
+        .unwrap()
+        .unwrap();
+    assert!(license.paused_at.is_none(), "license should be resumed");
+    assert_eq!(
+        license.expires_at.unwrap() - original_exp,
+        4 * 86400,
+        "expiration should move out by the time between the two events"
+    );
+}
+
+#[tokio::test]
+async fn test_stripe_webhook_subscription_updated_without_pause_change_ignored() {
+    let state = create_test_app_state();
+
+    let body = post_stripe_event(
+        &state,
+        json!({
+            "type": "customer.subscription.updated",
+            "data": {
+                "object": {
+                    "id": "sub_other_update",
+                    "customer": "cus_test",
+                    "status": "active",
+                    "pause_collection": null
+                },
+                "previous_attributes": { "cancel_at_period_end": true }
+            }
+        }),
+    )
+    .await;
+    assert_eq!(body, "Event ignored");
+}
+
 #[tokio::test]
 async fn test_stripe_webhook_unknown_event_ignored() {
     let state = create_test_app_state();
@@ -1889,6 +2234,86 @@ async fn test_lemonsqueezy_webhook_subscription_cancelled_returns_ok() {
     );
 }
 
+#[tokio::test]
+async fn test_lemonsqueezy_webhook_subscription_paused_and_unpaused() {
+    let state = create_test_app_state();
+    let master_key = test_master_key();
+
+    let license_id: String;
+    let original_exp = now() + (ONE_MONTH * 86400);
+
+    {
+        let conn = state.db.get().unwrap();
+        let org = create_test_org(&conn, "Test Org");
+        setup_lemonsqueezy_config(&conn, &org.id, &master_key);
+        let project = create_test_project(&conn, &org.id, "Test Project", &master_key);
+        let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+        let license = create_test_license_with_subscription(
+            &conn,
+            &project.id,
+            &product.id,
+            Some(original_exp),
+            "lemonsqueezy",
+            "sub_ls_pause",
+        );
+        license_id = license.id.clone();
+    }
+
+    // Paused 2 days before the resume
+    let resumed_at = now();
+    for (event_name, updated_at) in [
+        ("subscription_paused", resumed_at - (2 * 86400)),
+        ("subscription_unpaused", resumed_at),
+    ] {
+        let updated_at = chrono::DateTime::from_timestamp(updated_at, 0)
+            .unwrap()
+            .to_rfc3339();
+        let payload = json!({
+            "meta": { "event_name": event_name },
+            "data": {
+                "id": "sub_ls_pause",
+                "attributes": {
+                    "customer_id": 12345,
+                    "status": "paused",
+                    "updated_at": updated_at
+                }
+            }
+        });
+        let payload_bytes = serde_json::to_vec(&payload).unwrap();
+        let signature = compute_lemonsqueezy_signature(&payload_bytes, "ls_whsec_test_secret");
+
+        let response = webhook_app(state.clone())
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/webhook/lemonsqueezy")
+                    .header("content-type", "application/json")
+                    .header("x-signature", signature)
+                    .body(Body::from(payload_bytes))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            response.status(),
+            axum::http::StatusCode::OK,
+            "{} webhook should return OK status",
+            event_name
+        );
+    }
+
+    let conn = state.db.get().unwrap();
+    let license = queries::get_license_by_id(&conn, &license_id)
+        .unwrap()
+        .unwrap();
+    assert!(license.paused_at.is_none(), "license should be resumed");
+    assert_eq!(
+        license.expires_at.unwrap() - original_exp,
+        2 * 86400,
+        "expiration should move out by the time between the two updates"
+    );
+}
+
 #[tokio::test]
 async fn test_webhook_provider_not_configured_returns_ok() {
     let state = create_test_app_state();
@@ -0,0 +1,217 @@
+//! Tests for `POST /operators/workspaces`: the whole tree is created in one
+//! transaction, and a failure anywhere in it leaves no rows behind.
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::handlers;
+
+struct WorkspaceFixture {
+    state: AppState,
+    admin_key: String,
+    view_key: String,
+}
+
+fn setup() -> WorkspaceFixture {
+    let state = create_test_app_state();
+    let mut conn = state.db.get().unwrap();
+    let (_, admin_key) = create_test_operator(&mut conn, "admin@test.com", OperatorRole::Admin);
+    let (_, view_key) = create_test_operator(&mut conn, "view@test.com", OperatorRole::View);
+
+    drop(conn);
+    WorkspaceFixture {
+        state,
+        admin_key,
+        view_key,
+    }
+}
+
+impl WorkspaceFixture {
+    async fn create(&self, key: &str, body: &Value) -> (StatusCode, Value) {
+        let app = handlers::operators::router(self.state.clone()).with_state(self.state.clone());
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/operators/workspaces")
+                    .header("Authorization", format!("Bearer {}", key))
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    fn count(&self, table: &str) -> i64 {
+        let conn = self.state.db.get().unwrap();
+        conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+            row.get(0)
+        })
+        .unwrap()
+    }
+
+    /// Nothing but the two operators from setup
+    fn assert_nothing_created(&self) {
+        assert_eq!(self.count("organizations"), 0);
+        assert_eq!(self.count("org_members"), 0);
+        assert_eq!(self.count("org_service_configs"), 0);
+        assert_eq!(self.count("users"), 2);
+        assert_eq!(self.count("api_keys"), 2);
+        assert_eq!(self.count("projects"), 0);
+        assert_eq!(self.count("products"), 0);
+        assert_eq!(self.count("product_provider_links"), 0);
+    }
+}
+
+fn workspace() -> Value {
+    json!({
+        "org": {
+            "name": "Acme",
+            "stripe_config": {
+                "secret_key": "sk_test_123",
+                "publishable_key": "pk_test_123",
+                "webhook_secret": "whsec_123"
+            },
+            "payment_provider": "stripe"
+        },
+        "owner": { "email": "owner@acme.com", "name": "Acme Owner" },
+        "projects": [{
+            "name": "Acme App",
+            "license_key_prefix": "ACME",
+            "products": [
+                {
+                    "name": "Pro",
+                    "tier": "pro",
+                    "price_cents": 4900,
+                    "currency": "usd",
+                    "provider_links": [{ "provider": "stripe", "linked_id": "price_pro" }]
+                },
+                { "name": "Basic", "tier": "basic" }
+            ]
+        }]
+    })
+}
+
+#[tokio::test]
+async fn test_workspace_created_with_whole_tree() {
+    let f = setup();
+    let (status, body) = f.create(&f.admin_key, &workspace()).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let org_id = body["org"]["id"].as_str().unwrap();
+    assert_eq!(body["org"]["name"], "Acme");
+    assert_eq!(body["org"]["defaults"]["payment"], "stripe");
+    assert_eq!(body["owner"]["email"], "owner@acme.com");
+
+    let projects = body["projects"].as_array().unwrap();
+    assert_eq!(projects.len(), 1);
+    assert_eq!(projects[0]["org_id"], org_id);
+    assert!(!projects[0]["public_key"].as_str().unwrap().is_empty());
+    let products = projects[0]["products"].as_array().unwrap();
+    assert_eq!(products.len(), 2);
+    assert_eq!(products[0]["tier"], "pro");
+    assert_eq!(products[0]["provider_links"][0]["linked_id"], "price_pro");
+    assert_eq!(products[1]["provider_links"], json!([]));
+
+    let conn = f.state.db.get().unwrap();
+    let (owner, _) = queries::get_user_by_api_key(
+        &conn,
+        body["owner_api_key"].as_str().unwrap(),
+        &f.state.emails(),
+    )
+    .unwrap()
+    .expect("owner API key should authenticate");
+    assert_eq!(owner.id, body["owner"]["id"]);
+    let member = queries::get_org_member_by_user_and_org(&conn, &owner.id, org_id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(member.role, OrgMemberRole::Owner);
+
+    let stripe = queries::get_org_stripe_config(&conn, org_id, &test_master_key())
+        .unwrap()
+        .unwrap();
+    assert_eq!(stripe.secret_key, "sk_test_123");
+    assert_eq!(f.count("products"), 2);
+
+    let entries = queries::query_audit_logs(
+        &f.state.audit.get().unwrap(),
+        &AuditLogQuery {
+            action: Some("create_workspace".into()),
+            ..Default::default()
+        },
+    )
+    .unwrap()
+    .0;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].resource_id, org_id);
+}
+
+#[tokio::test]
+async fn test_invalid_product_rolls_back_whole_workspace() {
+    let f = setup();
+    let mut body = workspace();
+    body["projects"][0]["products"][1]["tier"] = json!(" ");
+
+    let (status, response) = f.create(&f.admin_key, &body).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", response);
+    assert_eq!(response["field"]["path"], "projects[0].products[1]");
+    f.assert_nothing_created();
+}
+
+#[tokio::test]
+async fn test_failure_inside_transaction_rolls_back() {
+    let f = setup();
+    // Only found out once the org exists: LemonSqueezy has no config
+    let mut body = workspace();
+    body["org"]["payment_provider"] = json!("lemonsqueezy");
+
+    let (status, response) = f.create(&f.admin_key, &body).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", response);
+    assert_eq!(response["field"]["path"], "org");
+    f.assert_nothing_created();
+}
+
+#[tokio::test]
+async fn test_workspace_field_errors_report_their_path() {
+    let f = setup();
+    let mut duplicate_link = workspace();
+    duplicate_link["projects"][0]["products"][0]["provider_links"][1] =
+        json!({ "provider": "stripe", "linked_id": "price_other" });
+    let mut existing_owner = workspace();
+    existing_owner["owner"]["email"] = json!("admin@test.com");
+    let mut bad_project = workspace();
+    bad_project["projects"][0]["license_key_prefix"] = json!("");
+
+    for (body, path) in [
+        (duplicate_link, "projects[0].products[0].provider_links[1]"),
+        (existing_owner, "owner.email"),
+        (bad_project, "projects[0]"),
+    ] {
+        let (status, response) = f.create(&f.admin_key, &body).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", response);
+        assert_eq!(response["field"]["path"], path);
+    }
+    f.assert_nothing_created();
+}
+
+#[tokio::test]
+async fn test_workspace_requires_admin_operator() {
+    let f = setup();
+    let (status, _) = f.create(&f.view_key, &workspace()).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    f.assert_nothing_created();
+}
@@ -44,7 +44,7 @@ async fn test_license_list_large_dataset() {
 
     for i in 0..1000 {
         let input = CreateLicense {
-            email_hash: Some(email_hasher.hash(&format!("user{}@example.com", i))),
+            email_hash: Some(email_hasher.hash(&parse_email(&format!("user{}@example.com", i)))),
             customer_id: Some(format!("customer-{}", i)),
             expires_at: Some(future_timestamp(ONE_YEAR)),
             updates_expires_at: Some(future_timestamp(ONE_YEAR)),
@@ -52,6 +52,7 @@ async fn test_license_list_large_dataset() {
             payment_provider_customer_id: None,
             payment_provider_subscription_id: None,
             payment_provider_order_id: None,
+            seats: None,
         };
         queries::create_license(&mut conn, &project.id, &product.id, &input)
             .expect("Failed to create license");
@@ -109,7 +110,7 @@ async fn test_license_list_large_dataset() {
 
     // Query with email filter
     let start = Instant::now();
-    let email_hash = email_hasher.hash("user500@example.com");
+    let email_hash = email_hasher.hash(&parse_email("user500@example.com"));
     let (filtered, filter_total) = queries::get_all_licenses_by_email_hash_for_admin_paginated(
         &conn,
         &project.id,
@@ -199,8 +200,10 @@ async fn test_audit_log_large_volume() {
             to_timestamp: None,
             auth_type: None,
             auth_credential: None,
+            request_id: None,
             limit,
             offset,
+            project_ids: None,
         }
     }
 
@@ -303,6 +306,14 @@ async fn test_license_many_devices() {
         device_limit: None,           // None = unlimited
         device_inactive_days: None,
         features: vec!["unlimited_devices".to_string()],
+        entitlements: Default::default(),
+        seat_count: None,
+        available_from: None,
+        available_until: None,
+        checkout_fields: vec![],
+        extends_updates_for_product_id: None,
+        renewal_fallback: Default::default(),
+        revoke_on_partial_refund: false,
     };
     let product =
         queries::create_product(&mut conn, &project.id, &input).expect("Failed to create product");
@@ -442,7 +453,7 @@ async fn test_user_many_api_keys() {
     // Authenticate with one of the keys
     let test_key = &active_keys[25];
     let start = Instant::now();
-    let auth_result = queries::get_user_by_api_key(&mut conn, test_key);
+    let auth_result = queries::get_user_by_api_key(&mut conn, test_key, &test_email_column());
     let auth_duration = start.elapsed();
     assert!(auth_result.is_ok(), "Authentication should succeed");
     let (auth_user, auth_key) = auth_result.unwrap().expect("Should find user by API key");
@@ -494,6 +505,23 @@ async fn test_org_many_projects() {
             email_from: None,
             email_enabled: true,
             email_webhook_url: None,
+            email_webhook_secret: None,
+            jwt_issuer: None,
+            jwt_audience: None,
+            upgrade_auto_discount: false,
+            upgrade_old_license: UpgradeOldLicense::Revoke,
+            allow_project_id_auth: true,
+            allow_link_checkout: false,
+            max_validations_per_hour_per_license: None,
+            statement_descriptor_suffix: None,
+            receipt_email_enabled: false,
+            checkout_message: None,
+            attestation_audiences: vec![],
+            duplicate_purchase_check: true,
+            converted_trial_action: ConvertedTrialAction::Keep,
+            require_terms_acceptance: false,
+            checkout_metadata_keys: vec![],
+            templates: vec![],
         };
         let (private_key, public_key) = jwt::generate_keypair();
         queries::create_project(
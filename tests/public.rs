@@ -3,6 +3,9 @@
 #[path = "public/buy.rs"]
 mod buy;
 
+#[path = "public/duplicate_purchase.rs"]
+mod duplicate_purchase;
+
 #[path = "public/callback.rs"]
 mod callback;
 
@@ -12,6 +15,9 @@ mod redeem;
 #[path = "public/validate.rs"]
 mod validate;
 
+#[path = "public/attestation.rs"]
+mod attestation;
+
 #[path = "public/license.rs"]
 mod license;
 
@@ -20,3 +26,51 @@ mod devices;
 
 #[path = "public/refresh.rs"]
 mod refresh;
+
+#[path = "public/token_claims.rs"]
+mod token_claims;
+
+#[path = "public/project_lookup.rs"]
+mod project_lookup;
+
+#[path = "public/project_key.rs"]
+mod project_key;
+
+#[path = "public/catalog.rs"]
+mod catalog;
+
+#[path = "public/provider_calls.rs"]
+mod provider_calls;
+
+#[path = "public/provider_errors.rs"]
+mod provider_errors;
+
+#[path = "public/validation_throttling.rs"]
+mod validation_throttling;
+
+#[path = "public/checkout_settings.rs"]
+mod checkout_settings;
+
+#[path = "public/updates.rs"]
+mod updates;
+
+#[path = "public/downloads.rs"]
+mod downloads;
+
+#[path = "public/buy_wait.rs"]
+mod buy_wait;
+
+#[path = "public/client_flags.rs"]
+mod client_flags;
+
+#[path = "public/project_health.rs"]
+mod project_health;
+
+#[path = "public/custom_domains.rs"]
+mod custom_domains;
+
+#[path = "public/usage_geo.rs"]
+mod usage_geo;
+
+#[path = "public/seen_versions.rs"]
+mod seen_versions;
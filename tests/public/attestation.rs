@@ -0,0 +1,277 @@
+//! Tests for GET /validate/attest - short-lived license attestations for
+//! third parties.
+
+use axum::{body::Body, http::Request, http::StatusCode};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::Utc;
+use serde_json::Value;
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::{
+    DeviceType, ONE_YEAR, RevokeLicense, create_test_app_state, create_test_device,
+    create_test_license, create_test_org, create_test_product, create_test_project,
+    future_timestamp, public_app, queries, test_master_key,
+};
+
+use paycheck::jwt::{self, ATTESTATION_LIFETIME_SECS, DeviceInfo};
+
+const AUDIENCE: &str = "plugins.example.com";
+
+struct Setup {
+    app: axum::Router,
+    state: paycheck::db::AppState,
+    token: String,
+    public_key: String,
+    license_id: String,
+    product_id: String,
+    device_id: String,
+    device_jti: String,
+}
+
+/// Project allowing `AUDIENCE`, with an active license and a device token
+fn setup() -> Setup {
+    let state = create_test_app_state();
+    let master_key = test_master_key();
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&mut conn, "Test Org");
+    let project = create_test_project(&mut conn, &org.id, "Test Project", &master_key);
+    conn.execute(
+        "UPDATE projects SET attestation_audiences = ?1 WHERE id = ?2",
+        rusqlite::params![format!("[\"{}\"]", AUDIENCE), project.id],
+    )
+    .unwrap();
+    let project = queries::get_project_by_id(&conn, &project.id)
+        .unwrap()
+        .unwrap();
+    let product = create_test_product(&mut conn, &project.id, "Pro Plan", "pro");
+    let license = create_test_license(
+        &conn,
+        &project.id,
+        &product.id,
+        Some(future_timestamp(ONE_YEAR)),
+    );
+    let device = create_test_device(&mut conn, &license.id, "test-device", DeviceType::Uuid);
+
+    let license_token = jwt::build_license_claims(
+        &license,
+        &product,
+        &project,
+        &DeviceInfo {
+            device_id: &device.device_id,
+            device_type: device.device_type,
+            activated_at: device.activated_at,
+        },
+    );
+    let private_key = queries::decrypt_project_private_key(&conn, &project, &master_key).unwrap();
+    let token = jwt::sign_license_token(&license_token, &private_key, &device.jti).unwrap();
+
+    Setup {
+        app: public_app(state.clone()),
+        state: state.clone(),
+        token,
+        public_key: project.public_key,
+        license_id: license.id,
+        product_id: product.id,
+        device_id: device.device_id,
+        device_jti: device.jti,
+    }
+}
+
+async fn attest(setup: &Setup, audience: &str) -> (StatusCode, Value) {
+    let response = setup
+        .app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("GET")
+                .uri(format!(
+                    "/validate/attest?audience={}&public_key={}",
+                    urlencoding::encode(audience),
+                    urlencoding::encode(&setup.public_key)
+                ))
+                .header("Authorization", format!("Bearer {}", setup.token))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+fn payload(token: &str) -> Value {
+    let payload = token.split('.').nth(1).expect("JWT should have a payload");
+    serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn test_attestation_verifies_against_project_key_for_its_audience() {
+    let setup = setup();
+
+    let (status, json) = attest(&setup, AUDIENCE).await;
+    assert_eq!(
+        status,
+        StatusCode::OK,
+        "allowlisted audience should be attested: {}",
+        json
+    );
+
+    let token = json["attestation"].as_str().unwrap();
+    let claims =
+        jwt::verify_attestation(token, &setup.public_key, AUDIENCE, Utc::now().timestamp())
+            .expect("attestation should verify against the project's public key");
+
+    assert_eq!(
+        claims.custom.license_hash,
+        jwt::license_hash(&setup.license_id, AUDIENCE)
+    );
+    assert_eq!(claims.custom.tier, "pro");
+    assert_eq!(claims.custom.product_id, setup.product_id);
+    assert!(
+        claims.custom.valid_until.is_some(),
+        "a license with an expiration should carry valid_until"
+    );
+
+    assert!(
+        jwt::verify_attestation(
+            token,
+            &setup.public_key,
+            "other.example.com",
+            Utc::now().timestamp()
+        )
+        .is_err(),
+        "attestation must not verify for a different audience"
+    );
+}
+
+#[tokio::test]
+async fn test_disallowed_audience_is_rejected() {
+    let setup = setup();
+
+    let (status, json) = attest(&setup, "evil.example.com").await;
+
+    assert_eq!(
+        status,
+        StatusCode::FORBIDDEN,
+        "audience outside attestation_audiences should be rejected"
+    );
+    assert!(json.get("attestation").is_none());
+}
+
+#[tokio::test]
+async fn test_project_without_audiences_issues_no_attestations() {
+    let setup = setup();
+    {
+        let conn = setup.state.db.get().unwrap();
+        conn.execute("UPDATE projects SET attestation_audiences = '[]'", [])
+            .unwrap();
+    }
+
+    let (status, _) = attest(&setup, AUDIENCE).await;
+
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_attestation_expires_after_five_minutes() {
+    let setup = setup();
+
+    let (status, json) = attest(&setup, AUDIENCE).await;
+    assert_eq!(status, StatusCode::OK);
+    let token = json["attestation"].as_str().unwrap();
+
+    let claims = payload(token);
+    let iat = claims["iat"].as_i64().unwrap();
+    let exp = claims["exp"].as_i64().unwrap();
+    assert_eq!(exp - iat, ATTESTATION_LIFETIME_SECS as i64);
+    assert!(
+        (json["expires_at"].as_i64().unwrap() - exp).abs() <= 1,
+        "expires_at should match the token's exp"
+    );
+
+    assert!(
+        jwt::verify_attestation(token, &setup.public_key, AUDIENCE, exp - 1).is_ok(),
+        "attestation should verify until it expires"
+    );
+    assert!(
+        jwt::verify_attestation(token, &setup.public_key, AUDIENCE, exp + 3600).is_err(),
+        "expired attestation must not verify"
+    );
+}
+
+#[tokio::test]
+async fn test_attestation_carries_no_sensitive_claims() {
+    let setup = setup();
+
+    let (status, json) = attest(&setup, AUDIENCE).await;
+    assert_eq!(status, StatusCode::OK);
+    let token = json["attestation"].as_str().unwrap();
+    let claims = payload(token);
+
+    for key in [
+        "sub",
+        "jti",
+        "email",
+        "email_hash",
+        "device_id",
+        "device_type",
+        "features",
+    ] {
+        assert!(
+            claims.get(key).is_none(),
+            "attestation must not carry `{}`: {}",
+            key,
+            claims
+        );
+    }
+    let raw = claims.to_string();
+    for secret in [&setup.license_id, &setup.device_id, &setup.device_jti] {
+        assert!(
+            !raw.contains(secret.as_str()),
+            "attestation must not leak {}: {}",
+            secret,
+            raw
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_revoked_license_is_not_attested() {
+    let setup = setup();
+    {
+        let conn = setup.state.db.get().unwrap();
+        queries::revoke_license(&conn, &setup.license_id, &RevokeLicense::default(), None).unwrap();
+    }
+
+    let (status, json) = attest(&setup, AUDIENCE).await;
+
+    assert_eq!(status, StatusCode::FORBIDDEN, "revoked license: {}", json);
+    assert!(json.get("attestation").is_none());
+}
+
+#[tokio::test]
+async fn test_revoked_token_is_not_attested() {
+    let setup = setup();
+    {
+        let conn = setup.state.db.get().unwrap();
+        conn.execute(
+            "UPDATE devices SET jti = 'replaced' WHERE jti = ?1",
+            [&setup.device_jti],
+        )
+        .unwrap();
+    }
+
+    let (status, _) = attest(&setup, AUDIENCE).await;
+
+    assert_eq!(
+        status,
+        StatusCode::FORBIDDEN,
+        "a token no device holds anymore should not be attested"
+    );
+}
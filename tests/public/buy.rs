@@ -1,7 +1,8 @@
-//! Tests for the POST /buy endpoint validation logic.
+//! Tests for the /buy endpoint validation logic and response negotiation.
 //!
 //! Note: These tests only cover validation errors that occur before payment
 //! provider API calls. Full buy flow testing would require HTTP mocking.
+//! Free products never reach a provider, so their flow is tested end to end.
 //!
 //! The /buy endpoint now only requires product_id. Device info is NOT required
 //! since purchase ≠ activation. Users activate via /redeem/key with device info.
@@ -15,6 +16,10 @@ use tower::ServiceExt;
 mod common;
 use common::*;
 
+use paycheck::error::msg;
+use paycheck::extractors::PayloadSource;
+use paycheck::handlers::public::{BuyRequest, BuyResponse, UpgradeQuote, buy_response};
+
 #[tokio::test]
 async fn test_buy_product_not_found_returns_error() {
     let state = create_test_app_state();
@@ -285,3 +290,860 @@ async fn test_buy_accepts_optional_fields() {
         details
     );
 }
+
+// ============ Upgrade purchases ============
+
+struct UpgradeFixture {
+    state: AppState,
+    project: Project,
+    basic: Product,
+    pro: Product,
+    license: License,
+}
+
+/// A project with Basic and Pro products and an active Basic license
+/// for "test-customer" with 73 of its 365 days left.
+fn upgrade_fixture() -> UpgradeFixture {
+    let state = create_test_app_state();
+    let conn = state.db.get().unwrap();
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "Test Project", &state.master_key);
+    let basic = create_test_product(&conn, &project.id, "Basic Plan", "basic");
+    let pro = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+    let license = create_test_license(&conn, &project.id, &basic.id, Some(future_timestamp(73)));
+    drop(conn);
+
+    UpgradeFixture {
+        state,
+        project,
+        basic,
+        pro,
+        license,
+    }
+}
+
+async fn post_buy(state: &AppState, body: Value) -> (axum::http::StatusCode, String) {
+    let response = public_app(state.clone())
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/buy")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: Value = serde_json::from_slice(&body).expect("Response should be valid JSON");
+    (
+        status,
+        json["details"].as_str().unwrap_or_default().to_string(),
+    )
+}
+
+async fn buy_upgrade(
+    f: &UpgradeFixture,
+    license_id: &str,
+    customer_id: Option<&str>,
+) -> (axum::http::StatusCode, String) {
+    post_buy(
+        &f.state,
+        json!({
+            "product_id": f.pro.id,
+            "customer_id": customer_id,
+            "upgrade_from_license_id": license_id,
+        }),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn test_buy_upgrade_unknown_license_returns_404() {
+    let f = upgrade_fixture();
+
+    let (status, details) = buy_upgrade(&f, "nonexistent-license", Some("test-customer")).await;
+    assert_eq!(status, axum::http::StatusCode::NOT_FOUND);
+    assert_eq!(details, "License to upgrade not found");
+}
+
+#[tokio::test]
+async fn test_buy_upgrade_license_from_other_project_returns_404() {
+    let f = upgrade_fixture();
+    let conn = f.state.db.get().unwrap();
+    let other_project = create_test_project(
+        &conn,
+        &f.project.org_id,
+        "Other Project",
+        &f.state.master_key,
+    );
+    let other_product = create_test_product(&conn, &other_project.id, "Other Plan", "basic");
+    let other_license = create_test_license(
+        &conn,
+        &other_project.id,
+        &other_product.id,
+        Some(future_timestamp(73)),
+    );
+    drop(conn);
+
+    let (status, details) = buy_upgrade(&f, &other_license.id, Some("test-customer")).await;
+    assert_eq!(
+        status,
+        axum::http::StatusCode::NOT_FOUND,
+        "licenses from other projects look missing"
+    );
+    assert_eq!(details, "License to upgrade not found");
+}
+
+#[tokio::test]
+async fn test_buy_upgrade_inactive_license_rejected() {
+    let f = upgrade_fixture();
+    let conn = f.state.db.get().unwrap();
+    let expired = create_test_license(&conn, &f.project.id, &f.basic.id, Some(past_timestamp(1)));
+    queries::revoke_license(&conn, &f.license.id, &RevokeLicense::default(), None).unwrap();
+    drop(conn);
+
+    for license_id in [&f.license.id, &expired.id] {
+        let (status, details) = buy_upgrade(&f, license_id, Some("test-customer")).await;
+        assert_eq!(status, axum::http::StatusCode::BAD_REQUEST);
+        assert_eq!(details, "License to upgrade is revoked or expired");
+    }
+}
+
+#[tokio::test]
+async fn test_buy_upgrade_customer_mismatch_rejected() {
+    let f = upgrade_fixture();
+
+    for customer_id in [Some("someone-else"), None] {
+        let (status, details) = buy_upgrade(&f, &f.license.id, customer_id).await;
+        assert_eq!(
+            status,
+            axum::http::StatusCode::BAD_REQUEST,
+            "customer_id {:?}",
+            customer_id
+        );
+        assert_eq!(details, "customer_id does not match the license to upgrade");
+    }
+}
+
+#[tokio::test]
+async fn test_buy_upgrade_to_same_product_rejected() {
+    let f = upgrade_fixture();
+
+    let (status, details) = post_buy(
+        &f.state,
+        json!({
+            "product_id": f.basic.id,
+            "customer_id": "test-customer",
+            "upgrade_from_license_id": f.license.id,
+        }),
+    )
+    .await;
+    assert_eq!(status, axum::http::StatusCode::BAD_REQUEST);
+    assert_eq!(details, "License is already for this product");
+}
+
+#[tokio::test]
+async fn test_buy_valid_upgrade_passes_verification() {
+    let f = upgrade_fixture();
+
+    // Gets as far as the payment provider check
+    let (status, details) = buy_upgrade(&f, &f.license.id, Some("test-customer")).await;
+    assert_eq!(status, axum::http::StatusCode::BAD_REQUEST);
+    assert_eq!(details, "No payment provider configured");
+}
+
+#[test]
+fn test_upgrade_quote_prorates_remaining_value() {
+    let f = upgrade_fixture();
+    let conn = f.state.db.get().unwrap();
+    let now = f.license.expires_at.unwrap() - 73 * 86400;
+
+    let quote = UpgradeQuote::for_license(
+        &conn,
+        &f.project,
+        &f.pro,
+        &f.license.id,
+        Some("test-customer"),
+        now,
+    )
+    .unwrap();
+    assert_eq!(quote.from_product.id, f.basic.id);
+    assert_eq!(quote.days_remaining, Some(73));
+    // 73 of 365 days left on a $49.99 license, rounded down
+    assert_eq!(quote.credit_cents, Some(999));
+
+    // A perpetual license keeps its full value
+    let perpetual = create_test_license(&conn, &f.project.id, &f.basic.id, None);
+    let quote = UpgradeQuote::for_license(
+        &conn,
+        &f.project,
+        &f.pro,
+        &perpetual.id,
+        Some("test-customer"),
+        now,
+    )
+    .unwrap();
+    assert_eq!(quote.days_remaining, None);
+    assert_eq!(quote.credit_cents, Some(4999));
+}
+
+// ============ Sale windows ============
+
+/// Sale window of the product in `sale_window_state`
+const ON_SALE_FROM: i64 = 1_767_225_600;
+const ON_SALE_UNTIL: i64 = ON_SALE_FROM + 14 * 86400;
+
+/// A product on sale from `ON_SALE_FROM` until `ON_SALE_UNTIL`, with the clock
+/// pinned to `at`.
+fn sale_window_state(at: i64) -> (AppState, Product) {
+    let mut state = create_test_app_state();
+    state.clock = Clock::fixed(at);
+    let conn = state.db.get().unwrap();
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "Test Project", &state.master_key);
+    let product = create_test_product(&conn, &project.id, "Launch Edition", "pro");
+    conn.execute(
+        "UPDATE products SET available_from = ?1, available_until = ?2 WHERE id = ?3",
+        rusqlite::params![ON_SALE_FROM, ON_SALE_UNTIL, product.id],
+    )
+    .unwrap();
+    drop(conn);
+    (state, product)
+}
+
+async fn buy_at(at: i64) -> (axum::http::StatusCode, Value) {
+    let (state, product) = sale_window_state(at);
+    let response = public_app(state)
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/buy")
+                .header("content-type", "application/json")
+                .body(Body::from(json!({ "product_id": product.id }).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+#[tokio::test]
+async fn test_buy_before_sale_window_rejected_as_coming_soon() {
+    let (status, json) = buy_at(ON_SALE_FROM - 1).await;
+
+    assert_eq!(status, axum::http::StatusCode::BAD_REQUEST);
+    assert_eq!(json["code"], "product_unavailable");
+    assert_eq!(json["details"], "Product is not on sale yet");
+    assert_eq!(json["window"]["availability"], "coming_soon");
+    assert_eq!(json["window"]["available_from"], ON_SALE_FROM);
+    assert_eq!(json["window"]["available_until"], ON_SALE_UNTIL);
+}
+
+#[tokio::test]
+async fn test_buy_at_sale_window_bounds() {
+    // Start is inclusive, end is exclusive
+    for at in [ON_SALE_FROM, ON_SALE_UNTIL - 1] {
+        let (status, json) = buy_at(at).await;
+        assert_eq!(status, axum::http::StatusCode::BAD_REQUEST, "at {}", at);
+        assert_eq!(
+            json["details"], "No payment provider configured",
+            "at {} should get past the sale window check",
+            at
+        );
+        assert!(json["code"].is_null());
+    }
+}
+
+#[tokio::test]
+async fn test_buy_after_sale_window_rejected_as_ended() {
+    let (status, json) = buy_at(ON_SALE_UNTIL).await;
+
+    assert_eq!(status, axum::http::StatusCode::BAD_REQUEST);
+    assert_eq!(json["code"], "product_unavailable");
+    assert_eq!(json["details"], "Product is no longer on sale");
+    assert_eq!(json["window"]["availability"], "ended");
+}
+
+// ============ Checkout fields ============
+
+/// A product collecting a required company name and an optional billing email
+/// and seat estimate. No payment provider is configured, so fields that pass
+/// validation fail on the provider config instead.
+fn checkout_fields_state() -> (AppState, Product) {
+    let state = create_test_app_state();
+    let conn = state.db.get().unwrap();
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "Test Project", &state.master_key);
+    let product = create_test_product(&conn, &project.id, "Business", "business");
+    let fields = json!([
+        { "key": "company", "label": "Company", "type": "text", "required": true },
+        { "key": "billing_email", "label": "Billing email", "type": "email" },
+        { "key": "team_size", "label": "Team size", "type": "number" }
+    ]);
+    conn.execute(
+        "UPDATE products SET checkout_fields = ?1 WHERE id = ?2",
+        rusqlite::params![fields.to_string(), product.id],
+    )
+    .unwrap();
+    let product = queries::get_product_by_id(&conn, &product.id)
+        .unwrap()
+        .unwrap();
+    drop(conn);
+    (state, product)
+}
+
+async fn buy_with_fields(fields: Value) -> (axum::http::StatusCode, Value) {
+    let (state, product) = checkout_fields_state();
+    send_buy(
+        &state,
+        Request::builder()
+            .method("POST")
+            .uri("/buy")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                json!({ "product_id": product.id, "fields": fields }).to_string(),
+            ))
+            .unwrap(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn test_buy_missing_required_checkout_field_rejected() {
+    for fields in [json!({}), json!({ "company": "   " })] {
+        let (status, json) = buy_with_fields(fields.clone()).await;
+        assert_eq!(status, axum::http::StatusCode::BAD_REQUEST, "{}", fields);
+        assert_eq!(json["details"], "Missing required checkout field 'company'");
+    }
+}
+
+#[tokio::test]
+async fn test_buy_invalid_checkout_field_values_rejected() {
+    let long = "x".repeat(501);
+    for (fields, details) in [
+        (
+            json!({ "company": "Acme", "billing_email": "not-an-email" }),
+            "Checkout field 'billing_email' must be an email address",
+        ),
+        (
+            json!({ "company": "Acme", "team_size": "a dozen" }),
+            "Checkout field 'team_size' must be a number",
+        ),
+        (
+            json!({ "company": "Acme", "vat_id": "GB123" }),
+            "Unknown checkout field 'vat_id'",
+        ),
+        (
+            json!({ "company": long }),
+            "Checkout field 'company' must be at most 500 characters",
+        ),
+    ] {
+        let (status, json) = buy_with_fields(fields).await;
+        assert_eq!(status, axum::http::StatusCode::BAD_REQUEST);
+        assert_eq!(json["details"], details);
+    }
+}
+
+#[tokio::test]
+async fn test_buy_valid_checkout_fields_accepted() {
+    let (status, json) = buy_with_fields(json!({
+        "company": "Acme <b>Inc</b>",
+        "billing_email": "Billing@Acme.example",
+        "team_size": "12"
+    }))
+    .await;
+
+    assert_eq!(status, axum::http::StatusCode::BAD_REQUEST);
+    assert_eq!(
+        json["details"], "No payment provider configured",
+        "valid fields should get past the checkout field check"
+    );
+}
+
+#[test]
+fn test_checkout_values_normalized() {
+    let (_, product) = checkout_fields_state();
+    let values = [
+        ("company", "  Acme  "),
+        ("billing_email", " Billing@Acme.example "),
+        ("team_size", ""),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_string(), v.to_string()))
+    .collect();
+
+    let accepted = product.checkout_values(&values).unwrap();
+
+    assert_eq!(accepted["company"], "Acme");
+    assert_eq!(accepted["billing_email"], "billing@acme.example");
+    assert!(
+        !accepted.contains_key("team_size"),
+        "blank optional fields are dropped"
+    );
+}
+
+// ============ Checkout metadata ============
+
+/// A project allowing `utm_source` and `campaign` metadata, with no payment
+/// provider so accepted metadata fails on the provider config instead.
+async fn buy_with_metadata(metadata: Value) -> (axum::http::StatusCode, Value) {
+    let state = create_test_app_state();
+    let conn = state.db.get().unwrap();
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "Test Project", &state.master_key);
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+    conn.execute(
+        "UPDATE projects SET checkout_metadata_keys = ?1 WHERE id = ?2",
+        rusqlite::params![r#"["utm_source","campaign"]"#, project.id],
+    )
+    .unwrap();
+    drop(conn);
+
+    send_buy(
+        &state,
+        Request::builder()
+            .method("POST")
+            .uri("/buy")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                json!({ "product_id": product.id, "metadata": metadata }).to_string(),
+            ))
+            .unwrap(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn test_buy_disallowed_metadata_key_rejected() {
+    let (status, json) =
+        buy_with_metadata(json!({ "utm_source": "newsletter", "referrer": "x" })).await;
+    assert_eq!(status, axum::http::StatusCode::BAD_REQUEST);
+    assert_eq!(
+        json["details"],
+        "Metadata key 'referrer' is not allowed for this project"
+    );
+}
+
+#[tokio::test]
+async fn test_buy_overlong_metadata_value_rejected() {
+    let (status, json) = buy_with_metadata(json!({ "campaign": "x".repeat(501) })).await;
+    assert_eq!(status, axum::http::StatusCode::BAD_REQUEST);
+    assert_eq!(
+        json["details"],
+        "Metadata 'campaign' must be at most 500 characters"
+    );
+}
+
+#[tokio::test]
+async fn test_buy_allowed_metadata_accepted() {
+    let (status, json) =
+        buy_with_metadata(json!({ "utm_source": "newsletter", "campaign": "" })).await;
+    assert_eq!(status, axum::http::StatusCode::BAD_REQUEST);
+    assert_eq!(
+        json["details"], "No payment provider configured",
+        "allowed metadata should get past the metadata check"
+    );
+}
+
+// ============ Form bodies and purchase links ============
+
+/// A project with one product and no payment provider, so a request that
+/// parses and passes the link checks fails on the provider config.
+fn link_fixture(allow_link_checkout: bool) -> (AppState, Project, Product) {
+    let state = create_test_app_state();
+    let conn = state.db.get().unwrap();
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "Test Project", &state.master_key);
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+    conn.execute(
+        "UPDATE projects SET allow_link_checkout = ?1 WHERE id = ?2",
+        rusqlite::params![allow_link_checkout, project.id],
+    )
+    .unwrap();
+    drop(conn);
+    (state, project, product)
+}
+
+fn link_uri(project: &Project, product: &Product) -> String {
+    format!(
+        "/buy?public_key={}&product_id={}",
+        urlencoding::encode(&project.public_key),
+        product.id
+    )
+}
+
+async fn send_buy(state: &AppState, request: Request<Body>) -> (axum::http::StatusCode, Value) {
+    let response = public_app(state.clone()).oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+#[tokio::test]
+async fn test_buy_accepts_form_body() {
+    let (state, project, product) = link_fixture(false);
+
+    let form = format!(
+        "public_key={}&product_id={}&customer_id=cust_123",
+        urlencoding::encode(&project.public_key),
+        product.id
+    );
+    let (status, json) = send_buy(
+        &state,
+        Request::builder()
+            .method("POST")
+            .uri("/buy")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(Body::from(form))
+            .unwrap(),
+    )
+    .await;
+
+    // Form POSTs don't need allow_link_checkout
+    assert_eq!(status, axum::http::StatusCode::BAD_REQUEST);
+    assert_eq!(json["details"], "No payment provider configured");
+}
+
+#[tokio::test]
+async fn test_buy_invalid_form_body_returns_json_error() {
+    let (state, _, _) = link_fixture(false);
+
+    let (status, json) = send_buy(
+        &state,
+        Request::builder()
+            .method("POST")
+            .uri("/buy")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(Body::from("provider=stripe"))
+            .unwrap(),
+    )
+    .await;
+
+    assert_eq!(status, axum::http::StatusCode::BAD_REQUEST);
+    assert_eq!(json["error"], "Invalid form body");
+}
+
+#[tokio::test]
+async fn test_buy_link_requires_allow_link_checkout() {
+    let (state, project, product) = link_fixture(false);
+
+    let (status, json) = send_buy(
+        &state,
+        Request::builder()
+            .uri(link_uri(&project, &product))
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+
+    assert_eq!(status, axum::http::StatusCode::FORBIDDEN);
+    assert_eq!(json["details"], msg::LINK_CHECKOUT_DISABLED);
+}
+
+#[tokio::test]
+async fn test_buy_link_accepted_when_enabled() {
+    let (state, project, product) = link_fixture(true);
+
+    let (status, json) = send_buy(
+        &state,
+        Request::builder()
+            .uri(link_uri(&project, &product))
+            .header("sec-fetch-mode", "navigate")
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+
+    assert_eq!(status, axum::http::StatusCode::BAD_REQUEST);
+    assert_eq!(json["details"], "No payment provider configured");
+}
+
+#[tokio::test]
+async fn test_buy_link_refuses_scripted_requests() {
+    let (state, project, product) = link_fixture(true);
+
+    for mode in ["cors", "no-cors", "same-origin"] {
+        let (status, json) = send_buy(
+            &state,
+            Request::builder()
+                .uri(link_uri(&project, &product))
+                .header("sec-fetch-mode", mode)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+
+        assert_eq!(status, axum::http::StatusCode::FORBIDDEN, "mode {}", mode);
+        assert_eq!(json["details"], msg::LINK_CHECKOUT_SCRIPTED);
+    }
+}
+
+/// Negotiate a successful purchase's response for a request with the given
+/// `redirect` field, source and `Accept` header. Returns (status, Location).
+fn negotiate(
+    redirect: Option<bool>,
+    source: PayloadSource,
+    accept: Option<&str>,
+) -> (axum::http::StatusCode, Option<String>) {
+    let mut headers = axum::http::HeaderMap::new();
+    if let Some(accept) = accept {
+        headers.insert("accept", accept.parse().unwrap());
+    }
+    let request: BuyRequest =
+        serde_json::from_value(json!({ "product_id": "p", "redirect": redirect })).unwrap();
+    let buy = BuyResponse {
+        checkout_url: CHECKOUT_URL.into(),
+        session_id: "session-1".into(),
+        terms: None,
+    };
+    let response = buy_response(buy, &request, source, &headers);
+    let location = response
+        .headers()
+        .get("location")
+        .map(|value| value.to_str().unwrap().to_string());
+    (response.status(), location)
+}
+
+const CHECKOUT_URL: &str = "https://checkout.stripe.com/c/pay/cs_test_123";
+const BROWSER: &str = "text/html,application/xhtml+xml,*/*;q=0.8";
+
+#[test]
+fn test_buy_response_negotiation() {
+    use axum::http::StatusCode;
+
+    let ok = (StatusCode::OK, None);
+    let see_other = (StatusCode::SEE_OTHER, Some(CHECKOUT_URL.to_string()));
+    let cases = [
+        // JSON requests keep getting JSON
+        (None, PayloadSource::Json, None, &ok),
+        (None, PayloadSource::Json, Some(BROWSER), &ok),
+        // Forms and links redirect unless they ask for JSON
+        (None, PayloadSource::Form, Some(BROWSER), &see_other),
+        (None, PayloadSource::Query, None, &see_other),
+        (None, PayloadSource::Query, Some("application/json"), &ok),
+        // An explicit `redirect` wins
+        (Some(true), PayloadSource::Json, None, &see_other),
+        (Some(false), PayloadSource::Query, Some(BROWSER), &ok),
+    ];
+
+    for (redirect, source, accept, expected) in cases {
+        assert_eq!(
+            &negotiate(redirect, source, accept),
+            expected,
+            "redirect={:?} source={:?} accept={:?}",
+            redirect,
+            source,
+            accept
+        );
+    }
+}
+
+// ============ Free products ============
+
+/// A project with a free product (price 0) and no payment provider.
+fn free_product_state() -> (AppState, Product) {
+    let state = create_test_app_state();
+    let conn = state.db.get().unwrap();
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "Test Project", &state.master_key);
+    let product = create_test_product(&conn, &project.id, "Community", "community");
+    conn.execute(
+        "UPDATE products SET price_cents = 0 WHERE id = ?1",
+        rusqlite::params![product.id],
+    )
+    .unwrap();
+    let product = queries::get_product_by_id(&conn, &product.id)
+        .unwrap()
+        .unwrap();
+    drop(conn);
+    (state, product)
+}
+
+async fn claim_free(
+    state: &AppState,
+    body: Value,
+    client: Option<&str>,
+) -> (axum::http::StatusCode, Value) {
+    let mut request = Request::builder()
+        .method("POST")
+        .uri("/buy")
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    if let Some(addr) = client {
+        let addr: std::net::SocketAddr = addr.parse().unwrap();
+        request
+            .extensions_mut()
+            .insert(axum::extract::ConnectInfo(addr));
+    }
+    let response = public_app(state.clone()).oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+fn license_count(state: &AppState, product_id: &str) -> i64 {
+    let conn = state.db.get().unwrap();
+    conn.query_row(
+        "SELECT COUNT(*) FROM licenses WHERE product_id = ?1",
+        rusqlite::params![product_id],
+        |row| row.get(0),
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_free_product_issues_license_without_provider() {
+    let (state, product) = free_product_state();
+
+    let (status, json) = claim_free(
+        &state,
+        json!({ "product_id": product.id, "email": "fan@example.com" }),
+        None,
+    )
+    .await;
+    assert_eq!(status, axum::http::StatusCode::OK, "{}", json);
+    let session_id = json["session_id"].as_str().unwrap();
+    assert_eq!(
+        json["checkout_url"],
+        format!("{}/callback?session={}", state.base_url, session_id)
+    );
+
+    let conn = state.db.get().unwrap();
+    let session = queries::get_payment_session(&conn, session_id)
+        .unwrap()
+        .unwrap();
+    assert!(session.completed);
+    let license = queries::get_license_by_id(&conn, &session.license_id.unwrap())
+        .unwrap()
+        .unwrap();
+    assert_eq!(license.payment_provider.as_deref(), Some("free"));
+    assert_eq!(
+        license.email_hash,
+        Some(test_email_hasher().hash(&parse_email("fan@example.com")))
+    );
+    drop(conn);
+
+    // The callback hands out an activation code as for a paid purchase
+    let response = public_app(state.clone())
+        .oneshot(
+            Request::builder()
+                .uri(format!("/callback?session={}", session_id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let location = response.headers()["location"].to_str().unwrap();
+    assert!(location.contains("code="), "{}", location);
+    assert!(location.contains("status=success"), "{}", location);
+}
+
+#[tokio::test]
+async fn test_free_product_requires_email() {
+    let (state, product) = free_product_state();
+
+    for body in [
+        json!({ "product_id": product.id }),
+        json!({ "product_id": product.id, "email": "not-an-email" }),
+    ] {
+        let (status, _) = claim_free(&state, body.clone(), None).await;
+        assert_eq!(status, axum::http::StatusCode::BAD_REQUEST, "{}", body);
+    }
+    let (_, json) = claim_free(&state, json!({ "product_id": product.id }), None).await;
+    assert_eq!(json["details"], msg::FREE_PRODUCT_EMAIL_REQUIRED);
+    assert_eq!(license_count(&state, &product.id), 0);
+}
+
+#[tokio::test]
+async fn test_free_product_one_license_per_email() {
+    let (state, product) = free_product_state();
+    let claim = |email: &str| json!({ "product_id": product.id, "email": email });
+
+    let (status, _) = claim_free(&state, claim("fan@example.com"), None).await;
+    assert_eq!(status, axum::http::StatusCode::OK);
+
+    // Same address, differently written
+    let (status, json) = claim_free(&state, claim(" Fan@Example.com"), None).await;
+    assert_eq!(status, axum::http::StatusCode::CONFLICT);
+    assert_eq!(json["details"], msg::FREE_LICENSE_ALREADY_CLAIMED);
+
+    // A revoked license still counts as claimed
+    let conn = state.db.get().unwrap();
+    conn.execute("UPDATE licenses SET revoked = 1", []).unwrap();
+    drop(conn);
+    let (status, _) = claim_free(&state, claim("fan@example.com"), None).await;
+    assert_eq!(status, axum::http::StatusCode::CONFLICT);
+
+    let (status, _) = claim_free(&state, claim("other@example.com"), None).await;
+    assert_eq!(status, axum::http::StatusCode::OK);
+    assert_eq!(license_count(&state, &product.id), 2);
+}
+
+#[tokio::test]
+async fn test_free_claims_rate_limited_per_client() {
+    let (state, product) = free_product_state();
+    let claim =
+        |i: usize| json!({ "product_id": product.id, "email": format!("fan{}@example.com", i) });
+
+    for i in 0..5 {
+        let (status, _) = claim_free(&state, claim(i), Some("203.0.113.7:4000")).await;
+        assert_eq!(status, axum::http::StatusCode::OK, "claim {}", i);
+    }
+    let (status, json) = claim_free(&state, claim(5), Some("203.0.113.7:4001")).await;
+    assert_eq!(status, axum::http::StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(json["details"], msg::FREE_LICENSE_THROTTLED);
+
+    // Another client isn't affected
+    let (status, _) = claim_free(&state, claim(5), Some("198.51.100.1:4000")).await;
+    assert_eq!(status, axum::http::StatusCode::OK);
+    assert_eq!(license_count(&state, &product.id), 6);
+}
+
+#[tokio::test]
+async fn test_free_product_rejects_upgrade() {
+    let (state, product) = free_product_state();
+
+    let (status, json) = claim_free(
+        &state,
+        json!({
+            "product_id": product.id,
+            "email": "fan@example.com",
+            "upgrade_from_license_id": "some-license",
+        }),
+        None,
+    )
+    .await;
+    assert_eq!(status, axum::http::StatusCode::BAD_REQUEST);
+    assert_eq!(json["details"], msg::FREE_PRODUCT_NO_UPGRADE);
+}
+
+#[tokio::test]
+async fn test_paid_product_still_goes_through_provider() {
+    let (state, product) = free_product_state();
+    let conn = state.db.get().unwrap();
+    let paid = create_test_product(&conn, &product.project_id, "Pro", "pro");
+    drop(conn);
+
+    let (status, json) = claim_free(
+        &state,
+        json!({ "product_id": paid.id, "email": "fan@example.com" }),
+        None,
+    )
+    .await;
+    assert_eq!(status, axum::http::StatusCode::BAD_REQUEST);
+    assert_eq!(json["details"], "No payment provider configured");
+    assert_eq!(license_count(&state, &paid.id), 0);
+}
@@ -0,0 +1,300 @@
+//! Tests for GET /buy/wait and POST /redeem/claim: an app waits on its
+//! checkout, gets a one-time claim code once payment completes, and exchanges
+//! it for a license token without the buyer copying codes from email.
+
+use std::time::{Duration, Instant};
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    response::Response,
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+struct WaitFixture {
+    state: AppState,
+    project: Project,
+    product: Product,
+    session: PaymentSession,
+}
+
+fn setup() -> WaitFixture {
+    let state = create_test_app_state();
+    let conn = state.db.get().unwrap();
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+    let session = create_test_payment_session(&conn, &product.id, None);
+
+    drop(conn);
+    WaitFixture {
+        state,
+        project,
+        product,
+        session,
+    }
+}
+
+impl WaitFixture {
+    /// Complete the checkout, as its webhook would, with a new license.
+    fn complete(&self) -> License {
+        let conn = self.state.db.get().unwrap();
+        let license = create_test_license(
+            &conn,
+            &self.project.id,
+            &self.product.id,
+            Some(future_timestamp(ONE_YEAR)),
+        );
+        complete_payment_session(&conn, &self.session.id, &license.id);
+        license
+    }
+
+    async fn wait(&self, timeout: u64) -> (StatusCode, Value) {
+        let uri = format!("/buy/wait?session={}&timeout={}", self.session.id, timeout);
+        let response = public_app(self.state.clone())
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        (status, body_json(response).await)
+    }
+
+    /// Wait on the completed session and return its claim code.
+    async fn claim_code(&self) -> String {
+        let (status, body) = self.wait(0).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["activation_hint"], "claim", "{}", body);
+        body["claim_code"].as_str().unwrap().to_string()
+    }
+
+    async fn claim(&self, session_id: &str, code: &str, device_id: &str) -> (StatusCode, Value) {
+        let body = json!({
+            "public_key": self.project.public_key,
+            "session_id": session_id,
+            "code": code,
+            "device_id": device_id,
+            "device_type": "uuid",
+        });
+        let response = public_app(self.state.clone())
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/redeem/claim")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        (status, body_json(response).await)
+    }
+}
+
+async fn body_json(response: Response) -> Value {
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    serde_json::from_slice(&bytes).unwrap_or(Value::Null)
+}
+
+#[tokio::test]
+async fn test_pending_session_answers_pending_at_timeout() {
+    let f = setup();
+    let started = Instant::now();
+    let (status, body) = f.wait(1).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], "pending");
+    assert_eq!(body["activation_hint"], "wait");
+    assert!(body.get("claim_code").is_none());
+    assert!(started.elapsed() >= Duration::from_millis(500), "held open");
+}
+
+#[tokio::test]
+async fn test_wait_answers_as_soon_as_payment_completes() {
+    let f = setup();
+    let completer = {
+        let state = f.state.clone();
+        let (project_id, product_id, session_id) = (
+            f.project.id.clone(),
+            f.product.id.clone(),
+            f.session.id.clone(),
+        );
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            let conn = state.db.get().unwrap();
+            let license = create_test_license(&conn, &project_id, &product_id, None);
+            complete_payment_session(&conn, &session_id, &license.id);
+        })
+    };
+
+    let started = Instant::now();
+    let (status, body) = f.wait(20).await;
+    completer.await.unwrap();
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["status"], "completed");
+    assert_eq!(body["activation_hint"], "claim");
+    assert!(body["claim_code"].as_str().unwrap().starts_with("pcl_"));
+    assert!(body["claim_expires_at"].as_i64().is_some());
+    assert!(
+        started.elapsed() < Duration::from_secs(5),
+        "answered on completion, not at the timeout"
+    );
+}
+
+#[tokio::test]
+async fn test_claim_code_activates_once() {
+    let f = setup();
+    let license = f.complete();
+    let code = f.claim_code().await;
+
+    let (status, body) = f.claim(&f.session.id, &code, "laptop").await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert!(body["token"].as_str().is_some());
+    assert_eq!(body["tier"], "pro");
+    let devices =
+        queries::list_devices_for_license(&f.state.db.get().unwrap(), &license.id).unwrap();
+    assert_eq!(devices.len(), 1);
+
+    let (status, body) = f.claim(&f.session.id, &code, "desktop").await;
+    assert_eq!(status, StatusCode::FORBIDDEN, "{}", body);
+
+    // No new code once the license is claimed
+    let (_, body) = f.wait(0).await;
+    assert_eq!(body["status"], "completed");
+    assert_eq!(body["activation_hint"], "already_claimed");
+    assert!(body.get("claim_code").is_none());
+}
+
+#[tokio::test]
+async fn test_claim_code_is_bound_to_its_session() {
+    let f = setup();
+    f.complete();
+    let code = f.claim_code().await;
+
+    let other = create_test_payment_session(&f.state.db.get().unwrap(), &f.product.id, None);
+    let (status, _) = f.claim(&other.id, &code, "laptop").await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // Still good for its own session
+    let (status, body) = f.claim(&f.session.id, &code, "laptop").await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+#[tokio::test]
+async fn test_expired_claim_code_is_refused() {
+    let f = setup();
+    f.complete();
+    let code = f.claim_code().await;
+    f.state
+        .db
+        .get()
+        .unwrap()
+        .execute(
+            "UPDATE payment_sessions SET claim_expires_at = ?1 WHERE id = ?2",
+            rusqlite::params![past_timestamp(ONE_DAY), f.session.id],
+        )
+        .unwrap();
+
+    let (status, body) = f.claim(&f.session.id, &code, "laptop").await;
+    assert_eq!(status, StatusCode::FORBIDDEN, "{}", body);
+
+    // Waiting again hands out a fresh one
+    let code = f.claim_code().await;
+    let (status, _) = f.claim(&f.session.id, &code, "laptop").await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_new_wait_voids_previous_code() {
+    let f = setup();
+    f.complete();
+    let first = f.claim_code().await;
+    let second = f.claim_code().await;
+    assert_ne!(first, second);
+
+    let (status, _) = f.claim(&f.session.id, &first, "laptop").await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = f.claim(&f.session.id, &second, "laptop").await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_failed_activation_leaves_code_usable() {
+    let f = setup();
+    let (license, devices) = {
+        let conn = f.state.db.get().unwrap();
+        create_license_at_device_limit(&conn, &f.project.id, &f.product)
+    };
+    complete_payment_session(&f.state.db.get().unwrap(), &f.session.id, &license.id);
+    let code = f.claim_code().await;
+
+    let (status, _) = f.claim(&f.session.id, &code, "one-too-many").await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // Reactivating a device already on the license fits within the limit
+    let (status, body) = f.claim(&f.session.id, &code, &devices[0].device_id).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+#[tokio::test]
+async fn test_abandoned_checkout_is_expired() {
+    let f = setup();
+    f.state
+        .db
+        .get()
+        .unwrap()
+        .execute(
+            "UPDATE payment_sessions SET created_at = ?1 WHERE id = ?2",
+            rusqlite::params![past_timestamp(2 * ONE_DAY), f.session.id],
+        )
+        .unwrap();
+
+    let (status, body) = f.wait(5).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], "expired");
+    assert_eq!(body["activation_hint"], "start_over");
+}
+
+#[tokio::test]
+async fn test_review_session_offers_no_claim() {
+    let f = setup();
+    queries::mark_payment_session_for_review(&f.state.db.get().unwrap(), &f.session.id).unwrap();
+    queries::try_claim_payment_session(&f.state.db.get().unwrap(), &f.session.id).unwrap();
+
+    let (_, body) = f.wait(0).await;
+    assert_eq!(body["status"], "completed");
+    assert_eq!(body["activation_hint"], "review");
+    assert!(body.get("claim_code").is_none());
+}
+
+#[tokio::test]
+async fn test_unknown_session_is_not_found() {
+    let f = setup();
+    let response = public_app(f.state.clone())
+        .oneshot(
+            Request::builder()
+                .uri("/buy/wait?session=missing&timeout=0")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_waits_are_rate_limited_per_session() {
+    let f = setup();
+    for _ in 0..60 {
+        let (status, _) = f.wait(0).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    let (status, _) = f.wait(0).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+}
@@ -11,9 +11,10 @@ use tower::ServiceExt;
 mod common;
 use common::create_test_app_state;
 use common::{
-    CreateProject, LICENSE_VALID_DAYS, complete_payment_session, create_test_license,
-    create_test_org, create_test_payment_session, create_test_product, create_test_project,
-    future_timestamp, public_app, queries, test_master_key,
+    ConvertedTrialAction, CreateProject, LICENSE_VALID_DAYS, UpgradeOldLicense,
+    complete_payment_session, create_test_license, create_test_org, create_test_payment_session,
+    create_test_product, create_test_project, future_timestamp, public_app, queries,
+    test_master_key,
 };
 
 #[tokio::test]
@@ -187,6 +188,23 @@ async fn test_callback_project_redirect_url() {
             email_from: None,
             email_enabled: true,
             email_webhook_url: None,
+            email_webhook_secret: None,
+            jwt_issuer: None,
+            jwt_audience: None,
+            upgrade_auto_discount: false,
+            upgrade_old_license: UpgradeOldLicense::Revoke,
+            allow_project_id_auth: true,
+            allow_link_checkout: false,
+            max_validations_per_hour_per_license: None,
+            statement_descriptor_suffix: None,
+            receipt_email_enabled: false,
+            checkout_message: None,
+            attestation_audiences: vec![],
+            duplicate_purchase_check: true,
+            converted_trial_action: ConvertedTrialAction::Keep,
+            require_terms_acceptance: false,
+            checkout_metadata_keys: vec![],
+            templates: vec![],
         };
         let (private_key, public_key) = paycheck::jwt::generate_keypair();
         let project = queries::create_project(
@@ -0,0 +1,135 @@
+//! Tests for GET /products, the public product catalog.
+//!
+//! Products outside their sale window are hidden unless the caller asks for
+//! them with `include_unavailable`, in which case they're marked
+//! `coming_soon` or `ended`.
+
+use axum::{body::Body, http::Request, http::StatusCode};
+use serde_json::Value;
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+const NOW: i64 = 1_767_225_600;
+
+struct CatalogFixture {
+    state: AppState,
+    project: Project,
+    always: Product,
+    upcoming: Product,
+    ended: Product,
+}
+
+/// One product always on sale, one whose window opens tomorrow, and one whose
+/// window closed yesterday.
+fn setup() -> CatalogFixture {
+    let mut state = create_test_app_state();
+    state.clock = Clock::fixed(NOW);
+    let conn = state.db.get().unwrap();
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "Test Project", &state.master_key);
+    let always = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+    let upcoming = create_test_product(&conn, &project.id, "Launch Edition", "pro");
+    let ended = create_test_product(&conn, &project.id, "Early Bird", "pro");
+    for (product, from, until) in [
+        (&upcoming, NOW + 86400, NOW + 15 * 86400),
+        (&ended, NOW - 15 * 86400, NOW - 86400),
+    ] {
+        conn.execute(
+            "UPDATE products SET available_from = ?1, available_until = ?2 WHERE id = ?3",
+            rusqlite::params![from, until, product.id],
+        )
+        .unwrap();
+    }
+    drop(conn);
+    CatalogFixture {
+        state,
+        project,
+        always,
+        upcoming,
+        ended,
+    }
+}
+
+async fn catalog(f: &CatalogFixture, query: &str) -> (StatusCode, Value) {
+    let response = public_app(f.state.clone())
+        .oneshot(
+            Request::builder()
+                .uri(format!(
+                    "/products?public_key={}{}",
+                    urlencoding::encode(&f.project.public_key),
+                    query
+                ))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+/// Availability of each listed product, by product ID
+fn listed(json: &Value) -> Vec<(String, String)> {
+    let mut listed: Vec<(String, String)> = json["products"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| {
+            (
+                p["id"].as_str().unwrap().to_string(),
+                p["availability"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect();
+    listed.sort();
+    listed
+}
+
+#[tokio::test]
+async fn test_catalog_hides_products_outside_sale_window() {
+    let f = setup();
+
+    let (status, json) = catalog(&f, "").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        listed(&json),
+        vec![(f.always.id.clone(), "available".to_string())]
+    );
+}
+
+#[tokio::test]
+async fn test_catalog_include_unavailable_marks_window() {
+    let f = setup();
+
+    let (status, json) = catalog(&f, "&include_unavailable=true").await;
+
+    assert_eq!(status, StatusCode::OK);
+    let mut expected = vec![
+        (f.always.id.clone(), "available".to_string()),
+        (f.upcoming.id.clone(), "coming_soon".to_string()),
+        (f.ended.id.clone(), "ended".to_string()),
+    ];
+    expected.sort();
+    assert_eq!(listed(&json), expected);
+}
+
+#[tokio::test]
+async fn test_catalog_omits_product_limits() {
+    let f = setup();
+
+    let (_, json) = catalog(&f, "").await;
+
+    let product = &json["products"][0];
+    assert_eq!(product["name"], "Pro Plan");
+    assert!(
+        product.get("device_limit").is_none(),
+        "catalog is customer-facing"
+    );
+}
@@ -0,0 +1,309 @@
+//! Tests for project checkout settings: the statement descriptor suffix,
+//! receipt email, and checkout message reach the provider's checkout request,
+//! and invalid values are rejected when the project is saved. Checkout
+//! metadata from /buy rides along under `meta_` keys.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::{
+    Form, Router,
+    body::Body,
+    http::{Request, StatusCode},
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::handlers;
+use paycheck::models::EmailAddress;
+use paycheck::payments::{CheckoutSettings, LemonSqueezyClient, StripeClient};
+
+/// Project with every checkout setting set
+fn configured_project() -> Project {
+    let state = create_test_app_state();
+    let conn = state.db.get().unwrap();
+    let org = create_test_org(&conn, "Test Org");
+    let mut project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    project.statement_descriptor_suffix = Some("MYAPP PRO".to_string());
+    project.receipt_email_enabled = true;
+    project.checkout_message = Some("You will receive your license code by email".to_string());
+    project
+}
+
+fn buyer_email() -> EmailAddress {
+    EmailAddress::parse("buyer@example.com").unwrap()
+}
+
+/// Mock Stripe that records the form of every request.
+async fn mock_stripe() -> (String, Arc<Mutex<Vec<(String, String)>>>) {
+    let captured = Arc::new(Mutex::new(Vec::new()));
+    let sink = captured.clone();
+    let app = Router::new().fallback(move |Form(form): Form<Vec<(String, String)>>| {
+        let sink = sink.clone();
+        async move {
+            *sink.lock().unwrap() = form;
+            axum::Json(json!({
+                "id": "cs_test_123",
+                "url": "https://checkout.stripe.com/c/pay/cs_test_123"
+            }))
+        }
+    });
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (format!("http://{}", addr), captured)
+}
+
+async fn stripe_checkout_form(
+    settings: &CheckoutSettings,
+    metadata: &BTreeMap<String, String>,
+) -> Vec<(String, String)> {
+    let (api_base, captured) = mock_stripe().await;
+    let config = StripeConfig {
+        secret_key: "sk_test_xxx".to_string(),
+        publishable_key: "pk_test_xxx".to_string(),
+        webhook_secret: "whsec_test123secret456".to_string(),
+    };
+    StripeClient::with_endpoint(&config, &api_base, Duration::from_secs(5))
+        .create_checkout_session(
+            "session-1",
+            "project-1",
+            "product-1",
+            "price_123",
+            "https://example.com/callback",
+            "https://example.com/cancel",
+            None,
+            &BTreeMap::new(),
+            metadata,
+            settings,
+        )
+        .await
+        .unwrap();
+    captured.lock().unwrap().clone()
+}
+
+fn form_value<'a>(form: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    form.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+}
+
+#[tokio::test]
+async fn test_stripe_checkout_includes_project_settings() {
+    let project = configured_project();
+    let settings = CheckoutSettings::for_project(&project, Some(&buyer_email()));
+
+    let form = stripe_checkout_form(&settings, &BTreeMap::new()).await;
+    assert_eq!(
+        form_value(&form, "payment_intent_data[statement_descriptor_suffix]"),
+        Some("MYAPP PRO")
+    );
+    assert_eq!(
+        form_value(&form, "payment_intent_data[receipt_email]"),
+        Some("buyer@example.com")
+    );
+    assert_eq!(
+        form_value(&form, "custom_text[submit][message]"),
+        Some("You will receive your license code by email")
+    );
+}
+
+#[tokio::test]
+async fn test_stripe_checkout_without_settings_sends_none() {
+    let form = stripe_checkout_form(&CheckoutSettings::default(), &BTreeMap::new()).await;
+    assert!(
+        form.iter()
+            .all(|(k, _)| !k.starts_with("payment_intent_data") && !k.starts_with("custom_text")),
+        "{:?}",
+        form
+    );
+}
+
+#[tokio::test]
+async fn test_receipt_email_needs_project_opt_in() {
+    let mut project = configured_project();
+    project.receipt_email_enabled = false;
+
+    let settings = CheckoutSettings::for_project(&project, Some(&buyer_email()));
+    assert_eq!(settings.receipt_email, None);
+
+    let form = stripe_checkout_form(&settings, &BTreeMap::new()).await;
+    assert_eq!(
+        form_value(&form, "payment_intent_data[receipt_email]"),
+        None
+    );
+}
+
+/// Body of the checkout request sent to a mock Lemon Squeezy.
+async fn lemonsqueezy_checkout_body(
+    settings: &CheckoutSettings,
+    metadata: &BTreeMap<String, String>,
+) -> Value {
+    let captured = Arc::new(Mutex::new(Value::Null));
+    let sink = captured.clone();
+    let app = Router::new().fallback(move |axum::Json(body): axum::Json<Value>| {
+        let sink = sink.clone();
+        async move {
+            *sink.lock().unwrap() = body;
+            axum::Json(json!({ "data": {
+                "id": "checkout-1",
+                "attributes": { "url": "https://store.lemonsqueezy.com/checkout/1" }
+            }}))
+        }
+    });
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let api_base = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let config = LemonSqueezyConfig {
+        api_key: "lskey_test_xxx".to_string(),
+        store_id: "12345".to_string(),
+        webhook_secret: "ls_whsec_test_secret".to_string(),
+        verify_imported_keys: false,
+    };
+    LemonSqueezyClient::with_endpoint(&config, &api_base, Duration::from_secs(5))
+        .create_checkout(
+            "session-1",
+            "project-1",
+            "product-1",
+            "999",
+            "https://example.com/callback",
+            metadata,
+            settings,
+        )
+        .await
+        .unwrap();
+
+    captured.lock().unwrap().clone()
+}
+
+#[tokio::test]
+async fn test_lemonsqueezy_checkout_includes_email_and_message() {
+    let settings = CheckoutSettings::for_project(&configured_project(), Some(&buyer_email()));
+    let body = lemonsqueezy_checkout_body(&settings, &BTreeMap::new()).await;
+
+    let attributes = &body["data"]["attributes"];
+    assert_eq!(attributes["checkout_data"]["email"], "buyer@example.com");
+    assert_eq!(
+        attributes["product_options"]["receipt_thank_you_note"],
+        "You will receive your license code by email"
+    );
+}
+
+fn metadata() -> BTreeMap<String, String> {
+    BTreeMap::from([
+        ("utm_source".to_string(), "newsletter".to_string()),
+        ("campaign".to_string(), "spring-sale".to_string()),
+    ])
+}
+
+#[tokio::test]
+async fn test_stripe_checkout_includes_metadata() {
+    let form = stripe_checkout_form(&CheckoutSettings::default(), &metadata()).await;
+    assert_eq!(
+        form_value(&form, "metadata[meta_utm_source]"),
+        Some("newsletter")
+    );
+    assert_eq!(
+        form_value(&form, "metadata[meta_campaign]"),
+        Some("spring-sale")
+    );
+    // Alongside Paycheck's own keys, not in place of them
+    assert_eq!(
+        form_value(&form, "metadata[paycheck_session_id]"),
+        Some("session-1")
+    );
+}
+
+#[tokio::test]
+async fn test_lemonsqueezy_checkout_includes_metadata() {
+    let body = lemonsqueezy_checkout_body(&CheckoutSettings::default(), &metadata()).await;
+
+    let custom = &body["data"]["attributes"]["checkout_data"]["custom"];
+    assert_eq!(custom["meta_utm_source"], "newsletter");
+    assert_eq!(custom["meta_campaign"], "spring-sale");
+    assert_eq!(custom["paycheck_session_id"], "session-1");
+}
+
+/// PUT the project through the admin API.
+async fn update_project(body: Value) -> (StatusCode, Value) {
+    let state = create_test_app_state();
+    let mut conn = state.db.get().unwrap();
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, api_key) =
+        create_test_org_member(&mut conn, &org.id, "owner@test.com", OrgMemberRole::Owner);
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    drop(conn);
+
+    let app = handlers::orgs::router(state.clone(), paycheck::config::RateLimitConfig::disabled())
+        .with_state(state.clone());
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("PUT")
+                .uri(format!("/orgs/{}/projects/{}", org.id, project.id))
+                .header("Authorization", format!("Bearer {}", api_key))
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+#[tokio::test]
+async fn test_settings_saved_and_shown_on_project() {
+    let (status, project) = update_project(json!({
+        "statement_descriptor_suffix": "MYAPP PRO",
+        "receipt_email_enabled": true,
+        "checkout_message": "You will receive your license code by email"
+    }))
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(project["statement_descriptor_suffix"], "MYAPP PRO");
+    assert_eq!(project["receipt_email_enabled"], true);
+    assert_eq!(
+        project["checkout_message"],
+        "You will receive your license code by email"
+    );
+}
+
+#[tokio::test]
+async fn test_overlong_statement_descriptor_rejected() {
+    let (status, body) = update_project(json!({
+        "statement_descriptor_suffix": "MYAPP PROFESSIONAL EDITION"
+    }))
+    .await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        body["details"],
+        "statement_descriptor_suffix must be at most 22 characters"
+    );
+}
+
+#[tokio::test]
+async fn test_statement_descriptor_charset_rejected() {
+    for suffix in ["MYAPP*PRO", "\"MYAPP\"", "MYAPP <PRO>", "12345", "MYÄPP"] {
+        let (status, body) = update_project(json!({ "statement_descriptor_suffix": suffix })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", suffix);
+        assert!(
+            body["details"]
+                .as_str()
+                .unwrap()
+                .starts_with("statement_descriptor_suffix"),
+            "{}: {}",
+            suffix,
+            body
+        );
+    }
+}
@@ -0,0 +1,302 @@
+//! Tests for project client flags: set through the org API, returned as-is
+//! by /validate and /refresh, plus the `update_required` hint from
+//! `min_client_version`.
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    response::Response,
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::config::RateLimitConfig;
+use paycheck::handlers;
+use paycheck::jwt::{self, DeviceInfo};
+
+struct FlagsFixture {
+    state: AppState,
+    org_id: String,
+    project: Project,
+    api_key: String,
+    license: License,
+    jti: String,
+    token: String,
+}
+
+fn setup() -> FlagsFixture {
+    let state = create_test_app_state();
+    let master_key = test_master_key();
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, api_key) =
+        create_test_org_member(&mut conn, &org.id, "admin@test.com", OrgMemberRole::Owner);
+    let project = create_test_project(&conn, &org.id, "Test Project", &master_key);
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+    let license = create_test_license(
+        &conn,
+        &project.id,
+        &product.id,
+        Some(future_timestamp(ONE_YEAR)),
+    );
+    let device = create_test_device(&conn, &license.id, "laptop", DeviceType::Uuid);
+
+    let claims = jwt::build_license_claims(
+        &license,
+        &product,
+        &project,
+        &DeviceInfo {
+            device_id: &device.device_id,
+            device_type: device.device_type,
+            activated_at: device.activated_at,
+        },
+    );
+    let private_key = queries::decrypt_project_private_key(&conn, &project, &master_key).unwrap();
+    let token = jwt::sign_license_token(&claims, &private_key, &device.jti).unwrap();
+
+    drop(conn);
+    FlagsFixture {
+        state,
+        org_id: org.id,
+        project,
+        api_key,
+        license,
+        jti: device.jti,
+        token,
+    }
+}
+
+impl FlagsFixture {
+    async fn org(&self, method: &str, path: &str, body: Value) -> (StatusCode, Value) {
+        let app = handlers::orgs::router(self.state.clone(), RateLimitConfig::disabled())
+            .with_state(self.state.clone());
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri(format!(
+                        "/orgs/{}/projects/{}{}",
+                        self.org_id, self.project.id, path
+                    ))
+                    .header("Authorization", format!("Bearer {}", self.api_key))
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        (status, body_json(response).await)
+    }
+
+    async fn set_flags(&self, body: Value) -> (StatusCode, Value) {
+        self.org("PUT", "/client-flags", body).await
+    }
+
+    /// POST /validate, reporting `client_version` in the header if given.
+    async fn validate(&self, client_version: Option<&str>) -> Value {
+        self.validate_at("/validate", client_version).await
+    }
+
+    async fn validate_at(&self, uri: &str, client_version: Option<&str>) -> Value {
+        let mut request = Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header("content-type", "application/json");
+        if let Some(version) = client_version {
+            request = request.header("X-Paycheck-Client-Version", version);
+        }
+        let body = json!({ "public_key": self.project.public_key, "jti": self.jti });
+        let response = public_app(self.state.clone())
+            .oneshot(request.body(Body::from(body.to_string())).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        body_json(response).await
+    }
+
+    async fn refresh(&self, client_version: Option<&str>) -> Value {
+        let mut request = Request::builder()
+            .method("POST")
+            .uri("/refresh")
+            .header("Authorization", format!("Bearer {}", self.token));
+        if let Some(version) = client_version {
+            request = request.header("X-Paycheck-Client-Version", version);
+        }
+        let response = public_app(self.state.clone())
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        body_json(response).await
+    }
+}
+
+async fn body_json(response: Response) -> Value {
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    serde_json::from_slice(&bytes).unwrap_or(Value::Null)
+}
+
+#[tokio::test]
+async fn test_flag_changes_appear_on_next_validate() {
+    let f = setup();
+    let body = f.validate(None).await;
+    assert_eq!(body["valid"], true);
+    assert_eq!(body["client_flags"], json!({}));
+    assert_eq!(body["update_required"], false);
+
+    let (status, body) = f
+        .set_flags(json!({ "client_flags": { "banner": "Maintenance on Sunday" } }))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let body = f.validate(None).await;
+    assert_eq!(body["client_flags"]["banner"], "Maintenance on Sunday");
+
+    let flags = json!({ "sync_enabled": false, "limits": { "export": 10 } });
+    f.set_flags(json!({ "client_flags": flags })).await;
+    let body = f.validate(None).await;
+    assert_eq!(body["client_flags"], flags);
+    assert_eq!(f.refresh(None).await["client_flags"], flags);
+}
+
+#[tokio::test]
+async fn test_flags_set_through_project_update() {
+    let f = setup();
+    let (status, body) = f
+        .org(
+            "PUT",
+            "",
+            json!({ "client_flags": { "new_editor": true }, "min_client_version": "1.4" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["client_flags"]["new_editor"], true);
+    assert_eq!(body["min_client_version"], "1.4");
+
+    let body = f.validate(Some("1.3.9")).await;
+    assert_eq!(body["client_flags"]["new_editor"], true);
+    assert_eq!(body["update_required"], true);
+}
+
+#[tokio::test]
+async fn test_oversized_flags_rejected_at_save() {
+    let f = setup();
+    f.set_flags(json!({ "client_flags": { "banner": "hello" } }))
+        .await;
+
+    let oversized = json!({ "banner": "x".repeat(5000) });
+    let (status, _) = f.set_flags(json!({ "client_flags": oversized })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = f.org("PUT", "", json!({ "client_flags": oversized })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = f
+        .set_flags(json!({ "client_flags": ["not", "an", "object"] }))
+        .await;
+    assert!(status.is_client_error(), "{}", status);
+
+    let body = f.validate(None).await;
+    assert_eq!(body["client_flags"], json!({ "banner": "hello" }));
+}
+
+#[tokio::test]
+async fn test_update_required_when_client_is_older_than_minimum() {
+    let f = setup();
+    let (status, body) = f
+        .set_flags(json!({ "client_flags": {}, "min_client_version": "2.1.0" }))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let body = f.validate(Some("2.0.9")).await;
+    assert_eq!(body["update_required"], true);
+    assert_eq!(body["min_client_version"], "2.1.0");
+    assert_eq!(f.validate(Some("v1.9")).await["update_required"], true);
+    assert_eq!(f.validate(Some("2.1")).await["update_required"], false);
+    assert_eq!(
+        f.validate(Some("3.0.0-beta.1")).await["update_required"],
+        false
+    );
+
+    // Without a version the app can't be told anything
+    assert_eq!(f.validate(None).await["update_required"], false);
+    assert_eq!(f.validate(Some("nightly")).await["update_required"], false);
+
+    // The query parameter works too, and /refresh answers the same
+    let body = f.validate_at("/validate?client_version=2.0", None).await;
+    assert_eq!(body["update_required"], true);
+    assert_eq!(f.refresh(Some("2.0.0")).await["update_required"], true);
+}
+
+#[tokio::test]
+async fn test_flags_reach_apps_with_revoked_licenses() {
+    let f = setup();
+    f.set_flags(
+        json!({ "client_flags": { "banner": "Please update" }, "min_client_version": "2" }),
+    )
+    .await;
+    queries::revoke_license(
+        &f.state.db.get().unwrap(),
+        &f.license.id,
+        &RevokeLicense::default(),
+        None,
+    )
+    .unwrap();
+
+    let body = f.validate(Some("1.0")).await;
+    assert_eq!(body["valid"], false);
+    assert_eq!(body["client_flags"]["banner"], "Please update");
+    assert_eq!(body["update_required"], true);
+}
+
+#[tokio::test]
+async fn test_min_client_version_must_be_a_version() {
+    let f = setup();
+    for version in ["latest", "1..2", "1.2.3.4.5", ""] {
+        let (status, _) = f
+            .set_flags(json!({ "client_flags": {}, "min_client_version": version }))
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", version);
+    }
+    let (status, _) = f
+        .org("PUT", "", json!({ "min_client_version": "soon" }))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // null clears it
+    f.set_flags(json!({ "client_flags": {}, "min_client_version": "2.0" }))
+        .await;
+    let (status, body) = f
+        .org("PUT", "", json!({ "min_client_version": null }))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["min_client_version"], Value::Null);
+}
+
+#[tokio::test]
+async fn test_flag_changes_are_audit_logged() {
+    let f = setup();
+    f.set_flags(json!({ "client_flags": { "banner": "one" } }))
+        .await;
+    f.set_flags(json!({ "client_flags": { "banner": "two" } }))
+        .await;
+
+    let conn = f.state.audit.get().unwrap();
+    let details: String = conn
+        .query_row(
+            "SELECT details FROM audit_logs WHERE action = 'update_client_flags' AND resource_id = ?1
+             ORDER BY timestamp DESC, rowid DESC LIMIT 1",
+            rusqlite::params![f.project.id],
+            |row| row.get(0),
+        )
+        .unwrap();
+    let details: Value = serde_json::from_str(&details).unwrap();
+    assert_eq!(details["client_flags"]["banner"], "two");
+    assert_eq!(details["previous_client_flags"]["banner"], "one");
+}
@@ -0,0 +1,270 @@
+//! Tests for host-based routing on custom domains: a request to a verified
+//! custom domain is for the domain's project, and anything naming another
+//! project is rejected. Hosts are synthetic `Host` headers.
+
+use axum::{Router, body::Body, http::Request, http::StatusCode, middleware};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::config::RateLimitConfig;
+use paycheck::handlers;
+use paycheck::middleware::custom_domain_host;
+
+const HOST: &str = "licenses.example.com";
+const OTHER_HOST: &str = "licenses.other.example";
+const PENDING_HOST: &str = "pending.example.com";
+
+struct DomainFixture {
+    state: AppState,
+    app: Router,
+    project: Project,
+    device: Device,
+    /// A second project with its own verified domain
+    other: Project,
+    other_product: Product,
+}
+
+fn setup() -> DomainFixture {
+    let state = create_test_app_state();
+    let conn = state.db.get().unwrap();
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "Project A", &test_master_key());
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+    let license = create_test_license(
+        &conn,
+        &project.id,
+        &product.id,
+        Some(future_timestamp(ONE_YEAR)),
+    );
+    let device = create_test_device(&conn, &license.id, "device-1", DeviceType::Uuid);
+    let other = create_test_project(&conn, &org.id, "Project B", &test_master_key());
+    let other_product = create_test_product(&conn, &other.id, "Other Plan", "pro");
+
+    for (hostname, project_id, verified) in [
+        (HOST, &project.id, true),
+        (OTHER_HOST, &other.id, true),
+        (PENDING_HOST, &project.id, false),
+    ] {
+        queries::create_custom_domain(&conn, &org.id, project_id, hostname, "pcv_token", None)
+            .unwrap();
+        if verified {
+            queries::mark_custom_domain_verified(&conn, hostname, now()).unwrap();
+        }
+    }
+    drop(conn);
+
+    let app = handlers::public::router(RateLimitConfig::disabled())
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            custom_domain_host,
+        ))
+        .with_state(state.clone());
+    DomainFixture {
+        state,
+        app,
+        project,
+        device,
+        other,
+        other_product,
+    }
+}
+
+impl DomainFixture {
+    async fn send(&self, request: Request<Body>) -> (StatusCode, Value) {
+        let response = self.app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    async fn get(&self, host: &str, uri: &str) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method("GET")
+            .uri(uri)
+            .header("Host", host)
+            .body(Body::empty())
+            .unwrap();
+        self.send(request).await
+    }
+
+    async fn post(&self, host: &str, uri: &str, body: Value) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header("Host", host)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        self.send(request).await
+    }
+
+    async fn validate(&self, host: &str) -> (StatusCode, Value) {
+        self.post(host, "/validate", json!({ "jti": self.device.jti }))
+            .await
+    }
+}
+
+#[tokio::test]
+async fn test_verified_domain_resolves_the_project() {
+    let f = setup();
+    for host in [HOST, "Licenses.Example.COM:8443", "licenses.example.com."] {
+        let (status, body) = f.validate(host).await;
+        assert_eq!(status, StatusCode::OK, "{}: {}", host, body);
+        assert_eq!(body["valid"], true, "{}", host);
+    }
+}
+
+#[tokio::test]
+async fn test_other_hosts_are_not_routed() {
+    let f = setup();
+    for host in [PENDING_HOST, "paycheck.example.com", "127.0.0.1:3000"] {
+        let (status, body) = f.validate(host).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", host);
+        assert_eq!(
+            body["details"],
+            "public_key is required (X-Paycheck-Project header or public_key field)",
+            "{}",
+            host
+        );
+    }
+
+    // The other project's domain is for the other project
+    let (status, body) = f.validate(OTHER_HOST).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["valid"], false);
+}
+
+#[tokio::test]
+async fn test_public_key_for_another_project_rejected() {
+    let f = setup();
+    let (status, body) = f
+        .post(
+            HOST,
+            "/validate",
+            json!({ "public_key": f.other.public_key, "jti": f.device.jti }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        body["details"],
+        "X-Paycheck-Project header does not match the request's public_key"
+    );
+
+    // The domain's own key is fine
+    let (status, _) = f
+        .post(
+            HOST,
+            "/validate",
+            json!({ "public_key": f.project.public_key, "jti": f.device.jti }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_project_header_for_another_project_rejected() {
+    let f = setup();
+    let request = Request::builder()
+        .method("POST")
+        .uri("/validate")
+        .header("Host", HOST)
+        .header("X-Paycheck-Project", &f.other.public_key)
+        .header("content-type", "application/json")
+        .body(Body::from(json!({ "jti": f.device.jti }).to_string()))
+        .unwrap();
+    let (status, body) = f.send(request).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        body["details"],
+        "X-Paycheck-Project header names a different project than this domain serves"
+    );
+}
+
+#[tokio::test]
+async fn test_buy_and_health_for_another_project_rejected() {
+    let f = setup();
+    let (status, body) = f
+        .post(HOST, "/buy", json!({ "product_id": f.other_product.id }))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["details"], "Product does not belong to this project");
+
+    let (status, _) = f
+        .get(HOST, &format!("/projects/{}/health", f.other.id))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = f
+        .get(HOST, &format!("/projects/{}/health", f.project.id))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_discovery_and_jwks_are_served_per_domain() {
+    let f = setup();
+    f.state
+        .db
+        .get()
+        .unwrap()
+        .execute(
+            "UPDATE projects SET jwt_issuer = 'https://licenses.example.com' WHERE id = ?1",
+            [&f.project.id],
+        )
+        .unwrap();
+
+    let (status, discovery) = f.get(HOST, "/discovery").await;
+    assert_eq!(status, StatusCode::OK, "{}", discovery);
+    assert_eq!(discovery["issuer"], "https://licenses.example.com");
+    let kid = paycheck::jwt::key_id(&f.project.public_key).unwrap();
+    assert_eq!(discovery["jwks"]["keys"][0]["kid"], kid);
+
+    let (status, jwks) = f.get(HOST, "/.well-known/jwks.json").await;
+    assert_eq!(status, StatusCode::OK, "{}", jwks);
+    assert_eq!(jwks, discovery["jwks"]);
+
+    let (_, other_discovery) = f.get(OTHER_HOST, "/discovery").await;
+    assert_eq!(other_discovery["issuer"], "paycheck");
+    let other_kid = paycheck::jwt::key_id(&f.other.public_key).unwrap();
+    let (_, other_jwks) = f.get(OTHER_HOST, "/.well-known/jwks.json").await;
+    assert_eq!(other_jwks["keys"][0]["kid"], other_kid);
+
+    // Elsewhere the project still has to be named
+    let (status, _) = f
+        .get("paycheck.example.com", "/.well-known/jwks.json")
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, jwks) = f
+        .get(
+            "paycheck.example.com",
+            &format!(
+                "/.well-known/jwks.json?public_key={}",
+                urlencoding::encode(&f.project.public_key)
+            ),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(jwks["keys"][0]["kid"], kid);
+}
+
+#[tokio::test]
+async fn test_domain_of_deleted_project_is_not_found() {
+    let f = setup();
+    f.state
+        .db
+        .get()
+        .unwrap()
+        .execute(
+            "UPDATE projects SET deleted_at = unixepoch() WHERE id = ?1",
+            [&f.project.id],
+        )
+        .unwrap();
+
+    let (status, _) = f.validate(HOST).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -19,27 +19,27 @@ use paycheck::jwt::{self, LicenseClaims};
 
 /// Helper to create a valid JWT for testing
 fn create_test_jwt(
-    _state: &paycheck::db::AppState,
+    state: &paycheck::db::AppState,
     project: &Project,
     product: &Product,
     license_id: &str,
     device: &Device,
 ) -> String {
-    let master_key = test_master_key();
-
     let claims = LicenseClaims {
         license_exp: Some(future_timestamp(ONE_YEAR)),
         updates_exp: Some(future_timestamp(UPDATES_VALID_DAYS)),
         tier: product.tier.clone(),
         features: product.features.clone(),
+        entitlements: product.entitlements.clone(),
         device_id: device.device_id.clone(),
         device_type: "uuid".to_string(),
+        license_ref: None,
         product_id: product.id.clone(),
     };
 
-    let private_key = master_key
-        .decrypt_private_key(&project.id, &project.private_key)
-        .unwrap();
+    let private_key =
+        queries::decrypt_project_private_key(&state.db.get().unwrap(), project, &state.master_key)
+            .unwrap();
 
     jwt::sign_claims(
         &claims,
@@ -120,6 +120,66 @@ async fn test_deactivate_with_valid_jwt_removes_device() {
     );
 }
 
+#[tokio::test]
+async fn test_deactivate_writes_public_audit_log() {
+    let mut state = create_test_app_state();
+    state.audit_log_enabled = true;
+    let master_key = test_master_key();
+
+    let token: String;
+    let license_id: String;
+
+    {
+        let mut conn = state.db.get().unwrap();
+        let org = create_test_org(&mut conn, "Test Org");
+        let project = create_test_project(&mut conn, &org.id, "Test Project", &master_key);
+        let product = create_test_product(&mut conn, &project.id, "Pro Plan", "pro");
+        let license = create_test_license(
+            &conn,
+            &project.id,
+            &product.id,
+            Some(future_timestamp(LICENSE_VALID_DAYS)),
+        );
+        let device = create_test_device(&mut conn, &license.id, "test-device", DeviceType::Uuid);
+
+        license_id = license.id.clone();
+        token = create_test_jwt(&state, &project, &product, &license.id, &device);
+    }
+
+    let app = public_app(state.clone());
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/devices/deactivate")
+                .header("Authorization", format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), axum::http::StatusCode::OK);
+
+    let audit_conn = state.audit.get().unwrap();
+    let (actor_type, user_id, details): (String, Option<String>, String) = audit_conn
+        .query_row(
+            "SELECT actor_type, user_id, details FROM audit_logs
+             WHERE action = 'deactivate_device' AND resource_id = 'test-device'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .expect("self-deactivation should write a deactivate_device audit log");
+    assert_eq!(
+        actor_type, "public",
+        "self-deactivation is audited as a public action"
+    );
+    assert_eq!(user_id, None, "public actions have no user");
+    let details: Value = serde_json::from_str(&details).unwrap();
+    assert_eq!(details["license_id"], license_id);
+    assert_eq!(details["self_deactivated"], true);
+}
+
 #[tokio::test]
 async fn test_deactivate_adds_jti_to_revoked_list() {
     let state = create_test_app_state();
@@ -455,14 +515,15 @@ async fn test_deactivate_machine_type_device() {
             updates_exp: Some(future_timestamp(UPDATES_VALID_DAYS)),
             tier: product.tier.clone(),
             features: product.features.clone(),
+            entitlements: product.entitlements.clone(),
             device_id: device.device_id.clone(),
             device_type: "machine".to_string(),
+            license_ref: None,
             product_id: product.id.clone(),
         };
 
-        let private_key = master_key
-            .decrypt_private_key(&project.id, &project.private_key)
-            .unwrap();
+        let private_key =
+            queries::decrypt_project_private_key(&conn, &project, &master_key).unwrap();
 
         token = jwt::sign_claims(
             &claims,
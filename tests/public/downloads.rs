@@ -0,0 +1,307 @@
+//! Tests for GET /download/{artifact_id}: the updates-window check against
+//! the artifact's release, the signed redirect, and per-license counts.
+
+use axum::{
+    body::Body,
+    http::{HeaderMap, Request, StatusCode, header},
+};
+use serde_json::Value;
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::artifacts::{DOWNLOAD_URL_LIFETIME_SECS, verify_download_url};
+use paycheck::jwt::DeviceInfo;
+
+struct Setup {
+    state: AppState,
+    org_id: String,
+    project_id: String,
+    owner_key: String,
+    token: String,
+    public_key: String,
+    license_id: String,
+    renewal_id: String,
+    /// Released yesterday, inside the updates window
+    covered: Artifact,
+    /// Released in two months, after the updates window ends
+    too_new: Artifact,
+}
+
+/// A year-long license with a month of updates, and a renewal product on
+/// sale that extends them (plus one that's no longer on sale)
+fn setup() -> Setup {
+    let state = create_test_app_state();
+    let master_key = test_master_key();
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, owner_key) =
+        create_test_org_member(&mut conn, &org.id, "owner@test.com", OrgMemberRole::Owner);
+    let project = create_test_project(&conn, &org.id, "Test Project", &master_key);
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+    let renewal = create_test_product(&conn, &project.id, "Updates Renewal", "renewal");
+    let retired = create_test_product(&conn, &project.id, "Old Renewal", "renewal-old");
+    conn.execute(
+        "UPDATE products SET extends_updates_for_product_id = ?1 WHERE id IN (?2, ?3)",
+        rusqlite::params![product.id, renewal.id, retired.id],
+    )
+    .unwrap();
+    conn.execute(
+        "UPDATE products SET available_until = ?1 WHERE id = ?2",
+        rusqlite::params![past_timestamp(ONE_DAY), retired.id],
+    )
+    .unwrap();
+
+    let license = create_test_license(
+        &conn,
+        &project.id,
+        &product.id,
+        Some(future_timestamp(ONE_YEAR)),
+    );
+    queries::extend_license_expiration(
+        &conn,
+        &license.id,
+        license.expires_at,
+        Some(future_timestamp(ONE_MONTH)),
+    )
+    .unwrap();
+    let device = create_test_device(&conn, &license.id, "test-device", DeviceType::Uuid);
+    let claims = jwt::build_license_claims(
+        &license,
+        &product,
+        &project,
+        &DeviceInfo {
+            device_id: &device.device_id,
+            device_type: device.device_type,
+            activated_at: device.activated_at,
+        },
+    );
+    let private_key = queries::decrypt_project_private_key(&conn, &project, &master_key).unwrap();
+    let token = jwt::sign_license_token(&claims, &private_key, &device.jti).unwrap();
+
+    let artifact = |name: &str, released_at: i64| {
+        queries::create_artifact(
+            &conn,
+            &project.id,
+            &CreateArtifact {
+                name: name.to_string(),
+                url: format!("https://cdn.example.com/releases/{}", name),
+                released_at: None,
+            },
+            released_at,
+            None,
+        )
+        .unwrap()
+    };
+    let covered = artifact("MyApp-1.0.dmg", past_timestamp(ONE_DAY));
+    let too_new = artifact("MyApp-2.0.dmg", future_timestamp(2 * ONE_MONTH));
+    drop(conn);
+
+    Setup {
+        state,
+        org_id: org.id,
+        project_id: project.id,
+        owner_key,
+        token,
+        public_key: project.public_key,
+        license_id: license.id,
+        renewal_id: renewal.id,
+        covered,
+        too_new,
+    }
+}
+
+impl Setup {
+    async fn download(&self, artifact_id: &str) -> (StatusCode, HeaderMap, Value) {
+        let response = public_app(self.state.clone())
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri(format!(
+                        "/download/{}?public_key={}",
+                        artifact_id,
+                        urlencoding::encode(&self.public_key)
+                    ))
+                    .header("Authorization", format!("Bearer {}", self.token))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            headers,
+            serde_json::from_slice(&body).unwrap_or(Value::Null),
+        )
+    }
+
+    fn signing_key(&self) -> [u8; 32] {
+        self.state.master_key.download_signing_key(&self.project_id)
+    }
+
+    fn download_count(&self, artifact_id: &str) -> i64 {
+        let conn = self.state.db.get().unwrap();
+        queries::get_artifact(&conn, &self.project_id, artifact_id)
+            .unwrap()
+            .unwrap()
+            .download_count
+    }
+}
+
+#[tokio::test]
+async fn test_covered_release_redirects_to_signed_url() {
+    let setup = setup();
+    let now = chrono::Utc::now().timestamp();
+
+    let (status, headers, _) = setup.download(&setup.covered.id).await;
+
+    assert_eq!(status, StatusCode::FOUND);
+    assert_eq!(headers[header::CACHE_CONTROL], "no-store");
+    let location = headers[header::LOCATION].to_str().unwrap();
+    assert!(
+        location.starts_with("https://cdn.example.com/releases/MyApp-1.0.dmg?paycheck_expires="),
+        "{}",
+        location
+    );
+    assert!(verify_download_url(location, &setup.signing_key(), now));
+    assert!(
+        !verify_download_url(
+            location,
+            &setup.signing_key(),
+            now + DOWNLOAD_URL_LIFETIME_SECS + 1
+        ),
+        "the signed URL should stop working once it expires"
+    );
+}
+
+#[tokio::test]
+async fn test_release_after_updates_window_is_refused_with_renewals() {
+    let setup = setup();
+
+    let (status, headers, json) = setup.download(&setup.too_new.id).await;
+
+    assert_eq!(status, StatusCode::FORBIDDEN, "{}", json);
+    assert!(headers.get(header::LOCATION).is_none());
+    assert_eq!(json["code"], "updates_expired");
+    let conn = setup.state.db.get().unwrap();
+    let license = queries::get_license_by_id(&conn, &setup.license_id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        json["updates_expires_at"],
+        license.updates_expires_at.unwrap()
+    );
+    assert_eq!(
+        json["renewal_product_ids"],
+        serde_json::json!([setup.renewal_id]),
+        "only renewals still on sale are suggested"
+    );
+    assert_eq!(setup.download_count(&setup.too_new.id), 0);
+}
+
+#[tokio::test]
+async fn test_renewed_updates_cover_newer_release() {
+    let setup = setup();
+    {
+        let conn = setup.state.db.get().unwrap();
+        queries::extend_license_expiration(
+            &conn,
+            &setup.license_id,
+            Some(future_timestamp(ONE_YEAR)),
+            Some(future_timestamp(ONE_YEAR)),
+        )
+        .unwrap();
+    }
+
+    let (status, _, json) = setup.download(&setup.too_new.id).await;
+
+    assert_eq!(status, StatusCode::FOUND, "{}", json);
+}
+
+#[tokio::test]
+async fn test_revoked_license_cannot_download() {
+    let setup = setup();
+    {
+        let conn = setup.state.db.get().unwrap();
+        queries::revoke_license(&conn, &setup.license_id, &RevokeLicense::default(), None).unwrap();
+    }
+
+    let (status, headers, json) = setup.download(&setup.covered.id).await;
+
+    assert_eq!(status, StatusCode::FORBIDDEN, "{}", json);
+    assert_eq!(json["code"], "license_revoked");
+    assert!(headers.get(header::LOCATION).is_none());
+    assert_eq!(setup.download_count(&setup.covered.id), 0);
+}
+
+#[tokio::test]
+async fn test_unknown_artifact_is_not_found() {
+    let setup = setup();
+
+    let (status, _, _) = setup.download("no-such-artifact").await;
+
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_downloads_are_counted_per_license() {
+    let setup = setup();
+    setup.download(&setup.covered.id).await;
+    setup.download(&setup.covered.id).await;
+
+    assert_eq!(setup.download_count(&setup.covered.id), 2);
+    let conn = setup.state.db.get().unwrap();
+    let downloads = queries::list_artifact_downloads(&conn, &setup.covered.id).unwrap();
+    assert_eq!(downloads.len(), 1);
+    assert_eq!(downloads[0].license_id, setup.license_id);
+    assert_eq!(downloads[0].count, 2);
+    assert!(downloads[0].first_downloaded_at <= downloads[0].last_downloaded_at);
+}
+
+#[tokio::test]
+async fn test_published_signing_key_verifies_redirects() {
+    let setup = setup();
+    let now = chrono::Utc::now().timestamp();
+    let (_, headers, _) = setup.download(&setup.covered.id).await;
+    let location = headers[header::LOCATION].to_str().unwrap();
+
+    let app = paycheck::handlers::orgs::router(
+        setup.state.clone(),
+        paycheck::config::RateLimitConfig::disabled(),
+    )
+    .with_state(setup.state.clone());
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("GET")
+                .uri(format!(
+                    "/orgs/{}/projects/{}/download-signing-key",
+                    setup.org_id, setup.project_id
+                ))
+                .header("Authorization", format!("Bearer {}", setup.owner_key))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["algorithm"], "HMAC-SHA256");
+
+    let key: [u8; 32] = hex::decode(json["key"].as_str().unwrap())
+        .unwrap()
+        .try_into()
+        .unwrap();
+    assert!(verify_download_url(location, &key, now));
+}
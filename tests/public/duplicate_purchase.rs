@@ -0,0 +1,244 @@
+//! Tests for duplicate purchase detection in /buy: a buyer who already holds
+//! an active license is refused, a recent unpaid checkout is handed back, and
+//! `force_new_purchase` or the project setting skips both.
+//!
+//! No payment provider is configured, so a request that gets past the checks
+//! fails with "No payment provider configured" instead of opening a checkout.
+
+use axum::{body::Body, http::Request, http::StatusCode};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::error::msg;
+
+const NO_PROVIDER: &str = "No payment provider configured";
+const CHECKOUT_URL: &str = "https://checkout.stripe.com/c/pay/cs_test_pending";
+
+struct Fixture {
+    state: AppState,
+    project: Project,
+    product: Product,
+    /// Held by "test-customer" / test@example.com
+    license: License,
+}
+
+fn setup() -> Fixture {
+    let state = create_test_app_state();
+    let conn = state.db.get().unwrap();
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+    let license = create_test_license(
+        &conn,
+        &project.id,
+        &product.id,
+        Some(future_timestamp(ONE_YEAR)),
+    );
+    drop(conn);
+    Fixture {
+        state,
+        project,
+        product,
+        license,
+    }
+}
+
+impl Fixture {
+    async fn buy(&self, extra: Value) -> (StatusCode, Value) {
+        let mut body = json!({
+            "public_key": self.project.public_key,
+            "product_id": self.product.id,
+        });
+        body.as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+
+        let response = public_app(self.state.clone())
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/buy")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        )
+    }
+
+    /// An unpaid checkout opened `age_secs` ago
+    fn pending_session(
+        &self,
+        customer_id: Option<&str>,
+        email: Option<&str>,
+        age_secs: i64,
+    ) -> PaymentSession {
+        let conn = self.state.db.get().unwrap();
+        let session = create_test_payment_session(&conn, &self.product.id, customer_id);
+        let email_hash = email.map(|email| test_email_hasher().hash(&parse_email(email)));
+        queries::set_payment_session_checkout(
+            &conn,
+            &session.id,
+            CHECKOUT_URL,
+            email_hash.as_deref(),
+        )
+        .unwrap();
+        conn.execute(
+            "UPDATE payment_sessions SET created_at = created_at - ?1 WHERE id = ?2",
+            rusqlite::params![age_secs, session.id],
+        )
+        .unwrap();
+        session
+    }
+}
+
+// ============ Already licensed ============
+
+#[tokio::test]
+async fn test_existing_license_for_customer_id_is_refused() {
+    let f = setup();
+
+    let (status, json) = f.buy(json!({ "customer_id": "test-customer" })).await;
+
+    assert_eq!(status, StatusCode::CONFLICT, "{}", json);
+    assert_eq!(json["code"], "already_licensed");
+    assert_eq!(json["already_licensed"], true);
+    assert_eq!(json["license_created_at"], f.license.created_at);
+    assert_eq!(json["recovery_hint"], msg::ALREADY_LICENSED_RECOVERY_HINT);
+}
+
+#[tokio::test]
+async fn test_existing_license_for_email_is_refused() {
+    let f = setup();
+
+    let (status, json) = f.buy(json!({ "email": "Test@Example.com" })).await;
+
+    assert_eq!(
+        status,
+        StatusCode::CONFLICT,
+        "the email should match the license however it's capitalized: {}",
+        json
+    );
+    assert_eq!(json["already_licensed"], true);
+}
+
+#[tokio::test]
+async fn test_revoked_or_expired_license_does_not_block_purchase() {
+    let f = setup();
+    {
+        let conn = f.state.db.get().unwrap();
+        queries::revoke_license(&conn, &f.license.id, &RevokeLicense::default(), None).unwrap();
+        create_test_license(
+            &conn,
+            &f.project.id,
+            &f.product.id,
+            Some(past_timestamp(ONE_DAY)),
+        );
+    }
+
+    let (status, json) = f.buy(json!({ "customer_id": "test-customer" })).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", json);
+    assert_eq!(json["details"], NO_PROVIDER);
+}
+
+#[tokio::test]
+async fn test_anonymous_buyer_is_not_checked() {
+    let f = setup();
+
+    let (status, json) = f.buy(json!({})).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", json);
+    assert_eq!(json["details"], NO_PROVIDER);
+}
+
+// ============ Pending checkout reuse ============
+
+#[tokio::test]
+async fn test_recent_pending_checkout_is_reused() {
+    let f = setup();
+    let session = f.pending_session(Some("new-customer"), None, 10 * 60);
+
+    let (status, json) = f.buy(json!({ "customer_id": "new-customer" })).await;
+
+    assert_eq!(status, StatusCode::OK, "{}", json);
+    assert_eq!(json["checkout_url"], CHECKOUT_URL);
+    assert_eq!(json["session_id"], session.id);
+}
+
+#[tokio::test]
+async fn test_pending_checkout_is_matched_by_email() {
+    let f = setup();
+    let session = f.pending_session(None, Some("new@example.com"), 60);
+
+    let (status, json) = f.buy(json!({ "email": "new@example.com" })).await;
+
+    assert_eq!(status, StatusCode::OK, "{}", json);
+    assert_eq!(json["session_id"], session.id);
+}
+
+#[tokio::test]
+async fn test_stale_pending_checkout_is_not_reused() {
+    let f = setup();
+    f.pending_session(Some("new-customer"), None, 31 * 60);
+
+    let (status, json) = f.buy(json!({ "customer_id": "new-customer" })).await;
+
+    assert_eq!(
+        status,
+        StatusCode::BAD_REQUEST,
+        "a checkout older than 30 minutes should not be handed back: {}",
+        json
+    );
+    assert_eq!(json["details"], NO_PROVIDER);
+}
+
+// ============ Opting out ============
+
+#[tokio::test]
+async fn test_force_new_purchase_skips_checks() {
+    let f = setup();
+    f.pending_session(Some("test-customer"), None, 60);
+
+    let (status, json) = f
+        .buy(json!({ "customer_id": "test-customer", "force_new_purchase": true }))
+        .await;
+
+    assert_eq!(
+        status,
+        StatusCode::BAD_REQUEST,
+        "a forced purchase should go on to the provider: {}",
+        json
+    );
+    assert_eq!(json["details"], NO_PROVIDER);
+}
+
+#[tokio::test]
+async fn test_projects_can_turn_off_duplicate_check() {
+    let f = setup();
+    {
+        let conn = f.state.db.get().unwrap();
+        conn.execute(
+            "UPDATE projects SET duplicate_purchase_check = 0 WHERE id = ?1",
+            rusqlite::params![f.project.id],
+        )
+        .unwrap();
+    }
+
+    let (status, json) = f.buy(json!({ "customer_id": "test-customer" })).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{}", json);
+    assert_eq!(json["details"], NO_PROVIDER);
+}
@@ -20,6 +20,7 @@ use paycheck::jwt::{self, LicenseClaims};
 
 /// Helper to create a valid JWT for testing
 fn create_test_jwt(
+    conn: &rusqlite::Connection,
     project: &Project,
     product: &Product,
     license_id: &str,
@@ -32,14 +33,14 @@ fn create_test_jwt(
         updates_exp: Some(future_timestamp(UPDATES_VALID_DAYS)),
         tier: product.tier.clone(),
         features: product.features.clone(),
+        entitlements: product.entitlements.clone(),
         device_id: device.device_id.clone(),
         device_type: "uuid".to_string(),
+        license_ref: None,
         product_id: product.id.clone(),
     };
 
-    let private_key = master_key
-        .decrypt_private_key(&project.id, &project.private_key)
-        .unwrap();
+    let private_key = queries::decrypt_project_private_key(conn, project, &master_key).unwrap();
 
     jwt::sign_claims(
         &claims,
@@ -72,7 +73,7 @@ fn setup_license_test() -> (axum::Router, paycheck::db::AppState, String, String
         );
         let device = create_test_device(&mut conn, &license.id, "test-device", DeviceType::Uuid);
 
-        token = create_test_jwt(&project, &product, &license.id, &device);
+        token = create_test_jwt(&conn, &project, &product, &license.id, &device);
         public_key = project.public_key.clone();
     }
 
@@ -168,7 +169,7 @@ async fn test_license_with_devices_returns_device_list() {
         let device1 = create_test_device(&mut conn, &license.id, "device-1", DeviceType::Uuid);
         let _device2 = create_test_device(&mut conn, &license.id, "device-2", DeviceType::Machine);
 
-        token = create_test_jwt(&project, &product, &license.id, &device1);
+        token = create_test_jwt(&conn, &project, &product, &license.id, &device1);
         public_key = project.public_key.clone();
     }
 
@@ -305,11 +306,11 @@ async fn test_license_revoked_shows_revoked_status() {
         );
         let device = create_test_device(&mut conn, &license.id, "test-device", DeviceType::Uuid);
 
-        token = create_test_jwt(&project, &product, &license.id, &device);
+        token = create_test_jwt(&conn, &project, &product, &license.id, &device);
         public_key = project.public_key.clone();
 
         // Revoke the license
-        queries::revoke_license(&mut conn, &license.id).unwrap();
+        queries::revoke_license(&mut conn, &license.id, &RevokeLicense::default(), None).unwrap();
     }
 
     let app = public_app(state);
@@ -367,7 +368,7 @@ async fn test_license_expired_shows_expired_status() {
         );
         let device = create_test_device(&mut conn, &license.id, "test-device", DeviceType::Uuid);
 
-        token = create_test_jwt(&project, &product, &license.id, &device);
+        token = create_test_jwt(&conn, &project, &product, &license.id, &device);
         public_key = project.public_key.clone();
     }
 
@@ -426,7 +427,7 @@ async fn test_license_perpetual_shows_active_status() {
         );
         let device = create_test_device(&mut conn, &license.id, "test-device", DeviceType::Uuid);
 
-        token = create_test_jwt(&project, &product, &license.id, &device);
+        token = create_test_jwt(&conn, &project, &product, &license.id, &device);
         public_key = project.public_key.clone();
     }
 
@@ -516,6 +517,14 @@ async fn test_license_shows_correct_limits() {
             device_limit: Some(5),
         device_inactive_days: None,
             features: vec![],
+            entitlements: Default::default(),
+            seat_count: None,
+            available_from: None,
+            available_until: None,
+            checkout_fields: vec![],
+            extends_updates_for_product_id: None,
+            renewal_fallback: Default::default(),
+            revoke_on_partial_refund: false,
         };
         let product =
             queries::create_product(&mut conn, &project.id, &input).expect("Failed to create product");
@@ -528,7 +537,7 @@ async fn test_license_shows_correct_limits() {
         );
         let device = create_test_device(&mut conn, &license.id, "test-device", DeviceType::Uuid);
 
-        token = create_test_jwt(&project, &product, &license.id, &device);
+        token = create_test_jwt(&conn, &project, &product, &license.id, &device);
         public_key = project.public_key.clone();
     }
 
@@ -0,0 +1,106 @@
+//! Tests for GET /projects/{project_id}/health: a working project can sign,
+//! a broken key is a bare 503, and unknown and deleted projects look alike.
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode, header},
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::config::RateLimitConfig;
+use paycheck::handlers;
+
+fn setup() -> (AppState, Project) {
+    let state = create_test_app_state();
+    let conn = state.db.get().unwrap();
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    drop(conn);
+    (state, project)
+}
+
+async fn get_health(state: &AppState, project_id: &str) -> (StatusCode, Option<String>, Value) {
+    let app = handlers::public::router(RateLimitConfig::disabled()).with_state(state.clone());
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("GET")
+                .uri(format!("/projects/{}/health", project_id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = response.status();
+    let cache_control = response
+        .headers()
+        .get(header::CACHE_CONTROL)
+        .map(|v| v.to_str().unwrap().to_string());
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (
+        status,
+        cache_control,
+        serde_json::from_slice(&body).unwrap_or(Value::Null),
+    )
+}
+
+#[tokio::test]
+async fn test_healthy_project_can_sign() {
+    let (state, project) = setup();
+    let (status, cache_control, body) = get_health(&state, &project.id).await;
+
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(
+        body,
+        json!({ "status": "ok", "can_sign": true, "jwks_available": true })
+    );
+    assert_eq!(cache_control.as_deref(), Some("public, max-age=60"));
+}
+
+#[tokio::test]
+async fn test_corrupted_private_key_is_unavailable() {
+    let (state, project) = setup();
+    {
+        let conn = state.db.get().unwrap();
+        conn.execute(
+            "UPDATE projects SET private_key = X'DEADBEEF' WHERE id = ?1",
+            [&project.id],
+        )
+        .unwrap();
+    }
+
+    let (status, _, body) = get_health(&state, &project.id).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(
+        body,
+        json!({ "status": "unavailable", "can_sign": false, "jwks_available": true }),
+        "the cause stays in the server log"
+    );
+}
+
+#[tokio::test]
+async fn test_unknown_and_deleted_projects_look_alike() {
+    let (state, project) = setup();
+    let (unknown_status, _, unknown_body) = get_health(&state, "no-such-project").await;
+    assert_eq!(unknown_status, StatusCode::NOT_FOUND);
+
+    {
+        let conn = state.db.get().unwrap();
+        conn.execute(
+            "UPDATE projects SET deleted_at = unixepoch() WHERE id = ?1",
+            [&project.id],
+        )
+        .unwrap();
+    }
+    let (deleted_status, _, deleted_body) = get_health(&state, &project.id).await;
+    assert_eq!(deleted_status, StatusCode::NOT_FOUND);
+    assert_eq!(deleted_body["error"], unknown_body["error"]);
+    assert_eq!(deleted_body["details"], unknown_body["details"]);
+}
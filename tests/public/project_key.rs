@@ -0,0 +1,321 @@
+//! Tests for the X-Paycheck-Project publishable key header.
+//!
+//! The header can stand in for the `public_key` body field, must agree with it
+//! when both are sent, and is required on /buy for projects that have turned
+//! off the deprecated product-id-only lookup.
+
+use axum::{Router, body::Body, http::Request, http::StatusCode};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+struct KeyFixture {
+    state: AppState,
+    project: Project,
+    product: Product,
+    license: License,
+    /// A second project in the same org, for mismatched keys
+    other: Project,
+}
+
+fn setup() -> KeyFixture {
+    let state = create_test_app_state();
+    let conn = state.db.get().unwrap();
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "Project A", &test_master_key());
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+    let license = create_test_license(
+        &conn,
+        &project.id,
+        &product.id,
+        Some(future_timestamp(ONE_YEAR)),
+    );
+    let other = create_test_project(&conn, &org.id, "Project B", &test_master_key());
+    drop(conn);
+    KeyFixture {
+        state,
+        project,
+        product,
+        license,
+        other,
+    }
+}
+
+async fn send(app: &Router, request: Request<Body>) -> (StatusCode, Value) {
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+fn post(uri: &str, project_key: Option<&str>, body: Value) -> Request<Body> {
+    let mut request = Request::builder()
+        .method("POST")
+        .uri(uri)
+        .header("content-type", "application/json");
+    if let Some(key) = project_key {
+        request = request.header("X-Paycheck-Project", key);
+    }
+    request.body(Body::from(body.to_string())).unwrap()
+}
+
+fn redeem_body(code: &str) -> Value {
+    json!({
+        "code": code,
+        "device_id": "device-1",
+        "device_type": "uuid"
+    })
+}
+
+#[tokio::test]
+async fn test_validate_accepts_header_without_body_key() {
+    let f = setup();
+    let device = create_test_device(
+        &f.state.db.get().unwrap(),
+        &f.license.id,
+        "device-1",
+        DeviceType::Uuid,
+    );
+    let app = public_app(f.state.clone());
+
+    let (status, body) = send(
+        &app,
+        post(
+            "/validate",
+            Some(&f.project.public_key),
+            json!({ "jti": device.jti }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["valid"], true);
+}
+
+#[tokio::test]
+async fn test_validate_without_any_key_rejected() {
+    let f = setup();
+    let app = public_app(f.state.clone());
+
+    let (status, body) = send(&app, post("/validate", None, json!({ "jti": "x" }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        body["details"],
+        "public_key is required (X-Paycheck-Project header or public_key field)"
+    );
+}
+
+#[tokio::test]
+async fn test_redeem_accepts_header_without_body_key() {
+    let f = setup();
+    let code = create_test_activation_code(
+        &f.state.db.get().unwrap(),
+        &f.license.id,
+        &f.project.license_key_prefix,
+    );
+    let app = public_app(f.state.clone());
+
+    let (status, body) = send(
+        &app,
+        post(
+            "/redeem",
+            Some(&f.project.public_key),
+            redeem_body(&code.code),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["token"].is_string());
+}
+
+#[tokio::test]
+async fn test_header_and_body_key_mismatch_rejected() {
+    let f = setup();
+    let app = public_app(f.state.clone());
+
+    let mut redeem = redeem_body("TEST-AAAA-BBBB");
+    redeem["public_key"] = json!(f.project.public_key);
+    let requests = [
+        (
+            "validate",
+            post(
+                "/validate",
+                Some(&f.other.public_key),
+                json!({ "public_key": f.project.public_key, "jti": "x" }),
+            ),
+        ),
+        ("redeem", post("/redeem", Some(&f.other.public_key), redeem)),
+        (
+            "buy",
+            post(
+                "/buy",
+                Some(&f.other.public_key),
+                json!({ "public_key": f.project.public_key, "product_id": f.product.id }),
+            ),
+        ),
+    ];
+
+    for (endpoint, request) in requests {
+        let (status, body) = send(&app, request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", endpoint);
+        assert_eq!(
+            body["details"], "X-Paycheck-Project header does not match the request's public_key",
+            "{}",
+            endpoint
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_buy_header_key_for_other_project_rejected() {
+    let f = setup();
+    let app = public_app(f.state.clone());
+
+    let (status, body) = send(
+        &app,
+        post(
+            "/buy",
+            Some(&f.other.public_key),
+            json!({ "product_id": f.product.id }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["details"], "Product does not belong to this project");
+}
+
+#[tokio::test]
+async fn test_buy_header_key_for_own_project_accepted() {
+    let f = setup();
+    let app = public_app(f.state.clone());
+
+    let (status, body) = send(
+        &app,
+        post(
+            "/buy",
+            Some(&f.project.public_key),
+            json!({ "product_id": f.product.id }),
+        ),
+    )
+    .await;
+    // Gets past project resolution to the provider check
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["details"], "No payment provider configured");
+}
+
+#[tokio::test]
+async fn test_buy_product_id_only_follows_project_setting() {
+    let f = setup();
+    let app = public_app(f.state.clone());
+    let buy = || post("/buy", None, json!({ "product_id": f.product.id }));
+
+    // Existing projects keep the deprecated lookup
+    let (_, body) = send(&app, buy()).await;
+    assert_eq!(body["details"], "No payment provider configured");
+
+    f.state
+        .db
+        .get()
+        .unwrap()
+        .execute(
+            "UPDATE projects SET allow_project_id_auth = 0 WHERE id = ?1",
+            [&f.project.id],
+        )
+        .unwrap();
+
+    let (status, body) = send(&app, buy()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        body["details"],
+        "public_key is required (X-Paycheck-Project header or public_key field)"
+    );
+
+    let (_, body) = send(
+        &app,
+        post(
+            "/buy",
+            Some(&f.project.public_key),
+            json!({ "product_id": f.product.id }),
+        ),
+    )
+    .await;
+    assert_eq!(body["details"], "No payment provider configured");
+}
+
+#[tokio::test]
+async fn test_deactivate_with_other_project_header_rejected() {
+    let f = setup();
+    let device = create_test_device(
+        &f.state.db.get().unwrap(),
+        &f.license.id,
+        "device-1",
+        DeviceType::Uuid,
+    );
+    let claims = paycheck::jwt::LicenseClaims {
+        license_exp: f.license.expires_at,
+        updates_exp: f.license.updates_expires_at,
+        tier: f.product.tier.clone(),
+        features: f.product.features.clone(),
+        entitlements: f.product.entitlements.clone(),
+        device_id: device.device_id.clone(),
+        device_type: "uuid".to_string(),
+        license_ref: None,
+        product_id: f.product.id.clone(),
+    };
+    let private_key = queries::decrypt_project_private_key(
+        &f.state.db.get().unwrap(),
+        &f.project,
+        &test_master_key(),
+    )
+    .unwrap();
+    let token = paycheck::jwt::sign_claims(
+        &claims,
+        &private_key,
+        &f.license.id,
+        &f.project.name,
+        &device.jti,
+    )
+    .unwrap();
+    let app = public_app(f.state.clone());
+
+    let deactivate = |project_key: &str| {
+        Request::builder()
+            .method("POST")
+            .uri("/devices/deactivate")
+            .header("Authorization", format!("Bearer {}", token))
+            .header("X-Paycheck-Project", project_key)
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let (status, _) = send(&app, deactivate(&f.other.public_key)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, body) = send(&app, deactivate(&f.project.public_key)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["deactivated"], true);
+}
+
+#[tokio::test]
+async fn test_discovery_advertises_header() {
+    let f = setup();
+    let app = public_app(f.state.clone());
+
+    let (status, body) = send(
+        &app,
+        Request::builder()
+            .uri(format!(
+                "/discovery?public_key={}",
+                urlencoding::encode(&f.project.public_key)
+            ))
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["project_key_header"], "X-Paycheck-Project");
+}
@@ -0,0 +1,231 @@
+//! Tests for unknown-project handling on public endpoints.
+//!
+//! Unknown public keys get a consistent 404 envelope, and recently-missed keys
+//! are answered from the negative cache without a database roundtrip.
+
+use axum::{Router, body::Body, http::Request, http::StatusCode};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::config::RateLimitConfig;
+use paycheck::handlers;
+use paycheck::jwt;
+
+const UNKNOWN_KEY: &str = "bm90LWEtcmVhbC1wcm9qZWN0LWtleQ==";
+
+/// Public and org routes sharing one state (org routes create/restore projects).
+fn app(state: &AppState) -> Router {
+    public_app(state.clone()).merge(
+        handlers::orgs::router(state.clone(), RateLimitConfig::disabled())
+            .with_state(state.clone()),
+    )
+}
+
+async fn send(app: &Router, request: Request<Body>) -> (StatusCode, Value) {
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+fn get(uri: String) -> Request<Body> {
+    Request::builder().uri(uri).body(Body::empty()).unwrap()
+}
+
+fn post(uri: &str, body: Value) -> Request<Body> {
+    Request::builder()
+        .method("POST")
+        .uri(uri)
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+fn discovery(public_key: &str) -> Request<Body> {
+    get(format!(
+        "/discovery?public_key={}",
+        urlencoding::encode(public_key)
+    ))
+}
+
+#[tokio::test]
+async fn test_unknown_public_key_returns_404_envelope() {
+    let state = create_test_app_state();
+    let product_id = {
+        let conn = state.db.get().unwrap();
+        let org = create_test_org(&conn, "Test Org");
+        let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+        create_test_product(&conn, &project.id, "Pro", "pro").id
+    };
+    let app = app(&state);
+
+    let requests = [
+        ("discovery", discovery(UNKNOWN_KEY)),
+        (
+            "redeem",
+            post(
+                "/redeem",
+                json!({
+                    "public_key": UNKNOWN_KEY,
+                    "code": "TEST-AAAA-BBBB",
+                    "device_id": "device-1",
+                    "device_type": "uuid"
+                }),
+            ),
+        ),
+        (
+            "license",
+            Request::builder()
+                .uri(format!(
+                    "/license?public_key={}",
+                    urlencoding::encode(UNKNOWN_KEY)
+                ))
+                .header("Authorization", "Bearer not.a.token")
+                .body(Body::empty())
+                .unwrap(),
+        ),
+        // The product exists, so the project lookup must be what fails
+        (
+            "buy",
+            post(
+                "/buy",
+                json!({ "public_key": UNKNOWN_KEY, "product_id": product_id }),
+            ),
+        ),
+    ];
+
+    for (endpoint, request) in requests {
+        let (status, body) = send(&app, request).await;
+        assert_eq!(
+            status,
+            StatusCode::NOT_FOUND,
+            "{} should return 404",
+            endpoint
+        );
+        assert_eq!(
+            (body["error"].as_str(), body["details"].as_str()),
+            (Some("Not found"), Some("Project not found")),
+            "{} should use the standard error envelope",
+            endpoint
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_repeat_miss_does_not_hit_database() {
+    let state = create_test_app_state();
+    let app = app(&state);
+
+    let (status, _) = send(&app, discovery(UNKNOWN_KEY)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(state.project_misses.is_known_missing(UNKNOWN_KEY));
+
+    // Any query against projects now fails, so only a cached answer can 404
+    state
+        .db
+        .get()
+        .unwrap()
+        .execute("ALTER TABLE projects RENAME TO projects_hidden", [])
+        .unwrap();
+
+    for _ in 0..100 {
+        let (status, body) = send(&app, discovery(UNKNOWN_KEY)).await;
+        assert_eq!(
+            status,
+            StatusCode::NOT_FOUND,
+            "repeat miss should be served from the cache"
+        );
+        assert_eq!(body["details"], "Project not found");
+    }
+
+    let (status, _) = send(&app, discovery("c29tZS1vdGhlci1rZXk=")).await;
+    assert_eq!(
+        status,
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "an uncached key should still reach the (now broken) database"
+    );
+}
+
+#[tokio::test]
+async fn test_project_creation_invalidates_cached_misses() {
+    let state = create_test_app_state();
+    let app = app(&state);
+    let (_, public_key) = jwt::generate_keypair();
+
+    let (org_id, api_key) = {
+        let mut conn = state.db.get().unwrap();
+        let org = create_test_org(&conn, "Test Org");
+        let (_, _, key) =
+            create_test_org_member(&mut conn, &org.id, "owner@test.com", OrgMemberRole::Owner);
+        (org.id, key)
+    };
+
+    let (status, _) = send(&app, discovery(&public_key)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(state.project_misses.is_known_missing(&public_key));
+
+    let mut request = post(
+        &format!("/orgs/{}/projects", org_id),
+        json!({ "name": "New Project", "license_key_prefix": "NEW" }),
+    );
+    request.headers_mut().insert(
+        "Authorization",
+        format!("Bearer {}", api_key).parse().unwrap(),
+    );
+    let (status, created) = send(&app, request).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(
+        !state.project_misses.is_known_missing(&public_key),
+        "creating a project should drop cached misses"
+    );
+
+    // The previously-missed key now belongs to a project and must resolve
+    state
+        .db
+        .get()
+        .unwrap()
+        .execute(
+            "UPDATE projects SET public_key = ?1 WHERE id = ?2",
+            rusqlite::params![public_key, created["id"].as_str().unwrap()],
+        )
+        .unwrap();
+    let (status, body) = send(&app, discovery(&public_key)).await;
+    assert_eq!(status, StatusCode::OK, "new project must not be blackholed");
+    assert_eq!(body["jwks"]["keys"][0]["kty"], "OKP");
+}
+
+#[test]
+fn test_miss_cache_expires_and_evicts() {
+    use std::time::Duration;
+
+    let cache = ProjectMissCache::new(2, Duration::from_secs(30));
+    cache.record_miss("a");
+    cache.record_miss("b");
+    cache.record_miss("c");
+    assert!(
+        !cache.is_known_missing("a"),
+        "oldest miss should be evicted"
+    );
+    assert!(cache.is_known_missing("b"));
+    assert!(cache.is_known_missing("c"));
+    assert_eq!(cache.len(), 2);
+
+    cache.invalidate("b");
+    assert!(!cache.is_known_missing("b"));
+
+    let expired = ProjectMissCache::new(8, Duration::ZERO);
+    expired.record_miss("a");
+    assert!(
+        !expired.is_known_missing("a"),
+        "misses should expire after the TTL"
+    );
+    expired.record_miss("b");
+    expired.cleanup();
+    assert!(expired.is_empty());
+}
@@ -0,0 +1,134 @@
+//! Tests for the outbound provider call limits: one org's backlog mustn't
+//! starve another org, and a saturated org's /buy requests get 503.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode, header},
+};
+use serde_json::json;
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::config::ProviderCallLimits;
+use paycheck::error::AppError;
+
+fn governor(per_org: usize, global: usize, wait_timeout_ms: u64) -> Arc<ProviderCallGovernor> {
+    Arc::new(ProviderCallGovernor::new(ProviderCallLimits {
+        per_org,
+        global,
+        wait_timeout_ms,
+    }))
+}
+
+/// Spawn `count` calls for `org_id` that each take `duration`. Each task
+/// returns how long it took from spawn to finish.
+fn spawn_calls(
+    governor: &Arc<ProviderCallGovernor>,
+    org_id: &str,
+    count: usize,
+    duration: Duration,
+) -> Vec<tokio::task::JoinHandle<Result<Duration, AppError>>> {
+    (0..count)
+        .map(|_| {
+            let governor = governor.clone();
+            let org_id = org_id.to_string();
+            tokio::spawn(async move {
+                let started = Instant::now();
+                governor
+                    .run(&org_id, async {
+                        tokio::time::sleep(duration).await;
+                        Ok(())
+                    })
+                    .await?;
+                Ok(started.elapsed())
+            })
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_saturated_org_does_not_block_other_org() {
+    let governor = governor(2, 4, 10_000);
+
+    let busy = spawn_calls(&governor, "org-busy", 20, Duration::from_millis(300));
+    // Let the busy org take its slots and queue the rest
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    let quiet = spawn_calls(&governor, "org-quiet", 4, Duration::from_millis(20));
+
+    for handle in quiet {
+        let elapsed = handle.await.unwrap().unwrap();
+        assert!(
+            elapsed < Duration::from_millis(250),
+            "quiet org waited behind the busy org: {:?}",
+            elapsed
+        );
+    }
+    for handle in busy {
+        handle.abort();
+    }
+}
+
+#[tokio::test]
+async fn test_global_limit_applies_across_orgs() {
+    let governor = governor(2, 2, 50);
+
+    let _held = [
+        governor.acquire("org-busy").await.unwrap(),
+        governor.acquire("org-busy").await.unwrap(),
+    ];
+
+    let err = governor.acquire("org-quiet").await.err().unwrap();
+    assert!(matches!(err, AppError::ProviderBusy { .. }), "{:?}", err);
+}
+
+#[tokio::test]
+async fn test_org_slots_free_up_after_calls_finish() {
+    let governor = governor(1, 1, 50);
+
+    let permit = governor.acquire("org-a").await.unwrap();
+    assert!(governor.acquire("org-a").await.is_err());
+    drop(permit);
+
+    assert!(governor.acquire("org-a").await.is_ok());
+}
+
+#[tokio::test]
+async fn test_buy_returns_503_with_retry_after_when_org_saturated() {
+    let mut state = create_test_app_state();
+    state.provider_calls = governor(1, 10, 50);
+    let master_key = test_master_key();
+
+    let (org_id, product_id) = {
+        let conn = state.db.get().unwrap();
+        let org = create_test_org(&conn, "Busy Org");
+        setup_stripe_config(&conn, &org.id, &master_key);
+        let project = create_test_project(&conn, &org.id, "Test Project", &master_key);
+        let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+        create_test_provider_link(&conn, &product.id, "stripe", "price_test_123");
+        (org.id, product.id)
+    };
+
+    // Another checkout for this org is already talking to Stripe
+    let _in_flight = state.provider_calls.acquire(&org_id).await.unwrap();
+
+    let response = public_app(state.clone())
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/buy")
+                .header("content-type", "application/json")
+                .body(Body::from(json!({ "product_id": product_id }).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+}
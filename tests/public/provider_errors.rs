@@ -0,0 +1,305 @@
+//! Tests for payment provider failures: each class of provider response is
+//! classified into a `PaymentError` and answered with the matching status,
+//! against a mock provider on a local port.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use axum::{
+    Router,
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde_json::{Value, json};
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::error::{AppError, msg};
+use paycheck::payments::{
+    CheckoutSettings, DEFAULT_RETRY_AFTER_SECS, LemonSqueezyClient, PaymentError, StripeClient,
+};
+
+/// What the mock provider answers to every request
+#[derive(Clone)]
+struct Canned {
+    status: StatusCode,
+    retry_after: Option<&'static str>,
+    body: Value,
+    delay: Duration,
+}
+
+impl Canned {
+    fn new(status: StatusCode, body: Value) -> Self {
+        Self {
+            status,
+            retry_after: None,
+            body,
+            delay: Duration::ZERO,
+        }
+    }
+}
+
+/// Serve `canned` on a local port and return its base URL.
+async fn mock_provider(canned: Canned) -> String {
+    let app = Router::new().fallback(move || {
+        let canned = canned.clone();
+        async move {
+            tokio::time::sleep(canned.delay).await;
+            let mut response = (canned.status, axum::Json(canned.body)).into_response();
+            if let Some(secs) = canned.retry_after {
+                response
+                    .headers_mut()
+                    .insert(header::RETRY_AFTER, secs.parse().unwrap());
+            }
+            response
+        }
+    });
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}", addr)
+}
+
+fn stripe_client(api_base: &str, timeout: Duration) -> StripeClient {
+    let config = StripeConfig {
+        secret_key: "sk_test_xxx".to_string(),
+        publishable_key: "pk_test_xxx".to_string(),
+        webhook_secret: "whsec_test123secret456".to_string(),
+    };
+    StripeClient::with_endpoint(&config, api_base, timeout)
+}
+
+async fn stripe_checkout(canned: Canned) -> Result<(String, String), PaymentError> {
+    let api_base = mock_provider(canned).await;
+    stripe_client(&api_base, Duration::from_secs(5))
+        .create_checkout_session(
+            "session-1",
+            "project-1",
+            "product-1",
+            "price_missing",
+            "https://example.com/callback",
+            "https://example.com/cancel",
+            None,
+            &BTreeMap::new(),
+            &BTreeMap::new(),
+            &CheckoutSettings::default(),
+        )
+        .await
+}
+
+/// Status, `Retry-After`, and body /buy would answer with.
+async fn api_response(err: PaymentError) -> (StatusCode, Option<String>, Value) {
+    let response: Response = AppError::from(err).into_response();
+    let status = response.status();
+    let retry_after = response
+        .headers()
+        .get(header::RETRY_AFTER)
+        .map(|value| value.to_str().unwrap().to_string());
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, retry_after, serde_json::from_slice(&body).unwrap())
+}
+
+#[tokio::test]
+async fn test_stripe_success_returns_session() {
+    let canned = Canned::new(
+        StatusCode::OK,
+        json!({ "id": "cs_test_123", "url": "https://checkout.stripe.com/c/pay/cs_test_123" }),
+    );
+
+    let (id, url) = stripe_checkout(canned).await.unwrap();
+    assert_eq!(id, "cs_test_123");
+    assert_eq!(url, "https://checkout.stripe.com/c/pay/cs_test_123");
+}
+
+#[tokio::test]
+async fn test_stripe_rejection_is_400_with_provider_message() {
+    let canned = Canned::new(
+        StatusCode::BAD_REQUEST,
+        json!({ "error": {
+            "type": "invalid_request_error",
+            "code": "resource_missing",
+            "message": "No such price: 'price_missing'"
+        }}),
+    );
+
+    let err = stripe_checkout(canned).await.unwrap_err();
+    assert!(
+        matches!(
+            &err,
+            PaymentError::ProviderRejected { code: Some(code), .. } if code == "resource_missing"
+        ),
+        "{:?}",
+        err
+    );
+    assert!(!err.is_transient());
+
+    let (status, retry_after, body) = api_response(err).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(retry_after, None);
+    assert_eq!(body["code"], "provider_rejected");
+    assert_eq!(
+        body["details"],
+        format!("{}: No such price: 'price_missing'", msg::PROVIDER_REJECTED)
+    );
+}
+
+#[tokio::test]
+async fn test_stripe_bad_credentials_are_400_without_provider_message() {
+    let canned = Canned::new(
+        StatusCode::UNAUTHORIZED,
+        json!({ "error": {
+            "type": "invalid_request_error",
+            "message": "Invalid API Key provided: sk_test_***xxx"
+        }}),
+    );
+
+    let err = stripe_checkout(canned).await.unwrap_err();
+    assert!(matches!(err, PaymentError::InvalidConfig(_)), "{:?}", err);
+
+    let (status, retry_after, body) = api_response(err).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(retry_after, None);
+    assert_eq!(body["code"], "provider_config_invalid");
+    // The provider's message can quote part of the key
+    assert_eq!(body["details"], msg::PROVIDER_CONFIG_INVALID);
+}
+
+#[tokio::test]
+async fn test_stripe_rate_limit_is_503_with_provider_retry_after() {
+    let mut canned = Canned::new(
+        StatusCode::TOO_MANY_REQUESTS,
+        json!({ "error": { "code": "rate_limit", "message": "Too many requests" } }),
+    );
+    canned.retry_after = Some("7");
+
+    let err = stripe_checkout(canned).await.unwrap_err();
+    assert!(
+        matches!(
+            err,
+            PaymentError::RateLimited {
+                retry_after_secs: 7
+            }
+        ),
+        "{:?}",
+        err
+    );
+
+    let (status, retry_after, body) = api_response(err).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(retry_after.as_deref(), Some("7"));
+    assert_eq!(body["code"], "provider_rate_limited");
+    assert_eq!(body["details"], msg::PROVIDER_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn test_stripe_server_error_is_503() {
+    let canned = Canned::new(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        json!({ "error": { "type": "api_error", "message": "Something went wrong" } }),
+    );
+
+    let err = stripe_checkout(canned).await.unwrap_err();
+    assert!(matches!(err, PaymentError::Network(_)), "{:?}", err);
+
+    let (status, retry_after, body) = api_response(err).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(retry_after, Some(DEFAULT_RETRY_AFTER_SECS.to_string()));
+    assert_eq!(body["code"], "provider_unavailable");
+}
+
+#[tokio::test]
+async fn test_stripe_timeout_is_503() {
+    let mut canned = Canned::new(StatusCode::OK, json!({}));
+    canned.delay = Duration::from_secs(2);
+    let api_base = mock_provider(canned).await;
+
+    let err = stripe_client(&api_base, Duration::from_millis(100))
+        .create_upgrade_coupon("session-1", 500, "usd")
+        .await
+        .unwrap_err();
+    assert!(matches!(err, PaymentError::Timeout), "{:?}", err);
+
+    let (status, retry_after, body) = api_response(err).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert!(retry_after.is_some());
+    assert_eq!(body["code"], "provider_timeout");
+}
+
+#[tokio::test]
+async fn test_unreachable_provider_is_503() {
+    // Bind and drop a listener for a port nothing listens on
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let api_base = format!("http://{}", listener.local_addr().unwrap());
+    drop(listener);
+
+    let err = stripe_client(&api_base, Duration::from_secs(5))
+        .create_upgrade_coupon("session-1", 500, "usd")
+        .await
+        .unwrap_err();
+    assert!(matches!(err, PaymentError::Network(_)), "{:?}", err);
+
+    let (status, _, _) = api_response(err).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn test_lemonsqueezy_rejection_uses_json_api_error() {
+    let canned = Canned::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        json!({ "errors": [{
+            "status": "422",
+            "title": "Unprocessable Entity",
+            "detail": "The selected variant is invalid."
+        }]}),
+    );
+    let api_base = mock_provider(canned).await;
+    let config = LemonSqueezyConfig {
+        api_key: "lskey_test_xxx".to_string(),
+        store_id: "12345".to_string(),
+        webhook_secret: "ls_whsec_test_secret".to_string(),
+        verify_imported_keys: false,
+    };
+
+    let err = LemonSqueezyClient::with_endpoint(&config, &api_base, Duration::from_secs(5))
+        .create_checkout(
+            "session-1",
+            "project-1",
+            "product-1",
+            "999",
+            "https://example.com/callback",
+            &BTreeMap::new(),
+            &CheckoutSettings::default(),
+        )
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(
+            &err,
+            PaymentError::ProviderRejected { code: None, message }
+                if message == "The selected variant is invalid."
+        ),
+        "{:?}",
+        err
+    );
+
+    let (status, _, _) = api_response(err).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn test_unreadable_webhook_signature_is_a_rejection() {
+    let client = stripe_client("http://127.0.0.1:1", Duration::from_secs(1));
+
+    let err = client
+        .verify_webhook_signature(b"{}", "garbage")
+        .unwrap_err();
+    assert!(
+        matches!(err, PaymentError::ProviderRejected { .. }),
+        "{:?}",
+        err
+    );
+}
@@ -343,6 +343,79 @@ async fn test_redeem_code_creates_device_record() {
     );
 }
 
+#[tokio::test]
+async fn test_redeem_writes_public_audit_log() {
+    let mut state = create_test_app_state();
+    state.audit_log_enabled = true;
+    let master_key = test_master_key();
+
+    let public_key: String;
+    let code: String;
+    let license_id: String;
+    let project_id: String;
+
+    {
+        let mut conn = state.db.get().unwrap();
+        let org = create_test_org(&mut conn, "Test Org");
+        let project = create_test_project(&mut conn, &org.id, "Test Project", &master_key);
+        let product = create_test_product(&mut conn, &project.id, "Pro Plan", "pro");
+        let license = create_test_license(
+            &conn,
+            &project.id,
+            &product.id,
+            Some(future_timestamp(ONE_YEAR)),
+        );
+
+        let activation_code =
+            queries::create_activation_code(&mut conn, &license.id, &project.license_key_prefix)
+                .unwrap();
+
+        public_key = project.public_key.clone();
+        code = activation_code.code.clone();
+        license_id = license.id.clone();
+        project_id = project.id.clone();
+    }
+
+    let app = public_app(state.clone());
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/redeem")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    serde_json::to_string(&json!({
+                        "public_key": public_key,
+                        "code": code,
+                        "device_id": "audited-device",
+                        "device_type": "uuid"
+                    }))
+                    .unwrap(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), axum::http::StatusCode::OK);
+
+    let audit_conn = state.audit.get().unwrap();
+    let (actor_type, user_id, logged_project, details): (String, Option<String>, String, String) =
+        audit_conn
+            .query_row(
+                "SELECT actor_type, user_id, project_id, details FROM audit_logs
+                 WHERE action = 'activate_device' AND resource_id = 'audited-device'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .expect("redeem should write an activate_device audit log");
+    assert_eq!(actor_type, "public", "redeem is audited as a public action");
+    assert_eq!(user_id, None, "public actions have no user");
+    assert_eq!(logged_project, project_id);
+    let details: Value = serde_json::from_str(&details).unwrap();
+    assert_eq!(details["license_id"], license_id);
+}
+
 #[tokio::test]
 async fn test_redeem_revoked_license_returns_forbidden() {
     let state = create_test_app_state();
@@ -368,7 +441,7 @@ async fn test_redeem_revoked_license_returns_forbidden() {
                 .unwrap();
 
         // Revoke the license
-        queries::revoke_license(&mut conn, &license.id).unwrap();
+        queries::revoke_license(&mut conn, &license.id, &RevokeLicense::default(), None).unwrap();
 
         public_key = project.public_key.clone();
         code = activation_code.code.clone();
@@ -485,6 +558,14 @@ async fn test_redeem_device_limit_exceeded_returns_error() {
             device_limit: Some(1), // Only 1 device allowed
             device_inactive_days: None,
             features: vec![],
+            entitlements: Default::default(),
+            seat_count: None,
+            available_from: None,
+            available_until: None,
+            checkout_fields: vec![],
+            extends_updates_for_product_id: None,
+            renewal_fallback: Default::default(),
+            revoke_on_partial_refund: false,
         };
         let product =
             queries::create_product(&mut conn, &project.id, &input).expect("Failed to create product");
@@ -656,6 +737,81 @@ async fn test_redeem_with_public_key() {
     );
 }
 
+#[tokio::test]
+async fn test_redeem_includes_product_entitlements_in_token() {
+    let state = create_test_app_state();
+    let master_key = test_master_key();
+
+    let public_key: String;
+    let code: String;
+
+    {
+        let conn = state.db.get().unwrap();
+        let org = create_test_org(&conn, "Test Org");
+        let project = create_test_project(&conn, &org.id, "Test Project", &master_key);
+        let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+        queries::update_product(
+            &conn,
+            &product.id,
+            &serde_json::from_value(json!({
+                "entitlements": { "seats": 5, "sso": true, "support": "priority" }
+            }))
+            .unwrap(),
+        )
+        .unwrap();
+        let license = create_test_license(&conn, &project.id, &product.id, None);
+
+        let activation_code =
+            queries::create_activation_code(&conn, &license.id, &project.license_key_prefix)
+                .unwrap();
+
+        public_key = project.public_key.clone();
+        code = activation_code.code.clone();
+    }
+
+    let app = public_app(state);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/redeem")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    serde_json::to_string(&json!({
+                        "public_key": public_key,
+                        "code": code,
+                        "device_id": "test-device",
+                        "device_type": "uuid"
+                    }))
+                    .unwrap(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), axum::http::StatusCode::OK);
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    let expected = json!({ "seats": 5, "sso": true, "support": "priority" });
+    assert_eq!(json["entitlements"], expected);
+
+    // The legacy features list is still there alongside
+    let claims = paycheck::jwt::decode_unverified(json["token"].as_str().unwrap()).unwrap();
+    assert_eq!(claims.features, ["feature1", "feature2"]);
+    assert_eq!(
+        serde_json::to_value(&claims.entitlements).unwrap(),
+        expected
+    );
+    assert_eq!(
+        claims.entitlement("seats"),
+        Some(&paycheck::models::EntitlementValue::Number(5))
+    );
+}
+
 // ============================================================================
 // Security Tests - Activation Code Security
 // ============================================================================
@@ -932,7 +1088,7 @@ mod activation_code_security {
                     .unwrap();
 
             // Then revoke the license
-            queries::revoke_license(&mut conn, &license.id).unwrap();
+            queries::revoke_license(&mut conn, &license.id, &RevokeLicense::default(), None).unwrap();
 
             public_key = project.public_key.clone();
             code = activation_code.code.clone();
@@ -1063,6 +1219,14 @@ mod device_limit_enforcement {
                 device_limit: Some(2),
         device_inactive_days: None,
                 features: vec![],
+                entitlements: Default::default(),
+                seat_count: None,
+                available_from: None,
+                available_until: None,
+                checkout_fields: vec![],
+                extends_updates_for_product_id: None,
+                renewal_fallback: Default::default(),
+                revoke_on_partial_refund: false,
             };
             let product = queries::create_product(&mut conn, &project.id, &input).unwrap();
 
@@ -1133,6 +1297,14 @@ mod device_limit_enforcement {
                 device_limit: Some(1),
         device_inactive_days: None,
                 features: vec![],
+                entitlements: Default::default(),
+                seat_count: None,
+                available_from: None,
+                available_until: None,
+                checkout_fields: vec![],
+                extends_updates_for_product_id: None,
+                renewal_fallback: Default::default(),
+                revoke_on_partial_refund: false,
             };
             let product = queries::create_product(&mut conn, &project.id, &input).unwrap();
 
@@ -1210,6 +1382,14 @@ mod device_limit_enforcement {
                 device_limit: None, // 0 means unlimited devices
                 device_inactive_days: None,
                 features: vec![],
+                entitlements: Default::default(),
+                seat_count: None,
+                available_from: None,
+                available_until: None,
+                checkout_fields: vec![],
+                extends_updates_for_product_id: None,
+                renewal_fallback: Default::default(),
+                revoke_on_partial_refund: false,
             };
             let product = queries::create_product(&mut conn, &project.id, &input).unwrap();
 
@@ -1283,6 +1463,14 @@ mod device_limit_enforcement {
                 device_limit: Some(1),
         device_inactive_days: None,
                 features: vec![],
+                entitlements: Default::default(),
+                seat_count: None,
+                available_from: None,
+                available_until: None,
+                checkout_fields: vec![],
+                extends_updates_for_product_id: None,
+                renewal_fallback: Default::default(),
+                revoke_on_partial_refund: false,
             };
             let product = queries::create_product(&mut conn, &project.id, &input).unwrap();
 
@@ -1368,6 +1556,14 @@ mod activation_limit_enforcement {
                 device_limit: Some(10),    // Device limit is higher
                 device_inactive_days: None,
                 features: vec![],
+                entitlements: Default::default(),
+                seat_count: None,
+                available_from: None,
+                available_until: None,
+                checkout_fields: vec![],
+                extends_updates_for_product_id: None,
+                renewal_fallback: Default::default(),
+                revoke_on_partial_refund: false,
             };
             let product = queries::create_product(&mut conn, &project.id, &input).unwrap();
 
@@ -1448,6 +1644,14 @@ mod activation_limit_enforcement {
                 device_limit: Some(10),    // Device limit is higher
                 device_inactive_days: None,
                 features: vec![],
+                entitlements: Default::default(),
+                seat_count: None,
+                available_from: None,
+                available_until: None,
+                checkout_fields: vec![],
+                extends_updates_for_product_id: None,
+                renewal_fallback: Default::default(),
+                revoke_on_partial_refund: false,
             };
             let product = queries::create_product(&mut conn, &project.id, &input).unwrap();
 
@@ -1593,6 +1797,14 @@ mod activation_limit_enforcement {
                 device_limit: Some(1),       // Only 1 device allowed!
                 device_inactive_days: None,
                 features: vec![],
+                entitlements: Default::default(),
+                seat_count: None,
+                available_from: None,
+                available_until: None,
+                checkout_fields: vec![],
+                extends_updates_for_product_id: None,
+                renewal_fallback: Default::default(),
+                revoke_on_partial_refund: false,
             };
             let product = queries::create_product(&mut conn, &project.id, &input).unwrap();
             let license = create_test_license(
@@ -1720,6 +1932,14 @@ mod activation_limit_enforcement {
                 device_limit: Some(100),   // High device limit
                 device_inactive_days: None,
                 features: vec![],
+                entitlements: Default::default(),
+                seat_count: None,
+                available_from: None,
+                available_until: None,
+                checkout_fields: vec![],
+                extends_updates_for_product_id: None,
+                renewal_fallback: Default::default(),
+                revoke_on_partial_refund: false,
             };
             let product = queries::create_product(&mut conn, &project.id, &input).unwrap();
             let license = create_test_license(
@@ -1846,6 +2066,14 @@ mod activation_limit_enforcement {
                 device_limit: Some(100),
         device_inactive_days: None,
                 features: vec![],
+                entitlements: Default::default(),
+                seat_count: None,
+                available_from: None,
+                available_until: None,
+                checkout_fields: vec![],
+                extends_updates_for_product_id: None,
+                renewal_fallback: Default::default(),
+                revoke_on_partial_refund: false,
             };
             let product = queries::create_product(&mut conn, &project.id, &input).unwrap();
             let license = create_test_license(
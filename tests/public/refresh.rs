@@ -66,14 +66,15 @@ fn setup_refresh_test() -> (Router, String, String, String, String) {
             updates_exp: Some(future_timestamp(UPDATES_VALID_DAYS)),
             tier: product.tier.clone(),
             features: product.features.clone(),
+            entitlements: product.entitlements.clone(),
             device_id: device.device_id.clone(),
             device_type: "uuid".to_string(),
+            license_ref: None,
             product_id: product.id.clone(),
         };
 
-        let private_key = master_key
-            .decrypt_private_key(&project.id, &project.private_key)
-            .unwrap();
+        let private_key =
+            queries::decrypt_project_private_key(&conn, &project, &master_key).unwrap();
 
         token = jwt::sign_claims(
             &claims,
@@ -97,8 +98,11 @@ fn setup_refresh_test() -> (Router, String, String, String, String) {
         audit: audit_pool,
         base_url: "http://localhost:3000".to_string(),
         audit_log_enabled: true,
+        audit_failure_mode: paycheck::models::AuditFailureMode::default(),
+        public_audit_retention_days: 0,
         master_key,
         email_hasher: paycheck::crypto::EmailHasher::from_bytes([0xAA; 32]),
+        pii_minimization: false,
         success_page_url: "http://localhost:3000/success".to_string(),
         activation_rate_limiter: std::sync::Arc::new(
             paycheck::rate_limit::ActivationRateLimiter::default(),
@@ -109,6 +113,20 @@ fn setup_refresh_test() -> (Router, String, String, String, String) {
         )),
         jwks_cache: std::sync::Arc::new(paycheck::jwt::JwksCache::new()),
         trusted_issuers: vec![],
+        org_dbs: std::sync::Arc::new(paycheck::db::OrgDbRegistry::disabled()),
+        project_misses: std::sync::Arc::new(paycheck::db::ProjectMissCache::default()),
+        clock: paycheck::util::Clock::system(),
+        provider_calls: std::sync::Arc::new(paycheck::payments::ProviderCallGovernor::default()),
+        validation_limiter: std::sync::Arc::new(paycheck::rate_limit::ValidationRateLimiter::new()),
+        free_license_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_free_licenses(),
+        ),
+        session_wait_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_session_waits(),
+        ),
+        allow_localhost_urls: false,
+        maintenance: std::sync::Arc::new(paycheck::middleware::MaintenanceMode::default()),
+        backups: None,
     };
 
     let app = Router::new()
@@ -270,8 +288,11 @@ async fn test_refresh_rejects_non_uuid_product_id() {
         audit: audit_pool,
         base_url: "http://localhost:3000".to_string(),
         audit_log_enabled: false,
+        audit_failure_mode: paycheck::models::AuditFailureMode::default(),
+        public_audit_retention_days: 0,
         master_key,
         email_hasher: paycheck::crypto::EmailHasher::from_bytes([0xAA; 32]),
+        pii_minimization: false,
         success_page_url: "http://localhost:3000/success".to_string(),
         activation_rate_limiter: std::sync::Arc::new(
             paycheck::rate_limit::ActivationRateLimiter::default(),
@@ -282,6 +303,20 @@ async fn test_refresh_rejects_non_uuid_product_id() {
         )),
         jwks_cache: std::sync::Arc::new(paycheck::jwt::JwksCache::new()),
         trusted_issuers: vec![],
+        org_dbs: std::sync::Arc::new(paycheck::db::OrgDbRegistry::disabled()),
+        project_misses: std::sync::Arc::new(paycheck::db::ProjectMissCache::default()),
+        clock: paycheck::util::Clock::system(),
+        provider_calls: std::sync::Arc::new(paycheck::payments::ProviderCallGovernor::default()),
+        validation_limiter: std::sync::Arc::new(paycheck::rate_limit::ValidationRateLimiter::new()),
+        free_license_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_free_licenses(),
+        ),
+        session_wait_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_session_waits(),
+        ),
+        allow_localhost_urls: false,
+        maintenance: std::sync::Arc::new(paycheck::middleware::MaintenanceMode::default()),
+        backups: None,
     };
 
     let app = Router::new()
@@ -343,14 +378,15 @@ async fn test_refresh_with_revoked_license_fails() {
             updates_exp: Some(future_timestamp(UPDATES_VALID_DAYS)),
             tier: product.tier.clone(),
             features: product.features.clone(),
+            entitlements: product.entitlements.clone(),
             device_id: device.device_id.clone(),
             device_type: "uuid".to_string(),
+            license_ref: None,
             product_id: product.id.clone(),
         };
 
-        let private_key = master_key
-            .decrypt_private_key(&project.id, &project.private_key)
-            .unwrap();
+        let private_key =
+            queries::decrypt_project_private_key(&conn, &project, &master_key).unwrap();
         token = jwt::sign_claims(
             &claims,
             &private_key,
@@ -361,7 +397,7 @@ async fn test_refresh_with_revoked_license_fails() {
         .unwrap();
 
         // Revoke the license
-        queries::revoke_license(&mut conn, &license.id).unwrap();
+        queries::revoke_license(&mut conn, &license.id, &RevokeLicense::default(), None).unwrap();
     }
 
     let audit_manager = SqliteConnectionManager::memory();
@@ -376,8 +412,11 @@ async fn test_refresh_with_revoked_license_fails() {
         audit: audit_pool,
         base_url: "http://localhost:3000".to_string(),
         audit_log_enabled: false,
+        audit_failure_mode: paycheck::models::AuditFailureMode::default(),
+        public_audit_retention_days: 0,
         master_key,
         email_hasher: paycheck::crypto::EmailHasher::from_bytes([0xAA; 32]),
+        pii_minimization: false,
         success_page_url: "http://localhost:3000/success".to_string(),
         activation_rate_limiter: std::sync::Arc::new(
             paycheck::rate_limit::ActivationRateLimiter::default(),
@@ -388,6 +427,20 @@ async fn test_refresh_with_revoked_license_fails() {
         )),
         jwks_cache: std::sync::Arc::new(paycheck::jwt::JwksCache::new()),
         trusted_issuers: vec![],
+        org_dbs: std::sync::Arc::new(paycheck::db::OrgDbRegistry::disabled()),
+        project_misses: std::sync::Arc::new(paycheck::db::ProjectMissCache::default()),
+        clock: paycheck::util::Clock::system(),
+        provider_calls: std::sync::Arc::new(paycheck::payments::ProviderCallGovernor::default()),
+        validation_limiter: std::sync::Arc::new(paycheck::rate_limit::ValidationRateLimiter::new()),
+        free_license_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_free_licenses(),
+        ),
+        session_wait_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_session_waits(),
+        ),
+        allow_localhost_urls: false,
+        maintenance: std::sync::Arc::new(paycheck::middleware::MaintenanceMode::default()),
+        backups: None,
     };
 
     let app = Router::new()
@@ -443,14 +496,15 @@ async fn test_refresh_with_revoked_jti_fails() {
             updates_exp: Some(future_timestamp(UPDATES_VALID_DAYS)),
             tier: product.tier.clone(),
             features: product.features.clone(),
+            entitlements: product.entitlements.clone(),
             device_id: device.device_id.clone(),
             device_type: "uuid".to_string(),
+            license_ref: None,
             product_id: product.id.clone(),
         };
 
-        let private_key = master_key
-            .decrypt_private_key(&project.id, &project.private_key)
-            .unwrap();
+        let private_key =
+            queries::decrypt_project_private_key(&conn, &project, &master_key).unwrap();
         token = jwt::sign_claims(
             &claims,
             &private_key,
@@ -476,8 +530,11 @@ async fn test_refresh_with_revoked_jti_fails() {
         audit: audit_pool,
         base_url: "http://localhost:3000".to_string(),
         audit_log_enabled: false,
+        audit_failure_mode: paycheck::models::AuditFailureMode::default(),
+        public_audit_retention_days: 0,
         master_key,
         email_hasher: paycheck::crypto::EmailHasher::from_bytes([0xAA; 32]),
+        pii_minimization: false,
         success_page_url: "http://localhost:3000/success".to_string(),
         activation_rate_limiter: std::sync::Arc::new(
             paycheck::rate_limit::ActivationRateLimiter::default(),
@@ -488,6 +545,20 @@ async fn test_refresh_with_revoked_jti_fails() {
         )),
         jwks_cache: std::sync::Arc::new(paycheck::jwt::JwksCache::new()),
         trusted_issuers: vec![],
+        org_dbs: std::sync::Arc::new(paycheck::db::OrgDbRegistry::disabled()),
+        project_misses: std::sync::Arc::new(paycheck::db::ProjectMissCache::default()),
+        clock: paycheck::util::Clock::system(),
+        provider_calls: std::sync::Arc::new(paycheck::payments::ProviderCallGovernor::default()),
+        validation_limiter: std::sync::Arc::new(paycheck::rate_limit::ValidationRateLimiter::new()),
+        free_license_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_free_licenses(),
+        ),
+        session_wait_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_session_waits(),
+        ),
+        allow_localhost_urls: false,
+        maintenance: std::sync::Arc::new(paycheck::middleware::MaintenanceMode::default()),
+        backups: None,
     };
 
     let app = Router::new()
@@ -547,14 +618,15 @@ async fn test_refresh_with_expired_jwt_succeeds() {
             updates_exp: Some(future_timestamp(UPDATES_VALID_DAYS)),
             tier: product.tier.clone(),
             features: product.features.clone(),
+            entitlements: product.entitlements.clone(),
             device_id: device.device_id.clone(),
             device_type: "uuid".to_string(),
+            license_ref: None,
             product_id: product.id.clone(),
         };
 
-        let private_key = master_key
-            .decrypt_private_key(&project.id, &project.private_key)
-            .unwrap();
+        let private_key =
+            queries::decrypt_project_private_key(&conn, &project, &master_key).unwrap();
 
         // Create a JWT that expired 1 hour ago
         token = sign_claims_with_exp_offset(
@@ -579,8 +651,11 @@ async fn test_refresh_with_expired_jwt_succeeds() {
         audit: audit_pool,
         base_url: "http://localhost:3000".to_string(),
         audit_log_enabled: false,
+        audit_failure_mode: paycheck::models::AuditFailureMode::default(),
+        public_audit_retention_days: 0,
         master_key,
         email_hasher: paycheck::crypto::EmailHasher::from_bytes([0xAA; 32]),
+        pii_minimization: false,
         success_page_url: "http://localhost:3000/success".to_string(),
         activation_rate_limiter: std::sync::Arc::new(
             paycheck::rate_limit::ActivationRateLimiter::default(),
@@ -591,6 +666,20 @@ async fn test_refresh_with_expired_jwt_succeeds() {
         )),
         jwks_cache: std::sync::Arc::new(paycheck::jwt::JwksCache::new()),
         trusted_issuers: vec![],
+        org_dbs: std::sync::Arc::new(paycheck::db::OrgDbRegistry::disabled()),
+        project_misses: std::sync::Arc::new(paycheck::db::ProjectMissCache::default()),
+        clock: paycheck::util::Clock::system(),
+        provider_calls: std::sync::Arc::new(paycheck::payments::ProviderCallGovernor::default()),
+        validation_limiter: std::sync::Arc::new(paycheck::rate_limit::ValidationRateLimiter::new()),
+        free_license_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_free_licenses(),
+        ),
+        session_wait_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_session_waits(),
+        ),
+        allow_localhost_urls: false,
+        maintenance: std::sync::Arc::new(paycheck::middleware::MaintenanceMode::default()),
+        backups: None,
     };
 
     let app = Router::new()
@@ -664,14 +753,15 @@ async fn test_refresh_with_expired_license_fails() {
             updates_exp: Some(future_timestamp(UPDATES_VALID_DAYS)),
             tier: product.tier.clone(),
             features: product.features.clone(),
+            entitlements: product.entitlements.clone(),
             device_id: device.device_id.clone(),
             device_type: "uuid".to_string(),
+            license_ref: None,
             product_id: product.id.clone(),
         };
 
-        let private_key = master_key
-            .decrypt_private_key(&project.id, &project.private_key)
-            .unwrap();
+        let private_key =
+            queries::decrypt_project_private_key(&conn, &project, &master_key).unwrap();
 
         // Create a valid (non-expired) JWT
         token = jwt::sign_claims(
@@ -696,8 +786,11 @@ async fn test_refresh_with_expired_license_fails() {
         audit: audit_pool,
         base_url: "http://localhost:3000".to_string(),
         audit_log_enabled: false,
+        audit_failure_mode: paycheck::models::AuditFailureMode::default(),
+        public_audit_retention_days: 0,
         master_key,
         email_hasher: paycheck::crypto::EmailHasher::from_bytes([0xAA; 32]),
+        pii_minimization: false,
         success_page_url: "http://localhost:3000/success".to_string(),
         activation_rate_limiter: std::sync::Arc::new(
             paycheck::rate_limit::ActivationRateLimiter::default(),
@@ -708,6 +801,20 @@ async fn test_refresh_with_expired_license_fails() {
         )),
         jwks_cache: std::sync::Arc::new(paycheck::jwt::JwksCache::new()),
         trusted_issuers: vec![],
+        org_dbs: std::sync::Arc::new(paycheck::db::OrgDbRegistry::disabled()),
+        project_misses: std::sync::Arc::new(paycheck::db::ProjectMissCache::default()),
+        clock: paycheck::util::Clock::system(),
+        provider_calls: std::sync::Arc::new(paycheck::payments::ProviderCallGovernor::default()),
+        validation_limiter: std::sync::Arc::new(paycheck::rate_limit::ValidationRateLimiter::new()),
+        free_license_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_free_licenses(),
+        ),
+        session_wait_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_session_waits(),
+        ),
+        allow_localhost_urls: false,
+        maintenance: std::sync::Arc::new(paycheck::middleware::MaintenanceMode::default()),
+        backups: None,
     };
 
     let app = Router::new()
@@ -764,6 +871,14 @@ async fn test_refresh_with_expired_license_exp_fails() {
             device_limit: Some(3),
         device_inactive_days: None,
             features: vec![],
+            entitlements: Default::default(),
+            seat_count: None,
+            available_from: None,
+            available_until: None,
+            checkout_fields: vec![],
+            extends_updates_for_product_id: None,
+            renewal_fallback: Default::default(),
+            revoke_on_partial_refund: false,
         };
         let product = queries::create_product(&mut conn, &project.id, &input).unwrap();
 
@@ -799,14 +914,15 @@ async fn test_refresh_with_expired_license_exp_fails() {
             updates_exp: Some(future_timestamp(ONE_YEAR - 2 * ONE_DAY)),
             tier: product.tier.clone(),
             features: product.features.clone(),
+            entitlements: product.entitlements.clone(),
             device_id: device.device_id.clone(),
             device_type: "uuid".to_string(),
+            license_ref: None,
             product_id: product.id.clone(),
         };
 
-        let private_key = master_key
-            .decrypt_private_key(&project.id, &project.private_key)
-            .unwrap();
+        let private_key =
+            queries::decrypt_project_private_key(&conn, &project, &master_key).unwrap();
 
         // Create a valid JWT
         token = jwt::sign_claims(
@@ -831,8 +947,11 @@ async fn test_refresh_with_expired_license_exp_fails() {
         audit: audit_pool,
         base_url: "http://localhost:3000".to_string(),
         audit_log_enabled: false,
+        audit_failure_mode: paycheck::models::AuditFailureMode::default(),
+        public_audit_retention_days: 0,
         master_key,
         email_hasher: paycheck::crypto::EmailHasher::from_bytes([0xAA; 32]),
+        pii_minimization: false,
         success_page_url: "http://localhost:3000/success".to_string(),
         activation_rate_limiter: std::sync::Arc::new(
             paycheck::rate_limit::ActivationRateLimiter::default(),
@@ -843,6 +962,20 @@ async fn test_refresh_with_expired_license_exp_fails() {
         )),
         jwks_cache: std::sync::Arc::new(paycheck::jwt::JwksCache::new()),
         trusted_issuers: vec![],
+        org_dbs: std::sync::Arc::new(paycheck::db::OrgDbRegistry::disabled()),
+        project_misses: std::sync::Arc::new(paycheck::db::ProjectMissCache::default()),
+        clock: paycheck::util::Clock::system(),
+        provider_calls: std::sync::Arc::new(paycheck::payments::ProviderCallGovernor::default()),
+        validation_limiter: std::sync::Arc::new(paycheck::rate_limit::ValidationRateLimiter::new()),
+        free_license_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_free_licenses(),
+        ),
+        session_wait_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_session_waits(),
+        ),
+        allow_localhost_urls: false,
+        maintenance: std::sync::Arc::new(paycheck::middleware::MaintenanceMode::default()),
+        backups: None,
     };
 
     let app = Router::new()
@@ -0,0 +1,267 @@
+//! Tests for the app versions recorded per license: /validate and /refresh
+//! keep the first and last `X-Paycheck-Client-Version` a valid license
+//! reported, at most hourly, and the usage endpoint counts licenses by
+//! last seen version.
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    response::Response,
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::config::RateLimitConfig;
+use paycheck::handlers;
+use paycheck::jwt::{self, DeviceInfo};
+
+/// 2025-06-15 15:06:40 UTC
+const NOW: i64 = 1_750_000_000;
+const HOUR: i64 = 3600;
+
+struct VersionsFixture {
+    state: AppState,
+    org_id: String,
+    project: Project,
+    product: Product,
+    api_key: String,
+    license: License,
+    jti: String,
+    token: String,
+}
+
+fn setup() -> VersionsFixture {
+    let state = create_test_app_state();
+    let master_key = test_master_key();
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, api_key) =
+        create_test_org_member(&mut conn, &org.id, "admin@test.com", OrgMemberRole::Owner);
+    let project = create_test_project(&conn, &org.id, "Test Project", &master_key);
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+    let license = create_test_license(
+        &conn,
+        &project.id,
+        &product.id,
+        Some(future_timestamp(ONE_YEAR)),
+    );
+    let device = create_test_device(&conn, &license.id, "laptop", DeviceType::Uuid);
+
+    let claims = jwt::build_license_claims(
+        &license,
+        &product,
+        &project,
+        &DeviceInfo {
+            device_id: &device.device_id,
+            device_type: device.device_type,
+            activated_at: device.activated_at,
+        },
+    );
+    let private_key = queries::decrypt_project_private_key(&conn, &project, &master_key).unwrap();
+    let token = jwt::sign_license_token(&claims, &private_key, &device.jti).unwrap();
+
+    drop(conn);
+    VersionsFixture {
+        state,
+        org_id: org.id,
+        project,
+        product,
+        api_key,
+        license,
+        jti: device.jti,
+        token,
+    }
+}
+
+impl VersionsFixture {
+    /// The public API with the clock pinned at `now`.
+    fn public_app_at(&self, now: i64) -> axum::Router {
+        let mut state = self.state.clone();
+        state.clock = Clock::fixed(now);
+        public_app(state)
+    }
+
+    /// POST /validate at `now`, reporting `client_version` in the header.
+    async fn validate(&self, now: i64, client_version: &str) -> Value {
+        let body = json!({ "public_key": self.project.public_key, "jti": self.jti });
+        let response = self
+            .public_app_at(now)
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/validate")
+                    .header("content-type", "application/json")
+                    .header("X-Paycheck-Client-Version", client_version)
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        body_json(response).await
+    }
+
+    /// POST /refresh at `now`, reporting `client_version` in the header.
+    async fn refresh(&self, now: i64, client_version: &str) {
+        let response = self
+            .public_app_at(now)
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/refresh")
+                    .header("Authorization", format!("Bearer {}", self.token))
+                    .header("X-Paycheck-Client-Version", client_version)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    async fn org_get(&self, path: &str) -> (StatusCode, Value) {
+        let app = handlers::orgs::router(self.state.clone(), RateLimitConfig::disabled())
+            .with_state(self.state.clone());
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri(format!(
+                        "/orgs/{}/projects/{}{}",
+                        self.org_id, self.project.id, path
+                    ))
+                    .header("Authorization", format!("Bearer {}", self.api_key))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        (status, body_json(response).await)
+    }
+
+    fn stored(&self) -> License {
+        let conn = self.state.db.get().unwrap();
+        queries::get_license_by_id(&conn, &self.license.id)
+            .unwrap()
+            .unwrap()
+    }
+}
+
+async fn body_json(response: Response) -> Value {
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    serde_json::from_slice(&bytes).unwrap_or(Value::Null)
+}
+
+#[tokio::test]
+async fn test_validate_records_first_and_last_version() {
+    let f = setup();
+    assert_eq!(f.validate(NOW, "2.0.0").await["valid"], true);
+    let license = f.stored();
+    assert_eq!(license.first_seen_version.as_deref(), Some("2.0.0"));
+    assert_eq!(license.last_seen_version.as_deref(), Some("2.0.0"));
+    assert_eq!(license.last_seen_version_at, Some(NOW));
+
+    f.validate(NOW + HOUR, "2.1.0-beta.1").await;
+    let license = f.stored();
+    assert_eq!(license.first_seen_version.as_deref(), Some("2.0.0"));
+    assert_eq!(license.last_seen_version.as_deref(), Some("2.1.0-beta.1"));
+    assert_eq!(license.last_seen_version_at, Some(NOW + HOUR));
+}
+
+#[tokio::test]
+async fn test_last_version_written_at_most_hourly() {
+    let f = setup();
+    f.validate(NOW, "2.0.0").await;
+    f.validate(NOW + HOUR - 1, "2.1.0").await;
+    f.refresh(NOW + HOUR - 1, "2.1.0").await;
+    let license = f.stored();
+    assert_eq!(license.last_seen_version.as_deref(), Some("2.0.0"));
+    assert_eq!(license.last_seen_version_at, Some(NOW));
+
+    // The query holds to the window too, for calls that raced the check
+    let conn = f.state.db.get().unwrap();
+    assert!(
+        !queries::record_license_seen_version(&conn, &f.license.id, "2.1.0", NOW + 60).unwrap()
+    );
+    drop(conn);
+
+    f.refresh(NOW + HOUR, "2.1.0").await;
+    let license = f.stored();
+    assert_eq!(license.last_seen_version.as_deref(), Some("2.1.0"));
+    assert_eq!(license.last_seen_version_at, Some(NOW + HOUR));
+}
+
+#[tokio::test]
+async fn test_garbage_versions_are_ignored() {
+    let f = setup();
+    let too_long = "1".repeat(51);
+    for garbage in [
+        "",
+        "latest",
+        "2.0.0; DROP TABLE licenses",
+        "<script>",
+        "1.2.3.4.5",
+        too_long.as_str(),
+    ] {
+        assert_eq!(f.validate(NOW, garbage).await["valid"], true);
+    }
+    let license = f.stored();
+    assert_eq!(license.first_seen_version, None);
+    assert_eq!(license.last_seen_version_at, None);
+
+    // A valid version is trimmed, and still recorded after the garbage
+    f.validate(NOW, " v3.0 ").await;
+    assert_eq!(f.stored().first_seen_version.as_deref(), Some("v3.0"));
+}
+
+#[tokio::test]
+async fn test_versions_shown_on_admin_license() {
+    let f = setup();
+    f.validate(NOW, "2.0.0").await;
+
+    let (status, detail) = f.org_get(&format!("/licenses/{}", f.license.id)).await;
+    assert_eq!(status, StatusCode::OK, "{}", detail);
+    assert_eq!(detail["first_seen_version"], "2.0.0");
+    assert_eq!(detail["last_seen_version"], "2.0.0");
+    assert_eq!(detail["last_seen_version_at"], NOW);
+
+    let (status, list) = f.org_get("/licenses").await;
+    assert_eq!(status, StatusCode::OK, "{}", list);
+    assert_eq!(list["items"][0]["last_seen_version"], "2.0.0");
+}
+
+#[tokio::test]
+async fn test_usage_versions_groups_licenses() {
+    let f = setup();
+    let conn = f.state.db.get().unwrap();
+    let mut licenses = Vec::new();
+    for version in [Some("2.0.0"), Some("2.1.0"), Some("2.1.0"), None] {
+        let license = create_test_license(&conn, &f.project.id, &f.product.id, None);
+        if let Some(version) = version {
+            queries::record_license_seen_version(&conn, &license.id, version, NOW).unwrap();
+        }
+        licenses.push(license);
+    }
+    // Deleted licenses aren't counted
+    queries::record_license_seen_version(&conn, &licenses[0].id, "1.0.0", NOW + HOUR).unwrap();
+    queries::soft_delete_license(&conn, &licenses[0].id).unwrap();
+    drop(conn);
+
+    let (status, usage) = f.org_get("/usage/versions").await;
+    assert_eq!(status, StatusCode::OK, "{}", usage);
+    assert_eq!(
+        usage["versions"],
+        json!([
+            { "version": null, "license_count": 2 },
+            { "version": "2.1.0", "license_count": 2 },
+        ])
+    );
+}
@@ -0,0 +1,527 @@
+//! Tests for per-project JWT issuer/audience overrides:
+//! issuance, the discovery document, and the grace window after a change.
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode},
+};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use common::{ONE_YEAR, UPDATES_VALID_DAYS};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::handlers::public::DISCOVERY_MAX_AGE_SECS;
+use paycheck::jwt::{self, LicenseClaims};
+
+const NEW_ISSUER: &str = "https://licenses.example.com";
+
+struct TokenFixture {
+    state: AppState,
+    project: Project,
+    /// Token signed with the project's current (pre-change) claims
+    token: String,
+}
+
+/// Create a project with a licensed device and a token issued with the
+/// project's current issuer/audience.
+fn setup_token_fixture() -> TokenFixture {
+    let state = create_test_app_state();
+    let master_key = test_master_key();
+    let conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "Test Project", &master_key);
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+    let license = create_test_license(
+        &conn,
+        &project.id,
+        &product.id,
+        Some(future_timestamp(ONE_YEAR)),
+    );
+    let device = create_test_device(&conn, &license.id, "test-device", DeviceType::Uuid);
+
+    let claims = LicenseClaims {
+        license_exp: Some(future_timestamp(ONE_YEAR)),
+        updates_exp: Some(future_timestamp(UPDATES_VALID_DAYS)),
+        tier: product.tier.clone(),
+        features: product.features.clone(),
+        entitlements: product.entitlements.clone(),
+        device_id: device.device_id.clone(),
+        device_type: "uuid".to_string(),
+        license_ref: None,
+        product_id: product.id.clone(),
+    };
+    let private_key = queries::decrypt_project_private_key(&conn, &project, &master_key).unwrap();
+    let token = jwt::sign_claims_with_issuer(
+        &claims,
+        &private_key,
+        &license.id,
+        project.token_issuer(),
+        project.token_audience(),
+        &device.jti,
+    )
+    .unwrap();
+
+    drop(conn);
+    TokenFixture {
+        state,
+        project,
+        token,
+    }
+}
+
+fn update_jwt_claims(state: &AppState, project_id: &str, update: Value) -> Project {
+    let input: UpdateProject = serde_json::from_value(update).unwrap();
+    input.validate().unwrap();
+    let conn = state.db.get().unwrap();
+    queries::update_project(&conn, project_id, &input, &state.master_key)
+        .unwrap()
+        .unwrap()
+}
+
+/// Decode the registered claims (iss, aud) of a token without verifying it.
+fn registered_claims(token: &str) -> Value {
+    let payload = token.split('.').nth(1).unwrap();
+    serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).unwrap()).unwrap()
+}
+
+async fn refresh(app: Router, token: &str) -> (StatusCode, Value) {
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/refresh")
+                .header("Authorization", format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+// ============================================================================
+// Issuance
+// ============================================================================
+
+#[tokio::test]
+async fn test_default_claims_unchanged() {
+    let fixture = setup_token_fixture();
+
+    let claims = registered_claims(&fixture.token);
+    assert_eq!(claims["iss"], "paycheck");
+    assert_eq!(claims["aud"], "Test Project");
+
+    let (status, json) = refresh(public_app(fixture.state), &fixture.token).await;
+    assert_eq!(status, StatusCode::OK);
+    let refreshed = registered_claims(json["token"].as_str().unwrap());
+    assert_eq!(refreshed["iss"], "paycheck");
+    assert_eq!(refreshed["aud"], "Test Project");
+}
+
+#[tokio::test]
+async fn test_refresh_issues_token_with_overrides() {
+    let fixture = setup_token_fixture();
+    update_jwt_claims(
+        &fixture.state,
+        &fixture.project.id,
+        json!({ "jwt_issuer": NEW_ISSUER, "jwt_audience": "my-desktop-app" }),
+    );
+
+    let (status, json) = refresh(public_app(fixture.state), &fixture.token).await;
+    assert_eq!(
+        status,
+        StatusCode::OK,
+        "old token should refresh during grace"
+    );
+
+    let refreshed = registered_claims(json["token"].as_str().unwrap());
+    assert_eq!(refreshed["iss"], NEW_ISSUER);
+    assert_eq!(refreshed["aud"], "my-desktop-app");
+}
+
+#[tokio::test]
+async fn test_redeem_issues_token_with_overrides() {
+    let state = create_test_app_state();
+    let master_key = test_master_key();
+    let (public_key, code) = {
+        let conn = state.db.get().unwrap();
+        let org = create_test_org(&conn, "Test Org");
+        let input = CreateProject {
+            name: "Override Project".to_string(),
+            license_key_prefix: "TEST".to_string(),
+            redirect_url: None,
+            email_from: None,
+            email_enabled: true,
+            email_webhook_url: None,
+            email_webhook_secret: None,
+            jwt_issuer: Some(NEW_ISSUER.to_string()),
+            jwt_audience: Some("urn:example:app".to_string()),
+            upgrade_auto_discount: false,
+            upgrade_old_license: UpgradeOldLicense::Revoke,
+            allow_project_id_auth: true,
+            allow_link_checkout: false,
+            max_validations_per_hour_per_license: None,
+            statement_descriptor_suffix: None,
+            receipt_email_enabled: false,
+            checkout_message: None,
+            attestation_audiences: vec![],
+            duplicate_purchase_check: true,
+            converted_trial_action: ConvertedTrialAction::Keep,
+            require_terms_acceptance: false,
+            checkout_metadata_keys: vec![],
+            templates: vec![],
+        };
+        input.validate().unwrap();
+        let (private_key, public_key) = jwt::generate_keypair();
+        let project = queries::create_project(
+            &conn,
+            &org.id,
+            &input,
+            &private_key,
+            &public_key,
+            &master_key,
+        )
+        .unwrap();
+        let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+        let license = create_test_license(
+            &conn,
+            &project.id,
+            &product.id,
+            Some(future_timestamp(ONE_YEAR)),
+        );
+        let code = queries::create_activation_code(&conn, &license.id, &project.license_key_prefix)
+            .unwrap();
+        (project.public_key, code.code)
+    };
+
+    let response = public_app(state)
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/redeem")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    json!({
+                        "public_key": public_key,
+                        "code": code,
+                        "device_id": "test-device",
+                        "device_type": "uuid"
+                    })
+                    .to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    let claims = registered_claims(json["token"].as_str().unwrap());
+    assert_eq!(claims["iss"], NEW_ISSUER);
+    assert_eq!(claims["aud"], "urn:example:app");
+}
+
+// ============================================================================
+// Grace window
+// ============================================================================
+
+#[tokio::test]
+async fn test_change_records_previous_claims() {
+    let fixture = setup_token_fixture();
+
+    let project = update_jwt_claims(
+        &fixture.state,
+        &fixture.project.id,
+        json!({ "jwt_issuer": NEW_ISSUER }),
+    );
+
+    assert_eq!(project.jwt_previous_issuer.as_deref(), Some("paycheck"));
+    assert_eq!(
+        project.jwt_previous_audience.as_deref(),
+        Some("Test Project")
+    );
+    let until = project.jwt_previous_until.expect("grace window recorded");
+    assert!(until > now() + 6 * 86400 && until <= now() + 7 * 86400);
+}
+
+#[tokio::test]
+async fn test_unchanged_claims_keep_existing_window() {
+    let fixture = setup_token_fixture();
+    let first = update_jwt_claims(
+        &fixture.state,
+        &fixture.project.id,
+        json!({ "jwt_issuer": NEW_ISSUER, "jwt_grace_days": 1 }),
+    );
+
+    // Re-sending the same value doesn't replace the previous issuer with itself
+    let second = update_jwt_claims(
+        &fixture.state,
+        &fixture.project.id,
+        json!({ "jwt_issuer": NEW_ISSUER, "jwt_grace_days": 30 }),
+    );
+    assert_eq!(second.jwt_previous_issuer.as_deref(), Some("paycheck"));
+    assert_eq!(second.jwt_previous_until, first.jwt_previous_until);
+}
+
+#[tokio::test]
+async fn test_old_issuer_rejected_after_grace_window() {
+    let fixture = setup_token_fixture();
+    update_jwt_claims(
+        &fixture.state,
+        &fixture.project.id,
+        json!({ "jwt_issuer": NEW_ISSUER }),
+    );
+    {
+        let conn = fixture.state.db.get().unwrap();
+        conn.execute(
+            "UPDATE projects SET jwt_previous_until = ?1 WHERE id = ?2",
+            rusqlite::params![now() - 1, &fixture.project.id],
+        )
+        .unwrap();
+    }
+
+    let (status, _) = refresh(public_app(fixture.state), &fixture.token).await;
+    assert_eq!(
+        status,
+        StatusCode::UNAUTHORIZED,
+        "old-issuer token should be rejected once the grace window has passed"
+    );
+}
+
+#[tokio::test]
+async fn test_zero_grace_rejects_old_issuer_immediately() {
+    let fixture = setup_token_fixture();
+    let project = update_jwt_claims(
+        &fixture.state,
+        &fixture.project.id,
+        json!({ "jwt_issuer": NEW_ISSUER, "jwt_grace_days": 0 }),
+    );
+    assert!(project.jwt_previous_issuer.is_none());
+    assert!(project.jwt_previous_until.is_none());
+
+    let (status, _) = refresh(public_app(fixture.state), &fixture.token).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_audience_override_is_verified() {
+    let fixture = setup_token_fixture();
+    update_jwt_claims(
+        &fixture.state,
+        &fixture.project.id,
+        json!({ "jwt_audience": "my-desktop-app", "jwt_grace_days": 0 }),
+    );
+
+    // Token carries the old default audience (project name) and no grace applies
+    let (status, _) = refresh(public_app(fixture.state), &fixture.token).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_license_info_accepts_old_issuer_during_grace() {
+    let fixture = setup_token_fixture();
+    update_jwt_claims(
+        &fixture.state,
+        &fixture.project.id,
+        json!({ "jwt_issuer": NEW_ISSUER }),
+    );
+
+    let response = public_app(fixture.state)
+        .oneshot(
+            Request::builder()
+                .method("GET")
+                .uri(format!(
+                    "/license?public_key={}",
+                    urlencoding::encode(&fixture.project.public_key)
+                ))
+                .header("Authorization", format!("Bearer {}", fixture.token))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+// ============================================================================
+// Validation
+// ============================================================================
+
+#[test]
+fn test_invalid_overrides_rejected() {
+    for (field, value) in [
+        ("jwt_issuer", ""),
+        ("jwt_issuer", "has space"),
+        ("jwt_issuer", "not a:uri"),
+        ("jwt_audience", "://missing-scheme"),
+    ] {
+        let input: UpdateProject = serde_json::from_value(json!({ field: value })).unwrap();
+        assert!(
+            input.validate().is_err(),
+            "{}={:?} should be rejected",
+            field,
+            value
+        );
+    }
+
+    let input: UpdateProject = serde_json::from_value(json!({ "jwt_grace_days": -1 })).unwrap();
+    assert!(input.validate().is_err());
+
+    for value in [NEW_ISSUER, "urn:example:app", "my-desktop-app"] {
+        let input: UpdateProject = serde_json::from_value(json!({ "jwt_issuer": value })).unwrap();
+        assert!(input.validate().is_ok(), "{:?} should be accepted", value);
+    }
+}
+
+// ============================================================================
+// GET /discovery
+// ============================================================================
+
+async fn discovery(state: AppState, public_key: &str) -> Value {
+    let response = public_app(state)
+        .oneshot(
+            Request::builder()
+                .method("GET")
+                .uri(format!(
+                    "/discovery?public_key={}",
+                    urlencoding::encode(public_key)
+                ))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
+#[tokio::test]
+async fn test_discovery_publishes_claims_and_key() {
+    let fixture = setup_token_fixture();
+    let public_key = fixture.project.public_key.clone();
+
+    let json = discovery(fixture.state, &public_key).await;
+    assert_eq!(json["issuer"], "paycheck");
+    assert_eq!(json["audience"], "Test Project");
+    assert_eq!(json["audience_verified"], false);
+    assert!(json.get("previous_issuer").is_none());
+
+    let key = &json["jwks"]["keys"][0];
+    assert_eq!(key["kty"], "OKP");
+    assert_eq!(key["crv"], "Ed25519");
+    assert_eq!(key["alg"], "EdDSA");
+    let x = URL_SAFE_NO_PAD.decode(key["x"].as_str().unwrap()).unwrap();
+    let expected = base64::engine::general_purpose::STANDARD
+        .decode(&public_key)
+        .unwrap();
+    assert_eq!(x, expected, "JWK x should be the project's public key");
+}
+
+#[tokio::test]
+async fn test_discovery_includes_previous_claims_during_grace() {
+    let fixture = setup_token_fixture();
+    let public_key = fixture.project.public_key.clone();
+    update_jwt_claims(
+        &fixture.state,
+        &fixture.project.id,
+        json!({ "jwt_issuer": NEW_ISSUER, "jwt_audience": "my-desktop-app" }),
+    );
+
+    let json = discovery(fixture.state, &public_key).await;
+    assert_eq!(json["issuer"], NEW_ISSUER);
+    assert_eq!(json["audience"], "my-desktop-app");
+    assert_eq!(json["audience_verified"], true);
+    assert_eq!(json["previous_issuer"], "paycheck");
+    assert_eq!(json["previous_audience"], "Test Project");
+    assert!(json["previous_until"].is_i64());
+}
+
+async fn discovery_response(
+    state: AppState,
+    public_key: &str,
+    if_none_match: Option<&str>,
+) -> axum::response::Response {
+    let mut request = Request::builder().method("GET").uri(format!(
+        "/discovery?public_key={}",
+        urlencoding::encode(public_key)
+    ));
+    if let Some(etag) = if_none_match {
+        request = request.header("If-None-Match", etag);
+    }
+    public_app(state)
+        .oneshot(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap()
+}
+
+fn etag(response: &axum::response::Response) -> String {
+    response.headers()["etag"].to_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn test_discovery_sets_cache_headers() {
+    let fixture = setup_token_fixture();
+
+    let response = discovery_response(fixture.state, &fixture.project.public_key, None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()["cache-control"],
+        format!("public, max-age={}", DISCOVERY_MAX_AGE_SECS)
+    );
+    assert!(etag(&response).starts_with('"'));
+}
+
+#[tokio::test]
+async fn test_discovery_not_modified_for_matching_etag() {
+    let fixture = setup_token_fixture();
+    let public_key = fixture.project.public_key.clone();
+
+    let first = discovery_response(fixture.state.clone(), &public_key, None).await;
+    let tag = etag(&first);
+
+    let second = discovery_response(fixture.state, &public_key, Some(&tag)).await;
+    assert_eq!(second.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(etag(&second), tag);
+}
+
+#[tokio::test]
+async fn test_discovery_reflects_project_change_immediately() {
+    let fixture = setup_token_fixture();
+    let public_key = fixture.project.public_key.clone();
+
+    let before = discovery_response(fixture.state.clone(), &public_key, None).await;
+    let old_tag = etag(&before);
+
+    update_jwt_claims(
+        &fixture.state,
+        &fixture.project.id,
+        json!({ "jwt_issuer": NEW_ISSUER }),
+    );
+
+    // A client revalidating with the old ETag gets the new document, no TTL wait
+    let after = discovery_response(fixture.state, &public_key, Some(&old_tag)).await;
+    assert_eq!(after.status(), StatusCode::OK);
+    assert_ne!(etag(&after), old_tag);
+    let body = axum::body::to_bytes(after.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let json: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["issuer"], NEW_ISSUER);
+}
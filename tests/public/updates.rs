@@ -0,0 +1,304 @@
+//! Tests for update access: `updates_valid` / `updates_expires_at` on
+//! POST /validate and GET /updates/check.
+//!
+//! Both answer from the license row, which renewal webhooks keep current,
+//! not from the token's `updates_exp` claim.
+
+use axum::{body::Body, http::Request, http::StatusCode};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::{
+    DeviceType, ONE_DAY, ONE_MONTH, ONE_YEAR, RevokeLicense, create_test_app_state,
+    create_test_device, create_test_license, create_test_org, create_test_product,
+    create_test_project, future_timestamp, past_timestamp, public_app, queries, test_master_key,
+};
+
+use paycheck::jwt::{self, DeviceInfo};
+
+struct Setup {
+    app: axum::Router,
+    state: paycheck::db::AppState,
+    token: String,
+    jti: String,
+    public_key: String,
+    license_id: String,
+}
+
+/// A year-long license whose product grants 30 days of updates, so the
+/// token's `updates_exp` claim is a month after activation
+fn setup() -> Setup {
+    let state = create_test_app_state();
+    let master_key = test_master_key();
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&mut conn, "Test Org");
+    let project = create_test_project(&mut conn, &org.id, "Test Project", &master_key);
+    let product = create_test_product(&mut conn, &project.id, "Pro Plan", "pro");
+    conn.execute(
+        "UPDATE products SET updates_exp_days = ?1 WHERE id = ?2",
+        rusqlite::params![ONE_MONTH, product.id],
+    )
+    .unwrap();
+    let product = queries::get_product_by_id(&conn, &product.id)
+        .unwrap()
+        .unwrap();
+    let license = create_test_license(
+        &conn,
+        &project.id,
+        &product.id,
+        Some(future_timestamp(ONE_YEAR)),
+    );
+    queries::extend_license_expiration(
+        &conn,
+        &license.id,
+        license.expires_at,
+        Some(future_timestamp(ONE_MONTH)),
+    )
+    .unwrap();
+    let device = create_test_device(&mut conn, &license.id, "test-device", DeviceType::Uuid);
+
+    let claims = jwt::build_license_claims(
+        &license,
+        &product,
+        &project,
+        &DeviceInfo {
+            device_id: &device.device_id,
+            device_type: device.device_type,
+            activated_at: device.activated_at,
+        },
+    );
+    let private_key = queries::decrypt_project_private_key(&conn, &project, &master_key).unwrap();
+    let token = jwt::sign_license_token(&claims, &private_key, &device.jti).unwrap();
+
+    Setup {
+        app: public_app(state.clone()),
+        state: state.clone(),
+        token,
+        jti: device.jti,
+        public_key: project.public_key,
+        license_id: license.id,
+    }
+}
+
+/// Move the license's updates window the way a renewal webhook does
+fn set_updates_expires_at(setup: &Setup, updates_expires_at: Option<i64>) {
+    let conn = setup.state.db.get().unwrap();
+    queries::extend_license_expiration(
+        &conn,
+        &setup.license_id,
+        Some(future_timestamp(ONE_YEAR)),
+        updates_expires_at,
+    )
+    .unwrap();
+}
+
+async fn send(setup: &Setup, request: Request<Body>) -> (StatusCode, Value) {
+    let response = setup.app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+async fn validate(setup: &Setup) -> Value {
+    let (status, json) = send(
+        setup,
+        Request::builder()
+            .method("POST")
+            .uri("/validate")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                json!({ "public_key": setup.public_key, "jti": setup.jti }).to_string(),
+            ))
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    json
+}
+
+async fn check_updates(setup: &Setup, released_at: i64) -> (StatusCode, Value) {
+    send(
+        setup,
+        Request::builder()
+            .method("GET")
+            .uri(format!(
+                "/updates/check?released_at={}&public_key={}",
+                released_at,
+                urlencoding::encode(&setup.public_key)
+            ))
+            .header("Authorization", format!("Bearer {}", setup.token))
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await
+}
+
+fn token_updates_exp(token: &str) -> i64 {
+    let payload = token.split('.').nth(1).expect("JWT should have a payload");
+    let claims: Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).unwrap()).unwrap();
+    claims["updates_exp"].as_i64().unwrap()
+}
+
+// ============ /validate ============
+
+#[tokio::test]
+async fn test_validate_reports_updates_window_from_license() {
+    let setup = setup();
+    let renewed_until = future_timestamp(ONE_YEAR + ONE_MONTH);
+    set_updates_expires_at(&setup, Some(renewed_until));
+
+    let json = validate(&setup).await;
+
+    assert_eq!(json["valid"], true);
+    assert_eq!(json["updates_valid"], true);
+    assert_eq!(
+        json["updates_expires_at"], renewed_until,
+        "updates_expires_at should be the renewed date on the license"
+    );
+    assert!(
+        token_updates_exp(&setup.token) < renewed_until,
+        "the token's claim is still the pre-renewal date"
+    );
+}
+
+#[tokio::test]
+async fn test_validate_reports_closed_updates_window_on_valid_license() {
+    let setup = setup();
+    set_updates_expires_at(&setup, Some(past_timestamp(ONE_DAY)));
+
+    let json = validate(&setup).await;
+
+    assert_eq!(json["valid"], true, "the license itself is still valid");
+    assert_eq!(json["updates_valid"], false);
+}
+
+#[tokio::test]
+async fn test_validate_reports_perpetual_updates() {
+    let setup = setup();
+    set_updates_expires_at(&setup, None);
+
+    let json = validate(&setup).await;
+
+    assert_eq!(json["updates_valid"], true);
+    assert!(json["updates_expires_at"].is_null());
+}
+
+#[tokio::test]
+async fn test_invalid_validation_has_no_updates_window() {
+    let setup = setup();
+    {
+        let conn = setup.state.db.get().unwrap();
+        queries::revoke_license(&conn, &setup.license_id, &RevokeLicense::default(), None).unwrap();
+    }
+
+    let json = validate(&setup).await;
+
+    assert_eq!(json["valid"], false);
+    assert_eq!(json["updates_valid"], false);
+    assert!(json["updates_expires_at"].is_null());
+}
+
+// ============ /updates/check ============
+
+#[tokio::test]
+async fn test_release_within_updates_window_is_covered() {
+    let setup = setup();
+    let released_at = future_timestamp(ONE_DAY);
+
+    let (status, json) = check_updates(&setup, released_at).await;
+
+    assert_eq!(status, StatusCode::OK, "{}", json);
+    assert_eq!(json["covered"], true);
+    assert_eq!(json["released_at"], released_at);
+}
+
+#[tokio::test]
+async fn test_release_after_updates_window_is_not_covered() {
+    let setup = setup();
+
+    let (status, json) = check_updates(&setup, future_timestamp(ONE_MONTH + ONE_DAY)).await;
+
+    assert_eq!(status, StatusCode::OK, "{}", json);
+    assert_eq!(
+        json["covered"], false,
+        "a build released after updates expired should not be covered"
+    );
+}
+
+#[tokio::test]
+async fn test_renewal_covers_release_before_token_refresh() {
+    let setup = setup();
+    let released_at = future_timestamp(2 * ONE_MONTH);
+    assert!(
+        token_updates_exp(&setup.token) < released_at,
+        "by its stale claim, the token doesn't cover this release"
+    );
+    let renewed_until = future_timestamp(ONE_YEAR);
+    set_updates_expires_at(&setup, Some(renewed_until));
+
+    let (status, json) = check_updates(&setup, released_at).await;
+
+    assert_eq!(status, StatusCode::OK, "{}", json);
+    assert_eq!(
+        json["covered"], true,
+        "the renewed window on the license should cover the release"
+    );
+    assert_eq!(json["updates_expires_at"], renewed_until);
+}
+
+#[tokio::test]
+async fn test_perpetual_updates_cover_any_release() {
+    let setup = setup();
+    set_updates_expires_at(&setup, None);
+
+    let (status, json) = check_updates(&setup, future_timestamp(10 * ONE_YEAR)).await;
+
+    assert_eq!(status, StatusCode::OK, "{}", json);
+    assert_eq!(json["covered"], true);
+    assert!(json["updates_expires_at"].is_null());
+}
+
+#[tokio::test]
+async fn test_revoked_license_gets_no_updates_answer() {
+    let setup = setup();
+    {
+        let conn = setup.state.db.get().unwrap();
+        queries::revoke_license(&conn, &setup.license_id, &RevokeLicense::default(), None).unwrap();
+    }
+
+    let (status, json) = check_updates(&setup, future_timestamp(ONE_DAY)).await;
+
+    assert_eq!(status, StatusCode::FORBIDDEN, "{}", json);
+    assert!(json.get("covered").is_none());
+}
+
+#[tokio::test]
+async fn test_updates_check_requires_release_timestamp() {
+    let setup = setup();
+
+    let (status, _) = send(
+        &setup,
+        Request::builder()
+            .method("GET")
+            .uri(format!(
+                "/updates/check?released_at=1.2.3&public_key={}",
+                urlencoding::encode(&setup.public_key)
+            ))
+            .header("Authorization", format!("Bearer {}", setup.token))
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+
+    assert_eq!(
+        status,
+        StatusCode::BAD_REQUEST,
+        "released_at takes a Unix timestamp, not a version string"
+    );
+}
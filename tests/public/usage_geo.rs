@@ -0,0 +1,237 @@
+//! Tests for the usage geography rollup: valid `/validate` calls from projects
+//! that opted in are counted per day, product and country, and nothing finer
+//! than the country is stored. Lookups use the tiny database in
+//! `tests/fixtures` (see `generate_geoip_test_db.py`), so the tests that need
+//! it only run with the `geoip` feature.
+
+use std::sync::Arc;
+
+use axum::{
+    Extension, Router,
+    body::Body,
+    http::{Request, StatusCode},
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::geoip::GeoIp;
+
+/// 2025-06-15 15:06:40 UTC
+const NOW: i64 = 1_750_000_000;
+#[cfg(feature = "geoip")]
+const TODAY: &str = "2025-06-15";
+
+/// In the fixture database: 81.2.69.0/24 is GB (London), 89.160.20.0/24 is SE
+const LONDON_IP: &str = "81.2.69.160";
+#[cfg(feature = "geoip")]
+const SWEDEN_IP: &str = "89.160.20.112";
+
+struct GeoFixture {
+    state: AppState,
+    project: Project,
+    product: Product,
+    jti: String,
+    expired_jti: String,
+}
+
+fn setup(usage_geo_enabled: bool) -> GeoFixture {
+    let mut state = create_test_app_state();
+    state.clock = Clock::fixed(NOW);
+    let conn = state.db.get().unwrap();
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    conn.execute(
+        "UPDATE projects SET usage_geo_enabled = ?1 WHERE id = ?2",
+        rusqlite::params![usage_geo_enabled, project.id],
+    )
+    .unwrap();
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+    let license = create_test_license(&conn, &project.id, &product.id, None);
+    let device = create_test_device(&conn, &license.id, "device-1", DeviceType::Uuid);
+    let expired = create_test_license(&conn, &project.id, &product.id, Some(past_timestamp(1)));
+    let expired_device = create_test_device(&conn, &expired.id, "device-2", DeviceType::Uuid);
+    drop(conn);
+
+    GeoFixture {
+        state,
+        project,
+        product,
+        jti: device.jti,
+        expired_jti: expired_device.jti,
+    }
+}
+
+impl GeoFixture {
+    fn app(&self, geoip: GeoIp) -> Router {
+        public_app(self.state.clone()).layer(Extension(Arc::new(geoip)))
+    }
+
+    async fn validate(&self, app: &Router, jti: &str, ip: &str) -> Value {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/validate")
+                    .header("content-type", "application/json")
+                    .header("x-forwarded-for", format!("{}, 10.0.0.1", ip))
+                    .body(Body::from(
+                        json!({ "public_key": self.project.public_key, "jti": jti }).to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    fn rollup(&self) -> Vec<UsageGeoDay> {
+        let conn = self.state.db.get().unwrap();
+        queries::get_usage_geo_days(
+            &conn,
+            &self.project.id,
+            "2000-01-01",
+            "2100-01-01",
+            Some(&self.product.id),
+        )
+        .unwrap()
+    }
+}
+
+#[cfg(feature = "geoip")]
+fn fixture_geoip() -> GeoIp {
+    let geoip = GeoIp::open(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/geoip-test.mmdb"
+    ));
+    assert!(geoip.is_enabled());
+    geoip
+}
+
+/// Every text value stored in any table of `conn`.
+#[cfg(feature = "geoip")]
+fn stored_text(conn: &rusqlite::Connection) -> Vec<String> {
+    let tables: Vec<String> = conn
+        .prepare("SELECT name FROM sqlite_master WHERE type = 'table'")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let mut values = Vec::new();
+    for table in tables {
+        let mut stmt = conn
+            .prepare(&format!("SELECT * FROM \"{}\"", table))
+            .unwrap();
+        let columns = stmt.column_count();
+        let mut rows = stmt.query([]).unwrap();
+        while let Some(row) = rows.next().unwrap() {
+            for i in 0..columns {
+                if let rusqlite::types::ValueRef::Text(text) = row.get_ref(i).unwrap() {
+                    values.push(String::from_utf8_lossy(text).into_owned());
+                }
+            }
+        }
+    }
+    values
+}
+
+#[cfg(feature = "geoip")]
+#[tokio::test]
+async fn test_valid_checks_counted_by_country() {
+    let f = setup(true);
+    let app = f.app(fixture_geoip());
+
+    for ip in [LONDON_IP, LONDON_IP, SWEDEN_IP] {
+        assert_eq!(f.validate(&app, &f.jti, ip).await["valid"], true);
+    }
+    // Invalid checks and addresses the database doesn't know aren't counted
+    assert_eq!(
+        f.validate(&app, &f.expired_jti, LONDON_IP).await["valid"],
+        false
+    );
+    assert_eq!(f.validate(&app, &f.jti, "192.0.2.1").await["valid"], true);
+
+    let day = |country: &str, count| UsageGeoDay {
+        date: TODAY.into(),
+        product_id: f.product.id.clone(),
+        country: country.into(),
+        count,
+    };
+    assert_eq!(f.rollup(), [day("GB", 2), day("SE", 1)]);
+}
+
+#[cfg(feature = "geoip")]
+#[tokio::test]
+async fn test_only_the_country_is_stored() {
+    let mut f = setup(true);
+    f.state.audit_log_enabled = true;
+    let app = f.app(fixture_geoip());
+    f.validate(&app, &f.jti, LONDON_IP).await;
+    f.validate(&app, &f.jti, SWEDEN_IP).await;
+    paycheck::db::outbox::relay_all(&f.state).unwrap();
+
+    let mut stored = stored_text(&f.state.db.get().unwrap());
+    stored.extend(stored_text(&f.state.audit.get().unwrap()));
+    for fine in [
+        "81.2.69",
+        "89.160.20",
+        "London",
+        "Linköping",
+        "51.51",
+        "58.41",
+    ] {
+        assert!(
+            !stored.iter().any(|value| value.contains(fine)),
+            "{} was stored",
+            fine
+        );
+    }
+    // The scan does see the rollup
+    assert!(stored.iter().any(|value| value == "GB"));
+}
+
+#[cfg(feature = "geoip")]
+#[tokio::test]
+async fn test_projects_that_did_not_opt_in_are_not_counted() {
+    let f = setup(false);
+    let app = f.app(fixture_geoip());
+    assert_eq!(f.validate(&app, &f.jti, LONDON_IP).await["valid"], true);
+    assert!(f.rollup().is_empty());
+}
+
+#[tokio::test]
+async fn test_validation_works_without_a_database() {
+    let f = setup(true);
+    for geoip in [
+        GeoIp::disabled(),
+        GeoIp::open(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/missing.mmdb"
+        )),
+    ] {
+        assert!(!geoip.is_enabled());
+        let app = f.app(geoip);
+        assert_eq!(f.validate(&app, &f.jti, LONDON_IP).await["valid"], true);
+        assert_eq!(
+            f.validate(&app, &f.expired_jti, LONDON_IP).await["valid"],
+            false
+        );
+    }
+
+    // Nor does it need the lookup at all
+    assert_eq!(
+        f.validate(&public_app(f.state.clone()), &f.jti, LONDON_IP)
+            .await["valid"],
+        true
+    );
+    assert!(f.rollup().is_empty());
+}
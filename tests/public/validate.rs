@@ -97,6 +97,112 @@ async fn test_validate_with_valid_jti_returns_valid() {
     );
 }
 
+#[tokio::test]
+async fn test_validate_reports_current_product_entitlements() {
+    let state = create_test_app_state();
+    let master_key = test_master_key();
+    let conn = state.db.get().unwrap();
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "Test Project", &master_key);
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+    let license = create_test_license(&conn, &project.id, &product.id, None);
+    let device = create_test_device(&conn, &license.id, "test-device", DeviceType::Uuid);
+    drop(conn);
+
+    let validate = || async {
+        let response = public_app(state.clone())
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/validate")
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        json!({ "public_key": project.public_key, "jti": device.jti }).to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice::<Value>(&body).unwrap()
+    };
+
+    let json = validate().await;
+    assert_eq!(json["valid"], true);
+    assert_eq!(json["entitlements"], json!({}));
+
+    // Read live from the product, so changes show before the token is refreshed
+    queries::update_product(
+        &state.db.get().unwrap(),
+        &product.id,
+        &serde_json::from_value(json!({ "entitlements": { "seats": 10 } })).unwrap(),
+    )
+    .unwrap();
+    let json = validate().await;
+    assert_eq!(json["entitlements"], json!({ "seats": 10 }));
+}
+
+#[tokio::test]
+async fn test_validate_lists_revoked_jtis_when_asked() {
+    let state = create_test_app_state();
+    let master_key = test_master_key();
+    let conn = state.db.get().unwrap();
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "Test Project", &master_key);
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+    let license = create_test_license(&conn, &project.id, &product.id, None);
+    let laptop = create_test_device(&conn, &license.id, "laptop", DeviceType::Uuid);
+    let desktop = create_test_device(&conn, &license.id, "desktop", DeviceType::Uuid);
+    // The desktop was deactivated remotely
+    queries::add_revoked_jti(&conn, &license.id, &desktop.jti, Some("lost device")).unwrap();
+    drop(conn);
+
+    let validate = |jti: String, query: &'static str| {
+        let app = public_app(state.clone());
+        let body = json!({ "public_key": project.public_key, "jti": jti }).to_string();
+        async move {
+            let response = app
+                .oneshot(
+                    Request::builder()
+                        .method("POST")
+                        .uri(format!("/validate{}", query))
+                        .header("content-type", "application/json")
+                        .body(Body::from(body))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            serde_json::from_slice::<Value>(&body).unwrap()
+        }
+    };
+
+    let json = validate(laptop.jti.clone(), "?include_revocations=true").await;
+    assert_eq!(json["valid"], true);
+    let revoked = json["revoked_jtis"].as_array().unwrap();
+    assert_eq!(revoked.len(), 1);
+    assert_eq!(revoked[0]["jti"], desktop.jti.as_str());
+    assert!(revoked[0]["revoked_at"].as_i64().is_some());
+    assert!(
+        revoked[0].get("details").is_none(),
+        "admin notes stay out of public responses"
+    );
+
+    let json = validate(laptop.jti.clone(), "").await;
+    assert!(json.get("revoked_jtis").is_none(), "only sent when asked for");
+
+    let json = validate(desktop.jti.clone(), "?include_revocations=true").await;
+    assert_eq!(json["valid"], false);
+    assert!(
+        json.get("revoked_jtis").is_none(),
+        "a rejected token learns nothing about the license"
+    );
+}
+
 #[tokio::test]
 async fn test_validate_with_unknown_jti_returns_invalid() {
     let (app, _jti, public_key, _license_id, _device_id) = setup_validate_test();
@@ -166,7 +272,7 @@ async fn test_validate_with_revoked_license_returns_invalid() {
         public_key = project.public_key.clone();
 
         // Revoke the license
-        queries::revoke_license(&mut conn, &license.id).unwrap();
+        queries::revoke_license(&mut conn, &license.id, &RevokeLicense::default(), None).unwrap();
     }
 
     let app = public_app(state);
@@ -528,6 +634,14 @@ async fn test_validate_perpetual_license_returns_valid() {
             device_limit: Some(3),
         device_inactive_days: None,
             features: vec![],
+            entitlements: Default::default(),
+            seat_count: None,
+            available_from: None,
+            available_until: None,
+            checkout_fields: vec![],
+            extends_updates_for_product_id: None,
+            renewal_fallback: Default::default(),
+            revoke_on_partial_refund: false,
         };
         let product =
             queries::create_product(&mut conn, &project.id, &input).expect("Failed to create product");
@@ -0,0 +1,245 @@
+//! Tests for per-license validation throttling: the project's hourly limit,
+//! the 429 once it is exceeded, and flagged licenses surfacing in the org API.
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, StatusCode, header},
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+use common::*;
+
+use paycheck::handlers;
+
+const NOW: i64 = 1_750_000_000;
+
+struct ThrottleFixture {
+    state: AppState,
+    org_id: String,
+    project_id: String,
+    public_key: String,
+    api_key: String,
+    license_id: String,
+    jti: String,
+    /// A second license on the project that stays under the limit
+    quiet_jti: String,
+}
+
+fn setup(limit: Option<i64>) -> ThrottleFixture {
+    let mut state = create_test_app_state();
+    state.clock = Clock::fixed(NOW);
+    let mut conn = state.db.get().unwrap();
+
+    let org = create_test_org(&conn, "Test Org");
+    let (_, _, api_key) =
+        create_test_org_member(&mut conn, &org.id, "owner@test.com", OrgMemberRole::Owner);
+    let project = create_test_project(&conn, &org.id, "Test Project", &test_master_key());
+    conn.execute(
+        "UPDATE projects SET max_validations_per_hour_per_license = ?1 WHERE id = ?2",
+        rusqlite::params![limit, project.id],
+    )
+    .unwrap();
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+
+    let license = create_test_license(&conn, &project.id, &product.id, None);
+    let device = create_test_device(&conn, &license.id, "shared-device", DeviceType::Uuid);
+    let quiet = create_test_license(&conn, &project.id, &product.id, None);
+    let quiet_device = create_test_device(&conn, &quiet.id, "quiet-device", DeviceType::Uuid);
+
+    drop(conn);
+    ThrottleFixture {
+        state,
+        org_id: org.id,
+        project_id: project.id,
+        public_key: project.public_key,
+        api_key,
+        license_id: license.id,
+        jti: device.jti,
+        quiet_jti: quiet_device.jti,
+    }
+}
+
+impl ThrottleFixture {
+    fn set_time(&mut self, now: i64) {
+        self.state.clock = Clock::fixed(now);
+    }
+
+    /// POST /validate from `ip`, returning the status and Retry-After header.
+    async fn validate(&self, jti: &str, ip: &str) -> (StatusCode, Option<String>) {
+        let response = public_app(self.state.clone())
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/validate")
+                    .header("content-type", "application/json")
+                    .header("x-forwarded-for", ip)
+                    .body(Body::from(
+                        json!({ "public_key": self.public_key, "jti": jti }).to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let retry_after = response
+            .headers()
+            .get(header::RETRY_AFTER)
+            .map(|v| v.to_str().unwrap().to_string());
+        (response.status(), retry_after)
+    }
+
+    fn org_app(&self) -> Router {
+        handlers::orgs::router(
+            self.state.clone(),
+            paycheck::config::RateLimitConfig::disabled(),
+        )
+        .with_state(self.state.clone())
+    }
+
+    async fn org_request(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(format!(
+                "/orgs/{}/projects/{}{}",
+                self.org_id, self.project_id, path
+            ))
+            .header("content-type", "application/json")
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .body(body.map_or_else(Body::empty, |b| Body::from(b.to_string())))
+            .unwrap();
+        let response = self.org_app().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        )
+    }
+
+    async fn flagged_licenses(&self) -> Value {
+        let (status, json) = self
+            .org_request("GET", "/licenses?flagged=true", None)
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        json
+    }
+}
+
+#[tokio::test]
+async fn test_validation_throttled_past_hourly_limit() {
+    let mut f = setup(Some(3));
+
+    for _ in 0..3 {
+        assert_eq!(f.validate(&f.jti, "203.0.113.1").await.0, StatusCode::OK);
+    }
+    let (status, retry_after) = f.validate(&f.jti, "203.0.113.1").await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(retry_after.as_deref(), Some("3600"));
+
+    // The limit is per license, not per project
+    assert_eq!(
+        f.validate(&f.quiet_jti, "203.0.113.1").await.0,
+        StatusCode::OK
+    );
+
+    // Still throttled late in the window, cleared once it ends
+    f.set_time(NOW + 3599);
+    let (status, retry_after) = f.validate(&f.jti, "203.0.113.1").await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(retry_after.as_deref(), Some("1"));
+    f.set_time(NOW + 3600);
+    assert_eq!(f.validate(&f.jti, "203.0.113.1").await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_no_limit_by_default() {
+    let f = setup(None);
+    for _ in 0..50 {
+        assert_eq!(f.validate(&f.jti, "203.0.113.1").await.0, StatusCode::OK);
+    }
+    assert_eq!(f.flagged_licenses().await["total"], 0);
+}
+
+#[tokio::test]
+async fn test_throttled_license_flagged_with_distinct_ips() {
+    let mut f = setup(Some(5));
+    assert_eq!(f.flagged_licenses().await["total"], 0);
+
+    // A shared token: several rejections in the same window, from three
+    // clients behind a different proxy each time
+    for i in 0..12 {
+        f.validate(&f.jti, &format!("198.51.100.{}, 10.0.0.{}", i % 3, i))
+            .await;
+    }
+    f.validate(&f.quiet_jti, "203.0.113.1").await;
+
+    let flagged = f.flagged_licenses().await;
+    assert_eq!(flagged["total"], 1);
+    let license = &flagged["items"][0];
+    assert_eq!(license["id"], f.license_id.as_str());
+    // Flagged once per window, not per rejected call
+    assert_eq!(license["abuse_flags"], 1);
+    assert_eq!(license["abuse_flagged_at"], NOW);
+    // Counted at the first rejection, by client IP only
+    assert_eq!(license["abuse_distinct_ips"], 3);
+
+    // Throttled again in a later window
+    f.set_time(NOW + 7200);
+    for _ in 0..6 {
+        f.validate(&f.jti, "198.51.100.1").await;
+    }
+    let license = &f.flagged_licenses().await["items"][0];
+    assert_eq!(license["abuse_flags"], 2);
+    assert_eq!(license["abuse_flagged_at"], NOW + 7200);
+    assert_eq!(license["abuse_distinct_ips"], 1);
+}
+
+#[tokio::test]
+async fn test_limit_set_through_project_update() {
+    let f = setup(None);
+
+    let (status, _) = f
+        .org_request(
+            "PUT",
+            "",
+            Some(json!({ "max_validations_per_hour_per_license": 0 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, project) = f
+        .org_request(
+            "PUT",
+            "",
+            Some(json!({ "max_validations_per_hour_per_license": 1 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(project["max_validations_per_hour_per_license"], 1);
+    assert_eq!(f.validate(&f.jti, "203.0.113.1").await.0, StatusCode::OK);
+    assert_eq!(
+        f.validate(&f.jti, "203.0.113.1").await.0,
+        StatusCode::TOO_MANY_REQUESTS
+    );
+
+    // null removes the limit
+    let (_, project) = f
+        .org_request(
+            "PUT",
+            "",
+            Some(json!({ "max_validations_per_hour_per_license": null })),
+        )
+        .await;
+    assert!(project["max_validations_per_hour_per_license"].is_null());
+    assert_eq!(f.validate(&f.jti, "203.0.113.1").await.0, StatusCode::OK);
+}
@@ -50,3 +50,6 @@ mod jwks_rsa_parsing;
 
 #[path = "security/email_validation.rs"]
 mod email_validation;
+
+#[path = "security/external_urls.rs"]
+mod external_urls;
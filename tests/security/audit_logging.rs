@@ -53,8 +53,11 @@ fn org_app_with_audit() -> (Router, AppState) {
         audit: audit_pool,
         base_url: "http://localhost:3000".to_string(),
         audit_log_enabled: true, // ENABLED for these tests
+        audit_failure_mode: paycheck::models::AuditFailureMode::default(),
+        public_audit_retention_days: 0,
         master_key,
         email_hasher: paycheck::crypto::EmailHasher::from_bytes([0xAA; 32]),
+        pii_minimization: false,
         success_page_url: "http://localhost:3000/success".to_string(),
         activation_rate_limiter: std::sync::Arc::new(
             paycheck::rate_limit::ActivationRateLimiter::default(),
@@ -65,6 +68,20 @@ fn org_app_with_audit() -> (Router, AppState) {
         )),
         jwks_cache: std::sync::Arc::new(paycheck::jwt::JwksCache::new()),
         trusted_issuers: vec![],
+        org_dbs: std::sync::Arc::new(paycheck::db::OrgDbRegistry::disabled()),
+        project_misses: std::sync::Arc::new(paycheck::db::ProjectMissCache::default()),
+        clock: paycheck::util::Clock::system(),
+        provider_calls: std::sync::Arc::new(paycheck::payments::ProviderCallGovernor::default()),
+        validation_limiter: std::sync::Arc::new(paycheck::rate_limit::ValidationRateLimiter::new()),
+        free_license_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_free_licenses(),
+        ),
+        session_wait_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_session_waits(),
+        ),
+        allow_localhost_urls: false,
+        maintenance: std::sync::Arc::new(paycheck::middleware::MaintenanceMode::default()),
+        backups: None,
     };
 
     let app = handlers::orgs::router(state.clone(), RateLimitConfig::disabled())
@@ -74,8 +91,7 @@ fn org_app_with_audit() -> (Router, AppState) {
 }
 
 /// Creates an operator app with audit logging ENABLED.
-#[allow(dead_code)]
-fn operator_app_with_audit() -> (Router, AppState) {
+fn operator_app_with_audit(pii_minimization: bool) -> (Router, AppState) {
     let master_key = test_master_key();
 
     let manager = SqliteConnectionManager::memory();
@@ -97,8 +113,11 @@ fn operator_app_with_audit() -> (Router, AppState) {
         audit: audit_pool,
         base_url: "http://localhost:3000".to_string(),
         audit_log_enabled: true, // ENABLED for these tests
+        audit_failure_mode: paycheck::models::AuditFailureMode::default(),
+        public_audit_retention_days: 0,
         master_key,
         email_hasher: paycheck::crypto::EmailHasher::from_bytes([0xAA; 32]),
+        pii_minimization,
         success_page_url: "http://localhost:3000/success".to_string(),
         activation_rate_limiter: std::sync::Arc::new(
             paycheck::rate_limit::ActivationRateLimiter::default(),
@@ -109,6 +128,20 @@ fn operator_app_with_audit() -> (Router, AppState) {
         )),
         jwks_cache: std::sync::Arc::new(paycheck::jwt::JwksCache::new()),
         trusted_issuers: vec![],
+        org_dbs: std::sync::Arc::new(paycheck::db::OrgDbRegistry::disabled()),
+        project_misses: std::sync::Arc::new(paycheck::db::ProjectMissCache::default()),
+        clock: paycheck::util::Clock::system(),
+        provider_calls: std::sync::Arc::new(paycheck::payments::ProviderCallGovernor::default()),
+        validation_limiter: std::sync::Arc::new(paycheck::rate_limit::ValidationRateLimiter::new()),
+        free_license_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_free_licenses(),
+        ),
+        session_wait_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_session_waits(),
+        ),
+        allow_localhost_urls: false,
+        maintenance: std::sync::Arc::new(paycheck::middleware::MaintenanceMode::default()),
+        backups: None,
     };
 
     let app = handlers::operators::router(state.clone()).with_state(state.clone());
@@ -1070,6 +1103,7 @@ mod audit_immutability {
             &AuditLogNames::default(),
             None,
             None,
+            None,
         )
         .unwrap();
 
@@ -1143,6 +1177,7 @@ mod audit_immutability {
                 &AuditLogNames::default(),
                 None,
                 None,
+                None,
             )
             .unwrap();
         }
@@ -2124,6 +2159,7 @@ mod org_scoped_queries {
                 &AuditLogNames::default(),
                 None,
                 None,
+                None,
             )
             .unwrap();
 
@@ -2143,6 +2179,7 @@ mod org_scoped_queries {
                 &AuditLogNames::default(),
                 None,
                 None,
+                None,
             )
             .unwrap();
 
@@ -2223,6 +2260,7 @@ mod org_scoped_queries {
                 &AuditLogNames::default(),
                 None,
                 None,
+                None,
             )
             .unwrap();
 
@@ -2242,6 +2280,7 @@ mod org_scoped_queries {
                 &AuditLogNames::default(),
                 None,
                 None,
+                None,
             )
             .unwrap();
 
@@ -2402,3 +2441,139 @@ mod org_scoped_queries {
         }
     }
 }
+
+// ============================================================================
+// PII MINIMIZATION TESTS
+// ============================================================================
+
+mod pii_minimization {
+    use super::*;
+
+    /// Create a user through the operator API and return (app, state, operator, new user id).
+    async fn create_user_via_api(
+        pii_minimization: bool,
+    ) -> (Router, AppState, User, String, String) {
+        let (app, state) = operator_app_with_audit(pii_minimization);
+        let (operator, api_key) = {
+            let mut conn = state.db.get().unwrap();
+            create_test_operator(&mut conn, "ops@admin.com", OperatorRole::Admin)
+        };
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/operators/users")
+                    .header("content-type", "application/json")
+                    .header("Authorization", format!("Bearer {}", api_key))
+                    .body(Body::from(
+                        json!({ "email": "jane@example.com", "name": "Jane Doe" }).to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let json = body_json(response).await;
+        assert_eq!(
+            json["email"], "jane@example.com",
+            "API responses always carry plaintext emails"
+        );
+        let user_id = json["id"].as_str().unwrap().to_string();
+
+        (app, state, operator, user_id, api_key)
+    }
+
+    fn stored_create_user_log(state: &AppState) -> AuditLog {
+        let conn = state.audit.get().unwrap();
+        let query: AuditLogQuery =
+            serde_json::from_value(json!({ "action": "create_user" })).unwrap();
+        let (logs, _) = queries::query_audit_logs(&conn, &query).unwrap();
+        assert_eq!(logs.len(), 1);
+        logs.into_iter().next().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_minimized_audit_log_stores_ids_only() {
+        let (_app, state, operator, user_id, _) = create_user_via_api(true).await;
+
+        let stored_email: String = state
+            .db
+            .get()
+            .unwrap()
+            .query_row("SELECT email FROM users WHERE id = ?1", [&user_id], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert!(stored_email.starts_with("enc1:"));
+
+        let log = stored_create_user_log(&state);
+        assert_eq!(log.user_id.as_deref(), Some(operator.id.as_str()));
+        assert_eq!(log.resource_id, user_id);
+        assert_eq!(log.user_email, None, "actor email should not be stored");
+        assert_eq!(log.user_name, None, "actor name should not be stored");
+        assert_eq!(log.resource_email, None);
+        assert_eq!(log.resource_name, None);
+        let details = log.details.unwrap().to_string();
+        assert!(
+            !details.contains("jane@example.com") && !details.contains("Jane Doe"),
+            "details should not contain PII: {}",
+            details
+        );
+    }
+
+    #[tokio::test]
+    async fn test_minimized_audit_log_resolved_for_operators() {
+        let (app, _state, _operator, _user_id, api_key) = create_user_via_api(true).await;
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri("/operators/audit-logs?action=create_user")
+                    .header("Authorization", format!("Bearer {}", api_key))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let json = body_json(response).await;
+        let item = &json["items"][0];
+        assert_eq!(item["user_email"], "ops@admin.com");
+        assert_eq!(item["resource_email"], "jane@example.com");
+        assert_eq!(item["resource_name"], "Jane Doe");
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri("/operators/audit-logs/text?action=create_user")
+                    .header("Authorization", format!("Bearer {}", api_key))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let text = String::from_utf8_lossy(&body);
+        assert!(
+            text.contains("ops@admin.com") && text.contains("Jane Doe"),
+            "text log should show resolved names: {}",
+            text
+        );
+    }
+
+    #[tokio::test]
+    async fn test_plaintext_mode_audit_log_keeps_names() {
+        let (_app, state, _operator, _user_id, _) = create_user_via_api(false).await;
+
+        let log = stored_create_user_log(&state);
+        assert_eq!(log.user_email.as_deref(), Some("ops@admin.com"));
+        assert_eq!(log.resource_email.as_deref(), Some("jane@example.com"));
+        assert_eq!(log.resource_name.as_deref(), Some("Jane Doe"));
+        assert_eq!(log.details.unwrap()["email"], "jane@example.com");
+    }
+}
@@ -53,8 +53,11 @@ fn org_app() -> (Router, AppState) {
         audit: audit_pool,
         base_url: "http://localhost:3000".to_string(),
         audit_log_enabled: false,
+        audit_failure_mode: paycheck::models::AuditFailureMode::default(),
+        public_audit_retention_days: 0,
         master_key,
         email_hasher: paycheck::crypto::EmailHasher::from_bytes([0xAA; 32]),
+        pii_minimization: false,
         success_page_url: "http://localhost:3000/success".to_string(),
         activation_rate_limiter: std::sync::Arc::new(
             paycheck::rate_limit::ActivationRateLimiter::default(),
@@ -65,6 +68,20 @@ fn org_app() -> (Router, AppState) {
         )),
         jwks_cache: std::sync::Arc::new(paycheck::jwt::JwksCache::new()),
         trusted_issuers: vec![],
+        org_dbs: std::sync::Arc::new(paycheck::db::OrgDbRegistry::disabled()),
+        project_misses: std::sync::Arc::new(paycheck::db::ProjectMissCache::default()),
+        clock: paycheck::util::Clock::system(),
+        provider_calls: std::sync::Arc::new(paycheck::payments::ProviderCallGovernor::default()),
+        validation_limiter: std::sync::Arc::new(paycheck::rate_limit::ValidationRateLimiter::new()),
+        free_license_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_free_licenses(),
+        ),
+        session_wait_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_session_waits(),
+        ),
+        allow_localhost_urls: false,
+        maintenance: std::sync::Arc::new(paycheck::middleware::MaintenanceMode::default()),
+        backups: None,
     };
 
     let app = handlers::orgs::router(state.clone(), RateLimitConfig::disabled())
@@ -96,8 +113,11 @@ fn operator_app() -> (Router, AppState) {
         audit: audit_pool,
         base_url: "http://localhost:3000".to_string(),
         audit_log_enabled: false,
+        audit_failure_mode: paycheck::models::AuditFailureMode::default(),
+        public_audit_retention_days: 0,
         master_key,
         email_hasher: paycheck::crypto::EmailHasher::from_bytes([0xAA; 32]),
+        pii_minimization: false,
         success_page_url: "http://localhost:3000/success".to_string(),
         activation_rate_limiter: std::sync::Arc::new(
             paycheck::rate_limit::ActivationRateLimiter::default(),
@@ -108,6 +128,20 @@ fn operator_app() -> (Router, AppState) {
         )),
         jwks_cache: std::sync::Arc::new(paycheck::jwt::JwksCache::new()),
         trusted_issuers: vec![],
+        org_dbs: std::sync::Arc::new(paycheck::db::OrgDbRegistry::disabled()),
+        project_misses: std::sync::Arc::new(paycheck::db::ProjectMissCache::default()),
+        clock: paycheck::util::Clock::system(),
+        provider_calls: std::sync::Arc::new(paycheck::payments::ProviderCallGovernor::default()),
+        validation_limiter: std::sync::Arc::new(paycheck::rate_limit::ValidationRateLimiter::new()),
+        free_license_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_free_licenses(),
+        ),
+        session_wait_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_session_waits(),
+        ),
+        allow_localhost_urls: false,
+        maintenance: std::sync::Arc::new(paycheck::middleware::MaintenanceMode::default()),
+        backups: None,
     };
 
     let app = handlers::operators::router(state.clone()).with_state(state.clone());
@@ -682,8 +716,13 @@ mod restore_cascade {
             create_test_org_member(&mut conn, &org.id, "member@test.com", OrgMemberRole::Owner);
 
         // Also grant operator role to user
-        queries::grant_operator_role(&mut conn, &user.id, OperatorRole::View)
-            .expect("Failed to grant operator role");
+        queries::grant_operator_role(
+            &mut conn,
+            &user.id,
+            OperatorRole::View,
+            &test_email_column(),
+        )
+        .expect("Failed to grant operator role");
 
         // Soft delete the user (cascades to org_member, operator_role stays on user)
         queries::soft_delete_user(&mut conn, &user.id).expect("Soft delete failed");
@@ -708,7 +747,7 @@ mod restore_cascade {
         );
 
         // User's operator_role should still be set
-        let user = queries::get_user_by_id(&mut conn, &user.id)
+        let user = queries::get_user_by_id(&mut conn, &user.id, &test_email_column())
             .unwrap()
             .expect("User should exist");
         assert!(
@@ -1153,7 +1192,7 @@ mod purge_verification {
 
         // Verify completely gone
         assert!(
-            queries::get_deleted_user_by_id(&mut conn, &user.id)
+            queries::get_deleted_user_by_id(&mut conn, &user.id, &test_email_column())
                 .expect("Query failed")
                 .is_none(),
             "User should be permanently removed after purge"
@@ -1188,7 +1227,8 @@ mod list_query_filtering {
 
         // List should exclude deleted user (include_deleted = false)
         let (users, total) =
-            queries::list_users_paginated(&mut conn, 100, 0, false).expect("Query failed");
+            queries::list_users_paginated(&mut conn, 100, 0, false, &test_email_column())
+                .expect("Query failed");
         assert_eq!(
             total, 2,
             "User count should be 2, excluding soft-deleted user"
@@ -1368,7 +1408,8 @@ mod list_query_filtering {
 
         // List with include_deleted=true should include all users
         let (users, total) =
-            queries::list_users_paginated(&mut conn, 100, 0, true).expect("Query failed");
+            queries::list_users_paginated(&mut conn, 100, 0, true, &test_email_column())
+                .expect("Query failed");
         assert_eq!(
             total, 2,
             "Total user count with include_deleted=true should be 2"
@@ -1450,9 +1491,10 @@ mod cascade_depth_tracking {
         queries::soft_delete_user(&mut conn, &user.id).expect("Soft delete failed");
 
         // User at depth 0
-        let deleted_user = queries::get_deleted_user_by_id(&mut conn, &user.id)
-            .unwrap()
-            .unwrap();
+        let deleted_user =
+            queries::get_deleted_user_by_id(&mut conn, &user.id, &test_email_column())
+                .unwrap()
+                .unwrap();
         assert_eq!(
             deleted_user.deleted_cascade_depth,
             Some(0),
@@ -35,7 +35,7 @@ fn test_email_hash_requires_secret_key() {
     let attacker_guess = hex::encode(hasher.finalize());
 
     // Server's actual hash using HMAC
-    let actual_secure_hash = email_hasher.hash(email);
+    let actual_secure_hash = email_hasher.hash(&parse_email(email));
 
     // Attacker's guess does NOT match
     assert_ne!(
@@ -53,8 +53,8 @@ fn test_different_keys_produce_different_hashes() {
 
     let email = "test@example.com";
 
-    let hash1 = hasher1.hash(email);
-    let hash2 = hasher2.hash(email);
+    let hash1 = hasher1.hash(&parse_email(email));
+    let hash2 = hasher2.hash(&parse_email(email));
 
     assert_ne!(
         hash1, hash2,
@@ -92,7 +92,7 @@ fn test_rainbow_table_attack_fails() {
 
     // Server computes hash using the secure HMAC-based function
     let victim_email = "john@gmail.com";
-    let secure_hash = email_hasher.hash(victim_email);
+    let secure_hash = email_hasher.hash(&parse_email(victim_email));
 
     // Attacker's rainbow table CANNOT reverse the secure hash
     let recovered = attacker_rainbow_table.get(&secure_hash);
@@ -113,9 +113,9 @@ fn test_hash_is_deterministic() {
     let email_hasher = test_email_hasher();
     let email = "test@example.com";
 
-    let hash1 = email_hasher.hash(email);
-    let hash2 = email_hasher.hash(email);
-    let hash3 = email_hasher.hash(email);
+    let hash1 = email_hasher.hash(&parse_email(email));
+    let hash2 = email_hasher.hash(&parse_email(email));
+    let hash3 = email_hasher.hash(&parse_email(email));
 
     assert_eq!(hash1, hash2, "Hash must be deterministic");
     assert_eq!(hash2, hash3, "Hash must be deterministic");
@@ -126,8 +126,8 @@ fn test_hash_is_deterministic() {
 fn test_hash_case_insensitive() {
     let email_hasher = test_email_hasher();
 
-    let hash1 = email_hasher.hash("Test@Example.COM");
-    let hash2 = email_hasher.hash("test@example.com");
+    let hash1 = email_hasher.hash(&parse_email("Test@Example.COM"));
+    let hash2 = email_hasher.hash(&parse_email("test@example.com"));
 
     assert_eq!(hash1, hash2, "Hash should be case-insensitive");
 }
@@ -142,8 +142,8 @@ fn test_hash_unicode_normalized() {
     // NFD form: e + combining acute accent (U+0065 U+0301)
     let email_nfd = "cafe\u{0301}@example.com";
 
-    let hash_nfc = email_hasher.hash(email_nfc);
-    let hash_nfd = email_hasher.hash(email_nfd);
+    let hash_nfc = email_hasher.hash(&parse_email(email_nfc));
+    let hash_nfd = email_hasher.hash(&parse_email(email_nfd));
 
     assert_eq!(
         hash_nfc, hash_nfd,
@@ -156,8 +156,8 @@ fn test_hash_unicode_normalized() {
 fn test_hash_trims_whitespace() {
     let email_hasher = test_email_hasher();
 
-    let hash1 = email_hasher.hash("  test@example.com  ");
-    let hash2 = email_hasher.hash("test@example.com");
+    let hash1 = email_hasher.hash(&parse_email("  test@example.com  "));
+    let hash2 = email_hasher.hash(&parse_email("test@example.com"));
 
     assert_eq!(hash1, hash2, "Hash should trim whitespace");
 }
@@ -181,7 +181,7 @@ fn test_secure_hash_database_lookup() {
 
     // Create license with secure email hash
     let email = "customer@example.com";
-    let secure_hash = state.email_hasher.hash(email);
+    let secure_hash = state.email_hasher.hash(&parse_email(email));
 
     let input = CreateLicense {
         email_hash: Some(secure_hash.clone()),
@@ -192,18 +192,19 @@ fn test_secure_hash_database_lookup() {
         payment_provider_customer_id: None,
         payment_provider_subscription_id: None,
         payment_provider_order_id: Some("order-123".to_string()),
+        seats: None,
     };
     let license = queries::create_license(&mut conn, &project.id, &product.id, &input).unwrap();
 
     // Verify we can look it up using the same email
-    let lookup_hash = state.email_hasher.hash(email);
+    let lookup_hash = state.email_hasher.hash(&parse_email(email));
     let found = queries::get_licenses_by_email_hash(&mut conn, &project.id, &lookup_hash).unwrap();
 
     assert_eq!(found.len(), 1, "Should find the license by email hash");
     assert_eq!(found[0].id, license.id);
 
     // Different email should not find it
-    let wrong_hash = state.email_hasher.hash("other@example.com");
+    let wrong_hash = state.email_hasher.hash(&parse_email("other@example.com"));
     let not_found = queries::get_licenses_by_email_hash(&mut conn, &project.id, &wrong_hash).unwrap();
 
     assert!(
@@ -43,7 +43,7 @@ mod email_hash_lookup {
             let product = create_test_product(&mut conn, &project.id, "Pro Plan", "pro");
 
             // Create license with known email hash
-            let email_hash = test_email_hasher().hash(email);
+            let email_hash = test_email_hasher().hash(&parse_email(email));
             let input = CreateLicense {
                 email_hash: Some(email_hash.clone()),
                 customer_id: Some("test-customer".to_string()),
@@ -53,6 +53,7 @@ mod email_hash_lookup {
                 payment_provider_customer_id: None,
                 payment_provider_subscription_id: None,
                 payment_provider_order_id: None,
+                seats: None,
             };
             let _license =
                 queries::create_license(&mut conn, &project.id, &product.id, &input).unwrap();
@@ -114,7 +115,7 @@ mod email_hash_lookup {
             let product = create_test_product(&mut conn, &project.id, "Pro Plan", "pro");
 
             // Create license with lowercase email hash
-            let email_hash = test_email_hasher().hash("test@example.com");
+            let email_hash = test_email_hasher().hash(&parse_email("test@example.com"));
             let input = CreateLicense {
                 email_hash: Some(email_hash.clone()),
                 customer_id: Some("test-customer".to_string()),
@@ -124,6 +125,7 @@ mod email_hash_lookup {
                 payment_provider_customer_id: None,
                 payment_provider_subscription_id: None,
                 payment_provider_order_id: None,
+                seats: None,
             };
             let _license =
                 queries::create_license(&mut conn, &project.id, &product.id, &input).unwrap();
@@ -191,7 +193,7 @@ mod email_hash_lookup {
             let product2 = create_test_product(&mut conn, &project.id, "Pro Plan", "pro");
             let product3 = create_test_product(&mut conn, &project.id, "Enterprise Plan", "enterprise");
 
-            let email_hash = test_email_hasher().hash(email);
+            let email_hash = test_email_hasher().hash(&parse_email(email));
 
             // Create 3 licenses with the same email hash
             for product in [&product1, &product2, &product3] {
@@ -204,6 +206,7 @@ mod email_hash_lookup {
                     payment_provider_customer_id: None,
                     payment_provider_subscription_id: None,
                     payment_provider_order_id: None,
+                    seats: None,
                 };
                 let _license =
                     queries::create_license(&mut conn, &project.id, &product.id, &input).unwrap();
@@ -260,7 +263,7 @@ mod email_hash_lookup {
             let project = create_test_project(&mut conn, &org.id, "Test Project", &master_key);
             let product = create_test_product(&mut conn, &project.id, "Pro Plan", "pro");
 
-            let email_hash = test_email_hasher().hash(email);
+            let email_hash = test_email_hasher().hash(&parse_email(email));
             let input = CreateLicense {
                 email_hash: Some(email_hash.clone()),
                 customer_id: Some("test-customer".to_string()),
@@ -270,6 +273,7 @@ mod email_hash_lookup {
                 payment_provider_customer_id: None,
                 payment_provider_subscription_id: None,
                 payment_provider_order_id: None,
+                seats: None,
             };
             let license = queries::create_license(&mut conn, &project.id, &product.id, &input).unwrap();
             license_id = license.id.clone();
@@ -301,7 +305,7 @@ mod email_hash_lookup {
         );
 
         // Verify the hash matches what we expect
-        let expected_hash = test_email_hasher().hash(email);
+        let expected_hash = test_email_hasher().hash(&parse_email(email));
         assert_eq!(
             stored_hash, expected_hash,
             "Stored hash should match SHA-256 hash of the email"
@@ -313,9 +317,9 @@ mod email_hash_lookup {
     async fn test_email_hash_deterministic() {
         let email = "deterministic@example.com";
 
-        let hash1 = test_email_hasher().hash(email);
-        let hash2 = test_email_hasher().hash(email);
-        let hash3 = test_email_hasher().hash(email);
+        let hash1 = test_email_hasher().hash(&parse_email(email));
+        let hash2 = test_email_hasher().hash(&parse_email(email));
+        let hash3 = test_email_hasher().hash(&parse_email(email));
 
         assert_eq!(
             hash1, hash2,
@@ -339,10 +343,10 @@ mod email_hash_lookup {
             " Test@Example.Com ",
         ];
 
-        let expected_hash = test_email_hasher().hash("test@example.com");
+        let expected_hash = test_email_hasher().hash(&parse_email("test@example.com"));
 
         for variant in variants {
-            let hash = test_email_hasher().hash(variant);
+            let hash = test_email_hasher().hash(&parse_email(variant));
             assert_eq!(
                 hash, expected_hash,
                 "Email '{}' should produce same hash as 'test@example.com'",
@@ -727,7 +731,7 @@ mod recovery_edge_cases {
             let project = create_test_project(&mut conn, &org.id, "Test Project", &master_key);
             let product = create_test_product(&mut conn, &project.id, "Pro Plan", "pro");
 
-            let email_hash = test_email_hasher().hash(email);
+            let email_hash = test_email_hasher().hash(&parse_email(email));
             let input = CreateLicense {
                 email_hash: Some(email_hash.clone()),
                 customer_id: Some("test-customer".to_string()),
@@ -737,11 +741,12 @@ mod recovery_edge_cases {
                 payment_provider_customer_id: None,
                 payment_provider_subscription_id: None,
                 payment_provider_order_id: None,
+                seats: None,
             };
             let license = queries::create_license(&mut conn, &project.id, &product.id, &input).unwrap();
 
             // Revoke the license
-            queries::revoke_license(&mut conn, &license.id).unwrap();
+            queries::revoke_license(&mut conn, &license.id, &RevokeLicense::default(), None).unwrap();
 
             public_key = project.public_key.clone();
         }
@@ -777,7 +782,7 @@ mod recovery_edge_cases {
         // No activation codes should have been created
         // (The query filters out revoked licenses)
         let mut conn = state.db.get().unwrap();
-        let email_hash = test_email_hasher().hash(email);
+        let email_hash = test_email_hasher().hash(&parse_email(email));
         let project = queries::get_project_by_public_key(&mut conn, &public_key)
             .unwrap()
             .unwrap();
@@ -804,7 +809,7 @@ mod recovery_edge_cases {
             let project = create_test_project(&mut conn, &org.id, "Test Project", &master_key);
             let product = create_test_product(&mut conn, &project.id, "Pro Plan", "pro");
 
-            let email_hash = test_email_hasher().hash(email);
+            let email_hash = test_email_hasher().hash(&parse_email(email));
             let input = CreateLicense {
                 email_hash: Some(email_hash.clone()),
                 customer_id: Some("test-customer".to_string()),
@@ -814,6 +819,7 @@ mod recovery_edge_cases {
                 payment_provider_customer_id: None,
                 payment_provider_subscription_id: None,
                 payment_provider_order_id: None,
+                seats: None,
             };
             let license = queries::create_license(&mut conn, &project.id, &product.id, &input).unwrap();
 
@@ -853,7 +859,7 @@ mod recovery_edge_cases {
 
         // No activation codes should have been created
         let mut conn = state.db.get().unwrap();
-        let email_hash = test_email_hasher().hash(email);
+        let email_hash = test_email_hasher().hash(&parse_email(email));
         let project = queries::get_project_by_public_key(&mut conn, &public_key)
             .unwrap()
             .unwrap();
@@ -880,7 +886,7 @@ mod recovery_edge_cases {
             let project = create_test_project(&mut conn, &org.id, "Test Project", &master_key);
             let product = create_test_product(&mut conn, &project.id, "Pro Plan", "pro");
 
-            let email_hash = test_email_hasher().hash(email);
+            let email_hash = test_email_hasher().hash(&parse_email(email));
             let input = CreateLicense {
                 email_hash: Some(email_hash.clone()),
                 customer_id: Some("test-customer".to_string()),
@@ -890,6 +896,7 @@ mod recovery_edge_cases {
                 payment_provider_customer_id: None,
                 payment_provider_subscription_id: None,
                 payment_provider_order_id: None,
+                seats: None,
             };
             let _license =
                 queries::create_license(&mut conn, &project.id, &product.id, &input).unwrap();
@@ -927,7 +934,7 @@ mod recovery_edge_cases {
 
         // No activation codes should have been created
         let mut conn = state.db.get().unwrap();
-        let email_hash = test_email_hasher().hash(email);
+        let email_hash = test_email_hasher().hash(&parse_email(email));
         let project = queries::get_project_by_public_key(&mut conn, &public_key)
             .unwrap()
             .unwrap();
@@ -1026,7 +1033,7 @@ mod recovery_edge_cases {
             let product = create_test_product(&mut conn, &project.id, "Pro Plan", "pro");
 
             // Create license with existing email
-            let email_hash = test_email_hasher().hash(existing_email);
+            let email_hash = test_email_hasher().hash(&parse_email(existing_email));
             let input = CreateLicense {
                 email_hash: Some(email_hash.clone()),
                 customer_id: Some("test-customer".to_string()),
@@ -1036,6 +1043,7 @@ mod recovery_edge_cases {
                 payment_provider_customer_id: None,
                 payment_provider_subscription_id: None,
                 payment_provider_order_id: None,
+                seats: None,
             };
             let _license =
                 queries::create_license(&mut conn, &project.id, &product.id, &input).unwrap();
@@ -1216,7 +1224,7 @@ mod recovery_edge_cases {
                     .unwrap();
 
             // Revoke the license
-            queries::revoke_license(&mut conn, &license.id).unwrap();
+            queries::revoke_license(&mut conn, &license.id, &RevokeLicense::default(), None).unwrap();
 
             public_key = project.public_key.clone();
             code = activation_code.code.clone();
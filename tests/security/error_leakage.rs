@@ -61,8 +61,11 @@ fn org_app() -> (Router, AppState) {
         audit: audit_pool,
         base_url: "http://localhost:3000".to_string(),
         audit_log_enabled: false,
+        audit_failure_mode: paycheck::models::AuditFailureMode::default(),
+        public_audit_retention_days: 0,
         master_key,
         email_hasher: paycheck::crypto::EmailHasher::from_bytes([0xAA; 32]),
+        pii_minimization: false,
         success_page_url: "http://localhost:3000/success".to_string(),
         activation_rate_limiter: std::sync::Arc::new(
             paycheck::rate_limit::ActivationRateLimiter::default(),
@@ -73,6 +76,20 @@ fn org_app() -> (Router, AppState) {
         )),
         jwks_cache: std::sync::Arc::new(paycheck::jwt::JwksCache::new()),
         trusted_issuers: vec![],
+        org_dbs: std::sync::Arc::new(paycheck::db::OrgDbRegistry::disabled()),
+        project_misses: std::sync::Arc::new(paycheck::db::ProjectMissCache::default()),
+        clock: paycheck::util::Clock::system(),
+        provider_calls: std::sync::Arc::new(paycheck::payments::ProviderCallGovernor::default()),
+        validation_limiter: std::sync::Arc::new(paycheck::rate_limit::ValidationRateLimiter::new()),
+        free_license_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_free_licenses(),
+        ),
+        session_wait_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_session_waits(),
+        ),
+        allow_localhost_urls: false,
+        maintenance: std::sync::Arc::new(paycheck::middleware::MaintenanceMode::default()),
+        backups: None,
     };
 
     let app = handlers::orgs::router(state.clone(), RateLimitConfig::disabled())
@@ -104,8 +121,11 @@ fn operator_app() -> (Router, AppState) {
         audit: audit_pool,
         base_url: "http://localhost:3000".to_string(),
         audit_log_enabled: false,
+        audit_failure_mode: paycheck::models::AuditFailureMode::default(),
+        public_audit_retention_days: 0,
         master_key,
         email_hasher: paycheck::crypto::EmailHasher::from_bytes([0xAA; 32]),
+        pii_minimization: false,
         success_page_url: "http://localhost:3000/success".to_string(),
         activation_rate_limiter: std::sync::Arc::new(
             paycheck::rate_limit::ActivationRateLimiter::default(),
@@ -116,6 +136,20 @@ fn operator_app() -> (Router, AppState) {
         )),
         jwks_cache: std::sync::Arc::new(paycheck::jwt::JwksCache::new()),
         trusted_issuers: vec![],
+        org_dbs: std::sync::Arc::new(paycheck::db::OrgDbRegistry::disabled()),
+        project_misses: std::sync::Arc::new(paycheck::db::ProjectMissCache::default()),
+        clock: paycheck::util::Clock::system(),
+        provider_calls: std::sync::Arc::new(paycheck::payments::ProviderCallGovernor::default()),
+        validation_limiter: std::sync::Arc::new(paycheck::rate_limit::ValidationRateLimiter::new()),
+        free_license_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_free_licenses(),
+        ),
+        session_wait_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_session_waits(),
+        ),
+        allow_localhost_urls: false,
+        maintenance: std::sync::Arc::new(paycheck::middleware::MaintenanceMode::default()),
+        backups: None,
     };
 
     let app = handlers::operators::router(state.clone()).with_state(state.clone());
@@ -838,8 +872,11 @@ mod consistent_error_format {
             audit: audit_pool,
             base_url: "http://localhost:3000".to_string(),
             audit_log_enabled: false,
+            audit_failure_mode: paycheck::models::AuditFailureMode::default(),
+            public_audit_retention_days: 0,
             master_key,
             email_hasher: paycheck::crypto::EmailHasher::from_bytes([0xAA; 32]),
+            pii_minimization: false,
             success_page_url: "http://localhost:3000/success".to_string(),
             activation_rate_limiter: std::sync::Arc::new(
                 paycheck::rate_limit::ActivationRateLimiter::default(),
@@ -850,6 +887,22 @@ mod consistent_error_format {
             )),
             jwks_cache: std::sync::Arc::new(paycheck::jwt::JwksCache::new()),
             trusted_issuers: vec![],
+            org_dbs: std::sync::Arc::new(paycheck::db::OrgDbRegistry::disabled()),
+            project_misses: std::sync::Arc::new(paycheck::db::ProjectMissCache::default()),
+            clock: paycheck::util::Clock::system(),
+            provider_calls: std::sync::Arc::new(paycheck::payments::ProviderCallGovernor::default()),
+            validation_limiter: std::sync::Arc::new(
+                paycheck::rate_limit::ValidationRateLimiter::new(),
+            ),
+            free_license_limiter: std::sync::Arc::new(
+                paycheck::rate_limit::ActivationRateLimiter::for_free_licenses(),
+            ),
+            session_wait_limiter: std::sync::Arc::new(
+                paycheck::rate_limit::ActivationRateLimiter::for_session_waits(),
+            ),
+            allow_localhost_urls: false,
+            maintenance: std::sync::Arc::new(paycheck::middleware::MaintenanceMode::default()),
+            backups: None,
         };
 
         // Create app with very low rate limits (1 RPM)
@@ -61,8 +61,11 @@ fn public_app() -> (Router, AppState) {
         audit: audit_pool,
         base_url: "http://localhost:3000".to_string(),
         audit_log_enabled: false,
+        audit_failure_mode: paycheck::models::AuditFailureMode::default(),
+        public_audit_retention_days: 0,
         master_key,
         email_hasher: paycheck::crypto::EmailHasher::from_bytes([0xAA; 32]),
+        pii_minimization: false,
         success_page_url: "http://localhost:3000/success".to_string(),
         activation_rate_limiter: std::sync::Arc::new(
             paycheck::rate_limit::ActivationRateLimiter::default(),
@@ -73,6 +76,20 @@ fn public_app() -> (Router, AppState) {
         )),
         jwks_cache: std::sync::Arc::new(paycheck::jwt::JwksCache::new()),
         trusted_issuers: vec![],
+        org_dbs: std::sync::Arc::new(paycheck::db::OrgDbRegistry::disabled()),
+        project_misses: std::sync::Arc::new(paycheck::db::ProjectMissCache::default()),
+        clock: paycheck::util::Clock::system(),
+        provider_calls: std::sync::Arc::new(paycheck::payments::ProviderCallGovernor::default()),
+        validation_limiter: std::sync::Arc::new(paycheck::rate_limit::ValidationRateLimiter::new()),
+        free_license_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_free_licenses(),
+        ),
+        session_wait_limiter: std::sync::Arc::new(
+            paycheck::rate_limit::ActivationRateLimiter::for_session_waits(),
+        ),
+        allow_localhost_urls: false,
+        maintenance: std::sync::Arc::new(paycheck::middleware::MaintenanceMode::default()),
+        backups: None,
     };
 
     // Build router without rate limiting (avoids panic on zero limits)
@@ -98,8 +115,10 @@ fn create_test_claims(
         updates_exp,
         tier: tier.to_string(),
         features: vec!["feature1".to_string()],
+        entitlements: Default::default(),
         device_id: device_id.to_string(),
         device_type: device_type.to_string(),
+        license_ref: None,
         product_id: product_id.to_string(),
     }
 }
@@ -132,10 +151,8 @@ fn setup_complete_license(state: &AppState) -> (String, Vec<u8>, String, String,
     .unwrap();
 
     // Get the private key to sign tokens
-    let private_key = state
-        .master_key
-        .decrypt_private_key(&project.id, &project.private_key)
-        .unwrap();
+    let private_key =
+        queries::decrypt_project_private_key(&conn, &project, &state.master_key).unwrap();
 
     // Create valid claims
     let claims = create_test_claims(
@@ -327,10 +344,8 @@ mod expiration_validation {
             Some(future_timestamp(ONE_YEAR)),
         );
 
-        let private_key = state
-            .master_key
-            .decrypt_private_key(&project.id, &project.private_key)
-            .unwrap();
+        let private_key =
+            queries::decrypt_project_private_key(&conn, &project, &state.master_key).unwrap();
 
         // Create token with a JTI that doesn't exist in the database
         let fake_jti = uuid::Uuid::new_v4().to_string();
@@ -1232,7 +1247,7 @@ mod license_revocation {
         // Revoke the license
         {
             let mut conn = state.db.get().unwrap();
-            queries::revoke_license(&mut conn, &license_id).unwrap();
+            queries::revoke_license(&mut conn, &license_id, &RevokeLicense::default(), None).unwrap();
         }
 
         // Now validate should fail
@@ -1267,7 +1282,7 @@ mod license_revocation {
         // Revoke the license
         {
             let mut conn = state.db.get().unwrap();
-            queries::revoke_license(&mut conn, &license_id).unwrap();
+            queries::revoke_license(&mut conn, &license_id, &RevokeLicense::default(), None).unwrap();
         }
 
         let response = app
@@ -1311,8 +1326,10 @@ mod claims_content {
                 "feat2".to_string(),
                 "feat3".to_string(),
             ],
+            entitlements: Default::default(),
             device_id: "my-device-uuid-123".to_string(),
             device_type: "machine".to_string(),
+            license_ref: None,
             product_id: "prod-abc-123".to_string(),
         };
 
@@ -1383,8 +1400,10 @@ mod claims_content {
             updates_exp: None,
             tier: "pro".to_string(),
             features: vec![],
+            entitlements: Default::default(),
             device_id: "".to_string(),
             device_type: "uuid".to_string(),
+            license_ref: None,
             product_id: "".to_string(),
         };
         assert!(
@@ -1397,8 +1416,10 @@ mod claims_content {
             updates_exp: None,
             tier: "pro".to_string(),
             features: vec![],
+            entitlements: Default::default(),
             device_id: "".to_string(),
             device_type: "uuid".to_string(),
+            license_ref: None,
             product_id: "".to_string(),
         };
         assert!(
@@ -1411,8 +1432,10 @@ mod claims_content {
             updates_exp: None,
             tier: "pro".to_string(),
             features: vec![],
+            entitlements: Default::default(),
             device_id: "".to_string(),
             device_type: "uuid".to_string(),
+            license_ref: None,
             product_id: "".to_string(),
         };
         assert!(
@@ -1426,8 +1449,10 @@ mod claims_content {
             updates_exp: Some(now),
             tier: "pro".to_string(),
             features: vec![],
+            entitlements: Default::default(),
             device_id: "".to_string(),
             device_type: "uuid".to_string(),
+            license_ref: None,
             product_id: "".to_string(),
         };
         assert!(
@@ -1445,8 +1470,10 @@ mod claims_content {
             updates_exp: None,
             tier: "pro".to_string(),
             features: vec!["export".to_string(), "api".to_string()],
+            entitlements: Default::default(),
             device_id: "".to_string(),
             device_type: "uuid".to_string(),
+            license_ref: None,
             product_id: "".to_string(),
         };
         assert!(
@@ -1476,8 +1503,10 @@ mod claims_content {
                 "feat:with:colons".to_string(),
                 "feat<with>brackets".to_string(),
             ],
+            entitlements: Default::default(),
             device_id: "device\"with'quotes".to_string(),
             device_type: "uuid".to_string(),
+            license_ref: None,
             product_id: "product@#$%^".to_string(),
         };
 
@@ -1517,8 +1546,10 @@ mod claims_content {
             updates_exp: None,
             tier: "tier".to_string(),
             features: vec!["feature".to_string(), "export".to_string()],
+            entitlements: Default::default(),
             device_id: "device-id".to_string(),
             device_type: "uuid".to_string(),
+            license_ref: None,
             product_id: "product-id".to_string(),
         };
 
@@ -0,0 +1,48 @@
+//! Webhook handler tests driven by recorded provider payloads.
+//!
+//! Each fixture in `webhooks/fixtures/` is a real-shaped event body for an event
+//! type Paycheck handles. Tests sign it with the org's test webhook secret, post
+//! it through the HTTP handler, and assert on the resulting license rows. The
+//! clock is pinned to the recording time so expirations are exact.
+
+#[path = "webhooks/helpers.rs"]
+mod helpers;
+
+#[path = "webhooks/stripe.rs"]
+mod stripe;
+
+#[path = "webhooks/lemonsqueezy.rs"]
+mod lemonsqueezy;
+
+#[path = "webhooks/upgrades.rs"]
+mod upgrades;
+
+#[path = "webhooks/sale_windows.rs"]
+mod sale_windows;
+
+#[path = "webhooks/refunds.rs"]
+mod refunds;
+
+#[path = "webhooks/disputes.rs"]
+mod disputes;
+
+#[path = "webhooks/checkout_fields.rs"]
+mod checkout_fields;
+
+#[path = "webhooks/org_limits.rs"]
+mod org_limits;
+
+#[path = "webhooks/update_renewals.rs"]
+mod update_renewals;
+
+#[path = "webhooks/trial_conversions.rs"]
+mod trial_conversions;
+
+#[path = "webhooks/webhook_mirror.rs"]
+mod webhook_mirror;
+
+#[path = "webhooks/payload_versions.rs"]
+mod payload_versions;
+
+#[path = "webhooks/email_webhook_signatures.rs"]
+mod email_webhook_signatures;
@@ -0,0 +1,192 @@
+//! Checkout field values, metadata, and the accepted terms version submitted
+//! to /buy end up on the license the webhook creates.
+
+use std::collections::BTreeMap;
+
+use super::helpers::*;
+
+/// Give the fixture's product checkout fields and start a session with
+/// `values`, as `/buy` would after validating them.
+fn session_with_fields(fixture: &WebhookFixture, values: &[(&str, &str)]) -> PaymentSession {
+    let conn = fixture.state.db.get().unwrap();
+    let fields = serde_json::json!([
+        { "key": "company", "label": "Company", "type": "text", "required": true },
+        { "key": "vat_id", "label": "VAT ID", "type": "text" }
+    ]);
+    conn.execute(
+        "UPDATE products SET checkout_fields = ?1 WHERE id = ?2",
+        rusqlite::params![fields.to_string(), fixture.product.id],
+    )
+    .unwrap();
+
+    queries::create_payment_session(
+        &conn,
+        &CreatePaymentSession {
+            product_id: fixture.product.id.clone(),
+            customer_id: None,
+            upgrade_from_license_id: None,
+            upgrade_days_remaining: None,
+            upgrade_credit_cents: None,
+            checkout_fields: values
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            accepted_terms_version: None,
+            metadata: BTreeMap::new(),
+        },
+    )
+    .unwrap()
+}
+
+async fn complete(fixture: &WebhookFixture, session: &PaymentSession) -> License {
+    let payload = fixture.checkout_payload("stripe_checkout_session_completed", session);
+    let (status, body) = fixture.post_stripe(payload).await;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "OK"));
+
+    let session = queries::get_payment_session(&fixture.state.db.get().unwrap(), &session.id)
+        .unwrap()
+        .unwrap();
+    fixture.license(&session.license_id.expect("session should link the license"))
+}
+
+#[tokio::test]
+async fn test_checkout_fields_copied_to_license() {
+    let fixture = WebhookFixture::stripe();
+    let session = session_with_fields(
+        &fixture,
+        &[("company", "Acme <b>Inc</b>"), ("vat_id", "GB123456789")],
+    );
+
+    let license = complete(&fixture, &session).await;
+
+    let expected = BTreeMap::from([
+        ("company".to_string(), "Acme <b>Inc</b>".to_string()),
+        ("vat_id".to_string(), "GB123456789".to_string()),
+    ]);
+    assert_eq!(license.checkout_fields, expected);
+
+    // Stored as entered; escaping is up to whatever renders it
+    let details = fixture.license_details(&license.id).await;
+    assert_eq!(details["checkout_fields"]["company"], "Acme <b>Inc</b>");
+    assert_eq!(details["checkout_fields"]["vat_id"], "GB123456789");
+}
+
+#[tokio::test]
+async fn test_checkout_without_fields_leaves_license_empty() {
+    let fixture = WebhookFixture::stripe();
+    let session = fixture.payment_session();
+
+    let license = complete(&fixture, &session).await;
+
+    assert!(license.checkout_fields.is_empty());
+    let stored: Option<String> = fixture
+        .state
+        .db
+        .get()
+        .unwrap()
+        .query_row(
+            "SELECT checkout_fields FROM licenses WHERE id = ?1",
+            [&license.id],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(stored, None);
+}
+
+#[tokio::test]
+async fn test_accepted_terms_version_copied_to_license() {
+    let fixture = WebhookFixture::stripe();
+    let session = queries::create_payment_session(
+        &fixture.state.db.get().unwrap(),
+        &CreatePaymentSession {
+            product_id: fixture.product.id.clone(),
+            customer_id: None,
+            upgrade_from_license_id: None,
+            upgrade_days_remaining: None,
+            upgrade_credit_cents: None,
+            checkout_fields: BTreeMap::new(),
+            accepted_terms_version: Some("2026-01".to_string()),
+            metadata: BTreeMap::new(),
+        },
+    )
+    .unwrap();
+
+    let license = complete(&fixture, &session).await;
+
+    assert_eq!(license.accepted_terms_version.as_deref(), Some("2026-01"));
+    let details = fixture.license_details(&license.id).await;
+    assert_eq!(details["accepted_terms_version"], "2026-01");
+}
+
+/// Allow `utm_source` and `campaign` metadata on the fixture's project.
+fn allow_metadata(fixture: &WebhookFixture) {
+    fixture
+        .state
+        .db
+        .get()
+        .unwrap()
+        .execute(
+            "UPDATE projects SET checkout_metadata_keys = ?1 WHERE id = ?2",
+            rusqlite::params![r#"["utm_source","campaign"]"#, fixture.project.id],
+        )
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_metadata_copied_to_license() {
+    let fixture = WebhookFixture::stripe();
+    allow_metadata(&fixture);
+    let metadata = BTreeMap::from([
+        ("utm_source".to_string(), "newsletter".to_string()),
+        ("campaign".to_string(), "spring-sale".to_string()),
+    ]);
+    let session = queries::create_payment_session(
+        &fixture.state.db.get().unwrap(),
+        &CreatePaymentSession {
+            product_id: fixture.product.id.clone(),
+            customer_id: None,
+            upgrade_from_license_id: None,
+            upgrade_days_remaining: None,
+            upgrade_credit_cents: None,
+            checkout_fields: BTreeMap::new(),
+            accepted_terms_version: None,
+            metadata: metadata.clone(),
+        },
+    )
+    .unwrap();
+
+    let license = complete(&fixture, &session).await;
+
+    assert_eq!(license.metadata, metadata);
+    let details = fixture.license_details(&license.id).await;
+    assert_eq!(details["metadata"]["utm_source"], "newsletter");
+    assert_eq!(details["metadata"]["campaign"], "spring-sale");
+}
+
+#[tokio::test]
+async fn test_echoed_metadata_used_without_session_copy() {
+    let fixture = WebhookFixture::stripe();
+    allow_metadata(&fixture);
+    let session = fixture.payment_session();
+
+    // A session from before metadata: only the provider's echo has it
+    let mut payload: serde_json::Value = serde_json::from_slice(
+        &fixture.checkout_payload("stripe_checkout_session_completed", &session),
+    )
+    .unwrap();
+    let echoed = &mut payload["data"]["object"]["metadata"];
+    echoed["meta_utm_source"] = "newsletter".into();
+    let (status, body) = fixture
+        .post_stripe(serde_json::to_vec(&payload).unwrap())
+        .await;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "OK"));
+
+    let session = queries::get_payment_session(&fixture.state.db.get().unwrap(), &session.id)
+        .unwrap()
+        .unwrap();
+    let license = fixture.license(&session.license_id.unwrap());
+    assert_eq!(
+        license.metadata,
+        BTreeMap::from([("utm_source".to_string(), "newsletter".to_string())])
+    );
+}
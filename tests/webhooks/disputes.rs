@@ -0,0 +1,212 @@
+//! Disputes suspend the license bought with the payment until they close:
+//! a won dispute reinstates it, a lost one revokes it as a chargeback.
+
+use super::helpers::*;
+
+const DISPUTE_ID: &str = "du_1QfXs7LzU8pJ3nRt8mWq2ZkA";
+
+/// Buy the product with a one-time Stripe payment and activate a device on
+/// the license. Returns the license and the device's token `jti`.
+async fn purchase_and_activate(fixture: &WebhookFixture) -> (License, String) {
+    let session = fixture.payment_session();
+    let (status, body) = fixture
+        .post_stripe(
+            fixture.checkout_payload("stripe_checkout_session_completed_payment", &session),
+        )
+        .await;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "OK"));
+
+    let conn = fixture.state.db.get().unwrap();
+    let license_id = queries::get_payment_session(&conn, &session.id)
+        .unwrap()
+        .unwrap()
+        .license_id
+        .expect("session should link the license");
+    // /validate checks expiry against the real clock, not the fixture's
+    conn.execute(
+        "UPDATE licenses SET expires_at = NULL WHERE id = ?1",
+        [&license_id],
+    )
+    .unwrap();
+    let device = create_test_device(&conn, &license_id, "laptop", DeviceType::Uuid);
+    drop(conn);
+
+    (fixture.license(&license_id), device.jti)
+}
+
+async fn validate(fixture: &WebhookFixture, jti: &str) -> serde_json::Value {
+    let response = public_app(fixture.state.clone())
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/validate")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    serde_json::json!({
+                        "public_key": fixture.project.public_key,
+                        "jti": jti
+                    })
+                    .to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
+fn dispute_closed(status: &str) -> Vec<u8> {
+    String::from_utf8(load_fixture("stripe_charge_dispute_closed", &[]))
+        .unwrap()
+        .replace(
+            "\"status\": \"won\"",
+            &format!("\"status\": \"{}\"", status),
+        )
+        .into_bytes()
+}
+
+async fn open_dispute(fixture: &WebhookFixture) {
+    let (status, body) = fixture
+        .post_stripe(load_fixture("stripe_charge_dispute_created", &[]))
+        .await;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "OK"));
+}
+
+#[tokio::test]
+async fn test_dispute_suspends_then_won_reinstates() {
+    let fixture = WebhookFixture::stripe();
+    let (license, jti) = purchase_and_activate(&fixture).await;
+    assert_eq!(validate(&fixture, &jti).await["valid"], true);
+
+    open_dispute(&fixture).await;
+
+    let suspended = fixture.license(&license.id);
+    assert!(suspended.suspended_for_dispute);
+    assert!(!suspended.revoked);
+    let result = validate(&fixture, &jti).await;
+    assert_eq!(result["valid"], false);
+    assert_eq!(result["reason"], "suspended_for_dispute");
+
+    let details = fixture.license_details(&license.id).await;
+    assert_eq!(details["suspended_for_dispute"], true);
+    assert_eq!(
+        details["devices"].as_array().unwrap().len(),
+        1,
+        "suspension keeps devices"
+    );
+    let disputes = details["disputes"].as_array().unwrap();
+    assert_eq!(disputes.len(), 1);
+    assert_eq!(disputes[0]["provider_dispute_id"], DISPUTE_ID);
+    assert_eq!(disputes[0]["status"], "open");
+    assert_eq!(disputes[0]["amount_cents"], 4999);
+    assert_eq!(disputes[0]["reason"], "fraudulent");
+
+    let (status, body) = fixture.post_stripe(dispute_closed("won")).await;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "OK"));
+
+    let reinstated = fixture.license(&license.id);
+    assert!(!reinstated.suspended_for_dispute);
+    assert!(!reinstated.revoked);
+    let result = validate(&fixture, &jti).await;
+    assert_eq!(result["valid"], true);
+    assert_eq!(result["reason"], serde_json::Value::Null);
+
+    let details = fixture.license_details(&license.id).await;
+    assert_eq!(details["disputes"][0]["status"], "won");
+    assert_eq!(details["disputes"][0]["closed_at"], RECORDED_AT);
+}
+
+#[tokio::test]
+async fn test_dispute_suspends_then_lost_revokes_as_chargeback() {
+    let fixture = WebhookFixture::stripe();
+    let (license, jti) = purchase_and_activate(&fixture).await;
+    assert_eq!(validate(&fixture, &jti).await["valid"], true);
+
+    open_dispute(&fixture).await;
+    assert_eq!(
+        validate(&fixture, &jti).await["reason"],
+        "suspended_for_dispute"
+    );
+
+    let (status, body) = fixture.post_stripe(dispute_closed("lost")).await;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "OK"));
+
+    let revoked = fixture.license(&license.id);
+    assert!(revoked.revoked);
+    assert!(!revoked.suspended_for_dispute);
+    assert_eq!(revoked.revoked_reason, Some(RevocationReason::Chargeback));
+    assert_eq!(revoked.revoked_by.as_deref(), Some("stripe"));
+    let result = validate(&fixture, &jti).await;
+    assert_eq!(result["valid"], false);
+    assert_eq!(result["reason"], "revoked");
+    assert_eq!(result["revocation"]["reason"], "chargeback");
+
+    let details = fixture.license_details(&license.id).await;
+    assert_eq!(details["disputes"][0]["status"], "lost");
+}
+
+#[tokio::test]
+async fn test_dispute_webhooks_are_idempotent() {
+    let fixture = WebhookFixture::stripe();
+    let (license, _) = purchase_and_activate(&fixture).await;
+
+    open_dispute(&fixture).await;
+    let (status, body) = fixture
+        .post_stripe(load_fixture("stripe_charge_dispute_created", &[]))
+        .await;
+    assert_eq!(
+        (status, body.as_str()),
+        (StatusCode::OK, "Dispute already recorded")
+    );
+
+    fixture.post_stripe(dispute_closed("won")).await;
+    let (status, body) = fixture.post_stripe(dispute_closed("lost")).await;
+    assert_eq!(
+        (status, body.as_str()),
+        (StatusCode::OK, "Dispute already closed")
+    );
+    assert!(!fixture.license(&license.id).revoked);
+}
+
+#[tokio::test]
+async fn test_dispute_under_review_is_ignored() {
+    let fixture = WebhookFixture::stripe();
+    let (license, _) = purchase_and_activate(&fixture).await;
+    open_dispute(&fixture).await;
+
+    let (status, body) = fixture.post_stripe(dispute_closed("under_review")).await;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "Event ignored"));
+    assert!(fixture.license(&license.id).suspended_for_dispute);
+}
+
+#[tokio::test]
+async fn test_closing_unknown_dispute_is_ok() {
+    let fixture = WebhookFixture::stripe();
+
+    let (status, body) = fixture.post_stripe(dispute_closed("lost")).await;
+    assert_eq!(
+        (status, body.as_str()),
+        (StatusCode::OK, "Dispute not found")
+    );
+}
+
+#[tokio::test]
+async fn test_project_dispute_list_filters_by_status() {
+    let fixture = WebhookFixture::stripe();
+    let (license, _) = purchase_and_activate(&fixture).await;
+    open_dispute(&fixture).await;
+
+    let open = fixture.admin_get("/disputes?status=open").await;
+    assert_eq!(open["total"], 1);
+    assert_eq!(open["items"][0]["license_id"], license.id.as_str());
+
+    fixture.post_stripe(dispute_closed("won")).await;
+
+    let open = fixture.admin_get("/disputes?status=open").await;
+    assert_eq!(open["total"], 0);
+    let all = fixture.admin_get("/disputes").await;
+    assert_eq!(all["total"], 1);
+    assert_eq!(all["items"][0]["status"], "won");
+}
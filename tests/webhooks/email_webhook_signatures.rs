@@ -0,0 +1,273 @@
+//! Email webhook signing: payloads POSTed to `email_webhook_url` carry an
+//! HMAC of the body when the project has an `email_webhook_secret`, which is
+//! stored encrypted and only ever shown masked.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::body::Bytes;
+use axum::http::HeaderMap;
+use hmac::{Hmac, Mac};
+use serde_json::{Value, json};
+use sha2::Sha256;
+use tokio::sync::mpsc;
+
+use super::helpers::*;
+
+const SECRET: &str = "whsec_paycheck_0123456789abcdefghijklmnop";
+
+/// A webhook the stub endpoint received, body as sent.
+struct Delivered {
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl Delivered {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(|v| v.to_str().unwrap())
+    }
+
+    fn json(&self) -> Value {
+        serde_json::from_slice(&self.body).unwrap()
+    }
+}
+
+/// Record every POST to `/hook`. Returns the URL and the received requests.
+async fn stub_webhook() -> (String, mpsc::UnboundedReceiver<Delivered>) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let app = Router::new().route(
+        "/hook",
+        post(move |headers: HeaderMap, body: Bytes| {
+            let tx = tx.clone();
+            async move {
+                let _ = tx.send(Delivered { headers, body });
+                StatusCode::OK
+            }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    (format!("http://{}/hook", addr), rx)
+}
+
+/// `v1=` and the hex HMAC-SHA256 of `{timestamp}.{body}`, as a receiver
+/// would compute it.
+fn expected_signature(secret: &str, timestamp: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("v1={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Request activation codes for `licenses` licenses of a project whose
+/// webhook goes to a stub, signed with `secret`. Returns what the stub got.
+async fn request_code(secret: Option<&str>, licenses: usize) -> Delivered {
+    let (url, mut rx) = stub_webhook().await;
+
+    let mut state = create_test_app_state();
+    state.allow_localhost_urls = true;
+    state.email_service =
+        Arc::new(EmailService::new(None, "noreply@test.com".into()).with_localhost_urls(true));
+    let conn = state.db.get().unwrap();
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "Test Project", &state.master_key);
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+    for _ in 0..licenses {
+        create_test_license(&conn, &project.id, &product.id, None);
+    }
+    let input: UpdateProject = serde_json::from_value(json!({
+        "email_webhook_url": url,
+        "email_webhook_secret": secret,
+    }))
+    .unwrap();
+    queries::update_project(&conn, &project.id, &input, &state.master_key)
+        .unwrap()
+        .unwrap();
+    drop(conn);
+
+    let response = public_app(state)
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/activation/request-code")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    json!({
+                        "email": "test@example.com",
+                        "public_key": project.public_key,
+                    })
+                    .to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    tokio::time::timeout(Duration::from_secs(2), rx.recv())
+        .await
+        .ok()
+        .flatten()
+        .expect("webhook called")
+}
+
+fn assert_signed(delivered: &Delivered) {
+    let timestamp = delivered
+        .header("x-paycheck-timestamp")
+        .expect("timestamp header");
+    let sent_at: i64 = timestamp.parse().unwrap();
+    assert!((chrono::Utc::now().timestamp() - sent_at).abs() < 60);
+    assert_eq!(
+        delivered.header("x-paycheck-signature"),
+        Some(expected_signature(SECRET, timestamp, &delivered.body).as_str())
+    );
+}
+
+#[tokio::test]
+async fn test_single_license_webhook_is_signed() {
+    let delivered = request_code(Some(SECRET), 1).await;
+
+    assert_eq!(delivered.json()["event"], "activation_code_created");
+    assert_signed(&delivered);
+}
+
+#[tokio::test]
+async fn test_multi_license_webhook_is_signed() {
+    let delivered = request_code(Some(SECRET), 2).await;
+
+    assert_eq!(delivered.json()["event"], "activation_codes_created");
+    assert_signed(&delivered);
+}
+
+#[tokio::test]
+async fn test_webhook_without_secret_is_unsigned() {
+    let delivered = request_code(None, 1).await;
+
+    assert!(delivered.header("x-paycheck-signature").is_none());
+    assert!(delivered.header("x-paycheck-timestamp").is_none());
+}
+
+#[tokio::test]
+async fn test_undecryptable_secret_is_not_sent_unsigned() {
+    let (url, mut rx) = stub_webhook().await;
+
+    let mut state = create_test_app_state();
+    state.allow_localhost_urls = true;
+    state.email_service =
+        Arc::new(EmailService::new(None, "noreply@test.com".into()).with_localhost_urls(true));
+    let conn = state.db.get().unwrap();
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "Test Project", &state.master_key);
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+    create_test_license(&conn, &project.id, &product.id, None);
+    // E.g. left under an old master key
+    conn.execute(
+        "UPDATE projects SET email_webhook_url = ?1, email_webhook_secret = ?2 WHERE id = ?3",
+        rusqlite::params![url, vec![0u8; 64], project.id],
+    )
+    .unwrap();
+    drop(conn);
+
+    let response = public_app(state)
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/activation/request-code")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    json!({
+                        "email": "test@example.com",
+                        "public_key": project.public_key,
+                    })
+                    .to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    // The same response as for an email without a license
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let delivered = tokio::time::timeout(Duration::from_millis(500), rx.recv()).await;
+    assert!(delivered.is_err(), "webhook must not be sent unsigned");
+}
+
+#[tokio::test]
+async fn test_secret_is_only_shown_masked() {
+    let fixture = WebhookFixture::stripe();
+    let conn = fixture.state.db.get().unwrap();
+    let input: UpdateProject =
+        serde_json::from_value(json!({ "email_webhook_secret": SECRET })).unwrap();
+    let project = queries::update_project(
+        &conn,
+        &fixture.project.id,
+        &input,
+        &fixture.state.master_key,
+    )
+    .unwrap()
+    .unwrap();
+    assert_ne!(
+        project.email_webhook_secret.as_deref(),
+        Some(SECRET.as_bytes())
+    );
+    assert_eq!(
+        queries::decrypt_project_webhook_secret(&conn, &project, &fixture.state.master_key)
+            .unwrap()
+            .as_deref(),
+        Some(SECRET)
+    );
+    drop(conn);
+
+    let settings = fixture.admin_get("/email-webhook").await;
+    assert_eq!(settings["email_webhook_secret"], "whsec_pa...mnop");
+
+    let project = fixture.admin_get("").await;
+    assert!(project.get("email_webhook_secret").is_none());
+    assert!(!project.to_string().contains(SECRET));
+}
+
+#[tokio::test]
+async fn test_secret_survives_other_updates_until_cleared() {
+    let fixture = WebhookFixture::stripe();
+    let conn = fixture.state.db.get().unwrap();
+    for (update, expected) in [
+        (json!({ "email_webhook_secret": SECRET }), Some(SECRET)),
+        (json!({ "name": "Renamed" }), Some(SECRET)),
+        (json!({ "email_webhook_secret": null }), None),
+    ] {
+        let input: UpdateProject = serde_json::from_value(update).unwrap();
+        let project = queries::update_project(
+            &conn,
+            &fixture.project.id,
+            &input,
+            &fixture.state.master_key,
+        )
+        .unwrap()
+        .unwrap();
+        let secret =
+            queries::decrypt_project_webhook_secret(&conn, &project, &fixture.state.master_key)
+                .unwrap();
+        assert_eq!(secret.as_deref(), expected);
+    }
+    drop(conn);
+
+    let settings = fixture.admin_get("/email-webhook").await;
+    assert!(settings["email_webhook_secret"].is_null());
+}
+
+#[test]
+fn test_short_secret_is_rejected() {
+    let input: UpdateProject =
+        serde_json::from_value(json!({ "email_webhook_secret": "too-short" })).unwrap();
+    assert!(input.validate().is_err());
+
+    // Never echoed back, e.g. in a 409 response
+    let input: UpdateProject =
+        serde_json::from_value(json!({ "email_webhook_secret": SECRET })).unwrap();
+    assert!(input.validate().is_ok());
+    assert!(!serde_json::to_string(&input).unwrap().contains(SECRET));
+}
@@ -0,0 +1,235 @@
+//! Shared helpers for fixture-driven webhook tests
+
+pub use axum::Router;
+pub use axum::body::{Body, to_bytes};
+pub use axum::http::{Request, StatusCode};
+pub use axum::routing::post;
+pub use tower::ServiceExt;
+
+#[path = "../common/mod.rs"]
+mod common;
+pub use common::*;
+
+pub use paycheck::handlers::webhooks::{handle_lemonsqueezy_webhook, handle_stripe_webhook};
+
+use hmac::{Hmac, Mac};
+use rusqlite::Connection;
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Webhook secrets configured by `setup_stripe_config` / `setup_lemonsqueezy_config`
+pub const STRIPE_WEBHOOK_SECRET: &str = "whsec_test123secret456";
+pub const LEMONSQUEEZY_WEBHOOK_SECRET: &str = "ls_whsec_test_secret";
+
+/// When the fixtures were recorded (2026-01-01T00:00:00Z); the test clock is pinned here
+pub const RECORDED_AT: i64 = 1_767_225_600;
+/// Billing period end in the renewal fixtures (2026-02-01T00:00:00Z)
+pub const PERIOD_END: i64 = 1_769_904_000;
+
+/// Subscription IDs that appear in the recorded payloads
+pub const STRIPE_SUBSCRIPTION_ID: &str = "sub_1QfXk2LzU8pJ3nRt4wVb9cEa";
+pub const LEMONSQUEEZY_SUBSCRIPTION_ID: &str = "482913";
+
+pub const SECONDS_PER_DAY: i64 = 86400;
+
+/// Org, project, and product for one provider, with the clock pinned to `RECORDED_AT`.
+pub struct WebhookFixture {
+    pub state: AppState,
+    pub project: Project,
+    pub product: Product,
+}
+
+impl WebhookFixture {
+    pub fn stripe() -> Self {
+        Self::new(setup_stripe_config)
+    }
+
+    pub fn lemonsqueezy() -> Self {
+        Self::new(setup_lemonsqueezy_config)
+    }
+
+    fn new(configure: fn(&Connection, &str, &MasterKey)) -> Self {
+        let mut state = create_test_app_state();
+        state.clock = Clock::fixed(RECORDED_AT);
+
+        let conn = state.db.get().unwrap();
+        let org = create_test_org(&conn, "Test Org");
+        configure(&conn, &org.id, &state.master_key);
+        let project = create_test_project(&conn, &org.id, "Test Project", &state.master_key);
+        let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+        drop(conn);
+
+        Self {
+            state,
+            project,
+            product,
+        }
+    }
+
+    /// Payment session for a checkout fixture, as created by `/buy`.
+    pub fn payment_session(&self) -> PaymentSession {
+        create_test_payment_session(&self.state.db.get().unwrap(), &self.product.id, None)
+    }
+
+    /// Existing subscription license that renewal/cancellation fixtures refer to.
+    pub fn subscription_license(&self, provider: &str, subscription_id: &str) -> License {
+        create_test_license_with_subscription(
+            &self.state.db.get().unwrap(),
+            &self.project.id,
+            &self.product.id,
+            Some(RECORDED_AT + 3 * SECONDS_PER_DAY),
+            provider,
+            subscription_id,
+        )
+    }
+
+    /// Load a fixture, filling `{{placeholders}}` for this fixture's session.
+    pub fn checkout_payload(&self, name: &str, session: &PaymentSession) -> Vec<u8> {
+        load_fixture(
+            name,
+            &[
+                ("session_id", session.id.as_str()),
+                ("project_id", self.project.id.as_str()),
+                ("product_id", self.product.id.as_str()),
+            ],
+        )
+    }
+
+    pub fn license(&self, license_id: &str) -> License {
+        queries::get_license_by_id(&self.state.db.get().unwrap(), license_id)
+            .unwrap()
+            .expect("license should exist")
+    }
+
+    pub fn license_count(&self) -> i64 {
+        self.state
+            .db
+            .get()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM licenses", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    /// GET the license through the admin API.
+    pub async fn license_details(&self, license_id: &str) -> serde_json::Value {
+        self.admin_get(&format!("/licenses/{}", license_id)).await
+    }
+
+    /// GET a path under this fixture's project through the admin API.
+    pub async fn admin_get(&self, project_path: &str) -> serde_json::Value {
+        let mut conn = self.state.db.get().unwrap();
+        let (_, _, api_key) = create_test_org_member(
+            &mut conn,
+            &self.project.org_id,
+            &format!("{}@test.com", uuid::Uuid::new_v4()),
+            OrgMemberRole::Owner,
+        );
+        drop(conn);
+
+        let app = paycheck::handlers::orgs::router(
+            self.state.clone(),
+            paycheck::config::RateLimitConfig::disabled(),
+        )
+        .with_state(self.state.clone());
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!(
+                        "/orgs/{}/projects/{}{}",
+                        self.project.org_id, self.project.id, project_path
+                    ))
+                    .header("Authorization", format!("Bearer {}", api_key))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    pub async fn post_stripe(&self, payload: Vec<u8>) -> (StatusCode, String) {
+        let header = stripe_signature_header(&payload, STRIPE_WEBHOOK_SECRET);
+        self.post("/webhook/stripe", "stripe-signature", header, payload)
+            .await
+    }
+
+    pub async fn post_lemonsqueezy(&self, payload: Vec<u8>) -> (StatusCode, String) {
+        let signature = lemonsqueezy_signature(&payload, LEMONSQUEEZY_WEBHOOK_SECRET);
+        self.post("/webhook/lemonsqueezy", "x-signature", signature, payload)
+            .await
+    }
+
+    async fn post(
+        &self,
+        uri: &str,
+        signature_header: &str,
+        signature: String,
+        payload: Vec<u8>,
+    ) -> (StatusCode, String) {
+        let app = Router::new()
+            .route("/webhook/stripe", post(handle_stripe_webhook))
+            .route("/webhook/lemonsqueezy", post(handle_lemonsqueezy_webhook))
+            .with_state(self.state.clone());
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(uri)
+                    .header("content-type", "application/json")
+                    .header(signature_header, signature)
+                    .body(Body::from(payload))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8_lossy(&body).into_owned())
+    }
+}
+
+/// Read `fixtures/<name>.json`, replacing `{{key}}` placeholders.
+pub fn load_fixture(name: &str, values: &[(&str, &str)]) -> Vec<u8> {
+    let path = format!(
+        "{}/tests/webhooks/fixtures/{}.json",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    );
+    let mut raw = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("failed to read fixture {}: {}", path, e));
+    for (key, value) in values {
+        raw = raw.replace(&format!("{{{{{}}}}}", key), value);
+    }
+    assert!(
+        !raw.contains("{{"),
+        "fixture {} has unfilled placeholders",
+        name
+    );
+    serde_json::from_str::<serde_json::Value>(&raw)
+        .unwrap_or_else(|e| panic!("fixture {} is not valid JSON: {}", name, e));
+    raw.into_bytes()
+}
+
+/// `stripe-signature` header value signed at the current time.
+pub fn stripe_signature_header(payload: &[u8], secret: &str) -> String {
+    let timestamp = chrono::Utc::now().timestamp();
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(format!("{}.", timestamp).as_bytes());
+    mac.update(payload);
+    format!(
+        "t={},v1={}",
+        timestamp,
+        hex::encode(mac.finalize().into_bytes())
+    )
+}
+
+/// `x-signature` header value (hex HMAC-SHA256 of the raw body).
+pub fn lemonsqueezy_signature(payload: &[u8], secret: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(payload);
+    hex::encode(mac.finalize().into_bytes())
+}
@@ -0,0 +1,157 @@
+//! LemonSqueezy webhook handling against recorded event payloads
+
+use super::helpers::*;
+
+fn renewal_fixture() -> (WebhookFixture, License) {
+    let fixture = WebhookFixture::lemonsqueezy();
+    let license = fixture.subscription_license("lemonsqueezy", LEMONSQUEEZY_SUBSCRIPTION_ID);
+    (fixture, license)
+}
+
+#[tokio::test]
+async fn test_order_created_creates_license() {
+    let fixture = WebhookFixture::lemonsqueezy();
+    let session = fixture.payment_session();
+    let payload = fixture.checkout_payload("lemonsqueezy_order_created", &session);
+
+    let (status, body) = fixture.post_lemonsqueezy(payload).await;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "OK"));
+
+    let conn = fixture.state.db.get().unwrap();
+    let session = queries::get_payment_session(&conn, &session.id)
+        .unwrap()
+        .unwrap();
+    assert!(session.completed, "session should be claimed");
+    let license = fixture.license(&session.license_id.expect("session should link the license"));
+
+    assert_eq!(license.payment_provider.as_deref(), Some("lemonsqueezy"));
+    assert_eq!(
+        license.payment_provider_subscription_id.as_deref(),
+        Some(LEMONSQUEEZY_SUBSCRIPTION_ID),
+        "subscription ID comes from first_order_item"
+    );
+    assert_eq!(
+        license.payment_provider_customer_id.as_deref(),
+        Some("2950117")
+    );
+    assert_eq!(
+        license.payment_provider_order_id.as_deref(),
+        Some("3618204")
+    );
+    assert_eq!(
+        license.email_hash,
+        Some(
+            fixture
+                .state
+                .email_hasher
+                .hash(&parse_email("buyer@example.com"))
+        )
+    );
+    // Purchases are identified by email hash; only imports carry a provider key
+    let external_key_hash: Option<String> = conn
+        .query_row(
+            "SELECT external_key_hash FROM licenses WHERE id = ?1",
+            [&license.id],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(external_key_hash, None);
+    assert_eq!(
+        license.expires_at,
+        Some(RECORDED_AT + 365 * SECONDS_PER_DAY)
+    );
+    assert_eq!(
+        license.updates_expires_at,
+        Some(RECORDED_AT + 365 * SECONDS_PER_DAY)
+    );
+}
+
+#[tokio::test]
+async fn test_order_created_bad_signature_rejected() {
+    let fixture = WebhookFixture::lemonsqueezy();
+    let session = fixture.payment_session();
+    let payload = fixture.checkout_payload("lemonsqueezy_order_created", &session);
+
+    let response = Router::new()
+        .route("/webhook/lemonsqueezy", post(handle_lemonsqueezy_webhook))
+        .with_state(fixture.state.clone())
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/webhook/lemonsqueezy")
+                .header(
+                    "x-signature",
+                    lemonsqueezy_signature(&payload, "wrong_secret"),
+                )
+                .body(Body::from(payload))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(fixture.license_count(), 0);
+}
+
+#[tokio::test]
+async fn test_subscription_payment_success_extends_license() {
+    let (fixture, license) = renewal_fixture();
+
+    let (status, body) = fixture
+        .post_lemonsqueezy(load_fixture(
+            "lemonsqueezy_subscription_payment_success",
+            &[],
+        ))
+        .await;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "OK"));
+
+    let renewed = fixture.license(&license.id);
+    assert_eq!(
+        renewed.expires_at,
+        Some(PERIOD_END),
+        "expiration should follow the invoice period_end"
+    );
+    assert_eq!(
+        renewed.updates_expires_at,
+        Some(PERIOD_END + 365 * SECONDS_PER_DAY)
+    );
+}
+
+#[tokio::test]
+async fn test_subscription_payment_success_redelivery_is_idempotent() {
+    let (fixture, license) = renewal_fixture();
+    let payload = load_fixture("lemonsqueezy_subscription_payment_success", &[]);
+
+    fixture.post_lemonsqueezy(payload.clone()).await;
+    fixture
+        .state
+        .db
+        .get()
+        .unwrap()
+        .execute(
+            "UPDATE licenses SET expires_at = ?1 WHERE id = ?2",
+            rusqlite::params![RECORDED_AT, license.id],
+        )
+        .unwrap();
+    let (status, body) = fixture.post_lemonsqueezy(payload).await;
+
+    assert_eq!(
+        (status, body.as_str()),
+        (StatusCode::OK, "Already processed")
+    );
+    assert_eq!(fixture.license(&license.id).expires_at, Some(RECORDED_AT));
+}
+
+#[tokio::test]
+async fn test_subscription_cancelled_leaves_license_to_expire() {
+    let (fixture, license) = renewal_fixture();
+
+    let (status, body) = fixture
+        .post_lemonsqueezy(load_fixture("lemonsqueezy_subscription_cancelled", &[]))
+        .await;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "OK"));
+
+    let unchanged = fixture.license(&license.id);
+    assert_eq!(unchanged.expires_at, license.expires_at);
+    assert!(!unchanged.revoked, "cancellation should not revoke");
+}
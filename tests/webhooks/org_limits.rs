@@ -0,0 +1,84 @@
+//! Checkout webhooks against the org's monthly license limit
+
+use super::helpers::*;
+
+use paycheck::models::{OrgLimitName, SetOrgLimit};
+use paycheck::quota;
+
+fn set_license_limit(fixture: &WebhookFixture, soft_value: Option<i64>, hard_value: Option<i64>) {
+    queries::set_org_limit(
+        &fixture.state.db.get().unwrap(),
+        &fixture.project.org_id,
+        OrgLimitName::LicensesPerMonth,
+        &SetOrgLimit {
+            soft_value,
+            hard_value,
+        },
+    )
+    .unwrap();
+}
+
+fn licenses_this_month(fixture: &WebhookFixture) -> i64 {
+    let usage = quota::org_usage(
+        &fixture.state.db.get().unwrap(),
+        &fixture.project.org_id,
+        RECORDED_AT,
+    )
+    .unwrap();
+    usage
+        .into_iter()
+        .find(|u| u.limit_name == OrgLimitName::LicensesPerMonth)
+        .unwrap()
+        .used
+}
+
+#[tokio::test]
+async fn test_checkout_counts_toward_monthly_license_limit() {
+    let mut fixture = WebhookFixture::stripe();
+    fixture.state.audit_log_enabled = true;
+    set_license_limit(&fixture, Some(1), Some(5));
+    let session = fixture.payment_session();
+    let payload = fixture.checkout_payload("stripe_checkout_session_completed", &session);
+
+    let (status, _) = fixture.post_stripe(payload).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(licenses_this_month(&fixture), 1);
+
+    let audited: i64 = fixture
+        .state
+        .audit
+        .get()
+        .unwrap()
+        .query_row(
+            "SELECT COUNT(*) FROM audit_logs WHERE action = 'reach_org_soft_limit' AND actor_type = 'public'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(audited, 1, "the webhook reached the soft value");
+}
+
+#[tokio::test]
+async fn test_checkout_past_hard_limit_is_retried() {
+    let fixture = WebhookFixture::stripe();
+    set_license_limit(&fixture, None, Some(0));
+    let session = fixture.payment_session();
+    let payload = fixture.checkout_payload("stripe_checkout_session_completed", &session);
+
+    let (status, _) = fixture.post_stripe(payload.clone()).await;
+    assert_eq!(
+        status,
+        StatusCode::SERVICE_UNAVAILABLE,
+        "the provider should retry"
+    );
+    assert_eq!(fixture.license_count(), 0);
+    let unclaimed = queries::get_payment_session(&fixture.state.db.get().unwrap(), &session.id)
+        .unwrap()
+        .unwrap();
+    assert!(!unclaimed.completed, "session should stay claimable");
+
+    set_license_limit(&fixture, None, None);
+    let (status, body) = fixture.post_stripe(payload).await;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "OK"));
+    assert_eq!(fixture.license_count(), 1);
+}
@@ -0,0 +1,272 @@
+//! Webhook payload schema versions: each version's payloads against the
+//! golden files in `golden/`, delivery in the project's pinned version, and
+//! the schema endpoint.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::body::Bytes;
+use axum::http::HeaderMap;
+use paycheck::email::{EmailTrigger, LicenseCodeInfo};
+use paycheck::webhook_payloads::{
+    ActivationCodeCreated, ActivationCodesCreated, LicenseDormant, WebhookEvent,
+    WebhookSchemaVersion,
+};
+use serde_json::{Value, json};
+use tokio::sync::mpsc;
+
+use super::helpers::*;
+
+/// Compare `event` in every version against `golden/v<N>/<event name>.json`.
+fn assert_golden<E: WebhookEvent>(event: &E) {
+    for version in WebhookSchemaVersion::ALL {
+        let path = format!(
+            "{}/tests/webhooks/golden/v{}/{}.json",
+            env!("CARGO_MANIFEST_DIR"),
+            version.as_ref(),
+            E::NAME
+        );
+        let raw = std::fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("failed to read golden file {}: {}", path, e));
+        let expected: Value = serde_json::from_str(&raw).unwrap();
+        let actual: Value = serde_json::from_slice(&event.to_json(version).unwrap()).unwrap();
+        assert_eq!(
+            actual,
+            expected,
+            "{} no longer matches; released versions must not change:\n{}",
+            path,
+            serde_json::to_string_pretty(&actual).unwrap()
+        );
+    }
+}
+
+fn metadata() -> BTreeMap<String, String> {
+    BTreeMap::from([("source".to_string(), "launch".to_string())])
+}
+
+#[test]
+fn test_activation_code_created_matches_golden() {
+    let no_metadata = BTreeMap::new();
+    assert_golden(&ActivationCodeCreated {
+        email: "",
+        code: "PC-AAAA-BBBB",
+        expires_at: RECORDED_AT + 1800,
+        expires_in_minutes: 30,
+        product_name: "Pro Plan",
+        project_id: "proj_1",
+        project_name: "My App",
+        license_id: "lic_1",
+        trigger: EmailTrigger::DormantNudge,
+        metadata: &no_metadata,
+    });
+}
+
+#[test]
+fn test_activation_codes_created_matches_golden() {
+    let licenses = [
+        LicenseCodeInfo {
+            product_name: "Pro Plan".into(),
+            code: "PC-AAAA-BBBB".into(),
+            license_id: "lic_1".into(),
+            purchased_at: RECORDED_AT,
+            metadata: metadata(),
+        },
+        LicenseCodeInfo {
+            product_name: "Team Plan".into(),
+            code: "PC-CCCC-DDDD".into(),
+            license_id: "lic_2".into(),
+            purchased_at: RECORDED_AT + 100,
+            metadata: BTreeMap::new(),
+        },
+    ];
+    assert_golden(&ActivationCodesCreated {
+        email: "buyer@example.com",
+        expires_at: RECORDED_AT + 1800,
+        expires_in_minutes: 30,
+        project_id: "proj_1",
+        project_name: "My App",
+        licenses: &licenses,
+        trigger: EmailTrigger::RecoveryRequest,
+    });
+}
+
+#[test]
+fn test_license_dormant_matches_golden() {
+    let metadata = metadata();
+    assert_golden(&LicenseDormant {
+        project_id: "proj_1",
+        project_name: "My App",
+        license_id: "lic_1",
+        product_name: "Pro Plan",
+        customer_id: Some("cus_123"),
+        purchased_at: RECORDED_AT,
+        dormant_since: RECORDED_AT + 30 * SECONDS_PER_DAY,
+        metadata: &metadata,
+    });
+}
+
+/// A webhook the stub endpoint received.
+struct Delivered {
+    headers: HeaderMap,
+    body: Value,
+}
+
+/// Record every POST to `/hook`. Returns the URL and the received requests.
+async fn stub_webhook() -> (String, mpsc::UnboundedReceiver<Delivered>) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let app = Router::new().route(
+        "/hook",
+        post(move |headers: HeaderMap, body: Bytes| {
+            let tx = tx.clone();
+            async move {
+                let body = serde_json::from_slice(&body).unwrap();
+                let _ = tx.send(Delivered { headers, body });
+                StatusCode::OK
+            }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    (format!("http://{}/hook", addr), rx)
+}
+
+/// Request an activation code for a project whose webhook goes to a stub,
+/// with `webhook_schema_version` set to `version`. Returns what the stub got.
+async fn request_code(version: Option<&str>) -> Delivered {
+    let (url, mut rx) = stub_webhook().await;
+
+    let mut state = create_test_app_state();
+    state.allow_localhost_urls = true;
+    state.email_service =
+        Arc::new(EmailService::new(None, "noreply@test.com".into()).with_localhost_urls(true));
+    let conn = state.db.get().unwrap();
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "Test Project", &state.master_key);
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+    create_test_license(&conn, &project.id, &product.id, None);
+    conn.execute(
+        "UPDATE projects SET email_webhook_url = ?1, webhook_schema_version = ?2 WHERE id = ?3",
+        rusqlite::params![url, version, project.id],
+    )
+    .unwrap();
+    drop(conn);
+
+    let response = public_app(state)
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/activation/request-code")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    json!({
+                        "email": "test@example.com",
+                        "public_key": project.public_key,
+                    })
+                    .to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    tokio::time::timeout(Duration::from_secs(2), rx.recv())
+        .await
+        .ok()
+        .flatten()
+        .expect("webhook called")
+}
+
+fn event_header(delivered: &Delivered) -> &str {
+    delivered.headers["x-paycheck-event"].to_str().unwrap()
+}
+
+#[tokio::test]
+async fn test_unpinned_project_gets_latest_version() {
+    let delivered = request_code(None).await;
+
+    assert_eq!(event_header(&delivered), "activation_code_created.v2");
+    assert_eq!(delivered.body["schema_version"], "2");
+    assert_eq!(delivered.body["event"], "activation_code_created");
+    assert_eq!(delivered.body["email"], "test@example.com");
+    assert_eq!(delivered.body["metadata"], json!({}));
+}
+
+#[tokio::test]
+async fn test_v1_project_gets_legacy_payload() {
+    let delivered = request_code(Some("1")).await;
+
+    assert_eq!(event_header(&delivered), "activation_code_created");
+    assert!(delivered.body.get("schema_version").is_none());
+    assert!(delivered.body.get("metadata").is_none());
+    assert_eq!(delivered.body["event"], "activation_code_created");
+    assert_eq!(delivered.body["email"], "test@example.com");
+}
+
+#[tokio::test]
+async fn test_schema_endpoint_follows_pinned_version() {
+    let fixture = WebhookFixture::stripe();
+
+    let latest = fixture.admin_get("/webhook-schema").await;
+    let event = &latest["$defs"]["activation_code_created"];
+    assert_eq!(event["properties"]["schema_version"]["const"], "2");
+    assert_eq!(latest["oneOf"].as_array().unwrap().len(), 3);
+
+    let conn = fixture.state.db.get().unwrap();
+    let pin: UpdateProject =
+        serde_json::from_value(json!({ "webhook_schema_version": "1" })).unwrap();
+    queries::update_project(&conn, &fixture.project.id, &pin, &fixture.state.master_key).unwrap();
+    drop(conn);
+
+    let v1 = fixture.admin_get("/webhook-schema").await;
+    let event = &v1["$defs"]["activation_code_created"];
+    assert!(event["properties"].get("schema_version").is_none());
+    assert!(
+        !event["required"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("email")),
+        "v1 leaves out an empty email"
+    );
+}
+
+#[tokio::test]
+async fn test_clearing_pin_returns_to_latest() {
+    let fixture = WebhookFixture::stripe();
+    let conn = fixture.state.db.get().unwrap();
+    for (update, expected) in [
+        (
+            json!({ "webhook_schema_version": "1" }),
+            Some(WebhookSchemaVersion::V1),
+        ),
+        (json!({ "name": "Renamed" }), Some(WebhookSchemaVersion::V1)),
+        (json!({ "webhook_schema_version": null }), None),
+    ] {
+        let input: UpdateProject = serde_json::from_value(update).unwrap();
+        queries::update_project(
+            &conn,
+            &fixture.project.id,
+            &input,
+            &fixture.state.master_key,
+        )
+        .unwrap();
+        let project = queries::get_project_by_id(&conn, &fixture.project.id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(project.webhook_schema_version, expected);
+    }
+    let project = queries::get_project_by_id(&conn, &fixture.project.id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(project.webhook_schema(), WebhookSchemaVersion::LATEST);
+}
+
+#[test]
+fn test_unknown_version_is_rejected() {
+    let result = serde_json::from_value::<UpdateProject>(json!({ "webhook_schema_version": "9" }));
+    assert!(result.is_err());
+}
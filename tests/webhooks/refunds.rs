@@ -0,0 +1,254 @@
+//! Refunds and lost chargebacks revoke the license bought with the payment,
+//! recording the reason the customer is shown.
+
+use super::helpers::*;
+
+/// Complete a checkout fixture and return the license it created.
+async fn purchase(fixture: &WebhookFixture, checkout: &str) -> License {
+    let session = fixture.payment_session();
+    let payload = fixture.checkout_payload(checkout, &session);
+    let (status, body) = if checkout.starts_with("stripe") {
+        fixture.post_stripe(payload).await
+    } else {
+        fixture.post_lemonsqueezy(payload).await
+    };
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "OK"));
+
+    let session = queries::get_payment_session(&fixture.state.db.get().unwrap(), &session.id)
+        .unwrap()
+        .unwrap();
+    fixture.license(&session.license_id.expect("session should link the license"))
+}
+
+#[tokio::test]
+async fn test_stripe_refund_revokes_license() {
+    let fixture = WebhookFixture::stripe();
+    let license = purchase(&fixture, "stripe_checkout_session_completed_payment").await;
+    assert!(!license.revoked);
+
+    let (status, body) = fixture
+        .post_stripe(load_fixture("stripe_charge_refunded", &[]))
+        .await;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "OK"));
+
+    let license = fixture.license(&license.id);
+    assert!(license.revoked);
+    assert_eq!(license.revoked_reason, Some(RevocationReason::Refund));
+    assert_eq!(license.revoked_by.as_deref(), Some("stripe"));
+    assert!(license.revoked_at.is_some());
+    assert_eq!(license.revoked_message, None);
+}
+
+/// The Stripe refund fixture with $10 of the $49.99 charge refunded.
+fn stripe_partial_refund() -> Vec<u8> {
+    String::from_utf8(load_fixture("stripe_charge_refunded", &[]))
+        .unwrap()
+        .replace("\"amount_refunded\": 4999", "\"amount_refunded\": 1000")
+        .replace("\"refunded\": true", "\"refunded\": false")
+        .into_bytes()
+}
+
+#[tokio::test]
+async fn test_stripe_partial_refund_ignored() {
+    let fixture = WebhookFixture::stripe();
+    let license = purchase(&fixture, "stripe_checkout_session_completed_payment").await;
+
+    let (status, body) = fixture.post_stripe(stripe_partial_refund()).await;
+    assert_eq!(
+        (status, body.as_str()),
+        (StatusCode::OK, "Partial refund ignored")
+    );
+
+    assert!(!fixture.license(&license.id).revoked);
+}
+
+#[tokio::test]
+async fn test_stripe_partial_refund_revokes_when_product_opts_in() {
+    let fixture = WebhookFixture::stripe();
+    fixture
+        .state
+        .db
+        .get()
+        .unwrap()
+        .execute(
+            "UPDATE products SET revoke_on_partial_refund = 1 WHERE id = ?1",
+            [&fixture.product.id],
+        )
+        .unwrap();
+    let license = purchase(&fixture, "stripe_checkout_session_completed_payment").await;
+
+    let (status, body) = fixture.post_stripe(stripe_partial_refund()).await;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "OK"));
+
+    let license = fixture.license(&license.id);
+    assert!(license.revoked);
+    assert_eq!(license.revoked_reason, Some(RevocationReason::Refund));
+}
+
+#[tokio::test]
+async fn test_refund_revokes_device_tokens() {
+    let mut fixture = WebhookFixture::stripe();
+    fixture.state.audit_log_enabled = true;
+    let license = purchase(&fixture, "stripe_checkout_session_completed_payment").await;
+    let conn = fixture.state.db.get().unwrap();
+    let devices = [
+        create_test_device(&conn, &license.id, "device-1", DeviceType::Uuid),
+        create_test_device(&conn, &license.id, "device-2", DeviceType::Machine),
+    ];
+    drop(conn);
+
+    let (status, body) = fixture
+        .post_stripe(load_fixture("stripe_charge_refunded", &[]))
+        .await;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "OK"));
+
+    let conn = fixture.state.db.get().unwrap();
+    for device in &devices {
+        assert!(queries::is_jti_revoked(&conn, &device.jti).unwrap());
+    }
+
+    let (actor_type, resource_id): (String, String) = fixture
+        .state
+        .audit
+        .get()
+        .unwrap()
+        .query_row(
+            "SELECT actor_type, resource_id FROM audit_logs WHERE action = 'receive_refund_webhook'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(actor_type, "system");
+    assert_eq!(resource_id, license.id);
+}
+
+#[tokio::test]
+async fn test_stripe_async_payment_failure_revokes_by_checkout_session() {
+    let fixture = WebhookFixture::stripe();
+    // A subscription checkout: no PaymentIntent, so the session ID matches
+    let license = purchase(&fixture, "stripe_checkout_session_completed").await;
+    let session = fixture.payment_session();
+    let payload =
+        String::from_utf8(fixture.checkout_payload("stripe_checkout_session_completed", &session))
+            .unwrap()
+            .replace(
+                "\"type\": \"checkout.session.completed\"",
+                "\"type\": \"checkout.session.async_payment_failed\"",
+            );
+
+    let (status, body) = fixture.post_stripe(payload.into_bytes()).await;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "OK"));
+
+    let license = fixture.license(&license.id);
+    assert!(license.revoked);
+    assert_eq!(
+        license.revoked_reason,
+        Some(RevocationReason::PaymentFailed)
+    );
+}
+
+#[tokio::test]
+async fn test_stripe_dispute_suspends_without_revoking() {
+    let fixture = WebhookFixture::stripe();
+    let license = purchase(&fixture, "stripe_checkout_session_completed_payment").await;
+
+    let (status, body) = fixture
+        .post_stripe(load_fixture("stripe_charge_dispute_created", &[]))
+        .await;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "OK"));
+
+    let license = fixture.license(&license.id);
+    assert!(license.suspended_for_dispute);
+    assert!(!license.revoked);
+    assert_eq!(license.revoked_reason, None);
+}
+
+#[tokio::test]
+async fn test_refund_after_lost_dispute_keeps_first_reason() {
+    let fixture = WebhookFixture::stripe();
+    let license = purchase(&fixture, "stripe_checkout_session_completed_payment").await;
+
+    fixture
+        .post_stripe(load_fixture("stripe_charge_dispute_created", &[]))
+        .await;
+    let lost = String::from_utf8(load_fixture("stripe_charge_dispute_closed", &[]))
+        .unwrap()
+        .replace("\"status\": \"won\"", "\"status\": \"lost\"");
+    fixture.post_stripe(lost.into_bytes()).await;
+    let (status, body) = fixture
+        .post_stripe(load_fixture("stripe_charge_refunded", &[]))
+        .await;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "Already revoked"));
+
+    assert_eq!(
+        fixture.license(&license.id).revoked_reason,
+        Some(RevocationReason::Chargeback)
+    );
+}
+
+#[tokio::test]
+async fn test_stripe_refund_for_unknown_payment_is_ok() {
+    let fixture = WebhookFixture::stripe();
+    // Subscription checkouts carry no PaymentIntent, so nothing matches
+    let license = purchase(&fixture, "stripe_checkout_session_completed").await;
+
+    let (status, body) = fixture
+        .post_stripe(load_fixture("stripe_charge_refunded", &[]))
+        .await;
+    assert_eq!(
+        (status, body.as_str()),
+        (StatusCode::OK, "License not found for payment")
+    );
+    assert!(!fixture.license(&license.id).revoked);
+}
+
+#[tokio::test]
+async fn test_lemonsqueezy_refund_revokes_license() {
+    let fixture = WebhookFixture::lemonsqueezy();
+    let session = fixture.payment_session();
+    let (status, _) = fixture
+        .post_lemonsqueezy(fixture.checkout_payload("lemonsqueezy_order_created", &session))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = fixture
+        .post_lemonsqueezy(fixture.checkout_payload("lemonsqueezy_order_refunded", &session))
+        .await;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "OK"));
+
+    let license_id = queries::get_payment_session(&fixture.state.db.get().unwrap(), &session.id)
+        .unwrap()
+        .unwrap()
+        .license_id
+        .unwrap();
+    let license = fixture.license(&license_id);
+    assert!(license.revoked);
+    assert_eq!(license.revoked_reason, Some(RevocationReason::Refund));
+    assert_eq!(license.revoked_by.as_deref(), Some("lemonsqueezy"));
+}
+
+#[tokio::test]
+async fn test_lemonsqueezy_partial_refund_ignored() {
+    let fixture = WebhookFixture::lemonsqueezy();
+    let session = fixture.payment_session();
+    fixture
+        .post_lemonsqueezy(fixture.checkout_payload("lemonsqueezy_order_created", &session))
+        .await;
+
+    let payload =
+        String::from_utf8(fixture.checkout_payload("lemonsqueezy_order_refunded", &session))
+            .unwrap()
+            .replace("\"status\": \"refunded\"", "\"status\": \"partial_refund\"");
+    let (status, body) = fixture.post_lemonsqueezy(payload.into_bytes()).await;
+    assert_eq!(
+        (status, body.as_str()),
+        (StatusCode::OK, "Partial refund ignored")
+    );
+
+    let license_id = queries::get_payment_session(&fixture.state.db.get().unwrap(), &session.id)
+        .unwrap()
+        .unwrap()
+        .license_id
+        .unwrap();
+    assert!(!fixture.license(&license_id).revoked);
+}
@@ -0,0 +1,65 @@
+//! Completing a checkout for a product with a sale window: what counts is when
+//! the checkout started, not when the webhook arrives.
+
+use super::helpers::*;
+
+/// Give the fixture's product a sale window and backdate the session's start.
+fn set_window(
+    fixture: &WebhookFixture,
+    session: &PaymentSession,
+    from: i64,
+    until: i64,
+    started_at: i64,
+) {
+    let conn = fixture.state.db.get().unwrap();
+    conn.execute(
+        "UPDATE products SET available_from = ?1, available_until = ?2 WHERE id = ?3",
+        rusqlite::params![from, until, fixture.product.id],
+    )
+    .unwrap();
+    conn.execute(
+        "UPDATE payment_sessions SET created_at = ?1 WHERE id = ?2",
+        rusqlite::params![started_at, session.id],
+    )
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_checkout_started_in_window_completes_after_it_closes() {
+    let fixture = WebhookFixture::stripe();
+    let session = fixture.payment_session();
+    // Window closed a minute after checkout started, well before the webhook
+    set_window(
+        &fixture,
+        &session,
+        RECORDED_AT - 14 * SECONDS_PER_DAY,
+        RECORDED_AT - 3600 + 60,
+        RECORDED_AT - 3600,
+    );
+
+    let payload = fixture.checkout_payload("stripe_checkout_session_completed", &session);
+    let (status, body) = fixture.post_stripe(payload).await;
+
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "OK"));
+    assert_eq!(fixture.license_count(), 1);
+}
+
+#[tokio::test]
+async fn test_checkout_started_before_window_creates_no_license() {
+    let fixture = WebhookFixture::lemonsqueezy();
+    let session = fixture.payment_session();
+    set_window(
+        &fixture,
+        &session,
+        RECORDED_AT - 60,
+        RECORDED_AT + 14 * SECONDS_PER_DAY,
+        RECORDED_AT - 3600,
+    );
+
+    let payload = fixture.checkout_payload("lemonsqueezy_order_created", &session);
+    let (status, body) = fixture.post_lemonsqueezy(payload).await;
+
+    assert_eq!(status, StatusCode::OK, "not retryable");
+    assert_eq!(body, "Product was not on sale when checkout started");
+    assert_eq!(fixture.license_count(), 0);
+}
@@ -0,0 +1,234 @@
+//! Stripe webhook handling against recorded event payloads
+
+use super::helpers::*;
+
+fn renewal_fixture() -> (WebhookFixture, License) {
+    let fixture = WebhookFixture::stripe();
+    let license = fixture.subscription_license("stripe", STRIPE_SUBSCRIPTION_ID);
+    (fixture, license)
+}
+
+#[tokio::test]
+async fn test_checkout_session_completed_creates_license() {
+    let fixture = WebhookFixture::stripe();
+    let session = fixture.payment_session();
+    let payload = fixture.checkout_payload("stripe_checkout_session_completed", &session);
+
+    let (status, body) = fixture.post_stripe(payload).await;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "OK"));
+
+    let conn = fixture.state.db.get().unwrap();
+    let session = queries::get_payment_session(&conn, &session.id)
+        .unwrap()
+        .unwrap();
+    assert!(session.completed, "session should be claimed");
+    let license = fixture.license(&session.license_id.expect("session should link the license"));
+
+    assert_eq!(license.payment_provider.as_deref(), Some("stripe"));
+    assert_eq!(
+        license.payment_provider_subscription_id.as_deref(),
+        Some(STRIPE_SUBSCRIPTION_ID)
+    );
+    assert_eq!(
+        license.payment_provider_customer_id.as_deref(),
+        Some("cus_RZp0a8kQ3mWn2F")
+    );
+    assert_eq!(
+        license.payment_provider_order_id.as_deref(),
+        Some("cs_test_a1B2c3D4e5F6g7H8i9J0kLmNoPqRsTuVwXyZ"),
+        "order ID is the checkout session ID"
+    );
+    assert_eq!(
+        license.email_hash,
+        Some(
+            fixture
+                .state
+                .email_hasher
+                .hash(&parse_email("buyer@example.com"))
+        ),
+        "email comes from customer_details"
+    );
+    // Purchases are identified by email hash; only imports carry a provider key
+    let external_key_hash: Option<String> = conn
+        .query_row(
+            "SELECT external_key_hash FROM licenses WHERE id = ?1",
+            [&license.id],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(external_key_hash, None);
+    assert_eq!(
+        license.expires_at,
+        Some(RECORDED_AT + 365 * SECONDS_PER_DAY)
+    );
+    assert_eq!(
+        license.updates_expires_at,
+        Some(RECORDED_AT + 365 * SECONDS_PER_DAY)
+    );
+}
+
+#[tokio::test]
+async fn test_checkout_session_completed_redelivery_creates_one_license() {
+    let fixture = WebhookFixture::stripe();
+    let session = fixture.payment_session();
+    let payload = fixture.checkout_payload("stripe_checkout_session_completed", &session);
+
+    fixture.post_stripe(payload.clone()).await;
+    let (status, body) = fixture.post_stripe(payload).await;
+
+    assert_eq!(
+        (status, body.as_str()),
+        (StatusCode::OK, "Already processed")
+    );
+    assert_eq!(fixture.license_count(), 1);
+}
+
+#[tokio::test]
+async fn test_checkout_session_completed_tampered_payload_rejected() {
+    let fixture = WebhookFixture::stripe();
+    let session = fixture.payment_session();
+    let payload = fixture.checkout_payload("stripe_checkout_session_completed", &session);
+    let header = stripe_signature_header(&payload, STRIPE_WEBHOOK_SECRET);
+
+    // Signature computed over the original payload, body altered afterwards
+    let tampered = String::from_utf8(payload)
+        .unwrap()
+        .replace("\"amount_total\": 4999", "\"amount_total\": 1")
+        .into_bytes();
+    let response = Router::new()
+        .route("/webhook/stripe", post(handle_stripe_webhook))
+        .with_state(fixture.state.clone())
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/webhook/stripe")
+                .header("stripe-signature", header)
+                .body(Body::from(tampered))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        fixture.license_count(),
+        0,
+        "no license without a valid signature"
+    );
+}
+
+#[tokio::test]
+async fn test_invoice_paid_subscription_cycle_extends_license() {
+    let (fixture, license) = renewal_fixture();
+
+    let (status, body) = fixture
+        .post_stripe(load_fixture("stripe_invoice_paid_subscription_cycle", &[]))
+        .await;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "OK"));
+
+    let renewed = fixture.license(&license.id);
+    assert_eq!(
+        renewed.expires_at,
+        Some(PERIOD_END),
+        "expiration should follow the invoice line period end"
+    );
+    assert_eq!(
+        renewed.updates_expires_at,
+        Some(PERIOD_END + 365 * SECONDS_PER_DAY)
+    );
+}
+
+#[tokio::test]
+async fn test_invoice_paid_subscription_update_extends_license() {
+    let (fixture, license) = renewal_fixture();
+
+    let (status, body) = fixture
+        .post_stripe(load_fixture("stripe_invoice_paid_subscription_update", &[]))
+        .await;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "OK"));
+    assert_eq!(fixture.license(&license.id).expires_at, Some(PERIOD_END));
+}
+
+#[tokio::test]
+async fn test_invoice_paid_redelivery_is_idempotent() {
+    let (fixture, license) = renewal_fixture();
+    let payload = load_fixture("stripe_invoice_paid_subscription_cycle", &[]);
+
+    fixture.post_stripe(payload.clone()).await;
+    // Move the license so a second extension would be visible
+    fixture
+        .state
+        .db
+        .get()
+        .unwrap()
+        .execute(
+            "UPDATE licenses SET expires_at = ?1 WHERE id = ?2",
+            rusqlite::params![RECORDED_AT, license.id],
+        )
+        .unwrap();
+    let (status, body) = fixture.post_stripe(payload).await;
+
+    assert_eq!(
+        (status, body.as_str()),
+        (StatusCode::OK, "Already processed")
+    );
+    assert_eq!(fixture.license(&license.id).expires_at, Some(RECORDED_AT));
+}
+
+#[tokio::test]
+async fn test_invoice_paid_subscription_create_leaves_license_untouched() {
+    let (fixture, license) = renewal_fixture();
+
+    let (status, body) = fixture
+        .post_stripe(load_fixture("stripe_invoice_paid_subscription_create", &[]))
+        .await;
+    assert_eq!(
+        (status, body.as_str()),
+        (StatusCode::OK, "Initial subscription - handled by checkout")
+    );
+
+    let unchanged = fixture.license(&license.id);
+    assert_eq!(unchanged.expires_at, license.expires_at);
+    assert_eq!(unchanged.updates_expires_at, license.updates_expires_at);
+}
+
+#[tokio::test]
+async fn test_invoice_paid_manual_is_ignored() {
+    let (fixture, license) = renewal_fixture();
+
+    let (status, body) = fixture
+        .post_stripe(load_fixture("stripe_invoice_paid_manual", &[]))
+        .await;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "Event ignored"));
+    assert_eq!(fixture.license(&license.id).expires_at, license.expires_at);
+}
+
+#[tokio::test]
+async fn test_customer_subscription_deleted_leaves_license_to_expire() {
+    let (fixture, license) = renewal_fixture();
+
+    let (status, body) = fixture
+        .post_stripe(load_fixture("stripe_customer_subscription_deleted", &[]))
+        .await;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "OK"));
+
+    let unchanged = fixture.license(&license.id);
+    assert_eq!(unchanged.expires_at, license.expires_at);
+    assert!(!unchanged.revoked, "cancellation should not revoke");
+}
+
+#[tokio::test]
+async fn test_renewal_for_unknown_subscription_is_acknowledged() {
+    let fixture = WebhookFixture::stripe();
+    let other = fixture.subscription_license("stripe", "sub_unrelated");
+
+    let (status, body) = fixture
+        .post_stripe(load_fixture("stripe_invoice_paid_subscription_cycle", &[]))
+        .await;
+    assert_eq!(
+        (status, body.as_str()),
+        (StatusCode::OK, "License not found for subscription"),
+        "unknown subscriptions are acknowledged so the provider stops retrying"
+    );
+    assert_eq!(fixture.license(&other.id).expires_at, other.expires_at);
+}
@@ -0,0 +1,216 @@
+//! Converting trials: a purchase by a buyer with an unconverted trial links
+//! the new license to it and applies the project's `converted_trial_action`,
+//! and `/conversion-stats` reports on the trials a project started.
+
+use super::helpers::*;
+
+/// A trial of the fixture's product for "test-customer".
+fn trial_license(fixture: &WebhookFixture) -> License {
+    let conn = fixture.state.db.get().unwrap();
+    let trial = create_test_license(
+        &conn,
+        &fixture.project.id,
+        &fixture.product.id,
+        Some(RECORDED_AT + 14 * SECONDS_PER_DAY),
+    );
+    queries::mark_license_trial(&conn, &trial.id).unwrap();
+    fixture.license(&trial.id)
+}
+
+fn set_converted_trial_action(fixture: &WebhookFixture, action: ConvertedTrialAction) {
+    fixture
+        .state
+        .db
+        .get()
+        .unwrap()
+        .execute(
+            "UPDATE projects SET converted_trial_action = ?1 WHERE id = ?2",
+            [action.as_ref(), fixture.project.id.as_str()],
+        )
+        .unwrap();
+}
+
+/// Complete a checkout by `customer_id` and return the license it issued.
+async fn purchase(fixture: &WebhookFixture, customer_id: &str) -> License {
+    let session = create_test_payment_session(
+        &fixture.state.db.get().unwrap(),
+        &fixture.product.id,
+        Some(customer_id),
+    );
+    let payload = fixture.checkout_payload("stripe_checkout_session_completed", &session);
+    let (status, body) = fixture.post_stripe(payload).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let session = queries::get_payment_session(&fixture.state.db.get().unwrap(), &session.id)
+        .unwrap()
+        .unwrap();
+    fixture.license(&session.license_id.expect("license issued"))
+}
+
+#[tokio::test]
+async fn test_purchase_links_trial_and_keeps_it() {
+    let fixture = WebhookFixture::stripe();
+    let trial = trial_license(&fixture);
+    assert!(trial.is_trial);
+
+    let paid = purchase(&fixture, "test-customer").await;
+    assert_eq!(
+        paid.converted_from_license_id.as_deref(),
+        Some(trial.id.as_str())
+    );
+    assert!(!paid.is_trial);
+
+    let kept = fixture.license(&trial.id);
+    assert!(!kept.revoked);
+    assert_eq!(kept.expires_at, trial.expires_at);
+}
+
+#[tokio::test]
+async fn test_converted_trial_expires() {
+    let fixture = WebhookFixture::stripe();
+    set_converted_trial_action(&fixture, ConvertedTrialAction::Expire);
+    let trial = trial_license(&fixture);
+
+    purchase(&fixture, "test-customer").await;
+
+    let expired = fixture.license(&trial.id);
+    assert_eq!(expired.expires_at, Some(RECORDED_AT));
+    assert!(!expired.revoked);
+}
+
+#[tokio::test]
+async fn test_converted_trial_revoked() {
+    let fixture = WebhookFixture::stripe();
+    set_converted_trial_action(&fixture, ConvertedTrialAction::Revoke);
+    let trial = trial_license(&fixture);
+
+    purchase(&fixture, "test-customer").await;
+
+    let revoked = fixture.license(&trial.id);
+    assert!(revoked.revoked);
+    assert_eq!(revoked.revoked_reason, Some(RevocationReason::Superseded));
+}
+
+#[tokio::test]
+async fn test_trial_converts_once() {
+    let fixture = WebhookFixture::stripe();
+    let trial = trial_license(&fixture);
+
+    let first = purchase(&fixture, "test-customer").await;
+    let second = purchase(&fixture, "test-customer").await;
+    assert_eq!(
+        first.converted_from_license_id.as_deref(),
+        Some(trial.id.as_str())
+    );
+    assert_eq!(second.converted_from_license_id, None);
+}
+
+#[tokio::test]
+async fn test_other_buyers_trial_not_converted() {
+    let fixture = WebhookFixture::stripe();
+    let trial = trial_license(&fixture);
+    {
+        // Neither the customer ID nor the recorded checkout's email matches
+        let conn = fixture.state.db.get().unwrap();
+        queries::update_license_email_hash(&conn, &trial.id, "other-email-hash").unwrap();
+    }
+
+    let paid = purchase(&fixture, "new-customer").await;
+    assert_eq!(paid.converted_from_license_id, None);
+}
+
+#[tokio::test]
+async fn test_conversion_audited() {
+    let mut fixture = WebhookFixture::stripe();
+    fixture.state.audit_log_enabled = true;
+    let trial = trial_license(&fixture);
+
+    let paid = purchase(&fixture, "test-customer").await;
+
+    let (resource_id, details): (String, String) = fixture
+        .state
+        .audit
+        .get()
+        .unwrap()
+        .query_row(
+            "SELECT resource_id, details FROM audit_logs WHERE action = 'trial_converted'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(resource_id, paid.id);
+    let details: serde_json::Value = serde_json::from_str(&details).unwrap();
+    assert_eq!(details["trial_license_id"], trial.id.as_str());
+    assert_eq!(details["converted_trial_action"], "keep");
+}
+
+/// A trial started `started` seconds after `RECORDED_AT`, converted
+/// `converted_after_days` later if given.
+fn seed_trial(fixture: &WebhookFixture, started: i64, converted_after_days: Option<i64>) {
+    let conn = fixture.state.db.get().unwrap();
+    let trial = create_test_license(&conn, &fixture.project.id, &fixture.product.id, None);
+    conn.execute(
+        "UPDATE licenses SET is_trial = 1, created_at = ?1 WHERE id = ?2",
+        rusqlite::params![RECORDED_AT + started, &trial.id],
+    )
+    .unwrap();
+    if let Some(days) = converted_after_days {
+        let paid = create_test_license(&conn, &fixture.project.id, &fixture.product.id, None);
+        conn.execute(
+            "UPDATE licenses SET converted_from_license_id = ?1, created_at = ?2 WHERE id = ?3",
+            rusqlite::params![
+                &trial.id,
+                RECORDED_AT + started + days * SECONDS_PER_DAY,
+                &paid.id
+            ],
+        )
+        .unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_conversion_stats_odd_count() {
+    let fixture = WebhookFixture::stripe();
+    seed_trial(&fixture, 0, Some(2));
+    seed_trial(&fixture, 10, Some(9));
+    seed_trial(&fixture, 20, Some(4));
+    seed_trial(&fixture, 30, None);
+
+    let stats = fixture.admin_get("/conversion-stats").await;
+    assert_eq!(stats["trials_started"], 4);
+    assert_eq!(stats["trials_converted"], 3);
+    assert_eq!(stats["conversion_rate"], 0.75);
+    assert_eq!(stats["median_days_to_convert"], 4.0);
+}
+
+#[tokio::test]
+async fn test_conversion_stats_even_count_and_range() {
+    let fixture = WebhookFixture::stripe();
+    seed_trial(&fixture, 0, Some(2));
+    seed_trial(&fixture, 10, Some(7));
+    seed_trial(&fixture, 20, None);
+    seed_trial(&fixture, 30, None);
+    // Started after the range, so not counted
+    seed_trial(&fixture, 100, Some(1));
+
+    let stats = fixture
+        .admin_get(&format!(
+            "/conversion-stats?from={}&to={}",
+            RECORDED_AT,
+            RECORDED_AT + 100
+        ))
+        .await;
+    assert_eq!(stats["trials_started"], 4);
+    assert_eq!(stats["trials_converted"], 2);
+    assert_eq!(stats["conversion_rate"], 0.5);
+    assert_eq!(stats["median_days_to_convert"], 4.5);
+}
+
+#[tokio::test]
+async fn test_conversion_stats_without_trials() {
+    let fixture = WebhookFixture::stripe();
+    let stats = fixture.admin_get("/conversion-stats").await;
+    assert_eq!(stats["trials_started"], 0);
+    assert!(stats["conversion_rate"].is_null());
+    assert!(stats["median_days_to_convert"].is_null());
+}
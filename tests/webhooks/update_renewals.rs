@@ -0,0 +1,190 @@
+//! Completing an updates-only renewal checkout: the buyer's license for the
+//! base product gets a longer update window and nothing else, and a buyer with
+//! no license falls back to the renewal product's `renewal_fallback`.
+
+use super::helpers::*;
+
+/// Turn the fixture's Pro product into an updates-only renewal of a new
+/// Basic product and return Basic.
+fn renewal_product(fixture: &WebhookFixture, fallback: RenewalFallback) -> Product {
+    let conn = fixture.state.db.get().unwrap();
+    let basic = create_test_product(&conn, &fixture.project.id, "Basic Plan", "basic");
+    conn.execute(
+        "UPDATE products SET extends_updates_for_product_id = ?1, renewal_fallback = ?2 WHERE id = ?3",
+        [basic.id.as_str(), fallback.as_ref(), fixture.product.id.as_str()],
+    )
+    .unwrap();
+    basic
+}
+
+/// A Basic license for "test-customer" whose updates run until `updates_expires_at`.
+fn base_license(fixture: &WebhookFixture, basic: &Product, updates_expires_at: i64) -> License {
+    let conn = fixture.state.db.get().unwrap();
+    let license = create_test_license(
+        &conn,
+        &fixture.project.id,
+        &basic.id,
+        Some(RECORDED_AT + 200 * SECONDS_PER_DAY),
+    );
+    conn.execute(
+        "UPDATE licenses SET updates_expires_at = ?1 WHERE id = ?2",
+        rusqlite::params![updates_expires_at, &license.id],
+    )
+    .unwrap();
+    fixture.license(&license.id)
+}
+
+fn session_for(fixture: &WebhookFixture, customer_id: Option<&str>) -> PaymentSession {
+    create_test_payment_session(
+        &fixture.state.db.get().unwrap(),
+        &fixture.product.id,
+        customer_id,
+    )
+}
+
+async fn complete(fixture: &WebhookFixture, session: &PaymentSession) -> String {
+    let payload = fixture.checkout_payload("stripe_checkout_session_completed", session);
+    let (status, body) = fixture.post_stripe(payload).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    body
+}
+
+fn reload_session(fixture: &WebhookFixture, session: &PaymentSession) -> PaymentSession {
+    queries::get_payment_session(&fixture.state.db.get().unwrap(), &session.id)
+        .unwrap()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_renewal_adds_to_remaining_update_window() {
+    let fixture = WebhookFixture::stripe();
+    let basic = renewal_product(&fixture, RenewalFallback::CreateLicense);
+    let license = base_license(&fixture, &basic, RECORDED_AT + 30 * SECONDS_PER_DAY);
+
+    let session = session_for(&fixture, Some("test-customer"));
+    assert_eq!(complete(&fixture, &session).await, "Updates extended");
+
+    let renewed = fixture.license(&license.id);
+    assert_eq!(
+        renewed.updates_expires_at,
+        Some(RECORDED_AT + (30 + 365) * SECONDS_PER_DAY)
+    );
+    assert_eq!(renewed.expires_at, license.expires_at);
+    assert_eq!(fixture.license_count(), 1, "no license is issued");
+    assert_eq!(
+        reload_session(&fixture, &session).license_id.as_deref(),
+        Some(license.id.as_str())
+    );
+}
+
+#[tokio::test]
+async fn test_lapsed_update_window_restarts_from_now() {
+    let fixture = WebhookFixture::stripe();
+    let basic = renewal_product(&fixture, RenewalFallback::CreateLicense);
+    let license = base_license(&fixture, &basic, RECORDED_AT - 90 * SECONDS_PER_DAY);
+
+    let session = session_for(&fixture, Some("test-customer"));
+    assert_eq!(complete(&fixture, &session).await, "Updates extended");
+
+    assert_eq!(
+        fixture.license(&license.id).updates_expires_at,
+        Some(RECORDED_AT + 365 * SECONDS_PER_DAY)
+    );
+}
+
+#[tokio::test]
+async fn test_renewal_leaves_devices_alone() {
+    let fixture = WebhookFixture::stripe();
+    let basic = renewal_product(&fixture, RenewalFallback::CreateLicense);
+    let license = base_license(&fixture, &basic, RECORDED_AT + 30 * SECONDS_PER_DAY);
+    {
+        let conn = fixture.state.db.get().unwrap();
+        create_test_device(&conn, &license.id, "laptop", DeviceType::Uuid);
+        create_test_device(&conn, &license.id, "desktop", DeviceType::Uuid);
+    }
+
+    let session = session_for(&fixture, Some("test-customer"));
+    complete(&fixture, &session).await;
+
+    let devices =
+        queries::list_devices_for_license(&fixture.state.db.get().unwrap(), &license.id).unwrap();
+    assert_eq!(devices.len(), 2);
+    let renewed = fixture.license(&license.id);
+    assert_eq!(renewed.activation_count, license.activation_count);
+    assert_eq!(renewed.seats, license.seats);
+}
+
+#[tokio::test]
+async fn test_renewal_matches_buyer_by_email() {
+    let fixture = WebhookFixture::stripe();
+    let basic = renewal_product(&fixture, RenewalFallback::CreateLicense);
+    let license = base_license(&fixture, &basic, RECORDED_AT + 30 * SECONDS_PER_DAY);
+    {
+        // The recorded checkout was paid as Buyer@Example.com
+        let conn = fixture.state.db.get().unwrap();
+        let email_hash = test_email_hasher().hash(&parse_email("buyer@example.com"));
+        queries::update_license_email_hash(&conn, &license.id, &email_hash).unwrap();
+    }
+
+    let session = session_for(&fixture, None);
+    assert_eq!(complete(&fixture, &session).await, "Updates extended");
+    assert_eq!(fixture.license_count(), 1);
+}
+
+#[tokio::test]
+async fn test_no_license_to_renew_issues_license_by_default() {
+    let fixture = WebhookFixture::stripe();
+    renewal_product(&fixture, RenewalFallback::CreateLicense);
+
+    let session = session_for(&fixture, Some("new-customer"));
+    assert_eq!(complete(&fixture, &session).await, "OK");
+
+    let session = reload_session(&fixture, &session);
+    let license = fixture.license(&session.license_id.expect("license issued"));
+    assert_eq!(license.product_id, fixture.product.id);
+    assert!(!session.needs_review);
+}
+
+#[tokio::test]
+async fn test_no_license_to_renew_held_for_review() {
+    let fixture = WebhookFixture::stripe();
+    let basic = renewal_product(&fixture, RenewalFallback::ManualReview);
+    // Another customer's license doesn't count
+    base_license(&fixture, &basic, RECORDED_AT + 30 * SECONDS_PER_DAY);
+
+    let session = session_for(&fixture, Some("new-customer"));
+    assert_eq!(complete(&fixture, &session).await, "Held for review");
+
+    let session = reload_session(&fixture, &session);
+    assert!(session.completed);
+    assert!(session.needs_review);
+    assert_eq!(session.license_id, None);
+    assert_eq!(fixture.license_count(), 1);
+
+    // Redelivery doesn't flip the outcome
+    assert_eq!(complete(&fixture, &session).await, "Already processed");
+}
+
+#[tokio::test]
+async fn test_renewal_recorded_on_license_details() {
+    let fixture = WebhookFixture::stripe();
+    let basic = renewal_product(&fixture, RenewalFallback::CreateLicense);
+    let license = base_license(&fixture, &basic, RECORDED_AT + 30 * SECONDS_PER_DAY);
+
+    let session = session_for(&fixture, Some("test-customer"));
+    complete(&fixture, &session).await;
+
+    let details = fixture.license_details(&license.id).await;
+    let renewals = details["update_renewals"].as_array().unwrap();
+    assert_eq!(renewals.len(), 1);
+    assert_eq!(renewals[0]["product_id"], fixture.product.id.as_str());
+    assert_eq!(renewals[0]["payment_session_id"], session.id.as_str());
+    assert_eq!(
+        renewals[0]["previous_updates_expires_at"],
+        RECORDED_AT + 30 * SECONDS_PER_DAY
+    );
+    assert_eq!(
+        renewals[0]["updates_expires_at"],
+        RECORDED_AT + 395 * SECONDS_PER_DAY
+    );
+}
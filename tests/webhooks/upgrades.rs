@@ -0,0 +1,154 @@
+//! Completing an upgrade checkout: the old license is revoked or ends its
+//! updates, and both licenses record the link.
+
+use super::helpers::*;
+
+/// A Basic license for "test-customer" and a payment session upgrading it to
+/// the fixture's Pro product.
+fn upgrade_checkout(
+    fixture: &WebhookFixture,
+    old_license_action: UpgradeOldLicense,
+) -> (License, PaymentSession) {
+    let conn = fixture.state.db.get().unwrap();
+    queries::update_project(
+        &conn,
+        &fixture.project.id,
+        &UpdateProject {
+            name: None,
+            license_key_prefix: None,
+            redirect_url: None,
+            email_from: None,
+            email_enabled: None,
+            email_webhook_url: None,
+            email_webhook_secret: None,
+            jwt_issuer: None,
+            jwt_audience: None,
+            jwt_grace_days: None,
+            upgrade_auto_discount: None,
+            upgrade_old_license: Some(old_license_action),
+            allow_project_id_auth: None,
+            allow_link_checkout: None,
+            max_validations_per_hour_per_license: None,
+            statement_descriptor_suffix: None,
+            receipt_email_enabled: None,
+            checkout_message: None,
+            attestation_audiences: None,
+            duplicate_purchase_check: None,
+            converted_trial_action: None,
+            require_terms_acceptance: None,
+            client_flags: None,
+            min_client_version: None,
+            checkout_metadata_keys: None,
+            usage_geo_enabled: None,
+            dormant_after_days: None,
+            dormant_nudge_email: None,
+            webhook_schema_version: None,
+            expected_version: None,
+        },
+        &fixture.state.master_key,
+    )
+    .unwrap();
+
+    let basic = create_test_product(&conn, &fixture.project.id, "Basic Plan", "basic");
+    let old_license = create_test_license(
+        &conn,
+        &fixture.project.id,
+        &basic.id,
+        Some(RECORDED_AT + 73 * SECONDS_PER_DAY),
+    );
+    let session = queries::create_payment_session(
+        &conn,
+        &CreatePaymentSession {
+            product_id: fixture.product.id.clone(),
+            customer_id: Some("test-customer".to_string()),
+            upgrade_from_license_id: Some(old_license.id.clone()),
+            upgrade_days_remaining: Some(73),
+            upgrade_credit_cents: Some(999),
+            checkout_fields: Default::default(),
+            accepted_terms_version: None,
+            metadata: Default::default(),
+        },
+    )
+    .unwrap();
+    (old_license, session)
+}
+
+/// Complete the checkout and return the new license.
+async fn complete(fixture: &WebhookFixture, session: &PaymentSession) -> License {
+    let payload = fixture.checkout_payload("stripe_checkout_session_completed", session);
+    let (status, body) = fixture.post_stripe(payload).await;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "OK"));
+
+    let session = queries::get_payment_session(&fixture.state.db.get().unwrap(), &session.id)
+        .unwrap()
+        .unwrap();
+    fixture.license(&session.license_id.expect("session should link the license"))
+}
+
+#[tokio::test]
+async fn test_upgrade_checkout_revokes_old_license() {
+    let fixture = WebhookFixture::stripe();
+    let (old_license, session) = upgrade_checkout(&fixture, UpgradeOldLicense::Revoke);
+
+    let new_license = complete(&fixture, &session).await;
+
+    let old_license = fixture.license(&old_license.id);
+    assert!(old_license.revoked);
+    assert_eq!(
+        old_license.revoked_reason,
+        Some(RevocationReason::Superseded)
+    );
+    assert_eq!(old_license.revoked_by.as_deref(), Some("stripe"));
+    assert!(!new_license.revoked);
+    assert_eq!(new_license.product_id, fixture.product.id);
+    assert_eq!(new_license.customer_id.as_deref(), Some("test-customer"));
+}
+
+#[tokio::test]
+async fn test_upgrade_checkout_updates_only_keeps_old_license_valid() {
+    let fixture = WebhookFixture::stripe();
+    let (old_license, session) = upgrade_checkout(&fixture, UpgradeOldLicense::UpdatesOnly);
+
+    complete(&fixture, &session).await;
+
+    let old_license_after = fixture.license(&old_license.id);
+    assert!(!old_license_after.revoked);
+    assert_eq!(old_license_after.expires_at, old_license.expires_at);
+    assert_eq!(
+        old_license_after.updates_expires_at,
+        Some(RECORDED_AT),
+        "update window ends at completion"
+    );
+}
+
+#[tokio::test]
+async fn test_upgrade_link_recorded_on_both_licenses() {
+    let fixture = WebhookFixture::stripe();
+    let (old_license, session) = upgrade_checkout(&fixture, UpgradeOldLicense::Revoke);
+    let new_license = complete(&fixture, &session).await;
+
+    let old_details = fixture.license_details(&old_license.id).await;
+    let new_details = fixture.license_details(&new_license.id).await;
+
+    for upgrade in [&old_details["upgraded_to"], &new_details["upgraded_from"]] {
+        assert_eq!(upgrade["from_license_id"], old_license.id.as_str());
+        assert_eq!(upgrade["to_license_id"], new_license.id.as_str());
+        assert_eq!(upgrade["payment_session_id"], session.id.as_str());
+        assert_eq!(upgrade["days_remaining"], 73);
+        assert_eq!(upgrade["credit_cents"], 999);
+        assert_eq!(upgrade["old_license_action"], "revoke");
+    }
+    assert!(old_details.get("upgraded_from").is_none());
+    assert!(new_details.get("upgraded_to").is_none());
+}
+
+#[tokio::test]
+async fn test_checkout_without_upgrade_leaves_other_licenses_alone() {
+    let fixture = WebhookFixture::stripe();
+    let (old_license, _) = upgrade_checkout(&fixture, UpgradeOldLicense::Revoke);
+
+    let session = fixture.payment_session();
+    complete(&fixture, &session).await;
+
+    assert!(!fixture.license(&old_license.id).revoked);
+}
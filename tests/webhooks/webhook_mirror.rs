@@ -0,0 +1,305 @@
+//! Webhook mirrors: received provider webhooks and sent activation webhooks
+//! are copied to the project's mirror URL while it is on, and a mirror that
+//! is down or expired never changes the real outcome.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::body::Bytes;
+use axum::http::HeaderMap;
+use serde_json::{Value, json};
+use tokio::sync::mpsc;
+
+use super::helpers::*;
+
+/// A request the stub mirror received.
+struct Mirrored {
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+/// Record every POST to `/mirror`. Returns the URL and the received requests.
+async fn stub_mirror() -> (String, mpsc::UnboundedReceiver<Mirrored>) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let app = Router::new().route(
+        "/mirror",
+        post(move |headers: HeaderMap, body: Bytes| {
+            let tx = tx.clone();
+            async move {
+                let _ = tx.send(Mirrored { headers, body });
+                StatusCode::OK
+            }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    (format!("http://{}/mirror", addr), rx)
+}
+
+/// A URL nothing is listening on.
+async fn closed_port_url() -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    drop(listener);
+    format!("http://{}/mirror", addr)
+}
+
+async fn next_mirrored(rx: &mut mpsc::UnboundedReceiver<Mirrored>) -> Option<Mirrored> {
+    tokio::time::timeout(Duration::from_secs(2), rx.recv())
+        .await
+        .ok()
+        .flatten()
+}
+
+fn header<'a>(mirrored: &'a Mirrored, name: &str) -> Option<&'a str> {
+    mirrored.headers.get(name).map(|v| v.to_str().unwrap())
+}
+
+/// The Stripe fixture, allowed to mirror to localhost, mirroring to `url`
+/// until `expires_at`.
+fn mirrored_fixture(url: &str, expires_at: i64) -> WebhookFixture {
+    let mut fixture = WebhookFixture::stripe();
+    fixture.state.allow_localhost_urls = true;
+    queries::set_project_webhook_mirror(
+        &fixture.state.db.get().unwrap(),
+        &fixture.project.id,
+        Some((url, expires_at)),
+    )
+    .unwrap();
+    fixture
+}
+
+#[tokio::test]
+async fn test_provider_webhook_is_forwarded_verbatim() {
+    let (url, mut rx) = stub_mirror().await;
+    let fixture = mirrored_fixture(&url, RECORDED_AT + SECONDS_PER_DAY);
+    let session = fixture.payment_session();
+    let payload = fixture.checkout_payload("stripe_checkout_session_completed", &session);
+
+    let (status, body) = fixture.post_stripe(payload.clone()).await;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "OK"));
+    assert_eq!(fixture.license_count(), 1);
+
+    let mirrored = next_mirrored(&mut rx).await.expect("webhook mirrored");
+    assert_eq!(mirrored.body.as_ref(), payload.as_slice());
+    assert_eq!(header(&mirrored, "x-paycheck-mirror"), Some("true"));
+    assert_eq!(header(&mirrored, "x-paycheck-provider"), Some("stripe"));
+    assert_eq!(header(&mirrored, "content-type"), Some("application/json"));
+    assert!(
+        header(&mirrored, "stripe-signature").is_some_and(|s| s.starts_with("t=")),
+        "signature forwarded"
+    );
+}
+
+#[tokio::test]
+async fn test_unreachable_mirror_does_not_affect_processing() {
+    let fixture = mirrored_fixture(&closed_port_url().await, RECORDED_AT + SECONDS_PER_DAY);
+    let session = fixture.payment_session();
+    let payload = fixture.checkout_payload("stripe_checkout_session_completed", &session);
+
+    let (status, body) = fixture.post_stripe(payload).await;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "OK"));
+    assert_eq!(fixture.license_count(), 1);
+}
+
+#[tokio::test]
+async fn test_expired_mirror_is_not_called() {
+    let (url, mut rx) = stub_mirror().await;
+    let fixture = mirrored_fixture(&url, RECORDED_AT);
+    let session = fixture.payment_session();
+    let payload = fixture.checkout_payload("stripe_checkout_session_completed", &session);
+
+    let (status, _) = fixture.post_stripe(payload).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(next_mirrored(&mut rx).await.is_none());
+}
+
+#[tokio::test]
+async fn test_unverified_webhook_is_not_forwarded() {
+    let (url, mut rx) = stub_mirror().await;
+    let fixture = mirrored_fixture(&url, RECORDED_AT + SECONDS_PER_DAY);
+    let session = fixture.payment_session();
+    let payload = fixture.checkout_payload("stripe_checkout_session_completed", &session);
+    let signature = stripe_signature_header(&payload, "whsec_someone_else");
+
+    let app = Router::new()
+        .route("/webhook/stripe", post(handle_stripe_webhook))
+        .with_state(fixture.state.clone());
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/webhook/stripe")
+                .header("content-type", "application/json")
+                .header("stripe-signature", signature)
+                .body(Body::from(payload))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert!(next_mirrored(&mut rx).await.is_none());
+}
+
+#[tokio::test]
+async fn test_activation_webhook_is_mirrored() {
+    let (webhook_url, mut webhook_rx) = stub_mirror().await;
+    let (mirror_url, mut mirror_rx) = stub_mirror().await;
+
+    let mut state = create_test_app_state();
+    state.allow_localhost_urls = true;
+    state.email_service =
+        Arc::new(EmailService::new(None, "noreply@test.com".into()).with_localhost_urls(true));
+    let conn = state.db.get().unwrap();
+    let org = create_test_org(&conn, "Test Org");
+    let project = create_test_project(&conn, &org.id, "Test Project", &state.master_key);
+    let product = create_test_product(&conn, &project.id, "Pro Plan", "pro");
+    create_test_license(&conn, &project.id, &product.id, None);
+    conn.execute(
+        "UPDATE projects SET email_webhook_url = ?1 WHERE id = ?2",
+        [webhook_url.as_str(), project.id.as_str()],
+    )
+    .unwrap();
+    // Email checks expiry against the wall clock
+    queries::set_project_webhook_mirror(
+        &conn,
+        &project.id,
+        Some((&mirror_url, future_timestamp(ONE_DAY))),
+    )
+    .unwrap();
+    drop(conn);
+
+    let response = public_app(state)
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/activation/request-code")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    json!({
+                        "email": "test@example.com",
+                        "public_key": project.public_key,
+                    })
+                    .to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let delivered = next_mirrored(&mut webhook_rx)
+        .await
+        .expect("webhook called");
+    let mirrored = next_mirrored(&mut mirror_rx)
+        .await
+        .expect("webhook mirrored");
+    assert_eq!(header(&delivered, "x-paycheck-mirror"), None);
+    assert_eq!(header(&mirrored, "x-paycheck-mirror"), Some("true"));
+    assert_eq!(
+        header(&mirrored, "x-paycheck-event"),
+        Some("activation_code_created")
+    );
+    let delivered: Value = serde_json::from_slice(&delivered.body).unwrap();
+    let mirrored: Value = serde_json::from_slice(&mirrored.body).unwrap();
+    assert_eq!(mirrored, delivered, "the mirror gets the same payload");
+}
+
+mod operator_api {
+    use super::*;
+
+    async fn send(
+        fixture: &WebhookFixture,
+        method: &str,
+        key: &str,
+        body: Value,
+    ) -> (StatusCode, Value) {
+        let app = paycheck::handlers::operators::router(fixture.state.clone())
+            .with_state(fixture.state.clone());
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri(format!(
+                        "/operators/organizations/{}/projects/{}/webhook-mirror",
+                        fixture.project.org_id, fixture.project.id
+                    ))
+                    .header("Authorization", format!("Bearer {}", key))
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (
+            status,
+            serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        )
+    }
+
+    fn operator(fixture: &WebhookFixture, role: OperatorRole) -> String {
+        let mut conn = fixture.state.db.get().unwrap();
+        create_test_operator(&mut conn, &format!("{}@test.com", role.as_ref()), role).1
+    }
+
+    #[tokio::test]
+    async fn test_set_get_and_clear() {
+        let mut fixture = WebhookFixture::stripe();
+        fixture.state.allow_localhost_urls = true;
+        let key = operator(&fixture, OperatorRole::Admin);
+        let url = "http://localhost:4242/webhooks";
+
+        let (status, body) = send(&fixture, "PUT", &key, json!({ "url": url })).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["active"], true);
+        assert_eq!(body["url"], url);
+        assert_eq!(body["expires_at"], RECORDED_AT + 7 * SECONDS_PER_DAY);
+
+        let (_, body) = send(&fixture, "GET", &key, Value::Null).await;
+        assert_eq!(body["active"], true);
+
+        let (status, body) = send(&fixture, "DELETE", &key, Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["active"], false);
+        assert!(body["url"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_expiry_is_bounded() {
+        let mut fixture = WebhookFixture::stripe();
+        fixture.state.allow_localhost_urls = true;
+        let key = operator(&fixture, OperatorRole::Admin);
+
+        for days in [0, 31] {
+            let body = json!({ "url": "http://localhost:4242/", "expires_in_days": days });
+            let (status, _) = send(&fixture, "PUT", &key, body).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{} days", days);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_url_rules_apply() {
+        let fixture = WebhookFixture::stripe();
+        let key = operator(&fixture, OperatorRole::Admin);
+
+        let body = json!({ "url": "http://hooks.example.com/" });
+        let (status, body) = send(&fixture, "PUT", &key, body).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["details"], "url must use https");
+    }
+
+    #[tokio::test]
+    async fn test_view_operator_cannot_set() {
+        let fixture = WebhookFixture::stripe();
+        let key = operator(&fixture, OperatorRole::View);
+
+        let body = json!({ "url": "https://hooks.example.com/" });
+        let (status, _) = send(&fixture, "PUT", &key, body).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+}